@@ -0,0 +1,283 @@
+//! On-disk CAN capture files.
+//!
+//! `can_monitor` with `save` writes the frames it captured to a file instead
+//! of returning them inline, so a trace can be fetched with a file transfer
+//! and opened in Wireshark or SavvyCAN. Two formats are supported:
+//!
+//! - **candump**: the text log written by `candump -l`, one
+//!   `(seconds.micros) can0 123#DEADBEEF` line per frame;
+//! - **pcapng**: one section with a single `LINKTYPE_CAN_SOCKETCAN`
+//!   interface and an Enhanced Packet Block per frame.
+//!
+//! Files go to [`DEFAULT_CAPTURE_DIR`], which sits under the agent's
+//! default file-transfer directory.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{CanError, CanResult};
+use crate::error_frame::CAN_ERR_FLAG;
+use crate::types::CanFrame;
+
+/// Where captures are saved unless the tool is configured otherwise.
+pub const DEFAULT_CAPTURE_DIR: &str = "/var/lib/zeroclaw/files/captures";
+
+/// Interface name recorded in capture files.
+pub const CAPTURE_INTERFACE: &str = "can0";
+
+/// Largest standard (11-bit) CAN ID.
+const MAX_STANDARD_ID: u32 = 0x7FF;
+
+/// SocketCAN flag marking a 29-bit ID.
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+
+/// `LINKTYPE_CAN_SOCKETCAN`.
+const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+
+/// Bytes of a SocketCAN frame header in a pcap packet (ID, length, padding).
+const SOCKETCAN_HEADER_LEN: usize = 8;
+
+/// File format of a saved capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Candump,
+    Pcapng,
+}
+
+impl CaptureFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "candump" => Some(Self::Candump),
+            "pcapng" => Some(Self::Pcapng),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Candump => "candump",
+            Self::Pcapng => "pcapng",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Candump => "log",
+            Self::Pcapng => "pcapng",
+        }
+    }
+}
+
+/// A frame and the time it was received.
+#[derive(Debug, Clone)]
+pub struct TimedFrame {
+    pub timestamp: SystemTime,
+    pub frame: CanFrame,
+}
+
+impl TimedFrame {
+    /// `frame`, received now.
+    pub fn now(frame: CanFrame) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            frame,
+        }
+    }
+
+    fn micros(&self) -> u64 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default()
+    }
+
+    /// SocketCAN `can_id`: error frames keep their flag, IDs above the
+    /// 11-bit range are marked extended.
+    fn socketcan_id(&self) -> u32 {
+        let id = self.frame.id;
+        if id & CAN_ERR_FLAG != 0 || id <= MAX_STANDARD_ID {
+            id
+        } else {
+            id | CAN_EFF_FLAG
+        }
+    }
+}
+
+/// Encode `frames` as a complete capture file.
+pub fn encode(format: CaptureFormat, frames: &[TimedFrame]) -> Vec<u8> {
+    match format {
+        CaptureFormat::Candump => encode_candump(frames),
+        CaptureFormat::Pcapng => encode_pcapng(frames),
+    }
+}
+
+fn encode_candump(frames: &[TimedFrame]) -> Vec<u8> {
+    let mut out = String::new();
+    for timed in frames {
+        let micros = timed.micros();
+        let id = timed.frame.id;
+        // candump prints 3 digits for standard IDs and 8 for extended and
+        // error frames.
+        let id = if id & CAN_ERR_FLAG == 0 && id <= MAX_STANDARD_ID {
+            format!("{id:03X}")
+        } else {
+            format!("{id:08X}")
+        };
+        let data: String = timed
+            .frame
+            .data
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+        out.push_str(&format!(
+            "({}.{:06}) {CAPTURE_INTERFACE} {id}#{data}\n",
+            micros / 1_000_000,
+            micros % 1_000_000
+        ));
+    }
+    out.into_bytes()
+}
+
+fn encode_pcapng(frames: &[TimedFrame]) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    // Section Header Block: byte-order magic, version 1.0, unknown length.
+    let mut shb = Vec::new();
+    shb.extend_from_slice(&0x1A2B_3C4Du32.to_le_bytes());
+    shb.extend_from_slice(&1u16.to_le_bytes());
+    shb.extend_from_slice(&0u16.to_le_bytes());
+    shb.extend_from_slice(&(-1i64).to_le_bytes());
+    push_block(&mut out, 0x0A0D_0D0A, &shb);
+
+    // Interface Description Block; timestamps use the default microseconds.
+    let mut idb = Vec::new();
+    idb.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+    idb.extend_from_slice(&0u16.to_le_bytes());
+    idb.extend_from_slice(&((SOCKETCAN_HEADER_LEN + 8) as u32).to_le_bytes());
+    push_block(&mut out, 1, &idb);
+
+    for timed in frames {
+        // SocketCAN header: big-endian can_id, payload length, 3 padding bytes.
+        let mut packet = Vec::with_capacity(SOCKETCAN_HEADER_LEN + timed.frame.data.len());
+        packet.extend_from_slice(&timed.socketcan_id().to_be_bytes());
+        packet.push(timed.frame.data.len() as u8);
+        packet.extend_from_slice(&[0, 0, 0]);
+        packet.extend_from_slice(&timed.frame.data);
+
+        let micros = timed.micros();
+        let mut epb = Vec::with_capacity(20 + packet.len() + 3);
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(micros as u32).to_le_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&packet);
+        epb.resize(epb.len().next_multiple_of(4), 0);
+        push_block(&mut out, 6, &epb);
+    }
+    out
+}
+
+/// Append a pcapng block: type, total length, body, total length.
+fn push_block(out: &mut Vec<u8>, block_type: u32, body: &[u8]) {
+    let total = (body.len() + 12) as u32;
+    out.extend_from_slice(&block_type.to_le_bytes());
+    out.extend_from_slice(&total.to_le_bytes());
+    out.extend_from_slice(body);
+    out.extend_from_slice(&total.to_le_bytes());
+}
+
+/// Write `frames` to `<dir>/<capture_id>.<ext>` and return the path and
+/// file size.
+pub async fn save(
+    dir: &Path,
+    capture_id: &str,
+    format: CaptureFormat,
+    frames: &[TimedFrame],
+) -> CanResult<(PathBuf, usize)> {
+    let io_error = |e: std::io::Error| CanError::Other(format!("saving capture failed: {e}"));
+    tokio::fs::create_dir_all(dir).await.map_err(io_error)?;
+    let path = dir.join(format!("{capture_id}.{}", format.extension()));
+    let contents = encode(format, frames);
+    tokio::fs::write(&path, &contents).await.map_err(io_error)?;
+    Ok((path, contents.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(micros: u64, id: u32, data: &[u8]) -> TimedFrame {
+        TimedFrame {
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            frame: CanFrame::new(id, data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn candump_lines_match_candump_log_format() {
+        let frames = [
+            at(1_436_509_052_249_713, 0x044, &[0x2A, 0x36, 0x6C]),
+            at(1_436_509_052_250_000, 0x18DA_F110, &[0x10]),
+            at(1_436_509_052_251_000, CAN_ERR_FLAG | 0x20, &[0; 8]),
+            at(1_436_509_052_252_000, 0x7DF, &[]),
+        ];
+        let text = String::from_utf8(encode(CaptureFormat::Candump, &frames)).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "(1436509052.249713) can0 044#2A366C");
+        assert_eq!(lines[1], "(1436509052.250000) can0 18DAF110#10");
+        assert_eq!(
+            lines[2],
+            "(1436509052.251000) can0 20000020#0000000000000000"
+        );
+        assert_eq!(lines[3], "(1436509052.252000) can0 7DF#");
+    }
+
+    #[test]
+    fn pcapng_has_socketcan_interface_and_one_packet_per_frame() {
+        let frames = [
+            at(5_000_001, 0x123, &[0xDE, 0xAD, 0xBE]),
+            at(5_000_002, 0x18DA_F110, &[0x01; 8]),
+        ];
+        let file = encode(CaptureFormat::Pcapng, &frames);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap());
+
+        // Section header, then the interface with link type 227.
+        assert_eq!(u32_at(0), 0x0A0D_0D0A);
+        assert_eq!(u32_at(8), 0x1A2B_3C4D);
+        assert_eq!(u32_at(28), 1);
+        assert_eq!(u16::from_le_bytes([file[36], file[37]]), 227);
+
+        // First packet: 3-byte payload padded to 12 bytes.
+        let epb = 48;
+        assert_eq!(u32_at(epb), 6);
+        assert_eq!(u32_at(epb + 4), 44);
+        assert_eq!(u32_at(epb + 12), 0);
+        assert_eq!(u32_at(epb + 16), 5_000_001);
+        assert_eq!(u32_at(epb + 20), 11);
+        assert_eq!(&file[epb + 28..epb + 32], &[0x00, 0x00, 0x01, 0x23]);
+        assert_eq!(file[epb + 32], 3);
+        assert_eq!(&file[epb + 36..epb + 39], &[0xDE, 0xAD, 0xBE]);
+
+        // Second packet carries the extended flag.
+        let epb = epb + 44;
+        assert_eq!(u32_at(epb), 6);
+        assert_eq!(&file[epb + 28..epb + 32], &[0x98, 0xDA, 0xF1, 0x10]);
+        assert_eq!(epb + u32_at(epb + 4) as usize, file.len());
+    }
+
+    #[tokio::test]
+    async fn save_writes_the_file_under_its_capture_id() {
+        let dir = std::env::temp_dir().join(format!("zc-capture-{}", uuid::Uuid::now_v7()));
+        let frames = [at(1_000_000, 0x100, &[0x01])];
+        let (path, size) = save(&dir, "abc", CaptureFormat::Candump, &frames)
+            .await
+            .unwrap();
+        assert_eq!(path, dir.join("abc.log"));
+        assert_eq!(std::fs::read(&path).unwrap().len(), size);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
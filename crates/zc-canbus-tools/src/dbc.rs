@@ -0,0 +1,443 @@
+//! DBC (CAN database) parsing and signal decoding.
+//!
+//! Proprietary CAN traffic is only readable with the OEM's message layout.
+//! This module understands the part of the DBC format needed to decode it:
+//! `BO_` messages and their `SG_` signals (Intel and Motorola byte order,
+//! signed values, factor/offset scaling, units and simple multiplexing).
+//! Everything else — nodes, comments, attributes, value tables — is skipped.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{CanError, CanResult};
+use crate::types::CanFrame;
+
+/// Bit 31 of a `BO_` ID marks an extended (29-bit) frame.
+const DBC_EXTENDED_FLAG: u32 = 0x8000_0000;
+
+/// Byte order of a signal (`@1` = Intel, `@0` = Motorola).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// Role of a signal in a multiplexed message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplex {
+    /// Always present.
+    None,
+    /// The multiplexer switch (`M`).
+    Multiplexor,
+    /// Present only when the switch has this value (`m<n>`).
+    Multiplexed(u64),
+}
+
+/// One `SG_` line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u16,
+    pub length: u16,
+    pub byte_order: ByteOrder,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: String,
+    pub multiplex: Multiplex,
+}
+
+impl Signal {
+    /// Raw (unscaled) value, or `None` if the signal lies outside `data`.
+    fn raw(&self, data: &[u8]) -> Option<u64> {
+        if self.length == 0 || self.length > 64 {
+            return None;
+        }
+        let start = usize::from(self.start_bit);
+        let value = match self.byte_order {
+            ByteOrder::LittleEndian => {
+                let mut value = 0u64;
+                for i in 0..self.length as usize {
+                    let bit = start + i;
+                    let byte = *data.get(bit / 8)?;
+                    value |= u64::from((byte >> (bit % 8)) & 1) << i;
+                }
+                value
+            }
+            ByteOrder::BigEndian => {
+                // Motorola "sawtooth" numbering: the start bit is the MSB;
+                // walk towards bit 0 of its byte, then on to bit 7 of the
+                // next byte.
+                let mut value = 0u64;
+                let mut bit = start;
+                for _ in 0..self.length {
+                    let byte = *data.get(bit / 8)?;
+                    value = (value << 1) | u64::from((byte >> (bit % 8)) & 1);
+                    bit = if bit % 8 == 0 { bit + 15 } else { bit - 1 };
+                }
+                value
+            }
+        };
+        Some(value)
+    }
+
+    /// Scaled engineering value, or `None` if the signal lies outside `data`.
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        let raw = self.raw(data)?;
+        let value = if self.signed && self.length < 64 && (raw >> (self.length - 1)) & 1 == 1 {
+            (raw | (u64::MAX << self.length)) as i64 as f64
+        } else if self.signed {
+            raw as i64 as f64
+        } else {
+            raw as f64
+        };
+        Some(value * self.factor + self.offset)
+    }
+}
+
+/// One `BO_` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    /// CAN ID without the DBC extended flag.
+    pub id: u32,
+    pub name: String,
+    pub dlc: u8,
+    pub signals: Vec<Signal>,
+}
+
+/// A decoded signal value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalValue<'a> {
+    pub name: &'a str,
+    pub value: f64,
+    pub unit: &'a str,
+}
+
+impl Message {
+    /// Decode every signal present in `data`. Multiplexed signals are only
+    /// included when the multiplexer selects them; signals that do not fit
+    /// in a short frame are left out.
+    pub fn decode<'a>(&'a self, data: &[u8]) -> Vec<SignalValue<'a>> {
+        let switch = self
+            .signals
+            .iter()
+            .find(|s| s.multiplex == Multiplex::Multiplexor)
+            .and_then(|s| s.raw(data));
+        self.signals
+            .iter()
+            .filter(|s| match s.multiplex {
+                Multiplex::Multiplexed(n) => switch == Some(n),
+                _ => true,
+            })
+            .filter_map(|s| {
+                s.decode(data).map(|value| SignalValue {
+                    name: &s.name,
+                    value,
+                    unit: &s.unit,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Messages from one or more DBC files, keyed by CAN ID.
+#[derive(Debug, Clone, Default)]
+pub struct Dbc {
+    messages: HashMap<u32, Message>,
+}
+
+impl Dbc {
+    /// Load a `.dbc` file.
+    pub fn load(path: impl AsRef<Path>) -> CanResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CanError::Other(format!("cannot read {}: {e}", path.display())))?;
+        Self::parse(&text).map_err(|e| CanError::Other(format!("{}: {e}", path.display())))
+    }
+
+    /// Parse DBC text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut dbc = Self::default();
+        let mut current: Option<Message> = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let at = |e: String| format!("line {}: {e}", i + 1);
+            if let Some(rest) = line.strip_prefix("BO_ ") {
+                if let Some(message) = current.take() {
+                    dbc.insert(message);
+                }
+                current = Some(parse_message(rest).map_err(at)?);
+            } else if let Some(rest) = line.strip_prefix("SG_ ") {
+                let message = current
+                    .as_mut()
+                    .ok_or_else(|| at("signal outside a BO_ block".into()))?;
+                message.signals.push(parse_signal(rest).map_err(at)?);
+            } else {
+                // A blank line or any other keyword ends the signal list.
+                if let Some(message) = current.take() {
+                    dbc.insert(message);
+                }
+            }
+        }
+        if let Some(message) = current {
+            dbc.insert(message);
+        }
+        Ok(dbc)
+    }
+
+    fn insert(&mut self, message: Message) {
+        self.messages.insert(message.id, message);
+    }
+
+    /// Add the messages of `other`. Messages already present win.
+    pub fn merge(&mut self, other: Dbc) {
+        for (id, message) in other.messages {
+            self.messages.entry(id).or_insert(message);
+        }
+    }
+
+    pub fn message(&self, id: u32) -> Option<&Message> {
+        self.messages.get(&id)
+    }
+
+    /// The message for `frame` and its decoded signals, if the ID is known.
+    pub fn decode(&self, frame: &CanFrame) -> Option<(&Message, Vec<SignalValue<'_>>)> {
+        let message = self.message(frame.id)?;
+        Some((message, message.decode(&frame.data)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// `<id> <name>: <dlc> <transmitter>`
+fn parse_message(rest: &str) -> Result<Message, String> {
+    let (head, tail) = rest
+        .split_once(':')
+        .ok_or("BO_ without ':' after the name")?;
+    let mut head = head.split_whitespace();
+    let id: u32 = head
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or("BO_ without a numeric ID")?;
+    let name = head.next().ok_or("BO_ without a name")?;
+    let dlc: u8 = tail
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or("BO_ without a DLC")?;
+    Ok(Message {
+        id: if id & DBC_EXTENDED_FLAG != 0 {
+            id & !DBC_EXTENDED_FLAG
+        } else {
+            id
+        },
+        name: name.to_string(),
+        dlc,
+        signals: Vec::new(),
+    })
+}
+
+/// `<name> [M|m<n>] : <start>|<len>@<0|1><+|-> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>`
+fn parse_signal(rest: &str) -> Result<Signal, String> {
+    let (head, layout) = rest
+        .split_once(':')
+        .ok_or("SG_ without ':' after the name")?;
+    let mut head = head.split_whitespace();
+    let name = head.next().ok_or("SG_ without a name")?.to_string();
+    let multiplex = match head.next() {
+        None => Multiplex::None,
+        Some("M") => Multiplex::Multiplexor,
+        Some(m) => m
+            .strip_prefix('m')
+            .and_then(|n| n.parse().ok())
+            .map(Multiplex::Multiplexed)
+            .ok_or_else(|| format!("{name}: invalid multiplex indicator '{m}'"))?,
+    };
+
+    let err = |what: &str| format!("{name}: {what}");
+    let layout = layout.trim();
+    let (bits, layout) = layout
+        .split_once(' ')
+        .ok_or_else(|| err("missing scaling"))?;
+    let (position, order) = bits
+        .split_once('@')
+        .ok_or_else(|| err("missing '@' byte order"))?;
+    let (start, length) = position
+        .split_once('|')
+        .ok_or_else(|| err("expected <start>|<length>"))?;
+    let start_bit: u16 = start.parse().map_err(|_| err("invalid start bit"))?;
+    let length: u16 = length.parse().map_err(|_| err("invalid length"))?;
+    if length == 0 || length > 64 {
+        return Err(err("length must be 1-64 bits"));
+    }
+    let mut order = order.chars();
+    let byte_order = match order.next() {
+        Some('1') => ByteOrder::LittleEndian,
+        Some('0') => ByteOrder::BigEndian,
+        _ => return Err(err("byte order must be @0 or @1")),
+    };
+    let signed = match order.next() {
+        Some('-') => true,
+        Some('+') => false,
+        _ => return Err(err("sign must be + or -")),
+    };
+
+    let scaling = layout
+        .trim()
+        .strip_prefix('(')
+        .and_then(|s| s.split_once(')'))
+        .ok_or_else(|| err("missing (factor,offset)"))?;
+    let (factor, offset) = scaling
+        .0
+        .split_once(',')
+        .ok_or_else(|| err("expected (factor,offset)"))?;
+    let factor: f64 = factor.trim().parse().map_err(|_| err("invalid factor"))?;
+    let offset: f64 = offset.trim().parse().map_err(|_| err("invalid offset"))?;
+
+    let unit = scaling
+        .1
+        .split_once('"')
+        .and_then(|(_, s)| s.split_once('"'))
+        .map(|(unit, _)| unit.to_string())
+        .unwrap_or_default();
+
+    Ok(Signal {
+        name,
+        start_bit,
+        length,
+        byte_order,
+        signed,
+        factor,
+        offset,
+        unit,
+        multiplex,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+VERSION ""
+
+BU_: ECM TCM
+
+BO_ 2364540158 EEC1: 8 ECM
+ SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX
+ SG_ ActualTorque : 16|8@1+ (1,-125) [-125|125] "%" Vector__XXX
+
+BO_ 768 BodyStatus: 8 BCM
+ SG_ Page M : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+ SG_ DoorsOpen m1 : 8|8@1+ (1,0) [0|4] "" Vector__XXX
+ SG_ CabinTemp m2 : 8|8@1- (0.5,0) [-64|63.5] "degC" Vector__XXX
+ SG_ Counter : 15|4@0+ (1,0) [0|15] "" Vector__XXX
+
+CM_ SG_ 768 Counter "Rolling counter";
+"#;
+
+    #[test]
+    fn parses_messages_and_signals() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        assert_eq!(dbc.len(), 2);
+
+        let eec1 = dbc.message(0x0CF0_04FE).unwrap();
+        assert_eq!(eec1.name, "EEC1");
+        assert_eq!(eec1.dlc, 8);
+        assert_eq!(eec1.signals.len(), 2);
+        assert_eq!(eec1.signals[0].unit, "rpm");
+        assert_eq!(eec1.signals[0].factor, 0.125);
+
+        let body = dbc.message(0x300).unwrap();
+        assert_eq!(body.signals[0].multiplex, Multiplex::Multiplexor);
+        assert_eq!(body.signals[2].multiplex, Multiplex::Multiplexed(2));
+        assert_eq!(body.signals[3].byte_order, ByteOrder::BigEndian);
+    }
+
+    #[test]
+    fn decodes_scaled_intel_signals() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        // 1500 rpm = 12000 * 0.125; torque 150 - 125 = 25 %.
+        let frame = CanFrame::new(0x0CF0_04FE, vec![0, 0, 150, 0xE0, 0x2E, 0, 0, 0]);
+        let (message, signals) = dbc.decode(&frame).unwrap();
+        assert_eq!(message.name, "EEC1");
+        assert_eq!(
+            signals,
+            vec![
+                SignalValue {
+                    name: "EngineSpeed",
+                    value: 1500.0,
+                    unit: "rpm"
+                },
+                SignalValue {
+                    name: "ActualTorque",
+                    value: 25.0,
+                    unit: "%"
+                },
+            ]
+        );
+        assert!(dbc.decode(&CanFrame::new(0x123, vec![0; 8])).is_none());
+    }
+
+    #[test]
+    fn decodes_multiplexed_signed_and_motorola() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        let body = dbc.message(0x300).unwrap();
+
+        // Page 2: CabinTemp = -8 * 0.5; Counter is bits 15..12 (upper nibble of byte 1).
+        let values = body.decode(&[2, 0xF8, 0, 0, 0, 0, 0, 0]);
+        let names: Vec<&str> = values.iter().map(|v| v.name).collect();
+        assert_eq!(names, ["Page", "CabinTemp", "Counter"]);
+        assert_eq!(values[1].value, -4.0);
+        assert_eq!(values[2].value, 15.0);
+
+        let values = body.decode(&[1, 0x03, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(values[1].name, "DoorsOpen");
+        assert_eq!(values[1].value, 3.0);
+    }
+
+    #[test]
+    fn motorola_signal_across_bytes() {
+        // 16-bit big-endian value starting at bit 7 (MSB of byte 0).
+        let signal = Signal {
+            name: "Speed".into(),
+            start_bit: 7,
+            length: 16,
+            byte_order: ByteOrder::BigEndian,
+            signed: false,
+            factor: 0.01,
+            offset: 0.0,
+            unit: "km/h".into(),
+            multiplex: Multiplex::None,
+        };
+        let value = signal.decode(&[0x12, 0x34]).unwrap();
+        assert!((value - 46.60).abs() < 1e-9);
+        assert!(signal.decode(&[0x12]).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_signals() {
+        let err =
+            Dbc::parse("BO_ 256 Msg: 8 ECM\n SG_ Bad : 0|8@2+ (1,0) [0|1] \"\" X\n").unwrap_err();
+        assert!(err.contains("line 2"), "{err}");
+        assert!(err.contains("Bad: byte order"), "{err}");
+
+        let err = Dbc::parse(" SG_ Orphan : 0|8@1+ (1,0) [0|1] \"\" X\n").unwrap_err();
+        assert!(err.contains("outside a BO_ block"), "{err}");
+    }
+
+    #[test]
+    fn merge_keeps_first_definition() {
+        let mut dbc = Dbc::parse("BO_ 256 First: 8 ECM\n").unwrap();
+        dbc.merge(Dbc::parse("BO_ 256 Second: 8 ECM\nBO_ 257 Other: 8 ECM\n").unwrap());
+        assert_eq!(dbc.len(), 2);
+        assert_eq!(dbc.message(256).unwrap().name, "First");
+    }
+}
@@ -4,12 +4,21 @@
 //! Generic codes: code → description.
 //! Manufacturer codes: (code, manufacturer) → description.
 //! Severity is inferred by code pattern (conservative heuristic).
+//!
+//! Tools look codes up through the [`DtcDatabase`] trait: [`BuiltinDtcDatabase`]
+//! serves the embedded data, [`FileDtcDatabase`] loads fleet-specific codes
+//! from CSV or JSON at startup, and [`LayeredDtcDatabase`] consults several
+//! databases in order so custom codes override or extend the built-in ones.
 
 use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
 
+use serde::Deserialize;
 use zc_protocol::dtc::DtcSeverity;
 
+use crate::error::{CanError, CanResult};
+
 static GENERIC_TSV: &str = include_str!("../data/dtc_generic.tsv");
 static MANUFACTURER_TSV: &str = include_str!("../data/dtc_manufacturer.tsv");
 
@@ -88,6 +97,283 @@ pub fn lookup_with_manufacturer(code: &str, manufacturer: &str) -> Option<DtcEnt
     lookup(code)
 }
 
+// ── Pluggable databases ───────────────────────────────────────
+
+/// A source of DTC descriptions and severities.
+pub trait DtcDatabase: Send + Sync {
+    /// Look up a code. Input is case-insensitive.
+    fn lookup(&self, code: &str) -> Option<DtcEntry>;
+
+    /// Look up a manufacturer-specific code, falling back to the generic one.
+    fn lookup_with_manufacturer(&self, code: &str, manufacturer: &str) -> Option<DtcEntry> {
+        let _ = manufacturer;
+        self.lookup(code)
+    }
+
+    /// Number of codes held (generic + manufacturer-specific).
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The embedded Wal33D database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinDtcDatabase;
+
+impl DtcDatabase for BuiltinDtcDatabase {
+    fn lookup(&self, code: &str) -> Option<DtcEntry> {
+        lookup(code)
+    }
+
+    fn lookup_with_manufacturer(&self, code: &str, manufacturer: &str) -> Option<DtcEntry> {
+        lookup_with_manufacturer(code, manufacturer)
+    }
+
+    fn len(&self) -> usize {
+        generic_count() + manufacturer_count()
+    }
+}
+
+/// The built-in database as a shared handle (the tools' default).
+pub fn builtin() -> Arc<dyn DtcDatabase> {
+    Arc::new(BuiltinDtcDatabase)
+}
+
+/// Severity source reported for codes loaded from a file.
+pub const CUSTOM_SOURCE: &str = "custom";
+
+/// One code in a custom database file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRecord {
+    code: String,
+    description: String,
+    #[serde(default)]
+    severity: Option<DtcSeverity>,
+    #[serde(default)]
+    manufacturer: Option<String>,
+}
+
+/// Fleet-specific codes loaded from a CSV or JSON file.
+///
+/// CSV needs a header with `code` and `description` columns; `severity`
+/// (`info`, `warning`, `critical`) and `manufacturer` are optional. JSON is
+/// an array of objects with the same keys. Rows without a severity get the
+/// built-in heuristic; rows with a manufacturer only match lookups for
+/// that manufacturer.
+#[derive(Debug, Clone, Default)]
+pub struct FileDtcDatabase {
+    generic: HashMap<String, DtcEntry>,
+    manufacturer: HashMap<(String, String), DtcEntry>,
+}
+
+impl FileDtcDatabase {
+    /// Load a `.csv` or `.json` file.
+    pub fn load(path: impl AsRef<Path>) -> CanResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CanError::Other(format!("cannot read {}: {e}", path.display())))?;
+        let parsed = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::from_csv(&text),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::from_json(&text),
+            _ => Err("expected a .csv or .json file".into()),
+        };
+        parsed.map_err(|e| CanError::Other(format!("{}: {e}", path.display())))
+    }
+
+    /// Parse a JSON array of records.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let records: Vec<FileRecord> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut db = Self::default();
+        for (i, record) in records.into_iter().enumerate() {
+            db.insert(record).map_err(|e| format!("entry {i}: {e}"))?;
+        }
+        Ok(db)
+    }
+
+    /// Parse CSV with a header row. Fields may be double-quoted.
+    pub fn from_csv(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty() && !l.starts_with('#'));
+        let (_, header) = lines.next().ok_or("empty CSV")?;
+        let header: Vec<String> = split_csv_line(header)
+            .map_err(|e| format!("line 1: {e}"))?
+            .into_iter()
+            .map(|h| h.trim().to_lowercase())
+            .collect();
+        let column = |name: &str| header.iter().position(|h| h == name);
+        let (Some(code_col), Some(desc_col)) = (column("code"), column("description")) else {
+            return Err("line 1: header needs code and description columns".into());
+        };
+        let (severity_col, mfr_col) = (column("severity"), column("manufacturer"));
+        if let Some(unknown) = header
+            .iter()
+            .find(|h| !["code", "description", "severity", "manufacturer"].contains(&h.as_str()))
+        {
+            return Err(format!("line 1: unknown column '{unknown}'"));
+        }
+
+        let mut db = Self::default();
+        for (index, line) in lines {
+            let line_no = index + 1;
+            let fields = split_csv_line(line).map_err(|e| format!("line {line_no}: {e}"))?;
+            if fields.len() != header.len() {
+                return Err(format!(
+                    "line {line_no}: expected {} fields, got {}",
+                    header.len(),
+                    fields.len()
+                ));
+            }
+            let optional = |col: Option<usize>| {
+                col.map(|c| fields[c].trim())
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+            };
+            let severity = optional(severity_col)
+                .map(|s| parse_severity(&s))
+                .transpose()
+                .map_err(|e| format!("line {line_no}: {e}"))?;
+            db.insert(FileRecord {
+                code: fields[code_col].clone(),
+                description: fields[desc_col].clone(),
+                severity,
+                manufacturer: optional(mfr_col),
+            })
+            .map_err(|e| format!("line {line_no}: {e}"))?;
+        }
+        Ok(db)
+    }
+
+    fn insert(&mut self, record: FileRecord) -> Result<(), String> {
+        let code = record.code.trim().to_uppercase();
+        if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("invalid DTC code '{}'", record.code));
+        }
+        let description = record.description.trim();
+        if description.is_empty() {
+            return Err(format!("{code}: description must not be empty"));
+        }
+        let entry = DtcEntry {
+            description: description.to_string(),
+            severity: record.severity.unwrap_or_else(|| infer_severity(&code)),
+            severity_source: CUSTOM_SOURCE,
+        };
+        let duplicate = match record.manufacturer {
+            Some(mfr) => self
+                .manufacturer
+                .insert((code.clone(), mfr.trim().to_uppercase()), entry)
+                .is_some(),
+            None => self.generic.insert(code.clone(), entry).is_some(),
+        };
+        if duplicate {
+            return Err(format!("{code} is listed twice"));
+        }
+        Ok(())
+    }
+}
+
+impl DtcDatabase for FileDtcDatabase {
+    fn lookup(&self, code: &str) -> Option<DtcEntry> {
+        self.generic.get(&code.to_uppercase()).cloned()
+    }
+
+    fn lookup_with_manufacturer(&self, code: &str, manufacturer: &str) -> Option<DtcEntry> {
+        let key = (code.to_uppercase(), manufacturer.to_uppercase());
+        self.manufacturer
+            .get(&key)
+            .cloned()
+            .or_else(|| self.lookup(code))
+    }
+
+    fn len(&self) -> usize {
+        self.generic.len() + self.manufacturer.len()
+    }
+}
+
+/// Several databases consulted in order; the first match wins.
+///
+/// With a default manufacturer, plain lookups also try that manufacturer's
+/// codes, which suits fleets of a single make.
+pub struct LayeredDtcDatabase {
+    layers: Vec<Arc<dyn DtcDatabase>>,
+    manufacturer: Option<String>,
+}
+
+impl LayeredDtcDatabase {
+    pub fn new(layers: Vec<Arc<dyn DtcDatabase>>) -> Self {
+        Self {
+            layers,
+            manufacturer: None,
+        }
+    }
+
+    pub fn with_manufacturer(mut self, manufacturer: Option<String>) -> Self {
+        self.manufacturer = manufacturer;
+        self
+    }
+}
+
+impl DtcDatabase for LayeredDtcDatabase {
+    fn lookup(&self, code: &str) -> Option<DtcEntry> {
+        match &self.manufacturer {
+            Some(mfr) => self.lookup_with_manufacturer(code, mfr),
+            None => self.layers.iter().find_map(|db| db.lookup(code)),
+        }
+    }
+
+    fn lookup_with_manufacturer(&self, code: &str, manufacturer: &str) -> Option<DtcEntry> {
+        self.layers
+            .iter()
+            .find_map(|db| db.lookup_with_manufacturer(code, manufacturer))
+    }
+
+    fn len(&self) -> usize {
+        self.layers.iter().map(|db| db.len()).sum()
+    }
+}
+
+fn parse_severity(s: &str) -> Result<DtcSeverity, String> {
+    match s.to_lowercase().as_str() {
+        "info" => Ok(DtcSeverity::Info),
+        "warning" => Ok(DtcSeverity::Warning),
+        "critical" => Ok(DtcSeverity::Critical),
+        other => Err(format!(
+            "invalid severity '{other}': expected info, warning or critical"
+        )),
+    }
+}
+
+/// Split one CSV line. Fields may be double-quoted; `""` inside quotes is
+/// a literal quote.
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".into());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
 /// Infer severity from the DTC code pattern.
 ///
 /// Conservative approach: only a small set of well-known patterns are Critical.
@@ -350,6 +636,86 @@ mod tests {
         );
     }
 
+    // --- Pluggable databases ---
+
+    const CUSTOM_CSV: &str = "\
+# fleet codes
+code,description,severity,manufacturer
+B1318,\"Battery voltage low, check charging\",warning,FORD
+P0300,Fleet override: misfire,,
+U3000,Tesla gateway fault,critical,TESLA
+";
+
+    #[test]
+    fn csv_database_lookup() {
+        let db = FileDtcDatabase::from_csv(CUSTOM_CSV).unwrap();
+        assert_eq!(db.len(), 3);
+
+        let entry = db.lookup("p0300").unwrap();
+        assert_eq!(entry.description, "Fleet override: misfire");
+        assert_eq!(entry.severity, DtcSeverity::Critical); // inferred
+        assert_eq!(entry.severity_source, CUSTOM_SOURCE);
+
+        assert!(
+            db.lookup("B1318").is_none(),
+            "manufacturer code needs a manufacturer"
+        );
+        let entry = db.lookup_with_manufacturer("B1318", "ford").unwrap();
+        assert_eq!(entry.description, "Battery voltage low, check charging");
+        assert_eq!(entry.severity, DtcSeverity::Warning);
+        assert!(db.lookup_with_manufacturer("P0300", "FORD").is_some());
+    }
+
+    #[test]
+    fn json_database_lookup() {
+        let db = FileDtcDatabase::from_json(
+            r#"[{"code": "u3000", "description": "Gateway fault", "manufacturer": "Tesla"}]"#,
+        )
+        .unwrap();
+        let entry = db.lookup_with_manufacturer("U3000", "TESLA").unwrap();
+        assert_eq!(entry.description, "Gateway fault");
+        assert_eq!(entry.severity, DtcSeverity::Warning);
+    }
+
+    #[test]
+    fn file_errors_name_the_line() {
+        let err = FileDtcDatabase::from_csv("code,text\nP0300,x\n").unwrap_err();
+        assert!(err.contains("line 1"), "{err}");
+        let err =
+            FileDtcDatabase::from_csv("code,description,severity\nP0300,x,severe\n").unwrap_err();
+        assert!(err.starts_with("line 2"), "{err}");
+        let err = FileDtcDatabase::from_csv("code,description\nP0300,a\np0300,b\n").unwrap_err();
+        assert!(err.contains("listed twice"), "{err}");
+        let err =
+            FileDtcDatabase::from_json(r#"[{"code": "P-1", "description": "x"}]"#).unwrap_err();
+        assert!(err.contains("entry 0"), "{err}");
+        assert!(FileDtcDatabase::load("/tmp/codes.txt").is_err());
+    }
+
+    #[test]
+    fn layered_database_prefers_custom_codes() {
+        let custom: Arc<dyn DtcDatabase> = Arc::new(FileDtcDatabase::from_csv(CUSTOM_CSV).unwrap());
+        let db = LayeredDtcDatabase::new(vec![custom.clone(), builtin()]);
+
+        assert_eq!(
+            db.lookup("P0300").unwrap().description,
+            "Fleet override: misfire"
+        );
+        // Not overridden → built-in entry.
+        assert_eq!(db.lookup("P0420").unwrap().severity_source, "database");
+        // Manufacturer code without a manufacturer → generic built-in entry.
+        assert_eq!(db.lookup("U3000").unwrap().description, "Control Module");
+        assert_eq!(db.len(), 3 + BuiltinDtcDatabase.len());
+
+        let tesla = LayeredDtcDatabase::new(vec![custom, builtin()])
+            .with_manufacturer(Some("TESLA".into()));
+        assert_eq!(
+            tesla.lookup("U3000").unwrap().description,
+            "Tesla gateway fault"
+        );
+        assert!(tesla.lookup("P0420").is_some());
+    }
+
     #[test]
     fn spot_check_multiple_categories() {
         // Verify at least one code from each category exists
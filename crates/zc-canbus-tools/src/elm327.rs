@@ -0,0 +1,626 @@
+//! ELM327 serial adapter backend.
+//!
+//! Cheap OBD-II dongles (USB, or Bluetooth through `/dev/rfcommN`) speak the
+//! ELM327 AT command set over a serial line instead of exposing a CAN
+//! socket. [`Elm327Interface`] maps that onto [`CanInterface`] so the same
+//! tools run on them:
+//!
+//! - **Init**: `ATZ`, then echo and linefeeds off, spaces and headers on,
+//!   CAN auto-formatting on, and `ATSP0` so the adapter detects the
+//!   protocol on the first request. Only the ISO 15765-4 CAN protocols
+//!   (6–9) are accepted.
+//! - **Requests**: a single-frame ISO-TP request becomes the `ATSH` header
+//!   plus its payload in hex; the adapter adds the PCI byte. Every response
+//!   line (`7E8 06 41 0C 1A F8 00 00 00`) is queued as a frame for
+//!   `recv_frame`.
+//! - **ISO-TP**: the adapter sends flow control itself and prints first and
+//!   consecutive frames as they arrive, so flow control frames from the
+//!   tools are dropped and reassembly works unchanged.
+//!
+//! The adapter only listens while it waits for a response, so
+//! `can_monitor` sees nothing between requests. Frames go through
+//! [`check_frame_safety`] before any command is written.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+use crate::ecu_profile;
+use crate::error::{CanError, CanResult};
+use crate::interface::{CanInterface, check_frame_safety};
+use crate::types::{CanFrame, OBD_REQUEST_ID};
+
+/// Serial speeds an adapter may be configured for; 38400 is the ELM327
+/// default.
+pub const SUPPORTED_BAUD_RATES: [u32; 5] = [9600, 38_400, 57_600, 115_200, 230_400];
+
+/// Serial speed unless configured.
+pub const DEFAULT_BAUD_RATE: u32 = 38_400;
+
+/// Largest standard (11-bit) CAN ID.
+const MAX_STANDARD_ID: u32 = 0x7FF;
+
+/// Longest wait for the `>` prompt after an AT command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `ATZ` restarts the chip, which takes about a second.
+const RESET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for a response to a request, including the protocol
+/// search on the first one.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Adapter answers that mean the request failed on the bus.
+const ERROR_REPLIES: [&str; 8] = [
+    "?",
+    "CAN ERROR",
+    "BUS ERROR",
+    "BUS BUSY",
+    "BUFFER FULL",
+    "DATA ERROR",
+    "UNABLE TO CONNECT",
+    "FB ERROR",
+];
+
+/// OBD-II protocol the adapter detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elm327Protocol {
+    /// ELM327 protocol number (`ATDPN`), 6–9.
+    pub number: u8,
+    /// 29-bit identifiers.
+    pub extended: bool,
+    /// Bus bitrate in bit/s.
+    pub bitrate: u32,
+}
+
+impl Elm327Protocol {
+    /// The CAN protocol with number `n`, or `None` for the older
+    /// (J1850, ISO 9141, KWP) protocols.
+    pub fn from_number(n: u8) -> Option<Self> {
+        let (extended, bitrate) = match n {
+            6 => (false, 500_000),
+            7 => (true, 500_000),
+            8 => (false, 250_000),
+            9 => (true, 250_000),
+            _ => return None,
+        };
+        Some(Self {
+            number: n,
+            extended,
+            bitrate,
+        })
+    }
+}
+
+/// Serial line state, held while a command is in flight.
+struct Port<T> {
+    io: T,
+    /// Header currently set with `ATSH`/`ATCP`.
+    header: Option<u32>,
+    /// Whether CAN auto-formatting (`ATCAF1`) is on.
+    auto_format: bool,
+}
+
+/// [`CanInterface`] over an ELM327 adapter on `T` (a serial port).
+pub struct Elm327Interface<T> {
+    name: String,
+    version: String,
+    protocol: Elm327Protocol,
+    port: Mutex<Port<T>>,
+    /// Response frames not yet returned by `recv_frame`.
+    rx: std::sync::Mutex<VecDeque<CanFrame>>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Elm327Interface<T> {
+    /// Initialise the adapter on `io` and detect the vehicle's protocol.
+    /// With `bitrate`, a protocol at another rate is an error. `name`
+    /// (the device path) is used in messages.
+    pub async fn connect(io: T, name: &str, bitrate: Option<u32>) -> CanResult<Self> {
+        let mut port = Port {
+            io,
+            header: None,
+            auto_format: true,
+        };
+
+        let reset = command(&mut port, name, "ATZ", RESET_TIMEOUT).await?;
+        let version = reset
+            .iter()
+            .find(|l| l.starts_with("ELM327"))
+            .cloned()
+            .ok_or_else(|| {
+                CanError::Interface(format!("{name}: no ELM327 adapter answered ATZ"))
+            })?;
+        for cmd in ["ATE0", "ATL0", "ATS1", "ATH1", "ATCAF1", "ATSP0"] {
+            expect_ok(&mut port, name, cmd).await?;
+        }
+
+        // The first request makes the adapter search for the protocol.
+        let search = command(&mut port, name, "0100", REQUEST_TIMEOUT).await?;
+        if let Some(err) = search.iter().find(|l| is_error_reply(l)) {
+            return Err(CanError::Interface(format!(
+                "{name}: protocol detection failed: {err}"
+            )));
+        }
+        let dpn = command(&mut port, name, "ATDPN", COMMAND_TIMEOUT).await?;
+        let number = dpn
+            .first()
+            .map(|l| l.trim_start_matches('A'))
+            .and_then(|n| u8::from_str_radix(n, 16).ok())
+            .unwrap_or(0);
+        let protocol = Elm327Protocol::from_number(number).ok_or_else(|| {
+            CanError::Interface(format!(
+                "{name}: vehicle uses ELM327 protocol {number}, not CAN (ISO 15765-4)"
+            ))
+        })?;
+        if let Some(expected) = bitrate
+            && expected != protocol.bitrate
+        {
+            return Err(CanError::Interface(format!(
+                "{name}: vehicle bus runs at {} bit/s, expected {expected}",
+                protocol.bitrate
+            )));
+        }
+
+        tracing::info!(
+            adapter = name,
+            version = %version,
+            protocol = protocol.number,
+            bitrate = protocol.bitrate,
+            "ELM327 adapter initialised"
+        );
+        Ok(Self {
+            name: name.to_string(),
+            version,
+            protocol,
+            port: Mutex::new(port),
+            rx: std::sync::Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Device path of the adapter.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adapter identification from `ATZ`, e.g. "ELM327 v1.5".
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The detected protocol.
+    pub fn protocol(&self) -> Elm327Protocol {
+        self.protocol
+    }
+
+    /// Point the adapter at `id`: the header, and the responses to accept
+    /// (the ECU's response ID for physical 11-bit requests, otherwise the
+    /// adapter's automatic filter).
+    async fn set_header(&self, port: &mut Port<T>, id: u32) -> CanResult<()> {
+        if port.header == Some(id) {
+            return Ok(());
+        }
+        if id > MAX_STANDARD_ID {
+            expect_ok(port, &self.name, &format!("ATCP{:02X}", (id >> 24) & 0x1F)).await?;
+            expect_ok(port, &self.name, &format!("ATSH{:06X}", id & 0xFF_FFFF)).await?;
+            expect_ok(port, &self.name, "ATAR").await?;
+        } else {
+            expect_ok(port, &self.name, &format!("ATSH{id:03X}")).await?;
+            if id == OBD_REQUEST_ID {
+                expect_ok(port, &self.name, "ATAR").await?;
+            } else {
+                let response = ecu_profile::all_profiles()
+                    .iter()
+                    .find(|p| p.request_id == id)
+                    .map_or(id + 8, |p| p.response_id);
+                expect_ok(port, &self.name, &format!("ATCRA{response:03X}")).await?;
+            }
+        }
+        port.header = Some(id);
+        Ok(())
+    }
+
+    async fn set_auto_format(&self, port: &mut Port<T>, on: bool) -> CanResult<()> {
+        if port.auto_format != on {
+            expect_ok(port, &self.name, if on { "ATCAF1" } else { "ATCAF0" }).await?;
+            port.auto_format = on;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> CanInterface for Elm327Interface<T> {
+    async fn send_frame(&self, frame: &CanFrame) -> CanResult<()> {
+        check_frame_safety(frame)?;
+
+        let pci = frame.data.first().copied().unwrap_or(0);
+        // The adapter already answered the ECU's first frame.
+        if pci >> 4 == 0x3 {
+            return Ok(());
+        }
+        let single_frame_len = (pci >> 4 == 0 && (1..=7).contains(&pci))
+            .then_some(pci as usize)
+            .filter(|len| *len < frame.data.len());
+
+        let mut port = self.port.lock().await;
+        self.set_header(&mut port, frame.id).await?;
+        // Single frames are re-framed by the adapter; anything else is
+        // sent as is.
+        let payload = match single_frame_len {
+            Some(len) => {
+                self.set_auto_format(&mut port, true).await?;
+                &frame.data[1..=len]
+            }
+            None => {
+                self.set_auto_format(&mut port, false).await?;
+                &frame.data[..]
+            }
+        };
+        if payload.is_empty() {
+            return Err(CanError::Interface(format!(
+                "{}: ELM327 adapters cannot send empty frames",
+                self.name
+            )));
+        }
+        let hex: String = payload.iter().map(|b| format!("{b:02X}")).collect();
+        let lines = command(&mut port, &self.name, &hex, REQUEST_TIMEOUT).await?;
+        drop(port);
+
+        let mut rx = self.rx.lock().unwrap();
+        for line in &lines {
+            if is_error_reply(line) {
+                return Err(CanError::Interface(format!(
+                    "{}: adapter reported {line}",
+                    self.name
+                )));
+            }
+            match parse_frame(line) {
+                Some(frame) => rx.push_back(frame),
+                None => tracing::trace!(adapter = %self.name, line = %line, "ELM327 line skipped"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn recv_frame(&self, timeout: Duration) -> CanResult<CanFrame> {
+        if let Some(frame) = self.rx.lock().unwrap().pop_front() {
+            return Ok(frame);
+        }
+        // Nothing arrives until the next request.
+        tokio::time::sleep(timeout).await;
+        Err(CanError::Timeout {
+            timeout_ms: timeout.as_millis() as u64,
+        })
+    }
+
+    async fn drain_rx_buffer(&self) {
+        self.rx.lock().unwrap().clear();
+    }
+}
+
+/// Send `cmd` and collect the reply lines up to the `>` prompt.
+async fn command<T: AsyncRead + AsyncWrite + Unpin>(
+    port: &mut Port<T>,
+    name: &str,
+    cmd: &str,
+    timeout: Duration,
+) -> CanResult<Vec<String>> {
+    let io_error =
+        |e: std::io::Error| CanError::Interface(format!("{name}: serial I/O failed: {e}"));
+    port.io
+        .write_all(format!("{cmd}\r").as_bytes())
+        .await
+        .map_err(io_error)?;
+    port.io.flush().await.map_err(io_error)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 256];
+    while !reply.contains(&b'>') {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(CanError::Interface(format!(
+                "{name}: no prompt from adapter after {cmd}"
+            )));
+        }
+        match tokio::time::timeout(remaining, port.io.read(&mut buf)).await {
+            // A serial port read with VTIME returns nothing after a quiet spell.
+            Ok(Ok(0)) => tokio::time::sleep(Duration::from_millis(10)).await,
+            Ok(Ok(n)) => reply.extend_from_slice(&buf[..n]),
+            Ok(Err(e)) => return Err(io_error(e)),
+            Err(_) => {}
+        }
+    }
+
+    let text = String::from_utf8_lossy(&reply);
+    let text = text.split('>').next().unwrap_or_default();
+    Ok(text
+        .split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && *l != cmd && !l.starts_with("SEARCHING"))
+        .map(String::from)
+        .collect())
+}
+
+async fn expect_ok<T: AsyncRead + AsyncWrite + Unpin>(
+    port: &mut Port<T>,
+    name: &str,
+    cmd: &str,
+) -> CanResult<()> {
+    let lines = command(port, name, cmd, COMMAND_TIMEOUT).await?;
+    if lines.iter().any(|l| l == "OK") {
+        Ok(())
+    } else {
+        Err(CanError::Interface(format!(
+            "{name}: {cmd} answered {:?}",
+            lines.join(" ")
+        )))
+    }
+}
+
+fn is_error_reply(line: &str) -> bool {
+    ERROR_REPLIES.iter().any(|e| line.starts_with(e))
+}
+
+/// A response line with headers and spaces on: `7E8 06 41 0C 1A F8` (11-bit)
+/// or `18 DA F1 10 06 41 0C 1A F8` (29-bit). `NO DATA` and other text give
+/// `None`.
+pub fn parse_frame(line: &str) -> Option<CanFrame> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (id, data) = match tokens.first()?.len() {
+        3 => (u32::from_str_radix(tokens[0], 16).ok()?, &tokens[1..]),
+        2 if tokens.len() >= 4 => {
+            let id = u32::from_str_radix(&tokens[..4].concat(), 16).ok()?;
+            (id, &tokens[4..])
+        }
+        _ => return None,
+    };
+    if data.is_empty() || data.len() > 8 {
+        return None;
+    }
+    let data = data
+        .iter()
+        .map(|b| {
+            (b.len() == 2)
+                .then(|| u8::from_str_radix(b, 16).ok())
+                .flatten()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    Some(CanFrame::new(id, data))
+}
+
+/// Open the adapter at `path` (e.g. `/dev/ttyUSB0`, `/dev/rfcomm0`) at
+/// `baud` and initialise it.
+#[cfg(target_os = "linux")]
+pub async fn open(
+    path: &str,
+    baud: u32,
+    bitrate: Option<u32>,
+) -> CanResult<Elm327Interface<tokio::fs::File>> {
+    use nix::sys::termios::{self, BaudRate, SetArg, SpecialCharacterIndices};
+
+    let speed = match baud {
+        9600 => BaudRate::B9600,
+        38_400 => BaudRate::B38400,
+        57_600 => BaudRate::B57600,
+        115_200 => BaudRate::B115200,
+        230_400 => BaudRate::B230400,
+        _ => {
+            return Err(CanError::Interface(format!(
+                "unsupported baud rate {baud}, expected one of {SUPPORTED_BAUD_RATES:?}"
+            )));
+        }
+    };
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| CanError::Interface(format!("{path}: cannot open adapter: {e}")))?;
+    let tty_error =
+        |e: nix::Error| CanError::Interface(format!("{path}: serial setup failed: {e}"));
+    let mut tty = termios::tcgetattr(&file).map_err(tty_error)?;
+    termios::cfmakeraw(&mut tty);
+    termios::cfsetspeed(&mut tty, speed).map_err(tty_error)?;
+    // Reads return after 100 ms without data instead of blocking.
+    tty.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+    tty.control_chars[SpecialCharacterIndices::VTIME as usize] = 1;
+    termios::tcsetattr(&file, SetArg::TCSANOW, &tty).map_err(tty_error)?;
+
+    Elm327Interface::connect(tokio::fs::File::from_std(file), path, bitrate).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader, DuplexStream};
+
+    /// A scripted adapter: answers each command with the reply for the
+    /// first matching prefix, `OK` for other AT commands and `NO DATA`
+    /// otherwise. Returns the commands it received.
+    fn fake_adapter(
+        io: DuplexStream,
+        replies: Vec<(&'static str, &'static str)>,
+    ) -> tokio::task::JoinHandle<Vec<String>> {
+        tokio::spawn(async move {
+            let (read, mut write) = tokio::io::split(io);
+            let mut lines = BufReader::new(read).split(b'\r');
+            let mut seen = Vec::new();
+            while let Ok(Some(line)) = lines.next_segment().await {
+                let cmd = String::from_utf8(line).unwrap();
+                let reply = match replies.iter().find(|(prefix, _)| cmd.starts_with(prefix)) {
+                    Some((_, reply)) => reply.to_string(),
+                    None if cmd.starts_with("AT") => "OK".to_string(),
+                    None => "NO DATA".to_string(),
+                };
+                seen.push(cmd);
+                let out = format!("{reply}\r\r>");
+                if write.write_all(out.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            seen
+        })
+    }
+
+    const INIT: [(&str, &str); 3] = [
+        ("ATZ", "\r\rELM327 v1.5"),
+        ("0100", "SEARCHING...\r7E8 06 41 00 BE 3F A8 13 00"),
+        ("ATDPN", "A6"),
+    ];
+
+    fn replies(extra: &[(&'static str, &'static str)]) -> Vec<(&'static str, &'static str)> {
+        extra.iter().chain(INIT.iter()).copied().collect()
+    }
+
+    #[test]
+    fn response_lines_become_frames() {
+        let frame = parse_frame("7E8 06 41 0C 1A F8 00 00 00").unwrap();
+        assert_eq!(frame.id, 0x7E8);
+        assert_eq!(frame.data, [0x06, 0x41, 0x0C, 0x1A, 0xF8, 0, 0, 0]);
+
+        let frame = parse_frame("18 DA F1 10 03 41 0D 32").unwrap();
+        assert_eq!(frame.id, 0x18DA_F110);
+        assert_eq!(frame.data, [0x03, 0x41, 0x0D, 0x32]);
+
+        for line in ["NO DATA", "OK", "ELM327 v1.5", "7E8", "7E8 0G 41"] {
+            assert!(parse_frame(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn only_can_protocols_are_accepted() {
+        assert_eq!(Elm327Protocol::from_number(8).unwrap().bitrate, 250_000);
+        assert!(Elm327Protocol::from_number(7).unwrap().extended);
+        assert!(Elm327Protocol::from_number(3).is_none());
+    }
+
+    #[tokio::test]
+    async fn connect_detects_the_protocol() {
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let adapter = fake_adapter(theirs, replies(&[]));
+        let elm = Elm327Interface::connect(ours, "/dev/ttyUSB0", Some(500_000))
+            .await
+            .unwrap();
+        assert_eq!(elm.version(), "ELM327 v1.5");
+        assert_eq!(elm.protocol().number, 6);
+        drop(elm);
+        let seen = adapter.await.unwrap();
+        assert_eq!(
+            seen,
+            [
+                "ATZ", "ATE0", "ATL0", "ATS1", "ATH1", "ATCAF1", "ATSP0", "0100", "ATDPN"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn connect_rejects_non_can_vehicles_and_wrong_bitrates() {
+        let (ours, theirs) = tokio::io::duplex(1024);
+        fake_adapter(theirs, replies(&[("ATDPN", "A3")]));
+        let err = Elm327Interface::connect(ours, "/dev/ttyUSB0", None)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("protocol 3, not CAN"), "{err}");
+
+        let (ours, theirs) = tokio::io::duplex(1024);
+        fake_adapter(theirs, replies(&[]));
+        let err = Elm327Interface::connect(ours, "/dev/ttyUSB0", Some(250_000))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("expected 250000"), "{err}");
+
+        let (ours, theirs) = tokio::io::duplex(1024);
+        fake_adapter(theirs, vec![("ATZ", "?")]);
+        assert!(
+            Elm327Interface::connect(ours, "/dev/ttyUSB0", None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn obd_request_goes_through_the_adapter() {
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let adapter = fake_adapter(theirs, replies(&[("010C", "7E8 04 41 0C 1A F8 00 00 00")]));
+        let elm = Elm327Interface::connect(ours, "/dev/ttyUSB0", None)
+            .await
+            .unwrap();
+
+        let frames = elm
+            .obd_request(0x01, Some(0x0C), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(frames[0].data[..4], [0x04, 0x41, 0x0C, 0x1A]);
+        assert!(
+            elm.obd_request(0x04, None, Duration::from_secs(1))
+                .await
+                .is_err()
+        );
+
+        drop(elm);
+        let seen = adapter.await.unwrap();
+        assert_eq!(seen[9..], ["ATSH7DF", "ATAR", "010C"]);
+    }
+
+    #[tokio::test]
+    async fn multi_frame_responses_are_reassembled() {
+        let vin =
+            "7E8 10 14 49 02 01 31 44 34\r7E8 21 47 50 30 30 52 35 35\r7E8 22 42 31 32 33 34 35 36";
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let adapter = fake_adapter(theirs, replies(&[("0902", vin)]));
+        let elm = Elm327Interface::connect(ours, "/dev/ttyUSB0", None)
+            .await
+            .unwrap();
+
+        elm.send_frame(&CanFrame::new(
+            OBD_REQUEST_ID,
+            vec![0x02, 0x09, 0x02, 0, 0, 0, 0, 0],
+        ))
+        .await
+        .unwrap();
+        let payload = crate::obd::isotp_recv(&elm, 0x7E8, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(&payload[..3], [0x49, 0x02, 0x01]);
+        assert_eq!(String::from_utf8_lossy(&payload[3..]), "1D4GP00R55B123456");
+
+        drop(elm);
+        // The flow control frame from isotp_recv never reached the adapter.
+        let seen = adapter.await.unwrap();
+        assert_eq!(seen[9..], ["ATSH7DF", "ATAR", "0902"]);
+    }
+
+    #[tokio::test]
+    async fn physical_requests_filter_on_the_response_id() {
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let adapter = fake_adapter(theirs, replies(&[("22F190", "CAN ERROR")]));
+        let elm = Elm327Interface::connect(ours, "/dev/ttyUSB0", None)
+            .await
+            .unwrap();
+
+        let err = elm
+            .send_frame(&CanFrame::new(
+                0x7E0,
+                vec![0x03, 0x22, 0xF1, 0x90, 0, 0, 0, 0],
+            ))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("adapter reported CAN ERROR"));
+        // Raw frames switch auto-formatting off.
+        elm.send_frame(&CanFrame::new(0x7E0, vec![0x10, 0x0A, 0x22]))
+            .await
+            .unwrap();
+
+        drop(elm);
+        let seen = adapter.await.unwrap();
+        assert_eq!(
+            seen[9..],
+            ["ATSH7E0", "ATCRA7E8", "22F190", "ATCAF0", "100A22"]
+        );
+    }
+}
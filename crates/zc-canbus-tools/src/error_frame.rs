@@ -0,0 +1,289 @@
+//! SocketCAN error frame classification.
+//!
+//! When error reporting is enabled on a socket (`CAN_RAW_ERR_FILTER`), the
+//! kernel delivers bus and controller errors as frames with
+//! [`CAN_ERR_FLAG`] set in the ID. The low ID bits give the error class and
+//! the data bytes give details (see `linux/can/error.h`). This module turns
+//! one such frame into a list of [`ErrorClass`]es.
+
+use serde::Serialize;
+
+use crate::types::CanFrame;
+
+/// Set in the CAN ID of an error frame.
+pub const CAN_ERR_FLAG: u32 = 0x2000_0000;
+
+// ── Error class bits (CAN ID) ──────────────────────────────────
+
+pub const CAN_ERR_TX_TIMEOUT: u32 = 0x0001;
+pub const CAN_ERR_LOSTARB: u32 = 0x0002;
+pub const CAN_ERR_CRTL: u32 = 0x0004;
+pub const CAN_ERR_PROT: u32 = 0x0008;
+pub const CAN_ERR_TRX: u32 = 0x0010;
+pub const CAN_ERR_ACK: u32 = 0x0020;
+pub const CAN_ERR_BUSOFF: u32 = 0x0040;
+pub const CAN_ERR_BUSERROR: u32 = 0x0080;
+pub const CAN_ERR_RESTARTED: u32 = 0x0100;
+/// `data[6]`/`data[7]` hold the TX/RX error counters.
+pub const CAN_ERR_CNT: u32 = 0x0200;
+
+// ── Controller status (data[1]) ────────────────────────────────
+
+const CRTL_RX_OVERFLOW: u8 = 0x01;
+const CRTL_TX_OVERFLOW: u8 = 0x02;
+const CRTL_RX_WARNING: u8 = 0x04;
+const CRTL_TX_WARNING: u8 = 0x08;
+const CRTL_RX_PASSIVE: u8 = 0x10;
+const CRTL_TX_PASSIVE: u8 = 0x20;
+
+// ── Protocol violation type (data[2]) and location (data[3]) ───
+
+const PROT_BIT: u8 = 0x01;
+const PROT_FORM: u8 = 0x02;
+const PROT_STUFF: u8 = 0x04;
+const PROT_BIT0: u8 = 0x08;
+const PROT_BIT1: u8 = 0x10;
+const PROT_OVERLOAD: u8 = 0x20;
+const PROT_LOC_CRC_SEQ: u8 = 0x08;
+const PROT_LOC_CRC_DEL: u8 = 0x18;
+
+/// Diagnostic category of a bus or controller error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// Transmission did not complete in time.
+    TxTimeout,
+    /// Lost arbitration (normal under load, suspicious if frequent).
+    LostArbitration,
+    /// RX or TX buffer overflow in the controller.
+    ControllerOverflow,
+    /// Error counter reached the warning level (96).
+    ErrorWarning,
+    /// Controller went error-passive (counter ≥ 128).
+    ErrorPassive,
+    /// Other controller problem.
+    ControllerProblem,
+    /// Transmitted bit read back with a different level.
+    BitError,
+    /// More than five consecutive equal bits.
+    StuffError,
+    /// Fixed-format field had an illegal value.
+    FormError,
+    /// CRC mismatch.
+    CrcError,
+    /// Overload frame.
+    Overload,
+    /// Other protocol violation.
+    ProtocolViolation,
+    /// Transceiver reported a wiring fault (CAN-H/CAN-L short or open).
+    Transceiver,
+    /// No node acknowledged the frame (nothing else on the bus, or wiring).
+    AckError,
+    /// Controller went bus-off (TX counter > 255).
+    BusOff,
+    /// Unspecified bus error.
+    BusError,
+    /// Controller was restarted after bus-off.
+    ControllerRestarted,
+}
+
+impl ErrorClass {
+    /// Snake-case name, as used in tool output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TxTimeout => "tx_timeout",
+            Self::LostArbitration => "lost_arbitration",
+            Self::ControllerOverflow => "controller_overflow",
+            Self::ErrorWarning => "error_warning",
+            Self::ErrorPassive => "error_passive",
+            Self::ControllerProblem => "controller_problem",
+            Self::BitError => "bit_error",
+            Self::StuffError => "stuff_error",
+            Self::FormError => "form_error",
+            Self::CrcError => "crc_error",
+            Self::Overload => "overload",
+            Self::ProtocolViolation => "protocol_violation",
+            Self::Transceiver => "transceiver",
+            Self::AckError => "ack_error",
+            Self::BusOff => "bus_off",
+            Self::BusError => "bus_error",
+            Self::ControllerRestarted => "controller_restarted",
+        }
+    }
+}
+
+/// Whether `frame` is an error frame.
+pub fn is_error_frame(frame: &CanFrame) -> bool {
+    frame.id & CAN_ERR_FLAG != 0
+}
+
+/// Classify an error frame. Returns an empty list for data frames.
+pub fn classify(frame: &CanFrame) -> Vec<ErrorClass> {
+    if !is_error_frame(frame) {
+        return Vec::new();
+    }
+    let class = frame.id;
+    let byte = |i: usize| frame.data.get(i).copied().unwrap_or(0);
+    let mut classes = Vec::new();
+
+    if class & CAN_ERR_TX_TIMEOUT != 0 {
+        classes.push(ErrorClass::TxTimeout);
+    }
+    if class & CAN_ERR_LOSTARB != 0 {
+        classes.push(ErrorClass::LostArbitration);
+    }
+    if class & CAN_ERR_CRTL != 0 {
+        let status = byte(1);
+        let before = classes.len();
+        if status & (CRTL_RX_OVERFLOW | CRTL_TX_OVERFLOW) != 0 {
+            classes.push(ErrorClass::ControllerOverflow);
+        }
+        if status & (CRTL_RX_WARNING | CRTL_TX_WARNING) != 0 {
+            classes.push(ErrorClass::ErrorWarning);
+        }
+        if status & (CRTL_RX_PASSIVE | CRTL_TX_PASSIVE) != 0 {
+            classes.push(ErrorClass::ErrorPassive);
+        }
+        if classes.len() == before {
+            classes.push(ErrorClass::ControllerProblem);
+        }
+    }
+    if class & CAN_ERR_PROT != 0 {
+        let kind = byte(2);
+        let location = byte(3);
+        let before = classes.len();
+        if kind & (PROT_BIT | PROT_BIT0 | PROT_BIT1) != 0 {
+            classes.push(ErrorClass::BitError);
+        }
+        if kind & PROT_STUFF != 0 {
+            classes.push(ErrorClass::StuffError);
+        }
+        if kind & PROT_FORM != 0 {
+            classes.push(ErrorClass::FormError);
+        }
+        if kind & PROT_OVERLOAD != 0 {
+            classes.push(ErrorClass::Overload);
+        }
+        if matches!(location, PROT_LOC_CRC_SEQ | PROT_LOC_CRC_DEL) {
+            classes.push(ErrorClass::CrcError);
+        }
+        if classes.len() == before {
+            classes.push(ErrorClass::ProtocolViolation);
+        }
+    }
+    if class & CAN_ERR_TRX != 0 {
+        classes.push(ErrorClass::Transceiver);
+    }
+    if class & CAN_ERR_ACK != 0 {
+        classes.push(ErrorClass::AckError);
+    }
+    if class & CAN_ERR_BUSOFF != 0 {
+        classes.push(ErrorClass::BusOff);
+    }
+    // Generic bus error only when no protocol detail was given.
+    if class & CAN_ERR_BUSERROR != 0 && class & CAN_ERR_PROT == 0 {
+        classes.push(ErrorClass::BusError);
+    }
+    if class & CAN_ERR_RESTARTED != 0 {
+        classes.push(ErrorClass::ControllerRestarted);
+    }
+    classes
+}
+
+/// TX and RX error counters carried by the frame, if present.
+pub fn error_counters(frame: &CanFrame) -> Option<(u8, u8)> {
+    if !is_error_frame(frame) || frame.id & CAN_ERR_CNT == 0 || frame.data.len() < 8 {
+        return None;
+    }
+    Some((frame.data[6], frame.data[7]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn err(class: u32, data: [u8; 8]) -> CanFrame {
+        CanFrame::new(CAN_ERR_FLAG | class, data.to_vec())
+    }
+
+    #[test]
+    fn data_frames_are_not_errors() {
+        let frame = CanFrame::new(0x7E8, vec![0x02, 0x41, 0x0C]);
+        assert!(!is_error_frame(&frame));
+        assert!(classify(&frame).is_empty());
+    }
+
+    #[test]
+    fn classifies_protocol_errors() {
+        let stuff = err(
+            CAN_ERR_PROT | CAN_ERR_BUSERROR,
+            [0, 0, PROT_STUFF, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(classify(&stuff), vec![ErrorClass::StuffError]);
+
+        let bit = err(CAN_ERR_PROT, [0, 0, PROT_BIT1, 0, 0, 0, 0, 0]);
+        assert_eq!(classify(&bit), vec![ErrorClass::BitError]);
+
+        let crc = err(CAN_ERR_PROT, [0, 0, 0, PROT_LOC_CRC_SEQ, 0, 0, 0, 0]);
+        assert_eq!(classify(&crc), vec![ErrorClass::CrcError]);
+
+        let other = err(CAN_ERR_PROT, [0; 8]);
+        assert_eq!(classify(&other), vec![ErrorClass::ProtocolViolation]);
+    }
+
+    #[test]
+    fn classifies_ack_and_restart() {
+        assert_eq!(
+            classify(&err(CAN_ERR_ACK, [0; 8])),
+            vec![ErrorClass::AckError]
+        );
+        assert_eq!(
+            classify(&err(CAN_ERR_BUSOFF | CAN_ERR_RESTARTED, [0; 8])),
+            vec![ErrorClass::BusOff, ErrorClass::ControllerRestarted]
+        );
+        assert_eq!(
+            classify(&err(CAN_ERR_BUSERROR, [0; 8])),
+            vec![ErrorClass::BusError]
+        );
+    }
+
+    #[test]
+    fn classifies_controller_status() {
+        let passive = err(CAN_ERR_CRTL, [0, CRTL_TX_PASSIVE, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(classify(&passive), vec![ErrorClass::ErrorPassive]);
+
+        let overflow_warn = err(
+            CAN_ERR_CRTL,
+            [0, CRTL_RX_OVERFLOW | CRTL_RX_WARNING, 0, 0, 0, 0, 0, 0],
+        );
+        assert_eq!(
+            classify(&overflow_warn),
+            vec![ErrorClass::ControllerOverflow, ErrorClass::ErrorWarning]
+        );
+
+        assert_eq!(
+            classify(&err(CAN_ERR_CRTL, [0; 8])),
+            vec![ErrorClass::ControllerProblem]
+        );
+    }
+
+    #[test]
+    fn reads_error_counters() {
+        let frame = err(
+            CAN_ERR_CRTL | CAN_ERR_CNT,
+            [0, CRTL_TX_WARNING, 0, 0, 0, 0, 100, 3],
+        );
+        assert_eq!(error_counters(&frame), Some((100, 3)));
+        assert_eq!(error_counters(&err(CAN_ERR_ACK, [0; 8])), None);
+    }
+
+    #[test]
+    fn class_names_match_serde() {
+        for class in [ErrorClass::AckError, ErrorClass::ControllerRestarted] {
+            assert_eq!(
+                serde_json::to_value(class).unwrap(),
+                serde_json::json!(class.as_str())
+            );
+        }
+    }
+}
@@ -1,9 +1,14 @@
 //! CAN bus interface abstraction.
 //!
-//! `CanInterface` trait with `send_frame`/`recv_frame`. Two impls:
+//! `CanInterface` trait with `send_frame`/`recv_frame`. Three impls:
 //! - `SocketCanInterface` — Linux-only, wraps `socketcan::CanSocket`
+//! - `Elm327Interface` — ELM327 serial adapters (in `elm327.rs`)
 //! - `MockCanInterface` — all platforms, scripted responses (in `mock.rs`)
 //!
+//! `SocketCanInterface::open` validates the interface name and optional
+//! bitrate up front and maps kernel errors (missing link, link down,
+//! tx queue full) to descriptive `CanError::Interface` messages.
+//!
 //! Safety enforcement happens at the interface level: `send_frame` rejects
 //! disallowed OBD-II modes before any bytes hit the bus.
 
@@ -13,10 +18,9 @@ use std::time::Duration;
 use crate::error::{CanError, CanResult};
 use crate::safety;
 use crate::types::{CanFrame, OBD_REQUEST_ID, OBD_RESPONSE_ID_MAX, OBD_RESPONSE_ID_MIN};
-#[cfg(target_os = "linux")]
 use crate::{ecu_profile, uds_safety};
 #[cfg(target_os = "linux")]
-use socketcan::{EmbeddedFrame, Frame};
+use socketcan::{EmbeddedFrame, Frame, SocketOptions};
 
 /// Trait for CAN bus interface implementations.
 #[async_trait]
@@ -27,6 +31,13 @@ pub trait CanInterface: Send + Sync {
     /// Receive a CAN frame, blocking up to `timeout`.
     async fn recv_frame(&self, timeout: Duration) -> CanResult<CanFrame>;
 
+    /// Enable or disable delivery of error frames (ID with
+    /// [`CAN_ERR_FLAG`](crate::error_frame::CAN_ERR_FLAG) set) to
+    /// `recv_frame`. Off by default so protocol code never sees them.
+    async fn set_error_frames(&self, _enabled: bool) -> CanResult<()> {
+        Ok(())
+    }
+
     /// Drain stale frames from the receive buffer.
     ///
     /// On real SocketCAN interfaces, CAN frames accumulate in the kernel
@@ -94,8 +105,108 @@ pub fn is_obd_response(id: u32) -> bool {
     (OBD_RESPONSE_ID_MIN..=OBD_RESPONSE_ID_MAX).contains(&id)
 }
 
+/// Reject frames that would start a disallowed OBD-II mode or UDS service.
+///
+/// ISO-TP flow control frames share the request IDs but are not service
+/// requests, so they always pass. Every transmitting interface calls this
+/// before a frame reaches the bus or adapter.
+pub fn check_frame_safety(frame: &CanFrame) -> CanResult<()> {
+    if frame.data.len() >= 2 {
+        let pci_len = frame.data[0];
+
+        // OBD-II safety: check mode for broadcast requests.
+        if frame.id == OBD_REQUEST_ID && (1..=7).contains(&pci_len) {
+            let mode = frame.data[1];
+            if !safety::is_mode_allowed(mode) {
+                return Err(CanError::SafetyViolation { mode });
+            }
+        }
+
+        // UDS safety: check service ID for known ECU request IDs.
+        let is_flow_control = (pci_len >> 4) == 0x03;
+        if !is_flow_control {
+            let is_ecu_request = ecu_profile::all_profiles()
+                .iter()
+                .any(|p| p.request_id == frame.id);
+            if is_ecu_request && (1..=7).contains(&pci_len) {
+                let service_id = frame.data[1];
+                if !uds_safety::is_uds_service_allowed(service_id) {
+                    return Err(CanError::UdsSafetyViolation {
+                        service_id,
+                        service_name: uds_safety::uds_service_name(service_id).to_string(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// ── Link settings ───────────────────────────────────────────────
+
+/// Bitrates accepted for a SocketCAN link. OBD-II uses 250k or 500k;
+/// 125k and 1M cover body and chassis buses.
+pub const SUPPORTED_BITRATES: [u32; 4] = [125_000, 250_000, 500_000, 1_000_000];
+
+/// Maximum Linux interface name length (IFNAMSIZ minus the NUL).
+const MAX_IFNAME_LEN: usize = 15;
+
+/// Reject interface names the kernel would never accept.
+pub fn validate_interface_name(name: &str) -> CanResult<()> {
+    if name.is_empty() {
+        return Err(CanError::Interface("interface name is empty".into()));
+    }
+    if name.len() > MAX_IFNAME_LEN {
+        return Err(CanError::Interface(format!(
+            "interface name '{name}' exceeds {MAX_IFNAME_LEN} characters"
+        )));
+    }
+    if name.contains(['/', ' ', '\0']) {
+        return Err(CanError::Interface(format!(
+            "interface name '{name}' contains invalid characters"
+        )));
+    }
+    Ok(())
+}
+
+/// Reject bitrates outside [`SUPPORTED_BITRATES`].
+pub fn validate_bitrate(bitrate: u32) -> CanResult<()> {
+    if SUPPORTED_BITRATES.contains(&bitrate) {
+        Ok(())
+    } else {
+        Err(CanError::Interface(format!(
+            "unsupported CAN bitrate {bitrate} (expected one of {SUPPORTED_BITRATES:?})"
+        )))
+    }
+}
+
 // ── SocketCAN (Linux-only) ──────────────────────────────────────
 
+#[cfg(target_os = "linux")]
+const ENODEV: i32 = 19;
+#[cfg(target_os = "linux")]
+const ENETDOWN: i32 = 100;
+#[cfg(target_os = "linux")]
+const ENOBUFS: i32 = 105;
+
+/// Turn a SocketCAN I/O error into an actionable `CanError`.
+#[cfg(target_os = "linux")]
+fn map_io_error(interface: &str, op: &str, e: &std::io::Error) -> CanError {
+    match e.raw_os_error() {
+        Some(ENODEV) => CanError::Interface(format!("{interface}: no such CAN interface")),
+        Some(ENETDOWN) => CanError::Interface(format!(
+            "{interface}: link is down (ip link set {interface} up)"
+        )),
+        Some(ENOBUFS) => CanError::Interface(format!(
+            "{interface}: transmit queue full (bus-off or no other node acknowledging)"
+        )),
+        _ if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            CanError::Interface(format!("{interface}: permission denied during {op}"))
+        }
+        _ => CanError::Interface(format!("{interface}: {op} failed: {e}")),
+    }
+}
+
 /// SocketCAN interface for Linux hosts.
 ///
 /// Wraps `socketcan::tokio::CanSocket` for async CAN bus I/O.
@@ -103,6 +214,7 @@ pub fn is_obd_response(id: u32) -> bool {
 /// checked before any frame reaches the bus.
 #[cfg(target_os = "linux")]
 pub struct SocketCanInterface {
+    name: String,
     socket: socketcan::tokio::CanSocket,
 }
 
@@ -110,10 +222,69 @@ pub struct SocketCanInterface {
 impl SocketCanInterface {
     /// Open a SocketCAN interface by name (e.g., "can0").
     pub fn new(interface_name: &str) -> CanResult<Self> {
+        Self::open(interface_name, None)
+    }
+
+    /// Open a SocketCAN interface and, if `bitrate` is given, check that the
+    /// link is running at that rate.
+    ///
+    /// The agent does not reconfigure the link itself (that needs
+    /// `CAP_NET_ADMIN`); a mismatch is reported as an error. Links that do
+    /// not report a bitrate, such as `vcan`, are accepted.
+    pub fn open(interface_name: &str, bitrate: Option<u32>) -> CanResult<Self> {
+        validate_interface_name(interface_name)?;
+        if let Some(expected) = bitrate {
+            validate_bitrate(expected)?;
+        }
+
         let socket = socketcan::tokio::CanSocket::open(interface_name)
-            .map_err(|e| CanError::Interface(format!("failed to open {interface_name}: {e}")))?;
-        tracing::info!(interface = interface_name, "SocketCAN interface opened");
-        Ok(Self { socket })
+            .map_err(|e| map_io_error(interface_name, "open", &e))?;
+
+        if let Some(expected) = bitrate {
+            check_link_bitrate(interface_name, expected)?;
+        }
+
+        tracing::info!(interface = interface_name, bitrate = ?bitrate, "SocketCAN interface opened");
+        Ok(Self {
+            name: interface_name.to_string(),
+            socket,
+        })
+    }
+
+    /// Interface name this socket is bound to.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Compare the link's configured bitrate (via netlink) with `expected`.
+#[cfg(target_os = "linux")]
+fn check_link_bitrate(interface_name: &str, expected: u32) -> CanResult<()> {
+    let link = match socketcan::nl::CanInterface::open(interface_name) {
+        Ok(link) => link,
+        Err(e) => {
+            tracing::warn!(interface = interface_name, error = %e, "cannot query CAN link, skipping bitrate check");
+            return Ok(());
+        }
+    };
+
+    match link.bit_rate() {
+        Ok(Some(actual)) if actual != expected => Err(CanError::Interface(format!(
+            "{interface_name}: link runs at {actual} bit/s, expected {expected} \
+             (ip link set {interface_name} type can bitrate {expected})"
+        ))),
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => {
+            tracing::debug!(
+                interface = interface_name,
+                "link reports no bitrate (virtual CAN?)"
+            );
+            Ok(())
+        }
+        Err(e) => {
+            tracing::warn!(interface = interface_name, error = %e, "failed to read CAN link bitrate");
+            Ok(())
+        }
     }
 }
 
@@ -121,35 +292,7 @@ impl SocketCanInterface {
 #[async_trait]
 impl CanInterface for SocketCanInterface {
     async fn send_frame(&self, frame: &CanFrame) -> CanResult<()> {
-        // ── Safety checks ────────────────────────────────────────
-        if frame.data.len() >= 2 {
-            let pci_len = frame.data[0];
-
-            // OBD-II safety: check mode for broadcast requests.
-            if frame.id == OBD_REQUEST_ID && (1..=7).contains(&pci_len) {
-                let mode = frame.data[1];
-                if !safety::is_mode_allowed(mode) {
-                    return Err(CanError::SafetyViolation { mode });
-                }
-            }
-
-            // UDS safety: check service ID for known ECU request IDs.
-            let is_flow_control = (pci_len >> 4) == 0x03;
-            if !is_flow_control {
-                let is_ecu_request = ecu_profile::all_profiles()
-                    .iter()
-                    .any(|p| p.request_id == frame.id);
-                if is_ecu_request && (1..=7).contains(&pci_len) {
-                    let service_id = frame.data[1];
-                    if !uds_safety::is_uds_service_allowed(service_id) {
-                        return Err(CanError::UdsSafetyViolation {
-                            service_id,
-                            service_name: uds_safety::uds_service_name(service_id).to_string(),
-                        });
-                    }
-                }
-            }
-        }
+        check_frame_safety(frame)?;
 
         // ── Send via SocketCAN ───────────────────────────────────
         let sc_frame =
@@ -160,7 +303,7 @@ impl CanInterface for SocketCanInterface {
         self.socket
             .write_frame(sc_frame)
             .await
-            .map_err(|e| CanError::Interface(format!("send failed: {e}")))?;
+            .map_err(|e| map_io_error(&self.name, "send", &e))?;
 
         tracing::trace!(id = format!("0x{:03X}", frame.id), "CAN TX");
         Ok(())
@@ -171,18 +314,32 @@ impl CanInterface for SocketCanInterface {
 
         match result {
             Ok(Ok(sc_frame)) => {
-                let id = sc_frame.raw_id();
+                // Keep the error flag so callers can tell error frames apart.
+                let id = if sc_frame.is_error_frame() {
+                    crate::error_frame::CAN_ERR_FLAG | (sc_frame.id_word() & 0x1FFF_FFFF)
+                } else {
+                    sc_frame.raw_id()
+                };
                 let data = sc_frame.data().to_vec();
                 tracing::trace!(id = format!("0x{id:03X}"), len = data.len(), "CAN RX");
                 Ok(CanFrame::new(id, data))
             }
-            Ok(Err(e)) => Err(CanError::Interface(format!("recv failed: {e}"))),
+            Ok(Err(e)) => Err(map_io_error(&self.name, "recv", &e)),
             Err(_) => Err(CanError::Timeout {
                 timeout_ms: timeout.as_millis() as u64,
             }),
         }
     }
 
+    async fn set_error_frames(&self, enabled: bool) -> CanResult<()> {
+        let result = if enabled {
+            self.socket.set_error_filter_accept_all()
+        } else {
+            self.socket.set_error_filter_drop_all()
+        };
+        result.map_err(|e| map_io_error(&self.name, "set error filter", &e))
+    }
+
     async fn drain_rx_buffer(&self) {
         let mut drained = 0u32;
         while let Ok(Ok(_)) =
@@ -208,4 +365,60 @@ mod tests {
         assert!(!is_obd_response(0x7F0));
         assert!(!is_obd_response(0x7DF)); // request ID, not response
     }
+
+    #[test]
+    fn interface_name_validation() {
+        assert!(validate_interface_name("can0").is_ok());
+        assert!(validate_interface_name("vcan0").is_ok());
+        assert!(validate_interface_name("").is_err());
+        assert!(validate_interface_name("a-very-long-can-name").is_err());
+        assert!(validate_interface_name("can/0").is_err());
+    }
+
+    #[test]
+    fn bitrate_validation() {
+        assert!(validate_bitrate(500_000).is_ok());
+        assert!(validate_bitrate(250_000).is_ok());
+        let err = validate_bitrate(333_333).unwrap_err();
+        assert!(err.to_string().contains("333333"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn io_errors_map_to_actionable_messages() {
+        let e = std::io::Error::from_raw_os_error(ENETDOWN);
+        assert!(
+            map_io_error("can0", "send", &e)
+                .to_string()
+                .contains("ip link set can0 up")
+        );
+
+        let e = std::io::Error::from_raw_os_error(ENODEV);
+        assert!(
+            map_io_error("can9", "open", &e)
+                .to_string()
+                .contains("no such CAN interface")
+        );
+
+        let e = std::io::Error::from_raw_os_error(ENOBUFS);
+        assert!(
+            map_io_error("can0", "send", &e)
+                .to_string()
+                .contains("transmit queue full")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn open_missing_interface_fails_cleanly() {
+        let err = SocketCanInterface::open("zcnone0", None).err().unwrap();
+        assert!(matches!(err, CanError::Interface(_)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn open_rejects_bad_bitrate_before_touching_socket() {
+        let err = SocketCanInterface::open("can0", Some(42)).err().unwrap();
+        assert!(err.to_string().contains("unsupported CAN bitrate"));
+    }
 }
@@ -2,11 +2,17 @@
 //!
 //! Provides a trait-based CAN interface abstraction, OBD-II protocol helpers,
 //! UDS (ISO 14229) protocol support for Hella ECUs, ISO-TP multi-frame support,
-//! a static DTC database, and 8 diagnostic tools.
+//! a static DTC database, a VIN decoder, a DBC signal decoder, and 10
+//! diagnostic tools. Besides SocketCAN, the tools run over ELM327 serial
+//! adapters (`elm327`).
 
+pub mod capture;
+pub mod dbc;
 pub mod dtc_db;
 pub mod ecu_profile;
+pub mod elm327;
 pub mod error;
+pub mod error_frame;
 pub mod ftb;
 pub mod interface;
 pub mod mock;
@@ -16,6 +22,7 @@ pub mod tools;
 pub mod types;
 pub mod uds;
 pub mod uds_safety;
+pub mod vin_decode;
 
 // Re-export key types for convenience
 pub use error::{CanError, CanResult};
@@ -23,4 +30,4 @@ pub use interface::CanInterface;
 #[cfg(target_os = "linux")]
 pub use interface::SocketCanInterface;
 pub use mock::MockCanInterface;
-pub use types::{CanFrame, CanTool, ToolResult};
+pub use types::{CanFrame, CanTool, ChunkSink, ToolResult};
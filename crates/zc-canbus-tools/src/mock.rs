@@ -5,14 +5,13 @@
 
 use async_trait::async_trait;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use crate::ecu_profile;
 use crate::error::{CanError, CanResult};
-use crate::interface::CanInterface;
-use crate::safety;
-use crate::types::{CanFrame, OBD_REQUEST_ID};
-use crate::uds_safety;
+use crate::error_frame;
+use crate::interface::{self, CanInterface};
+use crate::types::CanFrame;
 
 /// Mock CAN interface with scripted responses and frame recording.
 pub struct MockCanInterface {
@@ -22,6 +21,9 @@ pub struct MockCanInterface {
     sent_frames: Mutex<Vec<CanFrame>>,
     /// Whether to enforce OBD-II safety checks (default: true).
     enforce_safety: bool,
+    /// Whether queued error frames are delivered (default: false, like
+    /// a SocketCAN socket without an error filter).
+    error_frames: AtomicBool,
 }
 
 impl MockCanInterface {
@@ -31,6 +33,7 @@ impl MockCanInterface {
             responses: Mutex::new(Vec::new()),
             sent_frames: Mutex::new(Vec::new()),
             enforce_safety: true,
+            error_frames: AtomicBool::new(false),
         }
     }
 
@@ -40,6 +43,7 @@ impl MockCanInterface {
             responses: Mutex::new(responses),
             sent_frames: Mutex::new(Vec::new()),
             enforce_safety: true,
+            error_frames: AtomicBool::new(false),
         }
     }
 
@@ -68,44 +72,24 @@ impl Default for MockCanInterface {
 #[async_trait]
 impl CanInterface for MockCanInterface {
     async fn send_frame(&self, frame: &CanFrame) -> CanResult<()> {
-        if self.enforce_safety && frame.data.len() >= 2 {
-            let pci_len = frame.data[0];
-
-            // OBD-II safety: check mode for standard OBD-II broadcast requests.
-            // ISO-TP frames (FC = 0x30, etc.) use the same CAN ID but are not
-            // OBD service requests and must not be blocked.
-            if frame.id == OBD_REQUEST_ID && (1..=7).contains(&pci_len) {
-                let mode = frame.data[1];
-                if !safety::is_mode_allowed(mode) {
-                    return Err(CanError::SafetyViolation { mode });
-                }
-            }
-
-            // UDS safety: check service ID for known ECU request IDs.
-            // Skip ISO-TP Flow Control frames (PCI byte starts with 0x3x).
-            let is_flow_control = (pci_len >> 4) == 0x03;
-            if !is_flow_control {
-                let is_ecu_request = ecu_profile::all_profiles()
-                    .iter()
-                    .any(|p| p.request_id == frame.id);
-                if is_ecu_request && (1..=7).contains(&pci_len) {
-                    let service_id = frame.data[1];
-                    if !uds_safety::is_uds_service_allowed(service_id) {
-                        return Err(CanError::UdsSafetyViolation {
-                            service_id,
-                            service_name: uds_safety::uds_service_name(service_id).to_string(),
-                        });
-                    }
-                }
-            }
+        if self.enforce_safety {
+            interface::check_frame_safety(frame)?;
         }
 
         self.sent_frames.lock().unwrap().push(frame.clone());
         Ok(())
     }
 
+    async fn set_error_frames(&self, enabled: bool) -> CanResult<()> {
+        self.error_frames.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
     async fn recv_frame(&self, timeout: Duration) -> CanResult<CanFrame> {
         let mut responses = self.responses.lock().unwrap();
+        if !self.error_frames.load(Ordering::Relaxed) {
+            responses.retain(|f| !error_frame::is_error_frame(f));
+        }
         if responses.is_empty() {
             return Err(CanError::Timeout {
                 timeout_ms: timeout.as_millis() as u64,
@@ -118,6 +102,7 @@ impl CanInterface for MockCanInterface {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::OBD_REQUEST_ID;
 
     #[tokio::test]
     async fn records_sent_frames() {
@@ -139,6 +124,23 @@ mod tests {
         assert_eq!(received, response);
     }
 
+    #[tokio::test]
+    async fn error_frames_only_delivered_when_enabled() {
+        let err = CanFrame::new(
+            error_frame::CAN_ERR_FLAG | error_frame::CAN_ERR_ACK,
+            vec![0; 8],
+        );
+        let mock = MockCanInterface::with_responses(vec![err.clone()]);
+        assert!(mock.recv_frame(Duration::from_millis(10)).await.is_err());
+
+        mock.queue_response(err.clone());
+        mock.set_error_frames(true).await.unwrap();
+        assert_eq!(
+            mock.recv_frame(Duration::from_millis(10)).await.unwrap(),
+            err
+        );
+    }
+
     #[tokio::test]
     async fn timeout_when_empty() {
         let mock = MockCanInterface::new();
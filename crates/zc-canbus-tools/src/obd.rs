@@ -3,6 +3,8 @@
 
 use std::time::Duration;
 
+use zc_protocol::dtc::{IgnitionType, MonitorState, Readiness, ReadinessMonitor};
+
 use crate::error::{CanError, CanResult};
 use crate::interface::CanInterface;
 use crate::types::*;
@@ -19,14 +21,37 @@ pub fn build_request(mode: u8, pid: u8) -> CanFrame {
     )
 }
 
-/// Build Mode 0x03 request (stored DTCs — no PID byte needed).
-pub fn build_dtc_request() -> CanFrame {
+/// Build a Mode 0x02 request for `pid` in freeze frame number `frame`.
+pub fn build_freeze_frame_request(pid: u8, frame: u8) -> CanFrame {
+    CanFrame::new(
+        OBD_REQUEST_ID,
+        vec![0x03, MODE_FREEZE_FRAME, pid, frame, 0x00, 0x00, 0x00, 0x00],
+    )
+}
+
+/// Build a DTC list request for Mode 0x03, 0x07 or 0x0A (no PID byte needed).
+pub fn build_dtc_mode_request(mode: u8) -> CanFrame {
     CanFrame::new(
         OBD_REQUEST_ID,
-        vec![0x01, MODE_STORED_DTCS, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        vec![0x01, mode, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
     )
 }
 
+/// Build Mode 0x03 request (stored DTCs).
+pub fn build_dtc_request() -> CanFrame {
+    build_dtc_mode_request(MODE_STORED_DTCS)
+}
+
+/// Build Mode 0x07 request (pending DTCs).
+pub fn build_pending_dtc_request() -> CanFrame {
+    build_dtc_mode_request(MODE_PENDING_DTCS)
+}
+
+/// Build Mode 0x0A request (permanent DTCs).
+pub fn build_permanent_dtc_request() -> CanFrame {
+    build_dtc_mode_request(MODE_PERMANENT_DTCS)
+}
+
 // ---------------------------------------------------------------------------
 // Send + receive helper
 // ---------------------------------------------------------------------------
@@ -161,6 +186,32 @@ pub fn decode_dtc_bytes(b1: u8, b2: u8) -> Option<String> {
     Some(format!("{category}{digit1}{digit2:X}{digit3:X}{digit4:X}"))
 }
 
+/// Parse a Mode 0x03/0x07/0x0A single-frame response.
+///
+/// Expected frame data layout: `[num_bytes, response_sid, num_dtcs, dtc1_hi, dtc1_lo, ...]`.
+/// Returns `(num_dtcs_reported, codes)`; zero padding pairs are skipped.
+pub fn parse_dtc_response(frame: &CanFrame, expected_mode: u8) -> CanResult<(usize, Vec<String>)> {
+    if frame.data.len() >= 3 && frame.data[1] == 0x7F && frame.data[2] == expected_mode {
+        return Err(CanError::Protocol(format!(
+            "mode 0x{expected_mode:02X} not supported by ECU"
+        )));
+    }
+
+    let expected_sid = expected_mode + RESPONSE_SID_OFFSET;
+    if frame.data.len() < 3 || frame.data[1] != expected_sid {
+        return Err(CanError::Protocol(format!(
+            "invalid Mode {expected_mode:02X} response"
+        )));
+    }
+
+    let num_dtcs_reported = frame.data[2] as usize;
+    let codes = frame.data[3..]
+        .chunks_exact(2)
+        .filter_map(|pair| decode_dtc_bytes(pair[0], pair[1]))
+        .collect();
+    Ok((num_dtcs_reported, codes))
+}
+
 // ---------------------------------------------------------------------------
 // PID value decoders
 // ---------------------------------------------------------------------------
@@ -400,6 +451,153 @@ pub fn parse_pid_response(frame: &CanFrame, expected_mode: u8) -> CanResult<(u8,
     Ok((pid, &frame.data[3..data_end]))
 }
 
+/// Mode 0x02 PID naming the DTC that caused a freeze frame to be stored.
+pub const FREEZE_FRAME_DTC_PID: u8 = 0x02;
+
+/// Parse a Mode 0x02 single-frame response, which repeats the frame
+/// number after the PID.
+///
+/// Expected frame data layout: `[num_bytes, 0x42, pid, frame, data...]`
+/// Returns `(pid, frame, data_bytes_slice)`.
+pub fn parse_freeze_frame_response(frame: &CanFrame) -> CanResult<(u8, u8, &[u8])> {
+    let (pid, rest) = parse_pid_response(frame, MODE_FREEZE_FRAME)?;
+    let Some((&number, data)) = rest.split_first() else {
+        return Err(CanError::Protocol(
+            "freeze frame response without frame number".into(),
+        ));
+    };
+    Ok((pid, number, data))
+}
+
+// ---------------------------------------------------------------------------
+// Supported-PID bitmaps
+// ---------------------------------------------------------------------------
+
+/// Mode 0x01 PIDs that answer with a "PIDs supported" bitmap for the next
+/// 32 PIDs (0x00 covers 0x01-0x20, 0x20 covers 0x21-0x40, ...).
+pub const SUPPORTED_PID_RANGES: [u8; 4] = [0x00, 0x20, 0x40, 0x60];
+
+/// Decode the 4-byte bitmap returned for range PID `base`. The most
+/// significant bit of the first byte is PID `base + 1`, the least
+/// significant bit of the last byte is `base + 0x20` — which, when set,
+/// means the ECU also answers the next range.
+pub fn decode_supported_pids(base: u8, bitmap: &[u8]) -> CanResult<Vec<u8>> {
+    if bitmap.len() < 4 {
+        return Err(CanError::Decode(format!(
+            "PID 0x{base:02X}: need 4 bitmap bytes, got {}",
+            bitmap.len()
+        )));
+    }
+    let bits = u32::from_be_bytes([bitmap[0], bitmap[1], bitmap[2], bitmap[3]]);
+    Ok((0..32u8)
+        .filter(|i| bits & (1 << (31 - i)) != 0)
+        .filter_map(|i| base.checked_add(i + 1))
+        .collect())
+}
+
+// ---------------------------------------------------------------------------
+// Monitor status (PID 0x01)
+// ---------------------------------------------------------------------------
+
+/// Mode 0x01 PID reporting MIL status, DTC count and readiness monitors.
+pub const MONITOR_STATUS_PID: u8 = 0x01;
+
+/// Continuous monitors: byte B, supported bits 0-2, incomplete bits 4-6.
+const CONTINUOUS_MONITORS: [&str; 3] = ["misfire", "fuel_system", "components"];
+
+/// Non-continuous monitors of spark-ignition engines, byte C/D bits 0-7.
+const SPARK_MONITORS: [&str; 8] = [
+    "catalyst",
+    "heated_catalyst",
+    "evaporative_system",
+    "secondary_air_system",
+    "ac_refrigerant",
+    "oxygen_sensor",
+    "oxygen_sensor_heater",
+    "egr_system",
+];
+
+/// Non-continuous monitors of compression-ignition engines; `None` for
+/// reserved bits.
+const COMPRESSION_MONITORS: [Option<&str>; 8] = [
+    Some("nmhc_catalyst"),
+    Some("nox_scr_aftertreatment"),
+    None,
+    Some("boost_pressure"),
+    None,
+    Some("exhaust_gas_sensor"),
+    Some("pm_filter"),
+    Some("egr_vvt_system"),
+];
+
+/// Decode the 4 data bytes of PID 0x01 (SAE J1979): byte A holds the MIL
+/// (bit 7) and DTC count; byte B the continuous monitors and the ignition
+/// type (bit 3); bytes C and D which non-continuous monitors exist and
+/// which are still incomplete (a set D bit means "not complete").
+pub fn decode_readiness(data: &[u8]) -> CanResult<Readiness> {
+    let [a, b, c, d] = data
+        .get(..4)
+        .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+        .ok_or_else(|| {
+            CanError::Decode(format!(
+                "PID 0x{MONITOR_STATUS_PID:02X}: need 4 bytes, got {}",
+                data.len()
+            ))
+        })?;
+
+    let state = |supported: bool, incomplete: bool| match (supported, incomplete) {
+        (false, _) => MonitorState::Unsupported,
+        (true, true) => MonitorState::Incomplete,
+        (true, false) => MonitorState::Complete,
+    };
+    let bit = |byte: u8, n: usize| byte & (1 << n) != 0;
+
+    let ignition = if bit(b, 3) {
+        IgnitionType::Compression
+    } else {
+        IgnitionType::Spark
+    };
+    let mut monitors: Vec<ReadinessMonitor> = CONTINUOUS_MONITORS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| ReadinessMonitor {
+            name: (*name).into(),
+            continuous: true,
+            state: state(bit(b, i), bit(b, i + 4)),
+        })
+        .collect();
+    let names: [Option<&str>; 8] = match ignition {
+        IgnitionType::Spark => SPARK_MONITORS.map(Some),
+        IgnitionType::Compression => COMPRESSION_MONITORS,
+    };
+    monitors.extend(names.iter().enumerate().filter_map(|(i, name)| {
+        Some(ReadinessMonitor {
+            name: (*name)?.into(),
+            continuous: false,
+            state: state(bit(c, i), bit(d, i)),
+        })
+    }));
+
+    Ok(Readiness {
+        mil_on: bit(a, 7),
+        dtc_count: a & 0x7F,
+        ignition,
+        monitors,
+    })
+}
+
+/// Name and unit of a PID `decode_pid` understands, or `None`.
+pub fn pid_info(pid: u8) -> Option<(&'static str, &'static str)> {
+    decode_pid(pid, &[0; 4]).ok().map(|pv| (pv.name, pv.unit))
+}
+
+/// Whether `frame` is an ECU's negative response (`7F <mode> <NRC>`) to a
+/// request in `mode`. ECUs answer this way — or not at all — for PIDs they
+/// do not support.
+pub fn is_negative_response(frame: &CanFrame, mode: u8) -> bool {
+    frame.data.len() >= 3 && frame.data[1] == 0x7F && frame.data[2] == mode
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,6 +618,47 @@ mod tests {
         assert_eq!(frame.data[1], MODE_STORED_DTCS);
     }
 
+    #[test]
+    fn build_pending_and_permanent_dtc_requests() {
+        let pending = build_pending_dtc_request();
+        assert_eq!(pending.id, OBD_REQUEST_ID);
+        assert_eq!(&pending.data[..2], &[0x01, 0x07]);
+        let permanent = build_permanent_dtc_request();
+        assert_eq!(&permanent.data[..2], &[0x01, 0x0A]);
+    }
+
+    // --- DTC response parsing ---
+
+    #[test]
+    fn parse_dtc_response_pending() {
+        let frame = CanFrame::new(0x7E8, vec![0x04, 0x47, 0x01, 0x01, 0x71, 0x00, 0x00, 0x00]);
+        let (reported, codes) = parse_dtc_response(&frame, MODE_PENDING_DTCS).unwrap();
+        assert_eq!(reported, 1);
+        assert_eq!(codes, vec!["P0171"]);
+    }
+
+    #[test]
+    fn parse_dtc_response_permanent() {
+        let frame = CanFrame::new(0x7E8, vec![0x06, 0x4A, 0x02, 0x04, 0x20, 0xC1, 0x00, 0x00]);
+        let (reported, codes) = parse_dtc_response(&frame, MODE_PERMANENT_DTCS).unwrap();
+        assert_eq!(reported, 2);
+        assert_eq!(codes, vec!["P0420", "U0100"]);
+    }
+
+    #[test]
+    fn parse_dtc_response_rejects_other_mode() {
+        let frame = CanFrame::new(0x7E8, vec![0x02, 0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let err = parse_dtc_response(&frame, MODE_PENDING_DTCS).unwrap_err();
+        assert!(matches!(err, CanError::Protocol(_)));
+    }
+
+    #[test]
+    fn parse_dtc_response_unsupported_mode() {
+        let frame = CanFrame::new(0x7E8, vec![0x03, 0x7F, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00]);
+        let err = parse_dtc_response(&frame, MODE_PERMANENT_DTCS).unwrap_err();
+        assert!(err.to_string().contains("not supported"));
+    }
+
     // --- DTC byte decoding ---
 
     #[test]
@@ -500,6 +739,72 @@ mod tests {
         assert!(matches!(err, CanError::Decode(_)));
     }
 
+    // --- Supported-PID bitmaps ---
+
+    #[test]
+    fn decode_supported_pids_bitmap() {
+        // 0xBE1FA813: a typical gasoline ECU's answer to PID 0x00.
+        let pids = decode_supported_pids(0x00, &[0xBE, 0x1F, 0xA8, 0x13]).unwrap();
+        assert_eq!(
+            pids,
+            vec![
+                0x01, 0x03, 0x04, 0x05, 0x06, 0x07, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x13, 0x15,
+                0x1C, 0x1F, 0x20
+            ]
+        );
+        let next = decode_supported_pids(0x20, &[0x80, 0, 0, 0x01]).unwrap();
+        assert_eq!(next, vec![0x21, 0x40]);
+    }
+
+    #[test]
+    fn decode_supported_pids_short_bitmap() {
+        let err = decode_supported_pids(0x00, &[0xBE, 0x1F]).unwrap_err();
+        assert!(matches!(err, CanError::Decode(_)));
+    }
+
+    #[test]
+    fn decode_readiness_spark() {
+        // MIL on, 2 DTCs; misfire + fuel supported, fuel incomplete;
+        // catalyst, EVAP and O2 sensor supported, EVAP incomplete.
+        let r = decode_readiness(&[0x82, 0x23, 0x25, 0x04]).unwrap();
+        assert!(r.mil_on);
+        assert_eq!(r.dtc_count, 2);
+        assert_eq!(r.ignition, IgnitionType::Spark);
+        assert_eq!(r.monitors.len(), 11);
+        let state = |name: &str| r.monitors.iter().find(|m| m.name == name).unwrap().state;
+        assert_eq!(state("misfire"), MonitorState::Complete);
+        assert_eq!(state("fuel_system"), MonitorState::Incomplete);
+        assert_eq!(state("components"), MonitorState::Unsupported);
+        assert_eq!(state("catalyst"), MonitorState::Complete);
+        assert_eq!(state("evaporative_system"), MonitorState::Incomplete);
+        assert_eq!(state("oxygen_sensor"), MonitorState::Complete);
+        assert_eq!(state("egr_system"), MonitorState::Unsupported);
+        let incomplete: Vec<_> = r.incomplete().map(|m| m.name.as_str()).collect();
+        assert_eq!(incomplete, ["fuel_system", "evaporative_system"]);
+        assert!(!r.ready());
+    }
+
+    #[test]
+    fn decode_readiness_compression() {
+        // Bit 3 of B: diesel. NMHC, NOx/SCR, PM filter supported and done.
+        let r = decode_readiness(&[0x00, 0x0F, 0x43, 0x00]).unwrap();
+        assert_eq!(r.ignition, IgnitionType::Compression);
+        assert_eq!(r.monitors.len(), 9);
+        assert!(r.monitors.iter().any(|m| m.name == "pm_filter"));
+        assert!(r.ready());
+    }
+
+    #[test]
+    fn decode_readiness_short_response() {
+        assert!(decode_readiness(&[0x00, 0x07]).is_err());
+    }
+
+    #[test]
+    fn pid_info_known_and_unknown() {
+        assert_eq!(pid_info(0x0C), Some(("Engine RPM", "rpm")));
+        assert_eq!(pid_info(0x20), None);
+    }
+
     // --- Response parsing ---
 
     #[test]
@@ -510,6 +815,19 @@ mod tests {
         assert_eq!(data, &[0x1B, 0x58]);
     }
 
+    #[test]
+    fn build_and_parse_freeze_frame() {
+        let request = build_freeze_frame_request(0x0C, 1);
+        assert_eq!(&request.data[..4], &[0x03, 0x02, 0x0C, 0x01]);
+
+        let frame = CanFrame::new(0x7E8, vec![0x05, 0x42, 0x0C, 0x01, 0x1B, 0x58, 0x00, 0x00]);
+        let (pid, number, data) = parse_freeze_frame_response(&frame).unwrap();
+        assert_eq!((pid, number, data), (0x0C, 1, &[0x1B, 0x58][..]));
+
+        let truncated = CanFrame::new(0x7E8, vec![0x02, 0x42, 0x0C, 0, 0, 0, 0, 0]);
+        assert!(parse_freeze_frame_response(&truncated).is_err());
+    }
+
     #[test]
     fn parse_pid_response_wrong_sid() {
         let frame = CanFrame::new(0x7E8, vec![0x04, 0x42, 0x0C, 0x1B, 0x58, 0x00, 0x00, 0x00]);
@@ -1,10 +1,28 @@
-//! Raw CAN frame capture with optional ID filter and duration limit.
+//! Raw CAN frame capture with acceptance filters and duration limit.
+//!
+//! With `capture_errors`, SocketCAN error frames are captured alongside and
+//! counted per [`ErrorClass`], so a "flaky bus" report comes back as e.g.
+//! 40 ACK errors and two controller restarts.
+//!
+//! Frames whose ID is described in the loaded DBC files are decoded into
+//! named signals with scaled engineering values.
+//!
+//! With `save`, frames are written to a candump log or pcapng file under the
+//! capture directory instead of being returned inline (see [`capture`]); the
+//! result carries the capture ID and the path to fetch with a file transfer.
 
 use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::capture::{self, CaptureFormat, TimedFrame};
+use crate::dbc::Dbc;
 use crate::error::{CanError, CanResult};
+use crate::error_frame::{self, ErrorClass};
 use crate::interface::CanInterface;
+use crate::tools::{MAX_CAN_ID, parse_u32_arg};
 use crate::types::*;
 
 /// Maximum capture duration (safety limit).
@@ -13,8 +31,117 @@ const MAX_DURATION_SECS: u64 = 30;
 /// Maximum frames to capture per invocation.
 const MAX_FRAMES: usize = 1000;
 
+/// Maximum frames per invocation when saving to a capture file.
+const MAX_SAVED_FRAMES: usize = 100_000;
+
+/// Frames per streamed chunk unless `chunk_frames` is given.
+const DEFAULT_CHUNK_FRAMES: usize = 50;
+
+/// Largest allowed `chunk_frames`.
+const MAX_CHUNK_FRAMES: usize = 200;
+
+/// A partial batch is flushed at least this often while streaming.
+const CHUNK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest number of acceptance filters per capture.
+const MAX_FILTERS: usize = 32;
+
 /// Captures raw CAN frames with optional ID filtering and duration limit.
-pub struct CanMonitorTool;
+pub struct CanMonitorTool {
+    dbc: Arc<Dbc>,
+    capture_dir: PathBuf,
+}
+
+impl Default for CanMonitorTool {
+    fn default() -> Self {
+        Self::with_dbc(Arc::default())
+    }
+}
+
+impl CanMonitorTool {
+    /// Decode captured frames with the messages in `dbc`.
+    pub fn with_dbc(dbc: Arc<Dbc>) -> Self {
+        Self {
+            dbc,
+            capture_dir: PathBuf::from(capture::DEFAULT_CAPTURE_DIR),
+        }
+    }
+
+    /// Save capture files under `dir` instead of [`capture::DEFAULT_CAPTURE_DIR`].
+    pub fn with_capture_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.capture_dir = dir.into();
+        self
+    }
+}
+
+/// The `save` argument: no file, or the format to write.
+fn parse_save(args: &serde_json::Value) -> Result<Option<CaptureFormat>, String> {
+    match args.get("save") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(v) => v
+            .as_str()
+            .and_then(CaptureFormat::parse)
+            .map(Some)
+            .ok_or_else(|| "save must be \"candump\" or \"pcapng\"".into()),
+    }
+}
+
+/// An acceptance filter: a frame passes if `frame.id & mask == id & mask`,
+/// like a SocketCAN `can_filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AcceptanceFilter {
+    id: u32,
+    mask: u32,
+}
+
+impl AcceptanceFilter {
+    fn matches(&self, id: u32) -> bool {
+        id & self.mask == self.id & self.mask
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "id": format!("0x{:03X}", self.id),
+            "mask": format!("0x{:03X}", self.mask),
+        })
+    }
+}
+
+/// Collect `filters` (a list of `{"id", "mask"}`; mask defaults to all ID
+/// bits) and the older single `filter_id`. Empty means accept everything.
+fn parse_filters(args: &serde_json::Value) -> Result<Vec<AcceptanceFilter>, String> {
+    let mut filters = Vec::new();
+    if let Some(list) = args.get("filters") {
+        let list = list
+            .as_array()
+            .ok_or("filters must be a list of {id, mask} objects")?;
+        if list.len() > MAX_FILTERS {
+            return Err(format!("at most {MAX_FILTERS} filters are allowed"));
+        }
+        for (i, entry) in list.iter().enumerate() {
+            let id = entry
+                .get("id")
+                .and_then(parse_u32_arg)
+                .filter(|id| *id <= MAX_CAN_ID)
+                .ok_or_else(|| format!("filters[{i}]: invalid or missing id"))?;
+            let mask = match entry.get("mask") {
+                Some(m) => {
+                    parse_u32_arg(m).ok_or_else(|| format!("filters[{i}]: invalid mask"))?
+                        & MAX_CAN_ID
+                }
+                None => MAX_CAN_ID,
+            };
+            filters.push(AcceptanceFilter { id, mask });
+        }
+    }
+    if let Some(id) = args.get("filter_id").and_then(|v| v.as_u64()) {
+        filters.push(AcceptanceFilter {
+            id: id as u32,
+            mask: MAX_CAN_ID,
+        });
+    }
+    Ok(filters)
+}
 
 #[async_trait]
 impl CanTool for CanMonitorTool {
@@ -23,7 +150,7 @@ impl CanTool for CanMonitorTool {
     }
 
     fn description(&self) -> &str {
-        "Capture raw CAN bus frames for a specified duration. Optionally filter by CAN ID. Max 30 seconds, 1000 frames."
+        "Capture raw CAN bus frames for a specified duration. Optionally filter by CAN ID or ID/mask acceptance filters, decode frames into named signals with the loaded DBC files, and count bus error frames by class. Max 30 seconds, 1000 frames."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -39,10 +166,42 @@ impl CanTool for CanMonitorTool {
                     "type": "integer",
                     "description": "Optional CAN ID to filter (hex as decimal, e.g. 2024 for 0x7E8)"
                 },
+                "filters": {
+                    "type": "array",
+                    "description": "Acceptance filters; a frame is kept if it matches any. Mask defaults to all ID bits (exact match).",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "id": { "type": ["integer", "string"], "description": "CAN ID, e.g. 1280 or \"0x500\"" },
+                            "mask": { "type": ["integer", "string"], "description": "ID bits that must match, e.g. \"0x7F0\" for 0x500-0x50F" }
+                        },
+                        "required": ["id"]
+                    }
+                },
+                "decode": {
+                    "type": "boolean",
+                    "description": "Decode frames with the loaded DBC files into named signals",
+                    "default": true
+                },
                 "max_frames": {
                     "type": "integer",
                     "description": "Maximum number of frames to capture (max 1000)",
                     "default": 100
+                },
+                "chunk_frames": {
+                    "type": "integer",
+                    "description": "Frames per streamed chunk when streaming (max 200)",
+                    "default": 50
+                },
+                "capture_errors": {
+                    "type": "boolean",
+                    "description": "Also capture error frames and count them by class (bit, stuff, ACK, bus-off, restarts, ...)",
+                    "default": false
+                },
+                "save": {
+                    "type": "string",
+                    "enum": ["candump", "pcapng"],
+                    "description": "Write the frames to a capture file in this format instead of returning them (up to 100000 frames); the result has the capture ID and file path"
                 }
             },
             "required": []
@@ -53,6 +212,33 @@ impl CanTool for CanMonitorTool {
         &self,
         args: serde_json::Value,
         interface: &dyn CanInterface,
+    ) -> CanResult<ToolResult> {
+        self.capture(args, interface, None).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+        sink: &ChunkSink<'_>,
+    ) -> CanResult<ToolResult> {
+        self.capture(args, interface, Some(sink)).await
+    }
+}
+
+impl CanMonitorTool {
+    /// Capture frames. With a sink, frames are emitted in batches of
+    /// `chunk_frames` (or every [`CHUNK_INTERVAL`]) and left out of the
+    /// final result.
+    async fn capture(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+        sink: Option<&ChunkSink<'_>>,
     ) -> CanResult<ToolResult> {
         let duration_secs = args
             .get("duration_secs")
@@ -60,68 +246,260 @@ impl CanTool for CanMonitorTool {
             .unwrap_or(5)
             .min(MAX_DURATION_SECS);
 
-        let filter_id = args
-            .get("filter_id")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
+        let filters = match parse_filters(&args) {
+            Ok(f) => f,
+            Err(msg) => return Ok(ToolResult::failure(self.name(), msg)),
+        };
+        let save = match parse_save(&args) {
+            Ok(s) => s,
+            Err(msg) => return Ok(ToolResult::failure(self.name(), msg)),
+        };
+        // Saved frames are not returned, so there is nothing to decode.
+        let decode = save.is_none()
+            && !self.dbc.is_empty()
+            && args.get("decode").and_then(|v| v.as_bool()).unwrap_or(true);
 
+        let frame_limit = if save.is_some() {
+            MAX_SAVED_FRAMES
+        } else {
+            MAX_FRAMES
+        };
         let max_frames = args
             .get("max_frames")
             .and_then(|v| v.as_u64())
-            .unwrap_or(100) as usize;
-        let max_frames = max_frames.min(MAX_FRAMES);
+            .map_or(if save.is_some() { frame_limit } else { 100 }, |n| {
+                n as usize
+            });
+        let max_frames = max_frames.min(frame_limit);
+
+        let chunk_frames = args
+            .get("chunk_frames")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_CHUNK_FRAMES as u64)
+            .clamp(1, MAX_CHUNK_FRAMES as u64) as usize;
+
+        let capture_errors = args
+            .get("capture_errors")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         let deadline = Instant::now() + Duration::from_secs(duration_secs);
         let recv_timeout = Duration::from_millis(100);
         let mut captured: Vec<serde_json::Value> = Vec::new();
+        let mut count = 0usize;
+        let mut chunks = 0usize;
+        let mut last_flush = Instant::now();
+        let mut errors = ErrorTally::default();
+        let mut decoded = 0usize;
+        let mut saved: Vec<TimedFrame> = Vec::new();
 
-        while Instant::now() < deadline && captured.len() < max_frames {
-            match interface.recv_frame(recv_timeout).await {
-                Ok(frame) => {
-                    if let Some(fid) = filter_id
-                        && frame.id != fid
-                    {
-                        continue;
+        if capture_errors {
+            interface.set_error_frames(true).await?;
+        }
+
+        let captured_ok: CanResult<()> = async {
+            while Instant::now() < deadline && count < max_frames {
+                match interface.recv_frame(recv_timeout).await {
+                    Ok(frame) if error_frame::is_error_frame(&frame) => {
+                        errors.record(&frame);
+                        if save.is_some() {
+                            saved.push(TimedFrame::now(frame));
+                        }
                     }
+                    Ok(frame) => {
+                        if !filters.is_empty() && !filters.iter().any(|f| f.matches(frame.id)) {
+                            continue;
+                        }
+                        count += 1;
+                        if save.is_some() {
+                            saved.push(TimedFrame::now(frame));
+                            continue;
+                        }
 
-                    let hex_data: String = frame
-                        .data
-                        .iter()
-                        .map(|b| format!("{b:02X}"))
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    captured.push(serde_json::json!({
-                        "id": format!("0x{:03X}", frame.id),
-                        "data": hex_data,
-                        "dlc": frame.data.len(),
-                    }));
-                }
-                Err(CanError::Timeout { .. }) => {
-                    continue;
+                        let hex_data: String = frame
+                            .data
+                            .iter()
+                            .map(|b| format!("{b:02X}"))
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        let mut entry = serde_json::json!({
+                            "id": format!("0x{:03X}", frame.id),
+                            "data": hex_data,
+                            "dlc": frame.data.len(),
+                        });
+                        if decode && let Some((message, signals)) = self.dbc.decode(&frame) {
+                            let signals: serde_json::Map<String, serde_json::Value> = signals
+                                .iter()
+                                .map(|s| {
+                                    let value =
+                                        serde_json::json!({ "value": s.value, "unit": s.unit });
+                                    (s.name.to_string(), value)
+                                })
+                                .collect();
+                            entry["message"] = serde_json::json!(message.name);
+                            entry["signals"] = serde_json::Value::Object(signals);
+                            decoded += 1;
+                        }
+                        captured.push(entry);
+                    }
+                    Err(CanError::Timeout { .. }) => {}
+                    Err(e) => {
+                        return Err(e);
+                    }
                 }
-                Err(e) => {
-                    return Err(e);
+
+                if let Some(sink) = sink
+                    && !captured.is_empty()
+                    && (captured.len() >= chunk_frames || last_flush.elapsed() >= CHUNK_INTERVAL)
+                {
+                    emit_chunk(sink, &mut captured, count);
+                    chunks += 1;
+                    last_flush = Instant::now();
                 }
             }
+            Ok(())
         }
+        .await;
 
-        let count = captured.len();
-        let data = serde_json::json!({
+        // Restore the default filter even if capture failed, so later
+        // protocol requests never see error frames.
+        if capture_errors && let Err(e) = interface.set_error_frames(false).await {
+            tracing::warn!(error = %e, "failed to disable CAN error frames");
+        }
+        captured_ok?;
+
+        if let Some(sink) = sink
+            && !captured.is_empty()
+        {
+            emit_chunk(sink, &mut captured, count);
+            chunks += 1;
+        }
+
+        let filter_id = args.get("filter_id").and_then(|v| v.as_u64());
+        let mut data = serde_json::json!({
             "frames": captured,
             "count": count,
             "duration_secs": duration_secs,
             "filter_id": filter_id.map(|id| format!("0x{id:03X}")),
         });
+        if !filters.is_empty() {
+            data["filters"] = filters.iter().map(|f| f.to_json()).collect();
+        }
+        if decode {
+            data["decoded"] = serde_json::json!(decoded);
+        }
+        if sink.is_some() {
+            data["chunks"] = serde_json::json!(chunks);
+        }
+        if capture_errors {
+            data["errors"] = errors.to_json();
+        }
+        let mut saved_as = None;
+        if let Some(format) = save {
+            let capture_id = uuid::Uuid::now_v7().to_string();
+            let (path, bytes) =
+                match capture::save(&self.capture_dir, &capture_id, format, &saved).await {
+                    Ok(saved) => saved,
+                    Err(e) => return Ok(ToolResult::failure(self.name(), e.to_string())),
+                };
+            let path = path.display().to_string();
+            if let Some(obj) = data.as_object_mut() {
+                obj.remove("frames");
+            }
+            data["capture"] = serde_json::json!({
+                "capture_id": capture_id,
+                "format": format.as_str(),
+                "path": path,
+                "bytes": bytes,
+            });
+            saved_as = Some(format!(
+                "; saved as {} capture {capture_id} ({path})",
+                format.as_str()
+            ));
+        }
 
-        let summary = match filter_id {
-            Some(id) => format!("Captured {count} frames (filter: 0x{id:03X}) in {duration_secs}s"),
-            None => format!("Captured {count} frames in {duration_secs}s"),
+        let mut summary = match filters.as_slice() {
+            [] => format!("Captured {count} frames in {duration_secs}s"),
+            [f] if f.mask == MAX_CAN_ID => {
+                format!(
+                    "Captured {count} frames (filter: 0x{:03X}) in {duration_secs}s",
+                    f.id
+                )
+            }
+            many => format!(
+                "Captured {count} frames ({} filters) in {duration_secs}s",
+                many.len()
+            ),
         };
+        if decode {
+            summary.push_str(&format!(", {decoded} decoded with DBC"));
+        }
+        if capture_errors {
+            summary.push_str(&errors.summary());
+        }
+        if let Some(saved_as) = saved_as {
+            summary.push_str(&saved_as);
+        }
 
         Ok(ToolResult::success(self.name(), data, summary))
     }
 }
 
+/// Error frames seen during a capture.
+#[derive(Debug, Default)]
+struct ErrorTally {
+    total: usize,
+    by_class: BTreeMap<ErrorClass, usize>,
+    /// Last (TX, RX) error counters reported by the controller.
+    counters: Option<(u8, u8)>,
+}
+
+impl ErrorTally {
+    fn record(&mut self, frame: &CanFrame) {
+        self.total += 1;
+        for class in error_frame::classify(frame) {
+            *self.by_class.entry(class).or_default() += 1;
+        }
+        if let Some(counters) = error_frame::error_counters(frame) {
+            self.counters = Some(counters);
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let by_class: serde_json::Map<String, serde_json::Value> = self
+            .by_class
+            .iter()
+            .map(|(class, n)| (class.as_str().to_string(), serde_json::json!(n)))
+            .collect();
+        serde_json::json!({
+            "total": self.total,
+            "by_class": by_class,
+            "tx_error_counter": self.counters.map(|(tx, _)| tx),
+            "rx_error_counter": self.counters.map(|(_, rx)| rx),
+        })
+    }
+
+    /// Summary suffix, e.g. "; 12 error frames (ack_error: 10, controller_restarted: 2)".
+    fn summary(&self) -> String {
+        if self.total == 0 {
+            return "; no error frames".into();
+        }
+        let classes: Vec<String> = self
+            .by_class
+            .iter()
+            .map(|(class, n)| format!("{}: {n}", class.as_str()))
+            .collect();
+        format!("; {} error frames ({})", self.total, classes.join(", "))
+    }
+}
+
+/// Send the pending batch to the sink. `offset` is the index of its first frame.
+fn emit_chunk(sink: &ChunkSink<'_>, pending: &mut Vec<serde_json::Value>, count: usize) {
+    let frames = std::mem::take(pending);
+    let offset = count - frames.len();
+    sink(serde_json::json!({ "frames": frames, "offset": offset }));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,7 +512,7 @@ mod tests {
         mock.queue_response(CanFrame::new(0x200, vec![0x04, 0x05]));
         mock.queue_response(CanFrame::new(0x100, vec![0x06]));
 
-        let tool = CanMonitorTool;
+        let tool = CanMonitorTool::default();
         let result = tool
             .execute(
                 serde_json::json!({"duration_secs": 1, "max_frames": 10}),
@@ -154,7 +532,7 @@ mod tests {
         mock.queue_response(CanFrame::new(0x200, vec![0x02]));
         mock.queue_response(CanFrame::new(0x100, vec![0x03]));
 
-        let tool = CanMonitorTool;
+        let tool = CanMonitorTool::default();
         let result = tool
             .execute(
                 serde_json::json!({"duration_secs": 1, "filter_id": 256, "max_frames": 10}),
@@ -174,7 +552,7 @@ mod tests {
             mock.queue_response(CanFrame::new(0x100, vec![i]));
         }
 
-        let tool = CanMonitorTool;
+        let tool = CanMonitorTool::default();
         let result = tool
             .execute(
                 serde_json::json!({"duration_secs": 5, "max_frames": 10}),
@@ -190,7 +568,7 @@ mod tests {
     #[tokio::test]
     async fn monitor_empty_bus() {
         let mock = MockCanInterface::new();
-        let tool = CanMonitorTool;
+        let tool = CanMonitorTool::default();
         let result = tool
             .execute(serde_json::json!({"duration_secs": 1}), &mock)
             .await
@@ -199,4 +577,235 @@ mod tests {
         assert!(result.success);
         assert_eq!(result.data.unwrap()["count"], 0);
     }
+
+    fn err_frame(class: u32, data: [u8; 8]) -> CanFrame {
+        CanFrame::new(error_frame::CAN_ERR_FLAG | class, data.to_vec())
+    }
+
+    #[tokio::test]
+    async fn monitor_counts_error_frames_by_class() {
+        let mock = MockCanInterface::new();
+        mock.queue_response(CanFrame::new(0x100, vec![0x01]));
+        for _ in 0..3 {
+            mock.queue_response(err_frame(error_frame::CAN_ERR_ACK, [0; 8]));
+        }
+        mock.queue_response(err_frame(
+            error_frame::CAN_ERR_PROT | error_frame::CAN_ERR_BUSERROR,
+            [0, 0, 0x04, 0, 0, 0, 0, 0],
+        ));
+        mock.queue_response(err_frame(
+            error_frame::CAN_ERR_RESTARTED | error_frame::CAN_ERR_CNT,
+            [0, 0, 0, 0, 0, 0, 12, 4],
+        ));
+        mock.queue_response(CanFrame::new(0x200, vec![0x02]));
+
+        let result = CanMonitorTool::default()
+            .execute(
+                serde_json::json!({"duration_secs": 1, "max_frames": 10, "capture_errors": true}),
+                &mock,
+            )
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["count"], 2);
+        assert_eq!(data["errors"]["total"], 5);
+        assert_eq!(data["errors"]["by_class"]["ack_error"], 3);
+        assert_eq!(data["errors"]["by_class"]["stuff_error"], 1);
+        assert_eq!(data["errors"]["by_class"]["controller_restarted"], 1);
+        assert_eq!(data["errors"]["tx_error_counter"], 12);
+        assert_eq!(data["errors"]["rx_error_counter"], 4);
+        assert!(result.summary.unwrap().contains("5 error frames"));
+    }
+
+    #[tokio::test]
+    async fn monitor_ignores_error_frames_by_default() {
+        let mock = MockCanInterface::new();
+        mock.queue_response(err_frame(error_frame::CAN_ERR_ACK, [0; 8]));
+        mock.queue_response(CanFrame::new(0x100, vec![0x01]));
+
+        let result = CanMonitorTool::default()
+            .execute(serde_json::json!({"duration_secs": 1}), &mock)
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["count"], 1);
+        assert!(data.get("errors").is_none());
+    }
+
+    #[tokio::test]
+    async fn monitor_disables_error_frames_afterwards() {
+        let mock = MockCanInterface::new();
+        CanMonitorTool::default()
+            .execute(
+                serde_json::json!({"duration_secs": 1, "capture_errors": true}),
+                &mock,
+            )
+            .await
+            .unwrap();
+
+        mock.queue_response(err_frame(error_frame::CAN_ERR_ACK, [0; 8]));
+        assert!(mock.recv_frame(Duration::from_millis(10)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn monitor_saves_capture_file_instead_of_frames() {
+        let mock = MockCanInterface::new();
+        mock.queue_response(CanFrame::new(0x100, vec![0xDE, 0xAD]));
+        mock.queue_response(CanFrame::new(0x200, vec![0x02]));
+        mock.queue_response(CanFrame::new(0x100, vec![0xBE, 0xEF]));
+
+        let dir = std::env::temp_dir().join(format!("zc-monitor-{}", uuid::Uuid::now_v7()));
+        let tool = CanMonitorTool::default().with_capture_dir(&dir);
+        let result = tool
+            .execute(
+                serde_json::json!({"duration_secs": 1, "filter_id": 256, "save": "candump"}),
+                &mock,
+            )
+            .await
+            .unwrap();
+
+        assert!(result.success, "{:?}", result.error);
+        let data = result.data.unwrap();
+        assert_eq!(data["count"], 2);
+        assert!(data.get("frames").is_none());
+        let capture_id = data["capture"]["capture_id"].as_str().unwrap();
+        let path = dir.join(format!("{capture_id}.log"));
+        assert_eq!(data["capture"]["path"], path.display().to_string());
+        assert!(result.summary.unwrap().contains(capture_id));
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let ids: Vec<&str> = text.lines().map(|l| l.split(' ').nth(2).unwrap()).collect();
+        assert_eq!(ids, ["100#DEAD", "100#BEEF"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn monitor_rejects_unknown_save_format() {
+        let result = CanMonitorTool::default()
+            .execute(
+                serde_json::json!({"duration_secs": 1, "save": "asc"}),
+                &MockCanInterface::new(),
+            )
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("candump"));
+    }
+
+    #[tokio::test]
+    async fn monitor_streams_frames_in_chunks() {
+        let mock = MockCanInterface::new();
+        for i in 0..5u8 {
+            mock.queue_response(CanFrame::new(0x100, vec![i]));
+        }
+
+        let chunks = std::sync::Mutex::new(Vec::new());
+        let sink = |chunk: serde_json::Value| chunks.lock().unwrap().push(chunk);
+        let result = CanMonitorTool::default()
+            .execute_streaming(
+                serde_json::json!({"duration_secs": 5, "max_frames": 5, "chunk_frames": 2}),
+                &mock,
+                &sink,
+            )
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["count"], 5);
+        assert_eq!(data["chunks"], 3);
+        assert!(data["frames"].as_array().unwrap().is_empty());
+
+        let chunks = chunks.into_inner().unwrap();
+        let sizes: Vec<usize> = chunks
+            .iter()
+            .map(|c| c["frames"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(chunks[2]["offset"], 4);
+        assert_eq!(chunks[2]["frames"][0]["data"], "04");
+    }
+
+    #[tokio::test]
+    async fn monitor_with_acceptance_filters() {
+        let mock = MockCanInterface::new();
+        for id in [0x500, 0x50F, 0x510, 0x7E8, 0x18FE_F100] {
+            mock.queue_response(CanFrame::new(id, vec![0x01]));
+        }
+
+        let result = CanMonitorTool::default()
+            .execute(
+                serde_json::json!({
+                    "duration_secs": 1,
+                    "filters": [
+                        {"id": "0x500", "mask": "0x7F0"},
+                        {"id": 0x18FE_F100u32},
+                    ],
+                }),
+                &mock,
+            )
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        let ids: Vec<&str> = data["frames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["0x500", "0x50F", "0x18FEF100"]);
+        assert_eq!(data["filters"][0]["mask"], "0x7F0");
+        assert!(result.summary.unwrap().contains("2 filters"));
+    }
+
+    #[tokio::test]
+    async fn monitor_rejects_bad_filters() {
+        let mock = MockCanInterface::new();
+        let result = CanMonitorTool::default()
+            .execute(serde_json::json!({"filters": [{"mask": "0x7F0"}]}), &mock)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("filters[0]"));
+    }
+
+    #[tokio::test]
+    async fn monitor_decodes_dbc_signals() {
+        let dbc = Dbc::parse(
+            "BO_ 1280 Engine: 8 ECM\n SG_ Rpm : 0|16@1+ (0.25,0) [0|16383.75] \"rpm\" X\n SG_ Coolant : 16|8@1+ (1,-40) [-40|215] \"degC\" X\n",
+        )
+        .unwrap();
+        let mock = MockCanInterface::new();
+        mock.queue_response(CanFrame::new(0x500, vec![0x70, 0x17, 130, 0, 0, 0, 0, 0]));
+        mock.queue_response(CanFrame::new(0x501, vec![0x01]));
+
+        let tool = CanMonitorTool::with_dbc(Arc::new(dbc));
+        let result = tool
+            .execute(serde_json::json!({"duration_secs": 1}), &mock)
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["decoded"], 1);
+        assert_eq!(data["frames"][0]["message"], "Engine");
+        assert_eq!(data["frames"][0]["signals"]["Rpm"]["value"], 1500.0);
+        assert_eq!(data["frames"][0]["signals"]["Coolant"]["unit"], "degC");
+        assert!(data["frames"][1].get("signals").is_none());
+        assert!(result.summary.unwrap().ends_with("1 decoded with DBC"));
+
+        mock.queue_response(CanFrame::new(0x500, vec![0; 8]));
+        let raw = tool
+            .execute(
+                serde_json::json!({"duration_secs": 1, "decode": false}),
+                &mock,
+            )
+            .await
+            .unwrap()
+            .data
+            .unwrap();
+        assert!(raw["frames"][0].get("signals").is_none());
+        assert!(raw.get("decoded").is_none());
+    }
 }
@@ -0,0 +1,217 @@
+//! Tool: List supported OBD-II PIDs (Mode 0x01 PIDs 0x00/0x20/0x40/0x60).
+//!
+//! Each range PID answers with a 32-bit bitmap of the PIDs the ECU supports
+//! in the next 32; the last bit says whether the next range exists. The
+//! result is remembered in a [`SupportedPids`] cache shared with `read_pid`,
+//! which then rejects unsupported PIDs up front instead of waiting for a
+//! timeout or a confusing negative response.
+
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::error::{CanError, CanResult};
+use crate::interface::CanInterface;
+use crate::obd;
+use crate::types::{CanTool, MODE_CURRENT_DATA, ToolResult};
+
+/// PIDs the ECU reported as supported, once `list_supported_pids` has run.
+#[derive(Debug, Default)]
+pub struct SupportedPids {
+    pids: RwLock<Option<BTreeSet<u8>>>,
+}
+
+impl SupportedPids {
+    /// Replace the known set with a fresh discovery result.
+    pub fn record(&self, pids: impl IntoIterator<Item = u8>) {
+        *self.pids.write().unwrap_or_else(|e| e.into_inner()) = Some(pids.into_iter().collect());
+    }
+
+    /// The known set, or `None` before the first discovery.
+    pub fn get(&self) -> Option<BTreeSet<u8>> {
+        self.pids.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Whether `pid` is known not to be supported. Always false before the
+    /// first discovery.
+    pub fn is_unsupported(&self, pid: u8) -> bool {
+        self.pids
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(|pids| !obd::SUPPORTED_PID_RANGES.contains(&pid) && !pids.contains(&pid))
+    }
+}
+
+/// Discovers which Mode 0x01 PIDs the ECU supports.
+#[derive(Default)]
+pub struct ListSupportedPids {
+    supported: Arc<SupportedPids>,
+}
+
+impl ListSupportedPids {
+    /// Record discovered PIDs in `supported` (shared with `read_pid`).
+    pub fn with_cache(supported: Arc<SupportedPids>) -> Self {
+        Self { supported }
+    }
+}
+
+#[async_trait]
+impl CanTool for ListSupportedPids {
+    fn name(&self) -> &str {
+        "list_supported_pids"
+    }
+
+    fn description(&self) -> &str {
+        "List the OBD-II PIDs (Mode 0x01) the ECU supports, from its PID 0x00/0x20/0x40/0x60 bitmaps"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "timeout_ms": { "type": "integer", "description": "Per-bitmap response timeout in milliseconds", "default": 1000 }
+            }
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+    ) -> CanResult<ToolResult> {
+        let timeout = Duration::from_millis(
+            args.get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1000),
+        );
+
+        let mut pids: Vec<u8> = Vec::new();
+        let mut bitmaps = serde_json::Map::new();
+        for base in obd::SUPPORTED_PID_RANGES {
+            // Every ECU must answer PID 0x00; later ranges only if announced.
+            if base != 0x00 && !pids.contains(&base) {
+                break;
+            }
+
+            let request = obd::build_request(MODE_CURRENT_DATA, base);
+            let response = match obd::obd_query(interface, &request, timeout).await {
+                Ok(r) => r,
+                Err(CanError::Timeout { .. }) if base != 0x00 => break,
+                Err(e) => return Err(e),
+            };
+            if obd::is_negative_response(&response, MODE_CURRENT_DATA) {
+                if base == 0x00 {
+                    return Ok(ToolResult::failure(
+                        self.name(),
+                        "ECU rejected PID 0x00 (supported PIDs)",
+                    ));
+                }
+                break;
+            }
+
+            let (resp_pid, data) = obd::parse_pid_response(&response, MODE_CURRENT_DATA)?;
+            if resp_pid != base {
+                return Ok(ToolResult::failure(
+                    self.name(),
+                    format!("PID mismatch: requested 0x{base:02X}, got 0x{resp_pid:02X}"),
+                ));
+            }
+            let raw: String = data.iter().take(4).map(|b| format!("{b:02X}")).collect();
+            bitmaps.insert(format!("0x{base:02X}"), raw.into());
+            pids.extend(obd::decode_supported_pids(base, data)?);
+        }
+
+        let decodable: Vec<serde_json::Value> = pids
+            .iter()
+            .filter_map(|&pid| {
+                obd::pid_info(pid).map(
+                    |(name, unit)| serde_json::json!({ "pid": pid, "name": name, "unit": unit }),
+                )
+            })
+            .collect();
+        let sensors = pids
+            .iter()
+            .filter(|pid| !obd::SUPPORTED_PID_RANGES.contains(pid))
+            .count();
+        self.supported.record(pids.iter().copied());
+
+        let summary = format!(
+            "ECU supports {sensors} PIDs, {} of them readable with read_pid",
+            decodable.len()
+        );
+        let data = serde_json::json!({
+            "pids": pids,
+            "decodable": decodable,
+            "bitmaps": bitmaps,
+        });
+        Ok(ToolResult::success(self.name(), data, summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCanInterface;
+    use crate::types::CanFrame;
+
+    fn bitmap(base: u8, bytes: [u8; 4]) -> CanFrame {
+        let [a, b, c, d] = bytes;
+        CanFrame::new(0x7E8, vec![0x06, 0x41, base, a, b, c, d, 0])
+    }
+
+    #[tokio::test]
+    async fn follows_ranges_while_announced() {
+        let mock = MockCanInterface::with_responses(vec![
+            bitmap(0x00, [0x18, 0x18, 0x80, 0x01]), // 0x04 0x05 0x0C 0x0D 0x11 0x20
+            bitmap(0x20, [0x00, 0x02, 0x00, 0x00]), // 0x2F, no 0x40
+        ]);
+        let cache = Arc::new(SupportedPids::default());
+        let tool = ListSupportedPids::with_cache(cache.clone());
+
+        let result = tool.execute(serde_json::json!({}), &mock).await.unwrap();
+
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(
+            data["pids"],
+            serde_json::json!([0x04, 0x05, 0x0C, 0x0D, 0x11, 0x20, 0x2F])
+        );
+        assert_eq!(data["decodable"][2]["name"], "Engine RPM");
+        assert_eq!(data["bitmaps"]["0x00"], "18188001");
+        assert_eq!(
+            result.summary.unwrap(),
+            "ECU supports 6 PIDs, 6 of them readable with read_pid"
+        );
+        assert_eq!(mock.sent_frames().len(), 2);
+
+        assert!(cache.is_unsupported(0x0E));
+        assert!(!cache.is_unsupported(0x0C));
+    }
+
+    #[tokio::test]
+    async fn rejected_first_range_fails() {
+        let mock = MockCanInterface::with_responses(vec![CanFrame::new(
+            0x7E8,
+            vec![0x03, 0x7F, 0x01, 0x12, 0, 0, 0, 0],
+        )]);
+        let result = ListSupportedPids::default()
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("rejected"));
+    }
+
+    #[test]
+    fn cache_unknown_until_recorded() {
+        let cache = SupportedPids::default();
+        assert!(!cache.is_unsupported(0x0E));
+        cache.record([0x0C, 0x20]);
+        assert!(cache.is_unsupported(0x0E));
+        assert!(!cache.is_unsupported(0x20));
+        assert!(!cache.is_unsupported(0x40));
+        assert_eq!(cache.get().unwrap().len(), 2);
+    }
+}
@@ -1,47 +1,106 @@
 //! CAN bus diagnostic tool implementations.
 
 pub mod can_monitor;
+pub mod list_supported_pids;
+pub mod pid_burst;
 pub mod read_dtcs;
 pub mod read_freeze;
 pub mod read_pid;
+pub mod read_readiness;
 pub mod read_uds_did;
 pub mod read_uds_dtcs;
+pub mod read_vehicle_info;
 pub mod read_vin;
+pub mod send_frame;
 pub mod uds_session;
 
 pub use can_monitor::CanMonitorTool;
+pub use list_supported_pids::{ListSupportedPids, SupportedPids};
+pub use pid_burst::PidBurst;
 pub use read_dtcs::ReadDtcs;
 pub use read_freeze::ReadFreeze;
 pub use read_pid::ReadPid;
+pub use read_readiness::ReadReadiness;
 pub use read_uds_did::ReadUdsDid;
 pub use read_uds_dtcs::ReadUdsDtcs;
+pub use read_vehicle_info::ReadVehicleInfo;
 pub use read_vin::ReadVin;
+pub use send_frame::SendFrame;
 pub use uds_session::UdsSessionControl;
 
+use std::sync::Arc;
+
+use crate::dbc::Dbc;
+use crate::dtc_db::{self, DtcDatabase};
 use crate::types::CanTool;
 
+/// Largest valid CAN ID (29-bit extended).
+pub(crate) const MAX_CAN_ID: u32 = 0x1FFF_FFFF;
+
+/// Accept an ID as an integer (`2016`) or a hex string (`"0x7E0"`).
+pub(crate) fn parse_u32_arg(v: &serde_json::Value) -> Option<u32> {
+    if let Some(n) = v.as_u64() {
+        return u32::try_from(n).ok();
+    }
+    let s = v.as_str()?.trim();
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
 /// Returns all available CAN bus diagnostic tools.
 pub fn all_tools() -> Vec<Box<dyn CanTool>> {
+    with_dtc_database(dtc_db::builtin())
+}
+
+/// All tools, with the DTC readers describing codes from `db`.
+pub fn with_dtc_database(db: Arc<dyn DtcDatabase>) -> Vec<Box<dyn CanTool>> {
+    with_databases(db, Arc::default())
+}
+
+/// All tools, with the DTC readers describing codes from `db` and
+/// `can_monitor` decoding frames with `dbc`.
+///
+/// `read_pid` and `list_supported_pids` share one [`SupportedPids`] cache.
+pub fn with_databases(db: Arc<dyn DtcDatabase>, dbc: Arc<Dbc>) -> Vec<Box<dyn CanTool>> {
+    let supported = Arc::new(SupportedPids::default());
     vec![
-        Box::new(ReadPid),
-        Box::new(ReadDtcs),
+        Box::new(ReadPid::with_supported_pids(supported.clone())),
+        Box::new(ReadDtcs::with_database(db.clone())),
         Box::new(ReadVin),
         Box::new(ReadFreeze),
-        Box::new(CanMonitorTool),
-        Box::new(ReadUdsDtcs),
+        Box::new(CanMonitorTool::with_dbc(dbc)),
+        Box::new(ReadUdsDtcs::with_database(db)),
         Box::new(ReadUdsDid),
         Box::new(UdsSessionControl),
+        Box::new(PidBurst),
+        Box::new(ListSupportedPids::with_cache(supported)),
+        Box::new(ReadReadiness),
+        Box::new(ReadVehicleInfo),
     ]
 }
 
+/// Returns tools that transmit arbitrary frames. Only registered by agents
+/// running in bench mode — never on vehicles in the field.
+pub fn bench_tools() -> Vec<Box<dyn CanTool>> {
+    vec![Box::new(SendFrame)]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn all_tools_returns_eight() {
+    fn all_tools_returns_twelve() {
         let tools = all_tools();
-        assert_eq!(tools.len(), 8);
+        assert_eq!(tools.len(), 12);
+    }
+
+    #[test]
+    fn bench_tools_not_in_defaults() {
+        let defaults: Vec<String> = all_tools().iter().map(|t| t.name().to_string()).collect();
+        for tool in bench_tools() {
+            assert!(!defaults.contains(&tool.name().to_string()));
+        }
     }
 
     #[test]
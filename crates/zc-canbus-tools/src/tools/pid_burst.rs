@@ -0,0 +1,242 @@
+//! Tool: Raw burst capture of a single OBD-II PID (Mode 0x01).
+//!
+//! The agent normally publishes windowed aggregates (min/max/avg/last) for
+//! high-frequency signals. When an operator needs the individual samples
+//! for a detailed investigation, this tool polls one PID back-to-back and
+//! returns every decoded sample with its offset from the start of the burst.
+
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+use crate::error::{CanError, CanResult};
+use crate::interface::CanInterface;
+use crate::obd;
+use crate::types::{CanTool, MODE_CURRENT_DATA, ToolResult};
+
+/// Maximum number of samples per burst (safety limit).
+const MAX_SAMPLES: u64 = 500;
+
+/// Minimum spacing between requests, to avoid flooding the bus.
+const MIN_INTERVAL_MS: u64 = 10;
+
+/// Captures a burst of raw samples for a single live PID.
+pub struct PidBurst;
+
+#[async_trait]
+impl CanTool for PidBurst {
+    fn name(&self) -> &str {
+        "pid_burst"
+    }
+
+    fn description(&self) -> &str {
+        "Capture a burst of raw samples for one OBD-II PID (Mode 0x01). Max 500 samples, min 10ms spacing."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pid": { "type": "integer", "description": "OBD-II PID number (0x00-0xFF)" },
+                "samples": { "type": "integer", "description": "Number of samples to capture (max 500)", "default": 50 },
+                "interval_ms": { "type": "integer", "description": "Delay between requests in milliseconds (min 10)", "default": 50 },
+                "timeout_ms": { "type": "integer", "description": "Per-sample response timeout in milliseconds", "default": 500 }
+            },
+            "required": ["pid"]
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+    ) -> CanResult<ToolResult> {
+        let pid = match args.get("pid").and_then(parse_pid_arg) {
+            Some(p) => p,
+            None => {
+                return Ok(ToolResult::failure(
+                    self.name(),
+                    "Missing required argument: pid (u8)",
+                ));
+            }
+        };
+
+        let samples = args
+            .get("samples")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(50)
+            .clamp(1, MAX_SAMPLES);
+        let interval = Duration::from_millis(
+            args.get("interval_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(50)
+                .max(MIN_INTERVAL_MS),
+        );
+        let timeout = Duration::from_millis(
+            args.get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(500),
+        );
+
+        let request = obd::build_request(MODE_CURRENT_DATA, pid);
+        let start = Instant::now();
+        let mut captured: Vec<serde_json::Value> = Vec::new();
+        let mut values: Vec<f64> = Vec::new();
+        let mut missed = 0u64;
+        let mut name = "";
+        let mut unit = "";
+
+        for i in 0..samples {
+            if i > 0 {
+                tokio::time::sleep(interval).await;
+            }
+
+            let response = match obd::obd_query(interface, &request, timeout).await {
+                Ok(r) => r,
+                Err(CanError::Timeout { .. }) => {
+                    missed += 1;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let Ok((resp_pid, data)) = obd::parse_pid_response(&response, MODE_CURRENT_DATA) else {
+                missed += 1;
+                continue;
+            };
+            if resp_pid != pid {
+                missed += 1;
+                continue;
+            }
+
+            let pv = obd::decode_pid(pid, data)?;
+            name = pv.name;
+            unit = pv.unit;
+            values.push(pv.value);
+
+            let raw: String = data.iter().map(|b| format!("{b:02X}")).collect();
+            captured.push(serde_json::json!({
+                "offset_ms": start.elapsed().as_millis() as u64,
+                "value": pv.value,
+                "raw": raw,
+            }));
+        }
+
+        if values.is_empty() {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!("No responses for PID 0x{pid:02X} ({samples} requests)"),
+            ));
+        }
+
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let avg = values.iter().sum::<f64>() / values.len() as f64;
+
+        let count = captured.len();
+        let data = serde_json::json!({
+            "pid": pid,
+            "name": name,
+            "unit": unit,
+            "samples": captured,
+            "count": count,
+            "missed": missed,
+            "min": min,
+            "max": max,
+            "avg": avg,
+            "duration_ms": start.elapsed().as_millis() as u64,
+        });
+        let summary = format!(
+            "{name}: {count} samples (min {min:.1}, max {max:.1}, avg {avg:.1} {unit}), {missed} missed"
+        );
+
+        Ok(ToolResult::success(self.name(), data, summary))
+    }
+}
+
+/// Accept the PID as an integer (`12`) or a hex string (`"0x0C"`).
+fn parse_pid_arg(v: &serde_json::Value) -> Option<u8> {
+    if let Some(n) = v.as_u64() {
+        return u8::try_from(n).ok();
+    }
+    let s = v.as_str()?;
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u8::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCanInterface;
+    use crate::types::CanFrame;
+
+    fn rpm_frame(rpm: u16) -> CanFrame {
+        let raw = rpm * 4;
+        CanFrame::new(
+            0x7E8,
+            vec![0x04, 0x41, 0x0C, (raw >> 8) as u8, raw as u8, 0, 0, 0],
+        )
+    }
+
+    #[tokio::test]
+    async fn burst_collects_all_samples() {
+        let mock = MockCanInterface::with_responses(vec![
+            rpm_frame(800),
+            rpm_frame(1200),
+            rpm_frame(1000),
+        ]);
+
+        let args = serde_json::json!({ "pid": 0x0C, "samples": 3, "interval_ms": 10 });
+        let result = PidBurst.execute(args, &mock).await.unwrap();
+
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["count"], 3);
+        assert_eq!(data["missed"], 0);
+        assert_eq!(data["min"], 800.0);
+        assert_eq!(data["max"], 1200.0);
+        assert_eq!(data["avg"], 1000.0);
+        assert_eq!(data["samples"][1]["value"], 1200.0);
+        assert_eq!(mock.sent_frames().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn burst_counts_missed_samples() {
+        let mock = MockCanInterface::with_responses(vec![rpm_frame(900)]);
+
+        let args = serde_json::json!({ "pid": 0x0C, "samples": 3, "interval_ms": 10 });
+        let result = PidBurst.execute(args, &mock).await.unwrap();
+
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["count"], 1);
+        assert_eq!(data["missed"], 2);
+    }
+
+    #[tokio::test]
+    async fn burst_no_responses_fails() {
+        let mock = MockCanInterface::new();
+        let args = serde_json::json!({ "pid": 0x0C, "samples": 2, "interval_ms": 10 });
+        let result = PidBurst.execute(args, &mock).await.unwrap();
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn burst_accepts_hex_string_pid() {
+        let mock = MockCanInterface::with_responses(vec![rpm_frame(750)]);
+        let args = serde_json::json!({ "pid": "0x0C", "samples": 1 });
+        let result = PidBurst.execute(args, &mock).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.data.unwrap()["pid"], 0x0C);
+    }
+
+    #[tokio::test]
+    async fn burst_missing_pid_arg() {
+        let mock = MockCanInterface::new();
+        let result = PidBurst
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("pid"));
+    }
+}
@@ -1,18 +1,104 @@
-//! Tool: Read stored DTCs (Mode 0x03).
+//! Tool: Read DTCs — stored (Mode 0x03), pending (Mode 0x07) or permanent (Mode 0x0A).
 
 use async_trait::async_trait;
+use std::sync::Arc;
 use std::time::Duration;
 
-use zc_protocol::dtc::{DtcCode, DtcSeverity};
+use zc_protocol::dtc::{DtcCode, DtcScope, DtcSeverity};
 
-use crate::dtc_db;
-use crate::error::CanResult;
+use crate::dtc_db::{self, DtcDatabase};
+use crate::error::{CanError, CanResult};
 use crate::interface::CanInterface;
 use crate::obd;
-use crate::types::{CanTool, RESPONSE_SID_OFFSET, ToolResult};
+use crate::types::{CanTool, ToolResult};
 
-/// Reads stored Diagnostic Trouble Codes from the vehicle ECU.
-pub struct ReadDtcs;
+/// Lists queried by `scope: "all"`, in order.
+const ALL_SCOPES: [DtcScope; 3] = [DtcScope::Stored, DtcScope::Pending, DtcScope::Permanent];
+
+/// Reads Diagnostic Trouble Codes from the vehicle ECU.
+///
+/// Pending codes show intermittent faults before they mature into stored
+/// codes; permanent codes survive a Mode 0x04 clear until the ECU has
+/// verified the repair.
+pub struct ReadDtcs {
+    db: Arc<dyn DtcDatabase>,
+}
+
+impl Default for ReadDtcs {
+    fn default() -> Self {
+        Self::with_database(dtc_db::builtin())
+    }
+}
+
+impl ReadDtcs {
+    /// Describe codes from `db` instead of the built-in database.
+    pub fn with_database(db: Arc<dyn DtcDatabase>) -> Self {
+        Self { db }
+    }
+}
+
+/// Parse the `scope` argument. `None` means every list.
+fn parse_scope(value: Option<&str>) -> Result<Option<DtcScope>, String> {
+    match value.unwrap_or("stored") {
+        "stored" => Ok(Some(DtcScope::Stored)),
+        "pending" => Ok(Some(DtcScope::Pending)),
+        "permanent" => Ok(Some(DtcScope::Permanent)),
+        "all" => Ok(None),
+        other => Err(format!(
+            "Invalid scope '{other}': expected stored, pending, permanent or all"
+        )),
+    }
+}
+
+/// Query one DTC list and return `(num_dtcs_reported, dtcs)`.
+async fn read_scope(
+    db: &dyn DtcDatabase,
+    interface: &dyn CanInterface,
+    scope: DtcScope,
+    timeout: Duration,
+) -> CanResult<(usize, Vec<DtcCode>)> {
+    let request = obd::build_dtc_mode_request(scope.mode());
+    let response = obd::obd_query(interface, &request, timeout).await?;
+    let (num_dtcs_reported, codes) = obd::parse_dtc_response(&response, scope.mode())?;
+
+    let dtcs = codes
+        .into_iter()
+        .map(|code| {
+            let category = DtcCode::parse_category(&code);
+            let (description, severity, severity_source) = db
+                .lookup(&code)
+                .map(|e| {
+                    (
+                        Some(e.description),
+                        e.severity,
+                        Some(e.severity_source.to_string()),
+                    )
+                })
+                .unwrap_or((None, DtcSeverity::Unknown, None));
+
+            DtcCode {
+                code,
+                category,
+                severity,
+                severity_source,
+                description,
+                failure_type: None,
+                raw_dtc: None,
+                mil_status: false,
+                scope: Some(scope),
+                freeze_frame: None,
+            }
+        })
+        .collect();
+    Ok((num_dtcs_reported, dtcs))
+}
+
+fn codes_of(dtcs: &[DtcCode]) -> String {
+    dtcs.iter()
+        .map(|d| d.code.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
 #[async_trait]
 impl CanTool for ReadDtcs {
@@ -21,13 +107,19 @@ impl CanTool for ReadDtcs {
     }
 
     fn description(&self) -> &str {
-        "Read stored Diagnostic Trouble Codes (Mode 0x03) from the ECU"
+        "Read stored (Mode 0x03), pending (Mode 0x07) or permanent (Mode 0x0A) Diagnostic Trouble Codes from the ECU"
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
         serde_json::json!({
             "type": "object",
             "properties": {
+                "scope": {
+                    "type": "string",
+                    "enum": ["stored", "pending", "permanent", "all"],
+                    "description": "Which DTC list to read; pending codes reveal intermittent faults before they are confirmed",
+                    "default": "stored"
+                },
                 "timeout_ms": { "type": "integer", "description": "Response timeout in milliseconds", "default": 2000 }
             }
         })
@@ -38,64 +130,45 @@ impl CanTool for ReadDtcs {
         args: serde_json::Value,
         interface: &dyn CanInterface,
     ) -> CanResult<ToolResult> {
+        let scope = match parse_scope(args.get("scope").and_then(|v| v.as_str())) {
+            Ok(s) => s,
+            Err(e) => return Ok(ToolResult::failure(self.name(), e)),
+        };
         let timeout_ms = args
             .get("timeout_ms")
             .and_then(|v| v.as_u64())
             .unwrap_or(2000);
         let timeout = Duration::from_millis(timeout_ms);
 
-        let request = obd::build_dtc_request();
-        let response = obd::obd_query(interface, &request, timeout).await?;
-
-        // Mode 03 response: [length, SID(0x43), num_dtcs, dtc1_hi, dtc1_lo, ...]
-        let expected_sid = 0x03 + RESPONSE_SID_OFFSET; // 0x43
-        if response.data.len() < 3 || response.data[1] != expected_sid {
-            return Ok(ToolResult::failure(self.name(), "Invalid Mode 03 response"));
-        }
-
-        let num_dtcs_reported = response.data[2] as usize;
+        let Some(scope) = scope else {
+            return self.read_all(interface, timeout).await;
+        };
 
-        // Parse DTC byte pairs starting at index 3
-        let mut dtcs = Vec::new();
-        let dtc_bytes = &response.data[3..];
-        let mut i = 0;
-        while i + 1 < dtc_bytes.len() {
-            if let Some(code) = obd::decode_dtc_bytes(dtc_bytes[i], dtc_bytes[i + 1]) {
-                let category = DtcCode::parse_category(&code);
-                let (description, severity) = dtc_db::lookup(&code)
-                    .map(|e| (Some(e.description.to_string()), e.severity))
-                    .unwrap_or((None, DtcSeverity::Unknown));
-
-                let severity_source = if description.is_some() {
-                    Some("database".into())
-                } else {
-                    None
-                };
-
-                dtcs.push(DtcCode {
-                    code,
-                    category,
-                    severity,
-                    severity_source,
-                    description,
-                    failure_type: None,
-                    raw_dtc: None,
-                    mil_status: false,
-                    freeze_frame: None,
-                });
-            }
-            i += 2;
-        }
+        let (num_dtcs_reported, dtcs) =
+            match read_scope(self.db.as_ref(), interface, scope, timeout).await {
+                Ok(r) => r,
+                Err(e @ CanError::Protocol(_)) => {
+                    return Ok(ToolResult::failure(self.name(), e.to_string()));
+                }
+                Err(e) => return Err(e),
+            };
 
         let summary = if dtcs.is_empty() {
-            "No stored DTCs found".to_string()
-        } else {
-            let codes: Vec<&str> = dtcs.iter().map(|d| d.code.as_str()).collect();
+            format!("No {} DTCs found", scope.as_str())
+        } else if scope == DtcScope::Stored {
             format!(
                 "Found {} DTC(s) (reported {}): {}",
                 dtcs.len(),
                 num_dtcs_reported,
-                codes.join(", ")
+                codes_of(&dtcs)
+            )
+        } else {
+            format!(
+                "Found {} {} DTC(s) (reported {}): {}",
+                dtcs.len(),
+                scope.as_str(),
+                num_dtcs_reported,
+                codes_of(&dtcs)
             )
         };
 
@@ -104,6 +177,47 @@ impl CanTool for ReadDtcs {
     }
 }
 
+impl ReadDtcs {
+    /// Read every list. Lists the ECU does not answer (older vehicles often
+    /// lack Mode 0x0A) are noted in the summary instead of failing the read.
+    async fn read_all(
+        &self,
+        interface: &dyn CanInterface,
+        timeout: Duration,
+    ) -> CanResult<ToolResult> {
+        let mut dtcs = Vec::new();
+        let mut parts = Vec::new();
+        let mut answered = 0;
+        for scope in ALL_SCOPES {
+            match read_scope(self.db.as_ref(), interface, scope, timeout).await {
+                Ok((_, found)) => {
+                    answered += 1;
+                    if found.is_empty() {
+                        parts.push(format!("{}: none", scope.as_str()));
+                    } else {
+                        parts.push(format!("{}: {}", scope.as_str(), codes_of(&found)));
+                    }
+                    dtcs.extend(found);
+                }
+                Err(e) => {
+                    tracing::debug!(scope = scope.as_str(), error = %e, "DTC list not read");
+                    parts.push(format!("{}: unsupported", scope.as_str()));
+                }
+            }
+        }
+        if answered == 0 {
+            return Ok(ToolResult::failure(
+                self.name(),
+                "ECU did not answer any DTC request",
+            ));
+        }
+
+        let summary = format!("Found {} DTC(s) — {}", dtcs.len(), parts.join("; "));
+        let data = serde_json::to_value(&dtcs).unwrap_or_default();
+        Ok(ToolResult::success(self.name(), data, summary))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,7 +230,7 @@ mod tests {
         let response = CanFrame::new(0x7E8, vec![0x06, 0x43, 0x02, 0x03, 0x00, 0x01, 0x71, 0x00]);
         let mock = MockCanInterface::with_responses(vec![response]);
 
-        let result = ReadDtcs
+        let result = ReadDtcs::default()
             .execute(serde_json::json!({}), &mock)
             .await
             .unwrap();
@@ -137,7 +251,7 @@ mod tests {
         let response = CanFrame::new(0x7E8, vec![0x02, 0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
         let mock = MockCanInterface::with_responses(vec![response]);
 
-        let result = ReadDtcs
+        let result = ReadDtcs::default()
             .execute(serde_json::json!({}), &mock)
             .await
             .unwrap();
@@ -145,4 +259,83 @@ mod tests {
         assert!(result.success);
         assert!(result.summary.unwrap().contains("No stored DTCs"));
     }
+
+    #[tokio::test]
+    async fn read_pending_dtcs() {
+        let response = CanFrame::new(0x7E8, vec![0x04, 0x47, 0x01, 0x01, 0x71, 0x00, 0x00, 0x00]);
+        let mock = MockCanInterface::with_responses(vec![response]);
+
+        let result = ReadDtcs::default()
+            .execute(serde_json::json!({"scope": "pending"}), &mock)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(mock.sent_frames()[0].data[1], 0x07);
+        assert!(result.summary.unwrap().contains("1 pending DTC(s)"));
+        let dtcs = result.data.unwrap();
+        assert_eq!(dtcs[0]["code"], "P0171");
+        assert_eq!(dtcs[0]["scope"], "pending");
+    }
+
+    #[tokio::test]
+    async fn read_all_skips_unsupported_permanent() {
+        let mock = MockCanInterface::with_responses(vec![
+            CanFrame::new(0x7E8, vec![0x04, 0x43, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00]),
+            CanFrame::new(0x7E8, vec![0x04, 0x47, 0x01, 0x01, 0x71, 0x00, 0x00, 0x00]),
+            CanFrame::new(0x7E8, vec![0x03, 0x7F, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00]),
+        ]);
+
+        let result = ReadDtcs::default()
+            .execute(serde_json::json!({"scope": "all"}), &mock)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        let summary = result.summary.unwrap();
+        assert!(summary.contains("stored: P0300"));
+        assert!(summary.contains("pending: P0171"));
+        assert!(summary.contains("permanent: unsupported"));
+        let dtcs: Vec<DtcCode> = serde_json::from_value(result.data.unwrap()).unwrap();
+        assert_eq!(dtcs.len(), 2);
+        assert_eq!(dtcs[0].scope, Some(DtcScope::Stored));
+    }
+
+    #[tokio::test]
+    async fn invalid_scope_fails() {
+        let mock = MockCanInterface::new();
+        let result = ReadDtcs::default()
+            .execute(serde_json::json!({"scope": "history"}), &mock)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("Invalid scope"));
+        assert!(mock.sent_frames().is_empty());
+    }
+
+    #[tokio::test]
+    async fn custom_database_describes_codes() {
+        let custom = dtc_db::FileDtcDatabase::from_csv(
+            "code,description,severity\nP0171,Fleet: lean on bank 1 after CNG conversion,info\n",
+        )
+        .unwrap();
+        let db = dtc_db::LayeredDtcDatabase::new(vec![Arc::new(custom), dtc_db::builtin()]);
+        let response = CanFrame::new(0x7E8, vec![0x06, 0x43, 0x02, 0x03, 0x00, 0x01, 0x71, 0x00]);
+        let mock = MockCanInterface::with_responses(vec![response]);
+
+        let result = ReadDtcs::with_database(Arc::new(db))
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+
+        let dtcs: Vec<DtcCode> = serde_json::from_value(result.data.unwrap()).unwrap();
+        assert_eq!(dtcs[0].severity_source.as_deref(), Some("database"));
+        assert_eq!(
+            dtcs[1].description.as_deref(),
+            Some("Fleet: lean on bank 1 after CNG conversion")
+        );
+        assert_eq!(dtcs[1].severity, DtcSeverity::Info);
+        assert_eq!(dtcs[1].severity_source.as_deref(), Some("custom"));
+    }
 }
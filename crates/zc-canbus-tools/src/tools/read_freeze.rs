@@ -1,19 +1,23 @@
 //! Tool: Read freeze frame data (Mode 0x02).
 //!
-//! Reads multiple PIDs from the freeze frame snapshot captured when a DTC was
-//! set. Queries a standard set of PIDs and assembles a `FreezeFrame` struct.
+//! An ECU stores a freeze frame — a snapshot of its PIDs — when it sets a
+//! DTC, numbered from 0. For each frame number in turn the tool asks PID
+//! 0x02 for the DTC that stored it (stopping at the first frame with no
+//! DTC or no answer), discovers the frame's PIDs from its PID
+//! 0x00/0x20/0x40/0x60 bitmaps and decodes every one `decode_pid` knows.
+//! ECUs that do not answer the bitmaps are read for a standard set.
 
 use async_trait::async_trait;
 use std::time::Duration;
 
-use zc_protocol::dtc::FreezeFrame;
+use zc_protocol::dtc::{FreezeFrame, FreezeFramePid};
 
 use crate::error::CanResult;
 use crate::interface::CanInterface;
 use crate::obd;
 use crate::types::{CanTool, MODE_FREEZE_FRAME, ToolResult};
 
-/// Standard PIDs to read from freeze frame data.
+/// Standard PIDs read when a frame's supported PIDs are unknown.
 const FREEZE_FRAME_PIDS: &[u8] = &[
     0x04, // Engine load
     0x05, // Coolant temperature
@@ -23,7 +27,13 @@ const FREEZE_FRAME_PIDS: &[u8] = &[
     0x0D, // Vehicle speed
 ];
 
-/// Reads freeze frame data for a set of standard PIDs.
+/// Frame numbers tried unless `max_frames` is set.
+const DEFAULT_MAX_FRAMES: u64 = 8;
+
+/// Most frame numbers `max_frames` may ask for.
+const MAX_FRAMES: u64 = 32;
+
+/// Reads every stored freeze frame with its DTC and decoded PIDs.
 pub struct ReadFreeze;
 
 #[async_trait]
@@ -33,13 +43,15 @@ impl CanTool for ReadFreeze {
     }
 
     fn description(&self) -> &str {
-        "Read freeze frame data (Mode 0x02) captured when a DTC was set"
+        "Read the freeze frames (Mode 0x02) stored with each DTC, with the DTC that set each frame and its decoded PIDs"
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
         serde_json::json!({
             "type": "object",
             "properties": {
+                "dtc": { "type": "string", "description": "Only return frames stored for this DTC (e.g. \"P0300\")" },
+                "max_frames": { "type": "integer", "description": "Frame numbers to try, from 0 (at most 32)", "default": DEFAULT_MAX_FRAMES },
                 "timeout_ms": { "type": "integer", "description": "Per-PID response timeout in milliseconds", "default": 1000 }
             }
         })
@@ -55,68 +67,96 @@ impl CanTool for ReadFreeze {
             .and_then(|v| v.as_u64())
             .unwrap_or(1000);
         let timeout = Duration::from_millis(timeout_ms);
+        let max_frames = args
+            .get("max_frames")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_MAX_FRAMES);
+        if !(1..=MAX_FRAMES).contains(&max_frames) {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!("max_frames must be between 1 and {MAX_FRAMES}"),
+            ));
+        }
+        let wanted = args
+            .get("dtc")
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_ascii_uppercase());
 
-        let mut ff = FreezeFrame {
-            engine_rpm: None,
-            vehicle_speed: None,
-            coolant_temp: None,
-            engine_load: None,
-            fuel_system_status: None,
-            short_term_fuel_trim: None,
-            long_term_fuel_trim: None,
-        };
-
-        let mut sensor_count = 0usize;
+        let mut frames = Vec::new();
         let mut errors = Vec::new();
 
-        for &pid in FREEZE_FRAME_PIDS {
-            let request = obd::build_request(MODE_FREEZE_FRAME, pid);
-            match obd::obd_query(interface, &request, timeout).await {
-                Ok(response) => {
-                    if let Ok((_resp_pid, data)) =
-                        obd::parse_pid_response(&response, MODE_FREEZE_FRAME)
-                        && let Ok(pv) = obd::decode_pid(pid, data)
-                    {
-                        match pid {
-                            0x04 => ff.engine_load = Some(pv.value),
-                            0x05 => ff.coolant_temp = Some(pv.value),
-                            0x06 => ff.short_term_fuel_trim = Some(pv.value),
-                            0x07 => ff.long_term_fuel_trim = Some(pv.value),
-                            0x0C => ff.engine_rpm = Some(pv.value),
-                            0x0D => ff.vehicle_speed = Some(pv.value),
-                            _ => {}
-                        }
-                        sensor_count += 1;
+        for number in 0..max_frames as u8 {
+            let Some(dtc) = frame_dtc(interface, number, timeout).await else {
+                break;
+            };
+            if wanted.as_ref().is_some_and(|w| *w != dtc) {
+                continue;
+            }
+
+            let pids = match supported_pids(interface, number, timeout).await {
+                Some(pids) => pids
+                    .into_iter()
+                    .filter(|&pid| obd::pid_info(pid).is_some())
+                    .collect(),
+                None => FREEZE_FRAME_PIDS.to_vec(),
+            };
+
+            let mut ff = FreezeFrame {
+                frame: number,
+                dtc: Some(dtc),
+                ..Default::default()
+            };
+            for pid in pids {
+                let request = obd::build_freeze_frame_request(pid, number);
+                let response = match obd::obd_query(interface, &request, timeout).await {
+                    Ok(r) => r,
+                    Err(e) => {
+                        errors.push(format!("frame {number} PID 0x{pid:02X}: {e}"));
+                        continue;
                     }
+                };
+                let Ok((resp_pid, resp_frame, data)) = obd::parse_freeze_frame_response(&response)
+                else {
+                    continue;
+                };
+                if resp_pid != pid || resp_frame != number {
+                    continue;
                 }
-                Err(e) => {
-                    errors.push(format!("PID 0x{pid:02X}: {e}"));
+                let Ok(pv) = obd::decode_pid(pid, data) else {
+                    continue;
+                };
+                match pid {
+                    0x04 => ff.engine_load = Some(pv.value),
+                    0x05 => ff.coolant_temp = Some(pv.value),
+                    0x06 => ff.short_term_fuel_trim = Some(pv.value),
+                    0x07 => ff.long_term_fuel_trim = Some(pv.value),
+                    0x0C => ff.engine_rpm = Some(pv.value),
+                    0x0D => ff.vehicle_speed = Some(pv.value),
+                    _ => {}
                 }
+                ff.pids.push(FreezeFramePid {
+                    pid,
+                    name: pv.name.into(),
+                    value: pv.value,
+                    unit: pv.unit.into(),
+                });
             }
+            frames.push(ff);
         }
 
-        let summary = if sensor_count == 0 {
-            "No freeze frame data available".to_string()
-        } else {
-            let mut parts = Vec::new();
-            if let Some(rpm) = ff.engine_rpm {
-                parts.push(format!("RPM: {rpm:.0}"));
-            }
-            if let Some(speed) = ff.vehicle_speed {
-                parts.push(format!("Speed: {speed:.0} km/h"));
+        let summary = if frames.is_empty() {
+            match &wanted {
+                Some(dtc) => format!("No freeze frame stored for {dtc}"),
+                None => "No freeze frame data available".to_string(),
             }
-            if let Some(temp) = ff.coolant_temp {
-                parts.push(format!("Coolant: {temp:.0}\u{00b0}C"));
-            }
-            format!(
-                "Freeze frame: {sensor_count} sensor(s) read \u{2014} {}",
-                parts.join(", ")
-            )
+        } else {
+            let described: Vec<String> = frames.iter().map(describe).collect();
+            format!("{} freeze frame(s): {}", frames.len(), described.join("; "))
         };
 
         let data = serde_json::json!({
-            "freeze_frame": serde_json::to_value(&ff).unwrap_or_default(),
-            "sensors_read": sensor_count,
+            "freeze_frames": serde_json::to_value(&frames).unwrap_or_default(),
+            "frames_read": frames.len(),
             "errors": errors,
         });
 
@@ -124,29 +164,107 @@ impl CanTool for ReadFreeze {
     }
 }
 
+/// The DTC that stored frame `number`, or `None` when the frame is empty
+/// (`0000`), rejected or unanswered.
+async fn frame_dtc(interface: &dyn CanInterface, number: u8, timeout: Duration) -> Option<String> {
+    let request = obd::build_freeze_frame_request(obd::FREEZE_FRAME_DTC_PID, number);
+    let response = obd::obd_query(interface, &request, timeout).await.ok()?;
+    if obd::is_negative_response(&response, MODE_FREEZE_FRAME) {
+        return None;
+    }
+    let (pid, frame, data) = obd::parse_freeze_frame_response(&response).ok()?;
+    if pid != obd::FREEZE_FRAME_DTC_PID || frame != number || data.len() < 2 {
+        return None;
+    }
+    obd::decode_dtc_bytes(data[0], data[1])
+}
+
+/// PIDs stored in frame `number` from its supported-PID bitmaps, or `None`
+/// when the ECU does not answer PID 0x00 for the frame.
+async fn supported_pids(
+    interface: &dyn CanInterface,
+    number: u8,
+    timeout: Duration,
+) -> Option<Vec<u8>> {
+    let mut pids: Vec<u8> = Vec::new();
+    for base in obd::SUPPORTED_PID_RANGES {
+        if base != 0x00 && !pids.contains(&base) {
+            break;
+        }
+        let request = obd::build_freeze_frame_request(base, number);
+        let response = obd::obd_query(interface, &request, timeout).await.ok();
+        let bitmap = response
+            .as_ref()
+            .filter(|r| !obd::is_negative_response(r, MODE_FREEZE_FRAME))
+            .and_then(|r| obd::parse_freeze_frame_response(r).ok())
+            .filter(|&(pid, frame, _)| pid == base && frame == number)
+            .and_then(|(_, _, data)| obd::decode_supported_pids(base, data).ok());
+        match bitmap {
+            Some(found) => pids.extend(found),
+            None if base == 0x00 => return None,
+            None => break,
+        }
+    }
+    pids.retain(|pid| {
+        *pid != obd::FREEZE_FRAME_DTC_PID && !obd::SUPPORTED_PID_RANGES.contains(pid)
+    });
+    Some(pids)
+}
+
+/// "P0300 (frame 0) — RPM: 1750, Speed: 60 km/h, Coolant: 92°C".
+fn describe(ff: &FreezeFrame) -> String {
+    let mut parts = Vec::new();
+    if let Some(rpm) = ff.engine_rpm {
+        parts.push(format!("RPM: {rpm:.0}"));
+    }
+    if let Some(speed) = ff.vehicle_speed {
+        parts.push(format!("Speed: {speed:.0} km/h"));
+    }
+    if let Some(temp) = ff.coolant_temp {
+        parts.push(format!("Coolant: {temp:.0}\u{00b0}C"));
+    }
+    let label = format!(
+        "{} (frame {})",
+        ff.dtc.as_deref().unwrap_or("unknown DTC"),
+        ff.frame
+    );
+    if parts.is_empty() {
+        format!("{label} \u{2014} {} PID(s)", ff.pids.len())
+    } else {
+        format!("{label} \u{2014} {}", parts.join(", "))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::mock::MockCanInterface;
     use crate::types::CanFrame;
 
-    fn mode02_response(pid: u8, data: &[u8]) -> CanFrame {
-        let mut frame_data = vec![0x02 + data.len() as u8, 0x42, pid];
+    fn mode02_response(pid: u8, frame: u8, data: &[u8]) -> CanFrame {
+        let mut frame_data = vec![0x03 + data.len() as u8, 0x42, pid, frame];
         frame_data.extend_from_slice(data);
         frame_data.resize(8, 0x00);
         CanFrame::new(0x7E8, frame_data)
     }
 
+    fn negative_response() -> CanFrame {
+        CanFrame::new(0x7E8, vec![0x03, 0x7F, 0x02, 0x12, 0, 0, 0, 0])
+    }
+
     #[tokio::test]
-    async fn read_freeze_frame() {
-        // Queue responses for each PID in FREEZE_FRAME_PIDS order
+    async fn read_freeze_frame_standard_pids() {
+        // Frame 0 stored for P0300; no bitmap, so the standard set is read.
         let responses = vec![
-            mode02_response(0x04, &[0x80]),       // Engine load ~50%
-            mode02_response(0x05, &[0x84]),       // Coolant 92 C
-            mode02_response(0x06, &[0x80]),       // Short fuel trim 0%
-            mode02_response(0x07, &[0x80]),       // Long fuel trim 0%
-            mode02_response(0x0C, &[0x1B, 0x58]), // RPM 1750
-            mode02_response(0x0D, &[0x3C]),       // Speed 60 km/h
+            mode02_response(0x02, 0, &[0x03, 0x00]), // DTC P0300
+            negative_response(),                     // PID 0x00 bitmap
+            mode02_response(0x04, 0, &[0x80]),       // Engine load ~50%
+            mode02_response(0x05, 0, &[0x84]),       // Coolant 92 C
+            mode02_response(0x06, 0, &[0x80]),       // Short fuel trim 0%
+            mode02_response(0x07, 0, &[0x80]),       // Long fuel trim 0%
+            mode02_response(0x0C, 0, &[0x1B, 0x58]), // RPM 1750
+            mode02_response(0x0D, 0, &[0x3C]),       // Speed 60 km/h
+            negative_response(),                     // Frame 1: none
         ];
         let mock = MockCanInterface::with_responses(responses);
 
@@ -157,14 +275,78 @@ mod tests {
 
         assert!(result.success);
         let summary = result.summary.unwrap();
-        assert!(summary.contains("6 sensor(s)"));
+        assert!(summary.starts_with("1 freeze frame(s): P0300 (frame 0)"));
         assert!(summary.contains("RPM: 1750"));
         assert!(summary.contains("Speed: 60"));
+        let data = result.data.unwrap();
+        let frame = &data["freeze_frames"][0];
+        assert_eq!(frame["dtc"], "P0300");
+        assert_eq!(frame["coolant_temp"], 92.0);
+        assert_eq!(frame["pids"].as_array().unwrap().len(), 6);
+        assert_eq!(frame["pids"][4]["name"], "Engine RPM");
+    }
+
+    #[tokio::test]
+    async fn read_freeze_enumerates_frames_with_bitmaps() {
+        // Two frames, each announcing PIDs 0x02, 0x05 and 0x0C.
+        let bitmap = [0x48, 0x10, 0x00, 0x00];
+        let mut responses = Vec::new();
+        for (frame, dtc, coolant) in [(0, [0x03, 0x00], 0x84), (1, [0x01, 0x71], 0x64)] {
+            responses.extend([
+                mode02_response(0x02, frame, &dtc),
+                mode02_response(0x00, frame, &bitmap),
+                mode02_response(0x05, frame, &[coolant]),
+                mode02_response(0x0C, frame, &[0x1B, 0x58]),
+            ]);
+        }
+        responses.push(mode02_response(0x02, 2, &[0x00, 0x00])); // No frame 2
+        let mock = MockCanInterface::with_responses(responses);
+
+        let result = ReadFreeze
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["frames_read"], 2);
+        assert_eq!(data["freeze_frames"][1]["frame"], 1);
+        assert_eq!(data["freeze_frames"][1]["dtc"], "P0171");
+        assert_eq!(data["freeze_frames"][1]["coolant_temp"], 60.0);
+        assert_eq!(
+            data["freeze_frames"][0]["pids"].as_array().unwrap().len(),
+            2
+        );
+        // The frame number goes out in every request.
+        let requests = mock.sent_frames();
+        assert_eq!(requests.len(), 9);
+        assert_eq!(&requests[5].data[..4], &[0x03, 0x02, 0x00, 0x01]);
+    }
+
+    #[tokio::test]
+    async fn read_freeze_filters_by_dtc() {
+        let responses = vec![
+            mode02_response(0x02, 0, &[0x03, 0x00]),
+            mode02_response(0x02, 1, &[0x01, 0x71]),
+            negative_response(),
+        ];
+        let mock = MockCanInterface::with_responses(responses);
+
+        let result = ReadFreeze
+            .execute(
+                serde_json::json!({ "dtc": "p0171", "max_frames": 2, "timeout_ms": 50 }),
+                &mock,
+            )
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["frames_read"], 1);
+        assert_eq!(data["freeze_frames"][0]["dtc"], "P0171");
     }
 
     #[tokio::test]
     async fn read_freeze_no_data() {
-        // Empty mock — all PIDs will timeout
+        // Empty mock — PID 0x02 of frame 0 times out
         let mock = MockCanInterface::new();
 
         let result = ReadFreeze
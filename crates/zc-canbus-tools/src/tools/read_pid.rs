@@ -1,15 +1,30 @@
 //! Tool: Read OBD-II PID (Mode 0x01 — current data).
 
 use async_trait::async_trait;
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::error::CanResult;
+use crate::error::{CanError, CanResult};
 use crate::interface::CanInterface;
 use crate::obd;
+use crate::tools::list_supported_pids::SupportedPids;
 use crate::types::{CanTool, MODE_CURRENT_DATA, ToolResult};
 
 /// Reads a live OBD-II PID and returns the decoded sensor value.
-pub struct ReadPid;
+///
+/// Once `list_supported_pids` has run, PIDs the ECU does not support are
+/// rejected without touching the bus.
+#[derive(Default)]
+pub struct ReadPid {
+    supported: Arc<SupportedPids>,
+}
+
+impl ReadPid {
+    /// Check PIDs against `supported` (filled by `list_supported_pids`).
+    pub fn with_supported_pids(supported: Arc<SupportedPids>) -> Self {
+        Self { supported }
+    }
+}
 
 #[async_trait]
 impl CanTool for ReadPid {
@@ -47,6 +62,13 @@ impl CanTool for ReadPid {
             }
         };
 
+        if self.supported.is_unsupported(pid) {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!("PID 0x{pid:02X} is not supported by this ECU (per list_supported_pids)"),
+            ));
+        }
+
         let timeout_ms = args
             .get("timeout_ms")
             .and_then(|v| v.as_u64())
@@ -54,7 +76,28 @@ impl CanTool for ReadPid {
         let timeout = Duration::from_millis(timeout_ms);
 
         let request = obd::build_request(MODE_CURRENT_DATA, pid);
-        let response = obd::obd_query(interface, &request, timeout).await?;
+        let response = match obd::obd_query(interface, &request, timeout).await {
+            Ok(r) => r,
+            Err(CanError::Timeout { timeout_ms }) => {
+                return Ok(ToolResult::failure(
+                    self.name(),
+                    format!(
+                        "No response to PID 0x{pid:02X} within {timeout_ms}ms — the ECU may not \
+                         support it; run list_supported_pids to check"
+                    ),
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        if obd::is_negative_response(&response, MODE_CURRENT_DATA) {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!(
+                    "ECU rejected PID 0x{pid:02X} (NRC 0x{:02X}): not supported",
+                    response.data.get(3).copied().unwrap_or(0)
+                ),
+            ));
+        }
 
         let (resp_pid, data) = obd::parse_pid_response(&response, MODE_CURRENT_DATA)?;
 
@@ -94,7 +137,7 @@ mod tests {
         let mock = MockCanInterface::with_responses(vec![response]);
 
         let args = serde_json::json!({ "pid": 0x0C });
-        let result = ReadPid.execute(args, &mock).await.unwrap();
+        let result = ReadPid::default().execute(args, &mock).await.unwrap();
 
         assert!(result.success);
         assert!(result.summary.unwrap().contains("3500"));
@@ -106,7 +149,7 @@ mod tests {
         let mock = MockCanInterface::with_responses(vec![response]);
 
         let args = serde_json::json!({ "pid": 0x0D });
-        let result = ReadPid.execute(args, &mock).await.unwrap();
+        let result = ReadPid::default().execute(args, &mock).await.unwrap();
 
         assert!(result.success);
         assert!(result.summary.unwrap().contains("60"));
@@ -116,9 +159,43 @@ mod tests {
     async fn missing_pid_arg() {
         let mock = MockCanInterface::new();
         let args = serde_json::json!({});
-        let result = ReadPid.execute(args, &mock).await.unwrap();
+        let result = ReadPid::default().execute(args, &mock).await.unwrap();
 
         assert!(!result.success);
         assert!(result.error.unwrap().contains("Missing"));
     }
+
+    #[tokio::test]
+    async fn unsupported_pid_rejected_without_query() {
+        let supported = Arc::new(SupportedPids::default());
+        supported.record([0x0C, 0x0D, 0x20]);
+        let mock = MockCanInterface::new();
+
+        let tool = ReadPid::with_supported_pids(supported);
+        let result = tool
+            .execute(serde_json::json!({ "pid": 0x0E }), &mock)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("not supported"));
+        assert!(mock.sent_frames().is_empty());
+    }
+
+    #[tokio::test]
+    async fn negative_response_and_timeout_explained() {
+        let rejected = CanFrame::new(0x7E8, vec![0x03, 0x7F, 0x01, 0x12, 0, 0, 0, 0]);
+        let mock = MockCanInterface::with_responses(vec![rejected]);
+        let args = serde_json::json!({ "pid": 0x0E });
+
+        let result = ReadPid::default()
+            .execute(args.clone(), &mock)
+            .await
+            .unwrap();
+        assert!(result.error.unwrap().contains("NRC 0x12"));
+
+        let result = ReadPid::default().execute(args, &mock).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("list_supported_pids"));
+    }
 }
@@ -0,0 +1,166 @@
+//! Tool: Read emissions readiness (Mode 0x01 PID 0x01).
+//!
+//! Decodes the MIL status, the emissions DTC count and which on-board
+//! monitors have completed since codes were last cleared — what an
+//! emissions inspection checks before looking at anything else.
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+use crate::error::{CanError, CanResult};
+use crate::interface::CanInterface;
+use crate::obd;
+use crate::types::{CanTool, MODE_CURRENT_DATA, ToolResult};
+
+/// Reads MIL status and readiness monitor completion.
+pub struct ReadReadiness;
+
+#[async_trait]
+impl CanTool for ReadReadiness {
+    fn name(&self) -> &str {
+        "read_readiness"
+    }
+
+    fn description(&self) -> &str {
+        "Read emissions readiness (Mode 0x01 PID 0x01): MIL status, DTC count and which readiness monitors are complete"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "timeout_ms": { "type": "integer", "description": "Response timeout in milliseconds", "default": 1000 }
+            }
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+    ) -> CanResult<ToolResult> {
+        let timeout = Duration::from_millis(
+            args.get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1000),
+        );
+
+        let request = obd::build_request(MODE_CURRENT_DATA, obd::MONITOR_STATUS_PID);
+        let response = match obd::obd_query(interface, &request, timeout).await {
+            Ok(r) => r,
+            Err(CanError::Timeout { timeout_ms }) => {
+                return Ok(ToolResult::failure(
+                    self.name(),
+                    format!("No response to PID 0x01 (monitor status) within {timeout_ms}ms"),
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        if obd::is_negative_response(&response, MODE_CURRENT_DATA) {
+            return Ok(ToolResult::failure(
+                self.name(),
+                "ECU rejected PID 0x01 (monitor status)",
+            ));
+        }
+
+        let (resp_pid, data) = obd::parse_pid_response(&response, MODE_CURRENT_DATA)?;
+        if resp_pid != obd::MONITOR_STATUS_PID {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!("PID mismatch: requested 0x01, got 0x{resp_pid:02X}"),
+            ));
+        }
+        let readiness = obd::decode_readiness(data)?;
+
+        let incomplete: Vec<&str> = readiness.incomplete().map(|m| m.name.as_str()).collect();
+        let mil = if readiness.mil_on { "on" } else { "off" };
+        let summary = if readiness.ready() {
+            format!(
+                "Ready: MIL off, {} DTC(s), all supported monitors complete",
+                readiness.dtc_count
+            )
+        } else if incomplete.is_empty() {
+            format!(
+                "Not ready: MIL on, {} DTC(s), all supported monitors complete",
+                readiness.dtc_count
+            )
+        } else {
+            format!(
+                "Not ready: MIL {mil}, {} DTC(s), {} monitor(s) incomplete \u{2014} {}",
+                readiness.dtc_count,
+                incomplete.len(),
+                incomplete.join(", ")
+            )
+        };
+
+        let mut data = serde_json::to_value(&readiness).unwrap_or_default();
+        data["ready"] = readiness.ready().into();
+        data["incomplete"] = incomplete.into();
+
+        Ok(ToolResult::success(self.name(), data, summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCanInterface;
+    use crate::types::CanFrame;
+
+    fn pid01_response(data: [u8; 4]) -> CanFrame {
+        let mut frame_data = vec![0x06, 0x41, 0x01];
+        frame_data.extend_from_slice(&data);
+        frame_data.push(0x00);
+        CanFrame::new(0x7E8, frame_data)
+    }
+
+    #[tokio::test]
+    async fn read_readiness_incomplete_monitors() {
+        let mock = MockCanInterface::with_responses(vec![pid01_response([0x01, 0x07, 0x65, 0x24])]);
+
+        let result = ReadReadiness
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(
+            result.summary.as_deref(),
+            Some(
+                "Not ready: MIL off, 1 DTC(s), 2 monitor(s) incomplete \u{2014} evaporative_system, oxygen_sensor"
+            )
+        );
+        let data = result.data.unwrap();
+        assert_eq!(data["ready"], false);
+        assert_eq!(data["mil_on"], false);
+        assert_eq!(data["ignition"], "spark");
+        assert_eq!(data["monitors"][0]["name"], "misfire");
+        assert_eq!(data["monitors"][0]["state"], "complete");
+    }
+
+    #[tokio::test]
+    async fn read_readiness_ready() {
+        let mock = MockCanInterface::with_responses(vec![pid01_response([0x00, 0x07, 0x65, 0x00])]);
+
+        let result = ReadReadiness
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert!(result.summary.unwrap().starts_with("Ready: MIL off"));
+        assert_eq!(result.data.unwrap()["incomplete"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn read_readiness_no_response() {
+        let mock = MockCanInterface::new();
+
+        let result = ReadReadiness
+            .execute(serde_json::json!({ "timeout_ms": 50 }), &mock)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+    }
+}
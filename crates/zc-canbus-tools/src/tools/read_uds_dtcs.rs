@@ -1,11 +1,12 @@
 //! Tool: Read DTCs via UDS 0x19 (ReadDTCInformation).
 
 use async_trait::async_trait;
+use std::sync::Arc;
 use std::time::Duration;
 
 use zc_protocol::dtc::{DtcCode, DtcSeverity};
 
-use crate::dtc_db;
+use crate::dtc_db::{self, DtcDatabase};
 use crate::ecu_profile;
 use crate::error::{CanError, CanResult};
 use crate::ftb;
@@ -15,7 +16,22 @@ use crate::types::{CanTool, ToolResult};
 use crate::uds;
 
 /// Reads DTCs from a UDS-capable ECU (Hella BCR/BCF) via service 0x19.
-pub struct ReadUdsDtcs;
+pub struct ReadUdsDtcs {
+    db: Arc<dyn DtcDatabase>,
+}
+
+impl Default for ReadUdsDtcs {
+    fn default() -> Self {
+        Self::with_database(dtc_db::builtin())
+    }
+}
+
+impl ReadUdsDtcs {
+    /// Describe codes from `db` instead of the built-in database.
+    pub fn with_database(db: Arc<dyn DtcDatabase>) -> Self {
+        Self { db }
+    }
+}
 
 #[async_trait]
 impl CanTool for ReadUdsDtcs {
@@ -115,8 +131,12 @@ impl CanTool for ReadUdsDtcs {
 
                 // Look up description and severity from database
                 let (description, severity, severity_source): (Option<String>, DtcSeverity, &str) =
-                    match dtc_db::lookup(&code) {
-                        Some(entry) => (Some(entry.description), entry.severity, "database"),
+                    match self.db.lookup(&code) {
+                        Some(entry) => (
+                            Some(entry.description),
+                            entry.severity,
+                            entry.severity_source,
+                        ),
                         None => (None, dtc_db::infer_severity(&code), "heuristic"),
                     };
 
@@ -129,6 +149,7 @@ impl CanTool for ReadUdsDtcs {
                     failure_type: Some(failure_type),
                     raw_dtc: Some(raw_dtc),
                     mil_status: false,
+                    scope: None,
                     freeze_frame: None,
                 });
 
@@ -179,7 +200,7 @@ mod tests {
         ));
 
         let args = serde_json::json!({"ecu": "BCR"});
-        let result = ReadUdsDtcs.execute(args, &mock).await.unwrap();
+        let result = ReadUdsDtcs::default().execute(args, &mock).await.unwrap();
 
         assert!(result.success);
         let summary = result.summary.unwrap();
@@ -215,7 +236,7 @@ mod tests {
         ));
 
         let args = serde_json::json!({"ecu": "BCR"});
-        let result = ReadUdsDtcs.execute(args, &mock).await.unwrap();
+        let result = ReadUdsDtcs::default().execute(args, &mock).await.unwrap();
         assert!(result.success);
 
         let data = result.data.unwrap();
@@ -234,7 +255,7 @@ mod tests {
         ));
 
         let args = serde_json::json!({"ecu": "BCR"});
-        let result = ReadUdsDtcs.execute(args, &mock).await.unwrap();
+        let result = ReadUdsDtcs::default().execute(args, &mock).await.unwrap();
 
         assert!(result.success);
         assert!(result.summary.unwrap().contains("No DTCs"));
@@ -244,7 +265,7 @@ mod tests {
     async fn read_missing_ecu_arg() {
         let mock = MockCanInterface::new();
         let args = serde_json::json!({});
-        let result = ReadUdsDtcs.execute(args, &mock).await.unwrap();
+        let result = ReadUdsDtcs::default().execute(args, &mock).await.unwrap();
 
         assert!(!result.success);
         assert!(result.error.unwrap().contains("Missing"));
@@ -254,7 +275,7 @@ mod tests {
     async fn read_unknown_ecu() {
         let mock = MockCanInterface::new();
         let args = serde_json::json!({"ecu": "XYZ"});
-        let result = ReadUdsDtcs.execute(args, &mock).await;
+        let result = ReadUdsDtcs::default().execute(args, &mock).await;
 
         assert!(matches!(result, Err(CanError::UnknownEcu { .. })));
     }
@@ -269,7 +290,7 @@ mod tests {
         ));
 
         let args = serde_json::json!({"ecu": "BCR"});
-        let result = ReadUdsDtcs.execute(args, &mock).await.unwrap();
+        let result = ReadUdsDtcs::default().execute(args, &mock).await.unwrap();
 
         let data = result.data.unwrap();
         let dtcs: Vec<serde_json::Value> = serde_json::from_value(data).unwrap();
@@ -289,7 +310,7 @@ mod tests {
         ));
 
         let args = serde_json::json!({"ecu": "BCR"});
-        let result = ReadUdsDtcs.execute(args, &mock).await.unwrap();
+        let result = ReadUdsDtcs::default().execute(args, &mock).await.unwrap();
 
         let data = result.data.unwrap();
         let dtcs: Vec<serde_json::Value> = serde_json::from_value(data).unwrap();
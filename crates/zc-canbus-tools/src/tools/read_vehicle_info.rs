@@ -0,0 +1,382 @@
+//! Tool: Read expanded vehicle information (Mode 0x09).
+//!
+//! Beyond the VIN, Mode 0x09 reports the ECU's software identity:
+//! calibration IDs (InfoType 0x04, 16 ASCII bytes each), calibration
+//! verification numbers (0x06, 4 bytes each, one per calibration ID in the
+//! same order) and the ECU name (0x0A, a 4-character acronym, `-` and a
+//! 15-character name). Recall and compliance checks compare these against
+//! the manufacturer's published values. Responses use the same ISO-TP path
+//! as `read_vin`.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::error::{CanError, CanResult};
+use crate::interface::CanInterface;
+use crate::obd;
+use crate::safety;
+use crate::types::{CanTool, MODE_VEHICLE_INFO, ToolResult};
+
+/// InfoType for calibration IDs.
+pub const INFO_CALIBRATION_ID: u8 = 0x04;
+
+/// InfoType for calibration verification numbers.
+pub const INFO_CVN: u8 = 0x06;
+
+/// InfoType for the ECU name.
+pub const INFO_ECU_NAME: u8 = 0x0A;
+
+/// `info` values and their InfoTypes, in request order.
+const INFO_TYPES: [(&str, u8); 3] = [
+    ("calid", INFO_CALIBRATION_ID),
+    ("cvn", INFO_CVN),
+    ("ecu_name", INFO_ECU_NAME),
+];
+
+/// A calibration ID and, when reported, its verification number.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Calibration {
+    pub calibration_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cvn: Option<String>,
+}
+
+/// ECU name from InfoType 0x0A, e.g. `ECM-EngineControl`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EcuName {
+    /// Short name such as "ECM" or "TCM".
+    pub acronym: String,
+    pub name: String,
+}
+
+/// Reads calibration IDs, CVNs and the ECU name via Mode 0x09.
+pub struct ReadVehicleInfo;
+
+#[async_trait]
+impl CanTool for ReadVehicleInfo {
+    fn name(&self) -> &str {
+        "read_vehicle_info"
+    }
+
+    fn description(&self) -> &str {
+        "Read calibration IDs (CALID), calibration verification numbers (CVN) and the ECU name via Mode 0x09, for recall and compliance checks"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "info": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": ["calid", "cvn", "ecu_name"] },
+                    "description": "What to read; all three unless set"
+                },
+                "timeout_ms": { "type": "integer", "description": "Response timeout per InfoType in milliseconds", "default": 3000 }
+            }
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+    ) -> CanResult<ToolResult> {
+        let timeout = Duration::from_millis(
+            args.get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(3000),
+        );
+        let wanted: Vec<(&str, u8)> = match args.get("info").and_then(|v| v.as_array()) {
+            Some(items) => {
+                let mut wanted = Vec::new();
+                for item in items {
+                    let name = item.as_str().unwrap_or_default();
+                    match INFO_TYPES.iter().find(|(n, _)| *n == name) {
+                        Some(entry) => wanted.push(*entry),
+                        None => {
+                            return Ok(ToolResult::failure(
+                                self.name(),
+                                format!("Unknown info '{name}': use calid, cvn or ecu_name"),
+                            ));
+                        }
+                    }
+                }
+                wanted
+            }
+            None => INFO_TYPES.to_vec(),
+        };
+
+        if !safety::is_mode_allowed(MODE_VEHICLE_INFO) {
+            return Err(CanError::SafetyViolation {
+                mode: MODE_VEHICLE_INFO,
+            });
+        }
+
+        let mut calids: Option<Vec<String>> = None;
+        let mut cvns: Option<Vec<String>> = None;
+        let mut ecu_name: Option<EcuName> = None;
+        let mut errors = Vec::new();
+
+        for (name, info_type) in wanted {
+            let items = match query(interface, info_type, timeout).await {
+                Ok(items) => items,
+                Err(e) => {
+                    errors.push(format!("{name}: {e}"));
+                    continue;
+                }
+            };
+            match info_type {
+                INFO_CALIBRATION_ID => calids = Some(decode_calibration_ids(&items)),
+                INFO_CVN => cvns = Some(decode_cvns(&items)),
+                _ => match decode_ecu_name(&items) {
+                    Some(n) => ecu_name = Some(n),
+                    None => errors.push(format!("{name}: empty response")),
+                },
+            }
+        }
+
+        if calids.is_none() && cvns.is_none() && ecu_name.is_none() {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!("No vehicle information read ({})", errors.join("; ")),
+            ));
+        }
+
+        let calibrations = pair(calids.as_deref().unwrap_or_default(), cvns.as_deref());
+
+        let mut parts = Vec::new();
+        if let Some(n) = &ecu_name {
+            parts.push(format!("{} ({})", n.acronym, n.name));
+        }
+        if !calibrations.is_empty() {
+            let described: Vec<String> = calibrations
+                .iter()
+                .map(|c| match &c.cvn {
+                    Some(cvn) => format!("{} [CVN {cvn}]", c.calibration_id),
+                    None => c.calibration_id.clone(),
+                })
+                .collect();
+            parts.push(format!("CALID {}", described.join(", ")));
+        } else if let Some(cvns) = cvns.as_ref().filter(|c| !c.is_empty()) {
+            parts.push(format!("CVN {}", cvns.join(", ")));
+        }
+        let summary = if parts.is_empty() {
+            "No calibration IDs reported".to_string()
+        } else {
+            parts.join("; ")
+        };
+
+        let data = serde_json::json!({
+            "ecu_name": ecu_name,
+            "calibrations": calibrations,
+            "calibration_ids": calids,
+            "cvns": cvns,
+            "errors": errors,
+        });
+
+        Ok(ToolResult::success(self.name(), data, summary))
+    }
+}
+
+/// Request `info_type` and return the data items after the
+/// `[0x49, info_type, count]` header.
+async fn query(
+    interface: &dyn CanInterface,
+    info_type: u8,
+    timeout: Duration,
+) -> CanResult<Vec<u8>> {
+    let request = obd::build_request(MODE_VEHICLE_INFO, info_type);
+    interface.send_frame(&request).await?;
+    let payload = obd::isotp_recv(interface, 0x7E8, timeout).await?;
+
+    if payload.len() >= 3 && payload[0] == 0x7F && payload[1] == MODE_VEHICLE_INFO {
+        return Err(CanError::Protocol(format!(
+            "InfoType 0x{info_type:02X} not supported (NRC 0x{:02X})",
+            payload[2]
+        )));
+    }
+    if payload.len() < 3 || payload[0] != 0x49 || payload[1] != info_type {
+        return Err(CanError::Protocol(format!(
+            "invalid response to InfoType 0x{info_type:02X}"
+        )));
+    }
+    Ok(payload[3..].to_vec())
+}
+
+/// Split into 16-byte ASCII calibration IDs, dropping the zero padding.
+pub fn decode_calibration_ids(data: &[u8]) -> Vec<String> {
+    data.chunks(16)
+        .map(ascii)
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+/// Split into 4-byte CVNs, as hex.
+pub fn decode_cvns(data: &[u8]) -> Vec<String> {
+    data.chunks_exact(4)
+        .map(|cvn| cvn.iter().map(|b| format!("{b:02X}")).collect())
+        .collect()
+}
+
+/// Parse "ECM\0-EngineControl\0\0\0" into acronym and name.
+pub fn decode_ecu_name(data: &[u8]) -> Option<EcuName> {
+    let text = ascii(data);
+    if text.is_empty() {
+        return None;
+    }
+    let (acronym, name) = match text.split_once('-') {
+        Some((acronym, name)) => (acronym.trim(), name.trim()),
+        None => (text.as_str(), ""),
+    };
+    Some(EcuName {
+        acronym: acronym.to_string(),
+        name: name.to_string(),
+    })
+}
+
+/// Printable characters of `bytes`, with NUL padding removed.
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|&b| b as char)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// CVNs belong to calibration IDs in order.
+fn pair(calids: &[String], cvns: Option<&[String]>) -> Vec<Calibration> {
+    calids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| Calibration {
+            calibration_id: id.clone(),
+            cvn: cvns.and_then(|c| c.get(i)).cloned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCanInterface;
+    use crate::types::CanFrame;
+
+    /// ISO-TP frames carrying `payload` from 0x7E8.
+    fn isotp_frames(payload: &[u8]) -> Vec<CanFrame> {
+        if payload.len() <= 7 {
+            let mut data = vec![payload.len() as u8];
+            data.extend_from_slice(payload);
+            data.resize(8, 0x00);
+            return vec![CanFrame::new(0x7E8, data)];
+        }
+        let mut frames = vec![{
+            let mut data = vec![0x10, payload.len() as u8];
+            data.extend_from_slice(&payload[..6]);
+            CanFrame::new(0x7E8, data)
+        }];
+        for (i, chunk) in payload[6..].chunks(7).enumerate() {
+            let mut data = vec![0x20 | ((i as u8 + 1) & 0x0F)];
+            data.extend_from_slice(chunk);
+            frames.push(CanFrame::new(0x7E8, data));
+        }
+        frames
+    }
+
+    fn calid(id: &str) -> Vec<u8> {
+        let mut bytes = id.as_bytes().to_vec();
+        bytes.resize(16, 0x00);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn read_vehicle_info_all() {
+        let mut calids = vec![0x49, 0x04, 0x02];
+        calids.extend(calid("JMB*36761500"));
+        calids.extend(calid("JMB*47872611"));
+        let cvns = [
+            0x49, 0x06, 0x02, 0x17, 0x91, 0xBC, 0x82, 0x16, 0xE0, 0x62, 0xBE,
+        ];
+        let mut ecu_name = vec![0x49, 0x0A, 0x01];
+        ecu_name.extend_from_slice(b"ECM\0-EngineControl\0\0\0");
+
+        let mut responses = isotp_frames(&calids);
+        responses.extend(isotp_frames(&cvns));
+        responses.extend(isotp_frames(&ecu_name));
+        let mock = MockCanInterface::with_responses(responses);
+
+        let result = ReadVehicleInfo
+            .execute(serde_json::json!({}), &mock)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(
+            result.summary.as_deref(),
+            Some(
+                "ECM (EngineControl); CALID JMB*36761500 [CVN 1791BC82], JMB*47872611 [CVN 16E062BE]"
+            )
+        );
+        let data = result.data.unwrap();
+        assert_eq!(data["ecu_name"]["acronym"], "ECM");
+        assert_eq!(data["calibrations"][1]["calibration_id"], "JMB*47872611");
+        assert_eq!(data["calibrations"][1]["cvn"], "16E062BE");
+        assert_eq!(data["errors"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn unsupported_info_types_are_reported() {
+        // CALID answered, CVN rejected, ECU name not requested.
+        let mut calids = vec![0x49, 0x04, 0x01];
+        calids.extend(calid("1234567890"));
+        let mut responses = isotp_frames(&calids);
+        responses.extend(isotp_frames(&[0x7F, 0x09, 0x12]));
+        let mock = MockCanInterface::with_responses(responses);
+
+        let result = ReadVehicleInfo
+            .execute(serde_json::json!({"info": ["calid", "cvn"]}), &mock)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["calibration_ids"], serde_json::json!(["1234567890"]));
+        assert!(data["calibrations"][0].get("cvn").is_none());
+        assert!(
+            data["errors"][0]
+                .as_str()
+                .unwrap()
+                .contains("not supported")
+        );
+    }
+
+    #[tokio::test]
+    async fn nothing_read_is_a_failure() {
+        let mock = MockCanInterface::new();
+        let result = ReadVehicleInfo
+            .execute(serde_json::json!({"timeout_ms": 50}), &mock)
+            .await
+            .unwrap();
+        assert!(!result.success);
+
+        let result = ReadVehicleInfo
+            .execute(serde_json::json!({"info": ["vin"]}), &mock)
+            .await
+            .unwrap();
+        assert!(result.error.unwrap().contains("Unknown info 'vin'"));
+    }
+
+    #[test]
+    fn decoders_handle_padding() {
+        assert_eq!(decode_calibration_ids(&calid("ABC")), ["ABC"]);
+        assert_eq!(decode_calibration_ids(&[0; 16]), Vec::<String>::new());
+        assert_eq!(decode_cvns(&[0xDE, 0xAD, 0xBE, 0xEF, 0x01]), ["DEADBEEF"]);
+        let name = decode_ecu_name(b"TCM\0-TransmissionCtl").unwrap();
+        assert_eq!(
+            (name.acronym.as_str(), name.name.as_str()),
+            ("TCM", "TransmissionCtl")
+        );
+    }
+}
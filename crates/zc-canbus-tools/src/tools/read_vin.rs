@@ -2,6 +2,8 @@
 //!
 //! VIN responses are 20 bytes (SID + PID + count + 17 chars) which exceeds
 //! a single CAN frame, so this tool uses ISO-TP multi-frame reassembly.
+//! The VIN is decoded with [`crate::vin_decode`] (manufacturer, model year,
+//! plant, check digit) and the result attached as `decoded`.
 
 use async_trait::async_trait;
 use std::time::Duration;
@@ -11,6 +13,7 @@ use crate::interface::CanInterface;
 use crate::obd;
 use crate::safety;
 use crate::types::{CanTool, MODE_VEHICLE_INFO, ToolResult};
+use crate::vin_decode;
 
 /// Reads the 17-character VIN via OBD-II Mode 0x09 PID 0x02.
 pub struct ReadVin;
@@ -80,8 +83,16 @@ impl CanTool for ReadVin {
         let vin_bytes = &payload[3..20];
         match std::str::from_utf8(vin_bytes) {
             Ok(vin) => {
-                let data = serde_json::json!({ "vin": vin });
-                let summary = format!("VIN: {vin}");
+                let (data, summary) = match vin_decode::decode(vin) {
+                    Ok(info) => {
+                        let summary = format!("VIN: {vin} ({})", describe(&info));
+                        (serde_json::json!({ "vin": vin, "decoded": info }), summary)
+                    }
+                    Err(e) => (
+                        serde_json::json!({ "vin": vin, "decode_error": e.to_string() }),
+                        format!("VIN: {vin} (not decodable: {e})"),
+                    ),
+                };
                 Ok(ToolResult::success(self.name(), data, summary))
             }
             Err(e) => Ok(ToolResult::failure(
@@ -92,6 +103,24 @@ impl CanTool for ReadVin {
     }
 }
 
+/// One-line description, e.g. "2003 Honda, United States, plant A".
+fn describe(info: &vin_decode::VinInfo) -> String {
+    let mut vehicle = Vec::new();
+    if let Some(year) = info.model_year {
+        vehicle.push(year.to_string());
+    }
+    vehicle.push(info.manufacturer.unwrap_or(&info.wmi).to_string());
+    let mut parts = vec![
+        vehicle.join(" "),
+        info.country.unwrap_or(info.region).to_string(),
+        format!("plant {}", info.plant_code),
+    ];
+    if !info.check_digit_valid {
+        parts.push("check digit mismatch".into());
+    }
+    parts.join(", ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,8 +158,22 @@ mod tests {
         assert!(result.success);
         let summary = result.summary.unwrap();
         assert!(summary.contains("1HGCM82633A004352"));
+        assert!(summary.contains("2003 Honda, United States, plant A"));
 
         let data = result.data.unwrap();
         assert_eq!(data["vin"], "1HGCM82633A004352");
+        assert_eq!(data["decoded"]["manufacturer"], "Honda");
+        assert_eq!(data["decoded"]["model_year"], 2003);
+        assert_eq!(data["decoded"]["plant_code"], "A");
+        assert_eq!(data["decoded"]["check_digit_valid"], true);
+    }
+
+    #[test]
+    fn describe_flags_unknown_manufacturer_and_bad_check_digit() {
+        let info = vin_decode::decode("9BWZZZ377VT004251").unwrap();
+        let text = describe(&info);
+        assert!(text.contains("9BW"), "{text}");
+        assert!(text.contains("South America"), "{text}");
+        assert!(text.ends_with("check digit mismatch"), "{text}");
     }
 }
@@ -0,0 +1,315 @@
+//! Tool: Transmit a single user-specified CAN frame (bench mode only).
+//!
+//! Lets engineers poke ECUs during bring-up: sends one frame with an
+//! arbitrary ID and payload, then optionally collects replies whose ID
+//! matches `reply_id` under `reply_mask`. The OBD-II mode and UDS service
+//! allowlists still apply — they are enforced by the interface, not here.
+//!
+//! Not part of [`super::all_tools`]; the agent registers it only when
+//! `bench_mode` is enabled. Every transmitted frame is logged on the
+//! `audit` tracing target and reported under `tx` in the result, also when
+//! collecting replies fails afterwards, so the cloud can add it to its
+//! audit trail.
+
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::error::{CanError, CanResult};
+use crate::interface::CanInterface;
+use crate::tools::{MAX_CAN_ID, parse_u32_arg};
+use crate::types::{CanFrame, CanTool, ToolResult};
+
+/// Classic CAN payload limit.
+const MAX_DATA_LEN: usize = 8;
+
+/// Longest reply window (safety limit).
+const MAX_WAIT_MS: u64 = 5000;
+
+/// Default reply window when `reply_id` is given without `wait_ms`.
+const DEFAULT_WAIT_MS: u64 = 500;
+
+/// Maximum replies collected per invocation.
+const MAX_REPLIES: usize = 100;
+
+/// Transmits one raw CAN frame and optionally collects matching replies.
+pub struct SendFrame;
+
+#[async_trait]
+impl CanTool for SendFrame {
+    fn name(&self) -> &str {
+        "send_frame"
+    }
+
+    fn description(&self) -> &str {
+        "Transmit a raw CAN frame (bench mode only) and optionally wait for replies matching an ID filter. Max 8 data bytes, 5s reply window."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "description": "CAN arbitration ID (integer or \"0x7E0\" string)" },
+                "data": { "type": "string", "description": "Payload as hex bytes, e.g. \"02 10 03\" (max 8 bytes)" },
+                "reply_id": { "type": "integer", "description": "Collect replies whose ID matches this value" },
+                "reply_mask": { "type": "integer", "description": "Mask applied to IDs before matching reply_id", "default": 0x1FFFFFFF },
+                "wait_ms": { "type": "integer", "description": "Reply window in milliseconds (max 5000)", "default": 500 },
+                "max_replies": { "type": "integer", "description": "Stop after this many replies (max 100)", "default": 16 }
+            },
+            "required": ["id", "data"]
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+    ) -> CanResult<ToolResult> {
+        let Some(id) = args.get("id").and_then(parse_u32_arg) else {
+            return Ok(ToolResult::failure(
+                self.name(),
+                "Missing required argument: id (CAN ID)",
+            ));
+        };
+        if id > MAX_CAN_ID {
+            return Ok(ToolResult::failure(
+                self.name(),
+                format!("CAN ID 0x{id:X} exceeds 29 bits"),
+            ));
+        }
+
+        let data = match args.get("data").map(parse_data_arg) {
+            Some(Ok(d)) => d,
+            Some(Err(msg)) => return Ok(ToolResult::failure(self.name(), msg)),
+            None => {
+                return Ok(ToolResult::failure(
+                    self.name(),
+                    "Missing required argument: data (hex bytes)",
+                ));
+            }
+        };
+
+        let reply_id = args.get("reply_id").and_then(parse_u32_arg);
+        let reply_mask = args
+            .get("reply_mask")
+            .and_then(parse_u32_arg)
+            .unwrap_or(MAX_CAN_ID);
+        let wait_ms = args
+            .get("wait_ms")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(if reply_id.is_some() {
+                DEFAULT_WAIT_MS
+            } else {
+                0
+            })
+            .min(MAX_WAIT_MS);
+        let max_replies = args
+            .get("max_replies")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(16)
+            .clamp(1, MAX_REPLIES as u64) as usize;
+
+        let hex = to_hex(&data);
+        let frame = CanFrame::new(id, data);
+        interface.drain_rx_buffer().await;
+        interface.send_frame(&frame).await?;
+
+        let sent_at = Utc::now();
+        tracing::info!(
+            target: "audit",
+            tool = "send_frame",
+            can_id = format!("0x{id:03X}"),
+            data = %hex,
+            "bench CAN frame transmitted"
+        );
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(wait_ms);
+        let mut replies: Vec<serde_json::Value> = Vec::new();
+        let mut error = None;
+
+        while Instant::now() < deadline && replies.len() < max_replies {
+            let wait = deadline
+                .saturating_duration_since(Instant::now())
+                .min(Duration::from_millis(100));
+            match interface.recv_frame(wait).await {
+                Ok(f) => {
+                    let matches = reply_id.is_none_or(|rid| f.id & reply_mask == rid & reply_mask);
+                    if !matches {
+                        continue;
+                    }
+                    replies.push(serde_json::json!({
+                        "id": format!("0x{:03X}", f.id),
+                        "data": to_hex(&f.data),
+                        "dlc": f.data.len(),
+                        "offset_ms": start.elapsed().as_millis() as u64,
+                    }));
+                }
+                Err(CanError::Timeout { .. }) => continue,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        let reply_count = replies.len();
+        let data = serde_json::json!({
+            "tx": {
+                "id": format!("0x{id:03X}"),
+                "data": hex,
+                "dlc": frame.data.len(),
+                "sent_at": sent_at,
+            },
+            "replies": replies,
+            "reply_count": reply_count,
+            "reply_filter": reply_id.map(|rid| format!("0x{rid:03X}/0x{reply_mask:X}")),
+            "wait_ms": wait_ms,
+        });
+        let summary = if wait_ms == 0 {
+            format!("Sent 0x{id:03X} [{hex}]")
+        } else {
+            format!("Sent 0x{id:03X} [{hex}], {reply_count} replies in {wait_ms}ms")
+        };
+
+        let mut result = ToolResult::success(self.name(), data, summary);
+        if let Some(error) = error {
+            result.success = false;
+            result.error = Some(format!(
+                "frame sent, but collecting replies failed: {error}"
+            ));
+        }
+        Ok(result)
+    }
+}
+
+/// Accept a payload as a hex string (`"02 10 03"`, `"021003"`) or a byte array.
+fn parse_data_arg(v: &serde_json::Value) -> Result<Vec<u8>, String> {
+    let bytes = if let Some(arr) = v.as_array() {
+        arr.iter()
+            .map(|b| {
+                b.as_u64()
+                    .and_then(|n| u8::try_from(n).ok())
+                    .ok_or_else(|| format!("invalid data byte: {b}"))
+            })
+            .collect::<Result<Vec<u8>, String>>()?
+    } else if let Some(s) = v.as_str() {
+        let digits: String = s
+            .split_whitespace()
+            .map(|w| w.trim_start_matches("0x").trim_start_matches("0X"))
+            .collect();
+        if !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+            return Err(format!("invalid hex in data: {s}"));
+        }
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&digits[i..i + 2], 16)
+                    .map_err(|_| format!("invalid hex in data: {s}"))
+            })
+            .collect::<Result<Vec<u8>, String>>()?
+    } else {
+        return Err("data must be a hex string or byte array".into());
+    };
+
+    if bytes.len() > MAX_DATA_LEN {
+        return Err(format!("data is {} bytes, max {MAX_DATA_LEN}", bytes.len()));
+    }
+    Ok(bytes)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCanInterface;
+
+    #[tokio::test]
+    async fn sends_frame_without_waiting() {
+        let mock = MockCanInterface::new();
+        let args = serde_json::json!({ "id": "0x123", "data": "11 22 33" });
+        let result = SendFrame.execute(args, &mock).await.unwrap();
+
+        assert!(result.success);
+        let sent = mock.sent_frames();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].id, 0x123);
+        assert_eq!(sent[0].data, vec![0x11, 0x22, 0x33]);
+
+        let data = result.data.unwrap();
+        assert_eq!(data["tx"]["data"], "11 22 33");
+        assert_eq!(data["reply_count"], 0);
+    }
+
+    #[tokio::test]
+    async fn collects_matching_replies() {
+        let mock = MockCanInterface::with_responses(vec![
+            CanFrame::new(0x321, vec![0xAA]),
+            CanFrame::new(0x7E8, vec![0x06, 0x50, 0x03]),
+            CanFrame::new(0x7E9, vec![0x06, 0x50, 0x03]),
+        ]);
+        let args = serde_json::json!({
+            "id": 0x7E0,
+            "data": [0x02, 0x10, 0x03],
+            "reply_id": "0x7E8",
+            "wait_ms": 20
+        });
+        let result = SendFrame.execute(args, &mock).await.unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["reply_count"], 1);
+        assert_eq!(data["replies"][0]["id"], "0x7E8");
+        assert_eq!(data["replies"][0]["data"], "06 50 03");
+    }
+
+    #[tokio::test]
+    async fn reply_mask_widens_filter() {
+        let mock = MockCanInterface::with_responses(vec![
+            CanFrame::new(0x7E8, vec![0x01]),
+            CanFrame::new(0x7EA, vec![0x02]),
+        ]);
+        let args = serde_json::json!({
+            "id": 0x7DF,
+            "data": "02 01 00",
+            "reply_id": 0x7E8,
+            "reply_mask": 0x7F8,
+            "wait_ms": 20
+        });
+        let result = SendFrame.execute(args, &mock).await.unwrap();
+        assert_eq!(result.data.unwrap()["reply_count"], 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_payload() {
+        let mock = MockCanInterface::new();
+        let args = serde_json::json!({ "id": 0x100, "data": "00 11 22 33 44 55 66 77 88" });
+        let result = SendFrame.execute(args, &mock).await.unwrap();
+        assert!(!result.success);
+        assert!(mock.sent_frames().is_empty());
+    }
+
+    #[tokio::test]
+    async fn blocked_obd_mode_still_rejected() {
+        // Mode 0x04 (clear DTCs) on the OBD broadcast ID.
+        let mock = MockCanInterface::new();
+        let args = serde_json::json!({ "id": 0x7DF, "data": "01 04" });
+        let err = SendFrame.execute(args, &mock).await.unwrap_err();
+        assert!(matches!(err, CanError::SafetyViolation { mode: 0x04 }));
+    }
+
+    #[test]
+    fn parse_data_forms() {
+        let compact = serde_json::json!("021003");
+        assert_eq!(parse_data_arg(&compact).unwrap(), vec![0x02, 0x10, 0x03]);
+        let prefixed = serde_json::json!("0x02 0x10");
+        assert_eq!(parse_data_arg(&prefixed).unwrap(), vec![0x02, 0x10]);
+        assert!(parse_data_arg(&serde_json::json!("abc")).is_err());
+    }
+}
@@ -27,6 +27,12 @@ pub const MODE_FREEZE_FRAME: u8 = 0x02;
 /// Mode 03: Show stored DTCs.
 pub const MODE_STORED_DTCS: u8 = 0x03;
 
+/// Mode 07: Show pending DTCs (detected during the current or last drive cycle).
+pub const MODE_PENDING_DTCS: u8 = 0x07;
+
+/// Mode 0A: Show permanent DTCs (cannot be cleared by Mode 04).
+pub const MODE_PERMANENT_DTCS: u8 = 0x0A;
+
 /// Mode 09: Request vehicle information (VIN, etc.).
 pub const MODE_VEHICLE_INFO: u8 = 0x09;
 
@@ -109,6 +115,9 @@ impl ToolResult {
 
 // ── CanTool Trait ────────────────────────────────────────────────
 
+/// Receiver for partial results emitted by a streaming tool.
+pub type ChunkSink<'a> = dyn Fn(serde_json::Value) + Send + Sync + 'a;
+
 /// Trait for CAN bus diagnostic tools.
 ///
 /// Structurally identical to ZeroClaw's `Tool` trait but owned by this crate.
@@ -130,4 +139,21 @@ pub trait CanTool: Send + Sync {
         args: serde_json::Value,
         interface: &dyn crate::interface::CanInterface,
     ) -> CanResult<ToolResult>;
+
+    /// Whether [`CanTool::execute_streaming`] emits partial results.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Execute, passing partial results to `sink` while the tool runs.
+    /// The returned result summarises the run. Defaults to [`CanTool::execute`].
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        interface: &dyn crate::interface::CanInterface,
+        sink: &ChunkSink<'_>,
+    ) -> CanResult<ToolResult> {
+        let _ = sink;
+        self.execute(args, interface).await
+    }
 }
@@ -0,0 +1,291 @@
+//! VIN (ISO 3779) validation and decoding.
+//!
+//! Splits a 17-character VIN into WMI / VDS / VIS, verifies the check digit
+//! (position 9, mandatory in North America) and decodes the manufacturer from
+//! a built-in WMI table, the model year (position 10) and the plant code
+//! (position 11). Model names live in the manufacturer-specific VDS and are
+//! not decoded.
+
+use chrono::Datelike;
+use serde::Serialize;
+
+use crate::error::{CanError, CanResult};
+
+/// Position weights for the check digit calculation.
+const CHECK_WEIGHTS: [u32; 17] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+/// Model year codes in order; index 0 is 1980 (and 2010).
+const YEAR_CODES: &str = "ABCDEFGHJKLMNPRSTVWXY123456789";
+
+/// World manufacturer identifiers known to the decoder.
+const WMI_TABLE: &[(&str, &str)] = &[
+    ("1FA", "Ford"),
+    ("1FM", "Ford"),
+    ("1FT", "Ford"),
+    ("1FU", "Freightliner"),
+    ("1G1", "Chevrolet"),
+    ("1GC", "Chevrolet"),
+    ("1GT", "GMC"),
+    ("1HG", "Honda"),
+    ("1J4", "Jeep"),
+    ("1C4", "Chrysler"),
+    ("1M1", "Mack"),
+    ("1M2", "Mack"),
+    ("1N4", "Nissan"),
+    ("1XK", "Kenworth"),
+    ("1XP", "Peterbilt"),
+    ("2HG", "Honda"),
+    ("2T1", "Toyota"),
+    ("3AK", "Freightliner"),
+    ("3VW", "Volkswagen"),
+    ("4T1", "Toyota"),
+    ("4V4", "Volvo Trucks"),
+    ("5N1", "Nissan"),
+    ("5YJ", "Tesla"),
+    ("JF1", "Subaru"),
+    ("JHM", "Honda"),
+    ("JM1", "Mazda"),
+    ("JN1", "Nissan"),
+    ("JTD", "Toyota"),
+    ("KMH", "Hyundai"),
+    ("KNA", "Kia"),
+    ("SAL", "Land Rover"),
+    ("TMB", "Skoda"),
+    ("VF1", "Renault"),
+    ("VF3", "Peugeot"),
+    ("VSS", "SEAT"),
+    ("WAU", "Audi"),
+    ("WBA", "BMW"),
+    ("WDB", "Mercedes-Benz"),
+    ("WDD", "Mercedes-Benz"),
+    ("WF0", "Ford"),
+    ("WMA", "MAN"),
+    ("WP0", "Porsche"),
+    ("WVW", "Volkswagen"),
+    ("XLR", "DAF"),
+    ("YS2", "Scania"),
+    ("YV1", "Volvo"),
+    ("ZFA", "Fiat"),
+];
+
+/// Decoded VIN fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VinInfo {
+    /// World manufacturer identifier (positions 1–3).
+    pub wmi: String,
+    /// Vehicle descriptor section (positions 4–9, including the check digit).
+    pub vds: String,
+    /// Vehicle identifier section (positions 10–17).
+    pub vis: String,
+    /// Manufacturer, if the WMI is in the built-in table.
+    pub manufacturer: Option<&'static str>,
+    /// Region of manufacture, from position 1.
+    pub region: &'static str,
+    pub country: Option<&'static str>,
+    pub model_year: Option<u16>,
+    /// Assembly plant code (position 11), manufacturer-specific.
+    pub plant_code: char,
+    /// Production sequence number (positions 12–17).
+    pub serial_number: String,
+    pub check_digit: char,
+    /// Whether position 9 matches the computed check digit. Vehicles built
+    /// outside North America often don't use one.
+    pub check_digit_valid: bool,
+}
+
+/// Validate and decode a VIN. Fails if it is not 17 characters from the VIN
+/// alphabet (digits and letters except I, O and Q); a wrong check digit is
+/// reported in [`VinInfo::check_digit_valid`] instead.
+pub fn decode(vin: &str) -> CanResult<VinInfo> {
+    decode_for_year(vin, chrono::Utc::now().year())
+}
+
+fn decode_for_year(vin: &str, current_year: i32) -> CanResult<VinInfo> {
+    let vin = vin.trim().to_ascii_uppercase();
+    if vin.len() != 17 {
+        return Err(CanError::Decode(format!(
+            "VIN must be 17 characters, got {}",
+            vin.len()
+        )));
+    }
+    if let Some(bad) = vin.chars().find(|c| transliterate(*c).is_none()) {
+        return Err(CanError::Decode(format!("invalid VIN character '{bad}'")));
+    }
+
+    let chars: Vec<char> = vin.chars().collect();
+    let check_digit = chars[8];
+    let wmi = &vin[..3];
+
+    Ok(VinInfo {
+        wmi: wmi.to_string(),
+        vds: vin[3..9].to_string(),
+        vis: vin[9..].to_string(),
+        manufacturer: WMI_TABLE
+            .iter()
+            .find(|(code, _)| *code == wmi)
+            .map(|(_, name)| *name),
+        region: region(chars[0]),
+        country: country(chars[0]),
+        model_year: model_year(chars[9], chars[6], chars[0], current_year),
+        plant_code: chars[10],
+        serial_number: vin[11..].to_string(),
+        check_digit,
+        check_digit_valid: compute_check_digit(&vin) == Some(check_digit),
+    })
+}
+
+/// Expected check digit ('0'–'9' or 'X') for a 17-character VIN.
+pub fn compute_check_digit(vin: &str) -> Option<char> {
+    if vin.len() != 17 {
+        return None;
+    }
+    let mut sum = 0;
+    for (c, weight) in vin.chars().zip(CHECK_WEIGHTS) {
+        sum += transliterate(c.to_ascii_uppercase())? * weight;
+    }
+    Some(match sum % 11 {
+        10 => 'X',
+        n => char::from_digit(n, 10)?,
+    })
+}
+
+/// Numeric value of a VIN character; `None` outside the VIN alphabet.
+fn transliterate(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        'A' | 'J' => Some(1),
+        'B' | 'K' | 'S' => Some(2),
+        'C' | 'L' | 'T' => Some(3),
+        'D' | 'M' | 'U' => Some(4),
+        'E' | 'N' | 'V' => Some(5),
+        'F' | 'W' => Some(6),
+        'G' | 'P' | 'X' => Some(7),
+        'H' | 'Y' => Some(8),
+        'R' | 'Z' => Some(9),
+        _ => None,
+    }
+}
+
+/// Model year from position 10. The code repeats every 30 years: North
+/// American light vehicles mark the 2010+ cycle with a letter in position 7;
+/// elsewhere the most recent year not in the future is assumed.
+fn model_year(code: char, position7: char, first: char, current_year: i32) -> Option<u16> {
+    let earlier = 1980 + YEAR_CODES.find(code)? as i32;
+    let later = earlier + 30;
+    let year = if matches!(first, '1'..='5') {
+        if position7.is_ascii_alphabetic() {
+            later
+        } else {
+            earlier
+        }
+    } else if later <= current_year + 1 {
+        later
+    } else {
+        earlier
+    };
+    u16::try_from(year).ok()
+}
+
+fn region(first: char) -> &'static str {
+    match first {
+        'A'..='H' => "Africa",
+        'J'..='R' => "Asia",
+        'S'..='Z' => "Europe",
+        '1'..='5' => "North America",
+        '6' | '7' => "Oceania",
+        _ => "South America",
+    }
+}
+
+fn country(first: char) -> Option<&'static str> {
+    match first {
+        '1' | '4' | '5' => Some("United States"),
+        '2' => Some("Canada"),
+        '3' => Some("Mexico"),
+        'J' => Some("Japan"),
+        'K' => Some("South Korea"),
+        'L' => Some("China"),
+        'W' => Some("Germany"),
+        'Z' => Some("Italy"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_honda() {
+        let info = decode_for_year("1HGCM82633A004352", 2026).unwrap();
+        assert_eq!(info.wmi, "1HG");
+        assert_eq!(info.vds, "CM8263");
+        assert_eq!(info.vis, "3A004352");
+        assert_eq!(info.manufacturer, Some("Honda"));
+        assert_eq!(info.region, "North America");
+        assert_eq!(info.country, Some("United States"));
+        assert_eq!(info.model_year, Some(2003));
+        assert_eq!(info.plant_code, 'A');
+        assert_eq!(info.serial_number, "004352");
+        assert!(info.check_digit_valid);
+    }
+
+    #[test]
+    fn check_digit_x_and_mismatch() {
+        assert_eq!(compute_check_digit("1M8GDM9AXKP042788"), Some('X'));
+        assert!(decode("1M8GDM9AXKP042788").unwrap().check_digit_valid);
+
+        let info = decode("1HGCM82643A004352").unwrap();
+        assert!(!info.check_digit_valid);
+        assert_eq!(info.check_digit, '4');
+    }
+
+    #[test]
+    fn model_year_cycles() {
+        // Letter in position 7 → 2010+ cycle for North American vehicles.
+        let tesla = decode_for_year("5YJ3E1EA2KF317000", 2026).unwrap();
+        assert_eq!(tesla.manufacturer, Some("Tesla"));
+        assert_eq!(tesla.model_year, Some(2019));
+        // Digit in position 7 → 1980–2009 cycle.
+        let mack = decode_for_year("1M8GDM9AXKP042788", 2026).unwrap();
+        assert_eq!(mack.model_year, Some(1989));
+
+        // Elsewhere: most recent year that isn't in the future.
+        assert_eq!(
+            decode_for_year("WBA3A5C51CF256651", 2026)
+                .unwrap()
+                .model_year,
+            Some(2012)
+        );
+        assert_eq!(
+            decode_for_year("WBA3A5C51YF256651", 2026)
+                .unwrap()
+                .model_year,
+            Some(2000)
+        );
+    }
+
+    #[test]
+    fn lowercase_and_whitespace_accepted() {
+        let info = decode(" 1hgcm82633a004352\n").unwrap();
+        assert_eq!(info.wmi, "1HG");
+        assert!(info.check_digit_valid);
+    }
+
+    #[test]
+    fn unknown_wmi_still_decodes() {
+        let info = decode_for_year("9BWZZZ377VT004251", 2025).unwrap();
+        assert_eq!(info.manufacturer, None);
+        assert_eq!(info.region, "South America");
+        assert_eq!(info.country, None);
+        assert_eq!(info.model_year, Some(1997));
+    }
+
+    #[test]
+    fn rejects_malformed_vins() {
+        assert!(matches!(decode("1HGCM8263"), Err(CanError::Decode(_))));
+        let err = decode("1HGCM82633A00435O").unwrap_err();
+        assert!(err.to_string().contains("'O'"));
+        assert!(decode("1HGCM82633A00435-").is_err());
+    }
+}
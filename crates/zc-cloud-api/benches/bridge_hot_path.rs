@@ -0,0 +1,212 @@
+//! Allocation benchmark for the MQTT bridge hot path.
+//!
+//! Decodes 10k heartbeat and telemetry publishes the old way (owned
+//! `parse_topic` + full `Heartbeat`, cloned telemetry rows) and the new way
+//! (`parse_topic_ref` + `HeartbeatView`, moved rows into a reused buffer),
+//! then drives the real `handle_incoming_with` over the same heartbeats.
+//! Reports allocations per message and throughput.
+//!
+//! Run with `cargo bench -p zc-cloud-api --bench bridge_hot_path`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use chrono::Utc;
+use zc_cloud_api::db::telemetry::TelemetryRow;
+use zc_cloud_api::mqtt_bridge::{self, BridgeScratch};
+use zc_cloud_api::state::AppState;
+use zc_protocol::device::{DeviceStatus, Heartbeat, HeartbeatView, ServiceStatus};
+use zc_protocol::telemetry::{TelemetryBatch, TelemetryReading, TelemetrySource};
+use zc_protocol::topics;
+
+const MESSAGES: usize = 10_000;
+const TARGET_RATE: f64 = 10_000.0;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Run `f` once per message and print allocations/message and msg/s.
+fn measure(label: &str, messages: &[(String, Vec<u8>)], mut f: impl FnMut(&str, &[u8])) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for (topic, payload) in messages {
+        f(topic, payload);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    let rate = messages.len() as f64 / elapsed;
+    println!(
+        "{label:<28} {:>8.2} allocs/msg {:>12.0} msg/s{}",
+        allocs as f64 / messages.len() as f64,
+        rate,
+        if rate < TARGET_RATE {
+            "  (below 10k msg/s)"
+        } else {
+            ""
+        },
+    );
+}
+
+fn heartbeats() -> Vec<(String, Vec<u8>)> {
+    (0..MESSAGES)
+        .map(|i| {
+            let device_id = format!("rpi-{:03}", i % 100);
+            let hb = Heartbeat {
+                device_id: device_id.clone(),
+                fleet_id: "fleet-alpha".into(),
+                status: DeviceStatus::Online,
+                uptime_secs: i as u64,
+                ollama_status: ServiceStatus::Running,
+                can_status: ServiceStatus::Running,
+                agent_version: "0.1.0".into(),
+                machine_id: Some("a8b9c0d1e2f34567890abcdef0123456".into()),
+                cert_expires_at: None,
+                encodings: vec![],
+                version: zc_protocol::device::HEARTBEAT_VERSION,
+                vitals: None,
+                timestamp: Utc::now(),
+            };
+            (
+                topics::heartbeat("fleet-alpha", &device_id),
+                serde_json::to_vec(&hb).unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn telemetry_batches() -> Vec<(String, Vec<u8>)> {
+    (0..MESSAGES)
+        .map(|i| {
+            let device_id = format!("rpi-{:03}", i % 100);
+            let reading = |name: &str, value: f64, unit: &str| TelemetryReading {
+                device_id: device_id.clone(),
+                time: Utc::now(),
+                metric_name: name.into(),
+                value_numeric: Some(value),
+                value_text: None,
+                value_json: None,
+                unit: Some(unit.into()),
+                source: TelemetrySource::Obd2,
+            };
+            let batch = TelemetryBatch {
+                device_id: device_id.clone(),
+                readings: vec![
+                    reading("engine_rpm", 3000.0, "rpm"),
+                    reading("coolant_temp", 90.0, "celsius"),
+                ],
+                collected_at: Utc::now(),
+            };
+            (
+                topics::telemetry_obd2("fleet-alpha", &device_id),
+                serde_json::to_vec(&batch).unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn main() {
+    let heartbeats = heartbeats();
+    let telemetry = telemetry_batches();
+
+    println!("bridge hot path, {MESSAGES} messages per run");
+
+    measure("heartbeat decode (owned)", &heartbeats, |topic, payload| {
+        let parsed = topics::parse_topic(topic).unwrap();
+        let hb: Heartbeat = serde_json::from_slice(payload).unwrap();
+        black_box((parsed, hb));
+    });
+    measure(
+        "heartbeat decode (borrowed)",
+        &heartbeats,
+        |topic, payload| {
+            let parsed = topics::parse_topic_ref(topic).unwrap();
+            assert!(payload.len() <= mqtt_bridge::max_payload_bytes(parsed.category));
+            let hb: HeartbeatView = serde_json::from_slice(payload).unwrap();
+            black_box((parsed, hb));
+        },
+    );
+
+    measure("telemetry rows (cloned)", &telemetry, |topic, payload| {
+        let parsed = topics::parse_topic(topic).unwrap();
+        let device_id = parsed.device_id.unwrap();
+        let batch: TelemetryBatch = serde_json::from_slice(payload).unwrap();
+        let rows: Vec<TelemetryRow> = batch
+            .readings
+            .iter()
+            .map(|r| TelemetryRow {
+                time: r.time,
+                device_id: device_id.clone(),
+                metric_name: r.metric_name.clone(),
+                value_numeric: r.value_numeric,
+                value_text: r.value_text.clone(),
+                value_json: r.value_json.clone(),
+                unit: r.unit.clone(),
+                source: format!("{:?}", r.source).to_lowercase(),
+            })
+            .collect();
+        black_box(rows);
+    });
+    let mut rows: Vec<TelemetryRow> = Vec::new();
+    measure("telemetry rows (moved)", &telemetry, |topic, payload| {
+        let parsed = topics::parse_topic_ref(topic).unwrap();
+        let device_id = parsed.device_id.unwrap();
+        let batch: TelemetryBatch = serde_json::from_slice(payload).unwrap();
+        rows.clear();
+        rows.extend(batch.readings.into_iter().map(|r| TelemetryRow {
+            time: r.time,
+            device_id: device_id.to_string(),
+            metric_name: r.metric_name,
+            value_numeric: r.value_numeric,
+            value_text: r.value_text,
+            value_json: r.value_json,
+            unit: r.unit,
+            source: r.source.as_str().to_string(),
+        }));
+        black_box(&rows);
+    });
+
+    // End to end through the bridge, in-memory state (no database).
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let state = AppState::new();
+    let mut scratch = BridgeScratch::default();
+    runtime.block_on(async {
+        // Warm up: auto-register every device once so the measured run only
+        // covers the steady-state heartbeat path.
+        for (topic, payload) in heartbeats.iter().take(100) {
+            mqtt_bridge::handle_incoming_with(topic, payload, &state, &mut scratch).await;
+        }
+    });
+    measure("heartbeat bridge (e2e)", &heartbeats, |topic, payload| {
+        runtime.block_on(mqtt_bridge::handle_incoming_with(
+            topic,
+            payload,
+            &state,
+            &mut scratch,
+        ));
+    });
+}
@@ -0,0 +1,21 @@
+//! Generates the gRPC service from `proto/`, with a vendored `protoc` so
+//! builds do not need one installed.
+
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::var_os("PROTOC").is_none() {
+        // SAFETY: build scripts are single-threaded.
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    }
+    // google/protobuf/timestamp.proto ships with the vendored protoc.
+    let well_known = protoc_bin_vendored::include_path()?;
+    tonic_prost_build::configure()
+        .build_client(true)
+        .compile_protos(
+            &[PathBuf::from("proto/zeroclaw/v1/fleet.proto")],
+            &[PathBuf::from("proto"), well_known],
+        )?;
+    println!("cargo:rerun-if-changed=proto");
+    Ok(())
+}
@@ -0,0 +1,10 @@
+-- Per-device command queue for offline delivery.
+--
+-- Commands sent to an offline device are stored with status 'queued' and the
+-- full serialized envelope, then replayed over MQTT on the next heartbeat.
+
+ALTER TABLE commands ADD COLUMN IF NOT EXISTS envelope JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_commands_queued
+    ON commands (device_id, created_at)
+    WHERE status = 'queued';
@@ -0,0 +1,28 @@
+-- Inference prompt/threshold A/B experiments.
+--
+-- At most one experiment is active at a time. Each dispatched command is
+-- assigned a variant; the parse result is recorded immediately and the
+-- command's terminal status is filled in when the device responds.
+
+CREATE TABLE IF NOT EXISTS experiments (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    variants JSONB NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    stopped_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS experiment_assignments (
+    command_id UUID PRIMARY KEY,
+    experiment_id UUID NOT NULL REFERENCES experiments(id) ON DELETE CASCADE,
+    variant TEXT NOT NULL,
+    parsed BOOLEAN NOT NULL,
+    confidence DOUBLE PRECISION,
+    outcome TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_experiment_assignments_experiment
+    ON experiment_assignments (experiment_id, variant);
@@ -0,0 +1,16 @@
+-- Device self-test reports (provisioning verification records).
+--
+-- Agents publish one on first boot and whenever the self_test command
+-- runs. The latest row per device is its current verification status.
+
+CREATE TABLE IF NOT EXISTS device_self_tests (
+    id          BIGSERIAL PRIMARY KEY,
+    device_id   TEXT NOT NULL,
+    passed      BOOLEAN NOT NULL,
+    report      JSONB NOT NULL,
+    started_at  TIMESTAMPTZ NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_self_tests_device_time
+    ON device_self_tests (device_id, received_at DESC);
@@ -0,0 +1,11 @@
+-- Repair hints and reference links per DTC code.
+--
+-- Maintained through the admin API (single edits or CSV import) and
+-- merged into DTC entries of command responses as they are ingested.
+
+CREATE TABLE IF NOT EXISTS dtc_knowledge (
+    code        TEXT PRIMARY KEY,
+    repair_hint TEXT,
+    links       JSONB NOT NULL DEFAULT '[]',
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
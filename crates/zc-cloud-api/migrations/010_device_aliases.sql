@@ -0,0 +1,26 @@
+-- Alternate identifiers (VIN, serial number, asset tag) per device.
+--
+-- REST endpoints accept any alias where a device ID is expected. Each
+-- (kind, value) pair belongs to at most one device, which is how a second
+-- device claiming an already-registered VIN is detected. Values are stored
+-- normalized (trimmed, upper-case).
+
+CREATE TABLE IF NOT EXISTS device_aliases (
+    kind        TEXT NOT NULL,
+    value       TEXT NOT NULL,
+    device_id   TEXT NOT NULL REFERENCES devices(device_id) ON DELETE CASCADE,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (kind, value)
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_aliases_device ON device_aliases (device_id);
+CREATE INDEX IF NOT EXISTS idx_device_aliases_value ON device_aliases (value);
+
+-- Existing VINs become aliases; where two devices share one, the earliest
+-- provisioned keeps it.
+INSERT INTO device_aliases (kind, value, device_id, created_at)
+SELECT 'vin', upper(trim(vin)), device_id, created_at
+FROM devices
+WHERE vin IS NOT NULL AND trim(vin) <> ''
+ORDER BY created_at
+ON CONFLICT (kind, value) DO NOTHING;
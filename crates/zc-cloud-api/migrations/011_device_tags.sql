@@ -0,0 +1,14 @@
+-- Free-form key=value tags per device (region, customer, hardware revision).
+--
+-- Used to filter the device list (`?tag=env:prod`) and to target fleet
+-- broadcasts at a subset of devices. One value per key and device.
+
+CREATE TABLE IF NOT EXISTS device_tags (
+    device_id   TEXT NOT NULL REFERENCES devices(device_id) ON DELETE CASCADE,
+    key         TEXT NOT NULL,
+    value       TEXT NOT NULL,
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (device_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_tags_key_value ON device_tags (key, value);
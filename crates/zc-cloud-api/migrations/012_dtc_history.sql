@@ -0,0 +1,28 @@
+-- DTC history per device, built from ingested read_dtcs / read_uds_dtcs
+-- responses.
+--
+-- Every successful scan is recorded in dtc_scans (also scans that found no
+-- codes, so cleared faults become inactive); each code a scan reported is
+-- one dtc_history row. A code is active when it was seen in the device's
+-- latest scan.
+
+CREATE TABLE IF NOT EXISTS dtc_scans (
+    device_id   TEXT NOT NULL REFERENCES devices(device_id) ON DELETE CASCADE,
+    scanned_at  TIMESTAMPTZ NOT NULL,
+    command_id  UUID NOT NULL,
+    tool_name   TEXT NOT NULL,
+    dtc_count   INTEGER NOT NULL,
+    PRIMARY KEY (device_id, scanned_at, command_id)
+);
+
+CREATE TABLE IF NOT EXISTS dtc_history (
+    device_id    TEXT NOT NULL REFERENCES devices(device_id) ON DELETE CASCADE,
+    code         TEXT NOT NULL,
+    observed_at  TIMESTAMPTZ NOT NULL,
+    command_id   UUID NOT NULL,
+    severity     TEXT NOT NULL DEFAULT 'unknown',
+    description  TEXT,
+    PRIMARY KEY (device_id, code, observed_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dtc_history_code ON dtc_history (code);
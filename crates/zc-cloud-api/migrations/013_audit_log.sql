@@ -0,0 +1,17 @@
+-- Audit log of operator actions on commands.
+--
+-- Records the two-person approval chain of high-risk commands: the
+-- request, the approval (or cancellation / expiry) and who did it.
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id          BIGSERIAL PRIMARY KEY,
+    at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor       TEXT NOT NULL,
+    action      TEXT NOT NULL,
+    command_id  UUID,
+    device_id   TEXT,
+    detail      JSONB NOT NULL DEFAULT '{}'::jsonb
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_command_id ON audit_log (command_id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log (at DESC);
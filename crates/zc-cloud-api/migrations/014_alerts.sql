@@ -0,0 +1,32 @@
+-- Operator-managed alerts raised by anomaly detectors.
+--
+-- An alert moves open -> acknowledged / snoozed -> resolved; `history`
+-- holds every transition with its actor and comment. At most one
+-- unresolved alert exists per dedup key.
+
+CREATE TABLE IF NOT EXISTS alerts (
+    id               UUID PRIMARY KEY,
+    kind             TEXT NOT NULL,
+    fleet_id         TEXT NOT NULL,
+    device_id        TEXT NOT NULL,
+    scope            TEXT NOT NULL,
+    title            TEXT NOT NULL,
+    detail           JSONB NOT NULL DEFAULT '{}'::jsonb,
+    state            TEXT NOT NULL DEFAULT 'open',
+    occurrences      BIGINT NOT NULL DEFAULT 1,
+    first_seen_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_seen_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    acknowledged_by  TEXT,
+    acknowledged_at  TIMESTAMPTZ,
+    snoozed_until    TIMESTAMPTZ,
+    resolved_by      TEXT,
+    resolved_at      TIMESTAMPTZ,
+    updated_at       TIMESTAMPTZ NOT NULL DEFAULT now(),
+    history          JSONB NOT NULL DEFAULT '[]'::jsonb,
+    dedup_key        TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_alerts_dedup_unresolved
+    ON alerts (dedup_key) WHERE state <> 'resolved';
+CREATE INDEX IF NOT EXISTS idx_alerts_fleet_state ON alerts (fleet_id, state);
+CREATE INDEX IF NOT EXISTS idx_alerts_last_seen ON alerts (last_seen_at DESC);
@@ -0,0 +1,41 @@
+-- Recurring commands dispatched by the cloud scheduler.
+--
+-- A schedule targets one device (`device_id`) or a whole fleet, optionally
+-- narrowed by tags. `next_run_at` is NULL while the schedule is paused.
+-- Every firing is recorded in schedule_runs with the commands it created.
+
+CREATE TABLE IF NOT EXISTS schedules (
+    id           UUID PRIMARY KEY,
+    name         TEXT NOT NULL,
+    cron         TEXT NOT NULL,
+    fleet_id     TEXT NOT NULL,
+    device_id    TEXT,
+    tags         JSONB NOT NULL DEFAULT '[]'::jsonb,
+    command      TEXT NOT NULL DEFAULT '',
+    tool_name    TEXT,
+    tool_args    JSONB,
+    enabled      BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by   TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    next_run_at  TIMESTAMPTZ,
+    last_run_at  TIMESTAMPTZ,
+    run_count    BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedules_next_run ON schedules (next_run_at)
+    WHERE enabled;
+CREATE INDEX IF NOT EXISTS idx_schedules_fleet ON schedules (fleet_id);
+
+CREATE TABLE IF NOT EXISTS schedule_runs (
+    id             UUID PRIMARY KEY,
+    schedule_id    UUID NOT NULL REFERENCES schedules (id) ON DELETE CASCADE,
+    scheduled_for  TIMESTAMPTZ NOT NULL,
+    fired_at       TIMESTAMPTZ NOT NULL,
+    command_ids    UUID[] NOT NULL DEFAULT '{}',
+    broadcast_id   UUID,
+    error          TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedule_runs_schedule
+    ON schedule_runs (schedule_id, fired_at DESC);
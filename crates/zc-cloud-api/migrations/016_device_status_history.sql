@@ -0,0 +1,16 @@
+-- Device status transitions (online / degraded / offline).
+--
+-- Written by the cloud's status evaluator when heartbeats go quiet, by
+-- heartbeats that bring a device back, and by MQTT presence messages.
+
+CREATE TABLE IF NOT EXISTS device_status_history (
+    id           UUID PRIMARY KEY,
+    device_id    TEXT NOT NULL,
+    from_status  TEXT NOT NULL,
+    to_status    TEXT NOT NULL,
+    reason       TEXT NOT NULL,
+    changed_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_status_history_device
+    ON device_status_history (device_id, changed_at DESC);
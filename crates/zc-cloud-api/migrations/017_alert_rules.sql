@@ -0,0 +1,20 @@
+-- Operator-defined alerting rules.
+--
+-- `condition` is the tagged JSON condition (telemetry threshold, DTC
+-- severity or heartbeat gap). A rule covers a fleet, or one device in it
+-- when `device_id` is set. Firings are recorded as alerts (014_alerts.sql).
+
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id           UUID PRIMARY KEY,
+    name         TEXT NOT NULL,
+    fleet_id     TEXT NOT NULL,
+    device_id    TEXT,
+    condition    JSONB NOT NULL,
+    webhook_url  TEXT,
+    enabled      BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by   TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_rules_fleet ON alert_rules (fleet_id);
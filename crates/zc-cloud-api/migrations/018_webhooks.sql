@@ -0,0 +1,37 @@
+-- Outbound webhook sinks and their delivery log.
+--
+-- A webhook subscribes to notification events (`command_failed`,
+-- `device_offline`, `critical_dtc`) for one fleet, or for all fleets when
+-- `fleet_id` is NULL. `format` is `generic` (JSON) or `slack`. Every
+-- notification sent to a webhook is a delivery, retried with backoff until
+-- it is `delivered` or `failed`.
+
+CREATE TABLE IF NOT EXISTS webhooks (
+    id          UUID PRIMARY KEY,
+    name        TEXT NOT NULL,
+    url         TEXT NOT NULL,
+    format      TEXT NOT NULL DEFAULT 'generic',
+    events      TEXT[] NOT NULL,
+    fleet_id    TEXT,
+    enabled     BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by  TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id               UUID PRIMARY KEY,
+    webhook_id       UUID NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event            TEXT NOT NULL,
+    device_id        TEXT NOT NULL,
+    payload          JSONB NOT NULL,
+    status           TEXT NOT NULL DEFAULT 'pending',
+    attempts         INTEGER NOT NULL DEFAULT 0,
+    response_status  INTEGER,
+    last_error       TEXT,
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at       TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook
+    ON webhook_deliveries (webhook_id, created_at DESC);
@@ -0,0 +1,9 @@
+-- Conversations: commands sent with the same conversation_id. The last few
+-- turns of a conversation are passed to inference so follow-ups ("now do
+-- the same on can1") can refer back to them.
+
+ALTER TABLE commands ADD COLUMN IF NOT EXISTS conversation_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_commands_conversation
+    ON commands (conversation_id, created_at DESC)
+    WHERE conversation_id IS NOT NULL;
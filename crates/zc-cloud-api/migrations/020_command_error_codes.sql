@@ -0,0 +1,9 @@
+-- Machine-readable failure class of a command response (`ErrorCode` in
+-- zc-protocol, e.g. `can_timeout`), so the dashboard and alert rules can
+-- branch on it instead of matching error text.
+
+ALTER TABLE commands ADD COLUMN IF NOT EXISTS error_code TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_commands_error_code
+    ON commands (error_code, created_at DESC)
+    WHERE error_code IS NOT NULL;
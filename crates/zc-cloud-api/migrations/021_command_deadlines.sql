@@ -0,0 +1,9 @@
+-- When a pending command is given up on: set as it is published to the
+-- device (timeout_secs plus a grace period); the cloud marks commands
+-- still pending after it as `timeout`.
+
+ALTER TABLE commands ADD COLUMN IF NOT EXISTS deadline_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_commands_pending_deadline
+    ON commands (deadline_at)
+    WHERE status = 'pending';
@@ -0,0 +1,10 @@
+-- Audit trail beyond the approval chain: command dispatches, shell
+-- executions, shadow desired changes and device provisioning, each with
+-- its fleet (for tenant filtering) and outcome.
+
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS fleet_id TEXT;
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS outcome TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_action_at ON audit_log (action, at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_log_device_at ON audit_log (device_id, at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor_at ON audit_log (actor, at DESC);
@@ -0,0 +1,7 @@
+-- Expiry of the client certificate each device connects with, as reported
+-- in its heartbeats, for the expiry check and the device list filter.
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS certificate_expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_devices_certificate_expires_at
+    ON devices (certificate_expires_at) WHERE certificate_expires_at IS NOT NULL;
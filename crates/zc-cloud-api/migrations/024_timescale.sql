@@ -0,0 +1,59 @@
+-- TimescaleDB layout for telemetry and heartbeats (optional).
+--
+-- Only run when TIMESCALEDB_ENABLED is set, after the plain migrations:
+-- converts both tables into hypertables (existing rows are moved into
+-- chunks) and keeps 1-minute and 1-hour rollups of numeric readings as
+-- continuous aggregates, which outlive the raw chunks dropped by the
+-- retention job.
+
+CREATE EXTENSION IF NOT EXISTS timescaledb;
+
+SELECT create_hypertable('telemetry_readings', 'time',
+    chunk_time_interval => INTERVAL '1 day',
+    if_not_exists => TRUE,
+    migrate_data => TRUE);
+
+SELECT create_hypertable('heartbeats', 'received_at',
+    chunk_time_interval => INTERVAL '1 day',
+    if_not_exists => TRUE,
+    migrate_data => TRUE);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS telemetry_1m
+WITH (timescaledb.continuous) AS
+SELECT time_bucket(INTERVAL '1 minute', time) AS bucket,
+       device_id,
+       metric_name,
+       avg(value_numeric) AS avg_value,
+       min(value_numeric) AS min_value,
+       max(value_numeric) AS max_value,
+       count(value_numeric) AS samples
+FROM telemetry_readings
+WHERE value_numeric IS NOT NULL
+GROUP BY bucket, device_id, metric_name
+WITH NO DATA;
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS telemetry_1h
+WITH (timescaledb.continuous) AS
+SELECT time_bucket(INTERVAL '1 hour', time) AS bucket,
+       device_id,
+       metric_name,
+       avg(value_numeric) AS avg_value,
+       min(value_numeric) AS min_value,
+       max(value_numeric) AS max_value,
+       count(value_numeric) AS samples
+FROM telemetry_readings
+WHERE value_numeric IS NOT NULL
+GROUP BY bucket, device_id, metric_name
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('telemetry_1m',
+    start_offset => INTERVAL '1 hour',
+    end_offset => INTERVAL '1 minute',
+    schedule_interval => INTERVAL '1 minute',
+    if_not_exists => TRUE);
+
+SELECT add_continuous_aggregate_policy('telemetry_1h',
+    start_offset => INTERVAL '1 day',
+    end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '1 hour',
+    if_not_exists => TRUE);
@@ -0,0 +1,5 @@
+-- System vitals (CPU load, free memory and disk, temperature, signal
+-- strength) from the latest heartbeat that carried them, shown in device
+-- details.
+
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS vitals JSONB;
@@ -0,0 +1,14 @@
+-- Fleet agent releases available for over-the-air updates.
+--
+-- A release is a downloadable agent binary identified by its version. It
+-- is delivered to devices on the `update` shadow; devices verify `sha256`
+-- before installing and report the outcome on the same shadow.
+
+CREATE TABLE IF NOT EXISTS agent_releases (
+    version     TEXT PRIMARY KEY,
+    url         TEXT NOT NULL,
+    sha256      TEXT NOT NULL,
+    notes       TEXT,
+    created_by  TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,27 @@
+-- Fleets as first-class resources, and their API tokens.
+--
+-- Devices still name their fleet in `metadata.fleet`; once any fleet is
+-- registered here, new devices must be provisioned into a registered one.
+-- A fleet token authenticates as a principal scoped to its fleet. Only the
+-- token's SHA-256 is stored; the secret is shown once, when it is issued.
+
+CREATE TABLE IF NOT EXISTS fleets (
+    fleet_id     TEXT PRIMARY KEY,
+    name         TEXT NOT NULL,
+    description  TEXT,
+    created_by   TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS fleet_tokens (
+    id          UUID PRIMARY KEY,
+    fleet_id    TEXT NOT NULL REFERENCES fleets (fleet_id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    role        TEXT NOT NULL,
+    sha256      TEXT NOT NULL UNIQUE,
+    created_by  TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at  TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_fleet_tokens_fleet ON fleet_tokens (fleet_id);
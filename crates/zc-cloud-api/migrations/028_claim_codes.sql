@@ -0,0 +1,23 @@
+-- One-time claim codes for device onboarding.
+--
+-- An agent without an identity presents a code and its hardware ID on the
+-- bootstrap MQTT topic; the cloud provisions the device into the code's
+-- fleet and marks the code claimed by that hardware. Only the code's
+-- SHA-256 is stored; the code is shown once, when it is created.
+
+CREATE TABLE IF NOT EXISTS claim_codes (
+    id           UUID PRIMARY KEY,
+    sha256       TEXT NOT NULL UNIQUE,
+    fleet_id     TEXT NOT NULL,
+    -- Preassigned device ID, or the one assigned when claimed.
+    device_id    TEXT,
+    tags         JSONB NOT NULL DEFAULT '{}',
+    config       JSONB,
+    created_by   TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at   TIMESTAMPTZ NOT NULL,
+    claimed_at   TIMESTAMPTZ,
+    hardware_id  TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_claim_codes_fleet ON claim_codes (fleet_id, created_at DESC);
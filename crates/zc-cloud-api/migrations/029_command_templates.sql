@@ -0,0 +1,36 @@
+-- Saved multi-step diagnostics ("full health check").
+--
+-- A template is a named list of command steps with `{{name}}` parameters,
+-- shared by every fleet when `fleet_id` is NULL. Each dispatch is recorded
+-- in template_runs with the commands every step created; the report is
+-- assembled from those commands when it is read.
+
+CREATE TABLE IF NOT EXISTS command_templates (
+    id           UUID PRIMARY KEY,
+    name         TEXT NOT NULL,
+    description  TEXT,
+    fleet_id     TEXT,
+    parameters   JSONB NOT NULL DEFAULT '[]'::jsonb,
+    steps        JSONB NOT NULL,
+    created_by   TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_command_templates_name
+    ON command_templates (COALESCE(fleet_id, ''), lower(name));
+
+CREATE TABLE IF NOT EXISTS template_runs (
+    id             UUID PRIMARY KEY,
+    template_id    UUID NOT NULL REFERENCES command_templates (id) ON DELETE CASCADE,
+    template_name  TEXT NOT NULL,
+    fleet_id       TEXT NOT NULL,
+    device_id      TEXT,
+    tags           JSONB NOT NULL DEFAULT '[]'::jsonb,
+    params         JSONB NOT NULL DEFAULT '{}'::jsonb,
+    steps          JSONB NOT NULL,
+    initiated_by   TEXT NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_template_runs_template
+    ON template_runs (template_id, created_at DESC);
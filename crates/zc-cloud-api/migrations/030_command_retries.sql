@@ -0,0 +1,14 @@
+-- Automatic retries of command publishes that fail.
+--
+-- `attempts` counts publishes to the device, `max_attempts` is the limit
+-- the command was created with. While a failed publish waits for its
+-- retry, `next_attempt_at` is set and `deadline_at` is cleared so the
+-- command does not time out before it was delivered.
+
+ALTER TABLE commands
+    ADD COLUMN IF NOT EXISTS attempts INT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS max_attempts INT NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS next_attempt_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_commands_next_attempt
+    ON commands (next_attempt_at) WHERE next_attempt_at IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Idempotency keys for command submission.
+--
+-- A client that retries `POST /commands` with the same `Idempotency-Key`
+-- gets the command created by the first request instead of a second one.
+-- Keys are scoped to the initiator and last as long as the command.
+
+ALTER TABLE commands
+    ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_commands_idempotency_key
+    ON commands (initiated_by, idempotency_key) WHERE idempotency_key IS NOT NULL;
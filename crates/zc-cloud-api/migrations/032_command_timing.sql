@@ -0,0 +1,15 @@
+-- Command timing without mixing the cloud and device clocks.
+--
+-- `published_at` and `received_at` are cloud times: the successful publish
+-- to the device and the arrival of its response. `execution_ms` is the
+-- device's own measurement and `device_responded_at` its wall-clock stamp,
+-- read through `clock_offset_ms` (device minus cloud, estimated from
+-- heartbeats when the response arrived). `latency_ms` is now
+-- `received_at - created_at` on the cloud clock.
+
+ALTER TABLE commands
+    ADD COLUMN IF NOT EXISTS published_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS received_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS device_responded_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS execution_ms BIGINT,
+    ADD COLUMN IF NOT EXISTS clock_offset_ms BIGINT;
@@ -0,0 +1,164 @@
+//! Fleet agent releases for over-the-air updates.
+//!
+//! A release names an agent version, the URL of its binary and the binary's
+//! SHA-256. Deploying it sets the desired state of each target device's
+//! `update` shadow ([`UPDATE_SHADOW`]) to the release, so delivery, retries
+//! and devices that were offline are handled by shadow reconciliation like
+//! any other desired state.
+//!
+//! The agent downloads and verifies the binary, restarts into it and
+//! reports the outcome on the same shadow ([`UpdateReport`]). The report
+//! echoes the release whatever the outcome, so the delta clears and a
+//! failed release is not redelivered; [`report_received`] raises an
+//! `agent_update_failed` alert for `failed` and `rolled_back` reports.
+//!
+//! Releases are kept in the [`AgentReleaseStore`] and cannot be changed
+//! once registered: a fixed binary is a new version.
+//!
+//! [`AgentReleaseStore`]: crate::store::AgentReleaseStore
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use zc_protocol::shadows::{AgentUpdate, UPDATE_SHADOW, UpdateReport, UpdateStatus};
+
+use crate::alerts::NewAlert;
+use crate::state::AppState;
+
+/// Kind of alerts raised for updates that failed or were rolled back.
+pub const KIND_AGENT_UPDATE_FAILED: &str = "agent_update_failed";
+
+/// A registered agent release.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgentRelease {
+    pub version: String,
+    /// http(s) URL of the agent binary.
+    pub url: String,
+    /// Lowercase hex SHA-256 of the binary.
+    pub sha256: String,
+    pub notes: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AgentRelease {
+    /// Desired state of the `update` shadow that installs this release.
+    pub fn update(&self) -> AgentUpdate {
+        AgentUpdate {
+            agent_version: self.version.clone(),
+            url: self.url.clone(),
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+/// A SHA-256 digest as 64 lowercase hex digits.
+pub fn normalize_sha256(raw: &str) -> Result<String, String> {
+    let digest = raw.trim().to_ascii_lowercase();
+    if digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(digest)
+    } else {
+        Err("sha256 must be 64 hex digits".into())
+    }
+}
+
+/// Act on a device's report on the [`UPDATE_SHADOW`]: raise an alert when
+/// an update failed or was rolled back. Reports this version does not
+/// understand are ignored.
+pub async fn report_received(
+    state: &AppState,
+    fleet_id: &str,
+    device_id: &str,
+    reported: &serde_json::Value,
+) {
+    let Ok(report) = serde_json::from_value::<UpdateReport>(reported.clone()) else {
+        return;
+    };
+    let outcome = match report.status {
+        UpdateStatus::Installed => {
+            tracing::info!(device_id, version = %report.agent_version, "agent update installed");
+            return;
+        }
+        UpdateStatus::Failed => "failed",
+        UpdateStatus::RolledBack => "was rolled back",
+    };
+    tracing::warn!(
+        device_id,
+        version = %report.agent_version,
+        error = ?report.error,
+        "agent update {outcome}"
+    );
+    let new = NewAlert {
+        kind: KIND_AGENT_UPDATE_FAILED.into(),
+        fleet_id: fleet_id.to_string(),
+        device_id: device_id.to_string(),
+        scope: "device".into(),
+        title: format!(
+            "Agent update to {} {outcome} on {device_id}",
+            report.agent_version
+        ),
+        detail: serde_json::json!({
+            "shadow_name": UPDATE_SHADOW,
+            "agent_version": report.agent_version,
+            "status": report.status,
+            "error": report.error,
+        }),
+        dedup_key: format!(
+            "{KIND_AGENT_UPDATE_FAILED}:{fleet_id}:{device_id}:{}",
+            report.agent_version
+        ),
+    };
+    crate::alerts::raise(state, new).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_is_normalized() {
+        let digest = "AB".repeat(32);
+        assert_eq!(normalize_sha256(&digest), Ok("ab".repeat(32)));
+        assert!(normalize_sha256("abc").is_err());
+        assert!(normalize_sha256(&"zz".repeat(32)).is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_reports_raise_one_alert_per_release() {
+        let state = AppState::with_sample_data();
+        let report = |version: &str, status: &str| {
+            serde_json::json!({
+                "agent_version": version,
+                "url": "https://releases.example.com/agent",
+                "sha256": "ab".repeat(32),
+                "status": status,
+                "error": "checksum mismatch",
+            })
+        };
+
+        report_received(
+            &state,
+            "fleet-alpha",
+            "rpi-001",
+            &report("0.3.0", "installed"),
+        )
+        .await;
+        assert!(state.alerts.read().await.is_empty());
+
+        report_received(&state, "fleet-alpha", "rpi-001", &report("0.3.1", "failed")).await;
+        report_received(
+            &state,
+            "fleet-alpha",
+            "rpi-001",
+            &report("0.3.1", "rolled_back"),
+        )
+        .await;
+        report_received(&state, "fleet-alpha", "rpi-001", &report("0.3.2", "failed")).await;
+
+        let alerts = state.alerts.read().await;
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].kind, KIND_AGENT_UPDATE_FAILED);
+        assert_eq!(alerts[0].occurrences, 2);
+        assert_eq!(alerts[0].detail["status"], "rolled_back");
+    }
+}
@@ -0,0 +1,935 @@
+//! Operator-defined alerting rules.
+//!
+//! A rule watches a fleet (or one device in it) for a condition and raises
+//! an alert ([`crate::alerts`]) when the condition starts to hold for a
+//! device:
+//!
+//! - `telemetry`: a numeric reading of `metric` compared with `threshold`
+//!   (`gt`, `gte`, `lt`, `lte`), checked as telemetry is ingested over REST
+//!   or MQTT;
+//! - `dtc_severity`: a DTC scan reporting a code of at least
+//!   `min_severity`, checked as scans are recorded;
+//! - `heartbeat_gap`: no heartbeat for `seconds`, checked by [`run`] every
+//!   [`TICK_SECS`];
+//! - `command_error`: a command response with one of the error `codes`
+//!   (any code when empty), checked as responses are ingested.
+//!
+//! Rules are edge-triggered per device: a rule fires when its condition
+//! starts to hold and not again until it has stopped holding (a reading
+//! back within bounds, a scan without such codes, a fresh heartbeat, a
+//! response without such an error). Each
+//! firing goes through [`crate::alerts::raise`] under a per-rule,
+//! per-device key, so a repeat while the alert is unresolved bumps its
+//! occurrences instead of opening another. A rule with a `webhook_url` also
+//! POSTs the alert there, without retries.
+//!
+//! Rules are stored like schedules (database or memory) and cached in the
+//! [`RuleEngine`] so that ingest never queries them. In database mode the
+//! cache is reloaded after every change and on every tick, which picks up
+//! rules changed through other replicas. Firing state is per process.
+
+use std::collections::HashSet;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use zc_protocol::commands::{CommandResponse, ErrorCode};
+use zc_protocol::device::DeviceStatus;
+use zc_protocol::dtc::DtcSeverity;
+
+use crate::alerts::{Alert, NewAlert};
+use crate::db::telemetry::TelemetryRow;
+use crate::dtc_history::DtcScan;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// How often heartbeat-gap rules are checked (and, in database mode, the
+/// rule cache reloaded).
+pub const TICK_SECS: u64 = 30;
+/// Kind of alerts raised by rules.
+pub const KIND_RULE: &str = "rule";
+/// Timeout for webhook deliveries.
+pub const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+/// How a telemetry value is compared with a rule's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Comparison {
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Gt => value > threshold,
+            Self::Gte => value >= threshold,
+            Self::Lt => value < threshold,
+            Self::Lte => value <= threshold,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Gt => ">",
+            Self::Gte => ">=",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+        }
+    }
+}
+
+/// What a rule watches for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// A numeric reading of `metric` compared with `threshold`.
+    Telemetry {
+        metric: String,
+        op: Comparison,
+        threshold: f64,
+    },
+    /// A DTC scan with a code of at least this severity.
+    DtcSeverity { min_severity: DtcSeverity },
+    /// No heartbeat for this many seconds.
+    HeartbeatGap { seconds: u64 },
+    /// A command response with one of these error codes; any code when empty.
+    CommandError {
+        #[serde(default)]
+        codes: Vec<ErrorCode>,
+    },
+}
+
+impl RuleCondition {
+    /// Reject conditions that can never be evaluated.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Telemetry {
+                metric, threshold, ..
+            } => {
+                if metric.trim().is_empty() {
+                    return Err("telemetry rules need a metric".into());
+                }
+                if !threshold.is_finite() {
+                    return Err("threshold must be a finite number".into());
+                }
+            }
+            Self::DtcSeverity { min_severity } => {
+                if *min_severity == DtcSeverity::Unknown {
+                    return Err("min_severity must be info, warning or critical".into());
+                }
+            }
+            Self::HeartbeatGap { seconds } => {
+                if *seconds == 0 {
+                    return Err("heartbeat gap must be at least 1 second".into());
+                }
+            }
+            Self::CommandError { codes } => {
+                if codes.contains(&ErrorCode::Unknown) {
+                    return Err("unknown error code".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Short human-readable form, used in alert titles.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Telemetry {
+                metric,
+                op,
+                threshold,
+            } => format!("{metric} {} {threshold}", op.symbol()),
+            Self::DtcSeverity { min_severity } => {
+                format!("DTC of severity {}+", severity_name(*min_severity))
+            }
+            Self::HeartbeatGap { seconds } => format!("no heartbeat for {seconds}s"),
+            Self::CommandError { codes } if codes.is_empty() => "command error".into(),
+            Self::CommandError { codes } => {
+                let names: Vec<&str> = codes.iter().map(|c| c.as_str()).collect();
+                format!("command error {}", names.join(" or "))
+            }
+        }
+    }
+}
+
+/// Severity order; unknown severities rank below everything.
+fn severity_rank(severity: DtcSeverity) -> u8 {
+    match severity {
+        DtcSeverity::Unknown => 0,
+        DtcSeverity::Info => 1,
+        DtcSeverity::Warning => 2,
+        DtcSeverity::Critical => 3,
+    }
+}
+
+fn parse_severity(name: &str) -> DtcSeverity {
+    match name {
+        "info" => DtcSeverity::Info,
+        "warning" => DtcSeverity::Warning,
+        "critical" => DtcSeverity::Critical,
+        _ => DtcSeverity::Unknown,
+    }
+}
+
+fn severity_name(severity: DtcSeverity) -> &'static str {
+    match severity {
+        DtcSeverity::Info => "info",
+        DtcSeverity::Warning => "warning",
+        DtcSeverity::Critical => "critical",
+        DtcSeverity::Unknown => "unknown",
+    }
+}
+
+/// An alerting rule.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertRule {
+    pub id: Uuid,
+    pub name: String,
+    pub fleet_id: String,
+    /// Single watched device; the whole fleet when `None`.
+    pub device_id: Option<String>,
+    pub condition: RuleCondition,
+    /// Where each firing is POSTed, if anywhere.
+    pub webhook_url: Option<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AlertRule {
+    /// Whether the rule watches `device_id` in `fleet_id`.
+    pub fn covers(&self, fleet_id: &str, device_id: &str) -> bool {
+        self.enabled
+            && self.fleet_id == fleet_id
+            && self.device_id.as_deref().is_none_or(|d| d == device_id)
+    }
+}
+
+/// Filters for listing rules.
+#[derive(Debug, Clone, Default)]
+pub struct RuleFilter {
+    pub fleet_id: Option<String>,
+    pub device_id: Option<String>,
+    pub enabled: Option<bool>,
+    /// Only these fleets (the caller's tenants); `None` for all.
+    pub fleets: Option<Vec<String>>,
+}
+
+impl RuleFilter {
+    pub fn matches(&self, rule: &AlertRule) -> bool {
+        self.fleet_id.as_deref().is_none_or(|f| rule.fleet_id == f)
+            && self
+                .device_id
+                .as_deref()
+                .is_none_or(|d| rule.device_id.as_deref() == Some(d))
+            && self.enabled.is_none_or(|e| rule.enabled == e)
+            && self
+                .fleets
+                .as_ref()
+                .is_none_or(|fleets| fleets.contains(&rule.fleet_id))
+    }
+}
+
+/// Cached rules and which (rule, device) pairs are currently firing.
+pub struct RuleEngine {
+    /// All rules; the store itself in memory mode.
+    rules: RwLock<Vec<AlertRule>>,
+    firing: Mutex<HashSet<(Uuid, String)>>,
+    http: reqwest::Client,
+}
+
+impl Default for RuleEngine {
+    fn default() -> Self {
+        Self {
+            rules: RwLock::new(Vec::new()),
+            firing: Mutex::new(HashSet::new()),
+            http: reqwest::Client::builder()
+                .timeout(StdDuration::from_secs(WEBHOOK_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl RuleEngine {
+    /// Enabled rules whose condition `pick` accepts.
+    fn matching(&self, pick: impl Fn(&RuleCondition) -> bool) -> Vec<AlertRule> {
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|r| r.enabled && pick(&r.condition))
+            .cloned()
+            .collect()
+    }
+
+    fn replace(&self, rules: Vec<AlertRule>) {
+        let ids: HashSet<Uuid> = rules.iter().map(|r| r.id).collect();
+        *self.rules.write().unwrap() = rules;
+        self.firing
+            .lock()
+            .unwrap()
+            .retain(|(rule_id, _)| ids.contains(rule_id));
+    }
+
+    /// Record whether the rule holds for the device; `true` when it just
+    /// started to.
+    fn observe(&self, rule_id: Uuid, device_id: &str, holds: bool) -> bool {
+        let mut firing = self.firing.lock().unwrap();
+        if holds {
+            firing.insert((rule_id, device_id.to_string()))
+        } else {
+            firing.remove(&(rule_id, device_id.to_string()));
+            false
+        }
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+fn not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("alert rule {id} not found"))
+}
+
+/// Reload the rule cache from the database (no-op in memory mode).
+pub async fn reload(state: &AppState) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::alert_rules::list(pool, &RuleFilter::default())
+            .await
+            .map_err(internal)?;
+        state.alert_rules.replace(
+            rows.into_iter()
+                .filter_map(crate::db::alert_rules::AlertRuleRow::into_rule)
+                .collect(),
+        );
+    }
+    Ok(())
+}
+
+/// Store a new rule.
+pub async fn create(state: &AppState, rule: &AlertRule) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        crate::db::alert_rules::insert(pool, rule)
+            .await
+            .map_err(internal)?;
+        reload(state).await?;
+    } else {
+        state.alert_rules.rules.write().unwrap().push(rule.clone());
+    }
+    tracing::info!(
+        rule_id = %rule.id,
+        name = %rule.name,
+        fleet_id = %rule.fleet_id,
+        device_id = ?rule.device_id,
+        condition = %rule.condition.describe(),
+        "alert rule created"
+    );
+    Ok(())
+}
+
+/// Rules matching `filter`, oldest first.
+pub async fn list(state: &AppState, filter: &RuleFilter) -> ApiResult<Vec<AlertRule>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::alert_rules::list(pool, filter)
+            .await
+            .map_err(internal)?;
+        return Ok(rows
+            .into_iter()
+            .filter_map(crate::db::alert_rules::AlertRuleRow::into_rule)
+            .collect());
+    }
+    let rules = state.alert_rules.rules.read().unwrap();
+    Ok(rules
+        .iter()
+        .filter(|r| filter.matches(r))
+        .cloned()
+        .collect())
+}
+
+/// A rule by ID.
+pub async fn get(state: &AppState, id: Uuid) -> ApiResult<AlertRule> {
+    let rule = if let Some(pool) = &state.pool {
+        crate::db::alert_rules::get(pool, id)
+            .await
+            .map_err(internal)?
+            .and_then(crate::db::alert_rules::AlertRuleRow::into_rule)
+    } else {
+        state
+            .alert_rules
+            .rules
+            .read()
+            .unwrap()
+            .iter()
+            .find(|r| r.id == id)
+            .cloned()
+    };
+    rule.ok_or_else(|| not_found(id))
+}
+
+/// Enable or disable a rule. Disabling forgets what it was firing for.
+pub async fn set_enabled(state: &AppState, id: Uuid, enabled: bool) -> ApiResult<AlertRule> {
+    let mut rule = get(state, id).await?;
+    rule.enabled = enabled;
+    rule.updated_at = Utc::now();
+    if let Some(pool) = &state.pool {
+        crate::db::alert_rules::set_enabled(pool, &rule)
+            .await
+            .map_err(internal)?;
+        reload(state).await?;
+    } else {
+        let mut rules = state.alert_rules.rules.write().unwrap();
+        let stored = rules
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| not_found(id))?;
+        *stored = rule.clone();
+    }
+    if !enabled {
+        state
+            .alert_rules
+            .firing
+            .lock()
+            .unwrap()
+            .retain(|(rule_id, _)| *rule_id != id);
+    }
+    tracing::info!(rule_id = %id, enabled, "alert rule updated");
+    Ok(rule)
+}
+
+/// Delete a rule. Alerts it raised are kept.
+pub async fn delete(state: &AppState, id: Uuid) -> ApiResult<()> {
+    let deleted = if let Some(pool) = &state.pool {
+        let deleted = crate::db::alert_rules::delete(pool, id)
+            .await
+            .map_err(internal)?;
+        reload(state).await?;
+        deleted
+    } else {
+        let mut rules = state.alert_rules.rules.write().unwrap();
+        let before = rules.len();
+        rules.retain(|r| r.id != id);
+        rules.len() != before
+    };
+    if !deleted {
+        return Err(not_found(id));
+    }
+    state
+        .alert_rules
+        .firing
+        .lock()
+        .unwrap()
+        .retain(|(rule_id, _)| *rule_id != id);
+    tracing::info!(rule_id = %id, "alert rule deleted");
+    Ok(())
+}
+
+/// Check telemetry rules against a batch of a device's readings. `fleet_id`
+/// is looked up from the device when not known.
+pub async fn telemetry_ingested(
+    state: &AppState,
+    fleet_id: Option<&str>,
+    device_id: &str,
+    rows: &[TelemetryRow],
+) {
+    let rules = state
+        .alert_rules
+        .matching(|c| matches!(c, RuleCondition::Telemetry { .. }));
+    if rules.is_empty() {
+        return;
+    }
+    let Some(fleet_id) = resolve_fleet(state, fleet_id, device_id).await else {
+        return;
+    };
+    for rule in rules.iter().filter(|r| r.covers(&fleet_id, device_id)) {
+        let RuleCondition::Telemetry {
+            metric,
+            op,
+            threshold,
+        } = &rule.condition
+        else {
+            continue;
+        };
+        // Scoped so the iterator is not held across the `fire` await.
+        let crossing = {
+            let mut values = rows
+                .iter()
+                .filter(|r| r.metric_name == *metric)
+                .filter_map(|r| r.value_numeric.map(|v| (r, v)))
+                .peekable();
+            // Only readings of the metric say anything about the rule.
+            if values.peek().is_none() {
+                continue;
+            }
+            values.find(|(_, v)| op.holds(*v, *threshold))
+        };
+        if state
+            .alert_rules
+            .observe(rule.id, device_id, crossing.is_some())
+            && let Some((row, value)) = crossing
+        {
+            let observed = serde_json::json!({
+                "metric": metric,
+                "value": value,
+                "unit": row.unit,
+                "time": row.time,
+            });
+            fire(state, rule, &fleet_id, device_id, observed).await;
+        }
+    }
+}
+
+/// Check DTC severity rules against a recorded scan.
+pub async fn dtc_scanned(state: &AppState, fleet_id: &str, device_id: &str, scan: &DtcScan) {
+    let rules = state
+        .alert_rules
+        .matching(|c| matches!(c, RuleCondition::DtcSeverity { .. }));
+    for rule in rules.iter().filter(|r| r.covers(fleet_id, device_id)) {
+        let RuleCondition::DtcSeverity { min_severity } = rule.condition else {
+            continue;
+        };
+        let codes: Vec<&str> = scan
+            .dtcs
+            .iter()
+            .filter(|d| severity_rank(parse_severity(&d.severity)) >= severity_rank(min_severity))
+            .map(|d| d.code.as_str())
+            .collect();
+        if state
+            .alert_rules
+            .observe(rule.id, device_id, !codes.is_empty())
+        {
+            let observed = serde_json::json!({
+                "codes": codes,
+                "command_id": scan.command_id,
+                "scanned_at": scan.scanned_at,
+            });
+            fire(state, rule, fleet_id, device_id, observed).await;
+        }
+    }
+}
+
+/// Check command-error rules against a response from `fleet_id`.
+pub async fn command_responded(state: &AppState, fleet_id: &str, resp: &CommandResponse) {
+    let rules = state
+        .alert_rules
+        .matching(|c| matches!(c, RuleCondition::CommandError { .. }));
+    for rule in rules.iter().filter(|r| r.covers(fleet_id, &resp.device_id)) {
+        let RuleCondition::CommandError { codes } = &rule.condition else {
+            continue;
+        };
+        let holds = resp
+            .error_code
+            .is_some_and(|code| codes.is_empty() || codes.contains(&code));
+        if state.alert_rules.observe(rule.id, &resp.device_id, holds) {
+            let observed = serde_json::json!({
+                "error_code": resp.error_code,
+                "error": resp.error,
+                "command_id": resp.command_id,
+                "responded_at": resp.responded_at,
+            });
+            fire(state, rule, fleet_id, &resp.device_id, observed).await;
+        }
+    }
+}
+
+/// Check heartbeat-gap rules at `now`; returns how many fired.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<usize> {
+    reload(state).await?;
+    let rules = state
+        .alert_rules
+        .matching(|c| matches!(c, RuleCondition::HeartbeatGap { .. }));
+    let Some(shortest) = rules
+        .iter()
+        .filter_map(|r| match r.condition {
+            RuleCondition::HeartbeatGap { seconds } => Some(seconds),
+            _ => None,
+        })
+        .min()
+    else {
+        return Ok(0);
+    };
+
+    let cutoff = now - Duration::seconds(shortest as i64);
+    let silent: Vec<(String, Option<String>, DateTime<Utc>)> = if let Some(pool) = &state.pool {
+        crate::db::devices::list_heartbeat_gaps(pool, cutoff)
+            .await
+            .map_err(internal)?
+    } else {
+        state
+            .devices
+            .read()
+            .await
+            .values()
+            .filter(|d| {
+                matches!(
+                    d.status,
+                    DeviceStatus::Online | DeviceStatus::Degraded | DeviceStatus::Offline
+                )
+            })
+            .filter_map(|d| {
+                let last = d.last_heartbeat.filter(|t| *t <= cutoff)?;
+                let fleet = d.metadata.get("fleet")?.as_str().map(String::from);
+                Some((d.device_id.clone(), fleet, last))
+            })
+            .collect()
+    };
+
+    let mut holding = HashSet::new();
+    let mut fired = 0;
+    for rule in &rules {
+        let RuleCondition::HeartbeatGap { seconds } = rule.condition else {
+            continue;
+        };
+        for (device_id, fleet, last) in &silent {
+            let Some(fleet) = fleet else { continue };
+            let silent_secs = (now - *last).num_seconds();
+            if !rule.covers(fleet, device_id) || silent_secs < seconds as i64 {
+                continue;
+            }
+            holding.insert((rule.id, device_id.clone()));
+            if state.alert_rules.observe(rule.id, device_id, true) {
+                let observed = serde_json::json!({
+                    "last_heartbeat": last,
+                    "silent_secs": silent_secs,
+                });
+                fire(state, rule, fleet, device_id, observed).await;
+                fired += 1;
+            }
+        }
+    }
+    // Devices heard from again stop firing.
+    let gap_rules: HashSet<Uuid> = rules.iter().map(|r| r.id).collect();
+    state
+        .alert_rules
+        .firing
+        .lock()
+        .unwrap()
+        .retain(|key| !gap_rules.contains(&key.0) || holding.contains(key));
+    Ok(fired)
+}
+
+/// Check heartbeat gaps every [`TICK_SECS`], forever. The first tick is
+/// immediate and loads the rules.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state, Utc::now()).await {
+            tracing::error!(error = %e, "alert rule tick failed");
+        }
+    }
+}
+
+async fn resolve_fleet(state: &AppState, known: Option<&str>, device_id: &str) -> Option<String> {
+    match known {
+        Some(fleet_id) => Some(fleet_id.to_string()),
+        None => crate::auth::device_fleet(state, device_id).await,
+    }
+}
+
+/// Raise the rule's alert for a device and deliver its webhook.
+async fn fire(
+    state: &AppState,
+    rule: &AlertRule,
+    fleet_id: &str,
+    device_id: &str,
+    observed: serde_json::Value,
+) {
+    tracing::info!(
+        rule_id = %rule.id,
+        name = %rule.name,
+        device_id,
+        condition = %rule.condition.describe(),
+        "alert rule fired"
+    );
+    let new = NewAlert {
+        kind: KIND_RULE.into(),
+        fleet_id: fleet_id.to_string(),
+        device_id: device_id.to_string(),
+        scope: "device".into(),
+        title: format!(
+            "{}: {} on {device_id}",
+            rule.name,
+            rule.condition.describe()
+        ),
+        detail: serde_json::json!({
+            "rule_id": rule.id,
+            "rule_name": rule.name,
+            "condition": rule.condition,
+            "observed": observed,
+        }),
+        dedup_key: format!("{KIND_RULE}:{}:{device_id}", rule.id),
+    };
+    let Some(alert) = crate::alerts::raise(state, new).await else {
+        return;
+    };
+    if let Some(url) = &rule.webhook_url {
+        tokio::spawn(deliver_webhook(
+            state.alert_rules.http.clone(),
+            url.clone(),
+            rule.clone(),
+            alert,
+        ));
+    }
+}
+
+/// POST `{"rule": ..., "alert": ...}` to a rule's webhook. Failures are
+/// logged only.
+async fn deliver_webhook(http: reqwest::Client, url: String, rule: AlertRule, alert: Alert) {
+    let body = serde_json::json!({ "rule": rule, "alert": alert });
+    match http
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+    {
+        Ok(_) => tracing::debug!(rule_id = %rule.id, url = %url, "alert webhook delivered"),
+        Err(e) => {
+            tracing::warn!(error = %e, rule_id = %rule.id, url = %url, "alert webhook failed")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertFilter;
+    use crate::dtc_history::ObservedDtc;
+
+    fn rule(condition: RuleCondition) -> AlertRule {
+        let now = Utc::now();
+        AlertRule {
+            id: Uuid::now_v7(),
+            name: "test rule".into(),
+            fleet_id: "fleet-alpha".into(),
+            device_id: None,
+            condition,
+            webhook_url: None,
+            enabled: true,
+            created_by: "admin".into(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn reading(metric: &str, value: f64) -> TelemetryRow {
+        TelemetryRow {
+            time: Utc::now(),
+            device_id: "rpi-001".into(),
+            metric_name: metric.into(),
+            value_numeric: Some(value),
+            value_text: None,
+            value_json: None,
+            unit: Some("C".into()),
+            source: "obd2".into(),
+        }
+    }
+
+    async fn alerts(state: &AppState) -> Vec<Alert> {
+        let filter = AlertFilter {
+            limit: 50,
+            ..Default::default()
+        };
+        crate::alerts::list(state, &filter).await.unwrap().0
+    }
+
+    #[test]
+    fn conditions_validate_and_describe() {
+        let hot = RuleCondition::Telemetry {
+            metric: "coolant_temp".into(),
+            op: Comparison::Gt,
+            threshold: 110.0,
+        };
+        assert!(hot.validate().is_ok());
+        assert_eq!(hot.describe(), "coolant_temp > 110");
+        assert!(
+            RuleCondition::HeartbeatGap { seconds: 0 }
+                .validate()
+                .is_err()
+        );
+        let condition: RuleCondition =
+            serde_json::from_str(r#"{"type":"dtc_severity","min_severity":"warning"}"#).unwrap();
+        assert_eq!(
+            condition,
+            RuleCondition::DtcSeverity {
+                min_severity: DtcSeverity::Warning
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn telemetry_rule_fires_once_per_crossing() {
+        let state = AppState::with_sample_data();
+        let hot = rule(RuleCondition::Telemetry {
+            metric: "coolant_temp".into(),
+            op: Comparison::Gt,
+            threshold: 110.0,
+        });
+        create(&state, &hot).await.unwrap();
+
+        let ingest = |value| {
+            let state = state.clone();
+            async move {
+                telemetry_ingested(&state, None, "rpi-001", &[reading("coolant_temp", value)]).await
+            }
+        };
+        ingest(95.0).await;
+        assert!(alerts(&state).await.is_empty());
+
+        ingest(115.0).await;
+        ingest(118.0).await;
+        let raised = alerts(&state).await;
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].kind, KIND_RULE);
+        assert_eq!(raised[0].occurrences, 1);
+        assert_eq!(raised[0].detail["observed"]["value"], 115.0);
+
+        // Back in bounds, then over again: the same alert reoccurs.
+        ingest(100.0).await;
+        ingest(120.0).await;
+        assert_eq!(alerts(&state).await[0].occurrences, 2);
+
+        // Other metrics and fleets are not watched.
+        telemetry_ingested(&state, None, "sbc-010", &[reading("coolant_temp", 130.0)]).await;
+        assert_eq!(alerts(&state).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dtc_and_heartbeat_gap_rules() {
+        let state = AppState::with_sample_data();
+        create(
+            &state,
+            &rule(RuleCondition::DtcSeverity {
+                min_severity: DtcSeverity::Critical,
+            }),
+        )
+        .await
+        .unwrap();
+        let gap = rule(RuleCondition::HeartbeatGap { seconds: 300 });
+        create(&state, &gap).await.unwrap();
+
+        let scan = |severity: &str| DtcScan {
+            command_id: Uuid::now_v7(),
+            tool_name: "read_dtcs".into(),
+            scanned_at: Utc::now(),
+            dtcs: vec![ObservedDtc {
+                code: "P0300".into(),
+                severity: severity.into(),
+                description: None,
+            }],
+        };
+        dtc_scanned(&state, "fleet-alpha", "rpi-001", &scan("warning")).await;
+        assert!(alerts(&state).await.is_empty());
+        dtc_scanned(&state, "fleet-alpha", "rpi-001", &scan("critical")).await;
+        assert_eq!(
+            alerts(&state).await[0].detail["observed"]["codes"][0],
+            "P0300"
+        );
+
+        let now = Utc::now();
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .last_heartbeat = Some(now - Duration::minutes(10));
+        assert_eq!(tick(&state, now).await.unwrap(), 1);
+        assert_eq!(tick(&state, now).await.unwrap(), 0);
+        let raised = alerts(&state).await;
+        assert_eq!(raised.len(), 2);
+        assert!(raised.iter().any(|a| a.device_id == "rpi-002"));
+
+        // A heartbeat ends the gap; the next silence fires again.
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .last_heartbeat = Some(now);
+        assert_eq!(tick(&state, now).await.unwrap(), 0);
+        let later = now + Duration::minutes(6);
+        assert_eq!(tick(&state, later).await.unwrap(), 2);
+
+        set_enabled(&state, gap.id, false).await.unwrap();
+        assert_eq!(tick(&state, later).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn command_error_rule_matches_codes() {
+        let state = AppState::with_sample_data();
+        let condition: RuleCondition =
+            serde_json::from_str(r#"{"type":"command_error","codes":["can_timeout"]}"#).unwrap();
+        assert_eq!(condition.describe(), "command error can_timeout");
+        create(&state, &rule(condition)).await.unwrap();
+
+        let response = |code: Option<ErrorCode>| CommandResponse {
+            command_id: Uuid::now_v7(),
+            correlation_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            status: zc_protocol::commands::CommandStatus::Failed,
+            inference_tier: zc_protocol::commands::InferenceTier::Local,
+            response_text: None,
+            response_data: None,
+            latency_ms: 1000,
+            responded_at: Utc::now(),
+            error: code.map(|c| c.to_string()),
+            error_code: code,
+            cached: false,
+        };
+        command_responded(
+            &state,
+            "fleet-alpha",
+            &response(Some(ErrorCode::ShellBlocked)),
+        )
+        .await;
+        assert!(alerts(&state).await.is_empty());
+
+        command_responded(
+            &state,
+            "fleet-alpha",
+            &response(Some(ErrorCode::CanTimeout)),
+        )
+        .await;
+        command_responded(
+            &state,
+            "fleet-alpha",
+            &response(Some(ErrorCode::CanTimeout)),
+        )
+        .await;
+        let raised = alerts(&state).await;
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].occurrences, 1);
+        assert_eq!(raised[0].detail["observed"]["error_code"], "can_timeout");
+
+        // A clean response resets the rule.
+        command_responded(&state, "fleet-alpha", &response(None)).await;
+        command_responded(
+            &state,
+            "fleet-alpha",
+            &response(Some(ErrorCode::CanTimeout)),
+        )
+        .await;
+        assert_eq!(alerts(&state).await[0].occurrences, 2);
+
+        assert!(
+            RuleCondition::CommandError {
+                codes: vec![ErrorCode::Unknown]
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,715 @@
+//! Operator-managed alerts.
+//!
+//! Anomaly detectors ([`crate::failure_rates`]), operator-defined rules
+//! ([`crate::alert_rules`]), the certificate expiry check
+//! ([`crate::cert_expiry`]) and agent PID watches raise alerts here. An alert is open until an
+//! operator acknowledges, snoozes or resolves it:
+//!
+//! ```text
+//! open ──acknowledge──▶ acknowledged ──resolve──▶ resolved
+//!   │ ▲                      │
+//!   │ └──(snooze expires)──┐ │ snooze
+//!   └──────snooze────────▶ snoozed
+//! ```
+//!
+//! Every unresolved state can be resolved or snoozed; open and snoozed
+//! alerts can be acknowledged. Resolved alerts are final. Snooze expiry is
+//! evaluated lazily, like the approval window: a snoozed alert past its
+//! `snoozed_until` reads as open.
+//!
+//! Alerts are deduplicated by a key (kind, scope, fleet, device, tool): a
+//! condition that fires again while its alert is unresolved bumps
+//! `occurrences` instead of opening a second alert. Once resolved, the next
+//! firing opens a new one. Every change is persisted and broadcast as
+//! [`WsEvent::AlertRaised`] / [`WsEvent::AlertUpdated`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use zc_protocol::telemetry::ThresholdEvent;
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::failure_rates::FailureRate;
+use crate::state::AppState;
+
+/// Kind of alerts raised by the failure-rate tracker.
+pub const KIND_TOOL_FAILURE_RATE: &str = "tool_failure_rate";
+
+/// Kind of alerts raised when an agent PID watch crosses its threshold.
+pub const KIND_PID_THRESHOLD: &str = "pid_threshold";
+
+/// Where an alert is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Open,
+    Acknowledged,
+    Snoozed,
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Acknowledged => "acknowledged",
+            Self::Snoozed => "snoozed",
+            Self::Resolved => "resolved",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open" => Some(Self::Open),
+            "acknowledged" => Some(Self::Acknowledged),
+            "snoozed" => Some(Self::Snoozed),
+            "resolved" => Some(Self::Resolved),
+            _ => None,
+        }
+    }
+}
+
+/// What happened to an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertAction {
+    Raised,
+    /// The condition fired again while the alert was unresolved.
+    Reoccurred,
+    Acknowledged,
+    Snoozed,
+    Resolved,
+}
+
+impl AlertAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Raised => "raised",
+            Self::Reoccurred => "reoccurred",
+            Self::Acknowledged => "acknowledged",
+            Self::Snoozed => "snoozed",
+            Self::Resolved => "resolved",
+        }
+    }
+}
+
+/// One entry in an alert's history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertTransition {
+    pub at: DateTime<Utc>,
+    /// Operator who acted (`system` for detector events).
+    pub actor: String,
+    pub action: AlertAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<DateTime<Utc>>,
+}
+
+/// An operator action on an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Acknowledge,
+    Resolve,
+    Snooze { until: DateTime<Utc> },
+}
+
+/// A condition raised by a detector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewAlert {
+    pub kind: String,
+    pub fleet_id: String,
+    /// Device whose data raised the alert.
+    pub device_id: String,
+    /// `device` or `fleet`.
+    pub scope: String,
+    pub title: String,
+    /// Detector-specific context, replaced on every occurrence.
+    pub detail: serde_json::Value,
+    pub dedup_key: String,
+}
+
+impl NewAlert {
+    /// Alert for a tool failure rate that crossed its threshold.
+    pub fn tool_failure_rate(device_id: &str, rate: &FailureRate) -> Self {
+        let target = match &rate.device_id {
+            Some(device) => format!("on {device}"),
+            None => format!("across {}", rate.fleet_id),
+        };
+        Self {
+            kind: KIND_TOOL_FAILURE_RATE.into(),
+            fleet_id: rate.fleet_id.clone(),
+            device_id: device_id.to_string(),
+            scope: rate.scope.as_str().into(),
+            title: format!(
+                "{} failing {:.0}% {target} ({}/{})",
+                rate.tool_name,
+                rate.failure_rate * 100.0,
+                rate.failures,
+                rate.samples
+            ),
+            detail: serde_json::to_value(rate).unwrap_or_default(),
+            dedup_key: format!(
+                "{KIND_TOOL_FAILURE_RATE}:{}:{}:{}:{}",
+                rate.scope.as_str(),
+                rate.fleet_id,
+                rate.device_id.as_deref().unwrap_or("*"),
+                rate.tool_name
+            ),
+        }
+    }
+
+    /// Alert for a watched PID that crossed one of its bounds. The watch
+    /// and bound are part of the key, so a flapping value bumps one alert.
+    pub fn pid_threshold(fleet_id: &str, event: &ThresholdEvent) -> Self {
+        let unit = event
+            .unit
+            .as_deref()
+            .map(|u| format!(" {u}"))
+            .unwrap_or_default();
+        Self {
+            kind: KIND_PID_THRESHOLD.into(),
+            fleet_id: fleet_id.to_string(),
+            device_id: event.device_id.clone(),
+            scope: "device".into(),
+            title: format!(
+                "{} {}{unit} {} {} on {}",
+                event.metric_name,
+                event.value,
+                event.bound.as_str(),
+                event.threshold,
+                event.device_id
+            ),
+            detail: serde_json::to_value(event).unwrap_or_default(),
+            dedup_key: format!(
+                "{KIND_PID_THRESHOLD}:{fleet_id}:{}:{}:{}",
+                event.device_id,
+                event.watch_id,
+                event.bound.as_str()
+            ),
+        }
+    }
+}
+
+/// An alert and its full history.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub id: Uuid,
+    pub kind: String,
+    pub fleet_id: String,
+    /// Device whose data last raised the alert.
+    pub device_id: String,
+    pub scope: String,
+    pub title: String,
+    pub detail: serde_json::Value,
+    pub state: AlertState,
+    pub occurrences: u64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub snoozed_until: Option<DateTime<Utc>>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    /// Oldest first.
+    pub history: Vec<AlertTransition>,
+    #[serde(skip)]
+    pub dedup_key: String,
+}
+
+impl Alert {
+    /// A freshly raised, open alert.
+    pub fn raise(new: NewAlert, now: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            kind: new.kind,
+            fleet_id: new.fleet_id,
+            device_id: new.device_id,
+            scope: new.scope,
+            title: new.title,
+            detail: new.detail,
+            state: AlertState::Open,
+            occurrences: 1,
+            first_seen_at: now,
+            last_seen_at: now,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            snoozed_until: None,
+            resolved_by: None,
+            resolved_at: None,
+            updated_at: now,
+            history: vec![AlertTransition {
+                at: now,
+                actor: "system".into(),
+                action: AlertAction::Raised,
+                comment: None,
+                snoozed_until: None,
+            }],
+            dedup_key: new.dedup_key,
+        }
+    }
+
+    /// Wake the alert if its snooze has expired.
+    pub fn refresh(&mut self, now: DateTime<Utc>) {
+        if self.state == AlertState::Snoozed && self.snoozed_until.is_none_or(|t| t <= now) {
+            self.state = AlertState::Open;
+            self.snoozed_until = None;
+        }
+    }
+
+    /// Record another firing of the alert's condition.
+    pub fn reoccur(&mut self, new: NewAlert, now: DateTime<Utc>) {
+        self.refresh(now);
+        self.occurrences += 1;
+        self.last_seen_at = now;
+        self.device_id = new.device_id;
+        self.title = new.title;
+        self.detail = new.detail;
+        self.updated_at = now;
+        self.history.push(AlertTransition {
+            at: now,
+            actor: "system".into(),
+            action: AlertAction::Reoccurred,
+            comment: None,
+            snoozed_until: None,
+        });
+    }
+
+    /// Apply an operator action, or explain why it is not allowed.
+    pub fn apply(
+        &mut self,
+        transition: Transition,
+        actor: &str,
+        comment: Option<String>,
+        now: DateTime<Utc>,
+    ) -> ApiResult<AlertAction> {
+        self.refresh(now);
+        if self.state == AlertState::Resolved {
+            return Err(ApiError::Conflict(format!(
+                "alert {} is already resolved",
+                self.id
+            )));
+        }
+        let mut snoozed_until = None;
+        let action = match transition {
+            Transition::Acknowledge => {
+                if self.state == AlertState::Acknowledged {
+                    return Err(ApiError::Conflict(format!(
+                        "alert {} is already acknowledged",
+                        self.id
+                    )));
+                }
+                self.state = AlertState::Acknowledged;
+                self.snoozed_until = None;
+                self.acknowledged_by = Some(actor.to_string());
+                self.acknowledged_at = Some(now);
+                AlertAction::Acknowledged
+            }
+            Transition::Snooze { until } => {
+                if until <= now {
+                    return Err(ApiError::BadRequest("snooze must end in the future".into()));
+                }
+                self.state = AlertState::Snoozed;
+                self.snoozed_until = Some(until);
+                snoozed_until = Some(until);
+                AlertAction::Snoozed
+            }
+            Transition::Resolve => {
+                self.state = AlertState::Resolved;
+                self.snoozed_until = None;
+                self.resolved_by = Some(actor.to_string());
+                self.resolved_at = Some(now);
+                AlertAction::Resolved
+            }
+        };
+        self.updated_at = now;
+        self.history.push(AlertTransition {
+            at: now,
+            actor: actor.to_string(),
+            action,
+            comment,
+            snoozed_until,
+        });
+        Ok(action)
+    }
+
+    fn updated_event(&self, action: AlertAction) -> WsEvent {
+        let last = self.history.last();
+        WsEvent::AlertUpdated {
+            alert_id: self.id,
+            device_id: self.device_id.clone(),
+            fleet_id: self.fleet_id.clone(),
+            action: action.as_str().into(),
+            state: self.state.as_str().into(),
+            actor: last.map(|t| t.actor.clone()).unwrap_or_default(),
+            comment: last.and_then(|t| t.comment.clone()),
+            occurrences: self.occurrences,
+            snoozed_until: self.snoozed_until,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// Filters for listing alerts. `state` matches the effective state, so an
+/// expired snooze is listed as open.
+#[derive(Debug, Clone, Default)]
+pub struct AlertFilter {
+    pub state: Option<AlertState>,
+    /// Exclude resolved alerts.
+    pub unresolved: bool,
+    pub fleet_id: Option<String>,
+    pub device_id: Option<String>,
+    pub kind: Option<String>,
+    /// Only these fleets (the caller's tenants); `None` for all.
+    pub fleets: Option<Vec<String>>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl AlertFilter {
+    /// Whether a refreshed alert passes the filters (paging aside).
+    pub fn matches(&self, alert: &Alert) -> bool {
+        self.state.is_none_or(|s| alert.state == s)
+            && (!self.unresolved || alert.state != AlertState::Resolved)
+            && self.fleet_id.as_deref().is_none_or(|f| alert.fleet_id == f)
+            && self
+                .device_id
+                .as_deref()
+                .is_none_or(|d| alert.device_id == d)
+            && self.kind.as_deref().is_none_or(|k| alert.kind == k)
+            && self
+                .fleets
+                .as_ref()
+                .is_none_or(|fleets| fleets.contains(&alert.fleet_id))
+    }
+}
+
+/// Unresolved alerts of a fleet by effective state.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AlertCounts {
+    pub open: u64,
+    pub acknowledged: u64,
+    pub snoozed: u64,
+    pub unresolved: u64,
+}
+
+impl AlertCounts {
+    fn add(&mut self, state: AlertState, n: u64) {
+        match state {
+            AlertState::Open => self.open += n,
+            AlertState::Acknowledged => self.acknowledged += n,
+            AlertState::Snoozed => self.snoozed += n,
+            AlertState::Resolved => return,
+        }
+        self.unresolved += n;
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+/// Open a new alert or bump the unresolved one with the same key, returning
+/// it. Failures are logged (and `None` returned); detectors keep running
+/// without persistence.
+pub async fn raise(state: &AppState, new: NewAlert) -> Option<Alert> {
+    match try_raise(state, new).await {
+        Ok(alert) => Some(alert),
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to record alert");
+            None
+        }
+    }
+}
+
+async fn try_raise(state: &AppState, new: NewAlert) -> ApiResult<Alert> {
+    let now = Utc::now();
+    let (alert, action) = if let Some(pool) = &state.pool {
+        let existing = crate::db::alerts::find_unresolved(pool, &new.dedup_key)
+            .await
+            .map_err(internal)?
+            .and_then(crate::db::alerts::AlertRow::into_alert);
+        match existing {
+            Some(mut alert) => {
+                alert.reoccur(new, now);
+                crate::db::alerts::update(pool, &alert)
+                    .await
+                    .map_err(internal)?;
+                (alert, AlertAction::Reoccurred)
+            }
+            None => {
+                let alert = Alert::raise(new, now);
+                crate::db::alerts::insert(pool, &alert)
+                    .await
+                    .map_err(internal)?;
+                (alert, AlertAction::Raised)
+            }
+        }
+    } else {
+        let mut alerts = state.alerts.write().await;
+        match alerts
+            .iter_mut()
+            .find(|a| a.dedup_key == new.dedup_key && a.state != AlertState::Resolved)
+        {
+            Some(alert) => {
+                alert.reoccur(new, now);
+                (alert.clone(), AlertAction::Reoccurred)
+            }
+            None => {
+                let alert = Alert::raise(new, now);
+                alerts.push(alert.clone());
+                (alert, AlertAction::Raised)
+            }
+        }
+    };
+
+    tracing::info!(
+        alert_id = %alert.id,
+        kind = %alert.kind,
+        fleet_id = %alert.fleet_id,
+        occurrences = alert.occurrences,
+        action = action.as_str(),
+        "alert"
+    );
+    let event = match action {
+        AlertAction::Raised => WsEvent::AlertRaised {
+            alert_id: alert.id,
+            device_id: alert.device_id.clone(),
+            fleet_id: alert.fleet_id.clone(),
+            kind: alert.kind.clone(),
+            scope: alert.scope.clone(),
+            title: alert.title.clone(),
+            raised_at: alert.first_seen_at,
+        },
+        _ => alert.updated_event(action),
+    };
+    let _ = state.event_tx.send(event);
+    Ok(alert)
+}
+
+/// One page of alerts matching `filter` (most recently seen first) and the
+/// total number of matches.
+pub async fn list(state: &AppState, filter: &AlertFilter) -> ApiResult<(Vec<Alert>, u64)> {
+    let now = Utc::now();
+    if let Some(pool) = &state.pool {
+        let (rows, total) = tokio::try_join!(
+            crate::db::alerts::list_page(pool, filter),
+            crate::db::alerts::count(pool, filter),
+        )
+        .map_err(internal)?;
+        let page = rows
+            .into_iter()
+            .filter_map(crate::db::alerts::AlertRow::into_alert)
+            .map(|mut a| {
+                a.refresh(now);
+                a
+            })
+            .collect();
+        return Ok((page, total as u64));
+    }
+
+    let alerts = state.alerts.read().await;
+    let mut matching: Vec<Alert> = alerts
+        .iter()
+        .cloned()
+        .map(|mut a| {
+            a.refresh(now);
+            a
+        })
+        .filter(|a| filter.matches(a))
+        .collect();
+    matching.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at).then(b.id.cmp(&a.id)));
+    let total = matching.len() as u64;
+    Ok((
+        crate::routes::pagination::slice(matching, filter.offset, filter.limit),
+        total,
+    ))
+}
+
+/// An alert by ID.
+pub async fn get(state: &AppState, id: Uuid) -> ApiResult<Alert> {
+    let alert = if let Some(pool) = &state.pool {
+        crate::db::alerts::get(pool, id)
+            .await
+            .map_err(internal)?
+            .and_then(crate::db::alerts::AlertRow::into_alert)
+    } else {
+        state
+            .alerts
+            .read()
+            .await
+            .iter()
+            .find(|a| a.id == id)
+            .cloned()
+    };
+    let mut alert = alert.ok_or_else(|| ApiError::NotFound(format!("alert {id} not found")))?;
+    alert.refresh(Utc::now());
+    Ok(alert)
+}
+
+/// Apply an operator action, persist it and broadcast the change.
+/// `authorize` sees the alert first, so tenant checks happen before any
+/// change.
+pub async fn transition(
+    state: &AppState,
+    id: Uuid,
+    transition: Transition,
+    actor: &str,
+    comment: Option<String>,
+    authorize: impl FnOnce(&Alert) -> ApiResult<()>,
+) -> ApiResult<Alert> {
+    let now = Utc::now();
+    let (alert, action) = if let Some(pool) = &state.pool {
+        let mut alert = get(state, id).await?;
+        authorize(&alert)?;
+        let action = alert.apply(transition, actor, comment, now)?;
+        crate::db::alerts::update(pool, &alert)
+            .await
+            .map_err(internal)?;
+        (alert, action)
+    } else {
+        let mut alerts = state.alerts.write().await;
+        let alert = alerts
+            .iter_mut()
+            .find(|a| a.id == id)
+            .ok_or_else(|| ApiError::NotFound(format!("alert {id} not found")))?;
+        alert.refresh(now);
+        authorize(alert)?;
+        let action = alert.apply(transition, actor, comment, now)?;
+        (alert.clone(), action)
+    };
+
+    tracing::info!(
+        alert_id = %alert.id,
+        actor,
+        action = action.as_str(),
+        "alert"
+    );
+    let _ = state.event_tx.send(alert.updated_event(action));
+    Ok(alert)
+}
+
+/// Unresolved alerts of a fleet by effective state.
+pub async fn counts(state: &AppState, fleet_id: &str) -> ApiResult<AlertCounts> {
+    let mut counts = AlertCounts::default();
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::alerts::count_unresolved_by_state(pool, fleet_id)
+            .await
+            .map_err(internal)?;
+        for (name, n) in rows {
+            if let Some(s) = AlertState::parse(&name) {
+                counts.add(s, n as u64);
+            }
+        }
+        return Ok(counts);
+    }
+
+    let now = Utc::now();
+    for alert in state.alerts.read().await.iter() {
+        if alert.fleet_id == fleet_id {
+            let mut alert = alert.clone();
+            alert.refresh(now);
+            counts.add(alert.state, 1);
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn new_alert() -> NewAlert {
+        NewAlert {
+            kind: KIND_TOOL_FAILURE_RATE.into(),
+            fleet_id: "fleet-alpha".into(),
+            device_id: "rpi-001".into(),
+            scope: "fleet".into(),
+            title: "read_dtcs failing".into(),
+            detail: serde_json::json!({}),
+            dedup_key: "k".into(),
+        }
+    }
+
+    #[test]
+    fn state_names_round_trip() {
+        for state in [
+            AlertState::Open,
+            AlertState::Acknowledged,
+            AlertState::Snoozed,
+            AlertState::Resolved,
+        ] {
+            assert_eq!(AlertState::parse(state.as_str()), Some(state));
+            assert_eq!(
+                serde_json::to_value(state).unwrap(),
+                serde_json::json!(state.as_str())
+            );
+        }
+        assert_eq!(AlertState::parse("closed"), None);
+    }
+
+    #[test]
+    fn lifecycle_follows_the_state_machine() {
+        let now = Utc::now();
+        let mut alert = Alert::raise(new_alert(), now);
+        assert_eq!(alert.state, AlertState::Open);
+
+        let until = now + Duration::minutes(30);
+        alert
+            .apply(Transition::Snooze { until }, "alice", None, now)
+            .unwrap();
+        assert_eq!(alert.state, AlertState::Snoozed);
+
+        // Reoccurring while snoozed keeps the snooze.
+        alert.reoccur(new_alert(), now + Duration::minutes(5));
+        assert_eq!((alert.state, alert.occurrences), (AlertState::Snoozed, 2));
+
+        // The snooze lapses lazily.
+        alert.refresh(until);
+        assert_eq!(alert.state, AlertState::Open);
+        assert_eq!(alert.snoozed_until, None);
+
+        alert
+            .apply(Transition::Acknowledge, "bob", Some("on it".into()), until)
+            .unwrap();
+        assert_eq!(alert.acknowledged_by.as_deref(), Some("bob"));
+        assert!(matches!(
+            alert.apply(Transition::Acknowledge, "bob", None, until),
+            Err(ApiError::Conflict(_))
+        ));
+        assert!(matches!(
+            alert.apply(Transition::Snooze { until: now }, "bob", None, until),
+            Err(ApiError::BadRequest(_))
+        ));
+
+        alert
+            .apply(
+                Transition::Resolve,
+                "bob",
+                Some("ECU reflashed".into()),
+                until,
+            )
+            .unwrap();
+        assert_eq!(alert.state, AlertState::Resolved);
+        assert!(matches!(
+            alert.apply(Transition::Acknowledge, "alice", None, until),
+            Err(ApiError::Conflict(_))
+        ));
+
+        let actions: Vec<AlertAction> = alert.history.iter().map(|t| t.action).collect();
+        assert_eq!(
+            actions,
+            [
+                AlertAction::Raised,
+                AlertAction::Snoozed,
+                AlertAction::Reoccurred,
+                AlertAction::Acknowledged,
+                AlertAction::Resolved,
+            ]
+        );
+        assert_eq!(alert.history[4].comment.as_deref(), Some("ECU reflashed"));
+    }
+}
@@ -0,0 +1,842 @@
+//! Natural-language fleet queries answered from stored data.
+//!
+//! The inference engines turn operator text into tool intents for one
+//! device. Questions about the fleet's past — "which devices reported P0300
+//! this week", "which commands failed today", "which devices had
+//! coolant_temp above 110 in the last 24 hours" — need no device at all:
+//! [`parse`] turns them into an [`AnalyticsQuery`] over a time window, and
+//! [`answer`] runs it against the command, DTC history, telemetry and device
+//! tables (or the in-memory stores).
+//!
+//! Parsing is rule-based like [`crate::inference::rules`]. Queries:
+//!
+//! - `dtc_reports`: devices whose DTC scans reported a code (or any code, or
+//!   any critical code), with first/last sighting;
+//! - `failed_commands`: commands that ended `failed` or `timeout`, newest
+//!   first, optionally for one tool;
+//! - `telemetry_threshold`: devices with readings of a metric beyond a
+//!   threshold, with the count and peak;
+//! - `device_status`: devices currently `online`, `degraded` or `offline`.
+//!
+//! History queries default to the last [`DEFAULT_WINDOW_DAYS`] days when the
+//! text names no window. At most [`MAX_ROWS`] rows are returned.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use zc_protocol::commands::CommandStatus;
+
+use crate::alert_rules::Comparison;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Window of history queries that name none.
+pub const DEFAULT_WINDOW_DAYS: i64 = 7;
+/// Most rows in an answer.
+pub const MAX_ROWS: usize = 500;
+
+/// A structured fleet query.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnalyticsQuery {
+    /// Devices whose scans reported `code` (any code when `None`).
+    DtcReports {
+        code: Option<String>,
+        /// Only codes of critical severity.
+        critical_only: bool,
+    },
+    /// Commands that ended `failed` or `timeout`.
+    FailedCommands { tool_name: Option<String> },
+    /// Devices with readings of `metric` beyond `threshold`.
+    TelemetryThreshold {
+        metric: String,
+        op: Comparison,
+        threshold: f64,
+    },
+    /// Devices currently in `status`.
+    DeviceStatus { status: String },
+}
+
+/// A query with its time window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParsedQuery {
+    pub query: AnalyticsQuery,
+    /// Start of the window; `None` for current-state queries.
+    pub since: Option<DateTime<Utc>>,
+    pub until: DateTime<Utc>,
+    /// The window as phrased in answers ("this week", "in the last 2 hours").
+    pub window: String,
+}
+
+/// The answer to a fleet query.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalyticsAnswer {
+    #[serde(flatten)]
+    pub parsed: ParsedQuery,
+    /// Fleets the query covered; all fleets when `None`.
+    pub fleets: Option<Vec<String>>,
+    /// One-sentence answer.
+    pub summary: String,
+    /// Matching rows; their shape depends on the query type.
+    pub rows: Vec<serde_json::Value>,
+    /// Whether rows beyond [`MAX_ROWS`] were dropped.
+    pub truncated: bool,
+}
+
+/// Parse a fleet question asked at `now`. Returns `None` for text that is
+/// not a query this module can answer.
+pub fn parse(text: &str, now: DateTime<Utc>) -> Option<ParsedQuery> {
+    let lower = text.to_lowercase();
+    let lower = lower.trim();
+
+    let query = if let Some(code) = dtc_code(lower) {
+        AnalyticsQuery::DtcReports {
+            code: Some(code),
+            critical_only: false,
+        }
+    } else if lower.contains("command")
+        && matches_any(lower, &["fail", "timed out", "timeout", "error"])
+    {
+        AnalyticsQuery::FailedCommands {
+            tool_name: tool_name(lower),
+        }
+    } else if matches_any(lower, &["dtc", "trouble code", "fault code"]) {
+        AnalyticsQuery::DtcReports {
+            code: None,
+            critical_only: lower.contains("critical"),
+        }
+    } else if let Some(query) = threshold(lower) {
+        query
+    } else if let Some(status) = ["offline", "degraded", "online"]
+        .into_iter()
+        .find(|s| lower.contains(s))
+    {
+        return Some(ParsedQuery {
+            query: AnalyticsQuery::DeviceStatus {
+                status: status.into(),
+            },
+            since: None,
+            until: now,
+            window: "now".into(),
+        });
+    } else {
+        return None;
+    };
+
+    let (since, until, window) = window(lower, now);
+    Some(ParsedQuery {
+        query,
+        since: Some(since),
+        until,
+        window,
+    })
+}
+
+/// Answer a parsed query, limited to `fleets` (all fleets when `None`).
+pub async fn answer(
+    state: &AppState,
+    parsed: ParsedQuery,
+    fleets: Option<Vec<String>>,
+) -> ApiResult<AnalyticsAnswer> {
+    let (mut rows, summary) = match &parsed.query {
+        AnalyticsQuery::DtcReports {
+            code,
+            critical_only,
+        } => {
+            let rows =
+                dtc_reports(state, &parsed, code.as_deref(), *critical_only, &fleets).await?;
+            let what = match (code, critical_only) {
+                (Some(code), _) => code.clone(),
+                (None, true) => "critical DTCs".into(),
+                (None, false) => "DTCs".into(),
+            };
+            let summary = format!("{} reported {what} {}", devices(rows.len()), parsed.window);
+            (rows, summary)
+        }
+        AnalyticsQuery::FailedCommands { tool_name } => {
+            let rows = failed_commands(state, &parsed, tool_name.as_deref(), &fleets).await?;
+            let mut affected: Vec<&str> = rows
+                .iter()
+                .filter_map(|r| r["device_id"].as_str())
+                .collect();
+            affected.sort_unstable();
+            affected.dedup();
+            let what = match tool_name {
+                Some(tool) => format!("{tool} command"),
+                None => "command".into(),
+            };
+            let summary = format!(
+                "{} {what}{} failed on {} {}",
+                rows.len(),
+                if rows.len() == 1 { "" } else { "s" },
+                devices(affected.len()),
+                parsed.window
+            );
+            (rows, summary)
+        }
+        AnalyticsQuery::TelemetryThreshold {
+            metric,
+            op,
+            threshold,
+        } => {
+            let rows =
+                telemetry_threshold(state, &parsed, metric, *op, *threshold, &fleets).await?;
+            let summary = format!(
+                "{} had {metric} {} {threshold} {}",
+                devices(rows.len()),
+                op.symbol(),
+                parsed.window
+            );
+            (rows, summary)
+        }
+        AnalyticsQuery::DeviceStatus { status } => {
+            let rows = device_status(state, status, &fleets).await?;
+            let summary = format!("{} {} {status}", devices(rows.len()), verb(rows.len()));
+            (rows, summary)
+        }
+    };
+    let truncated = rows.len() > MAX_ROWS;
+    rows.truncate(MAX_ROWS);
+    Ok(AnalyticsAnswer {
+        parsed,
+        fleets,
+        summary,
+        rows,
+        truncated,
+    })
+}
+
+fn devices(n: usize) -> String {
+    if n == 1 {
+        "1 device".into()
+    } else {
+        format!("{n} devices")
+    }
+}
+
+fn verb(n: usize) -> &'static str {
+    if n == 1 { "is" } else { "are" }
+}
+
+fn matches_any(text: &str, patterns: &[&str]) -> bool {
+    patterns.iter().any(|p| text.contains(p))
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .map(|w| w.trim_end_matches('.'))
+        .filter(|w| !w.is_empty())
+}
+
+/// First well-formed DTC code in the text ("p0300" → "P0300").
+fn dtc_code(text: &str) -> Option<String> {
+    words(text).find_map(|w| crate::dtc_knowledge::normalize_code(w).ok())
+}
+
+/// A snake_case tool name such as `read_dtcs`.
+fn tool_name(text: &str) -> Option<String> {
+    words(text)
+        .find(|w| w.contains('_') && w.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+        .map(String::from)
+}
+
+/// Comparison phrases, longest first so "greater than or equal to" wins.
+const COMPARATORS: &[(&str, Comparison)] = &[
+    ("greater than or equal to", Comparison::Gte),
+    ("less than or equal to", Comparison::Lte),
+    ("at least", Comparison::Gte),
+    ("at most", Comparison::Lte),
+    ("greater than", Comparison::Gt),
+    ("higher than", Comparison::Gt),
+    ("more than", Comparison::Gt),
+    ("exceeding", Comparison::Gt),
+    ("above", Comparison::Gt),
+    ("over", Comparison::Gt),
+    ("less than", Comparison::Lt),
+    ("lower than", Comparison::Lt),
+    ("below", Comparison::Lt),
+    ("under", Comparison::Lt),
+    (">=", Comparison::Gte),
+    ("<=", Comparison::Lte),
+    (">", Comparison::Gt),
+    ("<", Comparison::Lt),
+];
+
+/// Metric names for words operators use.
+const METRIC_ALIASES: &[(&str, &str)] = &[
+    ("rpm", "engine_rpm"),
+    ("coolant", "coolant_temp"),
+    ("temperature", "coolant_temp"),
+    ("speed", "vehicle_speed"),
+];
+
+/// "coolant_temp above 110", "rpm over 4000".
+fn threshold(text: &str) -> Option<AnalyticsQuery> {
+    let padded = format!(" {text} ");
+    let (before, after, op) = COMPARATORS.iter().find_map(|(phrase, op)| {
+        let (before, after) = padded.split_once(&format!(" {phrase} "))?;
+        Some((before, after, *op))
+    })?;
+    let threshold = after
+        .split_whitespace()
+        .next()?
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '%' || c == '?')
+        .parse::<f64>()
+        .ok()
+        .filter(|t| t.is_finite())?;
+    let metric = words(before)
+        .filter(|w| w.contains('_'))
+        .last()
+        .map(String::from)
+        .or_else(|| {
+            METRIC_ALIASES
+                .iter()
+                .find(|(alias, _)| words(before).any(|w| w.starts_with(alias)))
+                .map(|(_, metric)| (*metric).to_string())
+        })?;
+    Some(AnalyticsQuery::TelemetryThreshold {
+        metric,
+        op,
+        threshold,
+    })
+}
+
+/// Time window named in the text: `(since, until, phrase)`.
+fn window(text: &str, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>, String) {
+    let midnight = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+    if text.contains("yesterday") {
+        return (midnight - Duration::days(1), midnight, "yesterday".into());
+    }
+    if text.contains("today") {
+        return (midnight, now, "today".into());
+    }
+    if text.contains("this week") {
+        let monday = midnight - Duration::days(now.weekday().num_days_from_monday() as i64);
+        return (monday, now, "this week".into());
+    }
+    if text.contains("this month") {
+        let first = midnight - Duration::days(now.day0() as i64);
+        return (first, now, "this month".into());
+    }
+    if let Some(minutes) = crate::inference::rules::extract_lookback_minutes(text) {
+        let phrase = match minutes {
+            m if m % (24 * 60) == 0 && m > 24 * 60 => plural(m / (24 * 60), "day"),
+            m if m % 60 == 0 => plural(m / 60, "hour"),
+            m => plural(m, "minute"),
+        };
+        return (
+            now - Duration::minutes(minutes as i64),
+            now,
+            format!("in the last {phrase}"),
+        );
+    }
+    if matches_any(text, &["last week", "past week"]) {
+        return (now - Duration::days(7), now, "in the last 7 days".into());
+    }
+    if matches_any(text, &["last month", "past month"]) {
+        return (now - Duration::days(30), now, "in the last 30 days".into());
+    }
+    (
+        now - Duration::days(DEFAULT_WINDOW_DAYS),
+        now,
+        format!("in the last {DEFAULT_WINDOW_DAYS} days"),
+    )
+}
+
+fn plural(n: u32, unit: &str) -> String {
+    if n == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{n} {unit}s")
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+/// Fleet of every known device (in-memory mode).
+async fn device_fleets(state: &AppState) -> HashMap<String, Option<String>> {
+    state
+        .devices
+        .read()
+        .await
+        .values()
+        .map(|d| {
+            let fleet = d.metadata.get("fleet").and_then(|f| f.as_str());
+            (d.device_id.clone(), fleet.map(String::from))
+        })
+        .collect()
+}
+
+fn in_fleets(fleets: &Option<Vec<String>>, fleet: Option<&str>) -> bool {
+    fleets
+        .as_ref()
+        .is_none_or(|fleets| fleet.is_some_and(|f| fleets.iter().any(|x| x == f)))
+}
+
+fn in_window(parsed: &ParsedQuery, at: DateTime<Utc>) -> bool {
+    parsed.since.is_none_or(|since| at >= since) && at < parsed.until
+}
+
+async fn dtc_reports(
+    state: &AppState,
+    parsed: &ParsedQuery,
+    code: Option<&str>,
+    critical_only: bool,
+    fleets: &Option<Vec<String>>,
+) -> ApiResult<Vec<serde_json::Value>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::analytics::dtc_reports(
+            pool,
+            code,
+            critical_only,
+            parsed.since,
+            parsed.until,
+            fleets.as_deref(),
+            MAX_ROWS + 1,
+        )
+        .await
+        .map_err(internal)?;
+        return Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "device_id": r.device_id,
+                    "codes": r.codes,
+                    "occurrences": r.occurrences,
+                    "first_seen": r.first_seen,
+                    "last_seen": r.last_seen,
+                })
+            })
+            .collect());
+    }
+
+    let device_fleets = device_fleets(state).await;
+    let history = state.dtc_history.read().await;
+    let mut rows: Vec<(DateTime<Utc>, serde_json::Value)> = Vec::new();
+    for (device_id, scans) in history.iter() {
+        if !in_fleets(
+            fleets,
+            device_fleets.get(device_id).cloned().flatten().as_deref(),
+        ) {
+            continue;
+        }
+        let mut codes: BTreeSet<&str> = BTreeSet::new();
+        let (mut occurrences, mut first, mut last) = (0u64, None, None);
+        for scan in scans.iter().filter(|s| in_window(parsed, s.scanned_at)) {
+            for dtc in &scan.dtcs {
+                if code.is_some_and(|c| dtc.code != c)
+                    || (critical_only && dtc.severity != "critical")
+                {
+                    continue;
+                }
+                codes.insert(&dtc.code);
+                occurrences += 1;
+                let at = scan.scanned_at;
+                first = Some(first.map_or(at, |f: DateTime<Utc>| f.min(at)));
+                last = Some(last.map_or(at, |l: DateTime<Utc>| l.max(at)));
+            }
+        }
+        if let (Some(first), Some(last)) = (first, last) {
+            let codes: Vec<&str> = codes.into_iter().collect();
+            rows.push((
+                last,
+                json!({
+                    "device_id": device_id,
+                    "codes": codes,
+                    "occurrences": occurrences,
+                    "first_seen": first,
+                    "last_seen": last,
+                }),
+            ));
+        }
+    }
+    rows.sort_by_key(|r| Reverse(r.0));
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
+async fn failed_commands(
+    state: &AppState,
+    parsed: &ParsedQuery,
+    tool_name: Option<&str>,
+    fleets: &Option<Vec<String>>,
+) -> ApiResult<Vec<serde_json::Value>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::analytics::failed_commands(
+            pool,
+            tool_name,
+            parsed.since,
+            parsed.until,
+            fleets.as_deref(),
+            MAX_ROWS + 1,
+        )
+        .await
+        .map_err(internal)?;
+        return Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "command_id": r.id,
+                    "device_id": r.device_id,
+                    "fleet_id": r.fleet_id,
+                    "command": r.natural_language,
+                    "tool_name": r.tool_name,
+                    "status": r.status,
+                    "error": r.error,
+                    "created_at": r.created_at,
+                })
+            })
+            .collect());
+    }
+
+    let commands = state.commands.read().await;
+    let mut rows: Vec<(DateTime<Utc>, Uuid, serde_json::Value)> = commands
+        .iter()
+        .filter(|c| matches!(c.status, CommandStatus::Failed | CommandStatus::Timeout))
+        .filter(|c| in_window(parsed, c.created_at))
+        .filter(|c| in_fleets(fleets, Some(&c.envelope.fleet_id)))
+        .filter(|c| {
+            tool_name.is_none_or(|t| {
+                c.envelope
+                    .parsed_intent
+                    .as_ref()
+                    .is_some_and(|i| i.tool_name == t)
+            })
+        })
+        .map(|c| {
+            let status = match c.status {
+                CommandStatus::Timeout => "timeout",
+                _ => "failed",
+            };
+            (
+                c.created_at,
+                c.envelope.id,
+                json!({
+                    "command_id": c.envelope.id,
+                    "device_id": c.envelope.device_id,
+                    "fleet_id": c.envelope.fleet_id,
+                    "command": c.envelope.natural_language,
+                    "tool_name": c.envelope.parsed_intent.as_ref().map(|i| &i.tool_name),
+                    "status": status,
+                    "error": c.response.as_ref().and_then(|r| r.error.as_ref()),
+                    "created_at": c.created_at,
+                }),
+            )
+        })
+        .collect();
+    rows.sort_by_key(|r| Reverse((r.0, r.1)));
+    Ok(rows.into_iter().map(|(_, _, row)| row).collect())
+}
+
+async fn telemetry_threshold(
+    state: &AppState,
+    parsed: &ParsedQuery,
+    metric: &str,
+    op: Comparison,
+    threshold: f64,
+    fleets: &Option<Vec<String>>,
+) -> ApiResult<Vec<serde_json::Value>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::analytics::telemetry_threshold(
+            pool,
+            metric,
+            op,
+            threshold,
+            parsed.since,
+            parsed.until,
+            fleets.as_deref(),
+            MAX_ROWS + 1,
+        )
+        .await
+        .map_err(internal)?;
+        return Ok(rows
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "device_id": r.device_id,
+                    "readings": r.readings,
+                    "peak": r.peak,
+                    "last_seen": r.last_seen,
+                })
+            })
+            .collect());
+    }
+
+    let device_fleets = device_fleets(state).await;
+    let telemetry = state.telemetry.read().await;
+    // device -> (readings, peak, last seen)
+    let mut by_device: BTreeMap<&str, (u64, f64, DateTime<Utc>)> = BTreeMap::new();
+    for row in telemetry.iter() {
+        let Some(value) = row.value_numeric else {
+            continue;
+        };
+        if row.metric_name != metric
+            || !op.holds(value, threshold)
+            || !in_window(parsed, row.time)
+            || !in_fleets(
+                fleets,
+                device_fleets
+                    .get(&row.device_id)
+                    .cloned()
+                    .flatten()
+                    .as_deref(),
+            )
+        {
+            continue;
+        }
+        let entry = by_device
+            .entry(&row.device_id)
+            .or_insert((0, value, row.time));
+        entry.0 += 1;
+        entry.1 = match op {
+            Comparison::Gt | Comparison::Gte => entry.1.max(value),
+            Comparison::Lt | Comparison::Lte => entry.1.min(value),
+        };
+        entry.2 = entry.2.max(row.time);
+    }
+    let mut rows: Vec<(DateTime<Utc>, serde_json::Value)> = by_device
+        .into_iter()
+        .map(|(device_id, (readings, peak, last_seen))| {
+            (
+                last_seen,
+                json!({
+                    "device_id": device_id,
+                    "readings": readings,
+                    "peak": peak,
+                    "last_seen": last_seen,
+                }),
+            )
+        })
+        .collect();
+    rows.sort_by_key(|r| Reverse(r.0));
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
+async fn device_status(
+    state: &AppState,
+    status: &str,
+    fleets: &Option<Vec<String>>,
+) -> ApiResult<Vec<serde_json::Value>> {
+    if let Some(pool) = &state.pool {
+        let rows =
+            crate::db::analytics::devices_by_status(pool, status, fleets.as_deref(), MAX_ROWS + 1)
+                .await
+                .map_err(internal)?;
+        return Ok(rows
+            .into_iter()
+            .map(|(device_id, fleet_id, last_heartbeat)| {
+                json!({
+                    "device_id": device_id,
+                    "fleet_id": fleet_id,
+                    "last_heartbeat": last_heartbeat,
+                })
+            })
+            .collect());
+    }
+
+    let wanted = crate::routes::devices::parse_device_status(status);
+    let devices = state.devices.read().await;
+    let mut rows: Vec<_> = devices
+        .values()
+        .filter(|d| d.status == wanted)
+        .filter_map(|d| {
+            let fleet = d.metadata.get("fleet").and_then(|f| f.as_str());
+            in_fleets(fleets, fleet).then(|| {
+                json!({
+                    "device_id": d.device_id,
+                    "fleet_id": fleet,
+                    "last_heartbeat": d.last_heartbeat,
+                })
+            })
+        })
+        .collect();
+    rows.sort_by(|a, b| a["device_id"].as_str().cmp(&b["device_id"].as_str()));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::db::telemetry::TelemetryRow;
+    use crate::dtc_history::{DtcScan, ObservedDtc};
+    use crate::state::CommandRecord;
+    use zc_protocol::commands::CommandEnvelope;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        // 2026-10-12 is a Monday.
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_fleet_questions() {
+        let now = at(15, 12);
+        let parsed = parse("Which devices reported P0300 this week?", now).unwrap();
+        assert_eq!(
+            parsed.query,
+            AnalyticsQuery::DtcReports {
+                code: Some("P0300".into()),
+                critical_only: false
+            }
+        );
+        assert_eq!(parsed.since, Some(at(12, 0)));
+        assert_eq!(parsed.window, "this week");
+
+        let parsed = parse("how many read_dtcs commands failed today", now).unwrap();
+        assert_eq!(
+            parsed.query,
+            AnalyticsQuery::FailedCommands {
+                tool_name: Some("read_dtcs".into())
+            }
+        );
+        assert_eq!(parsed.since, Some(at(15, 0)));
+
+        let parsed = parse(
+            "devices with coolant temperature above 110C in the last 2 hours",
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.query,
+            AnalyticsQuery::TelemetryThreshold {
+                metric: "coolant_temp".into(),
+                op: Comparison::Gt,
+                threshold: 110.0
+            }
+        );
+        assert_eq!(parsed.window, "in the last 2 hours");
+
+        let parsed = parse("critical DTCs yesterday", now).unwrap();
+        assert_eq!((parsed.since, parsed.until), (Some(at(14, 0)), at(15, 0)));
+
+        let parsed = parse("which devices are offline", now).unwrap();
+        assert_eq!(parsed.since, None);
+        assert_eq!(parsed.window, "now");
+
+        assert!(parse("read DTCs", now).is_some_and(|p| p.since == Some(now - Duration::days(7))));
+        assert!(parse("hello there", now).is_none());
+    }
+
+    #[tokio::test]
+    async fn answers_from_memory() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        let scan = |code: &str, severity: &str, scanned_at| DtcScan {
+            command_id: Uuid::now_v7(),
+            tool_name: "read_dtcs".into(),
+            scanned_at,
+            dtcs: vec![ObservedDtc {
+                code: code.into(),
+                severity: severity.into(),
+                description: None,
+            }],
+        };
+        {
+            let mut history = state.dtc_history.write().await;
+            history.insert(
+                "rpi-001".into(),
+                vec![
+                    scan("P0300", "critical", now - Duration::days(30)),
+                    scan("P0300", "critical", now - Duration::hours(3)),
+                ],
+            );
+            history.insert(
+                "sbc-010".into(),
+                vec![scan("P0300", "critical", now - Duration::hours(1))],
+            );
+            history.insert(
+                "rpi-002".into(),
+                vec![scan("P0420", "warning", now - Duration::hours(2))],
+            );
+        }
+        let ask = |text: &str, fleets: Option<Vec<String>>| {
+            let state = state.clone();
+            let parsed = parse(text, Utc::now()).unwrap();
+            async move { answer(&state, parsed, fleets).await.unwrap() }
+        };
+
+        let reply = ask("which devices reported p0300 in the last 10 days", None).await;
+        assert_eq!(reply.rows.len(), 2);
+        assert_eq!(reply.rows[0]["device_id"], "sbc-010");
+        let reply = ask(
+            "which devices reported P0300 in the last 7 days",
+            Some(vec!["fleet-alpha".into()]),
+        )
+        .await;
+        assert_eq!(reply.summary, "1 device reported P0300 in the last 7 days");
+        assert_eq!(reply.rows[0]["occurrences"], 1);
+        let reply = ask("any critical dtcs in the last 24 hours?", None).await;
+        assert_eq!(reply.rows.len(), 2);
+
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-002", "read DTCs", "tech");
+        envelope.parsed_intent = Some(zc_protocol::commands::ParsedIntent {
+            action: zc_protocol::commands::ActionKind::Tool,
+            tool_name: "read_dtcs".into(),
+            tool_args: json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        state.commands.write().await.push(CommandRecord {
+            envelope,
+            response: None,
+            status: CommandStatus::Timeout,
+            created_at: now,
+            deadline: None,
+            attempts: 0,
+            max_attempts: 1,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
+        });
+        let reply = ask("which commands failed in the last 2 hours", None).await;
+        assert_eq!(
+            reply.summary,
+            "1 command failed on 1 device in the last 2 hours"
+        );
+        assert_eq!(reply.rows[0]["status"], "timeout");
+
+        state.telemetry.write().await.extend(
+            [
+                (90.0, "rpi-001"),
+                (115.0, "rpi-001"),
+                (121.0, "rpi-001"),
+                (112.0, "rpi-002"),
+            ]
+            .map(|(value, device_id)| TelemetryRow {
+                time: now,
+                device_id: device_id.into(),
+                metric_name: "coolant_temp".into(),
+                value_numeric: Some(value),
+                value_text: None,
+                value_json: None,
+                unit: Some("C".into()),
+                source: "obd2".into(),
+            }),
+        );
+        let reply = ask(
+            "which devices had coolant_temp over 110 in the last 2 hours",
+            None,
+        )
+        .await;
+        assert_eq!(reply.rows.len(), 2);
+        let rpi_001 = reply
+            .rows
+            .iter()
+            .find(|r| r["device_id"] == "rpi-001")
+            .unwrap();
+        assert_eq!(
+            (rpi_001["readings"].as_u64(), rpi_001["peak"].as_f64()),
+            (Some(2), Some(121.0))
+        );
+
+        let reply = ask("which devices are online", Some(vec!["fleet-beta".into()])).await;
+        assert_eq!(reply.summary, "1 device is online");
+    }
+}
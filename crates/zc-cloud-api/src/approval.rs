@@ -0,0 +1,399 @@
+//! Two-person approval for high-risk commands.
+//!
+//! With approvers configured (`APPROVERS`), commands that invoke a
+//! high-risk tool (`clear_dtcs` and the bench-only `send_frame` by default)
+//! and fleet-wide shell broadcasts are stored as `awaiting_approval` instead
+//! of being delivered. A second operator listed as an approver confirms
+//! through `POST /commands/{id}/approve` (a command ID, or a broadcast ID to
+//! approve every device's copy) before the TTL runs out; the command is then
+//! dispatched as usual — published, or queued while the device is offline.
+//! Initiators cannot approve their own commands, and a request approved too
+//! late is cancelled. Every step is written to the [`crate::audit`] log.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use zc_protocol::commands::{ActionKind, CommandEnvelope, CommandStatus, ParsedIntent};
+
+use crate::audit::{AuditAction, AuditEntry};
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// Tools that need approval unless `APPROVAL_TOOLS` says otherwise.
+pub const DEFAULT_HIGH_RISK_TOOLS: &[&str] = &["clear_dtcs", "send_frame"];
+
+/// Actor recorded for expiries.
+const SYSTEM_ACTOR: &str = "system";
+
+/// Which commands need a second operator, and who may approve them.
+#[derive(Debug, Clone)]
+pub struct ApprovalPolicy {
+    /// Operators allowed to approve. Empty disables the workflow.
+    pub approvers: Vec<String>,
+    /// How long a held command can be approved.
+    pub ttl: Duration,
+    pub high_risk_tools: Vec<String>,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self {
+            approvers: Vec::new(),
+            ttl: Duration::minutes(15),
+            high_risk_tools: DEFAULT_HIGH_RISK_TOOLS
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn enabled(&self) -> bool {
+        !self.approvers.is_empty()
+    }
+
+    pub fn is_approver(&self, operator: &str) -> bool {
+        self.approvers.iter().any(|a| a == operator)
+    }
+
+    /// Why a command with `intent` needs approval, or `None` if it does not.
+    pub fn reason(&self, intent: Option<&ParsedIntent>, fleet_wide: bool) -> Option<String> {
+        if !self.enabled() {
+            return None;
+        }
+        let intent = intent?;
+        match intent.action {
+            ActionKind::Tool => intent
+                .tool_names()
+                .find(|name| self.high_risk_tools.iter().any(|t| t == name))
+                .map(|name| format!("high-risk tool '{name}'")),
+            ActionKind::Shell if fleet_wide => Some("fleet-wide shell command".into()),
+            _ => None,
+        }
+    }
+
+    /// Deadline for approving a command created at `created_at`.
+    pub fn expires_at(&self, created_at: DateTime<Utc>) -> DateTime<Utc> {
+        created_at + self.ttl
+    }
+}
+
+/// Record that `envelope` (already stored as `awaiting_approval`) is held.
+pub async fn request(state: &AppState, envelope: &CommandEnvelope, reason: &str) -> ApiResult<()> {
+    let expires_at = state.approval.expires_at(envelope.created_at);
+    crate::audit::record(
+        state,
+        AuditEntry::command(
+            &envelope.initiated_by,
+            AuditAction::ApprovalRequested,
+            envelope.id,
+            &envelope.device_id,
+            serde_json::json!({
+                "reason": reason,
+                "command": envelope.natural_language,
+                "expires_at": expires_at,
+            }),
+        )
+        .with_fleet(&envelope.fleet_id),
+    )
+    .await?;
+
+    tracing::info!(
+        command_id = %envelope.id,
+        device_id = %envelope.device_id,
+        reason,
+        "command held for approval"
+    );
+    let _ = state.event_tx.send(WsEvent::CommandApprovalRequested {
+        command_id: envelope.id,
+        device_id: envelope.device_id.clone(),
+        initiated_by: envelope.initiated_by.clone(),
+        reason: reason.to_string(),
+        expires_at,
+    });
+    Ok(())
+}
+
+/// One command released by an approval.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovedCommand {
+    pub command_id: Uuid,
+    pub device_id: String,
+    /// `pending` (published) or `queued` (device offline).
+    pub status: CommandStatus,
+}
+
+/// Approve the held command `id`, or every held command of broadcast `id`,
+/// and dispatch it.
+pub async fn approve(
+    state: &AppState,
+    id: Uuid,
+    approved_by: &str,
+    comment: Option<&str>,
+) -> ApiResult<Vec<ApprovedCommand>> {
+    if !state.approval.is_approver(approved_by) {
+        return Err(ApiError::Forbidden(format!(
+            "'{approved_by}' is not an approver"
+        )));
+    }
+
+    let commands = commands_for(state, id).await?;
+    if commands.is_empty() {
+        return Err(ApiError::NotFound(format!("command '{id}' not found")));
+    }
+    let held: Vec<CommandEnvelope> = commands
+        .into_iter()
+        .filter(|(_, status)| *status == CommandStatus::AwaitingApproval)
+        .map(|(envelope, _)| envelope)
+        .collect();
+    let Some(first) = held.first() else {
+        return Err(ApiError::Conflict(format!(
+            "command '{id}' is not awaiting approval"
+        )));
+    };
+    if first.initiated_by == approved_by {
+        return Err(ApiError::Forbidden(
+            "a command cannot be approved by the operator who initiated it".into(),
+        ));
+    }
+
+    let expires_at = state.approval.expires_at(first.created_at);
+    if Utc::now() > expires_at {
+        for envelope in &held {
+            expire(state, envelope).await?;
+        }
+        return Err(ApiError::Conflict(format!(
+            "approval window for command '{id}' closed at {expires_at}"
+        )));
+    }
+
+    let mut approved = Vec::with_capacity(held.len());
+    for envelope in held {
+        let reachable = crate::routes::commands::device_reachable(state, &envelope.device_id)
+            .await
+            .unwrap_or(false);
+        let status = if reachable {
+            CommandStatus::Pending
+        } else {
+            CommandStatus::Queued
+        };
+        if !transition(state, envelope.id, status).await? {
+            // Approved, cancelled or expired concurrently.
+            continue;
+        }
+
+        crate::audit::record(
+            state,
+            AuditEntry::command(
+                approved_by,
+                AuditAction::Approved,
+                envelope.id,
+                &envelope.device_id,
+                serde_json::json!({
+                    "initiated_by": envelope.initiated_by,
+                    "comment": comment,
+                }),
+            )
+            .with_fleet(&envelope.fleet_id),
+        )
+        .await?;
+        state
+            .metrics
+            .command_status(if reachable { "pending" } else { "queued" });
+        let _ = state.event_tx.send(WsEvent::CommandApproved {
+            command_id: envelope.id,
+            device_id: envelope.device_id.clone(),
+            initiated_by: envelope.initiated_by.clone(),
+            approved_by: approved_by.to_string(),
+            approved_at: Utc::now(),
+        });
+
+        if reachable {
+            // Start the clock first: a failed publish clears it until the retry.
+            crate::command_timeouts::started(state, &envelope).await;
+            crate::command_retries::publish(state, &envelope).await;
+        } else {
+            let _ = state.event_tx.send(WsEvent::CommandQueued {
+                command_id: envelope.id,
+                device_id: envelope.device_id.clone(),
+                queued_at: Utc::now(),
+            });
+        }
+
+        tracing::info!(
+            command_id = %envelope.id,
+            device_id = %envelope.device_id,
+            initiated_by = %envelope.initiated_by,
+            approved_by,
+            "command approved"
+        );
+        approved.push(ApprovedCommand {
+            command_id: envelope.id,
+            device_id: envelope.device_id,
+            status,
+        });
+    }
+    Ok(approved)
+}
+
+/// Cancel a held command whose approval window has closed.
+async fn expire(state: &AppState, envelope: &CommandEnvelope) -> ApiResult<()> {
+    if !transition(state, envelope.id, CommandStatus::Cancelled).await? {
+        return Ok(());
+    }
+    crate::audit::record(
+        state,
+        AuditEntry::command(
+            SYSTEM_ACTOR,
+            AuditAction::ApprovalExpired,
+            envelope.id,
+            &envelope.device_id,
+            serde_json::json!({ "initiated_by": envelope.initiated_by }),
+        )
+        .with_fleet(&envelope.fleet_id),
+    )
+    .await?;
+    state.metrics.command_status("cancelled");
+    let _ = state.event_tx.send(WsEvent::CommandCancelled {
+        command_id: envelope.id,
+        device_id: envelope.device_id.clone(),
+        requested_by: SYSTEM_ACTOR.into(),
+        reason: Some("approval expired".into()),
+        cancelled_at: Utc::now(),
+    });
+    Ok(())
+}
+
+/// Move a held command to `to`. Returns `false` if it was no longer held.
+async fn transition(state: &AppState, command_id: Uuid, to: CommandStatus) -> ApiResult<bool> {
+    if let Some(pool) = &state.pool {
+        let to = serde_json::to_value(to)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+        return crate::db::commands::transition_status(pool, command_id, "awaiting_approval", &to)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()));
+    }
+    let mut commands = state.commands.write().await;
+    match commands
+        .iter_mut()
+        .find(|r| r.envelope.id == command_id && r.status == CommandStatus::AwaitingApproval)
+    {
+        Some(record) => {
+            record.status = to;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// The command `id`, or the per-device commands of broadcast `id`, with
+/// their cloud-side status.
+pub(crate) async fn commands_for(
+    state: &AppState,
+    id: Uuid,
+) -> ApiResult<Vec<(CommandEnvelope, CommandStatus)>> {
+    if let Some(pool) = &state.pool {
+        let internal = |e: sqlx::Error| ApiError::Internal(e.to_string());
+        let rows = match crate::db::commands::get_by_id(pool, id)
+            .await
+            .map_err(internal)?
+        {
+            Some(row) => vec![row],
+            None => crate::db::commands::list_by_correlation(pool, id)
+                .await
+                .map_err(internal)?,
+        };
+        return Ok(rows
+            .into_iter()
+            .filter_map(|r| {
+                let status = serde_json::from_value(serde_json::json!(r.status)).ok()?;
+                let envelope = serde_json::from_value(r.envelope?).ok()?;
+                Some((envelope, status))
+            })
+            .collect());
+    }
+
+    let commands = state.commands.read().await;
+    let pick = |matches: &dyn Fn(&CommandEnvelope) -> bool| {
+        commands
+            .iter()
+            .filter(|r| matches(&r.envelope))
+            .map(|r| (r.envelope.clone(), r.status))
+            .collect::<Vec<_>>()
+    };
+    let single = pick(&|e| e.id == id);
+    Ok(if single.is_empty() {
+        pick(&|e| e.correlation_id == id)
+    } else {
+        single
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn intent(action: ActionKind, tool: &str) -> ParsedIntent {
+        ParsedIntent {
+            action,
+            tool_name: tool.into(),
+            tool_args: json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        }
+    }
+
+    fn policy() -> ApprovalPolicy {
+        ApprovalPolicy {
+            approvers: vec!["lead".into()],
+            ..ApprovalPolicy::default()
+        }
+    }
+
+    #[test]
+    fn high_risk_tools_and_fleet_shell_need_approval() {
+        let policy = policy();
+        let send_frame = intent(ActionKind::Tool, "send_frame");
+        assert!(policy.reason(Some(&send_frame), false).is_some());
+        assert!(
+            policy
+                .reason(Some(&intent(ActionKind::Tool, "read_dtcs")), true)
+                .is_none()
+        );
+
+        let shell = intent(ActionKind::Shell, "uptime");
+        assert!(policy.reason(Some(&shell), false).is_none());
+        assert_eq!(
+            policy.reason(Some(&shell), true).as_deref(),
+            Some("fleet-wide shell command")
+        );
+        assert!(policy.reason(None, true).is_none());
+    }
+
+    #[test]
+    fn high_risk_plan_step_needs_approval() {
+        let mut plan = intent(ActionKind::Tool, "read_dtcs");
+        plan.steps = vec![
+            zc_protocol::plan::PlanStep::new("read_dtcs", json!({})),
+            zc_protocol::plan::PlanStep::new("clear_dtcs", json!({})),
+        ];
+        assert_eq!(
+            policy().reason(Some(&plan), false).as_deref(),
+            Some("high-risk tool 'clear_dtcs'")
+        );
+    }
+
+    #[test]
+    fn disabled_without_approvers() {
+        let policy = ApprovalPolicy::default();
+        assert!(!policy.enabled());
+        let clear = intent(ActionKind::Tool, "clear_dtcs");
+        assert!(policy.reason(Some(&clear), false).is_none());
+    }
+}
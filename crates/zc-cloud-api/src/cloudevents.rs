@@ -0,0 +1,350 @@
+//! CloudEvents 1.0 export of the event stream.
+//!
+//! With `CLOUDEVENTS_SINK_URL` set, [`run`] follows the [`WsEvent`]
+//! broadcast and publishes every event (or only the `CLOUDEVENTS_TYPES`) to
+//! an [`EventSink`] as a structured-mode CloudEvent: `type` is the event tag
+//! under [`TYPE_PREFIX`], `subject` the device, and `data` the WebSocket
+//! frame, so consumers see exactly what dashboards see.
+//!
+//! [`HttpSink`] POSTs `application/cloudevents+json`, which Knative, Azure
+//! Event Grid and most HTTP bridges into Kafka, NATS or AMQP accept. A
+//! native broker client only has to implement [`EventSink`].
+//!
+//! Events are published one at a time, in broadcast order. Transient
+//! failures are retried with the webhook [`RetryPolicy`]; an event that runs
+//! out of attempts, or that the sink rejects, is logged and dropped. A sink
+//! slower than the event rate makes the exporter lag, and the skipped events
+//! are logged.
+
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::events::WsEvent;
+use crate::state::AppState;
+use crate::webhooks::RetryPolicy;
+
+/// CloudEvents specification version produced.
+pub const SPEC_VERSION: &str = "1.0";
+/// Prefix of every CloudEvent `type` (e.g. `com.zeroclaw.device_heartbeat`).
+pub const TYPE_PREFIX: &str = "com.zeroclaw.";
+/// `source` when `CLOUDEVENTS_SOURCE` is unset.
+pub const DEFAULT_SOURCE: &str = "/zeroclaw/cloud-api";
+/// Media type of a structured-mode CloudEvent.
+pub const CONTENT_TYPE: &str = "application/cloudevents+json";
+/// Timeout for a single publish over HTTP.
+pub const PUBLISH_TIMEOUT_SECS: u64 = 10;
+
+/// A CloudEvent in structured JSON form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudEvent {
+    pub specversion: String,
+    pub id: Uuid,
+    pub source: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// Device the event concerns.
+    pub subject: String,
+    pub time: DateTime<Utc>,
+    pub datacontenttype: String,
+    /// The event's WebSocket frame.
+    pub data: serde_json::Value,
+}
+
+impl CloudEvent {
+    /// Wrap `event`, published by `source`.
+    pub fn from_ws(event: &WsEvent, source: &str) -> Self {
+        Self {
+            specversion: SPEC_VERSION.into(),
+            id: Uuid::now_v7(),
+            source: source.into(),
+            event_type: format!("{TYPE_PREFIX}{}", event.event_type()),
+            subject: event.device_id().into(),
+            time: Utc::now(),
+            datacontenttype: "application/json".into(),
+            data: event.to_frame(),
+        }
+    }
+}
+
+/// Why a sink did not take an event.
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    /// Worth retrying (network error, 429, 5xx).
+    #[error("{0}")]
+    Transient(String),
+    /// Refused; retrying would not help.
+    #[error("{0}")]
+    Rejected(String),
+}
+
+/// Destination of exported events.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Short name for logs (e.g. `http`).
+    fn name(&self) -> &str;
+
+    /// Publish one event.
+    async fn publish(&self, event: &CloudEvent) -> Result<(), SinkError>;
+}
+
+/// POSTs each event to a URL in structured mode.
+pub struct HttpSink {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl HttpSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(StdDuration::from_secs(PUBLISH_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for HttpSink {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn publish(&self, event: &CloudEvent) -> Result<(), SinkError> {
+        let body = serde_json::to_vec(event).map_err(|e| SinkError::Rejected(e.to_string()))?;
+        let response = self
+            .http
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| SinkError::Transient(e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status.is_server_error() || status.as_u16() == 429 {
+            Err(SinkError::Transient(format!("HTTP {status}")))
+        } else {
+            Err(SinkError::Rejected(format!("HTTP {status}")))
+        }
+    }
+}
+
+/// Export settings from `CLOUDEVENTS_*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportConfig {
+    pub sink_url: String,
+    pub source: String,
+    /// Event tags to export; empty exports all.
+    pub types: Vec<String>,
+}
+
+/// Publishes broadcast events to a sink.
+#[derive(Clone)]
+pub struct Exporter {
+    sink: Arc<dyn EventSink>,
+    source: String,
+    types: Vec<String>,
+    retry: RetryPolicy,
+}
+
+impl Exporter {
+    pub fn new(
+        sink: Arc<dyn EventSink>,
+        source: String,
+        types: Vec<String>,
+        retry: RetryPolicy,
+    ) -> Self {
+        Self {
+            sink,
+            source,
+            types,
+            retry,
+        }
+    }
+
+    /// Exporter to the configured HTTP sink.
+    pub fn http(config: ExportConfig) -> Self {
+        Self::new(
+            Arc::new(HttpSink::new(config.sink_url)),
+            config.source,
+            config.types,
+            RetryPolicy::default(),
+        )
+    }
+
+    /// Whether `event` is selected for export.
+    pub fn wants(&self, event: &WsEvent) -> bool {
+        self.types.is_empty() || self.types.iter().any(|t| t == event.event_type())
+    }
+
+    /// Publish `event` if selected, retrying transient failures. Returns
+    /// whether the sink took it.
+    pub async fn export(&self, event: &WsEvent) -> bool {
+        if !self.wants(event) {
+            return false;
+        }
+        let cloud_event = CloudEvent::from_ws(event, &self.source);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match self.sink.publish(&cloud_event).await {
+                Ok(()) => return true,
+                Err(SinkError::Transient(e)) if attempts < self.retry.max_attempts => {
+                    tracing::debug!(sink = self.sink.name(), attempts, error = %e, "event export failed; retrying");
+                    tokio::time::sleep(self.retry.delay(attempts)).await;
+                    continue;
+                }
+                Err(e) => e,
+            };
+            tracing::warn!(
+                sink = self.sink.name(),
+                event_id = %cloud_event.id,
+                event_type = %cloud_event.event_type,
+                attempts,
+                error = %error,
+                "event export failed; dropped"
+            );
+            return false;
+        }
+    }
+}
+
+/// Export broadcast events, forever.
+pub async fn run(state: AppState, exporter: Exporter) {
+    let mut rx = state.event_tx.subscribe();
+    loop {
+        match rx.recv().await {
+            Ok(event) => {
+                exporter.export(&event).await;
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "event exporter lagged; events dropped");
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::Router;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+
+    use super::*;
+
+    fn heartbeat(device_id: &str) -> WsEvent {
+        WsEvent::DeviceHeartbeat {
+            device_id: device_id.into(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn offline(device_id: &str) -> WsEvent {
+        WsEvent::DeviceStatusChanged {
+            device_id: device_id.into(),
+            old_status: "online".into(),
+            new_status: "offline".into(),
+            changed_at: Utc::now(),
+        }
+    }
+
+    fn quick_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_delay: StdDuration::from_millis(5),
+            max_delay: StdDuration::from_millis(20),
+        }
+    }
+
+    /// Requests seen by a local receiver: content type and body.
+    type Received = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    /// A local receiver answering 503 to the first `failures` requests.
+    async fn receiver(failures: usize) -> (String, Received) {
+        let received: Received = Arc::default();
+        let seen = received.clone();
+        let app = Router::new().route(
+            "/events",
+            post(move |headers: HeaderMap, body: String| {
+                let seen = seen.clone();
+                async move {
+                    let mut seen = seen.lock().unwrap();
+                    let content_type = headers["content-type"].to_str().unwrap().to_string();
+                    seen.push((content_type, serde_json::from_str(&body).unwrap()));
+                    if seen.len() <= failures {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::ACCEPTED
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}/events"), received)
+    }
+
+    #[test]
+    fn cloud_event_wraps_the_frame() {
+        let event = CloudEvent::from_ws(&offline("rpi-001"), "/fleet-a");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["specversion"], "1.0");
+        assert_eq!(json["type"], "com.zeroclaw.device_status_changed");
+        assert_eq!(json["source"], "/fleet-a");
+        assert_eq!(json["subject"], "rpi-001");
+        assert_eq!(json["datacontenttype"], "application/json");
+        assert_eq!(json["data"]["new_status"], "offline");
+        assert_eq!(
+            json["data"]["schema_version"],
+            crate::events::EVENT_SCHEMA_VERSION
+        );
+    }
+
+    #[tokio::test]
+    async fn http_sink_posts_selected_events_and_retries() {
+        let (url, received) = receiver(1).await;
+        let exporter = Exporter::new(
+            Arc::new(HttpSink::new(url)),
+            DEFAULT_SOURCE.into(),
+            vec!["device_status_changed".into()],
+            quick_retries(3),
+        );
+
+        assert!(!exporter.export(&heartbeat("rpi-001")).await);
+        assert!(exporter.export(&offline("rpi-001")).await);
+
+        let received = received.lock().unwrap();
+        // One 503, then accepted; the heartbeat was never sent.
+        assert_eq!(received.len(), 2);
+        let (content_type, body) = &received[1];
+        assert_eq!(content_type, CONTENT_TYPE);
+        assert_eq!(body["type"], "com.zeroclaw.device_status_changed");
+        assert_eq!(body["id"], received[0].1["id"]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, received) = receiver(usize::MAX).await;
+        let exporter = Exporter::new(
+            Arc::new(HttpSink::new(url)),
+            DEFAULT_SOURCE.into(),
+            Vec::new(),
+            quick_retries(2),
+        );
+        assert!(!exporter.export(&heartbeat("rpi-001")).await);
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+}
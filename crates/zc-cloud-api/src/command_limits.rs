@@ -0,0 +1,207 @@
+//! Per-device and per-initiator rate limits on `POST /api/v1/commands`.
+//!
+//! A dashboard stuck in a retry loop, or a script gone wrong, would
+//! otherwise push commands over a device's cellular uplink as fast as it
+//! can send them. Every device and every initiator gets a
+//! [`TokenBucket`]; a command takes a token from both or, when either is
+//! empty, from neither and is refused with `429 Too Many Requests` and a
+//! `Retry-After` header. The limit is checked once the device is known to
+//! exist and before inference runs, so refused commands cost nothing.
+//!
+//! Buckets live in memory per replica and start full after a restart. Idle
+//! buckets are dropped once [`PRUNE_ABOVE`] keys are tracked. Fleet
+//! broadcasts and scheduled commands are not limited here; the agent's own
+//! limit still applies to them.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use zc_protocol::rate_limit::{RateLimit, TokenBucket};
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Default limit per device: 10 back to back, then one every 2 s.
+pub const DEFAULT_DEVICE_LIMIT: RateLimit = RateLimit::new(10, 30);
+/// Default limit per initiator: 30 back to back, then two a second.
+pub const DEFAULT_INITIATOR_LIMIT: RateLimit = RateLimit::new(30, 120);
+/// Keys tracked per scope before full buckets are forgotten.
+pub const PRUNE_ABOVE: usize = 10_000;
+
+/// Limits applied to command submission; `None` turns a scope off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandLimits {
+    pub per_device: Option<RateLimit>,
+    pub per_initiator: Option<RateLimit>,
+}
+
+/// Which limit refused a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitScope {
+    Device,
+    Initiator,
+}
+
+impl LimitScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Device => "device",
+            Self::Initiator => "initiator",
+        }
+    }
+}
+
+/// A command refused by a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Refusal {
+    pub scope: LimitScope,
+    pub retry_after: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    devices: HashMap<String, TokenBucket>,
+    initiators: HashMap<String, TokenBucket>,
+}
+
+/// Token buckets for [`CommandLimits`]. The default limits nothing.
+#[derive(Debug, Default)]
+pub struct CommandRateLimiter {
+    limits: CommandLimits,
+    buckets: Mutex<Buckets>,
+}
+
+/// The bucket for `key`, created full.
+fn bucket<'a>(
+    buckets: &'a mut HashMap<String, TokenBucket>,
+    key: &str,
+    limit: RateLimit,
+    now: Instant,
+) -> &'a mut TokenBucket {
+    if buckets.len() >= PRUNE_ABOVE && !buckets.contains_key(key) {
+        buckets.retain(|_, b| !b.is_full(now));
+    }
+    buckets
+        .entry(key.to_string())
+        .or_insert_with(|| TokenBucket::new(limit, now))
+}
+
+impl CommandRateLimiter {
+    pub fn new(limits: CommandLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::default(),
+        }
+    }
+
+    pub fn limits(&self) -> CommandLimits {
+        self.limits
+    }
+
+    /// Take a token for a command from `initiator` to `device_id`.
+    pub fn check(&self, device_id: &str, initiator: &str, now: Instant) -> Result<(), Refusal> {
+        let mut guard = self.buckets.lock().unwrap();
+        let Buckets {
+            devices,
+            initiators,
+        } = &mut *guard;
+        let mut device = self
+            .limits
+            .per_device
+            .map(|limit| bucket(devices, device_id, limit, now));
+        let mut user = self
+            .limits
+            .per_initiator
+            .map(|limit| bucket(initiators, initiator, limit, now));
+
+        for (scope, bucket) in [
+            (LimitScope::Device, device.as_deref_mut()),
+            (LimitScope::Initiator, user.as_deref_mut()),
+        ] {
+            if let Some(bucket) = bucket {
+                let retry_after = bucket.wait_time(now);
+                if !retry_after.is_zero() {
+                    return Err(Refusal { scope, retry_after });
+                }
+            }
+        }
+        for bucket in [device, user].into_iter().flatten() {
+            let _ = bucket.try_take(now);
+        }
+        Ok(())
+    }
+}
+
+/// Admit a command from `initiator` to `device_id`, or refuse it with
+/// [`ApiError::TooManyRequests`].
+pub fn admit(state: &AppState, device_id: &str, initiator: &str) -> ApiResult<()> {
+    let Err(refusal) = state
+        .command_limits
+        .check(device_id, initiator, Instant::now())
+    else {
+        return Ok(());
+    };
+    state.metrics.command_throttled(refusal.scope.as_str());
+    let subject = match refusal.scope {
+        LimitScope::Device => format!("device '{device_id}'"),
+        LimitScope::Initiator => format!("'{initiator}'"),
+    };
+    let retry_after_secs = refusal.retry_after.as_secs_f64().ceil() as u64;
+    tracing::warn!(
+        device_id,
+        initiator,
+        scope = refusal.scope.as_str(),
+        retry_after_secs,
+        "command rate limit exceeded"
+    );
+    Err(ApiError::TooManyRequests {
+        message: format!("too many commands for {subject}, retry in {retry_after_secs}s"),
+        retry_after_secs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> CommandRateLimiter {
+        CommandRateLimiter::new(CommandLimits {
+            per_device: Some(RateLimit::new(2, 60)),
+            per_initiator: Some(RateLimit::new(3, 60)),
+        })
+    }
+
+    #[test]
+    fn device_and_initiator_limits_apply_separately() {
+        let limiter = limiter();
+        let now = Instant::now();
+        assert!(limiter.check("rpi-001", "alice", now).is_ok());
+        assert!(limiter.check("rpi-001", "alice", now).is_ok());
+        let refusal = limiter.check("rpi-001", "bob", now).unwrap_err();
+        assert_eq!(refusal.scope, LimitScope::Device);
+        assert_eq!(refusal.retry_after, Duration::from_secs(1));
+
+        // Another device is fine until alice's own bucket runs dry.
+        assert!(limiter.check("rpi-002", "alice", now).is_ok());
+        let refusal = limiter.check("rpi-003", "alice", now).unwrap_err();
+        assert_eq!(refusal.scope, LimitScope::Initiator);
+
+        // A refused command took no token: bob still has his burst.
+        assert!(limiter.check("rpi-003", "bob", now).is_ok());
+        assert!(
+            limiter
+                .check("rpi-001", "bob", now + Duration::from_secs(1))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn default_limiter_admits_everything() {
+        let limiter = CommandRateLimiter::default();
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(limiter.check("rpi-001", "alice", now).is_ok());
+        }
+    }
+}
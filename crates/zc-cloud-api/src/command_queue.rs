@@ -0,0 +1,302 @@
+//! Per-device command queue for offline delivery.
+//!
+//! Commands addressed to a device that is offline (explicitly marked, or
+//! silent for longer than [`OFFLINE_AFTER_SECS`]) are stored with status
+//! `queued` instead of being published. When the device's next heartbeat
+//! arrives — over MQTT or REST — [`flush`] claims the queued envelopes by
+//! moving them to `pending`, then publishes them in creation order. The
+//! claim is atomic, so two heartbeats flushing at once never deliver a
+//! command twice; whatever fails to publish goes back in the queue.
+//!
+//! Envelopes to a device go out in the encoding it advertised in its last
+//! heartbeat ([`device_encoding`]); fleet broadcasts stay JSON so agents of
+//! every version read them.
+
+use chrono::{DateTime, Utc};
+
+use zc_mqtt_channel::{Channel, MessageProperties, MqttResult};
+use zc_protocol::commands::CommandEnvelope;
+use zc_protocol::device::DeviceStatus;
+use zc_protocol::encoding::{self, Encoding};
+
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// A device with no heartbeat for this long is treated as offline
+/// (three missed heartbeats at the agent's default 30s interval).
+pub const OFFLINE_AFTER_SECS: i64 = 90;
+
+/// MQTT v5 message expiry for published commands: a device that comes
+/// back later than this does not receive the stale command from the
+/// broker's session queue. Ignored under MQTT 3.1.1.
+pub const COMMAND_EXPIRY_SECS: u32 = 600;
+
+/// Whether a command can be delivered to the device right now.
+///
+/// Devices that have never sent a heartbeat (freshly provisioned) are
+/// considered reachable — there is no evidence they are offline.
+pub fn is_reachable(status: &DeviceStatus, last_heartbeat: Option<DateTime<Utc>>) -> bool {
+    if *status == DeviceStatus::Offline {
+        return false;
+    }
+    match last_heartbeat {
+        Some(ts) => (Utc::now() - ts).num_seconds() <= OFFLINE_AFTER_SECS,
+        None => true,
+    }
+}
+
+/// MQTT v5 properties for a command: expiry, correlation and command ID.
+pub fn command_properties(envelope: &CommandEnvelope) -> MessageProperties {
+    MessageProperties::correlated(envelope.correlation_id)
+        .with_expiry(COMMAND_EXPIRY_SECS)
+        .with_user_property("command_id", envelope.id.to_string())
+}
+
+/// Encoding `device_id` reads commands in: the most compact it advertised,
+/// JSON until it has.
+pub async fn device_encoding(state: &AppState, device_id: &str) -> Encoding {
+    state
+        .device_encodings
+        .read()
+        .await
+        .get(device_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Remember the encodings `device_id` listed in a heartbeat.
+pub async fn record_encodings(state: &AppState, device_id: &str, accepted: &[Encoding]) {
+    let encoding = Encoding::preferred(accepted);
+    if device_encoding(state, device_id).await != encoding {
+        tracing::info!(device_id, %encoding, "device command encoding changed");
+        state
+            .device_encodings
+            .write()
+            .await
+            .insert(device_id.to_string(), encoding);
+    }
+}
+
+/// Publish a command envelope to the device's command topic.
+pub async fn publish_envelope(
+    mqtt: &dyn Channel,
+    envelope: &CommandEnvelope,
+    encoding: Encoding,
+) -> MqttResult<()> {
+    let topic = zc_protocol::topics::command_request(&envelope.fleet_id, &envelope.device_id);
+    mqtt.publish_with_properties(
+        &topic,
+        &encoding::encode(envelope, encoding).unwrap_or_default(),
+        rumqttc::QoS::AtLeastOnce,
+        &command_properties(envelope),
+    )
+    .await
+}
+
+/// Publish a fleet broadcast envelope to the fleet's broadcast topic.
+pub async fn publish_broadcast(mqtt: &dyn Channel, envelope: &CommandEnvelope) -> MqttResult<()> {
+    let topic = zc_protocol::topics::broadcast_command(&envelope.fleet_id);
+    mqtt.publish_with_properties(
+        &topic,
+        &serde_json::to_vec(envelope).unwrap_or_default(),
+        rumqttc::QoS::AtLeastOnce,
+        &command_properties(envelope),
+    )
+    .await
+}
+
+/// Deliver all queued commands for `device_id`. Returns the number flushed.
+///
+/// No-op when the MQTT bridge is not connected — commands stay queued
+/// until a heartbeat arrives while it is.
+pub async fn flush(state: &AppState, device_id: &str) -> usize {
+    let Some(mqtt) = &state.mqtt else {
+        return 0;
+    };
+
+    let queued = match state.store.claim_queued(device_id).await {
+        Ok(queued) => queued,
+        Err(e) => {
+            tracing::error!(error = %e, device_id = device_id, "failed to claim queued commands");
+            return 0;
+        }
+    };
+
+    let encoding = device_encoding(state, device_id).await;
+    let mut flushed = 0;
+    for envelope in &queued {
+        if let Err(e) = publish_envelope(mqtt.as_ref(), envelope, encoding).await {
+            // Stop here so later commands are not delivered ahead of this one.
+            tracing::warn!(error = %e, command_id = %envelope.id, "failed to flush queued command");
+            let unsent: Vec<_> = queued[flushed..].iter().map(|e| e.id).collect();
+            if let Err(e) = state.store.requeue(&unsent).await {
+                tracing::error!(error = %e, device_id = device_id, "failed to requeue unsent commands");
+            }
+            break;
+        }
+
+        crate::command_timeouts::started(state, envelope).await;
+        crate::command_timing::published(state, envelope.id, Utc::now()).await;
+        flushed += 1;
+    }
+
+    if flushed > 0 {
+        tracing::info!(
+            device_id = device_id,
+            count = flushed,
+            "flushed queued commands"
+        );
+        let _ = state.event_tx.send(WsEvent::CommandQueueFlushed {
+            device_id: device_id.to_string(),
+            count: flushed,
+            flushed_at: Utc::now(),
+        });
+    }
+
+    flushed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use zc_mqtt_channel::MockChannel;
+    use zc_protocol::commands::CommandStatus;
+
+    use crate::state::CommandRecord;
+
+    fn queued_record(device_id: &str, text: &str) -> CommandRecord {
+        CommandRecord {
+            envelope: CommandEnvelope::new("fleet-alpha", device_id, text, "admin"),
+            response: None,
+            status: CommandStatus::Queued,
+            created_at: Utc::now(),
+            deadline: None,
+            attempts: 0,
+            max_attempts: 1,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
+        }
+    }
+
+    #[test]
+    fn offline_status_is_unreachable() {
+        assert!(!is_reachable(&DeviceStatus::Offline, Some(Utc::now())));
+    }
+
+    #[test]
+    fn stale_heartbeat_is_unreachable() {
+        let stale = Utc::now() - chrono::Duration::seconds(OFFLINE_AFTER_SECS + 10);
+        assert!(!is_reachable(&DeviceStatus::Online, Some(stale)));
+    }
+
+    #[test]
+    fn fresh_or_missing_heartbeat_is_reachable() {
+        assert!(is_reachable(&DeviceStatus::Online, Some(Utc::now())));
+        assert!(is_reachable(&DeviceStatus::Provisioning, None));
+    }
+
+    #[tokio::test]
+    async fn flush_publishes_in_order_and_marks_pending() {
+        let mqtt = Arc::new(MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        let mut rx = state.event_tx.subscribe();
+
+        {
+            let mut commands = state.commands.write().await;
+            commands.push(queued_record("rpi-001", "read DTCs"));
+            commands.push(queued_record("rpi-002", "read VIN"));
+            commands.push(queued_record("rpi-001", "read VIN"));
+        }
+
+        let flushed = flush(&state, "rpi-001").await;
+        assert_eq!(flushed, 2);
+
+        let published = mqtt.published_to("fleet/fleet-alpha/rpi-001/command/request");
+        assert_eq!(published.len(), 2);
+        let first: CommandEnvelope = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(first.natural_language, "read DTCs");
+        let properties = &published[0].properties;
+        assert_eq!(properties.expiry_secs, Some(COMMAND_EXPIRY_SECS));
+        assert_eq!(
+            properties.correlation_id,
+            Some(first.correlation_id.to_string())
+        );
+        assert_eq!(
+            properties.user_properties,
+            vec![("command_id".to_string(), first.id.to_string())]
+        );
+
+        let commands = state.commands.read().await;
+        let statuses: Vec<_> = commands.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                CommandStatus::Pending,
+                CommandStatus::Queued,
+                CommandStatus::Pending
+            ]
+        );
+
+        let json = serde_json::to_string(&rx.try_recv().unwrap()).unwrap();
+        assert!(json.contains("command_queue_flushed"));
+    }
+
+    #[tokio::test]
+    async fn concurrent_flushes_deliver_each_command_once() {
+        let mqtt = Arc::new(MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        {
+            let mut commands = state.commands.write().await;
+            commands.push(queued_record("rpi-001", "read DTCs"));
+            commands.push(queued_record("rpi-001", "read VIN"));
+        }
+
+        let (a, b) = tokio::join!(flush(&state, "rpi-001"), flush(&state, "rpi-001"));
+        assert_eq!(a + b, 2);
+        assert_eq!(
+            mqtt.published_to("fleet/fleet-alpha/rpi-001/command/request")
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_flush_requeues_unsent_commands() {
+        let mqtt = Arc::new(MockChannel::new());
+        mqtt.fail_publishes(true);
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        state
+            .commands
+            .write()
+            .await
+            .push(queued_record("rpi-001", "read DTCs"));
+
+        assert_eq!(flush(&state, "rpi-001").await, 0);
+        assert_eq!(state.commands.read().await[0].status, CommandStatus::Queued);
+
+        mqtt.fail_publishes(false);
+        assert_eq!(flush(&state, "rpi-001").await, 1);
+        assert_eq!(
+            state.commands.read().await[0].status,
+            CommandStatus::Pending
+        );
+    }
+
+    #[tokio::test]
+    async fn flush_without_mqtt_keeps_queue() {
+        let state = AppState::with_sample_data();
+        state
+            .commands
+            .write()
+            .await
+            .push(queued_record("rpi-001", "read DTCs"));
+
+        assert_eq!(flush(&state, "rpi-001").await, 0);
+        assert_eq!(state.commands.read().await[0].status, CommandStatus::Queued);
+    }
+}
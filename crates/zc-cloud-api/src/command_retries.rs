@@ -0,0 +1,334 @@
+//! Retry command publishes that fail.
+//!
+//! A command is published to its device once it is accepted (or approved).
+//! If the MQTT publish fails (the bridge is reconnecting, the broker
+//! refuses it) the command would sit `pending` until it times out without
+//! ever reaching the device. [`publish`] counts every attempt on the
+//! command. After a failure it schedules the next attempt with exponential
+//! backoff ([`RetryPolicy::delay`]) and clears the deadline; once the
+//! command's `max_attempts` publishes have failed, it fails the command with
+//! error code `delivery_failed` and handles that like a device response.
+//! [`run`] wakes every [`TICK_SECS`] and publishes the commands whose retry
+//! is due.
+//!
+//! Queued commands are not counted: [`crate::command_queue::flush`] stops
+//! at a failed publish and tries again on the device's next heartbeat.
+//! Commands that failed or timed out can be sent again by hand with
+//! `POST /api/v1/commands/{id}/retry`, which dispatches a copy under a new
+//! ID (agents ignore IDs they have already finished).
+
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use zc_protocol::commands::{
+    CommandEnvelope, CommandResponse, CommandStatus, ErrorCode, InferenceTier,
+};
+
+use crate::command_queue;
+use crate::error::ApiResult;
+use crate::state::AppState;
+
+/// How often due retries are published.
+pub const TICK_SECS: u64 = 5;
+/// Publishes per command unless configured.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// Wait after the first failed publish unless configured.
+pub const DEFAULT_BACKOFF_SECS: u64 = 5;
+/// Longest wait between attempts unless configured.
+pub const DEFAULT_MAX_BACKOFF_SECS: u64 = 300;
+
+/// How often and how far apart failed publishes are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Publishes per command, the first included; 1 disables retries.
+    pub max_attempts: u32,
+    /// Wait after the first failed publish, doubled after each further one.
+    pub backoff: Duration,
+    /// Longest wait between attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff: Duration::seconds(DEFAULT_BACKOFF_SECS as i64),
+            max_backoff: Duration::seconds(DEFAULT_MAX_BACKOFF_SECS as i64),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the next publish after `failed` failed ones.
+    pub fn delay(&self, failed: u32) -> Duration {
+        let factor = 2i32.pow(failed.saturating_sub(1).min(16));
+        (self.backoff * factor).min(self.max_backoff)
+    }
+}
+
+/// Publish a pending command to its device and record the attempt. Returns
+/// whether it was published; without an MQTT bridge nothing is sent or
+/// counted.
+pub async fn publish(state: &AppState, envelope: &CommandEnvelope) -> bool {
+    publish_at(state, envelope, Utc::now()).await
+}
+
+async fn publish_at(state: &AppState, envelope: &CommandEnvelope, now: DateTime<Utc>) -> bool {
+    let Some(mqtt) = &state.mqtt else {
+        return false;
+    };
+    let encoding = command_queue::device_encoding(state, &envelope.device_id).await;
+    let error = match command_queue::publish_envelope(mqtt.as_ref(), envelope, encoding).await {
+        Ok(()) => None,
+        Err(e) => {
+            tracing::error!(error = %e, command_id = %envelope.id, "failed to publish command to mqtt");
+            Some(e.to_string())
+        }
+    };
+    let published = error.is_none();
+    if let Err(e) = attempted(state, envelope, error, now).await {
+        tracing::error!(error = %e, command_id = %envelope.id, "failed to record publish attempt");
+    }
+    if published {
+        crate::command_timing::published(state, envelope.id, now).await;
+    }
+    published
+}
+
+/// Count a publish of `envelope` that failed with `error` (or succeeded)
+/// at `now`: schedule the retry, or fail the command when none is left.
+async fn attempted(
+    state: &AppState,
+    envelope: &CommandEnvelope,
+    error: Option<String>,
+    now: DateTime<Utc>,
+) -> ApiResult<()> {
+    let Some((made, allowed)) = state.store.publish_attempts(envelope.id).await? else {
+        return Ok(());
+    };
+    let made = made + 1;
+    let next_attempt_at = match error {
+        None => None,
+        Some(_) if made < allowed => {
+            let at = now + state.retry_policy.delay(made);
+            tracing::warn!(
+                command_id = %envelope.id,
+                attempt = made,
+                max_attempts = allowed,
+                retry_at = %at,
+                "command publish failed, will retry"
+            );
+            Some(at)
+        }
+        Some(error) => return give_up(state, envelope, made, &error, now).await,
+    };
+
+    state
+        .store
+        .record_attempt(envelope.id, made, next_attempt_at)
+        .await
+}
+
+/// Fail a command whose last allowed publish failed.
+async fn give_up(
+    state: &AppState,
+    envelope: &CommandEnvelope,
+    attempts: u32,
+    error: &str,
+    now: DateTime<Utc>,
+) -> ApiResult<()> {
+    let response = CommandResponse {
+        command_id: envelope.id,
+        correlation_id: envelope.correlation_id,
+        device_id: envelope.device_id.clone(),
+        status: CommandStatus::Failed,
+        inference_tier: InferenceTier::Local,
+        response_text: None,
+        response_data: None,
+        latency_ms: (now - envelope.created_at).num_milliseconds().max(0) as u64,
+        responded_at: now,
+        error: Some(format!("not delivered after {attempts} attempts: {error}")),
+        error_code: Some(ErrorCode::DeliveryFailed),
+        cached: false,
+    };
+
+    state.store.fail_undelivered(&response, attempts).await?;
+
+    tracing::error!(
+        command_id = %envelope.id,
+        device_id = %envelope.device_id,
+        attempts,
+        "command not delivered, giving up"
+    );
+    crate::command_timeouts::unanswered(
+        state,
+        &envelope.fleet_id,
+        envelope.parsed_intent.as_ref(),
+        &response,
+    )
+    .await;
+    Ok(())
+}
+
+/// Publish every pending command whose retry is due at `now`; returns the
+/// IDs of those that went out.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<Vec<Uuid>> {
+    if state.mqtt.is_none() {
+        return Ok(Vec::new());
+    }
+    let due = state.store.claim_due_attempts(now).await?;
+
+    let mut published = Vec::new();
+    for envelope in &due {
+        if publish_at(state, envelope, now).await {
+            crate::command_timeouts::started(state, envelope).await;
+            published.push(envelope.id);
+        }
+    }
+    if !published.is_empty() {
+        tracing::info!(count = published.len(), "retried command publishes");
+    }
+    Ok(published)
+}
+
+/// Publish due retries every [`TICK_SECS`], forever.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state, Utc::now()).await {
+            tracing::error!(error = %e, "command retry sweep failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use zc_mqtt_channel::MockChannel;
+
+    use crate::state::CommandRecord;
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        let secs: Vec<i64> = (1..=8).map(|n| policy.delay(n).num_seconds()).collect();
+        assert_eq!(secs, [5, 10, 20, 40, 80, 160, 300, 300]);
+        assert_eq!(policy.delay(u32::MAX), policy.max_backoff);
+    }
+
+    #[tokio::test]
+    async fn failed_publishes_back_off_then_fail_the_command() {
+        let mut state = AppState::with_sample_data();
+        let mqtt = Arc::new(MockChannel::new());
+        state.mqtt = Some(mqtt.clone());
+        state.retry_policy.max_attempts = 3;
+        let mut rx = state.event_tx.subscribe();
+
+        let envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let now = Utc::now();
+        state.commands.write().await.push(CommandRecord {
+            envelope: envelope.clone(),
+            response: None,
+            status: CommandStatus::Pending,
+            created_at: now,
+            deadline: Some(crate::command_timeouts::deadline(&envelope, now)),
+            attempts: 0,
+            max_attempts: 3,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
+        });
+        let record = |state: &AppState| {
+            let commands = state.commands.clone();
+            async move { commands.read().await.last().unwrap().clone() }
+        };
+
+        mqtt.fail_publishes(true);
+        assert!(!publish(&state, &envelope).await);
+        let first = record(&state).await;
+        assert_eq!(first.attempts, 1);
+        assert_eq!(first.deadline, None);
+        let retry_at = first.next_attempt_at.unwrap();
+
+        // Not due yet.
+        assert!(
+            tick(&state, retry_at - Duration::seconds(1))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(tick(&state, retry_at).await.unwrap().is_empty());
+        let second = record(&state).await;
+        assert_eq!(second.attempts, 2);
+        assert_eq!(second.status, CommandStatus::Pending);
+        let retry_at = second.next_attempt_at.unwrap();
+        assert_eq!(
+            retry_at - first.next_attempt_at.unwrap(),
+            Duration::seconds(10)
+        );
+
+        assert!(tick(&state, retry_at).await.unwrap().is_empty());
+        let last = record(&state).await;
+        assert_eq!(last.attempts, 3);
+        assert_eq!(last.status, CommandStatus::Failed);
+        assert_eq!(last.next_attempt_at, None);
+        let response = last.response.unwrap();
+        assert_eq!(response.error_code, Some(ErrorCode::DeliveryFailed));
+        assert!(
+            response
+                .error
+                .unwrap()
+                .starts_with("not delivered after 3 attempts")
+        );
+
+        let mut failed = None;
+        while let Ok(event) = rx.try_recv() {
+            if let crate::events::WsEvent::CommandResponse {
+                status, error_code, ..
+            } = event
+            {
+                failed = Some((status, error_code));
+            }
+        }
+        assert_eq!(
+            failed,
+            Some(("failed".to_string(), Some("delivery_failed".to_string())))
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_that_goes_through_restarts_the_clock() {
+        let mut state = AppState::with_sample_data();
+        let mqtt = Arc::new(MockChannel::new());
+        state.mqtt = Some(mqtt.clone());
+
+        let envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let now = Utc::now();
+        state.commands.write().await.push(CommandRecord {
+            envelope: envelope.clone(),
+            response: None,
+            status: CommandStatus::Pending,
+            created_at: now,
+            deadline: None,
+            attempts: 1,
+            max_attempts: 5,
+            next_attempt_at: Some(now),
+            idempotency_key: None,
+            timing: Default::default(),
+        });
+
+        assert_eq!(tick(&state, now).await.unwrap(), [envelope.id]);
+        assert_eq!(mqtt.published().len(), 1);
+        let commands = state.commands.read().await;
+        let record = commands.last().unwrap();
+        assert_eq!(record.attempts, 2);
+        assert_eq!(record.next_attempt_at, None);
+        assert!(record.deadline.is_some());
+        assert_eq!(record.status, CommandStatus::Pending);
+    }
+}
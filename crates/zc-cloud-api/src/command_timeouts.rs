@@ -0,0 +1,318 @@
+//! Time out commands the device never answered.
+//!
+//! The agent stops a command at its envelope's `timeout_secs` and answers
+//! `timeout` itself, but a device that loses power or its connection never
+//! answers at all. A command gets a deadline when it is published (status
+//! `pending`): `timeout_secs` plus [`GRACE_SECS`] for the round trip and
+//! local inference. [`run`] wakes every [`TICK_SECS`] and marks commands
+//! still pending past their deadline as `timeout` (error code `timeout`),
+//! then handles them like a device response: failure rates, experiment
+//! outcomes, `command_error` alert rules, metrics and a
+//! `WsEvent::CommandResponse` broadcast.
+//!
+//! Queued and held commands have no deadline until they are flushed or
+//! approved. A response that arrives after the deadline still replaces the
+//! timeout. In database mode the update is conditional on the command still
+//! being pending, so replicas sharing a database do not time it out twice.
+
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use zc_protocol::commands::{
+    CommandEnvelope, CommandResponse, CommandStatus, ErrorCode, InferenceTier, ParsedIntent,
+};
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// How often pending commands are checked.
+pub const TICK_SECS: u64 = 10;
+/// Allowance beyond `timeout_secs` for the MQTT round trip and inference.
+pub const GRACE_SECS: i64 = 30;
+/// Timeout assumed for an envelope without one (`timeout_secs = 0`); the
+/// agent's default `[command_timeouts] max_secs`.
+pub const UNSET_TIMEOUT_SECS: i64 = 300;
+
+/// When `envelope`, published at `published_at`, times out.
+pub fn deadline(envelope: &CommandEnvelope, published_at: DateTime<Utc>) -> DateTime<Utc> {
+    let timeout_secs = match i64::from(envelope.timeout_secs) {
+        0 => UNSET_TIMEOUT_SECS,
+        secs => secs,
+    };
+    published_at + Duration::seconds(timeout_secs + GRACE_SECS)
+}
+
+/// Start the clock on a command that has just been published to its device
+/// (a queued command flushed, a held command approved).
+pub async fn started(state: &AppState, envelope: &CommandEnvelope) {
+    let deadline = deadline(envelope, Utc::now());
+    if let Some(pool) = &state.pool {
+        if let Err(e) = crate::db::commands::set_deadline(pool, envelope.id, deadline).await {
+            tracing::error!(error = %e, command_id = %envelope.id, "failed to set command deadline");
+        }
+        return;
+    }
+    let mut commands = state.commands.write().await;
+    if let Some(record) = commands.iter_mut().find(|r| r.envelope.id == envelope.id) {
+        record.deadline = Some(deadline);
+    }
+}
+
+/// A command that timed out, with what its response handling needs.
+struct Overdue {
+    fleet_id: String,
+    intent: Option<ParsedIntent>,
+    response: CommandResponse,
+}
+
+fn timeout_response(
+    envelope_id: Uuid,
+    correlation_id: Uuid,
+    device_id: &str,
+    timeout_secs: i64,
+    inference_tier: InferenceTier,
+    created_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> CommandResponse {
+    CommandResponse {
+        command_id: envelope_id,
+        correlation_id,
+        device_id: device_id.to_string(),
+        status: CommandStatus::Timeout,
+        inference_tier,
+        response_text: None,
+        response_data: None,
+        latency_ms: (now - created_at).num_milliseconds().max(0) as u64,
+        responded_at: now,
+        error: Some(format!("no response within {timeout_secs}s")),
+        error_code: Some(ErrorCode::Timeout),
+        cached: false,
+    }
+}
+
+/// Mark every command still pending past its deadline at `now` as timed
+/// out; returns the responses recorded for them.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<Vec<CommandResponse>> {
+    let mut overdue = Vec::new();
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::commands::time_out_overdue(pool, now)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        for row in rows {
+            let tier = row
+                .inference_tier
+                .as_deref()
+                .and_then(|t| serde_json::from_value(serde_json::json!(t)).ok())
+                .unwrap_or(InferenceTier::Local);
+            let intent = row
+                .envelope
+                .and_then(|v| serde_json::from_value::<CommandEnvelope>(v).ok())
+                .and_then(|e| e.parsed_intent);
+            let response = timeout_response(
+                row.id,
+                row.correlation_id,
+                &row.device_id,
+                i64::from(row.timeout_secs),
+                tier,
+                row.created_at,
+                now,
+            );
+            overdue.push(Overdue {
+                fleet_id: row.fleet_id,
+                intent,
+                response,
+            });
+        }
+    } else {
+        let mut commands = state.commands.write().await;
+        for record in commands
+            .iter_mut()
+            .filter(|r| r.status == CommandStatus::Pending && r.deadline.is_some_and(|d| d <= now))
+        {
+            let envelope = &record.envelope;
+            let response = timeout_response(
+                envelope.id,
+                envelope.correlation_id,
+                &envelope.device_id,
+                i64::from(envelope.timeout_secs),
+                InferenceTier::Local,
+                record.created_at,
+                now,
+            );
+            record.status = CommandStatus::Timeout;
+            record.response = Some(response.clone());
+            overdue.push(Overdue {
+                fleet_id: envelope.fleet_id.clone(),
+                intent: envelope.parsed_intent.clone(),
+                response,
+            });
+        }
+    }
+
+    for Overdue {
+        fleet_id,
+        intent,
+        response,
+    } in &overdue
+    {
+        tracing::warn!(
+            command_id = %response.command_id,
+            device_id = %response.device_id,
+            "command timed out without a response"
+        );
+        unanswered(state, fleet_id, intent.as_ref(), response).await;
+    }
+    Ok(overdue.into_iter().map(|o| o.response).collect())
+}
+
+/// Handle a final response the cloud recorded itself (no device answer)
+/// like one from the device: experiment outcome, failure rates,
+/// `command_error` alert rules, metrics and a `WsEvent::CommandResponse`.
+pub(crate) async fn unanswered(
+    state: &AppState,
+    fleet_id: &str,
+    intent: Option<&ParsedIntent>,
+    response: &CommandResponse,
+) {
+    crate::experiments::record_outcome(state, response.command_id, response.status).await;
+    crate::failure_rates::record(
+        state,
+        fleet_id,
+        response.command_id,
+        &response.device_id,
+        intent,
+        response.status,
+        response.error.as_deref(),
+    )
+    .await;
+    crate::alert_rules::command_responded(state, fleet_id, response).await;
+    let status = crate::store::command_status_name(response.status);
+    state.metrics.command_status(&status);
+    let _ = state.event_tx.send(WsEvent::CommandResponse {
+        command_id: response.command_id,
+        device_id: response.device_id.clone(),
+        status,
+        inference_tier: None,
+        response_text: None,
+        response_data: None,
+        error: response.error.clone(),
+        error_code: response.error_code.map(|c| c.as_str().to_string()),
+        latency_ms: Some(response.latency_ms as i64),
+        responded_at: response.responded_at,
+    });
+}
+
+/// Check pending commands every [`TICK_SECS`], forever.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state, Utc::now()).await {
+            tracing::error!(error = %e, "command timeout sweep failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::CommandRecord;
+
+    #[test]
+    fn deadline_adds_grace_to_timeout() {
+        let now = Utc::now();
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        envelope.timeout_secs = 30;
+        assert_eq!(deadline(&envelope, now), now + Duration::seconds(60));
+        envelope.timeout_secs = 0;
+        assert_eq!(deadline(&envelope, now), now + Duration::seconds(330));
+    }
+
+    #[tokio::test]
+    async fn tick_times_out_overdue_pending_commands() {
+        let state = AppState::with_sample_data();
+        let mut rx = state.event_tx.subscribe();
+        let now = Utc::now();
+        let overdue = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let running = CommandEnvelope::new("fleet-alpha", "rpi-001", "read VIN", "admin");
+        let queued = CommandEnvelope::new("fleet-alpha", "rpi-002", "read VIN", "admin");
+        {
+            let mut commands = state.commands.write().await;
+            for (envelope, status, deadline) in [
+                (
+                    &overdue,
+                    CommandStatus::Pending,
+                    Some(now - Duration::seconds(1)),
+                ),
+                (
+                    &running,
+                    CommandStatus::Pending,
+                    Some(now + Duration::seconds(20)),
+                ),
+                (&queued, CommandStatus::Queued, None),
+            ] {
+                commands.push(CommandRecord {
+                    envelope: envelope.clone(),
+                    response: None,
+                    status,
+                    created_at: now - Duration::seconds(70),
+                    deadline,
+                    attempts: 0,
+                    max_attempts: 1,
+                    next_attempt_at: None,
+                    idempotency_key: None,
+                    timing: Default::default(),
+                });
+            }
+        }
+
+        let timed_out = tick(&state, now).await.unwrap();
+        assert_eq!(timed_out.len(), 1);
+        assert_eq!(timed_out[0].command_id, overdue.id);
+        assert_eq!(timed_out[0].error_code, Some(ErrorCode::Timeout));
+        assert_eq!(
+            timed_out[0].error.as_deref(),
+            Some("no response within 30s")
+        );
+
+        let commands = state.commands.read().await;
+        let statuses: Vec<_> = commands.iter().rev().take(3).map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                CommandStatus::Queued,
+                CommandStatus::Pending,
+                CommandStatus::Timeout
+            ]
+        );
+        drop(commands);
+
+        let mut broadcast = None;
+        while let Ok(event) = rx.try_recv() {
+            if let WsEvent::CommandResponse {
+                command_id,
+                status,
+                error_code,
+                ..
+            } = event
+            {
+                broadcast = Some((command_id, status, error_code));
+            }
+        }
+        assert_eq!(
+            broadcast,
+            Some((
+                overdue.id,
+                "timeout".to_string(),
+                Some("timeout".to_string())
+            ))
+        );
+
+        // Already timed out: the next sweep leaves it alone.
+        assert!(tick(&state, now).await.unwrap().is_empty());
+    }
+}
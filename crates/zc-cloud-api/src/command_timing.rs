@@ -0,0 +1,239 @@
+//! Where a command's time went, measured without mixing clocks.
+//!
+//! The cloud and each device keep their own wall clocks, which can be
+//! seconds (or, without NTP, hours) apart. Durations are therefore taken
+//! from one clock each:
+//!
+//! - `queue_ms`: cloud `created_at` to the successful publish
+//!   (`published_at`), covering offline queueing, approval and publish
+//!   retries.
+//! - `execution_ms`: the device's `latency_ms`, measured on its monotonic
+//!   clock from receipt to response.
+//! - `transport_ms`: cloud `published_at` to cloud `received_at`, less the
+//!   execution time: both MQTT legs plus any wait in the agent's inbox.
+//!
+//! Splitting the transport into `outbound_ms` and `inbound_ms` needs the
+//! device's `responded_at`, so it uses the clock offset estimated from
+//! heartbeats ([`heartbeat_received`]). Each heartbeat gives
+//! `timestamp - received` = offset minus that message's transit time; the
+//! largest of the last [`SKEW_SAMPLES`] is the estimate with the least
+//! transit in it. Estimates are kept in memory per replica, like the
+//! device encodings, and copied onto a command when its response arrives.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::state::AppState;
+
+/// Heartbeats the clock offset of a device is estimated from.
+pub const SKEW_SAMPLES: usize = 10;
+
+/// Recent heartbeat clock offsets of one device.
+#[derive(Debug, Clone, Default)]
+pub struct ClockSkew {
+    samples: VecDeque<i64>,
+}
+
+impl ClockSkew {
+    /// Add the offset (device minus cloud, in ms) seen on one heartbeat.
+    pub fn record(&mut self, offset_ms: i64) {
+        if self.samples.len() == SKEW_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(offset_ms);
+    }
+
+    /// How far the device clock is ahead of the cloud's (negative when
+    /// behind); `None` before the first heartbeat.
+    pub fn estimate(&self) -> Option<i64> {
+        self.samples.iter().copied().max()
+    }
+}
+
+/// Cloud-clock timestamps kept on a command.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandTiming {
+    /// When the command was last published to its device.
+    pub published_at: Option<DateTime<Utc>>,
+    /// When its response reached the cloud.
+    pub received_at: Option<DateTime<Utc>>,
+    /// The device's clock offset when the response arrived.
+    pub clock_offset_ms: Option<i64>,
+}
+
+/// How a response was received, stored alongside it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Receipt {
+    /// Cloud time the response arrived.
+    pub received_at: DateTime<Utc>,
+    /// The device's clock offset at that time, if known.
+    pub clock_offset_ms: Option<i64>,
+}
+
+impl Receipt {
+    /// A response from `device_id` arriving now.
+    pub async fn now(state: &AppState, device_id: &str) -> Self {
+        Self {
+            received_at: Utc::now(),
+            clock_offset_ms: clock_offset(state, device_id).await,
+        }
+    }
+}
+
+/// Per-command timing reported by `GET /api/v1/commands/{id}`. Durations
+/// are absent until the timestamps they need are known.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Breakdown {
+    pub created_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub received_at: Option<DateTime<Utc>>,
+    /// `responded_at` as reported, on the device's clock.
+    pub device_responded_at: Option<DateTime<Utc>>,
+    pub clock_offset_ms: Option<i64>,
+    pub queue_ms: Option<i64>,
+    pub execution_ms: Option<i64>,
+    pub transport_ms: Option<i64>,
+    pub outbound_ms: Option<i64>,
+    pub inbound_ms: Option<i64>,
+    /// Cloud `created_at` to `received_at`.
+    pub total_ms: Option<i64>,
+}
+
+impl Breakdown {
+    /// Work out the durations from a command's timestamps and the
+    /// device-measured `execution_ms`.
+    pub fn new(
+        created_at: DateTime<Utc>,
+        timing: &CommandTiming,
+        device_responded_at: Option<DateTime<Utc>>,
+        execution_ms: Option<i64>,
+    ) -> Self {
+        let ms = |from: DateTime<Utc>, to: DateTime<Utc>| (to - from).num_milliseconds().max(0);
+        let transport_ms = match (timing.published_at, timing.received_at, execution_ms) {
+            (Some(published), Some(received), Some(execution)) => {
+                Some((ms(published, received) - execution).max(0))
+            }
+            _ => None,
+        };
+        let inbound_ms = match (
+            transport_ms,
+            timing.received_at,
+            device_responded_at,
+            timing.clock_offset_ms,
+        ) {
+            (Some(transport), Some(received), Some(responded), Some(offset)) => {
+                let responded = responded - chrono::Duration::milliseconds(offset);
+                Some(
+                    (received - responded)
+                        .num_milliseconds()
+                        .clamp(0, transport),
+                )
+            }
+            _ => None,
+        };
+        Self {
+            created_at,
+            published_at: timing.published_at,
+            received_at: timing.received_at,
+            device_responded_at,
+            clock_offset_ms: timing.clock_offset_ms,
+            queue_ms: timing.published_at.map(|at| ms(created_at, at)),
+            execution_ms,
+            transport_ms,
+            outbound_ms: transport_ms.zip(inbound_ms).map(|(t, i)| t - i),
+            inbound_ms,
+            total_ms: timing.received_at.map(|at| ms(created_at, at)),
+        }
+    }
+}
+
+/// Add a heartbeat the device stamped `sent_at` and the cloud received at
+/// `received_at` to the device's clock offset estimate.
+pub async fn heartbeat_received(
+    state: &AppState,
+    device_id: &str,
+    sent_at: DateTime<Utc>,
+    received_at: DateTime<Utc>,
+) {
+    let offset_ms = (sent_at - received_at).num_milliseconds();
+    state
+        .clock_skew
+        .write()
+        .await
+        .entry(device_id.to_string())
+        .or_default()
+        .record(offset_ms);
+}
+
+/// Current clock offset estimate of `device_id`.
+pub async fn clock_offset(state: &AppState, device_id: &str) -> Option<i64> {
+    state
+        .clock_skew
+        .read()
+        .await
+        .get(device_id)
+        .and_then(ClockSkew::estimate)
+}
+
+/// Record that a command was published to its device at `at`.
+pub async fn published(state: &AppState, command_id: Uuid, at: DateTime<Utc>) {
+    if let Err(e) = state.store.set_published(command_id, at).await {
+        tracing::error!(error = %e, command_id = %command_id, "failed to record command publish time");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn skew_estimate_prefers_the_fastest_heartbeat() {
+        let mut skew = ClockSkew::default();
+        assert_eq!(skew.estimate(), None);
+        // Device 2 s ahead; transits of 300, 40 and 900 ms.
+        for offset in [1_700, 1_960, 1_100] {
+            skew.record(offset);
+        }
+        assert_eq!(skew.estimate(), Some(1_960));
+        for _ in 0..SKEW_SAMPLES {
+            skew.record(-500);
+        }
+        assert_eq!(skew.estimate(), Some(-500));
+    }
+
+    #[test]
+    fn breakdown_splits_queue_execution_and_transport() {
+        let created = Utc::now();
+        let published = created + Duration::milliseconds(2_000);
+        let received = published + Duration::milliseconds(1_500);
+        // Device clock 10 s ahead; the response took 200 ms to arrive.
+        let responded = received - Duration::milliseconds(200) + Duration::seconds(10);
+        let timing = CommandTiming {
+            published_at: Some(published),
+            received_at: Some(received),
+            clock_offset_ms: Some(10_000),
+        };
+
+        let breakdown = Breakdown::new(created, &timing, Some(responded), Some(1_000));
+        assert_eq!(breakdown.queue_ms, Some(2_000));
+        assert_eq!(breakdown.execution_ms, Some(1_000));
+        assert_eq!(breakdown.transport_ms, Some(500));
+        assert_eq!(breakdown.inbound_ms, Some(200));
+        assert_eq!(breakdown.outbound_ms, Some(300));
+        assert_eq!(breakdown.total_ms, Some(3_500));
+
+        // Without an offset the legs are unknown, the rest is not.
+        let timing = CommandTiming {
+            clock_offset_ms: None,
+            ..timing
+        };
+        let breakdown = Breakdown::new(created, &timing, Some(responded), Some(1_000));
+        assert_eq!(breakdown.transport_ms, Some(500));
+        assert_eq!(breakdown.inbound_ms, None);
+        assert_eq!(breakdown.outbound_ms, None);
+    }
+}
@@ -2,6 +2,20 @@
 
 use serde::Deserialize;
 
+use zc_protocol::rate_limit::RateLimit;
+
+use crate::approval::ApprovalPolicy;
+use crate::auth::{OidcConfig, Role};
+use crate::certificates::{CertificateAuthority, DeviceEndpoint};
+use crate::cloudevents::ExportConfig;
+use crate::command_limits::CommandLimits;
+use crate::command_retries::RetryPolicy;
+use crate::device_status::StatusThresholds;
+use crate::failure_rates::FailureThresholds;
+use crate::mqtt_bridge::FleetFilter;
+use crate::retention::RetentionPolicy;
+use crate::structured_mode::StructuredMode;
+
 /// Top-level API server configuration.
 #[derive(Debug, Clone, Deserialize)]
 pub struct ApiConfig {
@@ -32,18 +46,147 @@ pub struct ApiConfig {
     /// MQTT broker port (MQTT_BROKER_PORT, default 1883).
     #[serde(default = "default_mqtt_port")]
     pub mqtt_broker_port: u16,
-    /// Fleet ID for MQTT topic routing (MQTT_FLEET_ID, required when mqtt_enabled).
+    /// Fleets the MQTT bridge monitors (MQTT_FLEET_ID, required when
+    /// mqtt_enabled): one fleet ID, a comma-separated list, or `*` for all.
     #[serde(default)]
     pub mqtt_fleet_id: String,
     /// Use TLS for MQTT (MQTT_USE_TLS, default false — local mosquitto).
     #[serde(default)]
     pub mqtt_use_tls: bool,
+    /// MQTT protocol version (MQTT_PROTOCOL, `v311` or `v5`, default
+    /// `v311`). v5 adds command expiry and correlation user properties.
+    #[serde(default)]
+    pub mqtt_protocol: zc_mqtt_channel::MqttProtocol,
     /// Path to CA certificate for MQTT TLS (MQTT_CA_CERT).
     pub mqtt_ca_cert: Option<String>,
     /// Path to client certificate for MQTT mTLS (MQTT_CLIENT_CERT).
     pub mqtt_client_cert: Option<String>,
     /// Path to client private key for MQTT mTLS (MQTT_CLIENT_KEY).
     pub mqtt_client_key: Option<String>,
+    /// Operators allowed to approve high-risk commands (APPROVERS,
+    /// comma-separated). Empty disables two-person approval.
+    #[serde(default)]
+    pub approvers: Vec<String>,
+    /// Seconds a high-risk command waits for approval (APPROVAL_TTL_SECS, default 900).
+    #[serde(default = "default_approval_ttl_secs")]
+    pub approval_ttl_secs: u64,
+    /// Tools that need approval (APPROVAL_TOOLS, comma-separated;
+    /// defaults to `clear_dtcs,send_frame`).
+    #[serde(default = "default_approval_tools")]
+    pub approval_tools: Vec<String>,
+    /// OIDC issuer URL (OIDC_ISSUER). Setting it turns on bearer-token auth.
+    pub oidc_issuer: Option<String>,
+    /// Accepted token audiences (OIDC_AUDIENCE, comma-separated; empty accepts any).
+    #[serde(default)]
+    pub oidc_audiences: Vec<String>,
+    /// JWKS URL (OIDC_JWKS_URL); discovered from the issuer when unset.
+    pub oidc_jwks_url: Option<String>,
+    /// Claim with the user's name (OIDC_USERNAME_CLAIM, default `preferred_username`).
+    pub oidc_username_claim: Option<String>,
+    /// Claim with role or group names (OIDC_ROLES_CLAIM, default `roles`;
+    /// dotted paths such as `realm_access.roles` allowed).
+    pub oidc_roles_claim: Option<String>,
+    /// Claim value → role mapping (OIDC_ROLE_MAP, `group=role,...`).
+    #[serde(default)]
+    pub oidc_role_map: Vec<String>,
+    /// Role for users without a mapped claim value (OIDC_DEFAULT_ROLE).
+    pub oidc_default_role: Option<String>,
+    /// Claim listing the fleets a user may access (OIDC_TENANT_CLAIM).
+    pub oidc_tenant_claim: Option<String>,
+    /// Tolerance for token timestamps (OIDC_CLOCK_SKEW_SECS, default 60).
+    #[serde(default = "default_oidc_clock_skew_secs")]
+    pub oidc_clock_skew_secs: u64,
+    /// How long fetched signing keys are cached (OIDC_JWKS_TTL_SECS, default 3600).
+    #[serde(default = "default_oidc_jwks_ttl_secs")]
+    pub oidc_jwks_ttl_secs: u64,
+    /// Bearer token of the super-admin, who registers fleets and issues
+    /// their tokens (SUPER_ADMIN_TOKEN). Setting it also turns on auth.
+    pub super_admin_token: Option<String>,
+    /// Tool failure rate (0–1) that raises an anomaly alert
+    /// (FAILURE_RATE_THRESHOLD, default 0.5).
+    #[serde(default = "default_failure_rate_threshold")]
+    pub failure_rate_threshold: f64,
+    /// Per-tool thresholds (FAILURE_RATE_TOOL_THRESHOLDS, `tool=rate,...`).
+    #[serde(default)]
+    pub failure_rate_tool_thresholds: Vec<String>,
+    /// Outcomes needed before a window can alert (FAILURE_RATE_MIN_SAMPLES, default 5).
+    #[serde(default = "default_failure_rate_min_samples")]
+    pub failure_rate_min_samples: usize,
+    /// Rolling window length (FAILURE_RATE_WINDOW_SECS, default 3600).
+    #[serde(default = "default_failure_rate_window_secs")]
+    pub failure_rate_window_secs: u64,
+    /// Fleets that only accept structured `tool_name` / `tool_args` commands
+    /// (STRUCTURED_ONLY_FLEETS: comma-separated, or `*` for all).
+    #[serde(default)]
+    pub structured_only_fleets: String,
+    /// Heartbeat age after which an online device is degraded
+    /// (DEVICE_DEGRADED_AFTER_SECS, default 90).
+    #[serde(default = "default_device_degraded_after_secs")]
+    pub device_degraded_after_secs: u64,
+    /// Heartbeat age after which a device is offline
+    /// (DEVICE_OFFLINE_AFTER_SECS, default 300).
+    #[serde(default = "default_device_offline_after_secs")]
+    pub device_offline_after_secs: u64,
+    /// Commands per device (COMMAND_RATE_LIMIT_DEVICE, `burst/per_minute`,
+    /// default `10/30`; `off` disables).
+    #[serde(default = "default_command_rate_limit_device")]
+    pub command_rate_limit_device: String,
+    /// Commands per initiator (COMMAND_RATE_LIMIT_INITIATOR, default
+    /// `30/120`; `off` disables).
+    #[serde(default = "default_command_rate_limit_initiator")]
+    pub command_rate_limit_initiator: String,
+    /// Publishes per command before it fails with `delivery_failed`
+    /// (COMMAND_RETRY_MAX_ATTEMPTS, default 5; 1 disables retries).
+    #[serde(default = "default_command_retry_max_attempts")]
+    pub command_retry_max_attempts: u32,
+    /// Wait after the first failed publish, doubled after each further one
+    /// (COMMAND_RETRY_BACKOFF_SECS, default 5).
+    #[serde(default = "default_command_retry_backoff_secs")]
+    pub command_retry_backoff_secs: u64,
+    /// Longest wait between publish attempts
+    /// (COMMAND_RETRY_MAX_BACKOFF_SECS, default 300).
+    #[serde(default = "default_command_retry_max_backoff_secs")]
+    pub command_retry_max_backoff_secs: u64,
+    /// CA certificate that signs device certificates (PROVISIONING_CA_CERT).
+    /// Certificate provisioning is off unless it and the key are set.
+    pub provisioning_ca_cert: Option<String>,
+    /// Private key of the provisioning CA (PROVISIONING_CA_KEY).
+    pub provisioning_ca_key: Option<String>,
+    /// Validity of issued device certificates (DEVICE_CERT_VALIDITY_DAYS,
+    /// default 365).
+    #[serde(default = "default_device_cert_validity_days")]
+    pub device_cert_validity_days: u32,
+    /// Broker host handed to devices with their certificate
+    /// (DEVICE_MQTT_HOST, default `mqtt_broker_host`).
+    pub device_mqtt_host: Option<String>,
+    /// Broker port handed to devices (DEVICE_MQTT_PORT, default 8883).
+    #[serde(default = "default_device_mqtt_port")]
+    pub device_mqtt_port: u16,
+    /// How long before a device certificate expires it is alerted on
+    /// (CERT_EXPIRY_WARNING_DAYS, default 30).
+    #[serde(default = "default_cert_expiry_warning_days")]
+    pub cert_expiry_warning_days: u32,
+    /// Store telemetry and heartbeats in TimescaleDB hypertables with
+    /// telemetry rollups (TIMESCALEDB_ENABLED). Needs the extension.
+    #[serde(default)]
+    pub timescaledb_enabled: bool,
+    /// Days of telemetry kept (TELEMETRY_RETENTION_DAYS); unset keeps all.
+    pub telemetry_retention_days: Option<u32>,
+    /// Days of logged heartbeats kept (HEARTBEAT_RETENTION_DAYS); unset
+    /// keeps all.
+    pub heartbeat_retention_days: Option<u32>,
+    /// Publish events as CloudEvents to this HTTP endpoint
+    /// (CLOUDEVENTS_SINK_URL); unset disables the export.
+    pub cloudevents_sink_url: Option<String>,
+    /// CloudEvent `source` attribute (CLOUDEVENTS_SOURCE, default
+    /// `/zeroclaw/cloud-api`).
+    pub cloudevents_source: Option<String>,
+    /// Comma-separated event types to export (CLOUDEVENTS_TYPES, e.g.
+    /// `command_response,device_status_changed`); empty exports all.
+    #[serde(default)]
+    pub cloudevents_types: Vec<String>,
+    /// Port of the gRPC API (GRPC_PORT); unset disables it.
+    pub grpc_port: Option<u16>,
 }
 
 fn default_host() -> String {
@@ -66,6 +209,87 @@ fn default_mqtt_port() -> u16 {
     1883
 }
 
+fn default_approval_ttl_secs() -> u64 {
+    900
+}
+
+fn default_approval_tools() -> Vec<String> {
+    crate::approval::DEFAULT_HIGH_RISK_TOOLS
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn default_oidc_clock_skew_secs() -> u64 {
+    crate::auth::DEFAULT_CLOCK_SKEW_SECS
+}
+
+fn default_oidc_jwks_ttl_secs() -> u64 {
+    crate::auth::DEFAULT_JWKS_TTL_SECS
+}
+
+fn default_failure_rate_threshold() -> f64 {
+    crate::failure_rates::DEFAULT_FAILURE_RATE
+}
+
+fn default_failure_rate_min_samples() -> usize {
+    crate::failure_rates::DEFAULT_MIN_SAMPLES
+}
+
+fn default_failure_rate_window_secs() -> u64 {
+    crate::failure_rates::DEFAULT_WINDOW_SECS
+}
+
+fn default_device_degraded_after_secs() -> u64 {
+    crate::device_status::DEFAULT_DEGRADED_AFTER_SECS
+}
+
+fn default_device_offline_after_secs() -> u64 {
+    crate::device_status::DEFAULT_OFFLINE_AFTER_SECS
+}
+
+fn default_command_rate_limit_device() -> String {
+    crate::command_limits::DEFAULT_DEVICE_LIMIT.to_string()
+}
+
+fn default_command_rate_limit_initiator() -> String {
+    crate::command_limits::DEFAULT_INITIATOR_LIMIT.to_string()
+}
+
+fn default_command_retry_max_attempts() -> u32 {
+    crate::command_retries::DEFAULT_MAX_ATTEMPTS
+}
+
+fn default_command_retry_backoff_secs() -> u64 {
+    crate::command_retries::DEFAULT_BACKOFF_SECS
+}
+
+fn default_command_retry_max_backoff_secs() -> u64 {
+    crate::command_retries::DEFAULT_MAX_BACKOFF_SECS
+}
+
+fn default_device_cert_validity_days() -> u32 {
+    crate::certificates::DEFAULT_VALIDITY_DAYS
+}
+
+fn default_device_mqtt_port() -> u16 {
+    8883
+}
+
+fn default_cert_expiry_warning_days() -> u32 {
+    crate::cert_expiry::DEFAULT_WARNING_DAYS
+}
+
+/// Split a comma-separated value, dropping empty entries.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 fn env_bool(key: &str) -> bool {
     std::env::var(key)
         .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
@@ -92,14 +316,335 @@ impl ApiConfig {
                 .unwrap_or(default_mqtt_port()),
             mqtt_fleet_id: std::env::var("MQTT_FLEET_ID").unwrap_or_default(),
             mqtt_use_tls: env_bool("MQTT_USE_TLS"),
+            mqtt_protocol: std::env::var("MQTT_PROTOCOL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
             mqtt_ca_cert: std::env::var("MQTT_CA_CERT").ok(),
             mqtt_client_cert: std::env::var("MQTT_CLIENT_CERT").ok(),
             mqtt_client_key: std::env::var("MQTT_CLIENT_KEY").ok(),
+            approvers: std::env::var("APPROVERS")
+                .map(|v| split_list(&v))
+                .unwrap_or_default(),
+            approval_ttl_secs: std::env::var("APPROVAL_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_approval_ttl_secs()),
+            approval_tools: std::env::var("APPROVAL_TOOLS")
+                .map(|v| split_list(&v))
+                .unwrap_or_else(|_| default_approval_tools()),
+            oidc_issuer: std::env::var("OIDC_ISSUER").ok().filter(|v| !v.is_empty()),
+            oidc_audiences: std::env::var("OIDC_AUDIENCE")
+                .map(|v| split_list(&v))
+                .unwrap_or_default(),
+            oidc_jwks_url: std::env::var("OIDC_JWKS_URL").ok(),
+            oidc_username_claim: std::env::var("OIDC_USERNAME_CLAIM").ok(),
+            oidc_roles_claim: std::env::var("OIDC_ROLES_CLAIM").ok(),
+            oidc_role_map: std::env::var("OIDC_ROLE_MAP")
+                .map(|v| split_list(&v))
+                .unwrap_or_default(),
+            oidc_default_role: std::env::var("OIDC_DEFAULT_ROLE").ok(),
+            oidc_tenant_claim: std::env::var("OIDC_TENANT_CLAIM").ok(),
+            oidc_clock_skew_secs: std::env::var("OIDC_CLOCK_SKEW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_oidc_clock_skew_secs()),
+            oidc_jwks_ttl_secs: std::env::var("OIDC_JWKS_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_oidc_jwks_ttl_secs()),
+            super_admin_token: std::env::var("SUPER_ADMIN_TOKEN")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            failure_rate_threshold: std::env::var("FAILURE_RATE_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_failure_rate_threshold()),
+            failure_rate_tool_thresholds: std::env::var("FAILURE_RATE_TOOL_THRESHOLDS")
+                .map(|v| split_list(&v))
+                .unwrap_or_default(),
+            failure_rate_min_samples: std::env::var("FAILURE_RATE_MIN_SAMPLES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_failure_rate_min_samples()),
+            failure_rate_window_secs: std::env::var("FAILURE_RATE_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_failure_rate_window_secs()),
+            structured_only_fleets: std::env::var("STRUCTURED_ONLY_FLEETS").unwrap_or_default(),
+            device_degraded_after_secs: std::env::var("DEVICE_DEGRADED_AFTER_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_device_degraded_after_secs()),
+            device_offline_after_secs: std::env::var("DEVICE_OFFLINE_AFTER_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_device_offline_after_secs()),
+            command_rate_limit_device: std::env::var("COMMAND_RATE_LIMIT_DEVICE")
+                .unwrap_or_else(|_| default_command_rate_limit_device()),
+            command_rate_limit_initiator: std::env::var("COMMAND_RATE_LIMIT_INITIATOR")
+                .unwrap_or_else(|_| default_command_rate_limit_initiator()),
+            command_retry_max_attempts: std::env::var("COMMAND_RETRY_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_command_retry_max_attempts()),
+            command_retry_backoff_secs: std::env::var("COMMAND_RETRY_BACKOFF_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_command_retry_backoff_secs()),
+            command_retry_max_backoff_secs: std::env::var("COMMAND_RETRY_MAX_BACKOFF_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_command_retry_max_backoff_secs()),
+            provisioning_ca_cert: std::env::var("PROVISIONING_CA_CERT")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            provisioning_ca_key: std::env::var("PROVISIONING_CA_KEY")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            device_cert_validity_days: std::env::var("DEVICE_CERT_VALIDITY_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_device_cert_validity_days()),
+            device_mqtt_host: std::env::var("DEVICE_MQTT_HOST")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            device_mqtt_port: std::env::var("DEVICE_MQTT_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_device_mqtt_port()),
+            cert_expiry_warning_days: std::env::var("CERT_EXPIRY_WARNING_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default_cert_expiry_warning_days()),
+            timescaledb_enabled: env_bool("TIMESCALEDB_ENABLED"),
+            telemetry_retention_days: std::env::var("TELEMETRY_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            heartbeat_retention_days: std::env::var("HEARTBEAT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            cloudevents_sink_url: std::env::var("CLOUDEVENTS_SINK_URL")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            cloudevents_source: std::env::var("CLOUDEVENTS_SOURCE")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            cloudevents_types: std::env::var("CLOUDEVENTS_TYPES")
+                .map(|v| split_list(&v))
+                .unwrap_or_default(),
+            grpc_port: std::env::var("GRPC_PORT").ok().and_then(|v| v.parse().ok()),
             ..Self::default()
         }
     }
 }
 
+impl ApiConfig {
+    /// Parsed `mqtt_fleet_id`; `None` when unset.
+    pub fn mqtt_fleets(&self) -> Result<Option<FleetFilter>, String> {
+        FleetFilter::parse(&self.mqtt_fleet_id)
+    }
+
+    /// Two-person approval policy for high-risk commands.
+    pub fn approval_policy(&self) -> ApprovalPolicy {
+        ApprovalPolicy {
+            approvers: self.approvers.clone(),
+            ttl: chrono::Duration::seconds(self.approval_ttl_secs as i64),
+            high_risk_tools: self.approval_tools.clone(),
+        }
+    }
+
+    /// OIDC settings; `None` when `oidc_issuer` is unset (auth off).
+    pub fn oidc_config(&self) -> Result<Option<OidcConfig>, String> {
+        let Some(issuer) = &self.oidc_issuer else {
+            return Ok(None);
+        };
+        let role = |name: &str| {
+            Role::parse(name).ok_or_else(|| {
+                format!("unknown role '{name}' (expected viewer, operator, admin or super_admin)")
+            })
+        };
+
+        let mut config = OidcConfig::new(issuer.clone());
+        config.audiences = self.oidc_audiences.clone();
+        config.jwks_url = self.oidc_jwks_url.clone();
+        if let Some(claim) = &self.oidc_username_claim {
+            config.username_claim = claim.clone();
+        }
+        if let Some(claim) = &self.oidc_roles_claim {
+            config.roles_claim = claim.clone();
+        }
+        for entry in &self.oidc_role_map {
+            let (from, to) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("role mapping '{entry}' is not 'value=role'"))?;
+            config.role_map.push((from.trim().to_string(), role(to)?));
+        }
+        config.default_role = self.oidc_default_role.as_deref().map(role).transpose()?;
+        config.tenant_claim = self.oidc_tenant_claim.clone();
+        config.clock_skew = std::time::Duration::from_secs(self.oidc_clock_skew_secs);
+        config.jwks_ttl = std::time::Duration::from_secs(self.oidc_jwks_ttl_secs);
+        Ok(Some(config))
+    }
+
+    /// Alert thresholds for tool failure rates.
+    pub fn failure_thresholds(&self) -> Result<FailureThresholds, String> {
+        let rate = |value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|r| (0.0..=1.0).contains(r))
+                .ok_or_else(|| format!("'{value}' is not a rate between 0 and 1"))
+        };
+        if !(0.0..=1.0).contains(&self.failure_rate_threshold) {
+            return Err(format!(
+                "{} is not a rate between 0 and 1",
+                self.failure_rate_threshold
+            ));
+        }
+        let mut per_tool = Vec::new();
+        for entry in &self.failure_rate_tool_thresholds {
+            let (tool, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("threshold '{entry}' is not 'tool=rate'"))?;
+            per_tool.push((tool.trim().to_string(), rate(value)?));
+        }
+        Ok(FailureThresholds {
+            failure_rate: self.failure_rate_threshold,
+            per_tool,
+            min_samples: self.failure_rate_min_samples.max(1),
+            window: chrono::Duration::seconds(self.failure_rate_window_secs as i64),
+        })
+    }
+
+    /// Inference-free command mode; off when `structured_only_fleets` is empty.
+    pub fn structured_mode(&self) -> Result<StructuredMode, String> {
+        FleetFilter::parse(&self.structured_only_fleets).map(StructuredMode::new)
+    }
+
+    /// Rate limits on `POST /api/v1/commands`.
+    pub fn command_limits(&self) -> Result<CommandLimits, String> {
+        Ok(CommandLimits {
+            per_device: rate_limit(&self.command_rate_limit_device)?,
+            per_initiator: rate_limit(&self.command_rate_limit_initiator)?,
+        })
+    }
+
+    /// Automatic retries of failed command publishes.
+    pub fn retry_policy(&self) -> Result<RetryPolicy, String> {
+        if self.command_retry_max_attempts == 0 {
+            return Err("max attempts must be at least 1".into());
+        }
+        if self.command_retry_backoff_secs == 0 {
+            return Err("backoff must be at least 1 second".into());
+        }
+        if self.command_retry_max_backoff_secs < self.command_retry_backoff_secs {
+            return Err(format!(
+                "max backoff ({}s) must not be shorter than the backoff ({}s)",
+                self.command_retry_max_backoff_secs, self.command_retry_backoff_secs
+            ));
+        }
+        Ok(RetryPolicy {
+            max_attempts: self.command_retry_max_attempts,
+            backoff: chrono::Duration::seconds(self.command_retry_backoff_secs as i64),
+            max_backoff: chrono::Duration::seconds(self.command_retry_max_backoff_secs as i64),
+        })
+    }
+
+    /// Heartbeat ages for the degraded and offline device statuses.
+    pub fn status_thresholds(&self) -> Result<StatusThresholds, String> {
+        if self.device_degraded_after_secs == 0 {
+            return Err("degraded threshold must be at least 1 second".into());
+        }
+        if self.device_offline_after_secs <= self.device_degraded_after_secs {
+            return Err(format!(
+                "offline threshold ({}s) must be longer than the degraded threshold ({}s)",
+                self.device_offline_after_secs, self.device_degraded_after_secs
+            ));
+        }
+        Ok(StatusThresholds {
+            degraded_after: chrono::Duration::seconds(self.device_degraded_after_secs as i64),
+            offline_after: chrono::Duration::seconds(self.device_offline_after_secs as i64),
+        })
+    }
+
+    /// Retention from `telemetry_retention_days` and
+    /// `heartbeat_retention_days`; 0 days is refused.
+    pub fn retention_policy(&self) -> Result<RetentionPolicy, String> {
+        let days = |d: Option<u32>, what: &str| match d {
+            Some(0) => Err(format!("{what} retention must be at least 1 day")),
+            d => Ok(d.map(|d| chrono::Duration::days(d.into()))),
+        };
+        Ok(RetentionPolicy {
+            telemetry: days(self.telemetry_retention_days, "telemetry")?,
+            heartbeats: days(self.heartbeat_retention_days, "heartbeat")?,
+        })
+    }
+
+    /// CloudEvents export from the `cloudevents_*` settings; `None` without
+    /// a sink URL. Unknown event types and non-HTTP URLs are refused.
+    pub fn cloudevents_config(&self) -> Result<Option<ExportConfig>, String> {
+        let Some(url) = &self.cloudevents_sink_url else {
+            return Ok(None);
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("sink URL '{url}' must be http:// or https://"));
+        }
+        if let Some(unknown) = self
+            .cloudevents_types
+            .iter()
+            .find(|t| !crate::events::EVENT_TYPES.contains(&t.as_str()))
+        {
+            return Err(format!("unknown event type '{unknown}'"));
+        }
+        Ok(Some(ExportConfig {
+            sink_url: url.clone(),
+            source: self
+                .cloudevents_source
+                .clone()
+                .unwrap_or_else(|| crate::cloudevents::DEFAULT_SOURCE.into()),
+            types: self.cloudevents_types.clone(),
+        }))
+    }
+
+    /// CA for device certificates; `None` when neither
+    /// `provisioning_ca_cert` nor `provisioning_ca_key` is set.
+    pub fn certificate_authority(&self) -> Result<Option<CertificateAuthority>, String> {
+        let (cert, key) = match (&self.provisioning_ca_cert, &self.provisioning_ca_key) {
+            (None, None) => return Ok(None),
+            (Some(cert), Some(key)) => (cert, key),
+            _ => return Err("both the CA certificate and its key are required".into()),
+        };
+        let validity = self.device_cert_validity_days;
+        if !(1..=crate::certificates::MAX_VALIDITY_DAYS).contains(&validity) {
+            return Err(format!(
+                "validity must be between 1 and {} days",
+                crate::certificates::MAX_VALIDITY_DAYS
+            ));
+        }
+        let endpoint = DeviceEndpoint {
+            broker_host: self
+                .device_mqtt_host
+                .clone()
+                .unwrap_or_else(|| self.mqtt_broker_host.clone()),
+            broker_port: self.device_mqtt_port,
+            protocol: self.mqtt_protocol,
+        };
+        let mut ca = CertificateAuthority::load(cert, key, endpoint).map_err(|e| e.to_string())?;
+        ca.default_validity_days = validity;
+        Ok(Some(ca))
+    }
+}
+
+/// A `burst/per_minute` limit, or `None` for `off`.
+fn rate_limit(value: &str) -> Result<Option<RateLimit>, String> {
+    match value.trim() {
+        v if v.eq_ignore_ascii_case("off") => Ok(None),
+        v => v.parse().map(Some),
+    }
+}
+
 impl Default for ApiConfig {
     fn default() -> Self {
         Self {
@@ -113,9 +658,49 @@ impl Default for ApiConfig {
             mqtt_broker_port: default_mqtt_port(),
             mqtt_fleet_id: String::new(),
             mqtt_use_tls: false,
+            mqtt_protocol: Default::default(),
             mqtt_ca_cert: None,
             mqtt_client_cert: None,
             mqtt_client_key: None,
+            approvers: vec![],
+            approval_ttl_secs: default_approval_ttl_secs(),
+            approval_tools: default_approval_tools(),
+            oidc_issuer: None,
+            oidc_audiences: vec![],
+            oidc_jwks_url: None,
+            oidc_username_claim: None,
+            oidc_roles_claim: None,
+            oidc_role_map: vec![],
+            oidc_default_role: None,
+            oidc_tenant_claim: None,
+            oidc_clock_skew_secs: default_oidc_clock_skew_secs(),
+            oidc_jwks_ttl_secs: default_oidc_jwks_ttl_secs(),
+            super_admin_token: None,
+            failure_rate_threshold: default_failure_rate_threshold(),
+            failure_rate_tool_thresholds: vec![],
+            failure_rate_min_samples: default_failure_rate_min_samples(),
+            failure_rate_window_secs: default_failure_rate_window_secs(),
+            structured_only_fleets: String::new(),
+            device_degraded_after_secs: default_device_degraded_after_secs(),
+            device_offline_after_secs: default_device_offline_after_secs(),
+            command_rate_limit_device: default_command_rate_limit_device(),
+            command_rate_limit_initiator: default_command_rate_limit_initiator(),
+            command_retry_max_attempts: default_command_retry_max_attempts(),
+            command_retry_backoff_secs: default_command_retry_backoff_secs(),
+            command_retry_max_backoff_secs: default_command_retry_max_backoff_secs(),
+            provisioning_ca_cert: None,
+            provisioning_ca_key: None,
+            device_cert_validity_days: default_device_cert_validity_days(),
+            device_mqtt_host: None,
+            device_mqtt_port: default_device_mqtt_port(),
+            cert_expiry_warning_days: default_cert_expiry_warning_days(),
+            timescaledb_enabled: false,
+            telemetry_retention_days: None,
+            heartbeat_retention_days: None,
+            cloudevents_sink_url: None,
+            cloudevents_source: None,
+            cloudevents_types: vec![],
+            grpc_port: None,
         }
     }
 }
@@ -134,5 +719,198 @@ mod tests {
         assert!(!config.mqtt_enabled);
         assert_eq!(config.mqtt_broker_host, "localhost");
         assert_eq!(config.mqtt_broker_port, 1883);
+        assert!(config.approvers.is_empty());
+        assert!(!config.approval_policy().enabled());
+        assert!(config.oidc_config().unwrap().is_none());
+        assert!(!config.structured_mode().unwrap().enabled());
+    }
+
+    #[test]
+    fn oidc_config_parses_role_map() {
+        let config = ApiConfig {
+            oidc_issuer: Some("https://login.example.com".into()),
+            oidc_role_map: vec!["fleet-ops=operator".into(), "it-admins = Admin".into()],
+            oidc_default_role: Some("viewer".into()),
+            ..ApiConfig::default()
+        };
+        let oidc = config.oidc_config().unwrap().unwrap();
+        assert_eq!(oidc.roles_claim, "roles");
+        assert_eq!(
+            oidc.role_map,
+            vec![
+                ("fleet-ops".to_string(), Role::Operator),
+                ("it-admins".to_string(), Role::Admin)
+            ]
+        );
+        assert_eq!(oidc.default_role, Some(Role::Viewer));
+
+        let bad = ApiConfig {
+            oidc_role_map: vec!["fleet-ops=superuser".into()],
+            ..config
+        };
+        assert!(bad.oidc_config().unwrap_err().contains("superuser"));
+    }
+
+    #[test]
+    fn failure_thresholds_parse_per_tool_rates() {
+        let config = ApiConfig {
+            failure_rate_tool_thresholds: vec!["read_dtcs=0.2".into(), "shell = 0.8".into()],
+            ..ApiConfig::default()
+        };
+        let thresholds = config.failure_thresholds().unwrap();
+        assert_eq!(thresholds.rate_for("read_dtcs"), 0.2);
+        assert_eq!(thresholds.rate_for("shell"), 0.8);
+        assert_eq!(thresholds.rate_for("read_pid"), 0.5);
+
+        let bad = ApiConfig {
+            failure_rate_tool_thresholds: vec!["read_dtcs=20%".into()],
+            ..ApiConfig::default()
+        };
+        assert!(bad.failure_thresholds().is_err());
+    }
+
+    #[test]
+    fn command_limits_parse_or_turn_off() {
+        let limits = ApiConfig::default().command_limits().unwrap();
+        assert_eq!(
+            limits.per_device,
+            Some(crate::command_limits::DEFAULT_DEVICE_LIMIT)
+        );
+
+        let config = ApiConfig {
+            command_rate_limit_device: "5/10".into(),
+            command_rate_limit_initiator: "off".into(),
+            ..ApiConfig::default()
+        };
+        let limits = config.command_limits().unwrap();
+        assert_eq!(limits.per_device, Some(RateLimit::new(5, 10)));
+        assert_eq!(limits.per_initiator, None);
+
+        let bad = ApiConfig {
+            command_rate_limit_device: "lots".into(),
+            ..ApiConfig::default()
+        };
+        assert!(bad.command_limits().unwrap_err().contains("lots"));
+    }
+
+    #[test]
+    fn retry_policy_validates_attempts_and_backoff() {
+        assert_eq!(
+            ApiConfig::default().retry_policy().unwrap(),
+            RetryPolicy::default()
+        );
+
+        let bad = ApiConfig {
+            command_retry_max_attempts: 0,
+            ..ApiConfig::default()
+        };
+        assert!(bad.retry_policy().is_err());
+        let bad = ApiConfig {
+            command_retry_backoff_secs: 60,
+            command_retry_max_backoff_secs: 30,
+            ..ApiConfig::default()
+        };
+        assert!(bad.retry_policy().unwrap_err().contains("30s"));
+    }
+
+    #[test]
+    fn status_thresholds_require_offline_after_degraded() {
+        let thresholds = ApiConfig::default().status_thresholds().unwrap();
+        assert_eq!(thresholds, StatusThresholds::default());
+
+        let bad = ApiConfig {
+            device_degraded_after_secs: 300,
+            device_offline_after_secs: 120,
+            ..ApiConfig::default()
+        };
+        assert!(bad.status_thresholds().unwrap_err().contains("120s"));
+    }
+
+    #[test]
+    fn retention_policy_refuses_zero_days() {
+        assert!(
+            !ApiConfig::default()
+                .retention_policy()
+                .unwrap()
+                .is_enabled()
+        );
+
+        let config = ApiConfig {
+            telemetry_retention_days: Some(30),
+            ..ApiConfig::default()
+        };
+        let policy = config.retention_policy().unwrap();
+        assert_eq!(policy.telemetry, Some(chrono::Duration::days(30)));
+        assert_eq!(policy.heartbeats, None);
+
+        let bad = ApiConfig {
+            heartbeat_retention_days: Some(0),
+            ..ApiConfig::default()
+        };
+        assert!(bad.retention_policy().unwrap_err().contains("heartbeat"));
+    }
+
+    #[test]
+    fn cloudevents_config_validates_url_and_types() {
+        assert_eq!(ApiConfig::default().cloudevents_config(), Ok(None));
+
+        let config = ApiConfig {
+            cloudevents_sink_url: Some("https://events.example.com/ingest".into()),
+            cloudevents_types: vec!["command_response".into()],
+            ..ApiConfig::default()
+        };
+        let export = config.cloudevents_config().unwrap().unwrap();
+        assert_eq!(export.source, crate::cloudevents::DEFAULT_SOURCE);
+        assert_eq!(export.types, ["command_response"]);
+
+        let bad_type = ApiConfig {
+            cloudevents_types: vec!["command_exploded".into()],
+            ..config.clone()
+        };
+        assert!(
+            bad_type
+                .cloudevents_config()
+                .unwrap_err()
+                .contains("command_exploded")
+        );
+        let bad_url = ApiConfig {
+            cloudevents_sink_url: Some("nats://broker:4222".into()),
+            ..config
+        };
+        assert!(bad_url.cloudevents_config().is_err());
+    }
+
+    #[test]
+    fn certificate_authority_needs_cert_and_key() {
+        assert!(
+            ApiConfig::default()
+                .certificate_authority()
+                .unwrap()
+                .is_none()
+        );
+
+        let half = ApiConfig {
+            provisioning_ca_cert: Some("/etc/zeroclaw/ca.pem".into()),
+            ..ApiConfig::default()
+        };
+        assert!(half.certificate_authority().unwrap_err().contains("key"));
+
+        let missing = ApiConfig {
+            provisioning_ca_cert: Some("/nonexistent/ca.pem".into()),
+            provisioning_ca_key: Some("/nonexistent/ca.key".into()),
+            ..ApiConfig::default()
+        };
+        assert!(
+            missing
+                .certificate_authority()
+                .unwrap_err()
+                .contains("/nonexistent/ca.pem")
+        );
+    }
+
+    #[test]
+    fn split_list_trims_and_drops_empty() {
+        assert_eq!(split_list(" alice, bob,,"), vec!["alice", "bob"]);
+        assert!(split_list("").is_empty());
     }
 }
@@ -0,0 +1,158 @@
+//! Conversation history for follow-up commands.
+//!
+//! Every command carries a `conversation_id`: the one the operator sent to
+//! continue a conversation, or its own ID when it starts one. The history
+//! is not stored separately — it is the conversation's earlier commands
+//! (text, parsed intent, response) from the command log, in memory or in
+//! `commands.conversation_id` (migration 019). The last
+//! [`MAX_HISTORY_TURNS`] turns go to Bedrock in [`InferenceOverrides`] and,
+//! for commands the cloud could not parse, to the device's Ollama in the
+//! envelope.
+//!
+//! [`InferenceOverrides`]: crate::inference::InferenceOverrides
+
+use uuid::Uuid;
+
+use zc_protocol::commands::{ActionKind, ParsedIntent};
+use zc_protocol::conversation::{ConversationTurn, MAX_HISTORY_TURNS};
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// The last turns of `conversation_id` within `fleet_id`, oldest first.
+pub async fn history(
+    state: &AppState,
+    conversation_id: Uuid,
+    fleet_id: &str,
+) -> ApiResult<Vec<ConversationTurn>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::commands::list_by_conversation(
+            pool,
+            conversation_id,
+            fleet_id,
+            MAX_HISTORY_TURNS,
+        )
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(rows
+            .into_iter()
+            .rev()
+            .map(|row| {
+                let intent = row.tool_name.map(|tool_name| ParsedIntent {
+                    // Shell and reply intents are recovered from the envelope.
+                    action: row
+                        .envelope
+                        .as_ref()
+                        .and_then(|e| e.pointer("/parsed_intent/action"))
+                        .and_then(|a| serde_json::from_value::<ActionKind>(a.clone()).ok())
+                        .unwrap_or_default(),
+                    tool_name,
+                    tool_args: row.tool_args.unwrap_or_default(),
+                    confidence: row.confidence.unwrap_or_default(),
+                    steps: Vec::new(),
+                });
+                let response = row.response_text.or(row.error);
+                ConversationTurn::new(
+                    row.natural_language,
+                    intent,
+                    response.as_deref(),
+                    row.created_at,
+                )
+            })
+            .collect());
+    }
+
+    let commands = state.commands.read().await;
+    let mut turns: Vec<ConversationTurn> = commands
+        .iter()
+        .filter(|c| {
+            c.envelope.conversation_id == Some(conversation_id) && c.envelope.fleet_id == fleet_id
+        })
+        .map(|c| {
+            let response = c
+                .response
+                .as_ref()
+                .and_then(|r| r.response_text.as_deref().or(r.error.as_deref()));
+            ConversationTurn::new(
+                c.envelope.natural_language.clone(),
+                c.envelope.parsed_intent.clone(),
+                response,
+                c.created_at,
+            )
+        })
+        .collect();
+    turns.sort_by_key(|t| t.at);
+    let skip = turns.len().saturating_sub(MAX_HISTORY_TURNS);
+    Ok(turns.split_off(skip))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use zc_protocol::commands::{CommandEnvelope, CommandStatus};
+
+    use super::*;
+    use crate::state::CommandRecord;
+
+    #[tokio::test]
+    async fn history_is_per_conversation_and_fleet() {
+        let state = AppState::with_sample_data();
+        let conversation = Uuid::now_v7();
+        {
+            let mut commands = state.commands.write().await;
+            for (i, fleet) in ["fleet-alpha", "fleet-beta", "fleet-alpha"]
+                .into_iter()
+                .cycle()
+                .take(9)
+                .enumerate()
+            {
+                let mut envelope =
+                    CommandEnvelope::new(fleet, "rpi-001", format!("command {i}"), "tech");
+                envelope.conversation_id = Some(conversation);
+                commands.push(CommandRecord {
+                    envelope,
+                    response: None,
+                    status: CommandStatus::Completed,
+                    created_at: Utc::now() + chrono::Duration::seconds(i as i64),
+                    deadline: None,
+                    attempts: 0,
+                    max_attempts: 1,
+                    next_attempt_at: None,
+                    idempotency_key: None,
+                    timing: Default::default(),
+                });
+            }
+            commands.push(CommandRecord {
+                envelope: CommandEnvelope::new("fleet-alpha", "rpi-001", "unrelated", "tech"),
+                response: None,
+                status: CommandStatus::Completed,
+                created_at: Utc::now() + chrono::Duration::seconds(60),
+                deadline: None,
+                attempts: 0,
+                max_attempts: 1,
+                next_attempt_at: None,
+                idempotency_key: None,
+                timing: Default::default(),
+            });
+        }
+
+        let turns = history(&state, conversation, "fleet-alpha").await.unwrap();
+        let commands: Vec<&str> = turns.iter().map(|t| t.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            [
+                "command 2",
+                "command 3",
+                "command 5",
+                "command 6",
+                "command 8"
+            ]
+        );
+        assert!(
+            history(&state, Uuid::now_v7(), "fleet-alpha")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
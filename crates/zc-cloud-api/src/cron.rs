@@ -0,0 +1,241 @@
+//! Five-field cron expressions, evaluated in UTC.
+//!
+//! `minute hour day-of-month month day-of-week`, each field a `*`, a value,
+//! a range `a-b`, a step `*/n`, `a-b/n` or `a/n`, or a comma-separated list
+//! of those. Months and weekdays also take three-letter names (`jan`,
+//! `mon`); weekday 0 and 7 are both Sunday. As in Vixie cron, when both
+//! day fields are restricted a day matching either one fires. The macros
+//! `@hourly`, `@daily` (`@midnight`), `@weekly`, `@monthly` and `@yearly`
+//! (`@annually`) are accepted as well.
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead [`Cron::next_after`] looks before giving up (covers
+/// `0 0 29 2 *` across a leap-year gap).
+const SEARCH_DAYS: i64 = 366 * 5;
+
+/// A parsed cron expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day-of-month / day-of-week were not `*`.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let trimmed = expr.trim();
+        let expanded = match trimmed.to_ascii_lowercase().as_str() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            m if m.starts_with('@') => return Err(format!("unknown cron macro '{trimmed}'")),
+            _ => trimmed,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "cron expression '{trimmed}' must have 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ));
+        };
+        let mut weekdays = field(weekday, "weekday", 0, 7, WEEKDAYS)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            expr: trimmed.to_string(),
+            minutes: field(minute, "minute", 0, 59, &[])?,
+            hours: field(hour, "hour", 0, 23, &[])?,
+            days: field(day, "day-of-month", 1, 31, &[])?,
+            months: field(month, "month", 1, 12, MONTHS)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    /// The expression as written.
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    /// First matching minute strictly after `after`, or `None` if the
+    /// expression never fires (e.g. `0 0 31 2 *`).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after
+            .naive_utc()
+            .with_second(0)?
+            .with_nanosecond(0)?
+            .checked_add_signed(Duration::minutes(1))?;
+        let limit = t.checked_add_signed(Duration::days(SEARCH_DAYS))?;
+        while t < limit {
+            if !has(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = midnight(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.day_matches(t.date()) {
+                t = midnight(t.date().succ_opt()?);
+            } else if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t.and_utc());
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default()
+}
+
+/// Bit mask of the values a field allows.
+fn field(spec: &str, name: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let n = match names.iter().position(|n| n.eq_ignore_ascii_case(s)) {
+            Some(i) => i as u32 + min,
+            None => s
+                .parse()
+                .map_err(|_| format!("invalid {name} '{s}' in '{spec}'"))?,
+        };
+        if !(min..=max).contains(&n) {
+            return Err(format!("{name} {n} out of range {min}-{max}"));
+        }
+        Ok(n)
+    };
+    let mut mask = 0u64;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid {name} step in '{part}'"))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (value(a)?, value(b)?)
+        } else {
+            let a = value(range)?;
+            (a, if step.is_some() { max } else { a })
+        };
+        if start > end {
+            return Err(format!("{name} range '{range}' is reversed"));
+        }
+        for v in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expr: &str, after: &str) -> Option<DateTime<Utc>> {
+        Cron::parse(expr).unwrap().next_after(at(after))
+    }
+
+    #[test]
+    fn next_fire_times() {
+        assert_eq!(
+            next("@hourly", "2024-01-15T12:00:00Z"),
+            Some(at("2024-01-15T13:00:00Z"))
+        );
+        assert_eq!(
+            next("30 2 * * *", "2024-01-15T02:30:00Z"),
+            Some(at("2024-01-16T02:30:00Z"))
+        );
+        assert_eq!(
+            next("*/15 9-17 * * mon-fri", "2024-01-19T17:50:10Z"),
+            Some(at("2024-01-22T09:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 1 */3 *", "2024-02-10T00:00:00Z"),
+            Some(at("2024-04-01T00:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 29 feb *", "2024-03-01T00:00:00Z"),
+            Some(at("2028-02-29T00:00:00Z"))
+        );
+        // Both day fields restricted: the 13th or any Friday.
+        assert_eq!(
+            next("0 12 13 * 5", "2024-01-01T00:00:00Z"),
+            Some(at("2024-01-05T12:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 * * 7", "2024-01-15T00:00:00Z"),
+            Some(at("2024-01-21T00:00:00Z"))
+        );
+        assert_eq!(next("0 0 31 2 *", "2024-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expr in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "0 0 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 * foo *",
+            "@fortnightly",
+        ] {
+            assert!(Cron::parse(expr).is_err(), "{expr:?} should be rejected");
+        }
+        assert_eq!(Cron::parse(" @daily ").unwrap().as_str(), "@daily");
+    }
+}
@@ -0,0 +1,69 @@
+//! Agent release queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::agent_releases::AgentRelease;
+
+/// Agent release row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AgentReleaseRow {
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+    pub notes: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AgentReleaseRow> for AgentRelease {
+    fn from(row: AgentReleaseRow) -> Self {
+        Self {
+            version: row.version,
+            url: row.url,
+            sha256: row.sha256,
+            notes: row.notes,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Store a new release. Returns `false` if the version is already taken.
+pub async fn insert(pool: &PgPool, release: &AgentRelease) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO agent_releases (version, url, sha256, notes, created_by, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (version) DO NOTHING",
+    )
+    .bind(&release.version)
+    .bind(&release.url)
+    .bind(&release.sha256)
+    .bind(&release.notes)
+    .bind(&release.created_by)
+    .bind(release.created_at)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// All releases, newest first.
+pub async fn list(pool: &PgPool) -> Result<Vec<AgentReleaseRow>, sqlx::Error> {
+    sqlx::query_as::<_, AgentReleaseRow>(
+        "SELECT version, url, sha256, notes, created_by, created_at
+         FROM agent_releases ORDER BY created_at DESC",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// A release by version.
+pub async fn get(pool: &PgPool, version: &str) -> Result<Option<AgentReleaseRow>, sqlx::Error> {
+    sqlx::query_as::<_, AgentReleaseRow>(
+        "SELECT version, url, sha256, notes, created_by, created_at
+         FROM agent_releases WHERE version = $1",
+    )
+    .bind(version)
+    .fetch_optional(pool)
+    .await
+}
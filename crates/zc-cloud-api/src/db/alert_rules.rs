@@ -0,0 +1,111 @@
+//! Alert rule queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::alert_rules::{AlertRule, RuleFilter};
+
+/// Alert rule row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AlertRuleRow {
+    pub id: Uuid,
+    pub name: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub condition: serde_json::Value,
+    pub webhook_url: Option<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AlertRuleRow {
+    /// The rule, or `None` if the stored condition is not understood.
+    pub fn into_rule(self) -> Option<AlertRule> {
+        Some(AlertRule {
+            condition: serde_json::from_value(self.condition).ok()?,
+            id: self.id,
+            name: self.name,
+            fleet_id: self.fleet_id,
+            device_id: self.device_id,
+            webhook_url: self.webhook_url,
+            enabled: self.enabled,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+/// Store a new rule.
+pub async fn insert(pool: &PgPool, rule: &AlertRule) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO alert_rules (id, name, fleet_id, device_id, condition, webhook_url,
+             enabled, created_by, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(rule.id)
+    .bind(&rule.name)
+    .bind(&rule.fleet_id)
+    .bind(&rule.device_id)
+    .bind(serde_json::to_value(&rule.condition).unwrap_or_default())
+    .bind(&rule.webhook_url)
+    .bind(rule.enabled)
+    .bind(&rule.created_by)
+    .bind(rule.created_at)
+    .bind(rule.updated_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Rules matching `filter`, oldest first.
+pub async fn list(pool: &PgPool, filter: &RuleFilter) -> Result<Vec<AlertRuleRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM alert_rules WHERE TRUE");
+    if let Some(fleet_id) = &filter.fleet_id {
+        qb.push(" AND fleet_id = ").push_bind(fleet_id.clone());
+    }
+    if let Some(device_id) = &filter.device_id {
+        qb.push(" AND device_id = ").push_bind(device_id.clone());
+    }
+    if let Some(enabled) = filter.enabled {
+        qb.push(" AND enabled = ").push_bind(enabled);
+    }
+    if let Some(fleets) = &filter.fleets {
+        qb.push(" AND fleet_id = ANY(")
+            .push_bind(fleets.clone())
+            .push(")");
+    }
+    qb.push(" ORDER BY created_at, id");
+    qb.build_query_as::<AlertRuleRow>().fetch_all(pool).await
+}
+
+/// Get a rule by ID.
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<AlertRuleRow>, sqlx::Error> {
+    sqlx::query_as::<_, AlertRuleRow>("SELECT * FROM alert_rules WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Write back a rule's enabled flag.
+pub async fn set_enabled(pool: &PgPool, rule: &AlertRule) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE alert_rules SET enabled = $2, updated_at = $3 WHERE id = $1")
+        .bind(rule.id)
+        .bind(rule.enabled)
+        .bind(rule.updated_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Delete a rule. Returns `false` if it did not exist.
+pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM alert_rules WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
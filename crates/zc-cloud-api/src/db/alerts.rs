@@ -0,0 +1,195 @@
+//! Alert queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::alerts::{Alert, AlertFilter, AlertState};
+
+/// State with expired snoozes read as open.
+const EFFECTIVE_STATE: &str =
+    "(CASE WHEN state = 'snoozed' AND snoozed_until <= now() THEN 'open' ELSE state END)";
+
+/// Alert row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AlertRow {
+    pub id: Uuid,
+    pub kind: String,
+    pub fleet_id: String,
+    pub device_id: String,
+    pub scope: String,
+    pub title: String,
+    pub detail: serde_json::Value,
+    pub state: String,
+    pub occurrences: i64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub snoozed_until: Option<DateTime<Utc>>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    pub history: serde_json::Value,
+    pub dedup_key: String,
+}
+
+impl AlertRow {
+    /// The alert, or `None` if the stored state is unknown.
+    pub fn into_alert(self) -> Option<Alert> {
+        Some(Alert {
+            state: AlertState::parse(&self.state)?,
+            history: serde_json::from_value(self.history).unwrap_or_default(),
+            id: self.id,
+            kind: self.kind,
+            fleet_id: self.fleet_id,
+            device_id: self.device_id,
+            scope: self.scope,
+            title: self.title,
+            detail: self.detail,
+            occurrences: self.occurrences.max(0) as u64,
+            first_seen_at: self.first_seen_at,
+            last_seen_at: self.last_seen_at,
+            acknowledged_by: self.acknowledged_by,
+            acknowledged_at: self.acknowledged_at,
+            snoozed_until: self.snoozed_until,
+            resolved_by: self.resolved_by,
+            resolved_at: self.resolved_at,
+            updated_at: self.updated_at,
+            dedup_key: self.dedup_key,
+        })
+    }
+}
+
+fn push_where(filter: &AlertFilter, qb: &mut QueryBuilder<'_, Postgres>) {
+    qb.push(" WHERE TRUE");
+    if let Some(state) = filter.state {
+        qb.push(format!(" AND {EFFECTIVE_STATE} = "))
+            .push_bind(state.as_str());
+    }
+    if filter.unresolved {
+        qb.push(" AND state <> 'resolved'");
+    }
+    if let Some(fleet_id) = &filter.fleet_id {
+        qb.push(" AND fleet_id = ").push_bind(fleet_id.clone());
+    }
+    if let Some(device_id) = &filter.device_id {
+        qb.push(" AND device_id = ").push_bind(device_id.clone());
+    }
+    if let Some(kind) = &filter.kind {
+        qb.push(" AND kind = ").push_bind(kind.clone());
+    }
+    if let Some(fleets) = &filter.fleets {
+        qb.push(" AND fleet_id = ANY(")
+            .push_bind(fleets.clone())
+            .push(")");
+    }
+}
+
+/// Insert a newly raised alert.
+pub async fn insert(pool: &PgPool, alert: &Alert) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO alerts (id, kind, fleet_id, device_id, scope, title, detail, state,
+             occurrences, first_seen_at, last_seen_at, updated_at, history, dedup_key)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+    )
+    .bind(alert.id)
+    .bind(&alert.kind)
+    .bind(&alert.fleet_id)
+    .bind(&alert.device_id)
+    .bind(&alert.scope)
+    .bind(&alert.title)
+    .bind(&alert.detail)
+    .bind(alert.state.as_str())
+    .bind(alert.occurrences as i64)
+    .bind(alert.first_seen_at)
+    .bind(alert.last_seen_at)
+    .bind(alert.updated_at)
+    .bind(serde_json::to_value(&alert.history).unwrap_or_default())
+    .bind(&alert.dedup_key)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Write back an alert's mutable fields.
+pub async fn update(pool: &PgPool, alert: &Alert) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE alerts SET device_id = $2, title = $3, detail = $4, state = $5,
+             occurrences = $6, last_seen_at = $7, acknowledged_by = $8, acknowledged_at = $9,
+             snoozed_until = $10, resolved_by = $11, resolved_at = $12, updated_at = $13,
+             history = $14
+         WHERE id = $1",
+    )
+    .bind(alert.id)
+    .bind(&alert.device_id)
+    .bind(&alert.title)
+    .bind(&alert.detail)
+    .bind(alert.state.as_str())
+    .bind(alert.occurrences as i64)
+    .bind(alert.last_seen_at)
+    .bind(&alert.acknowledged_by)
+    .bind(alert.acknowledged_at)
+    .bind(alert.snoozed_until)
+    .bind(&alert.resolved_by)
+    .bind(alert.resolved_at)
+    .bind(alert.updated_at)
+    .bind(serde_json::to_value(&alert.history).unwrap_or_default())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Get an alert by ID.
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<AlertRow>, sqlx::Error> {
+    sqlx::query_as::<_, AlertRow>("SELECT * FROM alerts WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The unresolved alert with this dedup key, if any.
+pub async fn find_unresolved(
+    pool: &PgPool,
+    dedup_key: &str,
+) -> Result<Option<AlertRow>, sqlx::Error> {
+    sqlx::query_as::<_, AlertRow>(
+        "SELECT * FROM alerts WHERE dedup_key = $1 AND state <> 'resolved' LIMIT 1",
+    )
+    .bind(dedup_key)
+    .fetch_optional(pool)
+    .await
+}
+
+/// One page of alerts matching `filter` (most recently seen first).
+pub async fn list_page(pool: &PgPool, filter: &AlertFilter) -> Result<Vec<AlertRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM alerts");
+    push_where(filter, &mut qb);
+    qb.push(" ORDER BY last_seen_at DESC, id DESC LIMIT ")
+        .push_bind(filter.limit as i64)
+        .push(" OFFSET ")
+        .push_bind(filter.offset as i64);
+    qb.build_query_as::<AlertRow>().fetch_all(pool).await
+}
+
+/// Number of alerts matching `filter` (paging aside).
+pub async fn count(pool: &PgPool, filter: &AlertFilter) -> Result<i64, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM alerts");
+    push_where(filter, &mut qb);
+    qb.build_query_scalar::<i64>().fetch_one(pool).await
+}
+
+/// Unresolved alerts of a fleet grouped by effective state.
+pub async fn count_unresolved_by_state(
+    pool: &PgPool,
+    fleet_id: &str,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>(&format!(
+        "SELECT {EFFECTIVE_STATE} AS s, COUNT(*) FROM alerts
+         WHERE fleet_id = $1 AND state <> 'resolved'
+         GROUP BY s"
+    ))
+    .bind(fleet_id)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,161 @@
+//! Fleet analytics queries over command, DTC history, telemetry and device
+//! tables.
+//!
+//! Devices are scoped to fleets by `metadata->>'fleet'`, commands by their
+//! own `fleet_id`. A `None` fleet list covers every fleet.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+use crate::alert_rules::Comparison;
+use crate::db::commands::CommandRow;
+
+/// Per-device aggregate of reported codes.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DtcReportRow {
+    pub device_id: String,
+    pub codes: Vec<String>,
+    pub occurrences: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Per-device aggregate of readings beyond a threshold.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ThresholdRow {
+    pub device_id: String,
+    pub readings: i64,
+    pub peak: f64,
+    pub last_seen: DateTime<Utc>,
+}
+
+fn push_window(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    column: &str,
+    since: Option<DateTime<Utc>>,
+    until: DateTime<Utc>,
+) {
+    if let Some(since) = since {
+        qb.push(format!(" AND {column} >= ")).push_bind(since);
+    }
+    qb.push(format!(" AND {column} < ")).push_bind(until);
+}
+
+fn push_fleets(qb: &mut QueryBuilder<'_, Postgres>, column: &str, fleets: Option<&[String]>) {
+    if let Some(fleets) = fleets {
+        qb.push(format!(" AND {column} = ANY("))
+            .push_bind(fleets.to_vec())
+            .push(")");
+    }
+}
+
+/// Devices that reported `code` (any code when `None`) in the window, most
+/// recent sighting first.
+pub async fn dtc_reports(
+    pool: &PgPool,
+    code: Option<&str>,
+    critical_only: bool,
+    since: Option<DateTime<Utc>>,
+    until: DateTime<Utc>,
+    fleets: Option<&[String]>,
+    limit: usize,
+) -> Result<Vec<DtcReportRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT h.device_id,
+                array_agg(DISTINCT h.code ORDER BY h.code) AS codes,
+                COUNT(*) AS occurrences,
+                MIN(h.observed_at) AS first_seen,
+                MAX(h.observed_at) AS last_seen
+         FROM dtc_history h
+         JOIN devices d ON d.device_id = h.device_id
+         WHERE TRUE",
+    );
+    if let Some(code) = code {
+        qb.push(" AND h.code = ").push_bind(code.to_string());
+    }
+    if critical_only {
+        qb.push(" AND h.severity = 'critical'");
+    }
+    push_window(&mut qb, "h.observed_at", since, until);
+    push_fleets(&mut qb, "d.metadata->>'fleet'", fleets);
+    qb.push(" GROUP BY h.device_id ORDER BY last_seen DESC, h.device_id LIMIT ")
+        .push_bind(limit as i64);
+    qb.build_query_as::<DtcReportRow>().fetch_all(pool).await
+}
+
+/// Commands that ended `failed` or `timeout` in the window, newest first.
+pub async fn failed_commands(
+    pool: &PgPool,
+    tool_name: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    until: DateTime<Utc>,
+    fleets: Option<&[String]>,
+    limit: usize,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT * FROM commands WHERE status IN ('failed', 'timeout')");
+    if let Some(tool_name) = tool_name {
+        qb.push(" AND tool_name = ")
+            .push_bind(tool_name.to_string());
+    }
+    push_window(&mut qb, "created_at", since, until);
+    push_fleets(&mut qb, "fleet_id", fleets);
+    qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(limit as i64);
+    qb.build_query_as::<CommandRow>().fetch_all(pool).await
+}
+
+/// Devices with readings of `metric` beyond `threshold` in the window, most
+/// recent reading first. The peak is the maximum for `gt`/`gte` and the
+/// minimum for `lt`/`lte`.
+#[allow(clippy::too_many_arguments)]
+pub async fn telemetry_threshold(
+    pool: &PgPool,
+    metric: &str,
+    op: Comparison,
+    threshold: f64,
+    since: Option<DateTime<Utc>>,
+    until: DateTime<Utc>,
+    fleets: Option<&[String]>,
+    limit: usize,
+) -> Result<Vec<ThresholdRow>, sqlx::Error> {
+    let peak = match op {
+        Comparison::Gt | Comparison::Gte => "MAX",
+        Comparison::Lt | Comparison::Lte => "MIN",
+    };
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "SELECT t.device_id,
+                COUNT(*) AS readings,
+                {peak}(t.value_numeric) AS peak,
+                MAX(t.time) AS last_seen
+         FROM telemetry_readings t
+         JOIN devices d ON d.device_id = t.device_id
+         WHERE t.metric_name = "
+    ));
+    qb.push_bind(metric.to_string())
+        .push(format!(" AND t.value_numeric {} ", op.symbol()))
+        .push_bind(threshold);
+    push_window(&mut qb, "t.time", since, until);
+    push_fleets(&mut qb, "d.metadata->>'fleet'", fleets);
+    qb.push(" GROUP BY t.device_id ORDER BY last_seen DESC, t.device_id LIMIT ")
+        .push_bind(limit as i64);
+    qb.build_query_as::<ThresholdRow>().fetch_all(pool).await
+}
+
+/// Devices currently in `status`: `(device_id, fleet, last_heartbeat)`,
+/// ordered by device ID.
+pub async fn devices_by_status(
+    pool: &PgPool,
+    status: &str,
+    fleets: Option<&[String]>,
+    limit: usize,
+) -> Result<Vec<(String, Option<String>, Option<DateTime<Utc>>)>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT device_id, metadata->>'fleet', last_heartbeat FROM devices WHERE status = ",
+    );
+    qb.push_bind(status.to_string());
+    push_fleets(&mut qb, "metadata->>'fleet'", fleets);
+    qb.push(" ORDER BY device_id LIMIT ")
+        .push_bind(limit as i64);
+    qb.build_query_as().fetch_all(pool).await
+}
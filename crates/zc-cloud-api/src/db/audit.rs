@@ -0,0 +1,118 @@
+//! Audit log queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::audit::{AuditAction, AuditEntry, AuditFilter, AuditOutcome};
+
+/// Audit row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AuditRow {
+    pub id: i64,
+    pub at: DateTime<Utc>,
+    pub actor: String,
+    pub action: String,
+    pub command_id: Option<Uuid>,
+    pub device_id: Option<String>,
+    pub detail: serde_json::Value,
+    pub fleet_id: Option<String>,
+    pub outcome: Option<String>,
+}
+
+impl AuditRow {
+    /// The entry, or `None` for an action this version does not know.
+    pub fn into_entry(self) -> Option<AuditEntry> {
+        Some(AuditEntry {
+            action: AuditAction::parse(&self.action)?,
+            at: self.at,
+            actor: self.actor,
+            command_id: self.command_id,
+            device_id: self.device_id,
+            fleet_id: self.fleet_id,
+            outcome: self.outcome.as_deref().and_then(AuditOutcome::parse),
+            detail: self.detail,
+        })
+    }
+}
+
+fn push_where(filter: &AuditFilter, qb: &mut QueryBuilder<'_, Postgres>) {
+    qb.push(" WHERE TRUE");
+    if let Some(actor) = &filter.actor {
+        qb.push(" AND actor = ").push_bind(actor.clone());
+    }
+    if let Some(action) = filter.action {
+        qb.push(" AND action = ").push_bind(action.as_str());
+    }
+    if let Some(outcome) = filter.outcome {
+        qb.push(" AND outcome = ").push_bind(outcome.as_str());
+    }
+    if let Some(command_id) = filter.command_id {
+        qb.push(" AND command_id = ").push_bind(command_id);
+    }
+    if let Some(device_id) = &filter.device_id {
+        qb.push(" AND device_id = ").push_bind(device_id.clone());
+    }
+    if let Some(fleet_id) = &filter.fleet_id {
+        qb.push(" AND fleet_id = ").push_bind(fleet_id.clone());
+    }
+    if let Some(since) = filter.since {
+        qb.push(" AND at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        qb.push(" AND at < ").push_bind(until);
+    }
+    if let Some(fleets) = &filter.fleets {
+        qb.push(" AND fleet_id = ANY(")
+            .push_bind(fleets.clone())
+            .push(")");
+    }
+}
+
+/// Append an entry.
+pub async fn insert(pool: &PgPool, entry: &AuditEntry) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO audit_log (at, actor, action, command_id, device_id, detail, fleet_id, outcome)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(entry.at)
+    .bind(&entry.actor)
+    .bind(entry.action.as_str())
+    .bind(entry.command_id)
+    .bind(entry.device_id.as_deref())
+    .bind(&entry.detail)
+    .bind(entry.fleet_id.as_deref())
+    .bind(entry.outcome.map(AuditOutcome::as_str))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Entries about one command, oldest first.
+pub async fn list_for_command(
+    pool: &PgPool,
+    command_id: Uuid,
+) -> Result<Vec<AuditRow>, sqlx::Error> {
+    sqlx::query_as::<_, AuditRow>("SELECT * FROM audit_log WHERE command_id = $1 ORDER BY at, id")
+        .bind(command_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// One page of entries matching `filter`, newest first.
+pub async fn list_page(pool: &PgPool, filter: &AuditFilter) -> Result<Vec<AuditRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM audit_log");
+    push_where(filter, &mut qb);
+    qb.push(" ORDER BY at DESC, id DESC LIMIT ")
+        .push_bind(filter.limit as i64)
+        .push(" OFFSET ")
+        .push_bind(filter.offset as i64);
+    qb.build_query_as::<AuditRow>().fetch_all(pool).await
+}
+
+/// Number of entries matching `filter` (paging aside).
+pub async fn count(pool: &PgPool, filter: &AuditFilter) -> Result<i64, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM audit_log");
+    push_where(filter, &mut qb);
+    qb.build_query_scalar::<i64>().fetch_one(pool).await
+}
@@ -0,0 +1,137 @@
+//! Claim code queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::claim_codes::ClaimCode;
+
+/// Claim code row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ClaimCodeRow {
+    pub id: Uuid,
+    pub sha256: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub tags: serde_json::Value,
+    pub config: Option<serde_json::Value>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub hardware_id: Option<String>,
+}
+
+impl From<ClaimCodeRow> for ClaimCode {
+    fn from(row: ClaimCodeRow) -> Self {
+        Self {
+            id: row.id,
+            fleet_id: row.fleet_id,
+            device_id: row.device_id,
+            tags: serde_json::from_value(row.tags).unwrap_or_default(),
+            config: row.config,
+            sha256: row.sha256,
+            created_by: row.created_by,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            claimed_at: row.claimed_at,
+            hardware_id: row.hardware_id,
+        }
+    }
+}
+
+/// Store a new claim code.
+pub async fn insert(pool: &PgPool, code: &ClaimCode) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO claim_codes (id, sha256, fleet_id, device_id, tags, config, created_by,
+             created_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(code.id)
+    .bind(&code.sha256)
+    .bind(&code.fleet_id)
+    .bind(&code.device_id)
+    .bind(serde_json::to_value(&code.tags).unwrap_or_default())
+    .bind(&code.config)
+    .bind(&code.created_by)
+    .bind(code.created_at)
+    .bind(code.expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Claim codes newest first, limited to `fleets` when given.
+pub async fn list(
+    pool: &PgPool,
+    fleets: Option<&[String]>,
+) -> Result<Vec<ClaimCodeRow>, sqlx::Error> {
+    sqlx::query_as::<_, ClaimCodeRow>(
+        "SELECT * FROM claim_codes WHERE $1::TEXT[] IS NULL OR fleet_id = ANY($1)
+         ORDER BY created_at DESC, id DESC",
+    )
+    .bind(fleets)
+    .fetch_all(pool)
+    .await
+}
+
+/// A claim code by ID.
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<ClaimCodeRow>, sqlx::Error> {
+    sqlx::query_as::<_, ClaimCodeRow>("SELECT * FROM claim_codes WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The claim code with this SHA-256, if any.
+pub async fn by_sha256(pool: &PgPool, sha256: &str) -> Result<Option<ClaimCodeRow>, sqlx::Error> {
+    sqlx::query_as::<_, ClaimCodeRow>("SELECT * FROM claim_codes WHERE sha256 = $1")
+        .bind(sha256)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Delete an unclaimed code. Returns `false` if it is unknown or claimed.
+pub async fn delete_unclaimed(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM claim_codes WHERE id = $1 AND claimed_at IS NULL")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Mark an unclaimed code claimed. Returns `false` if someone else got
+/// there first.
+pub async fn mark_claimed(
+    pool: &PgPool,
+    id: Uuid,
+    hardware_id: &str,
+    device_id: &str,
+    at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE claim_codes SET claimed_at = $4, hardware_id = $2, device_id = $3
+         WHERE id = $1 AND claimed_at IS NULL",
+    )
+    .bind(id)
+    .bind(hardware_id)
+    .bind(device_id)
+    .bind(at)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Undo [`mark_claimed`] when the device could not be provisioned,
+/// restoring the preassigned device ID.
+pub async fn release(pool: &PgPool, id: Uuid, device_id: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE claim_codes SET claimed_at = NULL, hardware_id = NULL, device_id = $2
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(device_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,7 +1,7 @@
 //! Command dispatch and response queries.
 
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
 /// Command row returned from the database.
@@ -28,15 +28,44 @@ pub struct CommandRow {
     pub latency_ms: Option<i64>,
     pub responded_at: Option<DateTime<Utc>>,
     pub error: Option<String>,
+    /// Wire name of the response's `ErrorCode` (e.g. `can_timeout`).
+    pub error_code: Option<String>,
 
     pub created_at: DateTime<Utc>,
+
+    /// Serialized `CommandEnvelope`, replayed when a queued command is flushed.
+    pub envelope: Option<serde_json::Value>,
+    /// Conversation the command belongs to.
+    pub conversation_id: Option<Uuid>,
+    /// When the command times out if still pending.
+    pub deadline_at: Option<DateTime<Utc>>,
+    /// Publish attempts made so far.
+    pub attempts: i32,
+    /// Publish attempts allowed before the command fails.
+    pub max_attempts: i32,
+    /// When the next publish is due after a failed one.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// Client-supplied key that makes resubmission return this command.
+    pub idempotency_key: Option<String>,
+    /// When the command was last published to its device (cloud clock).
+    pub published_at: Option<DateTime<Utc>>,
+    /// When the device's response arrived (cloud clock).
+    pub received_at: Option<DateTime<Utc>>,
+    /// The response's `responded_at`, on the device's clock.
+    pub device_responded_at: Option<DateTime<Utc>>,
+    /// Execution time the device measured.
+    pub execution_ms: Option<i64>,
+    /// Device clock minus cloud clock when the response arrived.
+    pub clock_offset_ms: Option<i64>,
 }
 
-/// Insert a new command (status = 'pending') with inference results.
-pub async fn insert(pool: &PgPool, row: &CommandRow) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        "INSERT INTO commands (id, fleet_id, device_id, natural_language, initiated_by, correlation_id, timeout_secs, status, created_at, tool_name, tool_args, confidence, inference_tier)
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+/// Insert a new command (status = 'pending' or 'queued') with inference
+/// results. Returns `false` if its initiator already used its idempotency key.
+pub async fn insert(pool: &PgPool, row: &CommandRow) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO commands (id, fleet_id, device_id, natural_language, initiated_by, correlation_id, timeout_secs, status, created_at, tool_name, tool_args, confidence, inference_tier, envelope, conversation_id, deadline_at, max_attempts, idempotency_key)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+         ON CONFLICT DO NOTHING",
     )
     .bind(row.id)
     .bind(&row.fleet_id)
@@ -51,9 +80,14 @@ pub async fn insert(pool: &PgPool, row: &CommandRow) -> Result<(), sqlx::Error>
     .bind(&row.tool_args)
     .bind(row.confidence)
     .bind(&row.inference_tier)
+    .bind(&row.envelope)
+    .bind(row.conversation_id)
+    .bind(row.deadline_at)
+    .bind(row.max_attempts)
+    .bind(&row.idempotency_key)
     .execute(pool)
     .await?;
-    Ok(())
+    Ok(result.rows_affected() == 1)
 }
 
 /// Get a command by ID.
@@ -64,14 +98,321 @@ pub async fn get_by_id(pool: &PgPool, command_id: Uuid) -> Result<Option<Command
         .await
 }
 
-/// List recent commands (most recent first).
-pub async fn list_recent(pool: &PgPool, limit: i64) -> Result<Vec<CommandRow>, sqlx::Error> {
-    sqlx::query_as::<_, CommandRow>("SELECT * FROM commands ORDER BY created_at DESC LIMIT $1")
-        .bind(limit)
+/// The command `initiated_by` submitted with `idempotency_key`.
+pub async fn get_by_idempotency_key(
+    pool: &PgPool,
+    initiated_by: &str,
+    idempotency_key: &str,
+) -> Result<Option<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>(
+        "SELECT * FROM commands WHERE initiated_by = $1 AND idempotency_key = $2",
+    )
+    .bind(initiated_by)
+    .bind(idempotency_key)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Filters and paging for the command list.
+#[derive(Debug, Clone, Default)]
+pub struct CommandFilter {
+    pub device_id: Option<String>,
+    /// Wire status name (e.g. `completed`).
+    pub status: Option<String>,
+    /// Only commands created at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    pub initiated_by: Option<String>,
+    /// Wire name of the response's error code (e.g. `can_timeout`).
+    pub error_code: Option<String>,
+    /// Only commands to these fleets (the caller's tenants); `None` for all.
+    pub fleets: Option<Vec<String>>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl CommandFilter {
+    /// Whether a command with these fields passes the filters (paging aside).
+    pub fn matches(
+        &self,
+        fleet_id: &str,
+        device_id: &str,
+        status: &str,
+        initiated_by: &str,
+        created_at: DateTime<Utc>,
+        error_code: Option<&str>,
+    ) -> bool {
+        self.device_id.as_deref().is_none_or(|d| d == device_id)
+            && self.status.as_deref().is_none_or(|s| s == status)
+            && self
+                .initiated_by
+                .as_deref()
+                .is_none_or(|i| i == initiated_by)
+            && self.since.is_none_or(|t| created_at >= t)
+            && self
+                .error_code
+                .as_deref()
+                .is_none_or(|c| error_code == Some(c))
+            && self
+                .fleets
+                .as_ref()
+                .is_none_or(|fleets| fleets.iter().any(|f| f == fleet_id))
+    }
+
+    fn push_where(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        qb.push(" WHERE TRUE");
+        if let Some(device_id) = &self.device_id {
+            qb.push(" AND device_id = ").push_bind(device_id.clone());
+        }
+        if let Some(status) = &self.status {
+            qb.push(" AND status = ").push_bind(status.clone());
+        }
+        if let Some(since) = self.since {
+            qb.push(" AND created_at >= ").push_bind(since);
+        }
+        if let Some(initiated_by) = &self.initiated_by {
+            qb.push(" AND initiated_by = ")
+                .push_bind(initiated_by.clone());
+        }
+        if let Some(error_code) = &self.error_code {
+            qb.push(" AND error_code = ").push_bind(error_code.clone());
+        }
+        if let Some(fleets) = &self.fleets {
+            qb.push(" AND fleet_id = ANY(")
+                .push_bind(fleets.clone())
+                .push(")");
+        }
+    }
+}
+
+/// One page of commands matching `filter` (most recent first).
+pub async fn list_page(
+    pool: &PgPool,
+    filter: &CommandFilter,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM commands");
+    filter.push_where(&mut qb);
+    qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(filter.limit as i64)
+        .push(" OFFSET ")
+        .push_bind(filter.offset as i64);
+    qb.build_query_as::<CommandRow>().fetch_all(pool).await
+}
+
+/// Number of commands matching `filter`, ignoring paging.
+pub async fn count(pool: &PgPool, filter: &CommandFilter) -> Result<i64, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM commands");
+    filter.push_where(&mut qb);
+    qb.build_query_scalar::<i64>().fetch_one(pool).await
+}
+
+/// List the per-device commands sharing a correlation ID (a fleet broadcast).
+pub async fn list_by_correlation(
+    pool: &PgPool,
+    correlation_id: Uuid,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>(
+        "SELECT * FROM commands WHERE correlation_id = $1 ORDER BY device_id",
+    )
+    .bind(correlation_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// The commands with the given IDs, in no particular order.
+pub async fn list_by_ids(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>("SELECT * FROM commands WHERE id = ANY($1)")
+        .bind(ids)
         .fetch_all(pool)
         .await
 }
 
+/// The last `limit` commands of a conversation in `fleet_id`, newest first.
+pub async fn list_by_conversation(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    fleet_id: &str,
+    limit: usize,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>(
+        "SELECT * FROM commands WHERE conversation_id = $1 AND fleet_id = $2
+         ORDER BY created_at DESC, id DESC LIMIT $3",
+    )
+    .bind(conversation_id)
+    .bind(fleet_id)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Move a device's queued commands to `pending` and return them (oldest
+/// first, delivery order). A concurrent claim gets only rows this one did
+/// not.
+pub async fn claim_queued(pool: &PgPool, device_id: &str) -> Result<Vec<CommandRow>, sqlx::Error> {
+    let mut rows = sqlx::query_as::<_, CommandRow>(
+        "UPDATE commands SET status = 'pending' \
+         WHERE device_id = $1 AND status = 'queued' RETURNING *",
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    rows.sort_by_key(|r| r.created_at);
+    Ok(rows)
+}
+
+/// Put claimed commands that were not published back in the queue.
+pub async fn requeue(pool: &PgPool, command_ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE commands SET status = 'queued' WHERE id = ANY($1) AND status = 'pending'")
+        .bind(command_ids)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Set a command's status without touching response fields.
+pub async fn update_status(
+    pool: &PgPool,
+    command_id: Uuid,
+    status: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE commands SET status = $1 WHERE id = $2")
+        .bind(status)
+        .bind(command_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Move a command from status `from` to `to`. Returns `false` when the
+/// command was no longer in `from` (e.g. a concurrent approval won).
+pub async fn transition_status(
+    pool: &PgPool,
+    command_id: Uuid,
+    from: &str,
+    to: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE commands SET status = $1 WHERE id = $2 AND status = $3")
+        .bind(to)
+        .bind(command_id)
+        .bind(from)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Set when a pending command times out.
+pub async fn set_deadline(
+    pool: &PgPool,
+    command_id: Uuid,
+    deadline_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE commands SET deadline_at = $1 WHERE id = $2")
+        .bind(deadline_at)
+        .bind(command_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Mark every command still pending past its deadline as timed out;
+/// returns the commands moved.
+pub async fn time_out_overdue(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>(
+        "UPDATE commands SET status = 'timeout', error_code = 'timeout',
+         error = 'no response within ' || timeout_secs || 's',
+         responded_at = $1,
+         latency_ms = (EXTRACT(EPOCH FROM ($1 - created_at)) * 1000)::BIGINT
+         WHERE status = 'pending' AND deadline_at <= $1
+         RETURNING *",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await
+}
+
+/// Record a publish attempt of a pending command: the new attempt count
+/// and, after a failed publish, when to try again. Waiting for a retry
+/// clears the deadline. Returns `false` when the command is no longer
+/// pending.
+pub async fn record_attempt(
+    pool: &PgPool,
+    command_id: Uuid,
+    attempts: i32,
+    next_attempt_at: Option<DateTime<Utc>>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE commands SET attempts = $1, next_attempt_at = $2,
+         deadline_at = CASE WHEN $2::timestamptz IS NULL THEN deadline_at END
+         WHERE id = $3 AND status = 'pending'",
+    )
+    .bind(attempts)
+    .bind(next_attempt_at)
+    .bind(command_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Claim the pending commands whose next publish is due at `now`. The
+/// claim clears `next_attempt_at` and starts a deadline, so a command whose
+/// retry is lost (the replica stops mid-publish) still times out.
+pub async fn claim_due_attempts(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>(
+        "UPDATE commands SET next_attempt_at = NULL,
+         deadline_at = $1 + make_interval(secs => COALESCE(NULLIF(timeout_secs, 0), $2) + $3)
+         WHERE status = 'pending' AND next_attempt_at <= $1
+         RETURNING *",
+    )
+    .bind(now)
+    .bind(crate::command_timeouts::UNSET_TIMEOUT_SECS as i32)
+    .bind(crate::command_timeouts::GRACE_SECS as i32)
+    .fetch_all(pool)
+    .await
+}
+
+/// Record that a command was published to its device at `at`.
+pub async fn set_published(
+    pool: &PgPool,
+    command_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE commands SET published_at = $1 WHERE id = $2")
+        .bind(at)
+        .bind(command_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Record how a device response arrived: cloud receive time, the device's
+/// own timestamp and execution time, and the device clock offset.
+pub async fn set_receipt(
+    pool: &PgPool,
+    command_id: Uuid,
+    receipt: &crate::command_timing::Receipt,
+    device_responded_at: DateTime<Utc>,
+    execution_ms: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE commands SET received_at = $1, device_responded_at = $2, execution_ms = $3,
+         clock_offset_ms = $4
+         WHERE id = $5",
+    )
+    .bind(receipt.received_at)
+    .bind(device_responded_at)
+    .bind(execution_ms)
+    .bind(receipt.clock_offset_ms)
+    .bind(command_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 /// Update command with a response.
 #[allow(clippy::too_many_arguments)]
 pub async fn update_response(
@@ -83,11 +424,13 @@ pub async fn update_response(
     response_data: Option<&serde_json::Value>,
     latency_ms: i64,
     error: Option<&str>,
+    error_code: Option<&str>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
         "UPDATE commands SET status = $1, inference_tier = $2, response_text = $3,
-         response_data = $4, latency_ms = $5, responded_at = now(), error = $6
-         WHERE id = $7",
+         response_data = $4, latency_ms = $5, responded_at = now(), error = $6,
+         error_code = $7
+         WHERE id = $8",
     )
     .bind(status)
     .bind(inference_tier)
@@ -95,6 +438,7 @@ pub async fn update_response(
     .bind(response_data)
     .bind(latency_ms)
     .bind(error)
+    .bind(error_code)
     .bind(command_id)
     .execute(pool)
     .await?;
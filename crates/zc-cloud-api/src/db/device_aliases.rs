@@ -0,0 +1,79 @@
+//! Device identity alias queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Alias row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DeviceAliasRow {
+    pub kind: String,
+    pub value: String,
+    pub device_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Claim an alias for `row.device_id`.
+///
+/// Returns the device that owns the alias afterwards — `row.device_id` if
+/// the alias was free (or already theirs), otherwise the existing owner.
+pub async fn claim(pool: &PgPool, row: &DeviceAliasRow) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "INSERT INTO device_aliases (kind, value, device_id, created_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (kind, value) DO UPDATE SET kind = EXCLUDED.kind
+         RETURNING device_id",
+    )
+    .bind(&row.kind)
+    .bind(&row.value)
+    .bind(&row.device_id)
+    .bind(row.created_at)
+    .fetch_one(pool)
+    .await
+}
+
+/// Aliases registered for a device, ordered by kind and value.
+pub async fn list_for_device(
+    pool: &PgPool,
+    device_id: &str,
+) -> Result<Vec<DeviceAliasRow>, sqlx::Error> {
+    sqlx::query_as::<_, DeviceAliasRow>(
+        "SELECT * FROM device_aliases WHERE device_id = $1 ORDER BY kind, value",
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Aliases with the given value, optionally restricted to one kind.
+pub async fn lookup(
+    pool: &PgPool,
+    kind: Option<&str>,
+    value: &str,
+) -> Result<Vec<DeviceAliasRow>, sqlx::Error> {
+    sqlx::query_as::<_, DeviceAliasRow>(
+        "SELECT * FROM device_aliases
+         WHERE value = $1 AND ($2::TEXT IS NULL OR kind = $2)
+         ORDER BY kind",
+    )
+    .bind(value)
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+}
+
+/// Remove an alias from a device. Returns whether it existed.
+pub async fn delete(
+    pool: &PgPool,
+    device_id: &str,
+    kind: &str,
+    value: &str,
+) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("DELETE FROM device_aliases WHERE device_id = $1 AND kind = $2 AND value = $3")
+            .bind(device_id)
+            .bind(kind)
+            .bind(value)
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,91 @@
+//! Device report queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::db::commands::CommandRow;
+use crate::device_report::MetricSummary;
+
+/// Per-metric aggregate of a device's readings.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MetricSummaryRow {
+    pub metric_name: String,
+    pub source: String,
+    pub unit: Option<String>,
+    pub readings: i64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub avg: Option<f64>,
+    pub latest: Option<f64>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl From<MetricSummaryRow> for MetricSummary {
+    fn from(row: MetricSummaryRow) -> Self {
+        Self {
+            metric: row.metric_name,
+            source: row.source,
+            unit: row.unit,
+            readings: row.readings as u64,
+            min: row.min,
+            max: row.max,
+            avg: row.avg,
+            latest: row.latest,
+            last_seen: row.last_seen,
+        }
+    }
+}
+
+/// Statistics of each metric and source the device reported since `since`,
+/// by metric name.
+pub async fn telemetry_summary(
+    pool: &PgPool,
+    device_id: &str,
+    since: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<MetricSummaryRow>, sqlx::Error> {
+    sqlx::query_as::<_, MetricSummaryRow>(
+        "SELECT metric_name, source,
+             (array_agg(unit ORDER BY time DESC) FILTER (WHERE unit IS NOT NULL))[1] AS unit,
+             COUNT(*) AS readings,
+             MIN(value_numeric) AS min,
+             MAX(value_numeric) AS max,
+             AVG(value_numeric) AS avg,
+             (array_agg(value_numeric ORDER BY time DESC)
+                 FILTER (WHERE value_numeric IS NOT NULL))[1] AS latest,
+             MAX(time) AS last_seen
+         FROM telemetry_readings
+         WHERE device_id = $1 AND time >= $2
+         GROUP BY metric_name, source
+         ORDER BY metric_name, source
+         LIMIT $3",
+    )
+    .bind(device_id)
+    .bind(since)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// The device's completed `tool_name` commands created since `since`,
+/// newest first.
+pub async fn completed_commands(
+    pool: &PgPool,
+    device_id: &str,
+    tool_name: &str,
+    since: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<CommandRow>, sqlx::Error> {
+    sqlx::query_as::<_, CommandRow>(
+        "SELECT * FROM commands
+         WHERE device_id = $1 AND tool_name = $2 AND status = 'completed'
+             AND created_at >= $3
+         ORDER BY created_at DESC LIMIT $4",
+    )
+    .bind(device_id)
+    .bind(tool_name)
+    .bind(since)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,75 @@
+//! Device status history queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::device_status::{StatusChange, StatusReason};
+use crate::routes::devices::parse_device_status;
+
+/// Status history row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StatusChangeRow {
+    pub id: Uuid,
+    pub device_id: String,
+    pub from_status: String,
+    pub to_status: String,
+    pub reason: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl From<StatusChangeRow> for StatusChange {
+    fn from(row: StatusChangeRow) -> Self {
+        Self {
+            id: row.id,
+            device_id: row.device_id,
+            from: parse_device_status(&row.from_status),
+            to: parse_device_status(&row.to_status),
+            reason: StatusReason::parse(&row.reason).unwrap_or(StatusReason::HeartbeatTimeout),
+            changed_at: row.changed_at,
+        }
+    }
+}
+
+/// Record a status transition.
+pub async fn insert(pool: &PgPool, change: &StatusChange) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO device_status_history (id, device_id, from_status, to_status, reason, changed_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(change.id)
+    .bind(&change.device_id)
+    .bind(crate::device_status::status_name(change.from))
+    .bind(crate::device_status::status_name(change.to))
+    .bind(change.reason.as_str())
+    .bind(change.changed_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// One page of a device's status transitions, newest first.
+pub async fn list(
+    pool: &PgPool,
+    device_id: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<StatusChangeRow>, sqlx::Error> {
+    sqlx::query_as::<_, StatusChangeRow>(
+        "SELECT * FROM device_status_history WHERE device_id = $1
+         ORDER BY changed_at DESC, id DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(device_id)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Number of status transitions recorded for a device.
+pub async fn count(pool: &PgPool, device_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM device_status_history WHERE device_id = $1")
+        .bind(device_id)
+        .fetch_one(pool)
+        .await
+}
@@ -0,0 +1,81 @@
+//! Device tag queries.
+
+use std::collections::BTreeMap;
+
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+/// Set tags on a device (existing keys are overwritten), in one transaction.
+pub async fn upsert(
+    pool: &PgPool,
+    device_id: &str,
+    tags: &BTreeMap<String, String>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (key, value) in tags {
+        sqlx::query(
+            "INSERT INTO device_tags (device_id, key, value, updated_at)
+             VALUES ($1, $2, $3, now())
+             ON CONFLICT (device_id, key) DO UPDATE
+             SET value = EXCLUDED.value, updated_at = now()",
+        )
+        .bind(device_id)
+        .bind(key)
+        .bind(value)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// All tags of a device.
+pub async fn list_for_device(
+    pool: &PgPool,
+    device_id: &str,
+) -> Result<BTreeMap<String, String>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT key, value FROM device_tags WHERE device_id = $1",
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Remove one tag. Returns whether it existed.
+pub async fn delete(pool: &PgPool, device_id: &str, key: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM device_tags WHERE device_id = $1 AND key = $2")
+        .bind(device_id)
+        .bind(key)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Devices carrying every one of the `(key, value)` tags.
+pub async fn devices_matching(
+    pool: &PgPool,
+    tags: &[(String, String)],
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT device_id FROM devices WHERE TRUE");
+    push_tag_conditions(&mut qb, "devices.device_id", tags);
+    qb.push(" ORDER BY device_id");
+    qb.build_query_scalar::<String>().fetch_all(pool).await
+}
+
+/// Append one `AND EXISTS (...)` per tag, matching on `device_column`.
+pub fn push_tag_conditions(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    device_column: &str,
+    tags: &[(String, String)],
+) {
+    for (key, value) in tags {
+        qb.push(format!(
+            " AND EXISTS (SELECT 1 FROM device_tags t WHERE t.device_id = {device_column} AND t.key = "
+        ))
+        .push_bind(key.clone())
+        .push(" AND t.value = ")
+        .push_bind(value.clone())
+        .push(")");
+    }
+}
@@ -1,7 +1,9 @@
 //! Device registry queries.
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
 /// Device row returned from the database.
@@ -14,7 +16,10 @@ pub struct DeviceRow {
     pub vin: Option<String>,
     pub hardware_type: String,
     pub certificate_id: Option<String>,
+    pub certificate_expires_at: Option<DateTime<Utc>>,
     pub last_heartbeat: Option<DateTime<Utc>>,
+    /// Latest reported [`zc_protocol::device::DeviceVitals`].
+    pub vitals: Option<serde_json::Value>,
     pub metadata: serde_json::Value,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -27,6 +32,98 @@ pub async fn list_all(pool: &PgPool) -> Result<Vec<DeviceRow>, sqlx::Error> {
         .await
 }
 
+/// Filters and paging for the device list.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFilter {
+    /// Wire status name (e.g. `online`).
+    pub status: Option<String>,
+    /// Only devices heard from at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only devices whose certificate expires at or before this time.
+    pub cert_expires_before: Option<DateTime<Utc>>,
+    /// `(key, value)` tags the device must all carry.
+    pub tags: Vec<(String, String)>,
+    /// Only devices in these fleets (the caller's tenants); `None` for all.
+    pub fleets: Option<Vec<String>>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl DeviceFilter {
+    /// Whether a device with these fields passes the filters (paging aside).
+    pub fn matches(
+        &self,
+        status: &str,
+        last_heartbeat: Option<DateTime<Utc>>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        tags: Option<&BTreeMap<String, String>>,
+        fleet: Option<&str>,
+    ) -> bool {
+        self.status.as_deref().is_none_or(|s| s == status)
+            && self
+                .since
+                .is_none_or(|t| last_heartbeat.is_some_and(|hb| hb >= t))
+            && self
+                .cert_expires_before
+                .is_none_or(|t| cert_expires_at.is_some_and(|at| at <= t))
+            && crate::device_tags::matches(tags, &self.tags)
+            && self
+                .fleets
+                .as_ref()
+                .is_none_or(|fleets| fleet.is_some_and(|f| fleets.iter().any(|x| x == f)))
+    }
+
+    fn push_where(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        qb.push(" WHERE TRUE");
+        if let Some(status) = &self.status {
+            qb.push(" AND status = ").push_bind(status.clone());
+        }
+        if let Some(since) = self.since {
+            qb.push(" AND last_heartbeat >= ").push_bind(since);
+        }
+        if let Some(before) = self.cert_expires_before {
+            qb.push(" AND certificate_expires_at <= ").push_bind(before);
+        }
+        super::device_tags::push_tag_conditions(qb, "devices.device_id", &self.tags);
+        if let Some(fleets) = &self.fleets {
+            qb.push(" AND metadata->>'fleet' = ANY(")
+                .push_bind(fleets.clone())
+                .push(")");
+        }
+    }
+}
+
+/// One page of devices matching `filter`, ordered by device ID.
+pub async fn list_page(
+    pool: &PgPool,
+    filter: &DeviceFilter,
+) -> Result<Vec<DeviceRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM devices");
+    filter.push_where(&mut qb);
+    qb.push(" ORDER BY device_id LIMIT ")
+        .push_bind(filter.limit as i64)
+        .push(" OFFSET ")
+        .push_bind(filter.offset as i64);
+    qb.build_query_as::<DeviceRow>().fetch_all(pool).await
+}
+
+/// Number of devices matching `filter`, ignoring paging.
+pub async fn count(pool: &PgPool, filter: &DeviceFilter) -> Result<i64, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM devices");
+    filter.push_where(&mut qb);
+    qb.build_query_scalar::<i64>().fetch_one(pool).await
+}
+
+/// List devices whose metadata names `fleet_id` as their fleet.
+pub async fn list_by_fleet(pool: &PgPool, fleet_id: &str) -> Result<Vec<DeviceRow>, sqlx::Error> {
+    sqlx::query_as::<_, DeviceRow>(
+        "SELECT * FROM devices WHERE metadata->>'fleet' = $1 ORDER BY device_id",
+    )
+    .bind(fleet_id)
+    .fetch_all(pool)
+    .await
+}
+
 /// Get a device by its string identifier.
 pub async fn get_by_device_id(
     pool: &PgPool,
@@ -70,18 +167,87 @@ pub async fn insert(pool: &PgPool, row: &DeviceRow) -> Result<(), sqlx::Error> {
     Ok(())
 }
 
-/// Update the last heartbeat timestamp.
+/// Update the last heartbeat timestamp (and the certificate expiry, if
+/// reported) and mark the device online. Returns the status it had before,
+/// or `None` for an unknown device.
 pub async fn update_heartbeat(
     pool: &PgPool,
     device_id: &str,
     heartbeat_at: DateTime<Utc>,
-) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE devices SET last_heartbeat = $1, updated_at = now() WHERE device_id = $2")
-        .bind(heartbeat_at)
-        .bind(device_id)
-        .execute(pool)
-        .await?;
-    Ok(())
+    cert_expires_at: Option<DateTime<Utc>>,
+    vitals: Option<serde_json::Value>,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "WITH old AS (SELECT status FROM devices WHERE device_id = $2)
+         UPDATE devices SET last_heartbeat = $1, status = 'online',
+             certificate_expires_at = COALESCE($3, certificate_expires_at),
+             vitals = COALESCE($4, vitals),
+             updated_at = now()
+         WHERE device_id = $2
+         RETURNING (SELECT status FROM old)",
+    )
+    .bind(heartbeat_at)
+    .bind(device_id)
+    .bind(cert_expires_at)
+    .bind(vitals)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Record the newest certificate issued to a device. Returns `false` for
+/// an unknown device.
+pub async fn set_certificate(
+    pool: &PgPool,
+    device_id: &str,
+    certificate_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE devices SET certificate_id = $2, updated_at = now() WHERE device_id = $1",
+    )
+    .bind(device_id)
+    .bind(certificate_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Move a device to status `to` if its status is one of `from`. Returns
+/// the status it had (and so changed from), or `None` if it did not change.
+pub async fn transition_status(
+    pool: &PgPool,
+    device_id: &str,
+    from: &[&str],
+    to: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "WITH old AS (SELECT status FROM devices WHERE device_id = $1)
+         UPDATE devices SET status = $3, updated_at = now()
+         WHERE device_id = $1 AND status = ANY($2)
+         RETURNING (SELECT status FROM old)",
+    )
+    .bind(device_id)
+    .bind(from)
+    .bind(to)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Devices in one of `statuses` whose last heartbeat is at or before
+/// `cutoff`, as `(device_id, status, last_heartbeat)`.
+pub async fn list_silent_since(
+    pool: &PgPool,
+    statuses: &[&str],
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<(String, String, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String, DateTime<Utc>)>(
+        "SELECT device_id, status, last_heartbeat FROM devices
+         WHERE status = ANY($1) AND last_heartbeat <= $2
+         ORDER BY device_id",
+    )
+    .bind(statuses)
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await
 }
 
 /// Auto-register a device from its first heartbeat, or update if it already exists.
@@ -89,27 +255,37 @@ pub async fn update_heartbeat(
 /// Uses INSERT ... ON CONFLICT so that new devices are created automatically
 /// when they first connect, while existing devices just get their heartbeat
 /// and status updated. The `machine_id` (from `/etc/machine-id`) is stored
-/// in the metadata JSON for hardware fingerprinting.
+/// in the metadata JSON for hardware fingerprinting. A reported certificate
+/// expiry or vitals replace the stored ones; heartbeats without them leave
+/// them alone.
+///
+/// Returns the status an existing device had before, `None` if it was new.
 pub async fn upsert_from_heartbeat(
     pool: &PgPool,
     device_id: &str,
     fleet_id: &str,
     machine_id: Option<&str>,
     heartbeat_at: DateTime<Utc>,
-) -> Result<(), sqlx::Error> {
+    cert_expires_at: Option<DateTime<Utc>>,
+    vitals: Option<serde_json::Value>,
+) -> Result<Option<String>, sqlx::Error> {
     let now = Utc::now();
     let mut metadata = serde_json::json!({ "fleet": fleet_id, "auto_registered": true });
     if let Some(mid) = machine_id {
         metadata["machine_id"] = serde_json::Value::String(mid.to_string());
     }
-    sqlx::query(
-        "INSERT INTO devices (id, fleet_id, device_id, status, hardware_type, last_heartbeat, metadata, created_at, updated_at)
-         VALUES ($1, $2, $3, 'online', 'auto', $4, $5, $6, $6)
+    sqlx::query_scalar::<_, Option<String>>(
+        "WITH old AS (SELECT status FROM devices WHERE device_id = $3)
+         INSERT INTO devices (id, fleet_id, device_id, status, hardware_type, last_heartbeat, metadata, created_at, updated_at, certificate_expires_at, vitals)
+         VALUES ($1, $2, $3, 'online', 'auto', $4, $5, $6, $6, $7, $8)
          ON CONFLICT (device_id) DO UPDATE
          SET last_heartbeat = EXCLUDED.last_heartbeat,
              status = 'online',
              metadata = EXCLUDED.metadata,
-             updated_at = now()",
+             certificate_expires_at = COALESCE(EXCLUDED.certificate_expires_at, devices.certificate_expires_at),
+             vitals = COALESCE(EXCLUDED.vitals, devices.vitals),
+             updated_at = now()
+         RETURNING (SELECT status FROM old)",
     )
     .bind(Uuid::now_v7())
     .bind(Uuid::now_v7()) // fleet_id as UUID — placeholder for string fleet names
@@ -117,7 +293,42 @@ pub async fn upsert_from_heartbeat(
     .bind(heartbeat_at)
     .bind(metadata)
     .bind(now)
-    .execute(pool)
-    .await?;
-    Ok(())
+    .bind(cert_expires_at)
+    .bind(vitals)
+    .fetch_one(pool)
+    .await
+}
+
+/// Evaluated devices (not in maintenance, decommissioned or provisioning)
+/// whose last heartbeat is at or before `cutoff`, as `(device_id, fleet,
+/// last_heartbeat)`; the fleet comes from the device metadata.
+pub async fn list_heartbeat_gaps(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<(String, Option<String>, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, Option<String>, DateTime<Utc>)>(
+        "SELECT device_id, metadata->>'fleet', last_heartbeat FROM devices
+         WHERE status IN ('online', 'degraded', 'offline') AND last_heartbeat <= $1
+         ORDER BY device_id",
+    )
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await
+}
+
+/// Devices (other than decommissioned ones) whose certificate expires at
+/// or before `before`, as `(device_id, fleet, certificate_expires_at)`;
+/// the fleet comes from the device metadata.
+pub async fn list_certificates_expiring(
+    pool: &PgPool,
+    before: DateTime<Utc>,
+) -> Result<Vec<(String, Option<String>, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, Option<String>, DateTime<Utc>)>(
+        "SELECT device_id, metadata->>'fleet', certificate_expires_at FROM devices
+         WHERE status <> 'decommissioned' AND certificate_expires_at <= $1
+         ORDER BY certificate_expires_at, device_id",
+    )
+    .bind(before)
+    .fetch_all(pool)
+    .await
 }
@@ -0,0 +1,86 @@
+//! DTC scan history queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::dtc_history::DtcScan;
+
+/// Per-code aggregate of a device's history.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DtcSummaryRow {
+    pub code: String,
+    /// Severity and description as of the most recent sighting.
+    pub severity: String,
+    pub description: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub occurrences: i64,
+}
+
+/// Store a scan and one history row per reported code, in one transaction.
+pub async fn insert_scan(
+    pool: &PgPool,
+    device_id: &str,
+    scan: &DtcScan,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO dtc_scans (device_id, scanned_at, command_id, tool_name, dtc_count)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(device_id)
+    .bind(scan.scanned_at)
+    .bind(scan.command_id)
+    .bind(&scan.tool_name)
+    .bind(scan.dtcs.len() as i32)
+    .execute(&mut *tx)
+    .await?;
+    for dtc in &scan.dtcs {
+        sqlx::query(
+            "INSERT INTO dtc_history
+                 (device_id, code, observed_at, command_id, severity, description)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(device_id)
+        .bind(&dtc.code)
+        .bind(scan.scanned_at)
+        .bind(scan.command_id)
+        .bind(&dtc.severity)
+        .bind(dtc.description.as_deref())
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Time of the device's most recent scan.
+pub async fn latest_scan(
+    pool: &PgPool,
+    device_id: &str,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT MAX(scanned_at) FROM dtc_scans WHERE device_id = $1")
+        .bind(device_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Every code the device ever reported, most recently seen first.
+pub async fn summary(pool: &PgPool, device_id: &str) -> Result<Vec<DtcSummaryRow>, sqlx::Error> {
+    sqlx::query_as::<_, DtcSummaryRow>(
+        "SELECT code,
+                (array_agg(severity ORDER BY observed_at DESC))[1] AS severity,
+                (array_agg(description ORDER BY observed_at DESC))[1] AS description,
+                MIN(observed_at) AS first_seen,
+                MAX(observed_at) AS last_seen,
+                COUNT(*) AS occurrences
+         FROM dtc_history
+         WHERE device_id = $1
+         GROUP BY code
+         ORDER BY last_seen DESC, code",
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,63 @@
+//! DTC knowledge base queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Knowledge row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DtcKnowledgeRow {
+    pub code: String,
+    pub repair_hint: Option<String>,
+    /// JSON array of `{title, url}` objects.
+    pub links: serde_json::Value,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Insert or replace entries, all in one transaction.
+pub async fn upsert(pool: &PgPool, rows: &[DtcKnowledgeRow]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for row in rows {
+        sqlx::query(
+            "INSERT INTO dtc_knowledge (code, repair_hint, links, updated_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (code) DO UPDATE
+             SET repair_hint = EXCLUDED.repair_hint,
+                 links = EXCLUDED.links,
+                 updated_at = EXCLUDED.updated_at",
+        )
+        .bind(&row.code)
+        .bind(&row.repair_hint)
+        .bind(&row.links)
+        .bind(row.updated_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// All entries, ordered by code.
+pub async fn list(pool: &PgPool) -> Result<Vec<DtcKnowledgeRow>, sqlx::Error> {
+    sqlx::query_as::<_, DtcKnowledgeRow>("SELECT * FROM dtc_knowledge ORDER BY code")
+        .fetch_all(pool)
+        .await
+}
+
+/// Entries for the given codes (missing codes are skipped).
+pub async fn get_many(
+    pool: &PgPool,
+    codes: &[String],
+) -> Result<Vec<DtcKnowledgeRow>, sqlx::Error> {
+    sqlx::query_as::<_, DtcKnowledgeRow>("SELECT * FROM dtc_knowledge WHERE code = ANY($1)")
+        .bind(codes)
+        .fetch_all(pool)
+        .await
+}
+
+/// Delete the entry for a code. Returns whether it existed.
+pub async fn delete(pool: &PgPool, code: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM dtc_knowledge WHERE code = $1")
+        .bind(code)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
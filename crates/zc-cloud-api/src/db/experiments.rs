@@ -0,0 +1,133 @@
+//! Inference experiment queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Experiment row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ExperimentRow {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub variants: serde_json::Value,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+/// Variant assignment row for a single command.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AssignmentRow {
+    pub command_id: Uuid,
+    pub experiment_id: Uuid,
+    pub variant: String,
+    pub parsed: bool,
+    pub confidence: Option<f64>,
+    pub outcome: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Insert a new active experiment, stopping any experiment already running.
+pub async fn insert(pool: &PgPool, row: &ExperimentRow) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE experiments SET active = false, stopped_at = now() WHERE active")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO experiments (id, name, description, variants, active, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(row.id)
+    .bind(&row.name)
+    .bind(&row.description)
+    .bind(&row.variants)
+    .bind(row.active)
+    .bind(row.created_at)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// List experiments, newest first.
+pub async fn list(pool: &PgPool) -> Result<Vec<ExperimentRow>, sqlx::Error> {
+    sqlx::query_as::<_, ExperimentRow>("SELECT * FROM experiments ORDER BY created_at DESC")
+        .fetch_all(pool)
+        .await
+}
+
+/// Get an experiment by ID.
+pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<ExperimentRow>, sqlx::Error> {
+    sqlx::query_as::<_, ExperimentRow>("SELECT * FROM experiments WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The currently running experiment, if any.
+pub async fn get_active(pool: &PgPool) -> Result<Option<ExperimentRow>, sqlx::Error> {
+    sqlx::query_as::<_, ExperimentRow>(
+        "SELECT * FROM experiments WHERE active ORDER BY created_at DESC LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Stop an experiment. Returns the updated row, or None if it does not exist.
+pub async fn stop(pool: &PgPool, id: Uuid) -> Result<Option<ExperimentRow>, sqlx::Error> {
+    sqlx::query_as::<_, ExperimentRow>(
+        "UPDATE experiments
+         SET active = false, stopped_at = COALESCE(stopped_at, now())
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Record the variant a command was assigned and its parse result.
+pub async fn insert_assignment(pool: &PgPool, row: &AssignmentRow) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO experiment_assignments
+             (command_id, experiment_id, variant, parsed, confidence, outcome, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(row.command_id)
+    .bind(row.experiment_id)
+    .bind(&row.variant)
+    .bind(row.parsed)
+    .bind(row.confidence)
+    .bind(&row.outcome)
+    .bind(row.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Set the downstream outcome for an assigned command (no-op if unassigned).
+pub async fn update_outcome(
+    pool: &PgPool,
+    command_id: Uuid,
+    outcome: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE experiment_assignments SET outcome = $2 WHERE command_id = $1")
+        .bind(command_id)
+        .bind(outcome)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// All assignments for an experiment.
+pub async fn list_assignments(
+    pool: &PgPool,
+    experiment_id: Uuid,
+) -> Result<Vec<AssignmentRow>, sqlx::Error> {
+    sqlx::query_as::<_, AssignmentRow>(
+        "SELECT * FROM experiment_assignments WHERE experiment_id = $1",
+    )
+    .bind(experiment_id)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,166 @@
+//! Fleet and fleet token queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::Role;
+use crate::fleets::{Fleet, FleetToken};
+
+/// Fleet row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FleetRow {
+    pub fleet_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<FleetRow> for Fleet {
+    fn from(row: FleetRow) -> Self {
+        Self {
+            fleet_id: row.fleet_id,
+            name: row.name,
+            description: row.description,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Fleet token row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FleetTokenRow {
+    pub id: Uuid,
+    pub fleet_id: String,
+    pub name: String,
+    pub role: String,
+    pub sha256: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<FleetTokenRow> for FleetToken {
+    fn from(row: FleetTokenRow) -> Self {
+        Self {
+            id: row.id,
+            fleet_id: row.fleet_id,
+            name: row.name,
+            // Roles this version does not know grant the least.
+            role: Role::parse(&row.role).unwrap_or(Role::Viewer),
+            sha256: row.sha256,
+            created_by: row.created_by,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+/// Store a new fleet. Returns `false` if the ID is already taken.
+pub async fn insert(pool: &PgPool, fleet: &Fleet) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO fleets (fleet_id, name, description, created_by, created_at)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (fleet_id) DO NOTHING",
+    )
+    .bind(&fleet.fleet_id)
+    .bind(&fleet.name)
+    .bind(&fleet.description)
+    .bind(&fleet.created_by)
+    .bind(fleet.created_at)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Fleets ordered by ID, limited to `fleets` when given.
+pub async fn list(pool: &PgPool, fleets: Option<&[String]>) -> Result<Vec<FleetRow>, sqlx::Error> {
+    sqlx::query_as::<_, FleetRow>(
+        "SELECT fleet_id, name, description, created_by, created_at
+         FROM fleets WHERE $1::TEXT[] IS NULL OR fleet_id = ANY($1)
+         ORDER BY fleet_id",
+    )
+    .bind(fleets)
+    .fetch_all(pool)
+    .await
+}
+
+/// A fleet by ID.
+pub async fn get(pool: &PgPool, fleet_id: &str) -> Result<Option<FleetRow>, sqlx::Error> {
+    sqlx::query_as::<_, FleetRow>(
+        "SELECT fleet_id, name, description, created_by, created_at
+         FROM fleets WHERE fleet_id = $1",
+    )
+    .bind(fleet_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Whether any fleet is registered.
+pub async fn any(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM fleets)")
+        .fetch_one(pool)
+        .await
+}
+
+/// Delete a fleet and (by cascade) its tokens. Returns `false` if unknown.
+pub async fn delete(pool: &PgPool, fleet_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM fleets WHERE fleet_id = $1")
+        .bind(fleet_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Store a new fleet token.
+pub async fn insert_token(pool: &PgPool, token: &FleetToken) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO fleet_tokens (id, fleet_id, name, role, sha256, created_by, created_at,
+             expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(token.id)
+    .bind(&token.fleet_id)
+    .bind(&token.name)
+    .bind(token.role.as_str())
+    .bind(&token.sha256)
+    .bind(&token.created_by)
+    .bind(token.created_at)
+    .bind(token.expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Tokens of a fleet, oldest first.
+pub async fn list_tokens(pool: &PgPool, fleet_id: &str) -> Result<Vec<FleetTokenRow>, sqlx::Error> {
+    sqlx::query_as::<_, FleetTokenRow>(
+        "SELECT * FROM fleet_tokens WHERE fleet_id = $1 ORDER BY created_at, id",
+    )
+    .bind(fleet_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// The token with this SHA-256, if any.
+pub async fn token_by_sha256(
+    pool: &PgPool,
+    sha256: &str,
+) -> Result<Option<FleetTokenRow>, sqlx::Error> {
+    sqlx::query_as::<_, FleetTokenRow>("SELECT * FROM fleet_tokens WHERE sha256 = $1")
+        .bind(sha256)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Delete a fleet's token. Returns `false` if the fleet has no such token.
+pub async fn delete_token(pool: &PgPool, fleet_id: &str, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM fleet_tokens WHERE fleet_id = $1 AND id = $2")
+        .bind(fleet_id)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
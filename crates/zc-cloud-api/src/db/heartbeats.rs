@@ -0,0 +1,17 @@
+//! Heartbeat log queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Remove heartbeats received before `before`, dropping whole chunks on a
+/// hypertable. Returns the rows deleted, or chunks dropped.
+pub async fn prune(pool: &PgPool, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    if super::timescale::is_hypertable(pool, "heartbeats").await? {
+        return super::timescale::drop_chunks(pool, "heartbeats", before).await;
+    }
+    let result = sqlx::query("DELETE FROM heartbeats WHERE received_at < $1")
+        .bind(before)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
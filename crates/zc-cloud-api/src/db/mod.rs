@@ -2,10 +2,30 @@
 //!
 //! Each sub-module provides typed query functions over a `PgPool`.
 
+pub mod agent_releases;
+pub mod alert_rules;
+pub mod alerts;
+pub mod analytics;
+pub mod audit;
+pub mod claim_codes;
 pub mod commands;
+pub mod device_aliases;
+pub mod device_report;
+pub mod device_status;
+pub mod device_tags;
 pub mod devices;
+pub mod dtc_history;
+pub mod dtc_knowledge;
+pub mod experiments;
+pub mod fleets;
+pub mod heartbeats;
+pub mod schedules;
+pub mod self_tests;
 pub mod shadows;
 pub mod telemetry;
+pub mod templates;
+pub mod timescale;
+pub mod webhooks;
 
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
@@ -33,6 +53,90 @@ pub async fn connect(database_url: &str) -> Result<PgPool, sqlx::Error> {
     sqlx::raw_sql(include_str!("../../migrations/005_device_shadows.sql"))
         .execute(&pool)
         .await?;
+    sqlx::raw_sql(include_str!("../../migrations/006_command_queue.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/007_experiments.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/008_device_self_tests.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/009_dtc_knowledge.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/010_device_aliases.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/011_device_tags.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/012_dtc_history.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/013_audit_log.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/014_alerts.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/015_schedules.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!(
+        "../../migrations/016_device_status_history.sql"
+    ))
+    .execute(&pool)
+    .await?;
+    sqlx::raw_sql(include_str!("../../migrations/017_alert_rules.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/018_webhooks.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!(
+        "../../migrations/019_command_conversations.sql"
+    ))
+    .execute(&pool)
+    .await?;
+    sqlx::raw_sql(include_str!("../../migrations/020_command_error_codes.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/021_command_deadlines.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/022_audit_trail.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/023_certificate_expiry.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/025_device_vitals.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/026_agent_releases.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/027_fleets.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/028_claim_codes.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/029_command_templates.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!("../../migrations/030_command_retries.sql"))
+        .execute(&pool)
+        .await?;
+    sqlx::raw_sql(include_str!(
+        "../../migrations/031_command_idempotency_keys.sql"
+    ))
+    .execute(&pool)
+    .await?;
+    sqlx::raw_sql(include_str!("../../migrations/032_command_timing.sql"))
+        .execute(&pool)
+        .await?;
     tracing::info!("migrations complete");
 
     Ok(pool)
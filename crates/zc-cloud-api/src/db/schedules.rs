@@ -0,0 +1,232 @@
+//! Schedule and schedule run queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::schedules::{Schedule, ScheduleFilter, ScheduleRun};
+
+/// Schedule row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduleRow {
+    pub id: Uuid,
+    pub name: String,
+    pub cron: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub tags: serde_json::Value,
+    pub command: String,
+    pub tool_name: Option<String>,
+    pub tool_args: Option<serde_json::Value>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub run_count: i64,
+}
+
+impl From<ScheduleRow> for Schedule {
+    fn from(row: ScheduleRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            cron: row.cron,
+            fleet_id: row.fleet_id,
+            device_id: row.device_id,
+            tags: serde_json::from_value(row.tags).unwrap_or_default(),
+            command: row.command,
+            tool_name: row.tool_name,
+            tool_args: row.tool_args,
+            enabled: row.enabled,
+            created_by: row.created_by,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            next_run_at: row.next_run_at,
+            last_run_at: row.last_run_at,
+            run_count: row.run_count.max(0) as u64,
+        }
+    }
+}
+
+/// Schedule run row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduleRunRow {
+    pub id: Uuid,
+    pub schedule_id: Uuid,
+    pub scheduled_for: DateTime<Utc>,
+    pub fired_at: DateTime<Utc>,
+    pub command_ids: Vec<Uuid>,
+    pub broadcast_id: Option<Uuid>,
+    pub error: Option<String>,
+}
+
+impl From<ScheduleRunRow> for ScheduleRun {
+    fn from(row: ScheduleRunRow) -> Self {
+        Self {
+            id: row.id,
+            schedule_id: row.schedule_id,
+            scheduled_for: row.scheduled_for,
+            fired_at: row.fired_at,
+            command_ids: row.command_ids,
+            broadcast_id: row.broadcast_id,
+            error: row.error,
+        }
+    }
+}
+
+/// Insert a new schedule.
+pub async fn insert(pool: &PgPool, schedule: &Schedule) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO schedules (id, name, cron, fleet_id, device_id, tags, command, tool_name,
+             tool_args, enabled, created_by, created_at, updated_at, next_run_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+    )
+    .bind(schedule.id)
+    .bind(&schedule.name)
+    .bind(&schedule.cron)
+    .bind(&schedule.fleet_id)
+    .bind(&schedule.device_id)
+    .bind(serde_json::to_value(&schedule.tags).unwrap_or_default())
+    .bind(&schedule.command)
+    .bind(&schedule.tool_name)
+    .bind(&schedule.tool_args)
+    .bind(schedule.enabled)
+    .bind(&schedule.created_by)
+    .bind(schedule.created_at)
+    .bind(schedule.updated_at)
+    .bind(schedule.next_run_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Schedules matching `filter`, oldest first.
+pub async fn list(pool: &PgPool, filter: &ScheduleFilter) -> Result<Vec<ScheduleRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM schedules WHERE TRUE");
+    if let Some(fleet_id) = &filter.fleet_id {
+        qb.push(" AND fleet_id = ").push_bind(fleet_id.clone());
+    }
+    if let Some(device_id) = &filter.device_id {
+        qb.push(" AND device_id = ").push_bind(device_id.clone());
+    }
+    if let Some(enabled) = filter.enabled {
+        qb.push(" AND enabled = ").push_bind(enabled);
+    }
+    if let Some(fleets) = &filter.fleets {
+        qb.push(" AND fleet_id = ANY(")
+            .push_bind(fleets.clone())
+            .push(")");
+    }
+    qb.push(" ORDER BY created_at, id");
+    qb.build_query_as::<ScheduleRow>().fetch_all(pool).await
+}
+
+/// Get a schedule by ID.
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<ScheduleRow>, sqlx::Error> {
+    sqlx::query_as::<_, ScheduleRow>("SELECT * FROM schedules WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Enabled schedules whose next run is at or before `now`.
+pub async fn list_due(pool: &PgPool, now: DateTime<Utc>) -> Result<Vec<ScheduleRow>, sqlx::Error> {
+    sqlx::query_as::<_, ScheduleRow>(
+        "SELECT * FROM schedules WHERE enabled AND next_run_at <= $1 ORDER BY next_run_at",
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await
+}
+
+/// Move a due schedule from `scheduled_for` to `next`. Returns `false` if
+/// the slot was already claimed or the schedule was paused meanwhile.
+pub async fn claim(
+    pool: &PgPool,
+    id: Uuid,
+    scheduled_for: DateTime<Utc>,
+    next: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE schedules SET next_run_at = $3, last_run_at = $4, run_count = run_count + 1
+         WHERE id = $1 AND enabled AND next_run_at = $2",
+    )
+    .bind(id)
+    .bind(scheduled_for)
+    .bind(next)
+    .bind(now)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Write back a schedule's enabled flag and next run.
+pub async fn set_enabled(pool: &PgPool, schedule: &Schedule) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE schedules SET enabled = $2, next_run_at = $3, updated_at = $4 WHERE id = $1",
+    )
+    .bind(schedule.id)
+    .bind(schedule.enabled)
+    .bind(schedule.next_run_at)
+    .bind(schedule.updated_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete a schedule (its runs cascade). Returns `false` if it did not exist.
+pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM schedules WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record a firing.
+pub async fn insert_run(pool: &PgPool, run: &ScheduleRun) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO schedule_runs (id, schedule_id, scheduled_for, fired_at, command_ids,
+             broadcast_id, error)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(run.id)
+    .bind(run.schedule_id)
+    .bind(run.scheduled_for)
+    .bind(run.fired_at)
+    .bind(&run.command_ids)
+    .bind(run.broadcast_id)
+    .bind(&run.error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// One page of a schedule's runs, newest first.
+pub async fn list_runs(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<ScheduleRunRow>, sqlx::Error> {
+    sqlx::query_as::<_, ScheduleRunRow>(
+        "SELECT * FROM schedule_runs WHERE schedule_id = $1
+         ORDER BY fired_at DESC, id DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(schedule_id)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Number of runs recorded for a schedule.
+pub async fn count_runs(pool: &PgPool, schedule_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM schedule_runs WHERE schedule_id = $1")
+        .bind(schedule_id)
+        .fetch_one(pool)
+        .await
+}
@@ -0,0 +1,48 @@
+//! Device self-test report queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Self-test row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SelfTestRow {
+    pub id: i64,
+    pub device_id: String,
+    pub passed: bool,
+    /// The full `SelfTestReport` as published by the agent.
+    pub report: serde_json::Value,
+    pub started_at: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+}
+
+/// Store a report.
+pub async fn insert(
+    pool: &PgPool,
+    device_id: &str,
+    passed: bool,
+    report: &serde_json::Value,
+    started_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO device_self_tests (device_id, passed, report, started_at)
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(device_id)
+    .bind(passed)
+    .bind(report)
+    .bind(started_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Most recent report for a device.
+pub async fn latest(pool: &PgPool, device_id: &str) -> Result<Option<SelfTestRow>, sqlx::Error> {
+    sqlx::query_as::<_, SelfTestRow>(
+        "SELECT * FROM device_self_tests WHERE device_id = $1
+         ORDER BY received_at DESC LIMIT 1",
+    )
+    .bind(device_id)
+    .fetch_optional(pool)
+    .await
+}
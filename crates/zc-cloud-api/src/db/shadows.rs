@@ -41,7 +41,8 @@ pub async fn list_shadows(pool: &PgPool, device_id: &str) -> Result<Vec<ShadowRo
     .await
 }
 
-/// Upsert reported state (JSONB merge via `||`), incrementing version.
+/// Upsert reported state (JSONB merge via `||`, `null` values removing their
+/// key), incrementing version.
 pub async fn upsert_reported(
     pool: &PgPool,
     device_id: &str,
@@ -50,10 +51,10 @@ pub async fn upsert_reported(
 ) -> Result<ShadowRow, sqlx::Error> {
     sqlx::query_as::<_, ShadowRow>(
         "INSERT INTO device_shadows (device_id, shadow_name, reported, version, last_updated)
-         VALUES ($1, $2, $3, 1, now())
+         VALUES ($1, $2, jsonb_strip_nulls($3), 1, now())
          ON CONFLICT (device_id, shadow_name)
          DO UPDATE SET
-             reported = device_shadows.reported || $3,
+             reported = jsonb_strip_nulls(device_shadows.reported || $3),
              version = device_shadows.version + 1,
              last_updated = now()
          RETURNING *",
@@ -88,3 +89,18 @@ pub async fn set_desired(
     .fetch_one(pool)
     .await
 }
+
+/// Delete a shadow. Returns `false` if it did not exist.
+pub async fn delete_shadow(
+    pool: &PgPool,
+    device_id: &str,
+    shadow_name: &str,
+) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("DELETE FROM device_shadows WHERE device_id = $1 AND shadow_name = $2")
+            .bind(device_id)
+            .bind(shadow_name)
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -1,7 +1,9 @@
 //! Telemetry reading queries.
 
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use utoipa::ToSchema;
 
 /// Telemetry row returned from the database.
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -16,35 +18,126 @@ pub struct TelemetryRow {
     pub source: String,
 }
 
-/// Query telemetry readings for a device.
-pub async fn query_readings(
+/// Sort direction for telemetry pages. Readings are ordered by
+/// `(time, metric_name)` so that pages are stable across equal timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+}
+
+/// Keyset position: the `(time, metric_name)` of the last reading returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryCursor {
+    pub time: DateTime<Utc>,
+    pub metric_name: String,
+}
+
+impl TelemetryCursor {
+    pub fn from_row(row: &TelemetryRow) -> Self {
+        Self {
+            time: row.time,
+            metric_name: row.metric_name.clone(),
+        }
+    }
+
+    /// Opaque string form handed to clients as `next_cursor`.
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.time.timestamp_micros(), self.metric_name)
+    }
+
+    pub fn decode(s: &str) -> Option<Self> {
+        let (micros, metric_name) = s.split_once(':')?;
+        let time = DateTime::from_timestamp_micros(micros.parse().ok()?)?;
+        Some(Self {
+            time,
+            metric_name: metric_name.to_string(),
+        })
+    }
+
+    /// Whether `row` comes strictly after this cursor in `order`.
+    ///
+    /// Times compare at microsecond precision, the precision of both the
+    /// encoded cursor and PostgreSQL timestamps.
+    pub fn precedes(&self, row: &TelemetryRow, order: SortOrder) -> bool {
+        let key = (row.time.timestamp_micros(), row.metric_name.as_str());
+        let cursor = (self.time.timestamp_micros(), self.metric_name.as_str());
+        match order {
+            SortOrder::Asc => key > cursor,
+            SortOrder::Desc => key < cursor,
+        }
+    }
+}
+
+/// Filters and paging for a telemetry page query.
+#[derive(Debug, Clone, Default)]
+pub struct TelemetryFilter {
+    pub source: Option<String>,
+    pub metric_name: Option<String>,
+    pub order: SortOrder,
+    pub after: Option<TelemetryCursor>,
+    pub limit: u32,
+}
+
+impl TelemetryFilter {
+    /// Whether `row` passes the source/metric filters and the cursor.
+    pub fn matches(&self, row: &TelemetryRow) -> bool {
+        self.source.as_ref().is_none_or(|s| row.source == *s)
+            && self
+                .metric_name
+                .as_ref()
+                .is_none_or(|m| row.metric_name == *m)
+            && self
+                .after
+                .as_ref()
+                .is_none_or(|c| c.precedes(row, self.order))
+    }
+}
+
+/// Fetch one page of telemetry readings for a device.
+pub async fn query_page(
     pool: &PgPool,
     device_id: &str,
-    source: Option<&str>,
-    limit: u32,
+    filter: &TelemetryFilter,
 ) -> Result<Vec<TelemetryRow>, sqlx::Error> {
-    if let Some(src) = source {
-        sqlx::query_as::<_, TelemetryRow>(
-            "SELECT * FROM telemetry_readings
-             WHERE device_id = $1 AND source = $2
-             ORDER BY time DESC LIMIT $3",
-        )
-        .bind(device_id)
-        .bind(src)
-        .bind(limit as i64)
-        .fetch_all(pool)
-        .await
-    } else {
-        sqlx::query_as::<_, TelemetryRow>(
-            "SELECT * FROM telemetry_readings
-             WHERE device_id = $1
-             ORDER BY time DESC LIMIT $2",
-        )
-        .bind(device_id)
-        .bind(limit as i64)
-        .fetch_all(pool)
-        .await
+    let mut qb: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT * FROM telemetry_readings WHERE device_id = ");
+    qb.push_bind(device_id.to_string());
+
+    if let Some(source) = &filter.source {
+        qb.push(" AND source = ").push_bind(source.clone());
+    }
+    if let Some(metric) = &filter.metric_name {
+        qb.push(" AND metric_name = ").push_bind(metric.clone());
+    }
+    if let Some(cursor) = &filter.after {
+        let cmp = match filter.order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        qb.push(format!(" AND (time, metric_name) {cmp} ("))
+            .push_bind(cursor.time)
+            .push(", ")
+            .push_bind(cursor.metric_name.clone())
+            .push(")");
     }
+
+    let dir = filter.order.as_str();
+    qb.push(format!(" ORDER BY time {dir}, metric_name {dir} LIMIT "))
+        .push_bind(filter.limit as i64);
+
+    qb.build_query_as::<TelemetryRow>().fetch_all(pool).await
 }
 
 /// Insert a batch of telemetry readings.
@@ -67,3 +160,79 @@ pub async fn insert_batch(pool: &PgPool, readings: &[TelemetryRow]) -> Result<()
     }
     Ok(())
 }
+
+/// Remove readings older than `before`. On a hypertable whole chunks are
+/// dropped, so rows in a chunk that straddles `before` stay until the chunk
+/// ages out. Returns the rows deleted, or chunks dropped.
+pub async fn prune(pool: &PgPool, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    if super::timescale::is_hypertable(pool, "telemetry_readings").await? {
+        return super::timescale::drop_chunks(pool, "telemetry_readings", before).await;
+    }
+    let result = sqlx::query("DELETE FROM telemetry_readings WHERE time < $1")
+        .bind(before)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(secs: i64, metric: &str, source: &str) -> TelemetryRow {
+        TelemetryRow {
+            time: DateTime::from_timestamp(secs, 0).unwrap(),
+            device_id: "rpi-001".into(),
+            metric_name: metric.into(),
+            value_numeric: Some(1.0),
+            value_text: None,
+            value_json: None,
+            unit: None,
+            source: source.into(),
+        }
+    }
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = TelemetryCursor::from_row(&row(1_700_000_000, "engine_rpm", "obd2"));
+        let decoded = TelemetryCursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(decoded, cursor);
+    }
+
+    #[test]
+    fn cursor_rejects_garbage() {
+        assert!(TelemetryCursor::decode("not-a-cursor").is_none());
+        assert!(TelemetryCursor::decode("abc:engine_rpm").is_none());
+    }
+
+    #[test]
+    fn cursor_breaks_time_ties_by_metric() {
+        let cursor = TelemetryCursor::from_row(&row(100, "engine_rpm", "obd2"));
+        assert!(cursor.precedes(&row(100, "vehicle_speed", "obd2"), SortOrder::Asc));
+        assert!(cursor.precedes(&row(100, "coolant_temp", "obd2"), SortOrder::Desc));
+        assert!(!cursor.precedes(&row(100, "engine_rpm", "obd2"), SortOrder::Asc));
+    }
+
+    #[test]
+    fn decoded_cursor_excludes_its_own_row() {
+        // In-memory readings keep nanoseconds; the cursor only microseconds.
+        let mut reading = row(100, "engine_rpm", "obd2");
+        reading.time += chrono::Duration::nanoseconds(1_500);
+        let cursor =
+            TelemetryCursor::decode(&TelemetryCursor::from_row(&reading).encode()).unwrap();
+        assert!(!cursor.precedes(&reading, SortOrder::Asc));
+        assert!(!cursor.precedes(&reading, SortOrder::Desc));
+    }
+
+    #[test]
+    fn filter_matches_source_and_metric() {
+        let filter = TelemetryFilter {
+            source: Some("obd2".into()),
+            metric_name: Some("engine_rpm".into()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&row(1, "engine_rpm", "obd2")));
+        assert!(!filter.matches(&row(1, "engine_rpm", "system")));
+        assert!(!filter.matches(&row(1, "cpu_usage", "obd2")));
+    }
+}
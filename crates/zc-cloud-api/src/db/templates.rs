@@ -0,0 +1,183 @@
+//! Command template and template run queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::templates::{CommandTemplate, TemplateRun};
+
+/// Command template row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TemplateRow {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub fleet_id: Option<String>,
+    pub parameters: serde_json::Value,
+    pub steps: serde_json::Value,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<TemplateRow> for CommandTemplate {
+    fn from(row: TemplateRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            fleet_id: row.fleet_id,
+            parameters: serde_json::from_value(row.parameters).unwrap_or_default(),
+            steps: serde_json::from_value(row.steps).unwrap_or_default(),
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Template run row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TemplateRunRow {
+    pub id: Uuid,
+    pub template_id: Uuid,
+    pub template_name: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub tags: serde_json::Value,
+    pub params: serde_json::Value,
+    pub steps: serde_json::Value,
+    pub initiated_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<TemplateRunRow> for TemplateRun {
+    fn from(row: TemplateRunRow) -> Self {
+        Self {
+            id: row.id,
+            template_id: row.template_id,
+            template_name: row.template_name,
+            fleet_id: row.fleet_id,
+            device_id: row.device_id,
+            tags: serde_json::from_value(row.tags).unwrap_or_default(),
+            params: serde_json::from_value(row.params).unwrap_or_default(),
+            steps: serde_json::from_value(row.steps).unwrap_or_default(),
+            initiated_by: row.initiated_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Insert a new template. Returns `false` if its fleet already has one by
+/// that name.
+pub async fn insert(pool: &PgPool, template: &CommandTemplate) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO command_templates (id, name, description, fleet_id, parameters, steps,
+             created_by, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(template.id)
+    .bind(&template.name)
+    .bind(&template.description)
+    .bind(&template.fleet_id)
+    .bind(serde_json::to_value(&template.parameters).unwrap_or_default())
+    .bind(serde_json::to_value(&template.steps).unwrap_or_default())
+    .bind(&template.created_by)
+    .bind(template.created_at)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Templates, oldest first: shared ones plus those of `fleet_id` and
+/// `tenants` when given.
+pub async fn list(
+    pool: &PgPool,
+    fleet_id: Option<&str>,
+    tenants: Option<&[String]>,
+) -> Result<Vec<TemplateRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT * FROM command_templates WHERE (fleet_id IS NULL OR (TRUE");
+    if let Some(fleet_id) = fleet_id {
+        qb.push(" AND fleet_id = ").push_bind(fleet_id.to_string());
+    }
+    if let Some(tenants) = tenants {
+        qb.push(" AND fleet_id = ANY(")
+            .push_bind(tenants.to_vec())
+            .push(")");
+    }
+    qb.push(")) ORDER BY created_at, id");
+    qb.build_query_as::<TemplateRow>().fetch_all(pool).await
+}
+
+/// Get a template by ID.
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<TemplateRow>, sqlx::Error> {
+    sqlx::query_as::<_, TemplateRow>("SELECT * FROM command_templates WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Delete a template (its runs cascade). Returns `false` if it did not exist.
+pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM command_templates WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record a dispatch.
+pub async fn insert_run(pool: &PgPool, run: &TemplateRun) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO template_runs (id, template_id, template_name, fleet_id, device_id, tags,
+             params, steps, initiated_by, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(run.id)
+    .bind(run.template_id)
+    .bind(&run.template_name)
+    .bind(&run.fleet_id)
+    .bind(&run.device_id)
+    .bind(serde_json::to_value(&run.tags).unwrap_or_default())
+    .bind(serde_json::to_value(&run.params).unwrap_or_default())
+    .bind(serde_json::to_value(&run.steps).unwrap_or_default())
+    .bind(&run.initiated_by)
+    .bind(run.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// One page of a template's runs, newest first.
+pub async fn list_runs(
+    pool: &PgPool,
+    template_id: Uuid,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<TemplateRunRow>, sqlx::Error> {
+    sqlx::query_as::<_, TemplateRunRow>(
+        "SELECT * FROM template_runs WHERE template_id = $1
+         ORDER BY created_at DESC, id DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(template_id)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Number of runs recorded for a template.
+pub async fn count_runs(pool: &PgPool, template_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM template_runs WHERE template_id = $1")
+        .bind(template_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Get a run by ID.
+pub async fn get_run(pool: &PgPool, id: Uuid) -> Result<Option<TemplateRunRow>, sqlx::Error> {
+    sqlx::query_as::<_, TemplateRunRow>("SELECT * FROM template_runs WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
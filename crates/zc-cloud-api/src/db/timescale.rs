@@ -0,0 +1,50 @@
+//! Optional TimescaleDB support for the time-series tables.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Install the extension and convert telemetry and heartbeats into
+/// hypertables with telemetry rollups (migration 024). Idempotent; fails if
+/// the extension is not available on the server.
+pub async fn setup(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(include_str!("../../migrations/024_timescale.sql"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Whether `table` is a hypertable (always `false` without the extension).
+pub async fn is_hypertable(pool: &PgPool, table: &str) -> Result<bool, sqlx::Error> {
+    let installed: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !installed {
+        return Ok(false);
+    }
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM timescaledb_information.hypertables
+                        WHERE hypertable_name = $1)",
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await
+}
+
+/// Drop the chunks of hypertable `table` holding only rows older than
+/// `before`. Returns the number of chunks dropped.
+pub async fn drop_chunks(
+    pool: &PgPool,
+    table: &str,
+    before: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let dropped: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM drop_chunks($1::text::regclass, older_than => $2)",
+    )
+    .bind(table)
+    .bind(before)
+    .fetch_one(pool)
+    .await?;
+    Ok(dropped as u64)
+}
@@ -0,0 +1,207 @@
+//! Webhook and webhook delivery queries.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::webhooks::{
+    Delivery, DeliveryStatus, Webhook, WebhookEvent, WebhookFilter, WebhookFormat,
+};
+
+/// Webhook row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WebhookRow {
+    pub id: Uuid,
+    pub name: String,
+    pub url: String,
+    pub format: String,
+    pub events: Vec<String>,
+    pub fleet_id: Option<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            url: row.url,
+            format: WebhookFormat::parse(&row.format).unwrap_or(WebhookFormat::Generic),
+            // Events this version does not know are dropped.
+            events: row
+                .events
+                .iter()
+                .filter_map(|e| WebhookEvent::parse(e))
+                .collect(),
+            fleet_id: row.fleet_id,
+            enabled: row.enabled,
+            created_by: row.created_by,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Delivery row returned from the database.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DeliveryRow {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub device_id: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DeliveryRow {
+    /// The delivery, or `None` if its event is not known to this version.
+    pub fn into_delivery(self) -> Option<Delivery> {
+        Some(Delivery {
+            event: WebhookEvent::parse(&self.event)?,
+            id: self.id,
+            webhook_id: self.webhook_id,
+            device_id: self.device_id,
+            payload: self.payload,
+            status: DeliveryStatus::parse(&self.status).unwrap_or(DeliveryStatus::Failed),
+            attempts: self.attempts.max(0) as u32,
+            response_status: self.response_status.and_then(|s| u16::try_from(s).ok()),
+            last_error: self.last_error,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+/// Insert a new webhook.
+pub async fn insert(pool: &PgPool, webhook: &Webhook) -> Result<(), sqlx::Error> {
+    let events: Vec<&str> = webhook.events.iter().map(|e| e.as_str()).collect();
+    sqlx::query(
+        "INSERT INTO webhooks (id, name, url, format, events, fleet_id, enabled, created_by,
+             created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+    )
+    .bind(webhook.id)
+    .bind(&webhook.name)
+    .bind(&webhook.url)
+    .bind(webhook.format.as_str())
+    .bind(events)
+    .bind(&webhook.fleet_id)
+    .bind(webhook.enabled)
+    .bind(&webhook.created_by)
+    .bind(webhook.created_at)
+    .bind(webhook.updated_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Webhooks matching `filter`, oldest first.
+pub async fn list(pool: &PgPool, filter: &WebhookFilter) -> Result<Vec<WebhookRow>, sqlx::Error> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM webhooks WHERE TRUE");
+    if let Some(enabled) = filter.enabled {
+        qb.push(" AND enabled = ").push_bind(enabled);
+    }
+    if let Some(event) = filter.event {
+        qb.push(" AND ")
+            .push_bind(event.as_str())
+            .push(" = ANY(events)");
+    }
+    if let Some(fleets) = &filter.fleets {
+        qb.push(" AND fleet_id = ANY(")
+            .push_bind(fleets.clone())
+            .push(")");
+    }
+    qb.push(" ORDER BY created_at, id");
+    qb.build_query_as::<WebhookRow>().fetch_all(pool).await
+}
+
+/// Get a webhook by ID.
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<WebhookRow>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookRow>("SELECT * FROM webhooks WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Write back a webhook's enabled flag.
+pub async fn set_enabled(pool: &PgPool, webhook: &Webhook) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE webhooks SET enabled = $2, updated_at = $3 WHERE id = $1")
+        .bind(webhook.id)
+        .bind(webhook.enabled)
+        .bind(webhook.updated_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Delete a webhook and its deliveries. Returns `false` if it did not exist.
+pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Insert a delivery or write back its progress.
+pub async fn save_delivery(pool: &PgPool, delivery: &Delivery) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (id, webhook_id, event, device_id, payload, status,
+             attempts, response_status, last_error, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         ON CONFLICT (id) DO UPDATE SET
+             status = EXCLUDED.status,
+             attempts = EXCLUDED.attempts,
+             response_status = EXCLUDED.response_status,
+             last_error = EXCLUDED.last_error,
+             updated_at = EXCLUDED.updated_at",
+    )
+    .bind(delivery.id)
+    .bind(delivery.webhook_id)
+    .bind(delivery.event.as_str())
+    .bind(&delivery.device_id)
+    .bind(&delivery.payload)
+    .bind(delivery.status.as_str())
+    .bind(delivery.attempts as i32)
+    .bind(delivery.response_status.map(i32::from))
+    .bind(&delivery.last_error)
+    .bind(delivery.created_at)
+    .bind(delivery.updated_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// One page of a webhook's deliveries, newest first.
+pub async fn list_deliveries(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<DeliveryRow>, sqlx::Error> {
+    sqlx::query_as::<_, DeliveryRow>(
+        "SELECT * FROM webhook_deliveries WHERE webhook_id = $1
+         ORDER BY created_at DESC, id DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(webhook_id)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Number of deliveries recorded for a webhook.
+pub async fn count_deliveries(pool: &PgPool, webhook_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM webhook_deliveries WHERE webhook_id = $1")
+        .bind(webhook_id)
+        .fetch_one(pool)
+        .await
+}
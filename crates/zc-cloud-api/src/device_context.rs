@@ -0,0 +1,128 @@
+//! Device context for cloud inference.
+//!
+//! Assembled per command from what the agent reports in its `diagnostics`
+//! shadow — the capability manifest (`tools`) and the vehicle profile
+//! (`vehicle`: VIN, make, model year, fuel type, CAN link) — with the VIN
+//! registered at provisioning as a fallback. Bedrock appends it to its
+//! system prompt, like the agent does for Ollama.
+//!
+//! The agent's tool catalog (`tool_catalog` shadow) is loaded separately:
+//! inference lists and accepts only the tools in it.
+
+use zc_protocol::catalog::{TOOL_CATALOG_SHADOW, ToolCatalog};
+use zc_protocol::context::{DeviceContext, VehicleProfile};
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Context of `device_id`. Empty if the device has reported nothing yet.
+pub async fn load(state: &AppState, device_id: &str) -> ApiResult<DeviceContext> {
+    let reported = crate::preflight::reported_manifest(state, device_id).await?;
+    let mut context = reported.map(|r| from_reported(&r)).unwrap_or_default();
+
+    if context.vehicle.vin.is_none() {
+        context.vehicle.vin = provisioned_vin(state, device_id).await?;
+    }
+    Ok(context)
+}
+
+/// Tool catalog of `device_id`, or `None` if its agent has not advertised
+/// one (older agents) or it is unreadable.
+pub async fn catalog(state: &AppState, device_id: &str) -> ApiResult<Option<ToolCatalog>> {
+    Ok(
+        crate::preflight::reported_shadow(state, device_id, TOOL_CATALOG_SHADOW)
+            .await?
+            .and_then(|reported| serde_json::from_value::<ToolCatalog>(reported).ok())
+            .filter(|catalog| !catalog.is_empty()),
+    )
+}
+
+/// Context from the reported `diagnostics` shadow state. Fields the agent
+/// does not report (older versions) are left empty.
+fn from_reported(reported: &serde_json::Value) -> DeviceContext {
+    let field = |name: &str| reported.get(name).cloned().unwrap_or_default();
+    DeviceContext {
+        vehicle: serde_json::from_value::<VehicleProfile>(field("vehicle")).unwrap_or_default(),
+        tools: serde_json::from_value(field("tools")).unwrap_or_default(),
+    }
+}
+
+async fn provisioned_vin(state: &AppState, device_id: &str) -> ApiResult<Option<String>> {
+    if let Some(pool) = &state.pool {
+        return Ok(crate::db::devices::get_by_device_id(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .and_then(|row| row.vin));
+    }
+    Ok(state
+        .devices
+        .read()
+        .await
+        .get(device_id)
+        .and_then(|d| d.vin.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use zc_protocol::shadows::ShadowState;
+
+    #[tokio::test]
+    async fn context_from_reported_shadow() {
+        let state = AppState::with_sample_data();
+        state.shadows.write().await.insert(
+            ("rpi-001".into(), crate::preflight::MANIFEST_SHADOW.into()),
+            ShadowState {
+                reported: json!({
+                    "tools": ["read_dtcs", "read_pid"],
+                    "vehicle": {"make": "Kenworth", "fuel_type": "diesel"},
+                }),
+                desired: json!({}),
+                version: 1,
+                last_updated: chrono::Utc::now(),
+            },
+        );
+
+        let context = load(&state, "rpi-001").await.unwrap();
+        assert_eq!(context.tools, ["read_dtcs", "read_pid"]);
+        assert_eq!(context.vehicle.make.as_deref(), Some("Kenworth"));
+        assert!(context.prompt_section().unwrap().contains("spark-ignition"));
+    }
+
+    #[tokio::test]
+    async fn provisioned_vin_fills_in() {
+        let state = AppState::with_sample_data();
+        state.devices.write().await.get_mut("rpi-002").unwrap().vin =
+            Some("1HGCM82633A004352".into());
+
+        let context = load(&state, "rpi-002").await.unwrap();
+        assert_eq!(context.vehicle.vin.as_deref(), Some("1HGCM82633A004352"));
+        assert!(context.tools.is_empty());
+
+        // Older agents report no vehicle; a malformed one is ignored too.
+        let context = from_reported(&json!({"tools": ["self_test"], "vehicle": 3}));
+        assert_eq!(context.tools, ["self_test"]);
+        assert!(context.vehicle.is_empty());
+    }
+
+    #[tokio::test]
+    async fn catalog_from_reported_shadow() {
+        let state = AppState::with_sample_data();
+        assert!(catalog(&state, "rpi-001").await.unwrap().is_none());
+
+        state.shadows.write().await.insert(
+            ("rpi-001".into(), TOOL_CATALOG_SHADOW.into()),
+            ShadowState {
+                reported: json!({"tools": [
+                    {"name": "read_dtcs", "description": "Read DTCs", "parameters_schema": {}}
+                ]}),
+                desired: json!({}),
+                version: 1,
+                last_updated: chrono::Utc::now(),
+            },
+        );
+        let catalog = catalog(&state, "rpi-001").await.unwrap().unwrap();
+        assert_eq!(catalog.names(), ["read_dtcs"]);
+    }
+}
@@ -0,0 +1,421 @@
+//! Canonical device identity — VIN, serial number and asset tag aliases.
+//!
+//! Teams refer to the same device by its `device_id`, the vehicle's VIN or
+//! an asset tag. Aliases map those identifiers to the canonical `device_id`
+//! so every device-scoped endpoint (and the target of a natural-language
+//! command) accepts any of them.
+//!
+//! Resolution order for a reference:
+//!
+//! 1. `kind:value` (e.g. `vin:1HGBH41JXMN109186`) looks up that kind only.
+//! 2. An exact `device_id` match wins.
+//! 3. Otherwise the value is matched against aliases of every kind. If it
+//!    names more than one device the reference is ambiguous (409).
+//!
+//! Each alias belongs to at most one device: claiming a VIN (or any other
+//! alias) already held by another device is rejected with 409.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::device_aliases::DeviceAliasRow;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Longest serial number or asset tag accepted.
+pub const MAX_ALIAS_LEN: usize = 64;
+
+/// Kind of alternate identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasKind {
+    Vin,
+    Serial,
+    AssetTag,
+}
+
+impl AliasKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vin => "vin",
+            Self::Serial => "serial",
+            Self::AssetTag => "asset_tag",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "vin" => Some(Self::Vin),
+            "serial" => Some(Self::Serial),
+            "asset_tag" => Some(Self::AssetTag),
+            _ => None,
+        }
+    }
+
+    /// Normalize a raw value of this kind (trimmed, upper-case).
+    pub fn normalize(&self, raw: &str) -> Result<String, String> {
+        let value = raw.trim().to_ascii_uppercase();
+        match self {
+            Self::Vin => {
+                let valid = value.len() == 17
+                    && value
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() && !matches!(c, 'I' | 'O' | 'Q'));
+                if !valid {
+                    return Err(format!(
+                        "invalid VIN '{}': expected 17 characters, letters I, O and Q not allowed",
+                        raw.trim()
+                    ));
+                }
+            }
+            Self::Serial | Self::AssetTag => {
+                if value.is_empty() || value.len() > MAX_ALIAS_LEN {
+                    return Err(format!(
+                        "{} must be 1-{MAX_ALIAS_LEN} characters",
+                        self.as_str()
+                    ));
+                }
+                if value.chars().any(|c| c.is_whitespace() || c == '/') {
+                    return Err(format!(
+                        "{} must not contain whitespace or '/'",
+                        self.as_str()
+                    ));
+                }
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// An alternate identifier registered for a device.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceAlias {
+    pub kind: AliasKind,
+    /// Normalized value.
+    pub value: String,
+    /// Canonical device ID the alias resolves to.
+    pub device_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DeviceAlias {
+    /// Convert a database row; rows with an unknown kind are skipped.
+    pub fn from_row(row: DeviceAliasRow) -> Option<Self> {
+        Some(Self {
+            kind: AliasKind::parse(&row.kind)?,
+            value: row.value,
+            device_id: row.device_id,
+            created_at: row.created_at,
+        })
+    }
+
+    pub fn to_row(&self) -> DeviceAliasRow {
+        DeviceAliasRow {
+            kind: self.kind.as_str().to_string(),
+            value: self.value.clone(),
+            device_id: self.device_id.clone(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Split an explicit `kind:value` reference.
+pub fn split_reference(reference: &str) -> Option<(AliasKind, &str)> {
+    let (kind, value) = reference.split_once(':')?;
+    Some((AliasKind::parse(kind)?, value))
+}
+
+/// Resolve a device reference (device ID or alias) to the canonical ID.
+///
+/// Unknown plain references are returned unchanged so callers keep their
+/// own "device not found" handling.
+pub async fn resolve(state: &AppState, reference: &str) -> ApiResult<String> {
+    let (kind, value) = match split_reference(reference) {
+        Some((kind, raw)) => (
+            Some(kind),
+            kind.normalize(raw).map_err(ApiError::BadRequest)?,
+        ),
+        None => {
+            if device_exists(state, reference).await? {
+                return Ok(reference.to_string());
+            }
+            (None, reference.trim().to_ascii_uppercase())
+        }
+    };
+
+    let mut owners: Vec<String> = lookup(state, kind, &value)
+        .await?
+        .into_iter()
+        .map(|a| a.device_id)
+        .collect();
+    owners.sort();
+    owners.dedup();
+
+    match owners.len() {
+        0 if kind.is_some() => Err(ApiError::NotFound(format!(
+            "no device with alias '{reference}'"
+        ))),
+        0 => Ok(reference.to_string()),
+        1 => Ok(owners.remove(0)),
+        _ => Err(ApiError::Conflict(format!(
+            "'{reference}' is ambiguous: it is an alias of {}; use kind:value",
+            owners.join(", ")
+        ))),
+    }
+}
+
+/// Resolve a reference and require that the device exists (404 otherwise).
+pub async fn resolve_existing(state: &AppState, reference: &str) -> ApiResult<String> {
+    let device_id = resolve(state, reference).await?;
+    if device_exists(state, &device_id).await? {
+        Ok(device_id)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "device '{reference}' not found"
+        )))
+    }
+}
+
+/// Register an alias for `device_id`, rejecting values claimed elsewhere.
+///
+/// Claiming an alias the device already holds is a no-op.
+pub async fn claim(
+    state: &AppState,
+    device_id: &str,
+    kind: AliasKind,
+    raw: &str,
+) -> ApiResult<DeviceAlias> {
+    let value = kind.normalize(raw).map_err(ApiError::BadRequest)?;
+    // A device ID always wins resolution, so an alias equal to another
+    // device's ID could never be reached.
+    let raw = raw.trim();
+    if raw != device_id && device_exists(state, raw).await? {
+        return Err(ApiError::Conflict(format!(
+            "'{raw}' is already the ID of another device"
+        )));
+    }
+
+    let alias = DeviceAlias {
+        kind,
+        value,
+        device_id: device_id.to_string(),
+        created_at: Utc::now(),
+    };
+
+    let owner = if let Some(pool) = &state.pool {
+        crate::db::device_aliases::claim(pool, &alias.to_row())
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+    } else {
+        let mut aliases = state.device_aliases.write().await;
+        aliases
+            .entry((kind, alias.value.clone()))
+            .or_insert_with(|| alias.clone())
+            .device_id
+            .clone()
+    };
+
+    if owner != device_id {
+        return Err(conflict(&alias, &owner));
+    }
+    tracing::info!(device_id, kind = kind.as_str(), value = %alias.value, "device alias claimed");
+    Ok(alias)
+}
+
+/// Fail with 409 if the alias is held by a device other than `device_id`.
+pub async fn ensure_available(
+    state: &AppState,
+    device_id: &str,
+    kind: AliasKind,
+    value: &str,
+) -> ApiResult<()> {
+    match lookup(state, Some(kind), value)
+        .await?
+        .into_iter()
+        .find(|a| a.device_id != device_id)
+    {
+        Some(existing) => Err(conflict(&existing, &existing.device_id)),
+        None => Ok(()),
+    }
+}
+
+/// Aliases registered for a device, ordered by kind and value.
+pub async fn list_for_device(state: &AppState, device_id: &str) -> ApiResult<Vec<DeviceAlias>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::device_aliases::list_for_device(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(rows.into_iter().filter_map(DeviceAlias::from_row).collect());
+    }
+
+    let aliases = state.device_aliases.read().await;
+    let mut found: Vec<DeviceAlias> = aliases
+        .values()
+        .filter(|a| a.device_id == device_id)
+        .cloned()
+        .collect();
+    found.sort_by(|a, b| (a.kind, &a.value).cmp(&(b.kind, &b.value)));
+    Ok(found)
+}
+
+/// Remove an alias from a device. Returns whether it existed.
+pub async fn release(
+    state: &AppState,
+    device_id: &str,
+    kind: AliasKind,
+    value: &str,
+) -> ApiResult<bool> {
+    if let Some(pool) = &state.pool {
+        return crate::db::device_aliases::delete(pool, device_id, kind.as_str(), value)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()));
+    }
+
+    let mut aliases = state.device_aliases.write().await;
+    let key = (kind, value.to_string());
+    if aliases.get(&key).is_some_and(|a| a.device_id == device_id) {
+        aliases.remove(&key);
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+async fn lookup(
+    state: &AppState,
+    kind: Option<AliasKind>,
+    value: &str,
+) -> ApiResult<Vec<DeviceAlias>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::device_aliases::lookup(pool, kind.map(|k| k.as_str()), value)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(rows.into_iter().filter_map(DeviceAlias::from_row).collect());
+    }
+
+    let aliases = state.device_aliases.read().await;
+    Ok(aliases
+        .values()
+        .filter(|a| a.value == value && kind.is_none_or(|k| a.kind == k))
+        .cloned()
+        .collect())
+}
+
+async fn device_exists(state: &AppState, device_id: &str) -> ApiResult<bool> {
+    if let Some(pool) = &state.pool {
+        return crate::db::devices::exists(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()));
+    }
+    Ok(state.devices.read().await.contains_key(device_id))
+}
+
+fn conflict(alias: &DeviceAlias, owner: &str) -> ApiError {
+    let what = match alias.kind {
+        AliasKind::Vin => "VIN",
+        AliasKind::Serial => "serial",
+        AliasKind::AssetTag => "asset tag",
+    };
+    ApiError::Conflict(format!(
+        "{what} '{}' is already claimed by device '{owner}'",
+        alias.value
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vin_is_normalized_and_validated() {
+        assert_eq!(
+            AliasKind::Vin.normalize(" 1hgbh41jxmn109186 ").unwrap(),
+            "1HGBH41JXMN109186"
+        );
+        assert!(AliasKind::Vin.normalize("1HGBH41JXMN10918").is_err());
+        assert!(AliasKind::Vin.normalize("1HGBH41JXMN1O9186").is_err());
+        assert!(AliasKind::AssetTag.normalize("fl 07").is_err());
+        assert_eq!(AliasKind::AssetTag.normalize("fl-07").unwrap(), "FL-07");
+    }
+
+    #[test]
+    fn explicit_kind_prefix() {
+        assert_eq!(
+            split_reference("asset_tag:FL-07"),
+            Some((AliasKind::AssetTag, "FL-07"))
+        );
+        assert_eq!(split_reference("rpi-001"), None);
+        assert_eq!(split_reference("site:rpi-001"), None);
+    }
+
+    #[tokio::test]
+    async fn resolves_aliases_and_passes_unknown_through() {
+        let state = AppState::with_sample_data();
+        claim(&state, "rpi-001", AliasKind::Vin, "1HGBH41JXMN109186")
+            .await
+            .unwrap();
+        claim(&state, "rpi-001", AliasKind::AssetTag, "fl-07")
+            .await
+            .unwrap();
+
+        assert_eq!(resolve(&state, "rpi-002").await.unwrap(), "rpi-002");
+        assert_eq!(
+            resolve(&state, "1hgbh41jxmn109186").await.unwrap(),
+            "rpi-001"
+        );
+        assert_eq!(resolve(&state, "asset_tag:FL-07").await.unwrap(), "rpi-001");
+        assert_eq!(resolve(&state, "unknown-9").await.unwrap(), "unknown-9");
+        assert!(matches!(
+            resolve(&state, "serial:FL-07").await,
+            Err(ApiError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn second_device_cannot_claim_same_vin() {
+        let state = AppState::with_sample_data();
+        claim(&state, "rpi-001", AliasKind::Vin, "1HGBH41JXMN109186")
+            .await
+            .unwrap();
+        // Re-claiming by the owner is fine.
+        claim(&state, "rpi-001", AliasKind::Vin, "1HGBH41JXMN109186")
+            .await
+            .unwrap();
+
+        let err = claim(&state, "rpi-002", AliasKind::Vin, "1hgbh41jxmn109186")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::Conflict(msg) if msg.contains("rpi-001")));
+        assert!(
+            ensure_available(&state, "rpi-002", AliasKind::Vin, "1HGBH41JXMN109186")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn shared_value_across_kinds_is_ambiguous() {
+        let state = AppState::with_sample_data();
+        claim(&state, "rpi-001", AliasKind::Serial, "A100")
+            .await
+            .unwrap();
+        claim(&state, "rpi-002", AliasKind::AssetTag, "A100")
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            resolve(&state, "A100").await,
+            Err(ApiError::Conflict(_))
+        ));
+        assert_eq!(resolve(&state, "serial:A100").await.unwrap(), "rpi-001");
+    }
+
+    #[tokio::test]
+    async fn alias_cannot_shadow_another_device_id() {
+        let state = AppState::with_sample_data();
+        let err = claim(&state, "rpi-001", AliasKind::AssetTag, "rpi-002")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::Conflict(_)));
+    }
+}
@@ -0,0 +1,535 @@
+//! Diagnostic report of one device, for sharing with repair shops.
+//!
+//! [`build`] gathers what the cloud knows about a device over a window of
+//! days into one [`DeviceReport`]:
+//!
+//! - heartbeat: the last heartbeat, its vitals and the status transitions
+//!   in the window (the status history is the heartbeat record the cloud
+//!   keeps);
+//! - DTCs: codes active in the latest scan or seen in the window, from the
+//!   DTC history;
+//! - log errors: the error categories of completed `analyze_errors` runs in
+//!   the window, summed, most frequent first;
+//! - telemetry: per metric and source, reading count, min / max / average
+//!   and the latest numeric value.
+//!
+//! [`render_html`] turns the report into a self-contained HTML page that
+//! prints cleanly, so it can be saved as PDF from a browser.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use zc_protocol::device::{DeviceInfo, DeviceVitals};
+
+use crate::device_status::{StatusChange, status_name};
+use crate::dtc_history::DtcHistoryEntry;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Window when the request names none.
+pub const DEFAULT_DAYS: u32 = 7;
+/// Longest window.
+pub const MAX_DAYS: u32 = 90;
+/// Tool whose results feed the log error section.
+pub const LOG_TOOL: &str = "analyze_errors";
+/// Most recent log analyses folded into the report.
+pub const MAX_LOG_ANALYSES: usize = 50;
+/// Most log error categories listed.
+pub const MAX_LOG_CATEGORIES: usize = 10;
+/// Most status transitions listed.
+pub const MAX_STATUS_CHANGES: u32 = 50;
+/// Most telemetry metrics summarized.
+pub const MAX_METRICS: usize = 100;
+
+/// Heartbeat section.
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatSummary {
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    /// Age of the last heartbeat when the report was generated.
+    pub seconds_since: Option<i64>,
+    pub vitals: Option<DeviceVitals>,
+    /// Transitions in the window, newest first.
+    pub status_changes: Vec<StatusChange>,
+}
+
+/// DTC section.
+#[derive(Debug, Clone, Serialize)]
+pub struct DtcSummary {
+    pub last_scan_at: Option<DateTime<Utc>>,
+    /// Active codes first, then by most recent sighting.
+    pub dtcs: Vec<DtcHistoryEntry>,
+}
+
+/// One log error category, summed over the analyses in the window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogErrorCategory {
+    pub category: String,
+    pub description: Option<String>,
+    pub count: u64,
+    /// When the latest analysis that found it ran.
+    pub last_reported: DateTime<Utc>,
+    /// Up to three example messages.
+    pub examples: Vec<String>,
+}
+
+/// Log error section.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogErrorSummary {
+    /// `analyze_errors` results folded in.
+    pub analyses: usize,
+    pub last_analyzed_at: Option<DateTime<Utc>>,
+    /// Most frequent first.
+    pub categories: Vec<LogErrorCategory>,
+}
+
+/// One metric over the window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricSummary {
+    pub metric: String,
+    pub source: String,
+    pub unit: Option<String>,
+    pub readings: u64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub avg: Option<f64>,
+    /// Latest numeric value.
+    pub latest: Option<f64>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Everything in one report.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceReport {
+    pub device: DeviceInfo,
+    pub generated_at: DateTime<Utc>,
+    /// Start of the window; it ends at `generated_at`.
+    pub since: DateTime<Utc>,
+    pub heartbeat: HeartbeatSummary,
+    pub dtcs: DtcSummary,
+    pub log_errors: LogErrorSummary,
+    pub telemetry: Vec<MetricSummary>,
+}
+
+/// Assemble the report of `device_id` over the last `days` days.
+pub async fn build(
+    state: &AppState,
+    device_id: &str,
+    days: u32,
+    now: DateTime<Utc>,
+) -> ApiResult<DeviceReport> {
+    let device = state
+        .store
+        .get_device(device_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("device '{device_id}' not found")))?;
+    let since = now - Duration::days(days.into());
+
+    let (changes, _) =
+        crate::device_status::history(state, device_id, MAX_STATUS_CHANGES, 0).await?;
+    let heartbeat = HeartbeatSummary {
+        last_heartbeat: device.last_heartbeat,
+        seconds_since: device.last_heartbeat.map(|t| (now - t).num_seconds()),
+        vitals: device.vitals.clone(),
+        status_changes: changes
+            .into_iter()
+            .filter(|c| c.changed_at >= since)
+            .collect(),
+    };
+
+    let history = crate::dtc_history::history(state, device_id).await?;
+    let dtcs = DtcSummary {
+        last_scan_at: history.last_scan_at,
+        dtcs: history
+            .dtcs
+            .into_iter()
+            .filter(|d| d.active || d.last_seen >= since)
+            .collect(),
+    };
+
+    let analyses = state
+        .store
+        .completed_tool_results(device_id, LOG_TOOL, since, MAX_LOG_ANALYSES)
+        .await?;
+    let log_errors = LogErrorSummary {
+        analyses: analyses.len(),
+        last_analyzed_at: analyses.iter().map(|(at, _)| *at).max(),
+        categories: log_categories(&analyses),
+    };
+
+    Ok(DeviceReport {
+        telemetry: state
+            .store
+            .telemetry_summary(device_id, since, MAX_METRICS)
+            .await?,
+        device,
+        generated_at: now,
+        since,
+        heartbeat,
+        dtcs,
+        log_errors,
+    })
+}
+
+/// Sum the `patterns` of `analyze_errors` results (`{"tool_name",
+/// "success", "data": {"patterns": [...]}}`) per category.
+pub fn log_categories(analyses: &[(DateTime<Utc>, Value)]) -> Vec<LogErrorCategory> {
+    let mut by_category: BTreeMap<String, LogErrorCategory> = BTreeMap::new();
+    for (at, result) in analyses {
+        if result.get("success").and_then(Value::as_bool) == Some(false) {
+            continue;
+        }
+        let patterns = result
+            .get("data")
+            .and_then(|d| d.get("patterns"))
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for pattern in patterns {
+            let Some(category) = pattern.get("category").and_then(Value::as_str) else {
+                continue;
+            };
+            let entry =
+                by_category
+                    .entry(category.to_string())
+                    .or_insert_with(|| LogErrorCategory {
+                        category: category.to_string(),
+                        description: None,
+                        count: 0,
+                        last_reported: *at,
+                        examples: Vec::new(),
+                    });
+            entry.count += pattern.get("count").and_then(Value::as_u64).unwrap_or(0);
+            entry.last_reported = entry.last_reported.max(*at);
+            if entry.description.is_none() {
+                entry.description = pattern
+                    .get("description")
+                    .and_then(Value::as_str)
+                    .map(String::from);
+            }
+            for example in pattern
+                .get("examples")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if entry.examples.len() < 3 && !entry.examples.iter().any(|e| e == example) {
+                    entry.examples.push(example.to_string());
+                }
+            }
+        }
+    }
+    let mut categories: Vec<LogErrorCategory> = by_category.into_values().collect();
+    categories.sort_by(|a, b| b.count.cmp(&a.count).then(a.category.cmp(&b.category)));
+    categories.truncate(MAX_LOG_CATEGORIES);
+    categories
+}
+
+/// Escape text for HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn time(t: Option<DateTime<Utc>>) -> String {
+    t.map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "—".into())
+}
+
+fn number(v: Option<f64>) -> String {
+    v.map(|v| {
+        format!("{:.2}", v)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    })
+    .unwrap_or_else(|| "—".into())
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+h1{margin-bottom:0}h2{border-bottom:1px solid #ccc;margin-top:1.5em}\
+table{border-collapse:collapse;width:100%}th,td{text-align:left;padding:4px 8px;\
+border-bottom:1px solid #eee;vertical-align:top}.muted{color:#777}\
+.active{font-weight:bold;color:#b00}@media print{body{margin:0}}";
+
+/// The report as a standalone HTML page.
+pub fn render_html(report: &DeviceReport) -> String {
+    let device = &report.device;
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"utf-8\">");
+    html.push_str(&format!(
+        "<title>Diagnostic report: {}</title><style>{STYLE}</style></head><body>\n",
+        escape(&device.device_id)
+    ));
+    html.push_str(&format!(
+        "<h1>Diagnostic report: {}</h1>\n<p class=\"muted\">{} to {}</p>\n",
+        escape(&device.device_id),
+        time(Some(report.since)),
+        time(Some(report.generated_at)),
+    ));
+
+    html.push_str("<h2>Device</h2>\n<table>\n");
+    let mut rows = vec![
+        (
+            "Fleet",
+            device
+                .metadata
+                .get("fleet")
+                .and_then(Value::as_str)
+                .map(String::from)
+                .unwrap_or_else(|| device.fleet_id.to_string()),
+        ),
+        ("Status", status_name(device.status).to_string()),
+        (
+            "Hardware",
+            serde_json::to_value(&device.hardware_type)
+                .ok()
+                .and_then(|v| v.as_str().map(String::from))
+                .unwrap_or_default(),
+        ),
+    ];
+    if let Some(vin) = &device.vin {
+        rows.push(("VIN", vin.clone()));
+    }
+    rows.push(("Last heartbeat", time(report.heartbeat.last_heartbeat)));
+    if let Some(vitals) = &report.heartbeat.vitals {
+        let mut parts = Vec::new();
+        if let Some(load) = vitals.cpu_load {
+            parts.push(format!("load {}", number(Some(load))));
+        }
+        if let Some(bytes) = vitals.memory_free_bytes {
+            parts.push(format!("{} MiB memory free", bytes / (1024 * 1024)));
+        }
+        if let Some(bytes) = vitals.disk_free_bytes {
+            parts.push(format!("{} MiB disk free", bytes / (1024 * 1024)));
+        }
+        if let Some(temp) = vitals.temperature_c {
+            parts.push(format!("{} °C", number(Some(temp))));
+        }
+        if let Some(rssi) = vitals.rssi_dbm {
+            parts.push(format!("{rssi} dBm"));
+        }
+        rows.push(("Vitals", parts.join(", ")));
+    }
+    for (label, value) in rows {
+        html.push_str(&format!(
+            "<tr><th>{label}</th><td>{}</td></tr>\n",
+            escape(&value)
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Status changes</h2>\n");
+    if report.heartbeat.status_changes.is_empty() {
+        html.push_str("<p class=\"muted\">None in this period.</p>\n");
+    } else {
+        html.push_str("<table><tr><th>When</th><th>From</th><th>To</th><th>Reason</th></tr>\n");
+        for change in &report.heartbeat.status_changes {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                time(Some(change.changed_at)),
+                status_name(change.from),
+                status_name(change.to),
+                escape(change.reason.as_str()),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Diagnostic trouble codes</h2>\n");
+    if report.dtcs.dtcs.is_empty() {
+        html.push_str(&format!(
+            "<p class=\"muted\">No codes (last scan: {}).</p>\n",
+            time(report.dtcs.last_scan_at)
+        ));
+    } else {
+        html.push_str(
+            "<table><tr><th>Code</th><th>Severity</th><th>Description</th>\
+             <th>First seen</th><th>Last seen</th><th>Scans</th></tr>\n",
+        );
+        for dtc in &report.dtcs.dtcs {
+            html.push_str(&format!(
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                if dtc.active { " class=\"active\"" } else { "" },
+                escape(&dtc.code),
+                escape(&dtc.severity),
+                escape(dtc.description.as_deref().unwrap_or("")),
+                time(Some(dtc.first_seen)),
+                time(Some(dtc.last_seen)),
+                dtc.occurrences,
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Log errors</h2>\n");
+    if report.log_errors.categories.is_empty() {
+        html.push_str(&format!(
+            "<p class=\"muted\">No classified errors in {} log analyses.</p>\n",
+            report.log_errors.analyses
+        ));
+    } else {
+        html.push_str(
+            "<table><tr><th>Category</th><th>Count</th><th>Last reported</th>\
+             <th>Examples</th></tr>\n",
+        );
+        for category in &report.log_errors.categories {
+            let examples: Vec<String> = category.examples.iter().map(|e| escape(e)).collect();
+            html.push_str(&format!(
+                "<tr><td>{}<br><span class=\"muted\">{}</span></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&category.category),
+                escape(category.description.as_deref().unwrap_or("")),
+                category.count,
+                time(Some(category.last_reported)),
+                examples.join("<br>"),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Telemetry</h2>\n");
+    if report.telemetry.is_empty() {
+        html.push_str("<p class=\"muted\">No readings in this period.</p>\n");
+    } else {
+        html.push_str(
+            "<table><tr><th>Metric</th><th>Source</th><th>Readings</th><th>Min</th>\
+             <th>Avg</th><th>Max</th><th>Latest</th><th>Last seen</th></tr>\n",
+        );
+        for metric in &report.telemetry {
+            let unit = metric
+                .unit
+                .as_deref()
+                .map(|u| format!(" ({})", escape(u)))
+                .unwrap_or_default();
+            html.push_str(&format!(
+                "<tr><td>{}{unit}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&metric.metric),
+                escape(&metric.source),
+                metric.readings,
+                number(metric.min),
+                number(metric.avg),
+                number(metric.max),
+                number(metric.latest),
+                time(Some(metric.last_seen)),
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body></html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::telemetry::TelemetryRow;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn analysis(patterns: Value) -> Value {
+        serde_json::json!({"tool_name": LOG_TOOL, "success": true, "data": {"patterns": patterns}})
+    }
+
+    #[test]
+    fn log_categories_are_summed_across_analyses() {
+        let analyses = vec![
+            (
+                at("2024-01-15T10:00:00Z"),
+                analysis(serde_json::json!([
+                    {"category": "can_bus_error", "description": "CAN bus", "count": 4,
+                        "examples": ["bus-off on can0"]},
+                    {"category": "oom", "count": 1, "examples": ["Out of memory"]},
+                ])),
+            ),
+            (
+                at("2024-01-14T10:00:00Z"),
+                analysis(serde_json::json!([
+                    {"category": "can_bus_error", "count": 3,
+                        "examples": ["bus-off on can0", "arbitration lost"]},
+                ])),
+            ),
+            (
+                at("2024-01-13T10:00:00Z"),
+                serde_json::json!({"tool_name": LOG_TOOL, "success": false, "data": null}),
+            ),
+        ];
+        let categories = log_categories(&analyses);
+        assert_eq!(categories.len(), 2);
+        assert_eq!(categories[0].category, "can_bus_error");
+        assert_eq!(categories[0].count, 7);
+        assert_eq!(categories[0].description.as_deref(), Some("CAN bus"));
+        assert_eq!(
+            categories[0].examples,
+            ["bus-off on can0", "arbitration lost"]
+        );
+        assert_eq!(categories[0].last_reported, at("2024-01-15T10:00:00Z"));
+        assert_eq!(categories[1].count, 1);
+    }
+
+    #[tokio::test]
+    async fn report_covers_the_window_and_renders() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        let reading = |metric: &str, value: f64, age_days: i64| TelemetryRow {
+            time: now - Duration::days(age_days),
+            device_id: "rpi-001".into(),
+            metric_name: metric.into(),
+            value_numeric: Some(value),
+            value_text: None,
+            value_json: None,
+            unit: Some("°C".into()),
+            source: "obd2".into(),
+        };
+        state
+            .store
+            .append_telemetry(&mut vec![
+                reading("coolant_temp", 90.0, 2),
+                reading("coolant_temp", 110.0, 1),
+                reading("coolant_temp", 60.0, 30),
+            ])
+            .await
+            .unwrap();
+
+        let report = build(&state, "rpi-001", DEFAULT_DAYS, now).await.unwrap();
+        assert_eq!(report.device.device_id, "rpi-001");
+        assert_eq!(report.telemetry.len(), 1);
+        let coolant = &report.telemetry[0];
+        assert_eq!(coolant.readings, 2);
+        assert_eq!(coolant.min, Some(90.0));
+        assert_eq!(coolant.avg, Some(100.0));
+        assert_eq!(coolant.latest, Some(110.0));
+        assert_eq!(report.log_errors.analyses, 0);
+
+        let html = render_html(&report);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("coolant_temp (°C)"));
+        assert!(html.contains("<h2>Log errors</h2>"));
+
+        let missing = build(&state, "rpi-999", DEFAULT_DAYS, now).await;
+        assert!(matches!(missing, Err(ApiError::NotFound(_))));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!(
+            escape("<script>\"x\" & 'y'</script>"),
+            "&lt;script&gt;&quot;x&quot; &amp; &#39;y&#39;&lt;/script&gt;"
+        );
+    }
+}
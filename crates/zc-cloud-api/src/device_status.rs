@@ -0,0 +1,396 @@
+//! Online / degraded / offline status derived from heartbeat age.
+//!
+//! Heartbeats only record `last_heartbeat` and mark a device online. [`run`]
+//! wakes every [`TICK_SECS`] and moves devices that have gone quiet: online
+//! → degraded once the last heartbeat is older than
+//! [`StatusThresholds::degraded_after`], and online or degraded → offline
+//! after [`StatusThresholds::offline_after`]. The next heartbeat brings the
+//! device back online ([`heartbeat_received`]); MQTT presence messages move
+//! it directly.
+//!
+//! Every transition goes through [`record`], which keeps it as a
+//! [`StatusChange`] (served at `GET /devices/{id}/status-history`) and
+//! broadcasts `WsEvent::DeviceStatusChanged`. Provisioning, maintenance and
+//! decommissioned devices, and devices that never sent a heartbeat, are
+//! left alone. In database mode each move is a conditional update on the
+//! status read, so replicas sharing a database do not record it twice.
+
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use zc_protocol::device::DeviceStatus;
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::routes::devices::parse_device_status;
+use crate::state::AppState;
+
+/// How often the evaluator checks heartbeat age.
+pub const TICK_SECS: u64 = 15;
+/// Transitions kept per device in memory mode (the database keeps all).
+pub const MAX_HISTORY_KEPT: usize = 100;
+/// Default heartbeat age after which a device is degraded; matches the age
+/// after which commands are queued instead of published.
+pub const DEFAULT_DEGRADED_AFTER_SECS: u64 = crate::command_queue::OFFLINE_AFTER_SECS as u64;
+/// Default heartbeat age after which a device is offline.
+pub const DEFAULT_OFFLINE_AFTER_SECS: u64 = 300;
+
+/// Statuses the evaluator moves devices out of.
+const EVALUATED: &[&str] = &["online", "degraded"];
+
+/// Heartbeat ages at which a device is degraded and offline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusThresholds {
+    pub degraded_after: Duration,
+    pub offline_after: Duration,
+}
+
+impl Default for StatusThresholds {
+    fn default() -> Self {
+        Self {
+            degraded_after: Duration::seconds(DEFAULT_DEGRADED_AFTER_SECS as i64),
+            offline_after: Duration::seconds(DEFAULT_OFFLINE_AFTER_SECS as i64),
+        }
+    }
+}
+
+impl StatusThresholds {
+    /// The status a device in `status` should move to at `now`, or `None`
+    /// if it stays as it is.
+    pub fn evaluate(
+        &self,
+        status: DeviceStatus,
+        last_heartbeat: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DeviceStatus> {
+        if !matches!(status, DeviceStatus::Online | DeviceStatus::Degraded) {
+            return None;
+        }
+        let age = now - last_heartbeat?;
+        let target = if age >= self.offline_after {
+            DeviceStatus::Offline
+        } else if age >= self.degraded_after {
+            DeviceStatus::Degraded
+        } else {
+            return None;
+        };
+        (target != status).then_some(target)
+    }
+}
+
+/// What caused a status change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusReason {
+    /// No heartbeat within a threshold.
+    HeartbeatTimeout,
+    /// A heartbeat arrived from a degraded or offline device.
+    Heartbeat,
+    /// An MQTT presence message (the agent's "online" or its Last Will).
+    Presence,
+    /// The device claimed its identity with a claim code.
+    Claimed,
+}
+
+impl StatusReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HeartbeatTimeout => "heartbeat_timeout",
+            Self::Heartbeat => "heartbeat",
+            Self::Presence => "presence",
+            Self::Claimed => "claimed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "heartbeat_timeout" => Some(Self::HeartbeatTimeout),
+            "heartbeat" => Some(Self::Heartbeat),
+            "presence" => Some(Self::Presence),
+            "claimed" => Some(Self::Claimed),
+            _ => None,
+        }
+    }
+}
+
+/// One status transition of a device.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct StatusChange {
+    pub id: Uuid,
+    pub device_id: String,
+    pub from: DeviceStatus,
+    pub to: DeviceStatus,
+    pub reason: StatusReason,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl StatusChange {
+    pub fn new(
+        device_id: &str,
+        from: DeviceStatus,
+        to: DeviceStatus,
+        reason: StatusReason,
+        changed_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            device_id: device_id.to_string(),
+            from,
+            to,
+            reason,
+            changed_at,
+        }
+    }
+}
+
+/// Wire name of a status (e.g. `"online"`), as stored in the database.
+pub fn status_name(status: DeviceStatus) -> &'static str {
+    match status {
+        DeviceStatus::Provisioning => "provisioning",
+        DeviceStatus::Online => "online",
+        DeviceStatus::Degraded => "degraded",
+        DeviceStatus::Offline => "offline",
+        DeviceStatus::Maintenance => "maintenance",
+        DeviceStatus::Decommissioned => "decommissioned",
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+/// Keep a transition that has already been applied to the device and
+/// broadcast it.
+pub async fn record(state: &AppState, change: StatusChange) {
+    tracing::info!(
+        device_id = %change.device_id,
+        from = status_name(change.from),
+        to = status_name(change.to),
+        reason = change.reason.as_str(),
+        "device status changed"
+    );
+    let _ = state.event_tx.send(WsEvent::DeviceStatusChanged {
+        device_id: change.device_id.clone(),
+        old_status: status_name(change.from).to_string(),
+        new_status: status_name(change.to).to_string(),
+        changed_at: change.changed_at,
+    });
+
+    if let Some(pool) = &state.pool {
+        if let Err(e) = crate::db::device_status::insert(pool, &change).await {
+            tracing::error!(error = %e, device_id = %change.device_id, "failed to store status change");
+        }
+        return;
+    }
+    let mut history = state.status_history.write().await;
+    let kept = history
+        .iter()
+        .filter(|c| c.device_id == change.device_id)
+        .count();
+    if kept >= MAX_HISTORY_KEPT
+        && let Some(oldest) = history.iter().position(|c| c.device_id == change.device_id)
+    {
+        history.remove(oldest);
+    }
+    history.push(change);
+}
+
+/// A heartbeat marked the device online; record the recovery if it was
+/// degraded or offline before (`previous` is `None` for a new device).
+pub async fn heartbeat_received(state: &AppState, device_id: &str, previous: Option<DeviceStatus>) {
+    if let Some(from @ (DeviceStatus::Degraded | DeviceStatus::Offline)) = previous {
+        let change = StatusChange::new(
+            device_id,
+            from,
+            DeviceStatus::Online,
+            StatusReason::Heartbeat,
+            Utc::now(),
+        );
+        record(state, change).await;
+    }
+}
+
+/// One page of a device's transitions (newest first) and the total count.
+pub async fn history(
+    state: &AppState,
+    device_id: &str,
+    limit: u32,
+    offset: u32,
+) -> ApiResult<(Vec<StatusChange>, u64)> {
+    if let Some(pool) = &state.pool {
+        let (rows, total) = tokio::try_join!(
+            crate::db::device_status::list(pool, device_id, limit, offset),
+            crate::db::device_status::count(pool, device_id),
+        )
+        .map_err(internal)?;
+        return Ok((rows.into_iter().map(Into::into).collect(), total as u64));
+    }
+    let history = state.status_history.read().await;
+    let matching: Vec<StatusChange> = history
+        .iter()
+        .rev()
+        .filter(|c| c.device_id == device_id)
+        .cloned()
+        .collect();
+    let total = matching.len() as u64;
+    Ok((
+        crate::routes::pagination::slice(matching, offset, limit),
+        total,
+    ))
+}
+
+/// Move every device whose heartbeat is too old at `now`; returns the
+/// transitions recorded.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<Vec<StatusChange>> {
+    let thresholds = state.status_thresholds;
+    let mut changes = Vec::new();
+
+    if let Some(pool) = &state.pool {
+        let silent =
+            crate::db::devices::list_silent_since(pool, EVALUATED, now - thresholds.degraded_after)
+                .await
+                .map_err(internal)?;
+        for (device_id, status, last_heartbeat) in silent {
+            let from = parse_device_status(&status);
+            let Some(to) = thresholds.evaluate(from, Some(last_heartbeat), now) else {
+                continue;
+            };
+            // Only if still in the status read: a heartbeat may have landed.
+            let moved = crate::db::devices::transition_status(
+                pool,
+                &device_id,
+                &[status.as_str()],
+                status_name(to),
+            )
+            .await
+            .map_err(internal)?;
+            if moved.is_some() {
+                changes.push(StatusChange::new(
+                    &device_id,
+                    from,
+                    to,
+                    StatusReason::HeartbeatTimeout,
+                    now,
+                ));
+            }
+        }
+    } else {
+        let mut devices = state.devices.write().await;
+        for device in devices.values_mut() {
+            let Some(to) = thresholds.evaluate(device.status, device.last_heartbeat, now) else {
+                continue;
+            };
+            changes.push(StatusChange::new(
+                &device.device_id,
+                device.status,
+                to,
+                StatusReason::HeartbeatTimeout,
+                now,
+            ));
+            device.status = to;
+            device.updated_at = now;
+        }
+    }
+
+    for change in &changes {
+        record(state, change.clone()).await;
+    }
+    Ok(changes)
+}
+
+/// Evaluate heartbeat age every [`TICK_SECS`], forever.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state, Utc::now()).await {
+            tracing::error!(error = %e, "device status evaluation failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ago(now: DateTime<Utc>, secs: i64) -> Option<DateTime<Utc>> {
+        Some(now - Duration::seconds(secs))
+    }
+
+    #[test]
+    fn thresholds_move_online_to_degraded_to_offline() {
+        let t = StatusThresholds::default();
+        let now = Utc::now();
+        assert_eq!(t.evaluate(DeviceStatus::Online, ago(now, 30), now), None);
+        assert_eq!(
+            t.evaluate(DeviceStatus::Online, ago(now, 120), now),
+            Some(DeviceStatus::Degraded)
+        );
+        assert_eq!(t.evaluate(DeviceStatus::Degraded, ago(now, 120), now), None);
+        assert_eq!(
+            t.evaluate(DeviceStatus::Degraded, ago(now, 600), now),
+            Some(DeviceStatus::Offline)
+        );
+        // Straight to offline when the evaluator missed the degraded window.
+        assert_eq!(
+            t.evaluate(DeviceStatus::Online, ago(now, 600), now),
+            Some(DeviceStatus::Offline)
+        );
+        // Not derived: no heartbeat yet, or a status set by an operator.
+        assert_eq!(t.evaluate(DeviceStatus::Online, None, now), None);
+        assert_eq!(
+            t.evaluate(DeviceStatus::Maintenance, ago(now, 600), now),
+            None
+        );
+        assert_eq!(t.evaluate(DeviceStatus::Offline, ago(now, 600), now), None);
+    }
+
+    #[tokio::test]
+    async fn tick_records_transitions_and_heartbeat_recovers() {
+        let state = AppState::with_sample_data();
+        let mut rx = state.event_tx.subscribe();
+        let now = Utc::now();
+        {
+            let mut devices = state.devices.write().await;
+            devices.get_mut("rpi-001").unwrap().last_heartbeat = ago(now, 120);
+            devices.get_mut("rpi-002").unwrap().last_heartbeat = ago(now, 600);
+        }
+
+        let changes = tick(&state, now).await.unwrap();
+        let mut moved: Vec<_> = changes
+            .iter()
+            .map(|c| (c.device_id.as_str(), c.to))
+            .collect();
+        moved.sort_by_key(|(device_id, _)| *device_id);
+        assert_eq!(
+            moved,
+            vec![
+                ("rpi-001", DeviceStatus::Degraded),
+                ("rpi-002", DeviceStatus::Offline)
+            ]
+        );
+        assert_eq!(
+            state.devices.read().await["rpi-001"].status,
+            DeviceStatus::Degraded
+        );
+        let json = serde_json::to_string(&rx.try_recv().unwrap()).unwrap();
+        assert!(json.contains("device_status_changed"));
+
+        // Nothing more to do until the next threshold.
+        assert!(tick(&state, now).await.unwrap().is_empty());
+
+        heartbeat_received(&state, "rpi-002", Some(DeviceStatus::Offline)).await;
+        heartbeat_received(&state, "rpi-001", Some(DeviceStatus::Online)).await;
+        let (page, total) = history(&state, "rpi-002", 50, 0).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(page[0].to, DeviceStatus::Online);
+        assert_eq!(page[0].reason, StatusReason::Heartbeat);
+        assert_eq!(page[1].reason, StatusReason::HeartbeatTimeout);
+        assert_eq!(history(&state, "rpi-001", 50, 0).await.unwrap().1, 1);
+    }
+}
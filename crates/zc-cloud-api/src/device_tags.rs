@@ -0,0 +1,221 @@
+//! Device tags — free-form `key=value` labels for segmenting a fleet.
+//!
+//! Tags are set at provisioning or through `/devices/{id}/tags`, and are
+//! used to filter the device list and to target fleet broadcasts. Keys are
+//! lower-case (`[a-z0-9_.-]`, up to 63 characters); values keep their case
+//! but may not contain `,` (the selector separator).
+//!
+//! A selector is `key:value` (or `key=value`); several selectors are joined
+//! with `,` and must all match.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Tags of one device.
+pub type Tags = BTreeMap<String, String>;
+
+/// Longest tag key accepted.
+pub const MAX_KEY_LEN: usize = 63;
+
+/// Longest tag value accepted.
+pub const MAX_VALUE_LEN: usize = 128;
+
+/// Most tags a single device may carry.
+pub const MAX_TAGS_PER_DEVICE: usize = 32;
+
+/// Normalize and validate a tag key.
+pub fn normalize_key(raw: &str) -> Result<String, String> {
+    let key = raw.trim().to_ascii_lowercase();
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if valid {
+        Ok(key)
+    } else {
+        Err(format!(
+            "invalid tag key '{raw}': 1-{MAX_KEY_LEN} characters of a-z, 0-9, '_', '.', '-'"
+        ))
+    }
+}
+
+/// Validate a tag value (trimmed).
+pub fn normalize_value(raw: &str) -> Result<String, String> {
+    let value = raw.trim();
+    if value.is_empty() || value.len() > MAX_VALUE_LEN {
+        return Err(format!("tag value must be 1-{MAX_VALUE_LEN} characters"));
+    }
+    if value.chars().any(|c| c == ',' || c.is_control()) {
+        return Err(format!(
+            "invalid tag value '{value}': ',' and control characters are not allowed"
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Normalize every entry of a tag map.
+pub fn normalize(tags: &Tags) -> Result<Tags, String> {
+    if tags.len() > MAX_TAGS_PER_DEVICE {
+        return Err(format!(
+            "a device may carry at most {MAX_TAGS_PER_DEVICE} tags"
+        ));
+    }
+    tags.iter()
+        .map(|(k, v)| Ok((normalize_key(k)?, normalize_value(v)?)))
+        .collect()
+}
+
+/// Parse a comma-separated list of `key:value` selectors.
+pub fn parse_selectors(list: &str) -> Result<Vec<(String, String)>, String> {
+    list.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_selector)
+        .collect()
+}
+
+/// Parse one `key:value` (or `key=value`) selector.
+pub fn parse_selector(selector: &str) -> Result<(String, String), String> {
+    let (key, value) = selector
+        .split_once([':', '='])
+        .ok_or_else(|| format!("invalid tag selector '{selector}': expected key:value"))?;
+    Ok((normalize_key(key)?, normalize_value(value)?))
+}
+
+/// Whether `tags` satisfies every selector.
+pub fn matches(tags: Option<&Tags>, selectors: &[(String, String)]) -> bool {
+    selectors
+        .iter()
+        .all(|(k, v)| tags.and_then(|t| t.get(k)) == Some(v))
+}
+
+/// Tags of a device (empty if it has none).
+pub async fn tags_for(state: &AppState, device_id: &str) -> ApiResult<Tags> {
+    if let Some(pool) = &state.pool {
+        return crate::db::device_tags::list_for_device(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()));
+    }
+    Ok(state
+        .device_tags
+        .read()
+        .await
+        .get(device_id)
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Merge `tags` (already normalized) into a device's tags and return the result.
+pub async fn set_tags(state: &AppState, device_id: &str, tags: &Tags) -> ApiResult<Tags> {
+    let mut merged = tags_for(state, device_id).await?;
+    merged.extend(tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+    if merged.len() > MAX_TAGS_PER_DEVICE {
+        return Err(ApiError::BadRequest(format!(
+            "a device may carry at most {MAX_TAGS_PER_DEVICE} tags"
+        )));
+    }
+
+    if let Some(pool) = &state.pool {
+        crate::db::device_tags::upsert(pool, device_id, tags)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+    } else {
+        state
+            .device_tags
+            .write()
+            .await
+            .insert(device_id.to_string(), merged.clone());
+    }
+    tracing::info!(device_id, tags = ?tags, "device tags set");
+    Ok(merged)
+}
+
+/// Remove one tag from a device. Returns whether it existed.
+pub async fn remove_tag(state: &AppState, device_id: &str, key: &str) -> ApiResult<bool> {
+    if let Some(pool) = &state.pool {
+        return crate::db::device_tags::delete(pool, device_id, key)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()));
+    }
+    let mut all = state.device_tags.write().await;
+    Ok(all
+        .get_mut(device_id)
+        .is_some_and(|tags| tags.remove(key).is_some()))
+}
+
+/// Devices carrying every selected tag.
+pub async fn devices_matching(
+    state: &AppState,
+    selectors: &[(String, String)],
+) -> ApiResult<BTreeSet<String>> {
+    if let Some(pool) = &state.pool {
+        let ids = crate::db::device_tags::devices_matching(pool, selectors)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(ids.into_iter().collect());
+    }
+    let devices = state.devices.read().await;
+    let tags = state.device_tags.read().await;
+    Ok(devices
+        .keys()
+        .filter(|id| matches(tags.get(*id), selectors))
+        .cloned()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_lowercased_and_checked() {
+        assert_eq!(normalize_key(" Region ").unwrap(), "region");
+        assert!(normalize_key("has space").is_err());
+        assert!(normalize_key("a:b").is_err());
+        assert!(normalize_value("eu,us").is_err());
+        assert_eq!(normalize_value(" ACME Corp ").unwrap(), "ACME Corp");
+    }
+
+    #[test]
+    fn selectors_parse_and_match() {
+        let selectors = parse_selectors("env:prod, Region=eu-west").unwrap();
+        assert_eq!(
+            selectors,
+            vec![
+                ("env".to_string(), "prod".to_string()),
+                ("region".to_string(), "eu-west".to_string()),
+            ]
+        );
+        assert!(parse_selectors("env").is_err());
+
+        let tags: Tags = [("env", "prod"), ("region", "eu-west"), ("hw", "pi5")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert!(matches(Some(&tags), &selectors));
+        assert!(!matches(Some(&tags), &[("env".into(), "dev".into())]));
+        assert!(!matches(None, &selectors));
+        assert!(matches(None, &[]));
+    }
+
+    #[tokio::test]
+    async fn set_merges_and_remove_deletes() {
+        let state = AppState::with_sample_data();
+        let first: Tags = [("env".to_string(), "prod".to_string())].into();
+        set_tags(&state, "rpi-001", &first).await.unwrap();
+        let second: Tags = [("region".to_string(), "eu".to_string())].into();
+        let merged = set_tags(&state, "rpi-001", &second).await.unwrap();
+        assert_eq!(merged.len(), 2);
+
+        let prod = devices_matching(&state, &[("env".into(), "prod".into())])
+            .await
+            .unwrap();
+        assert_eq!(prod.into_iter().collect::<Vec<_>>(), vec!["rpi-001"]);
+
+        assert!(remove_tag(&state, "rpi-001", "env").await.unwrap());
+        assert!(!remove_tag(&state, "rpi-001", "env").await.unwrap());
+        assert_eq!(tags_for(&state, "rpi-001").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,397 @@
+//! Historical DTC tracking per device.
+//!
+//! Every successful `read_dtcs` / `read_uds_dtcs` response ingested (over
+//! MQTT or `POST /commands/{id}/respond`) is recorded as a scan. The
+//! per-device history aggregates scans into one entry per code with
+//! first/last sighting, occurrence count and whether the code is still
+//! active — reported by the device's latest scan. A later scan without the
+//! code (e.g. after the fault was cleared) makes it inactive.
+//!
+//! History covers confirmed codes only: `read_dtcs` reads with
+//! `scope: "pending"` or `"permanent"` are not scans, and from
+//! `scope: "all"` only the stored (Mode 0x03) codes count. A pending-only
+//! read would otherwise mark every stored code inactive.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use zc_protocol::commands::{CommandResponse, CommandStatus};
+
+use crate::dtc_knowledge::normalize_code;
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// Tools whose results are DTC scans.
+pub const SCAN_TOOLS: &[&str] = &["read_dtcs", "read_uds_dtcs"];
+
+/// One code reported by a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObservedDtc {
+    /// Normalized code (e.g. "P0300").
+    pub code: String,
+    pub severity: String,
+    pub description: Option<String>,
+}
+
+/// One successful DTC scan of a device.
+#[derive(Debug, Clone)]
+pub struct DtcScan {
+    pub command_id: Uuid,
+    pub tool_name: String,
+    pub scanned_at: DateTime<Utc>,
+    pub dtcs: Vec<ObservedDtc>,
+}
+
+/// History of one code on a device.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DtcHistoryEntry {
+    pub code: String,
+    /// Severity and description as of the most recent sighting.
+    pub severity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Number of scans that reported the code.
+    pub occurrences: u64,
+    /// Reported by the device's latest scan.
+    pub active: bool,
+}
+
+/// DTC history of a device, active codes first.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceDtcHistory {
+    pub device_id: String,
+    pub last_scan_at: Option<DateTime<Utc>>,
+    pub dtcs: Vec<DtcHistoryEntry>,
+}
+
+/// The scan carried by a command response, if it is a successful DTC read.
+///
+/// `response_data` is the agent's tool result: `{"tool_name", "success",
+/// "data": [DtcCode, ...]}`. Entries with invalid codes are skipped and
+/// duplicate codes within one scan are counted once. `tool_args` are the
+/// arguments the command was dispatched with.
+pub fn scan_from_response(
+    resp: &CommandResponse,
+    tool_args: Option<&serde_json::Value>,
+) -> Option<DtcScan> {
+    if resp.status != CommandStatus::Completed {
+        return None;
+    }
+    let data = resp.response_data.as_ref()?;
+    let tool_name = data.get("tool_name")?.as_str()?;
+    if !SCAN_TOOLS.contains(&tool_name)
+        || data.get("success").and_then(|s| s.as_bool()) == Some(false)
+    {
+        return None;
+    }
+    if matches!(
+        tool_args
+            .and_then(|a| a.get("scope"))
+            .and_then(|s| s.as_str()),
+        Some("pending" | "permanent")
+    ) {
+        return None;
+    }
+
+    let mut dtcs: Vec<ObservedDtc> = Vec::new();
+    for entry in data
+        .get("data")
+        .and_then(|d| d.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if entry
+            .get("scope")
+            .and_then(|s| s.as_str())
+            .is_some_and(|s| s != "stored")
+        {
+            continue;
+        }
+        let Some(code) = entry
+            .get("code")
+            .and_then(|c| c.as_str())
+            .and_then(|c| normalize_code(c).ok())
+        else {
+            continue;
+        };
+        if dtcs.iter().any(|d| d.code == code) {
+            continue;
+        }
+        dtcs.push(ObservedDtc {
+            code,
+            severity: entry
+                .get("severity")
+                .and_then(|s| s.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            description: entry
+                .get("description")
+                .and_then(|d| d.as_str())
+                .map(String::from),
+        });
+    }
+
+    Some(DtcScan {
+        command_id: resp.command_id,
+        tool_name: tool_name.to_string(),
+        scanned_at: resp.responded_at,
+        dtcs,
+    })
+}
+
+/// Record the scan in `resp`, if any, check it against the fleet's DTC
+/// alert rules and announce critical codes. Failures are logged and do not
+/// affect response ingestion.
+pub async fn record(
+    state: &AppState,
+    fleet_id: &str,
+    resp: &CommandResponse,
+    tool_args: Option<&serde_json::Value>,
+) {
+    let Some(scan) = scan_from_response(resp, tool_args) else {
+        return;
+    };
+    let dtc_count = scan.dtcs.len();
+    crate::alert_rules::dtc_scanned(state, fleet_id, &resp.device_id, &scan).await;
+    let critical: Vec<String> = scan
+        .dtcs
+        .iter()
+        .filter(|d| d.severity == "critical")
+        .map(|d| d.code.clone())
+        .collect();
+    if !critical.is_empty() {
+        let _ = state.event_tx.send(WsEvent::CriticalDtcDetected {
+            device_id: resp.device_id.clone(),
+            fleet_id: fleet_id.to_string(),
+            command_id: scan.command_id,
+            codes: critical,
+            detected_at: scan.scanned_at,
+        });
+    }
+
+    if let Some(pool) = &state.pool {
+        if let Err(e) = crate::db::dtc_history::insert_scan(pool, &resp.device_id, &scan).await {
+            tracing::warn!(error = %e, device_id = %resp.device_id, "failed to record DTC scan");
+            return;
+        }
+    } else {
+        let mut history = state.dtc_history.write().await;
+        let scans = history.entry(resp.device_id.clone()).or_default();
+        let at = scans.partition_point(|s| s.scanned_at <= scan.scanned_at);
+        scans.insert(at, scan);
+    }
+    tracing::debug!(device_id = %resp.device_id, dtc_count, "DTC scan recorded");
+}
+
+/// Aggregate scans (oldest first) into per-code history.
+pub fn summarize(device_id: &str, scans: &[DtcScan]) -> DeviceDtcHistory {
+    let last_scan_at = scans.last().map(|s| s.scanned_at);
+    let mut by_code: BTreeMap<&str, DtcHistoryEntry> = BTreeMap::new();
+    for scan in scans {
+        for dtc in &scan.dtcs {
+            let entry = by_code
+                .entry(dtc.code.as_str())
+                .or_insert_with(|| DtcHistoryEntry {
+                    code: dtc.code.clone(),
+                    severity: dtc.severity.clone(),
+                    description: None,
+                    first_seen: scan.scanned_at,
+                    last_seen: scan.scanned_at,
+                    occurrences: 0,
+                    active: false,
+                });
+            entry.occurrences += 1;
+            entry.last_seen = scan.scanned_at;
+            entry.severity = dtc.severity.clone();
+            if dtc.description.is_some() {
+                entry.description = dtc.description.clone();
+            }
+        }
+    }
+    let dtcs = by_code
+        .into_values()
+        .map(|mut e| {
+            e.active = Some(e.last_seen) == last_scan_at;
+            e
+        })
+        .collect();
+    finish(device_id, last_scan_at, dtcs)
+}
+
+/// Sort active codes first, then by most recent sighting.
+fn finish(
+    device_id: &str,
+    last_scan_at: Option<DateTime<Utc>>,
+    mut dtcs: Vec<DtcHistoryEntry>,
+) -> DeviceDtcHistory {
+    dtcs.sort_by(|a, b| {
+        b.active
+            .cmp(&a.active)
+            .then(b.last_seen.cmp(&a.last_seen))
+            .then(a.code.cmp(&b.code))
+    });
+    DeviceDtcHistory {
+        device_id: device_id.to_string(),
+        last_scan_at,
+        dtcs,
+    }
+}
+
+/// DTC history of a device.
+pub async fn history(state: &AppState, device_id: &str) -> ApiResult<DeviceDtcHistory> {
+    if let Some(pool) = &state.pool {
+        let last_scan_at = crate::db::dtc_history::latest_scan(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        let rows = crate::db::dtc_history::summary(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        let dtcs = rows
+            .into_iter()
+            .map(|r| DtcHistoryEntry {
+                active: Some(r.last_seen) == last_scan_at,
+                code: r.code,
+                severity: r.severity,
+                description: r.description,
+                first_seen: r.first_seen,
+                last_seen: r.last_seen,
+                occurrences: r.occurrences.max(0) as u64,
+            })
+            .collect();
+        return Ok(finish(device_id, last_scan_at, dtcs));
+    }
+
+    let history = state.dtc_history.read().await;
+    Ok(summarize(
+        device_id,
+        history
+            .get(device_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+    use zc_protocol::commands::InferenceTier;
+
+    fn response(tool: &str, codes: &[&str], at: DateTime<Utc>) -> CommandResponse {
+        let data: Vec<_> = codes
+            .iter()
+            .map(|c| json!({"code": c, "severity": "warning", "description": format!("{c} desc")}))
+            .collect();
+        CommandResponse {
+            command_id: Uuid::now_v7(),
+            correlation_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            status: CommandStatus::Completed,
+            inference_tier: InferenceTier::Local,
+            response_text: None,
+            response_data: Some(json!({"tool_name": tool, "success": true, "data": data})),
+            latency_ms: 10,
+            responded_at: at,
+            error: None,
+            error_code: None,
+            cached: false,
+        }
+    }
+
+    #[test]
+    fn only_successful_dtc_reads_are_scans() {
+        let now = Utc::now();
+        let scan = scan_from_response(
+            &response("read_dtcs", &["p0300", "P0300", "bad"], now),
+            None,
+        )
+        .unwrap();
+        assert_eq!(scan.dtcs.len(), 1);
+        assert_eq!(scan.dtcs[0].code, "P0300");
+
+        assert!(scan_from_response(&response("read_pid", &["P0300"], now), None).is_none());
+        let mut failed = response("read_dtcs", &[], now);
+        failed.status = CommandStatus::Failed;
+        assert!(scan_from_response(&failed, None).is_none());
+
+        // An empty scan still counts: it clears previously active codes.
+        let empty = scan_from_response(&response("read_uds_dtcs", &[], now), None).unwrap();
+        assert!(empty.dtcs.is_empty());
+    }
+
+    #[test]
+    fn only_stored_codes_are_tracked() {
+        let now = Utc::now();
+        let pending = response("read_dtcs", &[], now);
+        let args = json!({"scope": "pending"});
+        assert!(scan_from_response(&pending, Some(&args)).is_none());
+
+        let mut all = response("read_dtcs", &["P0300", "P0171"], now);
+        all.response_data.as_mut().unwrap()["data"][1]["scope"] = json!("pending");
+        all.response_data.as_mut().unwrap()["data"][0]["scope"] = json!("stored");
+        let args = json!({"scope": "all"});
+        let scan = scan_from_response(&all, Some(&args)).unwrap();
+        assert_eq!(scan.dtcs.len(), 1);
+        assert_eq!(scan.dtcs[0].code, "P0300");
+    }
+
+    #[test]
+    fn summary_tracks_first_last_and_active() {
+        let t0 = Utc::now() - Duration::hours(3);
+        let t1 = t0 + Duration::hours(1);
+        let t2 = t0 + Duration::hours(2);
+        let scans: Vec<DtcScan> = [
+            response("read_dtcs", &["P0300", "P0171"], t0),
+            response("read_dtcs", &["P0300"], t1),
+            response("read_dtcs", &["P0300", "U0100"], t2),
+        ]
+        .iter()
+        .filter_map(|r| scan_from_response(r, None))
+        .collect();
+
+        let history = summarize("rpi-001", &scans);
+        assert_eq!(history.last_scan_at, Some(t2));
+        let codes: Vec<&str> = history.dtcs.iter().map(|d| d.code.as_str()).collect();
+        assert_eq!(codes, vec!["P0300", "U0100", "P0171"]);
+
+        let misfire = &history.dtcs[0];
+        assert_eq!(misfire.first_seen, t0);
+        assert_eq!(misfire.last_seen, t2);
+        assert_eq!(misfire.occurrences, 3);
+        assert!(misfire.active);
+        assert!(!history.dtcs[2].active);
+    }
+
+    #[tokio::test]
+    async fn record_keeps_scans_ordered() {
+        let state = AppState::with_sample_data();
+        let t0 = Utc::now() - Duration::minutes(10);
+        record(
+            &state,
+            "fleet-alpha",
+            &response("read_dtcs", &[], t0 + Duration::minutes(5)),
+            None,
+        )
+        .await;
+        // Arrives late but was scanned earlier.
+        record(
+            &state,
+            "fleet-alpha",
+            &response("read_dtcs", &["P0420"], t0),
+            None,
+        )
+        .await;
+
+        let history = history(&state, "rpi-001").await.unwrap();
+        assert_eq!(history.dtcs.len(), 1);
+        assert!(!history.dtcs[0].active);
+        assert_eq!(history.last_scan_at, Some(t0 + Duration::minutes(5)));
+    }
+}
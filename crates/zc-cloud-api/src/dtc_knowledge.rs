@@ -0,0 +1,402 @@
+//! DTC knowledge base — repair hints and reference links per code.
+//!
+//! Entries are maintained through the admin API, one at a time or as a CSV
+//! import. When a command response is ingested, every DTC entry in its
+//! `response_data` with a matching code gains `repair_hint` and `links`
+//! fields, so technicians see guidance next to the raw code. The response
+//! is stored and broadcast already enriched.
+//!
+//! CSV format (header required, one link per row, rows for the same code
+//! are merged; `repair_hint` may be left empty on the extra rows):
+//!
+//! ```text
+//! code,repair_hint,link_title,link_url
+//! P0300,"Check plugs, coils and injectors",Misfire guide,https://example.com/p0300
+//! P0300,,Coil test video,https://example.com/coil
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::dtc_knowledge::DtcKnowledgeRow;
+use crate::state::AppState;
+
+/// Column header expected on the first line of a CSV import.
+pub const CSV_HEADER: [&str; 4] = ["code", "repair_hint", "link_title", "link_url"];
+
+/// A reference link shown with a code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DtcLink {
+    pub title: String,
+    pub url: String,
+}
+
+/// Knowledge base entry for one code.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DtcKnowledge {
+    /// Normalized code (e.g. "P0300").
+    pub code: String,
+    pub repair_hint: Option<String>,
+    pub links: Vec<DtcLink>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl DtcKnowledge {
+    pub fn from_row(row: DtcKnowledgeRow) -> Self {
+        Self {
+            code: row.code,
+            repair_hint: row.repair_hint,
+            links: serde_json::from_value(row.links).unwrap_or_default(),
+            updated_at: row.updated_at,
+        }
+    }
+
+    pub fn to_row(&self) -> DtcKnowledgeRow {
+        DtcKnowledgeRow {
+            code: self.code.clone(),
+            repair_hint: self.repair_hint.clone(),
+            links: serde_json::to_value(&self.links).unwrap_or_default(),
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// Check that the entry carries something and its links are usable.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.repair_hint.is_none() && self.links.is_empty() {
+            return Err(format!(
+                "{}: needs a repair_hint or at least one link",
+                self.code
+            ));
+        }
+        for link in &self.links {
+            if link.title.trim().is_empty() {
+                return Err(format!("{}: link title must not be empty", self.code));
+            }
+            if !(link.url.starts_with("https://") || link.url.starts_with("http://")) {
+                return Err(format!(
+                    "{}: link url must be http(s): {}",
+                    self.code, link.url
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Normalize a code to upper case and check it has the standard
+/// five-character form (`P0300`, `U0100`, `B1A2F`).
+pub fn normalize_code(code: &str) -> Result<String, String> {
+    let code = code.trim().to_ascii_uppercase();
+    let mut chars = code.chars();
+    let valid = code.len() == 5
+        && matches!(chars.next(), Some('P' | 'C' | 'B' | 'U'))
+        && chars.all(|c| c.is_ascii_hexdigit());
+    if valid {
+        Ok(code)
+    } else {
+        Err(format!("invalid DTC code '{code}'"))
+    }
+}
+
+/// Parse a CSV import into entries, merging rows for the same code.
+pub fn parse_csv(text: &str) -> Result<Vec<DtcKnowledge>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = lines.next().ok_or("empty CSV")?;
+    let header = split_csv_line(header).map_err(|e| format!("line 1: {e}"))?;
+    if header.iter().map(|h| h.trim()).ne(CSV_HEADER) {
+        return Err(format!("line 1: header must be {}", CSV_HEADER.join(",")));
+    }
+
+    let now = Utc::now();
+    let mut entries: BTreeMap<String, DtcKnowledge> = BTreeMap::new();
+    for (index, line) in lines {
+        let line_no = index + 1;
+        let fields = split_csv_line(line).map_err(|e| format!("line {line_no}: {e}"))?;
+        let [code, hint, title, url]: [String; 4] = fields
+            .try_into()
+            .map_err(|f: Vec<_>| format!("line {line_no}: expected 4 fields, got {}", f.len()))?;
+        let code = normalize_code(&code).map_err(|e| format!("line {line_no}: {e}"))?;
+
+        let entry = entries.entry(code.clone()).or_insert_with(|| DtcKnowledge {
+            code,
+            repair_hint: None,
+            links: Vec::new(),
+            updated_at: now,
+        });
+        let hint = hint.trim();
+        if !hint.is_empty() {
+            if entry.repair_hint.as_deref().is_some_and(|h| h != hint) {
+                return Err(format!(
+                    "line {line_no}: conflicting repair_hint for {}",
+                    entry.code
+                ));
+            }
+            entry.repair_hint = Some(hint.to_string());
+        }
+        match (title.trim(), url.trim()) {
+            ("", "") => {}
+            (title, url) => entry.links.push(DtcLink {
+                title: title.to_string(),
+                url: url.to_string(),
+            }),
+        }
+    }
+
+    let entries: Vec<DtcKnowledge> = entries.into_values().collect();
+    for entry in &entries {
+        entry.validate()?;
+    }
+    Ok(entries)
+}
+
+/// Split one CSV line. Fields may be double-quoted; `""` inside quotes is
+/// a literal quote.
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".into());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Store entries, replacing existing ones for the same codes.
+pub async fn upsert(state: &AppState, entries: &[DtcKnowledge]) -> Result<(), sqlx::Error> {
+    if let Some(pool) = &state.pool {
+        let rows: Vec<DtcKnowledgeRow> = entries.iter().map(DtcKnowledge::to_row).collect();
+        crate::db::dtc_knowledge::upsert(pool, &rows).await
+    } else {
+        let mut kb = state.dtc_knowledge.write().await;
+        for entry in entries {
+            kb.insert(entry.code.clone(), entry.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Entries for the given codes, keyed by code.
+pub async fn lookup(
+    state: &AppState,
+    codes: &[String],
+) -> Result<HashMap<String, DtcKnowledge>, sqlx::Error> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::dtc_knowledge::get_many(pool, codes).await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.code.clone(), DtcKnowledge::from_row(r)))
+            .collect())
+    } else {
+        let kb = state.dtc_knowledge.read().await;
+        Ok(codes
+            .iter()
+            .filter_map(|c| kb.get(c).map(|k| (c.clone(), k.clone())))
+            .collect())
+    }
+}
+
+/// DTC entries in a command's `response_data`: either a bare array of
+/// `DtcCode` objects or the tool result wrapper holding one in `data`.
+fn dtc_entries_mut(data: &mut serde_json::Value) -> Option<&mut Vec<serde_json::Value>> {
+    if data.is_array() {
+        return data.as_array_mut();
+    }
+    data.get_mut("data")?.as_array_mut()
+}
+
+/// Codes of all DTC entries in `data`.
+pub fn codes_in(data: &serde_json::Value) -> Vec<String> {
+    let entries = match data {
+        serde_json::Value::Array(items) => items,
+        _ => match data.get("data").and_then(|d| d.as_array()) {
+            Some(items) => items,
+            None => return Vec::new(),
+        },
+    };
+    let mut codes: Vec<String> = entries
+        .iter()
+        .filter_map(|e| e.get("code")?.as_str())
+        .filter_map(|c| normalize_code(c).ok())
+        .collect();
+    codes.sort();
+    codes.dedup();
+    codes
+}
+
+/// Add `repair_hint` / `links` to each DTC entry with a knowledge entry.
+/// Returns how many entries were enriched.
+pub fn enrich_value(data: &mut serde_json::Value, kb: &HashMap<String, DtcKnowledge>) -> usize {
+    let Some(entries) = dtc_entries_mut(data) else {
+        return 0;
+    };
+    let mut enriched = 0;
+    for entry in entries.iter_mut() {
+        let Some(knowledge) = entry
+            .get("code")
+            .and_then(|c| c.as_str())
+            .and_then(|c| normalize_code(c).ok())
+            .and_then(|c| kb.get(&c))
+        else {
+            continue;
+        };
+        let Some(object) = entry.as_object_mut() else {
+            continue;
+        };
+        if let Some(hint) = &knowledge.repair_hint {
+            object.insert("repair_hint".into(), hint.clone().into());
+        }
+        if !knowledge.links.is_empty() {
+            object.insert(
+                "links".into(),
+                serde_json::to_value(&knowledge.links).unwrap_or_default(),
+            );
+        }
+        enriched += 1;
+    }
+    enriched
+}
+
+/// Enrich a command's `response_data` in place. Lookup failures are
+/// logged and leave the data unchanged.
+pub async fn enrich(state: &AppState, data: &mut Option<serde_json::Value>) {
+    let Some(data) = data.as_mut() else {
+        return;
+    };
+    let codes = codes_in(data);
+    if codes.is_empty() {
+        return;
+    }
+    match lookup(state, &codes).await {
+        Ok(kb) if !kb.is_empty() => {
+            let enriched = enrich_value(data, &kb);
+            tracing::debug!(enriched, "DTC entries enriched with repair hints");
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!(error = %e, "DTC knowledge lookup failed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn knowledge(code: &str, hint: &str) -> DtcKnowledge {
+        DtcKnowledge {
+            code: code.into(),
+            repair_hint: Some(hint.into()),
+            links: vec![DtcLink {
+                title: "Guide".into(),
+                url: "https://example.com/guide".into(),
+            }],
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn normalize_accepts_standard_codes() {
+        assert_eq!(normalize_code(" p0300 ").unwrap(), "P0300");
+        assert_eq!(normalize_code("U0100").unwrap(), "U0100");
+        assert_eq!(normalize_code("b1a2f").unwrap(), "B1A2F");
+        assert!(normalize_code("X0300").is_err());
+        assert!(normalize_code("P030").is_err());
+        assert!(normalize_code("P03G0").is_err());
+    }
+
+    #[test]
+    fn csv_merges_rows_per_code() {
+        let csv = "code,repair_hint,link_title,link_url\n\
+                   p0300,\"Check plugs, coils and injectors\",Misfire guide,https://example.com/p0300\n\
+                   P0300,,Coil test,https://example.com/coil\n\
+                   P0171,\"Look for \"\"vacuum\"\" leaks\",,\n";
+        let entries = parse_csv(csv).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].code, "P0171");
+        assert_eq!(
+            entries[0].repair_hint.as_deref(),
+            Some("Look for \"vacuum\" leaks")
+        );
+        assert!(entries[0].links.is_empty());
+        assert_eq!(entries[1].code, "P0300");
+        assert_eq!(
+            entries[1].repair_hint.as_deref(),
+            Some("Check plugs, coils and injectors")
+        );
+        assert_eq!(entries[1].links.len(), 2);
+        assert_eq!(entries[1].links[1].title, "Coil test");
+    }
+
+    #[test]
+    fn csv_errors_name_the_line() {
+        let bad_header = parse_csv("code,hint\nP0300,x\n").unwrap_err();
+        assert!(bad_header.contains("line 1"));
+
+        let bad_code = parse_csv("code,repair_hint,link_title,link_url\nP03,x,,\n").unwrap_err();
+        assert!(bad_code.contains("line 2"), "{bad_code}");
+
+        let bad_url =
+            parse_csv("code,repair_hint,link_title,link_url\nP0300,,Guide,ftp://x\n").unwrap_err();
+        assert!(bad_url.contains("http"));
+
+        let conflict = parse_csv("code,repair_hint,link_title,link_url\nP0300,a,,\n\nP0300,b,,\n")
+            .unwrap_err();
+        assert!(conflict.contains("line 4"), "{conflict}");
+
+        assert!(parse_csv("code,repair_hint,link_title,link_url\nP0300,\"open,,\n").is_err());
+    }
+
+    #[test]
+    fn enrich_adds_hints_to_tool_result() {
+        let mut data = json!({
+            "tool_name": "read_dtcs",
+            "success": true,
+            "data": [
+                {"code": "P0300", "category": "powertrain", "severity": "critical", "mil_status": true},
+                {"code": "P0420", "category": "powertrain", "severity": "warning", "mil_status": false}
+            ]
+        });
+        assert_eq!(codes_in(&data), vec!["P0300", "P0420"]);
+
+        let kb = HashMap::from([("P0300".to_string(), knowledge("P0300", "Check coils"))]);
+        assert_eq!(enrich_value(&mut data, &kb), 1);
+
+        assert_eq!(data["data"][0]["repair_hint"], "Check coils");
+        assert_eq!(
+            data["data"][0]["links"][0]["url"],
+            "https://example.com/guide"
+        );
+        assert!(data["data"][1].get("repair_hint").is_none());
+    }
+
+    #[test]
+    fn enrich_ignores_non_dtc_data() {
+        let kb = HashMap::from([("P0300".to_string(), knowledge("P0300", "x"))]);
+        let mut data = json!({"entries": [{"code": "P0300"}]});
+        assert!(codes_in(&data).is_empty());
+        assert_eq!(enrich_value(&mut data, &kb), 0);
+
+        let mut bare = json!([{"code": "P0300"}]);
+        assert_eq!(enrich_value(&mut bare, &kb), 1);
+    }
+}
@@ -1,6 +1,6 @@
 //! Unified API error type with Axum `IntoResponse` support.
 
-use axum::http::StatusCode;
+use axum::http::{HeaderValue, StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use serde_json::json;
 
@@ -19,6 +19,26 @@ pub enum ApiError {
 
     #[error("conflict: {0}")]
     Conflict(String),
+
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    /// A rate limit refused the request; retry after `retry_after_secs`.
+    #[error("too many requests: {message}")]
+    TooManyRequests {
+        message: String,
+        retry_after_secs: u64,
+    },
+
+    /// A checked precondition failed; `details` is returned alongside the message.
+    #[error("precondition failed: {message}")]
+    PreconditionFailed {
+        message: String,
+        details: serde_json::Value,
+    },
 }
 
 impl IntoResponse for ApiError {
@@ -28,14 +48,41 @@ impl IntoResponse for ApiError {
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
             ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            ApiError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
+            ApiError::PreconditionFailed { message, .. } => {
+                (StatusCode::PRECONDITION_FAILED, message.clone())
+            }
+            ApiError::TooManyRequests { message, .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, message.clone())
+            }
         };
 
-        let body = json!({
+        let mut body = json!({
             "error": message,
             "status": status.as_u16(),
         });
+        let retry_after = match self {
+            ApiError::PreconditionFailed { details, .. } => {
+                body["details"] = details;
+                None
+            }
+            ApiError::TooManyRequests {
+                retry_after_secs, ..
+            } => {
+                body["retry_after_secs"] = json!(retry_after_secs);
+                Some(retry_after_secs)
+            }
+            _ => None,
+        };
 
-        (status, axum::Json(body)).into_response()
+        let mut response = (status, axum::Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
 
@@ -66,6 +113,37 @@ mod tests {
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
+    #[tokio::test]
+    async fn precondition_failed_carries_details() {
+        let err = ApiError::PreconditionFailed {
+            message: "pre-flight check failed".into(),
+            details: json!({"checks": []}),
+        };
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], 412);
+        assert!(json["details"]["checks"].is_array());
+    }
+
+    #[tokio::test]
+    async fn too_many_requests_sets_retry_after() {
+        let err = ApiError::TooManyRequests {
+            message: "too many commands for device 'rpi-001', retry in 2s".into(),
+            retry_after_secs: 2,
+        };
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], 429);
+        assert_eq!(json["retry_after_secs"], 2);
+    }
+
     #[tokio::test]
     async fn internal_error_response() {
         let err = ApiError::Internal("database timeout".into());
@@ -0,0 +1,673 @@
+//! JSON Schema for WebSocket events, served at `GET /api/v1/events/schema`.
+//!
+//! [`EVENT_FIELDS`] is the source of the schema. Tests check it against
+//! the serialized [`WsEvent`](crate::events::WsEvent) variants and against
+//! a pinned copy of the current version, so a breaking change cannot ship
+//! without bumping [`EVENT_SCHEMA_VERSION`].
+
+use serde_json::{Map, Value, json};
+
+use crate::events::EVENT_SCHEMA_VERSION;
+
+/// JSON type of an event field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    /// String or `null`.
+    OptString,
+    Uuid,
+    DateTime,
+    /// Non-negative integer.
+    UInt,
+    /// Any number (integer or fractional).
+    Number,
+    /// Integer or `null`.
+    OptInt,
+    Bool,
+    /// Array of strings.
+    StringList,
+    /// Arbitrary JSON (including `null`).
+    Json,
+}
+
+impl FieldType {
+    /// Short name used in the pinned contract.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::OptString => "string?",
+            Self::Uuid => "uuid",
+            Self::DateTime => "date-time",
+            Self::UInt => "uint",
+            Self::Number => "number",
+            Self::OptInt => "int?",
+            Self::Bool => "bool",
+            Self::StringList => "string[]",
+            Self::Json => "json",
+        }
+    }
+
+    fn schema(self) -> Value {
+        match self {
+            Self::String => json!({"type": "string"}),
+            Self::OptString => json!({"type": ["string", "null"]}),
+            Self::Uuid => json!({"type": "string", "format": "uuid"}),
+            Self::DateTime => json!({"type": "string", "format": "date-time"}),
+            Self::UInt => json!({"type": "integer", "minimum": 0}),
+            Self::Number => json!({"type": "number"}),
+            Self::OptInt => json!({"type": ["integer", "null"]}),
+            Self::Bool => json!({"type": "boolean"}),
+            Self::StringList => json!({"type": "array", "items": {"type": "string"}}),
+            Self::Json => json!({}),
+        }
+    }
+
+    /// Whether `value` conforms to this type.
+    pub fn accepts(self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::OptString => value.is_string() || value.is_null(),
+            Self::Uuid => value
+                .as_str()
+                .is_some_and(|s| uuid::Uuid::parse_str(s).is_ok()),
+            Self::DateTime => value
+                .as_str()
+                .is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_ok()),
+            Self::UInt => value.is_u64(),
+            Self::Number => value.is_number(),
+            Self::OptInt => value.is_i64() || value.is_u64() || value.is_null(),
+            Self::Bool => value.is_boolean(),
+            Self::StringList => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(Value::is_string)),
+            Self::Json => true,
+        }
+    }
+}
+
+/// Fields of each event type, in serialization order. Every field is
+/// always present in a frame (optional values are sent as `null`).
+pub const EVENT_FIELDS: &[(&str, &[(&str, FieldType)])] = {
+    use FieldType::*;
+    &[
+        (
+            "command_dispatched",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("command", String),
+                ("initiated_by", String),
+                ("created_at", DateTime),
+            ],
+        ),
+        (
+            "command_queued",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("queued_at", DateTime),
+            ],
+        ),
+        (
+            "command_queue_flushed",
+            &[
+                ("device_id", String),
+                ("count", UInt),
+                ("flushed_at", DateTime),
+            ],
+        ),
+        (
+            "command_response",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("status", String),
+                ("inference_tier", OptString),
+                ("response_text", OptString),
+                ("response_data", Json),
+                ("error", OptString),
+                ("error_code", OptString),
+                ("latency_ms", OptInt),
+                ("responded_at", DateTime),
+            ],
+        ),
+        (
+            "command_response_chunk",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("seq", UInt),
+                ("data", Json),
+                ("sent_at", DateTime),
+            ],
+        ),
+        (
+            "command_cancelled",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("requested_by", String),
+                ("reason", OptString),
+                ("cancelled_at", DateTime),
+            ],
+        ),
+        (
+            "command_approval_requested",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("initiated_by", String),
+                ("reason", String),
+                ("expires_at", DateTime),
+            ],
+        ),
+        (
+            "command_approved",
+            &[
+                ("command_id", Uuid),
+                ("device_id", String),
+                ("initiated_by", String),
+                ("approved_by", String),
+                ("approved_at", DateTime),
+            ],
+        ),
+        (
+            "device_heartbeat",
+            &[("device_id", String), ("timestamp", DateTime)],
+        ),
+        (
+            "device_status_changed",
+            &[
+                ("device_id", String),
+                ("old_status", String),
+                ("new_status", String),
+                ("changed_at", DateTime),
+            ],
+        ),
+        (
+            "device_provisioned",
+            &[
+                ("device_id", String),
+                ("fleet_id", String),
+                ("hardware_type", String),
+                ("provisioned_at", DateTime),
+            ],
+        ),
+        (
+            "telemetry_ingested",
+            &[
+                ("device_id", String),
+                ("count", UInt),
+                ("source", String),
+                ("timestamp", DateTime),
+            ],
+        ),
+        (
+            "shadow_updated",
+            &[
+                ("device_id", String),
+                ("shadow_name", String),
+                ("version", UInt),
+                ("timestamp", DateTime),
+            ],
+        ),
+        (
+            "shadow_reconcile",
+            &[
+                ("device_id", String),
+                ("shadow_name", String),
+                ("status", String),
+                ("attempts", UInt),
+                ("timestamp", DateTime),
+            ],
+        ),
+        (
+            "tool_failure_anomaly",
+            &[
+                ("device_id", String),
+                ("fleet_id", String),
+                ("scope", String),
+                ("tool_name", String),
+                ("failures", UInt),
+                ("samples", UInt),
+                ("failure_rate", Number),
+                ("threshold", Number),
+                ("recent_errors", Json),
+                ("detected_at", DateTime),
+            ],
+        ),
+        (
+            "self_test_reported",
+            &[
+                ("device_id", String),
+                ("trigger", String),
+                ("passed", Bool),
+                ("failed_checks", StringList),
+                ("reported_at", DateTime),
+            ],
+        ),
+        (
+            "critical_dtc_detected",
+            &[
+                ("device_id", String),
+                ("fleet_id", String),
+                ("command_id", Uuid),
+                ("codes", StringList),
+                ("detected_at", DateTime),
+            ],
+        ),
+        (
+            "alert_raised",
+            &[
+                ("alert_id", Uuid),
+                ("device_id", String),
+                ("fleet_id", String),
+                ("kind", String),
+                ("scope", String),
+                ("title", String),
+                ("raised_at", DateTime),
+            ],
+        ),
+        (
+            "alert_updated",
+            &[
+                ("alert_id", Uuid),
+                ("device_id", String),
+                ("fleet_id", String),
+                ("action", String),
+                ("state", String),
+                ("actor", String),
+                ("comment", OptString),
+                ("occurrences", UInt),
+                ("snoozed_until", OptString),
+                ("updated_at", DateTime),
+            ],
+        ),
+    ]
+};
+
+/// Fields declared for `event_type`, if it is known.
+pub fn fields(event_type: &str) -> Option<&'static [(&'static str, FieldType)]> {
+    EVENT_FIELDS
+        .iter()
+        .find(|(name, _)| *name == event_type)
+        .map(|(_, fields)| *fields)
+}
+
+/// Schema for one event type. Unknown properties are allowed so clients
+/// validating against it keep working when fields are added.
+fn event_schema(event_type: &str, fields: &[(&str, FieldType)]) -> Value {
+    let mut properties = Map::new();
+    properties.insert(
+        "schema_version".into(),
+        json!({"const": EVENT_SCHEMA_VERSION}),
+    );
+    properties.insert("type".into(), json!({"const": event_type}));
+    let mut required = vec!["schema_version", "type"];
+    for (name, ty) in fields {
+        properties.insert((*name).into(), ty.schema());
+        required.push(*name);
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// The full event schema document (JSON Schema draft 2020-12).
+pub fn schema() -> Value {
+    let defs: Map<String, Value> = EVENT_FIELDS
+        .iter()
+        .map(|(name, fields)| ((*name).to_string(), event_schema(name, fields)))
+        .collect();
+    let one_of: Vec<Value> = EVENT_FIELDS
+        .iter()
+        .map(|(name, _)| json!({"$ref": format!("#/$defs/{name}")}))
+        .collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "WsEvent",
+        "description": "WebSocket event frame. New event types and fields may be added \
+                        within a schema_version; clients must ignore unknown ones.",
+        "schema_version": EVENT_SCHEMA_VERSION,
+        "oneOf": one_of,
+        "$defs": defs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EVENT_TYPES, WsEvent};
+    use chrono::Utc;
+
+    /// Version 1 of the event contract. Fields may be added to
+    /// `EVENT_FIELDS` freely; removing or retyping anything listed here
+    /// means bumping `EVENT_SCHEMA_VERSION` and re-pinning this list.
+    const PINNED_V1: &str = "
+        command_dispatched.command_id: uuid
+        command_dispatched.device_id: string
+        command_dispatched.command: string
+        command_dispatched.initiated_by: string
+        command_dispatched.created_at: date-time
+        command_queued.command_id: uuid
+        command_queued.device_id: string
+        command_queued.queued_at: date-time
+        command_queue_flushed.device_id: string
+        command_queue_flushed.count: uint
+        command_queue_flushed.flushed_at: date-time
+        command_response.command_id: uuid
+        command_response.device_id: string
+        command_response.status: string
+        command_response.inference_tier: string?
+        command_response.response_text: string?
+        command_response.response_data: json
+        command_response.error: string?
+        command_response.latency_ms: int?
+        command_response.responded_at: date-time
+        command_response_chunk.command_id: uuid
+        command_response_chunk.device_id: string
+        command_response_chunk.seq: uint
+        command_response_chunk.data: json
+        command_response_chunk.sent_at: date-time
+        command_cancelled.command_id: uuid
+        command_cancelled.device_id: string
+        command_cancelled.requested_by: string
+        command_cancelled.reason: string?
+        command_cancelled.cancelled_at: date-time
+        command_approval_requested.command_id: uuid
+        command_approval_requested.device_id: string
+        command_approval_requested.initiated_by: string
+        command_approval_requested.reason: string
+        command_approval_requested.expires_at: date-time
+        command_approved.command_id: uuid
+        command_approved.device_id: string
+        command_approved.initiated_by: string
+        command_approved.approved_by: string
+        command_approved.approved_at: date-time
+        device_heartbeat.device_id: string
+        device_heartbeat.timestamp: date-time
+        device_status_changed.device_id: string
+        device_status_changed.old_status: string
+        device_status_changed.new_status: string
+        device_status_changed.changed_at: date-time
+        device_provisioned.device_id: string
+        device_provisioned.fleet_id: string
+        device_provisioned.hardware_type: string
+        device_provisioned.provisioned_at: date-time
+        telemetry_ingested.device_id: string
+        telemetry_ingested.count: uint
+        telemetry_ingested.source: string
+        telemetry_ingested.timestamp: date-time
+        shadow_updated.device_id: string
+        shadow_updated.shadow_name: string
+        shadow_updated.version: uint
+        shadow_updated.timestamp: date-time
+        shadow_reconcile.device_id: string
+        shadow_reconcile.shadow_name: string
+        shadow_reconcile.status: string
+        shadow_reconcile.attempts: uint
+        shadow_reconcile.timestamp: date-time
+        tool_failure_anomaly.device_id: string
+        tool_failure_anomaly.fleet_id: string
+        tool_failure_anomaly.scope: string
+        tool_failure_anomaly.tool_name: string
+        tool_failure_anomaly.failures: uint
+        tool_failure_anomaly.samples: uint
+        tool_failure_anomaly.failure_rate: number
+        tool_failure_anomaly.threshold: number
+        tool_failure_anomaly.recent_errors: json
+        tool_failure_anomaly.detected_at: date-time
+        self_test_reported.device_id: string
+        self_test_reported.trigger: string
+        self_test_reported.passed: bool
+        self_test_reported.failed_checks: string[]
+        self_test_reported.reported_at: date-time
+        critical_dtc_detected.device_id: string
+        critical_dtc_detected.fleet_id: string
+        critical_dtc_detected.command_id: uuid
+        critical_dtc_detected.codes: string[]
+        critical_dtc_detected.detected_at: date-time
+        alert_raised.alert_id: uuid
+        alert_raised.device_id: string
+        alert_raised.fleet_id: string
+        alert_raised.kind: string
+        alert_raised.scope: string
+        alert_raised.title: string
+        alert_raised.raised_at: date-time
+        alert_updated.alert_id: uuid
+        alert_updated.device_id: string
+        alert_updated.fleet_id: string
+        alert_updated.action: string
+        alert_updated.state: string
+        alert_updated.actor: string
+        alert_updated.comment: string?
+        alert_updated.occurrences: uint
+        alert_updated.snoozed_until: string?
+        alert_updated.updated_at: date-time
+    ";
+
+    /// One instance of every event variant.
+    fn samples() -> Vec<WsEvent> {
+        let id = uuid::Uuid::now_v7();
+        let now = Utc::now();
+        vec![
+            WsEvent::CommandDispatched {
+                command_id: id,
+                device_id: "rpi-001".into(),
+                command: "read DTCs".into(),
+                initiated_by: "admin".into(),
+                created_at: now,
+            },
+            WsEvent::CommandQueued {
+                command_id: id,
+                device_id: "rpi-002".into(),
+                queued_at: now,
+            },
+            WsEvent::CommandQueueFlushed {
+                device_id: "rpi-002".into(),
+                count: 2,
+                flushed_at: now,
+            },
+            WsEvent::CommandResponse {
+                command_id: id,
+                device_id: "rpi-001".into(),
+                status: "completed".into(),
+                inference_tier: None,
+                response_text: Some("No DTCs found".into()),
+                response_data: Some(json!({"dtcs": []})),
+                error: None,
+                error_code: Some("can_timeout".into()),
+                latency_ms: Some(45),
+                responded_at: now,
+            },
+            WsEvent::CommandResponseChunk {
+                command_id: id,
+                device_id: "rpi-001".into(),
+                seq: 0,
+                data: json!({"frames": []}),
+                sent_at: now,
+            },
+            WsEvent::CommandCancelled {
+                command_id: id,
+                device_id: "rpi-001".into(),
+                requested_by: "ops".into(),
+                reason: None,
+                cancelled_at: now,
+            },
+            WsEvent::CommandApprovalRequested {
+                command_id: id,
+                device_id: "rpi-001".into(),
+                initiated_by: "tech".into(),
+                reason: "high-risk tool 'clear_dtcs'".into(),
+                expires_at: now,
+            },
+            WsEvent::CommandApproved {
+                command_id: id,
+                device_id: "rpi-001".into(),
+                initiated_by: "tech".into(),
+                approved_by: "lead".into(),
+                approved_at: now,
+            },
+            WsEvent::DeviceHeartbeat {
+                device_id: "rpi-001".into(),
+                timestamp: now,
+            },
+            WsEvent::DeviceStatusChanged {
+                device_id: "rpi-001".into(),
+                old_status: "online".into(),
+                new_status: "offline".into(),
+                changed_at: now,
+            },
+            WsEvent::DeviceProvisioned {
+                device_id: "rpi-003".into(),
+                fleet_id: "fleet-alpha".into(),
+                hardware_type: "raspberry_pi4".into(),
+                provisioned_at: now,
+            },
+            WsEvent::TelemetryIngested {
+                device_id: "rpi-001".into(),
+                count: 3,
+                source: "obd2".into(),
+                timestamp: now,
+            },
+            WsEvent::ShadowUpdated {
+                device_id: "rpi-001".into(),
+                shadow_name: "config".into(),
+                version: 4,
+                timestamp: now,
+            },
+            WsEvent::ShadowReconcile {
+                device_id: "rpi-001".into(),
+                shadow_name: "config".into(),
+                status: "pending".into(),
+                attempts: 2,
+                timestamp: now,
+            },
+            WsEvent::ToolFailureAnomaly {
+                device_id: "rpi-001".into(),
+                fleet_id: "fleet-alpha".into(),
+                scope: "fleet".into(),
+                tool_name: "read_dtcs".into(),
+                failures: 4,
+                samples: 5,
+                failure_rate: 0.8,
+                threshold: 0.5,
+                recent_errors: serde_json::json!([{"command_id": id, "error": "no response from ECU"}]),
+                detected_at: now,
+            },
+            WsEvent::SelfTestReported {
+                device_id: "rpi-001".into(),
+                trigger: "first_boot".into(),
+                passed: false,
+                failed_checks: vec!["broker".into()],
+                reported_at: now,
+            },
+            WsEvent::CriticalDtcDetected {
+                device_id: "rpi-001".into(),
+                fleet_id: "fleet-alpha".into(),
+                command_id: id,
+                codes: vec!["P0300".into()],
+                detected_at: now,
+            },
+            WsEvent::AlertRaised {
+                alert_id: id,
+                device_id: "rpi-001".into(),
+                fleet_id: "fleet-alpha".into(),
+                kind: "tool_failure_rate".into(),
+                scope: "fleet".into(),
+                title: "read_dtcs failing 80% across fleet-alpha (4/5)".into(),
+                raised_at: now,
+            },
+            WsEvent::AlertUpdated {
+                alert_id: id,
+                device_id: "rpi-001".into(),
+                fleet_id: "fleet-alpha".into(),
+                action: "snoozed".into(),
+                state: "snoozed".into(),
+                actor: "alice".into(),
+                comment: None,
+                occurrences: 2,
+                snoozed_until: Some(now),
+                updated_at: now,
+            },
+        ]
+    }
+
+    #[test]
+    fn every_event_type_is_described() {
+        let described: Vec<&str> = EVENT_FIELDS.iter().map(|(name, _)| *name).collect();
+        assert_eq!(described, EVENT_TYPES);
+
+        let mut sampled: Vec<&str> = samples().iter().map(|e| e.event_type()).collect();
+        sampled.sort();
+        let mut all = EVENT_TYPES.to_vec();
+        all.sort();
+        assert_eq!(sampled, all, "samples() must cover every variant");
+    }
+
+    #[test]
+    fn frames_match_declared_fields() {
+        for event in samples() {
+            let frame = event.to_frame();
+            let obj = frame.as_object().unwrap();
+            let fields = fields(event.event_type()).unwrap();
+
+            assert_eq!(obj["schema_version"], EVENT_SCHEMA_VERSION);
+            assert_eq!(
+                obj.len(),
+                fields.len() + 2,
+                "{}: frame has undeclared fields",
+                event.event_type()
+            );
+            for (name, ty) in fields {
+                let value = obj
+                    .get(*name)
+                    .unwrap_or_else(|| panic!("{}.{name} missing", event.event_type()));
+                assert!(
+                    ty.accepts(value),
+                    "{}.{name} = {value} is not {}",
+                    event.event_type(),
+                    ty.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pinned_contract_is_preserved() {
+        assert_eq!(
+            EVENT_SCHEMA_VERSION, 1,
+            "schema version changed: re-pin the contract below"
+        );
+        for line in PINNED_V1.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (path, ty) = line.split_once(": ").unwrap();
+            let (event_type, field) = path.split_once('.').unwrap();
+            let current = fields(event_type)
+                .unwrap_or_else(|| panic!("event type '{event_type}' removed"))
+                .iter()
+                .find(|(name, _)| *name == field)
+                .unwrap_or_else(|| panic!("{path} removed"));
+            assert_eq!(current.1.name(), ty, "{path} changed type");
+        }
+    }
+
+    #[test]
+    fn schema_document_lists_every_event() {
+        let doc = schema();
+        assert_eq!(doc["schema_version"], EVENT_SCHEMA_VERSION);
+        assert_eq!(doc["oneOf"].as_array().unwrap().len(), EVENT_TYPES.len());
+
+        let def = &doc["$defs"]["command_cancelled"];
+        assert_eq!(def["properties"]["type"]["const"], "command_cancelled");
+        assert_eq!(
+            def["properties"]["reason"]["type"],
+            json!(["string", "null"])
+        );
+        let required = def["required"].as_array().unwrap();
+        assert!(required.contains(&json!("schema_version")));
+        assert!(required.contains(&json!("cancelled_at")));
+    }
+}
@@ -1,4 +1,10 @@
 //! Real-time event types broadcast over WebSocket connections.
+//!
+//! Every frame carries `schema_version` ([`EVENT_SCHEMA_VERSION`]). Within
+//! a version, new event types and new fields may appear, so clients must
+//! ignore what they don't know. Removing, renaming or retyping a field
+//! bumps the version. The full contract is served as JSON Schema by
+//! [`crate::event_schema`].
 
 use chrono::{DateTime, Utc};
 use serde::Serialize;
@@ -17,6 +23,20 @@ pub enum WsEvent {
         created_at: DateTime<Utc>,
     },
 
+    /// A command was queued because the target device is offline.
+    CommandQueued {
+        command_id: Uuid,
+        device_id: String,
+        queued_at: DateTime<Utc>,
+    },
+
+    /// Queued commands were delivered after the device came back online.
+    CommandQueueFlushed {
+        device_id: String,
+        count: usize,
+        flushed_at: DateTime<Utc>,
+    },
+
     /// A command response was received from a device.
     CommandResponse {
         command_id: Uuid,
@@ -26,10 +46,48 @@ pub enum WsEvent {
         response_text: Option<String>,
         response_data: Option<serde_json::Value>,
         error: Option<String>,
+        /// Wire name of the response's `ErrorCode` (e.g. `can_timeout`).
+        error_code: Option<String>,
         latency_ms: Option<i64>,
         responded_at: DateTime<Utc>,
     },
 
+    /// A command was cancelled by an operator.
+    CommandCancelled {
+        command_id: Uuid,
+        device_id: String,
+        requested_by: String,
+        reason: Option<String>,
+        cancelled_at: DateTime<Utc>,
+    },
+
+    /// A high-risk command is held until a second operator approves it.
+    CommandApprovalRequested {
+        command_id: Uuid,
+        device_id: String,
+        initiated_by: String,
+        reason: String,
+        expires_at: DateTime<Utc>,
+    },
+
+    /// A held command was approved and dispatched.
+    CommandApproved {
+        command_id: Uuid,
+        device_id: String,
+        initiated_by: String,
+        approved_by: String,
+        approved_at: DateTime<Utc>,
+    },
+
+    /// A partial result arrived for a command that is still running.
+    CommandResponseChunk {
+        command_id: Uuid,
+        device_id: String,
+        seq: u32,
+        data: serde_json::Value,
+        sent_at: DateTime<Utc>,
+    },
+
     /// A device heartbeat was received.
     DeviceHeartbeat {
         device_id: String,
@@ -67,6 +125,165 @@ pub enum WsEvent {
         version: u64,
         timestamp: DateTime<Utc>,
     },
+
+    /// A shadow's outstanding delta was re-delivered, converged, or gave up
+    /// after too many deliveries (`status`: pending / converged / exhausted).
+    ShadowReconcile {
+        device_id: String,
+        shadow_name: String,
+        status: String,
+        attempts: u64,
+        timestamp: DateTime<Utc>,
+    },
+
+    /// A tool's failure rate on a device or across a fleet (`scope`) crossed
+    /// its alert threshold. `device_id` is the device whose response tipped
+    /// it over; `recent_errors` holds the latest failures, newest first.
+    ToolFailureAnomaly {
+        device_id: String,
+        fleet_id: String,
+        scope: String,
+        tool_name: String,
+        failures: u64,
+        samples: u64,
+        failure_rate: f64,
+        threshold: f64,
+        recent_errors: serde_json::Value,
+        detected_at: DateTime<Utc>,
+    },
+
+    /// A device published a self-test (provisioning verification) report.
+    SelfTestReported {
+        device_id: String,
+        trigger: String,
+        passed: bool,
+        failed_checks: Vec<String>,
+        reported_at: DateTime<Utc>,
+    },
+
+    /// A DTC scan reported codes of critical severity.
+    CriticalDtcDetected {
+        device_id: String,
+        fleet_id: String,
+        command_id: Uuid,
+        codes: Vec<String>,
+        detected_at: DateTime<Utc>,
+    },
+
+    /// A new alert was opened. `device_id` is the device whose data raised
+    /// it; fleet-scope alerts are listed under that device too.
+    AlertRaised {
+        alert_id: Uuid,
+        device_id: String,
+        fleet_id: String,
+        kind: String,
+        scope: String,
+        title: String,
+        raised_at: DateTime<Utc>,
+    },
+
+    /// An alert reoccurred or an operator acknowledged, snoozed or resolved
+    /// it (`action`). `state` is the alert's state afterwards.
+    AlertUpdated {
+        alert_id: Uuid,
+        device_id: String,
+        fleet_id: String,
+        action: String,
+        state: String,
+        actor: String,
+        comment: Option<String>,
+        occurrences: u64,
+        snoozed_until: Option<DateTime<Utc>>,
+        updated_at: DateTime<Utc>,
+    },
+}
+
+/// Version of the event contract, sent in every WebSocket frame.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// All `type` tags a client may subscribe to.
+pub const EVENT_TYPES: &[&str] = &[
+    "command_dispatched",
+    "command_queued",
+    "command_queue_flushed",
+    "command_response",
+    "command_response_chunk",
+    "command_cancelled",
+    "command_approval_requested",
+    "command_approved",
+    "device_heartbeat",
+    "device_status_changed",
+    "device_provisioned",
+    "telemetry_ingested",
+    "shadow_updated",
+    "shadow_reconcile",
+    "tool_failure_anomaly",
+    "self_test_reported",
+    "critical_dtc_detected",
+    "alert_raised",
+    "alert_updated",
+];
+
+impl WsEvent {
+    /// The serialized `type` tag.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::CommandDispatched { .. } => "command_dispatched",
+            Self::CommandQueued { .. } => "command_queued",
+            Self::CommandQueueFlushed { .. } => "command_queue_flushed",
+            Self::CommandResponse { .. } => "command_response",
+            Self::CommandResponseChunk { .. } => "command_response_chunk",
+            Self::CommandCancelled { .. } => "command_cancelled",
+            Self::CommandApprovalRequested { .. } => "command_approval_requested",
+            Self::CommandApproved { .. } => "command_approved",
+            Self::DeviceHeartbeat { .. } => "device_heartbeat",
+            Self::DeviceStatusChanged { .. } => "device_status_changed",
+            Self::DeviceProvisioned { .. } => "device_provisioned",
+            Self::TelemetryIngested { .. } => "telemetry_ingested",
+            Self::ShadowUpdated { .. } => "shadow_updated",
+            Self::ShadowReconcile { .. } => "shadow_reconcile",
+            Self::ToolFailureAnomaly { .. } => "tool_failure_anomaly",
+            Self::SelfTestReported { .. } => "self_test_reported",
+            Self::CriticalDtcDetected { .. } => "critical_dtc_detected",
+            Self::AlertRaised { .. } => "alert_raised",
+            Self::AlertUpdated { .. } => "alert_updated",
+        }
+    }
+
+    /// The WebSocket frame for this event: the serialized event plus
+    /// `schema_version`.
+    pub fn to_frame(&self) -> serde_json::Value {
+        let mut frame = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = frame.as_object_mut() {
+            obj.insert("schema_version".into(), EVENT_SCHEMA_VERSION.into());
+        }
+        frame
+    }
+
+    /// The device the event concerns.
+    pub fn device_id(&self) -> &str {
+        match self {
+            Self::CommandDispatched { device_id, .. }
+            | Self::CommandQueued { device_id, .. }
+            | Self::CommandQueueFlushed { device_id, .. }
+            | Self::CommandResponse { device_id, .. }
+            | Self::CommandResponseChunk { device_id, .. }
+            | Self::CommandCancelled { device_id, .. }
+            | Self::CommandApprovalRequested { device_id, .. }
+            | Self::CommandApproved { device_id, .. }
+            | Self::DeviceHeartbeat { device_id, .. }
+            | Self::DeviceStatusChanged { device_id, .. }
+            | Self::DeviceProvisioned { device_id, .. }
+            | Self::TelemetryIngested { device_id, .. }
+            | Self::ShadowUpdated { device_id, .. }
+            | Self::ShadowReconcile { device_id, .. }
+            | Self::ToolFailureAnomaly { device_id, .. }
+            | Self::SelfTestReported { device_id, .. }
+            | Self::CriticalDtcDetected { device_id, .. }
+            | Self::AlertRaised { device_id, .. }
+            | Self::AlertUpdated { device_id, .. } => device_id,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +304,47 @@ mod tests {
         assert!(json.contains(r#""device_id":"rpi-001""#));
     }
 
+    #[test]
+    fn event_type_matches_serialized_tag() {
+        let events = [
+            WsEvent::CommandQueueFlushed {
+                device_id: "rpi-001".into(),
+                count: 2,
+                flushed_at: Utc::now(),
+            },
+            WsEvent::TelemetryIngested {
+                device_id: "rpi-001".into(),
+                count: 3,
+                source: "obd2".into(),
+                timestamp: Utc::now(),
+            },
+            WsEvent::DeviceProvisioned {
+                device_id: "rpi-003".into(),
+                fleet_id: "fleet-alpha".into(),
+                hardware_type: "raspberry_pi4".into(),
+                provisioned_at: Utc::now(),
+            },
+        ];
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["type"], event.event_type());
+            assert_eq!(json["device_id"], event.device_id());
+            assert!(EVENT_TYPES.contains(&event.event_type()));
+        }
+    }
+
+    #[test]
+    fn frame_includes_schema_version() {
+        let event = WsEvent::DeviceHeartbeat {
+            device_id: "rpi-001".into(),
+            timestamp: Utc::now(),
+        };
+        let frame = event.to_frame();
+        assert_eq!(frame["schema_version"], EVENT_SCHEMA_VERSION);
+        assert_eq!(frame["type"], "device_heartbeat");
+        assert_eq!(frame["device_id"], "rpi-001");
+    }
+
     #[test]
     fn heartbeat_event_serializes() {
         let event = WsEvent::DeviceHeartbeat {
@@ -107,6 +365,7 @@ mod tests {
             response_text: Some("No DTCs found".into()),
             response_data: None,
             error: None,
+            error_code: None,
             latency_ms: Some(45),
             responded_at: Utc::now(),
         };
@@ -141,4 +400,16 @@ mod tests {
         assert!(json.contains(r#""type":"device_status_changed""#));
         assert!(json.contains(r#""old_status":"online""#));
     }
+
+    #[test]
+    fn command_queued_event_serializes() {
+        let event = WsEvent::CommandQueued {
+            command_id: Uuid::nil(),
+            device_id: "rpi-002".into(),
+            queued_at: Utc::now(),
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains(r#""type":"command_queued""#));
+        assert!(json.contains(r#""device_id":"rpi-002""#));
+    }
 }
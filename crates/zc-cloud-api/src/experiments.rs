@@ -0,0 +1,382 @@
+//! Inference A/B experiments.
+//!
+//! An experiment splits dispatched commands across named variants by
+//! percentage. A variant can replace the LLM system prompt and/or set a
+//! minimum confidence below which a parse is discarded. For each command
+//! the assigned variant and parse result are recorded at dispatch time;
+//! the terminal status (completed / failed / timeout) is filled in when
+//! the device responds. At most one experiment is active at a time.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use zc_protocol::commands::CommandStatus;
+
+use crate::db::experiments::{AssignmentRow, ExperimentRow};
+use crate::inference::InferenceOverrides;
+use crate::state::AppState;
+
+/// One arm of an experiment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    /// Unique name within the experiment (e.g. "control").
+    pub name: String,
+    /// Share of traffic in percent. Weights across variants sum to 100.
+    pub weight: u8,
+    /// Replacement system prompt for LLM-backed tiers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Parses below this confidence are dropped for this variant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f64>,
+}
+
+impl Variant {
+    /// Inference overrides this variant applies.
+    pub fn overrides(&self) -> InferenceOverrides {
+        InferenceOverrides {
+            system_prompt: self.system_prompt.clone(),
+            ..InferenceOverrides::default()
+        }
+    }
+}
+
+/// An experiment definition.
+#[derive(Debug, Clone, Serialize)]
+pub struct Experiment {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub variants: Vec<Variant>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl Experiment {
+    pub fn from_row(row: ExperimentRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            variants: serde_json::from_value(row.variants).unwrap_or_default(),
+            active: row.active,
+            created_at: row.created_at,
+            stopped_at: row.stopped_at,
+        }
+    }
+}
+
+/// The variant a command was assigned and how it fared.
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub command_id: Uuid,
+    pub experiment_id: Uuid,
+    pub variant: String,
+    /// Whether inference produced a usable intent.
+    pub parsed: bool,
+    pub confidence: Option<f64>,
+    /// Terminal command status, once known.
+    pub outcome: Option<CommandStatus>,
+}
+
+impl Assignment {
+    fn from_row(row: AssignmentRow) -> Self {
+        Self {
+            command_id: row.command_id,
+            experiment_id: row.experiment_id,
+            variant: row.variant,
+            parsed: row.parsed,
+            confidence: row.confidence,
+            outcome: row
+                .outcome
+                .and_then(|s| serde_json::from_value(serde_json::Value::String(s)).ok()),
+        }
+    }
+}
+
+/// Aggregated results for one variant.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VariantResult {
+    pub variant: String,
+    pub weight: u8,
+    /// Commands assigned to this variant.
+    pub assigned: usize,
+    /// Commands whose text produced an intent.
+    pub parsed: usize,
+    pub parse_rate: Option<f64>,
+    pub avg_confidence: Option<f64>,
+    pub completed: usize,
+    /// Failed or timed out.
+    pub failed: usize,
+    /// Completed / (completed + failed); None until an outcome is known.
+    pub success_rate: Option<f64>,
+}
+
+/// Check that a variant set is usable.
+pub fn validate_variants(variants: &[Variant]) -> Result<(), String> {
+    if variants.is_empty() {
+        return Err("at least one variant is required".into());
+    }
+    for (i, v) in variants.iter().enumerate() {
+        if v.name.trim().is_empty() {
+            return Err(format!("variant {i} has an empty name"));
+        }
+        if variants[..i].iter().any(|other| other.name == v.name) {
+            return Err(format!("duplicate variant name '{}'", v.name));
+        }
+        if let Some(c) = v.min_confidence
+            && !(0.0..=1.0).contains(&c)
+        {
+            return Err(format!(
+                "variant '{}': min_confidence must be between 0 and 1",
+                v.name
+            ));
+        }
+    }
+    let total: u32 = variants.iter().map(|v| v.weight as u32).sum();
+    if total != 100 {
+        return Err(format!("variant weights must sum to 100, got {total}"));
+    }
+    Ok(())
+}
+
+/// Deterministically pick a variant for a command.
+///
+/// The bucket is derived from the command ID so the same command always
+/// maps to the same variant.
+pub fn pick_variant(variants: &[Variant], command_id: Uuid) -> Option<&Variant> {
+    let bucket = (command_id.as_u128() % 100) as u32;
+    let mut cumulative = 0u32;
+    for v in variants {
+        cumulative += v.weight as u32;
+        if bucket < cumulative {
+            return Some(v);
+        }
+    }
+    variants.last()
+}
+
+/// Aggregate assignments into per-variant results (in variant order).
+pub fn summarize(variants: &[Variant], assignments: &[Assignment]) -> Vec<VariantResult> {
+    variants
+        .iter()
+        .map(|v| {
+            let mine: Vec<&Assignment> =
+                assignments.iter().filter(|a| a.variant == v.name).collect();
+            let parsed = mine.iter().filter(|a| a.parsed).count();
+            let confidences: Vec<f64> = mine.iter().filter_map(|a| a.confidence).collect();
+            let completed = mine
+                .iter()
+                .filter(|a| a.outcome == Some(CommandStatus::Completed))
+                .count();
+            let failed = mine
+                .iter()
+                .filter(|a| {
+                    matches!(
+                        a.outcome,
+                        Some(CommandStatus::Failed | CommandStatus::Timeout)
+                    )
+                })
+                .count();
+            VariantResult {
+                variant: v.name.clone(),
+                weight: v.weight,
+                assigned: mine.len(),
+                parsed,
+                parse_rate: ratio(parsed, mine.len()),
+                avg_confidence: (!confidences.is_empty())
+                    .then(|| confidences.iter().sum::<f64>() / confidences.len() as f64),
+                completed,
+                failed,
+                success_rate: ratio(completed, completed + failed),
+            }
+        })
+        .collect()
+}
+
+fn ratio(n: usize, d: usize) -> Option<f64> {
+    (d > 0).then(|| n as f64 / d as f64)
+}
+
+/// The currently running experiment, if any.
+pub async fn active(state: &AppState) -> Result<Option<Experiment>, sqlx::Error> {
+    if let Some(pool) = &state.pool {
+        Ok(crate::db::experiments::get_active(pool)
+            .await?
+            .map(Experiment::from_row))
+    } else {
+        let experiments = state.experiments.read().await;
+        Ok(experiments.iter().find(|e| e.active).cloned())
+    }
+}
+
+/// All recorded assignments for an experiment.
+pub async fn assignments(
+    state: &AppState,
+    experiment_id: Uuid,
+) -> Result<Vec<Assignment>, sqlx::Error> {
+    if let Some(pool) = &state.pool {
+        Ok(
+            crate::db::experiments::list_assignments(pool, experiment_id)
+                .await?
+                .into_iter()
+                .map(Assignment::from_row)
+                .collect(),
+        )
+    } else {
+        let assignments = state.experiment_assignments.read().await;
+        Ok(assignments
+            .values()
+            .filter(|a| a.experiment_id == experiment_id)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Store a command's variant assignment.
+pub async fn record_assignment(state: &AppState, assignment: Assignment) {
+    if let Some(pool) = &state.pool {
+        let row = AssignmentRow {
+            command_id: assignment.command_id,
+            experiment_id: assignment.experiment_id,
+            variant: assignment.variant,
+            parsed: assignment.parsed,
+            confidence: assignment.confidence,
+            outcome: None,
+            created_at: Utc::now(),
+        };
+        if let Err(e) = crate::db::experiments::insert_assignment(pool, &row).await {
+            tracing::error!(error = %e, command_id = %row.command_id, "failed to record experiment assignment");
+        }
+    } else {
+        state
+            .experiment_assignments
+            .write()
+            .await
+            .insert(assignment.command_id, assignment);
+    }
+}
+
+/// Record a command's outcome against its assignment, if it has one.
+///
+/// Only terminal statuses count; intermediate updates are ignored.
+pub async fn record_outcome(state: &AppState, command_id: Uuid, status: CommandStatus) {
+    if !matches!(
+        status,
+        CommandStatus::Completed | CommandStatus::Failed | CommandStatus::Timeout
+    ) {
+        return;
+    }
+    if let Some(pool) = &state.pool {
+        let outcome = serde_json::to_value(status)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+        if let Err(e) = crate::db::experiments::update_outcome(pool, command_id, &outcome).await {
+            tracing::error!(error = %e, command_id = %command_id, "failed to record experiment outcome");
+        }
+    } else if let Some(a) = state
+        .experiment_assignments
+        .write()
+        .await
+        .get_mut(&command_id)
+    {
+        a.outcome = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(name: &str, weight: u8) -> Variant {
+        Variant {
+            name: name.into(),
+            weight,
+            system_prompt: None,
+            min_confidence: None,
+        }
+    }
+
+    fn assignment(variant: &str, parsed: bool, outcome: Option<CommandStatus>) -> Assignment {
+        Assignment {
+            command_id: Uuid::now_v7(),
+            experiment_id: Uuid::nil(),
+            variant: variant.into(),
+            parsed,
+            confidence: parsed.then_some(0.8),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn validate_rejects_bad_weights_and_names() {
+        assert!(validate_variants(&[variant("a", 50), variant("b", 50)]).is_ok());
+        assert!(validate_variants(&[]).is_err());
+        assert!(validate_variants(&[variant("a", 50), variant("b", 40)]).is_err());
+        assert!(validate_variants(&[variant("a", 50), variant("a", 50)]).is_err());
+
+        let mut bad = variant("a", 100);
+        bad.min_confidence = Some(1.5);
+        assert!(validate_variants(&[bad]).is_err());
+    }
+
+    #[test]
+    fn pick_variant_follows_weights() {
+        let variants = [variant("a", 30), variant("b", 70)];
+        assert_eq!(
+            pick_variant(&variants, Uuid::from_u128(29)).unwrap().name,
+            "a"
+        );
+        assert_eq!(
+            pick_variant(&variants, Uuid::from_u128(30)).unwrap().name,
+            "b"
+        );
+        assert_eq!(
+            pick_variant(&variants, Uuid::from_u128(199)).unwrap().name,
+            "b"
+        );
+    }
+
+    #[test]
+    fn pick_variant_splits_traffic() {
+        let variants = [variant("a", 50), variant("b", 50)];
+        let a = (0..1000)
+            .filter(|_| pick_variant(&variants, Uuid::now_v7()).unwrap().name == "a")
+            .count();
+        assert!((350..650).contains(&a), "split was {a}/1000");
+    }
+
+    #[test]
+    fn summarize_counts_per_variant() {
+        let variants = [variant("control", 50), variant("strict", 50)];
+        let assignments = [
+            assignment("control", true, Some(CommandStatus::Completed)),
+            assignment("control", true, Some(CommandStatus::Failed)),
+            assignment("control", false, None),
+            assignment("strict", true, Some(CommandStatus::Timeout)),
+        ];
+        let results = summarize(&variants, &assignments);
+
+        assert_eq!(results[0].assigned, 3);
+        assert_eq!(results[0].parsed, 2);
+        assert_eq!(results[0].completed, 1);
+        assert_eq!(results[0].failed, 1);
+        assert_eq!(results[0].success_rate, Some(0.5));
+        assert_eq!(results[0].avg_confidence, Some(0.8));
+
+        assert_eq!(results[1].assigned, 1);
+        assert_eq!(results[1].failed, 1);
+        assert_eq!(results[1].success_rate, Some(0.0));
+    }
+
+    #[test]
+    fn summarize_empty_variant_has_no_rates() {
+        let results = summarize(&[variant("a", 100)], &[]);
+        assert_eq!(results[0].assigned, 0);
+        assert_eq!(results[0].parse_rate, None);
+        assert_eq!(results[0].success_rate, None);
+    }
+}
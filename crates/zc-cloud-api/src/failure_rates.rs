@@ -0,0 +1,443 @@
+//! Rolling tool failure rates and anomaly alerts.
+//!
+//! Every final command response (over MQTT or `POST /commands/{id}/respond`)
+//! is counted against its tool twice: per (device, tool) and per
+//! (fleet, tool). Outcomes older than the window are dropped. When a
+//! window holds at least `min_samples` outcomes and its failure rate
+//! reaches the tool's threshold, a [`WsEvent::ToolFailureAnomaly`] is
+//! emitted with the most recent error messages — e.g. `read_dtcs` starting
+//! to fail across a fleet after a firmware update. The alert fires once
+//! and re-arms when the rate drops back below the threshold. Each crossing
+//! also raises an alert that operators acknowledge and resolve; a crossing
+//! while the previous alert is unresolved counts as a reoccurrence.
+//!
+//! `failed` and `timeout` count as failures, `completed` as a success;
+//! cancellations and conversational replies are not counted. Shell
+//! commands are tracked together as the `shell` tool. Windows are kept in
+//! memory in both modes and start empty after a restart.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use zc_protocol::commands::{ActionKind, CommandStatus, ParsedIntent};
+
+use crate::alerts::NewAlert;
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// Default failure rate (0–1) that raises an alert.
+pub const DEFAULT_FAILURE_RATE: f64 = 0.5;
+/// Default number of outcomes a window needs before it can alert.
+pub const DEFAULT_MIN_SAMPLES: usize = 5;
+/// Default window length.
+pub const DEFAULT_WINDOW_SECS: u64 = 3600;
+/// Outcomes kept per window, whatever their age.
+const MAX_OUTCOMES: usize = 1000;
+/// Error messages attached to an alert.
+const ERROR_SAMPLES: usize = 5;
+
+/// When a failure rate counts as anomalous.
+#[derive(Debug, Clone)]
+pub struct FailureThresholds {
+    /// Failure rate (0–1) that raises an alert, unless overridden per tool.
+    pub failure_rate: f64,
+    /// Per-tool rates, e.g. a stricter one for `read_dtcs`.
+    pub per_tool: Vec<(String, f64)>,
+    /// Outcomes a window needs before it can alert.
+    pub min_samples: usize,
+    /// How far back outcomes are counted.
+    pub window: Duration,
+}
+
+impl Default for FailureThresholds {
+    fn default() -> Self {
+        Self {
+            failure_rate: DEFAULT_FAILURE_RATE,
+            per_tool: Vec::new(),
+            min_samples: DEFAULT_MIN_SAMPLES,
+            window: Duration::seconds(DEFAULT_WINDOW_SECS as i64),
+        }
+    }
+}
+
+impl FailureThresholds {
+    /// Alert threshold for `tool`.
+    pub fn rate_for(&self, tool: &str) -> f64 {
+        self.per_tool
+            .iter()
+            .find(|(name, _)| name == tool)
+            .map(|(_, rate)| *rate)
+            .unwrap_or(self.failure_rate)
+    }
+}
+
+/// What a failure rate is aggregated over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Device,
+    Fleet,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Device => "device",
+            Self::Fleet => "fleet",
+        }
+    }
+}
+
+/// A failed command kept as evidence for an alert.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorSample {
+    pub command_id: Uuid,
+    pub device_id: String,
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// Current failure rate of one tool on one device or fleet.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureRate {
+    pub scope: Scope,
+    pub fleet_id: String,
+    /// Set for device scope only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    pub tool_name: String,
+    pub samples: usize,
+    pub failures: usize,
+    pub failure_rate: f64,
+    pub threshold: f64,
+    /// The rate has crossed the threshold and not dropped back yet.
+    pub alerting: bool,
+    /// Most recent failures, newest first.
+    pub recent_errors: Vec<ErrorSample>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    scope: Scope,
+    fleet_id: String,
+    device_id: Option<String>,
+    tool: String,
+}
+
+#[derive(Debug)]
+struct Outcome {
+    at: DateTime<Utc>,
+    /// `Some` for failures.
+    failure: Option<ErrorSample>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    outcomes: VecDeque<Outcome>,
+    alerting: bool,
+}
+
+impl Window {
+    fn prune(&mut self, now: DateTime<Utc>, window: Duration) {
+        while self.outcomes.len() > MAX_OUTCOMES
+            || self.outcomes.front().is_some_and(|o| now - o.at > window)
+        {
+            self.outcomes.pop_front();
+        }
+    }
+
+    fn rate(&self, key: &Key, threshold: f64) -> FailureRate {
+        let failures: Vec<&ErrorSample> = self
+            .outcomes
+            .iter()
+            .rev()
+            .filter_map(|o| o.failure.as_ref())
+            .collect();
+        let samples = self.outcomes.len();
+        FailureRate {
+            scope: key.scope,
+            fleet_id: key.fleet_id.clone(),
+            device_id: key.device_id.clone(),
+            tool_name: key.tool.clone(),
+            samples,
+            failures: failures.len(),
+            failure_rate: if samples == 0 {
+                0.0
+            } else {
+                failures.len() as f64 / samples as f64
+            },
+            threshold,
+            alerting: self.alerting,
+            recent_errors: failures.into_iter().take(ERROR_SAMPLES).cloned().collect(),
+        }
+    }
+}
+
+/// Rolling outcome windows per (device, tool) and (fleet, tool).
+#[derive(Debug, Default)]
+pub struct FailureRateTracker {
+    thresholds: FailureThresholds,
+    windows: Mutex<HashMap<Key, Window>>,
+}
+
+impl FailureRateTracker {
+    pub fn new(thresholds: FailureThresholds) -> Self {
+        Self {
+            thresholds,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn thresholds(&self) -> &FailureThresholds {
+        &self.thresholds
+    }
+
+    /// Count one outcome; returns the windows that just crossed their
+    /// threshold.
+    pub fn observe(
+        &self,
+        fleet_id: &str,
+        device_id: &str,
+        tool: &str,
+        failure: Option<ErrorSample>,
+        now: DateTime<Utc>,
+    ) -> Vec<FailureRate> {
+        let threshold = self.thresholds.rate_for(tool);
+        let mut windows = self.windows.lock().unwrap();
+        let mut crossed = Vec::new();
+        for (scope, device) in [(Scope::Device, Some(device_id)), (Scope::Fleet, None)] {
+            let key = Key {
+                scope,
+                fleet_id: fleet_id.to_string(),
+                device_id: device.map(String::from),
+                tool: tool.to_string(),
+            };
+            let window = windows.entry(key.clone()).or_default();
+            window.outcomes.push_back(Outcome {
+                at: now,
+                failure: failure.clone(),
+            });
+            window.prune(now, self.thresholds.window);
+
+            let rate = window.rate(&key, threshold);
+            let anomalous =
+                rate.samples >= self.thresholds.min_samples && rate.failure_rate >= threshold;
+            if anomalous && !window.alerting {
+                window.alerting = true;
+                crossed.push(FailureRate {
+                    alerting: true,
+                    ..rate
+                });
+            } else if !anomalous {
+                window.alerting = false;
+            }
+        }
+        crossed
+    }
+
+    /// Current rates with outcomes in the window, optionally for one fleet
+    /// or device, highest failure rate first.
+    pub fn rates(
+        &self,
+        fleet_id: Option<&str>,
+        device_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Vec<FailureRate> {
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, w| {
+            w.prune(now, self.thresholds.window);
+            !w.outcomes.is_empty()
+        });
+        let mut rates: Vec<FailureRate> = windows
+            .iter()
+            .filter(|(key, _)| fleet_id.is_none_or(|f| key.fleet_id == f))
+            .filter(|(key, _)| device_id.is_none_or(|d| key.device_id.as_deref() == Some(d)))
+            .map(|(key, w)| w.rate(key, self.thresholds.rate_for(&key.tool)))
+            .collect();
+        rates.sort_by(|a, b| {
+            b.failure_rate
+                .total_cmp(&a.failure_rate)
+                .then_with(|| a.tool_name.cmp(&b.tool_name))
+                .then_with(|| a.device_id.cmp(&b.device_id))
+        });
+        rates
+    }
+}
+
+/// Name a command's outcome is tracked under: the tool, `shell` for shell
+/// commands, `None` for replies.
+pub fn tracked_tool(intent: &ParsedIntent) -> Option<String> {
+    match intent.action {
+        ActionKind::Tool if !intent.tool_name.is_empty() => Some(intent.tool_name.clone()),
+        ActionKind::Shell => Some("shell".into()),
+        _ => None,
+    }
+}
+
+/// Count a final response and alert on any threshold it pushes a window
+/// over: the anomaly is broadcast and raised as an operator-managed alert
+/// ([`crate::alerts`]).
+pub async fn record(
+    state: &AppState,
+    fleet_id: &str,
+    command_id: Uuid,
+    device_id: &str,
+    intent: Option<&ParsedIntent>,
+    status: CommandStatus,
+    error: Option<&str>,
+) {
+    let Some(tool) = intent.and_then(tracked_tool) else {
+        return;
+    };
+    let now = Utc::now();
+    let failure = match status {
+        CommandStatus::Completed => None,
+        CommandStatus::Failed | CommandStatus::Timeout => Some(ErrorSample {
+            command_id,
+            device_id: device_id.to_string(),
+            error: error.map(String::from),
+            at: now,
+        }),
+        _ => return,
+    };
+
+    for rate in state
+        .failure_rates
+        .observe(fleet_id, device_id, &tool, failure, now)
+    {
+        tracing::warn!(
+            scope = rate.scope.as_str(),
+            fleet_id = %rate.fleet_id,
+            device_id = ?rate.device_id,
+            tool = %rate.tool_name,
+            failures = rate.failures,
+            samples = rate.samples,
+            threshold = rate.threshold,
+            "tool failure rate above threshold"
+        );
+        let alert = NewAlert::tool_failure_rate(device_id, &rate);
+        let _ = state.event_tx.send(WsEvent::ToolFailureAnomaly {
+            device_id: device_id.to_string(),
+            fleet_id: rate.fleet_id,
+            scope: rate.scope.as_str().into(),
+            tool_name: rate.tool_name,
+            failures: rate.failures as u64,
+            samples: rate.samples as u64,
+            failure_rate: rate.failure_rate,
+            threshold: rate.threshold,
+            recent_errors: serde_json::to_value(&rate.recent_errors).unwrap_or_default(),
+            detected_at: now,
+        });
+        crate::alerts::raise(state, alert).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(device_id: &str, at: DateTime<Utc>) -> Option<ErrorSample> {
+        Some(ErrorSample {
+            command_id: Uuid::now_v7(),
+            device_id: device_id.into(),
+            error: Some("no response from ECU".into()),
+            at,
+        })
+    }
+
+    #[test]
+    fn alerts_once_per_crossing_and_rearms() {
+        let tracker = FailureRateTracker::new(FailureThresholds {
+            min_samples: 4,
+            ..FailureThresholds::default()
+        });
+        let now = Utc::now();
+        let observe = |fail: bool| {
+            tracker.observe(
+                "fleet-alpha",
+                "rpi-001",
+                "read_dtcs",
+                if fail { failure("rpi-001", now) } else { None },
+                now,
+            )
+        };
+
+        // 2/3 failing, but below min_samples.
+        assert!(observe(true).is_empty());
+        assert!(observe(false).is_empty());
+        assert!(observe(true).is_empty());
+
+        // 3/4 failing: device and fleet windows both cross.
+        let crossed = observe(true);
+        assert_eq!(crossed.len(), 2);
+        assert_eq!(crossed[0].scope, Scope::Device);
+        assert_eq!(crossed[1].scope, Scope::Fleet);
+        assert_eq!((crossed[0].failures, crossed[0].samples), (3, 4));
+        assert_eq!(crossed[0].recent_errors.len(), 3);
+
+        // Still above: no repeat alert.
+        assert!(observe(true).is_empty());
+
+        // 4/8 is still at the threshold; 4/9 drops below and re-arms.
+        for _ in 0..4 {
+            assert!(observe(false).is_empty());
+        }
+        assert!(!tracker.rates(None, Some("rpi-001"), now)[0].alerting);
+        assert_eq!(observe(true).len(), 2);
+    }
+
+    #[test]
+    fn fleet_window_spans_devices_and_old_outcomes_expire() {
+        let tracker = FailureRateTracker::new(FailureThresholds {
+            failure_rate: 0.9,
+            per_tool: vec![("read_dtcs".into(), 0.6)],
+            min_samples: 3,
+            window: Duration::minutes(10),
+        });
+        let start = Utc::now();
+
+        // Old successes fall out of the window.
+        for device in ["rpi-001", "rpi-002", "sbc-010"] {
+            tracker.observe("fleet-alpha", device, "read_dtcs", None, start);
+        }
+        let later = start + Duration::minutes(30);
+        let mut crossed = Vec::new();
+        for device in ["rpi-001", "rpi-002", "sbc-010"] {
+            crossed = tracker.observe(
+                "fleet-alpha",
+                device,
+                "read_dtcs",
+                failure(device, later),
+                later,
+            );
+        }
+        // Each device has a single failure; only the fleet crosses.
+        assert_eq!(crossed.len(), 1);
+        assert_eq!(crossed[0].scope, Scope::Fleet);
+        assert_eq!(crossed[0].threshold, 0.6);
+        assert_eq!(crossed[0].recent_errors[0].device_id, "sbc-010");
+
+        let rates = tracker.rates(Some("fleet-alpha"), None, later);
+        assert_eq!(rates.len(), 4);
+        assert!(rates.iter().all(|r| r.failure_rate == 1.0));
+    }
+
+    #[test]
+    fn replies_are_not_tracked() {
+        let mut intent = ParsedIntent {
+            action: ActionKind::Reply,
+            tool_name: String::new(),
+            tool_args: serde_json::Value::Null,
+            confidence: 1.0,
+            steps: Vec::new(),
+        };
+        assert_eq!(tracked_tool(&intent), None);
+        intent.action = ActionKind::Shell;
+        intent.tool_name = "uptime".into();
+        assert_eq!(tracked_tool(&intent).as_deref(), Some("shell"));
+    }
+}
@@ -0,0 +1,618 @@
+//! Files pushed to devices and fetched from them over MQTT.
+//!
+//! The cloud drives the chunked protocol of [`zc_protocol::transfer`]: it
+//! publishes the offer, answers each push ack with the chunk the device
+//! asked for and assembles fetched chunks in order. A transfer that stops
+//! making progress (the device went offline, a chunk was lost) is offered
+//! again on the device's next heartbeat after [`STALL_SECS`]; the device
+//! resumes a push from the bytes it already staged and the cloud resumes a
+//! fetch from the bytes it already holds.
+//!
+//! Transfers live in memory in both modes: the data is only kept until it
+//! is downloaded, and a restart of the API fails nothing on the device —
+//! a new request starts over.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use zc_protocol::encoding::{self, EncodingError};
+use zc_protocol::topics;
+use zc_protocol::transfer::{
+    MAX_CHUNK_BYTES, TransferAck, TransferChunk, TransferComplete, TransferDirection,
+    TransferOffer, TransferOutcome,
+};
+
+use crate::state::AppState;
+
+/// Largest file pushed or fetched.
+pub const MAX_FILE_BYTES: usize = 8 * 1024 * 1024;
+/// Request body limit of a push: the base64 content plus the JSON around it.
+pub const MAX_PUSH_BODY_BYTES: usize = MAX_FILE_BYTES / 3 * 4 + 64 * 1024;
+/// A transfer without progress for this long is offered again on the
+/// device's next heartbeat.
+pub const STALL_SECS: i64 = 30;
+
+/// Where a transfer stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    /// Offered, not acknowledged yet.
+    Pending,
+    InProgress,
+    Completed,
+    Failed,
+}
+
+impl TransferStatus {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+/// A push or fetch and its progress.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileTransfer {
+    pub id: Uuid,
+    pub device_id: String,
+    pub fleet_id: String,
+    pub direction: TransferDirection,
+    /// Absolute path on the device.
+    pub path: String,
+    /// File size; known for a fetch once the device acknowledged it.
+    pub size: Option<u64>,
+    /// Lowercase hex SHA-256 of the file.
+    pub sha256: Option<String>,
+    /// Bytes the device (push) or the cloud (fetch) holds so far.
+    pub transferred: u64,
+    pub status: TransferStatus,
+    pub error: Option<String>,
+    pub requested_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Push: the file. Fetch: the bytes received so far.
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl FileTransfer {
+    /// A new push of `data` to `path`.
+    pub fn push(
+        device_id: &str,
+        fleet_id: &str,
+        path: &str,
+        data: Vec<u8>,
+        requested_by: &str,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::now_v7(),
+            device_id: device_id.into(),
+            fleet_id: fleet_id.into(),
+            direction: TransferDirection::Push,
+            path: path.into(),
+            size: Some(data.len() as u64),
+            sha256: Some(sha256_hex(&data)),
+            transferred: 0,
+            status: TransferStatus::Pending,
+            error: None,
+            requested_by: requested_by.into(),
+            created_at: now,
+            updated_at: now,
+            data,
+        }
+    }
+
+    /// A new fetch of `path`.
+    pub fn fetch(device_id: &str, fleet_id: &str, path: &str, requested_by: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::now_v7(),
+            device_id: device_id.into(),
+            fleet_id: fleet_id.into(),
+            direction: TransferDirection::Fetch,
+            path: path.into(),
+            size: None,
+            sha256: None,
+            transferred: 0,
+            status: TransferStatus::Pending,
+            error: None,
+            requested_by: requested_by.into(),
+            created_at: now,
+            updated_at: now,
+            data: Vec::new(),
+        }
+    }
+
+    /// The offer that starts or resumes this transfer.
+    pub fn offer(&self) -> TransferOffer {
+        TransferOffer {
+            transfer_id: self.id,
+            direction: self.direction,
+            path: self.path.clone(),
+            size: self
+                .size
+                .filter(|_| self.direction == TransferDirection::Push),
+            sha256: self
+                .sha256
+                .clone()
+                .filter(|_| self.direction == TransferDirection::Push),
+            offset: match self.direction {
+                TransferDirection::Push => 0,
+                TransferDirection::Fetch => self.data.len() as u64,
+            },
+            chunk_size: MAX_CHUNK_BYTES,
+            offered_at: Utc::now(),
+        }
+    }
+
+    fn fail(&mut self, error: impl Into<String>) {
+        self.status = TransferStatus::Failed;
+        self.error = Some(error.into());
+        self.data = Vec::new();
+        self.updated_at = Utc::now();
+    }
+}
+
+/// Register a transfer and offer it to the device.
+pub async fn start(state: &AppState, transfer: FileTransfer) -> FileTransfer {
+    state
+        .file_transfers
+        .write()
+        .await
+        .insert(transfer.id, transfer.clone());
+    tracing::info!(
+        transfer_id = %transfer.id,
+        device_id = %transfer.device_id,
+        direction = transfer.direction.as_str(),
+        path = %transfer.path,
+        size = ?transfer.size,
+        "file transfer requested"
+    );
+    publish(
+        state,
+        &transfer,
+        topics::transfer_offer(&transfer.fleet_id, &transfer.device_id),
+        &transfer.offer(),
+    )
+    .await;
+    transfer
+}
+
+/// One transfer.
+pub async fn get(state: &AppState, id: Uuid) -> Option<FileTransfer> {
+    state.file_transfers.read().await.get(&id).cloned()
+}
+
+/// Transfers of a device, newest first.
+pub async fn list(state: &AppState, device_id: &str) -> Vec<FileTransfer> {
+    let mut transfers: Vec<FileTransfer> = state
+        .file_transfers
+        .read()
+        .await
+        .values()
+        .filter(|t| t.device_id == device_id)
+        .cloned()
+        .collect();
+    transfers.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    transfers
+}
+
+/// Handle a device ack: send the next push chunk, or note the size of a
+/// fetch. Acks of finished or unknown transfers are ignored.
+pub async fn ack_received(state: &AppState, payload: &[u8]) -> Result<(), EncodingError> {
+    let ack: TransferAck = encoding::decode(payload)?;
+    let chunk = {
+        let mut transfers = state.file_transfers.write().await;
+        let Some(transfer) = active(&mut transfers, ack.transfer_id, &ack.device_id) else {
+            return Ok(());
+        };
+        transfer.status = TransferStatus::InProgress;
+        transfer.updated_at = Utc::now();
+        match transfer.direction {
+            TransferDirection::Push => {
+                let start = ack.next_offset as usize;
+                if start >= transfer.data.len() {
+                    // The device has everything and is about to complete.
+                    transfer.transferred = transfer.data.len() as u64;
+                    return Ok(());
+                }
+                transfer.transferred = ack.next_offset;
+                let end = (start + MAX_CHUNK_BYTES as usize).min(transfer.data.len());
+                Some((
+                    TransferChunk::new(transfer.id, ack.next_offset, &transfer.data[start..end]),
+                    topics::transfer_chunk(&transfer.fleet_id, &transfer.device_id),
+                    transfer.clone(),
+                ))
+            }
+            TransferDirection::Fetch => {
+                match ack.size {
+                    Some(size) if size as usize > MAX_FILE_BYTES => {
+                        transfer.fail(format!(
+                            "file is {size} bytes, more than the {MAX_FILE_BYTES} allowed"
+                        ));
+                    }
+                    size => {
+                        transfer.size = size;
+                        // The device restarts from what it was offered;
+                        // drop anything past that.
+                        transfer.data.truncate(ack.next_offset as usize);
+                        transfer.transferred = transfer.data.len() as u64;
+                    }
+                }
+                None
+            }
+        }
+    };
+    if let Some((chunk, topic, transfer)) = chunk {
+        publish(state, &transfer, topic, &chunk).await;
+    }
+    Ok(())
+}
+
+/// Append an uploaded chunk of a fetch. Chunks that are not the next one
+/// (duplicates, or past a lost chunk) are dropped; the fetch is offered
+/// again from the gap if it completes short.
+pub async fn upload_received(
+    state: &AppState,
+    device_id: &str,
+    payload: &[u8],
+) -> Result<(), EncodingError> {
+    let chunk: TransferChunk = encoding::decode(payload)?;
+    let mut transfers = state.file_transfers.write().await;
+    let Some(transfer) = active(&mut transfers, chunk.transfer_id, device_id) else {
+        return Ok(());
+    };
+    if transfer.direction != TransferDirection::Fetch || chunk.offset != transfer.data.len() as u64
+    {
+        tracing::debug!(transfer_id = %chunk.transfer_id, offset = chunk.offset, "dropping out-of-order upload chunk");
+        return Ok(());
+    }
+    let Ok(bytes) = chunk.bytes() else {
+        tracing::warn!(transfer_id = %chunk.transfer_id, "upload chunk is not valid base64");
+        return Ok(());
+    };
+    if transfer.data.len() + bytes.len() > MAX_FILE_BYTES {
+        transfer.fail(format!("upload exceeds {MAX_FILE_BYTES} bytes"));
+        return Ok(());
+    }
+    transfer.data.extend_from_slice(&bytes);
+    transfer.transferred = transfer.data.len() as u64;
+    transfer.status = TransferStatus::InProgress;
+    transfer.updated_at = Utc::now();
+    Ok(())
+}
+
+/// Finish a transfer. A fetch is checked against the size and SHA-256 the
+/// device reports; one that is missing chunks is offered again from the
+/// first missing byte.
+pub async fn complete_received(state: &AppState, payload: &[u8]) -> Result<(), EncodingError> {
+    let complete: TransferComplete = encoding::decode(payload)?;
+    let resume = {
+        let mut transfers = state.file_transfers.write().await;
+        let Some(transfer) = active(&mut transfers, complete.transfer_id, &complete.device_id)
+        else {
+            return Ok(());
+        };
+        match (complete.outcome, transfer.direction) {
+            (TransferOutcome::Failed, _) => {
+                transfer.fail(
+                    complete
+                        .error
+                        .unwrap_or_else(|| "failed on the device".into()),
+                );
+                None
+            }
+            (TransferOutcome::Completed, TransferDirection::Push) => {
+                transfer.status = TransferStatus::Completed;
+                transfer.transferred = transfer.data.len() as u64;
+                transfer.data = Vec::new();
+                transfer.updated_at = Utc::now();
+                None
+            }
+            (TransferOutcome::Completed, TransferDirection::Fetch) => {
+                transfer.size = Some(complete.size);
+                transfer.updated_at = Utc::now();
+                if (transfer.data.len() as u64) < complete.size {
+                    Some(transfer.clone())
+                } else if complete.sha256.as_deref() != Some(sha256_hex(&transfer.data).as_str()) {
+                    transfer.fail("received data does not match the device's checksum");
+                    None
+                } else {
+                    transfer.status = TransferStatus::Completed;
+                    transfer.sha256 = complete.sha256;
+                    None
+                }
+            }
+        }
+    };
+    match resume {
+        Some(transfer) => {
+            tracing::info!(transfer_id = %transfer.id, received = transfer.data.len(), "fetch incomplete, resuming");
+            publish(
+                state,
+                &transfer,
+                topics::transfer_offer(&transfer.fleet_id, &transfer.device_id),
+                &transfer.offer(),
+            )
+            .await;
+        }
+        None => {
+            if let Some(transfer) = get(state, complete.transfer_id).await {
+                tracing::info!(
+                    transfer_id = %transfer.id,
+                    device_id = %transfer.device_id,
+                    status = ?transfer.status,
+                    error = ?transfer.error,
+                    "file transfer finished"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Offer again the transfers of `device_id` that made no progress for
+/// [`STALL_SECS`]. Called when the device sends a heartbeat.
+pub async fn resume_stalled(state: &AppState, device_id: &str) {
+    let stalled: Vec<FileTransfer> = {
+        let mut transfers = state.file_transfers.write().await;
+        let cutoff = Utc::now() - Duration::seconds(STALL_SECS);
+        transfers
+            .values_mut()
+            .filter(|t| t.device_id == device_id && !t.status.is_terminal())
+            .filter(|t| t.updated_at < cutoff)
+            .map(|t| {
+                t.updated_at = Utc::now();
+                t.clone()
+            })
+            .collect()
+    };
+    for transfer in stalled {
+        tracing::info!(transfer_id = %transfer.id, device_id, "resuming stalled file transfer");
+        publish(
+            state,
+            &transfer,
+            topics::transfer_offer(&transfer.fleet_id, &transfer.device_id),
+            &transfer.offer(),
+        )
+        .await;
+    }
+}
+
+/// The unfinished transfer `id` of `device_id`.
+fn active<'a>(
+    transfers: &'a mut HashMap<Uuid, FileTransfer>,
+    id: Uuid,
+    device_id: &str,
+) -> Option<&'a mut FileTransfer> {
+    match transfers.get_mut(&id) {
+        Some(t) if t.device_id == device_id && !t.status.is_terminal() => Some(t),
+        _ => {
+            tracing::debug!(transfer_id = %id, device_id, "ignoring message for an unknown or finished transfer");
+            None
+        }
+    }
+}
+
+/// Publish a transfer message in the encoding the device reads. Delivery
+/// failures are left to the stall check.
+async fn publish<T: Serialize>(
+    state: &AppState,
+    transfer: &FileTransfer,
+    topic: String,
+    message: &T,
+) {
+    let Some(mqtt) = &state.mqtt else {
+        tracing::debug!(transfer_id = %transfer.id, "MQTT not configured, transfer waits");
+        return;
+    };
+    let encoding = crate::command_queue::device_encoding(state, &transfer.device_id).await;
+    let payload = match encoding::encode(message, encoding) {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to serialize transfer message");
+            return;
+        }
+    };
+    if let Err(e) = mqtt
+        .publish(&topic, &payload, rumqttc::QoS::AtLeastOnce)
+        .await
+    {
+        tracing::warn!(transfer_id = %transfer.id, error = %e, "failed to publish transfer message");
+    }
+}
+
+/// Lowercase hex SHA-256 of `bytes`.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use zc_mqtt_channel::MockChannel;
+
+    fn state() -> (AppState, Arc<MockChannel>) {
+        let mqtt = Arc::new(MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        (state, mqtt)
+    }
+
+    fn ack(id: Uuid, next_offset: u64, size: Option<u64>) -> Vec<u8> {
+        serde_json::to_vec(&TransferAck {
+            transfer_id: id,
+            device_id: "rpi-001".into(),
+            next_offset,
+            size,
+        })
+        .unwrap()
+    }
+
+    fn completed(id: Uuid, data: &[u8]) -> Vec<u8> {
+        serde_json::to_vec(&TransferComplete {
+            transfer_id: id,
+            device_id: "rpi-001".into(),
+            outcome: TransferOutcome::Completed,
+            size: data.len() as u64,
+            sha256: Some(sha256_hex(data)),
+            error: None,
+            timestamp: Utc::now(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn push_sends_the_chunk_the_device_asks_for() {
+        let (state, mqtt) = state();
+        let data = vec![7u8; MAX_CHUNK_BYTES as usize + 10];
+        let transfer = start(
+            &state,
+            FileTransfer::push(
+                "rpi-001",
+                "fleet-alpha",
+                "/var/lib/zeroclaw/files/a.dbc",
+                data,
+                "alice",
+            ),
+        )
+        .await;
+        let offers = mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/offer");
+        let offer: TransferOffer = encoding::decode(&offers[0].payload).unwrap();
+        assert_eq!(offer.size, Some(MAX_CHUNK_BYTES as u64 + 10));
+
+        // Resumed after a reconnect: the device already holds the first chunk.
+        ack_received(&state, &ack(transfer.id, MAX_CHUNK_BYTES as u64, None))
+            .await
+            .unwrap();
+        let chunks = mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/chunk");
+        let chunk: TransferChunk = encoding::decode(&chunks[0].payload).unwrap();
+        assert_eq!(chunk.offset, MAX_CHUNK_BYTES as u64);
+        assert_eq!(chunk.bytes().unwrap().len(), 10);
+
+        complete_received(&state, &completed(transfer.id, b""))
+            .await
+            .unwrap();
+        let done = get(&state, transfer.id).await.unwrap();
+        assert_eq!(done.status, TransferStatus::Completed);
+        assert!(done.data.is_empty());
+
+        // Late acks of a finished transfer send nothing.
+        ack_received(&state, &ack(transfer.id, 0, None))
+            .await
+            .unwrap();
+        assert_eq!(
+            mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/chunk")
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_resumes_after_a_lost_chunk_and_verifies() {
+        let (state, mqtt) = state();
+        let transfer = start(
+            &state,
+            FileTransfer::fetch("rpi-001", "fleet-alpha", "/var/crash/core.1", "alice"),
+        )
+        .await;
+        let upload = |offset: u64, bytes: &[u8]| {
+            serde_json::to_vec(&TransferChunk::new(transfer.id, offset, bytes)).unwrap()
+        };
+        ack_received(&state, &ack(transfer.id, 0, Some(8)))
+            .await
+            .unwrap();
+        upload_received(&state, "rpi-001", &upload(0, b"abcd"))
+            .await
+            .unwrap();
+        // The chunk at 4 is lost; the one after it is dropped.
+        upload_received(&state, "rpi-001", &upload(6, b"gh"))
+            .await
+            .unwrap();
+        complete_received(&state, &completed(transfer.id, b"abcdefgh"))
+            .await
+            .unwrap();
+
+        let offers = mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/offer");
+        assert_eq!(offers.len(), 2);
+        let resumed: TransferOffer = encoding::decode(&offers[1].payload).unwrap();
+        assert_eq!(resumed.offset, 4);
+
+        ack_received(&state, &ack(transfer.id, 4, Some(8)))
+            .await
+            .unwrap();
+        upload_received(&state, "rpi-001", &upload(4, b"efgh"))
+            .await
+            .unwrap();
+        complete_received(&state, &completed(transfer.id, b"abcdefgh"))
+            .await
+            .unwrap();
+        let done = get(&state, transfer.id).await.unwrap();
+        assert_eq!(done.status, TransferStatus::Completed);
+        assert_eq!(done.data, b"abcdefgh");
+
+        // A corrupted upload fails the checksum.
+        let bad = start(
+            &state,
+            FileTransfer::fetch("rpi-001", "fleet-alpha", "/var/crash/core.2", "alice"),
+        )
+        .await;
+        ack_received(&state, &ack(bad.id, 0, Some(4)))
+            .await
+            .unwrap();
+        upload_received(
+            &state,
+            "rpi-001",
+            &serde_json::to_vec(&TransferChunk::new(bad.id, 0, b"xxxx")).unwrap(),
+        )
+        .await
+        .unwrap();
+        complete_received(&state, &completed(bad.id, b"abcd"))
+            .await
+            .unwrap();
+        assert_eq!(
+            get(&state, bad.id).await.unwrap().status,
+            TransferStatus::Failed
+        );
+    }
+
+    #[tokio::test]
+    async fn stalled_transfers_are_offered_again() {
+        let (state, mqtt) = state();
+        let transfer = start(
+            &state,
+            FileTransfer::fetch("rpi-001", "fleet-alpha", "/var/crash/core.1", "alice"),
+        )
+        .await;
+        resume_stalled(&state, "rpi-001").await;
+        assert_eq!(
+            mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/offer")
+                .len(),
+            1
+        );
+
+        state
+            .file_transfers
+            .write()
+            .await
+            .get_mut(&transfer.id)
+            .unwrap()
+            .updated_at -= Duration::seconds(STALL_SECS + 1);
+        resume_stalled(&state, "rpi-001").await;
+        resume_stalled(&state, "rpi-002").await;
+        assert_eq!(
+            mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/offer")
+                .len(),
+            2
+        );
+    }
+}
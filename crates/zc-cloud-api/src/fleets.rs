@@ -0,0 +1,366 @@
+//! Fleets as first-class resources, and their API tokens.
+//!
+//! Devices name their fleet in `metadata.fleet`. Registering fleets is
+//! optional: while none is registered any fleet name is accepted, as
+//! before; once one is, devices can only be provisioned into registered
+//! fleets. Fleets are created and deleted by super-admins, and a fleet is
+//! only deleted once it has no devices.
+//!
+//! A fleet token (`zcf_...`) is a bearer token for automation scoped to
+//! one fleet: it authenticates as a [`Principal`] with the token's role
+//! (at most `admin`) and the fleet as its only tenant, so it is held to
+//! the same fleet boundaries as an OIDC user with that tenant claim. Only
+//! the token's SHA-256 is stored; the secret is returned once, when the
+//! token is issued. Tokens are checked whenever auth is on.
+//!
+//! Fleets and tokens live in the active backend's
+//! [`FleetStore`](crate::store::FleetStore).
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use ring::rand::SecureRandom;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::auth::{Principal, Role};
+use crate::error::{ApiError, ApiResult};
+use crate::file_transfers::sha256_hex;
+use crate::state::AppState;
+
+/// Prefix of fleet token secrets, to tell them from OIDC tokens.
+pub const TOKEN_PREFIX: &str = "zcf_";
+/// Longest accepted fleet ID.
+pub const MAX_FLEET_ID_LEN: usize = 64;
+
+/// A registered fleet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fleet {
+    /// The name devices carry in `metadata.fleet` and MQTT topics use.
+    pub fleet_id: String,
+    /// Display name.
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An API token scoped to one fleet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FleetToken {
+    pub id: Uuid,
+    pub fleet_id: String,
+    /// What the token is for (e.g. `ci-pipeline`); recorded as the actor.
+    pub name: String,
+    pub role: Role,
+    /// Lowercase hex SHA-256 of the secret.
+    #[serde(skip)]
+    pub sha256: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    /// The token is refused from this time on; never when `None`.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl FleetToken {
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    /// The principal requests carrying this token act as.
+    pub fn principal(&self) -> Principal {
+        Principal {
+            subject: format!("fleet-token:{}", self.id),
+            name: format!("token:{}", self.name),
+            role: self.role,
+            tenants: Some(vec![self.fleet_id.clone()]),
+        }
+    }
+}
+
+/// A newly issued token with its secret, which is not stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedToken {
+    #[serde(flatten)]
+    pub token: FleetToken,
+    /// Bearer secret; shown only in this response.
+    pub secret: String,
+}
+
+/// Fleet IDs appear in MQTT topics: 1 to [`MAX_FLEET_ID_LEN`] letters,
+/// digits, `-` or `_`.
+pub fn validate_fleet_id(fleet_id: &str) -> Result<(), String> {
+    if fleet_id.is_empty() || fleet_id.len() > MAX_FLEET_ID_LEN {
+        return Err(format!(
+            "fleet_id must be 1 to {MAX_FLEET_ID_LEN} characters"
+        ));
+    }
+    if !fleet_id
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        return Err("fleet_id may only contain letters, digits, '-' and '_'".into());
+    }
+    Ok(())
+}
+
+/// Lowercase hex SHA-256 of a bearer secret, as tokens are stored.
+pub fn token_sha256(secret: &str) -> String {
+    sha256_hex(secret.as_bytes())
+}
+
+fn not_found(fleet_id: &str) -> ApiError {
+    ApiError::NotFound(format!("fleet '{fleet_id}' not found"))
+}
+
+/// Store a new fleet; 409 if its ID is already registered.
+pub async fn create(state: &AppState, fleet: &Fleet) -> ApiResult<()> {
+    if !state.store.insert_fleet(fleet).await? {
+        return Err(ApiError::Conflict(format!(
+            "fleet '{}' already exists",
+            fleet.fleet_id
+        )));
+    }
+    tracing::info!(fleet_id = %fleet.fleet_id, name = %fleet.name, "fleet registered");
+    Ok(())
+}
+
+/// Registered fleets ordered by ID, limited to `tenants` when given.
+pub async fn list(state: &AppState, tenants: Option<&[String]>) -> ApiResult<Vec<Fleet>> {
+    state.store.list_fleets(tenants).await
+}
+
+/// A fleet by ID.
+pub async fn get(state: &AppState, fleet_id: &str) -> ApiResult<Fleet> {
+    state
+        .store
+        .fleet(fleet_id)
+        .await?
+        .ok_or_else(|| not_found(fleet_id))
+}
+
+/// Refuse provisioning into `fleet_id` when fleets are registered and it
+/// is not one of them.
+pub async fn ensure_registered(state: &AppState, fleet_id: &str) -> ApiResult<()> {
+    if !state.store.any_fleet().await? || state.store.fleet(fleet_id).await?.is_some() {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!(
+            "fleet '{fleet_id}' is not registered"
+        )))
+    }
+}
+
+/// Delete a fleet and its tokens; 409 while devices remain in it.
+pub async fn delete(state: &AppState, fleet_id: &str) -> ApiResult<()> {
+    let devices = state.store.fleet_devices(fleet_id).await?.len();
+    if devices > 0 {
+        return Err(ApiError::Conflict(format!(
+            "fleet '{fleet_id}' still has {devices} device(s)"
+        )));
+    }
+
+    if !state.store.delete_fleet(fleet_id).await? {
+        return Err(not_found(fleet_id));
+    }
+    tracing::info!(fleet_id, "fleet deleted");
+    Ok(())
+}
+
+/// Issue a token for a registered fleet. Tokens carry at most `admin`.
+pub async fn issue_token(
+    state: &AppState,
+    fleet_id: &str,
+    name: String,
+    role: Role,
+    expires_at: Option<DateTime<Utc>>,
+    created_by: String,
+) -> ApiResult<IssuedToken> {
+    if role > Role::Admin {
+        return Err(ApiError::BadRequest(format!(
+            "fleet tokens cannot carry the {} role",
+            role.as_str()
+        )));
+    }
+    get(state, fleet_id).await?;
+
+    let mut bytes = [0u8; 32];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| ApiError::Internal("no randomness for token".into()))?;
+    let secret = format!("{TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+    let token = FleetToken {
+        id: Uuid::now_v7(),
+        fleet_id: fleet_id.to_string(),
+        name,
+        role,
+        sha256: token_sha256(&secret),
+        created_by,
+        created_at: Utc::now(),
+        expires_at,
+    };
+
+    state.store.insert_fleet_token(&token).await?;
+    tracing::info!(
+        fleet_id,
+        token_id = %token.id,
+        role = token.role.as_str(),
+        "fleet token issued"
+    );
+    Ok(IssuedToken { token, secret })
+}
+
+/// Tokens of a fleet, oldest first.
+pub async fn tokens(state: &AppState, fleet_id: &str) -> ApiResult<Vec<FleetToken>> {
+    get(state, fleet_id).await?;
+    state.store.list_fleet_tokens(fleet_id).await
+}
+
+/// Revoke a fleet's token; it is refused from the next request on.
+pub async fn revoke_token(state: &AppState, fleet_id: &str, id: Uuid) -> ApiResult<()> {
+    if !state.store.delete_fleet_token(fleet_id, id).await? {
+        return Err(ApiError::NotFound(format!(
+            "token {id} not found in fleet '{fleet_id}'"
+        )));
+    }
+    tracing::info!(fleet_id, token_id = %id, "fleet token revoked");
+    Ok(())
+}
+
+/// The unexpired token whose secret is `secret`, if any.
+pub async fn authenticate(state: &AppState, secret: &str) -> ApiResult<Option<FleetToken>> {
+    let digest = token_sha256(secret);
+    let token = state.store.fleet_token_by_sha256(&digest).await?;
+    Ok(token.filter(|t| !t.expired(Utc::now())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fleet(id: &str) -> Fleet {
+        Fleet {
+            fleet_id: id.into(),
+            name: id.into(),
+            description: None,
+            created_by: "root".into(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn fleet_ids_must_be_topic_safe() {
+        assert!(validate_fleet_id("fleet-alpha_2").is_ok());
+        assert!(validate_fleet_id("").is_err());
+        assert!(validate_fleet_id("a/b").is_err());
+        assert!(validate_fleet_id("fleet+").is_err());
+        assert!(validate_fleet_id(&"x".repeat(MAX_FLEET_ID_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn tokens_authenticate_until_revoked_or_expired() {
+        let state = AppState::with_sample_data();
+        create(&state, &fleet("fleet-alpha")).await.unwrap();
+
+        let issued = issue_token(
+            &state,
+            "fleet-alpha",
+            "ci".into(),
+            Role::Operator,
+            None,
+            "root".into(),
+        )
+        .await
+        .unwrap();
+        assert!(issued.secret.starts_with(TOKEN_PREFIX));
+        let found = authenticate(&state, &issued.secret).await.unwrap().unwrap();
+        let principal = found.principal();
+        assert_eq!(principal.role, Role::Operator);
+        assert_eq!(principal.tenants, Some(vec!["fleet-alpha".to_string()]));
+        assert!(authenticate(&state, "zcf_guess").await.unwrap().is_none());
+
+        let expired = issue_token(
+            &state,
+            "fleet-alpha",
+            "old".into(),
+            Role::Viewer,
+            Some(Utc::now() - chrono::Duration::minutes(1)),
+            "root".into(),
+        )
+        .await
+        .unwrap();
+        assert!(
+            authenticate(&state, &expired.secret)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        revoke_token(&state, "fleet-alpha", issued.token.id)
+            .await
+            .unwrap();
+        assert!(
+            authenticate(&state, &issued.secret)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let err = issue_token(
+            &state,
+            "fleet-alpha",
+            "root".into(),
+            Role::SuperAdmin,
+            None,
+            "root".into(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ApiError::BadRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn registry_gates_provisioning_and_deletion() {
+        let state = AppState::with_sample_data();
+        // Nothing registered: any fleet is accepted.
+        ensure_registered(&state, "fleet-gamma").await.unwrap();
+
+        create(&state, &fleet("fleet-alpha")).await.unwrap();
+        assert!(matches!(
+            create(&state, &fleet("fleet-alpha")).await,
+            Err(ApiError::Conflict(_))
+        ));
+        ensure_registered(&state, "fleet-alpha").await.unwrap();
+        assert!(ensure_registered(&state, "fleet-gamma").await.is_err());
+
+        // rpi-001 and rpi-002 are still in fleet-alpha.
+        assert!(matches!(
+            delete(&state, "fleet-alpha").await,
+            Err(ApiError::Conflict(_))
+        ));
+        create(&state, &fleet("fleet-empty")).await.unwrap();
+        issue_token(
+            &state,
+            "fleet-empty",
+            "ci".into(),
+            Role::Viewer,
+            None,
+            "root".into(),
+        )
+        .await
+        .unwrap();
+        delete(&state, "fleet-empty").await.unwrap();
+        assert!(
+            state
+                .store
+                .list_fleet_tokens("fleet-empty")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            get(&state, "fleet-empty").await,
+            Err(ApiError::NotFound(_))
+        ));
+    }
+}
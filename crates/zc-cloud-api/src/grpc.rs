@@ -0,0 +1,453 @@
+//! gRPC API (`zeroclaw.v1.Fleet`).
+//!
+//! A typed alternative to the JSON API for backend integrators, served on
+//! `GRPC_PORT` next to the REST port. The contract lives in
+//! `proto/zeroclaw/v1/fleet.proto`:
+//!
+//! - `ListDevices` pages the device list like `GET /api/v1/devices`.
+//! - `SendCommand` runs the `POST /api/v1/commands` handler, so aliases,
+//!   structured-only fleets, pre-flight checks, rate limits and auditing all
+//!   apply unchanged.
+//! - `StreamEvents` is the WebSocket feed as a server stream, filtered like
+//!   a WebSocket `subscribe` message. Each event carries its JSON frame.
+//!
+//! With OIDC on, calls carry the same bearer token as REST requests in the
+//! `authorization` metadata; `ListDevices` and `StreamEvents` need the
+//! viewer role and `SendCommand` the operator role.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::{Request, Response, Status};
+use zc_protocol::commands::CommandEnvelope;
+use zc_protocol::device::{DeviceInfo, DeviceStatus};
+
+use crate::auth::{AuthError, Principal, Role};
+use crate::db::devices::DeviceFilter;
+use crate::device_status::status_name;
+use crate::error::ApiError;
+use crate::events::WsEvent;
+use crate::routes::commands::SendCommandRequest;
+use crate::routes::devices::hardware_type_name;
+use crate::routes::{pagination, ws};
+use crate::state::AppState;
+
+/// Types generated from `proto/zeroclaw/v1/fleet.proto`.
+pub mod proto {
+    tonic::include_proto!("zeroclaw.v1");
+}
+
+use proto::fleet_server::{Fleet, FleetServer};
+
+/// Serve the gRPC API on `addr` until the process exits.
+pub async fn serve(state: AppState, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(FleetServer::new(FleetService::new(state)))
+        .serve(addr)
+        .await
+}
+
+/// The `Fleet` service over the application state.
+#[derive(Clone)]
+pub struct FleetService {
+    state: AppState,
+}
+
+impl FleetService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Authenticate the caller when auth is on and require `needed`.
+    async fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        needed: Role,
+    ) -> Result<Option<Principal>, Status> {
+        if !crate::auth::enabled(&self.state) {
+            return Ok(None);
+        }
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or_else(|| Status::unauthenticated(AuthError::MissingToken.to_string()))?;
+        let principal = crate::auth::authenticate(&self.state, token)
+            .await
+            .map_err(|e| match e {
+                AuthError::Forbidden(message) => Status::permission_denied(message),
+                AuthError::KeysUnavailable(_) | AuthError::StoreUnavailable(_) => {
+                    Status::unavailable(e.to_string())
+                }
+                _ => Status::unauthenticated(e.to_string()),
+            })?;
+        if principal.role < needed {
+            return Err(Status::permission_denied(format!(
+                "{} role required",
+                needed.as_str()
+            )));
+        }
+        Ok(Some(principal))
+    }
+}
+
+impl From<ApiError> for Status {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::NotFound(m) => Status::not_found(m),
+            ApiError::BadRequest(m) | ApiError::PayloadTooLarge(m) => Status::invalid_argument(m),
+            ApiError::Conflict(m) => Status::already_exists(m),
+            ApiError::Forbidden(m) => Status::permission_denied(m),
+            ApiError::TooManyRequests {
+                message,
+                retry_after_secs,
+            } => Status::resource_exhausted(format!("{message} (retry after {retry_after_secs}s)")),
+            ApiError::PreconditionFailed { message, details } => {
+                Status::failed_precondition(format!("{message}: {details}"))
+            }
+            ApiError::Internal(m) => Status::internal(m),
+        }
+    }
+}
+
+fn timestamp(t: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn device(d: DeviceInfo) -> proto::Device {
+    proto::Device {
+        fleet_id: d
+            .metadata
+            .get("fleet")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        status: status_name(d.status).into(),
+        hardware_type: hardware_type_name(&d.hardware_type),
+        last_heartbeat: d.last_heartbeat.map(timestamp),
+        certificate_expires_at: d.certificate_expires_at.map(timestamp),
+        device_id: d.device_id,
+        vin: d.vin,
+    }
+}
+
+fn command(envelope: CommandEnvelope) -> proto::Command {
+    let intent = envelope.parsed_intent;
+    proto::Command {
+        id: envelope.id.to_string(),
+        device_id: envelope.device_id,
+        fleet_id: envelope.fleet_id,
+        natural_language: envelope.natural_language,
+        initiated_by: envelope.initiated_by,
+        created_at: Some(timestamp(envelope.created_at)),
+        timeout_secs: envelope.timeout_secs,
+        tool_name: intent.as_ref().map(|i| i.tool_name.clone()),
+        tool_args_json: intent.map(|i| i.tool_args.to_string()),
+        conversation_id: envelope.conversation_id.map(|id| id.to_string()),
+    }
+}
+
+fn event(e: &WsEvent) -> proto::Event {
+    proto::Event {
+        r#type: e.event_type().into(),
+        device_id: e.device_id().into(),
+        schema_version: crate::events::EVENT_SCHEMA_VERSION,
+        data_json: e.to_frame().to_string(),
+    }
+}
+
+#[tonic::async_trait]
+impl Fleet for FleetService {
+    async fn list_devices(
+        &self,
+        request: Request<proto::ListDevicesRequest>,
+    ) -> Result<Response<proto::ListDevicesResponse>, Status> {
+        let principal = self.authorize(&request, Role::Viewer).await?;
+        let req = request.into_inner();
+        let status = req
+            .status
+            .map(|s| {
+                serde_json::from_value::<DeviceStatus>(serde_json::Value::String(s.clone()))
+                    .map_err(|_| Status::invalid_argument(format!("unknown status '{s}'")))
+            })
+            .transpose()?;
+        let since = req
+            .since
+            .map(|t| {
+                DateTime::from_timestamp(t.seconds, t.nanos.try_into().unwrap_or(0))
+                    .ok_or_else(|| Status::invalid_argument("since is out of range"))
+            })
+            .transpose()?;
+        let tags = crate::device_tags::parse_selectors(&req.tags.join(","))
+            .map_err(Status::invalid_argument)?;
+        let filter = DeviceFilter {
+            status: status.map(|s| status_name(s).to_string()),
+            since,
+            cert_expires_before: None,
+            tags,
+            fleets: principal.and_then(|p| p.tenants),
+            limit: pagination::limit(req.limit),
+            offset: req.offset,
+        };
+
+        let (devices, total) = self.state.store.list_devices(&filter).await?;
+        Ok(Response::new(proto::ListDevicesResponse {
+            devices: devices.into_iter().map(device).collect(),
+            total,
+        }))
+    }
+
+    async fn send_command(
+        &self,
+        request: Request<proto::SendCommandRequest>,
+    ) -> Result<Response<proto::Command>, Status> {
+        let principal = self.authorize(&request, Role::Operator).await?;
+        let req = request.into_inner();
+        let tool_args = req
+            .tool_args_json
+            .map(|json| {
+                serde_json::from_str(&json).map_err(|e| {
+                    Status::invalid_argument(format!("tool_args_json is not JSON: {e}"))
+                })
+            })
+            .transpose()?;
+        let conversation_id = req
+            .conversation_id
+            .map(|id| {
+                id.parse().map_err(|_| {
+                    Status::invalid_argument(format!("conversation_id '{id}' is not a UUID"))
+                })
+            })
+            .transpose()?;
+
+        let Json(envelope) = crate::routes::commands::send_command(
+            State(self.state.clone()),
+            principal.map(Extension),
+            HeaderMap::new(),
+            Json(SendCommandRequest {
+                device_id: req.device_id,
+                fleet_id: req.fleet_id,
+                command: req.command,
+                tool_name: req.tool_name,
+                tool_args,
+                initiated_by: req.initiated_by,
+                preflight: req.preflight,
+                force: req.force,
+                conversation_id,
+                idempotency_key: None,
+            }),
+        )
+        .await?;
+        Ok(Response::new(command(envelope)))
+    }
+
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let principal = self.authorize(&request, Role::Viewer).await?;
+        let tenants = principal.and_then(|p| p.tenants);
+        let req = request.into_inner();
+        if let Some(fleet) = tenants
+            .as_ref()
+            .and_then(|t| req.fleet_ids.iter().find(|f| !t.contains(f)))
+        {
+            return Err(Status::permission_denied(format!(
+                "no access to fleet '{fleet}'"
+            )));
+        }
+
+        // Subscribe first so nothing is missed while fleets are resolved.
+        let rx = self.state.event_tx.subscribe();
+        let subscription = ws::subscribe(
+            &self.state,
+            tenants.as_deref(),
+            req.device_ids,
+            req.fleet_ids,
+            req.event_types,
+        )
+        .await
+        .map_err(Status::invalid_argument)?;
+        tracing::info!("gRPC event stream opened");
+
+        let stream = futures::stream::unfold(
+            (rx, subscription),
+            |(mut rx, mut subscription)| async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(e) if subscription.admit(&e) => {
+                            return Some((Ok(event(&e)), (rx, subscription)));
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(n)) => {
+                            tracing::warn!("gRPC event stream lagged, skipped {n} events");
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+    use tonic::transport::Channel;
+    use tonic::transport::server::TcpIncoming;
+
+    use super::proto::fleet_client::FleetClient;
+    use super::*;
+    use crate::auth::{OidcConfig, OidcVerifier};
+
+    /// Serve `state` on a local port and connect a client.
+    async fn client(state: AppState) -> FleetClient<Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(FleetServer::new(FleetService::new(state)))
+                .serve_with_incoming(TcpIncoming::from(listener)),
+        );
+        FleetClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_devices_in_pages() {
+        let mut client = client(AppState::with_sample_data()).await;
+
+        let page = client
+            .list_devices(proto::ListDevicesRequest {
+                limit: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(page.total, 3);
+        let ids: Vec<&str> = page.devices.iter().map(|d| d.device_id.as_str()).collect();
+        assert_eq!(ids, ["rpi-001", "rpi-002"]);
+        assert_eq!(page.devices[0].fleet_id.as_deref(), Some("fleet-alpha"));
+
+        let err = client
+            .list_devices(proto::ListDevicesRequest {
+                status: Some("sleeping".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn sends_commands_through_the_rest_handler() {
+        let state = AppState::with_sample_data();
+        let mut client = client(state.clone()).await;
+
+        let command = client
+            .send_command(proto::SendCommandRequest {
+                device_id: "sbc-010".into(),
+                fleet_id: "fleet-beta".into(),
+                tool_name: Some("read_pid".into()),
+                tool_args_json: Some(r#"{"pid":12}"#.into()),
+                initiated_by: "integrator".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(command.tool_name.as_deref(), Some("read_pid"));
+        assert_eq!(command.tool_args_json.as_deref(), Some(r#"{"pid":12}"#));
+        assert_eq!(
+            state.commands.read().await[0].envelope.id.to_string(),
+            command.id
+        );
+
+        let err = client
+            .send_command(proto::SendCommandRequest {
+                device_id: "nope".into(),
+                fleet_id: "fleet-beta".into(),
+                command: "read DTCs".into(),
+                initiated_by: "integrator".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn streams_filtered_events() {
+        let state = AppState::with_sample_data();
+        let mut client = client(state.clone()).await;
+
+        let mut stream = client
+            .stream_events(proto::StreamEventsRequest {
+                fleet_ids: vec!["fleet-beta".into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        for device_id in ["rpi-001", "sbc-010"] {
+            state
+                .event_tx
+                .send(WsEvent::DeviceHeartbeat {
+                    device_id: device_id.into(),
+                    timestamp: Utc::now(),
+                })
+                .unwrap();
+        }
+
+        let received = stream.next().await.unwrap().unwrap();
+        assert_eq!(received.r#type, "device_heartbeat");
+        assert_eq!(received.device_id, "sbc-010");
+        let frame: serde_json::Value = serde_json::from_str(&received.data_json).unwrap();
+        assert_eq!(frame["device_id"], "sbc-010");
+
+        let err = client
+            .stream_events(proto::StreamEventsRequest {
+                event_types: vec!["nonsense".into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn requires_a_token_when_oidc_is_on() {
+        let mut state = AppState::with_sample_data();
+        let verifier = OidcVerifier::with_jwks(
+            OidcConfig::new("https://idp.example.com"),
+            &serde_json::json!({"keys": []}),
+        )
+        .unwrap();
+        state.oidc = Some(Arc::new(verifier));
+        let mut client = client(state).await;
+
+        let err = client
+            .list_devices(proto::ListDevicesRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+}
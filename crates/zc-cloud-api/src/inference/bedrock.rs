@@ -8,37 +8,55 @@ use async_trait::async_trait;
 use aws_sdk_bedrockruntime::Client as BedrockClient;
 use aws_sdk_bedrockruntime::types::{ContentBlock, ConversationRole, Message, SystemContentBlock};
 use serde::Deserialize;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::timeout;
 
-use super::{InferenceEngine, ParseResult};
+use super::stats::{InferenceStats, TokenUsage, UsageCounters};
+use super::{InferenceEngine, InferenceOverrides, ParseResult};
+use zc_protocol::catalog::ToolCatalog;
 use zc_protocol::commands::{ActionKind, ParsedIntent};
+use zc_protocol::plan::PlanStep;
 
-/// System prompt listing all 13 tools plus shell and reply action types.
+/// System prompt listing all 17 tools plus shell and reply action types.
 ///
 /// Embedded as a const to avoid pulling zc-canbus-tools/zc-log-tools as dependencies
 /// (which would bring in socketcan, regex, etc. into the cloud API binary).
+/// The tool list is only a fallback: for devices that advertise a tool
+/// catalog it is replaced by the catalog (see [`with_tools`]).
 const SYSTEM_PROMPT: &str = r#"You are an AI agent for an IoT fleet management platform. Parse operator commands into one of three actions.
 
 ## Action 1: tool — Invoke a diagnostic tool
 Available tools:
 
-1. read_dtcs — Read diagnostic trouble codes from the vehicle ECU. Args: {}
+1. read_dtcs — Read diagnostic trouble codes from the vehicle ECU. Args: {}; add "scope": "pending" for unconfirmed/intermittent faults, "permanent" for codes that survive a clear, or "all"
 2. read_vin — Read the Vehicle Identification Number. Args: {}
-3. read_freeze — Read freeze frame data. Args: {}
+3. read_freeze — Read the freeze frames stored with each DTC (the code that set each frame and its sensor values). Args: {}; add "dtc": "P0300" for one code's frames
 4. read_pid — Read an OBD-II sensor value. Args: {"pid": "0x0C"} (0x0C=RPM, 0x0D=speed, 0x05=coolant temp, 0x11=throttle, 0x2F=fuel level, 0x04=engine load, 0x0F=intake temp, 0x0E=timing advance)
-5. can_monitor — Monitor raw CAN bus traffic. Args: {"duration_secs": 10}
+5. can_monitor — Monitor raw CAN bus traffic. Args: {"duration_secs": 10}; add "capture_errors": true to count bus error frames (bit/stuff/ACK errors, bus-off, restarts); add "filters": [{"id": "0x500", "mask": "0x7F0"}] to capture only some CAN IDs (frames known from the device's DBC files come back decoded into named signals)
 6. read_uds_dtcs — Read DTCs from a UDS ECU (Hella BCR/BCF). Args: {"ecu": "BCR"} or {"ecu": "BCF"}
 7. read_uds_did — Read a Data Identifier from a UDS ECU. Args: {"ecu": "BCR"} (reads all known DIDs) or {"ecu": "BCR", "did": 64773}
 8. uds_session_control — Control diagnostic session on a UDS ECU. Args: {"ecu": "BCR", "session": "extended"} or {"ecu": "BCR", "tester_present": true}
-9. search_logs — Search device logs. Args: {"path": "/var/log/syslog", "query": "error"}
+9. search_logs — Search device logs. Args: {"path": "/var/log/syslog", "query": "error"}; "path" may also be a glob ("/var/log/*.log") or a list of paths; add "include_rotated": true to also search rotated files (syslog.1, syslog.2.gz) for incidents older than the last rotation; "remote://<ip>" reads syslog received from another device on the network (dashcam, telematics unit); all log tools accept "since"/"until" (RFC 3339 or relative like "2h") to limit entries to a time window
 10. analyze_errors — Analyze error patterns in logs. Args: {"path": "/var/log/syslog"}
-11. log_stats — Get log statistics. Args: {"path": "/var/log/syslog"}
-12. tail_logs — Show recent log entries. Args: {"path": "/var/log/syslog", "lines": 50}
-13. query_journal — Query systemd journal for a service. Args: {"unit": "nginx.service", "lines": 50}
+11. log_stats — Get log statistics, including severity counts over time (errors over time). Args: {"path": "/var/log/syslog"}; optional "bucket": "minute", "hour" or "day"
+12. tail_logs — Show recent log entries. Args: {"path": "/var/log/syslog", "lines": 50}; add "follow": true, "follow_secs": 60 to keep streaming new entries (live logs)
+13. query_journal — Query systemd journal for a service. Args: {"unit": "nginx.service", "lines": 50}; optional "priority" ("err"), "since" ("1 hour ago"), "boot" ("-1" for the previous boot)
+14. detect_anomalies — Find log message templates whose rate spiked (or that are new) in the recent window compared to the earlier baseline. Args: {"path": "/var/log/syslog"}; optional "recent" ("10m"), "threshold" (3), "min_count" (3)
+15. pid_burst — Capture a burst of raw samples for one OBD-II PID (detailed investigation of a signal). Args: {"pid": "0x0C", "samples": 50, "interval_ms": 50}
+16. get_local_history — Query the device's own journal of past commands and telemetry (fills gaps after an outage). Args: {"kind": "command", "since_minutes": 120, "status": "failed", "unpublished_only": true, "limit": 50} (all optional; kind is "command" or "telemetry", metric filters telemetry e.g. "engine_rpm")
+17. self_test — Run the device provisioning self-test (CAN interface, log paths, Ollama, broker connectivity, clock, disk space). Args: {}
+18. list_supported_pids — List the OBD-II PIDs the ECU supports (use before read_pid on an unfamiliar vehicle). Args: {}
+19. read_readiness — Read emissions readiness: MIL (check engine light) status, DTC count and which readiness monitors are complete (inspection readiness). Args: {}
+20. read_vehicle_info — Read the ECU's calibration IDs (CALID), calibration verification numbers (CVN) and ECU name, for recall/compliance checks. Args: {}; optional "info": ["calid", "cvn", "ecu_name"]
+21. agent_stats — Show which diagnostic tools are slow or failing on this device: per-tool call counts, failures, timeouts and latency since the agent started. Args: {}; optional "tool": "read_pid"
+22. correlate_faults — Line up DTCs with the CAN/driver log entries logged around their detection, as one timeline (why did this code set?). Args: {"dtcs": ["P0300"], "path": "/var/log/syslog"}; optional "window_secs" (300), "detected_at" (RFC 3339 or relative like "10m"; default now)
 
 Format: {"action": "tool", "tool_name": "<name>", "tool_args": {<args>}, "confidence": <0.0-1.0>}
 
+When a request needs several tools in sequence, return a plan of up to 8 steps instead. Arguments may refer to earlier results: {{steps.N.<path>}} is the result of step N (0-based; its "data" holds the tool output), {{prev.<path>}} the previous step's, and in a step with "for_each" (run once per element of an earlier array) {{item.<path>}} the current element.
+Example — "read the DTCs and the freeze frame for each code":
+{"action": "tool", "steps": [{"tool_name": "read_dtcs", "tool_args": {}}, {"tool_name": "read_freeze", "tool_args": {"dtc": "{{item.code}}"}, "for_each": "steps.0.data"}], "confidence": 0.85}
+
 ## Action 2: shell — Run a system command on the device
 For system info queries (CPU temp, disk space, memory, network, uptime, etc.).
 
@@ -97,6 +115,15 @@ const KNOWN_TOOLS: &[&str] = &[
     "log_stats",
     "tail_logs",
     "query_journal",
+    "detect_anomalies",
+    "pid_burst",
+    "get_local_history",
+    "self_test",
+    "list_supported_pids",
+    "read_readiness",
+    "read_vehicle_info",
+    "agent_stats",
+    "correlate_faults",
 ];
 
 /// Configuration for the Bedrock inference engine.
@@ -106,6 +133,10 @@ pub struct BedrockConfig {
     pub model_id: String,
     /// Per-request timeout.
     pub timeout: Duration,
+    /// US dollars per 1000 input tokens, for cost estimates.
+    pub input_cost_per_1k: f64,
+    /// US dollars per 1000 output tokens, for cost estimates.
+    pub output_cost_per_1k: f64,
 }
 
 impl BedrockConfig {
@@ -117,62 +148,112 @@ impl BedrockConfig {
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(15);
+        // Nova Lite on-demand pricing.
+        let cost = |name: &str, default: f64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        };
         Self {
             model_id,
             timeout: Duration::from_secs(timeout_secs),
+            input_cost_per_1k: cost("BEDROCK_INPUT_COST_PER_1K", 0.00006),
+            output_cost_per_1k: cost("BEDROCK_OUTPUT_COST_PER_1K", 0.00024),
         }
     }
+
+    /// Estimated cost of a call with these token counts.
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_cost_per_1k
+            + output_tokens as f64 * self.output_cost_per_1k)
+            / 1000.0
+    }
 }
 
 /// Bedrock Converse API inference engine.
 pub struct BedrockEngine {
     client: BedrockClient,
     config: BedrockConfig,
+    usage: UsageCounters,
 }
 
 impl BedrockEngine {
     /// Create a new engine with a pre-built Bedrock client.
     pub fn new(client: BedrockClient, config: BedrockConfig) -> Self {
-        Self { client, config }
+        Self {
+            client,
+            config,
+            usage: UsageCounters::default(),
+        }
     }
 }
 
 #[async_trait]
 impl InferenceEngine for BedrockEngine {
     async fn parse(&self, text: &str) -> Option<ParseResult> {
-        let result = timeout(self.config.timeout, self.call_converse(text)).await;
+        self.parse_with(text, &InferenceOverrides::default()).await
+    }
 
-        match result {
-            Ok(Ok(Some(intent))) => Some(ParseResult {
-                intent,
-                tier: "bedrock".into(),
-            }),
-            Ok(Ok(None)) => {
-                tracing::debug!("bedrock returned no match for: {text}");
-                None
+    async fn parse_with(&self, text: &str, overrides: &InferenceOverrides) -> Option<ParseResult> {
+        let system_prompt = system_prompt(overrides);
+        let started = Instant::now();
+        let result = timeout(self.config.timeout, self.converse(text, &system_prompt)).await;
+
+        // Tokens are billed whether or not the reply is usable.
+        let (intent, usage) = match result {
+            Ok(Ok((raw_text, usage))) => {
+                match raw_text
+                    .map(|raw| self.interpret(&raw, overrides.catalog.as_ref()))
+                    .transpose()
+                {
+                    Ok(intent) => (intent.flatten(), usage),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "bedrock inference failed");
+                        (None, usage)
+                    }
+                }
             }
             Ok(Err(e)) => {
                 tracing::warn!(error = %e, "bedrock inference failed");
-                None
+                (None, None)
             }
             Err(_) => {
                 tracing::warn!(
                     timeout_secs = self.config.timeout.as_secs(),
                     "bedrock inference timed out"
                 );
-                None
+                (None, None)
             }
+        };
+        if intent.is_none() {
+            tracing::debug!("bedrock returned no match for: {text}");
         }
+        self.usage
+            .record(self.tier_name(), intent.is_some(), started.elapsed(), usage);
+        intent.map(|intent| ParseResult {
+            intent,
+            tier: "bedrock".into(),
+        })
     }
 
     fn tier_name(&self) -> &str {
         "bedrock"
     }
+
+    fn stats(&self) -> Option<InferenceStats> {
+        Some(InferenceStats::new(self.tier_name(), self.usage.snapshot()))
+    }
 }
 
 impl BedrockEngine {
-    /// Call the Bedrock Converse API and parse the response.
-    async fn call_converse(&self, text: &str) -> anyhow::Result<Option<ParsedIntent>> {
+    /// Call the Bedrock Converse API. Returns the reply text and the tokens
+    /// the call was billed for.
+    async fn converse(
+        &self,
+        text: &str,
+        system_prompt: &str,
+    ) -> anyhow::Result<(Option<String>, Option<TokenUsage>)> {
         let user_message = Message::builder()
             .role(ConversationRole::User)
             .content(ContentBlock::Text(text.to_string()))
@@ -183,12 +264,22 @@ impl BedrockEngine {
             .client
             .converse()
             .model_id(&self.config.model_id)
-            .system(SystemContentBlock::Text(SYSTEM_PROMPT.to_string()))
+            .system(SystemContentBlock::Text(system_prompt.to_string()))
             .messages(user_message)
             .send()
             .await
             .map_err(|e| anyhow::anyhow!("bedrock converse error: {e}"))?;
 
+        let usage = response.usage().map(|u| {
+            let input_tokens = u.input_tokens().max(0) as u64;
+            let output_tokens = u.output_tokens().max(0) as u64;
+            TokenUsage {
+                input_tokens,
+                output_tokens,
+                cost_usd: self.config.cost(input_tokens, output_tokens),
+            }
+        });
+
         // Extract text from the response
         let output = response
             .output()
@@ -207,24 +298,29 @@ impl BedrockEngine {
             _ => None,
         };
 
-        let Some(raw_text) = text_content else {
-            return Ok(None);
-        };
+        Ok((text_content, usage))
+    }
 
+    /// Parse and validate the model's reply.
+    fn interpret(
+        &self,
+        raw_text: &str,
+        catalog: Option<&ToolCatalog>,
+    ) -> anyhow::Result<Option<ParsedIntent>> {
         // Parse the JSON from the LLM output
-        let json_str = extract_json(&raw_text);
+        let json_str = extract_json(raw_text);
         let call: LlmResponse = serde_json::from_str(json_str)
             .map_err(|e| anyhow::anyhow!("failed to parse bedrock JSON: {e} — raw: {raw_text}"))?;
 
         // Route based on action type
         match call.action.as_str() {
-            "tool" => self.validate_tool(call),
+            "tool" => self.validate_tool(call, catalog),
             "shell" => self.validate_shell(call),
             "reply" => self.validate_reply(call),
             _ => {
                 // Fallback: if tool_name present, try tool path
                 if call.tool_name.is_some() {
-                    self.validate_tool(call)
+                    self.validate_tool(call, catalog)
                 } else {
                     Ok(None)
                 }
@@ -232,12 +328,19 @@ impl BedrockEngine {
         }
     }
 
-    fn validate_tool(&self, call: LlmResponse) -> anyhow::Result<Option<ParsedIntent>> {
+    fn validate_tool(
+        &self,
+        call: LlmResponse,
+        catalog: Option<&ToolCatalog>,
+    ) -> anyhow::Result<Option<ParsedIntent>> {
+        if !call.steps.is_empty() {
+            return Ok(plan_intent(call.steps, call.confidence, catalog));
+        }
         let Some(tool_name) = call.tool_name else {
             return Ok(None);
         };
 
-        if !is_known_tool(&tool_name) {
+        if !is_known_tool(&tool_name, catalog) {
             tracing::warn!(tool_name = %tool_name, "bedrock returned unknown tool");
             return Ok(None);
         }
@@ -252,6 +355,7 @@ impl BedrockEngine {
             tool_name,
             tool_args: call.tool_args,
             confidence: call.confidence,
+            steps: Vec::new(),
         }))
     }
 
@@ -269,6 +373,7 @@ impl BedrockEngine {
             tool_name: command,
             tool_args: call.tool_args,
             confidence: call.confidence,
+            steps: Vec::new(),
         }))
     }
 
@@ -282,10 +387,45 @@ impl BedrockEngine {
             tool_name: String::new(),
             tool_args: serde_json::json!({ "message": message }),
             confidence: 1.0,
+            steps: Vec::new(),
         }))
     }
 }
 
+/// The variant's prompt (or the default one) with the device's tool
+/// catalog, plus the device context and the conversation so far.
+fn system_prompt(overrides: &InferenceOverrides) -> String {
+    let base = overrides.system_prompt.as_deref().unwrap_or(SYSTEM_PROMPT);
+    let base = match &overrides.catalog {
+        Some(catalog) => with_tools(base, catalog),
+        None => base.to_string(),
+    };
+    let prompt = match &overrides.device_context {
+        Some(context) => context.apply_to(&base),
+        None => base,
+    };
+    zc_protocol::conversation::apply_history(&prompt, &overrides.history)
+}
+
+/// `prompt` with its tool list (between "Available tools:" and the tool
+/// format line) replaced by `catalog`. Prompts without that list get the
+/// catalog appended.
+fn with_tools(prompt: &str, catalog: &ToolCatalog) -> String {
+    const START: &str = "Available tools:\n";
+    const END: &str = "\nFormat: {\"action\": \"tool\"";
+    if let Some(start) = prompt.find(START).map(|i| i + START.len())
+        && let Some(end) = prompt[start..].find(END).map(|i| i + start)
+    {
+        return format!(
+            "{}\n{}\n{}",
+            &prompt[..start],
+            catalog.prompt_list(),
+            &prompt[end..]
+        );
+    }
+    format!("{prompt}\n\n## Available tools\n{}", catalog.prompt_list())
+}
+
 /// Expected JSON shape from the LLM — supports all three action types.
 #[derive(Debug, Deserialize)]
 struct LlmResponse {
@@ -298,6 +438,35 @@ struct LlmResponse {
     message: Option<String>,
     #[serde(default)]
     confidence: f64,
+    #[serde(default)]
+    steps: Vec<PlanStep>,
+}
+
+/// Intent for a multi-step plan, or `None` if any step names an unknown
+/// tool, the plan is too long or confidence is too low.
+fn plan_intent(
+    steps: Vec<PlanStep>,
+    confidence: f64,
+    catalog: Option<&ToolCatalog>,
+) -> Option<ParsedIntent> {
+    if let Err(err) = zc_protocol::plan::validate(&steps) {
+        tracing::warn!(error = %err, "bedrock returned an invalid plan");
+        return None;
+    }
+    if let Some(step) = steps.iter().find(|s| !is_known_tool(&s.tool_name, catalog)) {
+        tracing::warn!(tool_name = %step.tool_name, "bedrock plan has unknown tool");
+        return None;
+    }
+    if confidence < 0.3 {
+        return None;
+    }
+    Some(ParsedIntent {
+        action: ActionKind::Tool,
+        tool_name: steps[0].tool_name.clone(),
+        tool_args: steps[0].tool_args.clone(),
+        confidence,
+        steps,
+    })
 }
 
 fn default_action() -> String {
@@ -328,9 +497,13 @@ fn extract_json(text: &str) -> &str {
     trimmed
 }
 
-/// Check if a tool name is one of our 10 known tools.
-fn is_known_tool(name: &str) -> bool {
-    KNOWN_TOOLS.contains(&name)
+/// Check if a tool name is in the device's catalog or, for devices without
+/// one, in [`KNOWN_TOOLS`].
+fn is_known_tool(name: &str, catalog: Option<&ToolCatalog>) -> bool {
+    match catalog {
+        Some(catalog) => catalog.contains(name),
+        None => KNOWN_TOOLS.contains(&name),
+    }
 }
 
 #[cfg(test)]
@@ -363,20 +536,113 @@ mod tests {
         assert_eq!(extract_json(input), "{\"tool_name\": \"log_stats\"}");
     }
 
+    // ── system_prompt ────────────────────────────────────────────
+
+    #[test]
+    fn system_prompt_appends_device_context() {
+        assert_eq!(system_prompt(&InferenceOverrides::default()), SYSTEM_PROMPT);
+
+        let overrides = InferenceOverrides {
+            system_prompt: Some("variant".into()),
+            device_context: Some(zc_protocol::context::DeviceContext {
+                tools: vec!["read_dtcs".into()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let prompt = system_prompt(&overrides);
+        assert!(prompt.starts_with("variant\n\n## This device"));
+        assert!(prompt.contains("read_dtcs"));
+    }
+
+    #[test]
+    fn system_prompt_lists_catalog_tools() {
+        let overrides = InferenceOverrides {
+            catalog: Some(catalog()),
+            ..Default::default()
+        };
+        let prompt = system_prompt(&overrides);
+        assert!(prompt.contains(
+            "Available tools:\n\n1. read_dtcs — Read DTCs. Args: {}\n\
+             2. send_frame — Send a raw CAN frame (bench mode). Args: {}\n\nFormat:"
+        ));
+        assert!(!prompt.contains("read_vin —"));
+        assert!(prompt.contains("## Action 2: shell"));
+
+        // A variant prompt without a tool list gets the catalog appended.
+        let overrides = InferenceOverrides {
+            system_prompt: Some("variant".into()),
+            catalog: Some(catalog()),
+            ..Default::default()
+        };
+        assert!(
+            system_prompt(&overrides).starts_with("variant\n\n## Available tools\n1. read_dtcs")
+        );
+    }
+
+    #[test]
+    fn system_prompt_appends_conversation() {
+        let overrides = InferenceOverrides {
+            history: vec![zc_protocol::conversation::ConversationTurn::new(
+                "run a CAN monitor on can0",
+                None,
+                Some("412 frames"),
+                chrono::Utc::now(),
+            )],
+            ..Default::default()
+        };
+        let prompt = system_prompt(&overrides);
+        assert!(prompt.starts_with(SYSTEM_PROMPT));
+        assert!(prompt.contains("## Conversation so far"));
+        assert!(prompt.contains("Result: 412 frames"));
+    }
+
+    // ── cost ─────────────────────────────────────────────────────
+
+    #[test]
+    fn cost_uses_per_1k_prices() {
+        let config = BedrockConfig {
+            model_id: "test".into(),
+            timeout: Duration::from_secs(1),
+            input_cost_per_1k: 0.0006,
+            output_cost_per_1k: 0.0024,
+        };
+        assert!((config.cost(1500, 250) - 0.0015).abs() < 1e-12);
+    }
+
     // ── is_known_tool ────────────────────────────────────────────
 
     #[test]
     fn known_tools_accepted() {
         for tool in KNOWN_TOOLS {
-            assert!(is_known_tool(tool), "should accept {tool}");
+            assert!(is_known_tool(tool, None), "should accept {tool}");
         }
     }
 
     #[test]
     fn unknown_tool_rejected() {
-        assert!(!is_known_tool("hack_ecu"));
-        assert!(!is_known_tool(""));
-        assert!(!is_known_tool("READ_DTCS")); // case-sensitive
+        assert!(!is_known_tool("hack_ecu", None));
+        assert!(!is_known_tool("", None));
+        assert!(!is_known_tool("READ_DTCS", None)); // case-sensitive
+    }
+
+    #[test]
+    fn catalog_replaces_known_tools() {
+        let catalog = catalog();
+        assert!(is_known_tool("send_frame", Some(&catalog)));
+        assert!(!is_known_tool("read_vin", Some(&catalog)));
+    }
+
+    fn catalog() -> ToolCatalog {
+        use zc_protocol::catalog::ToolSpec;
+        ToolCatalog::new(vec![
+            ToolSpec::new("read_dtcs", "Read DTCs", serde_json::json!({})),
+            ToolSpec::new(
+                "send_frame",
+                "Send a raw CAN frame (bench mode)",
+                serde_json::json!({}),
+            ),
+        ])
     }
 
     // ── LlmResponse deserialization ──────────────────────────────
@@ -406,6 +672,24 @@ mod tests {
         assert_eq!(resp.message.as_deref(), Some("Hello!"));
     }
 
+    #[test]
+    fn plan_steps_are_validated() {
+        let json = r#"{"action": "tool", "confidence": 0.85, "steps": [
+            {"tool_name": "read_dtcs"},
+            {"tool_name": "read_freeze", "tool_args": {"dtc": "{{item.code}}"}, "for_each": "steps.0.data"}
+        ]}"#;
+        let resp: LlmResponse = serde_json::from_str(json).unwrap();
+        let intent = plan_intent(resp.steps.clone(), resp.confidence, None).unwrap();
+        assert_eq!(intent.tool_name, "read_dtcs");
+        assert_eq!(intent.steps.len(), 2);
+
+        let mut unknown = resp.steps.clone();
+        unknown[1].tool_name = "hack_ecu".into();
+        assert!(plan_intent(unknown, 0.85, None).is_none());
+        assert!(plan_intent(resp.steps.clone(), 0.1, None).is_none());
+        assert!(plan_intent(resp.steps, 0.85, Some(&catalog())).is_none());
+    }
+
     #[test]
     fn deserialize_null_tool_name() {
         let json = r#"{"tool_name": null, "tool_args": {}, "confidence": 0.0}"#;
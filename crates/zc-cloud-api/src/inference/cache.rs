@@ -0,0 +1,277 @@
+//! Cache of cloud inference results.
+//!
+//! Operators repeat themselves ("check engine codes", "check engine codes?"),
+//! and every repeat that misses the rule engine would otherwise be billed by
+//! Bedrock again. Results are keyed by normalized text (single spaces, no
+//! trailing punctuation) plus the system prompt, device context, tool
+//! catalog and conversation the cloud tier was given, and expire after a
+//! TTL. Case is kept: a cached intent carries `tool_args` taken from the
+//! text (paths, search patterns, interface names), so "App.log" and
+//! "app.log" must not share one. Only intents are cached; texts the cloud
+//! could not parse are asked again.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::{InferenceOverrides, ParseResult};
+
+/// Tier name reported for results served from the cache.
+pub const CACHE_TIER: &str = "cache";
+
+/// Cache settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// How long a result is reused. Zero disables the cache.
+    pub ttl: Duration,
+    /// Most cached results; the oldest is evicted beyond this.
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(3600),
+            max_entries: 1000,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Load from `INFERENCE_CACHE_TTL_SECS` (default 3600, 0 disables) and
+    /// `INFERENCE_CACHE_MAX_ENTRIES` (default 1000).
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let ttl = std::env::var("INFERENCE_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map_or(defaults.ttl, Duration::from_secs);
+        let max_entries = std::env::var("INFERENCE_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(defaults.max_entries);
+        Self { ttl, max_entries }
+    }
+
+    /// Whether results are cached at all.
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.max_entries > 0
+    }
+}
+
+/// Cache counters.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub ttl_secs: u64,
+    /// Cloud spend avoided by hits, at the cloud tier's average cost per
+    /// request.
+    pub saved_cost_usd: f64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    result: ParseResult,
+    stored_at: Instant,
+}
+
+/// TTL cache from normalized text to parse result.
+#[derive(Debug)]
+pub struct InferenceCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl InferenceCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Unexpired result for `key`, reported under the [`CACHE_TIER`].
+    pub fn get(&self, key: &str, now: Instant) -> Option<ParseResult> {
+        let mut entries = self.entries.lock().unwrap();
+        let fresh = entries
+            .get(key)
+            .filter(|e| now.duration_since(e.stored_at) < self.config.ttl)
+            .map(|e| ParseResult {
+                intent: e.result.intent.clone(),
+                tier: CACHE_TIER.into(),
+            });
+        if fresh.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            entries.remove(key);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        fresh
+    }
+
+    /// Cache `result` under `key`, evicting expired entries and then the
+    /// oldest one when full.
+    pub fn insert(&self, key: String, result: ParseResult, now: Instant) {
+        if !self.config.enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, e| now.duration_since(e.stored_at) < self.config.ttl);
+            if entries.len() >= self.config.max_entries
+                && let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.stored_at)
+                    .map(|(k, _)| k.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                result,
+                stored_at: now,
+            },
+        );
+    }
+
+    /// Counters, with the saving estimated at `cost_per_request`.
+    pub fn stats(&self, cost_per_request: f64) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        CacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            hits,
+            misses: self.misses.load(Ordering::Relaxed),
+            ttl_secs: self.config.ttl.as_secs(),
+            saved_cost_usd: hits as f64 * cost_per_request,
+        }
+    }
+}
+
+/// Single spaces, no trailing punctuation; case is kept.
+pub fn normalize(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    text.trim_end_matches(['?', '!', '.'])
+        .trim_end()
+        .to_string()
+}
+
+/// Cache key of `text` under `overrides`. The prompt, device context, tool
+/// catalog and conversation change what the cloud answers, so they are
+/// part of the key.
+pub fn key(text: &str, overrides: &InferenceOverrides) -> String {
+    let text = normalize(text);
+    if overrides.system_prompt.is_none()
+        && overrides.device_context.is_none()
+        && overrides.history.is_empty()
+        && overrides.catalog.is_none()
+    {
+        return text;
+    }
+    let mut hasher = DefaultHasher::new();
+    overrides.system_prompt.hash(&mut hasher);
+    if let Some(context) = &overrides.device_context {
+        serde_json::to_string(context)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+    if let Some(catalog) = &overrides.catalog {
+        serde_json::to_string(catalog)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+    if !overrides.history.is_empty() {
+        serde_json::to_string(&overrides.history)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+    format!("{:016x}:{text}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zc_protocol::commands::{ActionKind, ParsedIntent};
+
+    fn result(tool: &str) -> ParseResult {
+        ParseResult {
+            intent: ParsedIntent {
+                action: ActionKind::Tool,
+                tool_name: tool.into(),
+                tool_args: serde_json::json!({}),
+                confidence: 0.9,
+                steps: Vec::new(),
+            },
+            tier: "bedrock".into(),
+        }
+    }
+
+    #[test]
+    fn keys_normalize_text_and_include_context() {
+        let plain = InferenceOverrides::default();
+        assert_eq!(
+            key("  check   engine codes?! ", &plain),
+            key("check engine codes", &plain)
+        );
+
+        let with_context = InferenceOverrides {
+            device_context: Some(zc_protocol::context::DeviceContext {
+                tools: vec!["read_dtcs".into()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_ne!(
+            key("check engine codes", &plain),
+            key("check engine codes", &with_context)
+        );
+    }
+
+    #[test]
+    fn keys_keep_argument_case() {
+        let plain = InferenceOverrides::default();
+        assert_ne!(
+            key("search /var/log/App.log for Timeout", &plain),
+            key("search /var/log/app.log for timeout", &plain)
+        );
+    }
+
+    #[test]
+    fn entries_expire_and_evict() {
+        let cache = InferenceCache::new(CacheConfig {
+            ttl: Duration::from_secs(60),
+            max_entries: 2,
+        });
+        let t0 = Instant::now();
+        cache.insert("a".into(), result("read_dtcs"), t0);
+        cache.insert("b".into(), result("read_vin"), t0 + Duration::from_secs(1));
+
+        let hit = cache.get("a", t0 + Duration::from_secs(59)).unwrap();
+        assert_eq!(
+            (hit.tier.as_str(), hit.intent.tool_name.as_str()),
+            ("cache", "read_dtcs")
+        );
+        assert!(cache.get("a", t0 + Duration::from_secs(60)).is_none());
+
+        // Full: the oldest entry makes room.
+        cache.insert("c".into(), result("read_pid"), t0 + Duration::from_secs(2));
+        cache.insert("d".into(), result("log_stats"), t0 + Duration::from_secs(3));
+        assert!(cache.get("b", t0 + Duration::from_secs(4)).is_none());
+        assert!(cache.get("d", t0 + Duration::from_secs(4)).is_some());
+
+        let stats = cache.stats(0.5);
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 2, 2));
+        assert_eq!(stats.saved_cost_usd, 1.0);
+    }
+}
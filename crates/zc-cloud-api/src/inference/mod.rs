@@ -6,13 +6,23 @@
 //! Two tiers:
 //! - **Rule-based** (local): pattern matching for known commands, ~80% coverage.
 //! - **Bedrock** (cloud): AWS Bedrock Converse API for complex queries.
+//!
+//! The tiered engine caches cloud results and counts requests, tokens and
+//! estimated cost per tier (see [`cache`] and [`stats`]).
 
 pub mod bedrock;
+pub mod cache;
 pub mod rules;
+pub mod stats;
 pub mod tiered;
 
 use async_trait::async_trait;
+use zc_protocol::catalog::ToolCatalog;
 use zc_protocol::commands::ParsedIntent;
+use zc_protocol::context::DeviceContext;
+use zc_protocol::conversation::ConversationTurn;
+
+use stats::InferenceStats;
 
 /// Result of inference: the parsed intent plus which tier produced it.
 #[derive(Debug, Clone)]
@@ -23,6 +33,23 @@ pub struct ParseResult {
     pub tier: String,
 }
 
+/// Per-request inputs beyond the text: experiment variant overrides, the
+/// target device's context and tool catalog, and the conversation so far.
+#[derive(Debug, Clone, Default)]
+pub struct InferenceOverrides {
+    /// Replacement system prompt for LLM-backed tiers.
+    pub system_prompt: Option<String>,
+    /// Vehicle and tools of the target device, appended to the system prompt
+    /// of LLM-backed tiers.
+    pub device_context: Option<DeviceContext>,
+    /// Earlier turns of the command's conversation, oldest first, appended
+    /// to the system prompt of LLM-backed tiers.
+    pub history: Vec<ConversationTurn>,
+    /// Tools advertised by the target device's agent. LLM-backed tiers list
+    /// them in their prompt, and every tier only returns intents using them.
+    pub catalog: Option<ToolCatalog>,
+}
+
 /// Trait for inference engines that parse natural language into tool intents.
 #[async_trait]
 pub trait InferenceEngine: Send + Sync {
@@ -30,8 +57,20 @@ pub trait InferenceEngine: Send + Sync {
     /// Returns None if the engine cannot parse the input.
     async fn parse(&self, text: &str) -> Option<ParseResult>;
 
+    /// Parse with experiment overrides and device inputs. Engines without a
+    /// prompt (e.g. the rule engine) only honour the tool catalog.
+    async fn parse_with(&self, text: &str, overrides: &InferenceOverrides) -> Option<ParseResult> {
+        let _ = overrides;
+        self.parse(text).await
+    }
+
     /// Name of this inference tier (for logging/audit).
     fn tier_name(&self) -> &str;
+
+    /// Usage and cost counters, for engines that track them.
+    fn stats(&self) -> Option<InferenceStats> {
+        None
+    }
 }
 
 pub use rules::RuleBasedEngine;
@@ -1,13 +1,15 @@
 //! Rule-based inference engine — pattern matching for known commands.
 //!
 //! Handles the common 80% of queries at zero cost and sub-millisecond latency.
-//! Falls through to cloud inference (Bedrock) for anything it can't match.
+//! Falls through to cloud inference (Bedrock) for anything it can't match,
+//! or whose tool the target device's catalog does not list.
 
 use async_trait::async_trait;
 use serde_json::json;
 
-use super::{InferenceEngine, ParseResult};
+use super::{InferenceEngine, InferenceOverrides, ParseResult};
 use zc_protocol::commands::{ActionKind, ParsedIntent};
+use zc_protocol::plan::PlanStep;
 
 /// Pattern-matching inference engine for structured commands.
 pub struct RuleBasedEngine;
@@ -33,6 +35,21 @@ impl InferenceEngine for RuleBasedEngine {
         })
     }
 
+    async fn parse_with(&self, text: &str, overrides: &InferenceOverrides) -> Option<ParseResult> {
+        let result = self.parse(text).await?;
+        if let Some(catalog) = &overrides.catalog
+            && result.intent.action == ActionKind::Tool
+            && let Some(missing) = result.intent.tool_names().find(|t| !catalog.contains(t))
+        {
+            tracing::debug!(
+                tool = missing,
+                "rule match not in the device's tool catalog"
+            );
+            return None;
+        }
+        Some(result)
+    }
+
     fn tier_name(&self) -> &str {
         "local"
     }
@@ -43,6 +60,61 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
     let lower = text.to_lowercase();
     let lower = lower.trim();
 
+    // send_frame (bench mode): "send frame 0x7E0 02 10 03 reply 0x7E8"
+    // Checked first so payload bytes are not mistaken for PIDs.
+    if let Some(intent) = try_parse_send_frame(lower) {
+        return Some(intent);
+    }
+
+    // get_local_history: "device local history", "unpublished results from the last 2 hours"
+    if let Some(intent) = try_parse_local_history(lower) {
+        return Some(intent);
+    }
+
+    // self_test: "run a self test", "verify provisioning"
+    if matches_any(
+        lower,
+        &[
+            "self test",
+            "self-test",
+            "selftest",
+            "provisioning check",
+            "verify provisioning",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "self_test".into(),
+            tool_args: json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+    }
+
+    // agent_stats: "agent stats", "which tools are slow", "failing tools"
+    if matches_any(
+        lower,
+        &[
+            "agent stats",
+            "agent statistics",
+            "tool stats",
+            "tool statistics",
+            "tool latency",
+            "slow tools",
+            "tools are slow",
+            "failing tools",
+            "tools are failing",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "agent_stats".into(),
+            tool_args: json!({}),
+            confidence: 0.85,
+            steps: Vec::new(),
+        });
+    }
+
     // ── UDS / Hella ECU commands (must come before generic OBD-II) ─
 
     // read_uds_dtcs: "read BCR dtcs", "BCR diagnostics", "hella dtcs", "BCF fault codes"
@@ -53,6 +125,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                 tool_name: "read_uds_dtcs".into(),
                 tool_args: json!({ "ecu": ecu }),
                 confidence: 0.92,
+                steps: Vec::new(),
             });
         }
 
@@ -75,6 +148,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                 tool_name: "read_uds_did".into(),
                 tool_args: json!({ "ecu": ecu }),
                 confidence: 0.90,
+                steps: Vec::new(),
             });
         }
 
@@ -87,6 +161,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                     tool_name: "uds_session_control".into(),
                     tool_args: json!({ "ecu": ecu, "tester_present": true }),
                     confidence: 0.90,
+                    steps: Vec::new(),
                 });
             }
             let session = if lower.contains("default") {
@@ -99,6 +174,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                 tool_name: "uds_session_control".into(),
                 tool_args: json!({ "ecu": ecu, "session": session }),
                 confidence: 0.90,
+                steps: Vec::new(),
             });
         }
     }
@@ -110,11 +186,64 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "read_uds_dtcs".into(),
             tool_args: json!({ "ecu": "BCR" }),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
     // ── CAN bus / OBD-II commands ───────────────────────────────
 
+    // correlate_faults: "correlate dtcs with logs", "fault timeline"
+    // Reads the DTCs first, then lines them up with the syslog.
+    if matches_any(lower, &["correlate", "line up", "timeline"])
+        && matches_any(lower, &["dtc", "fault", "trouble code"])
+    {
+        let steps = vec![
+            PlanStep::new("read_dtcs", json!({})),
+            PlanStep::new(
+                "correlate_faults",
+                json!({"dtcs": "{{steps.0.data}}", "path": "/var/log/syslog"}),
+            ),
+        ];
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: steps[0].tool_name.clone(),
+            tool_args: steps[0].tool_args.clone(),
+            confidence: 0.85,
+            steps,
+        });
+    }
+
+    // read_dtcs with a scope: "pending dtcs", "permanent codes", "all dtcs"
+    if matches_any(
+        lower,
+        &[
+            "dtc",
+            "trouble code",
+            "fault code",
+            "pending code",
+            "permanent code",
+        ],
+    ) {
+        let scope = if lower.contains("pending") {
+            Some("pending")
+        } else if lower.contains("permanent") {
+            Some("permanent")
+        } else if matches_any(lower, &["all dtc", "all trouble code", "all fault code"]) {
+            Some("all")
+        } else {
+            None
+        };
+        if let Some(scope) = scope {
+            return Some(ParsedIntent {
+                action: ActionKind::Tool,
+                tool_name: "read_dtcs".into(),
+                tool_args: json!({ "scope": scope }),
+                confidence: 0.90,
+                steps: Vec::new(),
+            });
+        }
+    }
+
     // read_dtcs: "read dtcs", "get dtcs", "diagnostic trouble codes", "check engine codes"
     if matches_any(
         lower,
@@ -132,6 +261,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "read_dtcs".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -151,6 +281,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "read_vin".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -169,14 +300,108 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "read_freeze".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
+    // list_supported_pids: "which pids are supported", "list available pids"
+    if matches_any(
+        lower,
+        &[
+            "supported pid",
+            "pids supported",
+            "available pid",
+            "pid discovery",
+            "discover pid",
+            "which pids",
+            "what pids",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "list_supported_pids".into(),
+            tool_args: json!({}),
+            confidence: 0.92,
+            steps: Vec::new(),
+        });
+    }
+
+    // read_vehicle_info: "calibration id", "read cvn", "ecu name"
+    if matches_any(
+        lower,
+        &[
+            "calibration id",
+            "calibration verification",
+            "calid",
+            "cvn",
+            "ecu name",
+            "ecu software",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "read_vehicle_info".into(),
+            tool_args: json!({}),
+            confidence: 0.90,
+            steps: Vec::new(),
+        });
+    }
+
+    // read_readiness: "emissions readiness", "ready for inspection", "mil status"
+    if matches_any(
+        lower,
+        &[
+            "readiness",
+            "ready for inspection",
+            "inspection ready",
+            "emissions ready",
+            "smog check",
+            "mil status",
+            "check engine light",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "read_readiness".into(),
+            tool_args: json!({}),
+            confidence: 0.90,
+            steps: Vec::new(),
+        });
+    }
+
+    // pid_burst: "burst capture rpm", "raw samples of pid 0x0C"
+    if let Some(intent) = try_parse_pid_burst(lower) {
+        return Some(intent);
+    }
+
     // read_pid: "read pid 0x0C", "read rpm", "read speed", "engine speed", etc.
     if let Some(intent) = try_parse_pid(lower) {
         return Some(intent);
     }
 
+    // can_monitor with error capture: "bus errors", "error frames", "flaky bus"
+    if matches_any(
+        lower,
+        &[
+            "bus error",
+            "can error",
+            "error frame",
+            "flaky bus",
+            "bus is flaky",
+            "bus-off",
+            "bus off",
+        ],
+    ) {
+        let duration = extract_duration(lower).unwrap_or(10);
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "can_monitor".into(),
+            tool_args: json!({ "duration_secs": duration, "capture_errors": true }),
+            confidence: 0.85,
+            steps: Vec::new(),
+        });
+    }
+
     // can_monitor: "monitor can", "sniff can", "capture can", "can bus traffic"
     if matches_any(
         lower,
@@ -195,6 +420,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "can_monitor".into(),
             tool_args: json!({ "duration_secs": duration }),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -214,6 +440,27 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                 "query": query.unwrap_or("error"),
             }),
             confidence: if query.is_some() { 0.90 } else { 0.75 },
+            steps: Vec::new(),
+        });
+    }
+
+    // detect_anomalies: "log anomalies", "unusual log activity", "log spikes"
+    if matches_any(
+        lower,
+        &[
+            "anomal",
+            "unusual log",
+            "log spike",
+            "spike in log",
+            "spiking",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "detect_anomalies".into(),
+            tool_args: json!({ "path": "/var/log/syslog" }),
+            confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -233,23 +480,59 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "analyze_errors".into(),
             tool_args: json!({ "path": "/var/log/syslog" }),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
-    // log_stats: "log stats", "log statistics", "log summary"
+    // log_stats: "log stats", "log statistics", "log summary", "errors over time"
     if matches_any(
         lower,
-        &["log stat", "log summar", "log overview", "show stat"],
+        &[
+            "log stat",
+            "log summar",
+            "log overview",
+            "show stat",
+            "errors over time",
+            "error trend",
+            "errors per hour",
+        ],
     ) {
         return Some(ParsedIntent {
             action: ActionKind::Tool,
             tool_name: "log_stats".into(),
             tool_args: json!({ "path": "/var/log/syslog" }),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
     // tail_logs: "tail logs", "recent logs", "latest logs", "show logs"
+    // tail_logs with follow: "follow the logs", "live logs", "tail -f"
+    if matches_any(
+        lower,
+        &[
+            "follow log",
+            "follow the log",
+            "live log",
+            "stream log",
+            "stream the log",
+            "tail -f",
+        ],
+    ) {
+        return Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "tail_logs".into(),
+            tool_args: json!({
+                "path": "/var/log/syslog",
+                "lines": extract_line_count(lower).unwrap_or(50),
+                "follow": true,
+                "follow_secs": 60,
+            }),
+            confidence: 0.85,
+            steps: Vec::new(),
+        });
+    }
+
     if matches_any(
         lower,
         &[
@@ -269,6 +552,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                 "lines": lines,
             }),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -297,6 +581,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             } else {
                 0.75
             },
+            steps: Vec::new(),
         });
     }
 
@@ -312,6 +597,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "ip -brief addr".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -322,6 +608,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "cat /sys/class/thermal/thermal_zone0/temp".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -332,6 +619,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "vcgencmd measure_temp".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -350,6 +638,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "sensors".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -363,6 +652,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "df -h".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -373,6 +663,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "free -h".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -383,6 +674,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "uptime".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -402,6 +694,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "dmesg --level=err,warn -T".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -412,6 +705,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "uname -a".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -422,6 +716,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "lscpu".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -441,6 +736,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "top -b -n 1".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -451,6 +747,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "ps aux".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -461,6 +758,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "hostname".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -474,6 +772,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "cat /etc/machine-id".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -495,6 +794,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "cat /sys/class/dmi/id/product_name".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -514,6 +814,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "cat /proc/device-tree/model".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -539,6 +840,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
                     • \"show open ports\" — lists active network connections"
             }),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -561,6 +863,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "iw dev".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -581,6 +884,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "ping -c 3 8.8.8.8".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -606,6 +910,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "gpspipe -w -n 3".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -626,6 +931,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "ip -details link show type can".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -647,6 +953,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "ss -tulnp".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -657,6 +964,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "du -sh /var/log".into(),
             tool_args: json!({}),
             confidence: 0.80,
+            steps: Vec::new(),
         });
     }
 
@@ -670,6 +978,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "lsblk".into(),
             tool_args: json!({}),
             confidence: 0.90,
+            steps: Vec::new(),
         });
     }
 
@@ -683,6 +992,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "date".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -696,6 +1006,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "whoami".into(),
             tool_args: json!({}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
@@ -709,6 +1020,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "systemctl list-units --type=service --state=running --no-pager".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
     }
 
@@ -719,6 +1031,7 @@ fn parse_command(text: &str) -> Option<ParsedIntent> {
             tool_name: "ethtool eth0".into(),
             tool_args: json!({}),
             confidence: 0.80,
+            steps: Vec::new(),
         });
     }
 
@@ -755,6 +1068,7 @@ fn try_parse_pid(text: &str) -> Option<ParsedIntent> {
                 tool_name: "read_pid".into(),
                 tool_args: json!({ "pid": pid }),
                 confidence: 0.92,
+                steps: Vec::new(),
             });
         }
     }
@@ -768,12 +1082,148 @@ fn try_parse_pid(text: &str) -> Option<ParsedIntent> {
             tool_name: "read_pid".into(),
             tool_args: json!({ "pid": pid }),
             confidence: 0.95,
+            steps: Vec::new(),
         });
     }
 
     None
 }
 
+/// Parse raw burst capture requests for a single PID.
+///
+/// Requires an explicit burst/raw-sample keyword so plain "read rpm" still
+/// goes to `read_pid`.
+fn try_parse_pid_burst(text: &str) -> Option<ParsedIntent> {
+    if !matches_any(text, &["burst", "raw sample", "high rate", "high-rate"]) {
+        return None;
+    }
+
+    let pid = try_parse_pid(text)
+        .and_then(|intent| intent.tool_args["pid"].as_str().map(String::from))
+        .or_else(|| {
+            let named = [("rpm", "0x0C"), ("speed", "0x0D"), ("throttle", "0x11")];
+            named
+                .iter()
+                .find(|(k, _)| text.contains(k))
+                .map(|(_, pid)| pid.to_string())
+        })?;
+
+    Some(ParsedIntent {
+        action: ActionKind::Tool,
+        tool_name: "pid_burst".into(),
+        tool_args: json!({ "pid": pid }),
+        confidence: 0.90,
+        steps: Vec::new(),
+    })
+}
+
+/// Parse queries against the device's own command/telemetry journal.
+///
+/// Modifiers: "failed", "unpublished"/"missing", "telemetry"/"command",
+/// and "last N hours|minutes".
+fn try_parse_local_history(text: &str) -> Option<ParsedIntent> {
+    if !matches_any(
+        text,
+        &[
+            "local history",
+            "device history",
+            "command history",
+            "telemetry history",
+            "local journal",
+            "unpublished result",
+            "missing result",
+        ],
+    ) {
+        return None;
+    }
+
+    let mut args = json!({});
+    if text.contains("telemetry") {
+        args["kind"] = json!("telemetry");
+    } else if text.contains("command") {
+        args["kind"] = json!("command");
+    }
+    if text.contains("fail") {
+        args["kind"] = json!("command");
+        args["status"] = json!("failed");
+    }
+    if matches_any(text, &["unpublished", "missing", "not sent", "offline"]) {
+        args["unpublished_only"] = json!(true);
+    }
+    if let Some(minutes) = extract_lookback_minutes(text) {
+        args["since_minutes"] = json!(minutes);
+    }
+
+    Some(ParsedIntent {
+        action: ActionKind::Tool,
+        tool_name: "get_local_history".into(),
+        tool_args: args,
+        confidence: 0.85,
+        steps: Vec::new(),
+    })
+}
+
+/// Parse "send frame <id> <bytes...> [reply <id>] [wait <n>ms]".
+///
+/// Only produced on an explicit "send frame"/"transmit frame" phrase; the
+/// agent rejects the tool unless it runs in bench mode.
+fn try_parse_send_frame(text: &str) -> Option<ParsedIntent> {
+    let rest = [
+        "send can frame",
+        "send frame",
+        "transmit can frame",
+        "transmit frame",
+    ]
+    .iter()
+    .find_map(|p| text.split_once(p).map(|(_, rest)| rest))?;
+
+    let mut words = rest.split_whitespace().peekable();
+    let id = words.next()?;
+    if !id.starts_with("0x") {
+        return None;
+    }
+
+    let mut data = Vec::new();
+    while let Some(w) = words.peek() {
+        let byte = w.trim_start_matches("0x");
+        if byte.len() == 2 && byte.chars().all(|c| c.is_ascii_hexdigit()) {
+            data.push(byte.to_uppercase());
+            words.next();
+        } else {
+            break;
+        }
+    }
+
+    let mut args =
+        json!({ "id": id.to_uppercase().replacen("0X", "0x", 1), "data": data.join(" ") });
+    while let Some(w) = words.next() {
+        match w {
+            "reply" | "expect" => {
+                if let Some(rid) = words.next().filter(|r| r.starts_with("0x")) {
+                    args["reply_id"] = json!(rid.to_uppercase().replacen("0X", "0x", 1));
+                }
+            }
+            "wait" => {
+                if let Some(ms) = words
+                    .next()
+                    .and_then(|m| m.trim_end_matches("ms").parse::<u64>().ok())
+                {
+                    args["wait_ms"] = json!(ms);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(ParsedIntent {
+        action: ActionKind::Tool,
+        tool_name: "send_frame".into(),
+        tool_args: args,
+        confidence: 0.95,
+        steps: Vec::new(),
+    })
+}
+
 /// Extract a known ECU name ("BCR", "BCF") from text (case-insensitive).
 fn extract_ecu_name(text: &str) -> Option<&'static str> {
     if text.contains("bcr") {
@@ -822,6 +1272,23 @@ fn extract_duration(text: &str) -> Option<u32> {
     None
 }
 
+/// Extract a look-back window in minutes from "last 2 hours", "past 30 minutes".
+pub(crate) fn extract_lookback_minutes(text: &str) -> Option<u32> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words.windows(2).find_map(|pair| {
+        let n = pair[0].parse::<u32>().ok()?;
+        if pair[1].starts_with("hour") || pair[1] == "h" {
+            Some(n * 60)
+        } else if pair[1].starts_with("min") {
+            Some(n)
+        } else if pair[1].starts_with("day") {
+            Some(n * 24 * 60)
+        } else {
+            None
+        }
+    })
+}
+
 /// Extract a search query from "search logs for X" or "grep logs X".
 fn extract_search_query(text: &str) -> Option<&str> {
     // "search logs for <query>"
@@ -892,6 +1359,33 @@ mod tests {
         parse_command(text)
     }
 
+    // ── Tool catalog ────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn matches_outside_the_catalog_fall_through() {
+        use zc_protocol::catalog::{ToolCatalog, ToolSpec};
+
+        let engine = RuleBasedEngine::new();
+        let overrides = InferenceOverrides {
+            catalog: Some(ToolCatalog::new(vec![ToolSpec::new(
+                "read_dtcs",
+                "Read DTCs",
+                json!({}),
+            )])),
+            ..Default::default()
+        };
+        let hit = engine.parse_with("read DTCs", &overrides).await.unwrap();
+        assert_eq!(hit.intent.tool_name, "read_dtcs");
+        assert!(engine.parse_with("read VIN", &overrides).await.is_none());
+        // Without a catalog (older agents) every rule applies.
+        assert!(
+            engine
+                .parse_with("read VIN", &InferenceOverrides::default())
+                .await
+                .is_some()
+        );
+    }
+
     // ── DTC commands ────────────────────────────────────────────
 
     #[test]
@@ -913,6 +1407,22 @@ mod tests {
         assert_eq!(intent.tool_name, "read_dtcs");
     }
 
+    #[test]
+    fn parse_dtc_scopes() {
+        let intent = parse("read pending DTCs").unwrap();
+        assert_eq!(intent.tool_name, "read_dtcs");
+        assert_eq!(intent.tool_args["scope"], "pending");
+
+        let intent = parse("show permanent trouble codes").unwrap();
+        assert_eq!(intent.tool_args["scope"], "permanent");
+
+        let intent = parse("read all DTCs").unwrap();
+        assert_eq!(intent.tool_args["scope"], "all");
+
+        let intent = parse("read DTCs").unwrap();
+        assert!(intent.tool_args.get("scope").is_none());
+    }
+
     // ── VIN commands ────────────────────────────────────────────
 
     #[test]
@@ -979,8 +1489,145 @@ mod tests {
         assert_eq!(intent.tool_args["pid"], "0x2F");
     }
 
+    // ── Supported PIDs ──────────────────────────────────────────
+
+    #[test]
+    fn parse_list_supported_pids() {
+        for text in ["list supported PIDs", "which PIDs does the ECU support?"] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "list_supported_pids", "{text}");
+            assert_eq!(intent.tool_args, json!({}));
+        }
+    }
+
+    // ── Vehicle info ────────────────────────────────────────────
+
+    #[test]
+    fn parse_read_vehicle_info() {
+        for text in [
+            "read the calibration IDs",
+            "get CVN for recall check",
+            "what is the ECU name",
+        ] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "read_vehicle_info", "{text}");
+        }
+    }
+
+    // ── Readiness monitors ──────────────────────────────────────
+
+    #[test]
+    fn parse_read_readiness() {
+        for text in [
+            "check emissions readiness",
+            "is the car ready for inspection?",
+            "is the check engine light on",
+        ] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "read_readiness", "{text}");
+            assert_eq!(intent.tool_args, json!({}));
+        }
+    }
+
+    // ── PID burst capture ───────────────────────────────────────
+
+    #[test]
+    fn parse_burst_capture_rpm() {
+        let intent = parse("burst capture rpm").unwrap();
+        assert_eq!(intent.tool_name, "pid_burst");
+        assert_eq!(intent.tool_args["pid"], "0x0C");
+    }
+
+    #[test]
+    fn parse_raw_samples_hex_pid() {
+        let intent = parse("get raw samples for pid 0x11").unwrap();
+        assert_eq!(intent.tool_name, "pid_burst");
+        assert_eq!(intent.tool_args["pid"], "0X11");
+    }
+
+    // ── Local history ───────────────────────────────────────────
+
+    #[test]
+    fn parse_local_history_plain() {
+        let intent = parse("get local history").unwrap();
+        assert_eq!(intent.tool_name, "get_local_history");
+        assert_eq!(intent.tool_args, json!({}));
+    }
+
+    #[test]
+    fn parse_local_history_with_filters() {
+        let intent =
+            parse("show failed commands from device history for the last 2 hours").unwrap();
+        assert_eq!(intent.tool_name, "get_local_history");
+        assert_eq!(intent.tool_args["kind"], "command");
+        assert_eq!(intent.tool_args["status"], "failed");
+        assert_eq!(intent.tool_args["since_minutes"], 120);
+    }
+
+    #[test]
+    fn parse_unpublished_results() {
+        let intent = parse("list unpublished results").unwrap();
+        assert_eq!(intent.tool_name, "get_local_history");
+        assert_eq!(intent.tool_args["unpublished_only"], true);
+    }
+
+    #[test]
+    fn parse_self_test() {
+        for text in [
+            "run a self-test",
+            "self test the device",
+            "verify provisioning",
+        ] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "self_test", "{text}");
+            assert_eq!(intent.tool_args, json!({}));
+        }
+    }
+
+    #[test]
+    fn parse_agent_stats() {
+        for text in [
+            "show agent stats",
+            "which tools are slow on this device?",
+            "tool latency",
+            "any failing tools",
+        ] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "agent_stats", "{text}");
+        }
+    }
+
+    #[test]
+    fn parse_correlate_faults() {
+        for text in [
+            "correlate the dtcs with the logs",
+            "show a fault timeline",
+            "line up trouble codes with syslog",
+        ] {
+            let intent = parse(text).unwrap();
+            let tools: Vec<_> = intent.steps.iter().map(|s| s.tool_name.as_str()).collect();
+            assert_eq!(tools, ["read_dtcs", "correlate_faults"], "{text}");
+            assert_eq!(intent.steps[1].tool_args["dtcs"], "{{steps.0.data}}");
+        }
+    }
+
     // ── CAN monitor ─────────────────────────────────────────────
 
+    #[test]
+    fn parse_send_frame_with_reply() {
+        let intent = parse("send frame 0x7e0 02 10 03 reply 0x7e8 wait 200ms").unwrap();
+        assert_eq!(intent.tool_name, "send_frame");
+        assert_eq!(intent.tool_args["id"], "0x7E0");
+        assert_eq!(intent.tool_args["data"], "02 10 03");
+        assert_eq!(intent.tool_args["reply_id"], "0x7E8");
+        assert_eq!(intent.tool_args["wait_ms"], 200);
+    }
+
+    #[test]
+    fn parse_send_frame_without_id_is_not_matched() {
+        assert!(try_parse_send_frame("send frame please").is_none());
+    }
+
     #[test]
     fn parse_monitor_can() {
         let intent = parse("monitor CAN bus traffic").unwrap();
@@ -995,6 +1642,17 @@ mod tests {
         assert_eq!(intent.tool_args["duration_secs"], 30);
     }
 
+    #[test]
+    fn parse_can_bus_errors() {
+        let intent = parse("check CAN bus errors for 20 seconds").unwrap();
+        assert_eq!(intent.tool_name, "can_monitor");
+        assert_eq!(intent.tool_args["capture_errors"], true);
+        assert_eq!(intent.tool_args["duration_secs"], 20);
+
+        let intent = parse("the bus is flaky, what's going on").unwrap();
+        assert_eq!(intent.tool_args["capture_errors"], true);
+    }
+
     // ── Log commands ────────────────────────────────────────────
 
     #[test]
@@ -1019,10 +1677,33 @@ mod tests {
         assert_eq!(intent.tool_name, "analyze_errors");
     }
 
+    #[test]
+    fn parse_detect_anomalies() {
+        for text in [
+            "any anomalies in the logs?",
+            "is something spiking in syslog",
+        ] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "detect_anomalies", "{text}");
+            assert_eq!(intent.tool_args["path"], "/var/log/syslog");
+        }
+    }
+
     #[test]
     fn parse_log_stats() {
         let intent = parse("show log statistics").unwrap();
         assert_eq!(intent.tool_name, "log_stats");
+        let intent = parse("chart errors over time").unwrap();
+        assert_eq!(intent.tool_name, "log_stats");
+    }
+
+    #[test]
+    fn parse_follow_logs() {
+        for text in ["follow the logs", "show live logs", "tail -f syslog"] {
+            let intent = parse(text).unwrap();
+            assert_eq!(intent.tool_name, "tail_logs", "{text}");
+            assert_eq!(intent.tool_args["follow"], true, "{text}");
+        }
     }
 
     #[test]
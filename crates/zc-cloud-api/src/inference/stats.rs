@@ -0,0 +1,121 @@
+//! Per-tier inference usage and cost counters.
+//!
+//! Engines that track usage report it through
+//! [`InferenceEngine::stats`](super::InferenceEngine::stats), served at
+//! `GET /api/v1/inference/stats`. Counters are kept in memory and start at
+//! zero after a restart.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::cache::CacheStats;
+
+/// Tokens billed for one LLM call and their estimated cost.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Usage of one inference tier since startup.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TierUsage {
+    /// Parses attempted by the tier.
+    pub requests: u64,
+    /// Parses that produced an intent.
+    pub hits: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Estimated spend in US dollars.
+    pub cost_usd: f64,
+    pub avg_latency_ms: f64,
+    #[serde(skip)]
+    latency_ms: u64,
+}
+
+/// Usage report of an inference engine.
+#[derive(Debug, Clone, Serialize)]
+pub struct InferenceStats {
+    /// Configured engine (`tiered`, `bedrock`).
+    pub engine: String,
+    /// Usage per tier (`local`, `cache`, `bedrock`).
+    pub tiers: BTreeMap<String, TierUsage>,
+    /// Sum of the tiers' estimated spend.
+    pub total_cost_usd: f64,
+    /// Cache of cloud results, when enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStats>,
+}
+
+impl InferenceStats {
+    /// Report for `tiers`, totalling their cost.
+    pub fn new(engine: &str, tiers: BTreeMap<String, TierUsage>) -> Self {
+        Self {
+            engine: engine.into(),
+            total_cost_usd: tiers.values().map(|t| t.cost_usd).sum(),
+            tiers,
+            cache: None,
+        }
+    }
+
+    /// Report for an engine that does not track usage.
+    pub fn untracked(engine: &str) -> Self {
+        Self::new(engine, BTreeMap::new())
+    }
+}
+
+/// Running usage counters, one per tier.
+#[derive(Debug, Default)]
+pub struct UsageCounters(Mutex<BTreeMap<String, TierUsage>>);
+
+impl UsageCounters {
+    /// Count one parse by `tier`.
+    pub fn record(&self, tier: &str, hit: bool, latency: Duration, usage: Option<TokenUsage>) {
+        let mut tiers = self.0.lock().unwrap();
+        let entry = tiers.entry(tier.to_string()).or_default();
+        entry.requests += 1;
+        entry.hits += u64::from(hit);
+        entry.latency_ms += latency.as_millis() as u64;
+        entry.avg_latency_ms = entry.latency_ms as f64 / entry.requests as f64;
+        if let Some(usage) = usage {
+            entry.input_tokens += usage.input_tokens;
+            entry.output_tokens += usage.output_tokens;
+            entry.cost_usd += usage.cost_usd;
+        }
+    }
+
+    /// Current usage per tier.
+    pub fn snapshot(&self) -> BTreeMap<String, TierUsage> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_accumulate_per_tier() {
+        let counters = UsageCounters::default();
+        let usage = TokenUsage {
+            input_tokens: 1200,
+            output_tokens: 40,
+            cost_usd: 0.0001,
+        };
+        counters.record("bedrock", true, Duration::from_millis(300), Some(usage));
+        counters.record("bedrock", false, Duration::from_millis(100), Some(usage));
+        counters.record("local", true, Duration::ZERO, None);
+
+        let stats = InferenceStats::new("tiered", counters.snapshot());
+        let bedrock = &stats.tiers["bedrock"];
+        assert_eq!((bedrock.requests, bedrock.hits), (2, 1));
+        assert_eq!((bedrock.input_tokens, bedrock.output_tokens), (2400, 80));
+        assert_eq!(bedrock.avg_latency_ms, 200.0);
+        assert!((stats.total_cost_usd - 0.0002).abs() < 1e-12);
+        assert_eq!(stats.tiers["local"].hits, 1);
+    }
+}
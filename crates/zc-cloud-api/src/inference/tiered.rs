@@ -3,39 +3,108 @@
 //! Tries the local (rule-based) engine first. If it returns `None`,
 //! falls back to the cloud (Bedrock) engine. The actual tier that
 //! produced the result is recorded in `ParseResult.tier`.
+//!
+//! With a cache ([`TieredEngine::with_cache`]), cloud results are reused
+//! for repeated phrasings and reported under the `cache` tier. Requests,
+//! hits and latency are counted per tier; token and cost counters come from
+//! the cloud engine's own [`InferenceEngine::stats`].
+
+use std::time::Instant;
 
 use async_trait::async_trait;
 
-use super::{InferenceEngine, ParseResult};
+use super::cache::{self, CACHE_TIER, CacheConfig, InferenceCache};
+use super::stats::{InferenceStats, UsageCounters};
+use super::{InferenceEngine, InferenceOverrides, ParseResult};
 
 /// Composite engine that tries local inference first, then cloud.
 pub struct TieredEngine {
     local: Box<dyn InferenceEngine>,
     cloud: Box<dyn InferenceEngine>,
+    cache: Option<InferenceCache>,
+    usage: UsageCounters,
 }
 
 impl TieredEngine {
     pub fn new(local: Box<dyn InferenceEngine>, cloud: Box<dyn InferenceEngine>) -> Self {
-        Self { local, cloud }
+        Self {
+            local,
+            cloud,
+            cache: None,
+            usage: UsageCounters::default(),
+        }
+    }
+
+    /// Cache cloud results (no-op when `config` disables caching).
+    pub fn with_cache(mut self, config: CacheConfig) -> Self {
+        self.cache = config.enabled().then(|| InferenceCache::new(config));
+        self
     }
 }
 
 #[async_trait]
 impl InferenceEngine for TieredEngine {
     async fn parse(&self, text: &str) -> Option<ParseResult> {
+        self.parse_with(text, &InferenceOverrides::default()).await
+    }
+
+    async fn parse_with(&self, text: &str, overrides: &InferenceOverrides) -> Option<ParseResult> {
         // Try local first
-        if let Some(result) = self.local.parse(text).await {
-            return Some(result);
+        let started = Instant::now();
+        let result = self.local.parse_with(text, overrides).await;
+        self.usage.record(
+            self.local.tier_name(),
+            result.is_some(),
+            started.elapsed(),
+            None,
+        );
+        if result.is_some() {
+            return result;
+        }
+
+        // Then earlier cloud answers to the same text
+        let key = cache::key(text, overrides);
+        if let Some(cache) = &self.cache {
+            let started = Instant::now();
+            let cached = cache.get(&key, started);
+            self.usage
+                .record(CACHE_TIER, cached.is_some(), started.elapsed(), None);
+            if cached.is_some() {
+                return cached;
+            }
         }
 
         // Fall back to cloud
         tracing::debug!("local inference missed, falling back to cloud");
-        self.cloud.parse(text).await
+        let result = self.cloud.parse_with(text, overrides).await;
+        if let (Some(cache), Some(result)) = (&self.cache, &result) {
+            cache.insert(key, result.clone(), Instant::now());
+        }
+        result
     }
 
     fn tier_name(&self) -> &str {
         "tiered"
     }
+
+    fn stats(&self) -> Option<InferenceStats> {
+        let mut tiers = self.usage.snapshot();
+        let cloud = self.cloud.stats().map(|s| s.tiers).unwrap_or_default();
+        // Average cloud cost per request prices the cache's savings.
+        let (requests, cost) = cloud
+            .values()
+            .fold((0, 0.0), |(n, c), t| (n + t.requests, c + t.cost_usd));
+        tiers.extend(cloud);
+        let mut stats = InferenceStats::new(self.tier_name(), tiers);
+        stats.cache = self.cache.as_ref().map(|cache| {
+            cache.stats(if requests == 0 {
+                0.0
+            } else {
+                cost / requests as f64
+            })
+        });
+        Some(stats)
+    }
 }
 
 #[cfg(test)]
@@ -59,6 +128,7 @@ mod tests {
                         tool_name: tool.into(),
                         tool_args: json!({}),
                         confidence: 0.95,
+                        steps: Vec::new(),
                     },
                     tier: name.into(),
                 }),
@@ -115,4 +185,137 @@ mod tests {
 
         assert!(engine.parse("hello world").await.is_none());
     }
+
+    /// Engine that reports the system prompt it was given as the tool name.
+    struct PromptEcho;
+
+    #[async_trait]
+    impl InferenceEngine for PromptEcho {
+        async fn parse(&self, text: &str) -> Option<ParseResult> {
+            self.parse_with(text, &InferenceOverrides::default()).await
+        }
+
+        async fn parse_with(
+            &self,
+            _text: &str,
+            overrides: &InferenceOverrides,
+        ) -> Option<ParseResult> {
+            Some(ParseResult {
+                intent: ParsedIntent {
+                    action: ActionKind::Tool,
+                    tool_name: overrides.system_prompt.clone().unwrap_or_default(),
+                    tool_args: json!({}),
+                    confidence: 0.9,
+                    steps: Vec::new(),
+                },
+                tier: "cloud".into(),
+            })
+        }
+
+        fn tier_name(&self) -> &str {
+            "cloud"
+        }
+    }
+
+    #[tokio::test]
+    async fn overrides_forwarded_to_cloud() {
+        let engine = TieredEngine::new(Box::new(MockEngine::miss("local")), Box::new(PromptEcho));
+        let overrides = InferenceOverrides {
+            system_prompt: Some("variant-b".into()),
+            ..InferenceOverrides::default()
+        };
+
+        let result = engine.parse_with("hello", &overrides).await.unwrap();
+        assert_eq!(result.intent.tool_name, "variant-b");
+    }
+
+    /// Cloud engine that counts its calls and bills a fixed cost per call.
+    #[derive(Default)]
+    struct BilledCloud {
+        usage: UsageCounters,
+    }
+
+    #[async_trait]
+    impl InferenceEngine for BilledCloud {
+        async fn parse(&self, text: &str) -> Option<ParseResult> {
+            let usage = crate::inference::stats::TokenUsage {
+                input_tokens: 1000,
+                output_tokens: 50,
+                cost_usd: 0.01,
+            };
+            self.usage
+                .record("bedrock", true, std::time::Duration::ZERO, Some(usage));
+            Some(ParseResult {
+                intent: ParsedIntent {
+                    action: ActionKind::Reply,
+                    tool_name: String::new(),
+                    tool_args: json!({ "message": text }),
+                    confidence: 1.0,
+                    steps: Vec::new(),
+                },
+                tier: "bedrock".into(),
+            })
+        }
+
+        fn tier_name(&self) -> &str {
+            "bedrock"
+        }
+
+        fn stats(&self) -> Option<InferenceStats> {
+            Some(InferenceStats::new("bedrock", self.usage.snapshot()))
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_serves_repeated_cloud_queries() {
+        let engine = TieredEngine::new(
+            Box::new(MockEngine::miss("local")),
+            Box::new(BilledCloud::default()),
+        )
+        .with_cache(CacheConfig::default());
+
+        let first = engine.parse("Is the engine OK?").await.unwrap();
+        assert_eq!(first.tier, "bedrock");
+        let second = engine.parse("Is the  engine OK").await.unwrap();
+        assert_eq!(second.tier, "cache");
+        assert_eq!(second.intent.tool_args, first.intent.tool_args);
+
+        // A different prompt is a different question.
+        let overrides = InferenceOverrides {
+            system_prompt: Some("variant-b".into()),
+            ..InferenceOverrides::default()
+        };
+        let third = engine
+            .parse_with("Is the engine OK", &overrides)
+            .await
+            .unwrap();
+        assert_eq!(third.tier, "bedrock");
+
+        let stats = engine.stats().unwrap();
+        assert_eq!(stats.tiers["local"].requests, 3);
+        assert_eq!(stats.tiers["bedrock"].requests, 2);
+        assert_eq!(
+            (stats.tiers["cache"].requests, stats.tiers["cache"].hits),
+            (3, 1)
+        );
+        assert!((stats.total_cost_usd - 0.02).abs() < 1e-12);
+        let cache = stats.cache.unwrap();
+        assert_eq!((cache.entries, cache.hits), (2, 1));
+        assert!((cache.saved_cost_usd - 0.01).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_disables_cache() {
+        let engine = TieredEngine::new(
+            Box::new(MockEngine::miss("local")),
+            Box::new(BilledCloud::default()),
+        )
+        .with_cache(CacheConfig {
+            ttl: std::time::Duration::ZERO,
+            ..CacheConfig::default()
+        });
+        engine.parse("hello").await.unwrap();
+        assert_eq!(engine.parse("hello").await.unwrap().tier, "bedrock");
+        assert!(engine.stats().unwrap().cache.is_none());
+    }
 }
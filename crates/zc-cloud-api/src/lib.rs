@@ -4,11 +4,53 @@
 //! (e.g. `zc-e2e-tests`) can access internal types like `AppState`,
 //! `build_router`, and `InferenceEngine`.
 
+pub mod agent_releases;
+pub mod alert_rules;
+pub mod alerts;
+pub mod analytics;
+pub mod approval;
+pub mod audit;
+pub mod auth;
+pub mod cert_expiry;
+pub mod certificates;
+pub mod claim_codes;
+pub mod cloudevents;
+pub mod command_limits;
+pub mod command_queue;
+pub mod command_retries;
+pub mod command_timeouts;
+pub mod command_timing;
 pub mod config;
+pub mod conversations;
+pub mod cron;
 pub mod db;
+pub mod device_context;
+pub mod device_identity;
+pub mod device_report;
+pub mod device_status;
+pub mod device_tags;
+pub mod dtc_history;
+pub mod dtc_knowledge;
 pub mod error;
+pub mod event_schema;
 pub mod events;
+pub mod experiments;
+pub mod failure_rates;
+pub mod file_transfers;
+pub mod fleets;
+pub mod grpc;
 pub mod inference;
+pub mod metrics;
 pub mod mqtt_bridge;
+pub mod mqtt_quarantine;
+pub mod openapi;
+pub mod preflight;
+pub mod retention;
 pub mod routes;
+pub mod schedules;
+pub mod shadow_reconcile;
 pub mod state;
+pub mod store;
+pub mod structured_mode;
+pub mod templates;
+pub mod webhooks;
@@ -12,7 +12,11 @@ use tracing_subscriber::EnvFilter;
 use zc_cloud_api::config::ApiConfig;
 use zc_cloud_api::inference::InferenceEngine;
 use zc_cloud_api::state::AppState;
-use zc_cloud_api::{db, inference, mqtt_bridge, routes};
+use zc_cloud_api::{
+    alert_rules, auth, cert_expiry, cloudevents, command_limits, command_retries, command_timeouts,
+    db, device_status, failure_rates, fleets, grpc, inference, mqtt_bridge, retention, routes,
+    schedules, webhooks,
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -59,7 +63,13 @@ async fn main() -> anyhow::Result<()> {
                 bedrock_client,
                 bedrock_config,
             ));
-            Arc::new(inference::tiered::TieredEngine::new(local, cloud))
+            let cache = inference::cache::CacheConfig::from_env();
+            tracing::info!(
+                ttl_secs = cache.ttl.as_secs(),
+                max_entries = cache.max_entries,
+                "inference cache configured"
+            );
+            Arc::new(inference::tiered::TieredEngine::new(local, cloud).with_cache(cache))
         }
         other => {
             if other != "local" {
@@ -77,6 +87,12 @@ async fn main() -> anyhow::Result<()> {
     let mut state = if let Ok(database_url) = std::env::var("DATABASE_URL") {
         tracing::info!("connecting to PostgreSQL");
         let pool = db::connect(&database_url).await?;
+        if config.timescaledb_enabled {
+            db::timescale::setup(&pool)
+                .await
+                .map_err(|e| anyhow::anyhow!("TIMESCALEDB_ENABLED: {e}"))?;
+            tracing::info!("telemetry and heartbeats stored in TimescaleDB hypertables");
+        }
         AppState::with_pool(pool, inference)
     } else {
         tracing::warn!("DATABASE_URL not set — using in-memory state with sample data");
@@ -88,76 +104,166 @@ async fn main() -> anyhow::Result<()> {
         "inference engine active"
     );
 
+    let approval = config.approval_policy();
+    if approval.enabled() {
+        tracing::info!(
+            approvers = approval.approvers.len(),
+            tools = ?approval.high_risk_tools,
+            ttl_secs = approval.ttl.num_seconds(),
+            "two-person approval enabled for high-risk commands"
+        );
+    }
+    state.approval = Arc::new(approval);
+
+    let thresholds = config
+        .failure_thresholds()
+        .map_err(|e| anyhow::anyhow!("FAILURE_RATE_*: {e}"))?;
+    tracing::info!(
+        threshold = thresholds.failure_rate,
+        per_tool = ?thresholds.per_tool,
+        min_samples = thresholds.min_samples,
+        window_secs = thresholds.window.num_seconds(),
+        "tool failure-rate alerts configured"
+    );
+    state.failure_rates = Arc::new(failure_rates::FailureRateTracker::new(thresholds));
+
+    let structured_mode = config
+        .structured_mode()
+        .map_err(|e| anyhow::anyhow!("STRUCTURED_ONLY_FLEETS: {e}"))?;
+    if structured_mode.enabled() {
+        tracing::info!(
+            fleets = %config.structured_only_fleets,
+            "structured-only command mode enabled, natural-language commands refused"
+        );
+    }
+    state.structured_mode = Arc::new(structured_mode);
+
+    let limits = config
+        .command_limits()
+        .map_err(|e| anyhow::anyhow!("COMMAND_RATE_LIMIT_*: {e}"))?;
+    tracing::info!(
+        per_device = ?limits.per_device.map(|l| l.to_string()),
+        per_initiator = ?limits.per_initiator.map(|l| l.to_string()),
+        "command rate limits configured"
+    );
+    state.command_limits = Arc::new(command_limits::CommandRateLimiter::new(limits));
+
+    state.status_thresholds = config.status_thresholds().map_err(|e| {
+        anyhow::anyhow!("DEVICE_DEGRADED_AFTER_SECS/DEVICE_OFFLINE_AFTER_SECS: {e}")
+    })?;
+
+    state.retry_policy = config.retry_policy().map_err(|e| {
+        anyhow::anyhow!("COMMAND_RETRY_MAX_ATTEMPTS/COMMAND_RETRY_BACKOFF_SECS: {e}")
+    })?;
+
+    state.retention = config
+        .retention_policy()
+        .map_err(|e| anyhow::anyhow!("TELEMETRY_RETENTION_DAYS/HEARTBEAT_RETENTION_DAYS: {e}"))?;
+    let cloudevents_config = config
+        .cloudevents_config()
+        .map_err(|e| anyhow::anyhow!("CLOUDEVENTS_SINK_URL/CLOUDEVENTS_TYPES: {e}"))?;
+
+    if let Some(oidc) = config
+        .oidc_config()
+        .map_err(|e| anyhow::anyhow!("OIDC_ROLE_MAP/OIDC_DEFAULT_ROLE: {e}"))?
+    {
+        tracing::info!(
+            issuer = %oidc.issuer,
+            audiences = ?oidc.audiences,
+            roles_claim = %oidc.roles_claim,
+            tenant_claim = ?oidc.tenant_claim,
+            "OIDC authentication enabled"
+        );
+        state.oidc = Some(Arc::new(auth::OidcVerifier::new(oidc)));
+    }
+    if let Some(token) = &config.super_admin_token {
+        tracing::info!("super-admin token configured");
+        state.super_admin_token_sha256 = Some(fleets::token_sha256(token));
+    }
+
+    if let Some(ca) = config
+        .certificate_authority()
+        .map_err(|e| anyhow::anyhow!("PROVISIONING_CA_CERT/PROVISIONING_CA_KEY: {e}"))?
+    {
+        tracing::info!(
+            broker = format!("{}:{}", ca.endpoint.broker_host, ca.endpoint.broker_port),
+            validity_days = ca.default_validity_days,
+            "device certificate provisioning enabled"
+        );
+        state.certificate_authority = Some(Arc::new(ca));
+    }
+    state.cert_expiry = Arc::new(cert_expiry::CertExpiryMonitor::new(chrono::Duration::days(
+        config.cert_expiry_warning_days.into(),
+    )));
+
     // Start MQTT bridge if enabled.
     if config.mqtt_enabled {
-        if config.mqtt_fleet_id.is_empty() {
-            anyhow::bail!("MQTT_ENABLED=true but MQTT_FLEET_ID is not set");
-        }
+        let fleets = config
+            .mqtt_fleets()
+            .map_err(|e| anyhow::anyhow!("MQTT_FLEET_ID: {e}"))?
+            .ok_or_else(|| anyhow::anyhow!("MQTT_ENABLED=true but MQTT_FLEET_ID is not set"))?;
+        let subscriptions = fleets.subscription_ids();
 
         tracing::info!(
             broker = format!("{}:{}", config.mqtt_broker_host, config.mqtt_broker_port),
-            fleet_id = %config.mqtt_fleet_id,
+            fleets = ?subscriptions,
             tls = config.mqtt_use_tls,
+            protocol = ?config.mqtt_protocol,
             "connecting to mqtt broker"
         );
 
-        let (channel, eventloop) = if config.mqtt_use_tls {
-            let mqtt_config = zc_mqtt_channel::MqttConfig {
-                broker_host: config.mqtt_broker_host.clone(),
-                broker_port: config.mqtt_broker_port,
-                client_id: "zc-cloud-api".to_string(),
-                use_tls: true,
-                ca_cert_path: config
-                    .mqtt_ca_cert
-                    .clone()
-                    .unwrap_or_else(|| "certs/ca.pem".to_string()),
-                client_cert_path: config
-                    .mqtt_client_cert
-                    .clone()
-                    .unwrap_or_else(|| "certs/client.pem".to_string()),
-                client_key_path: config
-                    .mqtt_client_key
-                    .clone()
-                    .unwrap_or_else(|| "certs/client.key".to_string()),
-                keepalive_secs: 30,
-            };
-            zc_mqtt_channel::MqttChannel::new(&mqtt_config, &config.mqtt_fleet_id, "cloud-api")?
-        } else {
-            zc_mqtt_channel::MqttChannel::new_plaintext(
-                &config.mqtt_broker_host,
-                config.mqtt_broker_port,
-                "zc-cloud-api",
-                &config.mqtt_fleet_id,
-                "cloud-api",
-            )
+        // The cloud publishes to explicit per-device topics, so the
+        // channel's own fleet only names the connection.
+        let channel_fleet = subscriptions[0];
+        // Cert paths are only read when TLS is on.
+        let mqtt_config = zc_mqtt_channel::MqttConfig {
+            broker_host: config.mqtt_broker_host.clone(),
+            broker_port: config.mqtt_broker_port,
+            client_id: "zc-cloud-api".to_string(),
+            use_tls: config.mqtt_use_tls,
+            ca_cert_path: config
+                .mqtt_ca_cert
+                .clone()
+                .unwrap_or_else(|| "certs/ca.pem".to_string()),
+            client_cert_path: config
+                .mqtt_client_cert
+                .clone()
+                .unwrap_or_else(|| "certs/client.pem".to_string()),
+            client_key_path: config
+                .mqtt_client_key
+                .clone()
+                .unwrap_or_else(|| "certs/client.key".to_string()),
+            keepalive_secs: 30,
+            protocol: config.mqtt_protocol,
+            encoding: Default::default(),
+            clean_session: true,
+            // The bridge is not a device: no presence will.
+            last_will: false,
+            qos: Default::default(),
+            offline_buffer: Default::default(),
+            reconnect: Default::default(),
         };
+        let (channel, eventloop) =
+            zc_mqtt_channel::MqttChannel::new(&mqtt_config, channel_fleet, "cloud-api")?;
 
-        // Subscribe to fleet-wide topics.
-        channel
-            .subscribe_fleet_responses()
-            .await
-            .map_err(|e| anyhow::anyhow!("failed to subscribe to fleet responses: {e}"))?;
-        channel
-            .subscribe_fleet_heartbeats()
-            .await
-            .map_err(|e| anyhow::anyhow!("failed to subscribe to fleet heartbeats: {e}"))?;
-        channel
-            .subscribe_fleet_shadow_updates()
-            .await
-            .map_err(|e| anyhow::anyhow!("failed to subscribe to fleet shadow updates: {e}"))?;
-        // Subscribe to all three telemetry sources.
-        for source in &["obd2", "system", "canbus"] {
+        // Subscribe to every device-to-cloud topic of each monitored fleet.
+        for fleet in &subscriptions {
             channel
-                .subscribe_fleet_telemetry(source)
+                .subscribe_bridge(fleet)
                 .await
-                .map_err(|e| {
-                    anyhow::anyhow!("failed to subscribe to fleet telemetry/{source}: {e}")
-                })?;
+                .map_err(|e| anyhow::anyhow!("failed to subscribe to fleet {fleet}: {e}"))?;
         }
+        // Claim requests from devices onboarding with a claim code.
+        channel
+            .subscribe_bootstrap_claims()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to subscribe to bootstrap claims: {e}"))?;
 
         tracing::info!("mqtt subscriptions established");
 
+        state.mqtt_health = Some(channel.health().clone());
         state.mqtt = Some(Arc::new(channel));
+        state.mqtt_fleets = fleets;
 
         // Spawn the bridge event loop.
         let bridge_state = state.clone();
@@ -166,6 +272,95 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("mqtt bridge spawned");
     }
 
+    // Fire recurring commands; spawned after the bridge so dispatches publish.
+    tokio::spawn(schedules::run(state.clone()));
+    tracing::info!(
+        tick_secs = schedules::TICK_SECS,
+        "command scheduler spawned"
+    );
+
+    // Derive degraded/offline from heartbeat age.
+    tokio::spawn(device_status::run(state.clone()));
+    tracing::info!(
+        tick_secs = device_status::TICK_SECS,
+        degraded_after_secs = state.status_thresholds.degraded_after.num_seconds(),
+        offline_after_secs = state.status_thresholds.offline_after.num_seconds(),
+        "device status evaluator spawned"
+    );
+
+    // Load alert rules and watch for heartbeat gaps.
+    tokio::spawn(alert_rules::run(state.clone()));
+    tracing::info!(
+        tick_secs = alert_rules::TICK_SECS,
+        "alert rule evaluator spawned"
+    );
+
+    // Alert on device certificates nearing expiry.
+    tokio::spawn(cert_expiry::run(state.clone()));
+    tracing::info!(
+        tick_secs = cert_expiry::TICK_SECS,
+        warning_days = config.cert_expiry_warning_days,
+        "certificate expiry check spawned"
+    );
+
+    // Prune telemetry and heartbeats past their retention.
+    if state.retention.is_enabled() {
+        tokio::spawn(retention::run(state.clone()));
+        tracing::info!(
+            tick_secs = retention::TICK_SECS,
+            telemetry_days = ?config.telemetry_retention_days,
+            heartbeat_days = ?config.heartbeat_retention_days,
+            "retention job spawned"
+        );
+    }
+
+    // Notify webhook sinks of failed commands, offline devices and critical DTCs.
+    // Time out commands their device never answered.
+    tokio::spawn(command_timeouts::run(state.clone()));
+    tracing::info!(
+        tick_secs = command_timeouts::TICK_SECS,
+        grace_secs = command_timeouts::GRACE_SECS,
+        "command timeout sweeper spawned"
+    );
+
+    // Re-publish commands whose publish to the broker failed.
+    tokio::spawn(command_retries::run(state.clone()));
+    tracing::info!(
+        tick_secs = command_retries::TICK_SECS,
+        max_attempts = state.retry_policy.max_attempts,
+        backoff_secs = state.retry_policy.backoff.num_seconds(),
+        "command retry sweeper spawned"
+    );
+
+    tokio::spawn(webhooks::run(state.clone()));
+    tracing::info!("webhook dispatcher spawned");
+
+    // Publish events as CloudEvents to an external pipeline.
+    if let Some(export) = cloudevents_config {
+        tracing::info!(
+            sink = %export.sink_url,
+            source = %export.source,
+            types = ?export.types,
+            "CloudEvents exporter spawned"
+        );
+        tokio::spawn(cloudevents::run(
+            state.clone(),
+            cloudevents::Exporter::http(export),
+        ));
+    }
+
+    // Typed gRPC API next to the REST one.
+    if let Some(port) = config.grpc_port {
+        let addr: std::net::SocketAddr = format!("{}:{port}", config.host).parse()?;
+        tracing::info!(addr = %addr, "gRPC API listening");
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(state, addr).await {
+                tracing::error!(error = %e, "gRPC API stopped");
+            }
+        });
+    }
+
     let app = routes::build_router(state);
 
     let addr = format!("{}:{}", config.host, config.port);
@@ -0,0 +1,442 @@
+//! Prometheus metrics for the cloud API, served at `GET /metrics`.
+//!
+//! Counters and histograms are kept in-process and rendered in the
+//! Prometheus text exposition format on scrape:
+//!
+//! - `zc_commands_total{status}` — commands reaching each status
+//!   (stored as pending/queued, final responses, cancellations)
+//! - `zc_commands_throttled_total{scope}` — commands refused by a
+//!   submission rate limit (`device` or `initiator`)
+//! - `zc_inference_total{tier}` — NL parses per inference tier
+//!   (`unparsed` when no tier produced an intent)
+//! - `zc_mqtt_messages_total{fleet,category}` — bridge publishes per fleet
+//!   and topic category
+//! - `zc_mqtt_dropped_total{reason}` — bridge publishes dropped before parsing
+//! - `zc_mqtt_parsed_total{category}` / `zc_mqtt_parse_failures_total{category}`
+//!   — publishes whose payload did / did not deserialize (failures are
+//!   quarantined, see [`crate::mqtt_quarantine`])
+//! - `zc_mqtt_reconnects_total` / `zc_mqtt_reconnect_storms_total` — bridge
+//!   reconnects, and reconnect storms (see [`zc_mqtt_channel::reconnect`])
+//! - `zc_websocket_clients` — connected WebSocket clients
+//! - `zc_db_query_duration_seconds{op}` — database call latency
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// Upper bounds (seconds) of the DB latency histogram buckets.
+const DB_BUCKETS: [f64; 11] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Process-wide metric registry, shared through `AppState`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    commands: LabeledCounter,
+    commands_throttled: LabeledCounter,
+    inference: LabeledCounter,
+    mqtt_messages: LabeledCounter,
+    mqtt_dropped: LabeledCounter,
+    mqtt_parsed: LabeledCounter,
+    mqtt_parse_failures: LabeledCounter,
+    mqtt_reconnects: AtomicU64,
+    mqtt_reconnect_storms: AtomicU64,
+    websocket_clients: AtomicI64,
+    db_latency: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+/// Counter keyed by its label values (one value per label name).
+#[derive(Debug, Default)]
+struct LabeledCounter(Mutex<BTreeMap<Vec<String>, u64>>);
+
+impl LabeledCounter {
+    fn inc(&self, values: &[&str]) {
+        let mut counts = self.0.lock().unwrap();
+        let existing = counts
+            .iter_mut()
+            .find(|(key, _)| key.iter().map(String::as_str).eq(values.iter().copied()));
+        match existing {
+            Some((_, n)) => *n += 1,
+            None => {
+                counts.insert(values.iter().map(|v| v.to_string()).collect(), 1);
+            }
+        }
+    }
+
+    /// Totals per value of label `index`, summed over the other labels.
+    fn totals_by(&self, index: usize) -> BTreeMap<String, u64> {
+        let mut totals = BTreeMap::new();
+        for (values, n) in self.0.lock().unwrap().iter() {
+            if let Some(value) = values.get(index) {
+                *totals.entry(value.clone()).or_default() += n;
+            }
+        }
+        totals
+    }
+
+    fn get(&self, values: &[&str]) -> u64 {
+        let key: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        self.0.lock().unwrap().get(&key).copied().unwrap_or(0)
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str, labels: &[&str]) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for (values, n) in self.0.lock().unwrap().iter() {
+            let pairs: Vec<String> = labels
+                .iter()
+                .zip(values)
+                .map(|(label, value)| format!("{label}=\"{}\"", escape(value)))
+                .collect();
+            let _ = writeln!(out, "{name}{{{}}} {n}", pairs.join(","));
+        }
+    }
+}
+
+/// Cumulative histogram over [`DB_BUCKETS`].
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    buckets: [u64; DB_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(DB_BUCKETS) {
+            if secs <= le {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A command reached `status` (wire name, e.g. `"completed"`).
+    pub fn command_status(&self, status: &str) {
+        self.commands.inc(&[status]);
+    }
+
+    /// A command was refused by the `scope` submission rate limit.
+    pub fn command_throttled(&self, scope: &str) {
+        self.commands_throttled.inc(&[scope]);
+    }
+
+    /// A command was parsed by `tier`, or not parsed at all (`None`).
+    pub fn inference(&self, tier: Option<&str>) {
+        self.inference.inc(&[tier.unwrap_or("unparsed")]);
+    }
+
+    /// The bridge accepted a publish from `fleet` in topic `category`.
+    pub fn mqtt_message(&self, fleet: &str, category: &str) {
+        self.mqtt_messages.inc(&[fleet, category]);
+    }
+
+    /// The bridge dropped a publish before parsing it.
+    pub fn mqtt_dropped(&self, reason: &str) {
+        self.mqtt_dropped.inc(&[reason]);
+    }
+
+    /// A publish in topic `category` was deserialized and handled.
+    pub fn mqtt_parsed(&self, category: &str) {
+        self.mqtt_parsed.inc(&[category]);
+    }
+
+    /// A publish in topic `category` failed to deserialize.
+    pub fn mqtt_parse_failed(&self, category: &str) {
+        self.mqtt_parse_failures.inc(&[category]);
+    }
+
+    /// The bridge reconnected to the broker.
+    pub fn mqtt_reconnect(&self) {
+        self.mqtt_reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The bridge's reconnects turned into a storm.
+    pub fn mqtt_reconnect_storm(&self) {
+        self.mqtt_reconnect_storms.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a WebSocket client for as long as the returned guard lives.
+    pub fn websocket_client(&self) -> WebSocketGuard<'_> {
+        self.websocket_clients.fetch_add(1, Ordering::Relaxed);
+        WebSocketGuard(self)
+    }
+
+    pub fn websocket_clients(&self) -> i64 {
+        self.websocket_clients.load(Ordering::Relaxed)
+    }
+
+    /// Record the latency of database call `op`.
+    pub fn observe_db(&self, op: &'static str, secs: f64) {
+        self.db_latency
+            .lock()
+            .unwrap()
+            .entry(op)
+            .or_default()
+            .observe(secs);
+    }
+
+    /// Await `fut` and record its latency as database call `op`.
+    pub async fn time_db<T>(&self, op: &'static str, fut: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let result = fut.await;
+        self.observe_db(op, start.elapsed().as_secs_f64());
+        result
+    }
+
+    /// Commands counted with `status` so far.
+    pub fn commands_with_status(&self, status: &str) -> u64 {
+        self.commands.get(&[status])
+    }
+
+    /// Bridge publishes counted for `fleet` and `category` so far.
+    pub fn mqtt_messages(&self, fleet: &str, category: &str) -> u64 {
+        self.mqtt_messages.get(&[fleet, category])
+    }
+
+    /// Received / parsed / failed publishes per topic category.
+    pub fn mqtt_topic_stats(&self) -> Vec<TopicStats> {
+        let received = self.mqtt_messages.totals_by(1);
+        let parsed = self.mqtt_parsed.totals_by(0);
+        let failed = self.mqtt_parse_failures.totals_by(0);
+        let mut categories: Vec<&String> = received.keys().chain(failed.keys()).collect();
+        categories.sort();
+        categories.dedup();
+        categories
+            .into_iter()
+            .map(|category| TopicStats {
+                category: category.clone(),
+                received: received.get(category).copied().unwrap_or(0),
+                parsed: parsed.get(category).copied().unwrap_or(0),
+                failed: failed.get(category).copied().unwrap_or(0),
+            })
+            .collect()
+    }
+
+    /// Prometheus text exposition of every metric.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.commands.render(
+            &mut out,
+            "zc_commands_total",
+            "Commands reaching each status.",
+            &["status"],
+        );
+        self.commands_throttled.render(
+            &mut out,
+            "zc_commands_throttled_total",
+            "Commands refused by a submission rate limit, by scope.",
+            &["scope"],
+        );
+        self.inference.render(
+            &mut out,
+            "zc_inference_total",
+            "Natural-language parses per inference tier.",
+            &["tier"],
+        );
+        self.mqtt_messages.render(
+            &mut out,
+            "zc_mqtt_messages_total",
+            "MQTT publishes handled by the bridge, by fleet and topic category.",
+            &["fleet", "category"],
+        );
+        self.mqtt_dropped.render(
+            &mut out,
+            "zc_mqtt_dropped_total",
+            "MQTT publishes dropped by the bridge before parsing.",
+            &["reason"],
+        );
+        self.mqtt_parsed.render(
+            &mut out,
+            "zc_mqtt_parsed_total",
+            "MQTT publishes whose payload deserialized, by topic category.",
+            &["category"],
+        );
+        self.mqtt_parse_failures.render(
+            &mut out,
+            "zc_mqtt_parse_failures_total",
+            "MQTT publishes quarantined because their payload failed to deserialize.",
+            &["category"],
+        );
+
+        for (name, help, value) in [
+            (
+                "zc_mqtt_reconnects_total",
+                "MQTT bridge reconnects to the broker.",
+                &self.mqtt_reconnects,
+            ),
+            (
+                "zc_mqtt_reconnect_storms_total",
+                "MQTT bridge reconnect storms (too many reconnects within the storm window).",
+                &self.mqtt_reconnect_storms,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP zc_websocket_clients Connected WebSocket clients."
+        );
+        let _ = writeln!(out, "# TYPE zc_websocket_clients gauge");
+        let _ = writeln!(out, "zc_websocket_clients {}", self.websocket_clients());
+
+        let name = "zc_db_query_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} Database call latency.");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (op, h) in self.db_latency.lock().unwrap().iter() {
+            for (n, le) in h.buckets.iter().zip(DB_BUCKETS) {
+                let _ = writeln!(out, "{name}_bucket{{op=\"{op}\",le=\"{le}\"}} {n}");
+            }
+            let _ = writeln!(out, "{name}_bucket{{op=\"{op}\",le=\"+Inf\"}} {}", h.count);
+            let _ = writeln!(out, "{name}_sum{{op=\"{op}\"}} {}", h.sum);
+            let _ = writeln!(out, "{name}_count{{op=\"{op}\"}} {}", h.count);
+        }
+        out
+    }
+}
+
+/// Bridge counters for one topic category. `received` counts every
+/// admitted publish, so it also includes topics without a handler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TopicStats {
+    pub category: String,
+    pub received: u64,
+    pub parsed: u64,
+    pub failed: u64,
+}
+
+/// Keeps a WebSocket client counted; decrements the gauge on drop.
+pub struct WebSocketGuard<'a>(&'a Metrics);
+
+impl Drop for WebSocketGuard<'_> {
+    fn drop(&mut self) {
+        self.0.websocket_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Escape a label value for the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_render_with_labels() {
+        let metrics = Metrics::new();
+        metrics.command_status("completed");
+        metrics.command_status("completed");
+        metrics.command_status("queued");
+        metrics.inference(Some("local"));
+        metrics.inference(None);
+        metrics.mqtt_message("fleet-alpha", "heartbeat");
+        metrics.mqtt_message("fleet-beta", "heartbeat");
+        metrics.mqtt_dropped("oversized");
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE zc_commands_total counter"));
+        assert!(text.contains("zc_commands_total{status=\"completed\"} 2"));
+        assert!(text.contains("zc_commands_total{status=\"queued\"} 1"));
+        assert!(text.contains("zc_inference_total{tier=\"unparsed\"} 1"));
+        assert!(
+            text.contains("zc_mqtt_messages_total{fleet=\"fleet-alpha\",category=\"heartbeat\"} 1")
+        );
+        assert_eq!(metrics.mqtt_messages("fleet-beta", "heartbeat"), 1);
+        assert!(text.contains("zc_mqtt_dropped_total{reason=\"oversized\"} 1"));
+        assert!(text.contains("zc_mqtt_reconnects_total 0"));
+    }
+
+    #[test]
+    fn topic_stats_sum_fleets_per_category() {
+        let metrics = Metrics::new();
+        metrics.mqtt_message("fleet-alpha", "heartbeat");
+        metrics.mqtt_message("fleet-beta", "heartbeat");
+        metrics.mqtt_message("fleet-alpha", "telemetry");
+        metrics.mqtt_parsed("heartbeat");
+        metrics.mqtt_parse_failed("heartbeat");
+        metrics.mqtt_parse_failed("telemetry");
+
+        assert_eq!(
+            metrics.mqtt_topic_stats(),
+            [
+                TopicStats {
+                    category: "heartbeat".into(),
+                    received: 2,
+                    parsed: 1,
+                    failed: 1,
+                },
+                TopicStats {
+                    category: "telemetry".into(),
+                    received: 1,
+                    parsed: 0,
+                    failed: 1,
+                },
+            ]
+        );
+        assert!(
+            metrics
+                .render()
+                .contains("zc_mqtt_parse_failures_total{category=\"telemetry\"} 1")
+        );
+    }
+
+    #[test]
+    fn websocket_gauge_follows_guards() {
+        let metrics = Metrics::new();
+        let a = metrics.websocket_client();
+        let b = metrics.websocket_client();
+        assert_eq!(metrics.websocket_clients(), 2);
+        drop(a);
+        assert!(metrics.render().contains("zc_websocket_clients 1"));
+        drop(b);
+        assert_eq!(metrics.websocket_clients(), 0);
+    }
+
+    #[test]
+    fn db_histogram_is_cumulative() {
+        let metrics = Metrics::new();
+        metrics.observe_db("commands.insert", 0.003);
+        metrics.observe_db("commands.insert", 0.2);
+
+        let text = metrics.render();
+        let op = "op=\"commands.insert\"";
+        assert!(text.contains(&format!(
+            "zc_db_query_duration_seconds_bucket{{{op},le=\"0.001\"}} 0"
+        )));
+        assert!(text.contains(&format!(
+            "zc_db_query_duration_seconds_bucket{{{op},le=\"0.005\"}} 1"
+        )));
+        assert!(text.contains(&format!(
+            "zc_db_query_duration_seconds_bucket{{{op},le=\"0.25\"}} 2"
+        )));
+        assert!(text.contains(&format!(
+            "zc_db_query_duration_seconds_bucket{{{op},le=\"+Inf\"}} 2"
+        )));
+        assert!(text.contains(&format!("zc_db_query_duration_seconds_count{{{op}}} 2")));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -1,34 +1,139 @@
 //! MQTT bridge — subscribes to device messages and dispatches them
 //! through the existing API logic (heartbeat, response, telemetry).
 
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
 use chrono::Utc;
-use rumqttc::{Event, Packet, QoS};
 
-use zc_protocol::commands::CommandResponse;
-use zc_protocol::device::Heartbeat;
-use zc_protocol::shadows::{ShadowDelta, ShadowUpdate};
-use zc_protocol::telemetry::TelemetryBatch;
+use zc_mqtt_channel::{ChannelEvent, ConnectionMonitor, MqttEventLoop, ReconnectConfig};
+use zc_protocol::bootstrap::ClaimRequest;
+use zc_protocol::commands::{CommandResponse, CommandResponseChunk};
+use zc_protocol::device::{DevicePresence, DeviceStatus, Heartbeat, HeartbeatView};
+use zc_protocol::encoding::{Encoding, EncodingError, decode};
+use zc_protocol::self_test::SelfTestReport;
+use zc_protocol::shadows::{ShadowDocument, ShadowGetRequest, ShadowUpdate, UPDATE_SHADOW};
+use zc_protocol::telemetry::{TelemetryBatch, ThresholdEvent};
 use zc_protocol::topics;
 
+use crate::db::telemetry::TelemetryRow;
+use crate::device_status::{StatusChange, StatusReason, status_name};
 use crate::events::WsEvent;
+use crate::routes::devices::parse_device_status;
 use crate::state::AppState;
+use crate::store::{RespondedCommand, command_status_name};
+
+/// Largest heartbeat payload accepted; real heartbeats are ~300 bytes.
+pub const MAX_HEARTBEAT_BYTES: usize = 4 * 1024;
+/// Largest telemetry batch accepted.
+pub const MAX_TELEMETRY_BYTES: usize = 512 * 1024;
+/// Largest command response accepted (DTC lists and log excerpts).
+pub const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+/// Limit for every other topic category.
+pub const MAX_OTHER_BYTES: usize = 64 * 1024;
+
+/// Payload size limit for a topic category, checked before parsing.
+pub fn max_payload_bytes(category: &str) -> usize {
+    match category {
+        "heartbeat" => MAX_HEARTBEAT_BYTES,
+        "telemetry" => MAX_TELEMETRY_BYTES,
+        "command" => MAX_RESPONSE_BYTES,
+        _ => MAX_OTHER_BYTES,
+    }
+}
+
+/// Fleets the bridge monitors (`MQTT_FLEET_ID`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FleetFilter {
+    /// Every fleet on the broker (`*`).
+    #[default]
+    All,
+    /// Only the listed fleets.
+    Only(BTreeSet<String>),
+}
+
+impl FleetFilter {
+    /// Parse a comma-separated fleet list, or `*` for all fleets.
+    /// Returns `None` for an empty list.
+    pub fn parse(list: &str) -> Result<Option<Self>, String> {
+        let mut fleets = BTreeSet::new();
+        for fleet in list.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if fleet == "*" {
+                return Ok(Some(Self::All));
+            }
+            if fleet.contains(['/', '+', '#']) {
+                return Err(format!("invalid fleet id '{fleet}'"));
+            }
+            fleets.insert(fleet.to_string());
+        }
+        Ok((!fleets.is_empty()).then_some(Self::Only(fleets)))
+    }
+
+    pub fn admits(&self, fleet_id: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(fleets) => fleets.contains(fleet_id),
+        }
+    }
+
+    /// Fleet IDs to pass to [`topics::bridge_subscriptions`].
+    pub fn subscription_ids(&self) -> Vec<&str> {
+        match self {
+            Self::All => vec![topics::ALL_FLEETS],
+            Self::Only(fleets) => fleets.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Buffers reused across messages by the bridge loop so the hot path
+/// doesn't reallocate them per publish.
+#[derive(Debug, Default)]
+pub struct BridgeScratch {
+    telemetry_rows: Vec<TelemetryRow>,
+}
 
 /// Run the MQTT bridge event loop.
 ///
-/// Drives the rumqttc `EventLoop`, classifying incoming publishes and
+/// Drives the channel's [`MqttEventLoop`] (MQTT 3.1.1 or 5), classifying incoming publishes and
 /// dispatching them through the same business logic as the HTTP endpoints.
-pub async fn run(mut eventloop: rumqttc::EventLoop, state: AppState) {
+/// Connects and errors are reported to `state.mqtt_health`, which sets the
+/// reconnect backoff.
+pub async fn run(mut eventloop: MqttEventLoop, state: AppState) {
     tracing::info!("mqtt bridge started");
+    let mut scratch = BridgeScratch::default();
+    let health = state
+        .mqtt_health
+        .clone()
+        .unwrap_or_else(|| Arc::new(ConnectionMonitor::new(ReconnectConfig::default())));
 
     loop {
         match eventloop.poll().await {
-            Ok(Event::Incoming(Packet::Publish(publish))) => {
-                handle_incoming(&publish.topic, &publish.payload, &state).await;
+            Ok(ChannelEvent::Publish { publish, .. }) => {
+                handle_incoming_with(&publish.topic, &publish.payload, &state, &mut scratch).await;
+            }
+            Ok(ChannelEvent::Connected { .. }) => {
+                let outcome = health.on_connected();
+                if outcome.reconnect {
+                    state.metrics.mqtt_reconnect();
+                    tracing::info!("mqtt bridge reconnected");
+                }
+                if outcome.storm_started {
+                    state.metrics.mqtt_reconnect_storm();
+                    tracing::warn!("mqtt reconnect storm: bridge connection keeps dropping");
+                }
+            }
+            Ok(ChannelEvent::Rejected(e)) => {
+                tracing::warn!(error = %e, "mqtt broker rejected a request");
             }
-            Ok(_) => {} // ConnAck, SubAck, PingResp, etc.
+            Ok(ChannelEvent::Other) => {} // acks, pings, etc.
             Err(e) => {
-                tracing::error!(error = %e, "mqtt event loop error — reconnecting in 5s");
-                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                let delay = health.on_error(&e);
+                tracing::error!(
+                    error = %e,
+                    retry_in_ms = delay.as_millis() as u64,
+                    "mqtt event loop error — reconnecting"
+                );
+                tokio::time::sleep(delay).await;
             }
         }
     }
@@ -36,98 +141,266 @@ pub async fn run(mut eventloop: rumqttc::EventLoop, state: AppState) {
 
 /// Classify and handle an incoming MQTT publish.
 pub async fn handle_incoming(topic: &str, payload: &[u8], state: &AppState) {
-    let Some(parsed) = topics::parse_topic(topic) else {
+    handle_incoming_with(topic, payload, state, &mut BridgeScratch::default()).await;
+}
+
+/// [`handle_incoming`] with caller-owned scratch buffers.
+pub async fn handle_incoming_with(
+    topic: &str,
+    payload: &[u8],
+    state: &AppState,
+    scratch: &mut BridgeScratch,
+) {
+    if let Some((hardware_id, action)) = topics::parse_bootstrap_topic(topic) {
+        handle_bootstrap(hardware_id, action, payload, state).await;
+        return;
+    }
+    let Some(parsed) = topics::parse_topic_ref(topic) else {
         tracing::debug!(topic = topic, "ignoring unknown mqtt topic");
+        state.metrics.mqtt_dropped("unknown_topic");
         return;
     };
 
-    match (parsed.category.as_str(), parsed.action.as_str()) {
-        ("command", "response") => {
-            handle_command_response(payload, state).await;
+    if !state.mqtt_fleets.admits(parsed.fleet_id) {
+        tracing::debug!(topic = topic, "ignoring publish from unmonitored fleet");
+        state.metrics.mqtt_dropped("fleet_not_monitored");
+        return;
+    }
+
+    let limit = max_payload_bytes(parsed.category);
+    if payload.len() > limit {
+        tracing::warn!(
+            topic = topic,
+            size = payload.len(),
+            limit = limit,
+            "dropping oversized mqtt payload"
+        );
+        state.metrics.mqtt_dropped("oversized");
+        return;
+    }
+    state.metrics.mqtt_message(parsed.fleet_id, parsed.category);
+
+    match dispatch(&parsed, payload, state, scratch).await {
+        Ok(true) => state.metrics.mqtt_parsed(parsed.category),
+        Ok(false) => {}
+        Err(e) => {
+            tracing::warn!(
+                topic = topic,
+                error = %e,
+                "malformed mqtt payload, quarantined"
+            );
+            state.metrics.mqtt_parse_failed(parsed.category);
+            state
+                .mqtt_quarantine
+                .add(topic, &parsed, payload, e.to_string());
         }
-        ("heartbeat", "ping") => {
-            handle_heartbeat(payload, state).await;
+    }
+}
+
+/// Answer a claim request from a device onboarding with a claim code.
+///
+/// Bootstrap topics are outside the fleet hierarchy: the fleet filter is
+/// applied to the claim code's fleet instead ([`crate::claim_codes::redeem`]).
+/// The result is always JSON, as no encoding has been negotiated yet.
+async fn handle_bootstrap(hardware_id: &str, action: &str, payload: &[u8], state: &AppState) {
+    if action != "claim" {
+        return;
+    }
+    if payload.len() > MAX_OTHER_BYTES {
+        state.metrics.mqtt_dropped("oversized");
+        return;
+    }
+    state.metrics.mqtt_message("bootstrap", "bootstrap");
+    let request: ClaimRequest = match decode(payload) {
+        Ok(request) => request,
+        Err(e) => {
+            tracing::warn!(hardware_id, error = %e, "malformed claim request");
+            state.metrics.mqtt_parse_failed("bootstrap");
+            return;
+        }
+    };
+    state.metrics.mqtt_parsed("bootstrap");
+    let result = match crate::claim_codes::redeem(state, hardware_id, &request).await {
+        Ok(Some(result)) => result,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!(hardware_id, error = %e, "failed to redeem claim code");
+            return;
+        }
+    };
+    let Some(mqtt) = &state.mqtt else {
+        return;
+    };
+    let Ok(bytes) = serde_json::to_vec(&result) else {
+        return;
+    };
+    if let Err(e) = mqtt
+        .publish(
+            &topics::bootstrap_result(hardware_id),
+            &bytes,
+            rumqttc::QoS::AtLeastOnce,
+        )
+        .await
+    {
+        tracing::error!(hardware_id, error = %e, "failed to publish claim result");
+    }
+}
+
+/// Re-run a quarantined publish through its handler, e.g. after a protocol
+/// fix. The message leaves the quarantine once it parses; otherwise its
+/// error is updated. Counters are not touched: they describe live traffic.
+pub async fn replay(state: &AppState, id: uuid::Uuid) -> Option<Result<(), String>> {
+    let message = state.mqtt_quarantine.get(id)?;
+    let Some(parsed) = topics::parse_topic_ref(&message.topic) else {
+        return Some(Err(format!("unknown topic {}", message.topic)));
+    };
+    let result = dispatch(&parsed, &message.raw, state, &mut BridgeScratch::default())
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+    match &result {
+        Ok(()) => {
+            state.mqtt_quarantine.remove(id);
+            tracing::info!(topic = %message.topic, id = %id, "quarantined mqtt payload replayed");
         }
+        Err(e) => state.mqtt_quarantine.replay_failed(id, e.clone()),
+    }
+    Some(result)
+}
+
+/// Hand a publish to its topic's handler. `Ok(false)` when no handler
+/// exists for the topic; `Err` when the payload does not deserialize.
+async fn dispatch(
+    parsed: &topics::TopicRef<'_>,
+    payload: &[u8],
+    state: &AppState,
+    scratch: &mut BridgeScratch,
+) -> Result<bool, EncodingError> {
+    match (parsed.category, parsed.action) {
+        ("command", "response") => handle_command_response(payload, state).await?,
+        ("command", "stream") => handle_response_chunk(payload, state)?,
+        ("heartbeat", "ping") => handle_heartbeat(parsed.fleet_id, payload, state).await?,
         ("telemetry", _source) => {
-            if let Some(device_id) = &parsed.device_id {
-                handle_telemetry(device_id, payload, state).await;
-            }
+            let Some(device_id) = parsed.device_id else {
+                return Ok(false);
+            };
+            handle_telemetry(
+                parsed.fleet_id,
+                device_id,
+                payload,
+                state,
+                &mut scratch.telemetry_rows,
+            )
+            .await?;
         }
         ("shadow", "update") => {
-            if let Some(device_id) = &parsed.device_id {
-                handle_shadow_update(&parsed.fleet_id, device_id, payload, state).await;
-            }
+            let Some(device_id) = parsed.device_id else {
+                return Ok(false);
+            };
+            handle_shadow_update(parsed.fleet_id, device_id, payload, state).await?;
+        }
+        ("shadow", "get") => {
+            let Some(device_id) = parsed.device_id else {
+                return Ok(false);
+            };
+            handle_shadow_get(parsed.fleet_id, device_id, payload, state).await?;
+        }
+        ("selftest", "report") => handle_self_test_report(payload, state).await?,
+        ("alert", "notify") => handle_threshold_event(parsed.fleet_id, payload, state).await?,
+        ("transfer", "ack") => crate::file_transfers::ack_received(state, payload).await?,
+        ("transfer", "upload") => {
+            let Some(device_id) = parsed.device_id else {
+                return Ok(false);
+            };
+            crate::file_transfers::upload_received(state, device_id, payload).await?;
+        }
+        ("transfer", "complete") => {
+            crate::file_transfers::complete_received(state, payload).await?;
+        }
+        ("status", "presence") => {
+            let Some(device_id) = parsed.device_id else {
+                return Ok(false);
+            };
+            handle_presence(device_id, payload, state).await?;
         }
         _ => {
             tracing::debug!(
-                topic = topic,
+                fleet_id = parsed.fleet_id,
                 category = parsed.category,
                 action = parsed.action,
                 "ignoring unhandled mqtt topic"
             );
+            return Ok(false);
         }
     }
+    Ok(true)
+}
+
+/// Relay a streamed partial result to WebSocket clients.
+///
+/// Chunks are not persisted — the final response on `command/response`
+/// remains the record of the command's outcome.
+fn handle_response_chunk(payload: &[u8], state: &AppState) -> Result<(), EncodingError> {
+    let chunk: CommandResponseChunk = decode(payload)?;
+
+    tracing::debug!(command_id = %chunk.command_id, seq = chunk.seq, "mqtt response chunk received");
+
+    let _ = state.event_tx.send(WsEvent::CommandResponseChunk {
+        command_id: chunk.command_id,
+        device_id: chunk.device_id,
+        seq: chunk.seq,
+        data: chunk.data,
+        sent_at: chunk.sent_at,
+    });
+    Ok(())
 }
 
 /// Handle an incoming command response from a device.
-async fn handle_command_response(payload: &[u8], state: &AppState) {
-    let resp: CommandResponse = match serde_json::from_slice(payload) {
-        Ok(r) => r,
-        Err(e) => {
-            tracing::warn!(error = %e, "failed to parse command response payload");
-            return;
-        }
-    };
+async fn handle_command_response(payload: &[u8], state: &AppState) -> Result<(), EncodingError> {
+    let mut resp: CommandResponse = decode(payload)?;
+    let receipt = crate::command_timing::Receipt::now(state, &resp.device_id).await;
+    crate::dtc_knowledge::enrich(state, &mut resp.response_data).await;
 
     let command_id = resp.command_id;
-    let status_str = serde_json::to_value(resp.status)
-        .ok()
-        .and_then(|v| v.as_str().map(String::from))
-        .unwrap_or_else(|| format!("{:?}", resp.status).to_lowercase());
-
+    let status_str = command_status_name(resp.status);
     let inference_tier_str = serde_json::to_value(resp.inference_tier)
         .ok()
         .and_then(|v| v.as_str().map(String::from));
 
-    if let Some(pool) = &state.pool {
-        let row = match crate::db::commands::get_by_id(pool, command_id).await {
-            Ok(Some(row)) => row,
-            Ok(None) => {
-                tracing::warn!(command_id = %command_id, "mqtt response for unknown command");
-                return;
-            }
-            Err(e) => {
-                tracing::error!(error = %e, "db error looking up command");
-                return;
-            }
-        };
-
-        let latency_ms = (resp.responded_at - row.created_at).num_milliseconds();
-
-        if let Err(e) = crate::db::commands::update_response(
-            pool,
-            command_id,
-            &status_str,
-            inference_tier_str.as_deref().unwrap_or("unknown"),
-            resp.response_text.as_deref(),
-            resp.response_data.as_ref(),
-            latency_ms,
-            resp.error.as_deref(),
-        )
-        .await
-        {
-            tracing::error!(error = %e, "failed to update command response in db");
-            return;
+    let RespondedCommand {
+        fleet_id,
+        initiated_by,
+        tool_args,
+        intent,
+    } = match state.store.record_response(&resp, &receipt).await {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            tracing::warn!(command_id = %command_id, "mqtt response for unknown command");
+            return Ok(());
         }
-    } else {
-        let mut commands = state.commands.write().await;
-        if let Some(record) = commands.iter_mut().find(|r| r.envelope.id == command_id) {
-            record.response = Some(resp.clone());
-        } else {
-            tracing::warn!(command_id = %command_id, "mqtt response for unknown command (in-memory)");
-            return;
+        Err(e) => {
+            tracing::error!(error = %e, "failed to record command response");
+            return Ok(());
         }
-    }
+    };
+
+    crate::experiments::record_outcome(state, command_id, resp.status).await;
+    crate::dtc_history::record(state, &fleet_id, &resp, tool_args.as_ref()).await;
+    crate::failure_rates::record(
+        state,
+        &fleet_id,
+        command_id,
+        &resp.device_id,
+        intent.as_ref(),
+        resp.status,
+        resp.error.as_deref(),
+    )
+    .await;
+    crate::alert_rules::command_responded(state, &fleet_id, &resp).await;
+    crate::audit::shell_responded(state, &initiated_by, &fleet_id, intent.as_ref(), &resp).await;
+    crate::audit::can_frame_responded(state, &initiated_by, &fleet_id, intent.as_ref(), &resp)
+        .await;
+    state.metrics.command_status(&status_str);
 
     tracing::info!(command_id = %command_id, status = %status_str, "mqtt command response ingested");
 
@@ -139,126 +412,216 @@ async fn handle_command_response(payload: &[u8], state: &AppState) {
         response_text: resp.response_text,
         response_data: resp.response_data,
         error: resp.error,
+        error_code: resp.error_code.map(|c| c.as_str().to_string()),
         latency_ms: Some(resp.latency_ms as i64),
         responded_at: Utc::now(),
     });
+    Ok(())
+}
+
+/// Store a device self-test report as its provisioning verification record.
+async fn handle_self_test_report(payload: &[u8], state: &AppState) -> Result<(), EncodingError> {
+    let report: SelfTestReport = decode(payload)?;
+    if let Err(e) = crate::routes::self_test::record_report(state, report).await {
+        tracing::error!(error = %e, "failed to store self-test report");
+    }
+    Ok(())
+}
+
+/// Raise an alert for a PID watch that crossed its threshold. Recoveries
+/// are logged only; operators resolve the alert.
+async fn handle_threshold_event(
+    fleet_id: &str,
+    payload: &[u8],
+    state: &AppState,
+) -> Result<(), EncodingError> {
+    let event: ThresholdEvent = decode(payload)?;
+    if event.exceeded {
+        crate::alerts::raise(
+            state,
+            crate::alerts::NewAlert::pid_threshold(fleet_id, &event),
+        )
+        .await;
+    } else {
+        tracing::debug!(
+            device_id = %event.device_id,
+            watch_id = %event.watch_id,
+            metric = %event.metric_name,
+            "pid watch back within threshold"
+        );
+    }
+    Ok(())
+}
+
+/// Apply a retained presence message: the agent's "online" after a
+/// connect, or the broker publishing its "offline" Last Will when the
+/// connection dropped.
+///
+/// Only moves between online, degraded and offline; provisioning or
+/// decommissioned devices and unknown devices are left alone (heartbeats
+/// still auto-register). The device comes from the topic.
+async fn handle_presence(
+    device_id: &str,
+    payload: &[u8],
+    state: &AppState,
+) -> Result<(), EncodingError> {
+    let presence: DevicePresence = decode(payload)?;
+    let (from, to): (&[DeviceStatus], _) = if presence.online {
+        (
+            &[DeviceStatus::Offline, DeviceStatus::Degraded],
+            DeviceStatus::Online,
+        )
+    } else {
+        (
+            &[DeviceStatus::Online, DeviceStatus::Degraded],
+            DeviceStatus::Offline,
+        )
+    };
+
+    let previous = if let Some(pool) = &state.pool {
+        let from: Vec<&str> = from.iter().map(|s| status_name(*s)).collect();
+        match crate::db::devices::transition_status(pool, device_id, &from, status_name(to)).await {
+            Ok(previous) => previous.map(|s| parse_device_status(&s)),
+            Err(e) => {
+                tracing::error!(error = %e, device_id, "failed to update device presence in db");
+                None
+            }
+        }
+    } else {
+        let mut devices = state.devices.write().await;
+        match devices.get_mut(device_id) {
+            Some(device) if from.contains(&device.status) => {
+                device.updated_at = Utc::now();
+                Some(std::mem::replace(&mut device.status, to))
+            }
+            _ => None,
+        }
+    };
+    let Some(previous) = previous else {
+        tracing::debug!(
+            device_id,
+            online = presence.online,
+            "presence without status change"
+        );
+        return Ok(());
+    };
+
+    let change = StatusChange::new(device_id, previous, to, StatusReason::Presence, Utc::now());
+    crate::device_status::record(state, change).await;
+    Ok(())
 }
 
 /// Handle an incoming heartbeat from a device.
 ///
 /// Auto-registers unknown devices on first heartbeat so that new edge agents
 /// appear in the dashboard without manual provisioning.
-async fn handle_heartbeat(payload: &[u8], state: &AppState) {
-    let hb: Heartbeat = match serde_json::from_slice(payload) {
-        Ok(h) => h,
-        Err(e) => {
-            tracing::warn!(error = %e, "failed to parse heartbeat payload");
-            return;
-        }
+///
+/// Parses a borrowed [`HeartbeatView`]: heartbeats are the highest-volume
+/// topic and only a handful of fields are needed.
+///
+/// The fleet comes from the topic, which the broker authorised, rather than
+/// the payload; a mismatch is logged.
+async fn handle_heartbeat(
+    fleet_id: &str,
+    payload: &[u8],
+    state: &AppState,
+) -> Result<(), EncodingError> {
+    let received_at = Utc::now();
+    let mut hb: HeartbeatView = match Encoding::detect(payload) {
+        Encoding::Json => serde_json::from_slice(payload)?,
+        // CBOR is decoded owned, so the view cannot borrow.
+        Encoding::Cbor => decode::<Heartbeat>(payload)?.into(),
     };
+    if hb.fleet_id != fleet_id {
+        tracing::warn!(
+            device_id = %hb.device_id,
+            topic_fleet = fleet_id,
+            payload_fleet = %hb.fleet_id,
+            "heartbeat fleet does not match its topic, using the topic"
+        );
+        hb.fleet_id = fleet_id.into();
+    }
 
-    if let Some(pool) = &state.pool {
-        if let Err(e) = crate::db::devices::upsert_from_heartbeat(
-            pool,
+    let previous = match state
+        .store
+        .upsert_from_heartbeat(
             &hb.device_id,
             &hb.fleet_id,
             hb.machine_id.as_deref(),
             hb.timestamp,
+            hb.cert_expires_at,
+            hb.vitals.as_ref(),
         )
         .await
-        {
-            tracing::error!(error = %e, "failed to upsert heartbeat in db");
-        }
-    } else {
-        let mut devices = state.devices.write().await;
-        if let Some(device) = devices.get_mut(&hb.device_id) {
-            device.last_heartbeat = Some(hb.timestamp);
-            device.status = zc_protocol::device::DeviceStatus::Online;
-            // Update machine_id in metadata if newly provided.
-            if let Some(ref mid) = hb.machine_id
-                && let Some(obj) = device.metadata.as_object_mut()
-            {
-                obj.insert("machine_id".into(), serde_json::Value::String(mid.clone()));
-            }
-        } else {
-            // Auto-register: create a new device entry from the heartbeat.
+    {
+        Ok(None) => {
             tracing::info!(
                 device_id = %hb.device_id,
                 fleet_id = %hb.fleet_id,
                 machine_id = ?hb.machine_id,
-                "auto-registering new device from heartbeat"
-            );
-            let mut metadata = serde_json::json!({
-                "fleet": hb.fleet_id,
-                "auto_registered": true,
-            });
-            if let Some(ref mid) = hb.machine_id {
-                metadata["machine_id"] = serde_json::Value::String(mid.clone());
-            }
-            devices.insert(
-                hb.device_id.clone(),
-                zc_protocol::device::DeviceInfo {
-                    id: uuid::Uuid::now_v7(),
-                    fleet_id: zc_protocol::device::FleetId(uuid::Uuid::now_v7()),
-                    device_id: hb.device_id.clone(),
-                    status: zc_protocol::device::DeviceStatus::Online,
-                    vin: None,
-                    hardware_type: zc_protocol::device::HardwareType::Custom("auto".into()),
-                    certificate_id: None,
-                    last_heartbeat: Some(hb.timestamp),
-                    metadata,
-                    created_at: Utc::now(),
-                    updated_at: Utc::now(),
-                },
+                "auto-registered new device from heartbeat"
             );
+            None
         }
-    }
+        Ok(previous) => previous,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to upsert heartbeat");
+            None
+        }
+    };
 
     tracing::debug!(device_id = %hb.device_id, "mqtt heartbeat received");
+    crate::command_timing::heartbeat_received(state, &hb.device_id, hb.timestamp, received_at)
+        .await;
+    crate::device_status::heartbeat_received(state, &hb.device_id, previous).await;
+    crate::command_queue::record_encodings(state, &hb.device_id, &hb.encodings).await;
+
+    crate::command_queue::flush(state, &hb.device_id).await;
+    crate::shadow_reconcile::reconcile(state, &hb.fleet_id, &hb.device_id).await;
+    crate::file_transfers::resume_stalled(state, &hb.device_id).await;
 
     let _ = state.event_tx.send(WsEvent::DeviceHeartbeat {
-        device_id: hb.device_id,
+        device_id: hb.device_id.into_owned(),
         timestamp: Utc::now(),
     });
+    Ok(())
 }
 
 /// Handle incoming telemetry from a device.
-async fn handle_telemetry(device_id: &str, payload: &[u8], state: &AppState) {
-    let batch: TelemetryBatch = match serde_json::from_slice(payload) {
-        Ok(b) => b,
-        Err(e) => {
-            tracing::warn!(error = %e, device_id = device_id, "failed to parse telemetry payload");
-            return;
-        }
-    };
+///
+/// Rows are built into `rows`, which is left empty (but allocated) for the
+/// next batch.
+async fn handle_telemetry(
+    fleet_id: &str,
+    device_id: &str,
+    payload: &[u8],
+    state: &AppState,
+    rows: &mut Vec<TelemetryRow>,
+) -> Result<(), EncodingError> {
+    let batch: TelemetryBatch = decode(payload)?;
 
     let count = batch.readings.len();
     let source = batch
         .readings
         .first()
-        .map(|r| format!("{:?}", r.source).to_lowercase())
-        .unwrap_or_else(|| "unknown".to_string());
+        .map_or("unknown", |r| r.source.as_str());
 
-    if let Some(pool) = &state.pool {
-        let rows: Vec<crate::db::telemetry::TelemetryRow> = batch
-            .readings
-            .iter()
-            .map(|r| crate::db::telemetry::TelemetryRow {
-                time: r.time,
-                device_id: device_id.to_string(),
-                metric_name: r.metric_name.clone(),
-                value_numeric: r.value_numeric,
-                value_text: r.value_text.clone(),
-                value_json: r.value_json.clone(),
-                unit: r.unit.clone(),
-                source: format!("{:?}", r.source).to_lowercase(),
-            })
-            .collect();
-
-        if let Err(e) = crate::db::telemetry::insert_batch(pool, &rows).await {
-            tracing::error!(error = %e, "failed to insert telemetry batch");
-            return;
-        }
+    rows.clear();
+    rows.extend(batch.readings.into_iter().map(|r| TelemetryRow {
+        time: r.time,
+        device_id: device_id.to_string(),
+        metric_name: r.metric_name,
+        value_numeric: r.value_numeric,
+        value_text: r.value_text,
+        value_json: r.value_json,
+        unit: r.unit,
+        source: r.source.as_str().to_string(),
+    }));
+    crate::alert_rules::telemetry_ingested(state, Some(fleet_id), device_id, rows).await;
+
+    if let Err(e) = state.store.append_telemetry(rows).await {
+        tracing::error!(error = %e, "failed to insert telemetry batch");
+        return Ok(());
     }
 
     tracing::debug!(
@@ -270,82 +633,49 @@ async fn handle_telemetry(device_id: &str, payload: &[u8], state: &AppState) {
     let _ = state.event_tx.send(WsEvent::TelemetryIngested {
         device_id: device_id.to_string(),
         count,
-        source,
+        source: source.to_string(),
         timestamp: Utc::now(),
     });
+    Ok(())
 }
 
 /// Handle an incoming shadow update from a device.
-async fn handle_shadow_update(fleet_id: &str, device_id: &str, payload: &[u8], state: &AppState) {
-    let update: ShadowUpdate = match serde_json::from_slice(payload) {
-        Ok(u) => u,
+async fn handle_shadow_update(
+    fleet_id: &str,
+    device_id: &str,
+    payload: &[u8],
+    state: &AppState,
+) -> Result<(), EncodingError> {
+    let update: ShadowUpdate = decode(payload)?;
+
+    let shadow_name = update.shadow_name.clone();
+    let shadow = match state
+        .store
+        .merge_reported(device_id, &shadow_name, &update.reported)
+        .await
+    {
+        Ok(shadow) => shadow,
         Err(e) => {
-            tracing::warn!(error = %e, device_id = device_id, "failed to parse shadow update payload");
-            return;
+            tracing::error!(error = %e, "failed to upsert shadow reported state");
+            return Ok(());
         }
     };
+    let version = shadow.version;
 
-    let shadow_name = update.shadow_name.clone();
-    let version;
-
-    if let Some(pool) = &state.pool {
-        match crate::db::shadows::upsert_reported(pool, device_id, &shadow_name, &update.reported)
-            .await
-        {
-            Ok(row) => {
-                version = row.version as u64;
-                // Compute delta and publish if non-empty.
-                let delta = compute_delta(&row.desired, &row.reported);
-                if !delta.as_object().is_none_or(|o| o.is_empty()) {
-                    publish_shadow_delta(state, fleet_id, device_id, &shadow_name, delta, version)
-                        .await;
-                }
-            }
-            Err(e) => {
-                tracing::error!(error = %e, "failed to upsert shadow reported state");
-                return;
-            }
-        }
+    // Compute delta and publish if non-empty.
+    let delta = compute_delta(&shadow.desired, &shadow.reported);
+    if delta.as_object().is_none_or(|o| o.is_empty()) {
+        crate::shadow_reconcile::mark_converged(state, device_id, &shadow_name);
     } else {
-        let mut shadows = state.shadows.write().await;
-        let key = (device_id.to_string(), shadow_name.clone());
-        let entry = shadows
-            .entry(key)
-            .or_insert_with(|| zc_protocol::shadows::ShadowState {
-                reported: serde_json::Value::Object(Default::default()),
-                desired: serde_json::Value::Object(Default::default()),
-                version: 0,
-                last_updated: Utc::now(),
-            });
-
-        // Merge reported state (top-level key replacement).
-        if let (Some(existing), Some(incoming)) =
-            (entry.reported.as_object_mut(), update.reported.as_object())
-        {
-            for (k, v) in incoming {
-                existing.insert(k.clone(), v.clone());
-            }
-        }
-        entry.version += 1;
-        entry.last_updated = Utc::now();
-        version = entry.version;
-
-        // Compute delta and publish if non-empty.
-        let delta = compute_delta(&entry.desired, &entry.reported);
-        if !delta.as_object().is_none_or(|o| o.is_empty()) {
-            // Drop the write lock before publishing.
-            let delta_clone = delta.clone();
-            drop(shadows);
-            publish_shadow_delta(
-                state,
-                fleet_id,
-                device_id,
-                &shadow_name,
-                delta_clone,
-                version,
-            )
-            .await;
-        }
+        crate::shadow_reconcile::publish_delta(
+            state,
+            fleet_id,
+            device_id,
+            &shadow_name,
+            delta,
+            version,
+        )
+        .await;
     }
 
     tracing::info!(
@@ -354,6 +684,9 @@ async fn handle_shadow_update(fleet_id: &str, device_id: &str, payload: &[u8], s
         version = version,
         "shadow update processed"
     );
+    if shadow_name == UPDATE_SHADOW {
+        crate::agent_releases::report_received(state, fleet_id, device_id, &update.reported).await;
+    }
 
     let _ = state.event_tx.send(WsEvent::ShadowUpdated {
         device_id: device_id.to_string(),
@@ -361,9 +694,74 @@ async fn handle_shadow_update(fleet_id: &str, device_id: &str, payload: &[u8], s
         version,
         timestamp: Utc::now(),
     });
+    Ok(())
 }
 
-/// Compute delta: keys in `desired` that differ from `reported`.
+/// Reply to a device's shadow get request with the full document.
+///
+/// Agents ask on every (re)connect; the document carries the outstanding
+/// delta so desired state set while the device was offline is applied
+/// without waiting for heartbeat re-delivery. A missing shadow is answered
+/// with empty state at version 0.
+async fn handle_shadow_get(
+    fleet_id: &str,
+    device_id: &str,
+    payload: &[u8],
+    state: &AppState,
+) -> Result<(), EncodingError> {
+    let request: ShadowGetRequest = decode(payload)?;
+    let Some(mqtt) = &state.mqtt else {
+        return Ok(());
+    };
+
+    let empty = || serde_json::Value::Object(Default::default());
+    let (desired, reported, version) = match state
+        .store
+        .get_shadow(device_id, &request.shadow_name)
+        .await
+    {
+        Ok(Some(s)) => (s.desired, s.reported, s.version),
+        Ok(None) => (empty(), empty(), 0),
+        Err(e) => {
+            tracing::error!(error = %e, device_id = device_id, "failed to load shadow for get request");
+            return Ok(());
+        }
+    };
+
+    let document = ShadowDocument {
+        device_id: device_id.to_string(),
+        delta: compute_delta(&desired, &reported),
+        shadow_name: request.shadow_name,
+        desired,
+        reported,
+        version,
+        client_token: request.client_token,
+        timestamp: Utc::now(),
+    };
+    let encoding = crate::command_queue::device_encoding(state, device_id).await;
+    let bytes = zc_protocol::encoding::encode(&document, encoding)?;
+    let topic = topics::shadow_document(fleet_id, device_id);
+    if let Err(e) = mqtt
+        .publish(&topic, &bytes, rumqttc::QoS::AtLeastOnce)
+        .await
+    {
+        tracing::error!(error = %e, "failed to publish shadow document");
+        return Ok(());
+    }
+
+    tracing::info!(
+        device_id = device_id,
+        shadow = document.shadow_name,
+        version = version,
+        pending = document.delta.as_object().map_or(0, |o| o.len()),
+        "shadow document sent"
+    );
+    Ok(())
+}
+
+/// Compute delta: keys in `desired` that differ from `reported`. A `null`
+/// desired value is a tombstone: it stays in the delta until the device
+/// stops reporting the key.
 pub(crate) fn compute_delta(
     desired: &serde_json::Value,
     reported: &serde_json::Value,
@@ -373,7 +771,12 @@ pub(crate) fn compute_delta(
     if let Some(desired_obj) = desired.as_object() {
         let reported_obj = reported.as_object();
         for (key, desired_val) in desired_obj {
-            let reported_val = reported_obj.and_then(|r| r.get(key));
+            let reported_val = reported_obj
+                .and_then(|r| r.get(key))
+                .filter(|v| !v.is_null());
+            if desired_val.is_null() && reported_val.is_none() {
+                continue;
+            }
             if reported_val != Some(desired_val) {
                 delta.insert(key.clone(), desired_val.clone());
             }
@@ -383,41 +786,10 @@ pub(crate) fn compute_delta(
     serde_json::Value::Object(delta)
 }
 
-/// Publish a ShadowDelta to the device via MQTT.
-async fn publish_shadow_delta(
-    state: &AppState,
-    fleet_id: &str,
-    device_id: &str,
-    shadow_name: &str,
-    delta: serde_json::Value,
-    version: u64,
-) {
-    let shadow_delta = ShadowDelta {
-        device_id: device_id.to_string(),
-        shadow_name: shadow_name.to_string(),
-        delta,
-        version,
-        timestamp: Utc::now(),
-    };
-
-    if let Some(mqtt) = &state.mqtt {
-        let topic = topics::shadow_delta(fleet_id, device_id);
-        match serde_json::to_vec(&shadow_delta) {
-            Ok(payload) => {
-                if let Err(e) = mqtt.publish(&topic, &payload, QoS::AtLeastOnce).await {
-                    tracing::error!(error = %e, "failed to publish shadow delta");
-                }
-            }
-            Err(e) => {
-                tracing::error!(error = %e, "failed to serialize shadow delta");
-            }
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use zc_protocol::device::Heartbeat;
 
     fn sample_state() -> AppState {
         AppState::with_sample_data()
@@ -437,6 +809,10 @@ mod tests {
             can_status: zc_protocol::device::ServiceStatus::Running,
             agent_version: "0.1.0".into(),
             machine_id: None,
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
             timestamp: Utc::now(),
         };
 
@@ -451,6 +827,103 @@ mod tests {
         assert!(json.contains("rpi-001"));
     }
 
+    #[tokio::test]
+    async fn cbor_heartbeat_negotiates_cbor_commands() {
+        let mqtt = Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = sample_state();
+        state.mqtt = Some(mqtt.clone());
+        let heartbeat = |encodings: Vec<Encoding>, encoding| {
+            let hb = Heartbeat {
+                device_id: "rpi-001".into(),
+                fleet_id: "fleet-alpha".into(),
+                status: DeviceStatus::Online,
+                uptime_secs: 60,
+                ollama_status: zc_protocol::device::ServiceStatus::Running,
+                can_status: zc_protocol::device::ServiceStatus::Running,
+                agent_version: "0.2.0".into(),
+                machine_id: None,
+                cert_expires_at: None,
+                encodings,
+                version: zc_protocol::device::HEARTBEAT_VERSION,
+                vitals: None,
+                timestamp: Utc::now(),
+            };
+            zc_protocol::encoding::encode(&hb, encoding).unwrap()
+        };
+        let topic = topics::heartbeat("fleet-alpha", "rpi-001");
+
+        handle_incoming(
+            &topic,
+            &heartbeat(Encoding::ALL.to_vec(), Encoding::Cbor),
+            &state,
+        )
+        .await;
+        assert_eq!(state.metrics.mqtt_topic_stats()[0].parsed, 1);
+        assert_eq!(
+            crate::command_queue::device_encoding(&state, "rpi-001").await,
+            Encoding::Cbor
+        );
+
+        let envelope = zc_protocol::commands::CommandEnvelope::new(
+            "fleet-alpha",
+            "rpi-001",
+            "read DTCs",
+            "ops",
+        );
+        let encoding = crate::command_queue::device_encoding(&state, "rpi-001").await;
+        crate::command_queue::publish_envelope(mqtt.as_ref(), &envelope, encoding)
+            .await
+            .unwrap();
+        let payload = &mqtt.published()[0].payload;
+        assert!(payload.starts_with(&zc_protocol::encoding::CBOR_MAGIC));
+        let sent: zc_protocol::commands::CommandEnvelope = decode(payload).unwrap();
+        assert_eq!(sent.id, envelope.id);
+
+        // A downgraded agent heartbeats JSON without encodings.
+        handle_incoming(&topic, &heartbeat(vec![], Encoding::Json), &state).await;
+        assert_eq!(
+            crate::command_queue::device_encoding(&state, "rpi-001").await,
+            Encoding::Json
+        );
+    }
+
+    #[tokio::test]
+    async fn presence_marks_device_offline_and_back_online() {
+        let state = sample_state();
+        let mut rx = state.event_tx.subscribe();
+        let topic = topics::presence("fleet-alpha", "rpi-001");
+        let presence = |online| {
+            serde_json::to_vec(&DevicePresence {
+                device_id: "rpi-001".into(),
+                fleet_id: "fleet-alpha".into(),
+                online,
+                timestamp: None,
+            })
+            .unwrap()
+        };
+        let status = || async { state.devices.read().await["rpi-001"].status };
+
+        // Last Will published by the broker.
+        handle_incoming(&topic, &presence(false), &state).await;
+        assert_eq!(status().await, DeviceStatus::Offline);
+        let json = serde_json::to_string(&rx.try_recv().unwrap()).unwrap();
+        assert!(json.contains("device_status_changed"));
+        assert!(json.contains("\"new_status\":\"offline\""));
+
+        // A repeated (retained) will changes nothing.
+        handle_incoming(&topic, &presence(false), &state).await;
+        assert!(rx.try_recv().is_err());
+
+        handle_incoming(&topic, &presence(true), &state).await;
+        assert_eq!(status().await, DeviceStatus::Online);
+        assert_eq!(state.metrics.mqtt_topic_stats()[0].category, "status");
+
+        // Unknown devices are not registered from presence.
+        let unknown = topics::presence("fleet-alpha", "ghost-001");
+        handle_incoming(&unknown, &presence(false), &state).await;
+        assert!(!state.devices.read().await.contains_key("ghost-001"));
+    }
+
     #[tokio::test]
     async fn handle_heartbeat_auto_registers_unknown_device() {
         let state = sample_state();
@@ -466,6 +939,10 @@ mod tests {
             can_status: zc_protocol::device::ServiceStatus::Running,
             agent_version: "0.1.0".into(),
             machine_id: Some("abc123def456".into()),
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
             timestamp: Utc::now(),
         };
 
@@ -508,13 +985,22 @@ mod tests {
             initiated_by: "admin".into(),
             created_at: Utc::now(),
             timeout_secs: 30,
+            conversation_id: None,
+            history: Vec::new(),
         };
         {
             let mut cmds = state.commands.try_write().unwrap();
             cmds.push(crate::state::CommandRecord {
                 envelope,
                 response: None,
+                status: zc_protocol::commands::CommandStatus::Pending,
                 created_at: Utc::now(),
+                deadline: None,
+                attempts: 0,
+                max_attempts: 1,
+                next_attempt_at: None,
+                idempotency_key: None,
+                timing: Default::default(),
             });
         }
 
@@ -529,6 +1015,8 @@ mod tests {
             latency_ms: 42,
             responded_at: Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
         };
 
         let payload = serde_json::to_vec(&resp).unwrap();
@@ -545,6 +1033,111 @@ mod tests {
         let commands = state.commands.read().await;
         let record = commands.iter().find(|r| r.envelope.id == cmd_id).unwrap();
         assert!(record.response.is_some());
+        assert_eq!(state.metrics.commands_with_status("completed"), 1);
+    }
+
+    #[tokio::test]
+    async fn command_response_dtcs_get_repair_hints() {
+        let state = sample_state();
+        crate::dtc_knowledge::upsert(
+            &state,
+            &crate::dtc_knowledge::parse_csv(
+                "code,repair_hint,link_title,link_url\n\
+                 P0300,Check plugs and coils,Misfire guide,https://example.com/p0300\n",
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut rx = state.event_tx.subscribe();
+
+        let envelope = zc_protocol::commands::CommandEnvelope::new(
+            "fleet-alpha",
+            "rpi-001",
+            "read DTCs",
+            "admin",
+        );
+        let cmd_id = envelope.id;
+        state
+            .commands
+            .write()
+            .await
+            .push(crate::state::CommandRecord {
+                envelope,
+                response: None,
+                status: zc_protocol::commands::CommandStatus::Pending,
+                created_at: Utc::now(),
+                deadline: None,
+                attempts: 0,
+                max_attempts: 1,
+                next_attempt_at: None,
+                idempotency_key: None,
+                timing: Default::default(),
+            });
+
+        let resp = CommandResponse {
+            command_id: cmd_id,
+            correlation_id: cmd_id,
+            device_id: "rpi-001".into(),
+            status: zc_protocol::commands::CommandStatus::Completed,
+            inference_tier: zc_protocol::commands::InferenceTier::Local,
+            response_text: Some("Found 1 DTC(s)".into()),
+            response_data: Some(serde_json::json!({
+                "tool_name": "read_dtcs",
+                "success": true,
+                "data": [{"code": "P0300", "category": "powertrain",
+                          "severity": "critical", "mil_status": true}]
+            })),
+            latency_ms: 42,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        };
+        let payload = serde_json::to_vec(&resp).unwrap();
+        handle_incoming(
+            &topics::command_response("fleet-alpha", "rpi-001"),
+            &payload,
+            &state,
+        )
+        .await;
+
+        // The critical code also raises a CriticalDtcDetected event.
+        let response_data = std::iter::from_fn(|| rx.try_recv().ok())
+            .find_map(|e| match e {
+                WsEvent::CommandResponse { response_data, .. } => Some(response_data),
+                _ => None,
+            })
+            .expect("command response event");
+        let dtc = &response_data.unwrap()["data"][0];
+        assert_eq!(dtc["repair_hint"], "Check plugs and coils");
+        assert_eq!(dtc["links"][0]["title"], "Misfire guide");
+        let commands = state.commands.read().await;
+        let stored = commands[0].response.as_ref().unwrap();
+        assert!(stored.response_data.as_ref().unwrap()["data"][0]["repair_hint"].is_string());
+    }
+
+    #[tokio::test]
+    async fn handle_response_chunk_relays_event() {
+        let state = sample_state();
+        let mut rx = state.event_tx.subscribe();
+
+        let cmd_id = uuid::Uuid::now_v7();
+        let chunk = CommandResponseChunk {
+            command_id: cmd_id,
+            correlation_id: cmd_id,
+            device_id: "rpi-001".into(),
+            seq: 2,
+            data: serde_json::json!({"frames": [{"id": "0x7E8", "data": "02 01 0C"}]}),
+            sent_at: Utc::now(),
+        };
+        let topic = topics::command_stream("fleet-alpha", "rpi-001");
+        handle_incoming(&topic, &serde_json::to_vec(&chunk).unwrap(), &state).await;
+
+        let json = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(json["type"], "command_response_chunk");
+        assert_eq!(json["seq"], 2);
+        assert_eq!(json["data"]["frames"][0]["id"], "0x7E8");
     }
 
     #[tokio::test]
@@ -578,6 +1171,180 @@ mod tests {
         assert!(json.contains("rpi-001"));
     }
 
+    #[tokio::test]
+    async fn threshold_event_raises_a_deduplicated_alert() {
+        let state = sample_state();
+        let topic = topics::alert("fleet-alpha", "rpi-001");
+        let event = |value: f64, exceeded: bool| {
+            serde_json::to_vec(&zc_protocol::telemetry::ThresholdEvent {
+                device_id: "rpi-001".into(),
+                watch_id: "config-0".into(),
+                pid: 0x05,
+                metric_name: "coolant_temp".into(),
+                value,
+                unit: Some("°C".into()),
+                bound: zc_protocol::telemetry::ThresholdBound::Above,
+                threshold: 110.0,
+                exceeded,
+                time: Utc::now(),
+            })
+            .unwrap()
+        };
+
+        handle_incoming(&topic, &event(112.0, true), &state).await;
+        handle_incoming(&topic, &event(104.0, false), &state).await;
+        handle_incoming(&topic, &event(115.0, true), &state).await;
+
+        let filter = crate::alerts::AlertFilter {
+            limit: 50,
+            ..Default::default()
+        };
+        let (alerts, total) = crate::alerts::list(&state, &filter).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(alerts[0].kind, crate::alerts::KIND_PID_THRESHOLD);
+        assert_eq!(alerts[0].occurrences, 2);
+        assert_eq!(alerts[0].title, "coolant_temp 115 °C above 110 on rpi-001");
+        assert_eq!(alerts[0].detail["value"], 115.0);
+    }
+
+    #[tokio::test]
+    async fn telemetry_scratch_is_reused_across_batches() {
+        let state = sample_state();
+        let mut scratch = BridgeScratch::default();
+        let topic = topics::telemetry_obd2("fleet-alpha", "rpi-001");
+
+        for rpm in [3000.0, 3100.0] {
+            let batch = TelemetryBatch {
+                device_id: "rpi-001".into(),
+                readings: vec![zc_protocol::telemetry::TelemetryReading {
+                    device_id: "rpi-001".into(),
+                    time: Utc::now(),
+                    metric_name: "engine_rpm".into(),
+                    value_numeric: Some(rpm),
+                    value_text: None,
+                    value_json: None,
+                    unit: Some("rpm".into()),
+                    source: zc_protocol::TelemetrySource::Obd2,
+                }],
+                collected_at: Utc::now(),
+            };
+            let payload = serde_json::to_vec(&batch).unwrap();
+            handle_incoming_with(&topic, &payload, &state, &mut scratch).await;
+            assert!(scratch.telemetry_rows.is_empty());
+            assert!(scratch.telemetry_rows.capacity() >= 1);
+        }
+
+        let telemetry = state.telemetry.read().await;
+        let rpms: Vec<_> = telemetry
+            .iter()
+            .filter(|r| r.metric_name == "engine_rpm")
+            .filter_map(|r| r.value_numeric)
+            .collect();
+        assert!(rpms.ends_with(&[3000.0, 3100.0]));
+        assert!(telemetry.iter().all(|r| r.source == "obd2"));
+    }
+
+    #[tokio::test]
+    async fn oversized_payload_is_dropped() {
+        let state = sample_state();
+        let mut rx = state.event_tx.subscribe();
+
+        let hb = serde_json::json!({
+            "device_id": "rpi-001",
+            "fleet_id": "fleet-alpha",
+            "agent_version": "x".repeat(MAX_HEARTBEAT_BYTES),
+            "timestamp": Utc::now(),
+        });
+        let payload = serde_json::to_vec(&hb).unwrap();
+        let topic = topics::heartbeat("fleet-alpha", "rpi-001");
+        handle_incoming(&topic, &payload, &state).await;
+        assert!(rx.try_recv().is_err());
+
+        assert_eq!(max_payload_bytes("heartbeat"), MAX_HEARTBEAT_BYTES);
+        assert_eq!(max_payload_bytes("command"), MAX_RESPONSE_BYTES);
+        assert_eq!(max_payload_bytes("shadow"), MAX_OTHER_BYTES);
+    }
+
+    #[test]
+    fn fleet_filter_parsing() {
+        assert_eq!(FleetFilter::parse("").unwrap(), None);
+        assert_eq!(FleetFilter::parse("*").unwrap(), Some(FleetFilter::All));
+        assert_eq!(FleetFilter::parse("a, *").unwrap(), Some(FleetFilter::All));
+
+        let only = FleetFilter::parse("fleet-beta, fleet-alpha,")
+            .unwrap()
+            .unwrap();
+        assert!(only.admits("fleet-alpha"));
+        assert!(!only.admits("fleet-gamma"));
+        assert_eq!(only.subscription_ids(), vec!["fleet-alpha", "fleet-beta"]);
+        assert_eq!(
+            FleetFilter::All.subscription_ids(),
+            vec![topics::ALL_FLEETS]
+        );
+
+        assert!(FleetFilter::parse("fleet/alpha").is_err());
+        assert!(FleetFilter::parse("fleet-#").is_err());
+    }
+
+    fn heartbeat_payload(device_id: &str, fleet_id: &str) -> Vec<u8> {
+        serde_json::to_vec(&Heartbeat {
+            device_id: device_id.into(),
+            fleet_id: fleet_id.into(),
+            status: zc_protocol::device::DeviceStatus::Online,
+            uptime_secs: 1,
+            ollama_status: zc_protocol::device::ServiceStatus::Running,
+            can_status: zc_protocol::device::ServiceStatus::Running,
+            agent_version: "0.1.0".into(),
+            machine_id: None,
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
+            timestamp: Utc::now(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn multi_fleet_bridge_filters_and_counts_per_fleet() {
+        let mut state = sample_state();
+        state.mqtt_fleets = FleetFilter::parse("fleet-alpha,fleet-beta")
+            .unwrap()
+            .unwrap();
+
+        for (fleet, device) in [
+            ("fleet-alpha", "rpi-001"),
+            ("fleet-beta", "beta-001"),
+            ("fleet-gamma", "gamma-001"),
+        ] {
+            let topic = topics::heartbeat(fleet, device);
+            handle_incoming(&topic, &heartbeat_payload(device, fleet), &state).await;
+        }
+
+        let devices = state.devices.read().await;
+        assert_eq!(devices["beta-001"].metadata["fleet"], "fleet-beta");
+        assert!(!devices.contains_key("gamma-001"));
+        assert_eq!(state.metrics.mqtt_messages("fleet-alpha", "heartbeat"), 1);
+        assert_eq!(state.metrics.mqtt_messages("fleet-beta", "heartbeat"), 1);
+        assert_eq!(state.metrics.mqtt_messages("fleet-gamma", "heartbeat"), 0);
+        assert!(
+            state
+                .metrics
+                .render()
+                .contains("zc_mqtt_dropped_total{reason=\"fleet_not_monitored\"} 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn heartbeat_fleet_comes_from_topic() {
+        let state = sample_state();
+        let topic = topics::heartbeat("fleet-beta", "new-001");
+        handle_incoming(&topic, &heartbeat_payload("new-001", "fleet-alpha"), &state).await;
+
+        let devices = state.devices.read().await;
+        assert_eq!(devices["new-001"].metadata["fleet"], "fleet-beta");
+    }
+
     #[tokio::test]
     async fn handle_unknown_topic() {
         let state = sample_state();
@@ -599,6 +1366,13 @@ mod tests {
 
         // No event should be broadcast for malformed data.
         assert!(rx.try_recv().is_err());
+
+        // It is counted and quarantined instead of silently dropped.
+        assert_eq!(state.metrics.mqtt_topic_stats()[0].failed, 1);
+        let quarantined = state.mqtt_quarantine.list();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].topic, topic);
+        assert_eq!(quarantined[0].raw, b"not-json");
     }
 
     #[tokio::test]
@@ -663,6 +1437,20 @@ mod tests {
         assert!(delta.get("mode").is_none());
     }
 
+    #[test]
+    fn compute_delta_keeps_tombstones_until_cleared() {
+        let desired = serde_json::json!({"log_level": null, "mode": null});
+        let reported = serde_json::json!({"log_level": "debug"});
+        let delta = compute_delta(&desired, &reported);
+        assert_eq!(delta, serde_json::json!({"log_level": null}));
+        assert!(
+            compute_delta(&desired, &serde_json::json!({}))
+                .as_object()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn compute_delta_empty_when_matching() {
         let state = serde_json::json!({"firmware": "0.2.0"});
@@ -708,4 +1496,183 @@ mod tests {
             serde_json::from_slice(&delta_msgs[0].payload).unwrap();
         assert_eq!(delta.delta["firmware"], "0.2.0");
     }
+
+    #[tokio::test]
+    async fn shadow_get_replies_with_document() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = sample_state();
+        state.mqtt = Some(mqtt.clone());
+        state.shadows.write().await.insert(
+            ("rpi-001".to_string(), "config".to_string()),
+            zc_protocol::shadows::ShadowState {
+                reported: serde_json::json!({"firmware": "0.1.0", "debug": true}),
+                desired: serde_json::json!({"firmware": "0.2.0", "debug": null}),
+                version: 4,
+                last_updated: Utc::now(),
+            },
+        );
+
+        let request = ShadowGetRequest {
+            device_id: "rpi-001".into(),
+            shadow_name: "config".into(),
+            client_token: Some("boot-1".into()),
+        };
+        let payload = serde_json::to_vec(&request).unwrap();
+        handle_incoming(
+            &topics::shadow_get("fleet-alpha", "rpi-001"),
+            &payload,
+            &state,
+        )
+        .await;
+
+        let msgs = mqtt.published_to("fleet/fleet-alpha/rpi-001/shadow/document");
+        assert_eq!(msgs.len(), 1);
+        let document: ShadowDocument = serde_json::from_slice(&msgs[0].payload).unwrap();
+        assert_eq!(document.version, 4);
+        assert_eq!(document.client_token.as_deref(), Some("boot-1"));
+        assert_eq!(document.desired["firmware"], "0.2.0");
+        assert_eq!(document.delta["firmware"], "0.2.0");
+        assert!(document.delta["debug"].is_null());
+        assert!(document.delta.as_object().unwrap().contains_key("debug"));
+
+        // Unknown shadows come back empty.
+        let request = ShadowGetRequest {
+            device_id: "rpi-001".into(),
+            shadow_name: "missing".into(),
+            client_token: None,
+        };
+        let payload = serde_json::to_vec(&request).unwrap();
+        handle_incoming(
+            &topics::shadow_get("fleet-alpha", "rpi-001"),
+            &payload,
+            &state,
+        )
+        .await;
+        let msgs = mqtt.published_to("fleet/fleet-alpha/rpi-001/shadow/document");
+        let document: ShadowDocument = serde_json::from_slice(&msgs[1].payload).unwrap();
+        assert_eq!(document.version, 0);
+        assert!(document.pending_delta().is_none());
+    }
+
+    #[tokio::test]
+    async fn heartbeat_redelivers_missed_delta_until_converged() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = sample_state();
+        state.mqtt = Some(mqtt.clone());
+        state.shadows.write().await.insert(
+            ("rpi-001".to_string(), "config".to_string()),
+            zc_protocol::shadows::ShadowState {
+                reported: serde_json::json!({"mode": "normal"}),
+                desired: serde_json::json!({"mode": "debug"}),
+                version: 1,
+                last_updated: Utc::now(),
+            },
+        );
+
+        let hb = Heartbeat {
+            device_id: "rpi-001".into(),
+            fleet_id: "fleet-alpha".into(),
+            status: zc_protocol::device::DeviceStatus::Online,
+            uptime_secs: 5,
+            ollama_status: zc_protocol::device::ServiceStatus::Running,
+            can_status: zc_protocol::device::ServiceStatus::Running,
+            agent_version: "0.1.0".into(),
+            machine_id: None,
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
+            timestamp: Utc::now(),
+        };
+        let topic = topics::heartbeat("fleet-alpha", "rpi-001");
+        handle_incoming(&topic, &serde_json::to_vec(&hb).unwrap(), &state).await;
+        let delta_topic = "fleet/fleet-alpha/rpi-001/shadow/delta";
+        assert_eq!(mqtt.published_to(delta_topic).len(), 1);
+
+        // The device applies it and reports back.
+        let update = zc_protocol::shadows::ShadowUpdate {
+            device_id: "rpi-001".into(),
+            shadow_name: "config".into(),
+            reported: serde_json::json!({"mode": "debug"}),
+            version: 2,
+        };
+        let topic = topics::shadow_update("fleet-alpha", "rpi-001");
+        handle_incoming(&topic, &serde_json::to_vec(&update).unwrap(), &state).await;
+
+        let tracked = state.shadow_reconcile.get("rpi-001", "config").unwrap();
+        assert_eq!(
+            tracked.status,
+            crate::shadow_reconcile::ReconcileStatus::Converged
+        );
+        assert_eq!(mqtt.published_to(delta_topic).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn self_test_report_is_stored() {
+        let state = sample_state();
+        let mut rx = state.event_tx.subscribe();
+
+        let report = SelfTestReport {
+            device_id: "rpi-002".into(),
+            fleet_id: "fleet-alpha".into(),
+            agent_version: "0.1.0".into(),
+            trigger: zc_protocol::self_test::SelfTestTrigger::FirstBoot,
+            passed: true,
+            checks: vec![],
+            started_at: Utc::now(),
+            duration_ms: 20,
+        };
+        let payload = serde_json::to_vec(&report).unwrap();
+        let topic = topics::self_test_report("fleet-alpha", "rpi-002");
+        handle_incoming(&topic, &payload, &state).await;
+
+        assert!(state.self_tests.read().await.get("rpi-002").unwrap().passed);
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.event_type(), "self_test_reported");
+        assert_eq!(event.device_id(), "rpi-002");
+    }
+
+    #[tokio::test]
+    async fn claim_request_is_answered_on_the_bootstrap_topic() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = sample_state();
+        state.mqtt = Some(mqtt.clone());
+        let issued = crate::claim_codes::create(
+            &state,
+            crate::claim_codes::NewClaimCode {
+                fleet_id: "fleet-beta".into(),
+                device_id: Some("sbc-011".into()),
+                tags: Default::default(),
+                config: None,
+                ttl_days: 1,
+                created_by: "admin".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let request = ClaimRequest {
+            request_id: uuid::Uuid::now_v7(),
+            claim_code: issued.code,
+            hardware_id: "5f2e9a".into(),
+            hardware_type: "industrial_sbc".into(),
+            agent_version: "0.9.0".into(),
+            timestamp: Utc::now(),
+        };
+        let payload = serde_json::to_vec(&request).unwrap();
+        handle_incoming(&topics::bootstrap_claim("5f2e9a"), &payload, &state).await;
+
+        let msgs = mqtt.published_to("bootstrap/5f2e9a/result");
+        assert_eq!(msgs.len(), 1);
+        let result: zc_protocol::bootstrap::ClaimResult =
+            serde_json::from_slice(&msgs[0].payload).unwrap();
+        assert_eq!(result.request_id, request.request_id);
+        assert_eq!(result.fleet_id.as_deref(), Some("fleet-beta"));
+        assert_eq!(result.device_id.as_deref(), Some("sbc-011"));
+        assert_eq!(
+            state.devices.read().await["sbc-011"].status,
+            DeviceStatus::Online
+        );
+        assert_eq!(state.metrics.mqtt_messages("bootstrap", "bootstrap"), 1);
+    }
 }
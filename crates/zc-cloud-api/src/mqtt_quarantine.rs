@@ -0,0 +1,172 @@
+//! Quarantine for MQTT payloads that fail to deserialize.
+//!
+//! Instead of logging and dropping a malformed publish, the bridge keeps
+//! it here with its topic and the parse error, so it can be inspected —
+//! and replayed once the protocol mismatch behind it is fixed — through
+//! `/api/v1/admin/mqtt/quarantine`. The store is in memory in both modes
+//! and capped: past [`MAX_QUARANTINED`] messages the oldest is evicted.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use zc_protocol::topics::TopicRef;
+
+/// Messages kept before the oldest is evicted.
+pub const MAX_QUARANTINED: usize = 500;
+
+/// A publish that failed to deserialize.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedMessage {
+    pub id: Uuid,
+    pub topic: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub category: String,
+    pub error: String,
+    /// The payload as text (invalid UTF-8 replaced).
+    pub payload: String,
+    pub payload_bytes: usize,
+    pub received_at: DateTime<Utc>,
+    pub replay_attempts: u32,
+    pub last_replay_at: Option<DateTime<Utc>>,
+    /// The payload exactly as received, for replay.
+    #[serde(skip)]
+    pub raw: Vec<u8>,
+}
+
+/// Capped quarantine store, oldest first.
+#[derive(Debug)]
+pub struct Quarantine {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    messages: VecDeque<QuarantinedMessage>,
+    evicted: u64,
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Self::with_capacity(MAX_QUARANTINED)
+    }
+}
+
+impl Quarantine {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Quarantine a payload from `topic`; returns its ID.
+    pub fn add(&self, topic: &str, parsed: &TopicRef<'_>, payload: &[u8], error: String) -> Uuid {
+        let message = QuarantinedMessage {
+            id: Uuid::now_v7(),
+            topic: topic.to_string(),
+            fleet_id: parsed.fleet_id.to_string(),
+            device_id: parsed.device_id.map(String::from),
+            category: parsed.category.to_string(),
+            error,
+            payload: String::from_utf8_lossy(payload).into_owned(),
+            payload_bytes: payload.len(),
+            received_at: Utc::now(),
+            replay_attempts: 0,
+            last_replay_at: None,
+            raw: payload.to_vec(),
+        };
+        let id = message.id;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.messages.len() >= self.capacity {
+            inner.messages.pop_front();
+            inner.evicted += 1;
+        }
+        inner.messages.push_back(message);
+        id
+    }
+
+    /// Quarantined messages, newest first.
+    pub fn list(&self) -> Vec<QuarantinedMessage> {
+        let inner = self.inner.lock().unwrap();
+        inner.messages.iter().rev().cloned().collect()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<QuarantinedMessage> {
+        let inner = self.inner.lock().unwrap();
+        inner.messages.iter().find(|m| m.id == id).cloned()
+    }
+
+    /// Remove a message; `false` if it was not quarantined.
+    pub fn remove(&self, id: Uuid) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.messages.len();
+        inner.messages.retain(|m| m.id != id);
+        inner.messages.len() != before
+    }
+
+    /// Record a replay that failed again with `error`.
+    pub fn replay_failed(&self, id: Uuid, error: String) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(message) = inner.messages.iter_mut().find(|m| m.id == id) {
+            message.error = error;
+            message.replay_attempts += 1;
+            message.last_replay_at = Some(Utc::now());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Messages dropped to make room since startup.
+    pub fn evicted(&self) -> u64 {
+        self.inner.lock().unwrap().evicted
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zc_protocol::topics;
+
+    #[test]
+    fn oldest_message_is_evicted_at_capacity() {
+        let quarantine = Quarantine::with_capacity(2);
+        let topic = topics::heartbeat("fleet-alpha", "rpi-001");
+        let parsed = topics::parse_topic_ref(&topic).unwrap();
+        let first = quarantine.add(&topic, &parsed, b"{", "eof".into());
+        let second = quarantine.add(&topic, &parsed, b"[", "eof".into());
+        let third = quarantine.add(&topic, &parsed, b"\xff", "invalid".into());
+
+        assert_eq!(quarantine.len(), 2);
+        assert_eq!(quarantine.evicted(), 1);
+        assert!(quarantine.get(first).is_none());
+        let listed: Vec<Uuid> = quarantine.list().iter().map(|m| m.id).collect();
+        assert_eq!(listed, [third, second]);
+
+        let message = quarantine.get(third).unwrap();
+        assert_eq!(message.device_id.as_deref(), Some("rpi-001"));
+        assert_eq!(message.category, "heartbeat");
+        assert_eq!(message.payload, "\u{fffd}");
+        assert_eq!(message.raw, b"\xff");
+
+        quarantine.replay_failed(second, "still bad".into());
+        assert_eq!(quarantine.get(second).unwrap().replay_attempts, 1);
+        assert!(quarantine.remove(second));
+        assert!(!quarantine.remove(second));
+    }
+}
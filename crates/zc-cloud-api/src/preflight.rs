@@ -0,0 +1,322 @@
+//! Pre-flight readiness checks before a command is dispatched.
+//!
+//! Opt-in per command (`"preflight": true` on `POST /commands`). Instead of
+//! letting a command time out against a device that cannot run it, the
+//! dispatch is refused with `412 Precondition Failed` and a
+//! [`PreflightReport`] listing each check. `"force": true` dispatches anyway.
+//!
+//! Checks:
+//! - `online` — not marked offline, heartbeat within
+//!   [`OFFLINE_AFTER_SECS`](crate::command_queue::OFFLINE_AFTER_SECS)
+//! - `maintenance` — not in maintenance mode or decommissioned
+//! - `capability` — the parsed tool is in the device's capability manifest
+//!   (the `tools` list of its reported `diagnostics` shadow). Devices that
+//!   have not reported a manifest yet are `unknown`, which does not fail.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use zc_protocol::commands::{ActionKind, ParsedIntent};
+use zc_protocol::device::DeviceStatus;
+
+use crate::command_queue::{self, OFFLINE_AFTER_SECS};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Shadow whose reported state carries the capability manifest.
+pub const MANIFEST_SHADOW: &str = "diagnostics";
+
+/// Outcome of one pre-flight check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckOutcome {
+    Pass,
+    Fail,
+    /// Not enough information to decide; does not block dispatch.
+    Unknown,
+}
+
+/// Result of one check.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+    pub detail: String,
+}
+
+/// All checks for one dispatch.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    pub device_id: String,
+    /// True when no check failed.
+    pub passed: bool,
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    fn new(device_id: &str, checks: Vec<PreflightCheck>) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            passed: checks.iter().all(|c| c.outcome != CheckOutcome::Fail),
+            checks,
+        }
+    }
+
+    /// Names of failed checks.
+    pub fn failed(&self) -> Vec<&'static str> {
+        self.checks
+            .iter()
+            .filter(|c| c.outcome == CheckOutcome::Fail)
+            .map(|c| c.name)
+            .collect()
+    }
+
+    /// The 412 error returned when dispatch is refused.
+    pub fn into_error(self) -> ApiError {
+        ApiError::PreconditionFailed {
+            message: format!(
+                "pre-flight check failed for device '{}': {} (set \"force\": true to dispatch anyway)",
+                self.device_id,
+                self.failed().join(", ")
+            ),
+            details: serde_json::json!({ "preflight": self }),
+        }
+    }
+}
+
+/// Run every check for dispatching `intent` to `device_id`.
+pub async fn check(
+    state: &AppState,
+    device_id: &str,
+    intent: Option<&ParsedIntent>,
+) -> ApiResult<PreflightReport> {
+    let (status, last_heartbeat) = device_state(state, device_id).await?;
+    let manifest = capability_manifest(state, device_id).await?;
+    Ok(evaluate(
+        device_id,
+        status,
+        last_heartbeat,
+        manifest.as_deref(),
+        intent,
+        Utc::now(),
+    ))
+}
+
+/// Pure check evaluation (separated for testing).
+pub fn evaluate(
+    device_id: &str,
+    status: DeviceStatus,
+    last_heartbeat: Option<DateTime<Utc>>,
+    manifest: Option<&[String]>,
+    intent: Option<&ParsedIntent>,
+    now: DateTime<Utc>,
+) -> PreflightReport {
+    let online = if command_queue::is_reachable(&status, last_heartbeat) {
+        let detail = match last_heartbeat {
+            Some(ts) => format!("last heartbeat {}s ago", (now - ts).num_seconds()),
+            None => "no heartbeat yet".to_string(),
+        };
+        (CheckOutcome::Pass, detail)
+    } else {
+        let detail = match last_heartbeat {
+            Some(ts) => format!(
+                "last heartbeat {}s ago (offline after {OFFLINE_AFTER_SECS}s)",
+                (now - ts).num_seconds()
+            ),
+            None => "device is marked offline".to_string(),
+        };
+        (CheckOutcome::Fail, detail)
+    };
+
+    let maintenance = match status {
+        DeviceStatus::Maintenance => (CheckOutcome::Fail, "device is in maintenance mode".into()),
+        DeviceStatus::Decommissioned => (CheckOutcome::Fail, "device is decommissioned".into()),
+        _ => (CheckOutcome::Pass, "not in maintenance".to_string()),
+    };
+
+    let capability = match intent {
+        Some(intent) if intent.action == ActionKind::Tool => match manifest {
+            Some(tools) => match intent
+                .tool_names()
+                .find(|name| !tools.iter().any(|t| t == name))
+            {
+                None if intent.steps.is_empty() => (
+                    CheckOutcome::Pass,
+                    format!("'{}' is in the capability manifest", intent.tool_name),
+                ),
+                None => (
+                    CheckOutcome::Pass,
+                    "every plan step's tool is in the capability manifest".to_string(),
+                ),
+                Some(missing) => (
+                    CheckOutcome::Fail,
+                    format!("'{missing}' is not in the device's capability manifest"),
+                ),
+            },
+            None => (
+                CheckOutcome::Unknown,
+                "device has not reported a capability manifest".to_string(),
+            ),
+        },
+        Some(_) => (CheckOutcome::Pass, "no tool required".to_string()),
+        None => (
+            CheckOutcome::Unknown,
+            "command was not parsed; the device will interpret it".to_string(),
+        ),
+    };
+
+    let checks = [
+        ("online", online),
+        ("maintenance", maintenance),
+        ("capability", capability),
+    ]
+    .into_iter()
+    .map(|(name, (outcome, detail))| PreflightCheck {
+        name,
+        outcome,
+        detail,
+    })
+    .collect();
+    PreflightReport::new(device_id, checks)
+}
+
+async fn device_state(
+    state: &AppState,
+    device_id: &str,
+) -> ApiResult<(DeviceStatus, Option<DateTime<Utc>>)> {
+    if let Some(pool) = &state.pool {
+        let row = crate::db::devices::get_by_device_id(pool, device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .ok_or_else(|| ApiError::NotFound(format!("device '{device_id}' not found")))?;
+        let status =
+            serde_json::from_value(serde_json::json!(row.status)).unwrap_or(DeviceStatus::Online);
+        return Ok((status, row.last_heartbeat));
+    }
+
+    let devices = state.devices.read().await;
+    let device = devices
+        .get(device_id)
+        .ok_or_else(|| ApiError::NotFound(format!("device '{device_id}' not found")))?;
+    Ok((device.status, device.last_heartbeat))
+}
+
+/// Tool names from the device's reported manifest, if it sent one.
+async fn capability_manifest(state: &AppState, device_id: &str) -> ApiResult<Option<Vec<String>>> {
+    Ok(reported_manifest(state, device_id)
+        .await?
+        .and_then(|r| r.get("tools").cloned())
+        .and_then(|tools| serde_json::from_value(tools).ok()))
+}
+
+/// Reported state of the device's manifest shadow, if it sent one.
+pub(crate) async fn reported_manifest(
+    state: &AppState,
+    device_id: &str,
+) -> ApiResult<Option<serde_json::Value>> {
+    reported_shadow(state, device_id, MANIFEST_SHADOW).await
+}
+
+/// Reported state of the device's `shadow_name` shadow, if it sent one.
+pub(crate) async fn reported_shadow(
+    state: &AppState,
+    device_id: &str,
+    shadow_name: &str,
+) -> ApiResult<Option<serde_json::Value>> {
+    if let Some(pool) = &state.pool {
+        return Ok(crate::db::shadows::get_shadow(pool, device_id, shadow_name)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map(|row| row.reported));
+    }
+    Ok(state
+        .shadows
+        .read()
+        .await
+        .get(&(device_id.to_string(), shadow_name.to_string()))
+        .map(|s| s.reported.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn tool(name: &str) -> ParsedIntent {
+        ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: name.into(),
+            tool_args: serde_json::json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        }
+    }
+
+    fn outcome(report: &PreflightReport, name: &str) -> CheckOutcome {
+        report
+            .checks
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap()
+            .outcome
+    }
+
+    #[test]
+    fn ready_device_passes() {
+        let now = Utc::now();
+        let manifest = vec!["read_dtcs".to_string()];
+        let report = evaluate(
+            "rpi-001",
+            DeviceStatus::Online,
+            Some(now - Duration::seconds(10)),
+            Some(&manifest),
+            Some(&tool("read_dtcs")),
+            now,
+        );
+        assert!(report.passed);
+        assert!(report.failed().is_empty());
+    }
+
+    #[test]
+    fn stale_heartbeat_and_maintenance_fail() {
+        let now = Utc::now();
+        let report = evaluate(
+            "rpi-001",
+            DeviceStatus::Maintenance,
+            Some(now - Duration::seconds(600)),
+            None,
+            Some(&tool("read_dtcs")),
+            now,
+        );
+        assert!(!report.passed);
+        assert_eq!(report.failed(), vec!["online", "maintenance"]);
+        assert_eq!(outcome(&report, "capability"), CheckOutcome::Unknown);
+    }
+
+    #[test]
+    fn missing_tool_fails_capability() {
+        let now = Utc::now();
+        let manifest = vec!["search_logs".to_string()];
+        let report = evaluate(
+            "rpi-001",
+            DeviceStatus::Online,
+            Some(now),
+            Some(&manifest),
+            Some(&tool("read_dtcs")),
+            now,
+        );
+        assert_eq!(report.failed(), vec!["capability"]);
+
+        let mut reply = tool("");
+        reply.action = ActionKind::Reply;
+        let report = evaluate(
+            "rpi-001",
+            DeviceStatus::Online,
+            Some(now),
+            Some(&manifest),
+            Some(&reply),
+            now,
+        );
+        assert!(report.passed);
+    }
+}
@@ -0,0 +1,116 @@
+//! Telemetry and heartbeat retention.
+//!
+//! Neither table is pruned by anything else, so without a limit they grow
+//! forever. [`run`] wakes every [`TICK_SECS`] and removes telemetry older
+//! than `TELEMETRY_RETENTION_DAYS` and heartbeats older than
+//! `HEARTBEAT_RETENTION_DAYS` through [`crate::store`]; either left unset is
+//! kept. On TimescaleDB hypertables (`TIMESCALEDB_ENABLED`, see
+//! [`crate::db::timescale`]) whole chunks are dropped instead of rows, and
+//! the 1-minute and 1-hour telemetry rollups are kept.
+
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::error::ApiResult;
+use crate::state::AppState;
+
+/// How often retention is applied.
+pub const TICK_SECS: u64 = 3600;
+
+/// How long telemetry and heartbeats are kept; `None` keeps them forever.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub telemetry: Option<Duration>,
+    pub heartbeats: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    /// Whether anything is ever pruned.
+    pub fn is_enabled(&self) -> bool {
+        self.telemetry.is_some() || self.heartbeats.is_some()
+    }
+}
+
+/// What one pass removed (rows, or chunks on a hypertable).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    pub telemetry: u64,
+    pub heartbeats: u64,
+}
+
+/// Remove whatever has outlived the policy as of `now`.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<Pruned> {
+    let policy = state.retention;
+    let mut pruned = Pruned::default();
+    if let Some(keep) = policy.telemetry {
+        pruned.telemetry = state.store.prune_telemetry(now - keep).await?;
+    }
+    if let Some(keep) = policy.heartbeats {
+        pruned.heartbeats = state.store.prune_heartbeats(now - keep).await?;
+    }
+    Ok(pruned)
+}
+
+/// Apply retention every [`TICK_SECS`], forever.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match tick(&state, Utc::now()).await {
+            Ok(pruned) if pruned != Pruned::default() => tracing::info!(
+                telemetry = pruned.telemetry,
+                heartbeats = pruned.heartbeats,
+                "retention applied"
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::error!(error = %e, "retention failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::telemetry::TelemetryRow;
+
+    fn reading(time: DateTime<Utc>) -> TelemetryRow {
+        TelemetryRow {
+            time,
+            device_id: "rpi-001".into(),
+            metric_name: "engine_rpm".into(),
+            value_numeric: Some(800.0),
+            value_text: None,
+            value_json: None,
+            unit: None,
+            source: "obd2".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn prunes_telemetry_past_retention() {
+        let mut state = AppState::with_sample_data();
+        let now = Utc::now();
+        state.telemetry.write().await.extend([
+            reading(now - Duration::days(40)),
+            reading(now - Duration::days(31)),
+            reading(now - Duration::days(2)),
+        ]);
+
+        // Nothing configured: nothing goes.
+        assert_eq!(tick(&state, now).await.unwrap(), Pruned::default());
+        assert_eq!(state.telemetry.read().await.len(), 3);
+
+        state.retention = RetentionPolicy {
+            telemetry: Some(Duration::days(30)),
+            heartbeats: Some(Duration::days(7)),
+        };
+        let pruned = tick(&state, now).await.unwrap();
+        assert_eq!(pruned.telemetry, 2);
+        assert_eq!(pruned.heartbeats, 0);
+        let left = state.telemetry.read().await;
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].time, now - Duration::days(2));
+    }
+}
@@ -0,0 +1,323 @@
+//! Agent release and over-the-air update endpoints.
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use zc_protocol::shadows::UPDATE_SHADOW;
+
+use crate::agent_releases::AgentRelease;
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::state::AppState;
+
+/// Request body for registering a release.
+#[derive(Debug, Deserialize)]
+pub struct CreateAgentReleaseRequest {
+    /// Version the binary prints for `--version`.
+    pub version: String,
+    /// http(s) URL devices download the binary from.
+    pub url: String,
+    /// Hex SHA-256 of the binary.
+    pub sha256: String,
+    pub notes: Option<String>,
+    /// Owner (the authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub created_by: String,
+}
+
+/// Query parameters for the release list.
+#[derive(Debug, Deserialize)]
+pub struct ListAgentReleasesQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// Request body for deploying a release.
+#[derive(Debug, Deserialize)]
+pub struct DeployAgentReleaseRequest {
+    /// Devices to update (IDs or aliases).
+    #[serde(default)]
+    pub device_ids: Vec<String>,
+    /// Update every device of this fleet as well.
+    pub fleet_id: Option<String>,
+    /// Who deployed it (the authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub requested_by: String,
+}
+
+/// Devices a release was deployed to.
+#[derive(Debug, Serialize)]
+pub struct DeployAgentReleaseResponse {
+    pub version: String,
+    pub devices: Vec<String>,
+}
+
+/// POST /api/v1/agent-releases — register a release (409 if the version
+/// exists).
+pub async fn create_agent_release(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(req): Json<CreateAgentReleaseRequest>,
+) -> ApiResult<(StatusCode, Json<AgentRelease>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    let version = req.version.trim();
+    if version.is_empty() || version.contains(char::is_whitespace) {
+        return Err(ApiError::BadRequest(
+            "version must be a single non-empty word".into(),
+        ));
+    }
+    if !(req.url.starts_with("http://") || req.url.starts_with("https://")) {
+        return Err(ApiError::BadRequest("url must be an http(s) URL".into()));
+    }
+    let sha256 =
+        crate::agent_releases::normalize_sha256(&req.sha256).map_err(ApiError::BadRequest)?;
+
+    let release = AgentRelease {
+        version: version.to_string(),
+        url: req.url,
+        sha256,
+        notes: req.notes.filter(|n| !n.trim().is_empty()),
+        created_by,
+        created_at: Utc::now(),
+    };
+    if !state.store.insert_agent_release(&release).await? {
+        return Err(ApiError::Conflict(format!(
+            "agent release {} already exists",
+            release.version
+        )));
+    }
+    tracing::info!(
+        version = %release.version,
+        url = %release.url,
+        "agent release registered"
+    );
+    Ok((StatusCode::CREATED, Json(release)))
+}
+
+/// GET /api/v1/agent-releases — list releases, newest first. The total is
+/// in `X-Total-Count`.
+pub async fn list_agent_releases(
+    State(state): State<AppState>,
+    Query(query): Query<ListAgentReleasesQuery>,
+) -> ApiResult<Response> {
+    let releases = state.store.list_agent_releases().await?;
+    let total = releases.len() as u64;
+    let page = pagination::slice(releases, query.offset, pagination::limit(query.limit));
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/agent-releases/:version — one release.
+pub async fn get_agent_release(
+    State(state): State<AppState>,
+    Path(version): Path<String>,
+) -> ApiResult<Json<AgentRelease>> {
+    Ok(Json(release(&state, &version).await?))
+}
+
+async fn release(state: &AppState, version: &str) -> ApiResult<AgentRelease> {
+    state
+        .store
+        .agent_release(version)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("agent release {version} not found")))
+}
+
+/// POST /api/v1/agent-releases/:version/deploy — set the release as the
+/// desired state of the `update` shadow of the listed devices and of every
+/// device in `fleet_id`. Offline devices get it when they reconnect.
+pub async fn deploy_agent_release(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(version): Path<String>,
+    Json(req): Json<DeployAgentReleaseRequest>,
+) -> ApiResult<Json<DeployAgentReleaseResponse>> {
+    let release = release(&state, &version).await?;
+    let actor = crate::auth::actor(principal.as_deref(), req.requested_by);
+    if actor.trim().is_empty() {
+        return Err(ApiError::BadRequest("requested_by is required".into()));
+    }
+    if req.device_ids.is_empty() && req.fleet_id.is_none() {
+        return Err(ApiError::BadRequest(
+            "name device_ids or a fleet_id to deploy to".into(),
+        ));
+    }
+
+    let mut devices = Vec::new();
+    for reference in &req.device_ids {
+        devices.push(crate::device_identity::resolve_existing(&state, reference).await?);
+    }
+    if let Some(fleet_id) = &req.fleet_id {
+        let members = crate::routes::fleets::fleet_devices(&state, fleet_id).await?;
+        devices.extend(members.into_iter().map(|(device_id, _)| device_id));
+    }
+    devices.sort();
+    devices.dedup();
+
+    if let Some(user) = principal.as_deref() {
+        for device_id in &devices {
+            let fleet_id = crate::auth::device_fleet(&state, device_id)
+                .await
+                .unwrap_or_default();
+            if !user.can_access_fleet(&fleet_id) {
+                return Err(ApiError::Forbidden(format!(
+                    "no access to fleet '{fleet_id}' of device '{device_id}'"
+                )));
+            }
+        }
+    }
+
+    let desired =
+        serde_json::to_value(release.update()).map_err(|e| ApiError::Internal(e.to_string()))?;
+    for device_id in &devices {
+        let _ = crate::routes::shadows::apply_desired(
+            &state,
+            &actor,
+            device_id.clone(),
+            UPDATE_SHADOW.to_string(),
+            desired.clone(),
+        )
+        .await
+        .map_err(|status| ApiError::Internal(format!("failed to update {device_id}: {status}")))?;
+    }
+    tracing::info!(
+        version = %release.version,
+        devices = devices.len(),
+        "agent release deployed"
+    );
+    Ok(Json(DeployAgentReleaseResponse {
+        version: release.version,
+        devices,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_mqtt_channel::MockChannel;
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn post(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn release(version: &str) -> serde_json::Value {
+        serde_json::json!({
+            "version": version,
+            "url": format!("https://releases.example.com/zc-fleet-agent-{version}"),
+            "sha256": "AB".repeat(32),
+            "created_by": "alice",
+        })
+    }
+
+    #[tokio::test]
+    async fn releases_are_registered_once() {
+        let state = AppState::with_sample_data();
+        let (status, created) =
+            send(&state, post("/api/v1/agent-releases", release("0.3.0"))).await;
+        assert_eq!(status, StatusCode::CREATED, "{created}");
+        assert_eq!(created["sha256"], "ab".repeat(32));
+
+        let (status, _) = send(&state, post("/api/v1/agent-releases", release("0.3.0"))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        send(&state, post("/api/v1/agent-releases", release("0.3.1"))).await;
+
+        let (_, list) = send(
+            &state,
+            Request::get("/api/v1/agent-releases")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        let versions: Vec<_> = list
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["version"].as_str().unwrap())
+            .collect();
+        assert_eq!(versions, ["0.3.1", "0.3.0"]);
+
+        for (field, value) in [
+            ("sha256", serde_json::json!("abc")),
+            ("url", serde_json::json!("ftp://releases.example.com/agent")),
+            ("version", serde_json::json!(" ")),
+        ] {
+            let mut body = release("0.4.0");
+            body[field] = value;
+            let (status, _) = send(&state, post("/api/v1/agent-releases", body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{field}");
+        }
+    }
+
+    #[tokio::test]
+    async fn deploy_sets_the_update_shadow() {
+        let mqtt = Arc::new(MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        send(&state, post("/api/v1/agent-releases", release("0.3.0"))).await;
+
+        let (status, deployed) = send(
+            &state,
+            post(
+                "/api/v1/agent-releases/0.3.0/deploy",
+                serde_json::json!({"device_ids": ["rpi-001"], "requested_by": "alice"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{deployed}");
+        assert_eq!(deployed["devices"], serde_json::json!(["rpi-001"]));
+
+        let deltas = mqtt.published_to("fleet/fleet-alpha/rpi-001/shadow/delta");
+        assert_eq!(deltas.len(), 1);
+        let delta: zc_protocol::shadows::ShadowDelta =
+            serde_json::from_slice(&deltas[0].payload).unwrap();
+        assert_eq!(delta.shadow_name, "update");
+        assert_eq!(delta.delta["agent_version"], "0.3.0");
+        assert_eq!(delta.delta["sha256"], "ab".repeat(32));
+
+        let (status, _) = send(
+            &state,
+            post(
+                "/api/v1/agent-releases/9.9.9/deploy",
+                serde_json::json!({"device_ids": ["rpi-001"], "requested_by": "alice"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(
+            &state,
+            post(
+                "/api/v1/agent-releases/0.3.0/deploy",
+                serde_json::json!({"requested_by": "alice"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
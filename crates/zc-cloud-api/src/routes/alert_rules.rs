@@ -0,0 +1,333 @@
+//! Alert rule endpoints.
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::alert_rules::{AlertRule, RuleCondition, RuleFilter};
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::state::AppState;
+
+/// Request body for creating an alert rule.
+#[derive(Debug, Deserialize)]
+pub struct CreateAlertRuleRequest {
+    pub name: String,
+    /// Watched fleet; every device in it unless `device_id` is set.
+    pub fleet_id: String,
+    /// Single watched device ID or alias.
+    pub device_id: Option<String>,
+    /// `{"type": "telemetry", "metric", "op", "threshold"}`,
+    /// `{"type": "dtc_severity", "min_severity"}` or
+    /// `{"type": "heartbeat_gap", "seconds"}`.
+    pub condition: RuleCondition,
+    /// http(s) URL each firing is POSTed to.
+    pub webhook_url: Option<String>,
+    /// Owner (the authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub created_by: String,
+    /// Start disabled when `false`.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Query parameters for the rule list.
+#[derive(Debug, Deserialize)]
+pub struct ListAlertRulesQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    pub fleet_id: Option<String>,
+    /// Device ID or alias.
+    pub device_id: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// POST /api/v1/alert-rules — create an alerting rule.
+pub async fn create_alert_rule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(mut req): Json<CreateAlertRuleRequest>,
+) -> ApiResult<(StatusCode, Json<AlertRule>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    req.condition.validate().map_err(ApiError::BadRequest)?;
+    if let Some(url) = &req.webhook_url
+        && !(url.starts_with("http://") || url.starts_with("https://"))
+    {
+        return Err(ApiError::BadRequest(
+            "webhook_url must be an http(s) URL".into(),
+        ));
+    }
+    if let Some(Extension(user)) = &principal
+        && !user.can_access_fleet(&req.fleet_id)
+    {
+        return Err(ApiError::Forbidden(format!(
+            "no access to fleet '{}'",
+            req.fleet_id
+        )));
+    }
+
+    let device_id = match req.device_id.take() {
+        Some(reference) => {
+            let device_id = crate::device_identity::resolve_existing(&state, &reference).await?;
+            if let Some(fleet) = crate::auth::device_fleet(&state, &device_id).await
+                && fleet != req.fleet_id
+            {
+                return Err(ApiError::BadRequest(format!(
+                    "device '{device_id}' belongs to fleet '{fleet}', not '{}'",
+                    req.fleet_id
+                )));
+            }
+            Some(device_id)
+        }
+        None => None,
+    };
+
+    let now = Utc::now();
+    let rule = AlertRule {
+        id: Uuid::now_v7(),
+        name: req.name.trim().to_string(),
+        fleet_id: req.fleet_id,
+        device_id,
+        condition: req.condition,
+        webhook_url: req.webhook_url,
+        enabled: req.enabled,
+        created_by,
+        created_at: now,
+        updated_at: now,
+    };
+    crate::alert_rules::create(&state, &rule).await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+/// GET /api/v1/alert-rules — list rules, oldest first.
+///
+/// Filtered by `fleet_id`, `device_id` and `enabled`, paged with
+/// `limit`/`offset`. The unpaged match count is in `X-Total-Count`.
+pub async fn list_alert_rules(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListAlertRulesQuery>,
+) -> ApiResult<Response> {
+    let device_id = match &query.device_id {
+        Some(reference) => Some(crate::device_identity::resolve(&state, reference).await?),
+        None => None,
+    };
+    let filter = RuleFilter {
+        fleet_id: query.fleet_id,
+        device_id,
+        enabled: query.enabled,
+        fleets: principal.and_then(|Extension(user)| user.tenants),
+    };
+    let matching = crate::alert_rules::list(&state, &filter).await?;
+    let total = matching.len() as u64;
+    let page = pagination::slice(matching, query.offset, pagination::limit(query.limit));
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/alert-rules/:id — one rule.
+pub async fn get_alert_rule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<AlertRule>> {
+    let rule = crate::alert_rules::get(&state, id).await?;
+    check_access(principal.as_deref(), &rule)?;
+    Ok(Json(rule))
+}
+
+/// POST /api/v1/alert-rules/:id/enable — evaluate the rule again.
+pub async fn enable_alert_rule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<AlertRule>> {
+    set_enabled(&state, principal, id, true).await
+}
+
+/// POST /api/v1/alert-rules/:id/disable — stop evaluating the rule.
+pub async fn disable_alert_rule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<AlertRule>> {
+    set_enabled(&state, principal, id, false).await
+}
+
+/// DELETE /api/v1/alert-rules/:id — remove a rule. Alerts it raised stay.
+pub async fn delete_alert_rule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let rule = crate::alert_rules::get(&state, id).await?;
+    check_access(principal.as_deref(), &rule)?;
+    crate::alert_rules::delete(&state, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_enabled(
+    state: &AppState,
+    principal: Option<Extension<Principal>>,
+    id: Uuid,
+    enabled: bool,
+) -> ApiResult<Json<AlertRule>> {
+    let rule = crate::alert_rules::get(state, id).await?;
+    check_access(principal.as_deref(), &rule)?;
+    Ok(Json(
+        crate::alert_rules::set_enabled(state, id, enabled).await?,
+    ))
+}
+
+fn check_access(principal: Option<&Principal>, rule: &AlertRule) -> ApiResult<()> {
+    match principal {
+        Some(user) if !user.can_access_fleet(&rule.fleet_id) => Err(ApiError::Forbidden(format!(
+            "no access to fleet '{}'",
+            rule.fleet_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn post(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn alert_rule_lifecycle() {
+        let state = AppState::with_sample_data();
+        let (status, rule) = send(
+            &state,
+            post(
+                "/api/v1/alert-rules",
+                serde_json::json!({
+                    "name": "overheating",
+                    "fleet_id": "fleet-alpha",
+                    "condition": {"type": "telemetry", "metric": "coolant_temp",
+                        "op": "gt", "threshold": 110.0},
+                    "created_by": "alice",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{rule}");
+        assert_eq!(rule["condition"]["type"], "telemetry");
+        let id = rule["id"].as_str().unwrap().to_string();
+
+        // Ingesting an over-threshold reading raises an alert.
+        let (status, _) = send(
+            &state,
+            post(
+                "/api/v1/devices/rpi-002/telemetry",
+                serde_json::json!({"readings": [{"metric_name": "coolant_temp",
+                    "value_numeric": 121.5, "source": "obd2"}]}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, alerts) = send(&state, get("/api/v1/alerts?kind=rule")).await;
+        assert_eq!(alerts.as_array().unwrap().len(), 1);
+        assert_eq!(alerts[0]["device_id"], "rpi-002");
+
+        let (_, disabled) = send(
+            &state,
+            post(
+                &format!("/api/v1/alert-rules/{id}/disable"),
+                serde_json::json!({}),
+            ),
+        )
+        .await;
+        assert_eq!(disabled["enabled"], false);
+        let (_, list) = send(&state, get("/api/v1/alert-rules?enabled=true")).await;
+        assert!(list.as_array().unwrap().is_empty());
+
+        let delete = Request::delete(format!("/api/v1/alert-rules/{id}"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&state, delete).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&state, get(&format!("/api/v1/alert-rules/{id}"))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn invalid_alert_rules_are_rejected() {
+        let state = AppState::with_sample_data();
+        for (body, expected) in [
+            (
+                serde_json::json!({"name": " ", "fleet_id": "fleet-alpha", "created_by": "alice",
+                    "condition": {"type": "heartbeat_gap", "seconds": 60}}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "fleet_id": "fleet-alpha", "created_by": "alice",
+                    "condition": {"type": "heartbeat_gap", "seconds": 0}}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "fleet_id": "fleet-alpha", "created_by": "alice",
+                    "condition": {"type": "dtc_severity", "min_severity": "critical"},
+                    "webhook_url": "ftp://hooks.example.com"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "fleet_id": "fleet-beta", "created_by": "alice",
+                    "device_id": "rpi-001",
+                    "condition": {"type": "heartbeat_gap", "seconds": 60}}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "fleet_id": "fleet-alpha", "created_by": "alice",
+                    "condition": {"type": "uptime"}}),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ] {
+            let (status, error) = send(&state, post("/api/v1/alert-rules", body.clone())).await;
+            assert_eq!(status, expected, "{body} -> {error}");
+        }
+        let all = crate::alert_rules::list(&state, &Default::default())
+            .await
+            .unwrap();
+        assert!(all.is_empty());
+    }
+}
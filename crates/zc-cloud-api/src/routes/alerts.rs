@@ -0,0 +1,397 @@
+//! Alert management endpoints.
+
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::alerts::{Alert, AlertFilter, AlertState, Transition};
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::state::AppState;
+
+/// Query parameters for the alert list.
+#[derive(Debug, Deserialize)]
+pub struct ListAlertsQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    /// `open`, `acknowledged`, `snoozed`, `resolved`, or `unresolved` for
+    /// the first three.
+    pub state: Option<String>,
+    pub fleet_id: Option<String>,
+    /// Device ID or alias.
+    pub device_id: Option<String>,
+    pub kind: Option<String>,
+}
+
+/// Request body for acknowledging or resolving an alert.
+#[derive(Debug, Deserialize)]
+pub struct AlertActionRequest {
+    /// Acting operator. Replaced by the authenticated user when OIDC is on.
+    #[serde(default)]
+    pub actor: String,
+    /// Optional note kept in the alert's history.
+    pub comment: Option<String>,
+}
+
+/// Request body for snoozing an alert: `until` or `duration_secs`.
+#[derive(Debug, Deserialize)]
+pub struct SnoozeAlertRequest {
+    #[serde(default)]
+    pub actor: String,
+    pub comment: Option<String>,
+    pub until: Option<DateTime<Utc>>,
+    pub duration_secs: Option<u64>,
+}
+
+/// GET /api/v1/alerts — list alerts, most recently seen first.
+///
+/// Filtered by `state`, `fleet_id`, `device_id` and `kind`, paged with
+/// `limit`/`offset`. The unpaged match count is in `X-Total-Count`.
+pub async fn list_alerts(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListAlertsQuery>,
+) -> ApiResult<Response> {
+    let (alert_state, unresolved) = match query.state.as_deref() {
+        None => (None, false),
+        Some("unresolved") => (None, true),
+        Some(s) => match AlertState::parse(s) {
+            Some(s) => (Some(s), false),
+            None => {
+                return Err(ApiError::BadRequest(format!(
+                    "unknown alert state '{s}': expected open, acknowledged, snoozed, resolved or unresolved"
+                )));
+            }
+        },
+    };
+    let device_id = match &query.device_id {
+        Some(reference) => Some(crate::device_identity::resolve(&state, reference).await?),
+        None => None,
+    };
+    let filter = AlertFilter {
+        state: alert_state,
+        unresolved,
+        fleet_id: query.fleet_id,
+        device_id,
+        kind: query.kind,
+        fleets: principal.and_then(|Extension(user)| user.tenants),
+        limit: pagination::limit(query.limit),
+        offset: query.offset,
+    };
+    let (page, total) = crate::alerts::list(&state, &filter).await?;
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/alerts/:id — an alert with its history.
+pub async fn get_alert(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Alert>> {
+    let alert = crate::alerts::get(&state, id).await?;
+    check_access(principal.as_deref(), &alert)?;
+    Ok(Json(alert))
+}
+
+/// POST /api/v1/alerts/:id/acknowledge — take ownership of an open or
+/// snoozed alert.
+pub async fn acknowledge_alert(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AlertActionRequest>,
+) -> ApiResult<Json<Alert>> {
+    act(
+        &state,
+        principal,
+        id,
+        Transition::Acknowledge,
+        req.actor,
+        req.comment,
+    )
+    .await
+}
+
+/// POST /api/v1/alerts/:id/resolve — close an alert for good.
+pub async fn resolve_alert(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AlertActionRequest>,
+) -> ApiResult<Json<Alert>> {
+    act(
+        &state,
+        principal,
+        id,
+        Transition::Resolve,
+        req.actor,
+        req.comment,
+    )
+    .await
+}
+
+/// POST /api/v1/alerts/:id/snooze — silence an alert until a time; it
+/// reopens when the snooze ends.
+pub async fn snooze_alert(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<SnoozeAlertRequest>,
+) -> ApiResult<Json<Alert>> {
+    let until = match (req.until, req.duration_secs) {
+        (Some(until), None) => until,
+        (None, Some(secs)) => i64::try_from(secs)
+            .ok()
+            .and_then(Duration::try_seconds)
+            .and_then(|d| Utc::now().checked_add_signed(d))
+            .ok_or_else(|| ApiError::BadRequest("duration_secs is too large".into()))?,
+        _ => {
+            return Err(ApiError::BadRequest(
+                "exactly one of until or duration_secs is required".into(),
+            ));
+        }
+    };
+    act(
+        &state,
+        principal,
+        id,
+        Transition::Snooze { until },
+        req.actor,
+        req.comment,
+    )
+    .await
+}
+
+async fn act(
+    state: &AppState,
+    principal: Option<Extension<Principal>>,
+    id: Uuid,
+    transition: Transition,
+    actor: String,
+    comment: Option<String>,
+) -> ApiResult<Json<Alert>> {
+    let actor = crate::auth::actor(principal.as_deref(), actor);
+    if actor.trim().is_empty() {
+        return Err(ApiError::BadRequest("actor is required".into()));
+    }
+    let alert = crate::alerts::transition(state, id, transition, &actor, comment, |alert| {
+        check_access(principal.as_deref(), alert)
+    })
+    .await?;
+    Ok(Json(alert))
+}
+
+fn check_access(principal: Option<&Principal>, alert: &Alert) -> ApiResult<()> {
+    match principal {
+        Some(user) if !user.can_access_fleet(&alert.fleet_id) => Err(ApiError::Forbidden(format!(
+            "no access to fleet '{}'",
+            alert.fleet_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alerts::{KIND_TOOL_FAILURE_RATE, NewAlert};
+    use crate::events::WsEvent;
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(
+        state: &AppState,
+        request: Request<Body>,
+    ) -> (StatusCode, Option<String>, serde_json::Value) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let total = response
+            .headers()
+            .get("x-total-count")
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            total,
+            serde_json::from_slice(&body).unwrap_or_default(),
+        )
+    }
+
+    fn post(uri: &str, body: &str) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    async fn raise(state: &AppState, fleet_id: &str, device_id: &str, tool: &str) {
+        crate::alerts::raise(
+            state,
+            NewAlert {
+                kind: KIND_TOOL_FAILURE_RATE.into(),
+                fleet_id: fleet_id.into(),
+                device_id: device_id.into(),
+                scope: "device".into(),
+                title: format!("{tool} failing on {device_id}"),
+                detail: serde_json::json!({"tool_name": tool}),
+                dedup_key: format!("{fleet_id}:{device_id}:{tool}"),
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn acknowledge_snooze_and_resolve_are_broadcast() {
+        let state = AppState::with_sample_data();
+        let mut events = state.event_tx.subscribe();
+        raise(&state, "fleet-alpha", "rpi-001", "read_dtcs").await;
+        raise(&state, "fleet-alpha", "rpi-001", "read_dtcs").await;
+        raise(&state, "fleet-alpha", "rpi-002", "read_pid").await;
+        raise(&state, "fleet-beta", "sbc-010", "read_dtcs").await;
+
+        let (status, total, alerts) =
+            send(&state, get("/api/v1/alerts?fleet_id=fleet-alpha&limit=1")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(total.as_deref(), Some("2"));
+        assert_eq!(alerts.as_array().unwrap().len(), 1);
+
+        let (_, _, alerts) = send(&state, get("/api/v1/alerts?device_id=rpi-001")).await;
+        assert_eq!(alerts[0]["occurrences"], 2);
+        let id = alerts[0]["id"].as_str().unwrap().to_string();
+
+        let (status, _, alert) = send(
+            &state,
+            post(
+                &format!("/api/v1/alerts/{id}/snooze"),
+                r#"{"actor": "alice", "duration_secs": 3600}"#,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(alert["state"], "snoozed");
+
+        let (status, _, alert) = send(
+            &state,
+            post(
+                &format!("/api/v1/alerts/{id}/acknowledge"),
+                r#"{"actor": "bob", "comment": "checking the ECU"}"#,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(alert["state"], "acknowledged");
+        assert_eq!(alert["acknowledged_by"], "bob");
+        assert_eq!(alert["snoozed_until"], serde_json::Value::Null);
+
+        let (_, total, _) = send(&state, get("/api/v1/alerts?state=acknowledged")).await;
+        assert_eq!(total.as_deref(), Some("1"));
+
+        let (status, _, alert) = send(
+            &state,
+            post(
+                &format!("/api/v1/alerts/{id}/resolve"),
+                r#"{"actor": "bob"}"#,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(alert["resolved_by"], "bob");
+        assert_eq!(alert["history"].as_array().unwrap().len(), 5);
+
+        let (status, _, _) = send(
+            &state,
+            post(
+                &format!("/api/v1/alerts/{id}/acknowledge"),
+                r#"{"actor": "bob"}"#,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        // Firing again after resolution opens a new alert.
+        raise(&state, "fleet-alpha", "rpi-001", "read_dtcs").await;
+        let (_, total, _) = send(&state, get("/api/v1/alerts?state=unresolved")).await;
+        assert_eq!(total.as_deref(), Some("3"));
+
+        let mut actions = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                WsEvent::AlertRaised { .. } => actions.push("raised".to_string()),
+                WsEvent::AlertUpdated { action, .. } => actions.push(action),
+                _ => {}
+            }
+        }
+        assert_eq!(
+            actions,
+            [
+                "raised",
+                "reoccurred",
+                "raised",
+                "raised",
+                "snoozed",
+                "acknowledged",
+                "resolved",
+                "raised",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_are_rejected() {
+        let state = AppState::with_sample_data();
+        raise(&state, "fleet-alpha", "rpi-001", "read_dtcs").await;
+        let (_, _, alerts) = send(&state, get("/api/v1/alerts")).await;
+        let id = alerts[0]["id"].as_str().unwrap().to_string();
+
+        let (status, _, _) = send(&state, get("/api/v1/alerts?state=closed")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = send(
+            &state,
+            post(&format!("/api/v1/alerts/{id}/acknowledge"), "{}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = send(
+            &state,
+            post(
+                &format!("/api/v1/alerts/{id}/snooze"),
+                r#"{"actor": "alice"}"#,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = send(
+            &state,
+            post(
+                &format!("/api/v1/alerts/{id}/snooze"),
+                r#"{"actor": "alice", "until": "2020-01-01T00:00:00Z"}"#,
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = send(
+            &state,
+            get(&format!("/api/v1/alerts/{}", uuid::Uuid::now_v7())),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,99 @@
+//! Natural-language fleet query endpoint.
+
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::analytics::AnalyticsAnswer;
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Query parameters for a fleet query.
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQueryParams {
+    /// The question, e.g. "which devices reported P0300 this week".
+    pub q: String,
+    /// Only this fleet's devices and commands.
+    pub fleet_id: Option<String>,
+}
+
+/// GET /api/v1/analytics/query — answer a fleet question from stored data
+/// without sending anything to a device.
+///
+/// Users limited to some fleets get answers over those fleets only.
+pub async fn query_analytics(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(params): Query<AnalyticsQueryParams>,
+) -> ApiResult<Json<AnalyticsAnswer>> {
+    let Some(parsed) = crate::analytics::parse(&params.q, Utc::now()) else {
+        return Err(ApiError::BadRequest(format!(
+            "could not understand '{}'; try e.g. \"which devices reported P0300 this week\", \
+             \"which commands failed today\", \"devices with coolant_temp above 110 in the \
+             last 24 hours\" or \"which devices are offline\"",
+            params.q.trim()
+        )));
+    };
+    let tenants = principal.and_then(|Extension(user)| user.tenants);
+    let fleets = match params.fleet_id {
+        Some(fleet_id) => {
+            if tenants.as_ref().is_some_and(|t| !t.contains(&fleet_id)) {
+                return Err(ApiError::Forbidden(format!(
+                    "no access to fleet '{fleet_id}'"
+                )));
+            }
+            Some(vec![fleet_id])
+        }
+        None => tenants,
+    };
+    Ok(Json(
+        crate::analytics::answer(&state, parsed, fleets).await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn answers_fleet_questions() {
+        let state = AppState::with_sample_data();
+        let (status, answer) = send(
+            &state,
+            "/api/v1/analytics/query?q=which%20devices%20are%20online&fleet_id=fleet-alpha",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{answer}");
+        assert_eq!(answer["query"]["type"], "device_status");
+        assert_eq!(answer["summary"], "2 devices are online");
+        assert_eq!(answer["rows"][0]["device_id"], "rpi-001");
+
+        let (status, answer) = send(
+            &state,
+            "/api/v1/analytics/query?q=which%20devices%20reported%20P0300%20this%20week",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(answer["query"]["code"], "P0300");
+        assert_eq!(answer["window"], "this week");
+        assert!(answer["rows"].as_array().unwrap().is_empty());
+
+        let (status, _) = send(&state, "/api/v1/analytics/query?q=make%20coffee").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,151 @@
+//! Audit trail endpoint.
+
+use axum::Extension;
+use axum::extract::{Query, State};
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::audit::{AuditAction, AuditFilter, AuditOutcome};
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::state::AppState;
+
+/// Query parameters for the audit trail.
+#[derive(Debug, Deserialize)]
+pub struct ListAuditQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    pub actor: Option<String>,
+    /// e.g. `command_dispatched`, `shell_executed`.
+    pub action: Option<String>,
+    /// `succeeded`, `failed` or `rejected`.
+    pub outcome: Option<String>,
+    pub command_id: Option<Uuid>,
+    /// Device ID or alias.
+    pub device_id: Option<String>,
+    pub fleet_id: Option<String>,
+    /// Entries at or after this RFC 3339 time.
+    pub since: Option<DateTime<Utc>>,
+    /// Entries before this RFC 3339 time.
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// GET /api/v1/audit — the audit trail, newest first.
+///
+/// Filtered by `actor`, `action`, `outcome`, `command_id`, `device_id`,
+/// `fleet_id` and the `since`/`until` window, paged with `limit`/`offset`.
+/// The unpaged match count is in `X-Total-Count`. Users limited to some
+/// fleets only see entries of those fleets.
+pub async fn list_audit(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListAuditQuery>,
+) -> ApiResult<Response> {
+    let action = match query.action.as_deref() {
+        Some(a) => Some(
+            AuditAction::parse(a)
+                .ok_or_else(|| ApiError::BadRequest(format!("unknown audit action '{a}'")))?,
+        ),
+        None => None,
+    };
+    let outcome = match query.outcome.as_deref() {
+        Some(o) => Some(AuditOutcome::parse(o).ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "unknown audit outcome '{o}': expected succeeded, failed or rejected"
+            ))
+        })?),
+        None => None,
+    };
+    let device_id = match &query.device_id {
+        Some(reference) => Some(crate::device_identity::resolve(&state, reference).await?),
+        None => None,
+    };
+    let filter = AuditFilter {
+        actor: query.actor,
+        action,
+        outcome,
+        command_id: query.command_id,
+        device_id,
+        fleet_id: query.fleet_id,
+        since: query.since,
+        until: query.until,
+        fleets: principal.and_then(|Extension(user)| user.tenants),
+        limit: pagination::limit(query.limit),
+        offset: query.offset,
+    };
+    let (page, total) = crate::audit::list(&state, &filter).await?;
+    Ok(pagination::with_total(page, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn get(app: &axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn dispatches_are_audited_and_filterable() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state);
+        for (device_id, initiator) in [("rpi-001", "alice"), ("rpi-002", "bob")] {
+            let body = serde_json::json!({
+                "device_id": device_id,
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": initiator,
+            });
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::post("/api/v1/commands")
+                        .header("content-type", "application/json")
+                        .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let (status, all) = get(&app, "/api/v1/audit?action=command_dispatched").await;
+        assert_eq!(status, StatusCode::OK);
+        let actors: Vec<_> = all
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["actor"].as_str().unwrap())
+            .collect();
+        assert_eq!(actors, ["bob", "alice"]);
+        assert_eq!(all[0]["outcome"], "succeeded");
+        assert_eq!(all[0]["fleet_id"], "fleet-alpha");
+        assert_eq!(all[0]["detail"]["command"], "read DTCs");
+
+        let (_, alice) = get(&app, "/api/v1/audit?actor=alice&device_id=rpi-001").await;
+        assert_eq!(alice.as_array().unwrap().len(), 1);
+        let (_, none) = get(&app, "/api/v1/audit?device_id=rpi-003").await;
+        assert!(none.as_array().unwrap().is_empty());
+
+        let (status, _) = get(&app, "/api/v1/audit?action=deleted").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,15 @@
+//! Authenticated-user endpoint.
+
+use axum::Extension;
+use axum::Json;
+
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+
+/// GET /api/v1/auth/me — the user behind the bearer token, with the role and
+/// fleets it maps to. 404 when OIDC is not configured.
+pub async fn me(principal: Option<Extension<Principal>>) -> ApiResult<Json<Principal>> {
+    principal
+        .map(|Extension(p)| Json(p))
+        .ok_or_else(|| ApiError::NotFound("authentication is not enabled".into()))
+}
@@ -0,0 +1,307 @@
+//! Device certificate endpoint (see [`crate::certificates`]).
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use zc_protocol::shadows::{CERTIFICATE_SHADOW, CertificateRotation};
+
+use crate::audit::{AuditAction, AuditEntry, AuditOutcome};
+use crate::auth::Principal;
+use crate::certificates::{CertificateAuthority, CertificateError, ConnectionBundle};
+use crate::device_identity;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Request body for issuing a certificate.
+#[derive(Debug, Default, Deserialize)]
+pub struct IssueCertificateRequest {
+    /// PEM CSR from the device. Without one, a key pair is generated and
+    /// returned with the certificate.
+    pub csr: Option<String>,
+    /// Defaults to `DEVICE_CERT_VALIDITY_DAYS`.
+    pub validity_days: Option<u32>,
+    /// Also send the certificate to the device through its `certificate`
+    /// shadow, for rotation without a restart. Needs a `csr`.
+    #[serde(default)]
+    pub deliver: bool,
+}
+
+/// An issued certificate with everything the agent needs to connect.
+#[derive(Debug, Serialize)]
+pub struct CertificateBundle {
+    pub device_id: String,
+    pub certificate_id: String,
+    pub certificate_pem: String,
+    /// Only when the key pair was generated here. It is not stored; this
+    /// response is the only copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key_pem: Option<String>,
+    pub ca_cert_pem: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// `fleet_id`, `device_id` and the `[mqtt]` section of the agent config.
+    pub config: ConnectionBundle,
+    /// Whether the certificate was queued on the device's shadow.
+    pub delivered: bool,
+}
+
+/// POST /api/v1/devices/{id}/certificate — issue a client certificate.
+///
+/// 404 when no provisioning CA is configured; 400 for an invalid CSR or
+/// validity, or `deliver` without a CSR. Every attempt is audited.
+pub async fn issue_certificate(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Json(req): Json<IssueCertificateRequest>,
+) -> ApiResult<(StatusCode, Json<CertificateBundle>)> {
+    let Some(ca) = state.certificate_authority.clone() else {
+        return Err(ApiError::NotFound(
+            "certificate provisioning is not enabled".into(),
+        ));
+    };
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let actor = crate::auth::actor(principal.as_deref(), "operator".into());
+    let fleet_id = crate::auth::device_fleet(&state, &device_id).await;
+
+    let mut detail = serde_json::json!({
+        "csr": req.csr.is_some(),
+        "validity_days": req.validity_days,
+        "deliver": req.deliver,
+    });
+    let result = issue(&state, &ca, &actor, &device_id, fleet_id.as_deref(), req).await;
+    let outcome = match &result {
+        Ok(bundle) => {
+            detail["certificate_id"] = bundle.certificate_id.clone().into();
+            detail["not_after"] = bundle.not_after.to_rfc3339().into();
+            AuditOutcome::Succeeded
+        }
+        Err(ApiError::Internal(_)) => AuditOutcome::Failed,
+        Err(e) => {
+            detail["error"] = e.to_string().into();
+            AuditOutcome::Rejected
+        }
+    };
+    let mut entry = AuditEntry::device(&actor, AuditAction::CertificateIssued, &device_id, detail)
+        .with_outcome(outcome);
+    if let Some(fleet_id) = &fleet_id {
+        entry = entry.with_fleet(fleet_id);
+    }
+    crate::audit::record(&state, entry).await?;
+    result.map(|bundle| (StatusCode::CREATED, Json(bundle)))
+}
+
+async fn issue(
+    state: &AppState,
+    ca: &CertificateAuthority,
+    actor: &str,
+    device_id: &str,
+    fleet_id: Option<&str>,
+    req: IssueCertificateRequest,
+) -> ApiResult<CertificateBundle> {
+    if req.deliver && req.csr.is_none() {
+        return Err(ApiError::BadRequest(
+            "deliver needs a csr: private keys are never sent to devices".into(),
+        ));
+    }
+
+    let issued = ca
+        .issue(device_id, req.validity_days, req.csr.as_deref())
+        .map_err(|e| match e {
+            CertificateError::InvalidCsr(_) | CertificateError::InvalidValidity => {
+                ApiError::BadRequest(e.to_string())
+            }
+            _ => ApiError::Internal(e.to_string()),
+        })?;
+
+    if let Some(pool) = &state.pool {
+        crate::db::devices::set_certificate(pool, device_id, &issued.certificate_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+    } else if let Some(device) = state.devices.write().await.get_mut(device_id) {
+        device.certificate_id = Some(issued.certificate_id.clone());
+        device.updated_at = Utc::now();
+    }
+
+    if req.deliver {
+        let rotation = CertificateRotation {
+            certificate_id: issued.certificate_id.clone(),
+            certificate_pem: issued.certificate_pem.clone(),
+            ca_cert_pem: Some(ca.ca_cert_pem().to_string()),
+        };
+        let desired =
+            serde_json::to_value(&rotation).map_err(|e| ApiError::Internal(e.to_string()))?;
+        // The device acknowledges by reporting the delta back.
+        let _shadow = crate::routes::shadows::apply_desired(
+            state,
+            actor,
+            device_id.to_string(),
+            CERTIFICATE_SHADOW.to_string(),
+            desired,
+        )
+        .await
+        .map_err(|status| {
+            ApiError::Internal(format!("failed to set certificate shadow: {status}"))
+        })?;
+    }
+
+    tracing::info!(
+        device_id = %device_id,
+        certificate_id = %issued.certificate_id,
+        not_after = %issued.not_after,
+        csr = req.csr.is_some(),
+        delivered = req.deliver,
+        "device certificate issued"
+    );
+
+    Ok(CertificateBundle {
+        device_id: device_id.to_string(),
+        certificate_id: issued.certificate_id,
+        certificate_pem: issued.certificate_pem,
+        private_key_pem: issued.private_key_pem,
+        ca_cert_pem: ca.ca_cert_pem().to_string(),
+        not_before: issued.not_before,
+        not_after: issued.not_after,
+        config: ca.bundle(fleet_id.unwrap_or("default"), device_id),
+        delivered: req.deliver,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certificates::DeviceEndpoint;
+    use crate::routes::build_router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn state_with_ca() -> AppState {
+        let mut state = AppState::with_sample_data();
+        let endpoint = DeviceEndpoint {
+            broker_host: "mqtt.example.com".into(),
+            broker_port: 8883,
+            protocol: Default::default(),
+        };
+        state.certificate_authority = Some(Arc::new(
+            CertificateAuthority::self_signed("ZeroClaw Test CA", endpoint).unwrap(),
+        ));
+        state
+    }
+
+    fn csr() -> String {
+        let key = rcgen::KeyPair::generate().unwrap();
+        rcgen::CertificateParams::default()
+            .serialize_request(&key)
+            .unwrap()
+            .pem()
+            .unwrap()
+    }
+
+    async fn post(
+        state: &AppState,
+        device: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = build_router(state.clone())
+            .oneshot(
+                Request::post(format!("/api/v1/devices/{device}/certificate"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn disabled_without_ca() {
+        let state = AppState::with_sample_data();
+        let (status, json) = post(&state, "rpi-001", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(json["error"].as_str().unwrap().contains("not enabled"));
+    }
+
+    #[tokio::test]
+    async fn issues_key_and_bundle() {
+        let state = state_with_ca();
+        let (status, json) = post(&state, "rpi-001", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(
+            json["private_key_pem"]
+                .as_str()
+                .unwrap()
+                .contains("PRIVATE KEY")
+        );
+        assert!(
+            json["ca_cert_pem"]
+                .as_str()
+                .unwrap()
+                .contains("CERTIFICATE")
+        );
+        assert_eq!(json["config"]["fleet_id"], "fleet-alpha");
+        assert_eq!(json["config"]["mqtt"]["client_id"], "rpi-001");
+        assert_eq!(json["config"]["mqtt"]["broker_host"], "mqtt.example.com");
+        assert_eq!(json["delivered"], false);
+
+        let certificate_id = json["certificate_id"].as_str().unwrap();
+        let devices = state.devices.read().await;
+        assert_eq!(
+            devices["rpi-001"].certificate_id.as_deref(),
+            Some(certificate_id)
+        );
+        let audit = state.audit_log.read().await;
+        let entry = audit.back().unwrap();
+        assert_eq!(entry.action, AuditAction::CertificateIssued);
+        assert_eq!(entry.outcome, Some(AuditOutcome::Succeeded));
+        assert_eq!(entry.detail["certificate_id"], certificate_id);
+    }
+
+    #[tokio::test]
+    async fn delivered_csr_certificate_lands_in_shadow() {
+        let state = state_with_ca();
+        let (status, json) = post(
+            &state,
+            "rpi-001",
+            serde_json::json!({"csr": csr(), "deliver": true}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(json.get("private_key_pem").is_none());
+        assert_eq!(json["delivered"], true);
+
+        let shadows = state.shadows.read().await;
+        let shadow = &shadows[&("rpi-001".to_string(), CERTIFICATE_SHADOW.to_string())];
+        let rotation: CertificateRotation = serde_json::from_value(shadow.desired.clone()).unwrap();
+        assert_eq!(rotation.certificate_id, json["certificate_id"]);
+        assert_eq!(rotation.certificate_pem, json["certificate_pem"]);
+    }
+
+    #[tokio::test]
+    async fn deliver_without_csr_is_rejected() {
+        let state = state_with_ca();
+        let (status, json) = post(&state, "rpi-001", serde_json::json!({"deliver": true})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(json["error"].as_str().unwrap().contains("csr"));
+        let key = ("rpi-001".to_string(), CERTIFICATE_SHADOW.to_string());
+        assert!(!state.shadows.read().await.contains_key(&key));
+
+        let audit = state.audit_log.read().await;
+        assert_eq!(audit.back().unwrap().outcome, Some(AuditOutcome::Rejected));
+    }
+
+    #[tokio::test]
+    async fn unknown_device_is_not_found() {
+        let state = state_with_ca();
+        let (status, _) = post(&state, "nope", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
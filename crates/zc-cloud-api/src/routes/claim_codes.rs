@@ -0,0 +1,229 @@
+//! Claim code endpoints for device onboarding (see [`crate::claim_codes`]).
+//!
+//! Creating and revoking codes is as privileged as provisioning (admin);
+//! codes are listed to anyone who can see their fleet, never with the code
+//! itself.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::audit::{AuditAction, AuditEntry};
+use crate::auth::Principal;
+use crate::claim_codes::{ClaimCode, DEFAULT_TTL_DAYS, IssuedClaimCode, NewClaimCode};
+use crate::device_tags::Tags;
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Request body for creating a claim code.
+#[derive(Debug, Deserialize)]
+pub struct CreateClaimCodeRequest {
+    /// Fleet the claiming device joins.
+    pub fleet_id: String,
+    /// Device ID to assign; derived from the hardware ID when absent.
+    pub device_id: Option<String>,
+    /// Tags the device is provisioned with.
+    #[serde(default)]
+    pub tags: Tags,
+    /// Initial runtime settings for the agent (a config update with a
+    /// `version`).
+    pub config: Option<serde_json::Value>,
+    /// Days until an unclaimed code expires (default 7, at most 90).
+    pub expires_in_days: Option<u32>,
+    /// Who created it (the authenticated user, when auth is on).
+    #[serde(default)]
+    pub created_by: String,
+}
+
+fn check_fleet(principal: Option<&Principal>, fleet_id: &str) -> ApiResult<()> {
+    match principal {
+        Some(user) if !user.can_access_fleet(fleet_id) => Err(ApiError::Forbidden(format!(
+            "no access to fleet '{fleet_id}'"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// POST /api/v1/claim-codes — create a one-time claim code (admin). The
+/// code is only in this response.
+pub async fn create_claim_code(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(req): Json<CreateClaimCodeRequest>,
+) -> ApiResult<(StatusCode, Json<IssuedClaimCode>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    check_fleet(principal.as_deref(), &req.fleet_id)?;
+
+    let issued = crate::claim_codes::create(
+        &state,
+        NewClaimCode {
+            fleet_id: req.fleet_id,
+            device_id: req.device_id,
+            tags: req.tags,
+            config: req.config,
+            ttl_days: req.expires_in_days.unwrap_or(DEFAULT_TTL_DAYS),
+            created_by,
+        },
+    )
+    .await?;
+    let code = &issued.claim_code;
+    crate::audit::record(
+        &state,
+        AuditEntry::fleet(
+            &code.created_by,
+            AuditAction::ClaimCodeCreated,
+            &code.fleet_id,
+            serde_json::json!({
+                "claim_code_id": code.id,
+                "device_id": code.device_id,
+                "expires_at": code.expires_at,
+            }),
+        ),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// GET /api/v1/claim-codes — codes in the caller's fleets, newest first.
+pub async fn list_claim_codes(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> ApiResult<Json<Vec<ClaimCode>>> {
+    let tenants = principal.and_then(|Extension(p)| p.tenants);
+    Ok(Json(
+        crate::claim_codes::list(&state, tenants.as_deref()).await?,
+    ))
+}
+
+/// GET /api/v1/claim-codes/:id — one code and whether it was claimed.
+pub async fn get_claim_code(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ClaimCode>> {
+    let code = crate::claim_codes::get(&state, id).await?;
+    check_fleet(principal.as_deref(), &code.fleet_id)?;
+    Ok(Json(code))
+}
+
+/// DELETE /api/v1/claim-codes/:id — revoke an unclaimed code (admin; 409
+/// once claimed).
+pub async fn revoke_claim_code(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let code = crate::claim_codes::get(&state, id).await?;
+    check_fleet(principal.as_deref(), &code.fleet_id)?;
+    crate::claim_codes::revoke(&state, id).await?;
+    let actor = crate::auth::actor(principal.as_deref(), "operator".into());
+    crate::audit::record(
+        &state,
+        AuditEntry::fleet(
+            actor,
+            AuditAction::ClaimCodeRevoked,
+            code.fleet_id,
+            serde_json::json!({"claim_code_id": id}),
+        ),
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn json(method: &str, uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn codes_are_shown_once_and_revocable_until_claimed() {
+        let app = build_router(AppState::with_sample_data());
+
+        let (status, created) = send(
+            app.clone(),
+            json(
+                "POST",
+                "/api/v1/claim-codes",
+                serde_json::json!({
+                    "fleet_id": "fleet-alpha",
+                    "device_id": "rpi-101",
+                    "created_by": "alice",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["code"].as_str().unwrap().len(), 19);
+        assert!(created["claimed_at"].is_null());
+        let id = created["id"].as_str().unwrap();
+
+        let (status, listed) = send(
+            app.clone(),
+            Request::get("/api/v1/claim-codes")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(listed[0]["device_id"], "rpi-101");
+        assert!(listed[0].get("code").is_none());
+        assert!(listed[0].get("sha256").is_none());
+
+        let (status, _) = send(
+            app.clone(),
+            json(
+                "POST",
+                "/api/v1/claim-codes",
+                serde_json::json!({
+                    "fleet_id": "fleet-alpha",
+                    "config": {"heartbeat_interval_secs": 15},
+                    "created_by": "alice",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(
+            app.clone(),
+            Request::delete(format!("/api/v1/claim-codes/{id}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(
+            app,
+            Request::get(format!("/api/v1/claim-codes/{id}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -1,102 +1,248 @@
 //! Command dispatch endpoints.
 
-use axum::Json;
-use axum::extract::{Path, State};
-use chrono::Utc;
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::audit::{AuditAction, AuditEntry, AuditOutcome};
+use crate::auth::Principal;
+use crate::command_queue;
+use crate::command_timing::Breakdown;
+use crate::db::commands::CommandFilter;
 use crate::error::{ApiError, ApiResult};
 use crate::events::WsEvent;
-use crate::state::{AppState, CommandRecord};
-use zc_protocol::commands::CommandEnvelope;
+use crate::openapi::ApiErrorBody;
+use crate::routes::{csv, pagination};
+use crate::state::AppState;
+use crate::store::{CommandSummary, command_status_name};
+use crate::structured_mode;
+use zc_protocol::commands::{ActionKind, CommandCancel, CommandEnvelope, CommandStatus, ErrorCode};
 
 /// Request body for dispatching a command.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct SendCommandRequest {
-    /// Target device ID.
+    /// Target device ID, or any of its aliases (VIN, serial, asset tag).
     pub device_id: String,
     /// Target fleet ID.
     pub fleet_id: String,
-    /// Natural-language command text.
+    /// Natural-language command text. Optional when `tool_name` is set.
+    #[serde(default)]
     pub command: String,
-    /// Who is sending this command.
+    /// Tool to run as-is, skipping inference. Required for fleets in
+    /// structured-only mode.
+    pub tool_name: Option<String>,
+    /// Arguments for `tool_name` (a JSON object; defaults to `{}`).
+    pub tool_args: Option<serde_json::Value>,
+    /// Who is sending this command (the authenticated user, when OIDC is on).
     pub initiated_by: String,
+    /// Run pre-flight readiness checks and refuse dispatch (412) on failure.
+    #[serde(default)]
+    pub preflight: bool,
+    /// Dispatch even if pre-flight checks fail.
+    #[serde(default)]
+    pub force: bool,
+    /// Continue this conversation: its last turns are given to inference so
+    /// follow-ups ("now do the same on can1") resolve. Without it the command
+    /// starts a new conversation whose ID is the command's own.
+    pub conversation_id: Option<Uuid>,
+    /// Same as the `Idempotency-Key` header, for clients that cannot set
+    /// headers.
+    pub idempotency_key: Option<String>,
 }
 
+/// Longest accepted idempotency key.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
 /// POST /api/v1/commands — dispatch a command to a device.
+///
+/// With an `Idempotency-Key`, a repeated submission by the same initiator
+/// returns the command the first one created instead of dispatching again.
+#[utoipa::path(
+    post,
+    path = "/api/v1/commands",
+    tag = "commands",
+    params(("Idempotency-Key" = Option<String>, Header,
+        description = "Client-chosen key (up to 255 visible ASCII characters) that makes retries of this request return the original command")),
+    request_body = SendCommandRequest,
+    responses(
+        (status = 200, description = "Command accepted; `parsed_intent` is set once inferred", body = CommandEnvelope),
+        (status = 400, description = "Invalid command, tool arguments or idempotency key", body = ApiErrorBody),
+        (status = 403, description = "Fleet or tool not allowed", body = ApiErrorBody),
+        (status = 404, description = "Unknown device", body = ApiErrorBody),
+        (status = 409, description = "Idempotency key already used for another device or fleet", body = ApiErrorBody),
+        (status = 412, description = "Pre-flight checks failed", body = ApiErrorBody),
+        (status = 429, description = "Device command rate limit reached", body = ApiErrorBody),
+    )
+)]
 pub async fn send_command(
     State(state): State<AppState>,
-    Json(req): Json<SendCommandRequest>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    Json(mut req): Json<SendCommandRequest>,
 ) -> ApiResult<Json<CommandEnvelope>> {
-    // Verify device exists
-    if let Some(pool) = &state.pool {
-        let exists = crate::db::devices::exists(pool, &req.device_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-        if !exists {
-            return Err(ApiError::NotFound(format!(
-                "device '{}' not found",
-                req.device_id
-            )));
-        }
+    let idempotency_key = idempotency_key(&headers, req.idempotency_key.take())?;
+    req.device_id = crate::device_identity::resolve(&state, &req.device_id).await?;
+    let device_fleet = if principal.is_some() || state.structured_mode.enabled() {
+        crate::auth::device_fleet(&state, &req.device_id).await
     } else {
-        let devices = state.devices.read().await;
-        if !devices.contains_key(&req.device_id) {
-            return Err(ApiError::NotFound(format!(
-                "device '{}' not found",
-                req.device_id
+        None
+    };
+    if let Some(Extension(user)) = &principal {
+        if !user.can_access_fleet(&req.fleet_id) {
+            return Err(ApiError::Forbidden(format!(
+                "no access to fleet '{}'",
+                req.fleet_id
             )));
         }
+        crate::auth::check_device(&state, user, &req.device_id)
+            .await
+            .map_err(|e| ApiError::Forbidden(e.to_string()))?;
+        req.initiated_by = user.name.clone();
+    }
+    if let Some(key) = &idempotency_key
+        && let Some(original) = replay(&state, &req, key).await?
+    {
+        return Ok(Json(original));
+    }
+
+    // An explicit tool bypasses inference; structured-only fleets require one.
+    let structured = structured_mode::intent(req.tool_name.take(), req.tool_args.take())?;
+    state.structured_mode.check(
+        std::iter::once(req.fleet_id.as_str()).chain(device_fleet.as_deref()),
+        structured.is_some(),
+    )?;
+    if req.command.trim().is_empty()
+        && let Some(parsed) = &structured
+    {
+        req.command = structured_mode::describe(&parsed.intent);
     }
 
+    // Verify device exists and decide whether it can take the command now.
+    let reachable = device_reachable(&state, &req.device_id).await?;
+    crate::command_limits::admit(&state, &req.device_id, &req.initiated_by)?;
+
     let mut envelope = CommandEnvelope::new(
         &req.fleet_id,
         &req.device_id,
         &req.command,
         &req.initiated_by,
     );
+    envelope.conversation_id = Some(req.conversation_id.unwrap_or(envelope.id));
+    let history = match req.conversation_id {
+        Some(id) => crate::conversations::history(&state, id, &req.fleet_id).await?,
+        None => Vec::new(),
+    };
 
-    // Run NL inference to parse command into tool invocation.
-    let parse_result = state.inference.parse(&req.command).await;
+    // Otherwise assign an experiment variant (if one is running) and run NL
+    // inference, with the device's context, to parse the command into a
+    // tool invocation.
+    let (variant, parse_result) = match structured {
+        Some(parsed) => (None, Some(parsed)),
+        None => {
+            let experiment = crate::experiments::active(&state)
+                .await
+                .map_err(|e| ApiError::Internal(e.to_string()))?;
+            let variant = experiment.as_ref().and_then(|exp| {
+                crate::experiments::pick_variant(&exp.variants, envelope.id)
+                    .map(|v| (exp.id, v.clone()))
+            });
+            let mut overrides = variant
+                .as_ref()
+                .map(|(_, v)| v.overrides())
+                .unwrap_or_default();
+            overrides.device_context =
+                Some(crate::device_context::load(&state, &req.device_id).await?);
+            overrides.catalog = crate::device_context::catalog(&state, &req.device_id).await?;
+            overrides.history = history.clone();
+            let mut parse_result = state.inference.parse_with(&req.command, &overrides).await;
+            if let Some((_, v)) = &variant
+                && let Some(min) = v.min_confidence
+                && parse_result
+                    .as_ref()
+                    .is_some_and(|r| r.intent.confidence < min)
+            {
+                parse_result = None;
+            }
+            (variant, parse_result)
+        }
+    };
     let (parsed_intent, inference_tier) = match &parse_result {
         Some(r) => (Some(r.intent.clone()), Some(r.tier.clone())),
         None => (None, None),
     };
-    envelope.parsed_intent = parsed_intent.clone();
+    envelope.parsed_intent = parsed_intent;
+    // Unparsed commands fall to the device's Ollama, which needs the history too.
+    if envelope.parsed_intent.is_none() {
+        envelope.history = history;
+    }
+    state.metrics.inference(inference_tier.as_deref());
+
+    if req.preflight {
+        let report =
+            crate::preflight::check(&state, &req.device_id, envelope.parsed_intent.as_ref())
+                .await?;
+        if !report.passed {
+            if !req.force {
+                return Err(report.into_error());
+            }
+            tracing::warn!(
+                device_id = %req.device_id,
+                failed = ?report.failed(),
+                "pre-flight checks failed, dispatching anyway (force)"
+            );
+        }
+    }
+
+    // High-risk commands wait for a second operator instead of being delivered.
+    let approval = state
+        .approval
+        .reason(envelope.parsed_intent.as_ref(), false);
+    let status = if approval.is_some() {
+        CommandStatus::AwaitingApproval
+    } else if reachable {
+        CommandStatus::Pending
+    } else {
+        CommandStatus::Queued
+    };
 
     // Store the command (with parsed intent if available)
-    if let Some(pool) = &state.pool {
-        let row = crate::db::commands::CommandRow {
-            id: envelope.id,
-            fleet_id: envelope.fleet_id.clone(),
-            device_id: envelope.device_id.clone(),
-            natural_language: envelope.natural_language.clone(),
-            initiated_by: envelope.initiated_by.clone(),
-            correlation_id: envelope.correlation_id,
-            timeout_secs: envelope.timeout_secs as i32,
-            tool_name: parsed_intent.as_ref().map(|i| i.tool_name.clone()),
-            tool_args: parsed_intent.as_ref().map(|i| i.tool_args.clone()),
-            confidence: parsed_intent.as_ref().map(|i| i.confidence),
-            status: "pending".to_string(),
-            inference_tier,
-            response_text: None,
-            response_data: None,
-            latency_ms: None,
-            responded_at: None,
-            error: None,
-            created_at: envelope.created_at,
+    if !store_command(
+        &state,
+        &envelope,
+        status,
+        inference_tier,
+        idempotency_key.as_deref(),
+    )
+    .await?
+    {
+        // A concurrent submission with the same key was stored first.
+        let key = idempotency_key.unwrap_or_default();
+        return match replay(&state, &req, &key).await? {
+            Some(original) => Ok(Json(original)),
+            None => Err(ApiError::Conflict(format!(
+                "a command with Idempotency-Key '{key}' is already being dispatched"
+            ))),
         };
-        crate::db::commands::insert(pool, &row)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-    } else {
-        let mut commands = state.commands.write().await;
-        commands.push(CommandRecord {
-            envelope: envelope.clone(),
-            response: None,
-            created_at: Utc::now(),
-        });
+    }
+
+    if let Some((experiment_id, v)) = variant {
+        crate::experiments::record_assignment(
+            &state,
+            crate::experiments::Assignment {
+                command_id: envelope.id,
+                experiment_id,
+                variant: v.name,
+                parsed: parse_result.is_some(),
+                confidence: parse_result.as_ref().map(|r| r.intent.confidence),
+                outcome: None,
+            },
+        )
+        .await;
     }
 
     tracing::info!(
@@ -114,106 +260,1410 @@ pub async fn send_command(
         created_at: envelope.created_at,
     });
 
-    // Publish command envelope to MQTT if the bridge is connected.
-    if let Some(mqtt) = &state.mqtt {
-        let topic = zc_protocol::topics::command_request(&envelope.fleet_id, &envelope.device_id);
+    if let Some(reason) = approval {
+        crate::approval::request(&state, &envelope, &reason).await?;
+        return Ok(Json(envelope));
+    }
+
+    if !reachable {
+        tracing::info!(
+            command_id = %envelope.id,
+            device_id = %req.device_id,
+            "device offline, command queued"
+        );
+        let _ = state.event_tx.send(WsEvent::CommandQueued {
+            command_id: envelope.id,
+            device_id: envelope.device_id.clone(),
+            queued_at: Utc::now(),
+        });
+        return Ok(Json(envelope));
+    }
+
+    // Publish command envelope to MQTT if the bridge is connected; a failed
+    // publish is retried in the background.
+    crate::command_retries::publish(&state, &envelope).await;
+
+    Ok(Json(envelope))
+}
+
+/// The request's idempotency key, from the `Idempotency-Key` header or the
+/// `idempotency_key` field (400 if both are given and differ).
+fn idempotency_key(headers: &HeaderMap, field: Option<String>) -> ApiResult<Option<String>> {
+    let header = headers
+        .get("idempotency-key")
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::to_string)
+                .map_err(|_| ApiError::BadRequest("Idempotency-Key must be ASCII".into()))
+        })
+        .transpose()?;
+    let key = match (header, field) {
+        (Some(header), Some(field)) if header != field => {
+            return Err(ApiError::BadRequest(
+                "Idempotency-Key header and idempotency_key field differ".into(),
+            ));
+        }
+        (header, field) => header.or(field),
+    };
+    if let Some(key) = &key
+        && (key.is_empty()
+            || key.len() > MAX_IDEMPOTENCY_KEY_LEN
+            || !key.chars().all(|c| c.is_ascii_graphic()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Idempotency-Key must be 1 to {MAX_IDEMPOTENCY_KEY_LEN} visible ASCII characters"
+        )));
+    }
+    Ok(key)
+}
+
+/// The command the initiator already submitted with `key`, if any (409 if
+/// it went to another device or fleet).
+async fn replay(
+    state: &AppState,
+    req: &SendCommandRequest,
+    key: &str,
+) -> ApiResult<Option<CommandEnvelope>> {
+    let Some(original) = state
+        .store
+        .command_by_idempotency_key(&req.initiated_by, key)
+        .await?
+    else {
+        return Ok(None);
+    };
+    if original.device_id != req.device_id || original.fleet_id != req.fleet_id {
+        return Err(ApiError::Conflict(format!(
+            "Idempotency-Key '{key}' was already used for a command to device '{}' in fleet '{}'",
+            original.device_id, original.fleet_id
+        )));
+    }
+    tracing::info!(
+        command_id = %original.id,
+        idempotency_key = %key,
+        "repeated submission, returning the original command"
+    );
+    Ok(Some(original))
+}
+
+/// Whether `device_id` can take a command now (404 if it does not exist).
+pub(crate) async fn device_reachable(state: &AppState, device_id: &str) -> ApiResult<bool> {
+    let device = state
+        .store
+        .get_device(device_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("device '{device_id}' not found")))?;
+    Ok(command_queue::is_reachable(
+        &device.status,
+        device.last_heartbeat,
+    ))
+}
+
+/// Record a dispatched command in the database or in-memory log. Returns
+/// `false`, recording nothing, if its initiator already used
+/// `idempotency_key`.
+pub(crate) async fn store_command(
+    state: &AppState,
+    envelope: &CommandEnvelope,
+    status: CommandStatus,
+    inference_tier: Option<String>,
+    idempotency_key: Option<&str>,
+) -> ApiResult<bool> {
+    let status_str = command_status_name(status);
+    let deadline = (status == CommandStatus::Pending)
+        .then(|| crate::command_timeouts::deadline(envelope, Utc::now()));
+    let inserted = state
+        .store
+        .insert_command(
+            envelope,
+            status,
+            inference_tier,
+            deadline,
+            state.retry_policy.max_attempts,
+            idempotency_key,
+        )
+        .await?;
+    if !inserted {
+        return Ok(false);
+    }
+    state.metrics.command_status(&status_str);
+    crate::audit::record(
+        state,
+        AuditEntry::command(
+            &envelope.initiated_by,
+            AuditAction::CommandDispatched,
+            envelope.id,
+            &envelope.device_id,
+            serde_json::json!({
+                "command": envelope.natural_language,
+                "action": envelope.parsed_intent.as_ref().map(|i| i.action),
+                "tool_name": envelope.parsed_intent.as_ref().map(|i| &i.tool_name),
+                "status": status_str,
+            }),
+        )
+        .with_fleet(&envelope.fleet_id)
+        .with_outcome(AuditOutcome::Succeeded),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Request body for cancelling a command. Both fields are optional.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CancelCommandRequest {
+    /// Who is cancelling (defaults to "operator"; the authenticated user,
+    /// when OIDC is on).
+    pub requested_by: Option<String>,
+    /// Free-form reason shown in the final response.
+    pub reason: Option<String>,
+}
+
+/// Whether a command in `status` can no longer be cancelled.
+pub(crate) fn is_terminal(status: CommandStatus) -> bool {
+    matches!(
+        status,
+        CommandStatus::Completed
+            | CommandStatus::Failed
+            | CommandStatus::Timeout
+            | CommandStatus::Cancelled
+            | CommandStatus::Throttled
+    )
+}
+
+/// POST /api/v1/commands/:id/cancel — abort a queued or in-flight command.
+///
+/// Queued commands, and commands awaiting approval (which rejects them), are
+/// dropped before delivery. Anything already sent to
+/// the device gets a [`CommandCancel`] on its cancel topic; the agent stops
+/// the tool and reports a final `cancelled` response. Commands that have
+/// already finished return 409.
+#[utoipa::path(
+    post,
+    path = "/api/v1/commands/{id}/cancel",
+    tag = "commands",
+    params(("id" = Uuid, Path, description = "Command ID")),
+    request_body(content = Option<CancelCommandRequest>),
+    responses(
+        (status = 200, description = "`{id, device_id, status, requested_by, reason}`", body = Object),
+        (status = 404, description = "Unknown command", body = ApiErrorBody),
+        (status = 409, description = "Command already finished", body = ApiErrorBody),
+    )
+)]
+pub async fn cancel_command(
+    State(state): State<AppState>,
+    Path(command_id): Path<Uuid>,
+    principal: Option<Extension<Principal>>,
+    body: Option<Json<CancelCommandRequest>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let mut req = body.map(|Json(r)| r).unwrap_or_default();
+    let principal = principal.map(|Extension(p)| p);
+    let not_found = || ApiError::NotFound(format!("command '{command_id}' not found"));
+
+    let record = state
+        .store
+        .command(command_id)
+        .await?
+        .ok_or_else(not_found)?;
+    let status = record.response.as_ref().map_or(record.status, |r| r.status);
+    let CommandEnvelope {
+        fleet_id,
+        device_id,
+        ..
+    } = record.envelope;
+
+    if let Some(user) = &principal {
+        if !user.can_access_fleet(&fleet_id) {
+            return Err(ApiError::Forbidden(format!(
+                "no access to fleet '{fleet_id}'"
+            )));
+        }
+        req.requested_by = Some(user.name.clone());
+    }
+
+    if is_terminal(status) {
+        return Err(ApiError::Conflict(format!(
+            "command '{command_id}' already finished ({})",
+            format!("{status:?}").to_lowercase()
+        )));
+    }
+
+    let cancel = CommandCancel {
+        command_id,
+        device_id: device_id.clone(),
+        requested_by: req.requested_by.unwrap_or_else(|| "operator".into()),
+        reason: req.reason,
+        requested_at: Utc::now(),
+    };
+
+    // Queued and held commands never reached the device — nothing to tell it.
+    if !matches!(
+        status,
+        CommandStatus::Queued | CommandStatus::AwaitingApproval
+    ) && let Some(mqtt) = &state.mqtt
+    {
+        let topic = zc_protocol::topics::command_cancel(&fleet_id, &device_id);
         if let Err(e) = mqtt
             .publish(
                 &topic,
-                &serde_json::to_vec(&envelope).unwrap_or_default(),
+                &zc_protocol::encoding::encode(
+                    &cancel,
+                    command_queue::device_encoding(&state, &device_id).await,
+                )
+                .unwrap_or_default(),
                 rumqttc::QoS::AtLeastOnce,
             )
             .await
         {
-            tracing::error!(error = %e, "failed to publish command to mqtt");
+            tracing::error!(error = %e, command_id = %command_id, "failed to publish cancel to mqtt");
         }
     }
 
-    Ok(Json(envelope))
+    state
+        .store
+        .set_status(command_id, CommandStatus::Cancelled)
+        .await?;
+
+    crate::audit::record(
+        &state,
+        AuditEntry::command(
+            &cancel.requested_by,
+            AuditAction::Cancelled,
+            command_id,
+            &device_id,
+            serde_json::json!({
+                "reason": cancel.reason,
+                "was_awaiting_approval": status == CommandStatus::AwaitingApproval,
+            }),
+        )
+        .with_fleet(&fleet_id),
+    )
+    .await?;
+
+    state.metrics.command_status("cancelled");
+    tracing::info!(
+        command_id = %command_id,
+        device_id = %device_id,
+        requested_by = %cancel.requested_by,
+        "command cancelled"
+    );
+
+    let _ = state.event_tx.send(WsEvent::CommandCancelled {
+        command_id,
+        device_id: device_id.clone(),
+        requested_by: cancel.requested_by.clone(),
+        reason: cancel.reason.clone(),
+        cancelled_at: cancel.requested_at,
+    });
+
+    Ok(Json(serde_json::json!({
+        "id": command_id,
+        "device_id": device_id,
+        "status": "cancelled",
+        "requested_by": cancel.requested_by,
+        "reason": cancel.reason,
+    })))
 }
 
-/// GET /api/v1/commands/:id — get command status.
-pub async fn get_command(
+/// Request body for retrying a command. Optional.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RetryCommandRequest {
+    /// Who is retrying (defaults to the original initiator; the
+    /// authenticated user, when OIDC is on).
+    pub initiated_by: Option<String>,
+}
+
+/// POST /api/v1/commands/:id/retry — send a failed or timed-out command
+/// again.
+///
+/// The original keeps its result. A copy (same device, text and
+/// conversation) is dispatched as a new command through
+/// [`send_command`], so rate limits, approval and the offline queue apply;
+/// agents ignore command IDs they have already finished, which is why the
+/// copy needs its own. A single parsed tool is reused as-is, plans and
+/// unparsed text go through inference again. Other statuses return 409.
+#[utoipa::path(
+    post,
+    path = "/api/v1/commands/{id}/retry",
+    tag = "commands",
+    params(("id" = Uuid, Path, description = "Command ID")),
+    request_body(content = Option<RetryCommandRequest>),
+    responses(
+        (status = 200, description = "The new command", body = CommandEnvelope),
+        (status = 403, description = "Fleet not allowed", body = ApiErrorBody),
+        (status = 404, description = "Unknown command", body = ApiErrorBody),
+        (status = 409, description = "Command neither failed nor timed out", body = ApiErrorBody),
+        (status = 429, description = "Device command rate limit reached", body = ApiErrorBody),
+    )
+)]
+pub async fn retry_command(
     State(state): State<AppState>,
     Path(command_id): Path<Uuid>,
+    principal: Option<Extension<Principal>>,
+    body: Option<Json<RetryCommandRequest>>,
+) -> ApiResult<Json<CommandEnvelope>> {
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    check_command_fleets(&state, principal.as_deref(), command_id).await?;
+    let not_found = || ApiError::NotFound(format!("command '{command_id}' not found"));
+
+    let record = state
+        .store
+        .command(command_id)
+        .await?
+        .ok_or_else(not_found)?;
+    let status = record.response.as_ref().map_or(record.status, |r| r.status);
+    let original = record.envelope;
+    if !matches!(status, CommandStatus::Failed | CommandStatus::Timeout) {
+        return Err(ApiError::Conflict(format!(
+            "command '{command_id}' is {}; only failed or timed-out commands can be retried",
+            command_status_name(status)
+        )));
+    }
+
+    let (tool_name, tool_args) = match original.parsed_intent {
+        Some(intent) if intent.action == ActionKind::Tool && intent.steps.is_empty() => {
+            (Some(intent.tool_name), Some(intent.tool_args))
+        }
+        _ => (None, None),
+    };
+    let Json(retry) = send_command(
+        State(state.clone()),
+        principal,
+        HeaderMap::new(),
+        Json(SendCommandRequest {
+            device_id: original.device_id.clone(),
+            fleet_id: original.fleet_id.clone(),
+            command: original.natural_language.clone(),
+            tool_name,
+            tool_args,
+            initiated_by: req
+                .initiated_by
+                .unwrap_or_else(|| original.initiated_by.clone()),
+            preflight: false,
+            force: false,
+            conversation_id: original.conversation_id,
+            idempotency_key: None,
+        }),
+    )
+    .await?;
+
+    crate::audit::record(
+        &state,
+        AuditEntry::command(
+            &retry.initiated_by,
+            AuditAction::Retried,
+            command_id,
+            &original.device_id,
+            serde_json::json!({
+                "status": command_status_name(status),
+                "retry_command_id": retry.id,
+            }),
+        )
+        .with_fleet(&original.fleet_id),
+    )
+    .await?;
+    tracing::info!(
+        command_id = %command_id,
+        retry_command_id = %retry.id,
+        initiated_by = %retry.initiated_by,
+        "command retried"
+    );
+    Ok(Json(retry))
+}
+
+/// Request body for approving a held command.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApproveCommandRequest {
+    /// Approving operator; must be listed in `APPROVERS` and differ from the
+    /// initiator. Replaced by the authenticated user when OIDC is on.
+    pub approved_by: String,
+    /// Optional note stored in the audit log.
+    pub comment: Option<String>,
+}
+
+/// POST /api/v1/commands/:id/approve — approve a high-risk command held for
+/// a second operator, and dispatch it. `id` may also be a broadcast ID, which
+/// approves every device's copy.
+///
+/// 403 if `approved_by` is not an approver or initiated the command, 409 if
+/// nothing is awaiting approval or the approval window has closed.
+#[utoipa::path(
+    post,
+    path = "/api/v1/commands/{id}/approve",
+    tag = "commands",
+    params(("id" = Uuid, Path, description = "Command or broadcast ID")),
+    request_body = ApproveCommandRequest,
+    responses(
+        (status = 200, description = "`{id, approved_by, commands}` with the dispatched command IDs", body = Object),
+        (status = 403, description = "Not an approver, or the initiator", body = ApiErrorBody),
+        (status = 404, description = "Unknown command", body = ApiErrorBody),
+        (status = 409, description = "Nothing awaiting approval", body = ApiErrorBody),
+    )
+)]
+pub async fn approve_command(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    principal: Option<Extension<Principal>>,
+    Json(mut req): Json<ApproveCommandRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    if let Some(pool) = &state.pool {
-        let row = crate::db::commands::get_by_id(pool, command_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?
-            .ok_or_else(|| ApiError::NotFound(format!("command '{command_id}' not found")))?;
-        let json = serde_json::json!({
-            "id": row.id,
-            "device_id": row.device_id,
-            "command": row.natural_language,
-            "status": row.status,
-            "tool_name": row.tool_name,
-            "tool_args": row.tool_args,
-            "confidence": row.confidence,
-            "inference_tier": row.inference_tier,
-            "response_text": row.response_text,
-            "response_data": row.response_data,
-            "latency_ms": row.latency_ms,
-            "error": row.error,
-            "created_at": row.created_at,
-            "responded_at": row.responded_at,
-        });
-        return Ok(Json(json));
+    check_command_fleets(&state, principal.as_deref(), id).await?;
+    req.approved_by = crate::auth::actor(principal.as_deref(), req.approved_by);
+    let commands =
+        crate::approval::approve(&state, id, &req.approved_by, req.comment.as_deref()).await?;
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "approved_by": req.approved_by,
+        "commands": commands,
+    })))
+}
+
+/// GET /api/v1/commands/:id/audit — audit log entries of a command, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/commands/{id}/audit",
+    tag = "commands",
+    params(("id" = Uuid, Path, description = "Command ID")),
+    responses((status = 200, description = "Audit entries, oldest first", body = [AuditEntry]))
+)]
+pub async fn get_command_audit(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    principal: Option<Extension<Principal>>,
+) -> ApiResult<Json<Vec<AuditEntry>>> {
+    check_command_fleets(&state, principal.as_deref(), id).await?;
+    Ok(Json(crate::audit::for_command(&state, id).await?))
+}
+
+/// Refuse a command, or broadcast, to fleets outside the user's tenants.
+async fn check_command_fleets(
+    state: &AppState,
+    principal: Option<&Principal>,
+    id: Uuid,
+) -> ApiResult<()> {
+    let Some(user) = principal.filter(|p| p.tenants.is_some()) else {
+        return Ok(());
+    };
+    for (envelope, _) in crate::approval::commands_for(state, id).await? {
+        if !user.can_access_fleet(&envelope.fleet_id) {
+            return Err(ApiError::Forbidden(format!(
+                "no access to fleet '{}'",
+                envelope.fleet_id
+            )));
+        }
     }
+    Ok(())
+}
 
-    // In-memory fallback
-    let commands = state.commands.read().await;
-    let record = commands
-        .iter()
-        .find(|r| r.envelope.id == command_id)
+/// GET /api/v1/commands/:id — get command status.
+#[utoipa::path(
+    get,
+    path = "/api/v1/commands/{id}",
+    tag = "commands",
+    params(("id" = Uuid, Path, description = "Command ID")),
+    responses(
+        (status = 200, description = "`{command, status, response, created_at, attempts, max_attempts, next_attempt_at, idempotency_key, timing}`", body = Object),
+        (status = 403, description = "Fleet not allowed", body = ApiErrorBody),
+        (status = 404, description = "Unknown command", body = ApiErrorBody),
+    )
+)]
+pub async fn get_command(
+    State(state): State<AppState>,
+    Path(command_id): Path<Uuid>,
+    principal: Option<Extension<Principal>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    check_command_fleets(&state, principal.as_deref(), command_id).await?;
+    let record = state
+        .store
+        .command(command_id)
+        .await?
         .ok_or_else(|| ApiError::NotFound(format!("command '{command_id}' not found")))?;
+    // Timeouts and failed deliveries also leave a response; only one the
+    // device sent has device times.
+    let device_response = record.timing.received_at.and(record.response.as_ref());
 
     let json = serde_json::json!({
         "command": record.envelope,
+        "status": record.response.as_ref().map(|r| r.status).unwrap_or(record.status),
         "response": record.response,
         "created_at": record.created_at,
+        "attempts": record.attempts,
+        "max_attempts": record.max_attempts,
+        "next_attempt_at": record.next_attempt_at,
+        "idempotency_key": record.idempotency_key,
+        "timing": Breakdown::new(
+            record.created_at,
+            &record.timing,
+            device_response.map(|r| r.responded_at),
+            device_response.map(|r| r.latency_ms as i64),
+        ),
     });
     Ok(Json(json))
 }
 
-/// GET /api/v1/commands — list recent commands.
+/// Columns of the command list as CSV.
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "device_id",
+    "command",
+    "status",
+    "initiated_by",
+    "created_at",
+];
+
+/// Query parameters for the command list.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListCommandsQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    /// Target device ID or alias.
+    pub device_id: Option<String>,
+    /// Current status (e.g. `queued`, `completed`).
+    pub status: Option<CommandStatus>,
+    /// Only commands created at or after this RFC 3339 time.
+    pub since: Option<DateTime<Utc>>,
+    pub initiated_by: Option<String>,
+    /// Failure class of the response (e.g. `can_timeout`).
+    pub error_code: Option<ErrorCode>,
+}
+
+/// GET /api/v1/commands — list commands, most recent first.
+///
+/// Filtered by `device_id`, `status`, `since`, `initiated_by` and
+/// `error_code`, paged with
+/// `limit`/`offset`. The unpaged match count is in `X-Total-Count`.
+/// `Accept: text/csv` returns the page as CSV.
+#[utoipa::path(
+    get,
+    path = "/api/v1/commands",
+    tag = "commands",
+    params(ListCommandsQuery),
+    responses(
+        (status = 200, description = "One page of commands, most recent first", body = [CommandSummary],
+            headers(("X-Total-Count" = u64, description = "Commands matching the filter"))),
+        (status = 404, description = "Unknown device", body = ApiErrorBody),
+    )
+)]
 pub async fn list_commands(
     State(state): State<AppState>,
-) -> ApiResult<Json<Vec<serde_json::Value>>> {
-    if let Some(pool) = &state.pool {
-        let rows = crate::db::commands::list_recent(pool, 50)
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListCommandsQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let device_id = match &query.device_id {
+        Some(reference) => Some(crate::device_identity::resolve(&state, reference).await?),
+        None => None,
+    };
+    let filter = CommandFilter {
+        device_id,
+        status: query.status.map(command_status_name),
+        since: query.since,
+        initiated_by: query.initiated_by,
+        error_code: query.error_code.map(|c| c.as_str().to_string()),
+        fleets: principal.and_then(|Extension(p)| p.tenants),
+        limit: pagination::limit(query.limit),
+        offset: query.offset,
+    };
+
+    let (page, total) = state.store.list_commands(&filter).await?;
+    Ok(csv::paged(&headers, CSV_COLUMNS, page, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::build_router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_protocol::device::DeviceStatus;
+
+    async fn dispatch(app: &axum::Router, device_id: &str) -> Uuid {
+        let body = serde_json::json!({
+            "device_id": device_id,
+            "fleet_id": "fleet-alpha",
+            "command": "monitor CAN bus for 5 minutes",
+            "initiated_by": "admin",
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/v1/commands")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
             .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-        let recent: Vec<serde_json::Value> = rows
-            .into_iter()
-            .map(|r| {
-                serde_json::json!({
-                    "id": r.id,
-                    "device_id": r.device_id,
-                    "command": r.natural_language,
-                    "status": r.status,
-                    "created_at": r.created_at,
-                })
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let envelope: CommandEnvelope = serde_json::from_slice(&body).unwrap();
+        envelope.id
+    }
+
+    async fn cancel(app: &axum::Router, id: Uuid, body: Option<serde_json::Value>) -> StatusCode {
+        let request = Request::post(format!("/api/v1/commands/{id}/cancel"));
+        let request = match body {
+            Some(b) => request
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&b).unwrap())),
+            None => request.body(Body::empty()),
+        };
+        app.clone()
+            .oneshot(request.unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn cancel_in_flight_command_publishes_cancel() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        let mut rx = state.event_tx.subscribe();
+        let app = build_router(state.clone());
+
+        let id = dispatch(&app, "rpi-001").await;
+        let status = cancel(
+            &app,
+            id,
+            Some(serde_json::json!({"requested_by": "ops", "reason": "wrong bus"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let published = mqtt.published_to("fleet/fleet-alpha/rpi-001/command/cancel");
+        assert_eq!(published.len(), 1);
+        let msg: CommandCancel = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(msg.command_id, id);
+        assert_eq!(msg.requested_by, "ops");
+        assert_eq!(msg.reason.as_deref(), Some("wrong bus"));
+
+        assert_eq!(
+            state.commands.read().await[0].status,
+            CommandStatus::Cancelled
+        );
+        let cancelled = std::iter::from_fn(|| rx.try_recv().ok())
+            .find(|e| matches!(e, WsEvent::CommandCancelled { .. }));
+        assert!(cancelled.is_some());
+    }
+
+    #[tokio::test]
+    async fn cancel_queued_command_skips_mqtt() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .status = zc_protocol::device::DeviceStatus::Offline;
+        let app = build_router(state.clone());
+
+        let id = dispatch(&app, "rpi-002").await;
+        assert_eq!(cancel(&app, id, None).await, StatusCode::OK);
+
+        assert!(mqtt.published().is_empty());
+        assert_eq!(
+            state.commands.read().await[0].status,
+            CommandStatus::Cancelled
+        );
+        // A later heartbeat must not deliver the cancelled command.
+        assert_eq!(command_queue::flush(&state, "rpi-002").await, 0);
+    }
+
+    #[tokio::test]
+    async fn cancel_finished_command_conflicts() {
+        let app = build_router(AppState::with_sample_data());
+        let id = dispatch(&app, "rpi-001").await;
+
+        assert_eq!(cancel(&app, id, None).await, StatusCode::OK);
+        assert_eq!(cancel(&app, id, None).await, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn cancel_unknown_command_not_found() {
+        let app = build_router(AppState::with_sample_data());
+        assert_eq!(
+            cancel(&app, Uuid::now_v7(), None).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn timing_breakdown_reads_device_time_through_heartbeat_skew() {
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(std::sync::Arc::new(zc_mqtt_channel::MockChannel::new()));
+        let app = build_router(state.clone());
+
+        // The device clock runs an hour ahead.
+        let ahead = chrono::Duration::hours(1);
+        let now = Utc::now();
+        crate::command_timing::heartbeat_received(&state, "rpi-001", now + ahead, now).await;
+        let id = dispatch(&app, "rpi-001").await;
+
+        let response = serde_json::json!({
+            "command_id": id,
+            "correlation_id": id,
+            "device_id": "rpi-001",
+            "status": "completed",
+            "inference_tier": "local",
+            "latency_ms": 5,
+            "responded_at": Utc::now() + ahead,
+        });
+        let status = app
+            .clone()
+            .oneshot(
+                Request::post(format!("/api/v1/commands/{id}/respond"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(response.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::get(format!("/api/v1/commands/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let timing = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["timing"].clone();
+        assert!(timing["published_at"].is_string(), "{timing}");
+        assert!(timing["received_at"].is_string());
+        assert_eq!(timing["clock_offset_ms"], 3_600_000);
+        assert_eq!(timing["execution_ms"], 5);
+        // Every duration is on one clock: none of them is an hour long.
+        for field in [
+            "queue_ms",
+            "transport_ms",
+            "outbound_ms",
+            "inbound_ms",
+            "total_ms",
+        ] {
+            let ms = timing[field].as_i64().unwrap();
+            assert!((0..60_000).contains(&ms), "{field}: {ms}");
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_publish_schedules_a_retry() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        let app = build_router(state.clone());
+
+        mqtt.fail_publishes(true);
+        let id = dispatch(&app, "rpi-001").await;
+        {
+            let commands = state.commands.read().await;
+            assert_eq!(commands[0].status, CommandStatus::Pending);
+            assert_eq!(commands[0].attempts, 1);
+            assert!(commands[0].next_attempt_at.is_some());
+            assert_eq!(commands[0].deadline, None);
+        }
+
+        mqtt.fail_publishes(false);
+        let due = state.commands.read().await[0].next_attempt_at.unwrap();
+        assert_eq!(
+            crate::command_retries::tick(&state, due).await.unwrap(),
+            [id]
+        );
+        let response = app
+            .oneshot(
+                Request::get(format!("/api/v1/commands/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["attempts"], 2);
+        assert_eq!(json["max_attempts"], 5);
+        assert_eq!(json["next_attempt_at"], serde_json::Value::Null);
+        assert_eq!(mqtt.published().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_command_is_retried_as_a_new_command() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        let id = dispatch(&app, "rpi-001").await;
+        let retry = |app: axum::Router| async move {
+            let response = app
+                .oneshot(
+                    Request::post(format!("/api/v1/commands/{id}/retry"))
+                        .header("content-type", "application/json")
+                        .body(Body::from(r#"{"initiated_by":"ops"}"#))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            )
+        };
+
+        // Still pending.
+        assert_eq!(retry(app.clone()).await.0, StatusCode::CONFLICT);
+
+        {
+            let mut commands = state.commands.write().await;
+            commands[0].status = CommandStatus::Timeout;
+        }
+        let (status, body) = retry(app.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let new_id: Uuid = serde_json::from_value(body["id"].clone()).unwrap();
+        assert_ne!(new_id, id);
+        assert_eq!(body["device_id"], "rpi-001");
+        assert_eq!(body["natural_language"], "monitor CAN bus for 5 minutes");
+        assert_eq!(body["initiated_by"], "ops");
+
+        let commands = state.commands.read().await;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].status, CommandStatus::Timeout);
+        assert_eq!(commands[1].status, CommandStatus::Pending);
+        drop(commands);
+
+        let audit = crate::audit::for_command(&state, id).await.unwrap();
+        let retried = audit
+            .iter()
+            .find(|e| e.action == AuditAction::Retried)
+            .unwrap();
+        assert_eq!(retried.detail["retry_command_id"], new_id.to_string());
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_returns_the_original_command() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        let submit = |key: Option<&str>, body: serde_json::Value| {
+            let mut request =
+                Request::post("/api/v1/commands").header("content-type", "application/json");
+            if let Some(key) = key {
+                request = request.header("idempotency-key", key);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                (
+                    status,
+                    serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+                )
+            }
+        };
+        let command = |device: &str, by: &str| {
+            serde_json::json!({
+                "device_id": device,
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": by,
             })
+        };
+
+        let (status, first) = submit(Some("req-1"), command("rpi-001", "alice")).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, again) = submit(Some("req-1"), command("rpi-001", "alice")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(again["id"], first["id"]);
+
+        // The body field works too; it must agree with the header.
+        let mut body = command("rpi-001", "alice");
+        body["idempotency_key"] = "req-1".into();
+        assert_eq!(submit(None, body.clone()).await.1["id"], first["id"]);
+        let (status, _) = submit(Some("req-2"), body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(state.commands.read().await.len(), 1);
+
+        // Reused for another device; other initiators have their own keys.
+        let (status, _) = submit(Some("req-1"), command("rpi-002", "alice")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, other) = submit(Some("req-1"), command("rpi-001", "bob")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(other["id"], first["id"]);
+
+        let (status, _) = submit(Some("has space"), command("rpi-001", "alice")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(state.commands.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn command_targets_device_by_vin() {
+        let state = AppState::with_sample_data();
+        crate::device_identity::claim(
+            &state,
+            "rpi-002",
+            crate::device_identity::AliasKind::Vin,
+            "1HGBH41JXMN109186",
+        )
+        .await
+        .unwrap();
+        let app = build_router(state.clone());
+
+        let id = dispatch(&app, "vin:1hgbh41jxmn109186").await;
+        let commands = state.commands.read().await;
+        let record = commands.iter().find(|c| c.envelope.id == id).unwrap();
+        assert_eq!(record.envelope.device_id, "rpi-002");
+    }
+
+    #[tokio::test]
+    async fn list_filters_and_pages_commands() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        for device in ["rpi-001", "rpi-001", "rpi-002"] {
+            dispatch(&app, device).await;
+        }
+        state.commands.write().await[0].envelope.initiated_by = "ops".into();
+
+        let list = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let total = response
+                    .headers()
+                    .get("x-total-count")
+                    .map(|v| v.to_str().unwrap().to_string());
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                let json: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+                (status, total, json)
+            }
+        };
+
+        let (_, total, json) = list("/api/v1/commands?device_id=rpi-001&limit=1").await;
+        assert_eq!(total.as_deref(), Some("2"));
+        assert_eq!(json.as_array().unwrap().len(), 1);
+
+        let (_, total, json) = list("/api/v1/commands?initiated_by=ops").await;
+        assert_eq!(total.as_deref(), Some("1"));
+        assert_eq!(json[0]["device_id"], "rpi-001");
+
+        let (_, total, _) = list("/api/v1/commands?status=completed").await;
+        assert_eq!(total.as_deref(), Some("0"));
+
+        let (_, total, _) = list("/api/v1/commands?since=2999-01-01T00:00:00Z").await;
+        assert_eq!(total.as_deref(), Some("0"));
+
+        let (status, _, _) = list("/api/v1/commands?status=bogus").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn list_as_csv_quotes_commands() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        let id = dispatch(&app, "rpi-001").await;
+        state.commands.write().await[0].envelope.natural_language =
+            "read DTCs, then \"clear\" them".into();
+
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/commands")
+                    .header("accept", "text/csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-type"], csv::CONTENT_TYPE);
+        assert_eq!(response.headers()["x-total-count"], "1");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "id,device_id,command,status,initiated_by,created_at"
+        );
+        assert!(lines[1].starts_with(&format!(
+            "{id},rpi-001,\"read DTCs, then \"\"clear\"\" them\","
+        )));
+    }
+
+    #[tokio::test]
+    async fn command_flood_is_throttled_per_device() {
+        use crate::command_limits::{CommandLimits, CommandRateLimiter};
+        use zc_protocol::rate_limit::RateLimit;
+
+        let mut state = AppState::with_sample_data();
+        state.command_limits = std::sync::Arc::new(CommandRateLimiter::new(CommandLimits {
+            per_device: Some(RateLimit::new(2, 1)),
+            per_initiator: None,
+        }));
+        let app = build_router(state.clone());
+        let post = |device_id: &str| {
+            let body = serde_json::json!({
+                "device_id": device_id,
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": "dashboard",
+            });
+            app.clone().oneshot(
+                Request::post("/api/v1/commands")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(post("rpi-001").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(post("rpi-001").await.unwrap().status(), StatusCode::OK);
+        let refused = post("rpi-001").await.unwrap();
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(refused.headers().contains_key("retry-after"));
+        assert_eq!(post("rpi-002").await.unwrap().status(), StatusCode::OK);
+
+        // The refused command was never stored.
+        assert_eq!(state.commands.read().await.len(), 3);
+        assert!(
+            state
+                .metrics
+                .render()
+                .contains("zc_commands_throttled_total{scope=\"device\"} 1")
+        );
+    }
+
+    async fn dispatch_checked(
+        state: AppState,
+        device_id: &str,
+        force: bool,
+    ) -> (StatusCode, serde_json::Value) {
+        let body = serde_json::json!({
+            "device_id": device_id,
+            "fleet_id": "fleet-alpha",
+            "command": "read DTCs",
+            "initiated_by": "admin",
+            "preflight": true,
+            "force": force,
+        });
+        let response = build_router(state)
+            .oneshot(
+                Request::post("/api/v1/commands")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn preflight_refuses_offline_device_unless_forced() {
+        let state = AppState::with_sample_data();
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .last_heartbeat = Some(Utc::now() - chrono::Duration::minutes(10));
+
+        let (status, body) = dispatch_checked(state.clone(), "rpi-002", false).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let checks = &body["details"]["preflight"]["checks"];
+        assert_eq!(checks[0]["name"], "online");
+        assert_eq!(checks[0]["outcome"], "fail");
+        assert!(state.commands.read().await.is_empty());
+
+        let (status, _) = dispatch_checked(state.clone(), "rpi-002", true).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(state.commands.read().await[0].status, CommandStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn preflight_checks_maintenance_and_capability() {
+        let state = AppState::with_sample_data();
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-001")
+            .unwrap()
+            .status = DeviceStatus::Maintenance;
+        let (status, body) = dispatch_checked(state.clone(), "rpi-001", false).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert!(body["error"].as_str().unwrap().contains("maintenance"));
+
+        // Without a reported manifest the capability check does not block.
+        let (status, _) = dispatch_checked(state.clone(), "rpi-002", false).await;
+        assert_eq!(status, StatusCode::OK);
+
+        state.shadows.write().await.insert(
+            ("rpi-002".into(), crate::preflight::MANIFEST_SHADOW.into()),
+            zc_protocol::shadows::ShadowState {
+                reported: serde_json::json!({"tools": ["search_logs", "log_stats"]}),
+                desired: serde_json::json!({}),
+                version: 1,
+                last_updated: Utc::now(),
+            },
+        );
+        let (status, body) = dispatch_checked(state, "rpi-002", false).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(body["details"]["preflight"]["checks"][2]["outcome"], "fail");
+    }
+
+    fn with_approvers(mut state: AppState, ttl: chrono::Duration) -> AppState {
+        state.approval = std::sync::Arc::new(crate::approval::ApprovalPolicy {
+            approvers: vec!["lead".into(), "tech".into()],
+            ttl,
+            ..Default::default()
+        });
+        state
+    }
+
+    async fn post_json(
+        app: &axum::Router,
+        uri: String,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    async fn send_frame_command(app: &axum::Router) -> Uuid {
+        let (status, body) = post_json(
+            app,
+            "/api/v1/commands".into(),
+            serde_json::json!({
+                "device_id": "rpi-001",
+                "fleet_id": "fleet-alpha",
+                "command": "send frame 0x7e0 02 10 03 reply 0x7e8",
+                "initiated_by": "tech",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn high_risk_command_waits_for_second_operator() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = with_approvers(AppState::with_sample_data(), chrono::Duration::minutes(5));
+        state.mqtt = Some(mqtt.clone());
+        let mut rx = state.event_tx.subscribe();
+        let app = build_router(state.clone());
+
+        let id = send_frame_command(&app).await;
+        assert_eq!(
+            state.commands.read().await[0].status,
+            CommandStatus::AwaitingApproval
+        );
+        assert!(mqtt.published().is_empty());
+        let requested = std::iter::from_fn(|| rx.try_recv().ok())
+            .find(|e| matches!(e, WsEvent::CommandApprovalRequested { .. }));
+        assert!(requested.is_some());
+
+        let approve = |by: &str| serde_json::json!({"approved_by": by, "comment": "bench rig"});
+        let uri = format!("/api/v1/commands/{id}/approve");
+        let (status, _) = post_json(&app, uri.clone(), approve("tech")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "initiator cannot approve");
+        let (status, _) = post_json(&app, uri.clone(), approve("eve")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "not an approver");
+
+        let (status, body) = post_json(&app, uri.clone(), approve("lead")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["commands"][0]["status"], "pending");
+        assert_eq!(
+            mqtt.published_to(&zc_protocol::topics::command_request(
+                "fleet-alpha",
+                "rpi-001"
+            ))
+            .len(),
+            1
+        );
+        assert_eq!(
+            state.commands.read().await[0].status,
+            CommandStatus::Pending
+        );
+
+        let (status, _) = post_json(&app, uri, approve("lead")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let response = app
+            .oneshot(
+                Request::get(format!("/api/v1/commands/{id}/audit"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let audit: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        let chain: Vec<(&str, &str)> = audit
+            .iter()
+            .map(|e| (e["action"].as_str().unwrap(), e["actor"].as_str().unwrap()))
             .collect();
-        return Ok(Json(recent));
+        assert_eq!(
+            chain,
+            vec![
+                ("command_dispatched", "tech"),
+                ("approval_requested", "tech"),
+                ("approved", "lead")
+            ]
+        );
+        assert_eq!(audit[0]["detail"]["status"], "awaiting_approval");
+        assert_eq!(audit[2]["detail"]["comment"], "bench rig");
+    }
+
+    #[tokio::test]
+    async fn held_command_expires_or_can_be_rejected() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = with_approvers(AppState::with_sample_data(), chrono::Duration::seconds(-1));
+        state.mqtt = Some(mqtt.clone());
+        let app = build_router(state.clone());
+
+        let id = send_frame_command(&app).await;
+        let (status, _) = post_json(
+            &app,
+            format!("/api/v1/commands/{id}/approve"),
+            serde_json::json!({"approved_by": "lead"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            state.commands.read().await[0].status,
+            CommandStatus::Cancelled
+        );
+        let audit = crate::audit::for_command(&state, id).await.unwrap();
+        assert_eq!(
+            audit.last().unwrap().action,
+            crate::audit::AuditAction::ApprovalExpired
+        );
+
+        // Cancelling a held command rejects it without telling the device.
+        let id = send_frame_command(&app).await;
+        assert_eq!(cancel(&app, id, None).await, StatusCode::OK);
+        assert!(mqtt.published().is_empty());
+        let audit = crate::audit::for_command(&state, id).await.unwrap();
+        assert_eq!(audit.last().unwrap().detail["was_awaiting_approval"], true);
     }
 
-    // In-memory fallback
-    let commands = state.commands.read().await;
-    let recent: Vec<serde_json::Value> = commands
-        .iter()
-        .rev()
-        .take(50)
-        .map(|r| {
+    #[tokio::test]
+    async fn structured_only_fleet_refuses_natural_language() {
+        let mut state = AppState::with_sample_data();
+        state.structured_mode = std::sync::Arc::new(crate::structured_mode::StructuredMode::new(
+            crate::mqtt_bridge::FleetFilter::parse("fleet-beta").unwrap(),
+        ));
+        let app = build_router(state.clone());
+
+        // The device's own fleet counts, whatever fleet the request names.
+        let (status, _) = post_json(
+            &app,
+            "/api/v1/commands".into(),
             serde_json::json!({
-                "id": r.envelope.id,
-                "device_id": r.envelope.device_id,
-                "command": r.envelope.natural_language,
-                "status": r.response.as_ref().map(|r| &r.status),
-                "created_at": r.created_at,
-            })
-        })
-        .collect();
-    Ok(Json(recent))
+                "device_id": "sbc-010",
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": "tech",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = post_json(
+            &app,
+            "/api/v1/commands".into(),
+            serde_json::json!({
+                "device_id": "sbc-010",
+                "fleet_id": "fleet-beta",
+                "tool_name": "read_pid",
+                "tool_args": {"pid": 12},
+                "initiated_by": "tech",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["natural_language"], r#"read_pid {"pid":12}"#);
+        assert_eq!(body["parsed_intent"]["tool_name"], "read_pid");
+        assert_eq!(body["parsed_intent"]["confidence"], 1.0);
+
+        // Other fleets keep natural language.
+        let (status, body) = post_json(
+            &app,
+            "/api/v1/commands".into(),
+            serde_json::json!({
+                "device_id": "rpi-001",
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": "tech",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["parsed_intent"]["tool_name"], "read_dtcs");
+    }
+
+    #[tokio::test]
+    async fn follow_up_commands_carry_conversation_history() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, first) = post_json(
+            &app,
+            "/api/v1/commands".into(),
+            serde_json::json!({
+                "device_id": "rpi-001",
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": "tech",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        // A new conversation is named after its first command.
+        assert_eq!(first["conversation_id"], first["id"]);
+        assert!(first.get("history").is_none());
+
+        // The follow-up is not understood in the cloud, so the history goes
+        // to the device for its own model.
+        let (status, second) = post_json(
+            &app,
+            "/api/v1/commands".into(),
+            serde_json::json!({
+                "device_id": "rpi-001",
+                "fleet_id": "fleet-alpha",
+                "command": "same again but only the pending ones",
+                "initiated_by": "tech",
+                "conversation_id": first["id"],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(second["conversation_id"], first["id"]);
+        assert!(second["parsed_intent"].is_null());
+        assert_eq!(second["history"][0]["command"], "read DTCs");
+        assert_eq!(second["history"][0]["intent"]["tool_name"], "read_dtcs");
+    }
 }
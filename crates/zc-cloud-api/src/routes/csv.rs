@@ -0,0 +1,129 @@
+//! CSV output for list endpoints.
+//!
+//! JSON stays the default; a request with `Accept: text/csv` gets RFC 4180
+//! CSV instead. Each endpoint declares its columns, so the header row and
+//! column order do not change with the data. Rows are rendered from the same
+//! JSON the endpoint would return: strings as-is, `null` as an empty field,
+//! numbers, booleans and nested values in their compact JSON form. Fields
+//! containing a comma, quote or line break are quoted; lines end in CRLF.
+
+use axum::http::{HeaderMap, HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+use crate::routes::pagination;
+
+/// `Content-Type` of CSV responses.
+pub const CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Whether the client asked for CSV.
+pub fn accepts_csv(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/csv"))
+}
+
+/// Header row for `columns`.
+pub fn header_row(columns: &[&str]) -> String {
+    line(columns.iter().map(|c| escape(c)))
+}
+
+/// One row: the `columns` of `item`, in order. Missing keys are empty.
+pub fn record(columns: &[&str], item: &serde_json::Value) -> String {
+    line(columns.iter().map(|c| field(item.get(c))))
+}
+
+/// Header plus one row per item.
+pub fn render<T: Serialize>(columns: &[&str], items: &[T]) -> String {
+    let mut out = header_row(columns);
+    for item in items {
+        let value = serde_json::to_value(item).unwrap_or_default();
+        out.push_str(&record(columns, &value));
+    }
+    out
+}
+
+/// A CSV document as a response.
+pub fn response(body: String) -> Response {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response()
+}
+
+/// A page of a list endpoint in the negotiated format, with the unpaged
+/// count in `X-Total-Count`.
+pub fn paged<T: Serialize>(
+    headers: &HeaderMap,
+    columns: &[&str],
+    items: Vec<T>,
+    total: u64,
+) -> Response {
+    if !accepts_csv(headers) {
+        return pagination::with_total(items, total);
+    }
+    let mut response = response(render(columns, &items));
+    response
+        .headers_mut()
+        .insert(pagination::TOTAL_COUNT_HEADER, HeaderValue::from(total));
+    response
+}
+
+fn field(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => escape(s),
+        Some(other) => escape(&other.to_string()),
+    }
+}
+
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn line(fields: impl Iterator<Item = String>) -> String {
+    let mut out = fields.collect::<Vec<_>>().join(",");
+    out.push_str("\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fields_are_quoted_only_when_needed() {
+        let row = record(
+            &["a", "b", "c", "d"],
+            &json!({"a": "plain", "b": "x,y", "c": "say \"hi\"", "d": "two\nlines"}),
+        );
+        assert_eq!(row, "plain,\"x,y\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n");
+    }
+
+    #[test]
+    fn values_follow_column_order() {
+        let columns = ["id", "missing", "count", "ok", "data", "empty"];
+        let row = record(
+            &columns,
+            &json!({"ok": true, "count": 3, "id": "x", "data": {"k": [1, 2]}, "empty": null}),
+        );
+        assert_eq!(row, "x,,3,true,\"{\"\"k\"\":[1,2]}\",\r\n");
+    }
+
+    #[test]
+    fn render_writes_header_for_empty_lists() {
+        let items: Vec<serde_json::Value> = Vec::new();
+        assert_eq!(render(&["id", "name"], &items), "id,name\r\n");
+    }
+
+    #[test]
+    fn accept_header_selects_csv() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_csv(&headers));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/csv"));
+        assert!(accepts_csv(&headers));
+    }
+}
@@ -0,0 +1,176 @@
+//! Device identity alias endpoints (VIN, serial number, asset tag).
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+
+use crate::device_identity::{self, AliasKind, DeviceAlias};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Request body for registering an alias.
+#[derive(Debug, Deserialize)]
+pub struct AddAliasRequest {
+    pub kind: AliasKind,
+    pub value: String,
+}
+
+/// GET /api/v1/devices/:id/aliases — aliases registered for a device.
+pub async fn list_aliases(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+) -> ApiResult<Json<Vec<DeviceAlias>>> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    device_identity::list_for_device(&state, &device_id)
+        .await
+        .map(Json)
+}
+
+/// POST /api/v1/devices/:id/aliases — register an alias.
+///
+/// Returns 409 if another device already claims the value.
+pub async fn add_alias(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+    Json(req): Json<AddAliasRequest>,
+) -> ApiResult<(StatusCode, Json<DeviceAlias>)> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let alias = device_identity::claim(&state, &device_id, req.kind, &req.value).await?;
+    Ok((StatusCode::CREATED, Json(alias)))
+}
+
+/// DELETE /api/v1/devices/:id/aliases/:kind/:value — remove an alias.
+pub async fn delete_alias(
+    State(state): State<AppState>,
+    Path((reference, kind, value)): Path<(String, String, String)>,
+) -> ApiResult<StatusCode> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let kind = AliasKind::parse(&kind)
+        .ok_or_else(|| ApiError::BadRequest(format!("unknown alias kind '{kind}'")))?;
+    let value = kind.normalize(&value).map_err(ApiError::BadRequest)?;
+
+    if device_identity::release(&state, &device_id, kind, &value).await? {
+        tracing::info!(device_id = %device_id, kind = kind.as_str(), value = %value, "device alias removed");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "device '{device_id}' has no {} alias '{value}'",
+            kind.as_str()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn add(device: &str, kind: &str, value: &str) -> Request<Body> {
+        Request::post(format!("/api/v1/devices/{device}/aliases"))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({"kind": kind, "value": value}).to_string(),
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn alias_resolves_on_device_endpoints() {
+        let app = build_router(AppState::with_sample_data());
+
+        let (status, body) = send(app.clone(), add("rpi-001", "asset_tag", "fl-07")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["value"], "FL-07");
+
+        let (status, body) = send(
+            app.clone(),
+            Request::get("/api/v1/devices/FL-07")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["device_id"], "rpi-001");
+
+        let (_, body) = send(
+            app.clone(),
+            Request::get("/api/v1/devices/asset_tag:FL-07/aliases")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, _) = send(
+            app.clone(),
+            Request::delete("/api/v1/devices/rpi-001/aliases/asset_tag/fl-07")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, _) = send(
+            app,
+            Request::get("/api/v1/devices/FL-07")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn duplicate_vin_is_rejected() {
+        let app = build_router(AppState::with_sample_data());
+        let vin = "1HGBH41JXMN109186";
+
+        let (status, _) = send(app.clone(), add("rpi-001", "vin", vin)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = send(app.clone(), add("rpi-002", "vin", vin)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].as_str().unwrap().contains("rpi-001"));
+
+        // Provisioning another device with the same VIN is rejected too.
+        let (status, _) = send(
+            app,
+            Request::post("/api/v1/devices")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({
+                        "device_id": "rpi-003",
+                        "fleet_id": "fleet-alpha",
+                        "hardware_type": "raspberry_pi_4",
+                        "vin": vin.to_lowercase(),
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn unknown_device_and_bad_alias() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, _) = send(app.clone(), add("nope", "serial", "S-1")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(app, add("rpi-001", "vin", "TOO-SHORT")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
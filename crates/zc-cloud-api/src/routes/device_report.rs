@@ -0,0 +1,213 @@
+//! Device diagnostic report endpoint.
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, header};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::device_identity;
+use crate::device_report::{self, DEFAULT_DAYS, MAX_DAYS};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// `Content-Type` of the rendered report.
+const HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+
+/// Query parameters for the device report.
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    /// Days covered, ending now (default 7, max 90).
+    pub days: Option<u32>,
+    /// `json` or `html`; without it `Accept: text/html` selects HTML.
+    pub format: Option<String>,
+}
+
+/// GET /api/v1/devices/:id/report — heartbeat, DTC, log error and telemetry
+/// summary of a device over the last `days` days, as JSON or as a printable
+/// HTML page for sharing with a repair shop.
+pub async fn get_device_report(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let days = query.days.unwrap_or(DEFAULT_DAYS);
+    if !(1..=MAX_DAYS).contains(&days) {
+        return Err(ApiError::BadRequest(format!(
+            "days must be between 1 and {MAX_DAYS}"
+        )));
+    }
+    let html = match query.format.as_deref() {
+        Some("html") => true,
+        Some("json") => false,
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "unknown format '{other}' (expected json or html)"
+            )));
+        }
+        None => headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/html")),
+    };
+
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let report = device_report::build(&state, &device_id, days, Utc::now()).await?;
+    if html {
+        let body = device_report::render_html(&report);
+        return Ok(([(header::CONTENT_TYPE, HTML_CONTENT_TYPE)], body).into_response());
+    }
+    Ok(Json(report).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::{AppState, CommandRecord};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_protocol::commands::{ActionKind, CommandEnvelope, CommandStatus, ParsedIntent};
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, String, String) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    /// Record a command for rpi-001 calling the tool named in
+    /// `response_data` and ingest a completed response.
+    async fn respond(app: &axum::Router, state: &AppState, response_data: serde_json::Value) {
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "diagnose", "admin");
+        envelope.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: response_data["tool_name"].as_str().unwrap().into(),
+            tool_args: serde_json::json!({}),
+            confidence: 1.0,
+            steps: vec![],
+        });
+        let id = envelope.id;
+        state.commands.write().await.push(CommandRecord {
+            envelope: envelope.clone(),
+            response: None,
+            status: CommandStatus::Pending,
+            created_at: envelope.created_at,
+            deadline: None,
+            attempts: 0,
+            max_attempts: 1,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
+        });
+        let body = serde_json::json!({
+            "command_id": id,
+            "correlation_id": envelope.correlation_id,
+            "device_id": "rpi-001",
+            "status": "completed",
+            "inference_tier": "local",
+            "response_data": response_data,
+            "latency_ms": 40,
+            "responded_at": chrono::Utc::now(),
+        });
+        let (status, _, _) = send(
+            app.clone(),
+            Request::post(format!("/api/v1/commands/{id}/respond"))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn report_combines_dtcs_and_log_errors() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        respond(
+            &app,
+            &state,
+            serde_json::json!({"tool_name": "read_dtcs", "success": true,
+                "data": [{"code": "P0300", "severity": "critical",
+                    "description": "Random misfire <cyl>"}]}),
+        )
+        .await;
+        respond(
+            &app,
+            &state,
+            serde_json::json!({"tool_name": "analyze_errors", "success": true,
+                "data": {"patterns": [{"category": "can_bus_error", "count": 5,
+                    "examples": ["bus-off on can0"]}]}}),
+        )
+        .await;
+
+        let (status, content_type, body) = send(
+            app.clone(),
+            Request::get("/api/v1/devices/rpi-001/report")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("application/json"));
+        let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(report["device"]["device_id"], "rpi-001");
+        assert_eq!(report["dtcs"]["dtcs"][0]["code"], "P0300");
+        assert_eq!(report["log_errors"]["analyses"], 1);
+        assert_eq!(
+            report["log_errors"]["categories"][0]["category"],
+            "can_bus_error"
+        );
+        assert_eq!(report["log_errors"]["categories"][0]["count"], 5);
+
+        let (status, content_type, body) = send(
+            app,
+            Request::get("/api/v1/devices/rpi-001/report?format=html&days=30")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, super::HTML_CONTENT_TYPE);
+        assert!(body.contains("Diagnostic report: rpi-001"));
+        assert!(body.contains("Random misfire &lt;cyl&gt;"));
+        assert!(body.contains("can_bus_error"));
+    }
+
+    #[tokio::test]
+    async fn report_rejects_bad_queries() {
+        let app = build_router(AppState::with_sample_data());
+        for (uri, expected) in [
+            (
+                "/api/v1/devices/rpi-001/report?days=0",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/api/v1/devices/rpi-001/report?days=91",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/api/v1/devices/rpi-001/report?format=pdf",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("/api/v1/devices/rpi-999/report", StatusCode::NOT_FOUND),
+        ] {
+            let (status, _, _) =
+                send(app.clone(), Request::get(uri).body(Body::empty()).unwrap()).await;
+            assert_eq!(status, expected, "{uri}");
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Device tag endpoints.
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+
+use crate::device_identity;
+use crate::device_tags::{self, Tags};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// GET /api/v1/devices/:id/tags — tags of a device.
+pub async fn get_tags(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+) -> ApiResult<Json<Tags>> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    device_tags::tags_for(&state, &device_id).await.map(Json)
+}
+
+/// PUT /api/v1/devices/:id/tags — set tags (`{"key": "value"}`), keeping
+/// keys not mentioned. Returns the device's full tag set.
+pub async fn put_tags(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+    Json(tags): Json<Tags>,
+) -> ApiResult<Json<Tags>> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let tags = device_tags::normalize(&tags).map_err(ApiError::BadRequest)?;
+    device_tags::set_tags(&state, &device_id, &tags)
+        .await
+        .map(Json)
+}
+
+/// DELETE /api/v1/devices/:id/tags/:key — remove a tag.
+pub async fn delete_tag(
+    State(state): State<AppState>,
+    Path((reference, key)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let key = device_tags::normalize_key(&key).map_err(ApiError::BadRequest)?;
+    if device_tags::remove_tag(&state, &device_id, &key).await? {
+        tracing::info!(device_id = %device_id, key = %key, "device tag removed");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "device '{device_id}' has no tag '{key}'"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn put(device: &str, tags: serde_json::Value) -> Request<Body> {
+        Request::put(format!("/api/v1/devices/{device}/tags"))
+            .header("content-type", "application/json")
+            .body(Body::from(tags.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn tag_and_filter_device_list() {
+        let app = build_router(AppState::with_sample_data());
+
+        let (status, body) = send(
+            app.clone(),
+            put(
+                "rpi-001",
+                serde_json::json!({"Env": "prod", "region": "eu"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["env"], "prod");
+        send(
+            app.clone(),
+            put("sbc-010", serde_json::json!({"env": "prod"})),
+        )
+        .await;
+
+        let (_, body) = send(
+            app.clone(),
+            Request::get("/api/v1/devices?tag=env:prod")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        let ids: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["device_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["rpi-001", "sbc-010"]);
+
+        let (_, body) = send(
+            app.clone(),
+            Request::get("/api/v1/devices?tag=env:prod,region:eu")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, _) = send(
+            app.clone(),
+            Request::delete("/api/v1/devices/rpi-001/tags/region")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (_, body) = send(
+            app,
+            Request::get("/api/v1/devices/rpi-001/tags")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body, serde_json::json!({"env": "prod"}));
+    }
+
+    #[tokio::test]
+    async fn invalid_tags_are_rejected() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, _) = send(
+            app.clone(),
+            put("rpi-001", serde_json::json!({"bad key": "x"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(app, put("nope", serde_json::json!({"env": "prod"}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
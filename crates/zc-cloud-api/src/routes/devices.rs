@@ -1,97 +1,269 @@
 //! Device registry endpoints.
 
-use axum::Json;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use chrono::Utc;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::audit::{AuditAction, AuditEntry, AuditOutcome};
+use crate::auth::Principal;
+use crate::db::devices::DeviceFilter;
+use crate::device_identity::{self, AliasKind};
+use crate::device_status::{StatusChange, status_name};
+use crate::device_tags::{self, Tags};
 use crate::error::{ApiError, ApiResult};
 use crate::events::WsEvent;
+use crate::openapi::ApiErrorBody;
+use crate::routes::{csv, pagination};
 use crate::state::AppState;
 use zc_protocol::device::{DeviceInfo, DeviceStatus, FleetId, HardwareType};
 
+/// Columns of the device list as CSV.
+const CSV_COLUMNS: &[&str] = &["device_id", "status", "hardware_type", "last_heartbeat"];
+
 /// Summary view of a device (for list responses).
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct DeviceSummary {
     pub device_id: String,
     pub status: DeviceStatus,
     pub hardware_type: HardwareType,
     pub last_heartbeat: Option<chrono::DateTime<chrono::Utc>>,
+    /// Expiry of the device's client certificate, once reported.
+    pub certificate_expires_at: Option<DateTime<Utc>>,
 }
 
 /// Request body for provisioning a new device.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct ProvisionDeviceRequest {
     pub device_id: String,
     pub fleet_id: String,
     pub hardware_type: String,
     pub vin: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    /// Initial `key=value` tags.
+    #[serde(default)]
+    #[schema(value_type = BTreeMap<String, String>)]
+    pub tags: Tags,
 }
 
-/// GET /api/v1/devices — list all devices.
-pub async fn list_devices(State(state): State<AppState>) -> ApiResult<Json<Vec<DeviceSummary>>> {
-    if let Some(pool) = &state.pool {
-        let rows = crate::db::devices::list_all(pool)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-        let summaries = rows
-            .into_iter()
-            .map(|r| DeviceSummary {
-                device_id: r.device_id,
-                status: parse_device_status(&r.status),
-                hardware_type: parse_hardware_type(&r.hardware_type),
-                last_heartbeat: r.last_heartbeat,
-            })
-            .collect();
-        return Ok(Json(summaries));
-    }
+/// Query parameters for the device list.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListDevicesQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    pub status: Option<DeviceStatus>,
+    /// Only devices heard from at or after this RFC 3339 time.
+    pub since: Option<DateTime<Utc>>,
+    /// Comma-separated `key:value` tags the device must all carry.
+    pub tag: Option<String>,
+    /// Only devices whose certificate expires within this many days
+    /// (including already expired ones).
+    pub cert_expiring_within_days: Option<u32>,
+}
 
-    // In-memory fallback
-    let devices = state.devices.read().await;
+/// GET /api/v1/devices — list devices, ordered by device ID.
+///
+/// Filtered by `status`, `since`, `tag` (e.g. `env:prod,region:eu`) and
+/// `cert_expiring_within_days`, paged with `limit`/`offset`. The
+/// unpaged match count is in `X-Total-Count`. `Accept: text/csv` returns
+/// the page as CSV.
+#[utoipa::path(
+    get,
+    path = "/api/v1/devices",
+    tag = "devices",
+    params(ListDevicesQuery),
+    responses(
+        (status = 200, description = "One page of devices", body = [DeviceSummary],
+            headers(("X-Total-Count" = u64, description = "Devices matching the filter"))),
+        (status = 400, description = "Invalid tag selector", body = ApiErrorBody),
+    )
+)]
+pub async fn list_devices(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListDevicesQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let tags = query
+        .tag
+        .as_deref()
+        .map(device_tags::parse_selectors)
+        .transpose()
+        .map_err(ApiError::BadRequest)?
+        .unwrap_or_default();
+    let filter = DeviceFilter {
+        status: query.status.map(|s| status_name(s).to_string()),
+        since: query.since,
+        cert_expires_before: query
+            .cert_expiring_within_days
+            .map(|days| Utc::now() + chrono::Duration::days(days.into())),
+        tags,
+        fleets: principal.and_then(|Extension(p)| p.tenants),
+        limit: pagination::limit(query.limit),
+        offset: query.offset,
+    };
+
+    let (devices, total) = state.store.list_devices(&filter).await?;
     let summaries: Vec<DeviceSummary> = devices
-        .values()
+        .into_iter()
         .map(|d| DeviceSummary {
-            device_id: d.device_id.clone(),
+            device_id: d.device_id,
             status: d.status,
-            hardware_type: d.hardware_type.clone(),
+            hardware_type: d.hardware_type,
             last_heartbeat: d.last_heartbeat,
+            certificate_expires_at: d.certificate_expires_at,
         })
         .collect();
-    Ok(Json(summaries))
+    Ok(csv::paged(&headers, CSV_COLUMNS, summaries, total))
 }
 
 /// GET /api/v1/devices/:id — get device details.
+#[utoipa::path(
+    get,
+    path = "/api/v1/devices/{id}",
+    tag = "devices",
+    params(("id" = String, Path, description = "Device ID or alias")),
+    responses(
+        (status = 200, description = "The device", body = DeviceInfo),
+        (status = 404, description = "Unknown device", body = ApiErrorBody),
+    )
+)]
 pub async fn get_device(
     State(state): State<AppState>,
     Path(device_id): Path<String>,
 ) -> ApiResult<Json<DeviceInfo>> {
-    if let Some(pool) = &state.pool {
-        let row = crate::db::devices::get_by_device_id(pool, &device_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?
-            .ok_or_else(|| ApiError::NotFound(format!("device '{device_id}' not found")))?;
-        return Ok(Json(row_to_device_info(row)));
-    }
-
-    // In-memory fallback
-    let devices = state.devices.read().await;
-    devices
-        .get(&device_id)
-        .cloned()
+    let device_id = crate::device_identity::resolve(&state, &device_id).await?;
+    state
+        .store
+        .get_device(&device_id)
+        .await?
         .map(Json)
         .ok_or_else(|| ApiError::NotFound(format!("device '{device_id}' not found")))
 }
 
+/// Query parameters for the status history.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatusHistoryQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// GET /api/v1/devices/:id/status-history — online/degraded/offline
+/// transitions of a device, newest first, with what caused each. The total
+/// is in `X-Total-Count`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/devices/{id}/status-history",
+    tag = "devices",
+    params(("id" = String, Path, description = "Device ID or alias"), StatusHistoryQuery),
+    responses(
+        (status = 200, description = "Status changes, newest first", body = [StatusChange],
+            headers(("X-Total-Count" = u64, description = "All status changes of the device"))),
+        (status = 404, description = "Unknown device", body = ApiErrorBody),
+    )
+)]
+pub async fn get_status_history(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+    Query(query): Query<StatusHistoryQuery>,
+) -> ApiResult<Response> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let (page, total) = crate::device_status::history(
+        &state,
+        &device_id,
+        pagination::limit(query.limit),
+        query.offset,
+    )
+    .await?;
+    Ok(pagination::with_total(page, total))
+}
+
 /// POST /api/v1/devices — provision a new device.
+///
+/// A VIN, if given, is registered as an alias; 409 if another device has it.
+/// Every attempt is audited, refused ones with outcome `rejected`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/devices",
+    tag = "devices",
+    request_body = ProvisionDeviceRequest,
+    responses(
+        (status = 201, description = "Device provisioned", body = DeviceInfo),
+        (status = 400, description = "Invalid VIN or tags, or unregistered fleet", body = ApiErrorBody),
+        (status = 403, description = "Fleet not allowed", body = ApiErrorBody),
+        (status = 409, description = "Device ID or VIN already taken", body = ApiErrorBody),
+    )
+)]
 pub async fn provision_device(
     State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
     Json(req): Json<ProvisionDeviceRequest>,
 ) -> Result<(StatusCode, Json<DeviceInfo>), ApiError> {
+    let actor = crate::auth::actor(principal.as_deref(), "operator".into());
+    let device_id = req.device_id.clone();
+    let fleet_id = req.fleet_id.clone();
+    let mut detail = serde_json::json!({
+        "hardware_type": req.hardware_type,
+        "vin": req.vin,
+    });
+    let result = provision(&state, principal.as_deref(), req).await;
+    let outcome = match &result {
+        Ok(_) => AuditOutcome::Succeeded,
+        Err(ApiError::Internal(_)) => AuditOutcome::Failed,
+        Err(e) => {
+            detail["error"] = e.to_string().into();
+            AuditOutcome::Rejected
+        }
+    };
+    crate::audit::record(
+        &state,
+        AuditEntry::device(actor, AuditAction::DeviceProvisioned, device_id, detail)
+            .with_fleet(fleet_id)
+            .with_outcome(outcome),
+    )
+    .await?;
+    result
+}
+
+pub(crate) async fn provision(
+    state: &AppState,
+    principal: Option<&Principal>,
+    req: ProvisionDeviceRequest,
+) -> Result<(StatusCode, Json<DeviceInfo>), ApiError> {
+    if let Some(user) = principal
+        && !user.can_access_fleet(&req.fleet_id)
+    {
+        return Err(ApiError::Forbidden(format!(
+            "no access to fleet '{}'",
+            req.fleet_id
+        )));
+    }
+    crate::fleets::ensure_registered(state, &req.fleet_id).await?;
     let now = Utc::now();
     let hw_type = parse_hardware_type(&req.hardware_type);
+    let vin = req
+        .vin
+        .as_deref()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| AliasKind::Vin.normalize(v))
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+    if let Some(vin) = &vin {
+        device_identity::ensure_available(state, &req.device_id, AliasKind::Vin, vin).await?;
+    }
+    let tags = device_tags::normalize(&req.tags).map_err(ApiError::BadRequest)?;
     let metadata = req.metadata.unwrap_or(serde_json::json!({}));
     // Merge fleet_id string into metadata for human-readable reference.
     let metadata = {
@@ -105,55 +277,11 @@ pub async fn provision_device(
         m
     };
 
-    if let Some(pool) = &state.pool {
-        let exists = crate::db::devices::exists(pool, &req.device_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-        if exists {
-            return Err(ApiError::Conflict(format!(
-                "device '{}' already exists",
-                req.device_id
-            )));
-        }
-
-        let row = crate::db::devices::DeviceRow {
-            id: Uuid::now_v7(),
-            fleet_id: Uuid::now_v7(),
-            device_id: req.device_id.clone(),
-            status: "provisioning".to_string(),
-            vin: req.vin.clone(),
-            hardware_type: req.hardware_type.clone(),
-            certificate_id: None,
-            last_heartbeat: None,
-            metadata: metadata.clone(),
-            created_at: now,
-            updated_at: now,
-        };
-        crate::db::devices::insert(pool, &row)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-
-        let device = row_to_device_info(row);
-
-        let _ = state.event_tx.send(WsEvent::DeviceProvisioned {
-            device_id: req.device_id,
-            fleet_id: req.fleet_id,
-            hardware_type: req.hardware_type,
-            provisioned_at: now,
-        });
-
-        return Ok((StatusCode::CREATED, Json(device)));
-    }
-
-    // In-memory mode
-    {
-        let devices = state.devices.read().await;
-        if devices.contains_key(&req.device_id) {
-            return Err(ApiError::Conflict(format!(
-                "device '{}' already exists",
-                req.device_id
-            )));
-        }
+    if state.store.device_exists(&req.device_id).await? {
+        return Err(ApiError::Conflict(format!(
+            "device '{}' already exists",
+            req.device_id
+        )));
     }
 
     let device = DeviceInfo {
@@ -161,18 +289,23 @@ pub async fn provision_device(
         fleet_id: FleetId(Uuid::now_v7()),
         device_id: req.device_id.clone(),
         status: DeviceStatus::Provisioning,
-        vin: req.vin.clone(),
+        vin: vin.clone(),
         hardware_type: hw_type,
         certificate_id: None,
+        certificate_expires_at: None,
         last_heartbeat: None,
+        vitals: None,
         metadata,
         created_at: now,
         updated_at: now,
     };
 
-    {
-        let mut devices = state.devices.write().await;
-        devices.insert(req.device_id.clone(), device.clone());
+    state.store.insert_device(&device).await?;
+    if let Some(vin) = &vin {
+        device_identity::claim(state, &req.device_id, AliasKind::Vin, vin).await?;
+    }
+    if !tags.is_empty() {
+        device_tags::set_tags(state, &req.device_id, &tags).await?;
     }
 
     let _ = state.event_tx.send(WsEvent::DeviceProvisioned {
@@ -185,9 +318,10 @@ pub async fn provision_device(
     Ok((StatusCode::CREATED, Json(device)))
 }
 
-fn parse_device_status(s: &str) -> DeviceStatus {
+pub(crate) fn parse_device_status(s: &str) -> DeviceStatus {
     match s {
         "online" => DeviceStatus::Online,
+        "degraded" => DeviceStatus::Degraded,
         "offline" => DeviceStatus::Offline,
         "maintenance" => DeviceStatus::Maintenance,
         "decommissioned" => DeviceStatus::Decommissioned,
@@ -195,6 +329,16 @@ fn parse_device_status(s: &str) -> DeviceStatus {
     }
 }
 
+/// Wire name of a hardware type, as stored in the database.
+pub(crate) fn hardware_type_name(hardware_type: &HardwareType) -> String {
+    match hardware_type {
+        HardwareType::RaspberryPi4 => "raspberry_pi_4".into(),
+        HardwareType::RaspberryPi5 => "raspberry_pi_5".into(),
+        HardwareType::IndustrialSbc => "industrial_sbc".into(),
+        HardwareType::Custom(s) => s.clone(),
+    }
+}
+
 fn parse_hardware_type(s: &str) -> HardwareType {
     match s {
         "raspberry_pi_4" => HardwareType::RaspberryPi4,
@@ -204,7 +348,7 @@ fn parse_hardware_type(s: &str) -> HardwareType {
     }
 }
 
-fn row_to_device_info(r: crate::db::devices::DeviceRow) -> DeviceInfo {
+pub(crate) fn row_to_device_info(r: crate::db::devices::DeviceRow) -> DeviceInfo {
     DeviceInfo {
         id: r.id,
         fleet_id: FleetId(r.fleet_id),
@@ -213,7 +357,9 @@ fn row_to_device_info(r: crate::db::devices::DeviceRow) -> DeviceInfo {
         vin: r.vin,
         hardware_type: parse_hardware_type(&r.hardware_type),
         certificate_id: r.certificate_id,
+        certificate_expires_at: r.certificate_expires_at,
         last_heartbeat: r.last_heartbeat,
+        vitals: r.vitals.and_then(|v| serde_json::from_value(v).ok()),
         metadata: r.metadata,
         created_at: r.created_at,
         updated_at: r.updated_at,
@@ -268,7 +414,8 @@ mod tests {
             "hardware_type": "raspberry_pi_4"
         });
 
-        let response = app()
+        let state = AppState::with_sample_data();
+        let response = build_router(state.clone())
             .oneshot(
                 Request::post("/api/v1/devices")
                     .header("content-type", "application/json")
@@ -279,6 +426,19 @@ mod tests {
             .unwrap();
 
         assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        // The refused attempt is still audited.
+        let audit = state.audit_log.read().await;
+        let entry = audit.back().unwrap();
+        assert_eq!(entry.action, AuditAction::DeviceProvisioned);
+        assert_eq!(entry.outcome, Some(AuditOutcome::Rejected));
+        assert_eq!(entry.device_id.as_deref(), Some("rpi-001"));
+        assert!(
+            entry.detail["error"]
+                .as_str()
+                .unwrap()
+                .contains("already exists")
+        );
     }
 
     #[tokio::test]
@@ -346,4 +506,151 @@ mod tests {
         assert!(json.contains("device_provisioned"));
         assert!(json.contains("rpi-event-001"));
     }
+
+    #[tokio::test]
+    async fn list_pages_and_filters_with_total_header() {
+        let state = AppState::with_sample_data();
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("sbc-010")
+            .unwrap()
+            .status = DeviceStatus::Offline;
+        let app = build_router(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/devices?limit=2&offset=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-total-count"], "3");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<&str> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["device_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["rpi-002", "sbc-010"]);
+
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/devices?status=online&since=2000-01-01T00:00:00Z")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-total-count"], "2");
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_certificate_expiry() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        {
+            let mut devices = state.devices.write().await;
+            devices.get_mut("rpi-001").unwrap().certificate_expires_at =
+                Some(now + chrono::Duration::days(10));
+            devices.get_mut("rpi-002").unwrap().certificate_expires_at =
+                Some(now + chrono::Duration::days(200));
+        }
+
+        let response = build_router(state)
+            .oneshot(
+                Request::get("/api/v1/devices?cert_expiring_within_days=30")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-total-count"], "1");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json[0]["device_id"], "rpi-001");
+        assert!(json[0]["certificate_expires_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn list_as_csv_with_total_header() {
+        let response = build_router(AppState::with_sample_data())
+            .oneshot(
+                Request::get("/api/v1/devices?limit=2")
+                    .header("accept", "text/csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], csv::CONTENT_TYPE);
+        assert_eq!(response.headers()["x-total-count"], "3");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "device_id,status,hardware_type,last_heartbeat");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("rpi-001,online,"));
+    }
+
+    #[tokio::test]
+    async fn silent_devices_listed_by_status_with_history() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .last_heartbeat = Some(now - chrono::Duration::minutes(10));
+        crate::device_status::tick(&state, now).await.unwrap();
+        let app = build_router(state);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/devices?status=offline")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-total-count"], "1");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json[0]["device_id"], "rpi-002");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v1/devices/rpi-002/status-history")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json[0]["from"], "online");
+        assert_eq!(json[0]["to"], "offline");
+        assert_eq!(json[0]["reason"], "heartbeat_timeout");
+
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/devices/ghost/status-history")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 }
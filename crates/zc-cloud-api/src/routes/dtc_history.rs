@@ -0,0 +1,199 @@
+//! Per-device DTC history endpoint.
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::device_identity;
+use crate::dtc_history;
+use crate::error::ApiResult;
+use crate::routes::csv;
+use crate::state::AppState;
+
+/// Columns of the DTC history as CSV.
+const CSV_COLUMNS: &[&str] = &[
+    "code",
+    "severity",
+    "description",
+    "first_seen",
+    "last_seen",
+    "occurrences",
+    "active",
+];
+
+/// Query parameters for the DTC history.
+#[derive(Debug, Deserialize)]
+pub struct DtcHistoryQuery {
+    /// Only codes that are (`true`) or are no longer (`false`) active.
+    pub active: Option<bool>,
+}
+
+/// GET /api/v1/devices/:id/dtcs — every code the device has reported, with
+/// first/last sighting, occurrence count and active status. `Accept:
+/// text/csv` returns just the codes, one row each.
+pub async fn get_dtc_history(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+    Query(query): Query<DtcHistoryQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let device_id = device_identity::resolve_existing(&state, &reference).await?;
+    let mut history = dtc_history::history(&state, &device_id).await?;
+    if let Some(active) = query.active {
+        history.dtcs.retain(|d| d.active == active);
+    }
+    if csv::accepts_csv(&headers) {
+        return Ok(csv::response(csv::render(CSV_COLUMNS, &history.dtcs)));
+    }
+    Ok(Json(history).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::{AppState, CommandRecord};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_protocol::commands::{CommandEnvelope, CommandStatus};
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    /// Record a `read_dtcs` command for rpi-001 and ingest its response.
+    async fn scan(app: &axum::Router, state: &AppState, codes: &[&str]) {
+        let envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let id = envelope.id;
+        state.commands.write().await.push(CommandRecord {
+            envelope: envelope.clone(),
+            response: None,
+            status: CommandStatus::Pending,
+            created_at: envelope.created_at,
+            deadline: None,
+            attempts: 0,
+            max_attempts: 1,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
+        });
+        let data: Vec<_> = codes
+            .iter()
+            .map(|c| serde_json::json!({"code": c, "severity": "critical"}))
+            .collect();
+        let body = serde_json::json!({
+            "command_id": id,
+            "correlation_id": envelope.correlation_id,
+            "device_id": "rpi-001",
+            "status": "completed",
+            "inference_tier": "local",
+            "response_data": {"tool_name": "read_dtcs", "success": true, "data": data},
+            "latency_ms": 40,
+            "responded_at": chrono::Utc::now(),
+        });
+        let (status, _) = send(
+            app.clone(),
+            Request::post(format!("/api/v1/commands/{id}/respond"))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn history_aggregates_ingested_scans() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        scan(&app, &state, &["P0300", "P0171"]).await;
+        scan(&app, &state, &["P0300"]).await;
+
+        let (status, body) = send(
+            app.clone(),
+            Request::get("/api/v1/devices/rpi-001/dtcs")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let dtcs = body["dtcs"].as_array().unwrap();
+        assert_eq!(dtcs.len(), 2);
+        assert_eq!(dtcs[0]["code"], "P0300");
+        assert_eq!(dtcs[0]["occurrences"], 2);
+        assert_eq!(dtcs[0]["active"], true);
+        assert_eq!(dtcs[1]["code"], "P0171");
+        assert_eq!(dtcs[1]["active"], false);
+
+        let (_, body) = send(
+            app,
+            Request::get("/api/v1/devices/rpi-001/dtcs?active=false")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body["dtcs"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn history_as_csv() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        scan(&app, &state, &["P0300"]).await;
+
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/devices/rpi-001/dtcs")
+                    .header("accept", "text/csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/csv; charset=utf-8"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "code,severity,description,first_seen,last_seen,occurrences,active"
+        );
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("P0300,critical,"));
+        assert!(lines[1].ends_with(",1,true"));
+    }
+
+    #[tokio::test]
+    async fn unknown_device_and_empty_history() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, _) = send(
+            app.clone(),
+            Request::get("/api/v1/devices/nope/dtcs")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = send(
+            app,
+            Request::get("/api/v1/devices/rpi-002/dtcs")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["last_scan_at"].is_null());
+        assert!(body["dtcs"].as_array().unwrap().is_empty());
+    }
+}
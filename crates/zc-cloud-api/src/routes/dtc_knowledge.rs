@@ -0,0 +1,249 @@
+//! Admin endpoints for the DTC knowledge base (repair hints and links).
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::dtc_knowledge::{self, DtcKnowledge, DtcLink};
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+
+/// Request body for creating or replacing an entry.
+#[derive(Debug, Deserialize)]
+pub struct PutKnowledgeRequest {
+    pub repair_hint: Option<String>,
+    #[serde(default)]
+    pub links: Vec<DtcLink>,
+}
+
+/// GET /api/v1/admin/dtc-knowledge — all entries, ordered by code.
+pub async fn list_knowledge(State(state): State<AppState>) -> ApiResult<Json<Vec<DtcKnowledge>>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::dtc_knowledge::list(pool)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(Json(rows.into_iter().map(DtcKnowledge::from_row).collect()));
+    }
+
+    let kb = state.dtc_knowledge.read().await;
+    let mut entries: Vec<DtcKnowledge> = kb.values().cloned().collect();
+    entries.sort_by(|a, b| a.code.cmp(&b.code));
+    Ok(Json(entries))
+}
+
+/// GET /api/v1/admin/dtc-knowledge/{code} — one entry.
+pub async fn get_knowledge(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> ApiResult<Json<DtcKnowledge>> {
+    let code = dtc_knowledge::normalize_code(&code).map_err(ApiError::BadRequest)?;
+    dtc_knowledge::lookup(&state, std::slice::from_ref(&code))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .remove(&code)
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("no knowledge entry for {code}")))
+}
+
+/// PUT /api/v1/admin/dtc-knowledge/{code} — create or replace an entry.
+pub async fn put_knowledge(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Json(req): Json<PutKnowledgeRequest>,
+) -> ApiResult<Json<DtcKnowledge>> {
+    let entry = DtcKnowledge {
+        code: dtc_knowledge::normalize_code(&code).map_err(ApiError::BadRequest)?,
+        repair_hint: req
+            .repair_hint
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty()),
+        links: req.links,
+        updated_at: Utc::now(),
+    };
+    entry.validate().map_err(ApiError::BadRequest)?;
+
+    dtc_knowledge::upsert(&state, std::slice::from_ref(&entry))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    tracing::info!(code = %entry.code, links = entry.links.len(), "DTC knowledge entry saved");
+    Ok(Json(entry))
+}
+
+/// DELETE /api/v1/admin/dtc-knowledge/{code} — remove an entry.
+pub async fn delete_knowledge(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> ApiResult<StatusCode> {
+    let code = dtc_knowledge::normalize_code(&code).map_err(ApiError::BadRequest)?;
+    let existed = if let Some(pool) = &state.pool {
+        crate::db::dtc_knowledge::delete(pool, &code)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+    } else {
+        state.dtc_knowledge.write().await.remove(&code).is_some()
+    };
+
+    if existed {
+        tracing::info!(code = %code, "DTC knowledge entry deleted");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("no knowledge entry for {code}")))
+    }
+}
+
+/// POST /api/v1/admin/dtc-knowledge/import — bulk import from CSV.
+///
+/// The whole file is validated first; nothing is stored if any row is bad.
+/// Imported codes replace their existing entries.
+pub async fn import_knowledge(
+    State(state): State<AppState>,
+    body: String,
+) -> ApiResult<Json<serde_json::Value>> {
+    let entries = dtc_knowledge::parse_csv(&body).map_err(ApiError::BadRequest)?;
+    dtc_knowledge::upsert(&state, &entries)
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+
+    tracing::info!(count = entries.len(), "DTC knowledge imported");
+    Ok(Json(serde_json::json!({
+        "imported": entries.len(),
+        "codes": entries.iter().map(|e| e.code.as_str()).collect::<Vec<_>>(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::build_router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn put_get_delete_entry() {
+        let app = build_router(AppState::new());
+
+        let (status, body) = send(
+            app.clone(),
+            Request::put("/api/v1/admin/dtc-knowledge/p0420")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    r#"{"repair_hint": "Test O2 sensors before replacing the catalyst",
+                        "links": [{"title": "P0420 guide", "url": "https://example.com/p0420"}]}"#,
+                ))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["code"], "P0420");
+
+        let (status, body) = send(
+            app.clone(),
+            Request::get("/api/v1/admin/dtc-knowledge/P0420")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["links"][0]["title"], "P0420 guide");
+
+        let (status, _) = send(
+            app.clone(),
+            Request::delete("/api/v1/admin/dtc-knowledge/P0420")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, _) = send(
+            app,
+            Request::get("/api/v1/admin/dtc-knowledge/P0420")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn put_rejects_empty_entry_and_bad_code() {
+        let app = build_router(AppState::new());
+        let (status, _) = send(
+            app.clone(),
+            Request::put("/api/v1/admin/dtc-knowledge/P0420")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"repair_hint": "  "}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(
+            app,
+            Request::put("/api/v1/admin/dtc-knowledge/notacode")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"repair_hint": "x"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn import_csv_then_list() {
+        let app = build_router(AppState::new());
+        let csv = "code,repair_hint,link_title,link_url\n\
+                   P0300,Check plugs and coils,Misfire guide,https://example.com/p0300\n\
+                   P0171,Check for vacuum leaks,,\n";
+
+        let (status, body) = send(
+            app.clone(),
+            Request::post("/api/v1/admin/dtc-knowledge/import")
+                .header("content-type", "text/csv")
+                .body(Body::from(csv))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["imported"], 2);
+
+        let (_, body) = send(
+            app.clone(),
+            Request::get("/api/v1/admin/dtc-knowledge")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        let codes: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["code"].as_str().unwrap())
+            .collect();
+        assert_eq!(codes, vec!["P0171", "P0300"]);
+
+        let (status, body) = send(
+            app,
+            Request::post("/api/v1/admin/dtc-knowledge/import")
+                .body(Body::from(
+                    "code,repair_hint,link_title,link_url\nBAD,x,,\n",
+                ))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("line 2"));
+    }
+}
@@ -0,0 +1,303 @@
+//! Inference experiment endpoints.
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::experiments::{self, Experiment, Variant, VariantResult};
+use crate::state::AppState;
+
+/// Request body for starting an experiment.
+#[derive(Debug, Deserialize)]
+pub struct CreateExperimentRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub variants: Vec<Variant>,
+}
+
+/// An experiment with its per-variant results.
+#[derive(Debug, Serialize)]
+pub struct ExperimentResults {
+    #[serde(flatten)]
+    pub experiment: Experiment,
+    pub results: Vec<VariantResult>,
+}
+
+/// POST /api/v1/experiments — start an experiment (stops any running one).
+pub async fn create_experiment(
+    State(state): State<AppState>,
+    Json(req): Json<CreateExperimentRequest>,
+) -> Result<(StatusCode, Json<Experiment>), ApiError> {
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    experiments::validate_variants(&req.variants).map_err(ApiError::BadRequest)?;
+
+    let experiment = Experiment {
+        id: Uuid::now_v7(),
+        name: req.name,
+        description: req.description,
+        variants: req.variants,
+        active: true,
+        created_at: Utc::now(),
+        stopped_at: None,
+    };
+
+    if let Some(pool) = &state.pool {
+        let row = crate::db::experiments::ExperimentRow {
+            id: experiment.id,
+            name: experiment.name.clone(),
+            description: experiment.description.clone(),
+            variants: serde_json::to_value(&experiment.variants)
+                .map_err(|e| ApiError::Internal(e.to_string()))?,
+            active: true,
+            created_at: experiment.created_at,
+            stopped_at: None,
+        };
+        crate::db::experiments::insert(pool, &row)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+    } else {
+        let mut all = state.experiments.write().await;
+        for running in all.iter_mut().filter(|e| e.active) {
+            running.active = false;
+            running.stopped_at = Some(experiment.created_at);
+        }
+        all.push(experiment.clone());
+    }
+
+    tracing::info!(experiment_id = %experiment.id, name = %experiment.name, "experiment started");
+    Ok((StatusCode::CREATED, Json(experiment)))
+}
+
+/// GET /api/v1/experiments — list experiments, newest first.
+pub async fn list_experiments(State(state): State<AppState>) -> ApiResult<Json<Vec<Experiment>>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::experiments::list(pool)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        return Ok(Json(rows.into_iter().map(Experiment::from_row).collect()));
+    }
+
+    let all = state.experiments.read().await;
+    Ok(Json(all.iter().rev().cloned().collect()))
+}
+
+/// GET /api/v1/experiments/{id} — experiment with per-variant results.
+pub async fn get_experiment(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ExperimentResults>> {
+    let experiment = find(&state, id).await?;
+    let assignments = experiments::assignments(&state, id)
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+    let results = experiments::summarize(&experiment.variants, &assignments);
+    Ok(Json(ExperimentResults {
+        experiment,
+        results,
+    }))
+}
+
+/// POST /api/v1/experiments/{id}/stop — stop assigning traffic.
+pub async fn stop_experiment(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Experiment>> {
+    let experiment = if let Some(pool) = &state.pool {
+        crate::db::experiments::stop(pool, id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map(Experiment::from_row)
+    } else {
+        let mut all = state.experiments.write().await;
+        all.iter_mut().find(|e| e.id == id).map(|e| {
+            if e.active {
+                e.active = false;
+                e.stopped_at = Some(Utc::now());
+            }
+            e.clone()
+        })
+    }
+    .ok_or_else(|| ApiError::NotFound(format!("experiment '{id}' not found")))?;
+
+    tracing::info!(experiment_id = %id, "experiment stopped");
+    Ok(Json(experiment))
+}
+
+async fn find(state: &AppState, id: Uuid) -> ApiResult<Experiment> {
+    let experiment = if let Some(pool) = &state.pool {
+        crate::db::experiments::get_by_id(pool, id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map(Experiment::from_row)
+    } else {
+        let all = state.experiments.read().await;
+        all.iter().find(|e| e.id == id).cloned()
+    };
+    experiment.ok_or_else(|| ApiError::NotFound(format!("experiment '{id}' not found")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(match body {
+                Some(b) => Body::from(serde_json::to_vec(&b).unwrap()),
+                None => Body::empty(),
+            })
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+        (status, json)
+    }
+
+    async fn send(app: &Router, command: &str) -> String {
+        let (status, json) = call(
+            app,
+            "POST",
+            "/api/v1/commands",
+            Some(serde_json::json!({
+                "device_id": "rpi-001",
+                "fleet_id": "fleet-alpha",
+                "command": command,
+                "initiated_by": "admin"
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        json["id"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn records_parse_and_outcome_per_variant() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, exp) = call(
+            &app,
+            "POST",
+            "/api/v1/experiments",
+            Some(serde_json::json!({
+                "name": "prompt-v2",
+                "variants": [
+                    { "name": "control", "weight": 50 },
+                    { "name": "v2", "weight": 50, "system_prompt": "Be terse." }
+                ]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let exp_id = exp["id"].as_str().unwrap().to_string();
+
+        let cmd_id = send(&app, "read DTCs").await;
+        send(&app, "bake a pizza").await;
+
+        let (status, _) = call(
+            &app,
+            "POST",
+            &format!("/api/v1/commands/{cmd_id}/respond"),
+            Some(serde_json::json!({
+                "command_id": cmd_id,
+                "correlation_id": cmd_id,
+                "device_id": "rpi-001",
+                "status": "completed",
+                "inference_tier": "local",
+                "response_text": "No DTCs",
+                "latency_ms": 12,
+                "responded_at": chrono::Utc::now()
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, json) =
+            call(&app, "GET", &format!("/api/v1/experiments/{exp_id}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let results = json["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        let total = |key: &str| -> u64 { results.iter().map(|r| r[key].as_u64().unwrap()).sum() };
+        assert_eq!(total("assigned"), 2);
+        assert_eq!(total("parsed"), 1);
+        assert_eq!(total("completed"), 1);
+    }
+
+    #[tokio::test]
+    async fn min_confidence_drops_low_confidence_parses() {
+        let app = build_router(AppState::with_sample_data());
+        call(
+            &app,
+            "POST",
+            "/api/v1/experiments",
+            Some(serde_json::json!({
+                "name": "strict",
+                "variants": [{ "name": "strict", "weight": 100, "min_confidence": 1.0 }]
+            })),
+        )
+        .await;
+
+        let cmd_id = send(&app, "read DTCs").await;
+        let (_, json) = call(&app, "GET", &format!("/api/v1/commands/{cmd_id}"), None).await;
+        assert!(json["command"]["parsed_intent"].is_null());
+    }
+
+    #[tokio::test]
+    async fn rejects_weights_not_summing_to_100() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, _) = call(
+            &app,
+            "POST",
+            "/api/v1/experiments",
+            Some(serde_json::json!({
+                "name": "bad",
+                "variants": [{ "name": "a", "weight": 60 }, { "name": "b", "weight": 30 }]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn new_experiment_stops_previous_and_stop_endpoint() {
+        let app = build_router(AppState::with_sample_data());
+        let body = |name: &str| serde_json::json!({ "name": name, "variants": [{ "name": "only", "weight": 100 }] });
+        let (_, first) = call(&app, "POST", "/api/v1/experiments", Some(body("one"))).await;
+        let (_, second) = call(&app, "POST", "/api/v1/experiments", Some(body("two"))).await;
+
+        let (_, list) = call(&app, "GET", "/api/v1/experiments", None).await;
+        let list = list.as_array().unwrap();
+        assert_eq!(list[0]["name"], "two");
+        assert_eq!(list[0]["active"], true);
+        assert_eq!(list[1]["active"], false);
+        assert_eq!(list[1]["id"], first["id"]);
+
+        let uri = format!(
+            "/api/v1/experiments/{}/stop",
+            second["id"].as_str().unwrap()
+        );
+        let (status, stopped) = call(&app, "POST", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stopped["active"], false);
+        assert!(stopped["stopped_at"].is_string());
+    }
+}
@@ -0,0 +1,183 @@
+//! Tool failure-rate endpoint.
+
+use axum::extract::{Query, State};
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::auth::Principal;
+use crate::error::ApiResult;
+use crate::failure_rates::FailureRate;
+use crate::state::AppState;
+
+/// Query parameters for the failure-rate list.
+#[derive(Debug, Deserialize)]
+pub struct FailureRateQuery {
+    pub fleet_id: Option<String>,
+    /// Device windows of this device only (resolves aliases).
+    pub device_id: Option<String>,
+    /// Only windows that are (`true`) or are not (`false`) above threshold.
+    pub alerting: Option<bool>,
+}
+
+/// GET /api/v1/failure-rates — rolling failure rate per (device, tool) and
+/// (fleet, tool), highest first, with thresholds and recent errors.
+pub async fn list_failure_rates(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<FailureRateQuery>,
+) -> ApiResult<Json<Vec<FailureRate>>> {
+    let device_id = match &query.device_id {
+        Some(reference) => Some(crate::device_identity::resolve(&state, reference).await?),
+        None => None,
+    };
+    let mut rates =
+        state
+            .failure_rates
+            .rates(query.fleet_id.as_deref(), device_id.as_deref(), Utc::now());
+    if let Some(alerting) = query.alerting {
+        rates.retain(|r| r.alerting == alerting);
+    }
+    if let Some(Extension(user)) = &principal {
+        rates.retain(|r| user.can_access_fleet(&r.fleet_id));
+    }
+    Ok(Json(rates))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::WsEvent;
+    use crate::routes::build_router;
+    use crate::state::{AppState, CommandRecord};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use chrono::Utc;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_protocol::commands::{
+        ActionKind, CommandEnvelope, CommandResponse, CommandStatus, InferenceTier, ParsedIntent,
+    };
+
+    /// Store a `read_dtcs` command for `device_id` and post its response.
+    async fn respond(state: &AppState, device_id: &str, status: CommandStatus) {
+        let mut envelope = CommandEnvelope::new("fleet-alpha", device_id, "read DTCs", "tech");
+        envelope.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "read_dtcs".into(),
+            tool_args: serde_json::json!({}),
+            confidence: 0.95,
+            steps: Vec::new(),
+        });
+        let id = envelope.id;
+        state.commands.write().await.push(CommandRecord {
+            envelope,
+            response: None,
+            status: CommandStatus::Sent,
+            created_at: Utc::now(),
+            deadline: None,
+            attempts: 0,
+            max_attempts: 1,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
+        });
+
+        let failed = status != CommandStatus::Completed;
+        let resp = CommandResponse {
+            command_id: id,
+            correlation_id: id,
+            device_id: device_id.into(),
+            status,
+            inference_tier: InferenceTier::Local,
+            response_text: None,
+            response_data: None,
+            latency_ms: 120,
+            responded_at: Utc::now(),
+            error: failed.then(|| "ECU did not respond".to_string()),
+            error_code: None,
+            cached: false,
+        };
+        let response = build_router(state.clone())
+            .oneshot(
+                Request::post(format!("/api/v1/commands/{id}/respond"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&resp).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn fleet_wide_regression_raises_alert() {
+        let state = AppState::with_sample_data();
+        let mut events = state.event_tx.subscribe();
+
+        respond(&state, "rpi-001", CommandStatus::Completed).await;
+        for _ in 0..2 {
+            respond(&state, "rpi-001", CommandStatus::Failed).await;
+            respond(&state, "rpi-002", CommandStatus::Timeout).await;
+        }
+
+        let mut anomalies = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let WsEvent::ToolFailureAnomaly {
+                scope,
+                tool_name,
+                failures,
+                samples,
+                recent_errors,
+                ..
+            } = event
+            {
+                anomalies.push((scope, tool_name, failures, samples, recent_errors));
+            }
+        }
+        // 4 of 5 across the fleet; neither device alone has 5 samples.
+        assert_eq!(anomalies.len(), 1);
+        let (scope, tool, failures, samples, errors) = &anomalies[0];
+        assert_eq!((scope.as_str(), tool.as_str()), ("fleet", "read_dtcs"));
+        assert_eq!((*failures, *samples), (4, 5));
+        assert_eq!(errors[0]["device_id"], "rpi-002");
+        assert_eq!(errors[0]["error"], "ECU did not respond");
+
+        let alerts = state.alerts.read().await;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].scope, "fleet");
+        assert_eq!(
+            alerts[0].title,
+            "read_dtcs failing 80% across fleet-alpha (4/5)"
+        );
+        drop(alerts);
+
+        let response = build_router(state.clone())
+            .oneshot(
+                Request::get("/api/v1/failure-rates?alerting=true")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let rates: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let rates = rates.as_array().unwrap();
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0]["scope"], "fleet");
+        assert_eq!(rates[0]["failure_rate"], 0.8);
+        assert!(rates[0].get("device_id").is_none());
+
+        let response = build_router(state)
+            .oneshot(
+                Request::get("/api/v1/failure-rates?device_id=rpi-002")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let rates: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(rates[0]["samples"], 2);
+        assert_eq!(rates[0]["alerting"], false);
+    }
+}
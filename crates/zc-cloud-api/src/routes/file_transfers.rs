@@ -0,0 +1,321 @@
+//! File push and fetch endpoints.
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use axum::{Extension, Json};
+use base64::Engine;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use zc_protocol::transfer::TransferDirection;
+
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::file_transfers::{FileTransfer, MAX_FILE_BYTES, TransferStatus};
+use crate::state::AppState;
+
+/// Request body for pushing a file to a device.
+#[derive(Debug, Deserialize)]
+pub struct PushFileRequest {
+    /// Absolute destination path; must be in one of the device's
+    /// `file_transfer.allowed_dirs`.
+    pub path: String,
+    /// File content, base64 (standard alphabet).
+    pub content: String,
+    /// Who pushed it (the authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub requested_by: String,
+}
+
+/// Request body for fetching a file from a device.
+#[derive(Debug, Deserialize)]
+pub struct FetchFileRequest {
+    /// Absolute path of the file on the device.
+    pub path: String,
+    /// Who fetched it (the authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub requested_by: String,
+}
+
+/// POST /api/v1/devices/:id/files — push a file to the device (202; follow
+/// progress on `/transfers/:id`).
+pub async fn push_file(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(reference): Path<String>,
+    Json(req): Json<PushFileRequest>,
+) -> ApiResult<(StatusCode, Json<FileTransfer>)> {
+    let device_id = crate::device_identity::resolve_existing(&state, &reference).await?;
+    let requested_by = requester(principal.as_deref(), req.requested_by)?;
+    let path = device_path(&req.path)?;
+    let content = base64::engine::general_purpose::STANDARD
+        .decode(req.content.trim())
+        .map_err(|e| ApiError::BadRequest(format!("content is not valid base64: {e}")))?;
+    if content.len() > MAX_FILE_BYTES {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "file is {} bytes, more than the {MAX_FILE_BYTES} allowed",
+            content.len()
+        )));
+    }
+    let fleet_id = fleet_of(&state, &device_id).await;
+    let transfer = FileTransfer::push(&device_id, &fleet_id, path, content, &requested_by);
+    let transfer = crate::file_transfers::start(&state, transfer).await;
+    Ok((StatusCode::ACCEPTED, Json(transfer)))
+}
+
+/// POST /api/v1/devices/:id/files/fetch — fetch a file from the device
+/// (202; download it from `/transfers/:id/content` once completed).
+pub async fn fetch_file(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(reference): Path<String>,
+    Json(req): Json<FetchFileRequest>,
+) -> ApiResult<(StatusCode, Json<FileTransfer>)> {
+    let device_id = crate::device_identity::resolve_existing(&state, &reference).await?;
+    let requested_by = requester(principal.as_deref(), req.requested_by)?;
+    let path = device_path(&req.path)?;
+    let fleet_id = fleet_of(&state, &device_id).await;
+    let transfer = FileTransfer::fetch(&device_id, &fleet_id, path, &requested_by);
+    let transfer = crate::file_transfers::start(&state, transfer).await;
+    Ok((StatusCode::ACCEPTED, Json(transfer)))
+}
+
+/// GET /api/v1/devices/:id/files — the device's transfers, newest first.
+pub async fn list_device_transfers(
+    State(state): State<AppState>,
+    Path(reference): Path<String>,
+) -> ApiResult<Json<Vec<FileTransfer>>> {
+    let device_id = crate::device_identity::resolve_existing(&state, &reference).await?;
+    Ok(Json(crate::file_transfers::list(&state, &device_id).await))
+}
+
+/// GET /api/v1/transfers/:id — one transfer and its progress.
+pub async fn get_transfer(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<FileTransfer>> {
+    Ok(Json(find(&state, principal.as_deref(), id).await?))
+}
+
+/// GET /api/v1/transfers/:id/content — the file of a completed fetch.
+pub async fn get_transfer_content(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Response> {
+    let transfer = find(&state, principal.as_deref(), id).await?;
+    if transfer.direction != TransferDirection::Fetch {
+        return Err(ApiError::BadRequest(
+            "only fetched files can be downloaded".into(),
+        ));
+    }
+    if transfer.status != TransferStatus::Completed {
+        return Err(ApiError::Conflict(format!(
+            "transfer {id} is not completed"
+        )));
+    }
+    let name = transfer
+        .path
+        .rsplit('/')
+        .next()
+        .unwrap_or("file")
+        .replace('"', "");
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{name}\""),
+        )
+        .body(Body::from(transfer.data))
+        .map_err(|e| ApiError::Internal(e.to_string()))
+}
+
+async fn find(
+    state: &AppState,
+    principal: Option<&Principal>,
+    id: Uuid,
+) -> ApiResult<FileTransfer> {
+    let transfer = crate::file_transfers::get(state, id)
+        .await
+        .ok_or_else(|| ApiError::NotFound(format!("transfer {id} not found")))?;
+    match principal {
+        Some(user) if !user.can_access_fleet(&transfer.fleet_id) => Err(ApiError::Forbidden(
+            format!("no access to fleet '{}'", transfer.fleet_id),
+        )),
+        _ => Ok(transfer),
+    }
+}
+
+fn requester(principal: Option<&Principal>, requested_by: String) -> ApiResult<String> {
+    let requested_by = crate::auth::actor(principal, requested_by);
+    if requested_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("requested_by is required".into()));
+    }
+    Ok(requested_by)
+}
+
+/// An absolute file path without `..`; the device checks its allowed
+/// directories itself.
+fn device_path(path: &str) -> ApiResult<&str> {
+    let path = path.trim();
+    if !path.starts_with('/') || path.ends_with('/') || path.split('/').any(|p| p == "..") {
+        return Err(ApiError::BadRequest(
+            "path must be an absolute file path without '..'".into(),
+        ));
+    }
+    Ok(path)
+}
+
+async fn fleet_of(state: &AppState, device_id: &str) -> String {
+    crate::auth::device_fleet(state, device_id)
+        .await
+        .unwrap_or_else(|| "default".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use base64::Engine;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_mqtt_channel::MockChannel;
+    use zc_protocol::transfer::{TransferChunk, TransferComplete, TransferOffer, TransferOutcome};
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, body.to_vec())
+    }
+
+    fn post(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn json(body: &[u8]) -> serde_json::Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn push_publishes_an_offer() {
+        let mqtt = Arc::new(MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+
+        let content = base64::engine::general_purpose::STANDARD.encode(b"-----BEGIN CERT");
+        let (status, body) = send(
+            &state,
+            post(
+                "/api/v1/devices/rpi-001/files",
+                serde_json::json!({
+                    "path": "/var/lib/zeroclaw/files/ca.pem",
+                    "content": content,
+                    "requested_by": "alice",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let transfer = json(&body);
+        assert_eq!(transfer["status"], "pending");
+        assert_eq!(transfer["size"], 15);
+
+        let offers = mqtt.published_to("fleet/fleet-alpha/rpi-001/transfer/offer");
+        let offer: TransferOffer = serde_json::from_slice(&offers[0].payload).unwrap();
+        assert_eq!(offer.path, "/var/lib/zeroclaw/files/ca.pem");
+        assert_eq!(offer.sha256, transfer["sha256"].as_str().map(String::from));
+
+        let (_, list) = send(&state, get("/api/v1/devices/rpi-001/files")).await;
+        assert_eq!(json(&list).as_array().unwrap().len(), 1);
+
+        for body in [
+            serde_json::json!({"path": "relative", "content": "", "requested_by": "alice"}),
+            serde_json::json!({"path": "/a/../b", "content": "", "requested_by": "alice"}),
+            serde_json::json!({"path": "/a/b", "content": "!!", "requested_by": "alice"}),
+            serde_json::json!({"path": "/a/b", "content": ""}),
+        ] {
+            let (status, _) = send(&state, post("/api/v1/devices/rpi-001/files", body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        let (status, _) = send(
+            &state,
+            post(
+                "/api/v1/devices/nope/files",
+                serde_json::json!({"path": "/a/b", "content": "", "requested_by": "alice"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn fetched_file_is_downloadable_once_complete() {
+        let state = AppState::with_sample_data();
+        let (status, body) = send(
+            &state,
+            post(
+                "/api/v1/devices/rpi-001/files/fetch",
+                serde_json::json!({"path": "/var/crash/core.1234", "requested_by": "alice"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let id: uuid::Uuid = json(&body)["id"].as_str().unwrap().parse().unwrap();
+        let content = format!("/api/v1/transfers/{id}/content");
+
+        let (status, _) = send(&state, get(&content)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let data = b"core dump";
+        let upload = serde_json::to_vec(&TransferChunk::new(id, 0, data)).unwrap();
+        crate::mqtt_bridge::handle_incoming(
+            &zc_protocol::topics::transfer_upload("fleet-alpha", "rpi-001"),
+            &upload,
+            &state,
+        )
+        .await;
+        let complete = TransferComplete {
+            transfer_id: id,
+            device_id: "rpi-001".into(),
+            outcome: TransferOutcome::Completed,
+            size: data.len() as u64,
+            sha256: Some(crate::file_transfers::sha256_hex(data)),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        };
+        crate::mqtt_bridge::handle_incoming(
+            &zc_protocol::topics::transfer_complete("fleet-alpha", "rpi-001"),
+            &serde_json::to_vec(&complete).unwrap(),
+            &state,
+        )
+        .await;
+
+        let (_, transfer) = send(&state, get(&format!("/api/v1/transfers/{id}"))).await;
+        assert_eq!(json(&transfer)["status"], "completed");
+        let (status, body) = send(&state, get(&content)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, data);
+
+        let (status, _) = send(
+            &state,
+            get(&format!("/api/v1/transfers/{}", uuid::Uuid::now_v7())),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
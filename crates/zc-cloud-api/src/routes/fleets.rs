@@ -0,0 +1,979 @@
+//! Fleet registry, fleet API tokens, fleet-wide command broadcast and
+//! fleet summary.
+//!
+//! Registering and deleting fleets and managing their tokens is for
+//! super-admins (see [`crate::fleets`]); everyone else sees the registered
+//! fleets among their tenants.
+//!
+//! One natural-language command is parsed once and fanned out to every
+//! device in the fleet. Each device gets its own command record (ID from
+//! [`CommandEnvelope::for_device`], `correlation_id` = broadcast ID), so
+//! responses, cancellation and the offline queue work per device as for
+//! single commands. Reachable devices receive the command through a single
+//! publish on the fleet broadcast topic; offline devices are queued and get
+//! their copy on the next heartbeat.
+//!
+//! With `tags`, only fleet devices carrying every tag are targeted. Since
+//! the fleet topic reaches all agents, a tag-targeted broadcast is published
+//! to each selected device's own command topic instead.
+//!
+//! Broadcasts that need two-person approval (shell commands, high-risk
+//! tools) are stored as `awaiting_approval` for every device and published
+//! nothing; approving the broadcast ID releases each device's copy.
+
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use zc_protocol::commands::{CommandEnvelope, CommandStatus};
+
+use crate::alerts::AlertCounts;
+use crate::audit::{AuditAction, AuditEntry};
+use crate::auth::{Principal, Role};
+use crate::command_queue;
+use crate::device_tags;
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::fleets::{Fleet, FleetToken, IssuedToken};
+use crate::routes::commands::store_command;
+use crate::state::AppState;
+use crate::structured_mode;
+
+/// Request body for a fleet broadcast.
+#[derive(Debug, Deserialize)]
+pub struct BroadcastCommandRequest {
+    /// Natural-language command text. Optional when `tool_name` is set.
+    #[serde(default)]
+    pub command: String,
+    /// Tool to run as-is, skipping inference. Required for fleets in
+    /// structured-only mode.
+    pub tool_name: Option<String>,
+    /// Arguments for `tool_name` (a JSON object; defaults to `{}`).
+    pub tool_args: Option<serde_json::Value>,
+    /// Who is sending this command (the authenticated user, when OIDC is on).
+    pub initiated_by: String,
+    /// `key:value` tags a device must all carry to be targeted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// One device's share of a broadcast.
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastTarget {
+    pub device_id: String,
+    pub command_id: Uuid,
+    pub status: CommandStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A broadcast and the state of each device's command.
+#[derive(Debug, Serialize)]
+pub struct BroadcastSummary {
+    pub broadcast_id: Uuid,
+    pub fleet_id: String,
+    pub command: String,
+    pub created_at: DateTime<Utc>,
+    /// Device count per command status.
+    pub counts: BTreeMap<String, usize>,
+    pub devices: Vec<BroadcastTarget>,
+}
+
+/// POST /api/v1/fleets/{fleet_id}/commands — send one command to every
+/// device in the fleet.
+pub async fn broadcast_command(
+    State(state): State<AppState>,
+    Path(fleet_id): Path<String>,
+    principal: Option<Extension<Principal>>,
+    Json(mut req): Json<BroadcastCommandRequest>,
+) -> ApiResult<Json<BroadcastSummary>> {
+    req.initiated_by = crate::auth::actor(principal.as_deref(), req.initiated_by);
+    let structured = structured_mode::intent(req.tool_name.take(), req.tool_args.take())?;
+    state
+        .structured_mode
+        .check([fleet_id.as_str()], structured.is_some())?;
+    if req.command.trim().is_empty()
+        && let Some(parsed) = &structured
+    {
+        req.command = structured_mode::describe(&parsed.intent);
+    }
+    let selectors = req
+        .tags
+        .iter()
+        .map(|s| device_tags::parse_selector(s))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::BadRequest)?;
+    let mut devices = fleet_devices(&state, &fleet_id).await?;
+    if !selectors.is_empty() {
+        let tagged = device_tags::devices_matching(&state, &selectors).await?;
+        devices.retain(|(device_id, _)| tagged.contains(device_id));
+    }
+    if devices.is_empty() {
+        return Err(ApiError::NotFound(if selectors.is_empty() {
+            format!("no devices in fleet '{fleet_id}'")
+        } else {
+            format!(
+                "no devices in fleet '{fleet_id}' match tags {}",
+                req.tags.join(", ")
+            )
+        }));
+    }
+
+    let mut broadcast = CommandEnvelope::broadcast(&fleet_id, &req.command, &req.initiated_by);
+    let parse_result = match structured {
+        Some(parsed) => Some(parsed),
+        None => state.inference.parse(&req.command).await,
+    };
+    broadcast.parsed_intent = parse_result.as_ref().map(|r| r.intent.clone());
+    let inference_tier = parse_result.as_ref().map(|r| r.tier.clone());
+    state.metrics.inference(inference_tier.as_deref());
+
+    let approval = state
+        .approval
+        .reason(broadcast.parsed_intent.as_ref(), true);
+
+    let mut targets = Vec::with_capacity(devices.len());
+    let mut direct = Vec::new();
+    for (device_id, reachable) in &devices {
+        let envelope = broadcast.for_device(device_id);
+        if *reachable {
+            direct.push(envelope.clone());
+        }
+        let status = if approval.is_some() {
+            CommandStatus::AwaitingApproval
+        } else if *reachable {
+            CommandStatus::Pending
+        } else {
+            CommandStatus::Queued
+        };
+        store_command(&state, &envelope, status, inference_tier.clone(), None).await?;
+
+        let _ = state.event_tx.send(WsEvent::CommandDispatched {
+            command_id: envelope.id,
+            device_id: envelope.device_id.clone(),
+            command: envelope.natural_language.clone(),
+            initiated_by: envelope.initiated_by.clone(),
+            created_at: envelope.created_at,
+        });
+        if let Some(reason) = &approval {
+            crate::approval::request(&state, &envelope, reason).await?;
+        } else if !reachable {
+            let _ = state.event_tx.send(WsEvent::CommandQueued {
+                command_id: envelope.id,
+                device_id: envelope.device_id.clone(),
+                queued_at: Utc::now(),
+            });
+        }
+
+        targets.push(BroadcastTarget {
+            device_id: envelope.device_id,
+            command_id: envelope.id,
+            status,
+            response_text: None,
+            error: None,
+        });
+    }
+
+    let reachable = devices.iter().filter(|(_, r)| *r).count();
+    tracing::info!(
+        broadcast_id = %broadcast.id,
+        fleet_id = %fleet_id,
+        tags = ?req.tags,
+        devices = devices.len(),
+        queued = devices.len() - reachable,
+        "fleet command broadcast"
+    );
+
+    if approval.is_some() {
+        return Ok(Json(summarize(&broadcast, targets)));
+    }
+
+    if let Some(mqtt) = &state.mqtt {
+        // Agents derive their own command ID from the broadcast envelope, so
+        // if the broadcast cannot be published the copies go one by one.
+        let per_device = if !selectors.is_empty() {
+            true
+        } else if reachable > 0
+            && let Err(e) = command_queue::publish_broadcast(mqtt.as_ref(), &broadcast).await
+        {
+            tracing::error!(error = %e, broadcast_id = %broadcast.id, "failed to publish broadcast to mqtt, publishing per device");
+            true
+        } else {
+            false
+        };
+        if per_device {
+            for envelope in &direct {
+                crate::command_retries::publish(&state, envelope).await;
+            }
+        } else {
+            let now = Utc::now();
+            for envelope in &direct {
+                crate::command_timing::published(&state, envelope.id, now).await;
+            }
+        }
+    }
+
+    Ok(Json(summarize(&broadcast, targets)))
+}
+
+/// GET /api/v1/fleets/{fleet_id}/commands/{broadcast_id} — per-device
+/// progress of a broadcast.
+pub async fn get_broadcast(
+    State(state): State<AppState>,
+    Path((fleet_id, broadcast_id)): Path<(String, Uuid)>,
+) -> ApiResult<Json<BroadcastSummary>> {
+    let not_found = || ApiError::NotFound(format!("broadcast '{broadcast_id}' not found"));
+
+    let (envelope, outcomes) = state
+        .store
+        .broadcast_outcomes(broadcast_id, &fleet_id)
+        .await?
+        .ok_or_else(not_found)?;
+    let targets = outcomes
+        .into_iter()
+        .map(|o| BroadcastTarget {
+            device_id: o.device_id,
+            command_id: o.command_id,
+            status: o.status,
+            response_text: o.response_text,
+            error: o.error,
+        })
+        .collect();
+
+    Ok(Json(summarize(&envelope, targets)))
+}
+
+fn summarize(envelope: &CommandEnvelope, devices: Vec<BroadcastTarget>) -> BroadcastSummary {
+    let mut counts = BTreeMap::new();
+    for target in &devices {
+        let status = serde_json::to_value(target.status)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+        *counts.entry(status).or_insert(0) += 1;
+    }
+    BroadcastSummary {
+        broadcast_id: envelope.correlation_id,
+        fleet_id: envelope.fleet_id.clone(),
+        command: envelope.natural_language.clone(),
+        created_at: envelope.created_at,
+        counts,
+        devices,
+    }
+}
+
+/// Device and unresolved-alert counts for one fleet.
+#[derive(Debug, Serialize)]
+pub struct FleetSummary {
+    pub fleet_id: String,
+    pub devices: u64,
+    /// Devices that would receive a command now.
+    pub reachable: u64,
+    pub alerts: AlertCounts,
+}
+
+/// GET /api/v1/fleets/:fleet_id/summary — device counts and unresolved
+/// alerts by state.
+pub async fn get_fleet_summary(
+    State(state): State<AppState>,
+    Path(fleet_id): Path<String>,
+) -> ApiResult<Json<FleetSummary>> {
+    let (members, alerts) = tokio::try_join!(
+        fleet_devices(&state, &fleet_id),
+        crate::alerts::counts(&state, &fleet_id),
+    )?;
+    Ok(Json(FleetSummary {
+        devices: members.len() as u64,
+        reachable: members.iter().filter(|(_, reachable)| *reachable).count() as u64,
+        fleet_id,
+        alerts,
+    }))
+}
+
+/// Devices registered to a fleet with whether each is reachable now.
+pub(crate) async fn fleet_devices(
+    state: &AppState,
+    fleet_id: &str,
+) -> ApiResult<Vec<(String, bool)>> {
+    let devices = state.store.fleet_devices(fleet_id).await?;
+    Ok(devices
+        .into_iter()
+        .map(|d| {
+            let reachable = command_queue::is_reachable(&d.status, d.last_heartbeat);
+            (d.device_id, reachable)
+        })
+        .collect())
+}
+
+/// Request body for registering a fleet.
+#[derive(Debug, Deserialize)]
+pub struct CreateFleetRequest {
+    /// Name devices carry in `metadata.fleet`; letters, digits, `-`, `_`.
+    pub fleet_id: String,
+    /// Display name (defaults to `fleet_id`).
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Owner (the authenticated user, when auth is on).
+    #[serde(default)]
+    pub created_by: String,
+}
+
+/// Request body for issuing a fleet token.
+#[derive(Debug, Deserialize)]
+pub struct IssueTokenRequest {
+    /// What the token is for; recorded as the actor of its requests.
+    pub name: String,
+    /// `viewer`, `operator` or `admin`.
+    pub role: Role,
+    /// The token is refused after this many days; never expires without.
+    pub expires_in_days: Option<u32>,
+    /// Who issued it (the authenticated user, when auth is on).
+    #[serde(default)]
+    pub created_by: String,
+}
+
+/// POST /api/v1/fleets — register a fleet (super-admin; 409 if taken).
+pub async fn create_fleet(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(req): Json<CreateFleetRequest>,
+) -> ApiResult<(StatusCode, Json<Fleet>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    crate::fleets::validate_fleet_id(&req.fleet_id).map_err(ApiError::BadRequest)?;
+
+    let fleet = Fleet {
+        name: req
+            .name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| req.fleet_id.clone()),
+        fleet_id: req.fleet_id,
+        description: req.description.filter(|d| !d.trim().is_empty()),
+        created_by,
+        created_at: Utc::now(),
+    };
+    crate::fleets::create(&state, &fleet).await?;
+    crate::audit::record(
+        &state,
+        AuditEntry::fleet(
+            &fleet.created_by,
+            AuditAction::FleetCreated,
+            &fleet.fleet_id,
+            serde_json::json!({"name": fleet.name}),
+        ),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(fleet)))
+}
+
+/// GET /api/v1/fleets — registered fleets the caller may access, by ID.
+pub async fn list_fleets(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> ApiResult<Json<Vec<Fleet>>> {
+    let tenants = principal.and_then(|Extension(p)| p.tenants);
+    Ok(Json(crate::fleets::list(&state, tenants.as_deref()).await?))
+}
+
+/// GET /api/v1/fleets/:fleet_id — one registered fleet.
+pub async fn get_fleet(
+    State(state): State<AppState>,
+    Path(fleet_id): Path<String>,
+) -> ApiResult<Json<Fleet>> {
+    Ok(Json(crate::fleets::get(&state, &fleet_id).await?))
+}
+
+/// DELETE /api/v1/fleets/:fleet_id — delete an empty fleet and its tokens
+/// (super-admin; 409 while it has devices).
+pub async fn delete_fleet(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(fleet_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    crate::fleets::delete(&state, &fleet_id).await?;
+    let actor = crate::auth::actor(principal.as_deref(), "operator".into());
+    crate::audit::record(
+        &state,
+        AuditEntry::fleet(
+            actor,
+            AuditAction::FleetDeleted,
+            fleet_id,
+            serde_json::json!({}),
+        ),
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/v1/fleets/:fleet_id/tokens — issue a fleet token
+/// (super-admin). The secret is only in this response.
+pub async fn issue_token(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(fleet_id): Path<String>,
+    Json(req): Json<IssueTokenRequest>,
+) -> ApiResult<(StatusCode, Json<IssuedToken>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("name is required".into()));
+    }
+    let expires_at = req
+        .expires_in_days
+        .map(|days| Utc::now() + chrono::Duration::days(days.into()));
+
+    let issued = crate::fleets::issue_token(
+        &state,
+        &fleet_id,
+        name.to_string(),
+        req.role,
+        expires_at,
+        created_by,
+    )
+    .await?;
+    crate::audit::record(
+        &state,
+        AuditEntry::fleet(
+            &issued.token.created_by,
+            AuditAction::FleetTokenIssued,
+            &fleet_id,
+            serde_json::json!({
+                "token_id": issued.token.id,
+                "name": issued.token.name,
+                "role": issued.token.role,
+                "expires_at": issued.token.expires_at,
+            }),
+        ),
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// GET /api/v1/fleets/:fleet_id/tokens — a fleet's tokens, without their
+/// secrets (super-admin).
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    Path(fleet_id): Path<String>,
+) -> ApiResult<Json<Vec<FleetToken>>> {
+    Ok(Json(crate::fleets::tokens(&state, &fleet_id).await?))
+}
+
+/// DELETE /api/v1/fleets/:fleet_id/tokens/:token_id — revoke a fleet token
+/// (super-admin).
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path((fleet_id, token_id)): Path<(String, Uuid)>,
+) -> ApiResult<StatusCode> {
+    crate::fleets::revoke_token(&state, &fleet_id, token_id).await?;
+    let actor = crate::auth::actor(principal.as_deref(), "operator".into());
+    crate::audit::record(
+        &state,
+        AuditEntry::fleet(
+            actor,
+            AuditAction::FleetTokenRevoked,
+            fleet_id,
+            serde_json::json!({"token_id": token_id}),
+        ),
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::build_router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use zc_mqtt_channel::MockChannel;
+    use zc_protocol::commands::{CommandResponse, InferenceTier};
+    use zc_protocol::device::DeviceStatus;
+
+    async fn send(app: axum::Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn broadcast_request(fleet_id: &str) -> Request<Body> {
+        Request::post(format!("/api/v1/fleets/{fleet_id}/commands"))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"command": "read DTCs", "initiated_by": "admin"}"#,
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn broadcast_fans_out_and_publishes_once() {
+        let mut state = AppState::with_sample_data();
+        let mock = Arc::new(MockChannel::new());
+        state.mqtt = Some(mock.clone());
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .status = DeviceStatus::Offline;
+        let app = build_router(state.clone());
+
+        let (status, body) = send(app, broadcast_request("fleet-alpha")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["devices"].as_array().unwrap().len(), 2);
+        assert_eq!(body["counts"]["pending"], 1);
+        assert_eq!(body["counts"]["queued"], 1);
+
+        let published = mock.published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(
+            published[0].topic,
+            zc_protocol::topics::broadcast_command("fleet-alpha")
+        );
+        let sent: CommandEnvelope = serde_json::from_slice(&published[0].payload).unwrap();
+        assert!(sent.is_broadcast());
+        assert!(sent.parsed_intent.is_some());
+
+        // The agent's derived ID matches the cloud record.
+        let broadcast_id: Uuid = body["broadcast_id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(sent.id, broadcast_id);
+        let commands = state.commands.read().await;
+        let rpi1 = commands
+            .iter()
+            .find(|r| r.envelope.device_id == "rpi-001")
+            .unwrap();
+        assert_eq!(rpi1.envelope.id, sent.for_device("rpi-001").id);
+        assert_eq!(rpi1.envelope.correlation_id, broadcast_id);
+    }
+
+    #[tokio::test]
+    async fn fleet_shell_broadcast_needs_approval() {
+        let mut state = AppState::with_sample_data();
+        let mock = Arc::new(MockChannel::new());
+        state.mqtt = Some(mock.clone());
+        state.approval = Arc::new(crate::approval::ApprovalPolicy {
+            approvers: vec!["lead".into()],
+            ..Default::default()
+        });
+        let app = build_router(state.clone());
+
+        let (status, body) = send(
+            app.clone(),
+            Request::post("/api/v1/fleets/fleet-alpha/commands")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    r#"{"command": "run dmesg", "initiated_by": "tech"}"#,
+                ))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["counts"]["awaiting_approval"], 2);
+        assert!(mock.published().is_empty());
+
+        let broadcast_id = body["broadcast_id"].as_str().unwrap();
+        let (status, body) = send(
+            app,
+            Request::post(format!("/api/v1/commands/{broadcast_id}/approve"))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"approved_by": "lead"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["commands"].as_array().unwrap().len(), 2);
+        // Released copies go to each device's own topic.
+        assert_eq!(mock.published().len(), 2);
+        assert!(
+            state
+                .commands
+                .read()
+                .await
+                .iter()
+                .all(|r| r.status == CommandStatus::Pending)
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcast_to_unknown_fleet_is_404() {
+        let app = build_router(AppState::with_sample_data());
+        let (status, _) = send(app, broadcast_request("fleet-zeta")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn structured_only_fleet_broadcasts_explicit_tools() {
+        let mut state = AppState::with_sample_data();
+        state.structured_mode = Arc::new(crate::structured_mode::StructuredMode::new(
+            crate::mqtt_bridge::FleetFilter::parse("*").unwrap(),
+        ));
+        let app = build_router(state.clone());
+
+        let (status, _) = send(app.clone(), broadcast_request("fleet-alpha")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(state.commands.read().await.is_empty());
+
+        let request = Request::post("/api/v1/fleets/fleet-alpha/commands")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"tool_name": "read_dtcs", "initiated_by": "admin"}"#,
+            ))
+            .unwrap();
+        let (status, body) = send(app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["command"], "read_dtcs");
+        let commands = state.commands.read().await;
+        assert_eq!(commands.len(), 2);
+        let intent = commands[0].envelope.parsed_intent.as_ref().unwrap();
+        assert_eq!(intent.tool_name, "read_dtcs");
+    }
+
+    #[tokio::test]
+    async fn responses_aggregate_under_broadcast_id() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        let (_, body) = send(app.clone(), broadcast_request("fleet-alpha")).await;
+        let broadcast_id = body["broadcast_id"].as_str().unwrap().to_string();
+        let rpi1: Uuid = body["devices"][0]["command_id"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let response = CommandResponse {
+            command_id: rpi1,
+            correlation_id: broadcast_id.parse().unwrap(),
+            device_id: "rpi-001".into(),
+            status: CommandStatus::Completed,
+            inference_tier: InferenceTier::Local,
+            response_text: Some("No DTCs found".into()),
+            response_data: None,
+            latency_ms: 12,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        };
+        let (status, _) = send(
+            app.clone(),
+            Request::post(format!("/api/v1/commands/{rpi1}/respond"))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&response).unwrap()))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(
+            app,
+            Request::get(format!(
+                "/api/v1/fleets/fleet-alpha/commands/{broadcast_id}"
+            ))
+            .body(Body::empty())
+            .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["command"], "read DTCs");
+        assert_eq!(body["counts"]["completed"], 1);
+        assert_eq!(body["counts"]["pending"], 1);
+        assert_eq!(body["devices"][0]["device_id"], "rpi-001");
+        assert_eq!(body["devices"][0]["response_text"], "No DTCs found");
+    }
+
+    #[tokio::test]
+    async fn tagged_broadcast_targets_matching_devices_directly() {
+        let mut state = AppState::with_sample_data();
+        let mock = Arc::new(MockChannel::new());
+        state.mqtt = Some(mock.clone());
+        let canary = [("ring".to_string(), "canary".to_string())].into();
+        crate::device_tags::set_tags(&state, "rpi-002", &canary)
+            .await
+            .unwrap();
+        let app = build_router(state);
+
+        let request = |tags: &str| {
+            Request::post("/api/v1/fleets/fleet-alpha/commands")
+                .header("content-type", "application/json")
+                .body(Body::from(format!(
+                    r#"{{"command": "read DTCs", "initiated_by": "admin", "tags": ["{tags}"]}}"#
+                )))
+                .unwrap()
+        };
+
+        let (status, body) = send(app.clone(), request("ring:canary")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["devices"].as_array().unwrap().len(), 1);
+        assert_eq!(body["devices"][0]["device_id"], "rpi-002");
+
+        let published = mock.published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(
+            published[0].topic,
+            zc_protocol::topics::command_request("fleet-alpha", "rpi-002")
+        );
+        let sent: CommandEnvelope = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(sent.id.to_string(), body["devices"][0]["command_id"]);
+
+        let (status, _) = send(app, request("ring:stable")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn summary_counts_devices_and_unresolved_alerts() {
+        let state = AppState::with_sample_data();
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .status = DeviceStatus::Offline;
+        for (device_id, tool) in [("rpi-001", "read_dtcs"), ("rpi-002", "read_pid")] {
+            crate::alerts::raise(
+                &state,
+                crate::alerts::NewAlert {
+                    kind: crate::alerts::KIND_TOOL_FAILURE_RATE.into(),
+                    fleet_id: "fleet-alpha".into(),
+                    device_id: device_id.into(),
+                    scope: "device".into(),
+                    title: format!("{tool} failing"),
+                    detail: serde_json::json!({}),
+                    dedup_key: format!("{device_id}:{tool}"),
+                },
+            )
+            .await;
+        }
+        let id = state.alerts.read().await[0].id;
+        crate::alerts::transition(
+            &state,
+            id,
+            crate::alerts::Transition::Acknowledge,
+            "alice",
+            None,
+            |_| Ok(()),
+        )
+        .await
+        .unwrap();
+
+        let (status, body) = send(
+            build_router(state),
+            Request::get("/api/v1/fleets/fleet-alpha/summary")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            (body["devices"].as_u64(), body["reachable"].as_u64()),
+            (Some(2), Some(1))
+        );
+        assert_eq!(
+            body["alerts"],
+            serde_json::json!({"open": 1, "acknowledged": 1, "snoozed": 0, "unresolved": 2})
+        );
+    }
+
+    fn authed(method: &str, uri: &str, token: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fleet_tokens_stay_inside_their_fleet() {
+        let mut state = AppState::with_sample_data();
+        state.super_admin_token_sha256 = Some(crate::fleets::token_sha256("root-secret"));
+        let app = build_router(state);
+        let root = "root-secret";
+        let none = serde_json::Value::Null;
+
+        let (status, _) = send(
+            app.clone(),
+            Request::get("/api/v1/devices").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        for fleet_id in ["fleet-alpha", "fleet-beta"] {
+            let (status, body) = send(
+                app.clone(),
+                authed(
+                    "POST",
+                    "/api/v1/fleets",
+                    root,
+                    serde_json::json!({"fleet_id": fleet_id}),
+                ),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+            assert_eq!(body["created_by"], "super-admin");
+        }
+        let (status, body) = send(
+            app.clone(),
+            authed(
+                "POST",
+                "/api/v1/fleets/fleet-alpha/tokens",
+                root,
+                serde_json::json!({"name": "ci", "role": "admin"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let token = body["secret"].as_str().unwrap().to_string();
+        let token_id = body["id"].as_str().unwrap().to_string();
+        assert!(body.get("sha256").is_none());
+
+        // Managing fleets stays with the super-admin, even for fleet admins.
+        for (method, uri) in [
+            ("POST", "/api/v1/fleets"),
+            ("GET", "/api/v1/fleets/fleet-alpha/tokens"),
+            ("DELETE", "/api/v1/fleets/fleet-alpha"),
+        ] {
+            let (status, _) = send(
+                app.clone(),
+                authed(method, uri, &token, serde_json::json!({"fleet_id": "x"})),
+            )
+            .await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{method} {uri}");
+        }
+
+        let (status, body) = send(
+            app.clone(),
+            authed("GET", "/api/v1/fleets", &token, none.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let (status, body) = send(
+            app.clone(),
+            authed("GET", "/api/v1/devices", &token, none.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let devices: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["device_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(devices, ["rpi-001", "rpi-002"]);
+
+        // sbc-010 is in fleet-beta: not readable, commandable or listed.
+        let (status, _) = send(
+            app.clone(),
+            authed("GET", "/api/v1/devices/sbc-010", &token, none.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let command = |device_id: &str| {
+            serde_json::json!({
+                "device_id": device_id,
+                "fleet_id": "fleet-alpha",
+                "command": "read DTCs",
+                "initiated_by": "someone",
+            })
+        };
+        let (status, _) = send(
+            app.clone(),
+            authed("POST", "/api/v1/commands", &token, command("sbc-010")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(
+            app.clone(),
+            authed(
+                "POST",
+                "/api/v1/commands",
+                root,
+                serde_json::json!({
+                    "device_id": "sbc-010",
+                    "fleet_id": "fleet-beta",
+                    "command": "read DTCs",
+                    "initiated_by": "root",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let beta_command = body["id"].as_str().unwrap().to_string();
+        let (status, _) = send(
+            app.clone(),
+            authed(
+                "GET",
+                &format!("/api/v1/commands/{beta_command}"),
+                &token,
+                none.clone(),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = send(
+            app.clone(),
+            authed("POST", "/api/v1/commands", &token, command("rpi-001")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["initiated_by"], "token:ci");
+        let (_, body) = send(
+            app.clone(),
+            authed("GET", "/api/v1/commands", &token, none.clone()),
+        )
+        .await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["device_id"], "rpi-001");
+
+        // Once fleets are registered, devices go into registered ones.
+        let (status, _) = send(
+            app.clone(),
+            authed(
+                "POST",
+                "/api/v1/devices",
+                root,
+                serde_json::json!({
+                    "device_id": "rpi-100",
+                    "fleet_id": "fleet-gamma",
+                    "hardware_type": "raspberry_pi4",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(
+            app.clone(),
+            authed(
+                "DELETE",
+                &format!("/api/v1/fleets/fleet-alpha/tokens/{token_id}"),
+                root,
+                none.clone(),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(app, authed("GET", "/api/v1/devices", &token, none)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
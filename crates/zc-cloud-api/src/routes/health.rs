@@ -1,12 +1,33 @@
 //! Health check endpoint.
 
 use axum::Json;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
 use serde_json::{Value, json};
 
-/// GET /health — liveness check.
-pub async fn health() -> Json<Value> {
+use crate::state::AppState;
+
+/// GET /health — liveness check. With the MQTT bridge enabled, includes
+/// its connection health; `status` is `degraded` while it is disconnected.
+pub async fn health(State(state): State<AppState>) -> Json<Value> {
+    let Some(mqtt) = &state.mqtt_health else {
+        return Json(json!({
+            "status": "ok",
+            "version": env!("CARGO_PKG_VERSION"),
+        }));
+    };
     Json(json!({
-        "status": "ok",
+        "status": if mqtt.is_connected() { "ok" } else { "degraded" },
         "version": env!("CARGO_PKG_VERSION"),
+        "mqtt": mqtt.snapshot(),
     }))
 }
+
+/// GET /metrics — Prometheus text exposition (see [`crate::metrics`]).
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
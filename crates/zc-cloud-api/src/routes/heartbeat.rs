@@ -4,30 +4,44 @@ use axum::Json;
 use axum::extract::State;
 use chrono::Utc;
 
-use crate::error::{ApiError, ApiResult};
+use crate::error::ApiResult;
 use crate::events::WsEvent;
 use crate::state::AppState;
 use zc_protocol::device::Heartbeat;
 
 /// POST /api/v1/heartbeat — ingest a device heartbeat.
+#[utoipa::path(
+    post,
+    path = "/api/v1/heartbeat",
+    tag = "heartbeat",
+    request_body = Heartbeat,
+    responses((status = 200, description = "`{status: \"ok\"}`", body = Object)),
+    security(())
+)]
 pub async fn ingest_heartbeat(
     State(state): State<AppState>,
     Json(hb): Json<Heartbeat>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    // Update last_heartbeat in the database
-    if let Some(pool) = &state.pool {
-        crate::db::devices::update_heartbeat(pool, &hb.device_id, hb.timestamp)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-    } else {
-        // In-memory: update device heartbeat timestamp
-        let mut devices = state.devices.write().await;
-        if let Some(device) = devices.get_mut(&hb.device_id) {
-            device.last_heartbeat = Some(hb.timestamp);
-        }
-    }
+    let received_at = Utc::now();
+    let previous = state
+        .store
+        .record_heartbeat(
+            &hb.device_id,
+            hb.timestamp,
+            hb.cert_expires_at,
+            hb.vitals.as_ref(),
+        )
+        .await?;
 
     tracing::debug!(device_id = %hb.device_id, "heartbeat received");
+    crate::command_timing::heartbeat_received(&state, &hb.device_id, hb.timestamp, received_at)
+        .await;
+    crate::device_status::heartbeat_received(&state, &hb.device_id, previous).await;
+    crate::command_queue::record_encodings(&state, &hb.device_id, &hb.encodings).await;
+
+    // Device is back — deliver anything queued or missed while it was offline.
+    crate::command_queue::flush(&state, &hb.device_id).await;
+    crate::shadow_reconcile::reconcile(&state, &hb.fleet_id, &hb.device_id).await;
 
     // Broadcast real-time event
     let _ = state.event_tx.send(WsEvent::DeviceHeartbeat {
@@ -63,6 +77,10 @@ mod tests {
             can_status: ServiceStatus::Running,
             agent_version: "0.1.0".into(),
             machine_id: None,
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
             timestamp: Utc::now(),
         };
 
@@ -82,6 +100,54 @@ mod tests {
         assert_eq!(json["status"], "ok");
     }
 
+    #[tokio::test]
+    async fn heartbeat_vitals_show_in_device_detail() {
+        let app = app();
+        let post = |body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::post("/api/v1/heartbeat")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let heartbeat = serde_json::json!({
+            "device_id": "rpi-001",
+            "fleet_id": "fleet-alpha",
+            "status": "online",
+            "uptime_secs": 60,
+            "ollama_status": "running",
+            "can_status": "running",
+            "agent_version": "0.2.0",
+            "version": 2,
+            "vitals": {"cpu_load": 0.5, "disk_free_bytes": 1_000_000_000u64, "rssi_dbm": -61},
+            "timestamp": Utc::now(),
+        });
+        let response = post(heartbeat.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A version 1 heartbeat has no vitals and leaves the last ones.
+        let mut legacy = heartbeat;
+        legacy.as_object_mut().unwrap().remove("vitals");
+        legacy.as_object_mut().unwrap().remove("version");
+        post(legacy).await.unwrap();
+
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/devices/rpi-001")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json["vitals"],
+            serde_json::json!({"cpu_load": 0.5, "disk_free_bytes": 1_000_000_000u64, "rssi_dbm": -61})
+        );
+    }
+
     #[tokio::test]
     async fn heartbeat_broadcasts_event() {
         let state = AppState::with_sample_data();
@@ -97,6 +163,10 @@ mod tests {
             can_status: ServiceStatus::Stopped,
             agent_version: "0.1.0".into(),
             machine_id: None,
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
             timestamp: Utc::now(),
         };
 
@@ -114,4 +184,72 @@ mod tests {
         assert!(json.contains("device_heartbeat"));
         assert!(json.contains("rpi-001"));
     }
+
+    #[tokio::test]
+    async fn heartbeat_flushes_queued_commands() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        state
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-002")
+            .unwrap()
+            .status = zc_protocol::device::DeviceStatus::Offline;
+        let app = build_router(state.clone());
+
+        // Command to the offline device is queued, not published.
+        let body = serde_json::json!({
+            "device_id": "rpi-002",
+            "fleet_id": "fleet-alpha",
+            "command": "read DTCs",
+            "initiated_by": "admin",
+        });
+        app.clone()
+            .oneshot(
+                Request::post("/api/v1/commands")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(mqtt.published().is_empty());
+
+        let heartbeat = Heartbeat {
+            device_id: "rpi-002".into(),
+            fleet_id: "fleet-alpha".into(),
+            status: zc_protocol::device::DeviceStatus::Online,
+            uptime_secs: 5,
+            ollama_status: ServiceStatus::Running,
+            can_status: ServiceStatus::Running,
+            agent_version: "0.1.0".into(),
+            machine_id: None,
+            cert_expires_at: None,
+            encodings: vec![],
+            version: zc_protocol::device::HEARTBEAT_VERSION,
+            vitals: None,
+            timestamp: Utc::now(),
+        };
+        app.oneshot(
+            Request::post("/api/v1/heartbeat")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&heartbeat).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mqtt.published_to("fleet/fleet-alpha/rpi-002/command/request")
+                .len(),
+            1
+        );
+        let commands = state.commands.read().await;
+        assert_eq!(
+            commands[0].status,
+            zc_protocol::commands::CommandStatus::Pending
+        );
+    }
 }
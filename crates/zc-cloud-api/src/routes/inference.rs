@@ -0,0 +1,45 @@
+//! Inference usage endpoint.
+
+use axum::Json;
+use axum::extract::State;
+
+use crate::inference::stats::InferenceStats;
+use crate::state::AppState;
+
+/// GET /api/v1/inference/stats — requests, hits, tokens and estimated cost
+/// per inference tier since startup, with cache counters.
+///
+/// Engines that do not track usage (the rule engine alone) report no tiers.
+pub async fn inference_stats(State(state): State<AppState>) -> Json<InferenceStats> {
+    Json(
+        state
+            .inference
+            .stats()
+            .unwrap_or_else(|| InferenceStats::untracked(state.inference.tier_name())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn rule_engine_reports_no_tiers() {
+        let state = AppState::with_sample_data();
+        let request = Request::get("/api/v1/inference/stats")
+            .body(Body::empty())
+            .unwrap();
+        let response = build_router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats["engine"], "local");
+        assert_eq!(stats["tiers"], serde_json::json!({}));
+        assert_eq!(stats["total_cost_usd"], 0.0);
+    }
+}
@@ -1,20 +1,50 @@
 //! API route definitions and router builder.
 
+pub mod agent_releases;
+pub mod alert_rules;
+pub mod alerts;
+pub mod analytics;
+pub mod audit;
+pub mod auth;
+pub mod certificates;
+pub mod claim_codes;
 pub mod commands;
+pub mod csv;
+pub mod device_aliases;
+pub mod device_report;
+pub mod device_tags;
 pub mod devices;
+pub mod dtc_history;
+pub mod dtc_knowledge;
+pub mod experiments;
+pub mod failure_rates;
+pub mod file_transfers;
+pub mod fleets;
 pub mod health;
 pub mod heartbeat;
+pub mod inference;
+pub mod mqtt;
+pub mod pagination;
 pub mod responses;
+pub mod schedules;
+pub mod self_test;
 pub mod shadows;
 pub mod telemetry;
+pub mod templates;
+pub mod webhooks;
 pub mod ws;
 
 use axum::Router;
-use axum::routing::{get, post, put};
+use axum::extract::DefaultBodyLimit;
+use axum::http::HeaderName;
+use axum::routing::{delete, get, post, put};
 use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
+use crate::openapi::{self, ApiDoc};
 use crate::state::AppState;
 
 /// Build the Axum router with all routes and middleware.
@@ -22,7 +52,8 @@ pub fn build_router(state: AppState) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([HeaderName::from_static(pagination::TOTAL_COUNT_HEADER)]);
 
     let api = Router::new()
         // Device endpoints
@@ -31,12 +62,99 @@ pub fn build_router(state: AppState) -> Router {
             get(devices::list_devices).post(devices::provision_device),
         )
         .route("/devices/{id}", get(devices::get_device))
+        .route(
+            "/devices/{id}/certificate",
+            post(certificates::issue_certificate),
+        )
+        .route(
+            "/devices/{id}/aliases",
+            get(device_aliases::list_aliases).post(device_aliases::add_alias),
+        )
+        .route(
+            "/devices/{id}/aliases/{kind}/{value}",
+            delete(device_aliases::delete_alias),
+        )
+        .route(
+            "/devices/{id}/tags",
+            get(device_tags::get_tags).put(device_tags::put_tags),
+        )
+        .route("/devices/{id}/tags/{key}", delete(device_tags::delete_tag))
+        .route("/devices/{id}/dtcs", get(dtc_history::get_dtc_history))
+        .route(
+            "/devices/{id}/report",
+            get(device_report::get_device_report),
+        )
+        .route(
+            "/devices/{id}/files",
+            get(file_transfers::list_device_transfers)
+                .post(file_transfers::push_file)
+                .layer(DefaultBodyLimit::max(
+                    crate::file_transfers::MAX_PUSH_BODY_BYTES,
+                )),
+        )
+        .route(
+            "/devices/{id}/files/fetch",
+            post(file_transfers::fetch_file),
+        )
+        .route("/transfers/{id}", get(file_transfers::get_transfer))
+        .route(
+            "/transfers/{id}/content",
+            get(file_transfers::get_transfer_content),
+        )
+        .route(
+            "/devices/{id}/status-history",
+            get(devices::get_status_history),
+        )
+        .route(
+            "/devices/{id}/self-test",
+            get(self_test::get_self_test).post(self_test::ingest_self_test),
+        )
         // Command endpoints
         .route(
             "/commands",
             get(commands::list_commands).post(commands::send_command),
         )
         .route("/commands/{id}", get(commands::get_command))
+        .route("/commands/{id}/cancel", post(commands::cancel_command))
+        .route("/commands/{id}/approve", post(commands::approve_command))
+        .route("/commands/{id}/retry", post(commands::retry_command))
+        .route("/commands/{id}/audit", get(commands::get_command_audit))
+        // Fleet registry and tokens
+        .route(
+            "/fleets",
+            get(fleets::list_fleets).post(fleets::create_fleet),
+        )
+        .route(
+            "/fleets/{fleet_id}",
+            get(fleets::get_fleet).delete(fleets::delete_fleet),
+        )
+        .route(
+            "/fleets/{fleet_id}/tokens",
+            get(fleets::list_tokens).post(fleets::issue_token),
+        )
+        .route(
+            "/fleets/{fleet_id}/tokens/{token_id}",
+            delete(fleets::revoke_token),
+        )
+        // Device onboarding claim codes
+        .route(
+            "/claim-codes",
+            get(claim_codes::list_claim_codes).post(claim_codes::create_claim_code),
+        )
+        .route(
+            "/claim-codes/{id}",
+            get(claim_codes::get_claim_code).delete(claim_codes::revoke_claim_code),
+        )
+        // Fleet-wide broadcast
+        .route(
+            "/fleets/{fleet_id}/commands",
+            post(fleets::broadcast_command),
+        )
+        .route(
+            "/fleets/{fleet_id}/commands/{broadcast_id}",
+            get(fleets::get_broadcast),
+        )
+        .route("/fleets/{fleet_id}/summary", get(fleets::get_fleet_summary))
         // Command response ingestion
         .route("/commands/{id}/respond", post(responses::ingest_response))
         // Telemetry endpoints
@@ -44,21 +162,156 @@ pub fn build_router(state: AppState) -> Router {
             "/devices/{id}/telemetry",
             get(telemetry::get_telemetry).post(telemetry::ingest_telemetry),
         )
+        .route(
+            "/telemetry/batch",
+            post(telemetry::ingest_bulk).layer(DefaultBodyLimit::max(telemetry::MAX_BULK_BYTES)),
+        )
         // Shadow endpoints
         .route("/devices/{id}/shadows", get(shadows::list_shadows))
-        .route("/devices/{id}/shadows/{name}", get(shadows::get_shadow))
+        .route(
+            "/devices/{id}/shadows/{name}",
+            get(shadows::get_shadow).delete(shadows::delete_shadow),
+        )
         .route(
             "/devices/{id}/shadows/{name}/desired",
-            put(shadows::set_desired),
+            put(shadows::set_desired).delete(shadows::clear_desired),
+        )
+        // Inference experiments
+        .route(
+            "/experiments",
+            get(experiments::list_experiments).post(experiments::create_experiment),
+        )
+        .route("/experiments/{id}", get(experiments::get_experiment))
+        .route("/experiments/{id}/stop", post(experiments::stop_experiment))
+        // Inference usage and cost
+        .route("/inference/stats", get(inference::inference_stats))
+        // Tool failure-rate anomalies
+        .route("/failure-rates", get(failure_rates::list_failure_rates))
+        // Alert management
+        .route("/alerts", get(alerts::list_alerts))
+        .route("/alerts/{id}", get(alerts::get_alert))
+        .route("/alerts/{id}/acknowledge", post(alerts::acknowledge_alert))
+        .route("/alerts/{id}/snooze", post(alerts::snooze_alert))
+        .route("/alerts/{id}/resolve", post(alerts::resolve_alert))
+        // Fleet questions answered from stored data
+        .route("/analytics/query", get(analytics::query_analytics))
+        // Audit trail
+        .route("/audit", get(audit::list_audit))
+        // Alerting rules
+        .route(
+            "/alert-rules",
+            get(alert_rules::list_alert_rules).post(alert_rules::create_alert_rule),
+        )
+        .route(
+            "/alert-rules/{id}",
+            get(alert_rules::get_alert_rule).delete(alert_rules::delete_alert_rule),
+        )
+        .route(
+            "/alert-rules/{id}/enable",
+            post(alert_rules::enable_alert_rule),
+        )
+        .route(
+            "/alert-rules/{id}/disable",
+            post(alert_rules::disable_alert_rule),
+        )
+        // Scheduled commands
+        .route(
+            "/schedules",
+            get(schedules::list_schedules).post(schedules::create_schedule),
+        )
+        .route(
+            "/schedules/{id}",
+            get(schedules::get_schedule).delete(schedules::delete_schedule),
+        )
+        .route("/schedules/{id}/pause", post(schedules::pause_schedule))
+        .route("/schedules/{id}/resume", post(schedules::resume_schedule))
+        .route("/schedules/{id}/runs", get(schedules::list_schedule_runs))
+        // Saved multi-step diagnostics
+        .route(
+            "/templates",
+            get(templates::list_templates).post(templates::create_template),
+        )
+        .route(
+            "/templates/{id}",
+            get(templates::get_template).delete(templates::delete_template),
+        )
+        .route(
+            "/templates/{id}/dispatch",
+            post(templates::dispatch_template),
+        )
+        .route("/templates/{id}/runs", get(templates::list_template_runs))
+        .route(
+            "/templates/{id}/runs/{run_id}",
+            get(templates::get_template_run),
+        )
+        // Outbound webhook notifications
+        .route(
+            "/webhooks",
+            get(webhooks::list_webhooks).post(webhooks::create_webhook),
+        )
+        .route(
+            "/webhooks/{id}",
+            get(webhooks::get_webhook).delete(webhooks::delete_webhook),
+        )
+        .route("/webhooks/{id}/enable", post(webhooks::enable_webhook))
+        .route("/webhooks/{id}/disable", post(webhooks::disable_webhook))
+        .route(
+            "/webhooks/{id}/deliveries",
+            get(webhooks::list_webhook_deliveries),
+        )
+        // Agent releases for over-the-air updates
+        .route(
+            "/agent-releases",
+            get(agent_releases::list_agent_releases).post(agent_releases::create_agent_release),
+        )
+        .route(
+            "/agent-releases/{version}",
+            get(agent_releases::get_agent_release),
+        )
+        .route(
+            "/agent-releases/{version}/deploy",
+            post(agent_releases::deploy_agent_release),
+        )
+        // DTC knowledge base (repair hints and links)
+        .route("/admin/dtc-knowledge", get(dtc_knowledge::list_knowledge))
+        .route(
+            "/admin/dtc-knowledge/import",
+            post(dtc_knowledge::import_knowledge),
+        )
+        .route(
+            "/admin/dtc-knowledge/{code}",
+            get(dtc_knowledge::get_knowledge)
+                .put(dtc_knowledge::put_knowledge)
+                .delete(dtc_knowledge::delete_knowledge),
+        )
+        // MQTT bridge diagnostics
+        .route("/admin/mqtt/topics", get(mqtt::get_topic_stats))
+        .route("/admin/mqtt/quarantine", get(mqtt::list_quarantine))
+        .route(
+            "/admin/mqtt/quarantine/{id}",
+            get(mqtt::get_quarantined).delete(mqtt::delete_quarantined),
+        )
+        .route(
+            "/admin/mqtt/quarantine/{id}/replay",
+            post(mqtt::replay_quarantined),
         )
         // Heartbeat ingestion
         .route("/heartbeat", post(heartbeat::ingest_heartbeat))
         // WebSocket endpoint
-        .route("/ws", get(ws::ws_handler));
+        .route("/ws", get(ws::ws_handler))
+        .route("/events/schema", get(ws::event_schema))
+        // Authenticated user
+        .route("/auth/me", get(auth::me));
 
     Router::new()
         .route("/health", get(health::health))
+        .route("/metrics", get(health::metrics))
         .nest("/api/v1", api)
+        .merge(SwaggerUi::new(openapi::DOCS_PATH).url(openapi::SPEC_PATH, ApiDoc::openapi()))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::auth::require_auth,
+        ))
         .layer(TraceLayer::new_for_http())
         .layer(CompressionLayer::new())
         .layer(cors)
@@ -88,6 +341,77 @@ mod tests {
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
         assert_eq!(json["status"], "ok");
+        assert!(json.get("mqtt").is_none());
+    }
+
+    #[tokio::test]
+    async fn health_reports_mqtt_connection() {
+        let mut state = AppState::with_sample_data();
+        let monitor =
+            std::sync::Arc::new(zc_mqtt_channel::ConnectionMonitor::new(Default::default()));
+        state.mqtt_health = Some(monitor.clone());
+        let app = build_router(state);
+
+        let get = || async {
+            let response = app
+                .clone()
+                .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let json = get().await;
+        assert_eq!(json["status"], "degraded");
+        assert_eq!(json["mqtt"]["state"], "connecting");
+
+        monitor.on_connected();
+        monitor.on_error(&zc_mqtt_channel::MqttError::Connection("reset".into()));
+        monitor.on_connected();
+        let json = get().await;
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["mqtt"]["state"], "connected");
+        assert_eq!(json["mqtt"]["reconnects"], 1);
+    }
+
+    #[tokio::test]
+    async fn metrics_exposes_command_counters() {
+        let app = app();
+        let body = serde_json::json!({
+            "device_id": "rpi-001",
+            "fleet_id": "fleet-alpha",
+            "command": "read DTCs",
+            "initiated_by": "admin"
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/v1/commands")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            response.headers()["content-type"]
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain")
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("zc_commands_total{status=\"pending\"} 1"));
+        assert!(text.contains("zc_inference_total{tier="));
+        assert!(text.contains("zc_websocket_clients 0"));
     }
 
     #[tokio::test]
@@ -182,6 +506,48 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn event_schema_endpoint() {
+        let response = app()
+            .oneshot(
+                Request::get("/api/v1/events/schema")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["schema_version"], crate::events::EVENT_SCHEMA_VERSION);
+        assert!(json["$defs"]["device_heartbeat"].is_object());
+    }
+
+    #[tokio::test]
+    async fn openapi_document_and_swagger_ui() {
+        let response = app()
+            .oneshot(
+                Request::get("/api/v1/openapi.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(json["paths"]["/api/v1/devices/{id}"]["get"].is_object());
+
+        let response = app()
+            .oneshot(Request::get("/api/v1/docs/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     #[tokio::test]
     async fn list_commands_empty() {
         let response = app()
@@ -0,0 +1,59 @@
+//! Shared limit/offset paging for list endpoints.
+//!
+//! List bodies stay plain JSON arrays; the number of rows matching the
+//! filters (before paging) is returned in the `X-Total-Count` header.
+
+use axum::Json;
+use axum::http::HeaderValue;
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+/// Page size when `limit` is omitted.
+pub const DEFAULT_LIMIT: u32 = 50;
+
+/// Largest page a single request may return.
+pub const MAX_LIMIT: u32 = 500;
+
+/// Response header carrying the unpaged row count.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Effective page size for a requested `limit`.
+pub fn limit(requested: Option<u32>) -> u32 {
+    requested.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+/// Apply `offset`/`limit` to an already filtered and ordered list.
+pub fn slice<T>(items: Vec<T>, offset: u32, limit: u32) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect()
+}
+
+/// JSON array response with the total count header.
+pub fn with_total<T: Serialize>(items: Vec<T>, total: u64) -> Response {
+    let mut response = Json(items).into_response();
+    response
+        .headers_mut()
+        .insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_is_defaulted_and_clamped() {
+        assert_eq!(limit(None), DEFAULT_LIMIT);
+        assert_eq!(limit(Some(0)), 1);
+        assert_eq!(limit(Some(10_000)), MAX_LIMIT);
+    }
+
+    #[test]
+    fn slice_skips_then_takes() {
+        assert_eq!(slice(vec![1, 2, 3, 4, 5], 1, 2), vec![2, 3]);
+        assert!(slice(vec![1, 2], 5, 2).is_empty());
+    }
+}
@@ -8,13 +8,14 @@ use uuid::Uuid;
 use crate::error::{ApiError, ApiResult};
 use crate::events::WsEvent;
 use crate::state::AppState;
+use crate::store::{RespondedCommand, command_status_name};
 use zc_protocol::commands::CommandResponse;
 
 /// POST /api/v1/commands/{id}/respond — ingest a command response from a device.
 pub async fn ingest_response(
     State(state): State<AppState>,
     Path(command_id): Path<Uuid>,
-    Json(resp): Json<CommandResponse>,
+    Json(mut resp): Json<CommandResponse>,
 ) -> ApiResult<Json<serde_json::Value>> {
     // Validate that the response matches the path parameter.
     if resp.command_id != command_id {
@@ -24,46 +25,42 @@ pub async fn ingest_response(
         )));
     }
 
-    let status_str = serde_json::to_value(resp.status)
-        .ok()
-        .and_then(|v| v.as_str().map(String::from))
-        .unwrap_or_else(|| format!("{:?}", resp.status).to_lowercase());
-
+    let status_str = command_status_name(resp.status);
     let inference_tier_str = serde_json::to_value(resp.inference_tier)
         .ok()
         .and_then(|v| v.as_str().map(String::from));
 
-    if let Some(pool) = &state.pool {
-        // Verify command exists in DB.
-        let row = crate::db::commands::get_by_id(pool, command_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?
-            .ok_or_else(|| ApiError::NotFound(format!("command '{command_id}' not found")))?;
-
-        // Compute latency from dispatch to response.
-        let latency_ms = (resp.responded_at - row.created_at).num_milliseconds();
-
-        crate::db::commands::update_response(
-            pool,
-            command_id,
-            &status_str,
-            inference_tier_str.as_deref().unwrap_or("unknown"),
-            resp.response_text.as_deref(),
-            resp.response_data.as_ref(),
-            latency_ms,
-            resp.error.as_deref(),
-        )
-        .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
-    } else {
-        // In-memory mode: find and update the command record.
-        let mut commands = state.commands.write().await;
-        let record = commands
-            .iter_mut()
-            .find(|r| r.envelope.id == command_id)
-            .ok_or_else(|| ApiError::NotFound(format!("command '{command_id}' not found")))?;
-        record.response = Some(resp.clone());
-    }
+    crate::dtc_knowledge::enrich(&state, &mut resp.response_data).await;
+    let receipt = crate::command_timing::Receipt::now(&state, &resp.device_id).await;
+
+    let RespondedCommand {
+        fleet_id,
+        initiated_by,
+        tool_args,
+        intent,
+    } = state
+        .store
+        .record_response(&resp, &receipt)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("command '{command_id}' not found")))?;
+
+    crate::experiments::record_outcome(&state, command_id, resp.status).await;
+    crate::dtc_history::record(&state, &fleet_id, &resp, tool_args.as_ref()).await;
+    crate::failure_rates::record(
+        &state,
+        &fleet_id,
+        command_id,
+        &resp.device_id,
+        intent.as_ref(),
+        resp.status,
+        resp.error.as_deref(),
+    )
+    .await;
+    crate::alert_rules::command_responded(&state, &fleet_id, &resp).await;
+    crate::audit::shell_responded(&state, &initiated_by, &fleet_id, intent.as_ref(), &resp).await;
+    crate::audit::can_frame_responded(&state, &initiated_by, &fleet_id, intent.as_ref(), &resp)
+        .await;
+    state.metrics.command_status(&status_str);
 
     tracing::info!(command_id = %command_id, status = %status_str, "command response ingested");
 
@@ -76,6 +73,7 @@ pub async fn ingest_response(
         response_text: resp.response_text.clone(),
         response_data: resp.response_data.clone(),
         error: resp.error.clone(),
+        error_code: resp.error_code.map(|c| c.as_str().to_string()),
         latency_ms: Some(resp.latency_ms as i64),
         responded_at: Utc::now(),
     });
@@ -92,7 +90,7 @@ mod tests {
     use chrono::Utc;
     use http_body_util::BodyExt;
     use tower::ServiceExt;
-    use zc_protocol::commands::{CommandStatus, InferenceTier};
+    use zc_protocol::commands::{CommandStatus, ErrorCode, InferenceTier};
 
     fn app_with_command() -> (axum::Router, Uuid, AppState) {
         let state = AppState::with_sample_data();
@@ -109,6 +107,8 @@ mod tests {
             initiated_by: "admin".into(),
             created_at: Utc::now(),
             timeout_secs: 30,
+            conversation_id: None,
+            history: Vec::new(),
         };
 
         // We need to block to insert — use a sync approach via the Arc.
@@ -118,7 +118,14 @@ mod tests {
         guard.push(crate::state::CommandRecord {
             envelope,
             response: None,
+            status: CommandStatus::Pending,
             created_at: Utc::now(),
+            deadline: None,
+            attempts: 0,
+            max_attempts: 1,
+            next_attempt_at: None,
+            idempotency_key: None,
+            timing: Default::default(),
         });
         drop(guard);
 
@@ -141,6 +148,8 @@ mod tests {
             latency_ms: 42,
             responded_at: Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
         };
 
         let response = app
@@ -185,6 +194,8 @@ mod tests {
             latency_ms: 10,
             responded_at: Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
         };
 
         let response = app
@@ -217,6 +228,8 @@ mod tests {
             latency_ms: 55,
             responded_at: Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
         };
 
         app.oneshot(
@@ -235,6 +248,54 @@ mod tests {
         assert!(json.contains("Engine RPM: 850"));
     }
 
+    #[tokio::test]
+    async fn error_code_is_broadcast_and_filterable() {
+        let (_, cmd_id, state) = app_with_command();
+        let mut rx = state.event_tx.subscribe();
+        let app = build_router(state);
+
+        let resp = CommandResponse {
+            command_id: cmd_id,
+            correlation_id: cmd_id,
+            device_id: "rpi-001".into(),
+            status: CommandStatus::Failed,
+            inference_tier: InferenceTier::Local,
+            response_text: None,
+            response_data: None,
+            latency_ms: 1000,
+            responded_at: Utc::now(),
+            error: Some("Response timeout after 1000ms".into()),
+            error_code: Some(ErrorCode::CanTimeout),
+            cached: false,
+        };
+        app.clone()
+            .oneshot(
+                Request::post(format!("/api/v1/commands/{cmd_id}/respond"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&resp).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let event = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(event["error_code"], "can_timeout");
+
+        for (code, expected) in [("can_timeout", 1), ("shell_blocked", 0)] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get(format!("/api/v1/commands?error_code={code}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let page: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+            assert_eq!(page.len(), expected, "{code}");
+        }
+    }
+
     #[tokio::test]
     async fn ingest_response_id_mismatch() {
         let (app, cmd_id, _) = app_with_command();
@@ -251,6 +312,8 @@ mod tests {
             latency_ms: 10,
             responded_at: Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
         };
 
         let response = app
@@ -0,0 +1,387 @@
+//! Scheduled command endpoints.
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::Principal;
+use crate::cron::Cron;
+use crate::device_tags;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::schedules::{Schedule, ScheduleFilter};
+use crate::state::AppState;
+use crate::structured_mode;
+
+/// Request body for creating a schedule.
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: String,
+    /// Five-field cron expression or macro (`@hourly`, `@daily`, ...), UTC.
+    pub cron: String,
+    /// Target fleet; every device in it unless `device_id` is set.
+    pub fleet_id: String,
+    /// Single target device ID or alias.
+    pub device_id: Option<String>,
+    /// `key:value` tags a fleet device must all carry (fleet targets only).
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Natural-language command text. Optional when `tool_name` is set.
+    #[serde(default)]
+    pub command: String,
+    /// Tool to run as-is, skipping inference.
+    pub tool_name: Option<String>,
+    /// Arguments for `tool_name` (a JSON object; defaults to `{}`).
+    pub tool_args: Option<serde_json::Value>,
+    /// Owner, recorded as `initiated_by` on every dispatched command (the
+    /// authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub created_by: String,
+    /// Start paused when `false`.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Query parameters for the schedule list.
+#[derive(Debug, Deserialize)]
+pub struct ListSchedulesQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    pub fleet_id: Option<String>,
+    /// Device ID or alias.
+    pub device_id: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// Query parameters for a schedule's run history.
+#[derive(Debug, Deserialize)]
+pub struct ListRunsQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// POST /api/v1/schedules — create a recurring command.
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(mut req): Json<CreateScheduleRequest>,
+) -> ApiResult<(StatusCode, Json<Schedule>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    let cron = Cron::parse(&req.cron).map_err(ApiError::BadRequest)?;
+    if let Some(Extension(user)) = &principal
+        && !user.can_access_fleet(&req.fleet_id)
+    {
+        return Err(ApiError::Forbidden(format!(
+            "no access to fleet '{}'",
+            req.fleet_id
+        )));
+    }
+
+    let structured = structured_mode::intent(req.tool_name.clone(), req.tool_args.clone())?;
+    state
+        .structured_mode
+        .check([req.fleet_id.as_str()], structured.is_some())?;
+    if req.command.trim().is_empty() && structured.is_none() {
+        return Err(ApiError::BadRequest(
+            "command or tool_name is required".into(),
+        ));
+    }
+
+    let device_id = match req.device_id.take() {
+        Some(reference) => {
+            if !req.tags.is_empty() {
+                return Err(ApiError::BadRequest(
+                    "tags apply to fleet schedules only, not with device_id".into(),
+                ));
+            }
+            let device_id = crate::device_identity::resolve_existing(&state, &reference).await?;
+            if let Some(fleet) = crate::auth::device_fleet(&state, &device_id).await
+                && fleet != req.fleet_id
+            {
+                return Err(ApiError::BadRequest(format!(
+                    "device '{device_id}' belongs to fleet '{fleet}', not '{}'",
+                    req.fleet_id
+                )));
+            }
+            Some(device_id)
+        }
+        None => None,
+    };
+    for tag in &req.tags {
+        device_tags::parse_selector(tag).map_err(ApiError::BadRequest)?;
+    }
+
+    let now = Utc::now();
+    let next_run_at = cron.next_after(now);
+    if next_run_at.is_none() {
+        return Err(ApiError::BadRequest(format!(
+            "cron expression '{cron}' never fires"
+        )));
+    }
+    let schedule = Schedule {
+        id: Uuid::now_v7(),
+        name: req.name.trim().to_string(),
+        cron: cron.as_str().to_string(),
+        fleet_id: req.fleet_id,
+        device_id,
+        tags: req.tags,
+        command: req.command,
+        tool_name: req.tool_name,
+        tool_args: req.tool_args,
+        enabled: req.enabled,
+        created_by,
+        created_at: now,
+        updated_at: now,
+        next_run_at: next_run_at.filter(|_| req.enabled),
+        last_run_at: None,
+        run_count: 0,
+    };
+    crate::schedules::create(&state, &schedule).await?;
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+/// GET /api/v1/schedules — list schedules, oldest first.
+///
+/// Filtered by `fleet_id`, `device_id` and `enabled`, paged with
+/// `limit`/`offset`. The unpaged match count is in `X-Total-Count`.
+pub async fn list_schedules(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListSchedulesQuery>,
+) -> ApiResult<Response> {
+    let device_id = match &query.device_id {
+        Some(reference) => Some(crate::device_identity::resolve(&state, reference).await?),
+        None => None,
+    };
+    let filter = ScheduleFilter {
+        fleet_id: query.fleet_id,
+        device_id,
+        enabled: query.enabled,
+        fleets: principal.and_then(|Extension(user)| user.tenants),
+    };
+    let matching = crate::schedules::list(&state, &filter).await?;
+    let total = matching.len() as u64;
+    let page = pagination::slice(matching, query.offset, pagination::limit(query.limit));
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/schedules/:id — one schedule.
+pub async fn get_schedule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Schedule>> {
+    let schedule = crate::schedules::get(&state, id).await?;
+    check_access(principal.as_deref(), &schedule)?;
+    Ok(Json(schedule))
+}
+
+/// POST /api/v1/schedules/:id/pause — stop firing until resumed.
+pub async fn pause_schedule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Schedule>> {
+    set_enabled(&state, principal, id, false).await
+}
+
+/// POST /api/v1/schedules/:id/resume — fire again from the next slot on.
+pub async fn resume_schedule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Schedule>> {
+    set_enabled(&state, principal, id, true).await
+}
+
+/// DELETE /api/v1/schedules/:id — remove a schedule and its history.
+pub async fn delete_schedule(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let schedule = crate::schedules::get(&state, id).await?;
+    check_access(principal.as_deref(), &schedule)?;
+    crate::schedules::delete(&state, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/schedules/:id/runs — firings of a schedule, newest first,
+/// with the commands each created. The total is in `X-Total-Count`.
+pub async fn list_schedule_runs(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListRunsQuery>,
+) -> ApiResult<Response> {
+    let schedule = crate::schedules::get(&state, id).await?;
+    check_access(principal.as_deref(), &schedule)?;
+    let (page, total) =
+        crate::schedules::runs(&state, id, pagination::limit(query.limit), query.offset).await?;
+    Ok(pagination::with_total(page, total))
+}
+
+async fn set_enabled(
+    state: &AppState,
+    principal: Option<Extension<Principal>>,
+    id: Uuid,
+    enabled: bool,
+) -> ApiResult<Json<Schedule>> {
+    let schedule = crate::schedules::get(state, id).await?;
+    check_access(principal.as_deref(), &schedule)?;
+    Ok(Json(
+        crate::schedules::set_enabled(state, id, enabled).await?,
+    ))
+}
+
+fn check_access(principal: Option<&Principal>, schedule: &Schedule) -> ApiResult<()> {
+    match principal {
+        Some(user) if !user.can_access_fleet(&schedule.fleet_id) => Err(ApiError::Forbidden(
+            format!("no access to fleet '{}'", schedule.fleet_id),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn post(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn schedule_lifecycle() {
+        let state = AppState::with_sample_data();
+        let (status, schedule) = send(
+            &state,
+            post(
+                "/api/v1/schedules",
+                serde_json::json!({
+                    "name": "hourly log stats",
+                    "cron": "@hourly",
+                    "fleet_id": "fleet-alpha",
+                    "device_id": "rpi-001",
+                    "tool_name": "log_stats",
+                    "tool_args": {"path": "/var/log/syslog"},
+                    "created_by": "alice",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{schedule}");
+        assert_eq!(schedule["enabled"], true);
+        assert!(schedule["next_run_at"].is_string());
+        let id = schedule["id"].as_str().unwrap().to_string();
+
+        // Fire it now and check the history.
+        let now = chrono::Utc::now() + chrono::Duration::hours(2);
+        crate::schedules::tick(&state, now).await.unwrap();
+        let (status, runs) = send(&state, get(&format!("/api/v1/schedules/{id}/runs"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(runs.as_array().unwrap().len(), 1);
+        assert_eq!(runs[0]["command_ids"].as_array().unwrap().len(), 1);
+        let commands = state.commands.read().await;
+        let intent = commands[0].envelope.parsed_intent.as_ref().unwrap();
+        assert_eq!(intent.tool_name, "log_stats");
+        assert_eq!(commands[0].envelope.initiated_by, "alice");
+        drop(commands);
+
+        let (_, paused) = send(
+            &state,
+            post(
+                &format!("/api/v1/schedules/{id}/pause"),
+                serde_json::json!({}),
+            ),
+        )
+        .await;
+        assert_eq!(paused["enabled"], false);
+        assert!(paused["next_run_at"].is_null());
+        let (_, list) = send(&state, get("/api/v1/schedules?enabled=false")).await;
+        assert_eq!(list.as_array().unwrap().len(), 1);
+
+        let delete = Request::delete(format!("/api/v1/schedules/{id}"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&state, delete).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&state, get(&format!("/api/v1/schedules/{id}"))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn invalid_schedules_are_rejected() {
+        let state = AppState::with_sample_data();
+        for (body, expected) in [
+            (
+                serde_json::json!({"name": "x", "cron": "61 * * * *", "fleet_id": "fleet-alpha",
+                    "command": "read dtcs", "created_by": "alice"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "cron": "@daily", "fleet_id": "fleet-alpha",
+                    "created_by": "alice"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "cron": "@daily", "fleet_id": "fleet-beta",
+                    "device_id": "rpi-001", "command": "read dtcs", "created_by": "alice"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "cron": "@daily", "fleet_id": "fleet-alpha",
+                    "device_id": "rpi-999", "command": "read dtcs", "created_by": "alice"}),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                serde_json::json!({"name": "x", "cron": "0 0 31 2 *", "fleet_id": "fleet-alpha",
+                    "command": "read dtcs", "created_by": "alice"}),
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let (status, error) = send(&state, post("/api/v1/schedules", body.clone())).await;
+            assert_eq!(status, expected, "{body} -> {error}");
+        }
+        assert!(state.schedules.read().await.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+//! Device self-test reports — the provisioning verification record.
+//!
+//! Agents publish a report on first boot and after each `self_test`
+//! command. Reports arrive over MQTT (`selftest/report`) or this REST
+//! endpoint; both go through [`record_report`].
+
+use axum::Json;
+use axum::extract::{Path, State};
+use chrono::Utc;
+
+use zc_protocol::self_test::SelfTestReport;
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// Store a report as the device's latest verification record and notify
+/// WebSocket clients.
+pub async fn record_report(state: &AppState, report: SelfTestReport) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        let json = serde_json::to_value(&report).map_err(|e| ApiError::Internal(e.to_string()))?;
+        crate::db::self_tests::insert(
+            pool,
+            &report.device_id,
+            report.passed,
+            &json,
+            report.started_at,
+        )
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+    } else {
+        state
+            .self_tests
+            .write()
+            .await
+            .insert(report.device_id.clone(), report.clone());
+    }
+
+    let trigger = serde_json::to_value(report.trigger)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default();
+    tracing::info!(
+        device_id = %report.device_id,
+        passed = report.passed,
+        trigger = %trigger,
+        "self-test report stored"
+    );
+
+    let _ = state.event_tx.send(WsEvent::SelfTestReported {
+        failed_checks: report
+            .failed_checks()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        device_id: report.device_id,
+        trigger,
+        passed: report.passed,
+        reported_at: Utc::now(),
+    });
+    Ok(())
+}
+
+/// POST /api/v1/devices/{id}/self-test — ingest a self-test report.
+pub async fn ingest_self_test(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(report): Json<SelfTestReport>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let device_id = crate::device_identity::resolve(&state, &device_id).await?;
+    if report.device_id != device_id {
+        return Err(ApiError::BadRequest(format!(
+            "report is for device {}, not {device_id}",
+            report.device_id
+        )));
+    }
+    let passed = report.passed;
+    record_report(&state, report).await?;
+    Ok(Json(
+        serde_json::json!({ "status": "ok", "passed": passed }),
+    ))
+}
+
+/// GET /api/v1/devices/{id}/self-test — latest self-test report.
+pub async fn get_self_test(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let device_id = crate::device_identity::resolve(&state, &device_id).await?;
+    let report = if let Some(pool) = &state.pool {
+        crate::db::self_tests::latest(pool, &device_id)
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))?
+            .map(|row| row.report)
+    } else {
+        state
+            .self_tests
+            .read()
+            .await
+            .get(&device_id)
+            .and_then(|r| serde_json::to_value(r).ok())
+    };
+    report
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("no self-test report for device {device_id}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::build_router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_protocol::self_test::{CheckStatus, SelfTestCheck, SelfTestTrigger};
+
+    fn report(device_id: &str, broker: CheckStatus) -> SelfTestReport {
+        let checks = vec![
+            SelfTestCheck {
+                name: "clock".into(),
+                status: CheckStatus::Pass,
+                detail: "2026-10-16T00:00:00Z".into(),
+                duration_ms: 0,
+            },
+            SelfTestCheck {
+                name: "broker".into(),
+                status: broker,
+                detail: "broker.example.com:8883".into(),
+                duration_ms: 12,
+            },
+        ];
+        SelfTestReport {
+            device_id: device_id.into(),
+            fleet_id: "fleet-alpha".into(),
+            agent_version: "0.1.0".into(),
+            trigger: SelfTestTrigger::FirstBoot,
+            passed: SelfTestReport::all_passed(&checks),
+            checks,
+            started_at: Utc::now(),
+            duration_ms: 15,
+        }
+    }
+
+    async fn get(app: axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn post_then_get_latest_report() {
+        let state = AppState::with_sample_data();
+        let mut rx = state.event_tx.subscribe();
+        let app = build_router(state);
+
+        let (status, _) = get(app.clone(), "/api/v1/devices/rpi-001/self-test").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/v1/devices/rpi-001/self-test")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::to_vec(&report("rpi-001", CheckStatus::Fail)).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        match rx.try_recv().unwrap() {
+            WsEvent::SelfTestReported {
+                passed,
+                failed_checks,
+                trigger,
+                ..
+            } => {
+                assert!(!passed);
+                assert_eq!(failed_checks, vec!["broker"]);
+                assert_eq!(trigger, "first_boot");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+
+        let (status, body) = get(app, "/api/v1/devices/rpi-001/self-test").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["passed"], false);
+        assert_eq!(body["checks"][1]["name"], "broker");
+    }
+
+    #[tokio::test]
+    async fn post_rejects_mismatched_device() {
+        let response = build_router(AppState::with_sample_data())
+            .oneshot(
+                Request::post("/api/v1/devices/rpi-002/self-test")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::to_vec(&report("rpi-001", CheckStatus::Pass)).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,21 +1,27 @@
 //! Shadow REST endpoints for querying and setting device shadow state.
 
-use axum::Json;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
 use chrono::Utc;
-use rumqttc::QoS;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use zc_protocol::shadows::{ShadowDelta, ShadowState};
-use zc_protocol::topics;
+use zc_protocol::shadows::ShadowState;
 
+use crate::audit::{AuditAction, AuditEntry, AuditOutcome};
+use crate::auth::Principal;
 use crate::events::WsEvent;
 use crate::mqtt_bridge::compute_delta;
+use crate::shadow_reconcile::Reconciliation;
 use crate::state::AppState;
 
+/// Actor recorded for desired-state changes when auth is off.
+const OPERATOR: &str = "operator";
+
 /// Summary of a named shadow.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ShadowSummary {
     pub shadow_name: String,
     pub version: u64,
@@ -23,7 +29,7 @@ pub struct ShadowSummary {
 }
 
 /// Full shadow response including computed delta.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ShadowResponse {
     pub device_id: String,
     pub shadow_name: String,
@@ -32,151 +38,274 @@ pub struct ShadowResponse {
     pub delta: serde_json::Value,
     pub version: u64,
     pub last_updated: String,
+    /// Delivery history of the delta, once one has been published.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconciliation: Option<Reconciliation>,
 }
 
 /// Request body for setting desired state.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct SetDesiredRequest {
     pub desired: serde_json::Value,
 }
 
+/// Query parameters for clearing desired state.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClearDesiredQuery {
+    /// Comma-separated keys to clear; all desired keys when absent.
+    pub keys: Option<String>,
+}
+
 /// GET /api/v1/devices/{id}/shadows — list all shadows for a device.
+#[utoipa::path(
+    get,
+    path = "/api/v1/devices/{id}/shadows",
+    tag = "shadows",
+    params(("id" = String, Path, description = "Device ID or alias")),
+    responses((status = 200, description = "Shadows, by name", body = [ShadowSummary]))
+)]
 pub async fn list_shadows(
     State(state): State<AppState>,
     Path(device_id): Path<String>,
 ) -> Result<Json<Vec<ShadowSummary>>, StatusCode> {
-    if let Some(pool) = &state.pool {
-        let rows = crate::db::shadows::list_shadows(pool, &device_id)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let summaries: Vec<ShadowSummary> = rows
-            .into_iter()
-            .map(|r| ShadowSummary {
-                shadow_name: r.shadow_name,
-                version: r.version as u64,
-                last_updated: r.last_updated.to_rfc3339(),
-            })
-            .collect();
-        Ok(Json(summaries))
-    } else {
-        let shadows = state.shadows.read().await;
-        let summaries: Vec<ShadowSummary> = shadows
-            .iter()
-            .filter(|((did, _), _)| did == &device_id)
-            .map(|((_, name), s)| ShadowSummary {
-                shadow_name: name.clone(),
-                version: s.version,
-                last_updated: s.last_updated.to_rfc3339(),
-            })
-            .collect();
-        Ok(Json(summaries))
-    }
+    let device_id = crate::device_identity::resolve(&state, &device_id)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    let shadows = state
+        .store
+        .list_shadows(&device_id)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    let summaries: Vec<ShadowSummary> = shadows
+        .into_iter()
+        .map(|(shadow_name, s)| ShadowSummary {
+            shadow_name,
+            version: s.version,
+            last_updated: s.last_updated.to_rfc3339(),
+        })
+        .collect();
+    Ok(Json(summaries))
 }
 
 /// GET /api/v1/devices/{id}/shadows/{name} — get a specific shadow.
+#[utoipa::path(
+    get,
+    path = "/api/v1/devices/{id}/shadows/{name}",
+    tag = "shadows",
+    params(("id" = String, Path, description = "Device ID or alias"), ("name" = String, Path, description = "Shadow name")),
+    responses(
+        (status = 200, description = "The shadow with its delta", body = ShadowResponse),
+        (status = 404, description = "No such shadow"),
+    )
+)]
 pub async fn get_shadow(
     State(state): State<AppState>,
     Path((device_id, shadow_name)): Path<(String, String)>,
 ) -> Result<Json<ShadowResponse>, StatusCode> {
-    if let Some(pool) = &state.pool {
-        let row = crate::db::shadows::get_shadow(pool, &device_id, &shadow_name)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .ok_or(StatusCode::NOT_FOUND)?;
-        let delta = compute_delta(&row.desired, &row.reported);
-        Ok(Json(ShadowResponse {
-            device_id: row.device_id,
-            shadow_name: row.shadow_name,
-            reported: row.reported,
-            desired: row.desired,
-            delta,
-            version: row.version as u64,
-            last_updated: row.last_updated.to_rfc3339(),
-        }))
-    } else {
-        let shadows = state.shadows.read().await;
-        let key = (device_id.clone(), shadow_name.clone());
-        let shadow = shadows.get(&key).ok_or(StatusCode::NOT_FOUND)?;
-        let delta = compute_delta(&shadow.desired, &shadow.reported);
-        Ok(Json(ShadowResponse {
-            device_id,
-            shadow_name,
-            reported: shadow.reported.clone(),
-            desired: shadow.desired.clone(),
-            delta,
-            version: shadow.version,
-            last_updated: shadow.last_updated.to_rfc3339(),
-        }))
-    }
+    let device_id = crate::device_identity::resolve(&state, &device_id)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    let shadow = state
+        .store
+        .get_shadow(&device_id, &shadow_name)
+        .await
+        .map_err(|e| e.into_response().status())?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let delta = compute_delta(&shadow.desired, &shadow.reported);
+    let reconciliation = state.shadow_reconcile.get(&device_id, &shadow_name);
+    Ok(Json(ShadowResponse {
+        device_id,
+        shadow_name,
+        reported: shadow.reported,
+        desired: shadow.desired,
+        delta,
+        version: shadow.version,
+        last_updated: shadow.last_updated.to_rfc3339(),
+        reconciliation,
+    }))
 }
 
 /// PUT /api/v1/devices/{id}/shadows/{name}/desired — set desired state.
+#[utoipa::path(
+    put,
+    path = "/api/v1/devices/{id}/shadows/{name}/desired",
+    tag = "shadows",
+    params(("id" = String, Path, description = "Device ID or alias"), ("name" = String, Path, description = "Shadow name")),
+    request_body = SetDesiredRequest,
+    responses((status = 200, description = "The updated shadow", body = ShadowResponse))
+)]
 pub async fn set_desired(
     State(state): State<AppState>,
     Path((device_id, shadow_name)): Path<(String, String)>,
+    principal: Option<Extension<Principal>>,
     Json(req): Json<SetDesiredRequest>,
 ) -> Result<Json<ShadowResponse>, StatusCode> {
-    let reported;
-    let version;
-    let last_updated;
+    let device_id = crate::device_identity::resolve(&state, &device_id)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    let actor = crate::auth::actor(principal.as_deref(), OPERATOR.into());
+    apply_desired(&state, &actor, device_id, shadow_name, req.desired).await
+}
 
-    if let Some(pool) = &state.pool {
-        let row = crate::db::shadows::set_desired(pool, &device_id, &shadow_name, &req.desired)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        reported = row.reported;
-        version = row.version as u64;
-        last_updated = row.last_updated;
-    } else {
-        let mut shadows = state.shadows.write().await;
-        let key = (device_id.clone(), shadow_name.clone());
-        let entry = shadows.entry(key).or_insert_with(|| ShadowState {
-            reported: serde_json::Value::Object(Default::default()),
-            desired: serde_json::Value::Object(Default::default()),
-            version: 0,
-            last_updated: Utc::now(),
-        });
-        entry.desired = req.desired.clone();
-        entry.version += 1;
-        entry.last_updated = Utc::now();
-        reported = entry.reported.clone();
-        version = entry.version;
-        last_updated = entry.last_updated;
-    }
+/// DELETE /api/v1/devices/{id}/shadows/{name}/desired — clear desired state.
+///
+/// Every desired key (or only `?keys=a,b`) is removed. Keys the device
+/// still reports become `null` tombstones instead, so the published delta
+/// tells the device to drop them; once it reports them as `null` they leave
+/// reported state and the delta is empty. Without this, a key removed from
+/// desired state stayed in reported state forever.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/devices/{id}/shadows/{name}/desired",
+    tag = "shadows",
+    params(("id" = String, Path, description = "Device ID or alias"), ("name" = String, Path, description = "Shadow name"), ClearDesiredQuery),
+    responses(
+        (status = 200, description = "The updated shadow", body = ShadowResponse),
+        (status = 404, description = "No such shadow"),
+    )
+)]
+pub async fn clear_desired(
+    State(state): State<AppState>,
+    Path((device_id, shadow_name)): Path<(String, String)>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ClearDesiredQuery>,
+) -> Result<Json<ShadowResponse>, StatusCode> {
+    let device_id = crate::device_identity::resolve(&state, &device_id)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    let shadow = state
+        .store
+        .get_shadow(&device_id, &shadow_name)
+        .await
+        .map_err(|e| e.into_response().status())?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let keys: Option<Vec<String>> = query.keys.as_deref().map(|k| {
+        k.split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(String::from)
+            .collect()
+    });
+    let desired = tombstone(&shadow.desired, &shadow.reported, keys.as_deref());
+    let actor = crate::auth::actor(principal.as_deref(), OPERATOR.into());
+    apply_desired(&state, &actor, device_id, shadow_name, desired).await
+}
 
-    let delta = compute_delta(&req.desired, &reported);
+/// DELETE /api/v1/devices/{id}/shadows/{name} — delete a shadow.
+///
+/// Nothing is sent to the device; clear desired state first if it should
+/// drop the keys.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/devices/{id}/shadows/{name}",
+    tag = "shadows",
+    params(("id" = String, Path, description = "Device ID or alias"), ("name" = String, Path, description = "Shadow name")),
+    responses(
+        (status = 204, description = "Shadow deleted"),
+        (status = 404, description = "No such shadow"),
+    )
+)]
+pub async fn delete_shadow(
+    State(state): State<AppState>,
+    Path((device_id, shadow_name)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let device_id = crate::device_identity::resolve(&state, &device_id)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    let deleted = state
+        .store
+        .delete_shadow(&device_id, &shadow_name)
+        .await
+        .map_err(|e| e.into_response().status())?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.shadow_reconcile.reset(&device_id, &shadow_name);
+    tracing::info!(device_id = %device_id, shadow = %shadow_name, "shadow deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    // Publish ShadowDelta via MQTT if there's a difference.
-    if !delta.as_object().is_none_or(|o| o.is_empty())
-        && let Some(mqtt) = &state.mqtt
-    {
-        // Derive fleet_id from device info or use a default.
-        let fleet_id = if state.pool.is_some() {
-            // In DB mode, we'd look it up — for now just use the device_id path.
-            // The topic is fleet-level, but cloud bridge knows the fleet context.
-            String::new()
+/// Desired state with `keys` (all desired keys when `None`) cleared: keys
+/// present in `reported` become `null` tombstones, the rest are dropped.
+fn tombstone(
+    desired: &serde_json::Value,
+    reported: &serde_json::Value,
+    keys: Option<&[String]>,
+) -> serde_json::Value {
+    let mut cleared = desired.as_object().cloned().unwrap_or_default();
+    let targets: Vec<String> = match keys {
+        Some(keys) => keys.to_vec(),
+        None => cleared.keys().cloned().collect(),
+    };
+    for key in targets {
+        let reported_val = reported.get(&key).filter(|v| !v.is_null());
+        if reported_val.is_some() {
+            cleared.insert(key, serde_json::Value::Null);
         } else {
-            let devices = state.devices.read().await;
-            devices
-                .get(&device_id)
-                .and_then(|d| d.metadata.get("fleet"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("default")
-                .to_string()
-        };
+            cleared.remove(&key);
+        }
+    }
+    serde_json::Value::Object(cleared)
+}
 
-        let shadow_delta = ShadowDelta {
-            device_id: device_id.clone(),
-            shadow_name: shadow_name.clone(),
-            delta: delta.clone(),
-            version,
-            timestamp: Utc::now(),
-        };
+/// Store a new desired state on behalf of `actor`, audit it, publish the
+/// delta and broadcast the update.
+pub(crate) async fn apply_desired(
+    state: &AppState,
+    actor: &str,
+    device_id: String,
+    shadow_name: String,
+    desired: serde_json::Value,
+) -> Result<Json<ShadowResponse>, StatusCode> {
+    let ShadowState {
+        reported,
+        version,
+        last_updated,
+        ..
+    } = state
+        .store
+        .set_desired(&device_id, &shadow_name, &desired)
+        .await
+        .map_err(|e| e.into_response().status())?;
+
+    let delta = compute_delta(&desired, &reported);
+
+    // New desired state gets a fresh delivery budget.
+    state.shadow_reconcile.reset(&device_id, &shadow_name);
+
+    let fleet_id = crate::auth::device_fleet(state, &device_id).await;
+    let mut entry = AuditEntry::device(
+        actor,
+        AuditAction::ShadowDesiredChanged,
+        &device_id,
+        serde_json::json!({
+            "shadow_name": shadow_name,
+            "desired": desired,
+            "version": version,
+        }),
+    )
+    .with_outcome(AuditOutcome::Succeeded);
+    if let Some(fleet_id) = &fleet_id {
+        entry = entry.with_fleet(fleet_id);
+    }
+    crate::audit::record(state, entry)
+        .await
+        .map_err(|e| e.into_response().status())?;
 
-        let topic = topics::shadow_delta(&fleet_id, &device_id);
-        if let Ok(payload) = serde_json::to_vec(&shadow_delta) {
-            let _ = mqtt.publish(&topic, &payload, QoS::AtLeastOnce).await;
-        }
+    // Publish ShadowDelta via MQTT if there's a difference.
+    if !delta.as_object().is_none_or(|o| o.is_empty()) && state.mqtt.is_some() {
+        let fleet_id = fleet_id.unwrap_or_else(|| "default".to_string());
+
+        crate::shadow_reconcile::publish_delta(
+            state,
+            &fleet_id,
+            &device_id,
+            &shadow_name,
+            delta.clone(),
+            version,
+        )
+        .await;
     }
 
     // Broadcast event.
@@ -187,14 +316,16 @@ pub async fn set_desired(
         timestamp: Utc::now(),
     });
 
+    let reconciliation = state.shadow_reconcile.get(&device_id, &shadow_name);
     Ok(Json(ShadowResponse {
         device_id,
         shadow_name,
         reported,
-        desired: req.desired,
+        desired,
         delta,
         version,
         last_updated: last_updated.to_rfc3339(),
+        reconciliation,
     }))
 }
 
@@ -267,6 +398,13 @@ mod tests {
         assert_eq!(json["shadow_name"], "config");
         assert_eq!(json["desired"]["firmware"], "0.2.0");
         assert_eq!(json["version"], 1);
+
+        let audit = state.audit_log.read().await;
+        let entry = audit.back().unwrap();
+        assert_eq!(entry.action, AuditAction::ShadowDesiredChanged);
+        assert_eq!(entry.actor, "operator");
+        assert_eq!(entry.fleet_id.as_deref(), Some("fleet-alpha"));
+        assert_eq!(entry.detail["desired"]["firmware"], "0.2.0");
     }
 
     #[tokio::test]
@@ -340,7 +478,101 @@ mod tests {
         // Verify delta was published via MQTT.
         let delta_msgs = mqtt.published_to("fleet/fleet-alpha/rpi-001/shadow/delta");
         assert_eq!(delta_msgs.len(), 1);
-        let delta: ShadowDelta = serde_json::from_slice(&delta_msgs[0].payload).unwrap();
+        let delta: zc_protocol::shadows::ShadowDelta =
+            serde_json::from_slice(&delta_msgs[0].payload).unwrap();
         assert_eq!(delta.delta["firmware"], "0.2.0");
     }
+
+    #[tokio::test]
+    async fn clear_desired_publishes_tombstones() {
+        let mqtt = std::sync::Arc::new(zc_mqtt_channel::MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        {
+            let mut shadows = state.shadows.write().await;
+            shadows.insert(
+                ("rpi-001".to_string(), "config".to_string()),
+                ShadowState {
+                    reported: serde_json::json!({"log_level": "debug", "firmware": "0.2.0"}),
+                    desired: serde_json::json!({"log_level": "debug", "sample_rate": 10}),
+                    version: 4,
+                    last_updated: Utc::now(),
+                },
+            );
+        }
+
+        let response = app_with_state(state.clone())
+            .oneshot(
+                Request::delete("/api/v1/devices/rpi-001/shadows/config/desired")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        // Reported keys are tombstoned, unreported ones simply dropped.
+        assert_eq!(json["desired"], serde_json::json!({"log_level": null}));
+        assert_eq!(json["delta"], serde_json::json!({"log_level": null}));
+        let delta_msgs = mqtt.published_to("fleet/fleet-alpha/rpi-001/shadow/delta");
+        assert_eq!(delta_msgs.len(), 1);
+
+        // The device acknowledging with null drops the key and converges.
+        let update = zc_protocol::shadows::ShadowUpdate {
+            device_id: "rpi-001".into(),
+            shadow_name: "config".into(),
+            reported: serde_json::json!({"log_level": null}),
+            version: 5,
+        };
+        crate::mqtt_bridge::handle_incoming(
+            &zc_protocol::topics::shadow_update("fleet-alpha", "rpi-001"),
+            &serde_json::to_vec(&update).unwrap(),
+            &state,
+        )
+        .await;
+        let shadow =
+            state.shadows.read().await[&("rpi-001".to_string(), "config".to_string())].clone();
+        assert_eq!(shadow.reported, serde_json::json!({"firmware": "0.2.0"}));
+        assert_eq!(
+            compute_delta(&shadow.desired, &shadow.reported),
+            serde_json::json!({})
+        );
+        assert_eq!(
+            mqtt.published_to("fleet/fleet-alpha/rpi-001/shadow/delta")
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_shadow_removes_it() {
+        let state = AppState::with_sample_data();
+        let delete = || {
+            Request::delete("/api/v1/devices/rpi-001/shadows/config")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app_with_state(state.clone())
+            .oneshot(delete())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        state.shadows.write().await.insert(
+            ("rpi-001".to_string(), "config".to_string()),
+            ShadowState {
+                reported: serde_json::json!({}),
+                desired: serde_json::json!({"firmware": "0.2.0"}),
+                version: 1,
+                last_updated: Utc::now(),
+            },
+        );
+        let response = app_with_state(state.clone())
+            .oneshot(delete())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(state.shadows.read().await.is_empty());
+    }
 }
@@ -1,36 +1,85 @@
 //! Telemetry query and ingestion endpoints.
 
+use std::io::Read;
+
 use axum::Json;
+use axum::body::{Body, Bytes};
 use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, header};
+use axum::response::{IntoResponse, Response};
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use flate2::read::GzDecoder;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use zc_protocol::telemetry::TelemetryBatch;
 
+use crate::db::telemetry::{SortOrder, TelemetryCursor, TelemetryFilter, TelemetryRow};
 use crate::error::{ApiError, ApiResult};
 use crate::events::WsEvent;
+use crate::openapi::ApiErrorBody;
+use crate::routes::csv;
 use crate::state::AppState;
 
+/// Page size when `limit` is omitted.
+const DEFAULT_LIMIT: u32 = 100;
+
+/// Largest page a single JSON response may return.
+const MAX_LIMIT: u32 = 1000;
+
+/// Rows fetched per backend round-trip while streaming NDJSON or CSV.
+const STREAM_PAGE_SIZE: u32 = 1000;
+
+/// Columns of streamed CSV readings.
+const CSV_COLUMNS: &[&str] = &[
+    "time",
+    "metric_name",
+    "value_numeric",
+    "value_text",
+    "value_json",
+    "unit",
+    "source",
+];
+
 /// Query parameters for telemetry requests.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TelemetryQuery {
     /// Filter by telemetry source (obd2, system, canbus).
     pub source: Option<String>,
-    /// Maximum number of results.
-    #[serde(default = "default_limit")]
-    pub limit: u32,
+    /// Filter by metric name (e.g. `engine_rpm`).
+    pub metric: Option<String>,
+    /// Page size (JSON, max 1000) or total row cap (NDJSON/CSV, unbounded if omitted).
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+    /// Sort by time: `desc` (newest first, default) or `asc`.
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Response format: `json` (default), `ndjson` or `csv`.
+    #[serde(default)]
+    pub format: ResponseFormat,
 }
 
-fn default_limit() -> u32 {
-    100
+/// Response encoding for telemetry queries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Ndjson,
+    Csv,
 }
 
 /// Request body for ingesting telemetry readings.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct IngestTelemetryRequest {
     pub readings: Vec<TelemetryReadingInput>,
 }
 
 /// A single telemetry reading in the ingestion request.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TelemetryReadingInput {
     pub metric_name: String,
     pub value_numeric: Option<f64>,
@@ -41,81 +90,247 @@ pub struct TelemetryReadingInput {
     pub time: Option<DateTime<Utc>>,
 }
 
+/// Largest bulk ingestion body, before and after gzip decompression.
+pub const MAX_BULK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Outcome of one batch of a bulk ingestion.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResult {
+    pub device_id: String,
+    /// `ok` or `rejected`.
+    #[schema(value_type = String)]
+    pub status: &'static str,
+    /// Readings stored.
+    pub count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response to a bulk ingestion.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkIngestSummary {
+    pub accepted: usize,
+    pub rejected: usize,
+    /// Readings stored across accepted batches.
+    pub readings: usize,
+    /// One entry per batch, in request order.
+    pub batches: Vec<BatchResult>,
+}
+
 /// GET /api/v1/devices/:id/telemetry — query device telemetry.
+///
+/// Returns one page of readings plus a `next_cursor` to fetch the next one
+/// (null on the last page). With `format=ndjson` (or `Accept:
+/// application/x-ndjson`) all matching readings are streamed instead, one
+/// JSON object per line; `format=csv` (or `Accept: text/csv`) streams them as
+/// CSV with a header row.
+#[utoipa::path(
+    get,
+    path = "/api/v1/devices/{id}/telemetry",
+    tag = "telemetry",
+    params(("id" = String, Path, description = "Device ID or alias"), TelemetryQuery),
+    responses(
+        (status = 200, description = "`{device_id, source, metric, order, limit, readings, next_cursor}`", content(
+            (Object = "application/json"),
+            (String = "application/x-ndjson"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "Invalid cursor", body = ApiErrorBody),
+        (status = 404, description = "Unknown device", body = ApiErrorBody),
+    )
+)]
 pub async fn get_telemetry(
     State(state): State<AppState>,
     Path(device_id): Path<String>,
     Query(query): Query<TelemetryQuery>,
-) -> ApiResult<Json<serde_json::Value>> {
-    // Verify device exists
-    if let Some(pool) = &state.pool {
-        let exists = crate::db::devices::exists(pool, &device_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-        if !exists {
-            return Err(ApiError::NotFound(format!(
-                "device '{device_id}' not found"
-            )));
-        }
-
-        // Query real telemetry data
-        let rows = crate::db::telemetry::query_readings(
-            pool,
-            &device_id,
-            query.source.as_deref(),
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let device_id = crate::device_identity::resolve(&state, &device_id).await?;
+    ensure_device_exists(&state, &device_id).await?;
+
+    let after = query
+        .cursor
+        .as_deref()
+        .map(|c| {
+            TelemetryCursor::decode(c)
+                .ok_or_else(|| ApiError::BadRequest(format!("invalid cursor '{c}'")))
+        })
+        .transpose()?;
+
+    let mut filter = TelemetryFilter {
+        source: query.source.clone(),
+        metric_name: query.metric.clone(),
+        order: query.order,
+        after,
+        limit: 0,
+    };
+
+    let format = match query.format {
+        ResponseFormat::Json if accepts_ndjson(&headers) => ResponseFormat::Ndjson,
+        ResponseFormat::Json if csv::accepts_csv(&headers) => ResponseFormat::Csv,
+        format => format,
+    };
+    if format != ResponseFormat::Json {
+        return Ok(stream_readings(
+            state,
+            device_id,
+            filter,
             query.limit,
-        )
-        .await
-        .map_err(|e| ApiError::Internal(e.to_string()))?;
-
-        let readings: Vec<serde_json::Value> = rows
-            .into_iter()
-            .map(|r| {
-                serde_json::json!({
-                    "time": r.time,
-                    "metric_name": r.metric_name,
-                    "value_numeric": r.value_numeric,
-                    "value_text": r.value_text,
-                    "value_json": r.value_json,
-                    "unit": r.unit,
-                    "source": r.source,
-                })
-            })
-            .collect();
-
-        return Ok(Json(serde_json::json!({
-            "device_id": device_id,
-            "source": query.source,
-            "limit": query.limit,
-            "readings": readings,
-        })));
+            format,
+        ));
     }
 
-    // In-memory fallback: verify device exists, return empty readings
-    {
-        let devices = state.devices.read().await;
-        if !devices.contains_key(&device_id) {
-            return Err(ApiError::NotFound(format!(
-                "device '{device_id}' not found"
-            )));
-        }
-    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    // Fetch one extra row to learn whether another page follows.
+    filter.limit = limit + 1;
+    let mut rows = state.store.telemetry_page(&device_id, &filter).await?;
+
+    let next_cursor = if rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        rows.last().map(|r| TelemetryCursor::from_row(r).encode())
+    } else {
+        None
+    };
+    let readings: Vec<serde_json::Value> = rows.iter().map(reading_json).collect();
 
     Ok(Json(serde_json::json!({
         "device_id": device_id,
         "source": query.source,
-        "limit": query.limit,
-        "readings": [],
-        "message": "telemetry storage not yet implemented (in-memory mode)"
-    })))
+        "metric": query.metric,
+        "order": query.order.as_str(),
+        "limit": limit,
+        "readings": readings,
+        "next_cursor": next_cursor,
+    }))
+    .into_response())
+}
+
+async fn ensure_device_exists(state: &AppState, device_id: &str) -> ApiResult<()> {
+    if state.store.device_exists(device_id).await? {
+        Ok(())
+    } else {
+        Err(ApiError::NotFound(format!(
+            "device '{device_id}' not found"
+        )))
+    }
+}
+
+fn reading_json(r: &TelemetryRow) -> serde_json::Value {
+    serde_json::json!({
+        "time": r.time,
+        "metric_name": r.metric_name,
+        "value_numeric": r.value_numeric,
+        "value_text": r.value_text,
+        "value_json": r.value_json,
+        "unit": r.unit,
+        "source": r.source,
+    })
+}
+
+fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/x-ndjson"))
+}
+
+/// Paging state carried between chunks of a streamed response.
+struct StreamCursor {
+    state: AppState,
+    device_id: String,
+    filter: TelemetryFilter,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+/// Stream every matching reading as NDJSON or CSV, one backend page per
+/// chunk.
+fn stream_readings(
+    state: AppState,
+    device_id: String,
+    filter: TelemetryFilter,
+    max_rows: Option<u32>,
+    format: ResponseFormat,
+) -> Response {
+    let init = StreamCursor {
+        state,
+        device_id,
+        filter,
+        remaining: max_rows.map(|n| n as usize),
+        done: false,
+    };
+
+    let stream = futures::stream::unfold(init, move |mut c| async move {
+        if c.done || c.remaining == Some(0) {
+            return None;
+        }
+
+        let page_size = c.remaining.map_or(STREAM_PAGE_SIZE, |r| {
+            r.min(STREAM_PAGE_SIZE as usize) as u32
+        });
+        c.filter.limit = page_size;
+
+        let rows = match c.state.store.telemetry_page(&c.device_id, &c.filter).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::error!(error = %e, device_id = %c.device_id, "telemetry stream aborted");
+                c.done = true;
+                return Some((Err(e), c));
+            }
+        };
+
+        c.done = rows.len() < page_size as usize;
+        if let Some(remaining) = c.remaining.as_mut() {
+            *remaining -= rows.len();
+        }
+        c.filter.after = rows.last().map(TelemetryCursor::from_row);
+
+        let mut chunk = String::new();
+        for row in &rows {
+            if format == ResponseFormat::Csv {
+                chunk.push_str(&csv::record(CSV_COLUMNS, &reading_json(row)));
+            } else {
+                chunk.push_str(&reading_json(row).to_string());
+                chunk.push('\n');
+            }
+        }
+        Some((Ok::<_, ApiError>(chunk), c))
+    });
+
+    if format == ResponseFormat::Csv {
+        let head = futures::stream::once(async { Ok(csv::header_row(CSV_COLUMNS)) });
+        return (
+            [(header::CONTENT_TYPE, csv::CONTENT_TYPE)],
+            Body::from_stream(head.chain(stream)),
+        )
+            .into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream),
+    )
+        .into_response()
 }
 
 /// POST /api/v1/devices/:id/telemetry — ingest telemetry readings.
+#[utoipa::path(
+    post,
+    path = "/api/v1/devices/{id}/telemetry",
+    tag = "telemetry",
+    params(("id" = String, Path, description = "Device ID or alias")),
+    request_body = IngestTelemetryRequest,
+    responses(
+        (status = 200, description = "`{status: \"ok\", count}`", body = Object),
+        (status = 404, description = "Unknown device", body = ApiErrorBody),
+    ),
+    security(())
+)]
 pub async fn ingest_telemetry(
     State(state): State<AppState>,
     Path(device_id): Path<String>,
     Json(req): Json<IngestTelemetryRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
+    let device_id = crate::device_identity::resolve(&state, &device_id).await?;
     let now = Utc::now();
     let count = req.readings.len();
 
@@ -126,58 +341,180 @@ pub async fn ingest_telemetry(
         .map(|r| r.source.clone())
         .unwrap_or_else(|| "unknown".to_string());
 
-    // Verify device exists
-    if let Some(pool) = &state.pool {
-        let exists = crate::db::devices::exists(pool, &device_id)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-        if !exists {
-            return Err(ApiError::NotFound(format!(
-                "device '{device_id}' not found"
-            )));
-        }
+    ensure_device_exists(&state, &device_id).await?;
 
-        // Convert to TelemetryRow vec and insert.
-        let rows: Vec<crate::db::telemetry::TelemetryRow> = req
-            .readings
-            .into_iter()
-            .map(|r| crate::db::telemetry::TelemetryRow {
-                time: r.time.unwrap_or(now),
-                device_id: device_id.clone(),
-                metric_name: r.metric_name,
-                value_numeric: r.value_numeric,
-                value_text: r.value_text,
-                value_json: r.value_json,
-                unit: r.unit,
-                source: r.source,
-            })
-            .collect();
-        crate::db::telemetry::insert_batch(pool, &rows)
-            .await
-            .map_err(|e| ApiError::Internal(e.to_string()))?;
-    } else {
-        // In-memory fallback: verify device exists (accept data loss).
-        let devices = state.devices.read().await;
-        if !devices.contains_key(&device_id) {
-            return Err(ApiError::NotFound(format!(
-                "device '{device_id}' not found"
-            )));
-        }
-    }
+    let rows: Vec<TelemetryRow> = req
+        .readings
+        .into_iter()
+        .map(|r| TelemetryRow {
+            time: r.time.unwrap_or(now),
+            device_id: device_id.clone(),
+            metric_name: r.metric_name,
+            value_numeric: r.value_numeric,
+            value_text: r.value_text,
+            value_json: r.value_json,
+            unit: r.unit,
+            source: r.source,
+        })
+        .collect();
+    store_readings(&state, &device_id, rows, source).await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "count": count,
+    })))
+}
+
+/// Store one device's readings, evaluate alert rules over them and
+/// broadcast the ingestion.
+async fn store_readings(
+    state: &AppState,
+    device_id: &str,
+    mut rows: Vec<TelemetryRow>,
+    source: String,
+) -> ApiResult<()> {
+    let count = rows.len();
+    crate::alert_rules::telemetry_ingested(state, None, device_id, &rows).await;
+    state.store.append_telemetry(&mut rows).await?;
 
     tracing::debug!(device_id = %device_id, count = count, "telemetry ingested");
 
     let _ = state.event_tx.send(WsEvent::TelemetryIngested {
-        device_id,
+        device_id: device_id.to_string(),
         count,
         source,
-        timestamp: now,
+        timestamp: Utc::now(),
     });
+    Ok(())
+}
 
-    Ok(Json(serde_json::json!({
-        "status": "ok",
-        "count": count,
-    })))
+/// POST /api/v1/telemetry/batch — ingest telemetry for many devices at once.
+///
+/// The body is a JSON array of `TelemetryBatch`es (each reading is stored
+/// under its batch's `device_id`, which may be an alias), optionally sent
+/// with `Content-Encoding: gzip`. Each batch is stored or rejected on its
+/// own (e.g. an unknown device), so gateways proxying many vehicles learn
+/// which to resend from the per-batch results. The body is limited to
+/// [`MAX_BULK_BYTES`] both compressed and decompressed.
+#[utoipa::path(
+    post,
+    path = "/api/v1/telemetry/batch",
+    tag = "telemetry",
+    params(("Content-Encoding" = Option<String>, Header, description = "`gzip` for a compressed body")),
+    request_body = Vec<TelemetryBatch>,
+    responses(
+        (status = 200, description = "Per-batch results", body = BulkIngestSummary),
+        (status = 400, description = "Invalid JSON, gzip or content encoding", body = ApiErrorBody),
+        (status = 413, description = "Body too large", body = ApiErrorBody),
+    ),
+    security(())
+)]
+pub async fn ingest_bulk(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Json<BulkIngestSummary>> {
+    let json = decode_body(&headers, &body)?;
+    let batches: Vec<TelemetryBatch> = serde_json::from_slice(&json)
+        .map_err(|e| ApiError::BadRequest(format!("invalid telemetry batches: {e}")))?;
+
+    let mut summary = BulkIngestSummary {
+        accepted: 0,
+        rejected: 0,
+        readings: 0,
+        batches: Vec::with_capacity(batches.len()),
+    };
+    for batch in batches {
+        let device_id = batch.device_id.clone();
+        let result = match ingest_batch(&state, batch).await {
+            Ok(count) => {
+                summary.accepted += 1;
+                summary.readings += count;
+                BatchResult {
+                    device_id,
+                    status: "ok",
+                    count,
+                    error: None,
+                }
+            }
+            Err(ApiError::Internal(e)) => return Err(ApiError::Internal(e)),
+            Err(e) => {
+                summary.rejected += 1;
+                BatchResult {
+                    device_id,
+                    status: "rejected",
+                    count: 0,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        summary.batches.push(result);
+    }
+
+    tracing::debug!(
+        accepted = summary.accepted,
+        rejected = summary.rejected,
+        readings = summary.readings,
+        "bulk telemetry ingested"
+    );
+    Ok(Json(summary))
+}
+
+/// Store one batch of a bulk ingestion; returns the readings stored.
+async fn ingest_batch(state: &AppState, batch: TelemetryBatch) -> ApiResult<usize> {
+    let device_id = crate::device_identity::resolve(state, &batch.device_id).await?;
+    ensure_device_exists(state, &device_id).await?;
+
+    let source = batch
+        .readings
+        .first()
+        .map_or("unknown", |r| r.source.as_str())
+        .to_string();
+    let rows: Vec<TelemetryRow> = batch
+        .readings
+        .into_iter()
+        .map(|r| TelemetryRow {
+            time: r.time,
+            device_id: device_id.clone(),
+            metric_name: r.metric_name,
+            value_numeric: r.value_numeric,
+            value_text: r.value_text,
+            value_json: r.value_json,
+            unit: r.unit,
+            source: r.source.as_str().to_string(),
+        })
+        .collect();
+    let count = rows.len();
+    store_readings(state, &device_id, rows, source).await?;
+    Ok(count)
+}
+
+/// The request body, gunzipped if `Content-Encoding` says so.
+fn decode_body(headers: &HeaderMap, body: &Bytes) -> ApiResult<Vec<u8>> {
+    let encoding = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase());
+    match encoding.as_deref() {
+        None | Some("identity") => Ok(body.to_vec()),
+        Some("gzip") => {
+            let mut json = Vec::new();
+            // One byte over the limit tells a full body from an oversized one.
+            GzDecoder::new(body.as_ref())
+                .take(MAX_BULK_BYTES as u64 + 1)
+                .read_to_end(&mut json)
+                .map_err(|e| ApiError::BadRequest(format!("invalid gzip body: {e}")))?;
+            if json.len() > MAX_BULK_BYTES {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "decompressed body exceeds {MAX_BULK_BYTES} bytes"
+                )));
+            }
+            Ok(json)
+        }
+        Some(other) => Err(ApiError::BadRequest(format!(
+            "unsupported content encoding '{other}'"
+        ))),
+    }
 }
 
 #[cfg(test)]
@@ -298,4 +635,303 @@ mod tests {
         assert!(json.contains("telemetry_ingested"));
         assert!(json.contains("rpi-001"));
     }
+
+    async fn seeded_app() -> axum::Router {
+        let state = AppState::with_sample_data();
+        let mut rows: Vec<TelemetryRow> = [
+            (100, "engine_rpm", "obd2"),
+            (100, "vehicle_speed", "obd2"),
+            (200, "engine_rpm", "obd2"),
+            (200, "cpu_usage", "system"),
+            (300, "engine_rpm", "obd2"),
+        ]
+        .into_iter()
+        .map(|(secs, metric, source)| TelemetryRow {
+            time: DateTime::from_timestamp(secs, 0).unwrap(),
+            device_id: "rpi-001".into(),
+            metric_name: metric.into(),
+            value_numeric: Some(secs as f64),
+            value_text: None,
+            value_json: None,
+            unit: None,
+            source: source.into(),
+        })
+        .collect();
+        state.store.append_telemetry(&mut rows).await.unwrap();
+        build_router(state)
+    }
+
+    async fn get_json(app: axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn readings_of(json: &serde_json::Value) -> Vec<(String, f64)> {
+        json["readings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["metric_name"].as_str().unwrap().to_string(),
+                    r["value_numeric"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn ingested_readings_are_queryable() {
+        let app = app();
+        let body = serde_json::json!({
+            "readings": [{ "metric_name": "engine_rpm", "value_numeric": 3500.0, "source": "obd2" }]
+        });
+        app.clone()
+            .oneshot(
+                Request::post("/api/v1/devices/rpi-001/telemetry")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let (status, json) = get_json(app, "/api/v1/devices/rpi-001/telemetry").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["readings"][0]["value_numeric"], 3500.0);
+        assert!(json["next_cursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn newest_first_by_default() {
+        let (_, json) = get_json(seeded_app().await, "/api/v1/devices/rpi-001/telemetry").await;
+        assert_eq!(json["order"], "desc");
+        let readings = readings_of(&json);
+        assert_eq!(readings.len(), 5);
+        assert_eq!(readings[0], ("engine_rpm".into(), 300.0));
+        assert_eq!(readings[4], ("engine_rpm".into(), 100.0));
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_through_all_readings() {
+        let app = seeded_app().await;
+        let mut uri = "/api/v1/devices/rpi-001/telemetry?order=asc&limit=2".to_string();
+        let mut seen = Vec::new();
+
+        loop {
+            let (status, json) = get_json(app.clone(), &uri).await;
+            assert_eq!(status, StatusCode::OK);
+            seen.extend(readings_of(&json));
+            match json["next_cursor"].as_str() {
+                Some(cursor) => {
+                    uri = format!(
+                        "/api/v1/devices/rpi-001/telemetry?order=asc&limit=2&cursor={cursor}"
+                    )
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(
+            seen,
+            vec![
+                ("engine_rpm".into(), 100.0),
+                ("vehicle_speed".into(), 100.0),
+                ("cpu_usage".into(), 200.0),
+                ("engine_rpm".into(), 200.0),
+                ("engine_rpm".into(), 300.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn filters_by_metric_and_source() {
+        let app = seeded_app().await;
+
+        let (_, json) = get_json(
+            app.clone(),
+            "/api/v1/devices/rpi-001/telemetry?metric=engine_rpm",
+        )
+        .await;
+        assert_eq!(readings_of(&json).len(), 3);
+        assert_eq!(json["metric"], "engine_rpm");
+
+        let (_, json) = get_json(app, "/api/v1/devices/rpi-001/telemetry?source=system").await;
+        assert_eq!(readings_of(&json), vec![("cpu_usage".into(), 200.0)]);
+    }
+
+    #[tokio::test]
+    async fn invalid_cursor_is_bad_request() {
+        let (status, _) = get_json(
+            seeded_app().await,
+            "/api/v1/devices/rpi-001/telemetry?cursor=garbage",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn ndjson_streams_every_reading() {
+        let response = seeded_app()
+            .await
+            .oneshot(
+                Request::get("/api/v1/devices/rpi-001/telemetry?format=ndjson&metric=engine_rpm")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["value_numeric"], 300.0);
+    }
+
+    #[tokio::test]
+    async fn csv_streams_header_and_rows() {
+        let response = seeded_app()
+            .await
+            .oneshot(
+                Request::get("/api/v1/devices/rpi-001/telemetry?metric=engine_rpm")
+                    .header("accept", "text/csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], csv::CONTENT_TYPE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = std::str::from_utf8(&body).unwrap();
+        let lines: Vec<&str> = text.split_terminator("\r\n").collect();
+        assert_eq!(
+            lines[0],
+            "time,metric_name,value_numeric,value_text,value_json,unit,source"
+        );
+        assert_eq!(lines.len(), 4);
+        let first: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(&first[1..4], ["engine_rpm", "300.0", ""]);
+    }
+
+    #[tokio::test]
+    async fn ndjson_via_accept_header_honors_limit() {
+        let response = seeded_app()
+            .await
+            .oneshot(
+                Request::get("/api/v1/devices/rpi-001/telemetry?limit=2")
+                    .header("accept", "application/x-ndjson")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(std::str::from_utf8(&body).unwrap().lines().count(), 2);
+    }
+
+    fn bulk_body() -> serde_json::Value {
+        let reading = |device: &str| {
+            serde_json::json!({
+                "device_id": device,
+                "time": "2026-01-01T00:00:00Z",
+                "metric_name": "engine_rpm",
+                "value_numeric": 800.0,
+                "source": "obd2"
+            })
+        };
+        serde_json::json!([
+            {
+                "device_id": "rpi-001",
+                "readings": [reading("rpi-001"), reading("rpi-001")],
+                "collected_at": "2026-01-01T00:00:01Z"
+            },
+            {
+                "device_id": "nonexistent",
+                "readings": [reading("nonexistent")],
+                "collected_at": "2026-01-01T00:00:01Z"
+            }
+        ])
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn bulk_ingest_reports_each_batch() {
+        let state = AppState::with_sample_data();
+        let app = build_router(state.clone());
+        let body = gzip(&serde_json::to_vec(&bulk_body()).unwrap());
+
+        let response = app
+            .oneshot(
+                Request::post("/api/v1/telemetry/batch")
+                    .header("content-type", "application/json")
+                    .header("content-encoding", "gzip")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["accepted"], 1);
+        assert_eq!(json["rejected"], 1);
+        assert_eq!(json["readings"], 2);
+        assert_eq!(json["batches"][0]["status"], "ok");
+        assert_eq!(json["batches"][0]["count"], 2);
+        assert_eq!(json["batches"][1]["device_id"], "nonexistent");
+        assert_eq!(json["batches"][1]["status"], "rejected");
+        assert!(json["batches"][1]["error"].is_string());
+
+        let stored = state.telemetry.read().await;
+        assert_eq!(stored.len(), 2);
+        assert!(stored.iter().all(|r| r.device_id == "rpi-001"));
+    }
+
+    #[tokio::test]
+    async fn bulk_ingest_rejects_bad_bodies() {
+        let post = |encoding: Option<&str>, body: Vec<u8>| {
+            let mut request =
+                Request::post("/api/v1/telemetry/batch").header("content-type", "application/json");
+            if let Some(encoding) = encoding {
+                request = request.header("content-encoding", encoding);
+            }
+            app().oneshot(request.body(Body::from(body)).unwrap())
+        };
+
+        let plain = serde_json::to_vec(&bulk_body()).unwrap();
+        let response = post(Some("gzip"), plain.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post(Some("br"), plain).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post(None, b"{\"not\": \"an array\"}".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Compresses far below the limit but inflates past it.
+        let bomb = gzip(&vec![b' '; MAX_BULK_BYTES + 1]);
+        let response = post(Some("gzip"), bomb).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
@@ -0,0 +1,452 @@
+//! Command template endpoints (see [`crate::templates`]).
+
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::state::AppState;
+use crate::templates::{CommandTemplate, Target, TemplateParameter, TemplateReport, TemplateStep};
+
+/// Request body for saving a template.
+#[derive(Debug, Deserialize)]
+pub struct CreateTemplateRequest {
+    /// Unique within the template's fleet (case-insensitive).
+    pub name: String,
+    pub description: Option<String>,
+    /// Fleet the template belongs to; shared by all fleets when absent.
+    pub fleet_id: Option<String>,
+    /// Values the steps refer to as `{{name}}`.
+    #[serde(default)]
+    pub parameters: Vec<TemplateParameter>,
+    /// Commands dispatched in order (1 to 10).
+    pub steps: Vec<TemplateStep>,
+    /// Who saved it (the authenticated user, when auth is on).
+    #[serde(default)]
+    pub created_by: String,
+}
+
+/// Request body for dispatching a template.
+#[derive(Debug, Deserialize)]
+pub struct DispatchTemplateRequest {
+    /// Target fleet; every device in it unless `device_id` is set.
+    pub fleet_id: String,
+    /// Single target device ID or alias.
+    pub device_id: Option<String>,
+    /// `key:value` tags a fleet device must all carry (fleet targets only).
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Parameter values; strings, numbers or booleans.
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    /// Who is sending it, recorded on every command (the authenticated
+    /// user, when auth is on).
+    #[serde(default)]
+    pub initiated_by: String,
+}
+
+/// Query parameters for the template list.
+#[derive(Debug, Deserialize)]
+pub struct ListTemplatesQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    /// Only this fleet's templates (and shared ones).
+    pub fleet_id: Option<String>,
+}
+
+/// Query parameters for a template's run history.
+#[derive(Debug, Deserialize)]
+pub struct ListRunsQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// POST /api/v1/templates — save a named command sequence.
+pub async fn create_template(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(req): Json<CreateTemplateRequest>,
+) -> ApiResult<(StatusCode, Json<CommandTemplate>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    let template = CommandTemplate {
+        id: Uuid::now_v7(),
+        name: req.name.trim().to_string(),
+        description: req.description,
+        fleet_id: req.fleet_id,
+        parameters: req.parameters,
+        steps: req.steps,
+        created_by,
+        created_at: Utc::now(),
+    };
+    check_write(principal.as_deref(), &template)?;
+    template.validate().map_err(ApiError::BadRequest)?;
+    crate::templates::create(&state, &template).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// GET /api/v1/templates — templates visible to the caller, oldest first.
+///
+/// Filtered by `fleet_id`, paged with `limit`/`offset`. The unpaged match
+/// count is in `X-Total-Count`.
+pub async fn list_templates(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListTemplatesQuery>,
+) -> ApiResult<Response> {
+    let tenants = principal.and_then(|Extension(user)| user.tenants);
+    let matching =
+        crate::templates::list(&state, query.fleet_id.as_deref(), tenants.as_deref()).await?;
+    let total = matching.len() as u64;
+    let page = pagination::slice(matching, query.offset, pagination::limit(query.limit));
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/templates/:id — one template.
+pub async fn get_template(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<CommandTemplate>> {
+    let template = crate::templates::get(&state, id).await?;
+    check_read(principal.as_deref(), &template)?;
+    Ok(Json(template))
+}
+
+/// DELETE /api/v1/templates/:id — remove a template and its run history.
+pub async fn delete_template(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let template = crate::templates::get(&state, id).await?;
+    check_write(principal.as_deref(), &template)?;
+    crate::templates::delete(&state, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/v1/templates/:id/dispatch — send every step to a device or
+/// fleet; returns the run's report.
+pub async fn dispatch_template(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<DispatchTemplateRequest>,
+) -> ApiResult<(StatusCode, Json<TemplateReport>)> {
+    let template = crate::templates::get(&state, id).await?;
+    check_read(principal.as_deref(), &template)?;
+    if let Some(fleet) = &template.fleet_id
+        && *fleet != req.fleet_id
+    {
+        return Err(ApiError::BadRequest(format!(
+            "template '{}' belongs to fleet '{fleet}', not '{}'",
+            template.name, req.fleet_id
+        )));
+    }
+    let initiated_by = crate::auth::actor(principal.as_deref(), req.initiated_by);
+    if initiated_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("initiated_by is required".into()));
+    }
+    let device_id = match req.device_id {
+        Some(reference) => {
+            if !req.tags.is_empty() {
+                return Err(ApiError::BadRequest(
+                    "tags apply to fleet targets only, not with device_id".into(),
+                ));
+            }
+            Some(crate::device_identity::resolve_existing(&state, &reference).await?)
+        }
+        None => None,
+    };
+    let params = req
+        .params
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(s) => Ok((name, s)),
+            serde_json::Value::Number(_) | serde_json::Value::Bool(_) => {
+                Ok((name, value.to_string()))
+            }
+            _ => Err(ApiError::BadRequest(format!(
+                "parameter '{name}' must be a string, number or boolean"
+            ))),
+        })
+        .collect::<ApiResult<BTreeMap<_, _>>>()?;
+
+    let target = Target {
+        fleet_id: req.fleet_id,
+        device_id,
+        tags: req.tags,
+    };
+    let run =
+        crate::templates::dispatch(&state, principal, &template, target, params, initiated_by)
+            .await?;
+    let report = crate::templates::report(&state, run).await?;
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+/// GET /api/v1/templates/:id/runs — dispatches of a template, newest
+/// first, with the commands each step created. The total is in
+/// `X-Total-Count`.
+pub async fn list_template_runs(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListRunsQuery>,
+) -> ApiResult<Response> {
+    let template = crate::templates::get(&state, id).await?;
+    check_read(principal.as_deref(), &template)?;
+    let (mut page, total) =
+        crate::templates::runs(&state, id, pagination::limit(query.limit), query.offset).await?;
+    if let Some(user) = principal.as_deref() {
+        page.retain(|run| user.can_access_fleet(&run.fleet_id));
+    }
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/templates/:id/runs/:run_id — one run as a report: overall
+/// status, command counts and every device's result per step.
+pub async fn get_template_run(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path((id, run_id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<Json<TemplateReport>> {
+    let run = crate::templates::get_run(&state, id, run_id).await?;
+    if let Some(user) = principal.as_deref()
+        && !user.can_access_fleet(&run.fleet_id)
+    {
+        return Err(ApiError::Forbidden(format!(
+            "no access to fleet '{}'",
+            run.fleet_id
+        )));
+    }
+    Ok(Json(crate::templates::report(&state, run).await?))
+}
+
+/// Shared templates are visible to everyone, fleet templates to the fleet.
+fn check_read(principal: Option<&Principal>, template: &CommandTemplate) -> ApiResult<()> {
+    match (principal, &template.fleet_id) {
+        (Some(user), Some(fleet)) if !user.can_access_fleet(fleet) => {
+            Err(ApiError::Forbidden(format!("no access to fleet '{fleet}'")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Shared templates are changed only by callers without a tenant limit.
+fn check_write(principal: Option<&Principal>, template: &CommandTemplate) -> ApiResult<()> {
+    match (principal, &template.fleet_id) {
+        (Some(user), None) if user.tenants.is_some() => Err(ApiError::Forbidden(
+            "templates shared by all fleets need access to all fleets; set fleet_id".into(),
+        )),
+        _ => check_read(principal, template),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+    use zc_protocol::commands::{CommandResponse, CommandStatus, InferenceTier};
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn post(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn health_check() -> serde_json::Value {
+        serde_json::json!({
+            "name": "full health check",
+            "parameters": [{"name": "log", "default": "/var/log/syslog"}],
+            "steps": [
+                {"tool_name": "read_dtcs", "tool_args": {}},
+                {"tool_name": "log_stats", "tool_args": {"path": "{{log}}"}},
+                {"command": "check disk space"},
+            ],
+            "created_by": "alice",
+        })
+    }
+
+    #[tokio::test]
+    async fn template_dispatch_aggregates_step_results() {
+        let state = AppState::with_sample_data();
+        let (status, template) = send(&state, post("/api/v1/templates", health_check())).await;
+        assert_eq!(status, StatusCode::CREATED, "{template}");
+        let id = template["id"].as_str().unwrap().to_string();
+
+        let (status, _) = send(&state, post("/api/v1/templates", health_check())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, report) = send(
+            &state,
+            post(
+                &format!("/api/v1/templates/{id}/dispatch"),
+                serde_json::json!({
+                    "fleet_id": "fleet-alpha",
+                    "device_id": "rpi-001",
+                    "params": {"log": "/var/log/kern.log"},
+                    "initiated_by": "bob",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{report}");
+        assert_eq!(report["status"], "running");
+        assert_eq!(report["params"]["log"], "/var/log/kern.log");
+        let steps = report["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[1]["results"][0]["device_id"], "rpi-001");
+
+        let commands = state.commands.read().await;
+        assert_eq!(commands.len(), 3);
+        let intent = commands[1].envelope.parsed_intent.as_ref().unwrap();
+        assert_eq!(intent.tool_name, "log_stats");
+        assert_eq!(intent.tool_args["path"], "/var/log/kern.log");
+        assert_eq!(commands[1].envelope.initiated_by, "bob");
+        let ids: Vec<_> = commands.iter().map(|r| r.envelope.clone()).collect();
+        drop(commands);
+
+        // Every step answers; the last one fails.
+        for (i, envelope) in ids.iter().enumerate() {
+            let failed = i == 2;
+            let response = CommandResponse {
+                command_id: envelope.id,
+                correlation_id: envelope.correlation_id,
+                device_id: envelope.device_id.clone(),
+                status: if failed {
+                    CommandStatus::Failed
+                } else {
+                    CommandStatus::Completed
+                },
+                inference_tier: InferenceTier::Local,
+                response_text: Some(format!("step {i}")),
+                response_data: None,
+                latency_ms: 10,
+                responded_at: chrono::Utc::now(),
+                error: failed.then(|| "df: not found".to_string()),
+                error_code: None,
+                cached: false,
+            };
+            let mut commands = state.commands.write().await;
+            let record = commands
+                .iter_mut()
+                .find(|r| r.envelope.id == envelope.id)
+                .unwrap();
+            record.response = Some(response);
+        }
+
+        let run_id = report["run_id"].as_str().unwrap();
+        let (status, report) = send(
+            &state,
+            get(&format!("/api/v1/templates/{id}/runs/{run_id}")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["status"], "failed");
+        assert_eq!(report["counts"]["completed"], 2);
+        assert_eq!(report["counts"]["failed"], 1);
+        assert_eq!(report["steps"][0]["results"][0]["response_text"], "step 0");
+        assert_eq!(report["steps"][2]["results"][0]["error"], "df: not found");
+
+        let (_, runs) = send(&state, get(&format!("/api/v1/templates/{id}/runs"))).await;
+        assert_eq!(runs.as_array().unwrap().len(), 1);
+        assert_eq!(
+            runs[0]["steps"][0]["command_ids"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn fleet_dispatch_broadcasts_each_step() {
+        let state = AppState::with_sample_data();
+        let (_, template) = send(&state, post("/api/v1/templates", health_check())).await;
+        let id = template["id"].as_str().unwrap();
+
+        let (status, report) = send(
+            &state,
+            post(
+                &format!("/api/v1/templates/{id}/dispatch"),
+                serde_json::json!({"fleet_id": "fleet-alpha", "initiated_by": "bob"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{report}");
+        for step in report["steps"].as_array().unwrap() {
+            assert!(step["broadcast_id"].is_string());
+            assert_eq!(step["results"].as_array().unwrap().len(), 2);
+        }
+        assert_eq!(state.commands.read().await.len(), 6);
+
+        // Unknown targets are refused before anything is recorded.
+        let (status, _) = send(
+            &state,
+            post(
+                &format!("/api/v1/templates/{id}/dispatch"),
+                serde_json::json!({"fleet_id": "fleet-alpha", "device_id": "rpi-999",
+                    "initiated_by": "bob"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(
+            &state,
+            post(
+                &format!("/api/v1/templates/{id}/dispatch"),
+                serde_json::json!({"fleet_id": "fleet-alpha", "params": {"bus": "can1"},
+                    "initiated_by": "bob"}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, runs) = state
+            .store
+            .list_template_runs(id.parse().unwrap(), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(runs, 1);
+
+        let delete = Request::delete(format!("/api/v1/templates/{id}"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&state, delete).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, runs) = state
+            .store
+            .list_template_runs(id.parse().unwrap(), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(runs, 0);
+    }
+}
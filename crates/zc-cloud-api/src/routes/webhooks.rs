@@ -0,0 +1,328 @@
+//! Webhook management endpoints.
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::pagination;
+use crate::state::AppState;
+use crate::webhooks::{Webhook, WebhookEvent, WebhookFilter, WebhookFormat};
+
+/// Request body for creating a webhook.
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub name: String,
+    /// http(s) URL notifications are POSTed to.
+    pub url: String,
+    /// `generic` (default) or `slack`.
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// `command_failed`, `device_offline` and/or `critical_dtc`.
+    pub events: Vec<WebhookEvent>,
+    /// Only this fleet's devices; all fleets when omitted.
+    pub fleet_id: Option<String>,
+    /// Owner (the authenticated user, when OIDC is on).
+    #[serde(default)]
+    pub created_by: String,
+    /// Start disabled when `false`.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Query parameters for the webhook list.
+#[derive(Debug, Deserialize)]
+pub struct ListWebhooksQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+    pub enabled: Option<bool>,
+    /// Only webhooks subscribed to this event.
+    pub event: Option<WebhookEvent>,
+}
+
+/// Query parameters for a webhook's deliveries.
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesQuery {
+    /// Page size (default 50, max 500).
+    pub limit: Option<u32>,
+    /// Rows to skip before the page.
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// POST /api/v1/webhooks — register a notification sink.
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Json(mut req): Json<CreateWebhookRequest>,
+) -> ApiResult<(StatusCode, Json<Webhook>)> {
+    let created_by = crate::auth::actor(principal.as_deref(), req.created_by);
+    if created_by.trim().is_empty() {
+        return Err(ApiError::BadRequest("created_by is required".into()));
+    }
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".into()));
+    }
+    if !(req.url.starts_with("http://") || req.url.starts_with("https://")) {
+        return Err(ApiError::BadRequest("url must be an http(s) URL".into()));
+    }
+    req.events.sort_by_key(|e| e.as_str());
+    req.events.dedup();
+    if req.events.is_empty() {
+        return Err(ApiError::BadRequest(
+            "events must name at least one event".into(),
+        ));
+    }
+
+    let now = Utc::now();
+    let webhook = Webhook {
+        id: Uuid::now_v7(),
+        name: req.name.trim().to_string(),
+        url: req.url,
+        format: req.format,
+        events: req.events,
+        fleet_id: req.fleet_id,
+        enabled: req.enabled,
+        created_by,
+        created_at: now,
+        updated_at: now,
+    };
+    check_access(principal.as_deref(), &webhook)?;
+    crate::webhooks::create(&state, &webhook).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+/// GET /api/v1/webhooks — list webhooks, oldest first.
+///
+/// Filtered by `enabled` and `event`, paged with `limit`/`offset`. The
+/// unpaged match count is in `X-Total-Count`. Users limited to some fleets
+/// see only those fleets' webhooks.
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Query(query): Query<ListWebhooksQuery>,
+) -> ApiResult<Response> {
+    let filter = WebhookFilter {
+        enabled: query.enabled,
+        event: query.event,
+        fleets: principal.and_then(|Extension(user)| user.tenants),
+    };
+    let matching = crate::webhooks::list(&state, &filter).await?;
+    let total = matching.len() as u64;
+    let page = pagination::slice(matching, query.offset, pagination::limit(query.limit));
+    Ok(pagination::with_total(page, total))
+}
+
+/// GET /api/v1/webhooks/:id — one webhook.
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Webhook>> {
+    let webhook = crate::webhooks::get(&state, id).await?;
+    check_access(principal.as_deref(), &webhook)?;
+    Ok(Json(webhook))
+}
+
+/// POST /api/v1/webhooks/:id/enable — send notifications again.
+pub async fn enable_webhook(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Webhook>> {
+    set_enabled(&state, principal, id, true).await
+}
+
+/// POST /api/v1/webhooks/:id/disable — stop sending notifications.
+pub async fn disable_webhook(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Webhook>> {
+    set_enabled(&state, principal, id, false).await
+}
+
+/// DELETE /api/v1/webhooks/:id — remove a webhook and its deliveries.
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let webhook = crate::webhooks::get(&state, id).await?;
+    check_access(principal.as_deref(), &webhook)?;
+    crate::webhooks::delete(&state, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/webhooks/:id/deliveries — notifications sent to a webhook,
+/// newest first, with their status. The total is in `X-Total-Count`.
+pub async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListDeliveriesQuery>,
+) -> ApiResult<Response> {
+    let webhook = crate::webhooks::get(&state, id).await?;
+    check_access(principal.as_deref(), &webhook)?;
+    let (page, total) =
+        crate::webhooks::deliveries(&state, id, pagination::limit(query.limit), query.offset)
+            .await?;
+    Ok(pagination::with_total(page, total))
+}
+
+async fn set_enabled(
+    state: &AppState,
+    principal: Option<Extension<Principal>>,
+    id: Uuid,
+    enabled: bool,
+) -> ApiResult<Json<Webhook>> {
+    let webhook = crate::webhooks::get(state, id).await?;
+    check_access(principal.as_deref(), &webhook)?;
+    Ok(Json(
+        crate::webhooks::set_enabled(state, id, enabled).await?,
+    ))
+}
+
+/// All-fleet webhooks belong to users with access to every fleet.
+fn check_access(principal: Option<&Principal>, webhook: &Webhook) -> ApiResult<()> {
+    let Some(user) = principal else {
+        return Ok(());
+    };
+    let allowed = match &webhook.fleet_id {
+        Some(fleet_id) => user.can_access_fleet(fleet_id),
+        None => user.tenants.is_none(),
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(match &webhook.fleet_id {
+            Some(fleet_id) => format!("no access to fleet '{fleet_id}'"),
+            None => "all-fleet webhooks need access to every fleet".into(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::build_router;
+    use crate::state::AppState;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn post(uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn webhook_lifecycle() {
+        let state = AppState::with_sample_data();
+        let (status, webhook) = send(
+            &state,
+            post(
+                "/api/v1/webhooks",
+                serde_json::json!({
+                    "name": "ops channel",
+                    "url": "https://hooks.slack.com/services/T0/B0/x",
+                    "format": "slack",
+                    "events": ["device_offline", "critical_dtc", "device_offline"],
+                    "fleet_id": "fleet-alpha",
+                    "created_by": "alice",
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{webhook}");
+        assert_eq!(
+            webhook["events"],
+            serde_json::json!(["critical_dtc", "device_offline"])
+        );
+        let id = webhook["id"].as_str().unwrap().to_string();
+
+        let (_, list) = send(&state, get("/api/v1/webhooks?event=critical_dtc")).await;
+        assert_eq!(list.as_array().unwrap().len(), 1);
+        let (_, list) = send(&state, get("/api/v1/webhooks?event=command_failed")).await;
+        assert!(list.as_array().unwrap().is_empty());
+
+        let (_, disabled) = send(
+            &state,
+            post(
+                &format!("/api/v1/webhooks/{id}/disable"),
+                serde_json::json!({}),
+            ),
+        )
+        .await;
+        assert_eq!(disabled["enabled"], false);
+
+        let (status, deliveries) =
+            send(&state, get(&format!("/api/v1/webhooks/{id}/deliveries"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(deliveries.as_array().unwrap().is_empty());
+
+        let delete = Request::delete(format!("/api/v1/webhooks/{id}"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&state, delete).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&state, get(&format!("/api/v1/webhooks/{id}"))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn invalid_webhooks_are_rejected() {
+        let state = AppState::with_sample_data();
+        for (body, expected) in [
+            (
+                serde_json::json!({"name": "x", "url": "ftp://example.com", "events": ["device_offline"],
+                    "created_by": "alice"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "url": "https://example.com", "events": [],
+                    "created_by": "alice"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                serde_json::json!({"name": "x", "url": "https://example.com", "events": ["device_online"],
+                    "created_by": "alice"}),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                serde_json::json!({"name": "x", "url": "https://example.com", "format": "teams",
+                    "events": ["device_offline"], "created_by": "alice"}),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ] {
+            let (status, error) = send(&state, post("/api/v1/webhooks", body.clone())).await;
+            assert_eq!(status, expected, "{body} -> {error}");
+        }
+        assert!(state.webhooks.read().await.is_empty());
+    }
+}
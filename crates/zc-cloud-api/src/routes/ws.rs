@@ -1,28 +1,299 @@
 //! WebSocket endpoint for real-time event streaming.
+//!
+//! Every client receives all events until it sends a subscribe message:
+//!
+//! ```json
+//! {"type": "subscribe", "device_ids": ["rpi-001"], "fleet_ids": ["fleet-alpha"],
+//!  "event_types": ["command_response", "device_status_changed"]}
+//! ```
+//!
+//! Each list is optional; an empty or missing list does not filter on that
+//! field. Device and fleet filters combine as a union (events for a listed
+//! device *or* any device in a listed fleet). A new subscribe replaces the
+//! previous one, and `{"type": "unsubscribe"}` restores the full stream.
+//! The server answers with `subscribed` / `unsubscribed`, or `error` for a
+//! malformed message.
+//!
+//! A user limited to some fleets only ever receives events for devices in
+//! those fleets, whatever the subscription, and cannot subscribe to other
+//! fleets.
+
+use std::collections::HashSet;
 
 use axum::extract::State;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 
-use crate::events::WsEvent;
+use crate::auth::Principal;
+use crate::events::{EVENT_TYPES, WsEvent};
 use crate::state::AppState;
 
 /// GET /api/v1/ws — upgrade to WebSocket for real-time events.
-pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state.event_tx.subscribe()))
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> impl IntoResponse {
+    let tenants = principal.and_then(|Extension(p)| p.tenants);
+    ws.on_upgrade(move |socket| {
+        let rx = state.event_tx.subscribe();
+        handle_socket(socket, rx, state, tenants)
+    })
+}
+
+/// GET /api/v1/events/schema — JSON Schema for every WebSocket event.
+pub async fn event_schema() -> Json<serde_json::Value> {
+    Json(crate::event_schema::schema())
+}
+
+/// Control messages sent by the client.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {
+        #[serde(default)]
+        device_ids: Vec<String>,
+        #[serde(default)]
+        fleet_ids: Vec<String>,
+        #[serde(default)]
+        event_types: Vec<String>,
+    },
+    Unsubscribe,
+}
+
+/// Replies to client control messages.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Subscribed {
+        device_ids: Vec<String>,
+        fleet_ids: Vec<String>,
+        event_types: Vec<String>,
+        /// Devices currently known in the subscribed fleets.
+        fleet_devices: usize,
+    },
+    Unsubscribed,
+    Error {
+        message: String,
+    },
+}
+
+/// Active filter for one connection.
+#[derive(Debug, Default)]
+pub(crate) struct Subscription {
+    device_ids: HashSet<String>,
+    fleet_ids: HashSet<String>,
+    /// Devices resolved from `fleet_ids`, grown as devices are provisioned.
+    fleet_devices: HashSet<String>,
+    event_types: HashSet<String>,
+    /// The user's tenants, when limited to some fleets.
+    tenant: Option<TenantScope>,
+}
+
+/// Fleets a connection is limited to, and their devices.
+#[derive(Debug, Default)]
+struct TenantScope {
+    fleet_ids: HashSet<String>,
+    /// Resolved from `fleet_ids`, grown as devices are provisioned.
+    devices: HashSet<String>,
+}
+
+impl Subscription {
+    /// Whether `event` should be forwarded. Provisioning events for a
+    /// subscribed fleet add the new device to the filter.
+    pub(crate) fn admit(&mut self, event: &WsEvent) -> bool {
+        if let Some(scope) = &mut self.tenant {
+            if let WsEvent::DeviceProvisioned {
+                device_id,
+                fleet_id,
+                ..
+            } = event
+                && scope.fleet_ids.contains(fleet_id)
+            {
+                scope.devices.insert(device_id.clone());
+            }
+            if !scope.devices.contains(event.device_id()) {
+                return false;
+            }
+        }
+
+        if let WsEvent::DeviceProvisioned {
+            device_id,
+            fleet_id,
+            ..
+        } = event
+            && self.fleet_ids.contains(fleet_id)
+        {
+            self.fleet_devices.insert(device_id.clone());
+        }
+
+        if !self.event_types.is_empty() && !self.event_types.contains(event.event_type()) {
+            return false;
+        }
+        if self.device_ids.is_empty() && self.fleet_ids.is_empty() {
+            return true;
+        }
+        let device = event.device_id();
+        self.device_ids.contains(device) || self.fleet_devices.contains(device)
+    }
+}
+
+/// Build a subscription, resolving fleet IDs to their current devices.
+/// `tenants` limits it to those fleets.
+pub(crate) async fn subscribe(
+    state: &AppState,
+    tenants: Option<&[String]>,
+    device_ids: Vec<String>,
+    fleet_ids: Vec<String>,
+    event_types: Vec<String>,
+) -> Result<Subscription, String> {
+    if let Some(unknown) = event_types
+        .iter()
+        .find(|t| !EVENT_TYPES.contains(&t.as_str()))
+    {
+        return Err(format!("unknown event type '{unknown}'"));
+    }
+    let tenant = match tenants {
+        Some(tenants) => {
+            if let Some(fleet) = fleet_ids.iter().find(|f| !tenants.contains(f)) {
+                return Err(format!("no access to fleet '{fleet}'"));
+            }
+            let fleet_ids: HashSet<String> = tenants.iter().cloned().collect();
+            let devices = devices_in_fleets(state, &fleet_ids).await?;
+            Some(TenantScope { fleet_ids, devices })
+        }
+        None => None,
+    };
+
+    let fleet_ids: HashSet<String> = fleet_ids.into_iter().collect();
+    let fleet_devices = if fleet_ids.is_empty() {
+        HashSet::new()
+    } else {
+        devices_in_fleets(state, &fleet_ids).await?
+    };
+
+    Ok(Subscription {
+        device_ids: device_ids.into_iter().collect(),
+        fleet_ids,
+        fleet_devices,
+        event_types: event_types.into_iter().collect(),
+        tenant,
+    })
+}
+
+/// Device IDs whose `metadata.fleet` is one of `fleet_ids`.
+async fn devices_in_fleets(
+    state: &AppState,
+    fleet_ids: &HashSet<String>,
+) -> Result<HashSet<String>, String> {
+    let in_fleet = |metadata: &serde_json::Value| {
+        metadata
+            .get("fleet")
+            .and_then(|v| v.as_str())
+            .is_some_and(|f| fleet_ids.contains(f))
+    };
+
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::devices::list_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(rows
+            .into_iter()
+            .filter(|r| in_fleet(&r.metadata))
+            .map(|r| r.device_id)
+            .collect())
+    } else {
+        let devices = state.devices.read().await;
+        Ok(devices
+            .values()
+            .filter(|d| in_fleet(&d.metadata))
+            .map(|d| d.device_id.clone())
+            .collect())
+    }
 }
 
-async fn handle_socket(mut socket: WebSocket, mut rx: broadcast::Receiver<WsEvent>) {
+/// Apply a client text message to the subscription and produce the reply.
+async fn handle_client_message(
+    text: &str,
+    state: &AppState,
+    tenants: Option<&[String]>,
+    subscription: &mut Subscription,
+) -> ServerMessage {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe {
+            device_ids,
+            fleet_ids,
+            event_types,
+        }) => match subscribe(state, tenants, device_ids, fleet_ids, event_types).await {
+            Ok(sub) => {
+                *subscription = sub;
+                let sorted = |set: &HashSet<String>| {
+                    let mut v: Vec<String> = set.iter().cloned().collect();
+                    v.sort();
+                    v
+                };
+                ServerMessage::Subscribed {
+                    device_ids: sorted(&subscription.device_ids),
+                    fleet_ids: sorted(&subscription.fleet_ids),
+                    event_types: sorted(&subscription.event_types),
+                    fleet_devices: subscription.fleet_devices.len(),
+                }
+            }
+            Err(message) => ServerMessage::Error { message },
+        },
+        Ok(ClientMessage::Unsubscribe) => {
+            match subscribe(state, tenants, Vec::new(), Vec::new(), Vec::new()).await {
+                Ok(sub) => {
+                    *subscription = sub;
+                    ServerMessage::Unsubscribed
+                }
+                Err(message) => ServerMessage::Error { message },
+            }
+        }
+        Err(e) => ServerMessage::Error {
+            message: format!("invalid message: {e}"),
+        },
+    }
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    mut rx: broadcast::Receiver<WsEvent>,
+    state: AppState,
+    tenants: Option<Vec<String>>,
+) {
     tracing::info!("WebSocket client connected");
+    let metrics = state.metrics.clone();
+    let _client = metrics.websocket_client();
+    let mut subscription = match subscribe(
+        &state,
+        tenants.as_deref(),
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+    )
+    .await
+    {
+        Ok(sub) => sub,
+        Err(e) => {
+            tracing::error!("failed to resolve WebSocket tenants: {e}");
+            return;
+        }
+    };
 
     loop {
         tokio::select! {
-            // Forward broadcast events to the WebSocket client.
+            // Forward matching broadcast events to the WebSocket client.
             result = rx.recv() => {
                 match result {
                     Ok(event) => {
-                        let json = match serde_json::to_string(&event) {
+                        if !subscription.admit(&event) {
+                            continue;
+                        }
+                        let json = match serde_json::to_string(&event.to_frame()) {
                             Ok(j) => j,
                             Err(e) => {
                                 tracing::error!("failed to serialize event: {e}");
@@ -41,7 +312,7 @@ async fn handle_socket(mut socket: WebSocket, mut rx: broadcast::Receiver<WsEven
                     }
                 }
             }
-            // Handle incoming messages from the client (ping/pong, close).
+            // Handle incoming messages from the client (subscribe, ping/pong, close).
             msg = socket.recv() => {
                 match msg {
                     Some(Ok(Message::Close(_))) | None => break,
@@ -50,7 +321,22 @@ async fn handle_socket(mut socket: WebSocket, mut rx: broadcast::Receiver<WsEven
                             break;
                         }
                     }
-                    Some(Ok(_)) => {} // Ignore text/binary from client
+                    Some(Ok(Message::Text(text))) => {
+                        let reply = handle_client_message(
+                            text.as_str(),
+                            &state,
+                            tenants.as_deref(),
+                            &mut subscription,
+                        )
+                        .await;
+                        let Ok(json) = serde_json::to_string(&reply) else {
+                            continue;
+                        };
+                        if socket.send(Message::Text(json.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(_)) => {} // Ignore binary/pong from client
                     Some(Err(_)) => break,
                 }
             }
@@ -64,6 +350,22 @@ async fn handle_socket(mut socket: WebSocket, mut rx: broadcast::Receiver<WsEven
 mod tests {
     use super::*;
 
+    fn heartbeat(device_id: &str) -> WsEvent {
+        WsEvent::DeviceHeartbeat {
+            device_id: device_id.into(),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    fn telemetry(device_id: &str) -> WsEvent {
+        WsEvent::TelemetryIngested {
+            device_id: device_id.into(),
+            count: 5,
+            source: "obd2".into(),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
     #[test]
     fn ws_event_serializes_to_json() {
         let event = WsEvent::DeviceHeartbeat {
@@ -73,4 +375,149 @@ mod tests {
         let json = serde_json::to_string(&event).unwrap();
         assert!(json.contains("device_heartbeat"));
     }
+
+    #[test]
+    fn default_subscription_admits_everything() {
+        let mut sub = Subscription::default();
+        assert!(sub.admit(&heartbeat("rpi-001")));
+        assert!(sub.admit(&telemetry("sbc-010")));
+    }
+
+    #[tokio::test]
+    async fn device_and_event_type_filters() {
+        let state = AppState::with_sample_data();
+        let mut sub = Subscription::default();
+        let reply = handle_client_message(
+            r#"{"type":"subscribe","device_ids":["rpi-001"],"event_types":["device_heartbeat"]}"#,
+            &state,
+            None,
+            &mut sub,
+        )
+        .await;
+        assert!(matches!(reply, ServerMessage::Subscribed { .. }));
+
+        assert!(sub.admit(&heartbeat("rpi-001")));
+        assert!(!sub.admit(&heartbeat("rpi-002")));
+        assert!(!sub.admit(&telemetry("rpi-001")));
+    }
+
+    #[tokio::test]
+    async fn fleet_filter_resolves_devices_and_tracks_provisioning() {
+        let state = AppState::with_sample_data();
+        let mut sub = Subscription::default();
+        let reply = handle_client_message(
+            r#"{"type":"subscribe","fleet_ids":["fleet-alpha"]}"#,
+            &state,
+            None,
+            &mut sub,
+        )
+        .await;
+        assert!(matches!(
+            reply,
+            ServerMessage::Subscribed {
+                fleet_devices: 2,
+                ..
+            }
+        ));
+
+        assert!(sub.admit(&telemetry("rpi-002")));
+        assert!(!sub.admit(&telemetry("sbc-010")));
+
+        assert!(sub.admit(&WsEvent::DeviceProvisioned {
+            device_id: "rpi-003".into(),
+            fleet_id: "fleet-alpha".into(),
+            hardware_type: "raspberry_pi4".into(),
+            provisioned_at: chrono::Utc::now(),
+        }));
+        assert!(sub.admit(&heartbeat("rpi-003")));
+    }
+
+    #[tokio::test]
+    async fn unsubscribe_restores_full_stream() {
+        let state = AppState::with_sample_data();
+        let mut sub = Subscription::default();
+        handle_client_message(
+            r#"{"type":"subscribe","device_ids":["rpi-001"]}"#,
+            &state,
+            None,
+            &mut sub,
+        )
+        .await;
+        assert!(!sub.admit(&heartbeat("sbc-010")));
+
+        let reply =
+            handle_client_message(r#"{"type":"unsubscribe"}"#, &state, None, &mut sub).await;
+        assert!(matches!(reply, ServerMessage::Unsubscribed));
+        assert!(sub.admit(&heartbeat("sbc-010")));
+    }
+
+    #[tokio::test]
+    async fn invalid_messages_keep_current_filter() {
+        let state = AppState::with_sample_data();
+        let mut sub = Subscription::default();
+        handle_client_message(
+            r#"{"type":"subscribe","device_ids":["rpi-001"]}"#,
+            &state,
+            None,
+            &mut sub,
+        )
+        .await;
+
+        for bad in [
+            r#"{"type":"subscribe","event_types":["everything"]}"#,
+            "not json",
+        ] {
+            let reply = handle_client_message(bad, &state, None, &mut sub).await;
+            assert!(matches!(reply, ServerMessage::Error { .. }));
+        }
+        assert!(sub.admit(&heartbeat("rpi-001")));
+        assert!(!sub.admit(&heartbeat("rpi-002")));
+    }
+
+    #[tokio::test]
+    async fn tenants_bound_every_subscription() {
+        let state = AppState::with_sample_data();
+        let tenants = vec!["fleet-alpha".to_string()];
+        let mut sub = subscribe(&state, Some(&tenants), Vec::new(), Vec::new(), Vec::new())
+            .await
+            .unwrap();
+        assert!(sub.admit(&heartbeat("rpi-001")));
+        assert!(!sub.admit(&heartbeat("sbc-010")));
+
+        let reply = handle_client_message(
+            r#"{"type":"subscribe","fleet_ids":["fleet-beta"]}"#,
+            &state,
+            Some(&tenants),
+            &mut sub,
+        )
+        .await;
+        assert!(matches!(reply, ServerMessage::Error { .. }));
+
+        // Naming a device outside the tenants does not reach it.
+        handle_client_message(
+            r#"{"type":"subscribe","device_ids":["sbc-010","rpi-002"]}"#,
+            &state,
+            Some(&tenants),
+            &mut sub,
+        )
+        .await;
+        assert!(!sub.admit(&heartbeat("sbc-010")));
+        assert!(sub.admit(&heartbeat("rpi-002")));
+
+        handle_client_message(
+            r#"{"type":"unsubscribe"}"#,
+            &state,
+            Some(&tenants),
+            &mut sub,
+        )
+        .await;
+        assert!(!sub.admit(&heartbeat("sbc-010")));
+        assert!(sub.admit(&WsEvent::DeviceProvisioned {
+            device_id: "rpi-009".into(),
+            fleet_id: "fleet-alpha".into(),
+            hardware_type: "raspberry_pi4".into(),
+            provisioned_at: chrono::Utc::now(),
+        }));
+        assert!(sub.admit(&heartbeat("rpi-009")));
+    }
 }
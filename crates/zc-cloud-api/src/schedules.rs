@@ -0,0 +1,504 @@
+//! Scheduled (recurring) commands.
+//!
+//! A schedule pairs a [`Cron`] expression (UTC) with a command for one
+//! device or a whole fleet, optionally narrowed by tags. [`run`] wakes every
+//! [`TICK_SECS`] and fires each enabled schedule whose `next_run_at` has
+//! passed: the command goes through the normal dispatch path
+//! ([`send_command`] or [`broadcast_command`]), so inference, structured
+//! mode, approval and the offline queue apply exactly as for a command an
+//! operator sends. Occurrences missed while the server was down are not
+//! replayed; the schedule fires once and moves on to its next slot.
+//!
+//! Every firing, successful or not, is kept as a [`ScheduleRun`] with the
+//! command IDs it created. In database mode a firing is claimed by moving
+//! `next_run_at` forward with a conditional update first, so replicas
+//! sharing a database do not dispatch the same slot twice.
+
+use std::time::Duration as StdDuration;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::cron::Cron;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::commands::{SendCommandRequest, send_command};
+use crate::routes::fleets::{BroadcastCommandRequest, broadcast_command};
+use crate::state::AppState;
+
+/// How often the scheduler looks for due schedules.
+pub const TICK_SECS: u64 = 30;
+/// Runs kept per schedule in memory mode (the database keeps all).
+pub const MAX_RUNS_KEPT: usize = 100;
+
+/// A recurring command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Schedule {
+    pub id: Uuid,
+    pub name: String,
+    pub cron: String,
+    pub fleet_id: String,
+    /// Single target device; the whole fleet when `None`.
+    pub device_id: Option<String>,
+    /// `key:value` tags a fleet device must carry to be targeted.
+    pub tags: Vec<String>,
+    pub command: String,
+    pub tool_name: Option<String>,
+    pub tool_args: Option<serde_json::Value>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Next firing; `None` while paused or if the expression never fires.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub run_count: u64,
+}
+
+impl Schedule {
+    /// The parsed cron expression (validated when the schedule was created).
+    pub fn cron(&self) -> ApiResult<Cron> {
+        Cron::parse(&self.cron).map_err(ApiError::BadRequest)
+    }
+
+    fn due(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.next_run_at.is_some_and(|t| t <= now)
+    }
+}
+
+/// One firing of a schedule.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduleRun {
+    pub id: Uuid,
+    pub schedule_id: Uuid,
+    /// The slot that came due.
+    pub scheduled_for: DateTime<Utc>,
+    pub fired_at: DateTime<Utc>,
+    /// Commands created, one per targeted device.
+    pub command_ids: Vec<Uuid>,
+    /// Broadcast ID for fleet schedules.
+    pub broadcast_id: Option<Uuid>,
+    /// Why dispatch was refused (unknown device, empty fleet, ...).
+    pub error: Option<String>,
+}
+
+/// Filters for listing schedules.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleFilter {
+    pub fleet_id: Option<String>,
+    pub device_id: Option<String>,
+    pub enabled: Option<bool>,
+    /// Only these fleets (the caller's tenants); `None` for all.
+    pub fleets: Option<Vec<String>>,
+}
+
+impl ScheduleFilter {
+    pub fn matches(&self, schedule: &Schedule) -> bool {
+        self.fleet_id
+            .as_deref()
+            .is_none_or(|f| schedule.fleet_id == f)
+            && self
+                .device_id
+                .as_deref()
+                .is_none_or(|d| schedule.device_id.as_deref() == Some(d))
+            && self.enabled.is_none_or(|e| schedule.enabled == e)
+            && self
+                .fleets
+                .as_ref()
+                .is_none_or(|fleets| fleets.contains(&schedule.fleet_id))
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+fn not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("schedule {id} not found"))
+}
+
+/// Store a new schedule.
+pub async fn create(state: &AppState, schedule: &Schedule) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        crate::db::schedules::insert(pool, schedule)
+            .await
+            .map_err(internal)?;
+    } else {
+        state.schedules.write().await.push(schedule.clone());
+    }
+    tracing::info!(
+        schedule_id = %schedule.id,
+        name = %schedule.name,
+        cron = %schedule.cron,
+        fleet_id = %schedule.fleet_id,
+        device_id = ?schedule.device_id,
+        next_run_at = ?schedule.next_run_at,
+        "schedule created"
+    );
+    Ok(())
+}
+
+/// Schedules matching `filter`, oldest first.
+pub async fn list(state: &AppState, filter: &ScheduleFilter) -> ApiResult<Vec<Schedule>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::schedules::list(pool, filter)
+            .await
+            .map_err(internal)?;
+        return Ok(rows.into_iter().map(Into::into).collect());
+    }
+    let schedules = state.schedules.read().await;
+    Ok(schedules
+        .iter()
+        .filter(|s| filter.matches(s))
+        .cloned()
+        .collect())
+}
+
+/// A schedule by ID.
+pub async fn get(state: &AppState, id: Uuid) -> ApiResult<Schedule> {
+    let schedule = if let Some(pool) = &state.pool {
+        crate::db::schedules::get(pool, id)
+            .await
+            .map_err(internal)?
+            .map(Into::into)
+    } else {
+        state
+            .schedules
+            .read()
+            .await
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+    };
+    schedule.ok_or_else(|| not_found(id))
+}
+
+/// Pause or resume a schedule. Resuming picks the next slot after now, so
+/// slots missed while paused are skipped.
+pub async fn set_enabled(state: &AppState, id: Uuid, enabled: bool) -> ApiResult<Schedule> {
+    let mut schedule = get(state, id).await?;
+    let now = Utc::now();
+    schedule.enabled = enabled;
+    schedule.next_run_at = if enabled {
+        schedule.cron()?.next_after(now)
+    } else {
+        None
+    };
+    schedule.updated_at = now;
+    if let Some(pool) = &state.pool {
+        crate::db::schedules::set_enabled(pool, &schedule)
+            .await
+            .map_err(internal)?;
+    } else {
+        let mut schedules = state.schedules.write().await;
+        let stored = schedules
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or_else(|| not_found(id))?;
+        *stored = schedule.clone();
+    }
+    tracing::info!(schedule_id = %id, enabled, "schedule updated");
+    Ok(schedule)
+}
+
+/// Delete a schedule and its run history.
+pub async fn delete(state: &AppState, id: Uuid) -> ApiResult<()> {
+    let deleted = if let Some(pool) = &state.pool {
+        crate::db::schedules::delete(pool, id)
+            .await
+            .map_err(internal)?
+    } else {
+        let mut schedules = state.schedules.write().await;
+        let before = schedules.len();
+        schedules.retain(|s| s.id != id);
+        state
+            .schedule_runs
+            .write()
+            .await
+            .retain(|r| r.schedule_id != id);
+        schedules.len() != before
+    };
+    if !deleted {
+        return Err(not_found(id));
+    }
+    tracing::info!(schedule_id = %id, "schedule deleted");
+    Ok(())
+}
+
+/// One page of a schedule's runs (newest first) and the total count.
+pub async fn runs(
+    state: &AppState,
+    id: Uuid,
+    limit: u32,
+    offset: u32,
+) -> ApiResult<(Vec<ScheduleRun>, u64)> {
+    if let Some(pool) = &state.pool {
+        let (rows, total) = tokio::try_join!(
+            crate::db::schedules::list_runs(pool, id, limit, offset),
+            crate::db::schedules::count_runs(pool, id),
+        )
+        .map_err(internal)?;
+        return Ok((rows.into_iter().map(Into::into).collect(), total as u64));
+    }
+    let runs = state.schedule_runs.read().await;
+    let matching: Vec<ScheduleRun> = runs
+        .iter()
+        .rev()
+        .filter(|r| r.schedule_id == id)
+        .cloned()
+        .collect();
+    let total = matching.len() as u64;
+    Ok((
+        crate::routes::pagination::slice(matching, offset, limit),
+        total,
+    ))
+}
+
+/// Fire every schedule due at `now`; returns the runs recorded.
+pub async fn tick(state: &AppState, now: DateTime<Utc>) -> ApiResult<Vec<ScheduleRun>> {
+    let due: Vec<Schedule> = if let Some(pool) = &state.pool {
+        crate::db::schedules::list_due(pool, now)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    } else {
+        let schedules = state.schedules.read().await;
+        schedules.iter().filter(|s| s.due(now)).cloned().collect()
+    };
+
+    let mut runs = Vec::with_capacity(due.len());
+    for schedule in due {
+        let Some(scheduled_for) = schedule.next_run_at else {
+            continue;
+        };
+        if !claim(state, &schedule, scheduled_for, now).await? {
+            continue;
+        }
+        let run = fire(state, &schedule, scheduled_for, now).await;
+        record(state, &run).await?;
+        runs.push(run);
+    }
+    Ok(runs)
+}
+
+/// Advance the schedule past `scheduled_for`. `false` if someone else
+/// already did (another replica, or a pause in between).
+async fn claim(
+    state: &AppState,
+    schedule: &Schedule,
+    scheduled_for: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> ApiResult<bool> {
+    let next = Cron::parse(&schedule.cron)
+        .ok()
+        .and_then(|cron| cron.next_after(now));
+    if let Some(pool) = &state.pool {
+        return crate::db::schedules::claim(pool, schedule.id, scheduled_for, next, now)
+            .await
+            .map_err(internal);
+    }
+    let mut schedules = state.schedules.write().await;
+    match schedules
+        .iter_mut()
+        .find(|s| s.id == schedule.id && s.due(now) && s.next_run_at == Some(scheduled_for))
+    {
+        Some(stored) => {
+            stored.next_run_at = next;
+            stored.last_run_at = Some(now);
+            stored.run_count += 1;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Dispatch the schedule's command through the regular command endpoints.
+async fn fire(
+    state: &AppState,
+    schedule: &Schedule,
+    scheduled_for: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> ScheduleRun {
+    let mut run = ScheduleRun {
+        id: Uuid::now_v7(),
+        schedule_id: schedule.id,
+        scheduled_for,
+        fired_at: now,
+        command_ids: Vec::new(),
+        broadcast_id: None,
+        error: None,
+    };
+    let result = match &schedule.device_id {
+        Some(device_id) => send_command(
+            State(state.clone()),
+            None,
+            HeaderMap::new(),
+            Json(SendCommandRequest {
+                device_id: device_id.clone(),
+                fleet_id: schedule.fleet_id.clone(),
+                command: schedule.command.clone(),
+                tool_name: schedule.tool_name.clone(),
+                tool_args: schedule.tool_args.clone(),
+                initiated_by: schedule.created_by.clone(),
+                preflight: false,
+                force: false,
+                conversation_id: None,
+                idempotency_key: None,
+            }),
+        )
+        .await
+        .map(|Json(envelope)| run.command_ids.push(envelope.id)),
+        None => broadcast_command(
+            State(state.clone()),
+            Path(schedule.fleet_id.clone()),
+            None,
+            Json(BroadcastCommandRequest {
+                command: schedule.command.clone(),
+                tool_name: schedule.tool_name.clone(),
+                tool_args: schedule.tool_args.clone(),
+                initiated_by: schedule.created_by.clone(),
+                tags: schedule.tags.clone(),
+            }),
+        )
+        .await
+        .map(|Json(summary)| {
+            run.broadcast_id = Some(summary.broadcast_id);
+            run.command_ids = summary.devices.iter().map(|d| d.command_id).collect();
+        }),
+    };
+    match result {
+        Ok(()) => tracing::info!(
+            schedule_id = %schedule.id,
+            name = %schedule.name,
+            commands = run.command_ids.len(),
+            "schedule fired"
+        ),
+        Err(e) => {
+            tracing::warn!(schedule_id = %schedule.id, error = %e, "scheduled dispatch failed");
+            run.error = Some(e.to_string());
+        }
+    }
+    run
+}
+
+async fn record(state: &AppState, run: &ScheduleRun) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        return crate::db::schedules::insert_run(pool, run)
+            .await
+            .map_err(internal);
+    }
+    let mut runs = state.schedule_runs.write().await;
+    runs.push(run.clone());
+    let kept = runs
+        .iter()
+        .filter(|r| r.schedule_id == run.schedule_id)
+        .count();
+    if kept > MAX_RUNS_KEPT
+        && let Some(oldest) = runs.iter().position(|r| r.schedule_id == run.schedule_id)
+    {
+        runs.remove(oldest);
+    }
+    Ok(())
+}
+
+/// Scheduler loop: fire due schedules every [`TICK_SECS`].
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(StdDuration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&state, Utc::now()).await {
+            tracing::error!(error = %e, "scheduler tick failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn schedule(device_id: Option<&str>, next_run_at: DateTime<Utc>) -> Schedule {
+        let now = at("2024-01-15T00:00:00Z");
+        Schedule {
+            id: Uuid::now_v7(),
+            name: "nightly dtc scan".into(),
+            cron: "0 2 * * *".into(),
+            fleet_id: "fleet-alpha".into(),
+            device_id: device_id.map(String::from),
+            tags: Vec::new(),
+            command: "read DTCs".into(),
+            tool_name: None,
+            tool_args: None,
+            enabled: true,
+            created_by: "alice".into(),
+            created_at: now,
+            updated_at: now,
+            next_run_at: Some(next_run_at),
+            last_run_at: None,
+            run_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn due_schedules_fire_once_per_slot() {
+        let state = AppState::with_sample_data();
+        let now = at("2024-01-15T12:00:00Z");
+        let device = schedule(Some("rpi-001"), now - Duration::minutes(1));
+        let fleet = schedule(None, now - Duration::minutes(5));
+        let later = schedule(Some("rpi-002"), now + Duration::hours(1));
+        for s in [&device, &fleet, &later] {
+            create(&state, s).await.unwrap();
+        }
+
+        let runs = tick(&state, now).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        let device_run = runs.iter().find(|r| r.schedule_id == device.id).unwrap();
+        assert_eq!(device_run.command_ids.len(), 1);
+        assert!(device_run.error.is_none());
+        let fleet_run = runs.iter().find(|r| r.schedule_id == fleet.id).unwrap();
+        assert!(fleet_run.broadcast_id.is_some());
+        assert_eq!(fleet_run.command_ids.len(), 2);
+        assert_eq!(state.commands.read().await.len(), 3);
+
+        // Advanced to the next 02:00 slot; a second tick fires nothing.
+        let stored = get(&state, device.id).await.unwrap();
+        assert_eq!(stored.run_count, 1);
+        assert_eq!(stored.last_run_at, Some(now));
+        assert_eq!(stored.next_run_at, Some(at("2024-01-16T02:00:00Z")));
+        assert!(tick(&state, now).await.unwrap().is_empty());
+
+        // Paused schedules do not fire.
+        set_enabled(&state, later.id, false).await.unwrap();
+        assert!(
+            tick(&state, now + Duration::hours(2))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_dispatch_is_recorded() {
+        let state = AppState::with_sample_data();
+        let now = Utc::now();
+        let gone = schedule(Some("rpi-999"), now);
+        create(&state, &gone).await.unwrap();
+
+        let runs = tick(&state, now).await.unwrap();
+        assert!(runs[0].error.as_deref().unwrap().contains("rpi-999"));
+        let (history, total) = super::runs(&state, gone.id, 50, 0).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(history[0].id, runs[0].id);
+        assert!(get(&state, gone.id).await.unwrap().next_run_at.unwrap() > now);
+    }
+}
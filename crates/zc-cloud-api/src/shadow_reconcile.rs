@@ -0,0 +1,470 @@
+//! Re-delivery of shadow deltas a device missed while offline.
+//!
+//! A delta is published when desired state is set and whenever the device
+//! reports state that still differs from it. If the device is offline at
+//! that moment the message is lost, so on every heartbeat — over MQTT or
+//! REST — [`reconcile`] re-publishes each outstanding delta for the device.
+//!
+//! Re-delivery backs off exponentially from [`RETRY_BACKOFF_SECS`] and stops
+//! after [`MAX_ATTEMPTS`] deliveries of the same delta: the shadow is then
+//! `exhausted` until desired state is set again. A reported update that
+//! clears the delta marks the shadow `converged`.
+//!
+//! Tracking is kept in memory in both storage modes; after a restart every
+//! outstanding delta starts with a fresh retry budget.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use zc_protocol::shadows::ShadowDelta;
+use zc_protocol::topics;
+
+use crate::events::WsEvent;
+use crate::mqtt_bridge::compute_delta;
+use crate::state::AppState;
+
+/// Deliveries of one delta before re-delivery gives up.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Minimum gap between heartbeat-triggered re-deliveries, doubled after
+/// each attempt (60s, 120s, 240s, ...).
+pub const RETRY_BACKOFF_SECS: i64 = 60;
+
+/// Where a shadow's outstanding delta stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileStatus {
+    /// Delta published, device has not yet reported matching state.
+    Pending,
+    /// Reported state caught up with desired state.
+    Converged,
+    /// [`MAX_ATTEMPTS`] deliveries without convergence.
+    Exhausted,
+}
+
+impl ReconcileStatus {
+    /// The serialized name.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Converged => "converged",
+            Self::Exhausted => "exhausted",
+        }
+    }
+}
+
+/// Delivery history of a shadow's current delta.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Reconciliation {
+    pub status: ReconcileStatus,
+    /// Deliveries of the current delta.
+    pub attempts: u32,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub converged_at: Option<DateTime<Utc>>,
+    /// The delta being delivered; a different delta starts a new budget.
+    #[serde(skip)]
+    delta: serde_json::Value,
+}
+
+impl Reconciliation {
+    fn new(delta: &serde_json::Value) -> Self {
+        Self {
+            status: ReconcileStatus::Pending,
+            attempts: 0,
+            last_attempt_at: None,
+            converged_at: None,
+            delta: delta.clone(),
+        }
+    }
+}
+
+/// Outcome of asking to deliver a delta.
+#[derive(Debug, PartialEq, Eq)]
+enum Attempt {
+    /// Publish now; this is delivery number `n`.
+    Publish(u32),
+    /// A retry is not due yet.
+    Wait,
+    /// The budget is spent; `first` when this call exhausted it.
+    Exhausted { first: bool },
+}
+
+/// Per-shadow delivery tracking, keyed by (device_id, shadow_name).
+#[derive(Debug, Default)]
+pub struct ReconcileTracker {
+    entries: Mutex<HashMap<(String, String), Reconciliation>>,
+}
+
+impl ReconcileTracker {
+    /// Delivery history for a shadow, if a delta was ever published.
+    pub fn get(&self, device_id: &str, shadow_name: &str) -> Option<Reconciliation> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(device_id.to_string(), shadow_name.to_string()))
+            .cloned()
+    }
+
+    /// Forget a shadow's history (desired state was set again).
+    pub fn reset(&self, device_id: &str, shadow_name: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&(device_id.to_string(), shadow_name.to_string()));
+    }
+
+    /// Record that reported state matches desired state. Returns the number
+    /// of deliveries it took if the shadow was not already converged.
+    pub fn converged(&self, device_id: &str, shadow_name: &str, now: DateTime<Utc>) -> Option<u32> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get_mut(&(device_id.to_string(), shadow_name.to_string()))?;
+        if entry.status == ReconcileStatus::Converged {
+            return None;
+        }
+        entry.status = ReconcileStatus::Converged;
+        entry.converged_at = Some(now);
+        Some(entry.attempts)
+    }
+
+    /// Decide whether `delta` may be published now, and record the attempt
+    /// if so. `retry` applies the backoff (heartbeat-triggered re-delivery).
+    fn begin_attempt(
+        &self,
+        device_id: &str,
+        shadow_name: &str,
+        delta: &serde_json::Value,
+        retry: bool,
+        now: DateTime<Utc>,
+    ) -> Attempt {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries
+            .entry((device_id.to_string(), shadow_name.to_string()))
+            .or_insert_with(|| Reconciliation::new(delta));
+        if entry.delta != *delta || entry.status == ReconcileStatus::Converged {
+            *entry = Reconciliation::new(delta);
+        }
+
+        if entry.attempts >= MAX_ATTEMPTS {
+            let first = entry.status != ReconcileStatus::Exhausted;
+            entry.status = ReconcileStatus::Exhausted;
+            return Attempt::Exhausted { first };
+        }
+        if retry
+            && let Some(last) = entry.last_attempt_at
+            && now - last < backoff(entry.attempts)
+        {
+            return Attempt::Wait;
+        }
+
+        entry.attempts += 1;
+        entry.last_attempt_at = Some(now);
+        Attempt::Publish(entry.attempts)
+    }
+}
+
+/// Wait before the retry that follows delivery number `attempts`.
+fn backoff(attempts: u32) -> Duration {
+    Duration::seconds(RETRY_BACKOFF_SECS << attempts.saturating_sub(1).min(4))
+}
+
+/// Publish a shadow delta unless its retry budget is spent. Returns whether
+/// it was published.
+pub async fn publish_delta(
+    state: &AppState,
+    fleet_id: &str,
+    device_id: &str,
+    shadow_name: &str,
+    delta: serde_json::Value,
+    version: u64,
+) -> bool {
+    deliver(
+        state,
+        fleet_id,
+        device_id,
+        shadow_name,
+        delta,
+        version,
+        false,
+    )
+    .await
+}
+
+/// Record that a shadow's reported state caught up with desired state.
+pub fn mark_converged(state: &AppState, device_id: &str, shadow_name: &str) {
+    let now = Utc::now();
+    if let Some(attempts) = state
+        .shadow_reconcile
+        .converged(device_id, shadow_name, now)
+    {
+        tracing::info!(
+            device_id = device_id,
+            shadow = shadow_name,
+            attempts = attempts,
+            "shadow converged"
+        );
+        let _ = state.event_tx.send(WsEvent::ShadowReconcile {
+            device_id: device_id.to_string(),
+            shadow_name: shadow_name.to_string(),
+            status: ReconcileStatus::Converged.as_str().into(),
+            attempts: attempts as u64,
+            timestamp: now,
+        });
+    }
+}
+
+/// Re-publish every outstanding delta for `device_id` whose retry is due.
+/// Returns the number re-published.
+///
+/// No-op when the MQTT bridge is not connected.
+pub async fn reconcile(state: &AppState, fleet_id: &str, device_id: &str) -> usize {
+    if state.mqtt.is_none() {
+        return 0;
+    }
+
+    let shadows: Vec<(String, serde_json::Value, u64)> = match state
+        .store
+        .list_shadows(device_id)
+        .await
+    {
+        Ok(shadows) => shadows
+            .into_iter()
+            .map(|(name, s)| (name, compute_delta(&s.desired, &s.reported), s.version))
+            .collect(),
+        Err(e) => {
+            tracing::error!(error = %e, device_id = device_id, "failed to load shadows for reconciliation");
+            return 0;
+        }
+    };
+
+    let mut republished = 0;
+    for (shadow_name, delta, version) in shadows {
+        if delta.as_object().is_none_or(|o| o.is_empty()) {
+            continue;
+        }
+        if deliver(
+            state,
+            fleet_id,
+            device_id,
+            &shadow_name,
+            delta,
+            version,
+            true,
+        )
+        .await
+        {
+            republished += 1;
+        }
+    }
+
+    if republished > 0 {
+        tracing::info!(
+            device_id = device_id,
+            count = republished,
+            "re-published outstanding shadow deltas"
+        );
+    }
+    republished
+}
+
+async fn deliver(
+    state: &AppState,
+    fleet_id: &str,
+    device_id: &str,
+    shadow_name: &str,
+    delta: serde_json::Value,
+    version: u64,
+    retry: bool,
+) -> bool {
+    let Some(mqtt) = &state.mqtt else {
+        return false;
+    };
+
+    let now = Utc::now();
+    let attempt =
+        match state
+            .shadow_reconcile
+            .begin_attempt(device_id, shadow_name, &delta, retry, now)
+        {
+            Attempt::Publish(n) => n,
+            Attempt::Wait => return false,
+            Attempt::Exhausted { first } => {
+                if first {
+                    tracing::warn!(
+                        device_id = device_id,
+                        shadow = shadow_name,
+                        attempts = MAX_ATTEMPTS,
+                        "shadow delta not applied, giving up re-delivery"
+                    );
+                    let _ = state.event_tx.send(WsEvent::ShadowReconcile {
+                        device_id: device_id.to_string(),
+                        shadow_name: shadow_name.to_string(),
+                        status: ReconcileStatus::Exhausted.as_str().into(),
+                        attempts: MAX_ATTEMPTS as u64,
+                        timestamp: now,
+                    });
+                }
+                return false;
+            }
+        };
+
+    let shadow_delta = ShadowDelta {
+        device_id: device_id.to_string(),
+        shadow_name: shadow_name.to_string(),
+        delta,
+        version,
+        timestamp: now,
+    };
+    let encoding = crate::command_queue::device_encoding(state, device_id).await;
+    let payload = match zc_protocol::encoding::encode(&shadow_delta, encoding) {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to serialize shadow delta");
+            return false;
+        }
+    };
+    let topic = topics::shadow_delta(fleet_id, device_id);
+    if let Err(e) = mqtt
+        .publish(&topic, &payload, rumqttc::QoS::AtLeastOnce)
+        .await
+    {
+        tracing::error!(error = %e, "failed to publish shadow delta");
+        return false;
+    }
+
+    if attempt > 1 {
+        let _ = state.event_tx.send(WsEvent::ShadowReconcile {
+            device_id: device_id.to_string(),
+            shadow_name: shadow_name.to_string(),
+            status: ReconcileStatus::Pending.as_str().into(),
+            attempts: attempt as u64,
+            timestamp: now,
+        });
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use zc_mqtt_channel::MockChannel;
+    use zc_protocol::shadows::ShadowState;
+
+    const DELTA_TOPIC: &str = "fleet/fleet-alpha/rpi-001/shadow/delta";
+
+    async fn state_with_outstanding_delta() -> (AppState, Arc<MockChannel>) {
+        let mqtt = Arc::new(MockChannel::new());
+        let mut state = AppState::with_sample_data();
+        state.mqtt = Some(mqtt.clone());
+        state.shadows.write().await.insert(
+            ("rpi-001".to_string(), "config".to_string()),
+            ShadowState {
+                reported: serde_json::json!({"firmware": "0.1.0"}),
+                desired: serde_json::json!({"firmware": "0.2.0"}),
+                version: 2,
+                last_updated: Utc::now(),
+            },
+        );
+        (state, mqtt)
+    }
+
+    #[tokio::test]
+    async fn heartbeat_republishes_outstanding_delta() {
+        let (state, mqtt) = state_with_outstanding_delta().await;
+
+        assert_eq!(reconcile(&state, "fleet-alpha", "rpi-001").await, 1);
+        let msgs = mqtt.published_to(DELTA_TOPIC);
+        assert_eq!(msgs.len(), 1);
+        let delta: ShadowDelta = serde_json::from_slice(&msgs[0].payload).unwrap();
+        assert_eq!(delta.delta["firmware"], "0.2.0");
+        assert_eq!(delta.version, 2);
+
+        // The next heartbeat is inside the backoff window.
+        assert_eq!(reconcile(&state, "fleet-alpha", "rpi-001").await, 0);
+        assert_eq!(mqtt.published_to(DELTA_TOPIC).len(), 1);
+        let tracked = state.shadow_reconcile.get("rpi-001", "config").unwrap();
+        assert_eq!(tracked.status, ReconcileStatus::Pending);
+        assert_eq!(tracked.attempts, 1);
+    }
+
+    #[test]
+    fn retries_back_off_and_stop_at_cap() {
+        let tracker = ReconcileTracker::default();
+        let delta = serde_json::json!({"mode": "debug", "level": 3});
+        let mut now = Utc::now();
+
+        assert_eq!(
+            tracker.begin_attempt("rpi-001", "config", &delta, true, now),
+            Attempt::Publish(1)
+        );
+        now += Duration::seconds(RETRY_BACKOFF_SECS - 1);
+        assert_eq!(
+            tracker.begin_attempt("rpi-001", "config", &delta, true, now),
+            Attempt::Wait
+        );
+        for n in 2..=MAX_ATTEMPTS {
+            now += backoff(n - 1);
+            assert_eq!(
+                tracker.begin_attempt("rpi-001", "config", &delta, true, now),
+                Attempt::Publish(n)
+            );
+        }
+
+        // Reported updates do not get past the cap either.
+        assert_eq!(
+            tracker.begin_attempt("rpi-001", "config", &delta, false, now),
+            Attempt::Exhausted { first: true }
+        );
+        assert_eq!(
+            tracker.begin_attempt("rpi-001", "config", &delta, true, now + Duration::days(1)),
+            Attempt::Exhausted { first: false }
+        );
+
+        // Progress (a smaller delta) or a new desired state starts over.
+        let progressed = serde_json::json!({"level": 3});
+        assert_eq!(
+            tracker.begin_attempt("rpi-001", "config", &progressed, false, now),
+            Attempt::Publish(1)
+        );
+        tracker.reset("rpi-001", "config");
+        assert!(tracker.get("rpi-001", "config").is_none());
+    }
+
+    #[tokio::test]
+    async fn convergence_stops_redelivery() {
+        let (state, mqtt) = state_with_outstanding_delta().await;
+        let mut rx = state.event_tx.subscribe();
+        let delta = serde_json::json!({"firmware": "0.2.0"});
+        assert!(publish_delta(&state, "fleet-alpha", "rpi-001", "config", delta, 2).await);
+
+        // Device applies the change.
+        state
+            .shadows
+            .write()
+            .await
+            .get_mut(&("rpi-001".to_string(), "config".to_string()))
+            .unwrap()
+            .reported = serde_json::json!({"firmware": "0.2.0"});
+        mark_converged(&state, "rpi-001", "config");
+
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.event_type(), "shadow_reconcile");
+        let frame = event.to_frame();
+        assert_eq!(frame["status"], "converged");
+        assert_eq!(frame["attempts"], 1);
+
+        let tracked = state.shadow_reconcile.get("rpi-001", "config").unwrap();
+        assert_eq!(tracked.status, ReconcileStatus::Converged);
+        assert!(tracked.converged_at.is_some());
+        assert_eq!(reconcile(&state, "fleet-alpha", "rpi-001").await, 0);
+        assert_eq!(mqtt.published_to(DELTA_TOPIC).len(), 1);
+
+        // Already converged: no second event.
+        mark_converged(&state, "rpi-001", "config");
+        assert!(rx.try_recv().is_err());
+    }
+}
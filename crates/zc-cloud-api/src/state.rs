@@ -3,8 +3,11 @@
 //! Supports two modes:
 //! - **Database mode**: uses `PgPool` for persistent storage (production).
 //! - **In-memory mode**: uses `RwLock<HashMap>` (tests and development).
+//!
+//! The registry, command log, telemetry and shadows are reached through
+//! [`AppState::store`] in both modes (see [`crate::store`]).
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
@@ -12,18 +15,51 @@ use sqlx::PgPool;
 use tokio::sync::{RwLock, broadcast};
 use uuid::Uuid;
 
-use zc_protocol::commands::{CommandEnvelope, CommandResponse};
+use zc_protocol::commands::{CommandEnvelope, CommandResponse, CommandStatus};
 use zc_protocol::device::{DeviceInfo, DeviceStatus, HardwareType};
+use zc_protocol::encoding::Encoding;
+use zc_protocol::self_test::SelfTestReport;
 use zc_protocol::shadows::ShadowState;
 
+use crate::alert_rules::RuleEngine;
+use crate::alerts::Alert;
+use crate::approval::ApprovalPolicy;
+use crate::audit::AuditEntry;
+use crate::auth::OidcVerifier;
+use crate::cert_expiry::CertExpiryMonitor;
+use crate::certificates::CertificateAuthority;
+use crate::command_limits::CommandRateLimiter;
+use crate::command_retries::RetryPolicy;
+use crate::command_timing::{ClockSkew, CommandTiming};
+use crate::db::telemetry::TelemetryRow;
+use crate::device_identity::{AliasKind, DeviceAlias};
+use crate::device_status::{StatusChange, StatusThresholds};
+use crate::device_tags::Tags;
+use crate::dtc_history::DtcScan;
+use crate::dtc_knowledge::DtcKnowledge;
 use crate::events::WsEvent;
+use crate::experiments::{Assignment, Experiment};
+use crate::failure_rates::FailureRateTracker;
+use crate::file_transfers::FileTransfer;
 use crate::inference::InferenceEngine;
+use crate::metrics::Metrics;
+use crate::mqtt_bridge::FleetFilter;
+use crate::mqtt_quarantine::Quarantine;
+use crate::retention::RetentionPolicy;
+use crate::schedules::{Schedule, ScheduleRun};
+use crate::shadow_reconcile::ReconcileTracker;
+use crate::store::{MemoryStore, PgStore, Store};
+use crate::structured_mode::StructuredMode;
+use crate::webhooks::{Delivery, Webhook};
 
 /// Shared application state, wrapped in `Arc` for Axum handler sharing.
 #[derive(Clone)]
 pub struct AppState {
     /// PostgreSQL connection pool (None in test/in-memory mode).
     pub pool: Option<PgPool>,
+    /// Device registry, command log, telemetry, shadows, claim codes,
+    /// templates, fleets and agent releases, in whichever backend is active.
+    pub store: Arc<dyn Store>,
     /// In-memory device registry (used when pool is None).
     pub devices: Arc<RwLock<HashMap<String, DeviceInfo>>>,
     /// In-memory command log (used when pool is None).
@@ -36,6 +72,81 @@ pub struct AppState {
     pub mqtt: Option<Arc<dyn zc_mqtt_channel::Channel>>,
     /// In-memory shadow store: (device_id, shadow_name) -> ShadowState.
     pub shadows: Arc<RwLock<HashMap<(String, String), ShadowState>>>,
+    /// Delivery tracking for outstanding shadow deltas (both storage modes).
+    pub shadow_reconcile: Arc<ReconcileTracker>,
+    /// In-memory telemetry readings in `(time, metric_name)` order, capped
+    /// at [`crate::store::TELEMETRY_CAPACITY`] (used when pool is None).
+    pub telemetry: Arc<RwLock<Vec<TelemetryRow>>>,
+    /// In-memory inference experiments (used when pool is None).
+    pub experiments: Arc<RwLock<Vec<Experiment>>>,
+    /// In-memory experiment assignments keyed by command ID.
+    pub experiment_assignments: Arc<RwLock<HashMap<Uuid, Assignment>>>,
+    /// In-memory latest self-test report per device (used when pool is None).
+    pub self_tests: Arc<RwLock<HashMap<String, SelfTestReport>>>,
+    /// In-memory DTC knowledge base keyed by code (used when pool is None).
+    pub dtc_knowledge: Arc<RwLock<HashMap<String, DtcKnowledge>>>,
+    /// In-memory DTC scans per device, oldest first (used when pool is None).
+    pub dtc_history: Arc<RwLock<HashMap<String, Vec<DtcScan>>>>,
+    /// In-memory device aliases keyed by (kind, normalized value) (used when pool is None).
+    pub device_aliases: Arc<RwLock<HashMap<(AliasKind, String), DeviceAlias>>>,
+    /// In-memory device tags keyed by device ID (used when pool is None).
+    pub device_tags: Arc<RwLock<HashMap<String, Tags>>>,
+    /// Prometheus counters and histograms served at `/metrics`.
+    pub metrics: Arc<Metrics>,
+    /// Fleets the MQTT bridge handles (all unless `MQTT_FLEET_ID` lists some).
+    pub mqtt_fleets: FleetFilter,
+    /// Connection health of the MQTT bridge (None when MQTT disabled).
+    pub mqtt_health: Option<Arc<zc_mqtt_channel::ConnectionMonitor>>,
+    /// MQTT payloads that failed to deserialize (both modes).
+    pub mqtt_quarantine: Arc<Quarantine>,
+    /// Two-person approval policy for high-risk commands (off unless `APPROVERS` is set).
+    pub approval: Arc<ApprovalPolicy>,
+    /// OIDC token verifier (auth is off when `None`, i.e. `OIDC_ISSUER` unset).
+    pub oidc: Option<Arc<OidcVerifier>>,
+    /// CA issuing device certificates (None unless `PROVISIONING_CA_*` is set).
+    pub certificate_authority: Option<Arc<CertificateAuthority>>,
+    /// Certificate expiry warning window and the certificates alerted on.
+    pub cert_expiry: Arc<CertExpiryMonitor>,
+    /// Rolling per-tool failure rates and their alert thresholds.
+    pub failure_rates: Arc<FailureRateTracker>,
+    /// Fleets limited to structured commands (none unless `STRUCTURED_ONLY_FLEETS` is set).
+    pub structured_mode: Arc<StructuredMode>,
+    /// In-memory audit log, oldest first, capped at
+    /// [`crate::audit::MEMORY_CAPACITY`] (used when pool is None).
+    pub audit_log: Arc<RwLock<VecDeque<AuditEntry>>>,
+    /// In-memory alerts, oldest first (used when pool is None).
+    pub alerts: Arc<RwLock<Vec<Alert>>>,
+    /// Alert rules and their firing state.
+    pub alert_rules: Arc<RuleEngine>,
+    /// In-memory command schedules, oldest first (used when pool is None).
+    pub schedules: Arc<RwLock<Vec<Schedule>>>,
+    /// In-memory schedule runs, oldest first (used when pool is None).
+    pub schedule_runs: Arc<RwLock<Vec<ScheduleRun>>>,
+    /// Heartbeat ages at which devices turn degraded and offline.
+    pub status_thresholds: StatusThresholds,
+    /// How long telemetry and heartbeats are kept.
+    pub retention: RetentionPolicy,
+    /// Automatic re-publishing of commands whose publish failed.
+    pub retry_policy: RetryPolicy,
+    /// In-memory device status transitions, oldest first (used when pool is None).
+    pub status_history: Arc<RwLock<Vec<StatusChange>>>,
+    /// In-memory webhooks, oldest first (used when pool is None).
+    pub webhooks: Arc<RwLock<Vec<Webhook>>>,
+    /// In-memory webhook deliveries, oldest first (used when pool is None).
+    pub webhook_deliveries: Arc<RwLock<Vec<Delivery>>>,
+    /// SHA-256 of the super-admin bearer token (none unless
+    /// `SUPER_ADMIN_TOKEN` is set).
+    pub super_admin_token_sha256: Option<String>,
+    /// File transfers to and from devices (in memory in both modes).
+    pub file_transfers: Arc<RwLock<HashMap<Uuid, FileTransfer>>>,
+    /// Encoding each device reads commands in, from its latest heartbeat
+    /// (both modes; JSON for devices not listed).
+    pub device_encodings: Arc<RwLock<HashMap<String, Encoding>>>,
+    /// Clock offset estimates per device, from recent heartbeats (both
+    /// modes).
+    pub clock_skew: Arc<RwLock<HashMap<String, ClockSkew>>>,
+    /// Rate limits on command submission (unlimited until configured).
+    pub command_limits: Arc<CommandRateLimiter>,
 }
 
 /// A command with its response (if available).
@@ -43,14 +154,32 @@ pub struct AppState {
 pub struct CommandRecord {
     pub envelope: CommandEnvelope,
     pub response: Option<CommandResponse>,
+    /// Cloud-side lifecycle status (`Queued` until flushed to the device).
+    pub status: CommandStatus,
     pub created_at: DateTime<Utc>,
+    /// When the command times out if still pending (set once it is
+    /// published to the device).
+    pub deadline: Option<DateTime<Utc>>,
+    /// Publish attempts made so far.
+    pub attempts: u32,
+    /// Publish attempts allowed before the command fails with
+    /// `delivery_failed` (see [`crate::command_retries`]).
+    pub max_attempts: u32,
+    /// When the next publish is due after a failed one.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// Client-supplied key that makes resubmission return this command.
+    pub idempotency_key: Option<String>,
+    /// Cloud-side publish and receive times.
+    pub timing: CommandTiming,
 }
 
 impl AppState {
     /// Create state backed by a PostgreSQL pool with a custom inference engine.
     pub fn with_pool(pool: PgPool, inference: Arc<dyn InferenceEngine>) -> Self {
         let (event_tx, _) = broadcast::channel(256);
+        let metrics = Arc::new(Metrics::new());
         Self {
+            store: Arc::new(PgStore::new(pool.clone(), metrics.clone())),
             pool: Some(pool),
             devices: Arc::new(RwLock::new(HashMap::new())),
             commands: Arc::new(RwLock::new(Vec::new())),
@@ -58,20 +187,108 @@ impl AppState {
             inference,
             mqtt: None,
             shadows: Arc::new(RwLock::new(HashMap::new())),
+            shadow_reconcile: Arc::new(ReconcileTracker::default()),
+            telemetry: Arc::new(RwLock::new(Vec::new())),
+            experiments: Arc::new(RwLock::new(Vec::new())),
+            experiment_assignments: Arc::new(RwLock::new(HashMap::new())),
+            self_tests: Arc::new(RwLock::new(HashMap::new())),
+            dtc_knowledge: Arc::new(RwLock::new(HashMap::new())),
+            dtc_history: Arc::new(RwLock::new(HashMap::new())),
+            device_aliases: Arc::new(RwLock::new(HashMap::new())),
+            device_tags: Arc::new(RwLock::new(HashMap::new())),
+            metrics,
+            mqtt_fleets: FleetFilter::All,
+            mqtt_health: None,
+            mqtt_quarantine: Arc::new(Quarantine::default()),
+            approval: Arc::new(ApprovalPolicy::default()),
+            oidc: None,
+            certificate_authority: None,
+            cert_expiry: Arc::new(CertExpiryMonitor::default()),
+            failure_rates: Arc::new(FailureRateTracker::default()),
+            structured_mode: Arc::new(StructuredMode::default()),
+            audit_log: Arc::new(RwLock::new(VecDeque::new())),
+            alerts: Arc::new(RwLock::new(Vec::new())),
+            alert_rules: Arc::new(RuleEngine::default()),
+            schedules: Arc::new(RwLock::new(Vec::new())),
+            schedule_runs: Arc::new(RwLock::new(Vec::new())),
+            status_thresholds: StatusThresholds::default(),
+            retention: RetentionPolicy::default(),
+            retry_policy: RetryPolicy::default(),
+            status_history: Arc::new(RwLock::new(Vec::new())),
+            webhooks: Arc::new(RwLock::new(Vec::new())),
+            webhook_deliveries: Arc::new(RwLock::new(Vec::new())),
+            super_admin_token_sha256: None,
+            file_transfers: Arc::new(RwLock::new(HashMap::new())),
+            device_encodings: Arc::new(RwLock::new(HashMap::new())),
+            clock_skew: Arc::new(RwLock::new(HashMap::new())),
+            command_limits: Arc::new(CommandRateLimiter::default()),
         }
     }
 
     /// Create in-memory state (for tests).
     pub fn new() -> Self {
+        Self::in_memory(HashMap::new())
+    }
+
+    /// In-memory state over `devices`; the store shares its maps with the
+    /// state fields.
+    fn in_memory(devices: HashMap<String, DeviceInfo>) -> Self {
         let (event_tx, _) = broadcast::channel(256);
+        let devices = Arc::new(RwLock::new(devices));
+        let commands = Arc::new(RwLock::new(Vec::new()));
+        let shadows = Arc::new(RwLock::new(HashMap::new()));
+        let telemetry = Arc::new(RwLock::new(Vec::new()));
+        let device_tags = Arc::new(RwLock::new(HashMap::new()));
         Self {
             pool: None,
-            devices: Arc::new(RwLock::new(HashMap::new())),
-            commands: Arc::new(RwLock::new(Vec::new())),
+            store: Arc::new(MemoryStore::new(
+                devices.clone(),
+                commands.clone(),
+                telemetry.clone(),
+                shadows.clone(),
+                device_tags.clone(),
+            )),
+            devices,
+            commands,
             event_tx,
             inference: Arc::new(crate::inference::RuleBasedEngine::new()),
             mqtt: None,
-            shadows: Arc::new(RwLock::new(HashMap::new())),
+            shadows,
+            shadow_reconcile: Arc::new(ReconcileTracker::default()),
+            telemetry,
+            experiments: Arc::new(RwLock::new(Vec::new())),
+            experiment_assignments: Arc::new(RwLock::new(HashMap::new())),
+            self_tests: Arc::new(RwLock::new(HashMap::new())),
+            dtc_knowledge: Arc::new(RwLock::new(HashMap::new())),
+            dtc_history: Arc::new(RwLock::new(HashMap::new())),
+            device_aliases: Arc::new(RwLock::new(HashMap::new())),
+            device_tags,
+            metrics: Arc::new(Metrics::new()),
+            mqtt_fleets: FleetFilter::All,
+            mqtt_health: None,
+            mqtt_quarantine: Arc::new(Quarantine::default()),
+            approval: Arc::new(ApprovalPolicy::default()),
+            oidc: None,
+            certificate_authority: None,
+            cert_expiry: Arc::new(CertExpiryMonitor::default()),
+            failure_rates: Arc::new(FailureRateTracker::default()),
+            structured_mode: Arc::new(StructuredMode::default()),
+            audit_log: Arc::new(RwLock::new(VecDeque::new())),
+            alerts: Arc::new(RwLock::new(Vec::new())),
+            alert_rules: Arc::new(RuleEngine::default()),
+            schedules: Arc::new(RwLock::new(Vec::new())),
+            schedule_runs: Arc::new(RwLock::new(Vec::new())),
+            status_thresholds: StatusThresholds::default(),
+            retention: RetentionPolicy::default(),
+            retry_policy: RetryPolicy::default(),
+            status_history: Arc::new(RwLock::new(Vec::new())),
+            webhooks: Arc::new(RwLock::new(Vec::new())),
+            webhook_deliveries: Arc::new(RwLock::new(Vec::new())),
+            super_admin_token_sha256: None,
+            file_transfers: Arc::new(RwLock::new(HashMap::new())),
+            device_encodings: Arc::new(RwLock::new(HashMap::new())),
+            clock_skew: Arc::new(RwLock::new(HashMap::new())),
+            command_limits: Arc::new(CommandRateLimiter::default()),
         }
     }
 
@@ -102,7 +319,9 @@ impl AppState {
                     vin: None,
                     hardware_type: HardwareType::RaspberryPi4,
                     certificate_id: None,
+                    certificate_expires_at: None,
                     last_heartbeat: Some(now),
+                    vitals: None,
                     metadata: serde_json::json!({"fleet": fleet}),
                     created_at: now,
                     updated_at: now,
@@ -110,16 +329,7 @@ impl AppState {
             );
         }
 
-        let (event_tx, _) = broadcast::channel(256);
-        Self {
-            pool: None,
-            devices: Arc::new(RwLock::new(devices)),
-            commands: Arc::new(RwLock::new(Vec::new())),
-            event_tx,
-            inference: Arc::new(crate::inference::RuleBasedEngine::new()),
-            mqtt: None,
-            shadows: Arc::new(RwLock::new(HashMap::new())),
-        }
+        Self::in_memory(devices)
     }
 }
 
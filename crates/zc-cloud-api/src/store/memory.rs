@@ -0,0 +1,1160 @@
+//! In-memory backend, for development and tests.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use zc_protocol::commands::{CommandEnvelope, CommandResponse, CommandStatus, ErrorCode};
+use zc_protocol::device::{DeviceInfo, DeviceStatus, DeviceVitals, FleetId, HardwareType};
+use zc_protocol::shadows::ShadowState;
+
+use super::{
+    AgentReleaseStore, ClaimCodeStore, CommandOutcome, CommandStore, CommandSummary, DeviceStore,
+    FleetStore, RespondedCommand, ShadowStore, TelemetryStore, TemplateStore, command_status_name,
+};
+use crate::agent_releases::AgentRelease;
+use crate::claim_codes::ClaimCode;
+use crate::command_timing::Receipt;
+use crate::db::commands::CommandFilter;
+use crate::db::devices::DeviceFilter;
+use crate::db::telemetry::{SortOrder, TelemetryFilter, TelemetryRow};
+use crate::device_report::MetricSummary;
+use crate::device_status::status_name;
+use crate::device_tags::Tags;
+use crate::error::ApiResult;
+use crate::fleets::{Fleet, FleetToken};
+use crate::routes::pagination;
+use crate::state::CommandRecord;
+use crate::templates::{CommandTemplate, MAX_RUNS_KEPT, TemplateRun};
+
+/// Telemetry readings kept in memory; the oldest are dropped beyond this.
+pub const TELEMETRY_CAPACITY: usize = 100_000;
+
+/// Store over shared in-memory maps. Nothing survives a restart.
+///
+/// Telemetry is kept in `(time, metric_name)` order, the order pages are
+/// served in, so a page is found by binary search from its cursor.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    devices: Arc<RwLock<HashMap<String, DeviceInfo>>>,
+    commands: Arc<RwLock<Vec<CommandRecord>>>,
+    telemetry: Arc<RwLock<Vec<TelemetryRow>>>,
+    shadows: Arc<RwLock<HashMap<(String, String), ShadowState>>>,
+    device_tags: Arc<RwLock<HashMap<String, Tags>>>,
+    claim_codes: Arc<RwLock<Vec<ClaimCode>>>,
+    /// Oldest first.
+    templates: Arc<RwLock<Vec<CommandTemplate>>>,
+    /// Oldest first, at most [`MAX_RUNS_KEPT`] per template.
+    template_runs: Arc<RwLock<Vec<TemplateRun>>>,
+    fleets: Arc<RwLock<Vec<Fleet>>>,
+    fleet_tokens: Arc<RwLock<Vec<FleetToken>>>,
+    /// Oldest first.
+    agent_releases: Arc<RwLock<Vec<AgentRelease>>>,
+}
+
+impl MemoryStore {
+    /// Store over the given maps, which the caller may keep using, and
+    /// empty ones for the rest.
+    pub fn new(
+        devices: Arc<RwLock<HashMap<String, DeviceInfo>>>,
+        commands: Arc<RwLock<Vec<CommandRecord>>>,
+        telemetry: Arc<RwLock<Vec<TelemetryRow>>>,
+        shadows: Arc<RwLock<HashMap<(String, String), ShadowState>>>,
+        device_tags: Arc<RwLock<HashMap<String, Tags>>>,
+    ) -> Self {
+        Self {
+            devices,
+            commands,
+            telemetry,
+            shadows,
+            device_tags,
+            claim_codes: Arc::default(),
+            templates: Arc::default(),
+            template_runs: Arc::default(),
+            fleets: Arc::default(),
+            fleet_tokens: Arc::default(),
+            agent_releases: Arc::default(),
+        }
+    }
+}
+
+/// Order telemetry is kept in; the key cursors compare on.
+fn telemetry_key(row: &TelemetryRow) -> (i64, &str) {
+    (row.time.timestamp_micros(), row.metric_name.as_str())
+}
+
+fn empty_shadow() -> ShadowState {
+    ShadowState {
+        reported: serde_json::Value::Object(Default::default()),
+        desired: serde_json::Value::Object(Default::default()),
+        version: 0,
+        last_updated: Utc::now(),
+    }
+}
+
+#[async_trait]
+impl DeviceStore for MemoryStore {
+    async fn get_device(&self, device_id: &str) -> ApiResult<Option<DeviceInfo>> {
+        Ok(self.devices.read().await.get(device_id).cloned())
+    }
+
+    async fn device_exists(&self, device_id: &str) -> ApiResult<bool> {
+        Ok(self.devices.read().await.contains_key(device_id))
+    }
+
+    async fn list_devices(&self, filter: &DeviceFilter) -> ApiResult<(Vec<DeviceInfo>, u64)> {
+        let devices = self.devices.read().await;
+        let tags = self.device_tags.read().await;
+        let mut matching: Vec<DeviceInfo> = devices
+            .values()
+            .filter(|d| {
+                filter.matches(
+                    status_name(d.status),
+                    d.last_heartbeat,
+                    d.certificate_expires_at,
+                    tags.get(&d.device_id),
+                    d.metadata.get("fleet").and_then(|f| f.as_str()),
+                )
+            })
+            .cloned()
+            .collect();
+        matching.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        let total = matching.len() as u64;
+        Ok((
+            pagination::slice(matching, filter.offset, filter.limit),
+            total,
+        ))
+    }
+
+    async fn insert_device(&self, device: &DeviceInfo) -> ApiResult<()> {
+        self.devices
+            .write()
+            .await
+            .insert(device.device_id.clone(), device.clone());
+        Ok(())
+    }
+
+    async fn record_heartbeat(
+        &self,
+        device_id: &str,
+        at: DateTime<Utc>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        vitals: Option<&DeviceVitals>,
+    ) -> ApiResult<Option<DeviceStatus>> {
+        let mut devices = self.devices.write().await;
+        Ok(devices.get_mut(device_id).map(|device| {
+            device.last_heartbeat = Some(at);
+            if cert_expires_at.is_some() {
+                device.certificate_expires_at = cert_expires_at;
+            }
+            if vitals.is_some() {
+                device.vitals = vitals.cloned();
+            }
+            std::mem::replace(&mut device.status, DeviceStatus::Online)
+        }))
+    }
+
+    async fn upsert_from_heartbeat(
+        &self,
+        device_id: &str,
+        fleet_id: &str,
+        machine_id: Option<&str>,
+        at: DateTime<Utc>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        vitals: Option<&DeviceVitals>,
+    ) -> ApiResult<Option<DeviceStatus>> {
+        let mut devices = self.devices.write().await;
+        if let Some(device) = devices.get_mut(device_id) {
+            device.last_heartbeat = Some(at);
+            if cert_expires_at.is_some() {
+                device.certificate_expires_at = cert_expires_at;
+            }
+            if vitals.is_some() {
+                device.vitals = vitals.cloned();
+            }
+            // Update machine_id in metadata if newly provided.
+            if let Some(mid) = machine_id
+                && let Some(obj) = device.metadata.as_object_mut()
+            {
+                obj.insert("machine_id".into(), mid.into());
+            }
+            return Ok(Some(std::mem::replace(
+                &mut device.status,
+                DeviceStatus::Online,
+            )));
+        }
+
+        let mut metadata = serde_json::json!({
+            "fleet": fleet_id,
+            "auto_registered": true,
+        });
+        if let Some(mid) = machine_id {
+            metadata["machine_id"] = mid.into();
+        }
+        let now = Utc::now();
+        devices.insert(
+            device_id.to_string(),
+            DeviceInfo {
+                id: Uuid::now_v7(),
+                fleet_id: FleetId(Uuid::now_v7()),
+                device_id: device_id.to_string(),
+                status: DeviceStatus::Online,
+                vin: None,
+                hardware_type: HardwareType::Custom("auto".into()),
+                certificate_id: None,
+                certificate_expires_at: cert_expires_at,
+                last_heartbeat: Some(at),
+                vitals: vitals.cloned(),
+                metadata,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        Ok(None)
+    }
+
+    async fn prune_heartbeats(&self, _before: DateTime<Utc>) -> ApiResult<u64> {
+        // Heartbeats are not logged in memory.
+        Ok(0)
+    }
+
+    async fn transition_device_status(
+        &self,
+        device_id: &str,
+        from: &[DeviceStatus],
+        to: DeviceStatus,
+    ) -> ApiResult<Option<DeviceStatus>> {
+        let mut devices = self.devices.write().await;
+        match devices.get_mut(device_id) {
+            Some(device) if from.contains(&device.status) => {
+                let previous = device.status;
+                device.status = to;
+                device.updated_at = Utc::now();
+                Ok(Some(previous))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandStore for MemoryStore {
+    async fn insert_command(
+        &self,
+        envelope: &CommandEnvelope,
+        status: CommandStatus,
+        _inference_tier: Option<String>,
+        deadline: Option<DateTime<Utc>>,
+        max_attempts: u32,
+        idempotency_key: Option<&str>,
+    ) -> ApiResult<bool> {
+        let mut commands = self.commands.write().await;
+        if let Some(key) = idempotency_key
+            && commands.iter().any(|r| {
+                r.envelope.initiated_by == envelope.initiated_by
+                    && r.idempotency_key.as_deref() == Some(key)
+            })
+        {
+            return Ok(false);
+        }
+        commands.push(CommandRecord {
+            envelope: envelope.clone(),
+            response: None,
+            status,
+            created_at: Utc::now(),
+            deadline,
+            attempts: 0,
+            max_attempts,
+            next_attempt_at: None,
+            idempotency_key: idempotency_key.map(str::to_string),
+            timing: Default::default(),
+        });
+        Ok(true)
+    }
+
+    async fn command_by_idempotency_key(
+        &self,
+        initiated_by: &str,
+        idempotency_key: &str,
+    ) -> ApiResult<Option<CommandEnvelope>> {
+        Ok(self
+            .commands
+            .read()
+            .await
+            .iter()
+            .find(|r| {
+                r.envelope.initiated_by == initiated_by
+                    && r.idempotency_key.as_deref() == Some(idempotency_key)
+            })
+            .map(|r| r.envelope.clone()))
+    }
+
+    async fn command(&self, command_id: Uuid) -> ApiResult<Option<CommandRecord>> {
+        let commands = self.commands.read().await;
+        Ok(commands
+            .iter()
+            .find(|r| r.envelope.id == command_id)
+            .cloned())
+    }
+
+    async fn set_status(&self, command_id: Uuid, status: CommandStatus) -> ApiResult<()> {
+        let mut commands = self.commands.write().await;
+        if let Some(record) = commands.iter_mut().find(|r| r.envelope.id == command_id) {
+            record.status = status;
+        }
+        Ok(())
+    }
+
+    async fn set_published(&self, command_id: Uuid, at: DateTime<Utc>) -> ApiResult<()> {
+        let mut commands = self.commands.write().await;
+        if let Some(record) = commands.iter_mut().find(|r| r.envelope.id == command_id) {
+            record.timing.published_at = Some(at);
+        }
+        Ok(())
+    }
+
+    async fn list_commands(&self, filter: &CommandFilter) -> ApiResult<(Vec<CommandSummary>, u64)> {
+        let commands = self.commands.read().await;
+        let matching: Vec<CommandSummary> = commands
+            .iter()
+            .rev()
+            .filter_map(|r| {
+                let status = r.response.as_ref().map_or(r.status, |r| r.status);
+                let status = command_status_name(status);
+                let error_code = r
+                    .response
+                    .as_ref()
+                    .and_then(|r| r.error_code)
+                    .map(ErrorCode::as_str);
+                filter
+                    .matches(
+                        &r.envelope.fleet_id,
+                        &r.envelope.device_id,
+                        &status,
+                        &r.envelope.initiated_by,
+                        r.created_at,
+                        error_code,
+                    )
+                    .then(|| CommandSummary {
+                        id: r.envelope.id,
+                        device_id: r.envelope.device_id.clone(),
+                        command: r.envelope.natural_language.clone(),
+                        status,
+                        error_code: error_code.map(String::from),
+                        initiated_by: r.envelope.initiated_by.clone(),
+                        created_at: r.created_at,
+                    })
+            })
+            .collect();
+        let total = matching.len() as u64;
+        Ok((
+            pagination::slice(matching, filter.offset, filter.limit),
+            total,
+        ))
+    }
+
+    async fn record_response(
+        &self,
+        resp: &CommandResponse,
+        receipt: &Receipt,
+    ) -> ApiResult<Option<RespondedCommand>> {
+        let mut commands = self.commands.write().await;
+        let Some(record) = commands
+            .iter_mut()
+            .find(|r| r.envelope.id == resp.command_id)
+        else {
+            return Ok(None);
+        };
+        record.status = resp.status;
+        record.response = Some(resp.clone());
+        record.timing.received_at = Some(receipt.received_at);
+        record.timing.clock_offset_ms = receipt.clock_offset_ms;
+        let intent = record.envelope.parsed_intent.clone();
+        Ok(Some(RespondedCommand {
+            fleet_id: record.envelope.fleet_id.clone(),
+            initiated_by: record.envelope.initiated_by.clone(),
+            tool_args: intent.as_ref().map(|i| i.tool_args.clone()),
+            intent,
+        }))
+    }
+
+    async fn claim_queued(&self, device_id: &str) -> ApiResult<Vec<CommandEnvelope>> {
+        let mut commands = self.commands.write().await;
+        Ok(commands
+            .iter_mut()
+            .filter(|r| r.envelope.device_id == device_id && r.status == CommandStatus::Queued)
+            .map(|r| {
+                r.status = CommandStatus::Pending;
+                r.envelope.clone()
+            })
+            .collect())
+    }
+
+    async fn requeue(&self, command_ids: &[Uuid]) -> ApiResult<()> {
+        let mut commands = self.commands.write().await;
+        for record in commands
+            .iter_mut()
+            .filter(|r| command_ids.contains(&r.envelope.id) && r.status == CommandStatus::Pending)
+        {
+            record.status = CommandStatus::Queued;
+        }
+        Ok(())
+    }
+
+    async fn publish_attempts(&self, command_id: Uuid) -> ApiResult<Option<(u32, u32)>> {
+        let commands = self.commands.read().await;
+        Ok(commands
+            .iter()
+            .find(|r| r.envelope.id == command_id && r.status == CommandStatus::Pending)
+            .map(|r| (r.attempts, r.max_attempts)))
+    }
+
+    async fn record_attempt(
+        &self,
+        command_id: Uuid,
+        attempts: u32,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> ApiResult<()> {
+        let mut commands = self.commands.write().await;
+        if let Some(record) = commands
+            .iter_mut()
+            .find(|r| r.envelope.id == command_id && r.status == CommandStatus::Pending)
+        {
+            record.attempts = attempts;
+            record.next_attempt_at = next_attempt_at;
+            if next_attempt_at.is_some() {
+                record.deadline = None;
+            }
+        }
+        Ok(())
+    }
+
+    async fn claim_due_attempts(&self, now: DateTime<Utc>) -> ApiResult<Vec<CommandEnvelope>> {
+        let mut commands = self.commands.write().await;
+        Ok(commands
+            .iter_mut()
+            .filter(|r| {
+                r.status == CommandStatus::Pending && r.next_attempt_at.is_some_and(|t| t <= now)
+            })
+            .map(|r| {
+                r.next_attempt_at = None;
+                r.deadline = Some(crate::command_timeouts::deadline(&r.envelope, now));
+                r.envelope.clone()
+            })
+            .collect())
+    }
+
+    async fn fail_undelivered(&self, response: &CommandResponse, attempts: u32) -> ApiResult<()> {
+        let mut commands = self.commands.write().await;
+        if let Some(record) = commands
+            .iter_mut()
+            .find(|r| r.envelope.id == response.command_id)
+        {
+            record.attempts = attempts;
+            record.next_attempt_at = None;
+            record.deadline = None;
+            record.status = response.status;
+            record.response = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    async fn completed_tool_results(
+        &self,
+        device_id: &str,
+        tool_name: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> ApiResult<Vec<(DateTime<Utc>, serde_json::Value)>> {
+        let commands = self.commands.read().await;
+        let mut results: Vec<(DateTime<Utc>, DateTime<Utc>, serde_json::Value)> = commands
+            .iter()
+            .filter(|r| {
+                r.envelope.device_id == device_id
+                    && r.created_at >= since
+                    && r.envelope
+                        .parsed_intent
+                        .as_ref()
+                        .is_some_and(|i| i.tool_name == tool_name)
+            })
+            .filter_map(|r| {
+                let response = r.response.as_ref()?;
+                if response.status != CommandStatus::Completed {
+                    return None;
+                }
+                let responded_at = r.timing.received_at.unwrap_or(response.responded_at);
+                Some((r.created_at, responded_at, response.response_data.clone()?))
+            })
+            .collect();
+        results.sort_by_key(|(created_at, ..)| std::cmp::Reverse(*created_at));
+        Ok(results
+            .into_iter()
+            .take(limit)
+            .map(|(_, responded_at, data)| (responded_at, data))
+            .collect())
+    }
+
+    async fn command_outcomes(&self, ids: &[Uuid]) -> ApiResult<Vec<CommandOutcome>> {
+        let commands = self.commands.read().await;
+        Ok(commands
+            .iter()
+            .filter(|r| ids.contains(&r.envelope.id))
+            .map(record_outcome)
+            .collect())
+    }
+
+    async fn broadcast_outcomes(
+        &self,
+        broadcast_id: Uuid,
+        fleet_id: &str,
+    ) -> ApiResult<Option<(CommandEnvelope, Vec<CommandOutcome>)>> {
+        let commands = self.commands.read().await;
+        let records: Vec<&CommandRecord> = commands
+            .iter()
+            .filter(|r| {
+                r.envelope.correlation_id == broadcast_id
+                    && r.envelope.id != broadcast_id
+                    && r.envelope.fleet_id == fleet_id
+            })
+            .collect();
+        let Some(first) = records.first() else {
+            return Ok(None);
+        };
+        let envelope = first.envelope.clone();
+        let mut outcomes: Vec<CommandOutcome> = records.into_iter().map(record_outcome).collect();
+        outcomes.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        Ok(Some((envelope, outcomes)))
+    }
+}
+
+fn record_outcome(record: &CommandRecord) -> CommandOutcome {
+    let response = record.response.as_ref();
+    CommandOutcome {
+        device_id: record.envelope.device_id.clone(),
+        command_id: record.envelope.id,
+        status: response.map(|r| r.status).unwrap_or(record.status),
+        response_text: response.and_then(|r| r.response_text.clone()),
+        response_data: response.and_then(|r| r.response_data.clone()),
+        error: response.and_then(|r| r.error.clone()),
+    }
+}
+
+#[async_trait]
+impl TelemetryStore for MemoryStore {
+    async fn append_telemetry(&self, rows: &mut Vec<TelemetryRow>) -> ApiResult<()> {
+        rows.sort_by(|a, b| telemetry_key(a).cmp(&telemetry_key(b)));
+        let mut telemetry = self.telemetry.write().await;
+        let in_order = telemetry
+            .last()
+            .zip(rows.first())
+            .is_none_or(|(last, first)| telemetry_key(last) <= telemetry_key(first));
+        telemetry.extend(rows.drain(..));
+        if !in_order {
+            // Late readings: the stable sort merges the two sorted runs.
+            telemetry.sort_by(|a, b| telemetry_key(a).cmp(&telemetry_key(b)));
+        }
+        if let Some(excess) = telemetry.len().checked_sub(TELEMETRY_CAPACITY) {
+            telemetry.drain(..excess);
+        }
+        Ok(())
+    }
+
+    async fn telemetry_page(
+        &self,
+        device_id: &str,
+        filter: &TelemetryFilter,
+    ) -> ApiResult<Vec<TelemetryRow>> {
+        let telemetry = self.telemetry.read().await;
+        let cursor = filter
+            .after
+            .as_ref()
+            .map(|c| (c.time.timestamp_micros(), c.metric_name.as_str()));
+        let wanted = |r: &&TelemetryRow| r.device_id == device_id && filter.matches(r);
+        let limit = filter.limit as usize;
+        let rows = match filter.order {
+            SortOrder::Asc => {
+                let start =
+                    cursor.map_or(0, |c| telemetry.partition_point(|r| telemetry_key(r) <= c));
+                telemetry[start..]
+                    .iter()
+                    .filter(wanted)
+                    .take(limit)
+                    .cloned()
+                    .collect()
+            }
+            SortOrder::Desc => {
+                let end = cursor.map_or(telemetry.len(), |c| {
+                    telemetry.partition_point(|r| telemetry_key(r) < c)
+                });
+                telemetry[..end]
+                    .iter()
+                    .rev()
+                    .filter(wanted)
+                    .take(limit)
+                    .cloned()
+                    .collect()
+            }
+        };
+        Ok(rows)
+    }
+
+    async fn prune_telemetry(&self, before: DateTime<Utc>) -> ApiResult<u64> {
+        let mut telemetry = self.telemetry.write().await;
+        let len = telemetry.len();
+        telemetry.retain(|r| r.time >= before);
+        Ok((len - telemetry.len()) as u64)
+    }
+
+    async fn telemetry_summary(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> ApiResult<Vec<MetricSummary>> {
+        let telemetry = self.telemetry.read().await;
+        let mut by_metric: BTreeMap<(&str, &str), MetricTotals> = BTreeMap::new();
+        for row in telemetry
+            .iter()
+            .filter(|r| r.device_id == device_id && r.time >= since)
+        {
+            let totals = by_metric
+                .entry((row.metric_name.as_str(), row.source.as_str()))
+                .or_insert_with(|| MetricTotals {
+                    summary: MetricSummary {
+                        metric: row.metric_name.clone(),
+                        source: row.source.clone(),
+                        unit: None,
+                        readings: 0,
+                        min: None,
+                        max: None,
+                        avg: None,
+                        latest: None,
+                        last_seen: row.time,
+                    },
+                    sum: 0.0,
+                    numeric: 0,
+                    latest_at: DateTime::<Utc>::MIN_UTC,
+                });
+            let summary = &mut totals.summary;
+            summary.readings += 1;
+            summary.last_seen = summary.last_seen.max(row.time);
+            if row.unit.is_some() {
+                summary.unit = row.unit.clone();
+            }
+            if let Some(value) = row.value_numeric {
+                summary.min = Some(summary.min.map_or(value, |m| m.min(value)));
+                summary.max = Some(summary.max.map_or(value, |m| m.max(value)));
+                totals.sum += value;
+                totals.numeric += 1;
+                if row.time >= totals.latest_at {
+                    totals.latest_at = row.time;
+                    summary.latest = Some(value);
+                }
+            }
+        }
+        Ok(by_metric
+            .into_values()
+            .take(limit)
+            .map(|mut totals| {
+                totals.summary.avg =
+                    (totals.numeric > 0).then(|| totals.sum / totals.numeric as f64);
+                totals.summary
+            })
+            .collect())
+    }
+}
+
+/// Running totals of one metric while summarizing telemetry.
+struct MetricTotals {
+    summary: MetricSummary,
+    sum: f64,
+    /// Readings with a numeric value.
+    numeric: u64,
+    latest_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl ShadowStore for MemoryStore {
+    async fn list_shadows(&self, device_id: &str) -> ApiResult<Vec<(String, ShadowState)>> {
+        let shadows = self.shadows.read().await;
+        let mut matching: Vec<(String, ShadowState)> = shadows
+            .iter()
+            .filter(|((did, _), _)| did == device_id)
+            .map(|((_, name), s)| (name.clone(), s.clone()))
+            .collect();
+        matching.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(matching)
+    }
+
+    async fn get_shadow(&self, device_id: &str, name: &str) -> ApiResult<Option<ShadowState>> {
+        let shadows = self.shadows.read().await;
+        Ok(shadows
+            .get(&(device_id.to_string(), name.to_string()))
+            .cloned())
+    }
+
+    async fn set_desired(
+        &self,
+        device_id: &str,
+        name: &str,
+        desired: &serde_json::Value,
+    ) -> ApiResult<ShadowState> {
+        let mut shadows = self.shadows.write().await;
+        let entry = shadows
+            .entry((device_id.to_string(), name.to_string()))
+            .or_insert_with(empty_shadow);
+        entry.desired = desired.clone();
+        entry.version += 1;
+        entry.last_updated = Utc::now();
+        Ok(entry.clone())
+    }
+
+    async fn merge_reported(
+        &self,
+        device_id: &str,
+        name: &str,
+        reported: &serde_json::Value,
+    ) -> ApiResult<ShadowState> {
+        let mut shadows = self.shadows.write().await;
+        let entry = shadows
+            .entry((device_id.to_string(), name.to_string()))
+            .or_insert_with(empty_shadow);
+        if let (Some(existing), Some(incoming)) =
+            (entry.reported.as_object_mut(), reported.as_object())
+        {
+            for (k, v) in incoming {
+                if v.is_null() {
+                    existing.remove(k);
+                } else {
+                    existing.insert(k.clone(), v.clone());
+                }
+            }
+        }
+        entry.version += 1;
+        entry.last_updated = Utc::now();
+        Ok(entry.clone())
+    }
+
+    async fn delete_shadow(&self, device_id: &str, name: &str) -> ApiResult<bool> {
+        let mut shadows = self.shadows.write().await;
+        Ok(shadows
+            .remove(&(device_id.to_string(), name.to_string()))
+            .is_some())
+    }
+}
+
+#[async_trait]
+impl ClaimCodeStore for MemoryStore {
+    async fn insert_claim_code(&self, code: &ClaimCode) -> ApiResult<()> {
+        self.claim_codes.write().await.push(code.clone());
+        Ok(())
+    }
+
+    async fn list_claim_codes(&self, fleets: Option<&[String]>) -> ApiResult<Vec<ClaimCode>> {
+        let codes = self.claim_codes.read().await;
+        Ok(codes
+            .iter()
+            .rev()
+            .filter(|c| fleets.is_none_or(|f| f.contains(&c.fleet_id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn claim_code(&self, id: Uuid) -> ApiResult<Option<ClaimCode>> {
+        let codes = self.claim_codes.read().await;
+        Ok(codes.iter().find(|c| c.id == id).cloned())
+    }
+
+    async fn claim_code_by_sha256(&self, sha256: &str) -> ApiResult<Option<ClaimCode>> {
+        let codes = self.claim_codes.read().await;
+        Ok(codes.iter().find(|c| c.sha256 == sha256).cloned())
+    }
+
+    async fn delete_unclaimed_claim_code(&self, id: Uuid) -> ApiResult<bool> {
+        let mut codes = self.claim_codes.write().await;
+        let before = codes.len();
+        codes.retain(|c| !(c.id == id && c.claimed_at.is_none()));
+        Ok(codes.len() != before)
+    }
+
+    async fn mark_claim_code_claimed(
+        &self,
+        id: Uuid,
+        hardware_id: &str,
+        device_id: &str,
+        at: DateTime<Utc>,
+    ) -> ApiResult<bool> {
+        let mut codes = self.claim_codes.write().await;
+        match codes
+            .iter_mut()
+            .find(|c| c.id == id && c.claimed_at.is_none())
+        {
+            Some(code) => {
+                code.claimed_at = Some(at);
+                code.hardware_id = Some(hardware_id.to_string());
+                code.device_id = Some(device_id.to_string());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn release_claim_code(&self, id: Uuid, device_id: Option<&str>) -> ApiResult<()> {
+        let mut codes = self.claim_codes.write().await;
+        if let Some(code) = codes.iter_mut().find(|c| c.id == id) {
+            code.claimed_at = None;
+            code.hardware_id = None;
+            code.device_id = device_id.map(str::to_string);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TemplateStore for MemoryStore {
+    async fn insert_template(&self, template: &CommandTemplate) -> ApiResult<bool> {
+        let mut templates = self.templates.write().await;
+        let taken = templates.iter().any(|t| {
+            t.fleet_id == template.fleet_id && t.name.eq_ignore_ascii_case(&template.name)
+        });
+        if !taken {
+            templates.push(template.clone());
+        }
+        Ok(!taken)
+    }
+
+    async fn list_templates(
+        &self,
+        fleet_id: Option<&str>,
+        tenants: Option<&[String]>,
+    ) -> ApiResult<Vec<CommandTemplate>> {
+        let visible = |fleet: &Option<String>| match fleet {
+            None => true,
+            Some(f) => {
+                fleet_id.is_none_or(|wanted| wanted == f)
+                    && tenants.is_none_or(|tenants| tenants.contains(f))
+            }
+        };
+        Ok(self
+            .templates
+            .read()
+            .await
+            .iter()
+            .filter(|t| visible(&t.fleet_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn template(&self, id: Uuid) -> ApiResult<Option<CommandTemplate>> {
+        let templates = self.templates.read().await;
+        Ok(templates.iter().find(|t| t.id == id).cloned())
+    }
+
+    async fn delete_template(&self, id: Uuid) -> ApiResult<bool> {
+        let mut templates = self.templates.write().await;
+        let before = templates.len();
+        templates.retain(|t| t.id != id);
+        self.template_runs
+            .write()
+            .await
+            .retain(|r| r.template_id != id);
+        Ok(templates.len() != before)
+    }
+
+    async fn insert_template_run(&self, run: &TemplateRun) -> ApiResult<()> {
+        let mut runs = self.template_runs.write().await;
+        runs.push(run.clone());
+        let kept = runs
+            .iter()
+            .filter(|r| r.template_id == run.template_id)
+            .count();
+        if kept > MAX_RUNS_KEPT
+            && let Some(oldest) = runs.iter().position(|r| r.template_id == run.template_id)
+        {
+            runs.remove(oldest);
+        }
+        Ok(())
+    }
+
+    async fn list_template_runs(
+        &self,
+        template_id: Uuid,
+        limit: u32,
+        offset: u32,
+    ) -> ApiResult<(Vec<TemplateRun>, u64)> {
+        let runs = self.template_runs.read().await;
+        let matching: Vec<TemplateRun> = runs
+            .iter()
+            .rev()
+            .filter(|r| r.template_id == template_id)
+            .cloned()
+            .collect();
+        let total = matching.len() as u64;
+        Ok((pagination::slice(matching, offset, limit), total))
+    }
+
+    async fn template_run(&self, id: Uuid) -> ApiResult<Option<TemplateRun>> {
+        let runs = self.template_runs.read().await;
+        Ok(runs.iter().find(|r| r.id == id).cloned())
+    }
+}
+
+#[async_trait]
+impl FleetStore for MemoryStore {
+    async fn insert_fleet(&self, fleet: &Fleet) -> ApiResult<bool> {
+        let mut fleets = self.fleets.write().await;
+        let taken = fleets.iter().any(|f| f.fleet_id == fleet.fleet_id);
+        if !taken {
+            fleets.push(fleet.clone());
+        }
+        Ok(!taken)
+    }
+
+    async fn list_fleets(&self, tenants: Option<&[String]>) -> ApiResult<Vec<Fleet>> {
+        let mut fleets: Vec<Fleet> = self
+            .fleets
+            .read()
+            .await
+            .iter()
+            .filter(|f| tenants.is_none_or(|t| t.contains(&f.fleet_id)))
+            .cloned()
+            .collect();
+        fleets.sort_by(|a, b| a.fleet_id.cmp(&b.fleet_id));
+        Ok(fleets)
+    }
+
+    async fn fleet(&self, fleet_id: &str) -> ApiResult<Option<Fleet>> {
+        let fleets = self.fleets.read().await;
+        Ok(fleets.iter().find(|f| f.fleet_id == fleet_id).cloned())
+    }
+
+    async fn any_fleet(&self) -> ApiResult<bool> {
+        Ok(!self.fleets.read().await.is_empty())
+    }
+
+    async fn fleet_devices(&self, fleet_id: &str) -> ApiResult<Vec<DeviceInfo>> {
+        let devices = self.devices.read().await;
+        let mut members: Vec<DeviceInfo> = devices
+            .values()
+            .filter(|d| d.metadata.get("fleet").and_then(|f| f.as_str()) == Some(fleet_id))
+            .cloned()
+            .collect();
+        members.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        Ok(members)
+    }
+
+    async fn delete_fleet(&self, fleet_id: &str) -> ApiResult<bool> {
+        let mut fleets = self.fleets.write().await;
+        let before = fleets.len();
+        fleets.retain(|f| f.fleet_id != fleet_id);
+        self.fleet_tokens
+            .write()
+            .await
+            .retain(|t| t.fleet_id != fleet_id);
+        Ok(fleets.len() != before)
+    }
+
+    async fn insert_fleet_token(&self, token: &FleetToken) -> ApiResult<()> {
+        self.fleet_tokens.write().await.push(token.clone());
+        Ok(())
+    }
+
+    async fn list_fleet_tokens(&self, fleet_id: &str) -> ApiResult<Vec<FleetToken>> {
+        let tokens = self.fleet_tokens.read().await;
+        Ok(tokens
+            .iter()
+            .filter(|t| t.fleet_id == fleet_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn fleet_token_by_sha256(&self, sha256: &str) -> ApiResult<Option<FleetToken>> {
+        let tokens = self.fleet_tokens.read().await;
+        Ok(tokens.iter().find(|t| t.sha256 == sha256).cloned())
+    }
+
+    async fn delete_fleet_token(&self, fleet_id: &str, id: Uuid) -> ApiResult<bool> {
+        let mut tokens = self.fleet_tokens.write().await;
+        let before = tokens.len();
+        tokens.retain(|t| !(t.fleet_id == fleet_id && t.id == id));
+        Ok(tokens.len() != before)
+    }
+}
+
+#[async_trait]
+impl AgentReleaseStore for MemoryStore {
+    async fn insert_agent_release(&self, release: &AgentRelease) -> ApiResult<bool> {
+        let mut releases = self.agent_releases.write().await;
+        let taken = releases.iter().any(|r| r.version == release.version);
+        if !taken {
+            releases.push(release.clone());
+        }
+        Ok(!taken)
+    }
+
+    async fn list_agent_releases(&self) -> ApiResult<Vec<AgentRelease>> {
+        let releases = self.agent_releases.read().await;
+        Ok(releases.iter().rev().cloned().collect())
+    }
+
+    async fn agent_release(&self, version: &str) -> ApiResult<Option<AgentRelease>> {
+        let releases = self.agent_releases.read().await;
+        Ok(releases.iter().find(|r| r.version == version).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reading(secs: i64, device_id: &str, metric: &str) -> TelemetryRow {
+        TelemetryRow {
+            time: DateTime::from_timestamp(secs, 0).unwrap(),
+            device_id: device_id.into(),
+            metric_name: metric.into(),
+            value_numeric: Some(secs as f64),
+            value_text: None,
+            value_json: None,
+            unit: None,
+            source: "obd2".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn telemetry_pages_follow_the_cursor_across_late_readings() {
+        let store = MemoryStore::default();
+        let mut rows = vec![
+            reading(300, "rpi-001", "rpm"),
+            reading(100, "rpi-001", "rpm"),
+            reading(200, "rpi-002", "rpm"),
+        ];
+        store.append_telemetry(&mut rows).await.unwrap();
+        assert!(rows.is_empty());
+        // Arrives late, and sorts before `rpm` at the same time.
+        let mut late = vec![
+            reading(200, "rpi-001", "coolant"),
+            reading(200, "rpi-001", "rpm"),
+        ];
+        store.append_telemetry(&mut late).await.unwrap();
+
+        let page = |order, after: Option<&TelemetryRow>| TelemetryFilter {
+            order,
+            after: after.map(crate::db::telemetry::TelemetryCursor::from_row),
+            limit: 2,
+            ..TelemetryFilter::default()
+        };
+        let key = |rows: &[TelemetryRow]| -> Vec<(i64, String)> {
+            rows.iter()
+                .map(|r| (r.time.timestamp(), r.metric_name.clone()))
+                .collect()
+        };
+
+        let first = store
+            .telemetry_page("rpi-001", &page(SortOrder::Asc, None))
+            .await
+            .unwrap();
+        assert_eq!(key(&first), [(100, "rpm".into()), (200, "coolant".into())]);
+        let second = store
+            .telemetry_page("rpi-001", &page(SortOrder::Asc, first.last()))
+            .await
+            .unwrap();
+        assert_eq!(key(&second), [(200, "rpm".into()), (300, "rpm".into())]);
+
+        let newest = store
+            .telemetry_page("rpi-001", &page(SortOrder::Desc, second.first()))
+            .await
+            .unwrap();
+        assert_eq!(key(&newest), [(200, "coolant".into()), (100, "rpm".into())]);
+    }
+
+    #[tokio::test]
+    async fn telemetry_keeps_the_newest_readings() {
+        let store = MemoryStore::default();
+        let mut rows: Vec<TelemetryRow> = (0..TELEMETRY_CAPACITY as i64 + 10)
+            .map(|secs| reading(secs, "rpi-001", "rpm"))
+            .collect();
+        store.append_telemetry(&mut rows).await.unwrap();
+
+        let telemetry = store.telemetry.read().await;
+        assert_eq!(telemetry.len(), TELEMETRY_CAPACITY);
+        assert_eq!(telemetry[0].time.timestamp(), 10);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_registers_unknown_devices() {
+        let store = MemoryStore::default();
+        let now = Utc::now();
+
+        let previous = store
+            .upsert_from_heartbeat("rpi-new", "fleet-alpha", Some("abc"), now, None, None)
+            .await
+            .unwrap();
+        assert_eq!(previous, None);
+        let device = store.get_device("rpi-new").await.unwrap().unwrap();
+        assert_eq!(device.status, DeviceStatus::Online);
+        assert_eq!(device.metadata["fleet"], "fleet-alpha");
+        assert_eq!(device.metadata["machine_id"], "abc");
+
+        store
+            .devices
+            .write()
+            .await
+            .get_mut("rpi-new")
+            .unwrap()
+            .status = DeviceStatus::Offline;
+        let previous = store
+            .record_heartbeat("rpi-new", now, None, None)
+            .await
+            .unwrap();
+        assert_eq!(previous, Some(DeviceStatus::Offline));
+        assert_eq!(
+            store
+                .record_heartbeat("rpi-missing", now, None, None)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn reported_state_merges_and_null_removes() {
+        let store = MemoryStore::default();
+        store
+            .merge_reported("rpi-001", "config", &json!({"a": 1, "b": 2}))
+            .await
+            .unwrap();
+        let shadow = store
+            .merge_reported("rpi-001", "config", &json!({"b": null, "c": 3}))
+            .await
+            .unwrap();
+        assert_eq!(shadow.reported, json!({"a": 1, "c": 3}));
+        assert_eq!(shadow.version, 2);
+
+        let shadow = store
+            .set_desired("rpi-001", "config", &json!({"a": 5}))
+            .await
+            .unwrap();
+        assert_eq!(shadow.version, 3);
+        assert_eq!(shadow.reported, json!({"a": 1, "c": 3}));
+
+        store
+            .set_desired("rpi-001", "alpha", &json!({}))
+            .await
+            .unwrap();
+        let names: Vec<String> = store
+            .list_shadows("rpi-001")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["alpha", "config"]);
+        assert!(store.delete_shadow("rpi-001", "alpha").await.unwrap());
+        assert!(!store.delete_shadow("rpi-001", "alpha").await.unwrap());
+    }
+}
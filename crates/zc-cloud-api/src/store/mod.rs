@@ -0,0 +1,422 @@
+//! Storage backends behind one interface.
+//!
+//! The registry, command log, telemetry, shadows, claim codes, command
+//! templates, fleets and agent releases are kept either in PostgreSQL ([`PgStore`]) or in memory ([`MemoryStore`], for
+//! development and tests). Routes and the MQTT bridge go through
+//! [`AppState::store`] instead of branching on the pool, so each operation
+//! has one code path and another backend only needs these traits.
+//!
+//! The in-memory store shares its registry, command, telemetry, shadow and
+//! tag maps with the matching [`AppState`] fields, which subsystems not yet
+//! behind a trait still use directly; it owns the rest.
+//!
+//! [`AppState::store`]: crate::state::AppState::store
+//! [`AppState`]: crate::state::AppState
+
+mod memory;
+mod postgres;
+
+pub use memory::{MemoryStore, TELEMETRY_CAPACITY};
+pub use postgres::PgStore;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use zc_protocol::commands::{CommandEnvelope, CommandResponse, CommandStatus, ParsedIntent};
+use zc_protocol::device::{DeviceInfo, DeviceStatus, DeviceVitals};
+use zc_protocol::shadows::ShadowState;
+
+use crate::agent_releases::AgentRelease;
+use crate::claim_codes::ClaimCode;
+use crate::command_timing::Receipt;
+use crate::db::commands::CommandFilter;
+use crate::db::devices::DeviceFilter;
+use crate::db::telemetry::{TelemetryFilter, TelemetryRow};
+use crate::device_report::MetricSummary;
+use crate::error::ApiResult;
+use crate::fleets::{Fleet, FleetToken};
+use crate::state::CommandRecord;
+use crate::templates::{CommandTemplate, TemplateRun};
+
+/// Device registry.
+#[async_trait]
+pub trait DeviceStore: Send + Sync {
+    async fn get_device(&self, device_id: &str) -> ApiResult<Option<DeviceInfo>>;
+
+    async fn device_exists(&self, device_id: &str) -> ApiResult<bool>;
+
+    /// One page of devices matching `filter`, ordered by device ID, and the
+    /// unpaged match count.
+    async fn list_devices(&self, filter: &DeviceFilter) -> ApiResult<(Vec<DeviceInfo>, u64)>;
+
+    async fn insert_device(&self, device: &DeviceInfo) -> ApiResult<()>;
+
+    /// Record a heartbeat from a known device and mark it online. Reported
+    /// certificate expiry and vitals replace the stored ones. Returns the
+    /// status it had before, or `None` for an unknown device.
+    async fn record_heartbeat(
+        &self,
+        device_id: &str,
+        at: DateTime<Utc>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        vitals: Option<&DeviceVitals>,
+    ) -> ApiResult<Option<DeviceStatus>>;
+
+    /// Record a heartbeat, registering the device if it is new. Returns the
+    /// status an existing device had before, `None` if it was new.
+    async fn upsert_from_heartbeat(
+        &self,
+        device_id: &str,
+        fleet_id: &str,
+        machine_id: Option<&str>,
+        at: DateTime<Utc>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        vitals: Option<&DeviceVitals>,
+    ) -> ApiResult<Option<DeviceStatus>>;
+
+    /// Drop logged heartbeats received before `before`. Returns how many
+    /// rows (or hypertable chunks) went.
+    async fn prune_heartbeats(&self, before: DateTime<Utc>) -> ApiResult<u64>;
+
+    /// Move a device to `to` if its status is one of `from`. Returns the
+    /// status it left, or `None` if it was not moved.
+    async fn transition_device_status(
+        &self,
+        device_id: &str,
+        from: &[DeviceStatus],
+        to: DeviceStatus,
+    ) -> ApiResult<Option<DeviceStatus>>;
+}
+
+/// A command in the list view.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CommandSummary {
+    pub id: Uuid,
+    pub device_id: String,
+    pub command: String,
+    /// Wire status name (e.g. `completed`).
+    pub status: String,
+    pub error_code: Option<String>,
+    pub initiated_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where one command stands, for reports over several commands.
+#[derive(Debug, Clone)]
+pub struct CommandOutcome {
+    pub device_id: String,
+    pub command_id: Uuid,
+    /// The response's status once there is one.
+    pub status: CommandStatus,
+    pub response_text: Option<String>,
+    pub response_data: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// The stored command a response answered.
+#[derive(Debug, Clone)]
+pub struct RespondedCommand {
+    pub fleet_id: String,
+    pub initiated_by: String,
+    pub tool_args: Option<serde_json::Value>,
+    pub intent: Option<ParsedIntent>,
+}
+
+/// Command log.
+#[async_trait]
+pub trait CommandStore: Send + Sync {
+    /// Record a dispatched (or queued) command that may be published up to
+    /// `max_attempts` times. Returns `false`, storing nothing, if its
+    /// initiator already submitted a command with `idempotency_key`.
+    async fn insert_command(
+        &self,
+        envelope: &CommandEnvelope,
+        status: CommandStatus,
+        inference_tier: Option<String>,
+        deadline: Option<DateTime<Utc>>,
+        max_attempts: u32,
+        idempotency_key: Option<&str>,
+    ) -> ApiResult<bool>;
+
+    /// The command `initiated_by` submitted with `idempotency_key`.
+    async fn command_by_idempotency_key(
+        &self,
+        initiated_by: &str,
+        idempotency_key: &str,
+    ) -> ApiResult<Option<CommandEnvelope>>;
+
+    /// A command with its response and delivery state.
+    async fn command(&self, command_id: Uuid) -> ApiResult<Option<CommandRecord>>;
+
+    /// Set a command's cloud-side status (e.g. `cancelled`).
+    async fn set_status(&self, command_id: Uuid, status: CommandStatus) -> ApiResult<()>;
+
+    /// Record that a command was published to its device at `at` (cloud
+    /// clock).
+    async fn set_published(&self, command_id: Uuid, at: DateTime<Utc>) -> ApiResult<()>;
+
+    /// One page of commands matching `filter`, most recent first, and the
+    /// unpaged match count.
+    async fn list_commands(&self, filter: &CommandFilter) -> ApiResult<(Vec<CommandSummary>, u64)>;
+
+    /// Attach a device's response, received as described by `receipt`, to
+    /// its command. Returns `None` for an unknown command.
+    async fn record_response(
+        &self,
+        resp: &CommandResponse,
+        receipt: &Receipt,
+    ) -> ApiResult<Option<RespondedCommand>>;
+
+    /// Move a device's queued commands to `pending` and return them in
+    /// creation order. Each queued command is claimed by one caller only.
+    async fn claim_queued(&self, device_id: &str) -> ApiResult<Vec<CommandEnvelope>>;
+
+    /// Return claimed commands that were not published to the queue.
+    async fn requeue(&self, command_ids: &[Uuid]) -> ApiResult<()>;
+
+    /// `(attempts, max_attempts)` of a command that is still pending.
+    async fn publish_attempts(&self, command_id: Uuid) -> ApiResult<Option<(u32, u32)>>;
+
+    /// Count `attempts` publishes of a pending command, the next one due at
+    /// `next_attempt_at`. Scheduling a retry clears the deadline.
+    async fn record_attempt(
+        &self,
+        command_id: Uuid,
+        attempts: u32,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> ApiResult<()>;
+
+    /// Claim the pending commands whose next publish is due at `now`,
+    /// clearing `next_attempt_at` and starting a deadline, so a command
+    /// whose retry is lost still times out.
+    async fn claim_due_attempts(&self, now: DateTime<Utc>) -> ApiResult<Vec<CommandEnvelope>>;
+
+    /// Fail a command none of whose `attempts` publishes went through, with
+    /// the cloud-made `response`.
+    async fn fail_undelivered(&self, response: &CommandResponse, attempts: u32) -> ApiResult<()>;
+
+    /// `(responded_at, response_data)` of the device's completed commands
+    /// that called `tool_name`, created since `since`, newest first.
+    async fn completed_tool_results(
+        &self,
+        device_id: &str,
+        tool_name: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> ApiResult<Vec<(DateTime<Utc>, serde_json::Value)>>;
+
+    /// Outcomes of the commands in `ids`, in no particular order.
+    async fn command_outcomes(&self, ids: &[Uuid]) -> ApiResult<Vec<CommandOutcome>>;
+
+    /// The per-device commands of broadcast `broadcast_id` in `fleet_id`,
+    /// ordered by device ID, with the first one's envelope; `None` when
+    /// there are none.
+    async fn broadcast_outcomes(
+        &self,
+        broadcast_id: Uuid,
+        fleet_id: &str,
+    ) -> ApiResult<Option<(CommandEnvelope, Vec<CommandOutcome>)>>;
+}
+
+/// Telemetry readings.
+#[async_trait]
+pub trait TelemetryStore: Send + Sync {
+    /// Store `rows`, leaving the vector empty (but allocated) for reuse.
+    async fn append_telemetry(&self, rows: &mut Vec<TelemetryRow>) -> ApiResult<()>;
+
+    /// One page of a device's readings matching `filter`.
+    async fn telemetry_page(
+        &self,
+        device_id: &str,
+        filter: &TelemetryFilter,
+    ) -> ApiResult<Vec<TelemetryRow>>;
+
+    /// Drop readings older than `before`. Returns how many rows (or
+    /// hypertable chunks) went.
+    async fn prune_telemetry(&self, before: DateTime<Utc>) -> ApiResult<u64>;
+
+    /// Per-metric statistics of a device's readings since `since`, by
+    /// metric name and source, for at most `limit` metrics.
+    async fn telemetry_summary(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> ApiResult<Vec<MetricSummary>>;
+}
+
+/// Device shadows, keyed by device ID and shadow name.
+#[async_trait]
+pub trait ShadowStore: Send + Sync {
+    /// A device's shadows, ordered by name.
+    async fn list_shadows(&self, device_id: &str) -> ApiResult<Vec<(String, ShadowState)>>;
+
+    async fn get_shadow(&self, device_id: &str, name: &str) -> ApiResult<Option<ShadowState>>;
+
+    /// Replace desired state, creating the shadow if needed.
+    async fn set_desired(
+        &self,
+        device_id: &str,
+        name: &str,
+        desired: &serde_json::Value,
+    ) -> ApiResult<ShadowState>;
+
+    /// Merge reported state (top-level keys replaced, `null` removing its
+    /// key), creating the shadow if needed.
+    async fn merge_reported(
+        &self,
+        device_id: &str,
+        name: &str,
+        reported: &serde_json::Value,
+    ) -> ApiResult<ShadowState>;
+
+    /// Returns `false` if the shadow did not exist.
+    async fn delete_shadow(&self, device_id: &str, name: &str) -> ApiResult<bool>;
+}
+
+/// Device claim codes.
+#[async_trait]
+pub trait ClaimCodeStore: Send + Sync {
+    async fn insert_claim_code(&self, code: &ClaimCode) -> ApiResult<()>;
+
+    /// Codes newest first, limited to `fleets` when given.
+    async fn list_claim_codes(&self, fleets: Option<&[String]>) -> ApiResult<Vec<ClaimCode>>;
+
+    async fn claim_code(&self, id: Uuid) -> ApiResult<Option<ClaimCode>>;
+
+    /// The code with this SHA-256, if any.
+    async fn claim_code_by_sha256(&self, sha256: &str) -> ApiResult<Option<ClaimCode>>;
+
+    /// Delete an unclaimed code. Returns `false` if it is unknown or
+    /// claimed.
+    async fn delete_unclaimed_claim_code(&self, id: Uuid) -> ApiResult<bool>;
+
+    /// Mark an unclaimed code claimed. Returns `false` if someone else got
+    /// there first.
+    async fn mark_claim_code_claimed(
+        &self,
+        id: Uuid,
+        hardware_id: &str,
+        device_id: &str,
+        at: DateTime<Utc>,
+    ) -> ApiResult<bool>;
+
+    /// Undo [`mark_claim_code_claimed`](Self::mark_claim_code_claimed),
+    /// restoring the preassigned `device_id`.
+    async fn release_claim_code(&self, id: Uuid, device_id: Option<&str>) -> ApiResult<()>;
+}
+
+/// Command templates and their runs.
+#[async_trait]
+pub trait TemplateStore: Send + Sync {
+    /// Returns `false`, storing nothing, if the template's fleet already
+    /// has one by that name (ignoring case).
+    async fn insert_template(&self, template: &CommandTemplate) -> ApiResult<bool>;
+
+    /// Templates, oldest first: those of `fleet_id` (and shared ones) when
+    /// given, limited to shared templates and `tenants` when those are given.
+    async fn list_templates(
+        &self,
+        fleet_id: Option<&str>,
+        tenants: Option<&[String]>,
+    ) -> ApiResult<Vec<CommandTemplate>>;
+
+    async fn template(&self, id: Uuid) -> ApiResult<Option<CommandTemplate>>;
+
+    /// Delete a template and its runs. Returns `false` if it did not exist.
+    async fn delete_template(&self, id: Uuid) -> ApiResult<bool>;
+
+    /// Record a run. The memory store keeps the last
+    /// [`MAX_RUNS_KEPT`](crate::templates::MAX_RUNS_KEPT) per template.
+    async fn insert_template_run(&self, run: &TemplateRun) -> ApiResult<()>;
+
+    /// One page of a template's runs, newest first, and the total count.
+    async fn list_template_runs(
+        &self,
+        template_id: Uuid,
+        limit: u32,
+        offset: u32,
+    ) -> ApiResult<(Vec<TemplateRun>, u64)>;
+
+    async fn template_run(&self, id: Uuid) -> ApiResult<Option<TemplateRun>>;
+}
+
+/// Registered fleets and their API tokens.
+#[async_trait]
+pub trait FleetStore: Send + Sync {
+    /// Returns `false`, storing nothing, if the fleet ID is taken.
+    async fn insert_fleet(&self, fleet: &Fleet) -> ApiResult<bool>;
+
+    /// Fleets ordered by ID, limited to `tenants` when given.
+    async fn list_fleets(&self, tenants: Option<&[String]>) -> ApiResult<Vec<Fleet>>;
+
+    async fn fleet(&self, fleet_id: &str) -> ApiResult<Option<Fleet>>;
+
+    /// Whether any fleet is registered.
+    async fn any_fleet(&self) -> ApiResult<bool>;
+
+    /// Devices whose `metadata.fleet` is `fleet_id`, ordered by ID.
+    async fn fleet_devices(&self, fleet_id: &str) -> ApiResult<Vec<DeviceInfo>>;
+
+    /// Delete a fleet and its tokens. Returns `false` if it did not exist.
+    async fn delete_fleet(&self, fleet_id: &str) -> ApiResult<bool>;
+
+    async fn insert_fleet_token(&self, token: &FleetToken) -> ApiResult<()>;
+
+    /// A fleet's tokens, oldest first.
+    async fn list_fleet_tokens(&self, fleet_id: &str) -> ApiResult<Vec<FleetToken>>;
+
+    /// The token with this SHA-256, expired or not.
+    async fn fleet_token_by_sha256(&self, sha256: &str) -> ApiResult<Option<FleetToken>>;
+
+    /// Returns `false` if the fleet has no such token.
+    async fn delete_fleet_token(&self, fleet_id: &str, id: Uuid) -> ApiResult<bool>;
+}
+
+/// Registered agent releases.
+#[async_trait]
+pub trait AgentReleaseStore: Send + Sync {
+    /// Returns `false`, storing nothing, if the version is taken.
+    async fn insert_agent_release(&self, release: &AgentRelease) -> ApiResult<bool>;
+
+    /// All releases, newest first.
+    async fn list_agent_releases(&self) -> ApiResult<Vec<AgentRelease>>;
+
+    async fn agent_release(&self, version: &str) -> ApiResult<Option<AgentRelease>>;
+}
+
+/// Every store a backend provides.
+pub trait Store:
+    DeviceStore
+    + CommandStore
+    + TelemetryStore
+    + ShadowStore
+    + ClaimCodeStore
+    + TemplateStore
+    + FleetStore
+    + AgentReleaseStore
+{
+}
+
+impl<T> Store for T where
+    T: DeviceStore
+        + CommandStore
+        + TelemetryStore
+        + ShadowStore
+        + ClaimCodeStore
+        + TemplateStore
+        + FleetStore
+        + AgentReleaseStore
+{
+}
+
+/// Wire name of a command status (e.g. `"completed"`).
+pub(crate) fn command_status_name(status: CommandStatus) -> String {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_else(|| format!("{status:?}").to_lowercase())
+}
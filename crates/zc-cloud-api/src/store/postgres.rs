@@ -0,0 +1,837 @@
+//! PostgreSQL backend, over the queries in [`crate::db`].
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use zc_protocol::commands::{CommandEnvelope, CommandResponse, CommandStatus, InferenceTier};
+use zc_protocol::device::{DeviceInfo, DeviceStatus, DeviceVitals};
+use zc_protocol::shadows::ShadowState;
+
+use super::{
+    AgentReleaseStore, ClaimCodeStore, CommandOutcome, CommandStore, CommandSummary, DeviceStore,
+    FleetStore, RespondedCommand, ShadowStore, TelemetryStore, TemplateStore, command_status_name,
+};
+use crate::agent_releases::AgentRelease;
+use crate::claim_codes::ClaimCode;
+use crate::command_timing::{CommandTiming, Receipt};
+use crate::db::commands::{CommandFilter, CommandRow};
+use crate::db::devices::{DeviceFilter, DeviceRow};
+use crate::db::shadows::ShadowRow;
+use crate::db::telemetry::{TelemetryFilter, TelemetryRow};
+use crate::device_report::MetricSummary;
+use crate::device_status::status_name;
+use crate::error::{ApiError, ApiResult};
+use crate::fleets::{Fleet, FleetToken};
+use crate::metrics::Metrics;
+use crate::routes::devices::{hardware_type_name, parse_device_status, row_to_device_info};
+use crate::state::CommandRecord;
+use crate::templates::{CommandTemplate, TemplateRun};
+
+/// Store backed by a PostgreSQL pool. Hot-path queries are timed in
+/// [`Metrics`].
+#[derive(Clone)]
+pub struct PgStore {
+    pool: PgPool,
+    metrics: Arc<Metrics>,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool, metrics: Arc<Metrics>) -> Self {
+        Self { pool, metrics }
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+/// Vitals as stored in the `vitals` JSONB column.
+fn vitals_json(vitals: &DeviceVitals) -> serde_json::Value {
+    serde_json::to_value(vitals).unwrap_or_default()
+}
+
+fn shadow_state(row: ShadowRow) -> ShadowState {
+    ShadowState {
+        reported: row.reported,
+        desired: row.desired,
+        version: row.version as u64,
+        last_updated: row.last_updated,
+    }
+}
+
+#[async_trait]
+impl DeviceStore for PgStore {
+    async fn get_device(&self, device_id: &str) -> ApiResult<Option<DeviceInfo>> {
+        let row = self
+            .metrics
+            .time_db(
+                "devices.get_by_device_id",
+                crate::db::devices::get_by_device_id(&self.pool, device_id),
+            )
+            .await
+            .map_err(internal)?;
+        Ok(row.map(row_to_device_info))
+    }
+
+    async fn device_exists(&self, device_id: &str) -> ApiResult<bool> {
+        crate::db::devices::exists(&self.pool, device_id)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_devices(&self, filter: &DeviceFilter) -> ApiResult<(Vec<DeviceInfo>, u64)> {
+        let (rows, total) = tokio::try_join!(
+            crate::db::devices::list_page(&self.pool, filter),
+            crate::db::devices::count(&self.pool, filter),
+        )
+        .map_err(internal)?;
+        Ok((
+            rows.into_iter().map(row_to_device_info).collect(),
+            total as u64,
+        ))
+    }
+
+    async fn insert_device(&self, device: &DeviceInfo) -> ApiResult<()> {
+        let row = DeviceRow {
+            id: device.id,
+            fleet_id: device.fleet_id.0,
+            device_id: device.device_id.clone(),
+            status: status_name(device.status).to_string(),
+            vin: device.vin.clone(),
+            hardware_type: hardware_type_name(&device.hardware_type),
+            certificate_id: device.certificate_id.clone(),
+            certificate_expires_at: device.certificate_expires_at,
+            last_heartbeat: device.last_heartbeat,
+            vitals: device.vitals.as_ref().map(vitals_json),
+            metadata: device.metadata.clone(),
+            created_at: device.created_at,
+            updated_at: device.updated_at,
+        };
+        crate::db::devices::insert(&self.pool, &row)
+            .await
+            .map_err(internal)
+    }
+
+    async fn record_heartbeat(
+        &self,
+        device_id: &str,
+        at: DateTime<Utc>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        vitals: Option<&DeviceVitals>,
+    ) -> ApiResult<Option<DeviceStatus>> {
+        let previous = crate::db::devices::update_heartbeat(
+            &self.pool,
+            device_id,
+            at,
+            cert_expires_at,
+            vitals.map(vitals_json),
+        )
+        .await
+        .map_err(internal)?;
+        Ok(previous.map(|s| parse_device_status(&s)))
+    }
+
+    async fn upsert_from_heartbeat(
+        &self,
+        device_id: &str,
+        fleet_id: &str,
+        machine_id: Option<&str>,
+        at: DateTime<Utc>,
+        cert_expires_at: Option<DateTime<Utc>>,
+        vitals: Option<&DeviceVitals>,
+    ) -> ApiResult<Option<DeviceStatus>> {
+        let previous = self
+            .metrics
+            .time_db(
+                "devices.upsert_from_heartbeat",
+                crate::db::devices::upsert_from_heartbeat(
+                    &self.pool,
+                    device_id,
+                    fleet_id,
+                    machine_id,
+                    at,
+                    cert_expires_at,
+                    vitals.map(vitals_json),
+                ),
+            )
+            .await
+            .map_err(internal)?;
+        Ok(previous.map(|s| parse_device_status(&s)))
+    }
+
+    async fn prune_heartbeats(&self, before: DateTime<Utc>) -> ApiResult<u64> {
+        crate::db::heartbeats::prune(&self.pool, before)
+            .await
+            .map_err(internal)
+    }
+
+    async fn transition_device_status(
+        &self,
+        device_id: &str,
+        from: &[DeviceStatus],
+        to: DeviceStatus,
+    ) -> ApiResult<Option<DeviceStatus>> {
+        let from: Vec<&str> = from.iter().map(|s| status_name(*s)).collect();
+        let previous =
+            crate::db::devices::transition_status(&self.pool, device_id, &from, status_name(to))
+                .await
+                .map_err(internal)?;
+        Ok(previous.as_deref().map(parse_device_status))
+    }
+}
+
+#[async_trait]
+impl CommandStore for PgStore {
+    async fn insert_command(
+        &self,
+        envelope: &CommandEnvelope,
+        status: CommandStatus,
+        inference_tier: Option<String>,
+        deadline: Option<DateTime<Utc>>,
+        max_attempts: u32,
+        idempotency_key: Option<&str>,
+    ) -> ApiResult<bool> {
+        let parsed_intent = envelope.parsed_intent.as_ref();
+        let row = CommandRow {
+            id: envelope.id,
+            fleet_id: envelope.fleet_id.clone(),
+            device_id: envelope.device_id.clone(),
+            natural_language: envelope.natural_language.clone(),
+            initiated_by: envelope.initiated_by.clone(),
+            correlation_id: envelope.correlation_id,
+            timeout_secs: envelope.timeout_secs as i32,
+            tool_name: parsed_intent.map(|i| i.tool_name.clone()),
+            tool_args: parsed_intent.map(|i| crate::structured_mode::redact_args(&i.tool_args)),
+            confidence: parsed_intent.map(|i| i.confidence),
+            status: command_status_name(status),
+            inference_tier,
+            response_text: None,
+            response_data: None,
+            latency_ms: None,
+            responded_at: None,
+            error: None,
+            error_code: None,
+            created_at: envelope.created_at,
+            envelope: serde_json::to_value(envelope).ok(),
+            conversation_id: envelope.conversation_id,
+            deadline_at: deadline,
+            attempts: 0,
+            max_attempts: max_attempts as i32,
+            next_attempt_at: None,
+            idempotency_key: idempotency_key.map(str::to_string),
+            published_at: None,
+            received_at: None,
+            device_responded_at: None,
+            execution_ms: None,
+            clock_offset_ms: None,
+        };
+        self.metrics
+            .time_db(
+                "commands.insert",
+                crate::db::commands::insert(&self.pool, &row),
+            )
+            .await
+            .map_err(internal)
+    }
+
+    async fn command_by_idempotency_key(
+        &self,
+        initiated_by: &str,
+        idempotency_key: &str,
+    ) -> ApiResult<Option<CommandEnvelope>> {
+        let row =
+            crate::db::commands::get_by_idempotency_key(&self.pool, initiated_by, idempotency_key)
+                .await
+                .map_err(internal)?;
+        Ok(row
+            .and_then(|r| r.envelope)
+            .and_then(|envelope| serde_json::from_value(envelope).ok()))
+    }
+
+    async fn command(&self, command_id: Uuid) -> ApiResult<Option<CommandRecord>> {
+        let row = crate::db::commands::get_by_id(&self.pool, command_id)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(row_to_record))
+    }
+
+    async fn set_status(&self, command_id: Uuid, status: CommandStatus) -> ApiResult<()> {
+        crate::db::commands::update_status(&self.pool, command_id, &command_status_name(status))
+            .await
+            .map_err(internal)
+    }
+
+    async fn set_published(&self, command_id: Uuid, at: DateTime<Utc>) -> ApiResult<()> {
+        crate::db::commands::set_published(&self.pool, command_id, at)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_commands(&self, filter: &CommandFilter) -> ApiResult<(Vec<CommandSummary>, u64)> {
+        let (rows, total) = tokio::try_join!(
+            crate::db::commands::list_page(&self.pool, filter),
+            crate::db::commands::count(&self.pool, filter),
+        )
+        .map_err(internal)?;
+        let page = rows
+            .into_iter()
+            .map(|r| CommandSummary {
+                id: r.id,
+                device_id: r.device_id,
+                command: r.natural_language,
+                status: r.status,
+                error_code: r.error_code,
+                initiated_by: r.initiated_by,
+                created_at: r.created_at,
+            })
+            .collect();
+        Ok((page, total as u64))
+    }
+
+    async fn record_response(
+        &self,
+        resp: &CommandResponse,
+        receipt: &Receipt,
+    ) -> ApiResult<Option<RespondedCommand>> {
+        let Some(row) = crate::db::commands::get_by_id(&self.pool, resp.command_id)
+            .await
+            .map_err(internal)?
+        else {
+            return Ok(None);
+        };
+
+        // Latency from dispatch to response, both on the cloud clock.
+        let latency_ms = (receipt.received_at - row.created_at).num_milliseconds();
+        let inference_tier = serde_json::to_value(resp.inference_tier)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from));
+        self.metrics
+            .time_db(
+                "commands.update_response",
+                crate::db::commands::update_response(
+                    &self.pool,
+                    resp.command_id,
+                    &command_status_name(resp.status),
+                    inference_tier.as_deref().unwrap_or("unknown"),
+                    resp.response_text.as_deref(),
+                    resp.response_data.as_ref(),
+                    latency_ms,
+                    resp.error.as_deref(),
+                    resp.error_code.map(|c| c.as_str()),
+                ),
+            )
+            .await
+            .map_err(internal)?;
+        crate::db::commands::set_receipt(
+            &self.pool,
+            resp.command_id,
+            receipt,
+            resp.responded_at,
+            resp.latency_ms as i64,
+        )
+        .await
+        .map_err(internal)?;
+
+        Ok(Some(RespondedCommand {
+            fleet_id: row.fleet_id,
+            initiated_by: row.initiated_by,
+            tool_args: row.tool_args,
+            intent: row
+                .envelope
+                .and_then(|v| serde_json::from_value::<CommandEnvelope>(v).ok())
+                .and_then(|e| e.parsed_intent),
+        }))
+    }
+
+    async fn claim_queued(&self, device_id: &str) -> ApiResult<Vec<CommandEnvelope>> {
+        let rows = crate::db::commands::claim_queued(&self.pool, device_id)
+            .await
+            .map_err(internal)?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| r.envelope.and_then(|v| serde_json::from_value(v).ok()))
+            .collect())
+    }
+
+    async fn requeue(&self, command_ids: &[Uuid]) -> ApiResult<()> {
+        crate::db::commands::requeue(&self.pool, command_ids)
+            .await
+            .map_err(internal)
+    }
+
+    async fn publish_attempts(&self, command_id: Uuid) -> ApiResult<Option<(u32, u32)>> {
+        let row = crate::db::commands::get_by_id(&self.pool, command_id)
+            .await
+            .map_err(internal)?;
+        Ok(row
+            .filter(|r| r.status == "pending")
+            .map(|r| (r.attempts.max(0) as u32, r.max_attempts.max(1) as u32)))
+    }
+
+    async fn record_attempt(
+        &self,
+        command_id: Uuid,
+        attempts: u32,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> ApiResult<()> {
+        crate::db::commands::record_attempt(
+            &self.pool,
+            command_id,
+            attempts as i32,
+            next_attempt_at,
+        )
+        .await
+        .map_err(internal)?;
+        Ok(())
+    }
+
+    async fn claim_due_attempts(&self, now: DateTime<Utc>) -> ApiResult<Vec<CommandEnvelope>> {
+        let rows = crate::db::commands::claim_due_attempts(&self.pool, now)
+            .await
+            .map_err(internal)?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| r.envelope.and_then(|v| serde_json::from_value(v).ok()))
+            .collect())
+    }
+
+    async fn fail_undelivered(&self, response: &CommandResponse, attempts: u32) -> ApiResult<()> {
+        crate::db::commands::record_attempt(&self.pool, response.command_id, attempts as i32, None)
+            .await
+            .map_err(internal)?;
+        crate::db::commands::update_response(
+            &self.pool,
+            response.command_id,
+            &command_status_name(response.status),
+            "local",
+            None,
+            None,
+            response.latency_ms as i64,
+            response.error.as_deref(),
+            response.error_code.map(|c| c.as_str()),
+        )
+        .await
+        .map_err(internal)
+    }
+
+    async fn completed_tool_results(
+        &self,
+        device_id: &str,
+        tool_name: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> ApiResult<Vec<(DateTime<Utc>, serde_json::Value)>> {
+        let rows = crate::db::device_report::completed_commands(
+            &self.pool, device_id, tool_name, since, limit,
+        )
+        .await
+        .map_err(internal)?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| Some((r.responded_at.unwrap_or(r.created_at), r.response_data?)))
+            .collect())
+    }
+
+    async fn command_outcomes(&self, ids: &[Uuid]) -> ApiResult<Vec<CommandOutcome>> {
+        let rows = crate::db::commands::list_by_ids(&self.pool, ids)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(row_to_outcome).collect())
+    }
+
+    async fn broadcast_outcomes(
+        &self,
+        broadcast_id: Uuid,
+        fleet_id: &str,
+    ) -> ApiResult<Option<(CommandEnvelope, Vec<CommandOutcome>)>> {
+        let rows: Vec<CommandRow> =
+            crate::db::commands::list_by_correlation(&self.pool, broadcast_id)
+                .await
+                .map_err(internal)?
+                .into_iter()
+                .filter(|r| r.id != broadcast_id && r.fleet_id == fleet_id)
+                .collect();
+        let Some(first) = rows.first() else {
+            return Ok(None);
+        };
+        let envelope: CommandEnvelope = first
+            .envelope
+            .clone()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .ok_or_else(|| ApiError::Internal("broadcast envelope missing".into()))?;
+        Ok(Some((
+            envelope,
+            rows.into_iter().map(row_to_outcome).collect(),
+        )))
+    }
+}
+
+/// A command row as the in-memory record. Rows from before envelopes were
+/// stored get one rebuilt from their text. The response carries the
+/// device's own times when it sent one, as an in-memory response does.
+fn row_to_record(row: CommandRow) -> CommandRecord {
+    let status: CommandStatus =
+        serde_json::from_value(serde_json::json!(row.status)).unwrap_or(CommandStatus::Pending);
+    let envelope = row
+        .envelope
+        .and_then(|v| serde_json::from_value::<CommandEnvelope>(v).ok())
+        .unwrap_or_else(|| {
+            let mut envelope = CommandEnvelope::new(
+                &row.fleet_id,
+                &row.device_id,
+                &row.natural_language,
+                &row.initiated_by,
+            );
+            envelope.id = row.id;
+            envelope.correlation_id = row.correlation_id;
+            envelope.conversation_id = row.conversation_id;
+            envelope
+        });
+    let response = row.responded_at.map(|responded_at| CommandResponse {
+        command_id: row.id,
+        correlation_id: row.correlation_id,
+        device_id: row.device_id.clone(),
+        status,
+        inference_tier: row
+            .inference_tier
+            .and_then(|t| serde_json::from_value(serde_json::json!(t)).ok())
+            .unwrap_or(InferenceTier::Local),
+        response_text: row.response_text,
+        response_data: row.response_data,
+        latency_ms: row.execution_ms.or(row.latency_ms).unwrap_or(0).max(0) as u64,
+        responded_at: row.device_responded_at.unwrap_or(responded_at),
+        error: row.error,
+        error_code: row
+            .error_code
+            .and_then(|c| serde_json::from_value(serde_json::json!(c)).ok()),
+        cached: false,
+    });
+    CommandRecord {
+        envelope,
+        response,
+        status,
+        created_at: row.created_at,
+        deadline: row.deadline_at,
+        attempts: row.attempts.max(0) as u32,
+        max_attempts: row.max_attempts.max(1) as u32,
+        next_attempt_at: row.next_attempt_at,
+        idempotency_key: row.idempotency_key,
+        timing: CommandTiming {
+            published_at: row.published_at,
+            received_at: row.received_at,
+            clock_offset_ms: row.clock_offset_ms,
+        },
+    }
+}
+
+fn row_to_outcome(row: CommandRow) -> CommandOutcome {
+    CommandOutcome {
+        status: serde_json::from_value(serde_json::json!(row.status))
+            .unwrap_or(CommandStatus::Pending),
+        device_id: row.device_id,
+        command_id: row.id,
+        response_text: row.response_text,
+        response_data: row.response_data,
+        error: row.error,
+    }
+}
+
+#[async_trait]
+impl TelemetryStore for PgStore {
+    async fn append_telemetry(&self, rows: &mut Vec<TelemetryRow>) -> ApiResult<()> {
+        let result = self
+            .metrics
+            .time_db(
+                "telemetry.insert_batch",
+                crate::db::telemetry::insert_batch(&self.pool, rows),
+            )
+            .await;
+        rows.clear();
+        result.map_err(internal)
+    }
+
+    async fn telemetry_page(
+        &self,
+        device_id: &str,
+        filter: &TelemetryFilter,
+    ) -> ApiResult<Vec<TelemetryRow>> {
+        crate::db::telemetry::query_page(&self.pool, device_id, filter)
+            .await
+            .map_err(internal)
+    }
+
+    async fn prune_telemetry(&self, before: DateTime<Utc>) -> ApiResult<u64> {
+        crate::db::telemetry::prune(&self.pool, before)
+            .await
+            .map_err(internal)
+    }
+
+    async fn telemetry_summary(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> ApiResult<Vec<MetricSummary>> {
+        let rows = crate::db::device_report::telemetry_summary(&self.pool, device_id, since, limit)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+}
+
+#[async_trait]
+impl ShadowStore for PgStore {
+    async fn list_shadows(&self, device_id: &str) -> ApiResult<Vec<(String, ShadowState)>> {
+        let rows = crate::db::shadows::list_shadows(&self.pool, device_id)
+            .await
+            .map_err(internal)?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.shadow_name.clone(), shadow_state(r)))
+            .collect())
+    }
+
+    async fn get_shadow(&self, device_id: &str, name: &str) -> ApiResult<Option<ShadowState>> {
+        let row = crate::db::shadows::get_shadow(&self.pool, device_id, name)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(shadow_state))
+    }
+
+    async fn set_desired(
+        &self,
+        device_id: &str,
+        name: &str,
+        desired: &serde_json::Value,
+    ) -> ApiResult<ShadowState> {
+        crate::db::shadows::set_desired(&self.pool, device_id, name, desired)
+            .await
+            .map(shadow_state)
+            .map_err(internal)
+    }
+
+    async fn merge_reported(
+        &self,
+        device_id: &str,
+        name: &str,
+        reported: &serde_json::Value,
+    ) -> ApiResult<ShadowState> {
+        crate::db::shadows::upsert_reported(&self.pool, device_id, name, reported)
+            .await
+            .map(shadow_state)
+            .map_err(internal)
+    }
+
+    async fn delete_shadow(&self, device_id: &str, name: &str) -> ApiResult<bool> {
+        crate::db::shadows::delete_shadow(&self.pool, device_id, name)
+            .await
+            .map_err(internal)
+    }
+}
+
+#[async_trait]
+impl ClaimCodeStore for PgStore {
+    async fn insert_claim_code(&self, code: &ClaimCode) -> ApiResult<()> {
+        crate::db::claim_codes::insert(&self.pool, code)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_claim_codes(&self, fleets: Option<&[String]>) -> ApiResult<Vec<ClaimCode>> {
+        let rows = crate::db::claim_codes::list(&self.pool, fleets)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn claim_code(&self, id: Uuid) -> ApiResult<Option<ClaimCode>> {
+        let row = crate::db::claim_codes::get(&self.pool, id)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn claim_code_by_sha256(&self, sha256: &str) -> ApiResult<Option<ClaimCode>> {
+        let row = crate::db::claim_codes::by_sha256(&self.pool, sha256)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn delete_unclaimed_claim_code(&self, id: Uuid) -> ApiResult<bool> {
+        crate::db::claim_codes::delete_unclaimed(&self.pool, id)
+            .await
+            .map_err(internal)
+    }
+
+    async fn mark_claim_code_claimed(
+        &self,
+        id: Uuid,
+        hardware_id: &str,
+        device_id: &str,
+        at: DateTime<Utc>,
+    ) -> ApiResult<bool> {
+        crate::db::claim_codes::mark_claimed(&self.pool, id, hardware_id, device_id, at)
+            .await
+            .map_err(internal)
+    }
+
+    async fn release_claim_code(&self, id: Uuid, device_id: Option<&str>) -> ApiResult<()> {
+        crate::db::claim_codes::release(&self.pool, id, device_id)
+            .await
+            .map_err(internal)
+    }
+}
+
+#[async_trait]
+impl TemplateStore for PgStore {
+    async fn insert_template(&self, template: &CommandTemplate) -> ApiResult<bool> {
+        crate::db::templates::insert(&self.pool, template)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_templates(
+        &self,
+        fleet_id: Option<&str>,
+        tenants: Option<&[String]>,
+    ) -> ApiResult<Vec<CommandTemplate>> {
+        let rows = crate::db::templates::list(&self.pool, fleet_id, tenants)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn template(&self, id: Uuid) -> ApiResult<Option<CommandTemplate>> {
+        let row = crate::db::templates::get(&self.pool, id)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn delete_template(&self, id: Uuid) -> ApiResult<bool> {
+        crate::db::templates::delete(&self.pool, id)
+            .await
+            .map_err(internal)
+    }
+
+    async fn insert_template_run(&self, run: &TemplateRun) -> ApiResult<()> {
+        crate::db::templates::insert_run(&self.pool, run)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_template_runs(
+        &self,
+        template_id: Uuid,
+        limit: u32,
+        offset: u32,
+    ) -> ApiResult<(Vec<TemplateRun>, u64)> {
+        let (rows, total) = tokio::try_join!(
+            crate::db::templates::list_runs(&self.pool, template_id, limit, offset),
+            crate::db::templates::count_runs(&self.pool, template_id),
+        )
+        .map_err(internal)?;
+        Ok((rows.into_iter().map(Into::into).collect(), total as u64))
+    }
+
+    async fn template_run(&self, id: Uuid) -> ApiResult<Option<TemplateRun>> {
+        let row = crate::db::templates::get_run(&self.pool, id)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+}
+
+#[async_trait]
+impl FleetStore for PgStore {
+    async fn insert_fleet(&self, fleet: &Fleet) -> ApiResult<bool> {
+        crate::db::fleets::insert(&self.pool, fleet)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_fleets(&self, tenants: Option<&[String]>) -> ApiResult<Vec<Fleet>> {
+        let rows = crate::db::fleets::list(&self.pool, tenants)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn fleet(&self, fleet_id: &str) -> ApiResult<Option<Fleet>> {
+        let row = crate::db::fleets::get(&self.pool, fleet_id)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn any_fleet(&self) -> ApiResult<bool> {
+        crate::db::fleets::any(&self.pool).await.map_err(internal)
+    }
+
+    async fn fleet_devices(&self, fleet_id: &str) -> ApiResult<Vec<DeviceInfo>> {
+        let rows = crate::db::devices::list_by_fleet(&self.pool, fleet_id)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(row_to_device_info).collect())
+    }
+
+    async fn delete_fleet(&self, fleet_id: &str) -> ApiResult<bool> {
+        crate::db::fleets::delete(&self.pool, fleet_id)
+            .await
+            .map_err(internal)
+    }
+
+    async fn insert_fleet_token(&self, token: &FleetToken) -> ApiResult<()> {
+        crate::db::fleets::insert_token(&self.pool, token)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_fleet_tokens(&self, fleet_id: &str) -> ApiResult<Vec<FleetToken>> {
+        let rows = crate::db::fleets::list_tokens(&self.pool, fleet_id)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn fleet_token_by_sha256(&self, sha256: &str) -> ApiResult<Option<FleetToken>> {
+        let row = crate::db::fleets::token_by_sha256(&self.pool, sha256)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn delete_fleet_token(&self, fleet_id: &str, id: Uuid) -> ApiResult<bool> {
+        crate::db::fleets::delete_token(&self.pool, fleet_id, id)
+            .await
+            .map_err(internal)
+    }
+}
+
+#[async_trait]
+impl AgentReleaseStore for PgStore {
+    async fn insert_agent_release(&self, release: &AgentRelease) -> ApiResult<bool> {
+        crate::db::agent_releases::insert(&self.pool, release)
+            .await
+            .map_err(internal)
+    }
+
+    async fn list_agent_releases(&self) -> ApiResult<Vec<AgentRelease>> {
+        let rows = crate::db::agent_releases::list(&self.pool)
+            .await
+            .map_err(internal)?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn agent_release(&self, version: &str) -> ApiResult<Option<AgentRelease>> {
+        let row = crate::db::agent_releases::get(&self.pool, version)
+            .await
+            .map_err(internal)?;
+        Ok(row.map(Into::into))
+    }
+}
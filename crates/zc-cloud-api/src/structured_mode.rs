@@ -0,0 +1,172 @@
+//! Inference-free command mode for security-sensitive fleets.
+//!
+//! Any command may name its tool directly (`tool_name` / `tool_args`), which
+//! skips the inference engines. Fleets listed in `STRUCTURED_ONLY_FLEETS`
+//! accept nothing else: natural-language commands are refused with 400
+//! before they reach an inference module. Agents in those fleets should set
+//! `structured_commands_only` so the device refuses envelopes without a
+//! parsed intent as well.
+
+use serde_json::Value;
+use zc_protocol::commands::{ActionKind, ParsedIntent};
+
+use crate::error::{ApiError, ApiResult};
+use crate::inference::ParseResult;
+use crate::mqtt_bridge::FleetFilter;
+
+/// Inference tier recorded for structured dispatches.
+pub const TIER: &str = "structured";
+
+/// Tool arguments holding a pre-signed URL, whose query string is the
+/// credential (`upload_logs`).
+const SIGNED_URL_ARGS: &[&str] = &["upload_url"];
+
+/// Fleets that only take structured commands.
+#[derive(Debug, Clone, Default)]
+pub struct StructuredMode {
+    fleets: Option<FleetFilter>,
+}
+
+impl StructuredMode {
+    /// `None` leaves every fleet free to use natural language.
+    pub fn new(fleets: Option<FleetFilter>) -> Self {
+        Self { fleets }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.fleets.is_some()
+    }
+
+    pub fn applies_to(&self, fleet_id: &str) -> bool {
+        self.fleets.as_ref().is_some_and(|f| f.admits(fleet_id))
+    }
+
+    /// Refuse a natural-language command aimed at a structured-only fleet.
+    pub fn check<'a>(
+        &self,
+        fleets: impl IntoIterator<Item = &'a str>,
+        structured: bool,
+    ) -> ApiResult<()> {
+        if structured {
+            return Ok(());
+        }
+        match fleets.into_iter().find(|f| self.applies_to(f)) {
+            Some(fleet) => Err(ApiError::BadRequest(format!(
+                "fleet '{fleet}' accepts structured commands only: set tool_name and tool_args"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The intent for an explicit `tool_name` / `tool_args` dispatch, or `None`
+/// when the request carries neither.
+pub fn intent(
+    tool_name: Option<String>,
+    tool_args: Option<Value>,
+) -> ApiResult<Option<ParseResult>> {
+    let tool_name = match tool_name {
+        Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+        Some(_) => return Err(ApiError::BadRequest("tool_name must not be empty".into())),
+        None if tool_args.is_some() => {
+            return Err(ApiError::BadRequest(
+                "tool_args given without tool_name".into(),
+            ));
+        }
+        None => return Ok(None),
+    };
+    let tool_args = tool_args.unwrap_or_else(|| Value::Object(Default::default()));
+    if !tool_args.is_object() {
+        return Err(ApiError::BadRequest(
+            "tool_args must be a JSON object".into(),
+        ));
+    }
+    Ok(Some(ParseResult {
+        intent: ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name,
+            tool_args,
+            confidence: 1.0,
+            steps: Vec::new(),
+        },
+        tier: TIER.into(),
+    }))
+}
+
+/// Command text recorded for a structured dispatch sent without one, with
+/// [`redact_args`] applied.
+pub fn describe(intent: &ParsedIntent) -> String {
+    match intent.tool_args.as_object() {
+        Some(args) if !args.is_empty() => {
+            format!("{} {}", intent.tool_name, redact_args(&intent.tool_args))
+        }
+        _ => intent.tool_name.clone(),
+    }
+}
+
+/// `tool_args` as kept in the command log: the query string of a pre-signed
+/// URL is replaced by `REDACTED`, so the signature is not stored or audited.
+pub fn redact_args(args: &Value) -> Value {
+    let mut args = args.clone();
+    for name in SIGNED_URL_ARGS {
+        if let Some(url) = args.get_mut(*name)
+            && let Some((base, _)) = url.as_str().and_then(|u| u.split_once('?'))
+        {
+            *url = Value::String(format!("{base}?REDACTED"));
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn explicit_tool_becomes_full_confidence_intent() {
+        assert!(intent(None, None).unwrap().is_none());
+
+        let parsed = intent(Some(" read_pid ".into()), Some(json!({"pid": 12})))
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.tier, TIER);
+        assert_eq!(parsed.intent.action, ActionKind::Tool);
+        assert_eq!(parsed.intent.tool_name, "read_pid");
+        assert_eq!(parsed.intent.confidence, 1.0);
+        assert_eq!(describe(&parsed.intent), r#"read_pid {"pid":12}"#);
+
+        let bare = intent(Some("read_dtcs".into()), None).unwrap().unwrap();
+        assert_eq!(bare.intent.tool_args, json!({}));
+        assert_eq!(describe(&bare.intent), "read_dtcs");
+
+        let upload = intent(
+            Some("upload_logs".into()),
+            Some(json!({
+                "path": "/var/log/syslog",
+                "upload_url": "https://bucket.example.com/rpi-001/syslog.gz?X-Amz-Signature=abc",
+            })),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            redact_args(&upload.intent.tool_args)["upload_url"],
+            "https://bucket.example.com/rpi-001/syslog.gz?REDACTED"
+        );
+        assert!(!describe(&upload.intent).contains("X-Amz-Signature"));
+
+        assert!(intent(Some(" ".into()), None).is_err());
+        assert!(intent(None, Some(json!({}))).is_err());
+        assert!(intent(Some("read_pid".into()), Some(json!([12]))).is_err());
+    }
+
+    #[test]
+    fn only_listed_fleets_are_strict() {
+        let mode = StructuredMode::new(FleetFilter::parse("fleet-secure").unwrap());
+        assert!(mode.enabled());
+        assert!(mode.check(["fleet-alpha"], false).is_ok());
+        assert!(mode.check(["fleet-alpha", "fleet-secure"], false).is_err());
+        assert!(mode.check(["fleet-secure"], true).is_ok());
+        assert!(!StructuredMode::default().applies_to("fleet-secure"));
+    }
+}
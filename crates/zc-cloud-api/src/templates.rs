@@ -0,0 +1,696 @@
+//! Command templates: saved multi-step diagnostics.
+//!
+//! A template is a named list of steps ("full health check" = read DTCs,
+//! log stats, disk usage), each a command as `POST /api/v1/commands` takes
+//! it: natural language, or an explicit `tool_name` / `tool_args`. Step
+//! text and string tool arguments may contain `{{name}}` placeholders for
+//! the template's declared parameters; a string that is exactly one
+//! placeholder takes a numeric or boolean value as such, so
+//! `"window_secs": "{{window}}"` becomes a number.
+//!
+//! [`dispatch`] sends every step, in order, to one device ([`send_command`])
+//! or a fleet ([`broadcast_command`]), so inference, structured mode,
+//! approval, rate limits and the offline queue apply exactly as for
+//! commands an operator sends one by one. Templates have at most
+//! [`MAX_STEPS`] steps, which fits the default per-device burst. Each
+//! dispatch is kept as a [`TemplateRun`] with the commands every step
+//! created; [`report`] folds their current state into a single
+//! [`TemplateReport`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use zc_protocol::commands::CommandStatus;
+
+use crate::auth::Principal;
+use crate::error::{ApiError, ApiResult};
+use crate::routes::commands::{SendCommandRequest, is_terminal, send_command};
+use crate::routes::fleets::{BroadcastCommandRequest, broadcast_command};
+use crate::state::AppState;
+
+/// Most steps a template may have.
+pub const MAX_STEPS: usize = 10;
+/// Most parameters a template may declare.
+pub const MAX_PARAMETERS: usize = 20;
+/// Runs kept per template in memory mode (the database keeps all).
+pub const MAX_RUNS_KEPT: usize = 100;
+
+/// One command of a template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateStep {
+    /// Natural-language command text. Optional when `tool_name` is set.
+    #[serde(default)]
+    pub command: String,
+    /// Tool to run as-is, skipping inference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Arguments for `tool_name` (a JSON object).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_args: Option<Value>,
+}
+
+/// A value filled in at dispatch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateParameter {
+    /// Placeholder name: letters, digits and `_`.
+    pub name: String,
+    /// Used when a dispatch leaves the parameter out; required otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A saved command sequence.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandTemplate {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Fleet the template belongs to; shared by all fleets when `None`.
+    pub fleet_id: Option<String>,
+    pub parameters: Vec<TemplateParameter>,
+    pub steps: Vec<TemplateStep>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CommandTemplate {
+    /// Check names, step shapes and that every placeholder is declared.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.steps.is_empty() || self.steps.len() > MAX_STEPS {
+            return Err(format!("a template needs 1 to {MAX_STEPS} steps"));
+        }
+        if self.parameters.len() > MAX_PARAMETERS {
+            return Err(format!("at most {MAX_PARAMETERS} parameters"));
+        }
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            if parameter.name.is_empty()
+                || !parameter
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(format!(
+                    "parameter name '{}' must be letters, digits and '_'",
+                    parameter.name
+                ));
+            }
+            if self.parameters[..i]
+                .iter()
+                .any(|p| p.name == parameter.name)
+            {
+                return Err(format!("parameter '{}' declared twice", parameter.name));
+            }
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            let step_no = i + 1;
+            match &step.tool_name {
+                Some(name) if name.trim().is_empty() => {
+                    return Err(format!("step {step_no}: tool_name must not be empty"));
+                }
+                Some(_) => {}
+                None if step.tool_args.is_some() => {
+                    return Err(format!("step {step_no}: tool_args given without tool_name"));
+                }
+                None if step.command.trim().is_empty() => {
+                    return Err(format!("step {step_no}: command or tool_name is required"));
+                }
+                None => {}
+            }
+            if step.tool_args.as_ref().is_some_and(|a| !a.is_object()) {
+                return Err(format!("step {step_no}: tool_args must be a JSON object"));
+            }
+            let mut used = placeholders(&step.command);
+            if let Some(args) = &step.tool_args {
+                value_placeholders(args, &mut used);
+            }
+            if let Some(unknown) = used
+                .iter()
+                .find(|name| !self.parameters.iter().any(|p| p.name == **name))
+            {
+                return Err(format!(
+                    "step {step_no}: placeholder '{{{{{unknown}}}}}' is not a declared parameter"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The steps with `params` (and defaults) filled in.
+    pub fn render(&self, params: &BTreeMap<String, String>) -> Result<Vec<TemplateStep>, String> {
+        if let Some(unknown) = params
+            .keys()
+            .find(|name| !self.parameters.iter().any(|p| &p.name == *name))
+        {
+            return Err(format!("unknown parameter '{unknown}'"));
+        }
+        let mut values = HashMap::new();
+        for parameter in &self.parameters {
+            let value = params
+                .get(&parameter.name)
+                .or(parameter.default.as_ref())
+                .ok_or_else(|| format!("parameter '{}' is required", parameter.name))?;
+            values.insert(parameter.name.as_str(), value.as_str());
+        }
+        Ok(self
+            .steps
+            .iter()
+            .map(|step| TemplateStep {
+                command: substitute(&step.command, &values),
+                tool_name: step.tool_name.clone(),
+                tool_args: step
+                    .tool_args
+                    .as_ref()
+                    .map(|args| substitute_value(args, &values)),
+            })
+            .collect())
+    }
+}
+
+/// Names of the `{{name}}` placeholders in `text`.
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        names.push(after[..end].trim());
+        rest = &after[end + 2..];
+    }
+    names
+}
+
+fn value_placeholders<'a>(value: &'a Value, names: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => names.extend(placeholders(s)),
+        Value::Array(items) => items.iter().for_each(|v| value_placeholders(v, names)),
+        Value::Object(map) => map.values().for_each(|v| value_placeholders(v, names)),
+        _ => {}
+    }
+}
+
+fn substitute(text: &str, values: &HashMap<&str, &str>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = after[..end].trim();
+        out.push_str(values.get(name).copied().unwrap_or_default());
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn substitute_value(value: &Value, values: &HashMap<&str, &str>) -> Value {
+    match value {
+        Value::String(s) => {
+            let rendered = substitute(s, values);
+            let whole = s.trim().starts_with("{{")
+                && s.trim().ends_with("}}")
+                && placeholders(s).len() == 1;
+            match serde_json::from_str::<Value>(&rendered) {
+                Ok(typed @ (Value::Number(_) | Value::Bool(_))) if whole => typed,
+                _ => Value::String(rendered),
+            }
+        }
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| substitute_value(v, values)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), substitute_value(v, values)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Where a template is dispatched.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub fleet_id: String,
+    /// Single device; every (tagged) fleet device when `None`.
+    pub device_id: Option<String>,
+    /// `key:value` tags a fleet device must carry.
+    pub tags: Vec<String>,
+}
+
+/// One step of a run and the commands it created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunStep {
+    /// The rendered command text.
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Commands created, one per targeted device.
+    pub command_ids: Vec<Uuid>,
+    /// Broadcast ID for fleet runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broadcast_id: Option<Uuid>,
+    /// Why dispatch of this step was refused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One dispatch of a template.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateRun {
+    pub id: Uuid,
+    pub template_id: Uuid,
+    pub template_name: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub tags: Vec<String>,
+    /// Parameter values used, defaults included.
+    pub params: BTreeMap<String, String>,
+    pub steps: Vec<RunStep>,
+    pub initiated_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Overall state of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// Some command has not finished yet.
+    Running,
+    /// Every command of every step completed.
+    Completed,
+    /// All finished, but a step was refused or a command did not complete.
+    Failed,
+}
+
+/// One device's result for a step.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub device_id: String,
+    pub command_id: Uuid,
+    pub status: CommandStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A step and its per-device results.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepReport {
+    /// Position in the template, from 1.
+    pub step: usize,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub results: Vec<StepResult>,
+}
+
+/// A run with the current results of all its commands.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateReport {
+    pub run_id: Uuid,
+    pub template_id: Uuid,
+    pub template_name: String,
+    pub fleet_id: String,
+    pub device_id: Option<String>,
+    pub params: BTreeMap<String, String>,
+    pub initiated_by: String,
+    pub created_at: DateTime<Utc>,
+    pub status: RunStatus,
+    /// Command count per status, over all steps.
+    pub counts: BTreeMap<String, usize>,
+    pub steps: Vec<StepReport>,
+}
+
+fn not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("template {id} not found"))
+}
+
+/// Store a new template; 409 if its fleet already has one by that name.
+pub async fn create(state: &AppState, template: &CommandTemplate) -> ApiResult<()> {
+    if !state.store.insert_template(template).await? {
+        return Err(ApiError::Conflict(format!(
+            "template '{}' already exists",
+            template.name
+        )));
+    }
+    tracing::info!(
+        template_id = %template.id,
+        name = %template.name,
+        fleet_id = ?template.fleet_id,
+        steps = template.steps.len(),
+        "command template created"
+    );
+    Ok(())
+}
+
+/// Templates, oldest first: those of `fleet_id` (and shared ones) when
+/// given, limited to shared templates and `tenants` when those are given.
+pub async fn list(
+    state: &AppState,
+    fleet_id: Option<&str>,
+    tenants: Option<&[String]>,
+) -> ApiResult<Vec<CommandTemplate>> {
+    state.store.list_templates(fleet_id, tenants).await
+}
+
+/// A template by ID.
+pub async fn get(state: &AppState, id: Uuid) -> ApiResult<CommandTemplate> {
+    state.store.template(id).await?.ok_or_else(|| not_found(id))
+}
+
+/// Delete a template and its run history (not the commands it created).
+pub async fn delete(state: &AppState, id: Uuid) -> ApiResult<()> {
+    if !state.store.delete_template(id).await? {
+        return Err(not_found(id));
+    }
+    tracing::info!(template_id = %id, "command template deleted");
+    Ok(())
+}
+
+/// Send every step of `template` to `target`, in order, and record the run.
+///
+/// A first step that cannot be dispatched (unknown device, empty fleet,
+/// rate limit, ...) fails the whole request and nothing is recorded; a
+/// later step that fails is recorded with its error and the rest still go
+/// out.
+pub async fn dispatch(
+    state: &AppState,
+    principal: Option<Extension<Principal>>,
+    template: &CommandTemplate,
+    target: Target,
+    params: BTreeMap<String, String>,
+    initiated_by: String,
+) -> ApiResult<TemplateRun> {
+    let rendered = template.render(&params).map_err(ApiError::BadRequest)?;
+    let params = template
+        .parameters
+        .iter()
+        .filter_map(|p| {
+            let value = params.get(&p.name).or(p.default.as_ref())?;
+            Some((p.name.clone(), value.clone()))
+        })
+        .collect();
+
+    let mut steps = Vec::with_capacity(rendered.len());
+    for (i, step) in rendered.into_iter().enumerate() {
+        let mut run_step = RunStep {
+            command: step.command.clone(),
+            tool_name: step.tool_name.clone(),
+            command_ids: Vec::new(),
+            broadcast_id: None,
+            error: None,
+        };
+        let result = match &target.device_id {
+            Some(device_id) => send_command(
+                State(state.clone()),
+                principal.clone(),
+                HeaderMap::new(),
+                Json(SendCommandRequest {
+                    device_id: device_id.clone(),
+                    fleet_id: target.fleet_id.clone(),
+                    command: step.command,
+                    tool_name: step.tool_name,
+                    tool_args: step.tool_args,
+                    initiated_by: initiated_by.clone(),
+                    preflight: false,
+                    force: false,
+                    conversation_id: None,
+                    idempotency_key: None,
+                }),
+            )
+            .await
+            .map(|Json(envelope)| {
+                run_step.command = envelope.natural_language.clone();
+                run_step.command_ids.push(envelope.id);
+            }),
+            None => broadcast_command(
+                State(state.clone()),
+                Path(target.fleet_id.clone()),
+                principal.clone(),
+                Json(BroadcastCommandRequest {
+                    command: step.command,
+                    tool_name: step.tool_name,
+                    tool_args: step.tool_args,
+                    initiated_by: initiated_by.clone(),
+                    tags: target.tags.clone(),
+                }),
+            )
+            .await
+            .map(|Json(summary)| {
+                run_step.command = summary.command.clone();
+                run_step.broadcast_id = Some(summary.broadcast_id);
+                run_step.command_ids = summary.devices.iter().map(|d| d.command_id).collect();
+            }),
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if i == 0 => return Err(e),
+            Err(e) => {
+                tracing::warn!(template_id = %template.id, step = i + 1, error = %e, "template step dispatch failed");
+                run_step.error = Some(e.to_string());
+            }
+        }
+        steps.push(run_step);
+    }
+
+    let run = TemplateRun {
+        id: Uuid::now_v7(),
+        template_id: template.id,
+        template_name: template.name.clone(),
+        fleet_id: target.fleet_id,
+        device_id: target.device_id,
+        tags: target.tags,
+        params,
+        steps,
+        initiated_by,
+        created_at: Utc::now(),
+    };
+    state.store.insert_template_run(&run).await?;
+    tracing::info!(
+        template_id = %template.id,
+        run_id = %run.id,
+        fleet_id = %run.fleet_id,
+        device_id = ?run.device_id,
+        commands = run.steps.iter().map(|s| s.command_ids.len()).sum::<usize>(),
+        "command template dispatched"
+    );
+    Ok(run)
+}
+
+/// One page of a template's runs (newest first) and the total count.
+pub async fn runs(
+    state: &AppState,
+    template_id: Uuid,
+    limit: u32,
+    offset: u32,
+) -> ApiResult<(Vec<TemplateRun>, u64)> {
+    state
+        .store
+        .list_template_runs(template_id, limit, offset)
+        .await
+}
+
+/// A run of `template_id` by ID.
+pub async fn get_run(state: &AppState, template_id: Uuid, run_id: Uuid) -> ApiResult<TemplateRun> {
+    let run = state.store.template_run(run_id).await?;
+    run.filter(|r| r.template_id == template_id)
+        .ok_or_else(|| ApiError::NotFound(format!("template run {run_id} not found")))
+}
+
+/// Fold the current state of a run's commands into one report.
+pub async fn report(state: &AppState, run: TemplateRun) -> ApiResult<TemplateReport> {
+    let ids: Vec<Uuid> = run
+        .steps
+        .iter()
+        .flat_map(|s| s.command_ids.iter().copied())
+        .collect();
+    let mut results = command_results(state, &ids).await?;
+
+    let mut counts = BTreeMap::new();
+    let mut running = false;
+    let mut failed = false;
+    let mut steps = Vec::with_capacity(run.steps.len());
+    for (i, step) in run.steps.into_iter().enumerate() {
+        failed |= step.error.is_some();
+        let mut step_results: Vec<StepResult> = step
+            .command_ids
+            .iter()
+            .filter_map(|id| results.remove(id))
+            .collect();
+        step_results.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        for result in &step_results {
+            running |= !is_terminal(result.status);
+            failed |= is_terminal(result.status) && result.status != CommandStatus::Completed;
+            *counts
+                .entry(crate::store::command_status_name(result.status))
+                .or_insert(0) += 1;
+        }
+        steps.push(StepReport {
+            step: i + 1,
+            command: step.command,
+            tool_name: step.tool_name,
+            broadcast_id: step.broadcast_id,
+            error: step.error,
+            results: step_results,
+        });
+    }
+    let status = if running {
+        RunStatus::Running
+    } else if failed {
+        RunStatus::Failed
+    } else {
+        RunStatus::Completed
+    };
+
+    Ok(TemplateReport {
+        run_id: run.id,
+        template_id: run.template_id,
+        template_name: run.template_name,
+        fleet_id: run.fleet_id,
+        device_id: run.device_id,
+        params: run.params,
+        initiated_by: run.initiated_by,
+        created_at: run.created_at,
+        status,
+        counts,
+        steps,
+    })
+}
+
+/// Current status and response of each command in `ids`.
+async fn command_results(state: &AppState, ids: &[Uuid]) -> ApiResult<HashMap<Uuid, StepResult>> {
+    let outcomes = state.store.command_outcomes(ids).await?;
+    Ok(outcomes
+        .into_iter()
+        .map(|o| {
+            let result = StepResult {
+                device_id: o.device_id,
+                command_id: o.command_id,
+                status: o.status,
+                response_text: o.response_text,
+                response_data: o.response_data,
+                error: o.error,
+            };
+            (result.command_id, result)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(steps: Vec<TemplateStep>, parameters: Vec<TemplateParameter>) -> CommandTemplate {
+        CommandTemplate {
+            id: Uuid::now_v7(),
+            name: "full health check".into(),
+            description: None,
+            fleet_id: None,
+            parameters,
+            steps,
+            created_by: "alice".into(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn step(command: &str, tool: Option<(&str, Value)>) -> TemplateStep {
+        TemplateStep {
+            command: command.into(),
+            tool_name: tool.as_ref().map(|(name, _)| name.to_string()),
+            tool_args: tool.map(|(_, args)| args),
+        }
+    }
+
+    fn parameter(name: &str, default: Option<&str>) -> TemplateParameter {
+        TemplateParameter {
+            name: name.into(),
+            default: default.map(String::from),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn placeholders_are_filled_and_typed() {
+        let t = template(
+            vec![
+                step("read DTCs on {{ interface }}", None),
+                step(
+                    "",
+                    Some((
+                        "log_stats",
+                        serde_json::json!({
+                            "path": "/var/log/{{file}}",
+                            "window_secs": "{{window}}",
+                            "label": "{{interface}}",
+                        }),
+                    )),
+                ),
+            ],
+            vec![
+                parameter("interface", Some("can0")),
+                parameter("file", None),
+                parameter("window", Some("300")),
+            ],
+        );
+        t.validate().unwrap();
+
+        let params = BTreeMap::from([("file".to_string(), "syslog".to_string())]);
+        let steps = t.render(&params).unwrap();
+        assert_eq!(steps[0].command, "read DTCs on can0");
+        let args = steps[1].tool_args.as_ref().unwrap();
+        assert_eq!(args["path"], "/var/log/syslog");
+        assert_eq!(args["window_secs"], 300);
+        assert_eq!(args["label"], "can0");
+
+        assert!(t.render(&BTreeMap::new()).unwrap_err().contains("'file'"));
+        let extra = BTreeMap::from([
+            ("file".to_string(), "syslog".to_string()),
+            ("device".to_string(), "x".to_string()),
+        ]);
+        assert!(t.render(&extra).unwrap_err().contains("unknown parameter"));
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        let undeclared = template(vec![step("read DTCs on {{bus}}", None)], vec![]);
+        assert!(undeclared.validate().unwrap_err().contains("{{bus}}"));
+
+        let empty_step = template(vec![step(" ", None)], vec![]);
+        assert!(empty_step.validate().is_err());
+
+        let args_array = template(
+            vec![step("", Some(("log_stats", serde_json::json!([1]))))],
+            vec![],
+        );
+        assert!(args_array.validate().is_err());
+
+        let too_long = template(vec![step("uptime", None); MAX_STEPS + 1], vec![]);
+        assert!(too_long.validate().is_err());
+
+        let bad_name = template(vec![step("uptime", None)], vec![parameter("a-b", None)]);
+        assert!(bad_name.validate().is_err());
+    }
+}
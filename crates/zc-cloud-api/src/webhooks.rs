@@ -0,0 +1,793 @@
+//! Outbound webhook notification sinks.
+//!
+//! A webhook subscribes to notification events for one fleet (or all
+//! fleets) and receives a POST for each:
+//!
+//! - `command_failed`: a command response with status `failed` or `timeout`;
+//! - `device_offline`: a device status change to `offline`;
+//! - `critical_dtc`: a DTC scan reporting critical codes.
+//!
+//! [`run`] follows the [`WsEvent`] broadcast, so a notification goes out for
+//! exactly the events dashboards see. The body is a generic JSON document
+//! (`format: generic`, with the event frame under `data`) or a Slack
+//! incoming-webhook message (`format: slack`, a single `text`).
+//!
+//! Every notification is a [`Delivery`], retried on network errors, 429 and
+//! 5xx responses with doubling backoff ([`RetryPolicy`]) and recorded after
+//! each attempt, so the management API shows what was sent and why it
+//! failed. Other 4xx responses fail the delivery at once. Deliveries are
+//! made by the process that saw the event; one still retrying when the
+//! server stops is left `pending`.
+
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+use crate::events::WsEvent;
+use crate::state::AppState;
+
+/// Deliveries kept per webhook in memory mode (the database keeps all).
+pub const MAX_DELIVERIES_KEPT: usize = 100;
+/// Timeout for a single delivery attempt.
+pub const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+/// Body format of a webhook's requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// JSON document with the event frame.
+    #[default]
+    Generic,
+    /// Slack incoming-webhook message.
+    Slack,
+}
+
+impl WebhookFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Generic => "generic",
+            Self::Slack => "slack",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "generic" => Some(Self::Generic),
+            "slack" => Some(Self::Slack),
+            _ => None,
+        }
+    }
+}
+
+/// Notification events a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    CommandFailed,
+    DeviceOffline,
+    CriticalDtc,
+}
+
+impl WebhookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::CommandFailed => "command_failed",
+            Self::DeviceOffline => "device_offline",
+            Self::CriticalDtc => "critical_dtc",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "command_failed" => Some(Self::CommandFailed),
+            "device_offline" => Some(Self::DeviceOffline),
+            "critical_dtc" => Some(Self::CriticalDtc),
+            _ => None,
+        }
+    }
+
+    /// The notification event a broadcast event amounts to, if any.
+    pub fn from_ws(event: &WsEvent) -> Option<Self> {
+        match event {
+            WsEvent::CommandResponse { status, .. }
+                if status == "failed" || status == "timeout" =>
+            {
+                Some(Self::CommandFailed)
+            }
+            WsEvent::DeviceStatusChanged { new_status, .. } if new_status == "offline" => {
+                Some(Self::DeviceOffline)
+            }
+            WsEvent::CriticalDtcDetected { .. } => Some(Self::CriticalDtc),
+            _ => None,
+        }
+    }
+}
+
+/// A webhook sink.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub name: String,
+    pub url: String,
+    pub format: WebhookFormat,
+    pub events: Vec<WebhookEvent>,
+    /// Only events from this fleet's devices; all fleets when `None`.
+    pub fleet_id: Option<String>,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    /// Whether `event` from a device in `fleet_id` goes to this webhook.
+    pub fn wants(&self, event: WebhookEvent, fleet_id: Option<&str>) -> bool {
+        self.enabled
+            && self.events.contains(&event)
+            && self.fleet_id.as_deref().is_none_or(|f| Some(f) == fleet_id)
+    }
+}
+
+/// Filters for listing webhooks.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookFilter {
+    pub enabled: Option<bool>,
+    /// Only webhooks subscribed to this event.
+    pub event: Option<WebhookEvent>,
+    /// Only webhooks of these fleets (the caller's tenants); `None` for all.
+    /// All-fleet webhooks are excluded when set.
+    pub fleets: Option<Vec<String>>,
+}
+
+impl WebhookFilter {
+    pub fn matches(&self, webhook: &Webhook) -> bool {
+        self.enabled.is_none_or(|e| webhook.enabled == e)
+            && self.event.is_none_or(|e| webhook.events.contains(&e))
+            && self.fleets.as_ref().is_none_or(|fleets| {
+                webhook
+                    .fleet_id
+                    .as_ref()
+                    .is_some_and(|f| fleets.contains(f))
+            })
+    }
+}
+
+/// Where a delivery stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not yet accepted; attempts remain.
+    Pending,
+    Delivered,
+    /// Rejected, or out of attempts.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "delivered" => Some(Self::Delivered),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// One notification sent (or being sent) to a webhook.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub device_id: String,
+    /// Request body.
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last response, if one arrived.
+    pub response_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Attempts and backoff for deliveries.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: StdDuration,
+    pub max_delay: StdDuration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: StdDuration::from_secs(2),
+            max_delay: StdDuration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait after the `attempt`-th failed attempt.
+    pub fn delay(&self, attempt: u32) -> StdDuration {
+        let exp = attempt.saturating_sub(1).min(16);
+        self.initial_delay
+            .saturating_mul(1 << exp)
+            .min(self.max_delay)
+    }
+}
+
+fn internal(e: sqlx::Error) -> ApiError {
+    ApiError::Internal(e.to_string())
+}
+
+fn not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("webhook {id} not found"))
+}
+
+/// Store a new webhook.
+pub async fn create(state: &AppState, webhook: &Webhook) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        crate::db::webhooks::insert(pool, webhook)
+            .await
+            .map_err(internal)?;
+    } else {
+        state.webhooks.write().await.push(webhook.clone());
+    }
+    tracing::info!(
+        webhook_id = %webhook.id,
+        name = %webhook.name,
+        fleet_id = ?webhook.fleet_id,
+        format = webhook.format.as_str(),
+        "webhook created"
+    );
+    Ok(())
+}
+
+/// Webhooks matching `filter`, oldest first.
+pub async fn list(state: &AppState, filter: &WebhookFilter) -> ApiResult<Vec<Webhook>> {
+    if let Some(pool) = &state.pool {
+        let rows = crate::db::webhooks::list(pool, filter)
+            .await
+            .map_err(internal)?;
+        return Ok(rows.into_iter().map(Into::into).collect());
+    }
+    let webhooks = state.webhooks.read().await;
+    Ok(webhooks
+        .iter()
+        .filter(|w| filter.matches(w))
+        .cloned()
+        .collect())
+}
+
+/// A webhook by ID.
+pub async fn get(state: &AppState, id: Uuid) -> ApiResult<Webhook> {
+    if let Some(pool) = &state.pool {
+        return crate::db::webhooks::get(pool, id)
+            .await
+            .map_err(internal)?
+            .map(Into::into)
+            .ok_or_else(|| not_found(id));
+    }
+    let webhooks = state.webhooks.read().await;
+    webhooks
+        .iter()
+        .find(|w| w.id == id)
+        .cloned()
+        .ok_or_else(|| not_found(id))
+}
+
+/// Enable or disable a webhook. Deliveries already under way finish.
+pub async fn set_enabled(state: &AppState, id: Uuid, enabled: bool) -> ApiResult<Webhook> {
+    let mut webhook = get(state, id).await?;
+    webhook.enabled = enabled;
+    webhook.updated_at = Utc::now();
+    if let Some(pool) = &state.pool {
+        crate::db::webhooks::set_enabled(pool, &webhook)
+            .await
+            .map_err(internal)?;
+    } else {
+        let mut webhooks = state.webhooks.write().await;
+        let stored = webhooks
+            .iter_mut()
+            .find(|w| w.id == id)
+            .ok_or_else(|| not_found(id))?;
+        *stored = webhook.clone();
+    }
+    tracing::info!(webhook_id = %id, enabled, "webhook updated");
+    Ok(webhook)
+}
+
+/// Delete a webhook and its deliveries.
+pub async fn delete(state: &AppState, id: Uuid) -> ApiResult<()> {
+    let deleted = if let Some(pool) = &state.pool {
+        crate::db::webhooks::delete(pool, id)
+            .await
+            .map_err(internal)?
+    } else {
+        let mut webhooks = state.webhooks.write().await;
+        let before = webhooks.len();
+        webhooks.retain(|w| w.id != id);
+        let deleted = webhooks.len() != before;
+        drop(webhooks);
+        state
+            .webhook_deliveries
+            .write()
+            .await
+            .retain(|d| d.webhook_id != id);
+        deleted
+    };
+    if !deleted {
+        return Err(not_found(id));
+    }
+    tracing::info!(webhook_id = %id, "webhook deleted");
+    Ok(())
+}
+
+/// One page of a webhook's deliveries (newest first) and the total count.
+pub async fn deliveries(
+    state: &AppState,
+    webhook_id: Uuid,
+    limit: u32,
+    offset: u32,
+) -> ApiResult<(Vec<Delivery>, u64)> {
+    if let Some(pool) = &state.pool {
+        let (rows, total) = tokio::try_join!(
+            crate::db::webhooks::list_deliveries(pool, webhook_id, limit, offset),
+            crate::db::webhooks::count_deliveries(pool, webhook_id),
+        )
+        .map_err(internal)?;
+        return Ok((
+            rows.into_iter()
+                .filter_map(crate::db::webhooks::DeliveryRow::into_delivery)
+                .collect(),
+            total as u64,
+        ));
+    }
+    let deliveries = state.webhook_deliveries.read().await;
+    let matching: Vec<Delivery> = deliveries
+        .iter()
+        .rev()
+        .filter(|d| d.webhook_id == webhook_id)
+        .cloned()
+        .collect();
+    let total = matching.len() as u64;
+    Ok((
+        crate::routes::pagination::slice(matching, offset, limit),
+        total,
+    ))
+}
+
+/// Record a new delivery or its progress.
+async fn save_delivery(state: &AppState, delivery: &Delivery) -> ApiResult<()> {
+    if let Some(pool) = &state.pool {
+        return crate::db::webhooks::save_delivery(pool, delivery)
+            .await
+            .map_err(internal);
+    }
+    let mut deliveries = state.webhook_deliveries.write().await;
+    if let Some(stored) = deliveries.iter_mut().find(|d| d.id == delivery.id) {
+        *stored = delivery.clone();
+        return Ok(());
+    }
+    deliveries.push(delivery.clone());
+    let kept = deliveries
+        .iter()
+        .filter(|d| d.webhook_id == delivery.webhook_id)
+        .count();
+    if kept > MAX_DELIVERIES_KEPT
+        && let Some(oldest) = deliveries
+            .iter()
+            .position(|d| d.webhook_id == delivery.webhook_id)
+    {
+        deliveries.remove(oldest);
+    }
+    Ok(())
+}
+
+/// One-line description of a notification, used as the Slack text.
+fn summary(event: &WsEvent) -> String {
+    match event {
+        WsEvent::CommandResponse {
+            command_id,
+            device_id,
+            status,
+            error,
+            ..
+        } => match error {
+            Some(error) => format!("Command {command_id} {status} on {device_id}: {error}"),
+            None => format!("Command {command_id} {status} on {device_id}"),
+        },
+        WsEvent::DeviceStatusChanged {
+            device_id,
+            old_status,
+            ..
+        } => format!("{device_id} went offline (was {old_status})"),
+        WsEvent::CriticalDtcDetected {
+            device_id, codes, ..
+        } => format!("Critical DTC on {device_id}: {}", codes.join(", ")),
+        other => format!("{} on {}", other.event_type(), other.device_id()),
+    }
+}
+
+/// Request body for a webhook.
+pub fn payload(
+    webhook: &Webhook,
+    delivery_id: Uuid,
+    kind: WebhookEvent,
+    fleet_id: Option<&str>,
+    event: &WsEvent,
+) -> serde_json::Value {
+    let text = summary(event);
+    match webhook.format {
+        WebhookFormat::Slack => match fleet_id {
+            Some(fleet_id) => serde_json::json!({ "text": format!("[{fleet_id}] {text}") }),
+            None => serde_json::json!({ "text": text }),
+        },
+        WebhookFormat::Generic => serde_json::json!({
+            "event": kind,
+            "summary": text,
+            "fleet_id": fleet_id,
+            "device_id": event.device_id(),
+            "webhook_id": webhook.id,
+            "delivery_id": delivery_id,
+            "data": event.to_frame(),
+        }),
+    }
+}
+
+/// Sends notifications to subscribed webhooks.
+#[derive(Clone)]
+pub struct Notifier {
+    http: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl Notifier {
+    pub fn new(retry: RetryPolicy) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(StdDuration::from_secs(DELIVERY_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+            retry,
+        }
+    }
+
+    /// Start a delivery to every webhook subscribed to `event`. Returns the
+    /// delivery tasks, each resolving to the final state of its delivery.
+    pub async fn notify(&self, state: &AppState, event: &WsEvent) -> Vec<JoinHandle<Delivery>> {
+        let Some(kind) = WebhookEvent::from_ws(event) else {
+            return Vec::new();
+        };
+        let filter = WebhookFilter {
+            enabled: Some(true),
+            event: Some(kind),
+            fleets: None,
+        };
+        let webhooks = match list(state, &filter).await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to load webhooks");
+                return Vec::new();
+            }
+        };
+        if webhooks.is_empty() {
+            return Vec::new();
+        }
+        let fleet_id = match event {
+            WsEvent::CriticalDtcDetected { fleet_id, .. } => Some(fleet_id.clone()),
+            _ => crate::auth::device_fleet(state, event.device_id()).await,
+        };
+
+        let mut tasks = Vec::new();
+        for webhook in webhooks
+            .into_iter()
+            .filter(|w| w.wants(kind, fleet_id.as_deref()))
+        {
+            let id = Uuid::now_v7();
+            let now = Utc::now();
+            let delivery = Delivery {
+                id,
+                webhook_id: webhook.id,
+                event: kind,
+                device_id: event.device_id().to_string(),
+                payload: payload(&webhook, id, kind, fleet_id.as_deref(), event),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                response_status: None,
+                last_error: None,
+                created_at: now,
+                updated_at: now,
+            };
+            if let Err(e) = save_delivery(state, &delivery).await {
+                tracing::error!(error = %e, webhook_id = %webhook.id, "failed to record webhook delivery");
+            }
+            tasks.push(tokio::spawn(self.clone().deliver(
+                state.clone(),
+                webhook.url,
+                delivery,
+            )));
+        }
+        tasks
+    }
+
+    /// POST the delivery until it is accepted, rejected or out of attempts,
+    /// recording every attempt.
+    async fn deliver(self, state: AppState, url: String, mut delivery: Delivery) -> Delivery {
+        loop {
+            delivery.attempts += 1;
+            let result = self.http.post(&url).json(&delivery.payload).send().await;
+            let retryable = match result {
+                Ok(response) if response.status().is_success() => {
+                    delivery.status = DeliveryStatus::Delivered;
+                    delivery.response_status = Some(response.status().as_u16());
+                    delivery.last_error = None;
+                    false
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    delivery.response_status = Some(status.as_u16());
+                    delivery.last_error = Some(format!("HTTP {status}"));
+                    status.is_server_error() || status.as_u16() == 429
+                }
+                Err(e) => {
+                    delivery.response_status = None;
+                    delivery.last_error = Some(e.to_string());
+                    true
+                }
+            };
+            let again = retryable && delivery.attempts < self.retry.max_attempts;
+            if delivery.status != DeliveryStatus::Delivered && !again {
+                delivery.status = DeliveryStatus::Failed;
+            }
+            delivery.updated_at = Utc::now();
+            if let Err(e) = save_delivery(&state, &delivery).await {
+                tracing::error!(error = %e, delivery_id = %delivery.id, "failed to record webhook delivery");
+            }
+            match delivery.status {
+                DeliveryStatus::Delivered => {
+                    tracing::debug!(delivery_id = %delivery.id, attempts = delivery.attempts, "webhook delivered");
+                    return delivery;
+                }
+                DeliveryStatus::Failed => {
+                    tracing::warn!(
+                        delivery_id = %delivery.id,
+                        webhook_id = %delivery.webhook_id,
+                        attempts = delivery.attempts,
+                        error = ?delivery.last_error,
+                        "webhook delivery failed"
+                    );
+                    return delivery;
+                }
+                DeliveryStatus::Pending => {
+                    tokio::time::sleep(self.retry.delay(delivery.attempts)).await;
+                }
+            }
+        }
+    }
+}
+
+/// Send webhook notifications for broadcast events, forever.
+pub async fn run(state: AppState) {
+    let notifier = Notifier::new(RetryPolicy::default());
+    let mut rx = state.event_tx.subscribe();
+    loop {
+        match rx.recv().await {
+            Ok(event) => {
+                notifier.notify(&state, &event).await;
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "webhook dispatcher lagged; events dropped");
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+
+    use super::*;
+
+    fn webhook(url: String, format: WebhookFormat, events: Vec<WebhookEvent>) -> Webhook {
+        let now = Utc::now();
+        Webhook {
+            id: Uuid::now_v7(),
+            name: "ops".into(),
+            url,
+            format,
+            events,
+            fleet_id: Some("fleet-alpha".into()),
+            enabled: true,
+            created_by: "admin".into(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn offline(device_id: &str) -> WsEvent {
+        WsEvent::DeviceStatusChanged {
+            device_id: device_id.into(),
+            old_status: "degraded".into(),
+            new_status: "offline".into(),
+            changed_at: Utc::now(),
+        }
+    }
+
+    /// A local receiver answering 500 to the first `failures` requests.
+    async fn receiver(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move || {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        StatusCode::NO_CONTENT
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}/hook"), calls)
+    }
+
+    fn quick_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_delay: StdDuration::from_millis(5),
+            max_delay: StdDuration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn events_and_payloads() {
+        let failed = WsEvent::CommandResponse {
+            command_id: Uuid::nil(),
+            device_id: "rpi-001".into(),
+            status: "timeout".into(),
+            inference_tier: None,
+            response_text: None,
+            response_data: None,
+            error: Some("no response".into()),
+            error_code: None,
+            latency_ms: None,
+            responded_at: Utc::now(),
+        };
+        assert_eq!(
+            WebhookEvent::from_ws(&failed),
+            Some(WebhookEvent::CommandFailed)
+        );
+        assert_eq!(
+            WebhookEvent::from_ws(&offline("rpi-001")),
+            Some(WebhookEvent::DeviceOffline)
+        );
+        let heartbeat = WsEvent::DeviceHeartbeat {
+            device_id: "rpi-001".into(),
+            timestamp: Utc::now(),
+        };
+        assert_eq!(WebhookEvent::from_ws(&heartbeat), None);
+
+        let slack = webhook(
+            "https://hooks.slack.com/x".into(),
+            WebhookFormat::Slack,
+            vec![WebhookEvent::CommandFailed],
+        );
+        let body = payload(
+            &slack,
+            Uuid::nil(),
+            WebhookEvent::CommandFailed,
+            Some("fleet-alpha"),
+            &failed,
+        );
+        assert_eq!(
+            body["text"],
+            format!(
+                "[fleet-alpha] Command {} timeout on rpi-001: no response",
+                Uuid::nil()
+            )
+        );
+        assert!(slack.wants(WebhookEvent::CommandFailed, Some("fleet-alpha")));
+        assert!(!slack.wants(WebhookEvent::CommandFailed, Some("fleet-beta")));
+        assert!(!slack.wants(WebhookEvent::DeviceOffline, Some("fleet-alpha")));
+
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1), StdDuration::from_secs(2));
+        assert_eq!(policy.delay(3), StdDuration::from_secs(8));
+        assert_eq!(policy.delay(10), StdDuration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn delivery_retries_until_accepted() {
+        let state = AppState::with_sample_data();
+        let (url, calls) = receiver(2).await;
+        let hook = webhook(
+            url,
+            WebhookFormat::Generic,
+            vec![WebhookEvent::DeviceOffline],
+        );
+        create(&state, &hook).await.unwrap();
+
+        let notifier = Notifier::new(quick_retries(5));
+        let tasks = notifier.notify(&state, &offline("rpi-001")).await;
+        assert_eq!(tasks.len(), 1);
+        // Devices of other fleets are not announced here.
+        assert!(
+            notifier
+                .notify(&state, &offline("sbc-010"))
+                .await
+                .is_empty()
+        );
+
+        let delivery = futures::future::join_all(tasks)
+            .await
+            .pop()
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.response_status, Some(204));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(delivery.payload["event"], "device_offline");
+        assert_eq!(delivery.payload["data"]["type"], "device_status_changed");
+
+        let (page, total) = deliveries(&state, hook.id, 50, 0).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(page[0], delivery);
+    }
+
+    #[tokio::test]
+    async fn delivery_fails_after_last_attempt() {
+        let state = AppState::with_sample_data();
+        let (url, calls) = receiver(usize::MAX).await;
+        let hook = webhook(url, WebhookFormat::Slack, vec![WebhookEvent::DeviceOffline]);
+        create(&state, &hook).await.unwrap();
+
+        let tasks = Notifier::new(quick_retries(3))
+            .notify(&state, &offline("rpi-002"))
+            .await;
+        let delivery = futures::future::join_all(tasks)
+            .await
+            .pop()
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(
+            delivery.last_error.as_deref(),
+            Some("HTTP 500 Internal Server Error")
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        delete(&state, hook.id).await.unwrap();
+        assert!(state.webhook_deliveries.read().await.is_empty());
+    }
+}
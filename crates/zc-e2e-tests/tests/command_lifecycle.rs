@@ -5,11 +5,10 @@ mod helpers;
 
 use axum::http::StatusCode;
 use chrono::Utc;
-use serde_json::json;
 use uuid::Uuid;
 
 use helpers::TestHarness;
-use zc_protocol::commands::{CommandStatus, InferenceTier, ParsedIntent};
+use zc_protocol::commands::CommandStatus;
 
 /// Full lifecycle: send "search logs" → cloud inference → agent executes → response ingested.
 #[tokio::test]
@@ -205,3 +204,68 @@ async fn e2e_latency_computed_on_response() {
     let resp = record.response.unwrap();
     assert!(resp.latency_ms < 5000);
 }
+
+/// Offline device: command is queued, then delivered and executed after the
+/// device's next MQTT heartbeat.
+#[tokio::test]
+async fn e2e_offline_device_queue_flushed_on_heartbeat() {
+    let h = TestHarness::with_sample_data();
+    h.cloud_state
+        .devices
+        .write()
+        .await
+        .get_mut("rpi-002")
+        .unwrap()
+        .status = zc_protocol::device::DeviceStatus::Offline;
+
+    let (status, cmd_json) = h
+        .send_command("rpi-002", "fleet-alpha", "read DTCs", "admin")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let cmd_id: Uuid = cmd_json["id"].as_str().unwrap().parse().unwrap();
+
+    // Nothing published while offline.
+    assert!(h.mqtt.published().is_empty());
+    let record = h.get_command_record(cmd_id).await.unwrap();
+    assert_eq!(record.status, CommandStatus::Queued);
+
+    // Device reconnects and heartbeats over MQTT.
+    let hb = zc_protocol::device::Heartbeat {
+        device_id: "rpi-002".into(),
+        fleet_id: "fleet-alpha".into(),
+        status: zc_protocol::device::DeviceStatus::Online,
+        uptime_secs: 12,
+        ollama_status: zc_protocol::device::ServiceStatus::Running,
+        can_status: zc_protocol::device::ServiceStatus::Running,
+        agent_version: "0.1.0".into(),
+        machine_id: None,
+        cert_expires_at: None,
+        encodings: vec![],
+        version: zc_protocol::device::HEARTBEAT_VERSION,
+        vitals: None,
+        timestamp: Utc::now(),
+    };
+    let topic = zc_protocol::topics::heartbeat("fleet-alpha", "rpi-002");
+    zc_cloud_api::mqtt_bridge::handle_incoming(
+        &topic,
+        &serde_json::to_vec(&hb).unwrap(),
+        &h.cloud_state,
+    )
+    .await;
+
+    let published = h.mqtt.published();
+    assert_eq!(published.len(), 1);
+    let envelope: zc_protocol::commands::CommandEnvelope =
+        serde_json::from_slice(&published[0].payload).unwrap();
+    assert_eq!(envelope.id, cmd_id);
+    assert_eq!(
+        h.get_command_record(cmd_id).await.unwrap().status,
+        CommandStatus::Pending
+    );
+
+    let agent_resp = h.agent_execute(&envelope).await;
+    h.rest_ingest_response(&agent_resp).await;
+    let record = h.get_command_record(cmd_id).await.unwrap();
+    assert_eq!(record.status, agent_resp.status);
+    assert!(record.response.is_some());
+}
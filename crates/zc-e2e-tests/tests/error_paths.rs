@@ -4,7 +4,6 @@ mod helpers;
 
 use axum::http::StatusCode;
 use chrono::Utc;
-use http_body_util::BodyExt;
 use serde_json::json;
 use tower::ServiceExt;
 use uuid::Uuid;
@@ -33,7 +32,7 @@ async fn e2e_command_to_unknown_device() {
 async fn e2e_unrecognized_command_no_intent() {
     let h = TestHarness::with_sample_data();
 
-    let (status, cmd_json) = h
+    let (status, _) = h
         .send_command("rpi-001", "fleet-alpha", "bake a pizza", "admin")
         .await;
     assert_eq!(status, StatusCode::OK);
@@ -65,6 +64,7 @@ async fn e2e_can_timeout_propagates() {
         tool_name: "read_vin".into(),
         tool_args: json!({}),
         confidence: 0.95,
+        steps: Vec::new(),
     });
 
     let agent_resp = h.agent_execute(&envelope).await;
@@ -114,6 +114,8 @@ async fn e2e_response_for_unknown_command() {
         latency_ms: 10,
         responded_at: Utc::now(),
         error: None,
+        error_code: None,
+        cached: false,
     };
 
     // REST path: should return 404
@@ -145,6 +147,8 @@ async fn e2e_response_id_mismatch() {
         latency_ms: 10,
         responded_at: Utc::now(),
         error: None,
+        error_code: None,
+        cached: false,
     };
 
     // POST to the correct command path, but body has wrong ID
@@ -175,6 +179,7 @@ async fn e2e_unknown_tool_in_intent() {
         tool_name: "self_destruct".into(),
         tool_args: json!({}),
         confidence: 0.99,
+        steps: Vec::new(),
     });
 
     let agent_resp = h.agent_execute(&envelope).await;
@@ -187,7 +192,7 @@ async fn e2e_unknown_tool_in_intent() {
 async fn e2e_empty_command_text() {
     let h = TestHarness::with_sample_data();
 
-    let (status, cmd_json) = h.send_command("rpi-001", "fleet-alpha", "", "admin").await;
+    let (status, _) = h.send_command("rpi-001", "fleet-alpha", "", "admin").await;
     assert_eq!(status, StatusCode::OK);
 
     // The envelope is still created and published
@@ -26,6 +26,10 @@ async fn e2e_heartbeat_updates_device() {
         can_status: ServiceStatus::Running,
         agent_version: "0.1.0".into(),
         machine_id: None,
+        cert_expires_at: None,
+        encodings: vec![],
+        version: zc_protocol::device::HEARTBEAT_VERSION,
+        vitals: None,
         timestamp: Utc::now(),
     };
 
@@ -61,6 +65,10 @@ async fn e2e_heartbeat_via_rest() {
         can_status: ServiceStatus::Stopped,
         agent_version: "0.1.0".into(),
         machine_id: None,
+        cert_expires_at: None,
+        encodings: vec![],
+        version: zc_protocol::device::HEARTBEAT_VERSION,
+        vitals: None,
         timestamp: Utc::now(),
     };
 
@@ -117,8 +125,14 @@ async fn e2e_telemetry_via_mqtt() {
     let event_json = serde_json::to_string(&event).unwrap();
     assert!(event_json.contains("telemetry_ingested"));
     assert!(event_json.contains("rpi-001"));
-    // In-memory mode doesn't store telemetry, but the event carries the count
     assert!(event_json.contains("2") || event_json.contains("\"count\":2"));
+
+    // Readings are queryable through the REST API
+    let (status, json) = h.get_telemetry("rpi-001", "metric=coolant_temp").await;
+    assert_eq!(status, StatusCode::OK);
+    let readings = json["readings"].as_array().unwrap();
+    assert_eq!(readings.len(), 1);
+    assert_eq!(readings[0]["value_numeric"], 90.0);
 }
 
 /// REST telemetry ingestion processes readings and broadcasts event.
@@ -151,6 +165,20 @@ async fn e2e_telemetry_via_rest() {
     let event = rx.try_recv().unwrap();
     let event_json = serde_json::to_string(&event).unwrap();
     assert!(event_json.contains("telemetry_ingested"));
+
+    // Page through the stored readings one at a time
+    let (_, page1) = h.get_telemetry("rpi-001", "limit=1&order=asc").await;
+    assert_eq!(page1["readings"].as_array().unwrap().len(), 1);
+    let cursor = page1["next_cursor"].as_str().unwrap();
+    let (_, page2) = h
+        .get_telemetry("rpi-001", &format!("limit=1&order=asc&cursor={cursor}"))
+        .await;
+    assert_eq!(page2["readings"].as_array().unwrap().len(), 1);
+    assert!(page2["next_cursor"].is_null());
+    assert_ne!(
+        page1["readings"][0]["metric_name"],
+        page2["readings"][0]["metric_name"]
+    );
 }
 
 /// Full device lifecycle: provision → heartbeat → command → response.
@@ -174,6 +202,10 @@ async fn e2e_full_device_lifecycle() {
         can_status: ServiceStatus::Stopped,
         agent_version: "0.1.0".into(),
         machine_id: None,
+        cert_expires_at: None,
+        encodings: vec![],
+        version: zc_protocol::device::HEARTBEAT_VERSION,
+        vitals: None,
         timestamp: Utc::now(),
     };
     let (hb_status, _) = h.rest_heartbeat(&hb).await;
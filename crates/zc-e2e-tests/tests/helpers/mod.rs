@@ -3,6 +3,9 @@
 //! Bridges the cloud API and fleet agent through a shared `MockChannel`,
 //! exercising real code paths across all crate boundaries.
 
+// Each test binary compiles this module and uses only part of it.
+#![allow(dead_code)]
+
 use std::sync::Arc;
 
 use axum::Router;
@@ -315,4 +318,24 @@ impl TestHarness {
         let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
         (status, json)
     }
+
+    /// Query telemetry via REST API (GET /api/v1/devices/{id}/telemetry?{query}).
+    pub async fn get_telemetry(
+        &self,
+        device_id: &str,
+        query: &str,
+    ) -> (StatusCode, serde_json::Value) {
+        let url = format!("/api/v1/devices/{device_id}/telemetry?{query}");
+        let response = self
+            .cloud_router
+            .clone()
+            .oneshot(Request::get(&url).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        (status, json)
+    }
 }
@@ -0,0 +1,343 @@
+//! Named CAN buses.
+//!
+//! A device may sit on several buses, e.g. `can0` on the powertrain and
+//! `can1` on the body. `[[can_buses]]` names each one with its interface,
+//! bitrate and protocol (the older `can_interface`/`can_bitrate` describe a
+//! single bus). CAN tools pick a bus with the `bus` argument and run on the
+//! first one without it. Every bus has its own lock, so commands take turns
+//! per bus while tools on different buses run side by side. Telemetry and
+//! PID watch sampling take the default bus's lock too
+//! ([`CanBus::shared_lock`]), so their queries never interleave with a
+//! command's ISO-TP exchange.
+//!
+//! A bus is reached through SocketCAN or, with `adapter = "elm327"`,
+//! through an ELM327 OBD-II adapter on a serial port.
+
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::sync::{Mutex, MutexGuard};
+use zc_canbus_tools::CanInterface;
+#[cfg(target_os = "linux")]
+use zc_canbus_tools::elm327;
+
+/// Tool argument naming the bus a CAN tool runs on.
+pub const BUS_ARG: &str = "bus";
+
+/// Name of the only bus when none are configured.
+pub const DEFAULT_BUS_NAME: &str = "can0";
+
+/// Longest bus name.
+pub const MAX_BUS_NAME_LEN: usize = 32;
+
+/// The only tools allowed on a [`CanBusProtocol::Raw`] bus.
+const RAW_BUS_TOOLS: &[&str] = &["can_monitor", "send_frame"];
+
+/// What a bus carries, and so which tools may use it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanBusProtocol {
+    /// OBD-II and UDS diagnostics (ISO 15765-4): every CAN tool.
+    #[default]
+    Obd2,
+    /// No diagnostic ECUs: only monitoring (and bench `send_frame`).
+    Raw,
+}
+
+impl CanBusProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Obd2 => "obd2",
+            Self::Raw => "raw",
+        }
+    }
+
+    /// Whether `tool` may run on a bus with this protocol.
+    pub fn allows(self, tool: &str) -> bool {
+        match self {
+            Self::Obd2 => true,
+            Self::Raw => RAW_BUS_TOOLS.contains(&tool),
+        }
+    }
+}
+
+/// How the agent reaches a bus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanAdapter {
+    /// A SocketCAN network interface.
+    #[default]
+    SocketCan,
+    /// An ELM327 adapter on a serial device.
+    Elm327,
+}
+
+impl CanAdapter {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SocketCan => "socketcan",
+            Self::Elm327 => "elm327",
+        }
+    }
+}
+
+/// One `[[can_buses]]` entry.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CanBusConfig {
+    /// Name used in the `bus` argument, e.g. "powertrain".
+    pub name: String,
+    /// SocketCAN interface, e.g. "can0", or the adapter's serial device,
+    /// e.g. "/dev/ttyUSB0".
+    pub interface: String,
+    /// Expected link bitrate in bit/s; checked, not configured.
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    #[serde(default)]
+    pub protocol: CanBusProtocol,
+    #[serde(default)]
+    pub adapter: CanAdapter,
+    /// Serial speed of an ELM327 adapter.
+    #[serde(default)]
+    pub baud: Option<u32>,
+}
+
+/// Whether `name` can be used as a bus name: 1-32 ASCII letters, digits,
+/// `-` or `_`.
+pub fn is_valid_bus_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_BUS_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Open the interface of `bus`, falling back to a mock interface when
+/// SocketCAN or the adapter is unavailable.
+pub async fn open(bus: &CanBusConfig) -> Box<dyn CanInterface> {
+    #[cfg(target_os = "linux")]
+    let opened = match bus.adapter {
+        CanAdapter::SocketCan => {
+            zc_canbus_tools::SocketCanInterface::open(&bus.interface, bus.bitrate)
+                .map(|s| Box::new(s) as Box<dyn CanInterface>)
+        }
+        CanAdapter::Elm327 => {
+            let baud = bus.baud.unwrap_or(elm327::DEFAULT_BAUD_RATE);
+            elm327::open(&bus.interface, baud, bus.bitrate)
+                .await
+                .map(|e| Box::new(e) as Box<dyn CanInterface>)
+        }
+    };
+    #[cfg(target_os = "linux")]
+    match opened {
+        Ok(iface) => {
+            tracing::info!(bus = %bus.name, interface = %bus.interface, adapter = bus.adapter.as_str(), "real CAN interface opened");
+            iface
+        }
+        Err(e) => {
+            tracing::warn!(bus = %bus.name, interface = %bus.interface, adapter = bus.adapter.as_str(), error = %e, "CAN open failed, falling back to mock");
+            Box::new(zc_canbus_tools::MockCanInterface::new())
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        tracing::warn!(
+            bus = %bus.name,
+            interface = %bus.interface,
+            adapter = bus.adapter.as_str(),
+            "CAN adapters not available on this platform, using mock"
+        );
+        Box::new(zc_canbus_tools::MockCanInterface::new())
+    }
+}
+
+/// A bus the executor can run CAN tools on.
+pub struct CanBus<'a> {
+    pub name: String,
+    pub protocol: CanBusProtocol,
+    pub interface: &'a dyn CanInterface,
+    /// Held while a command or a sampling round uses `interface`.
+    lock: Arc<Mutex<()>>,
+}
+
+impl<'a> CanBus<'a> {
+    pub fn new(
+        name: impl Into<String>,
+        protocol: CanBusProtocol,
+        interface: &'a dyn CanInterface,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            protocol,
+            interface,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// The bus's lock, for loops that query the bus outside commands.
+    pub fn shared_lock(&self) -> Arc<Mutex<()>> {
+        self.lock.clone()
+    }
+}
+
+/// The buses of a device; the first is the default.
+pub struct CanBuses<'a> {
+    buses: Vec<CanBus<'a>>,
+}
+
+impl<'a> CanBuses<'a> {
+    /// The buses in order. Empty `buses` fall back to a bus named
+    /// [`DEFAULT_BUS_NAME`] on `fallback`.
+    pub fn new(buses: Vec<CanBus<'a>>, fallback: &'a dyn CanInterface) -> Self {
+        if buses.is_empty() {
+            return Self::single(fallback);
+        }
+        Self { buses }
+    }
+
+    /// `interface` as the only bus, named [`DEFAULT_BUS_NAME`].
+    pub fn single(interface: &'a dyn CanInterface) -> Self {
+        Self {
+            buses: vec![CanBus::new(
+                DEFAULT_BUS_NAME,
+                CanBusProtocol::Obd2,
+                interface,
+            )],
+        }
+    }
+
+    /// The bus used when a tool names none.
+    pub fn default_bus(&self) -> &CanBus<'a> {
+        &self.buses[0]
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.buses.iter().map(|b| b.name.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buses.is_empty()
+    }
+
+    /// The bus named by the `bus` argument of `args`, or the default bus.
+    pub fn select(&self, args: &serde_json::Value) -> Result<&CanBus<'a>, String> {
+        self.position(args).map(|i| &self.buses[i])
+    }
+
+    fn position(&self, args: &serde_json::Value) -> Result<usize, String> {
+        let Some(arg) = args.get(BUS_ARG).filter(|v| !v.is_null()) else {
+            return Ok(0);
+        };
+        let name = arg.as_str().ok_or("bus must be a string")?;
+        self.buses
+            .iter()
+            .position(|b| b.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown bus '{name}' (configured: {})",
+                    self.names().join(", ")
+                )
+            })
+    }
+
+    /// Lock the buses named by each of `calls` (CAN tool arguments), in
+    /// configuration order so that commands never wait on each other in a
+    /// cycle. A `bus` that is not a known name yet (a plan template) locks
+    /// every bus.
+    pub async fn lock<'s>(
+        &'s self,
+        calls: impl IntoIterator<Item = &serde_json::Value>,
+    ) -> Vec<MutexGuard<'s, ()>> {
+        let mut positions = Vec::new();
+        for args in calls {
+            match self.position(args) {
+                Ok(i) => positions.push(i),
+                Err(_) => positions.extend(0..self.buses.len()),
+            }
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        let mut guards = Vec::with_capacity(positions.len());
+        for i in positions {
+            guards.push(self.buses[i].lock.lock().await);
+        }
+        guards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+    use zc_canbus_tools::MockCanInterface;
+
+    #[test]
+    fn bus_names_are_checked() {
+        assert!(is_valid_bus_name("powertrain"));
+        assert!(is_valid_bus_name("body_can-1"));
+        for bad in ["", "two words", "bus/0", &"x".repeat(33)] {
+            assert!(!is_valid_bus_name(bad), "{bad}");
+        }
+    }
+
+    #[test]
+    fn raw_buses_only_allow_monitoring() {
+        assert!(CanBusProtocol::Obd2.allows("read_dtcs"));
+        assert!(CanBusProtocol::Raw.allows("can_monitor"));
+        assert!(!CanBusProtocol::Raw.allows("read_dtcs"));
+    }
+
+    #[test]
+    fn select_routes_by_name_and_defaults_to_the_first_bus() {
+        let (can0, can1) = (MockCanInterface::new(), MockCanInterface::new());
+        let buses = CanBuses::new(
+            vec![
+                CanBus::new("powertrain", CanBusProtocol::Obd2, &can0),
+                CanBus::new("body", CanBusProtocol::Raw, &can1),
+            ],
+            &can0,
+        );
+        assert_eq!(buses.select(&json!({})).unwrap().name, "powertrain");
+        assert_eq!(
+            buses.select(&json!({"bus": null})).unwrap().name,
+            "powertrain"
+        );
+        assert_eq!(buses.select(&json!({"bus": "body"})).unwrap().name, "body");
+        let err = buses.select(&json!({"bus": "chassis"})).err().unwrap();
+        assert_eq!(err, "unknown bus 'chassis' (configured: powertrain, body)");
+        assert!(buses.select(&json!({"bus": 1})).is_err());
+    }
+
+    #[tokio::test]
+    async fn buses_are_locked_independently() {
+        let (can0, can1) = (MockCanInterface::new(), MockCanInterface::new());
+        let buses = CanBuses::new(
+            vec![
+                CanBus::new("powertrain", CanBusProtocol::Obd2, &can0),
+                CanBus::new("body", CanBusProtocol::Obd2, &can1),
+            ],
+            &can0,
+        );
+        let held = buses.lock([&json!({})]).await;
+        assert_eq!(held.len(), 1);
+        // The body bus is still free while the powertrain bus is held.
+        let body = json!({"bus": "body"});
+        let other = tokio::time::timeout(Duration::from_millis(50), buses.lock([&body])).await;
+        assert_eq!(other.unwrap().len(), 1);
+        let blocked =
+            tokio::time::timeout(Duration::from_millis(50), buses.lock([&json!({})])).await;
+        assert!(blocked.is_err());
+    }
+
+    #[test]
+    fn no_configured_buses_fall_back_to_one_default_bus() {
+        let can = MockCanInterface::new();
+        let buses = CanBuses::new(Vec::new(), &can);
+        assert_eq!(buses.names(), [DEFAULT_BUS_NAME]);
+    }
+}
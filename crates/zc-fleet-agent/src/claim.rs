@@ -0,0 +1,385 @@
+//! First-boot onboarding with a claim code (`[claim]` in agent.toml).
+//!
+//! A device configured with a claim code instead of `fleet_id` and
+//! `device_id` gets its identity from the cloud. On first boot
+//! [`identity`] connects with the configured MQTT settings, publishes a
+//! [`ClaimRequest`] on `bootstrap/{hardware_id}/claim` and waits for the
+//! [`ClaimResult`] on `bootstrap/{hardware_id}/result`, repeating the
+//! request every `retry_secs` until the cloud answers. An accepted claim
+//! is written to `identity_path` and used on every later boot, so the code
+//! is only ever presented once; a rejected claim stops the agent.
+//!
+//! The hardware ID defaults to `/etc/machine-id`. The initial runtime
+//! settings in the result are kept with the identity and applied as a
+//! config update at each start.
+//!
+//! See [`zc_protocol::bootstrap`] for the protocol.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use zc_mqtt_channel::{Channel, ChannelEvent, MqttChannel, MqttConfig, MqttError};
+use zc_protocol::bootstrap::{ClaimRequest, ClaimResult, ClaimStatus};
+use zc_protocol::topics;
+
+/// Claim code onboarding settings (`[claim]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClaimConfig {
+    /// One-time code created in the cloud (`POST /api/v1/claim-codes`).
+    pub code: String,
+    /// Hardware fingerprint sent with the claim; `/etc/machine-id` when unset.
+    #[serde(default)]
+    pub hardware_id: Option<String>,
+    /// Hardware platform for the device registry (`raspberry_pi_4`,
+    /// `raspberry_pi_5`, `industrial_sbc` or a custom name).
+    #[serde(default = "default_hardware_type")]
+    pub hardware_type: String,
+    /// Where the claimed identity is kept.
+    #[serde(default = "default_identity_path")]
+    pub identity_path: String,
+    /// Seconds between claim attempts while the cloud has not answered.
+    #[serde(default = "default_retry_secs")]
+    pub retry_secs: u64,
+}
+
+fn default_hardware_type() -> String {
+    "custom".into()
+}
+
+fn default_identity_path() -> String {
+    "/var/lib/zeroclaw/identity.json".into()
+}
+
+fn default_retry_secs() -> u64 {
+    30
+}
+
+/// Fleet and device IDs assigned by an accepted claim.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimedIdentity {
+    pub fleet_id: String,
+    pub device_id: String,
+    pub hardware_id: String,
+    /// Initial runtime settings (a config update), if the code had any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<serde_json::Value>,
+    pub claimed_at: DateTime<Utc>,
+}
+
+/// Why the device could not get an identity.
+#[derive(Debug, thiserror::Error)]
+pub enum ClaimError {
+    #[error("no hardware ID: set claim.hardware_id or provide /etc/machine-id")]
+    NoHardwareId,
+    #[error("hardware ID {0:?} cannot be used in an MQTT topic")]
+    InvalidHardwareId(String),
+    #[error("claim rejected by the cloud: {0}")]
+    Rejected(String),
+    #[error("claim accepted without a fleet and device ID")]
+    Incomplete,
+    #[error("identity file {path}: {source}")]
+    Identity {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error(transparent)]
+    Mqtt(#[from] MqttError),
+}
+
+/// Hardware ID for the claim: configured, or `/etc/machine-id`.
+pub fn hardware_id(config: &ClaimConfig) -> Result<String, ClaimError> {
+    let id = match &config.hardware_id {
+        Some(id) => id.trim().to_string(),
+        None => std::fs::read_to_string("/etc/machine-id")
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default(),
+    };
+    if id.is_empty() {
+        return Err(ClaimError::NoHardwareId);
+    }
+    if id.contains(['/', '+', '#']) {
+        return Err(ClaimError::InvalidHardwareId(id));
+    }
+    Ok(id)
+}
+
+/// The identity kept at `path`; `None` before the first claim.
+pub fn load_identity(path: &Path) -> Result<Option<ClaimedIdentity>, ClaimError> {
+    let error = |source| ClaimError::Identity {
+        path: path.display().to_string(),
+        source,
+    };
+    let json = match std::fs::read(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(error(e)),
+    };
+    serde_json::from_slice(&json)
+        .map(Some)
+        .map_err(|e| error(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+/// Write the identity to `path`, replacing it atomically.
+pub fn save_identity(path: &Path, identity: &ClaimedIdentity) -> Result<(), ClaimError> {
+    let write = || -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec_pretty(identity).map_err(io::Error::other)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)
+    };
+    write().map_err(|source| ClaimError::Identity {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+/// Claim requests sent by this agent and the answer to them.
+#[derive(Debug)]
+pub struct Bootstrap {
+    claim_code: String,
+    hardware_id: String,
+    hardware_type: String,
+    sent: HashSet<Uuid>,
+}
+
+impl Bootstrap {
+    pub fn new(config: &ClaimConfig, hardware_id: String) -> Self {
+        Self {
+            claim_code: config.code.trim().to_string(),
+            hardware_id,
+            hardware_type: config.hardware_type.clone(),
+            sent: HashSet::new(),
+        }
+    }
+
+    pub fn hardware_id(&self) -> &str {
+        &self.hardware_id
+    }
+
+    /// A new claim request, remembered so its result is recognised.
+    pub fn request(&mut self) -> ClaimRequest {
+        let request = ClaimRequest {
+            request_id: Uuid::now_v7(),
+            claim_code: self.claim_code.clone(),
+            hardware_id: self.hardware_id.clone(),
+            hardware_type: self.hardware_type.clone(),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: Utc::now(),
+        };
+        self.sent.insert(request.request_id);
+        request
+    }
+
+    /// The outcome carried by a result payload; `None` for results to
+    /// requests this agent did not send and for unreadable payloads.
+    pub fn on_result(&self, payload: &[u8]) -> Option<Result<ClaimedIdentity, ClaimError>> {
+        let result: ClaimResult = match serde_json::from_slice(payload) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!(error = %e, "unreadable claim result");
+                return None;
+            }
+        };
+        if !self.sent.contains(&result.request_id) {
+            return None;
+        }
+        Some(match (result.status, result.fleet_id, result.device_id) {
+            (ClaimStatus::Accepted, Some(fleet_id), Some(device_id)) => Ok(ClaimedIdentity {
+                fleet_id,
+                device_id,
+                hardware_id: self.hardware_id.clone(),
+                config: result.config,
+                claimed_at: result.timestamp,
+            }),
+            (ClaimStatus::Accepted, _, _) => Err(ClaimError::Incomplete),
+            (ClaimStatus::Rejected, _, _) => Err(ClaimError::Rejected(
+                result.error.unwrap_or_else(|| "no reason given".into()),
+            )),
+        })
+    }
+}
+
+/// The device's identity: the one kept at `identity_path`, or a new one
+/// claimed from the cloud over MQTT and then kept there.
+pub async fn identity(
+    mqtt: &MqttConfig,
+    config: &ClaimConfig,
+) -> Result<ClaimedIdentity, ClaimError> {
+    let path = Path::new(&config.identity_path);
+    if let Some(identity) = load_identity(path)? {
+        tracing::info!(path = %config.identity_path, "claimed identity loaded");
+        return Ok(identity);
+    }
+    let mut bootstrap = Bootstrap::new(config, hardware_id(config)?);
+    tracing::info!(
+        hardware_id = bootstrap.hardware_id(),
+        "claiming device identity"
+    );
+    let identity = claim(mqtt, &mut bootstrap, Duration::from_secs(config.retry_secs)).await?;
+    save_identity(path, &identity)?;
+    tracing::info!(
+        fleet_id = %identity.fleet_id,
+        device_id = %identity.device_id,
+        "device identity claimed"
+    );
+    Ok(identity)
+}
+
+/// Run the bootstrap exchange on a connection of its own, which is closed
+/// before the agent connects as the device.
+async fn claim(
+    mqtt: &MqttConfig,
+    bootstrap: &mut Bootstrap,
+    retry: Duration,
+) -> Result<ClaimedIdentity, ClaimError> {
+    // Not a device yet: no presence will, nothing buffered for later.
+    let mut mqtt = mqtt.clone();
+    mqtt.last_will = false;
+    mqtt.clean_session = true;
+    let hardware_id = bootstrap.hardware_id().to_string();
+    let (channel, mut eventloop) = MqttChannel::new(&mqtt, "bootstrap", &hardware_id)?;
+    let result_topic = topics::bootstrap_result(&hardware_id);
+    let mut connected = false;
+    let mut resend = tokio::time::interval(retry);
+    resend.tick().await;
+
+    loop {
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                Ok(ChannelEvent::Connected { .. }) => {
+                    connected = true;
+                    channel.subscribe(&result_topic, QoS::AtLeastOnce).await?;
+                    send(&channel, bootstrap).await;
+                    resend.reset();
+                }
+                Ok(ChannelEvent::Publish { publish, .. }) if publish.topic == result_topic => {
+                    if let Some(outcome) = bootstrap.on_result(&publish.payload) {
+                        return outcome;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    connected = false;
+                    tracing::warn!(error = %e, "bootstrap connection failed, retrying");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            },
+            _ = resend.tick(), if connected => {
+                tracing::info!("no claim result yet, asking again");
+                send(&channel, bootstrap).await;
+            }
+        }
+    }
+}
+
+async fn send(channel: &MqttChannel, bootstrap: &mut Bootstrap) {
+    let request = bootstrap.request();
+    let topic = topics::bootstrap_claim(bootstrap.hardware_id());
+    let payload = serde_json::to_vec(&request).unwrap_or_default();
+    if let Err(e) = channel.publish(&topic, &payload, QoS::AtLeastOnce).await {
+        tracing::warn!(error = %e, "failed to publish claim request");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(hardware_id: Option<&str>) -> ClaimConfig {
+        ClaimConfig {
+            code: " ABCD-EFGH-JKLM-NPQR ".into(),
+            hardware_id: hardware_id.map(String::from),
+            hardware_type: "raspberry_pi_5".into(),
+            identity_path: default_identity_path(),
+            retry_secs: 30,
+        }
+    }
+
+    #[test]
+    fn hardware_ids_must_be_topic_safe() {
+        assert_eq!(
+            hardware_id(&config(Some(" a8b9c0d1 "))).unwrap(),
+            "a8b9c0d1"
+        );
+        assert!(matches!(
+            hardware_id(&config(Some("a/b"))),
+            Err(ClaimError::InvalidHardwareId(_))
+        ));
+        assert!(matches!(
+            hardware_id(&config(Some(""))),
+            Err(ClaimError::NoHardwareId)
+        ));
+    }
+
+    #[test]
+    fn only_results_to_own_requests_count() {
+        let mut bootstrap = Bootstrap::new(&config(None), "a8b9c0d1".into());
+        let request = bootstrap.request();
+        assert_eq!(request.claim_code, "ABCD-EFGH-JKLM-NPQR");
+        assert_eq!(request.hardware_type, "raspberry_pi_5");
+
+        let stranger = ClaimResult::accepted(Uuid::now_v7(), "fleet-alpha", "dev-x", None);
+        assert!(
+            bootstrap
+                .on_result(&serde_json::to_vec(&stranger).unwrap())
+                .is_none()
+        );
+        assert!(bootstrap.on_result(b"not json").is_none());
+
+        let accepted = ClaimResult::accepted(
+            request.request_id,
+            "fleet-alpha",
+            "dev-a8b9c0d1",
+            Some(serde_json::json!({"version": 1})),
+        );
+        let identity = bootstrap
+            .on_result(&serde_json::to_vec(&accepted).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.fleet_id, "fleet-alpha");
+        assert_eq!(identity.device_id, "dev-a8b9c0d1");
+        assert_eq!(identity.hardware_id, "a8b9c0d1");
+
+        let rejected = ClaimResult::rejected(request.request_id, "claim code expired");
+        let outcome = bootstrap
+            .on_result(&serde_json::to_vec(&rejected).unwrap())
+            .unwrap();
+        assert!(matches!(outcome, Err(ClaimError::Rejected(e)) if e == "claim code expired"));
+    }
+
+    #[test]
+    fn identity_round_trips_through_the_file() {
+        let dir = std::env::temp_dir().join(format!("zc-claim-{}", std::process::id()));
+        let path = dir.join("state").join("identity.json");
+        assert!(load_identity(&path).unwrap().is_none());
+
+        let identity = ClaimedIdentity {
+            fleet_id: "fleet-alpha".into(),
+            device_id: "dev-a8b9c0d1".into(),
+            hardware_id: "a8b9c0d1".into(),
+            config: Some(serde_json::json!({"version": 1, "heartbeat_interval_secs": 15})),
+            claimed_at: Utc::now(),
+        };
+        save_identity(&path, &identity).unwrap();
+        assert_eq!(load_identity(&path).unwrap(), Some(identity));
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(matches!(
+            load_identity(&path),
+            Err(ClaimError::Identity { .. })
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
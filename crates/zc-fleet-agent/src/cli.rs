@@ -0,0 +1,118 @@
+//! Command-line argument handling for the agent binary.
+
+/// Config path used when none is given on the command line.
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/zeroclaw/agent.toml";
+
+pub const USAGE: &str = "\
+Usage:
+  zc-fleet-agent [CONFIG]                    run the agent (default: /etc/zeroclaw/agent.toml)
+  zc-fleet-agent --validate-config [CONFIG]  check a config file and exit
+  zc-fleet-agent --generate-config           print a commented default agent.toml
+  zc-fleet-agent --version                   print the agent version
+  zc-fleet-agent --help                      show this message";
+
+/// What the binary was asked to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Run { config_path: String },
+    ValidateConfig { config_path: String },
+    GenerateConfig,
+    Version,
+    Help,
+}
+
+/// Parse arguments (excluding the program name).
+pub fn parse<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let first = args.next();
+    let command = match first.as_deref() {
+        None => Command::Run {
+            config_path: DEFAULT_CONFIG_PATH.to_string(),
+        },
+        Some("--help" | "-h") => Command::Help,
+        Some("--generate-config") => Command::GenerateConfig,
+        Some("--version" | "-V") => Command::Version,
+        Some("--validate-config") => Command::ValidateConfig {
+            config_path: args
+                .next()
+                .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string()),
+        },
+        Some(flag) if flag.starts_with('-') => return Err(format!("unknown option '{flag}'")),
+        Some(path) => Command::Run {
+            config_path: path.to_string(),
+        },
+    };
+
+    if let Some(extra) = args.next() {
+        return Err(format!("unexpected argument '{extra}'"));
+    }
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_strs(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn no_args_runs_with_default_path() {
+        assert_eq!(
+            parse_strs(&[]).unwrap(),
+            Command::Run {
+                config_path: DEFAULT_CONFIG_PATH.into()
+            }
+        );
+    }
+
+    #[test]
+    fn positional_path_runs() {
+        assert_eq!(
+            parse_strs(&["./agent.toml"]).unwrap(),
+            Command::Run {
+                config_path: "./agent.toml".into()
+            }
+        );
+    }
+
+    #[test]
+    fn validate_config_with_and_without_path() {
+        assert_eq!(
+            parse_strs(&["--validate-config", "/tmp/a.toml"]).unwrap(),
+            Command::ValidateConfig {
+                config_path: "/tmp/a.toml".into()
+            }
+        );
+        assert_eq!(
+            parse_strs(&["--validate-config"]).unwrap(),
+            Command::ValidateConfig {
+                config_path: DEFAULT_CONFIG_PATH.into()
+            }
+        );
+    }
+
+    #[test]
+    fn generate_config() {
+        assert_eq!(
+            parse_strs(&["--generate-config"]).unwrap(),
+            Command::GenerateConfig
+        );
+    }
+
+    #[test]
+    fn version() {
+        assert_eq!(parse_strs(&["--version"]).unwrap(), Command::Version);
+        assert_eq!(parse_strs(&["-V"]).unwrap(), Command::Version);
+    }
+
+    #[test]
+    fn rejects_unknown_flag_and_extra_args() {
+        assert!(parse_strs(&["--verbose"]).is_err());
+        assert!(parse_strs(&["--generate-config", "extra"]).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! How long a command may run on the device.
+//!
+//! The executor stops a command's action (tool, shell command or plan) once
+//! the envelope's `timeout_secs` has elapsed and answers with status
+//! `timeout`. The envelope's value is capped by `max_secs` and, for a single
+//! tool, by that tool's limit, so a generous cloud default cannot keep a
+//! quick query — or a CAN read on a hung bus — running for minutes.
+//! Inference time does not count; the clock starts when the action does.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Per-tool limits used unless overridden in `[command_timeouts]`: single
+/// request/response queries answer within a second or two on a healthy bus.
+pub const DEFAULT_TOOL_MAX_SECS: &[(&str, u64)] = &[
+    ("read_vin", 10),
+    ("read_pid", 10),
+    ("read_uds_did", 10),
+    ("read_readiness", 10),
+    ("read_freeze", 20),
+    ("list_supported_pids", 20),
+    ("read_vehicle_info", 20),
+];
+
+/// Added to every limit so a tool that runs for exactly the allowed time (a
+/// 30 s `can_monitor` under the default 30 s timeout) returns its result
+/// instead of being cut off at the end.
+pub const OVERRUN_GRACE: Duration = Duration::from_secs(2);
+
+/// Command timeout settings (`[command_timeouts]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandTimeoutConfig {
+    /// Longest any command may run, whatever its envelope asks for.
+    #[serde(default = "default_max_secs")]
+    pub max_secs: u64,
+    /// Per-tool limits in seconds, on top of [`DEFAULT_TOOL_MAX_SECS`].
+    #[serde(default)]
+    pub tool_max_secs: BTreeMap<String, u64>,
+}
+
+fn default_max_secs() -> u64 {
+    300
+}
+
+impl Default for CommandTimeoutConfig {
+    fn default() -> Self {
+        Self {
+            max_secs: default_max_secs(),
+            tool_max_secs: BTreeMap::new(),
+        }
+    }
+}
+
+impl CommandTimeoutConfig {
+    /// Limit of a tool, if it has one.
+    fn tool_max_secs(&self, tool: &str) -> Option<u64> {
+        self.tool_max_secs.get(tool).copied().or_else(|| {
+            DEFAULT_TOOL_MAX_SECS
+                .iter()
+                .find(|(name, _)| *name == tool)
+                .map(|(_, secs)| *secs)
+        })
+    }
+
+    /// Effective timeout in seconds for an envelope's `timeout_secs` (0
+    /// means "no preference") running `tool`.
+    pub fn limit_secs(&self, timeout_secs: u32, tool: Option<&str>) -> u64 {
+        let requested = match u64::from(timeout_secs) {
+            0 => self.max_secs,
+            secs => secs.min(self.max_secs),
+        };
+        tool.and_then(|t| self.tool_max_secs(t))
+            .map_or(requested, |max| requested.min(max))
+    }
+
+    /// How long the action may run before it is stopped.
+    pub fn limit(&self, timeout_secs: u32, tool: Option<&str>) -> Duration {
+        Duration::from_secs(self.limit_secs(timeout_secs, tool)) + OVERRUN_GRACE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_timeout_is_capped() {
+        let config = CommandTimeoutConfig::default();
+        assert_eq!(config.limit_secs(30, Some("can_monitor")), 30);
+        assert_eq!(config.limit_secs(3600, None), 300);
+        assert_eq!(config.limit_secs(0, None), 300);
+        // Quick queries get their own, shorter limit.
+        assert_eq!(config.limit_secs(30, Some("read_vin")), 10);
+        assert_eq!(config.limit_secs(5, Some("read_vin")), 5);
+        assert_eq!(
+            config.limit(30, Some("read_pid")),
+            Duration::from_secs(10) + OVERRUN_GRACE
+        );
+    }
+
+    #[test]
+    fn configured_tool_limits_override_defaults() {
+        let config: CommandTimeoutConfig =
+            toml::from_str("max_secs = 60\n[tool_max_secs]\nread_vin = 45\nsearch_logs = 20\n")
+                .unwrap();
+        assert_eq!(config.limit_secs(120, Some("read_vin")), 45);
+        assert_eq!(config.limit_secs(120, Some("search_logs")), 20);
+        assert_eq!(config.limit_secs(120, Some("log_stats")), 60);
+    }
+}
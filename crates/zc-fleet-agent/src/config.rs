@@ -2,34 +2,158 @@
 
 use serde::Deserialize;
 use zc_mqtt_channel::MqttConfig;
+use zc_protocol::rate_limit::RateLimit;
 
+use crate::can_bus::{self, CanAdapter, CanBusConfig, CanBusProtocol};
+use crate::claim::ClaimConfig;
+use crate::command_timeout::CommandTimeoutConfig;
+use crate::device_context::VehicleConfig;
+use crate::file_transfer::FileTransferConfig;
+use crate::history::HistoryConfig;
+use crate::inbox::InboxConfig;
 use crate::inference::OllamaConfig;
+use crate::pid_watch::{self, PidWatchConfig};
+use crate::result_cache::ResultCacheConfig;
+use crate::self_test::SelfTestConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::updater::UpdateConfig;
 
 /// Top-level configuration for the fleet agent.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct AgentConfig {
-    /// Fleet this device belongs to.
+    /// Fleet this device belongs to. Left out with `[claim]`, which
+    /// gets it from the cloud.
+    #[serde(default)]
     pub fleet_id: String,
-    /// Unique device identifier (IoT Core thing name).
+    /// Unique device identifier (IoT Core thing name). Left out with
+    /// `[claim]`, which gets it from the cloud.
+    #[serde(default)]
     pub device_id: String,
+    /// Claim the fleet and device IDs with a one-time code on first boot.
+    #[serde(default)]
+    pub claim: Option<ClaimConfig>,
     /// MQTT connection settings.
     pub mqtt: MqttConfig,
     /// CAN bus interface name (e.g., "can0"). None disables CAN tools.
     #[serde(default)]
     pub can_interface: Option<String>,
+    /// Expected CAN link bitrate in bit/s (e.g., 500000). When set, the agent
+    /// refuses a link configured at a different rate.
+    #[serde(default)]
+    pub can_bitrate: Option<u32>,
+    /// Named CAN buses (`[[can_buses]]`), for devices on more than one bus.
+    /// Replaces `can_interface`/`can_bitrate`; the first bus is the default.
+    #[serde(default)]
+    pub can_buses: Vec<CanBusConfig>,
+    /// Bench mode: registers tools that transmit arbitrary CAN frames
+    /// (`send_frame`). Never enable on a vehicle in the field.
+    #[serde(default)]
+    pub bench_mode: bool,
+    /// Only run commands the cloud already parsed (`parsed_intent` set):
+    /// local inference is off and natural-language envelopes are rejected.
+    /// Set this on devices in the cloud's `STRUCTURED_ONLY_FLEETS`.
+    #[serde(default)]
+    pub structured_commands_only: bool,
+    /// Commands run at the same time. CAN tools still take turns on the
+    /// bus; log tools and shell commands run alongside them.
+    #[serde(default = "default_max_concurrent_commands")]
+    pub max_concurrent_commands: usize,
     /// Heartbeat interval in seconds.
     #[serde(default = "default_heartbeat_interval")]
     pub heartbeat_interval_secs: u64,
-    /// Log file paths to monitor (Phase 2: file watching).
+    /// Log files on this device, checked by the self-test. Adjustable at runtime.
     #[serde(default)]
-    #[allow(dead_code)]
     pub log_paths: Vec<String>,
+    /// Fleet-specific log formats (`[[log_formats]]`), selectable as the log
+    /// tools' `format` argument and included in auto-detection.
+    #[serde(default)]
+    pub log_formats: Vec<zc_log_tools::CustomFormatConfig>,
+    /// Syslog received from other devices on the network, read by the log
+    /// tools as `remote://<host>`. Optional — defaults to disabled.
+    #[serde(default)]
+    pub remote_syslog: zc_log_tools::RemoteSyslogConfig,
+    /// Vehicle the device is installed in, used as inference context.
+    #[serde(default)]
+    pub vehicle: VehicleConfig,
+    /// Fleet-specific DTC descriptions layered over the built-in database.
+    #[serde(default)]
+    pub dtc_database: DtcDatabaseConfig,
+    /// DBC files used by `can_monitor` to decode proprietary CAN traffic.
+    #[serde(default)]
+    pub dbc: DbcConfig,
     /// Shadow sync interval in seconds.
     #[serde(default = "default_shadow_sync_interval")]
     pub shadow_sync_interval_secs: u64,
     /// Local Ollama inference settings. Optional — defaults to enabled.
     #[serde(default)]
     pub ollama: OllamaConfig,
+    /// PID sampling + edge aggregation settings. Optional — defaults to disabled.
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Standing PID watches (`[[pid_watches]]`), sampled until replaced.
+    #[serde(default)]
+    pub pid_watches: Vec<PidWatchConfig>,
+    /// Local command/telemetry journal. Optional — defaults to enabled.
+    #[serde(default)]
+    pub history: HistoryConfig,
+    /// Persistent inbox of received commands. Optional — defaults to enabled.
+    #[serde(default)]
+    pub inbox: InboxConfig,
+    /// Provisioning self-test. Optional — runs once on first boot by default.
+    #[serde(default)]
+    pub self_test: SelfTestConfig,
+    /// Cache of recent tool results. Optional — defaults to disabled.
+    #[serde(default)]
+    pub result_cache: ResultCacheConfig,
+    /// Limits on how long a command may run. Optional — 300 s at most.
+    #[serde(default)]
+    pub command_timeouts: CommandTimeoutConfig,
+    /// Commands accepted from the broker before further ones are answered
+    /// `throttled` without running. Defaults to 20 back to back, then one
+    /// a second.
+    #[serde(default = "default_command_rate_limit")]
+    pub command_rate_limit: RateLimit,
+    /// Self-update from releases on the `update` shadow. Optional —
+    /// defaults to enabled.
+    #[serde(default)]
+    pub update: UpdateConfig,
+    /// Files pushed by the cloud and fetched back. Optional — defaults to
+    /// enabled for the standard data and crash directories.
+    #[serde(default)]
+    pub file_transfer: FileTransferConfig,
+}
+
+/// Custom DTC codes (`[dtc_database]` in agent.toml).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DtcDatabaseConfig {
+    /// CSV or JSON files with extra codes, loaded at startup. Earlier files
+    /// win over later ones, and all of them over the built-in database.
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// Manufacturer used for lookups, so its specific codes (e.g. `"Ford"`)
+    /// take precedence over generic descriptions.
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+}
+
+/// CAN databases (`[dbc]` in agent.toml).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DbcConfig {
+    /// DBC files loaded at startup. When two files describe the same CAN
+    /// ID, the earlier file wins.
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+fn default_max_concurrent_commands() -> usize {
+    4
+}
+
+fn default_command_rate_limit() -> RateLimit {
+    RateLimit::new(20, 60)
 }
 
 fn default_heartbeat_interval() -> u64 {
@@ -40,15 +164,927 @@ fn default_shadow_sync_interval() -> u64 {
     60
 }
 
+// Accepted ranges for interval settings.
+pub(crate) const HEARTBEAT_SECS: (u64, u64) = (5, 3600);
+const SHADOW_SYNC_SECS: (u64, u64) = (5, 86_400);
+const OLLAMA_TIMEOUT_SECS: (u64, u64) = (1, 300);
+pub(crate) const TELEMETRY_WINDOW_SECS: (u64, u64) = (1, 3600);
+pub(crate) const TELEMETRY_SAMPLE_MS: (u64, u64) = (10, 60_000);
+const HISTORY_MAX_ENTRIES: (u64, u64) = (100, 100_000);
+const SELF_TEST_MIN_FREE_MB: (u64, u64) = (1, 1_000_000);
+const RESULT_CACHE_MAX_ENTRIES: (u64, u64) = (1, 10_000);
+const REMOTE_SYSLOG_MAX_BYTES: (u64, u64) = (65_536, 1 << 30);
+const REMOTE_SYSLOG_MAX_HOSTS: (u64, u64) = (1, 1024);
+const COMMAND_TIMEOUT_SECS: (u64, u64) = (1, 3600);
+const MAX_CONCURRENT_COMMANDS: (u64, u64) = (1, 32);
+const COMMAND_RATE_BURST: (u64, u64) = (1, 1000);
+const COMMAND_RATE_PER_MINUTE: (u64, u64) = (1, 6000);
+const OFFLINE_BUFFER_CAPACITY: (u64, u64) = (10, 100_000);
+const RECONNECT_MAX_DELAY_SECS: (u64, u64) = (1, 3600);
+const RECONNECT_STORM_THRESHOLD: (u64, u64) = (2, 1000);
+const UPDATE_BOOT_ATTEMPTS: (u64, u64) = (1, 10);
+const UPDATE_DOWNLOAD_SECS: (u64, u64) = (10, 3600);
+const TRANSFER_MAX_FILE_BYTES: (u64, u64) = (1024, 256 * 1024 * 1024);
+const CLAIM_RETRY_SECS: (u64, u64) = (5, 3600);
+/// rumqttc rejects keep-alive intervals below 5 seconds.
+const MIN_KEEPALIVE_SECS: u16 = 5;
+
+/// Errors from loading an agent config file.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// TOML syntax errors, wrong types and unknown keys. The message
+    /// carries the line/column reported by the TOML parser.
+    #[error("{path}: {message}")]
+    Parse { path: String, message: String },
+
+    #[error("{path}: invalid configuration\n{}", format_issues(.issues))]
+    Invalid {
+        path: String,
+        issues: Vec<ConfigIssue>,
+    },
+}
+
+/// A single semantic problem found by [`AgentConfig::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Dotted key path, e.g. `telemetry.window_secs`.
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+fn format_issues(issues: &[ConfigIssue]) -> String {
+    issues
+        .iter()
+        .map(|i| format!("  - {i}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 impl AgentConfig {
-    /// Load config from a TOML file path.
-    pub fn from_file(path: &str) -> anyhow::Result<Self> {
-        let contents = std::fs::read_to_string(path)?;
-        let config: Self = toml::from_str(&contents)?;
+    /// The configured CAN buses: `[[can_buses]]`, or a single bus named
+    /// after `can_interface`. Empty without CAN.
+    pub fn can_buses(&self) -> Vec<CanBusConfig> {
+        if !self.can_buses.is_empty() {
+            return self.can_buses.clone();
+        }
+        self.can_interface
+            .iter()
+            .map(|iface| CanBusConfig {
+                name: iface.clone(),
+                interface: iface.clone(),
+                bitrate: self.can_bitrate,
+                protocol: CanBusProtocol::Obd2,
+                adapter: CanAdapter::SocketCan,
+                baud: None,
+            })
+            .collect()
+    }
+
+    /// Load and validate config from a TOML file path.
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_toml_str(&contents, path)
+    }
+
+    /// Parse and validate config from TOML text. `path` is only used in
+    /// error messages.
+    pub fn from_toml_str(contents: &str, path: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(contents).map_err(|e| ConfigError::Parse {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?;
+
+        let issues = config.validate();
+        if !issues.is_empty() {
+            return Err(ConfigError::Invalid {
+                path: path.to_string(),
+                issues,
+            });
+        }
         Ok(config)
     }
+
+    /// Check semantic constraints that the TOML schema cannot express.
+    /// Returns every problem found, not just the first.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |field: &str, message: String| {
+            issues.push(ConfigIssue {
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        match &self.claim {
+            None => {
+                if self.fleet_id.trim().is_empty() {
+                    issue("fleet_id", "must not be empty".into());
+                }
+                if self.device_id.trim().is_empty() {
+                    issue("device_id", "must not be empty".into());
+                }
+            }
+            Some(claim) => {
+                for (field, value) in [("fleet_id", &self.fleet_id), ("device_id", &self.device_id)]
+                {
+                    if !value.is_empty() {
+                        issue(field, "must be left out with [claim]".into());
+                    }
+                }
+                if claim.code.trim().is_empty() {
+                    issue("claim.code", "must not be empty".into());
+                }
+                if claim.identity_path.trim().is_empty() {
+                    issue("claim.identity_path", "must not be empty".into());
+                }
+                check_range(
+                    &mut issue,
+                    "claim.retry_secs",
+                    claim.retry_secs,
+                    CLAIM_RETRY_SECS,
+                );
+            }
+        }
+        check_range(
+            &mut issue,
+            "heartbeat_interval_secs",
+            self.heartbeat_interval_secs,
+            HEARTBEAT_SECS,
+        );
+        check_range(
+            &mut issue,
+            "shadow_sync_interval_secs",
+            self.shadow_sync_interval_secs,
+            SHADOW_SYNC_SECS,
+        );
+        check_range(
+            &mut issue,
+            "max_concurrent_commands",
+            self.max_concurrent_commands as u64,
+            MAX_CONCURRENT_COMMANDS,
+        );
+
+        // [mqtt]
+        if self.mqtt.broker_host.trim().is_empty() {
+            issue("mqtt.broker_host", "must not be empty".into());
+        }
+        if self.mqtt.client_id.trim().is_empty() {
+            issue("mqtt.client_id", "must not be empty".into());
+        }
+        if self.mqtt.broker_port == 0 {
+            issue("mqtt.broker_port", "must not be 0".into());
+        }
+        if self.mqtt.keepalive_secs < MIN_KEEPALIVE_SECS {
+            issue(
+                "mqtt.keepalive_secs",
+                format!(
+                    "must be at least {MIN_KEEPALIVE_SECS}, got {}",
+                    self.mqtt.keepalive_secs
+                ),
+            );
+        }
+        if self.mqtt.use_tls {
+            for (field, value) in [
+                ("mqtt.client_cert_path", &self.mqtt.client_cert_path),
+                ("mqtt.client_key_path", &self.mqtt.client_key_path),
+                ("mqtt.ca_cert_path", &self.mqtt.ca_cert_path),
+            ] {
+                if value.trim().is_empty() {
+                    issue(field, "required when use_tls = true".into());
+                }
+            }
+        }
+        if self.mqtt.offline_buffer.enabled {
+            check_range(
+                &mut issue,
+                "mqtt.offline_buffer.capacity",
+                self.mqtt.offline_buffer.capacity as u64,
+                OFFLINE_BUFFER_CAPACITY,
+            );
+            if self
+                .mqtt
+                .offline_buffer
+                .path
+                .as_ref()
+                .is_some_and(|p| p.trim().is_empty())
+            {
+                issue(
+                    "mqtt.offline_buffer.path",
+                    "must not be empty; omit it to buffer in memory".into(),
+                );
+            }
+        }
+        let reconnect = &self.mqtt.reconnect;
+        check_range(
+            &mut issue,
+            "mqtt.reconnect.max_delay_secs",
+            reconnect.max_delay_secs,
+            RECONNECT_MAX_DELAY_SECS,
+        );
+        if reconnect.initial_delay_secs == 0
+            || reconnect.initial_delay_secs > reconnect.max_delay_secs
+        {
+            issue(
+                "mqtt.reconnect.initial_delay_secs",
+                format!(
+                    "must be between 1 and max_delay_secs ({}), got {}",
+                    reconnect.max_delay_secs, reconnect.initial_delay_secs
+                ),
+            );
+        }
+        check_range(
+            &mut issue,
+            "mqtt.reconnect.storm_threshold",
+            reconnect.storm_threshold.into(),
+            RECONNECT_STORM_THRESHOLD,
+        );
+
+        // CAN
+        if let Some(iface) = &self.can_interface
+            && let Err(e) = zc_canbus_tools::interface::validate_interface_name(iface)
+        {
+            issue("can_interface", e.to_string());
+        }
+        if let Some(bitrate) = self.can_bitrate {
+            if let Err(e) = zc_canbus_tools::interface::validate_bitrate(bitrate) {
+                issue("can_bitrate", e.to_string());
+            }
+            if self.can_interface.is_none() {
+                issue("can_bitrate", "has no effect without can_interface".into());
+            }
+        }
+
+        // [[can_buses]]
+        if !self.can_buses.is_empty() && self.can_interface.is_some() {
+            issue(
+                "can_buses",
+                "cannot be combined with can_interface; describe every bus in [[can_buses]]".into(),
+            );
+        }
+        let mut bus_names = std::collections::HashSet::new();
+        let mut bus_interfaces = std::collections::HashSet::new();
+        for (i, bus) in self.can_buses.iter().enumerate() {
+            let field = format!("can_buses[{i}]");
+            if !can_bus::is_valid_bus_name(&bus.name) {
+                issue(
+                    &format!("{field}.name"),
+                    format!(
+                        "\"{}\" must be 1-{} letters, digits, '-' or '_'",
+                        bus.name,
+                        can_bus::MAX_BUS_NAME_LEN
+                    ),
+                );
+            } else if !bus_names.insert(bus.name.as_str()) {
+                issue(
+                    &format!("{field}.name"),
+                    format!("duplicate bus name \"{}\"", bus.name),
+                );
+            }
+            let interface_check = match bus.adapter {
+                CanAdapter::SocketCan => {
+                    zc_canbus_tools::interface::validate_interface_name(&bus.interface)
+                        .map_err(|e| e.to_string())
+                }
+                CanAdapter::Elm327 if !bus.interface.starts_with("/dev/") => Err(format!(
+                    "\"{}\" must be a serial device path for adapter = \"elm327\"",
+                    bus.interface
+                )),
+                CanAdapter::Elm327 => Ok(()),
+            };
+            if let Err(e) = interface_check {
+                issue(&format!("{field}.interface"), e);
+            } else if !bus_interfaces.insert(bus.interface.as_str()) {
+                issue(
+                    &format!("{field}.interface"),
+                    format!("\"{}\" is already used by another bus", bus.interface),
+                );
+            }
+            if let Some(bitrate) = bus.bitrate
+                && let Err(e) = zc_canbus_tools::interface::validate_bitrate(bitrate)
+            {
+                issue(&format!("{field}.bitrate"), e.to_string());
+            }
+            match (bus.adapter, bus.baud) {
+                (CanAdapter::SocketCan, Some(_)) => issue(
+                    &format!("{field}.baud"),
+                    "has no effect without adapter = \"elm327\"".into(),
+                ),
+                (CanAdapter::Elm327, Some(baud))
+                    if !zc_canbus_tools::elm327::SUPPORTED_BAUD_RATES.contains(&baud) =>
+                {
+                    issue(
+                        &format!("{field}.baud"),
+                        format!(
+                            "unsupported baud rate {baud} (expected one of {:?})",
+                            zc_canbus_tools::elm327::SUPPORTED_BAUD_RATES
+                        ),
+                    )
+                }
+                _ => {}
+            }
+            if bus.adapter == CanAdapter::Elm327
+                && let Some(bitrate) = bus.bitrate
+                && bitrate != 250_000
+                && bitrate != 500_000
+            {
+                issue(
+                    &format!("{field}.bitrate"),
+                    "ELM327 adapters only reach OBD-II buses at 250000 or 500000".into(),
+                );
+            }
+        }
+
+        // [[log_formats]]
+        let mut format_names = std::collections::HashSet::new();
+        for (i, format) in self.log_formats.iter().enumerate() {
+            let field = format!("log_formats[{i}]");
+            if let Err(e) = zc_log_tools::parsers::CustomFormat::compile(format) {
+                issue(&field, e.to_string());
+            } else if !format_names.insert(format.name.trim()) {
+                issue(&field, format!("duplicate name '{}'", format.name.trim()));
+            }
+        }
+
+        // [remote_syslog]
+        if self.remote_syslog.enabled {
+            if self
+                .remote_syslog
+                .bind
+                .parse::<std::net::SocketAddr>()
+                .is_err()
+            {
+                issue(
+                    "remote_syslog.bind",
+                    format!("'{}' is not an address and port", self.remote_syslog.bind),
+                );
+            }
+            if self.remote_syslog.spool_dir.trim().is_empty() {
+                issue(
+                    "remote_syslog.spool_dir",
+                    "must not be empty when enabled".into(),
+                );
+            }
+            check_range(
+                &mut issue,
+                "remote_syslog.max_bytes_per_host",
+                self.remote_syslog.max_bytes_per_host,
+                REMOTE_SYSLOG_MAX_BYTES,
+            );
+            check_range(
+                &mut issue,
+                "remote_syslog.max_hosts",
+                self.remote_syslog.max_hosts as u64,
+                REMOTE_SYSLOG_MAX_HOSTS,
+            );
+            for (i, sender) in self.remote_syslog.allowed_senders.iter().enumerate() {
+                if let Err(e) = zc_log_tools::SenderNet::parse(sender) {
+                    issue(
+                        &format!("remote_syslog.allowed_senders[{i}]"),
+                        e.to_string(),
+                    );
+                }
+            }
+        }
+
+        // [vehicle]
+        if let Some(vin) = &self.vehicle.vin
+            && let Err(e) = zc_canbus_tools::vin_decode::decode(vin)
+        {
+            issue("vehicle.vin", e.to_string());
+        }
+
+        // [dtc_database]
+        for (i, path) in self.dtc_database.files.iter().enumerate() {
+            if let Err(e) = zc_canbus_tools::dtc_db::FileDtcDatabase::load(path) {
+                issue(&format!("dtc_database.files[{i}]"), e.to_string());
+            }
+        }
+        if let Some(manufacturer) = &self.dtc_database.manufacturer
+            && manufacturer.trim().is_empty()
+        {
+            issue("dtc_database.manufacturer", "must not be empty".into());
+        }
+
+        // [dbc]
+        for (i, path) in self.dbc.files.iter().enumerate() {
+            if let Err(e) = zc_canbus_tools::dbc::Dbc::load(path) {
+                issue(&format!("dbc.files[{i}]"), e.to_string());
+            }
+        }
+
+        // [ollama]
+        if self.ollama.enabled {
+            check_range(
+                &mut issue,
+                "ollama.timeout_secs",
+                self.ollama.timeout_secs,
+                OLLAMA_TIMEOUT_SECS,
+            );
+            if !(self.ollama.host.starts_with("http://")
+                || self.ollama.host.starts_with("https://"))
+            {
+                issue(
+                    "ollama.host",
+                    format!("must be an http(s) URL, got '{}'", self.ollama.host),
+                );
+            }
+            if self.ollama.model.trim().is_empty() {
+                issue("ollama.model", "must not be empty".into());
+            }
+        }
+
+        // [telemetry]
+        if self.telemetry.enabled {
+            check_range(
+                &mut issue,
+                "telemetry.window_secs",
+                self.telemetry.window_secs,
+                TELEMETRY_WINDOW_SECS,
+            );
+            check_range(
+                &mut issue,
+                "telemetry.sample_interval_ms",
+                self.telemetry.sample_interval_ms,
+                TELEMETRY_SAMPLE_MS,
+            );
+            if self.telemetry.sample_interval_ms > self.telemetry.window_secs * 1000 {
+                issue(
+                    "telemetry.sample_interval_ms",
+                    "must not exceed the aggregation window".into(),
+                );
+            }
+            if self.telemetry.pids.is_empty() {
+                issue(
+                    "telemetry.pids",
+                    "must list at least one PID when enabled".into(),
+                );
+            }
+        }
+
+        // [[pid_watches]]
+        for (field, message) in pid_watch::list_issues(&self.pid_watches) {
+            issue(&field, message);
+        }
+
+        // [history]
+        if self.history.enabled {
+            check_range(
+                &mut issue,
+                "history.max_entries",
+                self.history.max_entries as u64,
+                HISTORY_MAX_ENTRIES,
+            );
+            if self.history.path.trim().is_empty() {
+                issue("history.path", "must not be empty when enabled".into());
+            }
+        }
+
+        // [inbox]
+        if self.inbox.enabled && self.inbox.path.trim().is_empty() {
+            issue("inbox.path", "must not be empty when enabled".into());
+        }
+
+        // [self_test]
+        check_range(
+            &mut issue,
+            "self_test.min_free_mb",
+            self.self_test.min_free_mb,
+            SELF_TEST_MIN_FREE_MB,
+        );
+        if self.self_test.run_on_first_boot && self.self_test.marker_path.trim().is_empty() {
+            issue(
+                "self_test.marker_path",
+                "must not be empty when run_on_first_boot = true".into(),
+            );
+        }
+
+        // [result_cache]
+        if self.result_cache.enabled {
+            check_range(
+                &mut issue,
+                "result_cache.max_entries",
+                self.result_cache.max_entries as u64,
+                RESULT_CACHE_MAX_ENTRIES,
+            );
+        }
+        for tool in self
+            .result_cache
+            .ttl_secs
+            .keys()
+            .chain(&self.result_cache.forever)
+        {
+            if tool.trim().is_empty() {
+                issue("result_cache", "tool names must not be empty".into());
+            }
+        }
+        for tool in &self.result_cache.forever {
+            if self.result_cache.ttl_secs.contains_key(tool) {
+                issue(
+                    "result_cache.forever",
+                    format!("'{tool}' also has an entry in ttl_secs"),
+                );
+            }
+        }
+
+        // [command_timeouts]
+        check_range(
+            &mut issue,
+            "command_timeouts.max_secs",
+            self.command_timeouts.max_secs,
+            COMMAND_TIMEOUT_SECS,
+        );
+        for (tool, secs) in &self.command_timeouts.tool_max_secs {
+            if tool.trim().is_empty() {
+                issue("command_timeouts", "tool names must not be empty".into());
+            }
+            check_range(
+                &mut issue,
+                &format!("command_timeouts.tool_max_secs.{tool}"),
+                *secs,
+                COMMAND_TIMEOUT_SECS,
+            );
+        }
+
+        // [command_rate_limit]
+        check_range(
+            &mut issue,
+            "command_rate_limit.burst",
+            u64::from(self.command_rate_limit.burst),
+            COMMAND_RATE_BURST,
+        );
+        check_range(
+            &mut issue,
+            "command_rate_limit.per_minute",
+            u64::from(self.command_rate_limit.per_minute),
+            COMMAND_RATE_PER_MINUTE,
+        );
+
+        // [update]
+        if self.update.enabled {
+            check_range(
+                &mut issue,
+                "update.max_boot_attempts",
+                u64::from(self.update.max_boot_attempts),
+                UPDATE_BOOT_ATTEMPTS,
+            );
+            check_range(
+                &mut issue,
+                "update.download_timeout_secs",
+                self.update.download_timeout_secs,
+                UPDATE_DOWNLOAD_SECS,
+            );
+            if self.update.state_path.trim().is_empty() {
+                issue("update.state_path", "must not be empty when enabled".into());
+            }
+        }
+
+        // [file_transfer]
+        if self.file_transfer.enabled {
+            check_range(
+                &mut issue,
+                "file_transfer.max_file_bytes",
+                self.file_transfer.max_file_bytes,
+                TRANSFER_MAX_FILE_BYTES,
+            );
+            if self.file_transfer.staging_dir.trim().is_empty() {
+                issue(
+                    "file_transfer.staging_dir",
+                    "must not be empty when enabled".into(),
+                );
+            }
+            for dir in &self.file_transfer.allowed_dirs {
+                if !dir.starts_with('/') || dir.split('/').any(|part| part == "..") {
+                    issue(
+                        "file_transfer.allowed_dirs",
+                        format!("{dir:?} must be an absolute path without '..'"),
+                    );
+                }
+            }
+        }
+
+        issues
+    }
 }
 
+pub(crate) fn check_range(
+    issue: &mut impl FnMut(&str, String),
+    field: &str,
+    value: u64,
+    (min, max): (u64, u64),
+) {
+    if !(min..=max).contains(&value) {
+        issue(
+            field,
+            format!("must be between {min} and {max}, got {value}"),
+        );
+    }
+}
+
+/// Fully commented default `agent.toml`, printed by `--generate-config`.
+pub const DEFAULT_CONFIG_TEMPLATE: &str = r#"# ZeroClaw fleet agent configuration.
+# Check a file with: zc-fleet-agent --validate-config /etc/zeroclaw/agent.toml
+
+# Fleet this device belongs to.
+fleet_id = "fleet-alpha"
+
+# Unique device identifier (IoT Core thing name). Must match mqtt.client_id.
+device_id = "device-001"
+
+# Instead of fleet_id and device_id, a device can claim its identity with a
+# one-time code from POST /api/v1/claim-codes. On first boot it presents the
+# code and its hardware ID over MQTT and keeps the fleet and device IDs the
+# cloud assigns in identity_path; later boots reuse them. Remove fleet_id and
+# device_id when using this.
+# [claim]
+# code = "ABCD-EFGH-JKLM-NPQR"
+# hardware_id = "..."            # defaults to /etc/machine-id
+# hardware_type = "raspberry_pi_5"
+# identity_path = "/var/lib/zeroclaw/identity.json"
+# retry_secs = 30                # between attempts until answered (5-3600)
+
+# CAN bus interface name ("can0", "vcan0"). Omit to run without CAN tools.
+# can_interface = "can0"
+
+# Expected CAN link bitrate: 125000, 250000, 500000 or 1000000.
+# The agent checks the running link but does not reconfigure it.
+# can_bitrate = 500000
+
+# Devices on several CAN buses name each one instead of can_interface.
+# CAN tools take a "bus" argument and use the first bus without one.
+# protocol: "obd2" (OBD-II and UDS tools, default) or "raw" (can_monitor
+# and send_frame only). bitrate is optional and checked like can_bitrate.
+# [[can_buses]]
+# name = "powertrain"
+# interface = "can0"
+# bitrate = 500000
+#
+# [[can_buses]]
+# name = "body"
+# interface = "can1"
+# bitrate = 125000
+# protocol = "raw"
+#
+# Without SocketCAN hardware, an ELM327 OBD-II adapter (USB, or Bluetooth
+# bound to /dev/rfcommN) can serve a bus. interface is the serial device;
+# baud is 9600, 38400 (default), 57600, 115200 or 230400. The adapter
+# detects the vehicle's CAN protocol; can_monitor only sees responses.
+# [[can_buses]]
+# name = "obd"
+# interface = "/dev/ttyUSB0"
+# adapter = "elm327"
+# baud = 38400
+
+# Enable bench-only tools (send_frame). Never enable on a vehicle.
+bench_mode = false
+
+# Reject commands without a cloud-parsed intent and never run local
+# inference. For fleets where no LLM may be involved.
+structured_commands_only = false
+
+# Commands run at the same time (1-32). CAN tools still run one at a time.
+max_concurrent_commands = 4
+
+# Heartbeat interval in seconds (5-3600).
+heartbeat_interval_secs = 30
+
+# Shadow sync interval in seconds (5-86400).
+shadow_sync_interval_secs = 60
+
+# Log files available to the log tools.
+log_paths = ["/var/log/syslog"]
+
+[mqtt]
+# Broker hostname (e.g. the AWS IoT endpoint).
+broker_host = "broker.example.com"
+# 8883 for TLS, 1883 for plaintext local development.
+broker_port = 8883
+# MQTT client ID, normally the same as device_id.
+client_id = "device-001"
+# mTLS on/off. When true, the three certificate paths are required.
+use_tls = true
+client_cert_path = "/etc/zeroclaw/cert.pem"
+client_key_path = "/etc/zeroclaw/key.pem"
+ca_cert_path = "/etc/zeroclaw/AmazonRootCA1.pem"
+# Keep-alive in seconds (minimum 5).
+keepalive_secs = 30
+# "v311" or "v5". v5 tags responses with a correlation_id user property and
+# reports broker reason codes for rejected publishes and subscriptions.
+protocol = "v311"
+# "json" or "cbor". CBOR shrinks telemetry, heartbeats and responses on
+# metered links; only switch once the cloud reads it. Commands arrive in
+# CBOR when the cloud supports it, whatever this is set to.
+encoding = "json"
+# false keeps a persistent session: the broker retains subscriptions and
+# queues QoS 1 commands while the device is offline.
+clean_session = true
+# Register a Last Will: the broker publishes a retained "offline" presence
+# message when the agent drops, so the cloud marks the device offline at
+# once instead of waiting for missed heartbeats.
+last_will = true
+
+[mqtt.qos]
+# QoS (0, 1 or 2) per outbound message class.
+response = 1
+telemetry = 1
+heartbeat = 1
+other = 1
+
+[mqtt.offline_buffer]
+# Messages published while the broker is unreachable are held and sent
+# after reconnecting instead of being lost.
+enabled = true
+# Messages kept (10-100000); the oldest is dropped when full.
+capacity = 1000
+# Keep the buffer across agent restarts. In memory only when omitted.
+path = "/var/lib/zeroclaw/outbox.jsonl"
+
+[mqtt.reconnect]
+# Wait after a failed connection attempt, doubling (with jitter) up to
+# max_delay_secs.
+initial_delay_secs = 1
+max_delay_secs = 60
+# This many reconnects within storm_window_secs mark the connection unstable
+# in the diagnostics shadow.
+storm_threshold = 5
+storm_window_secs = 300
+
+[ollama]
+# Local inference for commands the cloud could not parse.
+enabled = true
+host = "http://localhost:11434"
+model = "phi3:mini"
+# Request timeout in seconds (1-300).
+timeout_secs = 5
+
+[telemetry]
+# Periodic PID sampling with on-device aggregation.
+enabled = false
+# Aggregation window in seconds (1-3600).
+window_secs = 10
+# Delay between sampling rounds in milliseconds (10-60000).
+sample_interval_ms = 1000
+# OBD-II Mode 01 PIDs: RPM, vehicle speed, coolant temperature.
+pids = [0x0C, 0x0D, 0x05]
+
+# Standing PID watches: sample a PID every interval_ms (at least 100),
+# publish each reading as telemetry and alert when it leaves above/below.
+# Up to 8 watches, including those started with the watch_pid tool.
+# [[pid_watches]]
+# pid = 0x05
+# interval_ms = 1000
+# above = 110.0
+
+[history]
+# Local journal of commands and telemetry, queryable by the cloud with
+# get_local_history after an outage.
+enabled = true
+path = "/var/lib/zeroclaw/history.jsonl"
+# Entries kept before the oldest are dropped (100-100000).
+max_entries = 5000
+
+[inbox]
+# Received commands are kept here until answered, so a restart mid-command
+# replays queued commands and reports the interrupted one as failed.
+enabled = true
+path = "/var/lib/zeroclaw/inbox.jsonl"
+
+[self_test]
+# Check CAN, log paths, Ollama, broker, clock and disk once on first boot
+# and publish the report as the provisioning verification record.
+run_on_first_boot = true
+# Delete this file to re-run the self-test on the next start.
+marker_path = "/var/lib/zeroclaw/self_test.done"
+# Free disk space (MB) below which the disk check fails; warns below 2x.
+min_free_mb = 100
+
+[result_cache]
+# Answer repeated identical tool calls (same tool and arguments) from a
+# cache, e.g. dashboard polls, instead of re-running log scans and CAN
+# queries. Cached responses are marked `cached: true`. Built-in TTLs:
+# read_vin and list_supported_pids never expire; log_stats, analyze_errors,
+# search_logs and detect_anomalies last 30 s. Other tools are not cached.
+enabled = false
+# Entries kept (1-10000).
+max_entries = 256
+# Tools whose results never expire.
+# forever = ["read_uds_did"]
+# Per-tool TTL in seconds; 0 turns caching off for that tool.
+# [result_cache.ttl_secs]
+# log_stats = 60
+# search_logs = 0
+
+[command_timeouts]
+# A command's action is stopped once the envelope's timeout_secs has elapsed
+# and reported with status "timeout". Longest any command may run (1-3600).
+max_secs = 300
+# Per-tool limits in seconds (1-3600). Built in: read_vin, read_pid,
+# read_uds_did and read_readiness 10 s, read_freeze, list_supported_pids and
+# read_vehicle_info 20 s.
+# [command_timeouts.tool_max_secs]
+# search_logs = 60
+
+[command_rate_limit]
+# Commands arriving faster than this are answered "throttled" without running.
+# Accepted back to back (1-1000).
+burst = 20
+# Refill rate once the burst is used up (1-6000).
+per_minute = 60
+
+[update]
+# Install agent releases delivered on the "update" shadow: download, check
+# the SHA-256, swap the binary and exit for systemd to restart the agent
+# (Restart=on-failure). A release that does not reach the broker within
+# max_boot_attempts starts is rolled back to the previous binary.
+enabled = true
+# Binary to replace; defaults to the running executable.
+# binary_path = "/opt/zeroclaw/zc-fleet-agent"
+state_path = "/var/lib/zeroclaw/update.json"
+# Starts a new release gets before it is rolled back (1-10).
+max_boot_attempts = 3
+# Longest a download may take in seconds (10-3600).
+download_timeout_secs = 300
+
+[file_transfer]
+# Accept files pushed by the cloud (DBC files, CA bundles, config blobs) and
+# requests to fetch files back (core dumps, pcap captures). Only paths under
+# allowed_dirs are written or read.
+enabled = true
+allowed_dirs = ["/var/lib/zeroclaw/files", "/var/crash"]
+# Where pushed files are assembled; partial transfers resume from here.
+staging_dir = "/var/lib/zeroclaw/transfers"
+# Largest file in either direction, in bytes (1 KiB - 256 MiB).
+max_file_bytes = 8388608
+
+# Custom log formats for fleet-specific application logs. Select one with the
+# log tools' `format` argument, or let auto-detection pick it: priority > 0
+# is tried before the built-in formats, otherwise only for lines they would
+# treat as plaintext. Needs a `message` group; `timestamp`, `severity` and
+# `source` are optional, other named groups become entry fields.
+# [[log_formats]]
+# name = "telematics"
+# pattern = '^\[(?P<timestamp>[^\]]+)\] <(?P<severity>\w)> (?P<source>\w+): (?P<message>.*)$'
+# priority = 0
+# timestamp_format = "%d/%m/%Y %H:%M:%S%.3f"
+# severity_map = { E = "error", W = "warning", I = "info" }
+
+[remote_syslog]
+# Receive syslog (RFC 3164/5424 over UDP) from other devices on the local
+# network, e.g. dashcams or telematics units. Each sender's messages are
+# kept in <spool_dir>/<ip>.log and read by the log tools as remote://<ip>
+# (remote://* for all senders).
+enabled = false
+bind = "0.0.0.0:5514"
+spool_dir = "/var/lib/zeroclaw/remote-logs"
+# Bytes kept per sender (65536-1073741824); the oldest half is dropped once
+# a sender's file grows past it.
+max_bytes_per_host = 4194304
+# Addresses or CIDR networks accepted, e.g. ["192.168.1.0/24"]; empty
+# accepts any sender that can reach the port.
+allowed_senders = []
+# Most senders spooled (1-1024); messages from further ones are dropped.
+max_hosts = 32
+
+# Vehicle this device is installed in. Added to the inference prompts so the
+# model picks commands that fit (e.g. no spark-ignition PIDs on a diesel).
+# Make and model year are decoded from the VIN unless set; without a VIN the
+# agent remembers the one from its first successful read_vin.
+# [vehicle]
+# vin = "1FUJGLDR7CLBP8834"
+# make = "Freightliner"
+# model = "Cascadia"
+# model_year = 2012
+# fuel_type = "diesel"     # gasoline | diesel | hybrid | electric
+
+# Manufacturer-specific DTC codes on top of the built-in database. CSV files
+# need a `code,description` header (optional `severity`, `manufacturer`
+# columns); JSON files hold an array of objects with the same keys. Codes in
+# these files win over built-in descriptions, earlier files over later ones.
+# [dtc_database]
+# files = ["/etc/zeroclaw/dtc_ford.csv"]
+# manufacturer = "Ford"
+
+# DBC files describing proprietary CAN messages. can_monitor decodes frames
+# with a known ID into named signals with scaled values (units included).
+# [dbc]
+# files = ["/etc/zeroclaw/dbc/body.dbc"]
+"#;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,7 +1107,10 @@ ca_cert_path = "/etc/zeroclaw/AmazonRootCA1.pem"
         assert_eq!(config.device_id, "rpi-001");
         assert_eq!(config.mqtt.broker_port, 8883); // default
         assert_eq!(config.heartbeat_interval_secs, 30); // default
+        assert_eq!(config.max_concurrent_commands, 4); // default
         assert!(config.can_interface.is_none());
+        assert!(config.can_bitrate.is_none());
+        assert!(!config.bench_mode);
         assert!(config.log_paths.is_empty());
     }
 
@@ -81,6 +1120,8 @@ ca_cert_path = "/etc/zeroclaw/AmazonRootCA1.pem"
 fleet_id = "fleet-beta"
 device_id = "sbc-042"
 can_interface = "can0"
+can_bitrate = 500000
+bench_mode = true
 heartbeat_interval_secs = 15
 log_paths = ["/var/log/syslog", "/var/log/zeroclaw.log"]
 
@@ -96,6 +1137,8 @@ keepalive_secs = 60
         let config: AgentConfig = toml::from_str(toml).unwrap();
         assert_eq!(config.fleet_id, "fleet-beta");
         assert_eq!(config.can_interface.as_deref(), Some("can0"));
+        assert_eq!(config.can_bitrate, Some(500_000));
+        assert!(config.bench_mode);
         assert_eq!(config.heartbeat_interval_secs, 15);
         assert_eq!(config.log_paths.len(), 2);
         assert_eq!(config.mqtt.keepalive_secs, 60);
@@ -181,4 +1224,497 @@ enabled = false
         assert_eq!(config.ollama.timeout_secs, 10);
         assert!(!config.ollama.enabled);
     }
+
+    #[test]
+    fn deserialize_telemetry_config() {
+        let toml = r#"
+fleet_id = "fleet-alpha"
+device_id = "rpi-001"
+
+[mqtt]
+broker_host = "broker.example.com"
+client_id = "rpi-001"
+
+[telemetry]
+enabled = true
+window_secs = 30
+pids = [12, 13]
+"#;
+        let config: AgentConfig = toml::from_str(toml).unwrap();
+        assert!(config.telemetry.enabled);
+        assert_eq!(config.telemetry.window_secs, 30);
+        assert_eq!(config.telemetry.sample_interval_ms, 1000); // default
+        assert_eq!(config.telemetry.pids, vec![0x0C, 0x0D]);
+    }
+
+    const MINIMAL: &str = r#"
+fleet_id = "fleet-alpha"
+device_id = "rpi-001"
+
+[mqtt]
+broker_host = "broker.example.com"
+client_id = "rpi-001"
+client_cert_path = "/certs/cert.pem"
+client_key_path = "/certs/key.pem"
+ca_cert_path = "/certs/ca.pem"
+"#;
+
+    #[test]
+    fn default_template_is_valid() {
+        let config = AgentConfig::from_toml_str(DEFAULT_CONFIG_TEMPLATE, "agent.toml").unwrap();
+        assert_eq!(config.heartbeat_interval_secs, 30);
+        assert_eq!(config.telemetry.pids, vec![0x0C, 0x0D, 0x05]);
+        assert!(!config.bench_mode);
+        assert!(!config.structured_commands_only);
+        assert!(config.mqtt.clean_session);
+        assert_eq!(config.mqtt.offline_buffer.capacity, 1000);
+    }
+
+    #[test]
+    fn dev_config_is_valid() {
+        let contents = include_str!("../../../dev/agent.toml");
+        AgentConfig::from_toml_str(contents, "dev/agent.toml").unwrap();
+    }
+
+    #[test]
+    fn unknown_key_is_rejected_with_location() {
+        let toml = MINIMAL.replace("device_id", "heartbeat_secs = 10\ndevice_id");
+        let err = AgentConfig::from_toml_str(&toml, "agent.toml").unwrap_err();
+        let msg = err.to_string();
+        assert!(matches!(err, ConfigError::Parse { .. }));
+        assert!(msg.contains("heartbeat_secs"), "{msg}");
+        assert!(msg.contains("line"), "{msg}");
+    }
+
+    #[test]
+    fn unknown_key_in_section_is_rejected() {
+        let toml = format!("{MINIMAL}\n[ollama]\nmodle = \"phi3\"\n");
+        let err = AgentConfig::from_toml_str(&toml, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("modle"));
+    }
+
+    #[test]
+    fn offline_buffer_settings_are_checked() {
+        let toml = format!("{MINIMAL}\n[mqtt.offline_buffer]\ncapacity = 1\npath = \" \"\n");
+        let err = AgentConfig::from_toml_str(&toml, "agent.toml").unwrap_err();
+        let ConfigError::Invalid { issues, .. } = &err else {
+            panic!("expected validation error, got {err}");
+        };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert!(fields.contains(&"mqtt.offline_buffer.capacity"));
+        assert!(fields.contains(&"mqtt.offline_buffer.path"));
+
+        let toml =
+            format!("{MINIMAL}\n[mqtt.reconnect]\ninitial_delay_secs = 120\nmax_delay_secs = 60\n");
+        let err = AgentConfig::from_toml_str(&toml, "agent.toml").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("mqtt.reconnect.initial_delay_secs")
+        );
+
+        let toml = format!("{MINIMAL}\n[mqtt.qos]\nheartbeat = 5\n");
+        assert!(matches!(
+            AgentConfig::from_toml_str(&toml, "agent.toml"),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn out_of_range_values_are_all_reported() {
+        let toml = MINIMAL.replace(
+            "device_id = \"rpi-001\"",
+            "device_id = \"rpi-001\"\nheartbeat_interval_secs = 1\ncan_bitrate = 42",
+        );
+        let err = AgentConfig::from_toml_str(&toml, "agent.toml").unwrap_err();
+        let ConfigError::Invalid { issues, .. } = &err else {
+            panic!("expected validation error, got {err}");
+        };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert!(fields.contains(&"heartbeat_interval_secs"));
+        assert!(fields.contains(&"can_bitrate"));
+        assert!(err.to_string().contains("between 5 and 3600, got 1"));
+    }
+
+    #[test]
+    fn tls_requires_certificate_paths() {
+        let toml = MINIMAL.replace("client_key_path = \"/certs/key.pem\"\n", "");
+        let err = AgentConfig::from_toml_str(&toml, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("mqtt.client_key_path"));
+    }
+
+    #[test]
+    fn telemetry_checked_only_when_enabled() {
+        let disabled = format!("{MINIMAL}\n[telemetry]\npids = []\n");
+        assert!(AgentConfig::from_toml_str(&disabled, "agent.toml").is_ok());
+
+        let enabled = format!("{MINIMAL}\n[telemetry]\nenabled = true\npids = []\n");
+        let err = AgentConfig::from_toml_str(&enabled, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("telemetry.pids"));
+    }
+
+    #[test]
+    fn history_limits_checked() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(config.history.enabled);
+        assert_eq!(config.history.max_entries, 5000);
+
+        let small = format!("{MINIMAL}\n[history]\nmax_entries = 10\n");
+        let err = AgentConfig::from_toml_str(&small, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("history.max_entries"));
+
+        let disabled = format!("{MINIMAL}\n[history]\nenabled = false\nmax_entries = 10\n");
+        assert!(AgentConfig::from_toml_str(&disabled, "agent.toml").is_ok());
+    }
+
+    #[test]
+    fn result_cache_checked() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(!config.result_cache.enabled);
+
+        let cache = format!(
+            "{MINIMAL}\n[result_cache]\nenabled = true\nforever = [\"read_pid\"]\n[result_cache.ttl_secs]\nread_pid = 5\n"
+        );
+        let err = AgentConfig::from_toml_str(&cache, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("result_cache.forever"));
+
+        let cache = format!("{MINIMAL}\n[result_cache]\nenabled = true\nmax_entries = 0\n");
+        let err = AgentConfig::from_toml_str(&cache, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("result_cache.max_entries"));
+    }
+
+    #[test]
+    fn remote_syslog_checked() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(!config.remote_syslog.enabled);
+        assert_eq!(config.remote_syslog.bind, "0.0.0.0:5514");
+
+        let syslog = format!("{MINIMAL}\n[remote_syslog]\nenabled = true\nbind = \"5514\"\n");
+        let err = AgentConfig::from_toml_str(&syslog, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("remote_syslog.bind"));
+
+        let syslog =
+            format!("{MINIMAL}\n[remote_syslog]\nenabled = true\nmax_bytes_per_host = 10\n");
+        let err = AgentConfig::from_toml_str(&syslog, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("remote_syslog.max_bytes_per_host"));
+
+        let syslog = format!(
+            "{MINIMAL}\n[remote_syslog]\nenabled = true\nmax_hosts = 0\nallowed_senders = [\"10.0.0.0/8\", \"cam1\"]\n"
+        );
+        let err = AgentConfig::from_toml_str(&syslog, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("remote_syslog.max_hosts"));
+        assert!(err.to_string().contains("remote_syslog.allowed_senders[1]"));
+    }
+
+    #[test]
+    fn command_timeouts_checked() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert_eq!(config.command_timeouts.max_secs, 300);
+
+        let timeouts = format!(
+            "{MINIMAL}\n[command_timeouts]\nmax_secs = 0\n[command_timeouts.tool_max_secs]\nread_pid = 5000\n"
+        );
+        let err = AgentConfig::from_toml_str(&timeouts, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("command_timeouts.max_secs"));
+        assert!(err.contains("command_timeouts.tool_max_secs.read_pid"));
+    }
+
+    #[test]
+    fn command_rate_limit_checked() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert_eq!(config.command_rate_limit, RateLimit::new(20, 60));
+
+        let limit = format!("{MINIMAL}\n[command_rate_limit]\nburst = 0\nper_minute = 10\n");
+        let err = AgentConfig::from_toml_str(&limit, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("command_rate_limit.burst"));
+        assert!(!err.contains("command_rate_limit.per_minute"));
+    }
+
+    #[test]
+    fn max_concurrent_commands_checked() {
+        let config = format!("max_concurrent_commands = 0\n{MINIMAL}");
+        let err = AgentConfig::from_toml_str(&config, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("max_concurrent_commands"));
+    }
+
+    #[test]
+    fn log_formats_parsed_and_checked() {
+        let formats = r#"
+[[log_formats]]
+name = "telematics"
+pattern = '^<(?P<severity>\w)> (?P<message>.*)$'
+priority = 5
+severity_map = { E = "error" }
+"#;
+        let config =
+            AgentConfig::from_toml_str(&format!("{MINIMAL}{formats}"), "agent.toml").unwrap();
+        assert_eq!(config.log_formats.len(), 1);
+        assert_eq!(config.log_formats[0].priority, 5);
+        assert_eq!(
+            config.log_formats[0].severity_map["E"],
+            zc_log_tools::LogSeverity::Error
+        );
+
+        let more = r#"
+[[log_formats]]
+name = "telematics"
+pattern = '(?P<message>.*)'
+
+[[log_formats]]
+name = "broken"
+pattern = '(?P<msg>.*'
+"#;
+        let bad = format!("{MINIMAL}{formats}{more}");
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml").unwrap_err();
+        let ConfigError::Invalid { issues, .. } = &err else {
+            panic!("expected validation error, got {err}");
+        };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(fields, ["log_formats[1]", "log_formats[2]"]);
+        assert!(issues[0].message.contains("duplicate name"));
+    }
+
+    #[test]
+    fn dtc_database_files_checked() {
+        let file = std::env::temp_dir().join(format!("zc-dtc-{}.csv", std::process::id()));
+        std::fs::write(&file, "code,description\nB1342,ECU is faulted\n").unwrap();
+
+        let section = format!(
+            "\n[dtc_database]\nfiles = [{:?}]\nmanufacturer = \"Ford\"\n",
+            file.display().to_string()
+        );
+        let config =
+            AgentConfig::from_toml_str(&format!("{MINIMAL}{section}"), "agent.toml").unwrap();
+        assert_eq!(config.dtc_database.files.len(), 1);
+        assert_eq!(config.dtc_database.manufacturer.as_deref(), Some("Ford"));
+
+        let missing = format!("{MINIMAL}\n[dtc_database]\nfiles = [\"/nonexistent/dtc.json\"]\n");
+        let err = AgentConfig::from_toml_str(&missing, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("dtc_database.files[0]"));
+
+        std::fs::remove_file(&file).ok();
+    }
+
+    #[test]
+    fn dbc_files_checked() {
+        let file = std::env::temp_dir().join(format!("zc-dbc-{}.dbc", std::process::id()));
+        std::fs::write(
+            &file,
+            "BO_ 1280 Engine: 8 ECM\n SG_ Rpm : 0|16@1+ (0.25,0) [0|16383.75] \"rpm\" X\n",
+        )
+        .unwrap();
+
+        let section = format!("\n[dbc]\nfiles = [{:?}]\n", file.display().to_string());
+        let config =
+            AgentConfig::from_toml_str(&format!("{MINIMAL}{section}"), "agent.toml").unwrap();
+        assert_eq!(config.dbc.files.len(), 1);
+
+        std::fs::write(
+            &file,
+            "BO_ 1280 Engine: 8 ECM\n SG_ Rpm : 0|16@3+ (1,0) [0|1] \"\" X\n",
+        )
+        .unwrap();
+        let err =
+            AgentConfig::from_toml_str(&format!("{MINIMAL}{section}"), "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("dbc.files[0]"));
+
+        std::fs::remove_file(&file).ok();
+    }
+
+    #[test]
+    fn inbox_path_checked_only_when_enabled() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(config.inbox.enabled);
+
+        let empty = format!("{MINIMAL}\n[inbox]\npath = \"\"\n");
+        let err = AgentConfig::from_toml_str(&empty, "agent.toml").unwrap_err();
+        assert!(err.to_string().contains("inbox.path"));
+
+        let disabled = format!("{MINIMAL}\n[inbox]\nenabled = false\npath = \"\"\n");
+        assert!(AgentConfig::from_toml_str(&disabled, "agent.toml").is_ok());
+    }
+
+    #[test]
+    fn self_test_settings_checked() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(config.self_test.run_on_first_boot);
+        assert_eq!(config.self_test.min_free_mb, 100);
+
+        let bad = format!("{MINIMAL}\n[self_test]\nmarker_path = \"\"\nmin_free_mb = 0\n");
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("self_test.marker_path"));
+        assert!(err.contains("self_test.min_free_mb"));
+    }
+
+    #[test]
+    fn update_settings_checked_only_when_enabled() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(config.update.enabled);
+        assert_eq!(config.update.max_boot_attempts, 3);
+
+        let bad = format!("{MINIMAL}\n[update]\nmax_boot_attempts = 0\nstate_path = \"\"\n");
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("update.max_boot_attempts"));
+        assert!(err.contains("update.state_path"));
+
+        let disabled = format!("{MINIMAL}\n[update]\nenabled = false\nmax_boot_attempts = 0\n");
+        assert!(AgentConfig::from_toml_str(&disabled, "agent.toml").is_ok());
+    }
+
+    #[test]
+    fn file_transfer_dirs_must_be_absolute() {
+        let config = AgentConfig::from_toml_str(MINIMAL, "agent.toml").unwrap();
+        assert!(config.file_transfer.enabled);
+        assert_eq!(config.file_transfer.max_file_bytes, 8 * 1024 * 1024);
+
+        let bad = format!(
+            "{MINIMAL}\n[file_transfer]\nallowed_dirs = [\"files\", \"/var/../etc\"]\nmax_file_bytes = 10\n"
+        );
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("\"files\" must be an absolute path"));
+        assert!(err.contains("\"/var/../etc\" must be an absolute path"));
+        assert!(err.contains("file_transfer.max_file_bytes"));
+    }
+
+    #[test]
+    fn can_buses_are_validated_and_replace_can_interface() {
+        let legacy = AgentConfig::from_toml_str(
+            &MINIMAL.replace(
+                "device_id = \"rpi-001\"",
+                "device_id = \"rpi-001\"\ncan_interface = \"can0\"\ncan_bitrate = 500000",
+            ),
+            "agent.toml",
+        )
+        .unwrap();
+        let buses = legacy.can_buses();
+        assert_eq!(buses.len(), 1);
+        assert_eq!(buses[0].name, "can0");
+        assert_eq!(buses[0].bitrate, Some(500_000));
+
+        let two = format!(
+            "{MINIMAL}\n[[can_buses]]\nname = \"powertrain\"\ninterface = \"can0\"\n\n[[can_buses]]\nname = \"body\"\ninterface = \"can1\"\nbitrate = 125000\nprotocol = \"raw\"\n"
+        );
+        let config = AgentConfig::from_toml_str(&two, "agent.toml").unwrap();
+        let buses = config.can_buses();
+        assert_eq!(buses.len(), 2);
+        assert_eq!(buses[0].protocol, CanBusProtocol::Obd2);
+        assert_eq!(buses[1].protocol, CanBusProtocol::Raw);
+        assert!(
+            AgentConfig::from_toml_str(MINIMAL, "agent.toml")
+                .unwrap()
+                .can_buses()
+                .is_empty()
+        );
+
+        let bad = format!(
+            "{MINIMAL}\n[[can_buses]]\nname = \"a b\"\ninterface = \"can0\"\nbitrate = 42\n\n[[can_buses]]\nname = \"body\"\ninterface = \"can0\"\n"
+        )
+        .replace(
+            "device_id = \"rpi-001\"",
+            "device_id = \"rpi-001\"\ncan_interface = \"can0\"",
+        );
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml").unwrap_err();
+        let ConfigError::Invalid { issues, .. } = &err else {
+            panic!("expected validation error, got {err}");
+        };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "can_buses",
+                "can_buses[0].name",
+                "can_buses[0].bitrate",
+                "can_buses[1].interface"
+            ]
+        );
+    }
+
+    #[test]
+    fn pid_watches_are_validated() {
+        let ok = format!("{MINIMAL}\n[[pid_watches]]\npid = 0x05\nabove = 110.0\n");
+        let config = AgentConfig::from_toml_str(&ok, "agent.toml").unwrap();
+        assert_eq!(config.pid_watches[0].pid, 5);
+        assert_eq!(config.pid_watches[0].interval_ms, 1000);
+
+        let bad = format!(
+            "{MINIMAL}\n[[pid_watches]]\npid = 0x05\ninterval_ms = 50\nabove = 60.0\nbelow = 90.0\n"
+        );
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("pid_watches[0].interval_ms"));
+        assert!(err.contains("pid_watches[0].below"));
+    }
+
+    #[test]
+    fn elm327_buses_take_a_serial_device_and_baud() {
+        let elm = format!(
+            "{MINIMAL}\n[[can_buses]]\nname = \"obd\"\ninterface = \"/dev/rfcomm0\"\nadapter = \"elm327\"\nbaud = 115200\nbitrate = 500000\n"
+        );
+        let config = AgentConfig::from_toml_str(&elm, "agent.toml").unwrap();
+        assert_eq!(config.can_buses[0].adapter, CanAdapter::Elm327);
+        assert_eq!(config.can_buses[0].baud, Some(115_200));
+
+        let bad = format!(
+            "{MINIMAL}\n[[can_buses]]\nname = \"obd\"\ninterface = \"ttyUSB0\"\nadapter = \"elm327\"\nbaud = 12345\nbitrate = 125000\n\n[[can_buses]]\nname = \"can\"\ninterface = \"can0\"\nbaud = 38400\n"
+        );
+        let err = AgentConfig::from_toml_str(&bad, "agent.toml").unwrap_err();
+        let ConfigError::Invalid { issues, .. } = &err else {
+            panic!("expected validation error, got {err}");
+        };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "can_buses[0].interface",
+                "can_buses[0].baud",
+                "can_buses[0].bitrate",
+                "can_buses[1].baud"
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        let err = AgentConfig::from_file("/nonexistent/agent.toml").unwrap_err();
+        assert!(matches!(err, ConfigError::Io { .. }));
+    }
+
+    #[test]
+    fn claim_replaces_fleet_and_device_ids() {
+        let claiming = MINIMAL
+            .replace("fleet_id = \"fleet-alpha\"\n", "")
+            .replace("device_id = \"rpi-001\"\n", "")
+            + "\n[claim]\ncode = \"ABCD-EFGH-JKLM-NPQR\"\n";
+        let config = AgentConfig::from_toml_str(&claiming, "agent.toml").unwrap();
+        let claim = config.claim.unwrap();
+        assert_eq!(claim.identity_path, "/var/lib/zeroclaw/identity.json");
+        assert_eq!(claim.retry_secs, 30);
+        assert!(config.device_id.is_empty());
+
+        let both = MINIMAL.to_string() + "\n[claim]\ncode = \"\"\nretry_secs = 1\n";
+        let err = AgentConfig::from_toml_str(&both, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("fleet_id: must be left out with [claim]"));
+        assert!(err.contains("claim.code"));
+        assert!(err.contains("claim.retry_secs"));
+
+        let neither = MINIMAL.replace("device_id = \"rpi-001\"\n", "");
+        let err = AgentConfig::from_toml_str(&neither, "agent.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("device_id: must not be empty"));
+    }
 }
@@ -0,0 +1,279 @@
+//! What the agent knows about its vehicle, for local inference.
+//!
+//! Seeded at startup from `[vehicle]` in agent.toml (a configured VIN is
+//! decoded for make and model year), the CAN settings and the executor's
+//! tools. A successful `read_vin` is remembered, so a device installed
+//! without a configured VIN learns it from its first VIN read; so are the
+//! PIDs found by `list_supported_pids`. The Ollama
+//! client appends the context to its system prompt, and the vehicle part is
+//! reported in the `diagnostics` shadow for cloud inference.
+
+use std::sync::RwLock;
+
+use serde::Deserialize;
+use zc_canbus_tools::vin_decode;
+use zc_protocol::context::{DeviceContext, FuelType, VehicleProfile};
+
+use crate::can_bus::{CanAdapter, CanBusConfig};
+use crate::config::AgentConfig;
+
+/// Tool whose result carries the VIN.
+pub const READ_VIN_TOOL: &str = "read_vin";
+
+/// Tool whose result carries the ECU's supported PIDs.
+pub const LIST_SUPPORTED_PIDS_TOOL: &str = "list_supported_pids";
+
+/// Makers that (almost) only build diesel trucks, so their fuel type can be
+/// assumed when it is not configured.
+const DIESEL_TRUCK_MAKERS: &[&str] = &[
+    "Freightliner",
+    "Mack",
+    "Kenworth",
+    "Peterbilt",
+    "Volvo Trucks",
+    "Scania",
+    "MAN",
+    "DAF",
+];
+
+/// Vehicle details (`[vehicle]` in agent.toml). All optional; configured
+/// values win over what is decoded from the VIN.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VehicleConfig {
+    #[serde(default)]
+    pub vin: Option<String>,
+    #[serde(default)]
+    pub make: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub model_year: Option<u16>,
+    #[serde(default)]
+    pub fuel_type: Option<FuelType>,
+}
+
+/// The device context, shared between the executor and the Ollama client.
+pub struct DeviceContextStore {
+    configured: VehicleConfig,
+    context: RwLock<DeviceContext>,
+}
+
+impl DeviceContextStore {
+    /// Context from the agent configuration, without tools.
+    pub fn from_config(config: &AgentConfig) -> Self {
+        let configured = config.vehicle.clone();
+        let mut vehicle = VehicleProfile {
+            vin: None,
+            make: configured.make.clone(),
+            model: configured.model.clone(),
+            model_year: configured.model_year,
+            fuel_type: configured.fuel_type,
+            can_protocol: can_protocol(&config.can_buses()),
+            supported_pids: Vec::new(),
+        };
+        if let Some(vin) = &configured.vin {
+            apply_vin(&mut vehicle, &configured, vin);
+        } else {
+            vehicle.fuel_type = vehicle.fuel_type.or_else(|| assumed_fuel(&vehicle));
+        }
+        Self {
+            configured,
+            context: RwLock::new(DeviceContext {
+                vehicle,
+                tools: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn snapshot(&self) -> DeviceContext {
+        self.context
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn set_tools(&self, tools: Vec<String>) {
+        self.context
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .tools = tools;
+    }
+
+    /// Remember a VIN read from the vehicle. Returns whether it changed.
+    pub fn remember_vin(&self, vin: &str) -> bool {
+        let mut context = self.context.write().unwrap_or_else(|e| e.into_inner());
+        let vin = vin.trim().to_ascii_uppercase();
+        if context.vehicle.vin.as_deref() == Some(vin.as_str()) {
+            return false;
+        }
+        apply_vin(&mut context.vehicle, &self.configured, &vin);
+        tracing::info!(vin = %vin, make = ?context.vehicle.make, "vehicle VIN remembered");
+        true
+    }
+
+    /// Remember the PIDs the ECU reported as supported. Returns whether
+    /// they changed.
+    pub fn remember_supported_pids(&self, mut pids: Vec<u8>) -> bool {
+        pids.sort_unstable();
+        pids.dedup();
+        let mut context = self.context.write().unwrap_or_else(|e| e.into_inner());
+        if context.vehicle.supported_pids == pids {
+            return false;
+        }
+        tracing::info!(count = pids.len(), "supported PIDs remembered");
+        context.vehicle.supported_pids = pids;
+        true
+    }
+}
+
+/// Set `vin` and fill make, model year and fuel type from it where they are
+/// not configured.
+fn apply_vin(vehicle: &mut VehicleProfile, configured: &VehicleConfig, vin: &str) {
+    vehicle.vin = Some(vin.trim().to_ascii_uppercase());
+    let decoded = vin_decode::decode(vin).ok();
+    vehicle.make = configured.make.clone().or_else(|| {
+        decoded
+            .as_ref()
+            .and_then(|d| d.manufacturer)
+            .map(String::from)
+    });
+    vehicle.model_year = configured
+        .model_year
+        .or_else(|| decoded.as_ref().and_then(|d| d.model_year));
+    vehicle.fuel_type = configured.fuel_type.or_else(|| assumed_fuel(vehicle));
+}
+
+fn assumed_fuel(vehicle: &VehicleProfile) -> Option<FuelType> {
+    let make = vehicle.make.as_deref()?;
+    if make == "Tesla" {
+        return Some(FuelType::Electric);
+    }
+    DIESEL_TRUCK_MAKERS
+        .contains(&make)
+        .then_some(FuelType::Diesel)
+}
+
+/// "can0, 500 kbit/s" from the CAN settings, one entry per bus
+/// ("powertrain: can0, 500 kbit/s; body: can1") with several.
+fn can_protocol(buses: &[CanBusConfig]) -> Option<String> {
+    let describe = |bus: &CanBusConfig| {
+        let mut text = match bus.bitrate {
+            Some(bps) => format!("{}, {} kbit/s", bus.interface, bps / 1000),
+            None => bus.interface.clone(),
+        };
+        if bus.adapter == CanAdapter::Elm327 {
+            text.push_str(" via ELM327");
+        }
+        text
+    };
+    match buses {
+        [] => None,
+        [bus] => Some(describe(bus)),
+        many => Some(
+            many.iter()
+                .map(|bus| format!("{}: {}", bus.name, describe(bus)))
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> AgentConfig {
+        let toml = format!(
+            r#"
+fleet_id = "fleet-alpha"
+device_id = "truck-001"
+can_interface = "can0"
+can_bitrate = 250000
+
+[mqtt]
+broker_host = "broker.example.com"
+client_id = "truck-001"
+client_cert_path = "/certs/cert.pem"
+client_key_path = "/certs/key.pem"
+ca_cert_path = "/certs/ca.pem"
+{extra}
+"#
+        );
+        AgentConfig::from_toml_str(&toml, "agent.toml").unwrap()
+    }
+
+    #[test]
+    fn configured_vin_is_decoded() {
+        let store = DeviceContextStore::from_config(&config(
+            "[vehicle]\nvin = \"1HGCM82633A004352\"\nmodel = \"Accord\"\n",
+        ));
+        let vehicle = store.snapshot().vehicle;
+        assert_eq!(vehicle.make.as_deref(), Some("Honda"));
+        assert_eq!(vehicle.model.as_deref(), Some("Accord"));
+        assert_eq!(vehicle.model_year, Some(2003));
+        assert_eq!(vehicle.fuel_type, None);
+        assert_eq!(vehicle.can_protocol.as_deref(), Some("can0, 250 kbit/s"));
+    }
+
+    #[test]
+    fn several_buses_are_listed_by_name() {
+        let bus = |name: &str, interface: &str, bitrate| CanBusConfig {
+            name: name.into(),
+            interface: interface.into(),
+            bitrate,
+            protocol: Default::default(),
+            adapter: Default::default(),
+            baud: None,
+        };
+        assert_eq!(
+            can_protocol(&[
+                bus("powertrain", "can0", Some(500_000)),
+                bus("body", "can1", None)
+            ])
+            .as_deref(),
+            Some("powertrain: can0, 500 kbit/s; body: can1")
+        );
+        let elm = CanBusConfig {
+            adapter: CanAdapter::Elm327,
+            ..bus("obd", "/dev/ttyUSB0", None)
+        };
+        assert_eq!(
+            can_protocol(&[elm]).as_deref(),
+            Some("/dev/ttyUSB0 via ELM327")
+        );
+        assert_eq!(can_protocol(&[]), None);
+    }
+
+    #[test]
+    fn read_vin_is_remembered() {
+        let store = DeviceContextStore::from_config(&config(""));
+        assert!(store.snapshot().vehicle.vin.is_none());
+
+        assert!(store.remember_vin("1m8gdm9axkp042788"));
+        assert!(!store.remember_vin("1M8GDM9AXKP042788"));
+        let vehicle = store.snapshot().vehicle;
+        assert_eq!(vehicle.vin.as_deref(), Some("1M8GDM9AXKP042788"));
+        assert_eq!(vehicle.model_year, Some(1989));
+    }
+
+    #[test]
+    fn configured_values_win_and_trucks_are_diesel() {
+        let store = DeviceContextStore::from_config(&config(
+            "[vehicle]\nmake = \"Mack\"\nmodel_year = 2021\n",
+        ));
+        store.remember_vin("1HGCM82633A004352");
+        let vehicle = store.snapshot().vehicle;
+        assert_eq!(vehicle.make.as_deref(), Some("Mack"));
+        assert_eq!(vehicle.model_year, Some(2021));
+        assert_eq!(vehicle.fuel_type, Some(FuelType::Diesel));
+
+        assert!(store.remember_supported_pids(vec![0x0D, 0x0C, 0x20]));
+        assert!(!store.remember_supported_pids(vec![0x0C, 0x0D, 0x20]));
+        store.set_tools(vec!["read_dtcs".into()]);
+        let section = store.snapshot().prompt_section().unwrap();
+        assert!(section.contains("2021 Mack"));
+        assert!(section.contains("read_dtcs"));
+        assert!(section.contains("Supported PIDs: 0x0C, 0x0D, 0x20"));
+    }
+}
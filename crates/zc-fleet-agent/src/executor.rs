@@ -1,34 +1,79 @@
 //! Command executor — dispatches command envelopes to the right action.
 //!
 //! Bridges between the MQTT command protocol (CommandEnvelope) and:
-//! - Tool registry (CAN bus + log tools) for `ActionKind::Tool`
+//! - Tool registry (CAN bus + log tools) for `ActionKind::Tool`, through
+//!   the optional result cache
+//! - Local history journal for the `get_local_history` tool
+//! - Provisioning checks for the `self_test` tool
+//! - Continuous PID watches for the `watch_pid` tool
+//! - The registry's execution statistics for the `agent_stats` tool
 //! - Shell executor for `ActionKind::Shell`
 //! - Direct reply for `ActionKind::Reply`
+//! - Multi-step plans (`ParsedIntent::steps`), each step run as a tool
+//!
+//! The executor is shared by concurrently running commands. Commands that
+//! use a CAN tool take turns on its bus (each of the [`CanBuses`] has one
+//! lock), since interleaved OBD-II requests would mix up each other's
+//! ISO-TP responses; tools on other buses, log tools and shell commands run
+//! alongside.
 
 use chrono::Utc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
-use zc_canbus_tools::CanInterface;
+use zc_canbus_tools::{CanInterface, ChunkSink};
 use zc_log_tools::LogSource;
+use zc_protocol::catalog::{ToolCatalog, ToolSpec};
 use zc_protocol::commands::{
-    ActionKind, CommandEnvelope, CommandResponse, CommandStatus, InferenceTier, ParsedIntent,
+    ActionKind, CommandEnvelope, CommandResponse, CommandStatus, ErrorCode, InferenceTier,
+    ParsedIntent, SHELL_COMMAND_KEY,
 };
+use zc_protocol::plan::{self, PlanStep, PlanVars};
+use zc_protocol::self_test::SelfTestTrigger;
 
+use crate::can_bus::{self, CanBuses};
+use crate::command_timeout::CommandTimeoutConfig;
+use crate::device_context::{self, DeviceContextStore};
+use crate::history::{self, HistoryQuery, LocalHistory};
 use crate::inference::{OllamaClient, sanitize_shell_command};
+use crate::pid_watch::{self, PidWatches};
 use crate::registry::{ToolKind, ToolRegistry};
+use crate::result_cache::ResultCache;
+use crate::self_test::{self, SelfTest};
 use crate::shell;
+use crate::tool_stats;
+
+/// Outcome of one run of a plan step.
+struct StepRun {
+    /// Arguments after template rendering.
+    args: serde_json::Value,
+    /// The tool's result (`response_data`).
+    output: serde_json::Value,
+    summary: Option<String>,
+    error: Option<String>,
+    cached: bool,
+    latency_ms: u64,
+}
 
 /// Executes commands by dispatching to the appropriate action handler.
 ///
 /// Generic over CAN interface and log source for testability.
 pub struct CommandExecutor<'a> {
     registry: &'a ToolRegistry,
-    can_interface: &'a dyn CanInterface,
+    can_buses: CanBuses<'a>,
     log_source: &'a dyn LogSource,
     ollama: Option<&'a OllamaClient>,
+    history: Option<&'a LocalHistory>,
+    self_test: Option<&'a SelfTest>,
+    pid_watches: Option<&'a PidWatches>,
+    device_context: Option<&'a DeviceContextStore>,
+    result_cache: Option<&'a ResultCache>,
+    structured_only: bool,
+    timeouts: CommandTimeoutConfig,
 }
 
 impl<'a> CommandExecutor<'a> {
+    /// An executor running CAN tools on `can_interface` as the only bus.
     pub fn new(
         registry: &'a ToolRegistry,
         can_interface: &'a dyn CanInterface,
@@ -37,9 +82,140 @@ impl<'a> CommandExecutor<'a> {
     ) -> Self {
         Self {
             registry,
-            can_interface,
+            can_buses: CanBuses::single(can_interface),
             log_source,
             ollama,
+            history: None,
+            self_test: None,
+            pid_watches: None,
+            device_context: None,
+            result_cache: None,
+            structured_only: false,
+            timeouts: CommandTimeoutConfig::default(),
+        }
+    }
+
+    /// Run CAN tools on these buses, chosen by their `bus` argument.
+    pub fn with_can_buses(mut self, buses: CanBuses<'a>) -> Self {
+        self.can_buses = buses;
+        self
+    }
+
+    /// Answer `get_local_history` from this journal.
+    pub fn with_history(mut self, history: &'a LocalHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Answer `self_test` by running these checks.
+    pub fn with_self_test(mut self, self_test: &'a SelfTest) -> Self {
+        self.self_test = Some(self_test);
+        self
+    }
+
+    /// Answer `watch_pid` by starting and stopping watches here.
+    pub fn with_pid_watches(mut self, watches: &'a PidWatches) -> Self {
+        self.pid_watches = Some(watches);
+        self
+    }
+
+    /// Remember the VIN from successful `read_vin` results, and the PIDs
+    /// from `list_supported_pids`, here.
+    pub fn with_device_context(mut self, context: &'a DeviceContextStore) -> Self {
+        self.device_context = Some(context);
+        self
+    }
+
+    /// Reuse recent results of identical tool calls from this cache.
+    pub fn with_result_cache(mut self, cache: &'a ResultCache) -> Self {
+        self.result_cache = Some(cache);
+        self
+    }
+
+    /// Cap envelope timeouts with these limits instead of the defaults.
+    pub fn with_timeouts(mut self, timeouts: CommandTimeoutConfig) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Reject envelopes without a `parsed_intent` instead of running local
+    /// inference on them.
+    pub fn structured_only(mut self) -> Self {
+        self.structured_only = true;
+        self
+    }
+
+    pub fn device_context(&self) -> Option<&'a DeviceContextStore> {
+        self.device_context
+    }
+
+    /// Execution statistics of the registry's tools.
+    pub fn tool_stats(&self) -> &'a tool_stats::ToolStats {
+        self.registry.stats()
+    }
+
+    /// Every tool name this executor answers: the registry, the built-in
+    /// `agent_stats`, plus `get_local_history` / `self_test` / `watch_pid`
+    /// when configured.
+    pub fn tool_names(&self) -> Vec<String> {
+        let mut names = self.registry.tool_names();
+        names.push(tool_stats::TOOL_NAME.to_string());
+        if self.history.is_some() {
+            names.push(history::TOOL_NAME.to_string());
+        }
+        if self.self_test.is_some() {
+            names.push(self_test::TOOL_NAME.to_string());
+        }
+        if self.pid_watches.is_some() {
+            names.push(pid_watch::TOOL_NAME.to_string());
+        }
+        names
+    }
+
+    /// The catalog advertised to the cloud: every tool of
+    /// [`CommandExecutor::tool_names`] with its description and schema.
+    ///
+    /// With more than one bus, CAN tools advertise the `bus` argument.
+    pub fn tool_catalog(&self) -> ToolCatalog {
+        let mut tools: Vec<ToolSpec> = self
+            .registry
+            .list_tools()
+            .into_iter()
+            .map(|mut t| {
+                if t.kind == ToolKind::CanBus && self.can_buses.len() > 1 {
+                    self.add_bus_arg(&mut t.schema);
+                }
+                ToolSpec::new(t.name, t.description, t.schema)
+            })
+            .collect();
+        tools.push(tool_stats::tool_spec());
+        if self.history.is_some() {
+            tools.push(history::tool_spec());
+        }
+        if self.self_test.is_some() {
+            tools.push(self_test::tool_spec());
+        }
+        if self.pid_watches.is_some() {
+            tools.push(pid_watch::tool_spec());
+        }
+        ToolCatalog::new(tools)
+    }
+
+    fn add_bus_arg(&self, schema: &mut serde_json::Value) {
+        let names = self.can_buses.names();
+        let description = format!(
+            "CAN bus to use (default: {})",
+            self.can_buses.default_bus().name
+        );
+        if let Some(properties) = schema["properties"].as_object_mut() {
+            properties.insert(
+                can_bus::BUS_ARG.to_string(),
+                serde_json::json!({
+                    "type": "string",
+                    "enum": names,
+                    "description": description,
+                }),
+            );
         }
     }
 
@@ -47,16 +223,53 @@ impl<'a> CommandExecutor<'a> {
     ///
     /// If `parsed_intent` is present (cloud pre-parsed), uses it directly.
     /// Otherwise attempts local inference via Ollama, falling back to an
-    /// error if no match is found.
+    /// error if no match is found. With [`CommandExecutor::structured_only`]
+    /// such envelopes are rejected outright.
     pub async fn execute(&self, envelope: &CommandEnvelope) -> CommandResponse {
+        self.run(envelope, None).await
+    }
+
+    /// Execute a command, passing partial results from streaming tools
+    /// (`can_monitor`, `tail_logs` with `follow`) to `sink` as they arrive.
+    ///
+    /// The final response reports the number of chunks emitted in
+    /// `response_data.streamed_chunks`. Non-streaming actions behave
+    /// exactly like [`CommandExecutor::execute`].
+    pub async fn execute_streaming(
+        &self,
+        envelope: &CommandEnvelope,
+        sink: &ChunkSink<'_>,
+    ) -> CommandResponse {
+        self.run(envelope, Some(sink)).await
+    }
+
+    async fn run(
+        &self,
+        envelope: &CommandEnvelope,
+        sink: Option<&ChunkSink<'_>>,
+    ) -> CommandResponse {
         let start = Instant::now();
 
         // Fast path: intent already parsed by cloud
         let (intent, tier) = if let Some(ref intent) = envelope.parsed_intent {
             (intent.clone(), InferenceTier::Local)
+        } else if self.structured_only {
+            tracing::warn!(
+                command_id = %envelope.id,
+                "rejecting command without parsed_intent (structured commands only)"
+            );
+            return self.error_response(
+                envelope,
+                start,
+                ErrorCode::StructuredOnly,
+                "structured commands only: envelope has no parsed_intent",
+            );
         } else if let Some(ollama) = self.ollama {
             // Local inference via Ollama
-            match ollama.parse(&envelope.natural_language).await {
+            match ollama
+                .parse_with_history(&envelope.natural_language, &envelope.history)
+                .await
+            {
                 Some(parsed) => {
                     tracing::info!(
                         action = ?parsed.action,
@@ -70,6 +283,7 @@ impl<'a> CommandExecutor<'a> {
                     return self.error_response(
                         envelope,
                         start,
+                        ErrorCode::InferenceNoMatch,
                         "no match for command — local inference returned no result",
                     );
                 }
@@ -78,15 +292,63 @@ impl<'a> CommandExecutor<'a> {
             return self.error_response(
                 envelope,
                 start,
+                ErrorCode::InferenceUnavailable,
                 "no parsed_intent and local inference not available",
             );
         };
 
-        // Route based on action kind
-        match intent.action {
-            ActionKind::Tool => self.execute_tool(envelope, &intent, tier, start).await,
-            ActionKind::Shell => self.execute_shell(envelope, &intent, tier, start).await,
-            ActionKind::Reply => self.execute_reply(envelope, &intent, tier, start),
+        // Waiting for the bus does not count against the command's timeout.
+        let _buses = self.can_buses.lock(self.can_calls(&intent)).await;
+
+        // Only a single tool has its own limit; plans and shell commands
+        // get the envelope's.
+        let tool = (intent.action == ActionKind::Tool && intent.steps.is_empty())
+            .then_some(intent.tool_name.as_str());
+        let limit = self.timeouts.limit(envelope.timeout_secs, tool);
+        let action = async {
+            if !intent.steps.is_empty() {
+                return self.execute_plan(envelope, &intent, tier, start).await;
+            }
+            // Route based on action kind
+            match intent.action {
+                ActionKind::Tool => {
+                    self.execute_tool(envelope, &intent, tier, start, sink)
+                        .await
+                }
+                ActionKind::Shell => self.execute_shell(envelope, &intent, tier, start).await,
+                ActionKind::Reply => self.execute_reply(envelope, &intent, tier, start),
+            }
+        };
+        match tokio::time::timeout(limit, action).await {
+            Ok(response) => response,
+            Err(_) => {
+                let secs = self.timeouts.limit_secs(envelope.timeout_secs, tool);
+                tracing::warn!(
+                    command_id = %envelope.id,
+                    tool = %intent.tool_name,
+                    timeout_secs = secs,
+                    "command timed out"
+                );
+                self.timeout_response(envelope, tier, start, secs)
+            }
+        }
+    }
+
+    /// Arguments of the CAN tools `intent` runs, itself or in its plan steps.
+    fn can_calls<'i>(&self, intent: &'i ParsedIntent) -> Vec<&'i serde_json::Value> {
+        let is_can = |name: &str| matches!(self.registry.lookup(name), Some((ToolKind::CanBus, _)));
+        if !intent.steps.is_empty() {
+            return intent
+                .steps
+                .iter()
+                .filter(|step| is_can(&step.tool_name))
+                .map(|step| &step.tool_args)
+                .collect();
+        }
+        if intent.action == ActionKind::Tool && is_can(&intent.tool_name) {
+            vec![&intent.tool_args]
+        } else {
+            Vec::new()
         }
     }
 
@@ -97,34 +359,143 @@ impl<'a> CommandExecutor<'a> {
         intent: &ParsedIntent,
         tier: InferenceTier,
         start: Instant,
+        sink: Option<&ChunkSink<'_>>,
     ) -> CommandResponse {
         let tool_name = &intent.tool_name;
+        if tool_name == history::TOOL_NAME {
+            return self.execute_history(envelope, intent, tier, start);
+        }
+        if tool_name == self_test::TOOL_NAME {
+            return self.execute_self_test(envelope, tier, start).await;
+        }
+        if tool_name == pid_watch::TOOL_NAME {
+            return self.execute_watch_pid(envelope, intent, tier, start);
+        }
+        if tool_name == tool_stats::TOOL_NAME {
+            return self.execute_agent_stats(envelope, intent, tier, start);
+        }
         let Some((kind, idx)) = self.registry.lookup(tool_name) else {
-            return self.error_response(envelope, start, &format!("unknown tool: {tool_name}"));
+            return self.error_response(
+                envelope,
+                start,
+                ErrorCode::UnknownTool,
+                &format!("unknown tool: {tool_name}"),
+            );
+        };
+        let bus = match kind {
+            ToolKind::CanBus => match self.can_buses.select(&intent.tool_args) {
+                Ok(bus) if bus.protocol.allows(tool_name) => Some(bus),
+                Ok(bus) => {
+                    return self.error_response(
+                        envelope,
+                        start,
+                        ErrorCode::InvalidArgs,
+                        &format!(
+                            "{tool_name} cannot run on bus '{}' ({} protocol)",
+                            bus.name,
+                            bus.protocol.as_str()
+                        ),
+                    );
+                }
+                Err(err) => {
+                    return self.error_response(envelope, start, ErrorCode::InvalidArgs, &err);
+                }
+            },
+            ToolKind::Log => None,
         };
 
-        let result = match kind {
-            ToolKind::CanBus => {
+        let sink = sink.filter(|_| self.registry.supports_streaming(kind, idx));
+        // Streaming runs exist to show live data, so they bypass the cache.
+        let cache = self.result_cache.filter(|_| sink.is_none());
+        if let Some(hit) = cache.and_then(|c| c.get(tool_name, &intent.tool_args)) {
+            tracing::debug!(
+                tool = %tool_name,
+                age_ms = hit.age.as_millis() as u64,
+                "serving tool result from cache"
+            );
+            return CommandResponse {
+                command_id: envelope.id,
+                correlation_id: envelope.correlation_id,
+                device_id: envelope.device_id.clone(),
+                status: CommandStatus::Completed,
+                inference_tier: tier,
+                response_text: hit.response_text,
+                response_data: hit.response_data,
+                latency_ms: start.elapsed().as_millis() as u64,
+                responded_at: Utc::now(),
+                error: None,
+                error_code: None,
+                cached: true,
+            };
+        }
+        let chunks = AtomicUsize::new(0);
+        let counting_sink = |data: serde_json::Value| {
+            chunks.fetch_add(1, Ordering::Relaxed);
+            if let Some(sink) = sink {
+                sink(data);
+            }
+        };
+
+        let args = intent.tool_args.clone();
+        let result = match (bus, sink) {
+            (Some(bus), None) => self.registry.execute_can(idx, args, bus.interface).await,
+            (Some(bus), Some(_)) => {
                 self.registry
-                    .execute_can(idx, intent.tool_args.clone(), self.can_interface)
+                    .execute_can_streaming(idx, args, bus.interface, &counting_sink)
                     .await
             }
-            ToolKind::Log => {
+            (None, None) => self.registry.execute_log(idx, args, self.log_source).await,
+            (None, Some(_)) => {
                 self.registry
-                    .execute_log(idx, intent.tool_args.clone(), self.log_source)
+                    .execute_log_streaming(idx, args, self.log_source, &counting_sink)
                     .await
             }
         };
+        let streamed_chunks = chunks.load(Ordering::Relaxed);
+        let result = result.map(|mut data| {
+            if streamed_chunks > 0 {
+                data["streamed_chunks"] = serde_json::json!(streamed_chunks);
+            }
+            if let Some(bus) = bus.filter(|_| self.can_buses.len() > 1) {
+                data[can_bus::BUS_ARG] = serde_json::json!(bus.name);
+            }
+            data
+        });
 
         let latency_ms = start.elapsed().as_millis() as u64;
 
         match result {
             Ok(data) => {
+                if tool_name == device_context::READ_VIN_TOOL
+                    && let Some(context) = self.device_context
+                    && let Some(vin) = data["data"]["vin"].as_str()
+                {
+                    context.remember_vin(vin);
+                }
+                if tool_name == device_context::LIST_SUPPORTED_PIDS_TOOL
+                    && let Some(context) = self.device_context
+                    && let Some(pids) = data["data"]["pids"].as_array()
+                {
+                    let pids = pids
+                        .iter()
+                        .filter_map(|p| p.as_u64())
+                        .filter_map(|p| u8::try_from(p).ok())
+                        .collect();
+                    context.remember_supported_pids(pids);
+                }
                 // Prefer the tool's summary (e.g. "Found 5 matches …") over a generic message
                 let summary = data["summary"]
                     .as_str()
                     .map(|s| s.to_string())
                     .unwrap_or_else(|| format!("Tool '{tool_name}' executed successfully"));
+                if let Some(cache) = cache {
+                    cache.put(
+                        tool_name,
+                        &intent.tool_args,
+                        Some(summary.clone()),
+                        Some(data.clone()),
+                    );
+                }
                 CommandResponse {
                     command_id: envelope.id,
                     correlation_id: envelope.correlation_id,
@@ -136,6 +507,8 @@ impl<'a> CommandExecutor<'a> {
                     latency_ms,
                     responded_at: Utc::now(),
                     error: None,
+                    error_code: None,
+                    cached: false,
                 }
             }
             Err(err) => CommandResponse {
@@ -148,8 +521,188 @@ impl<'a> CommandExecutor<'a> {
                 response_data: None,
                 latency_ms,
                 responded_at: Utc::now(),
-                error: Some(err),
+                error: Some(err.message),
+                error_code: Some(err.code),
+                cached: false,
+            },
+        }
+    }
+
+    /// Execute a multi-step plan: each step runs as a tool, in order, with
+    /// its arguments rendered from earlier outputs. The first failure stops
+    /// the plan; later steps are reported as skipped.
+    ///
+    /// `response_data.steps` holds one record per run (a `for_each` step
+    /// runs once per item) with its status, rendered arguments and result.
+    async fn execute_plan(
+        &self,
+        envelope: &CommandEnvelope,
+        intent: &ParsedIntent,
+        tier: InferenceTier,
+        start: Instant,
+    ) -> CommandResponse {
+        if let Err(err) = plan::validate(&intent.steps) {
+            return self.error_response(envelope, start, ErrorCode::PlanFailed, &err);
+        }
+
+        let mut vars = PlanVars::default();
+        let mut records = Vec::new();
+        let mut summaries = Vec::new();
+        let mut failure: Option<String> = None;
+        for (index, step) in intent.steps.iter().enumerate() {
+            if failure.is_some() {
+                records.push(serde_json::json!({
+                    "step": index,
+                    "tool_name": step.tool_name,
+                    "status": "skipped",
+                }));
+                continue;
+            }
+            let items = match &step.for_each {
+                Some(path) => vars
+                    .items(path)
+                    .map(|items| items.into_iter().map(Some).collect()),
+                None => Ok(vec![None]),
+            };
+            let items: Vec<Option<serde_json::Value>> = match items {
+                Ok(items) => items,
+                Err(err) => {
+                    records.push(serde_json::json!({
+                        "step": index,
+                        "tool_name": step.tool_name,
+                        "status": "failed",
+                        "error": err,
+                    }));
+                    failure = Some(format!("step {index} ({}): {err}", step.tool_name));
+                    continue;
+                }
+            };
+
+            let mut outputs = Vec::new();
+            for (i, item) in items.into_iter().enumerate() {
+                let bound;
+                let scope = match item {
+                    Some(item) => {
+                        bound = vars.with_item(item);
+                        &bound
+                    }
+                    None => &vars,
+                };
+                let run = self.run_step(envelope, step, scope, tier).await;
+                let mut record = serde_json::json!({
+                    "step": index,
+                    "tool_name": step.tool_name,
+                    "tool_args": run.args,
+                    "status": if run.error.is_none() { "completed" } else { "failed" },
+                    "latency_ms": run.latency_ms,
+                    "result": run.output,
+                });
+                if step.for_each.is_some() {
+                    record["item"] = serde_json::json!(i);
+                }
+                if let Some(summary) = &run.summary {
+                    record["summary"] = serde_json::json!(summary);
+                    summaries.push(format!("{}: {summary}", step.tool_name));
+                }
+                if run.cached {
+                    record["cached"] = serde_json::json!(true);
+                }
+                if let Some(err) = run.error {
+                    record["error"] = serde_json::json!(err);
+                    failure = Some(format!("step {index} ({}): {err}", step.tool_name));
+                }
+                records.push(record);
+                outputs.push(run.output);
+                if failure.is_some() {
+                    break;
+                }
+            }
+            vars.push_output(match step.for_each {
+                Some(_) => serde_json::Value::Array(outputs),
+                None => outputs.pop().unwrap_or_default(),
+            });
+        }
+
+        let completed = records
+            .iter()
+            .filter(|r| r["status"] == "completed")
+            .count();
+        CommandResponse {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: envelope.device_id.clone(),
+            status: if failure.is_none() {
+                CommandStatus::Completed
+            } else {
+                CommandStatus::Failed
             },
+            inference_tier: tier,
+            response_text: Some(if summaries.is_empty() {
+                format!("{completed} plan step(s) completed")
+            } else {
+                summaries.join("\n")
+            }),
+            response_data: Some(serde_json::json!({
+                "steps": records,
+                "completed": completed,
+            })),
+            latency_ms: start.elapsed().as_millis() as u64,
+            responded_at: Utc::now(),
+            error_code: failure.as_ref().map(|_| ErrorCode::PlanFailed),
+            error: failure,
+            cached: false,
+        }
+    }
+
+    /// Run one step of a plan (one item of a `for_each` step) as a tool.
+    async fn run_step(
+        &self,
+        envelope: &CommandEnvelope,
+        step: &PlanStep,
+        vars: &PlanVars,
+        tier: InferenceTier,
+    ) -> StepRun {
+        let start = Instant::now();
+        let args = match vars.render(&step.tool_args) {
+            Ok(args) => args,
+            Err(err) => {
+                return StepRun {
+                    args: step.tool_args.clone(),
+                    output: serde_json::Value::Null,
+                    summary: None,
+                    error: Some(err),
+                    cached: false,
+                    latency_ms: 0,
+                };
+            }
+        };
+        let intent = ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: step.tool_name.clone(),
+            tool_args: args.clone(),
+            confidence: 1.0,
+            steps: Vec::new(),
+        };
+        let response = self
+            .execute_tool(envelope, &intent, tier, start, None)
+            .await;
+        let output = response.response_data.unwrap_or_default();
+        // Tools report their own failures in the result; they stop a plan too.
+        let error = response.error.or_else(|| {
+            (output["success"] == false).then(|| {
+                output["error"]
+                    .as_str()
+                    .unwrap_or("tool reported failure")
+                    .to_string()
+            })
+        });
+        StepRun {
+            args,
+            summary: response.response_text,
+            output,
+            error,
+            cached: response.cached,
+            latency_ms: start.elapsed().as_millis() as u64,
         }
     }
 
@@ -180,6 +733,8 @@ impl<'a> CommandExecutor<'a> {
                 latency_ms,
                 responded_at: Utc::now(),
                 error: Some("shell: command was empty after sanitization".into()),
+                error_code: Some(ErrorCode::ShellBlocked),
+                cached: false,
             };
         }
         if command_str != intent.tool_name {
@@ -216,10 +771,15 @@ impl<'a> CommandExecutor<'a> {
                     status: CommandStatus::Completed,
                     inference_tier: tier,
                     response_text: Some(output),
-                    response_data: None,
+                    response_data: Some(serde_json::json!({
+                        SHELL_COMMAND_KEY: command_str,
+                        "exit_code": result.exit_code,
+                    })),
                     latency_ms,
                     responded_at: Utc::now(),
                     error: None,
+                    error_code: None,
+                    cached: false,
                 }
             }
             Err(e) => {
@@ -231,10 +791,12 @@ impl<'a> CommandExecutor<'a> {
                     status: CommandStatus::Failed,
                     inference_tier: tier,
                     response_text: None,
-                    response_data: None,
+                    response_data: Some(serde_json::json!({ SHELL_COMMAND_KEY: command_str })),
                     latency_ms,
                     responded_at: Utc::now(),
+                    error_code: Some(shell_error_code(&e)),
                     error: Some(format!("shell: {e}")),
+                    cached: false,
                 }
             }
         }
@@ -266,6 +828,197 @@ impl<'a> CommandExecutor<'a> {
             latency_ms: start.elapsed().as_millis() as u64,
             responded_at: Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
+        }
+    }
+
+    /// Query the local journal (`get_local_history`).
+    fn execute_history(
+        &self,
+        envelope: &CommandEnvelope,
+        intent: &ParsedIntent,
+        tier: InferenceTier,
+        start: Instant,
+    ) -> CommandResponse {
+        let Some(journal) = self.history else {
+            return self.error_response(
+                envelope,
+                start,
+                ErrorCode::UnknownTool,
+                "local history is disabled on this device",
+            );
+        };
+        let query = match HistoryQuery::from_args(&intent.tool_args) {
+            Ok(q) => q,
+            Err(e) => return self.error_response(envelope, start, ErrorCode::InvalidArgs, &e),
+        };
+
+        let data = journal.query(&query);
+        let summary = format!(
+            "{} of {} matching local history entries ({} unpublished)",
+            data["returned"], data["matched"], data["unpublished"]
+        );
+        CommandResponse {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: envelope.device_id.clone(),
+            status: CommandStatus::Completed,
+            inference_tier: tier,
+            response_text: Some(summary.clone()),
+            response_data: Some(serde_json::json!({
+                "tool_name": history::TOOL_NAME,
+                "success": true,
+                "data": data,
+                "summary": summary,
+            })),
+            latency_ms: start.elapsed().as_millis() as u64,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        }
+    }
+
+    /// Start, stop or list PID watches (`watch_pid`). Returns at once; the
+    /// watch itself runs in [`pid_watch::run`].
+    fn execute_watch_pid(
+        &self,
+        envelope: &CommandEnvelope,
+        intent: &ParsedIntent,
+        tier: InferenceTier,
+        start: Instant,
+    ) -> CommandResponse {
+        let Some(watches) = self.pid_watches else {
+            return self.error_response(
+                envelope,
+                start,
+                ErrorCode::UnknownTool,
+                "PID watches are not available on this device",
+            );
+        };
+        let (summary, data) = match watches.execute(&intent.tool_args) {
+            Ok(done) => done,
+            Err(e) => return self.error_response(envelope, start, ErrorCode::InvalidArgs, &e),
+        };
+        CommandResponse {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: envelope.device_id.clone(),
+            status: CommandStatus::Completed,
+            inference_tier: tier,
+            response_text: Some(summary.clone()),
+            response_data: Some(serde_json::json!({
+                "tool_name": pid_watch::TOOL_NAME,
+                "success": true,
+                "data": data,
+                "summary": summary,
+            })),
+            latency_ms: start.elapsed().as_millis() as u64,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        }
+    }
+
+    /// Report the registry's execution statistics (`agent_stats`).
+    fn execute_agent_stats(
+        &self,
+        envelope: &CommandEnvelope,
+        intent: &ParsedIntent,
+        tier: InferenceTier,
+        start: Instant,
+    ) -> CommandResponse {
+        let (summary, data) = self.registry.stats().execute(&intent.tool_args);
+        CommandResponse {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: envelope.device_id.clone(),
+            status: CommandStatus::Completed,
+            inference_tier: tier,
+            response_text: Some(summary.clone()),
+            response_data: Some(serde_json::json!({
+                "tool_name": tool_stats::TOOL_NAME,
+                "success": true,
+                "data": data,
+                "summary": summary,
+            })),
+            latency_ms: start.elapsed().as_millis() as u64,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        }
+    }
+
+    /// Run the provisioning checks (`self_test`).
+    ///
+    /// Completes even when checks fail; `data.passed` carries the verdict.
+    async fn execute_self_test(
+        &self,
+        envelope: &CommandEnvelope,
+        tier: InferenceTier,
+        start: Instant,
+    ) -> CommandResponse {
+        let Some(runner) = self.self_test else {
+            return self.error_response(
+                envelope,
+                start,
+                ErrorCode::UnknownTool,
+                "self-test is not available on this device",
+            );
+        };
+
+        let report = runner.run(SelfTestTrigger::Command).await;
+        let failed = report.failed_checks();
+        let summary = if failed.is_empty() {
+            format!("Self-test passed ({} checks)", report.checks.len())
+        } else {
+            format!("Self-test failed: {}", failed.join(", "))
+        };
+        CommandResponse {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: envelope.device_id.clone(),
+            status: CommandStatus::Completed,
+            inference_tier: tier,
+            response_text: Some(summary.clone()),
+            response_data: Some(serde_json::json!({
+                "tool_name": self_test::TOOL_NAME,
+                "success": report.passed,
+                "data": report,
+                "summary": summary,
+            })),
+            latency_ms: start.elapsed().as_millis() as u64,
+            responded_at: Utc::now(),
+            error: None,
+            error_code: None,
+            cached: false,
+        }
+    }
+
+    /// Final response for an action stopped by its timeout.
+    fn timeout_response(
+        &self,
+        envelope: &CommandEnvelope,
+        tier: InferenceTier,
+        start: Instant,
+        secs: u64,
+    ) -> CommandResponse {
+        CommandResponse {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: envelope.device_id.clone(),
+            status: CommandStatus::Timeout,
+            inference_tier: tier,
+            response_text: None,
+            response_data: None,
+            latency_ms: start.elapsed().as_millis() as u64,
+            responded_at: Utc::now(),
+            error: Some(format!("command timed out after {secs}s")),
+            error_code: Some(ErrorCode::Timeout),
+            cached: false,
         }
     }
 
@@ -273,6 +1026,7 @@ impl<'a> CommandExecutor<'a> {
         &self,
         envelope: &CommandEnvelope,
         start: Instant,
+        code: ErrorCode,
         message: &str,
     ) -> CommandResponse {
         CommandResponse {
@@ -286,10 +1040,22 @@ impl<'a> CommandExecutor<'a> {
             latency_ms: start.elapsed().as_millis() as u64,
             responded_at: Utc::now(),
             error: Some(message.to_string()),
+            error_code: Some(code),
+            cached: false,
         }
     }
 }
 
+/// Failure class of a shell error: validation rejections are `ShellBlocked`,
+/// everything after the command was allowed to run is `ShellFailed`.
+fn shell_error_code(err: &shell::ShellError) -> ErrorCode {
+    match err {
+        shell::ShellError::Timeout(_) => ErrorCode::Timeout,
+        shell::ShellError::Exec(_) => ErrorCode::ShellFailed,
+        _ => ErrorCode::ShellBlocked,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -300,35 +1066,121 @@ mod tests {
     use zc_log_tools::MockLogSource;
     use zc_protocol::commands::ParsedIntent;
 
-    use crate::inference::OllamaConfig;
+    use crate::inference::OllamaConfig;
+
+    /// Helper: build executor without Ollama (backward-compat path).
+    fn make_executor<'a>(
+        registry: &'a ToolRegistry,
+        can: &'a MockCanInterface,
+        logs: &'a MockLogSource,
+    ) -> CommandExecutor<'a> {
+        CommandExecutor::new(registry, can, logs, None)
+    }
+
+    // ── Tool action tests (existing) ─────────────────────────────
+
+    #[tokio::test]
+    async fn execute_without_intent_no_ollama_fails() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let executor = make_executor(&registry, &can, &logs);
+
+        let cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let resp = executor.execute(&cmd).await;
+
+        assert_eq!(resp.status, CommandStatus::Failed);
+        assert!(
+            resp.error
+                .unwrap()
+                .contains("local inference not available")
+        );
+    }
+
+    // ── Plan tests ───────────────────────────────────────────────
+
+    fn plan_intent(steps: Vec<PlanStep>) -> ParsedIntent {
+        ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: steps[0].tool_name.clone(),
+            tool_args: steps[0].tool_args.clone(),
+            confidence: 0.9,
+            steps,
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_plan_chains_outputs_into_later_steps() {
+        let registry = ToolRegistry::with_defaults();
+        // Mode 0x03 response with P0300 and P0171; freeze frame PIDs time out.
+        let can = MockCanInterface::with_responses(vec![zc_canbus_tools::CanFrame::new(
+            0x7E8,
+            vec![0x06, 0x43, 0x02, 0x03, 0x00, 0x01, 0x71, 0x00],
+        )]);
+        let logs = MockLogSource::new();
+        let executor = make_executor(&registry, &can, &logs);
+
+        let mut cmd = CommandEnvelope::new(
+            "fleet-alpha",
+            "rpi-001",
+            "read DTCs and the freeze frame for each",
+            "admin",
+        );
+        cmd.parsed_intent = Some(plan_intent(vec![
+            PlanStep::new("read_dtcs", json!({})),
+            PlanStep::new(
+                "read_freeze",
+                json!({"dtc": "{{item.code}}", "timeout_ms": 10}),
+            )
+            .for_each("steps.0.data"),
+        ]));
+        let resp = executor.execute(&cmd).await;
 
-    /// Helper: build executor without Ollama (backward-compat path).
-    fn make_executor<'a>(
-        registry: &'a ToolRegistry,
-        can: &'a MockCanInterface,
-        logs: &'a MockLogSource,
-    ) -> CommandExecutor<'a> {
-        CommandExecutor::new(registry, can, logs, None)
+        assert_eq!(resp.status, CommandStatus::Completed, "{:?}", resp.error);
+        let data = resp.response_data.unwrap();
+        assert_eq!(data["completed"], 3);
+        let steps = data["steps"].as_array().unwrap();
+        assert_eq!(steps[0]["tool_name"], "read_dtcs");
+        assert!(steps[0]["summary"].as_str().unwrap().contains("P0300"));
+        assert_eq!(
+            (&steps[1]["tool_args"]["dtc"], &steps[1]["item"]),
+            (&json!("P0300"), &json!(0))
+        );
+        assert_eq!(steps[2]["tool_args"]["dtc"], "P0171");
+        assert_eq!(steps[2]["status"], "completed");
+        assert!(
+            resp.response_text
+                .unwrap()
+                .starts_with("read_dtcs: Found 2")
+        );
     }
 
-    // ── Tool action tests (existing) ─────────────────────────────
-
     #[tokio::test]
-    async fn execute_without_intent_no_ollama_fails() {
+    async fn execute_plan_stops_at_first_failure() {
         let registry = ToolRegistry::with_defaults();
         let can = MockCanInterface::new();
         let logs = MockLogSource::with_syslog_sample();
         let executor = make_executor(&registry, &can, &logs);
 
-        let cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "plan", "admin");
+        cmd.parsed_intent = Some(plan_intent(vec![
+            PlanStep::new("log_stats", json!({"path": "/var/log/syslog"})),
+            PlanStep::new("search_logs", json!({"path": "{{steps.5.path}}"})),
+            PlanStep::new("log_stats", json!({"path": "/var/log/syslog"})),
+        ]));
         let resp = executor.execute(&cmd).await;
 
         assert_eq!(resp.status, CommandStatus::Failed);
-        assert!(
-            resp.error
-                .unwrap()
-                .contains("local inference not available")
-        );
+        assert_eq!(resp.error_code, Some(ErrorCode::PlanFailed));
+        assert!(resp.error.unwrap().starts_with("step 1 (search_logs)"));
+        let data = resp.response_data.unwrap();
+        let statuses: Vec<&str> = data["steps"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, ["completed", "failed", "skipped"]);
     }
 
     #[tokio::test]
@@ -344,10 +1196,12 @@ mod tests {
             tool_name: "nonexistent_tool".into(),
             tool_args: json!({}),
             confidence: 0.9,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
         assert_eq!(resp.status, CommandStatus::Failed);
+        assert_eq!(resp.error_code, Some(ErrorCode::UnknownTool));
         assert!(resp.error.unwrap().contains("unknown tool"));
     }
 
@@ -364,6 +1218,7 @@ mod tests {
             tool_name: "log_stats".into(),
             tool_args: json!({"path": "/var/log/syslog"}),
             confidence: 0.95,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
@@ -372,6 +1227,49 @@ mod tests {
         assert!(resp.latency_ms < 1000);
     }
 
+    #[tokio::test]
+    async fn repeated_tool_call_is_served_from_cache() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let cache = ResultCache::new(&crate::result_cache::ResultCacheConfig::default());
+        let executor = make_executor(&registry, &can, &logs).with_result_cache(&cache);
+
+        let command = |tool: &str, args: serde_json::Value| {
+            let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "poll", "dashboard");
+            cmd.parsed_intent = Some(ParsedIntent {
+                action: ActionKind::Tool,
+                tool_name: tool.into(),
+                tool_args: args,
+                confidence: 1.0,
+                steps: Vec::new(),
+            });
+            cmd
+        };
+        let stats = command("log_stats", json!({"path": "/var/log/syslog"}));
+
+        let first = executor.execute(&stats).await;
+        assert_eq!(first.status, CommandStatus::Completed);
+        assert!(!first.cached);
+        let second = executor.execute(&stats).await;
+        assert!(second.cached);
+        assert_eq!(second.command_id, stats.id);
+        assert_eq!(second.response_data, first.response_data);
+        assert_eq!(second.response_text, first.response_text);
+
+        // Failures and tools without a TTL are not cached.
+        let missing = command("log_stats", json!({"path": "/var/log/missing"}));
+        assert_eq!(
+            executor.execute(&missing).await.status,
+            CommandStatus::Failed
+        );
+        assert!(!executor.execute(&missing).await.cached);
+        let tail = command("tail_logs", json!({"path": "/var/log/syslog"}));
+        executor.execute(&tail).await;
+        assert!(!executor.execute(&tail).await.cached);
+        assert_eq!(cache.len(), 1);
+    }
+
     #[tokio::test]
     async fn execute_preserves_ids() {
         let registry = ToolRegistry::with_defaults();
@@ -386,6 +1284,7 @@ mod tests {
             tool_name: "search_logs".into(),
             tool_args: json!({"path": "/var/log/syslog", "query": "error"}),
             confidence: 0.88,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
@@ -394,6 +1293,365 @@ mod tests {
         assert_eq!(resp.device_id, "rpi-001");
     }
 
+    #[tokio::test]
+    async fn execute_streaming_emits_chunks_for_can_monitor() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        for i in 0..3u8 {
+            can.queue_response(zc_canbus_tools::CanFrame::new(0x7E8, vec![i]));
+        }
+        let logs = MockLogSource::new();
+        let executor = make_executor(&registry, &can, &logs);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "monitor CAN", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "can_monitor".into(),
+            tool_args: json!({"duration_secs": 5, "max_frames": 3, "chunk_frames": 2}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+
+        let chunks = std::sync::Mutex::new(Vec::new());
+        let sink = |data: serde_json::Value| chunks.lock().unwrap().push(data);
+        let resp = executor.execute_streaming(&cmd, &sink).await;
+
+        assert_eq!(resp.status, CommandStatus::Completed);
+        assert_eq!(chunks.lock().unwrap().len(), 2);
+        let data = resp.response_data.unwrap();
+        assert_eq!(data["streamed_chunks"], 2);
+        assert_eq!(data["data"]["count"], 3);
+    }
+
+    /// Log source whose reads never finish, like a file on a hung mount.
+    struct StalledLogs;
+
+    #[async_trait::async_trait]
+    impl LogSource for StalledLogs {
+        async fn read_lines(&self, _path: &str) -> zc_log_tools::LogResult<Vec<String>> {
+            std::future::pending().await
+        }
+
+        async fn tail_lines(
+            &self,
+            _path: &str,
+            _count: usize,
+        ) -> zc_log_tools::LogResult<Vec<String>> {
+            std::future::pending().await
+        }
+
+        async fn exists(&self, _path: &str) -> bool {
+            true
+        }
+
+        async fn list_sources(&self) -> zc_log_tools::LogResult<Vec<String>> {
+            Ok(vec!["/var/log/syslog".into()])
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn execute_stops_action_at_envelope_timeout() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = StalledLogs;
+        let executor = CommandExecutor::new(&registry, &can, &logs, None);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "log stats", "admin");
+        cmd.timeout_secs = 5;
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: json!({"path": "/var/log/syslog"}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        let resp = executor.execute(&cmd).await;
+
+        assert_eq!(resp.status, CommandStatus::Timeout);
+        assert_eq!(resp.error_code, Some(ErrorCode::Timeout));
+        assert!(resp.error.unwrap().contains("after 5s"));
+    }
+
+    /// CAN interface that records how many requests overlap; each send
+    /// takes 100 ms and no ECU ever answers.
+    #[derive(Default)]
+    struct SharedBus {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl CanInterface for SharedBus {
+        async fn send_frame(
+            &self,
+            _frame: &zc_canbus_tools::CanFrame,
+        ) -> zc_canbus_tools::CanResult<()> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn recv_frame(
+            &self,
+            _timeout: std::time::Duration,
+        ) -> zc_canbus_tools::CanResult<zc_canbus_tools::CanFrame> {
+            Err(zc_canbus_tools::CanError::Interface("no ECU".into()))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn can_tools_take_turns_on_the_bus() {
+        let registry = ToolRegistry::with_defaults();
+        let can = SharedBus::default();
+        let logs = MockLogSource::with_syslog_sample();
+        let executor = CommandExecutor::new(&registry, &can, &logs, None);
+        let command = |tool: &str, args: serde_json::Value| {
+            let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", tool, "admin");
+            cmd.parsed_intent = Some(ParsedIntent {
+                action: ActionKind::Tool,
+                tool_name: tool.into(),
+                tool_args: args,
+                confidence: 0.9,
+                steps: Vec::new(),
+            });
+            cmd
+        };
+        let (vin, dtcs, stats) = (
+            command("read_vin", json!({})),
+            command("read_dtcs", json!({})),
+            command("log_stats", json!({"path": "/var/log/syslog"})),
+        );
+
+        let (vin, dtcs, stats) = tokio::join!(
+            executor.execute(&vin),
+            executor.execute(&dtcs),
+            executor.execute(&stats)
+        );
+
+        // Both CAN tools reached the bus, one after the other.
+        assert_eq!(can.max_in_flight.load(Ordering::SeqCst), 1);
+        assert_eq!(vin.error_code, Some(ErrorCode::CanError));
+        assert_eq!(dtcs.error_code, Some(ErrorCode::CanError));
+        assert_eq!(stats.status, CommandStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn can_tools_run_on_the_bus_they_name() {
+        use crate::can_bus::{CanBus, CanBusProtocol};
+
+        let registry = ToolRegistry::with_defaults();
+        let (powertrain, body) = (MockCanInterface::new(), MockCanInterface::new());
+        body.queue_response(zc_canbus_tools::CanFrame::new(0x3C0, vec![0x01]));
+        let logs = MockLogSource::with_syslog_sample();
+        let executor = make_executor(&registry, &powertrain, &logs).with_can_buses(CanBuses::new(
+            vec![
+                CanBus::new("powertrain", CanBusProtocol::Obd2, &powertrain),
+                CanBus::new("body", CanBusProtocol::Raw, &body),
+            ],
+            &powertrain,
+        ));
+        let command = |tool: &str, args: serde_json::Value| {
+            let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", tool, "admin");
+            cmd.parsed_intent = Some(ParsedIntent {
+                action: ActionKind::Tool,
+                tool_name: tool.into(),
+                tool_args: args,
+                confidence: 0.9,
+                steps: Vec::new(),
+            });
+            cmd
+        };
+
+        let resp = executor
+            .execute(&command(
+                "can_monitor",
+                json!({"duration_secs": 1, "bus": "body"}),
+            ))
+            .await;
+        assert_eq!(resp.status, CommandStatus::Completed);
+        let data = resp.response_data.unwrap();
+        assert_eq!(data["bus"], "body");
+        assert_eq!(data["data"]["count"], 1);
+
+        let resp = executor
+            .execute(&command("read_dtcs", json!({"bus": "body"})))
+            .await;
+        assert_eq!(resp.error_code, Some(ErrorCode::InvalidArgs));
+        assert!(resp.error.unwrap().contains("raw protocol"));
+        assert!(powertrain.sent_frames().is_empty());
+
+        let resp = executor
+            .execute(&command("read_vin", json!({"bus": "chassis"})))
+            .await;
+        assert_eq!(resp.error_code, Some(ErrorCode::InvalidArgs));
+        assert!(resp.error.unwrap().contains("unknown bus 'chassis'"));
+
+        let catalog = executor.tool_catalog();
+        let read_pid = catalog.tools.iter().find(|t| t.name == "read_pid").unwrap();
+        assert_eq!(
+            read_pid.parameters_schema["properties"]["bus"]["enum"],
+            json!(["powertrain", "body"])
+        );
+        let log_stats = catalog
+            .tools
+            .iter()
+            .find(|t| t.name == "log_stats")
+            .unwrap();
+        assert!(log_stats.parameters_schema["properties"]["bus"].is_null());
+    }
+
+    #[tokio::test]
+    async fn execute_streaming_non_streaming_tool_has_no_chunks() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let executor = make_executor(&registry, &can, &logs);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "show log stats", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: json!({"path": "/var/log/syslog"}),
+            confidence: 0.95,
+            steps: Vec::new(),
+        });
+
+        let sink = |_: serde_json::Value| panic!("log_stats should not stream");
+        let resp = executor.execute_streaming(&cmd, &sink).await;
+
+        assert_eq!(resp.status, CommandStatus::Completed);
+        assert!(resp.response_data.unwrap().get("streamed_chunks").is_none());
+    }
+
+    #[test]
+    fn tool_names_include_configured_builtins() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let journal = LocalHistory::in_memory(100);
+
+        let names = make_executor(&registry, &can, &logs).tool_names();
+        assert_eq!(names.len(), registry.len() + 1);
+        assert!(names.iter().any(|n| n == "read_dtcs"));
+        assert!(names.iter().any(|n| n == tool_stats::TOOL_NAME));
+        assert!(!names.iter().any(|n| n == history::TOOL_NAME));
+
+        let names = make_executor(&registry, &can, &logs)
+            .with_history(&journal)
+            .tool_names();
+        assert!(names.iter().any(|n| n == history::TOOL_NAME));
+    }
+
+    #[test]
+    fn tool_catalog_matches_tool_names() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let journal = LocalHistory::in_memory(100);
+        let watches = PidWatches::new();
+        let executor = make_executor(&registry, &can, &logs)
+            .with_history(&journal)
+            .with_pid_watches(&watches);
+
+        let catalog = executor.tool_catalog();
+        let mut names = catalog.names();
+        let mut expected = executor.tool_names();
+        names.sort();
+        expected.sort();
+        assert_eq!(names, expected);
+        let read_pid = catalog.tools.iter().find(|t| t.name == "read_pid").unwrap();
+        assert!(!read_pid.description.is_empty());
+        assert!(read_pid.parameters_schema["properties"]["pid"].is_object());
+        assert!(catalog.contains(history::TOOL_NAME));
+    }
+
+    #[tokio::test]
+    async fn watch_pid_starts_a_watch_and_returns() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let watches = PidWatches::new();
+        let executor = make_executor(&registry, &can, &logs).with_pid_watches(&watches);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "watch coolant", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: pid_watch::TOOL_NAME.into(),
+            tool_args: json!({"pid": 5, "above": 110, "duration_secs": 60}),
+            confidence: 0.95,
+            steps: Vec::new(),
+        });
+        let resp = executor.execute(&cmd).await;
+        assert_eq!(resp.status, CommandStatus::Completed);
+        assert!(resp.response_text.unwrap().contains("alerting above 110"));
+        assert_eq!(watches.list().len(), 1);
+
+        cmd.parsed_intent.as_mut().unwrap().tool_args = json!({"pid": 5, "interval_ms": 1});
+        let resp = executor.execute(&cmd).await;
+        assert_eq!(resp.error_code, Some(ErrorCode::InvalidArgs));
+
+        let without = make_executor(&registry, &can, &logs).execute(&cmd).await;
+        assert_eq!(without.error_code, Some(ErrorCode::UnknownTool));
+    }
+
+    #[tokio::test]
+    async fn execute_local_history_queries_journal() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let journal = LocalHistory::in_memory(100);
+
+        let mut earlier = CommandEnvelope::new("fleet-alpha", "rpi-001", "show log stats", "admin");
+        earlier.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: json!({"path": "/var/log/syslog"}),
+            confidence: 0.95,
+            steps: Vec::new(),
+        });
+        let executor = make_executor(&registry, &can, &logs).with_history(&journal);
+        let resp = executor.execute(&earlier).await;
+        journal.record_command(&earlier, &resp, false);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "local history", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "get_local_history".into(),
+            tool_args: json!({"unpublished_only": true}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        let resp = executor.execute(&cmd).await;
+
+        assert_eq!(resp.status, CommandStatus::Completed);
+        let data = resp.response_data.unwrap();
+        assert_eq!(data["data"]["matched"], 1);
+        assert_eq!(data["data"]["entries"][0]["tool_name"], "log_stats");
+    }
+
+    #[tokio::test]
+    async fn execute_local_history_without_journal_fails() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::new();
+        let executor = make_executor(&registry, &can, &logs);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "local history", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "get_local_history".into(),
+            tool_args: json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        let resp = executor.execute(&cmd).await;
+
+        assert_eq!(resp.status, CommandStatus::Failed);
+        assert!(resp.error.unwrap().contains("disabled"));
+    }
+
     // ── Shell action tests ───────────────────────────────────────
 
     #[tokio::test]
@@ -409,6 +1667,7 @@ mod tests {
             tool_name: "hostname".into(),
             tool_args: json!({}),
             confidence: 0.9,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
@@ -430,10 +1689,12 @@ mod tests {
             tool_name: "rm -rf /".into(),
             tool_args: json!({}),
             confidence: 0.9,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
         assert_eq!(resp.status, CommandStatus::Failed);
+        assert_eq!(resp.error_code, Some(ErrorCode::ShellBlocked));
         assert!(resp.error.unwrap().contains("shell:"));
     }
 
@@ -451,6 +1712,7 @@ mod tests {
             tool_name: "ip -details link show type can".into(),
             tool_args: json!({}),
             confidence: 0.85,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
@@ -477,6 +1739,7 @@ mod tests {
             tool_name: String::new(),
             tool_args: json!({"message": "I'm operational and monitoring the fleet."}),
             confidence: 1.0,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
@@ -501,6 +1764,7 @@ mod tests {
             tool_name: String::new(),
             tool_args: json!({}),
             confidence: 1.0,
+            steps: Vec::new(),
         });
         let resp = executor.execute(&cmd).await;
 
@@ -552,6 +1816,43 @@ mod tests {
         assert!(resp.response_data.is_some());
     }
 
+    #[tokio::test]
+    async fn structured_only_skips_ollama_and_rejects_bare_text() {
+        let server = MockServer::start().await;
+        let body = ollama_response(
+            r#"{"action": "tool", "tool_name": "log_stats", "tool_args": {"path": "/var/log/syslog"}, "confidence": 0.92}"#,
+        );
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let ollama = ollama_client_for(&server);
+        let executor =
+            CommandExecutor::new(&registry, &can, &logs, Some(&ollama)).structured_only();
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "show me log stats", "admin");
+        let resp = executor.execute(&cmd).await;
+        assert_eq!(resp.status, CommandStatus::Failed);
+        assert_eq!(resp.error_code, Some(ErrorCode::StructuredOnly));
+        assert!(resp.error.unwrap().contains("structured commands only"));
+
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: json!({"path": "/var/log/syslog"}),
+            confidence: 1.0,
+            steps: Vec::new(),
+        });
+        let resp = executor.execute(&cmd).await;
+        assert_eq!(resp.status, CommandStatus::Completed);
+    }
+
     #[tokio::test]
     async fn execute_ollama_shell_inference() {
         let server = MockServer::start().await;
@@ -621,6 +1922,97 @@ mod tests {
         let resp = executor.execute(&cmd).await;
 
         assert_eq!(resp.status, CommandStatus::Failed);
+        assert_eq!(resp.error_code, Some(ErrorCode::InferenceNoMatch));
         assert!(resp.error.unwrap().contains("no match"));
     }
+
+    #[tokio::test]
+    async fn execute_self_test_returns_report() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::new();
+        let config: crate::config::AgentConfig = toml::from_str(
+            r#"
+fleet_id = "fleet-alpha"
+device_id = "rpi-001"
+
+[mqtt]
+broker_host = "127.0.0.1"
+broker_port = 1
+client_id = "rpi-001"
+
+[ollama]
+enabled = false
+"#,
+        )
+        .unwrap();
+        let runner = SelfTest::new(&config);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "run a self test", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "self_test".into(),
+            tool_args: json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+
+        let without = make_executor(&registry, &can, &logs).execute(&cmd).await;
+        assert_eq!(without.status, CommandStatus::Failed);
+
+        let executor = make_executor(&registry, &can, &logs).with_self_test(&runner);
+        let resp = executor.execute(&cmd).await;
+        assert_eq!(resp.status, CommandStatus::Completed);
+        let data = resp.response_data.unwrap();
+        assert_eq!(data["tool_name"], "self_test");
+        assert_eq!(data["data"]["trigger"], "command");
+        assert_eq!(data["data"]["checks"].as_array().unwrap().len(), 6);
+        // Nothing listens on port 1.
+        assert_eq!(data["success"], false);
+        assert!(resp.response_text.unwrap().contains("broker"));
+    }
+
+    #[tokio::test]
+    async fn agent_stats_reports_tool_executions() {
+        let registry = ToolRegistry::with_defaults();
+        let can = MockCanInterface::new();
+        let logs = MockLogSource::with_syslog_sample();
+        let executor = make_executor(&registry, &can, &logs);
+
+        let mut cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "log stats", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: json!({"path": "/var/log/syslog"}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        assert_eq!(
+            executor.execute(&cmd).await.status,
+            CommandStatus::Completed
+        );
+
+        let mut cmd =
+            CommandEnvelope::new("fleet-alpha", "rpi-001", "which tools are slow", "admin");
+        cmd.parsed_intent = Some(ParsedIntent {
+            action: ActionKind::Tool,
+            tool_name: tool_stats::TOOL_NAME.into(),
+            tool_args: json!({}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        let resp = executor.execute(&cmd).await;
+        assert_eq!(resp.status, CommandStatus::Completed);
+        let data = resp.response_data.unwrap();
+        assert_eq!(data["tool_name"], tool_stats::TOOL_NAME);
+        assert_eq!(data["data"]["tools"][0]["tool"], "log_stats");
+        assert_eq!(data["data"]["tools"][0]["succeeded"], 1);
+        assert!(
+            resp.response_text
+                .unwrap()
+                .starts_with("1 call(s) across 1 tool(s)")
+        );
+        // agent_stats itself is not counted.
+        assert_eq!(executor.tool_stats().snapshot().len(), 1);
+    }
 }
@@ -0,0 +1,698 @@
+//! Files pushed by the cloud and fetched from the device over MQTT.
+//!
+//! See [`zc_protocol::transfer`] for the protocol. A push is staged in
+//! `staging_dir` as `<transfer_id>.part`, next to the offer it belongs to
+//! (`<transfer_id>.json`), so it resumes from the bytes already received
+//! after a reconnect or an agent restart. Once complete, the data must
+//! match the offered SHA-256 before it replaces the target file.
+//!
+//! Both directions only touch files under `allowed_dirs` and no larger
+//! than `max_file_bytes`, checked again on the real path so a symlink
+//! inside an allowed directory cannot lead out of it. A push is written
+//! to a temporary file of its own next to the target and renamed over it. A fetched file is read whole and sent from the
+//! offered offset, so the cloud can resume an interrupted upload.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use zc_mqtt_channel::{Channel, TransferClient};
+use zc_protocol::transfer::{
+    MAX_CHUNK_BYTES, TransferAck, TransferChunk, TransferComplete, TransferDirection,
+    TransferOffer, TransferOutcome,
+};
+
+/// File transfer settings (`[file_transfer]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileTransferConfig {
+    /// Accept transfers from the cloud. On by default.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Directories files may be pushed to or fetched from.
+    #[serde(default = "default_allowed_dirs")]
+    pub allowed_dirs: Vec<String>,
+    /// Where pushes are assembled until complete.
+    #[serde(default = "default_staging_dir")]
+    pub staging_dir: String,
+    /// Largest file pushed or fetched.
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_allowed_dirs() -> Vec<String> {
+    vec!["/var/lib/zeroclaw/files".into(), "/var/crash".into()]
+}
+
+fn default_staging_dir() -> String {
+    "/var/lib/zeroclaw/transfers".into()
+}
+
+fn default_max_file_bytes() -> u64 {
+    8 * 1024 * 1024
+}
+
+impl Default for FileTransferConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            allowed_dirs: default_allowed_dirs(),
+            staging_dir: default_staging_dir(),
+            max_file_bytes: default_max_file_bytes(),
+        }
+    }
+}
+
+/// Why a transfer was refused or failed.
+#[derive(Debug, thiserror::Error)]
+pub enum TransferError {
+    #[error("file transfer is disabled on this device")]
+    Disabled,
+    #[error("path {0} is outside the allowed directories")]
+    PathNotAllowed(String),
+    #[error("file is {size} bytes, more than the {max} allowed")]
+    TooLarge { size: u64, max: u64 },
+    #[error("invalid offer: {0}")]
+    InvalidOffer(String),
+    #[error("checksum mismatch: expected {expected}, got {actual}")]
+    Checksum { expected: String, actual: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Device side of file transfers.
+pub struct FileTransfers {
+    enabled: bool,
+    allowed_dirs: Vec<PathBuf>,
+    staging_dir: PathBuf,
+    max_file_bytes: u64,
+}
+
+impl FileTransfers {
+    pub fn new(config: &FileTransferConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            allowed_dirs: config.allowed_dirs.iter().map(PathBuf::from).collect(),
+            staging_dir: PathBuf::from(&config.staging_dir),
+            max_file_bytes: config.max_file_bytes,
+        }
+    }
+
+    /// Start or resume a transfer. A fetch uploads the whole file before
+    /// returning, so run it alongside the event loop.
+    pub async fn handle_offer<C: Channel + ?Sized>(
+        &self,
+        offer: TransferOffer,
+        client: &TransferClient<'_, C>,
+    ) {
+        tracing::info!(
+            transfer_id = %offer.transfer_id,
+            direction = offer.direction.as_str(),
+            path = %offer.path,
+            offset = offer.offset,
+            "file transfer offered"
+        );
+        let result = match offer.direction {
+            TransferDirection::Push => self.accept_push(&offer, client).await,
+            TransferDirection::Fetch => self.upload(&offer, client).await,
+        };
+        if let Err(e) = result {
+            tracing::warn!(transfer_id = %offer.transfer_id, error = %e, "file transfer failed");
+            self.discard(offer.transfer_id);
+            publish_complete(
+                client,
+                &TransferComplete::failed(offer.transfer_id, client.device_id(), e.to_string()),
+            )
+            .await;
+        }
+    }
+
+    /// Append a pushed chunk and ask for the next one, or install the
+    /// file once it is complete. Chunks of unknown transfers (e.g. a
+    /// redelivery after completion) are ignored.
+    pub async fn handle_chunk<C: Channel + ?Sized>(
+        &self,
+        chunk: TransferChunk,
+        client: &TransferClient<'_, C>,
+    ) {
+        let Some(offer) = self.load_offer(chunk.transfer_id) else {
+            tracing::debug!(transfer_id = %chunk.transfer_id, "chunk for an unknown transfer");
+            return;
+        };
+        match self.append(&offer, &chunk) {
+            Ok(Some(received)) => {
+                publish_ack(client, offer.transfer_id, received, None).await;
+            }
+            Ok(None) => match self.install(&offer) {
+                Ok(complete) => {
+                    tracing::info!(
+                        transfer_id = %offer.transfer_id,
+                        path = %offer.path,
+                        size = complete.size,
+                        "file transfer completed"
+                    );
+                    let complete = TransferComplete {
+                        device_id: client.device_id().to_string(),
+                        ..complete
+                    };
+                    publish_complete(client, &complete).await;
+                }
+                Err(e) => self.fail(&offer, e, client).await,
+            },
+            Err(e) => self.fail(&offer, e, client).await,
+        }
+    }
+
+    async fn accept_push<C: Channel + ?Sized>(
+        &self,
+        offer: &TransferOffer,
+        client: &TransferClient<'_, C>,
+    ) -> Result<(), TransferError> {
+        self.check(offer)?;
+        let size = offer
+            .size
+            .ok_or_else(|| TransferError::InvalidOffer("push without a size".into()))?;
+        if size > self.max_file_bytes {
+            return Err(TransferError::TooLarge {
+                size,
+                max: self.max_file_bytes,
+            });
+        }
+        if offer.sha256.as_ref().is_none_or(|s| !is_sha256(s)) {
+            return Err(TransferError::InvalidOffer(
+                "push without a valid sha256".into(),
+            ));
+        }
+
+        std::fs::create_dir_all(&self.staging_dir)?;
+        let part = self.part_path(offer.transfer_id);
+        let received = if self.load_offer(offer.transfer_id).as_ref() == Some(offer) {
+            std::fs::metadata(&part).map_or(0, |m| m.len())
+        } else {
+            let json = serde_json::to_vec(offer).map_err(io::Error::other)?;
+            std::fs::write(self.offer_path(offer.transfer_id), json)?;
+            std::fs::write(&part, b"")?;
+            0
+        };
+        if received == size {
+            // Nothing (left) to send: an empty file, or all chunks arrived
+            // before the agent restarted.
+            let complete = self.install(offer)?;
+            publish_complete(
+                client,
+                &TransferComplete {
+                    device_id: client.device_id().to_string(),
+                    ..complete
+                },
+            )
+            .await;
+        } else {
+            publish_ack(client, offer.transfer_id, received, None).await;
+        }
+        Ok(())
+    }
+
+    async fn upload<C: Channel + ?Sized>(
+        &self,
+        offer: &TransferOffer,
+        client: &TransferClient<'_, C>,
+    ) -> Result<(), TransferError> {
+        self.check(offer)?;
+        let path = Path::new(&offer.path);
+        let size = std::fs::metadata(path)?.len();
+        if size > self.max_file_bytes {
+            return Err(TransferError::TooLarge {
+                size,
+                max: self.max_file_bytes,
+            });
+        }
+        // Symlinks must not lead out of the allowed directories.
+        let real = std::fs::canonicalize(path)?;
+        if !self.allows_real(&real) {
+            return Err(TransferError::PathNotAllowed(offer.path.clone()));
+        }
+        let data = std::fs::read(&real)?;
+        let offset = offer.offset.min(data.len() as u64);
+        let chunk_size = offer.chunk_size.clamp(1, MAX_CHUNK_BYTES) as usize;
+
+        publish_ack(client, offer.transfer_id, offset, Some(data.len() as u64)).await;
+        let mut position = offset as usize;
+        for slice in data[position..].chunks(chunk_size) {
+            let chunk = TransferChunk::new(offer.transfer_id, position as u64, slice);
+            client
+                .publish_upload(&chunk)
+                .await
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            position += slice.len();
+        }
+        tracing::info!(
+            transfer_id = %offer.transfer_id,
+            path = %offer.path,
+            size = data.len(),
+            "file uploaded"
+        );
+        let complete = TransferComplete {
+            transfer_id: offer.transfer_id,
+            device_id: client.device_id().to_string(),
+            outcome: TransferOutcome::Completed,
+            size: data.len() as u64,
+            sha256: Some(sha256_hex(&data)),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        };
+        publish_complete(client, &complete).await;
+        Ok(())
+    }
+
+    /// Refuse transfers while disabled and paths outside the allowed
+    /// directories.
+    fn check(&self, offer: &TransferOffer) -> Result<(), TransferError> {
+        if !self.enabled {
+            return Err(TransferError::Disabled);
+        }
+        let path = Path::new(&offer.path);
+        let clean = path.is_absolute()
+            && path
+                .components()
+                .all(|c| !matches!(c, Component::ParentDir | Component::CurDir));
+        if clean
+            && self.allowed_dirs.iter().any(|dir| path.starts_with(dir))
+            && path.file_name().is_some()
+        {
+            Ok(())
+        } else {
+            Err(TransferError::PathNotAllowed(offer.path.clone()))
+        }
+    }
+
+    /// Whether `real`, a canonical path, is inside an allowed directory.
+    fn allows_real(&self, real: &Path) -> bool {
+        self.allowed_dirs
+            .iter()
+            .any(|dir| std::fs::canonicalize(dir).is_ok_and(|dir| real.starts_with(dir)))
+    }
+
+    /// Append `chunk` if it is the next one. Returns the bytes received
+    /// so far, or `None` once the file is complete.
+    fn append(
+        &self,
+        offer: &TransferOffer,
+        chunk: &TransferChunk,
+    ) -> Result<Option<u64>, TransferError> {
+        use std::io::Write;
+
+        let size = offer.size.unwrap_or_default();
+        let part = self.part_path(offer.transfer_id);
+        let received = std::fs::metadata(&part)?.len();
+        if chunk.offset != received {
+            // Duplicate or out of order: ask again for what is missing.
+            return Ok(Some(received));
+        }
+        let data = chunk
+            .bytes()
+            .map_err(|e| TransferError::InvalidOffer(format!("chunk data: {e}")))?;
+        let received = received + data.len() as u64;
+        if received > size {
+            return Err(TransferError::InvalidOffer(format!(
+                "received {received} bytes of a {size} byte file"
+            )));
+        }
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&part)?
+            .write_all(&data)?;
+        Ok((received < size).then_some(received))
+    }
+
+    /// Verify the assembled file and move it into place.
+    fn install(&self, offer: &TransferOffer) -> Result<TransferComplete, TransferError> {
+        use std::io::Write;
+
+        let part = self.part_path(offer.transfer_id);
+        let data = std::fs::read(&part)?;
+        let actual = sha256_hex(&data);
+        let expected = offer.sha256.clone().unwrap_or_default();
+        if !actual.eq_ignore_ascii_case(&expected) {
+            return Err(TransferError::Checksum { expected, actual });
+        }
+        let target = self.real_target(offer)?;
+        // Copy next to the target, then rename, so readers never see a
+        // half-written file even when staging is on another filesystem.
+        // The temporary name is the transfer's own and must not exist yet.
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = target.with_file_name(format!(".{name}.{}.transfer", offer.transfer_id));
+        let written = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+            .and_then(|mut file| file.write_all(&data))
+            .and_then(|()| std::fs::rename(&tmp, &target));
+        if let Err(e) = written {
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                let _ = std::fs::remove_file(&tmp);
+            }
+            return Err(e.into());
+        }
+        self.discard(offer.transfer_id);
+        Ok(TransferComplete {
+            transfer_id: offer.transfer_id,
+            device_id: String::new(),
+            outcome: TransferOutcome::Completed,
+            size: data.len() as u64,
+            sha256: Some(actual),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    /// The push target with its parent directory resolved, once that
+    /// directory is known to be inside an allowed one. Nothing is created
+    /// until the deepest existing ancestor has been checked, so a symlink
+    /// cannot make the agent create directories elsewhere either.
+    fn real_target(&self, offer: &TransferOffer) -> Result<PathBuf, TransferError> {
+        let not_allowed = || TransferError::PathNotAllowed(offer.path.clone());
+        let target = Path::new(&offer.path);
+        let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+            return Err(not_allowed());
+        };
+        for dir in self
+            .allowed_dirs
+            .iter()
+            .filter(|dir| target.starts_with(dir))
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        let existing = parent
+            .ancestors()
+            .find(|dir| dir.exists())
+            .ok_or_else(not_allowed)?;
+        if !self.allows_real(&std::fs::canonicalize(existing)?) {
+            return Err(not_allowed());
+        }
+        std::fs::create_dir_all(parent)?;
+        let parent = std::fs::canonicalize(parent)?;
+        if !self.allows_real(&parent) {
+            return Err(not_allowed());
+        }
+        Ok(parent.join(name))
+    }
+
+    async fn fail<C: Channel + ?Sized>(
+        &self,
+        offer: &TransferOffer,
+        error: TransferError,
+        client: &TransferClient<'_, C>,
+    ) {
+        tracing::warn!(transfer_id = %offer.transfer_id, error = %error, "file transfer failed");
+        self.discard(offer.transfer_id);
+        publish_complete(
+            client,
+            &TransferComplete::failed(offer.transfer_id, client.device_id(), error.to_string()),
+        )
+        .await;
+    }
+
+    fn load_offer(&self, transfer_id: Uuid) -> Option<TransferOffer> {
+        let json = std::fs::read(self.offer_path(transfer_id)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Drop the staged data of a transfer.
+    fn discard(&self, transfer_id: Uuid) {
+        let _ = std::fs::remove_file(self.part_path(transfer_id));
+        let _ = std::fs::remove_file(self.offer_path(transfer_id));
+    }
+
+    fn part_path(&self, transfer_id: Uuid) -> PathBuf {
+        self.staging_dir.join(format!("{transfer_id}.part"))
+    }
+
+    fn offer_path(&self, transfer_id: Uuid) -> PathBuf {
+        self.staging_dir.join(format!("{transfer_id}.json"))
+    }
+}
+
+async fn publish_ack<C: Channel + ?Sized>(
+    client: &TransferClient<'_, C>,
+    transfer_id: Uuid,
+    next_offset: u64,
+    size: Option<u64>,
+) {
+    let ack = TransferAck {
+        transfer_id,
+        device_id: client.device_id().to_string(),
+        next_offset,
+        size,
+    };
+    if let Err(e) = client.publish_ack(&ack).await {
+        tracing::warn!(transfer_id = %transfer_id, error = %e, "failed to publish transfer ack");
+    }
+}
+
+async fn publish_complete<C: Channel + ?Sized>(
+    client: &TransferClient<'_, C>,
+    complete: &TransferComplete,
+) {
+    if let Err(e) = client.publish_complete(complete).await {
+        tracing::warn!(transfer_id = %complete.transfer_id, error = %e, "failed to publish transfer result");
+    }
+}
+
+fn is_sha256(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zc_mqtt_channel::MockChannel;
+
+    fn tempdir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "zc-transfer-{name}-{}-{}",
+            std::process::id(),
+            Uuid::now_v7()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn transfers(dir: &Path) -> FileTransfers {
+        FileTransfers::new(&FileTransferConfig {
+            enabled: true,
+            allowed_dirs: vec![dir.join("files").to_string_lossy().into_owned()],
+            staging_dir: dir.join("staging").to_string_lossy().into_owned(),
+            max_file_bytes: 1024,
+        })
+    }
+
+    fn offer(direction: TransferDirection, path: &Path, data: &[u8]) -> TransferOffer {
+        TransferOffer {
+            transfer_id: Uuid::now_v7(),
+            direction,
+            path: path.to_string_lossy().into_owned(),
+            size: Some(data.len() as u64),
+            sha256: Some(sha256_hex(data)),
+            offset: 0,
+            chunk_size: 4,
+            offered_at: chrono::Utc::now(),
+        }
+    }
+
+    fn acks(mock: &MockChannel) -> Vec<TransferAck> {
+        mock.published_to("fleet/fleet-alpha/rpi-001/transfer/ack")
+            .iter()
+            .map(|m| serde_json::from_slice(&m.payload).unwrap())
+            .collect()
+    }
+
+    fn completes(mock: &MockChannel) -> Vec<TransferComplete> {
+        mock.published_to("fleet/fleet-alpha/rpi-001/transfer/complete")
+            .iter()
+            .map(|m| serde_json::from_slice(&m.payload).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn push_resumes_and_installs_verified_file() {
+        let dir = tempdir("push");
+        let target = dir.join("files/dbc/vehicle.dbc");
+        let data = b"BO_ 100 Engine: 8 ECU\n";
+        let offer = offer(TransferDirection::Push, &target, data);
+        let mock = MockChannel::new();
+        let client = TransferClient::new(&mock, "fleet-alpha", "rpi-001");
+
+        transfers(&dir).handle_offer(offer.clone(), &client).await;
+        assert_eq!(acks(&mock).last().unwrap().next_offset, 0);
+        let chunk = |offset: usize| {
+            TransferChunk::new(
+                offer.transfer_id,
+                offset as u64,
+                &data[offset..(offset + 8).min(data.len())],
+            )
+        };
+        transfers(&dir).handle_chunk(chunk(0), &client).await;
+        // A duplicate is not appended twice.
+        transfers(&dir).handle_chunk(chunk(0), &client).await;
+        assert_eq!(acks(&mock).last().unwrap().next_offset, 8);
+
+        // The offer repeated after a reconnect resumes where it stopped.
+        let fresh = transfers(&dir);
+        fresh.handle_offer(offer.clone(), &client).await;
+        assert_eq!(acks(&mock).last().unwrap().next_offset, 8);
+        fresh.handle_chunk(chunk(8), &client).await;
+        fresh.handle_chunk(chunk(16), &client).await;
+
+        let complete = completes(&mock);
+        assert_eq!(complete.len(), 1);
+        assert_eq!(complete[0].outcome, TransferOutcome::Completed);
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        assert!(fresh.load_offer(offer.transfer_id).is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn push_with_wrong_checksum_is_discarded() {
+        let dir = tempdir("checksum");
+        let target = dir.join("files/ca.pem");
+        let mut offer = offer(TransferDirection::Push, &target, b"cert");
+        offer.sha256 = Some("00".repeat(32));
+        let mock = MockChannel::new();
+        let client = TransferClient::new(&mock, "fleet-alpha", "rpi-001");
+        let transfers = transfers(&dir);
+
+        transfers.handle_offer(offer.clone(), &client).await;
+        transfers
+            .handle_chunk(TransferChunk::new(offer.transfer_id, 0, b"cert"), &client)
+            .await;
+
+        let complete = completes(&mock);
+        assert_eq!(complete[0].outcome, TransferOutcome::Failed);
+        assert!(complete[0].error.as_deref().unwrap().contains("checksum"));
+        assert!(!target.exists());
+        assert!(transfers.load_offer(offer.transfer_id).is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn fetch_uploads_from_the_offset() {
+        let dir = tempdir("fetch");
+        std::fs::create_dir_all(dir.join("files")).unwrap();
+        let source = dir.join("files/core.1234");
+        let data = b"0123456789";
+        std::fs::write(&source, data).unwrap();
+        let mut offer = offer(TransferDirection::Fetch, &source, b"");
+        offer.offset = 2;
+        let mock = MockChannel::new();
+        let client = TransferClient::new(&mock, "fleet-alpha", "rpi-001");
+
+        transfers(&dir).handle_offer(offer, &client).await;
+
+        assert_eq!(acks(&mock)[0].size, Some(10));
+        let chunks: Vec<TransferChunk> = mock
+            .published_to("fleet/fleet-alpha/rpi-001/transfer/upload")
+            .iter()
+            .map(|m| serde_json::from_slice(&m.payload).unwrap())
+            .collect();
+        let offsets: Vec<u64> = chunks.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, [2, 6]);
+        assert_eq!(chunks[1].bytes().unwrap(), b"6789");
+        let complete = completes(&mock);
+        assert_eq!(
+            complete[0].sha256.as_deref(),
+            Some(sha256_hex(data).as_str())
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn paths_outside_allowed_dirs_and_oversized_files_are_refused() {
+        let dir = tempdir("refuse");
+        let mock = MockChannel::new();
+        let client = TransferClient::new(&mock, "fleet-alpha", "rpi-001");
+        let transfers = transfers(&dir);
+
+        for path in [
+            PathBuf::from("/etc/shadow"),
+            dir.join("files/../escape"),
+            PathBuf::from("relative/file"),
+        ] {
+            transfers
+                .handle_offer(offer(TransferDirection::Fetch, &path, b""), &client)
+                .await;
+        }
+        transfers
+            .handle_offer(
+                offer(TransferDirection::Push, &dir.join("files/big"), &[0; 2048]),
+                &client,
+            )
+            .await;
+
+        let errors: Vec<String> = completes(&mock)
+            .into_iter()
+            .map(|c| c.error.unwrap())
+            .collect();
+        assert_eq!(errors.len(), 4);
+        assert!(
+            errors[..3]
+                .iter()
+                .all(|e| e.contains("outside the allowed"))
+        );
+        assert!(errors[3].contains("more than the 1024 allowed"));
+        assert!(acks(&mock).is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    #[tokio::test]
+    async fn push_through_a_symlink_out_of_allowed_dirs_is_refused() {
+        let dir = tempdir("symlink");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::create_dir_all(dir.join("files")).unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("files/link")).unwrap();
+        // A file that happens to carry the old temporary name stays put.
+        std::fs::write(dir.join("files/ca.transfer"), b"keep").unwrap();
+        let mock = MockChannel::new();
+        let client = TransferClient::new(&mock, "fleet-alpha", "rpi-001");
+        let transfers = transfers(&dir);
+
+        for target in [dir.join("files/link/ca.pem"), dir.join("files/ca.pem")] {
+            let offer = offer(TransferDirection::Push, &target, b"cert");
+            transfers.handle_offer(offer.clone(), &client).await;
+            transfers
+                .handle_chunk(TransferChunk::new(offer.transfer_id, 0, b"cert"), &client)
+                .await;
+        }
+
+        let complete = completes(&mock);
+        assert_eq!(complete[0].outcome, TransferOutcome::Failed);
+        assert!(
+            complete[0]
+                .error
+                .as_deref()
+                .unwrap()
+                .contains("outside the allowed")
+        );
+        assert!(!outside.join("ca.pem").exists());
+        assert_eq!(complete[1].outcome, TransferOutcome::Completed);
+        assert_eq!(std::fs::read(dir.join("files/ca.pem")).unwrap(), b"cert");
+        assert_eq!(
+            std::fs::read(dir.join("files/ca.transfer")).unwrap(),
+            b"keep"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
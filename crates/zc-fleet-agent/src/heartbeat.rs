@@ -1,15 +1,22 @@
 //! Periodic heartbeat publisher.
 //!
 //! Sends a `Heartbeat` message at a configurable interval so the cloud
-//! knows the device is alive.
+//! knows the device is alive. The interval follows runtime config updates.
+//! Over TLS each heartbeat also carries the client certificate's expiry,
+//! read from disk every time so a rotated certificate is reported, and
+//! host vitals (load, free memory and disk, temperature, signal) sampled by
+//! [`crate::vitals`].
 
 use std::time::Duration;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tokio::time;
 
-use zc_mqtt_channel::MqttChannel;
-use zc_protocol::device::{DeviceStatus, Heartbeat, ServiceStatus};
+use zc_mqtt_channel::{MqttChannel, MqttConfig};
+use zc_protocol::device::{DeviceStatus, HEARTBEAT_VERSION, Heartbeat, ServiceStatus};
+use zc_protocol::encoding::Encoding;
+
+use crate::runtime_config::RuntimeConfigRx;
 
 /// Read `/etc/machine-id` once at startup. Returns `None` if unavailable.
 fn read_machine_id() -> Option<String> {
@@ -19,13 +26,35 @@ fn read_machine_id() -> Option<String> {
         .filter(|s| !s.is_empty())
 }
 
-/// Run the heartbeat loop, publishing at `interval`.
+/// Expiry of the client certificate when connecting over TLS. A failure is
+/// logged once (`warned` remembers it) and leaves the field out.
+fn cert_expiry(mqtt: &MqttConfig, warned: &mut bool) -> Option<DateTime<Utc>> {
+    if !mqtt.use_tls {
+        return None;
+    }
+    match zc_mqtt_channel::tls::client_cert_expiry(mqtt) {
+        Ok(expires_at) => {
+            *warned = false;
+            Some(expires_at)
+        }
+        Err(e) => {
+            if !std::mem::replace(warned, true) {
+                tracing::warn!(error = %e, "could not read client certificate expiry");
+            }
+            None
+        }
+    }
+}
+
+/// Run the heartbeat loop, publishing every `heartbeat_interval_secs` of
+/// the current runtime config. A changed interval restarts the ticker.
 ///
 /// This function runs forever until the task is cancelled. Intended
 /// to be spawned as a background tokio task.
 pub async fn run(
     channel: &MqttChannel,
-    interval: Duration,
+    mqtt: &MqttConfig,
+    mut runtime: RuntimeConfigRx,
     start_time: tokio::time::Instant,
     can_available: bool,
     ollama_enabled: bool,
@@ -37,12 +66,23 @@ pub async fn run(
         tracing::warn!("could not read /etc/machine-id — heartbeats will omit machine_id");
     }
 
-    let mut ticker = time::interval(interval);
-    // Skip the first tick (fires immediately).
-    ticker.tick().await;
+    let mut cert_warned = false;
+    let mut interval = Duration::from_secs(runtime.borrow_and_update().heartbeat_interval_secs);
+    let mut ticker = time::interval_at(time::Instant::now() + interval, interval);
 
     loop {
-        ticker.tick().await;
+        tokio::select! {
+            _ = ticker.tick() => {}
+            Ok(()) = runtime.changed() => {
+                let next = Duration::from_secs(runtime.borrow_and_update().heartbeat_interval_secs);
+                if next != interval {
+                    tracing::info!(interval_secs = next.as_secs(), "heartbeat interval updated");
+                    interval = next;
+                    ticker = time::interval_at(time::Instant::now() + interval, interval);
+                }
+                continue;
+            }
+        }
 
         let heartbeat = Heartbeat {
             device_id: channel.device_id().to_string(),
@@ -61,6 +101,10 @@ pub async fn run(
             },
             agent_version: env!("CARGO_PKG_VERSION").to_string(),
             machine_id: machine_id.clone(),
+            cert_expires_at: cert_expiry(mqtt, &mut cert_warned),
+            encodings: Encoding::ALL.to_vec(),
+            version: HEARTBEAT_VERSION,
+            vitals: crate::vitals::sample().await,
             timestamp: Utc::now(),
         };
 
@@ -0,0 +1,636 @@
+//! Device-local command and telemetry journal.
+//!
+//! Every executed command and every telemetry window is appended to a
+//! bounded journal (JSON lines on disk, mirrored in memory), together with
+//! whether it reached the broker. When the cloud record has gaps — results
+//! produced during an outage, or dropped by payload limits — the cloud can
+//! ask the device directly with the `get_local_history` command, which
+//! filters the journal and returns per-kind summaries.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use zc_protocol::catalog::ToolSpec;
+use zc_protocol::commands::{CommandEnvelope, CommandResponse, CommandStatus};
+use zc_protocol::telemetry::{TelemetryReading, TelemetrySource};
+
+/// Tool name the executor routes to [`LocalHistory::query`].
+pub const TOOL_NAME: &str = "get_local_history";
+
+/// Catalog entry for [`TOOL_NAME`].
+pub fn tool_spec() -> ToolSpec {
+    ToolSpec::new(
+        TOOL_NAME,
+        "Query the device's own journal of past commands and telemetry (fills gaps after an outage)",
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "kind": {"type": "string", "description": "\"command\" or \"telemetry\"; both when omitted"},
+                "since_minutes": {"type": "integer", "description": "Only entries from the last N minutes"},
+                "since": {"type": "string", "description": "RFC 3339 start"},
+                "until": {"type": "string", "description": "RFC 3339 end"},
+                "tool_name": {"type": "string"},
+                "status": {"type": "string", "description": "Command status, e.g. \"failed\""},
+                "metric": {"type": "string", "description": "Telemetry metric, e.g. \"engine_rpm\""},
+                "unpublished_only": {"type": "boolean", "description": "Only entries that never reached the broker"},
+                "limit": {"type": "integer"}
+            }
+        }),
+    )
+}
+
+/// Entries returned when the query does not set `limit`.
+const DEFAULT_LIMIT: usize = 50;
+
+/// Largest `limit` accepted (keeps the response well under the MQTT cap).
+const MAX_LIMIT: usize = 500;
+
+/// Local journal settings (`[history]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Record commands and telemetry locally. On by default.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Journal file (JSON lines). Created if missing.
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// Entries kept; older ones are dropped.
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_path() -> String {
+    "/var/lib/zeroclaw/history.jsonl".into()
+}
+
+fn default_max_entries() -> usize {
+    5000
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            path: default_path(),
+            max_entries: default_max_entries(),
+        }
+    }
+}
+
+/// One journal record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HistoryEntry {
+    Command {
+        at: DateTime<Utc>,
+        command_id: String,
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool_name: Option<String>,
+        status: CommandStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        latency_ms: u64,
+        /// Whether the response was handed to the broker.
+        published: bool,
+    },
+    Telemetry {
+        at: DateTime<Utc>,
+        metric_name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit: Option<String>,
+        source: TelemetrySource,
+        published: bool,
+    },
+}
+
+impl HistoryEntry {
+    fn at(&self) -> DateTime<Utc> {
+        match self {
+            Self::Command { at, .. } | Self::Telemetry { at, .. } => *at,
+        }
+    }
+
+    fn published(&self) -> bool {
+        match self {
+            Self::Command { published, .. } | Self::Telemetry { published, .. } => *published,
+        }
+    }
+}
+
+/// Filters accepted by `get_local_history`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryQuery {
+    /// "command" or "telemetry"; None for both.
+    pub kind: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub tool_name: Option<String>,
+    pub status: Option<CommandStatus>,
+    pub metric: Option<String>,
+    /// Only entries that never reached the broker.
+    pub unpublished_only: bool,
+    pub limit: usize,
+}
+
+impl HistoryQuery {
+    /// Build from tool arguments. `since` / `until` are RFC 3339 timestamps;
+    /// `since_minutes` is a shorthand relative to now.
+    pub fn from_args(args: &serde_json::Value) -> Result<Self, String> {
+        let time_arg = |key: &str| -> Result<Option<DateTime<Utc>>, String> {
+            args.get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| {
+                    DateTime::parse_from_rfc3339(s)
+                        .map(|t| t.with_timezone(&Utc))
+                        .map_err(|e| format!("invalid {key} '{s}': {e}"))
+                })
+                .transpose()
+        };
+        let str_arg = |key: &str| args.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        let kind = str_arg("kind");
+        if let Some(k) = &kind
+            && k != "command"
+            && k != "telemetry"
+        {
+            return Err(format!("kind must be 'command' or 'telemetry', got '{k}'"));
+        }
+
+        let mut since = time_arg("since")?;
+        if let Some(minutes) = args.get("since_minutes").and_then(|v| v.as_i64()) {
+            since = Some(Utc::now() - Duration::minutes(minutes.max(0)));
+        }
+
+        let status = args
+            .get("status")
+            .map(|v| {
+                serde_json::from_value::<CommandStatus>(v.clone())
+                    .map_err(|_| format!("unknown status {v}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            kind,
+            since,
+            until: time_arg("until")?,
+            tool_name: str_arg("tool_name"),
+            status,
+            metric: str_arg("metric"),
+            unpublished_only: args
+                .get("unpublished_only")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            limit: args
+                .get("limit")
+                .and_then(|v| v.as_u64())
+                .map(|n| n as usize)
+                .unwrap_or(DEFAULT_LIMIT)
+                .clamp(1, MAX_LIMIT),
+        })
+    }
+
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let at = entry.at();
+        if self.since.is_some_and(|t| at < t) || self.until.is_some_and(|t| at > t) {
+            return false;
+        }
+        if self.unpublished_only && entry.published() {
+            return false;
+        }
+        match entry {
+            HistoryEntry::Command {
+                tool_name, status, ..
+            } => {
+                self.kind.as_deref().is_none_or(|k| k == "command")
+                    && self.metric.is_none()
+                    && self
+                        .tool_name
+                        .as_ref()
+                        .is_none_or(|t| tool_name.as_ref() == Some(t))
+                    && self.status.is_none_or(|s| s == *status)
+            }
+            HistoryEntry::Telemetry { metric_name, .. } => {
+                self.kind.as_deref().is_none_or(|k| k == "telemetry")
+                    && self.tool_name.is_none()
+                    && self.status.is_none()
+                    && self.metric.as_ref().is_none_or(|m| m == metric_name)
+            }
+        }
+    }
+}
+
+/// Bounded journal, optionally backed by a JSON-lines file.
+pub struct LocalHistory {
+    path: Option<PathBuf>,
+    max_entries: usize,
+    inner: Mutex<Journal>,
+}
+
+struct Journal {
+    entries: VecDeque<HistoryEntry>,
+    /// Lines currently in the file (including ones already evicted in memory).
+    lines_on_disk: usize,
+}
+
+impl LocalHistory {
+    /// Journal that lives only in memory (tests, or when the disk is read-only).
+    pub fn in_memory(max_entries: usize) -> Self {
+        Self {
+            path: None,
+            max_entries: max_entries.max(1),
+            inner: Mutex::new(Journal {
+                entries: VecDeque::new(),
+                lines_on_disk: 0,
+            }),
+        }
+    }
+
+    /// Open (or create) the journal file, loading the newest entries.
+    /// Unparseable lines are skipped.
+    pub fn open(config: &HistoryConfig) -> std::io::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut entries = VecDeque::new();
+        let mut lines_on_disk = 0;
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                for line in std::io::BufReader::new(file).lines() {
+                    let line = line?;
+                    lines_on_disk += 1;
+                    if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+                        entries.push_back(entry);
+                        if entries.len() > config.max_entries {
+                            entries.pop_front();
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self {
+            path: Some(path),
+            max_entries: config.max_entries.max(1),
+            inner: Mutex::new(Journal {
+                entries,
+                lines_on_disk,
+            }),
+        })
+    }
+
+    /// Number of entries currently held.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append an entry. Disk errors are logged; the in-memory copy is kept.
+    pub fn record(&self, entry: HistoryEntry) {
+        let mut journal = self.inner.lock().unwrap();
+        journal.entries.push_back(entry.clone());
+        while journal.entries.len() > self.max_entries {
+            journal.entries.pop_front();
+        }
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        // Rewrite the file once it holds twice the retained entries, so it
+        // stays bounded without rewriting on every append.
+        let result = if journal.lines_on_disk + 1 > self.max_entries * 2 {
+            rewrite(path, &journal.entries).map(|()| journal.entries.len())
+        } else {
+            append(path, &entry).map(|()| journal.lines_on_disk + 1)
+        };
+        match result {
+            Ok(lines) => journal.lines_on_disk = lines,
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path.display(), "failed to write history journal")
+            }
+        }
+    }
+
+    /// Record an executed command and whether its response was published.
+    pub fn record_command(
+        &self,
+        envelope: &CommandEnvelope,
+        response: &CommandResponse,
+        published: bool,
+    ) {
+        self.record(HistoryEntry::Command {
+            at: response.responded_at,
+            command_id: envelope.id.to_string(),
+            command: envelope.natural_language.clone(),
+            tool_name: envelope.parsed_intent.as_ref().map(|i| i.tool_name.clone()),
+            status: response.status,
+            summary: response.response_text.clone(),
+            error: response.error.clone(),
+            latency_ms: response.latency_ms,
+            published,
+        });
+    }
+
+    /// Record a telemetry window and whether it was published.
+    pub fn record_telemetry(&self, readings: &[TelemetryReading], published: bool) {
+        for r in readings {
+            self.record(HistoryEntry::Telemetry {
+                at: r.time,
+                metric_name: r.metric_name.clone(),
+                value: r.value_numeric,
+                unit: r.unit.clone(),
+                source: r.source,
+                published,
+            });
+        }
+    }
+
+    /// Filter the journal. Returns the newest `limit` matches plus
+    /// summaries over *all* matches.
+    pub fn query(&self, q: &HistoryQuery) -> serde_json::Value {
+        let journal = self.inner.lock().unwrap();
+        let matched: Vec<&HistoryEntry> = journal.entries.iter().filter(|e| q.matches(e)).collect();
+
+        let mut by_status: BTreeMap<String, usize> = BTreeMap::new();
+        let mut commands = 0;
+        let mut metrics: BTreeMap<&str, MetricSummary> = BTreeMap::new();
+        for entry in &matched {
+            match entry {
+                HistoryEntry::Command { status, .. } => {
+                    commands += 1;
+                    let key = serde_json::to_value(status)
+                        .ok()
+                        .and_then(|v| v.as_str().map(String::from))
+                        .unwrap_or_default();
+                    *by_status.entry(key).or_default() += 1;
+                }
+                HistoryEntry::Telemetry {
+                    at,
+                    metric_name,
+                    value,
+                    ..
+                } => metrics
+                    .entry(metric_name.as_str())
+                    .or_insert_with(|| MetricSummary::new(*at))
+                    .add(*at, *value),
+            }
+        }
+
+        let unpublished = matched.iter().filter(|e| !e.published()).count();
+        let entries: Vec<&HistoryEntry> = matched.iter().rev().take(q.limit).copied().collect();
+
+        serde_json::json!({
+            "matched": matched.len(),
+            "returned": entries.len(),
+            "unpublished": unpublished,
+            "journal_entries": journal.entries.len(),
+            "oldest_entry_at": journal.entries.front().map(|e| e.at()),
+            "commands": { "total": commands, "by_status": by_status },
+            "telemetry": metrics
+                .into_iter()
+                .map(|(name, s)| (name.to_string(), s.to_json()))
+                .collect::<serde_json::Map<_, _>>(),
+            "entries": entries,
+        })
+    }
+}
+
+/// Per-metric aggregate over matched telemetry entries.
+struct MetricSummary {
+    count: usize,
+    min: Option<f64>,
+    max: Option<f64>,
+    sum: f64,
+    valued: usize,
+    first_at: DateTime<Utc>,
+    last_at: DateTime<Utc>,
+}
+
+impl MetricSummary {
+    fn new(at: DateTime<Utc>) -> Self {
+        Self {
+            count: 0,
+            min: None,
+            max: None,
+            sum: 0.0,
+            valued: 0,
+            first_at: at,
+            last_at: at,
+        }
+    }
+
+    fn add(&mut self, at: DateTime<Utc>, value: Option<f64>) {
+        self.count += 1;
+        self.first_at = self.first_at.min(at);
+        self.last_at = self.last_at.max(at);
+        if let Some(v) = value {
+            self.min = Some(self.min.map_or(v, |m| m.min(v)));
+            self.max = Some(self.max.map_or(v, |m| m.max(v)));
+            self.sum += v;
+            self.valued += 1;
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "count": self.count,
+            "min": self.min,
+            "max": self.max,
+            "avg": (self.valued > 0).then(|| self.sum / self.valued as f64),
+            "first_at": self.first_at,
+            "last_at": self.last_at,
+        })
+    }
+}
+
+fn append(path: &Path, entry: &HistoryEntry) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+    writeln!(file, "{line}")
+}
+
+fn rewrite(path: &Path, entries: &VecDeque<HistoryEntry>) -> std::io::Result<()> {
+    let tmp = path.with_extension("jsonl.tmp");
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        for entry in entries {
+            let line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+            writeln!(file, "{line}")?;
+        }
+        file.flush()?;
+    }
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(status: CommandStatus, tool: &str, published: bool) -> HistoryEntry {
+        HistoryEntry::Command {
+            at: Utc::now(),
+            command_id: "c1".into(),
+            command: "read DTCs".into(),
+            tool_name: Some(tool.into()),
+            status,
+            summary: None,
+            error: None,
+            latency_ms: 10,
+            published,
+        }
+    }
+
+    fn telemetry(metric: &str, value: f64) -> HistoryEntry {
+        HistoryEntry::Telemetry {
+            at: Utc::now(),
+            metric_name: metric.into(),
+            value: Some(value),
+            unit: Some("rpm".into()),
+            source: TelemetrySource::Obd2,
+            published: true,
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("zc-history-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("history.jsonl").to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn evicts_oldest_beyond_max_entries() {
+        let history = LocalHistory::in_memory(3);
+        for v in 0..5 {
+            history.record(telemetry("engine_rpm", v as f64));
+        }
+        assert_eq!(history.len(), 3);
+        let result = history.query(&HistoryQuery {
+            limit: 10,
+            ..Default::default()
+        });
+        assert_eq!(result["telemetry"]["engine_rpm"]["min"], 2.0);
+    }
+
+    #[test]
+    fn filters_by_kind_status_and_published() {
+        let history = LocalHistory::in_memory(100);
+        history.record(command(CommandStatus::Completed, "read_dtcs", true));
+        history.record(command(CommandStatus::Failed, "read_vin", false));
+        history.record(telemetry("engine_rpm", 800.0));
+
+        let q = |args: serde_json::Value| history.query(&HistoryQuery::from_args(&args).unwrap());
+
+        assert_eq!(q(serde_json::json!({}))["matched"], 3);
+        assert_eq!(q(serde_json::json!({"kind": "command"}))["matched"], 2);
+        assert_eq!(q(serde_json::json!({"status": "failed"}))["matched"], 1);
+        assert_eq!(
+            q(serde_json::json!({"tool_name": "read_dtcs"}))["matched"],
+            1
+        );
+        assert_eq!(q(serde_json::json!({"metric": "engine_rpm"}))["matched"], 1);
+
+        let unpublished = q(serde_json::json!({"unpublished_only": true}));
+        assert_eq!(unpublished["matched"], 1);
+        assert_eq!(unpublished["entries"][0]["tool_name"], "read_vin");
+        assert_eq!(unpublished["commands"]["by_status"]["failed"], 1);
+    }
+
+    #[test]
+    fn since_filter_and_newest_first_limit() {
+        let history = LocalHistory::in_memory(100);
+        let mut old = command(CommandStatus::Completed, "read_dtcs", true);
+        if let HistoryEntry::Command { at, .. } = &mut old {
+            *at = Utc::now() - Duration::hours(3);
+        }
+        history.record(old);
+        history.record(command(CommandStatus::Completed, "read_vin", true));
+        history.record(command(CommandStatus::Completed, "read_pid", true));
+
+        let recent = history
+            .query(&HistoryQuery::from_args(&serde_json::json!({"since_minutes": 60})).unwrap());
+        assert_eq!(recent["matched"], 2);
+
+        let limited =
+            history.query(&HistoryQuery::from_args(&serde_json::json!({"limit": 1})).unwrap());
+        assert_eq!(limited["returned"], 1);
+        assert_eq!(limited["entries"][0]["tool_name"], "read_pid");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(HistoryQuery::from_args(&serde_json::json!({"kind": "alerts"})).is_err());
+        assert!(HistoryQuery::from_args(&serde_json::json!({"since": "yesterday"})).is_err());
+        assert!(HistoryQuery::from_args(&serde_json::json!({"status": "exploded"})).is_err());
+        let q = HistoryQuery::from_args(&serde_json::json!({"limit": 100000})).unwrap();
+        assert_eq!(q.limit, MAX_LIMIT);
+    }
+
+    #[test]
+    fn persists_and_reloads_from_disk() {
+        let config = HistoryConfig {
+            enabled: true,
+            path: temp_path("reload"),
+            max_entries: 10,
+        };
+        let history = LocalHistory::open(&config).unwrap();
+        history.record(command(CommandStatus::Completed, "read_dtcs", false));
+        history.record(telemetry("vehicle_speed", 42.0));
+        drop(history);
+
+        let reopened = LocalHistory::open(&config).unwrap();
+        assert_eq!(reopened.len(), 2);
+        let result = reopened.query(&HistoryQuery {
+            unpublished_only: true,
+            limit: 10,
+            ..Default::default()
+        });
+        assert_eq!(result["entries"][0]["tool_name"], "read_dtcs");
+    }
+
+    #[test]
+    fn compacts_file_when_it_grows() {
+        let config = HistoryConfig {
+            enabled: true,
+            path: temp_path("compact"),
+            max_entries: 2,
+        };
+        let history = LocalHistory::open(&config).unwrap();
+        for v in 0..7 {
+            history.record(telemetry("engine_rpm", v as f64));
+        }
+        let lines = std::fs::read_to_string(&config.path)
+            .unwrap()
+            .lines()
+            .count();
+        assert!(lines <= config.max_entries * 2, "file has {lines} lines");
+
+        let reopened = LocalHistory::open(&config).unwrap();
+        assert_eq!(reopened.len(), 2);
+    }
+}
@@ -0,0 +1,434 @@
+//! Persistent inbox of received commands.
+//!
+//! Every command envelope is written to disk when it arrives, marked when
+//! execution starts and marked again once its final response has been
+//! published. If the agent restarts in between, the cloud would otherwise
+//! wait forever for a response. On startup the leftovers are replayed:
+//! commands that never started are queued again; commands that were
+//! running cannot be resumed safely (a tool may have been half-way through
+//! a CAN exchange), so they are answered with a failed response saying the
+//! agent restarted during execution.
+//!
+//! Recently finished command IDs are remembered too, so that a command the
+//! broker redelivers after a restart is not executed twice.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use zc_protocol::commands::{
+    CommandEnvelope, CommandResponse, CommandStatus, ErrorCode, InferenceTier,
+};
+
+/// Error reported for a command that was running when the agent stopped.
+pub const RESTARTED_ERROR: &str = "restarted during execution";
+
+/// Finished command IDs remembered for duplicate detection.
+const RECENT_FINISHED: usize = 256;
+
+/// Inbox settings (`[inbox]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InboxConfig {
+    /// Persist received commands. On by default.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Inbox file (JSON lines). Created if missing.
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_path() -> String {
+    "/var/lib/zeroclaw/inbox.jsonl".into()
+}
+
+impl Default for InboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            path: default_path(),
+        }
+    }
+}
+
+/// One line of the inbox file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum InboxRecord {
+    Received { envelope: Box<CommandEnvelope> },
+    Started { command_id: String },
+    Finished { command_id: String },
+}
+
+/// Unfinished commands left over from the previous run.
+#[derive(Debug, Default)]
+pub struct Recovered {
+    /// Never started; safe to run now.
+    pub resume: Vec<CommandEnvelope>,
+    /// Started but not finished; answer with [`interrupted_response`].
+    pub interrupted: Vec<CommandEnvelope>,
+}
+
+/// Command inbox, optionally backed by a JSON-lines file.
+pub struct CommandInbox {
+    path: Option<PathBuf>,
+    inner: Mutex<Inbox>,
+}
+
+#[derive(Default)]
+struct Inbox {
+    pending: Vec<Pending>,
+    finished: VecDeque<String>,
+    /// Lines currently in the file.
+    lines_on_disk: usize,
+}
+
+struct Pending {
+    envelope: CommandEnvelope,
+    started: bool,
+}
+
+impl Inbox {
+    fn apply(&mut self, record: InboxRecord) {
+        match record {
+            InboxRecord::Received { envelope } => {
+                let id = envelope.id.to_string();
+                if !self.contains(&id) {
+                    self.pending.push(Pending {
+                        envelope: *envelope,
+                        started: false,
+                    });
+                }
+            }
+            InboxRecord::Started { command_id } => {
+                if let Some(p) = self.pending_mut(&command_id) {
+                    p.started = true;
+                }
+            }
+            InboxRecord::Finished { command_id } => {
+                self.pending
+                    .retain(|p| p.envelope.id.to_string() != command_id);
+                if !self.finished.contains(&command_id) {
+                    self.finished.push_back(command_id);
+                    while self.finished.len() > RECENT_FINISHED {
+                        self.finished.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    fn contains(&self, command_id: &str) -> bool {
+        self.finished.iter().any(|id| id == command_id)
+            || self
+                .pending
+                .iter()
+                .any(|p| p.envelope.id.to_string() == command_id)
+    }
+
+    fn pending_mut(&mut self, command_id: &str) -> Option<&mut Pending> {
+        self.pending
+            .iter_mut()
+            .find(|p| p.envelope.id.to_string() == command_id)
+    }
+
+    /// The records needed to rebuild the current state.
+    fn snapshot(&self) -> Vec<InboxRecord> {
+        let mut records: Vec<InboxRecord> = self
+            .finished
+            .iter()
+            .map(|id| InboxRecord::Finished {
+                command_id: id.clone(),
+            })
+            .collect();
+        for p in &self.pending {
+            records.push(InboxRecord::Received {
+                envelope: Box::new(p.envelope.clone()),
+            });
+            if p.started {
+                records.push(InboxRecord::Started {
+                    command_id: p.envelope.id.to_string(),
+                });
+            }
+        }
+        records
+    }
+}
+
+impl CommandInbox {
+    /// Inbox that lives only in memory (tests, or when the disk is read-only).
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            inner: Mutex::new(Inbox::default()),
+        }
+    }
+
+    /// Open (or create) the inbox file and load its state. Unparseable
+    /// lines are skipped; the file is compacted on open.
+    pub fn open(config: &InboxConfig) -> std::io::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut inbox = Inbox::default();
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                for line in std::io::BufReader::new(file).lines() {
+                    if let Ok(record) = serde_json::from_str::<InboxRecord>(&line?) {
+                        inbox.apply(record);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let records = inbox.snapshot();
+        rewrite(&path, &records)?;
+        inbox.lines_on_disk = records.len();
+
+        Ok(Self {
+            path: Some(path),
+            inner: Mutex::new(inbox),
+        })
+    }
+
+    /// Number of unfinished commands.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record a received command before it is queued. Returns `false` if
+    /// the command is already pending or recently finished (a redelivery),
+    /// in which case it must not be run again.
+    pub fn receive(&self, envelope: &CommandEnvelope) -> bool {
+        if self
+            .inner
+            .lock()
+            .unwrap()
+            .contains(&envelope.id.to_string())
+        {
+            return false;
+        }
+        self.record(InboxRecord::Received {
+            envelope: Box::new(envelope.clone()),
+        });
+        true
+    }
+
+    /// Mark a command as running.
+    pub fn start(&self, envelope: &CommandEnvelope) {
+        self.record(InboxRecord::Started {
+            command_id: envelope.id.to_string(),
+        });
+    }
+
+    /// Mark a command as done (its final response has been handed to the
+    /// broker, or it was dropped).
+    pub fn finish(&self, envelope: &CommandEnvelope) {
+        self.record(InboxRecord::Finished {
+            command_id: envelope.id.to_string(),
+        });
+    }
+
+    /// Unfinished commands, in arrival order, split by whether they had
+    /// started. They stay in the inbox until finished.
+    pub fn recover(&self) -> Recovered {
+        let inbox = self.inner.lock().unwrap();
+        let mut recovered = Recovered::default();
+        for p in &inbox.pending {
+            if p.started {
+                recovered.interrupted.push(p.envelope.clone());
+            } else {
+                recovered.resume.push(p.envelope.clone());
+            }
+        }
+        recovered
+    }
+
+    /// Apply a record and write it out. Disk errors are logged; the
+    /// in-memory state is kept.
+    fn record(&self, record: InboxRecord) {
+        let mut inbox = self.inner.lock().unwrap();
+        inbox.apply(record.clone());
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        // Compact once the file holds twice what is needed to rebuild the
+        // state, so it stays bounded without rewriting on every change.
+        let live = inbox.finished.len() + 2 * inbox.pending.len();
+        let result = if inbox.lines_on_disk + 1 > 2 * live.max(RECENT_FINISHED) {
+            let records = inbox.snapshot();
+            rewrite(path, &records).map(|()| records.len())
+        } else {
+            append(path, &record).map(|()| inbox.lines_on_disk + 1)
+        };
+        match result {
+            Ok(lines) => inbox.lines_on_disk = lines,
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path.display(), "failed to write command inbox")
+            }
+        }
+    }
+}
+
+/// Final response for a command that was running when the agent stopped.
+pub fn interrupted_response(envelope: &CommandEnvelope) -> CommandResponse {
+    let now = chrono::Utc::now();
+    CommandResponse {
+        command_id: envelope.id,
+        correlation_id: envelope.correlation_id,
+        device_id: envelope.device_id.clone(),
+        status: CommandStatus::Failed,
+        inference_tier: InferenceTier::Local,
+        response_text: Some(
+            "The agent restarted while this command was running; it was not resumed. \
+             Send it again if it is still needed."
+                .into(),
+        ),
+        response_data: None,
+        latency_ms: (now - envelope.created_at).num_milliseconds().max(0) as u64,
+        responded_at: now,
+        error: Some(RESTARTED_ERROR.into()),
+        error_code: Some(ErrorCode::Internal),
+        cached: false,
+    }
+}
+
+/// Append one record. Received envelopes are synced to disk before the
+/// command runs, so a power cut cannot lose them.
+fn append(path: &Path, record: &InboxRecord) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let line = serde_json::to_string(record).map_err(std::io::Error::other)?;
+    writeln!(file, "{line}")?;
+    if matches!(record, InboxRecord::Received { .. }) {
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+fn rewrite(path: &Path, records: &[InboxRecord]) -> std::io::Result<()> {
+    let tmp = path.with_extension("jsonl.tmp");
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        for record in records {
+            let line = serde_json::to_string(record).map_err(std::io::Error::other)?;
+            writeln!(file, "{line}")?;
+        }
+        file.flush()?;
+        file.get_ref().sync_data()?;
+    }
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(nl: &str) -> CommandEnvelope {
+        CommandEnvelope::new("fleet-alpha", "rpi-001", nl, "admin")
+    }
+
+    fn temp_config(name: &str) -> InboxConfig {
+        let dir = std::env::temp_dir().join(format!("zc-inbox-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        InboxConfig {
+            enabled: true,
+            path: dir.join("inbox.jsonl").to_string_lossy().into_owned(),
+        }
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let inbox = CommandInbox::in_memory();
+        let cmd = envelope("read DTCs");
+        assert!(inbox.receive(&cmd));
+        assert!(!inbox.receive(&cmd), "pending duplicate");
+
+        inbox.start(&cmd);
+        inbox.finish(&cmd);
+        assert!(inbox.is_empty());
+        assert!(!inbox.receive(&cmd), "redelivery after finish");
+        assert!(inbox.receive(&envelope("read VIN")));
+    }
+
+    #[test]
+    fn restart_splits_resumable_and_interrupted() {
+        let config = temp_config("recover");
+        let (done, running, queued) = (envelope("a"), envelope("b"), envelope("c"));
+        {
+            let inbox = CommandInbox::open(&config).unwrap();
+            for cmd in [&done, &running, &queued] {
+                assert!(inbox.receive(cmd));
+            }
+            inbox.start(&done);
+            inbox.finish(&done);
+            inbox.start(&running);
+            // Agent stops here.
+        }
+
+        let inbox = CommandInbox::open(&config).unwrap();
+        assert_eq!(inbox.len(), 2);
+        let recovered = inbox.recover();
+        assert_eq!(recovered.interrupted.len(), 1);
+        assert_eq!(recovered.interrupted[0].id, running.id);
+        assert_eq!(recovered.resume.len(), 1);
+        assert_eq!(recovered.resume[0].id, queued.id);
+
+        // The finished command is still recognised as a redelivery.
+        assert!(!inbox.receive(&done));
+
+        inbox.finish(&running);
+        inbox.start(&queued);
+        inbox.finish(&queued);
+        drop(inbox);
+        assert!(CommandInbox::open(&config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn file_stays_bounded() {
+        let config = temp_config("compact");
+        let inbox = CommandInbox::open(&config).unwrap();
+        for i in 0..(RECENT_FINISHED * 2) {
+            let cmd = envelope(&format!("cmd {i}"));
+            inbox.receive(&cmd);
+            inbox.start(&cmd);
+            inbox.finish(&cmd);
+        }
+        let lines = std::fs::read_to_string(&config.path)
+            .unwrap()
+            .lines()
+            .count();
+        assert!(lines <= RECENT_FINISHED * 2, "file has {lines} lines");
+    }
+
+    #[test]
+    fn interrupted_response_reports_restart() {
+        let cmd = envelope("clear DTCs");
+        let response = interrupted_response(&cmd);
+        assert_eq!(response.command_id, cmd.id);
+        assert_eq!(response.correlation_id, cmd.correlation_id);
+        assert_eq!(response.status, CommandStatus::Failed);
+        assert_eq!(response.error.as_deref(), Some(RESTARTED_ERROR));
+        assert_eq!(response.error_code, Some(ErrorCode::Internal));
+    }
+}
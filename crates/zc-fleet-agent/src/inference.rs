@@ -6,8 +6,14 @@
 //! - **shell**: Execute a safe system command on the device
 //! - **reply**: Return a conversational response (no execution)
 
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 use zc_protocol::commands::{ActionKind, ParsedIntent};
+use zc_protocol::conversation::ConversationTurn;
+
+use crate::device_context::DeviceContextStore;
+use crate::runtime_config::RuntimeConfigRx;
 
 /// System prompt teaching three action types: tool, shell, reply.
 const SYSTEM_PROMPT: &str = r#"You are an AI agent running on an IoT edge device in a vehicle fleet. You can do three things:
@@ -15,19 +21,28 @@ const SYSTEM_PROMPT: &str = r#"You are an AI agent running on an IoT edge device
 ## Action 1: tool — Invoke a diagnostic tool
 Use this for vehicle diagnostics and log analysis. Available tools:
 
-1. read_dtcs — Read diagnostic trouble codes from the vehicle ECU. Args: {}
+1. read_dtcs — Read diagnostic trouble codes from the vehicle ECU. Args: {}; add "scope": "pending" for unconfirmed/intermittent faults, "permanent" for codes that survive a clear, or "all"
 2. read_vin — Read the Vehicle Identification Number. Args: {}
-3. read_freeze — Read freeze frame data. Args: {}
+3. read_freeze — Read the freeze frames stored with each DTC (the code that set each frame and its sensor values). Args: {}; add "dtc": "P0300" for one code's frames
 4. read_pid — Read an OBD-II sensor value. Args: {"pid": "0x0C"} (0x0C=RPM, 0x0D=speed, 0x05=coolant temp, 0x11=throttle, 0x2F=fuel level, 0x04=engine load, 0x0F=intake temp, 0x0E=timing advance)
-5. can_monitor — Monitor raw CAN bus traffic. Args: {"duration_secs": 10}
+5. can_monitor — Monitor raw CAN bus traffic. Args: {"duration_secs": 10}; add "capture_errors": true to count bus error frames (bit/stuff/ACK errors, bus-off, restarts); add "filters": [{"id": "0x500", "mask": "0x7F0"}] to capture only some CAN IDs (frames known from the device's DBC files come back decoded into named signals)
 6. read_uds_dtcs — Read DTCs from a UDS ECU (Hella BCR/BCF). Args: {"ecu": "BCR"} or {"ecu": "BCF"}
 7. read_uds_did — Read a Data Identifier from a UDS ECU. Args: {"ecu": "BCR"} (reads all known DIDs) or {"ecu": "BCR", "did": 64773} (specific DID 0xFD05)
 8. uds_session_control — Control diagnostic session on a UDS ECU. Args: {"ecu": "BCR", "session": "extended"} or {"ecu": "BCR", "tester_present": true}
-9. search_logs — Search device logs. Args: {"path": "/var/log/syslog", "query": "error"}
+9. search_logs — Search device logs. Args: {"path": "/var/log/syslog", "query": "error"}; "path" may also be a glob ("/var/log/*.log") or a list of paths; add "include_rotated": true to also search rotated files (syslog.1, syslog.2.gz) for incidents older than the last rotation; "remote://<ip>" reads syslog received from another device on the network (dashcam, telematics unit); all log tools accept "since"/"until" (RFC 3339 or relative like "2h") to limit entries to a time window
 10. analyze_errors — Analyze error patterns in logs. Args: {"path": "/var/log/syslog"}
-11. log_stats — Get log statistics. Args: {"path": "/var/log/syslog"}
-12. tail_logs — Show recent log entries. Args: {"path": "/var/log/syslog", "lines": 50}
-13. query_journal — Query systemd journal for a service. Args: {"unit": "nginx.service", "lines": 50}
+11. log_stats — Get log statistics, including severity counts over time (errors over time). Args: {"path": "/var/log/syslog"}; optional "bucket": "minute", "hour" or "day"
+12. tail_logs — Show recent log entries. Args: {"path": "/var/log/syslog", "lines": 50}; add "follow": true, "follow_secs": 60 to keep streaming new entries (live logs)
+13. query_journal — Query systemd journal for a service. Args: {"unit": "nginx.service", "lines": 50}; optional "priority" ("err"), "since" ("1 hour ago"), "boot" ("-1" for the previous boot)
+14. detect_anomalies — Find log message templates whose rate spiked (or that are new) in the recent window compared to the earlier baseline. Args: {"path": "/var/log/syslog"}; optional "recent" ("10m"), "threshold" (3), "min_count" (3)
+15. pid_burst — Capture a burst of raw samples for one OBD-II PID (detailed investigation of a signal). Args: {"pid": "0x0C", "samples": 50, "interval_ms": 50}
+16. get_local_history — Query the device's own journal of past commands and telemetry (fills gaps after an outage). Args: {"kind": "command", "since_minutes": 120, "status": "failed", "unpublished_only": true, "limit": 50} (all optional; kind is "command" or "telemetry", metric filters telemetry e.g. "engine_rpm")
+17. self_test — Run the device provisioning self-test (CAN interface, log paths, Ollama, broker connectivity, clock, disk space). Args: {}
+18. list_supported_pids — List the OBD-II PIDs the ECU supports (use before read_pid on an unfamiliar vehicle). Args: {}
+19. read_readiness — Read emissions readiness: MIL (check engine light) status, DTC count and which readiness monitors are complete (inspection readiness). Args: {}
+20. read_vehicle_info — Read the ECU's calibration IDs (CALID), calibration verification numbers (CVN) and ECU name, for recall/compliance checks. Args: {}; optional "info": ["calid", "cvn", "ecu_name"]
+21. agent_stats — Show which diagnostic tools are slow or failing on this device: per-tool call counts, failures, timeouts and latency since the agent started. Args: {}; optional "tool": "read_pid"
+22. correlate_faults — Line up DTCs with the CAN/driver log entries logged around their detection, as one timeline (why did this code set?). Args: {"dtcs": ["P0300"], "path": "/var/log/syslog"}; optional "window_secs" (300), "detected_at" (RFC 3339 or relative like "10m"; default now)
 
 Response format: {"action": "tool", "tool_name": "<name>", "tool_args": {<args>}, "confidence": <0.0-1.0>}
 
@@ -76,7 +91,7 @@ Examples:
 - Respond with ONLY a JSON object (no markdown, no explanation)
 - Be generous in interpretation — operators use casual language
 - For vehicle/diagnostic queries → action: tool
-- For ANY log-related queries (show logs, tail logs, search logs, system logs, syslog, recent logs) → action: tool (use tail_logs, search_logs, analyze_errors, or log_stats)
+- For ANY log-related queries (show logs, tail logs, search logs, system logs, syslog, recent logs) → action: tool (use tail_logs, search_logs, analyze_errors, or log_stats; detect_anomalies for unusual activity or spikes)
 - For journal/service log queries (e.g. "show nginx logs", "journal for sshd") → action: tool (use query_journal)
 - For system/OS queries (CPU, memory, disk, network, processes) → action: shell
 - For conversation/greetings → action: reply
@@ -97,10 +112,26 @@ const KNOWN_TOOLS: &[&str] = &[
     "log_stats",
     "tail_logs",
     "query_journal",
+    "detect_anomalies",
+    "pid_burst",
+    "get_local_history",
+    "self_test",
+    "list_supported_pids",
+    "read_readiness",
+    "read_vehicle_info",
+    "agent_stats",
+    "correlate_faults",
 ];
 
 /// Log tools that require a "path" argument.
-const LOG_TOOLS: &[&str] = &["search_logs", "analyze_errors", "log_stats", "tail_logs"];
+const LOG_TOOLS: &[&str] = &[
+    "search_logs",
+    "analyze_errors",
+    "log_stats",
+    "tail_logs",
+    "detect_anomalies",
+    "correlate_faults",
+];
 
 /// Default log path when LLM omits it.
 const DEFAULT_LOG_PATH: &str = "/var/log/syslog";
@@ -151,6 +182,7 @@ fn ensure_log_tool_path(tool_name: &str, mut args: serde_json::Value) -> serde_j
 
 /// Configuration for the local Ollama inference endpoint.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct OllamaConfig {
     /// Ollama HTTP API base URL.
     #[serde(default = "default_host")]
@@ -245,6 +277,10 @@ fn default_action() -> String {
 pub struct OllamaClient {
     client: reqwest::Client,
     config: OllamaConfig,
+    /// Model override from runtime config updates.
+    runtime: Option<RuntimeConfigRx>,
+    /// Vehicle and tools appended to the system prompt.
+    context: Option<Arc<DeviceContextStore>>,
 }
 
 impl OllamaClient {
@@ -253,7 +289,42 @@ impl OllamaClient {
             .timeout(std::time::Duration::from_secs(config.timeout_secs))
             .build()
             .expect("failed to build reqwest client");
-        Self { client, config }
+        Self {
+            client,
+            config,
+            runtime: None,
+            context: None,
+        }
+    }
+
+    /// Use the Ollama model from runtime config updates.
+    pub fn with_runtime(mut self, runtime: RuntimeConfigRx) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Describe this device in the system prompt.
+    pub fn with_context(mut self, context: Arc<DeviceContextStore>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// System prompt, with the device context when there is one and the
+    /// conversation so far.
+    fn system_prompt(&self, history: &[ConversationTurn]) -> String {
+        let prompt = match &self.context {
+            Some(context) => context.snapshot().apply_to(SYSTEM_PROMPT),
+            None => SYSTEM_PROMPT.to_string(),
+        };
+        zc_protocol::conversation::apply_history(&prompt, history)
+    }
+
+    /// Model currently in effect.
+    pub fn model(&self) -> String {
+        match &self.runtime {
+            Some(rx) => rx.borrow().ollama_model.clone(),
+            None => self.config.model.clone(),
+        }
     }
 
     /// Parse a natural-language command into a `ParsedIntent`.
@@ -266,14 +337,26 @@ impl OllamaClient {
     /// Returns `None` if Ollama is unreachable, returns garbage, or
     /// confidence is below threshold.
     pub async fn parse(&self, text: &str) -> Option<ParsedIntent> {
+        self.parse_with_history(text, &[]).await
+    }
+
+    /// [`parse`](Self::parse) a follow-up command, with the earlier turns of
+    /// its conversation (oldest first) in the system prompt.
+    pub async fn parse_with_history(
+        &self,
+        text: &str,
+        history: &[ConversationTurn],
+    ) -> Option<ParsedIntent> {
         let url = format!("{}/api/chat", self.config.host);
+        let model = self.model();
+        let system_prompt = self.system_prompt(history);
 
         let body = ChatRequest {
-            model: &self.config.model,
+            model: &model,
             messages: vec![
                 ChatMessage {
                     role: "system",
-                    content: SYSTEM_PROMPT,
+                    content: &system_prompt,
                 },
                 ChatMessage {
                     role: "user",
@@ -369,6 +452,7 @@ impl OllamaClient {
             tool_name,
             tool_args,
             confidence: raw.confidence,
+            steps: Vec::new(),
         })
     }
 
@@ -406,6 +490,7 @@ impl OllamaClient {
             tool_name: sanitized,
             tool_args: raw.tool_args,
             confidence: raw.confidence,
+            steps: Vec::new(),
         })
     }
 
@@ -418,6 +503,7 @@ impl OllamaClient {
             tool_name: String::new(),
             tool_args: serde_json::json!({ "message": message }),
             confidence: raw.confidence.max(1.0),
+            steps: Vec::new(),
         })
     }
 }
@@ -474,6 +560,107 @@ mod tests {
         assert!((intent.confidence - 0.95).abs() < f64::EPSILON);
     }
 
+    #[tokio::test]
+    async fn device_context_is_added_to_system_prompt() {
+        let server = MockServer::start().await;
+        let client = client_for(&server);
+        assert!(!client.system_prompt(&[]).contains("## This device"));
+
+        let config = crate::config::AgentConfig::from_toml_str(
+            r#"
+fleet_id = "fleet-alpha"
+device_id = "truck-001"
+
+[mqtt]
+broker_host = "broker.example.com"
+client_id = "truck-001"
+client_cert_path = "/certs/cert.pem"
+client_key_path = "/certs/key.pem"
+ca_cert_path = "/certs/ca.pem"
+
+[vehicle]
+make = "Kenworth"
+"#,
+            "agent.toml",
+        )
+        .unwrap();
+        let context = Arc::new(DeviceContextStore::from_config(&config));
+        context.set_tools(vec!["read_pid".into()]);
+        let prompt = client.with_context(context).system_prompt(&[]);
+        assert!(prompt.starts_with(SYSTEM_PROMPT));
+        assert!(prompt.contains("- Vehicle: Kenworth"));
+        assert!(prompt.contains("spark-ignition"));
+        assert!(prompt.contains("- Available tools: read_pid"));
+    }
+
+    #[tokio::test]
+    async fn conversation_history_is_sent_with_follow_ups() {
+        let server = MockServer::start().await;
+        let body = ollama_response(
+            r#"{"action": "tool", "tool_name": "can_monitor", "tool_args": {"interface": "can1"}, "confidence": 0.9}"#,
+        );
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(wiremock::matchers::body_string_contains(
+                "run a CAN monitor on can0",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .mount(&server)
+            .await;
+
+        let client = client_for(&server);
+        let history = [ConversationTurn::new(
+            "run a CAN monitor on can0",
+            None,
+            Some("412 frames"),
+            chrono::Utc::now(),
+        )];
+        assert!(client.parse("now do the same on can1").await.is_none());
+        let intent = client
+            .parse_with_history("now do the same on can1", &history)
+            .await
+            .unwrap();
+        assert_eq!(intent.tool_args["interface"], "can1");
+    }
+
+    #[tokio::test]
+    async fn runtime_model_override_is_used() {
+        let server = MockServer::start().await;
+        let body = ollama_response(
+            r#"{"action": "tool", "tool_name": "read_vin", "tool_args": {}, "confidence": 0.9}"#,
+        );
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(wiremock::matchers::body_partial_json(
+                serde_json::json!({"model": "gemma:2b"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&body))
+            .mount(&server)
+            .await;
+
+        let (tx, rx) = tokio::sync::watch::channel(crate::runtime_config::RuntimeConfig {
+            version: 0,
+            heartbeat_interval_secs: 30,
+            telemetry_window_secs: 10,
+            telemetry_sample_interval_ms: 1000,
+            ollama_model: "phi3:mini".into(),
+            log_paths: vec![],
+            pid_watches: vec![],
+        });
+        let client = client_for(&server).with_runtime(rx);
+        assert_eq!(client.model(), "phi3:mini");
+        assert!(client.parse("read the vin").await.is_none());
+
+        crate::runtime_config::handle_update(
+            &serde_json::json!({"version": 1, "ollama_model": "gemma:2b"}),
+            &tx,
+        )
+        .unwrap();
+        assert_eq!(client.model(), "gemma:2b");
+        let intent = client.parse("read the vin").await.unwrap();
+        assert_eq!(intent.tool_name, "read_vin");
+    }
+
     #[tokio::test]
     async fn parse_tool_backward_compat_no_action() {
         // Old-format JSON without "action" field should default to tool
@@ -4,11 +4,27 @@
 //! access internal types like `CommandExecutor`, `ToolRegistry`, and
 //! `OllamaClient`.
 
+pub mod can_bus;
+pub mod claim;
+pub mod cli;
+pub mod command_timeout;
 pub mod config;
+pub mod device_context;
 pub mod executor;
+pub mod file_transfer;
 pub mod heartbeat;
+pub mod history;
+pub mod inbox;
 pub mod inference;
 pub mod mqtt_loop;
+pub mod pid_watch;
 pub mod registry;
+pub mod result_cache;
+pub mod runtime_config;
+pub mod self_test;
 pub mod shadow_sync;
 pub mod shell;
+pub mod telemetry;
+pub mod tool_stats;
+pub mod updater;
+pub mod vitals;
@@ -6,18 +6,65 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, watch};
 use tracing_subscriber::EnvFilter;
 
-use zc_fleet_agent::config::AgentConfig;
+use zc_canbus_tools::dbc::Dbc;
+use zc_canbus_tools::dtc_db::{self, DtcDatabase, FileDtcDatabase, LayeredDtcDatabase};
+use zc_fleet_agent::can_bus::{self, CanBus, CanBuses};
+use zc_fleet_agent::claim;
+use zc_fleet_agent::cli::{self, Command};
+use zc_fleet_agent::config::{self, AgentConfig};
+use zc_fleet_agent::device_context::DeviceContextStore;
+use zc_fleet_agent::executor::CommandExecutor;
+use zc_fleet_agent::file_transfer::FileTransfers;
+use zc_fleet_agent::history::LocalHistory;
+use zc_fleet_agent::inbox::CommandInbox;
 use zc_fleet_agent::inference;
+use zc_fleet_agent::pid_watch::{self, PidWatches};
 use zc_fleet_agent::registry::ToolRegistry;
+use zc_fleet_agent::result_cache::ResultCache;
+use zc_fleet_agent::runtime_config::{self, RuntimeConfig};
+use zc_fleet_agent::self_test::SelfTest;
 use zc_fleet_agent::shadow_sync::{DeviceShadowState, SharedShadowState};
-use zc_fleet_agent::{heartbeat, mqtt_loop, shadow_sync};
-use zc_mqtt_channel::ShadowClient;
+use zc_fleet_agent::updater::{self, BootOutcome, Updater};
+use zc_fleet_agent::{heartbeat, mqtt_loop, shadow_sync, telemetry};
+use zc_mqtt_channel::{OfflineBuffer, ShadowClient};
+use zc_protocol::self_test::SelfTestTrigger;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let config_path = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run { config_path }) => config_path,
+        Ok(Command::ValidateConfig { config_path }) => match AgentConfig::from_file(&config_path) {
+            Ok(_) => {
+                println!("{config_path}: OK");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        },
+        Ok(Command::GenerateConfig) => {
+            print!("{}", config::DEFAULT_CONFIG_TEMPLATE);
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            // Checked by the self-updater before it swaps a release in.
+            println!("zc-fleet-agent {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
         .json()
@@ -29,11 +76,18 @@ async fn main() -> anyhow::Result<()> {
     );
 
     // ── Load config ─────────────────────────────────────────────
-    let config_path = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "/etc/zeroclaw/agent.toml".to_string());
-
-    let config = AgentConfig::from_file(&config_path)?;
+    let mut config = AgentConfig::from_file(&config_path)?;
+    // A device onboarding with a claim code gets its IDs from the cloud
+    // (once; they are kept for later boots).
+    let claimed_config = match &config.claim {
+        Some(claim_config) => {
+            let identity = claim::identity(&config.mqtt, claim_config).await?;
+            config.fleet_id = identity.fleet_id;
+            config.device_id = identity.device_id;
+            identity.config
+        }
+        None => None,
+    };
     tracing::info!(
         fleet_id = %config.fleet_id,
         device_id = %config.device_id,
@@ -41,86 +95,291 @@ async fn main() -> anyhow::Result<()> {
     );
 
     // ── Build tool registry ─────────────────────────────────────
-    let registry = ToolRegistry::with_defaults();
+    if config.bench_mode {
+        tracing::warn!("bench mode enabled — raw CAN frame transmission (send_frame) available");
+    }
+    // Already validated with the rest of the config.
+    let log_formats = zc_log_tools::CustomFormats::compile(&config.log_formats)?;
+    if !log_formats.is_empty() {
+        tracing::info!(
+            formats = ?log_formats.names().collect::<Vec<_>>(),
+            "custom log formats loaded"
+        );
+    }
+    let mut dtc_layers: Vec<Arc<dyn DtcDatabase>> = Vec::new();
+    for path in &config.dtc_database.files {
+        let db = FileDtcDatabase::load(path)?;
+        tracing::info!(path = %path, codes = db.len(), "custom DTC codes loaded");
+        dtc_layers.push(Arc::new(db));
+    }
+    dtc_layers.push(dtc_db::builtin());
+    let dtc_db = LayeredDtcDatabase::new(dtc_layers)
+        .with_manufacturer(config.dtc_database.manufacturer.clone());
+    let mut dbc = Dbc::default();
+    for path in &config.dbc.files {
+        let file = Dbc::load(path)?;
+        tracing::info!(path = %path, messages = file.len(), "DBC file loaded");
+        dbc.merge(file);
+    }
+    let registry = ToolRegistry::configured(
+        config.bench_mode,
+        Arc::new(log_formats),
+        Arc::new(dtc_db),
+        Arc::new(dbc),
+    );
     tracing::info!(tool_count = registry.len(), "tool registry initialized");
 
+    // ── Self-update ─────────────────────────────────────────────
+    // A release on trial that keeps failing to come up is rolled back
+    // here, before it gets another try at connecting.
+    let updater = match Updater::new(&config.update) {
+        Ok(u) => Some(u),
+        Err(e) => {
+            tracing::warn!(error = %e, "self-update unavailable");
+            None
+        }
+    };
+    if let Some(updater) = &updater {
+        match updater.on_boot() {
+            BootOutcome::Idle => {}
+            BootOutcome::Trial(attempt) => {
+                tracing::info!(attempt, "starting agent release on trial");
+            }
+            BootOutcome::RolledBack => {
+                tracing::warn!("agent release rolled back, restarting the previous one");
+                std::process::exit(updater::RESTART_EXIT_CODE);
+            }
+        }
+    }
+
+    let file_transfers = FileTransfers::new(&config.file_transfer);
+
     // ── MQTT channel ────────────────────────────────────────────
-    let (channel, eventloop) = if config.mqtt.use_tls {
-        zc_mqtt_channel::MqttChannel::new(&config.mqtt, &config.fleet_id, &config.device_id)?
-    } else {
+    if !config.mqtt.use_tls {
         tracing::info!("MQTT plaintext mode (no TLS)");
-        zc_mqtt_channel::MqttChannel::new_plaintext(
-            &config.mqtt.broker_host,
-            config.mqtt.broker_port,
-            &config.mqtt.client_id,
-            &config.fleet_id,
-            &config.device_id,
-        )
+    }
+    let (channel, eventloop) =
+        zc_mqtt_channel::MqttChannel::new(&config.mqtt, &config.fleet_id, &config.device_id)?;
+    let buffer_config = &config.mqtt.offline_buffer;
+    let channel = if buffer_config.enabled {
+        let buffer = match OfflineBuffer::open(buffer_config) {
+            Ok(b) => {
+                tracing::info!(path = ?buffer_config.path, queued = b.len(), "offline buffer opened");
+                b
+            }
+            Err(e) => {
+                tracing::warn!(path = ?buffer_config.path, error = %e, "offline buffer file unavailable, keeping it in memory");
+                OfflineBuffer::in_memory(buffer_config.capacity)
+            }
+        };
+        channel.with_offline_buffer(buffer)
+    } else {
+        channel
     };
 
     // Subscribe to inbound topics
     channel.subscribe_commands().await?;
     channel.subscribe_shadow_delta().await?;
     channel.subscribe_config().await?;
+    channel.subscribe_transfers().await?;
     tracing::info!("MQTT subscriptions active");
 
+    // ── Runtime-adjustable settings ────────────────────────────
+    // Updated by broadcast ConfigUpdate messages in the MQTT loop.
+    let (config_tx, config_rx) = watch::channel(RuntimeConfig::from_agent(&config));
+    if let Some(update) = &claimed_config {
+        match runtime_config::handle_update(update, &config_tx) {
+            Ok(version) => tracing::info!(version, "claimed runtime config applied"),
+            Err(e) => tracing::warn!(error = %e, "claimed runtime config not applied"),
+        }
+    }
+
+    // ── Device context ──────────────────────────────────────────
+    // Vehicle, CAN link and tools, added to local inference prompts.
+    let device_context = Arc::new(DeviceContextStore::from_config(&config));
+    let vehicle = device_context.snapshot().vehicle;
+    if !vehicle.is_empty() {
+        tracing::info!(
+            vin = ?vehicle.vin,
+            make = ?vehicle.make,
+            fuel_type = ?vehicle.fuel_type,
+            "vehicle profile loaded"
+        );
+    }
+
     // ── Ollama local inference ──────────────────────────────────
-    let ollama_client = if config.ollama.enabled {
+    // Structured-only devices never run an LLM, whatever [ollama] says.
+    let ollama_enabled = config.ollama.enabled && !config.structured_commands_only;
+    let ollama_client = if ollama_enabled {
         tracing::info!(
             host = %config.ollama.host,
             model = %config.ollama.model,
             "ollama local inference enabled"
         );
-        Some(inference::OllamaClient::new(config.ollama.clone()))
+        Some(
+            inference::OllamaClient::new(config.ollama.clone())
+                .with_runtime(config_rx.clone())
+                .with_context(device_context.clone()),
+        )
+    } else if config.structured_commands_only {
+        tracing::info!("structured commands only, local inference disabled");
+        None
     } else {
         tracing::info!("ollama local inference disabled");
         None
     };
     let ollama_ref = ollama_client.as_ref();
 
-    // ── CAN interface ─────────────────────────────────────────
-    let can_interface: Box<dyn zc_canbus_tools::CanInterface> = match config
-        .can_interface
-        .as_deref()
-    {
-        #[cfg(target_os = "linux")]
-        Some(iface) => match zc_canbus_tools::SocketCanInterface::new(iface) {
-            Ok(s) => {
-                tracing::info!(interface = iface, "real SocketCAN interface opened");
-                Box::new(s)
+    // ── CAN buses ───────────────────────────────────────────────
+    let bus_configs = config.can_buses();
+    if bus_configs.is_empty() {
+        tracing::info!("no CAN interface configured, using mock");
+    }
+    let mut bus_interfaces: Vec<Box<dyn zc_canbus_tools::CanInterface>> = Vec::new();
+    for bus in &bus_configs {
+        bus_interfaces.push(can_bus::open(bus).await);
+    }
+    let mock_can = zc_canbus_tools::MockCanInterface::new();
+    let can_buses = CanBuses::new(
+        bus_configs
+            .iter()
+            .zip(&bus_interfaces)
+            .map(|(bus, iface)| CanBus::new(&bus.name, bus.protocol, &**iface))
+            .collect(),
+        &mock_can,
+    );
+    // Telemetry samples the default bus.
+    let can_interface = can_buses.default_bus().interface;
+    let bus_lock = can_buses.default_bus().shared_lock();
+    let can_available = !bus_configs.is_empty();
+
+    // ── Log source ──────────────────────────────────────────────
+    // Syslog from other devices on the network is read as remote://<host>.
+    let syslog_spool = Arc::new(zc_log_tools::SyslogSpool::from_config(
+        &config.remote_syslog,
+    ));
+    let log_source: Box<dyn zc_log_tools::LogSource> = if config.remote_syslog.enabled {
+        Box::new(zc_log_tools::UdpSyslogSource::new(
+            zc_log_tools::FileLogSource,
+            syslog_spool.clone(),
+        ))
+    } else {
+        Box::new(zc_log_tools::FileLogSource)
+    };
+    let syslog_listener = if config.remote_syslog.enabled {
+        match zc_log_tools::UdpSyslogListener::bind(&config.remote_syslog.bind, syslog_spool).await
+        {
+            Ok(listener) => {
+                // Validated at load.
+                let listener = listener
+                    .with_allowed_senders(config.remote_syslog.sender_nets().unwrap_or_default());
+                tracing::info!(bind = %config.remote_syslog.bind, spool_dir = %config.remote_syslog.spool_dir, "remote syslog listener started");
+                Some(listener)
             }
             Err(e) => {
-                tracing::warn!(interface = iface, error = %e, "SocketCAN open failed, falling back to mock");
-                Box::new(zc_canbus_tools::MockCanInterface::new())
+                tracing::warn!(error = %e, "remote syslog listener unavailable");
+                None
             }
-        },
-        #[cfg(not(target_os = "linux"))]
-        Some(iface) => {
-            tracing::warn!(
-                interface = iface,
-                "SocketCAN not available on this platform, using mock"
-            );
-            Box::new(zc_canbus_tools::MockCanInterface::new())
-        }
-        None => {
-            tracing::info!("no CAN interface configured, using mock");
-            Box::new(zc_canbus_tools::MockCanInterface::new())
         }
+    } else {
+        None
     };
-    let can_available = config.can_interface.is_some();
 
-    // ── Log source ──────────────────────────────────────────────
-    let log_source = zc_log_tools::FileLogSource;
+    // ── Local history journal ───────────────────────────────────
+    let history = if config.history.enabled {
+        match LocalHistory::open(&config.history) {
+            Ok(h) => {
+                tracing::info!(path = %config.history.path, entries = h.len(), "local history journal opened");
+                h
+            }
+            Err(e) => {
+                tracing::warn!(path = %config.history.path, error = %e, "history journal unavailable, keeping it in memory");
+                LocalHistory::in_memory(config.history.max_entries)
+            }
+        }
+    } else {
+        tracing::info!("local history disabled");
+        LocalHistory::in_memory(1)
+    };
+    let history_ref = config.history.enabled.then_some(&history);
+
+    // ── Command inbox ───────────────────────────────────────────
+    let inbox = if config.inbox.enabled {
+        match CommandInbox::open(&config.inbox) {
+            Ok(i) => {
+                tracing::info!(path = %config.inbox.path, unfinished = i.len(), "command inbox opened");
+                i
+            }
+            Err(e) => {
+                tracing::warn!(path = %config.inbox.path, error = %e, "command inbox unavailable, keeping it in memory");
+                CommandInbox::in_memory()
+            }
+        }
+    } else {
+        tracing::info!("command inbox disabled");
+        CommandInbox::in_memory()
+    };
+    let inbox_ref = config.inbox.enabled.then_some(&inbox);
+
+    // ── Result cache ────────────────────────────────────────────
+    let result_cache = ResultCache::new(&config.result_cache);
+    if config.result_cache.enabled {
+        tracing::info!(
+            max_entries = config.result_cache.max_entries,
+            "tool result cache enabled"
+        );
+    }
+
+    // ── Command executor ────────────────────────────────────────
+    let self_test = SelfTest::new(&config).with_runtime(config_rx.clone());
+    let pid_watches = PidWatches::new();
+    let mut executor = CommandExecutor::new(&registry, can_interface, &*log_source, ollama_ref)
+        .with_can_buses(can_buses)
+        .with_self_test(&self_test)
+        .with_pid_watches(&pid_watches)
+        .with_device_context(&device_context)
+        .with_timeouts(config.command_timeouts.clone());
+    if let Some(history) = history_ref {
+        executor = executor.with_history(history);
+    }
+    if config.result_cache.enabled {
+        executor = executor.with_result_cache(&result_cache);
+    }
+    if config.structured_commands_only {
+        executor = executor.structured_only();
+    }
+    device_context.set_tools(executor.tool_names());
+
+    // ── First-boot self-test ────────────────────────────────────
+    // Queued on the channel now, sent once the MQTT loop below connects.
+    if self_test.first_boot_pending() {
+        let report = self_test.run(SelfTestTrigger::FirstBoot).await;
+        tracing::info!(
+            passed = report.passed,
+            failed = ?report.failed_checks(),
+            "first-boot self-test finished"
+        );
+        match channel.publish_self_test(&report).await {
+            Ok(()) => {
+                if let Err(e) = self_test.mark_done(&report) {
+                    tracing::warn!(error = %e, "cannot write self-test marker, will re-run on next start");
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to publish self-test report"),
+        }
+    }
 
     // ── Shadow state ────────────────────────────────────────────
     let shadow_state: SharedShadowState = Arc::new(RwLock::new(DeviceShadowState {
         tool_count: registry.len(),
+        tools: executor.tool_names(),
+        vehicle: device_context.snapshot().vehicle,
         can_status: if can_available {
             "running".to_string()
         } else {
             "stopped".to_string()
         },
-        ollama_status: if config.ollama.enabled {
+        ollama_status: if ollama_enabled {
             "enabled".to_string()
         } else {
             "disabled".to_string()
@@ -129,6 +388,7 @@ async fn main() -> anyhow::Result<()> {
     }));
 
     let shadow_client = ShadowClient::new(&channel, &config.fleet_id, &config.device_id);
+    let tool_catalog = executor.tool_catalog();
 
     // ── Start background tasks ──────────────────────────────────
     let start_time = tokio::time::Instant::now();
@@ -137,16 +397,30 @@ async fn main() -> anyhow::Result<()> {
 
     tokio::select! {
         // Drive the MQTT event loop + dispatch commands
-        () = mqtt_loop::run(eventloop, &channel, &registry, &*can_interface, &log_source, ollama_ref, &shadow_state) => {
+        () = mqtt_loop::run(
+            eventloop,
+            &config.mqtt,
+            &channel,
+            &executor,
+            config.max_concurrent_commands,
+            config.command_rate_limit,
+            &shadow_state,
+            &config_tx,
+            history_ref,
+            inbox_ref,
+            updater.as_ref(),
+            &file_transfers,
+        ) => {
             tracing::error!("MQTT loop exited unexpectedly");
         }
         // Publish periodic heartbeats
         () = heartbeat::run(
             &channel,
-            Duration::from_secs(config.heartbeat_interval_secs),
+            &config.mqtt,
+            config_rx.clone(),
             start_time,
             can_available,
-            config.ollama.enabled,
+            ollama_enabled,
         ) => {
             tracing::error!("heartbeat loop exited unexpectedly");
         }
@@ -156,9 +430,28 @@ async fn main() -> anyhow::Result<()> {
             &shadow_state,
             Duration::from_secs(config.shadow_sync_interval_secs),
             start_time,
+            config_rx.clone(),
+            &tool_catalog,
         ) => {
             tracing::error!("shadow sync loop exited unexpectedly");
         }
+        // Sample PIDs and publish windowed aggregates
+        () = telemetry::run(&channel, can_interface, &bus_lock, &config.telemetry, config_rx.clone(), history_ref) => {
+            tracing::error!("telemetry loop exited unexpectedly");
+        }
+        // Sample watched PIDs and publish threshold crossings
+        () = pid_watch::run(&channel, can_interface, &bus_lock, &pid_watches, config_rx.clone(), history_ref) => {
+            tracing::error!("PID watch loop exited unexpectedly");
+        }
+        // Spool syslog messages from other devices on the network
+        () = async {
+            match &syslog_listener {
+                Some(listener) => listener.run().await,
+                None => std::future::pending().await,
+            }
+        } => {
+            tracing::error!("remote syslog listener exited unexpectedly");
+        }
         // Graceful shutdown on SIGINT/SIGTERM
         _ = tokio::signal::ctrl_c() => {
             tracing::info!("shutdown signal received");
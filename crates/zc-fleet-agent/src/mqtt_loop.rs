@@ -1,19 +1,41 @@
 //! MQTT event loop driver and incoming message dispatcher.
 //!
-//! Drives the rumqttc event loop in a loop, extracting incoming
+//! Drives the channel's event loop (MQTT 3.1.1 or 5), extracting incoming
 //! publishes and dispatching them through the command executor.
 
-use rumqttc::{Event, EventLoop, Packet};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::{Duration, Instant};
 
-use zc_canbus_tools::CanInterface;
-use zc_log_tools::LogSource;
-use zc_mqtt_channel::{Channel, IncomingMessage, MqttChannel, ShadowClient, classify};
-use zc_protocol::commands::{CommandResponse, CommandStatus};
+use rumqttc::QoS;
+use tokio::signal::unix::{Signal, SignalKind, signal};
+use tokio::sync::oneshot;
+
+use zc_mqtt_channel::{
+    Channel, ChannelEvent, IncomingMessage, MqttChannel, MqttConfig, MqttEventLoop, ShadowClient,
+    TransferClient, classify,
+};
+use zc_protocol::commands::{
+    CommandCancel, CommandEnvelope, CommandResponse, CommandResponseChunk, CommandStatus,
+    ErrorCode, InferenceTier,
+};
+use zc_protocol::rate_limit::{RateLimit, TokenBucket};
+use zc_protocol::self_test::SelfTestReport;
+use zc_protocol::shadows::{
+    AgentUpdate, CERTIFICATE_SHADOW, CertificateRotation, UPDATE_SHADOW, UpdateReport, UpdateStatus,
+};
+use zc_protocol::topics;
 
 use crate::executor::CommandExecutor;
-use crate::inference::OllamaClient;
-use crate::registry::ToolRegistry;
-use crate::shadow_sync::SharedShadowState;
+use crate::file_transfer::FileTransfers;
+use crate::history::LocalHistory;
+use crate::inbox::{self, CommandInbox};
+use crate::runtime_config::{self, RuntimeConfigTx, UpdateError};
+use crate::self_test;
+use crate::shadow_sync::{self, SharedShadowState};
+use crate::updater::{self, Updater};
 
 /// Maximum MQTT payload size in bytes.
 /// AWS IoT Core supports 128 KB payloads. We use 128 KB minus headroom
@@ -22,115 +44,688 @@ const MAX_MQTT_PAYLOAD: usize = 128 * 1024;
 
 /// Drive the MQTT event loop and dispatch incoming messages.
 ///
-/// Runs forever until the event loop returns an unrecoverable error or
-/// the task is cancelled. Intended to be spawned as a background tokio task.
+/// Commands start in arrival order, up to `max_concurrent` at a time; CAN
+/// tools among them take turns on the bus inside the executor. The event
+/// loop keeps polling while commands run so that cancellations, shadow
+/// deltas and further commands are still received. Runs forever until the
+/// task is cancelled. Intended to be spawned as a background tokio task.
+///
+/// Commands received faster than `rate_limit` allows are answered
+/// `throttled` at once and never queued, so a flood cannot pile up behind
+/// the execution slots.
+///
+/// With an `inbox`, commands left unfinished by the previous run are
+/// replayed first (see [`crate::inbox`]) and redelivered commands are
+/// dropped.
+///
+/// The TLS client certificate is reloaded from the paths in `mqtt`, with a
+/// reconnect, on SIGHUP and after a rotation arrives on the `certificate`
+/// shadow.
+///
+/// With an `updater`, a release delivered on the `update` shadow is
+/// installed in the background; once it is in place no further commands
+/// start, and the process exits for systemd to start the new release as
+/// soon as the running ones finish (see [`crate::updater`]).
+///
+/// File transfer offers and chunks go to `transfers`; uploads run
+/// alongside the event loop like commands (see [`crate::file_transfer`]).
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
-    mut eventloop: EventLoop,
+    mut eventloop: MqttEventLoop,
+    mqtt: &MqttConfig,
     channel: &MqttChannel,
-    registry: &ToolRegistry,
-    can_interface: &dyn CanInterface,
-    log_source: &dyn LogSource,
-    ollama: Option<&OllamaClient>,
+    executor: &CommandExecutor<'_>,
+    max_concurrent: usize,
+    rate_limit: RateLimit,
     shadow_state: &SharedShadowState,
+    config_tx: &RuntimeConfigTx,
+    history: Option<&LocalHistory>,
+    inbox: Option<&CommandInbox>,
+    updater: Option<&Updater>,
+    transfers: &FileTransfers,
 ) {
-    let executor = CommandExecutor::new(registry, can_interface, log_source, ollama);
     let shadow_client = ShadowClient::new(channel, channel.fleet_id(), channel.device_id());
+    let transfer_client = TransferClient::new(channel, channel.fleet_id(), channel.device_id());
+
+    let mut queue: VecDeque<CommandEnvelope> = VecDeque::new();
+    let mut running: Vec<RunningCommand<'_>> = Vec::new();
+    let mut flood_guard = TokenBucket::new(rate_limit, Instant::now());
+    let mut install: Option<PendingInstall<'_>> = None;
+    let mut active_transfers: Vec<ActiveTransfer<'_>> = Vec::new();
+    let mut restart = false;
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => Some(hangup),
+        Err(e) => {
+            tracing::warn!(error = %e, "cannot listen for SIGHUP, certificate reload by signal disabled");
+            None
+        }
+    };
+
+    if let Some(inbox) = inbox {
+        let recovered = inbox.recover();
+        for envelope in recovered.interrupted {
+            tracing::warn!(command_id = %envelope.id, "command was running when the agent stopped");
+            let response = inbox::interrupted_response(&envelope);
+            publish_response(channel, &envelope, response, history, Some(inbox)).await;
+        }
+        if !recovered.resume.is_empty() {
+            tracing::info!(
+                count = recovered.resume.len(),
+                "replaying commands received before restart"
+            );
+        }
+        queue.extend(recovered.resume);
+    }
 
     loop {
-        match eventloop.poll().await {
-            Ok(event) => {
-                if let Event::Incoming(Packet::Publish(publish)) = event {
-                    let msg = classify(&publish);
-                    handle_message(msg, channel, &executor, shadow_state, &shadow_client).await;
+        if restart && running.is_empty() {
+            tracing::info!("restarting into the new agent release");
+            std::process::exit(updater::RESTART_EXIT_CODE);
+        }
+        while !restart
+            && running.len() < max_concurrent
+            && let Some(envelope) = queue.pop_front()
+        {
+            if let Some(inbox) = inbox {
+                inbox.start(&envelope);
+            }
+            let (cancel_tx, cancel_rx) = oneshot::channel();
+            running.push(RunningCommand {
+                command_id: envelope.id.to_string(),
+                cancel: Some(cancel_tx),
+                task: Box::pin(process_command(
+                    envelope,
+                    channel,
+                    executor,
+                    shadow_state,
+                    history,
+                    inbox,
+                    cancel_rx,
+                )),
+            });
+        }
+
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                Ok(ChannelEvent::Publish { publish, .. }) => match classify(&publish) {
+                    IncomingMessage::Command(envelope) => {
+                        // Fleet broadcasts run under this device's derived ID,
+                        // which the cloud recorded when it fanned out.
+                        let envelope = if envelope.is_broadcast() {
+                            envelope.for_device(channel.device_id())
+                        } else {
+                            envelope
+                        };
+                        if inbox.is_some_and(|inbox| !inbox.receive(&envelope)) {
+                            tracing::info!(command_id = %envelope.id, "ignoring redelivered command");
+                        } else if let Err(retry_after) = flood_guard.try_take(Instant::now()) {
+                            tracing::warn!(
+                                command_id = %envelope.id,
+                                from = %envelope.initiated_by,
+                                retry_after_ms = retry_after.as_millis() as u64,
+                                "command rate limit exceeded, throttling"
+                            );
+                            let response = throttled_response(&envelope, retry_after);
+                            publish_response(channel, &envelope, response, history, inbox).await;
+                        } else {
+                            tracing::info!(
+                                command_id = %envelope.id,
+                                correlation_id = %envelope.correlation_id,
+                                from = %envelope.initiated_by,
+                                queued = queue.len() + running.len(),
+                                "received command"
+                            );
+                            queue.push_back(envelope);
+                        }
+                    }
+                    IncomingMessage::CommandCancel(cancel) => {
+                        handle_cancel(cancel, &mut queue, &mut running, channel, history, inbox)
+                            .await;
+                    }
+                    IncomingMessage::TransferOffer(offer) => {
+                        active_transfers.push(Box::pin(transfers.handle_offer(offer, &transfer_client)));
+                    }
+                    IncomingMessage::TransferChunk(chunk) => {
+                        transfers.handle_chunk(chunk, &transfer_client).await;
+                    }
+                    msg => match handle_message(msg, &shadow_client, shadow_state, config_tx, mqtt)
+                        .await
+                    {
+                        Followup::None => {}
+                        Followup::ReloadTls => reload_tls(&mut eventloop, mqtt),
+                        Followup::Install(update) => match updater {
+                            _ if install.is_some() || restart => {
+                                tracing::debug!(version = %update.agent_version, "agent update already in progress");
+                            }
+                            Some(updater) => {
+                                tracing::info!(version = %update.agent_version, url = %update.url, "installing agent release");
+                                install = Some(Box::pin(async move {
+                                    let result = updater.install(&update).await;
+                                    (update, result)
+                                }));
+                            }
+                            None => {
+                                let report = UpdateReport::new(
+                                    &update,
+                                    UpdateStatus::Failed,
+                                    Some("self-update is unavailable on this device".into()),
+                                );
+                                report_update(&shadow_client, &report).await;
+                            }
+                        },
+                    },
+                },
+                Ok(ChannelEvent::Connected { .. }) => {
+                    let outcome = channel.health().on_connected();
+                    if outcome.storm_started {
+                        tracing::warn!(
+                            reconnects = channel.health().snapshot().reconnects,
+                            "MQTT reconnect storm: connection keeps dropping"
+                        );
+                    }
+                    tracing::info!("connected to broker, resyncing shadows");
+                    if !channel.publish_online() {
+                        tracing::warn!("could not queue online presence");
+                    }
+                    channel.on_connected();
+                    shadow_state.write().await.mqtt = Some(channel.health().snapshot());
+                    shadow_sync::resync(&shadow_client).await;
+                    if let Some(report) = updater.and_then(Updater::on_connected) {
+                        report_update(&shadow_client, &report).await;
+                    }
+                }
+                Ok(ChannelEvent::Rejected(e)) => {
+                    tracing::warn!(error = %e, "broker rejected a request");
+                }
+                Ok(ChannelEvent::Other) => {
+                    // Keep draining what the first flush after reconnect
+                    // could not fit into the client's queue.
+                    if channel.offline_buffer().is_some_and(|b| !b.is_empty()) {
+                        channel.flush_offline();
+                    }
+                }
+                Err(e) => {
+                    channel.on_disconnected();
+                    let delay = channel.health().on_error(&e);
+                    shadow_state.write().await.mqtt = Some(channel.health().snapshot());
+                    tracing::error!(
+                        error = %e,
+                        retry_in_ms = delay.as_millis() as u64,
+                        "MQTT event loop error, reconnecting"
+                    );
+                    tokio::time::sleep(delay).await;
                 }
+            },
+            () = next_finished(&mut running) => {}
+            () = next_transfer_done(&mut active_transfers) => {}
+            (update, result) = next_install(&mut install) => match result {
+                Ok(()) => restart = true,
+                // Already reported when it was rolled back.
+                Err(e @ updater::UpdateError::PreviouslyRolledBack(_)) => {
+                    tracing::info!(version = %update.agent_version, reason = %e, "skipping agent release");
+                }
+                Err(e) => {
+                    tracing::warn!(version = %update.agent_version, error = %e, "agent update failed");
+                    let report = UpdateReport::new(&update, UpdateStatus::Failed, Some(e.to_string()));
+                    report_update(&shadow_client, &report).await;
+                }
+            },
+            Some(()) = next_hangup(&mut hangup) => {
+                tracing::info!("SIGHUP received, reloading TLS certificates");
+                reload_tls(&mut eventloop, mqtt);
+            }
+        }
+    }
+}
+
+/// A release being downloaded and installed.
+type PendingInstall<'a> =
+    Pin<Box<dyn Future<Output = (AgentUpdate, Result<(), updater::UpdateError>)> + 'a>>;
+
+/// Wait for the install in progress, if any, and clear it.
+async fn next_install(
+    install: &mut Option<PendingInstall<'_>>,
+) -> (AgentUpdate, Result<(), updater::UpdateError>) {
+    match install {
+        Some(future) => {
+            let outcome = future.await;
+            *install = None;
+            outcome
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for the next SIGHUP. Never resolves without a signal handler.
+async fn next_hangup(hangup: &mut Option<Signal>) -> Option<()> {
+    match hangup {
+        Some(hangup) => hangup.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Reconnect with the certificates now on disk. A failed reload keeps the
+/// current connection.
+fn reload_tls(eventloop: &mut MqttEventLoop, mqtt: &MqttConfig) {
+    if !mqtt.use_tls {
+        tracing::warn!("TLS is disabled, no certificates to reload");
+        return;
+    }
+    match eventloop.reload_tls(mqtt) {
+        Ok(()) => tracing::info!("TLS certificates reloaded, reconnecting"),
+        Err(e) => tracing::error!(error = %e, "TLS reload failed, keeping the current connection"),
+    }
+}
+
+/// Wait until one of the running commands finishes and drop it. Never
+/// resolves while nothing runs.
+async fn next_finished(running: &mut Vec<RunningCommand<'_>>) {
+    std::future::poll_fn(|cx| {
+        match running
+            .iter_mut()
+            .position(|command| command.task.as_mut().poll(cx).is_ready())
+        {
+            Some(i) => {
+                running.swap_remove(i);
+                Poll::Ready(())
             }
-            Err(e) => {
-                tracing::error!(error = %e, "MQTT event loop error, reconnecting in 5s");
-                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            None => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// A file transfer offer being answered (or a file being uploaded).
+type ActiveTransfer<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// Wait until one of the active transfers finishes and drop it. Never
+/// resolves while none is active.
+async fn next_transfer_done(transfers: &mut Vec<ActiveTransfer<'_>>) {
+    std::future::poll_fn(|cx| {
+        match transfers
+            .iter_mut()
+            .position(|transfer| transfer.as_mut().poll(cx).is_ready())
+        {
+            Some(i) => {
+                drop(transfers.swap_remove(i));
+                Poll::Ready(())
             }
+            None => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// A command holding one of the execution slots.
+struct RunningCommand<'a> {
+    command_id: String,
+    /// Taken when a cancellation is forwarded to the task.
+    cancel: Option<oneshot::Sender<CommandCancel>>,
+    task: Pin<Box<dyn Future<Output = ()> + 'a>>,
+}
+
+/// Abort a running command, or drop a command still waiting in the queue.
+///
+/// A running command is stopped by its task, which publishes the
+/// `cancelled` response. A queued command never starts; its `cancelled`
+/// response is published here. Cancels for unknown or already finished
+/// commands are ignored.
+async fn handle_cancel(
+    cancel: CommandCancel,
+    queue: &mut VecDeque<CommandEnvelope>,
+    running: &mut [RunningCommand<'_>],
+    channel: &MqttChannel,
+    history: Option<&LocalHistory>,
+    inbox: Option<&CommandInbox>,
+) {
+    let command_id = cancel.command_id.to_string();
+    tracing::info!(
+        command_id = %command_id,
+        requested_by = %cancel.requested_by,
+        "received cancel request"
+    );
+
+    if let Some(command) = running.iter_mut().find(|c| c.command_id == command_id) {
+        if let Some(tx) = command.cancel.take() {
+            let _ = tx.send(cancel);
         }
+        return;
+    }
+
+    if let Some(pos) = queue.iter().position(|e| e.id == cancel.command_id) {
+        let envelope = queue.remove(pos).expect("position is in range");
+        let response = cancelled_response(&envelope, &cancel, 0);
+        publish_response(channel, &envelope, response, history, inbox).await;
+        return;
     }
+
+    tracing::debug!(command_id = %command_id, "cancel for a command that is not running or queued");
 }
 
-async fn handle_message(
-    msg: IncomingMessage,
+/// Acknowledge, execute and report one command.
+async fn process_command(
+    envelope: CommandEnvelope,
     channel: &MqttChannel,
     executor: &CommandExecutor<'_>,
     shadow_state: &SharedShadowState,
-    shadow_client: &ShadowClient<'_, MqttChannel>,
+    history: Option<&LocalHistory>,
+    inbox: Option<&CommandInbox>,
+    cancel: oneshot::Receiver<CommandCancel>,
 ) {
-    match msg {
-        IncomingMessage::Command(envelope) => {
+    // Send acknowledgement
+    let ack = serde_json::json!({
+        "command_id": envelope.id,
+        "status": "processing",
+    });
+    if let Err(e) = channel.publish_ack(&ack).await {
+        tracing::warn!(error = %e, "failed to publish ack");
+    }
+
+    // Execute the command, streaming partial results as they arrive
+    let stream_topic = topics::command_stream(channel.fleet_id(), channel.device_id());
+    let response = execute_cancellable(&envelope, executor, channel, &stream_topic, cancel).await;
+
+    // Update shadow state with last command info.
+    {
+        let mut state = shadow_state.write().await;
+        state.last_command_id = Some(envelope.id.to_string());
+        state.last_command_tool = response
+            .response_data
+            .as_ref()
+            .and_then(|d| d.get("tool_name"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        state.last_command_at = Some(chrono::Utc::now().to_rfc3339());
+        // A read_vin or list_supported_pids may have taught the agent more
+        // about its vehicle.
+        if let Some(context) = executor.device_context() {
+            state.vehicle = context.snapshot().vehicle;
+        }
+        state.tool_stats = executor.tool_stats().snapshot();
+    }
+
+    match response.status {
+        CommandStatus::Completed => {
             tracing::info!(
                 command_id = %envelope.id,
-                from = %envelope.initiated_by,
-                "received command"
+                latency_ms = response.latency_ms,
+                "command completed"
+            );
+        }
+        CommandStatus::Cancelled => {
+            tracing::info!(
+                command_id = %envelope.id,
+                latency_ms = response.latency_ms,
+                "command cancelled"
+            );
+        }
+        _ => {
+            tracing::warn!(
+                command_id = %envelope.id,
+                error = ?response.error,
+                "command failed"
             );
+        }
+    }
 
-            // Send acknowledgement
-            let ack = serde_json::json!({
-                "command_id": envelope.id,
-                "status": "processing",
-            });
-            if let Err(e) = channel.publish_ack(&ack).await {
-                tracing::warn!(error = %e, "failed to publish ack");
+    publish_self_test_report(channel, &response).await;
+    publish_response(channel, &envelope, response, history, inbox).await;
+}
+
+/// Also publish a `self_test` result on the self-test topic, where the
+/// cloud keeps it as the device's verification record.
+async fn publish_self_test_report(channel: &MqttChannel, response: &CommandResponse) {
+    let Some(data) = response
+        .response_data
+        .as_ref()
+        .filter(|d| d["tool_name"] == self_test::TOOL_NAME)
+    else {
+        return;
+    };
+    match serde_json::from_value::<SelfTestReport>(data["data"].clone()) {
+        Ok(report) => {
+            if let Err(e) = channel.publish_self_test(&report).await {
+                tracing::warn!(error = %e, "failed to publish self-test report");
             }
+        }
+        Err(e) => tracing::warn!(error = %e, "malformed self-test result"),
+    }
+}
 
-            // Execute the command
-            let response = executor.execute(&envelope).await;
+/// Cap, publish and journal a command's final response, and take the
+/// command out of the inbox.
+async fn publish_response(
+    channel: &MqttChannel,
+    envelope: &CommandEnvelope,
+    response: CommandResponse,
+    history: Option<&LocalHistory>,
+    inbox: Option<&CommandInbox>,
+) {
+    // Cap response size to fit MQTT packet limit before publishing
+    let response = cap_response_size(response);
 
-            // Update shadow state with last command info.
-            {
-                let mut state = shadow_state.write().await;
-                state.last_command_id = Some(envelope.id.to_string());
-                state.last_command_tool = response
-                    .response_data
-                    .as_ref()
-                    .and_then(|d| d.get("tool_name"))
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
-                state.last_command_at = Some(chrono::Utc::now().to_rfc3339());
-            }
+    // Publish response back
+    let published = match channel.publish_response(&response).await {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to publish command response");
+            false
+        }
+    };
 
-            match response.status {
-                CommandStatus::Completed => {
-                    tracing::info!(
-                        command_id = %envelope.id,
-                        latency_ms = response.latency_ms,
-                        "command completed"
-                    );
-                }
-                _ => {
-                    tracing::warn!(
-                        command_id = %envelope.id,
-                        error = ?response.error,
-                        "command failed"
-                    );
-                }
-            }
+    if let Some(history) = history {
+        history.record_command(envelope, &response, published);
+    }
+    // Unpublished responses stay in the history journal; re-running the
+    // command on the next start would not help.
+    if let Some(inbox) = inbox {
+        inbox.finish(envelope);
+    }
+}
 
-            // Cap response size to fit MQTT packet limit before publishing
-            let response = cap_response_size(response);
+/// What the run loop must do after a shadow or config message.
+#[derive(Debug, PartialEq)]
+enum Followup {
+    None,
+    /// A rotated TLS certificate was installed; reconnect with it.
+    ReloadTls,
+    /// Install this agent release.
+    Install(AgentUpdate),
+}
 
-            // Publish response back
-            if let Err(e) = channel.publish_response(&response).await {
-                tracing::error!(error = %e, "failed to publish command response");
-            }
-        }
+/// Dispatch a shadow or config message.
+async fn handle_message(
+    msg: IncomingMessage,
+    shadow_client: &ShadowClient<'_, MqttChannel>,
+    shadow_state: &SharedShadowState,
+    config_tx: &RuntimeConfigTx,
+    mqtt: &MqttConfig,
+) -> Followup {
+    match msg {
         IncomingMessage::ShadowDelta(delta) => {
-            handle_shadow_delta(&delta, shadow_client).await;
+            return handle_shadow_delta(&delta, shadow_client, mqtt).await;
+        }
+        IncomingMessage::ShadowDocument(document) => {
+            if let Some(delta) = shadow_sync::document_delta(&document) {
+                return handle_shadow_delta(&delta, shadow_client, mqtt).await;
+            }
         }
         IncomingMessage::ConfigUpdate(config) => {
-            tracing::info!("received config update (handling not yet implemented)");
-            tracing::debug!(config = %config, "config payload");
-            // Phase 2: Apply runtime config changes
+            handle_config_update(&config, shadow_state, config_tx).await;
         }
         IncomingMessage::Unknown { topic, .. } => {
             tracing::debug!(topic = %topic, "ignoring unrecognized message");
         }
+        // Commands, cancellations and file transfers are handled by the
+        // run loop.
+        IncomingMessage::Command(_)
+        | IncomingMessage::CommandCancel(_)
+        | IncomingMessage::TransferOffer(_)
+        | IncomingMessage::TransferChunk(_) => {}
+    }
+    Followup::None
+}
+
+/// Apply a broadcast config update. The shadow sync loop reports the new
+/// version; a rejection is recorded in the shadow as `config_error`.
+async fn handle_config_update(
+    payload: &serde_json::Value,
+    shadow_state: &SharedShadowState,
+    config_tx: &RuntimeConfigTx,
+) {
+    let error = match runtime_config::handle_update(payload, config_tx) {
+        Ok(version) => {
+            tracing::info!(version, "runtime config update applied");
+            None
+        }
+        Err(e @ UpdateError::Stale { .. }) => {
+            tracing::debug!(error = %e, "ignoring config update");
+            return;
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "config update rejected");
+            Some(e.to_string())
+        }
+    };
+    shadow_state.write().await.config_error = error;
+}
+
+/// Execute a command with streaming, stopping early if a cancellation
+/// arrives on `cancel`.
+///
+/// Dropping the execution future aborts the tool mid-run; chunks already
+/// published stay published. A dropped sender does not cancel.
+async fn execute_cancellable<C: Channel>(
+    envelope: &CommandEnvelope,
+    executor: &CommandExecutor<'_>,
+    channel: &C,
+    stream_topic: &str,
+    cancel: oneshot::Receiver<CommandCancel>,
+) -> CommandResponse {
+    let start = Instant::now();
+    tokio::select! {
+        response = execute_with_stream(envelope, executor, channel, stream_topic) => response,
+        Ok(cancel) = cancel => {
+            cancelled_response(envelope, &cancel, start.elapsed().as_millis() as u64)
+        }
+    }
+}
+
+/// Final response for a command stopped by [`CommandCancel`].
+fn cancelled_response(
+    envelope: &CommandEnvelope,
+    cancel: &CommandCancel,
+    latency_ms: u64,
+) -> CommandResponse {
+    let mut text = format!("Command cancelled by {}", cancel.requested_by);
+    if let Some(reason) = &cancel.reason {
+        text.push_str(&format!(": {reason}"));
+    }
+    CommandResponse {
+        command_id: envelope.id,
+        correlation_id: envelope.correlation_id,
+        device_id: envelope.device_id.clone(),
+        status: CommandStatus::Cancelled,
+        inference_tier: InferenceTier::Local,
+        response_text: Some(text),
+        response_data: None,
+        latency_ms,
+        responded_at: chrono::Utc::now(),
+        error: None,
+        error_code: Some(ErrorCode::Cancelled),
+        cached: false,
     }
 }
 
+/// Final response for a command refused by the agent's rate limit.
+fn throttled_response(envelope: &CommandEnvelope, retry_after: Duration) -> CommandResponse {
+    let retry_secs = retry_after.as_secs_f64().ceil() as u64;
+    CommandResponse {
+        command_id: envelope.id,
+        correlation_id: envelope.correlation_id,
+        device_id: envelope.device_id.clone(),
+        status: CommandStatus::Throttled,
+        inference_tier: InferenceTier::Local,
+        response_text: None,
+        response_data: None,
+        latency_ms: 0,
+        responded_at: chrono::Utc::now(),
+        error: Some(format!(
+            "command rate limit exceeded, retry in {retry_secs}s"
+        )),
+        error_code: Some(ErrorCode::Throttled),
+        cached: false,
+    }
+}
+
+/// Execute a command, publishing partial results from streaming tools as
+/// [`CommandResponseChunk`]s on `stream_topic` while it runs.
+///
+/// Chunks are published in order with increasing `seq`; the final
+/// response is returned once execution and publishing have both finished.
+async fn execute_with_stream<C: Channel>(
+    envelope: &CommandEnvelope,
+    executor: &CommandExecutor<'_>,
+    channel: &C,
+    stream_topic: &str,
+) -> CommandResponse {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+
+    // `tx` moves into the sink and is dropped when execution finishes,
+    // which ends the publish loop below.
+    let execute = async move {
+        let sink = move |data: serde_json::Value| {
+            let _ = tx.send(data);
+        };
+        executor.execute_streaming(envelope, &sink).await
+    };
+
+    let publish = async {
+        let mut seq = 0u32;
+        while let Some(data) = rx.recv().await {
+            let chunk = CommandResponseChunk {
+                command_id: envelope.id,
+                correlation_id: envelope.correlation_id,
+                device_id: envelope.device_id.clone(),
+                seq,
+                data,
+                sent_at: chrono::Utc::now(),
+            };
+            let payload = cap_chunk_size(chunk);
+            if let Err(e) = channel
+                .publish(stream_topic, &payload, QoS::AtLeastOnce)
+                .await
+            {
+                tracing::warn!(error = %e, command_id = %envelope.id, seq, "failed to publish response chunk");
+            }
+            seq += 1;
+        }
+    };
+
+    let (response, ()) = tokio::join!(execute, publish);
+    response
+}
+
+/// Serialize a chunk, replacing oversized data with a truncation marker so
+/// the sequence stays gap-free.
+fn cap_chunk_size(mut chunk: CommandResponseChunk) -> Vec<u8> {
+    let bytes = serde_json::to_vec(&chunk).unwrap_or_default();
+    if bytes.len() <= MAX_MQTT_PAYLOAD {
+        return bytes;
+    }
+    tracing::warn!(
+        command_id = %chunk.command_id,
+        seq = chunk.seq,
+        bytes = bytes.len(),
+        "response chunk exceeds MQTT packet limit, dropping data"
+    );
+    chunk.data = serde_json::json!({
+        "truncated": true,
+        "original_bytes": bytes.len(),
+    });
+    serde_json::to_vec(&chunk).unwrap_or_default()
+}
+
 /// Ensure the serialized response fits within the MQTT packet limit.
 ///
 /// If the response exceeds [`MAX_MQTT_PAYLOAD`], truncates `response_data`
@@ -213,6 +808,7 @@ fn cap_response_size(mut response: CommandResponse) -> CommandResponse {
             "truncated": true,
             "original_bytes": original_len,
         }));
+        response.error_code = Some(ErrorCode::PayloadTooLarge);
 
         if let Some(s) = summary {
             response.response_text = Some(format!(
@@ -238,24 +834,33 @@ fn cap_response_size(mut response: CommandResponse) -> CommandResponse {
 /// Handle an incoming shadow delta from the cloud.
 ///
 /// For the "config" shadow, logs applied keys and acknowledges via ShadowClient.
+/// For the "certificate" shadow, installs the rotated certificate and
+/// asks the caller to reconnect with it.
+/// For the "update" shadow, asks the caller to install the release.
 /// Unknown shadow names are logged and ignored.
 async fn handle_shadow_delta<C: Channel>(
     delta: &zc_protocol::shadows::ShadowDelta,
     shadow_client: &ShadowClient<'_, C>,
-) {
+    mqtt: &MqttConfig,
+) -> Followup {
     match delta.shadow_name.as_str() {
         "config" => {
             if let Some(obj) = delta.delta.as_object() {
-                let keys: Vec<&str> = obj.keys().map(|k| k.as_str()).collect();
+                let (cleared, keys): (Vec<&str>, Vec<&str>) = obj
+                    .keys()
+                    .map(|k| k.as_str())
+                    .partition(|k| obj[*k].is_null());
                 tracing::info!(
                     shadow = "config",
                     version = delta.version,
                     keys = ?keys,
+                    cleared = ?cleared,
                     "applying config shadow delta"
                 );
             }
 
-            // Acknowledge by reporting the delta values as our reported state.
+            // Acknowledge by reporting the delta values as our reported state;
+            // tombstones are echoed as null, which removes the keys cloud-side.
             if let Err(e) = shadow_client
                 .report_state("config", delta.delta.clone(), delta.version)
                 .await
@@ -263,6 +868,12 @@ async fn handle_shadow_delta<C: Channel>(
                 tracing::warn!(error = %e, "failed to acknowledge config shadow delta");
             }
         }
+        CERTIFICATE_SHADOW => {
+            if handle_certificate_rotation(delta, shadow_client, mqtt).await {
+                return Followup::ReloadTls;
+            }
+        }
+        UPDATE_SHADOW => return handle_update_delta(delta, shadow_client).await,
         other => {
             tracing::debug!(
                 shadow = other,
@@ -271,15 +882,128 @@ async fn handle_shadow_delta<C: Channel>(
             );
         }
     }
+    Followup::None
+}
+
+/// Parse a release delivered on the "update" shadow. The release already
+/// running is reported as installed at once.
+async fn handle_update_delta<C: Channel>(
+    delta: &zc_protocol::shadows::ShadowDelta,
+    shadow_client: &ShadowClient<'_, C>,
+) -> Followup {
+    let update = match serde_json::from_value::<AgentUpdate>(delta.delta.clone()) {
+        Ok(update) => update,
+        Err(e) => {
+            tracing::warn!(error = %e, "ignoring invalid agent update");
+            return Followup::None;
+        }
+    };
+    if Updater::is_running(&update) {
+        let report = UpdateReport::new(&update, UpdateStatus::Installed, None);
+        report_update(shadow_client, &report).await;
+        return Followup::None;
+    }
+    Followup::Install(update)
+}
+
+async fn report_update<C: Channel>(shadow_client: &ShadowClient<'_, C>, report: &UpdateReport) {
+    let reported = serde_json::to_value(report).unwrap_or_default();
+    if let Err(e) = shadow_client.report_state(UPDATE_SHADOW, reported, 0).await {
+        tracing::warn!(error = %e, "failed to report agent update");
+    }
+}
+
+/// Install a certificate delivered on the "certificate" shadow.
+///
+/// On success the delta is reported back, which clears it cloud-side. A
+/// refused rotation is reported as `error` instead; the certificate keys
+/// stay in the delta, so the operator sees it never arrived.
+async fn handle_certificate_rotation<C: Channel>(
+    delta: &zc_protocol::shadows::ShadowDelta,
+    shadow_client: &ShadowClient<'_, C>,
+    mqtt: &MqttConfig,
+) -> bool {
+    let installed = if !mqtt.use_tls {
+        Err("TLS is disabled on this device".to_string())
+    } else {
+        serde_json::from_value::<CertificateRotation>(delta.delta.clone())
+            .map_err(|e| format!("invalid certificate rotation: {e}"))
+            .and_then(|rotation| {
+                zc_mqtt_channel::tls::install_certificate(
+                    mqtt,
+                    &rotation.certificate_pem,
+                    rotation.ca_cert_pem.as_deref(),
+                )
+                .map(|()| rotation.certificate_id)
+                .map_err(|e| e.to_string())
+            })
+    };
+
+    let reported = match &installed {
+        Ok(certificate_id) => {
+            tracing::info!(
+                certificate_id = %certificate_id,
+                version = delta.version,
+                "installed rotated client certificate"
+            );
+            let mut reported = delta.delta.clone();
+            reported["error"] = serde_json::Value::Null;
+            reported
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, version = delta.version, "certificate rotation refused");
+            serde_json::json!({ "error": e })
+        }
+    };
+    if let Err(e) = shadow_client
+        .report_state(CERTIFICATE_SHADOW, reported, delta.version)
+        .await
+    {
+        tracing::warn!(error = %e, "failed to report certificate rotation");
+    }
+    installed.is_ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use zc_mqtt_channel::MockChannel;
-    use zc_protocol::commands::{CommandEnvelope, CommandResponse, InferenceTier};
     use zc_protocol::shadows::ShadowDelta;
 
+    fn mqtt_config(use_tls: bool) -> MqttConfig {
+        let dir = std::env::temp_dir().join(format!("zc-certs-{}", std::process::id()));
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        MqttConfig {
+            broker_host: "localhost".into(),
+            broker_port: 8883,
+            client_id: "rpi-001".into(),
+            use_tls,
+            client_cert_path: path("device.pem"),
+            client_key_path: path("device.key"),
+            ca_cert_path: path("ca.pem"),
+            keepalive_secs: 30,
+            protocol: Default::default(),
+            encoding: Default::default(),
+            clean_session: true,
+            last_will: false,
+            qos: Default::default(),
+            offline_buffer: Default::default(),
+            reconnect: Default::default(),
+        }
+    }
+
+    fn certificate_delta(delta: serde_json::Value) -> ShadowDelta {
+        ShadowDelta {
+            device_id: "rpi-001".into(),
+            shadow_name: CERTIFICATE_SHADOW.into(),
+            delta,
+            version: 3,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    const CERT: &str = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+
     #[tokio::test]
     async fn delta_acknowledge_publishes_report() {
         let mock = MockChannel::new();
@@ -293,7 +1017,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
         };
 
-        handle_shadow_delta(&delta, &client).await;
+        handle_shadow_delta(&delta, &client, &mqtt_config(false)).await;
 
         let msgs = mock.published();
         assert_eq!(msgs.len(), 1);
@@ -317,12 +1041,313 @@ mod tests {
             timestamp: chrono::Utc::now(),
         };
 
-        handle_shadow_delta(&delta, &client).await;
+        handle_shadow_delta(&delta, &client, &mqtt_config(false)).await;
 
         // No message should be published for unknown shadows.
         assert!(mock.published().is_empty());
     }
 
+    #[tokio::test]
+    async fn update_delta_installs_new_release_only() {
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+        let release = |version: &str| AgentUpdate {
+            agent_version: version.into(),
+            url: format!("https://releases.example.com/zc-fleet-agent-{version}"),
+            sha256: "ab".repeat(32),
+        };
+        let delta = |update: &AgentUpdate| ShadowDelta {
+            device_id: "rpi-001".into(),
+            shadow_name: UPDATE_SHADOW.into(),
+            delta: serde_json::to_value(update).unwrap(),
+            version: 2,
+            timestamp: chrono::Utc::now(),
+        };
+
+        let newer = release("9.9.9");
+        assert_eq!(
+            handle_shadow_delta(&delta(&newer), &client, &mqtt_config(false)).await,
+            Followup::Install(newer)
+        );
+        assert!(mock.published().is_empty());
+
+        // The running release is confirmed without reinstalling it.
+        let running = release(env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            handle_shadow_delta(&delta(&running), &client, &mqtt_config(false)).await,
+            Followup::None
+        );
+        let msgs = mock.published();
+        assert_eq!(msgs.len(), 1);
+        let update: zc_protocol::shadows::ShadowUpdate =
+            serde_json::from_slice(&msgs[0].payload).unwrap();
+        assert_eq!(update.shadow_name, UPDATE_SHADOW);
+        assert_eq!(update.reported["status"], "installed");
+        assert_eq!(update.reported["agent_version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn certificate_rotation_installs_and_reports() {
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+        let mqtt = mqtt_config(true);
+        let dir = std::path::Path::new(&mqtt.client_cert_path)
+            .parent()
+            .unwrap();
+        std::fs::create_dir_all(dir).unwrap();
+
+        let delta = certificate_delta(serde_json::json!({
+            "certificate_id": "ab12",
+            "certificate_pem": CERT,
+        }));
+        assert_eq!(
+            handle_shadow_delta(&delta, &client, &mqtt).await,
+            Followup::ReloadTls
+        );
+        assert_eq!(
+            std::fs::read_to_string(&mqtt.client_cert_path).unwrap(),
+            CERT
+        );
+
+        let update: zc_protocol::shadows::ShadowUpdate =
+            serde_json::from_slice(&mock.published()[0].payload).unwrap();
+        assert_eq!(update.shadow_name, CERTIFICATE_SHADOW);
+        assert_eq!(update.reported["certificate_id"], "ab12");
+        assert!(update.reported["error"].is_null());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn certificate_rotation_refused_without_tls() {
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+
+        let delta = certificate_delta(serde_json::json!({
+            "certificate_id": "ab12",
+            "certificate_pem": CERT,
+        }));
+        assert_eq!(
+            handle_shadow_delta(&delta, &client, &mqtt_config(false)).await,
+            Followup::None
+        );
+
+        let update: zc_protocol::shadows::ShadowUpdate =
+            serde_json::from_slice(&mock.published()[0].payload).unwrap();
+        assert!(update.reported.get("certificate_id").is_none());
+        assert!(update.reported["error"].as_str().unwrap().contains("TLS"));
+    }
+
+    // ── streaming tests ─────────────────────────────────────────
+
+    #[tokio::test]
+    async fn streaming_tool_publishes_ordered_chunks() {
+        let registry = crate::registry::ToolRegistry::with_defaults();
+        let can = zc_canbus_tools::MockCanInterface::new();
+        for i in 0..5u8 {
+            can.queue_response(zc_canbus_tools::CanFrame::new(0x7E8, vec![i]));
+        }
+        let logs = zc_log_tools::MockLogSource::new();
+        let executor = CommandExecutor::new(&registry, &can, &logs, None);
+        let mock = MockChannel::new();
+
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "monitor CAN", "admin");
+        envelope.parsed_intent = Some(zc_protocol::commands::ParsedIntent {
+            action: zc_protocol::commands::ActionKind::Tool,
+            tool_name: "can_monitor".into(),
+            tool_args: serde_json::json!({"duration_secs": 5, "max_frames": 5, "chunk_frames": 2}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+
+        let topic = topics::command_stream("fleet-alpha", "rpi-001");
+        let response = execute_with_stream(&envelope, &executor, &mock, &topic).await;
+
+        assert_eq!(response.status, CommandStatus::Completed);
+        let chunks: Vec<CommandResponseChunk> = mock
+            .published_to(&topic)
+            .iter()
+            .map(|m| serde_json::from_slice(&m.payload).unwrap())
+            .collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks.iter().map(|c| c.seq).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert!(chunks.iter().all(|c| c.command_id == envelope.id));
+        assert_eq!(response.response_data.unwrap()["streamed_chunks"], 3);
+    }
+
+    #[tokio::test]
+    async fn non_streaming_tool_publishes_no_chunks() {
+        let registry = crate::registry::ToolRegistry::with_defaults();
+        let can = zc_canbus_tools::MockCanInterface::new();
+        let logs = zc_log_tools::MockLogSource::with_syslog_sample();
+        let executor = CommandExecutor::new(&registry, &can, &logs, None);
+        let mock = MockChannel::new();
+
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "log stats", "admin");
+        envelope.parsed_intent = Some(zc_protocol::commands::ParsedIntent {
+            action: zc_protocol::commands::ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: serde_json::json!({"path": "/var/log/syslog"}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+
+        let response = execute_with_stream(&envelope, &executor, &mock, "stream").await;
+        assert_eq!(response.status, CommandStatus::Completed);
+        assert!(mock.published().is_empty());
+    }
+
+    // ── cancellation tests ──────────────────────────────────────
+
+    fn follow_envelope() -> CommandEnvelope {
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "follow syslog", "admin");
+        envelope.parsed_intent = Some(zc_protocol::commands::ParsedIntent {
+            action: zc_protocol::commands::ActionKind::Tool,
+            tool_name: "tail_logs".into(),
+            tool_args: serde_json::json!({"path": "/var/log/syslog", "follow": true, "follow_secs": 60}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+        envelope
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_running_command() {
+        let registry = crate::registry::ToolRegistry::with_defaults();
+        let can = zc_canbus_tools::MockCanInterface::new();
+        let logs = zc_log_tools::MockLogSource::with_syslog_sample();
+        let executor = CommandExecutor::new(&registry, &can, &logs, None);
+        let mock = MockChannel::new();
+        let envelope = follow_envelope();
+
+        let (tx, rx) = oneshot::channel();
+        let cancel = CommandCancel {
+            command_id: envelope.id,
+            device_id: "rpi-001".into(),
+            requested_by: "admin".into(),
+            reason: Some("wrong file".into()),
+            requested_at: chrono::Utc::now(),
+        };
+        let started = Instant::now();
+        let (response, ()) = tokio::join!(
+            execute_cancellable(&envelope, &executor, &mock, "stream", rx),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                tx.send(cancel).unwrap();
+            }
+        );
+
+        assert_eq!(response.status, CommandStatus::Cancelled);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            response.response_text.as_deref(),
+            Some("Command cancelled by admin: wrong file")
+        );
+        // The initial tail was streamed before the cancel arrived.
+        assert!(!mock.published_to("stream").is_empty());
+    }
+
+    #[tokio::test]
+    async fn next_finished_drops_only_the_finished_command() {
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        let mut running = vec![
+            RunningCommand {
+                command_id: "slow".into(),
+                cancel: None,
+                task: Box::pin(std::future::pending()),
+            },
+            RunningCommand {
+                command_id: "quick".into(),
+                cancel: None,
+                task: Box::pin(async {
+                    let _ = done_rx.await;
+                }),
+            },
+        ];
+        done_tx.send(()).unwrap();
+
+        next_finished(&mut running).await;
+
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].command_id, "slow");
+    }
+
+    #[tokio::test]
+    async fn dropped_cancel_sender_does_not_cancel() {
+        let registry = crate::registry::ToolRegistry::with_defaults();
+        let can = zc_canbus_tools::MockCanInterface::new();
+        let logs = zc_log_tools::MockLogSource::with_syslog_sample();
+        let executor = CommandExecutor::new(&registry, &can, &logs, None);
+        let mock = MockChannel::new();
+
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "log stats", "admin");
+        envelope.parsed_intent = Some(zc_protocol::commands::ParsedIntent {
+            action: zc_protocol::commands::ActionKind::Tool,
+            tool_name: "log_stats".into(),
+            tool_args: serde_json::json!({"path": "/var/log/syslog"}),
+            confidence: 0.9,
+            steps: Vec::new(),
+        });
+
+        let (tx, rx) = oneshot::channel();
+        drop(tx);
+        let response = execute_cancellable(&envelope, &executor, &mock, "stream", rx).await;
+        assert_eq!(response.status, CommandStatus::Completed);
+    }
+
+    #[test]
+    fn cancelled_response_without_reason() {
+        let envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "monitor CAN", "admin");
+        let cancel = CommandCancel {
+            command_id: envelope.id,
+            device_id: "rpi-001".into(),
+            requested_by: "ops@example.com".into(),
+            reason: None,
+            requested_at: chrono::Utc::now(),
+        };
+        let response = cancelled_response(&envelope, &cancel, 42);
+        assert_eq!(response.status, CommandStatus::Cancelled);
+        assert_eq!(response.error_code, Some(ErrorCode::Cancelled));
+        assert_eq!(response.correlation_id, envelope.correlation_id);
+        assert_eq!(response.latency_ms, 42);
+        assert_eq!(
+            response.response_text.as_deref(),
+            Some("Command cancelled by ops@example.com")
+        );
+    }
+
+    #[test]
+    fn throttled_response_rounds_retry_up() {
+        let envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "admin");
+        let response = throttled_response(&envelope, Duration::from_millis(1200));
+        assert_eq!(response.status, CommandStatus::Throttled);
+        assert_eq!(response.error_code, Some(ErrorCode::Throttled));
+        assert_eq!(response.correlation_id, envelope.correlation_id);
+        assert_eq!(
+            response.error.as_deref(),
+            Some("command rate limit exceeded, retry in 2s")
+        );
+    }
+
+    #[test]
+    fn oversized_chunk_is_truncated() {
+        let envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "tail logs", "admin");
+        let chunk = CommandResponseChunk {
+            command_id: envelope.id,
+            correlation_id: envelope.correlation_id,
+            device_id: "rpi-001".into(),
+            seq: 0,
+            data: serde_json::json!({"entries": ["x".repeat(MAX_MQTT_PAYLOAD)]}),
+            sent_at: chrono::Utc::now(),
+        };
+        let bytes = cap_chunk_size(chunk);
+        assert!(bytes.len() <= MAX_MQTT_PAYLOAD);
+        let back: CommandResponseChunk = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(back.data["truncated"], true);
+    }
+
     // ── cap_response_size tests ─────────────────────────────────
 
     fn make_response(data: Option<serde_json::Value>) -> CommandResponse {
@@ -338,6 +1363,8 @@ mod tests {
             latency_ms: 100,
             responded_at: chrono::Utc::now(),
             error: None,
+            error_code: None,
+            cached: false,
         }
     }
 
@@ -400,6 +1427,7 @@ mod tests {
         assert!(kept < 1500, "should have trimmed: kept {kept}");
         // "shown" metadata should reflect trimmed count
         assert_eq!(data["data"]["shown"], kept);
+        assert_eq!(capped.error_code, None);
     }
 
     #[test]
@@ -431,6 +1459,7 @@ mod tests {
         // Should have fallback truncation marker
         let data = capped.response_data.unwrap();
         assert_eq!(data["truncated"], true);
+        assert_eq!(capped.error_code, Some(ErrorCode::PayloadTooLarge));
 
         let text = capped.response_text.unwrap();
         assert!(text.contains("tail_logs"));
@@ -444,4 +1473,30 @@ mod tests {
         assert_eq!(capped.response_text, resp.response_text);
         assert!(capped.response_data.is_none());
     }
+
+    #[tokio::test]
+    async fn config_update_applied_or_recorded_as_error() {
+        let shadow_state = SharedShadowState::default();
+        let (tx, rx) = tokio::sync::watch::channel(runtime_config::RuntimeConfig {
+            version: 0,
+            heartbeat_interval_secs: 30,
+            telemetry_window_secs: 10,
+            telemetry_sample_interval_ms: 1000,
+            ollama_model: "phi3:mini".into(),
+            log_paths: vec![],
+            pid_watches: vec![],
+        });
+
+        let bad = serde_json::json!({"version": 1, "heartbeat_interval_secs": 0});
+        handle_config_update(&bad, &shadow_state, &tx).await;
+        let error = shadow_state.read().await.config_error.clone().unwrap();
+        assert!(error.contains("heartbeat_interval_secs"));
+        assert_eq!(rx.borrow().version, 0);
+
+        let good = serde_json::json!({"version": 2, "heartbeat_interval_secs": 60});
+        handle_config_update(&good, &shadow_state, &tx).await;
+        assert!(shadow_state.read().await.config_error.is_none());
+        assert_eq!(rx.borrow().heartbeat_interval_secs, 60);
+        assert_eq!(rx.borrow().version, 2);
+    }
 }
@@ -0,0 +1,711 @@
+//! Continuous PID watches (`watch_pid`).
+//!
+//! Instead of operators issuing `read_pid` over and over, a watch samples
+//! one OBD-II PID on the default bus every `interval_ms`:
+//!
+//! - the `watch_pid` tool starts one for `duration_secs` (at most an hour),
+//!   stops one (`stop`) or lists them;
+//! - `[[pid_watches]]` in agent.toml, or `pid_watches` in a config update,
+//!   sets the standing watches, which run until replaced.
+//!
+//! Each round's readings go out as one `obd2` telemetry batch (and into the
+//! local history) with the watch in `value_json`. A watch with `above` or
+//! `below` also publishes a [`ThresholdEvent`] on `alert/notify` when a
+//! reading leaves the bounds and once more when it returns; the cloud
+//! raises an alert for the first. Like the telemetry loop, each round holds
+//! the default bus's lock, so it waits for a command using the bus.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use zc_canbus_tools::{CanInterface, obd};
+use zc_mqtt_channel::MqttChannel;
+use zc_protocol::catalog::ToolSpec;
+use zc_protocol::telemetry::{
+    TelemetryBatch, TelemetryReading, TelemetrySource, ThresholdBound, ThresholdEvent,
+};
+
+use crate::history::LocalHistory;
+use crate::runtime_config::RuntimeConfigRx;
+use crate::telemetry;
+
+/// Tool name answered by [`PidWatches::execute`].
+pub const TOOL_NAME: &str = "watch_pid";
+
+/// Shortest sampling interval.
+pub const MIN_INTERVAL_MS: u64 = 100;
+
+/// Sampling interval unless set.
+pub const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+/// How long a `watch_pid` watch runs unless set.
+pub const DEFAULT_DURATION_SECS: u64 = 300;
+
+/// Longest `watch_pid` watch; longer ones belong in `pid_watches`.
+pub const MAX_DURATION_SECS: u64 = 3600;
+
+/// Most watches running at once, standing ones included.
+pub const MAX_WATCHES: usize = 8;
+
+/// Standing watches are named `config-0`, `config-1`, ... by position.
+const STANDING_PREFIX: &str = "config-";
+
+/// Catalog entry for [`TOOL_NAME`].
+pub fn tool_spec() -> ToolSpec {
+    ToolSpec::new(
+        TOOL_NAME,
+        "Sample an OBD-II PID at an interval for a while, publishing readings as telemetry and alerting when a threshold is crossed (e.g. coolant above 110). Without pid, lists running watches",
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pid": {"type": "integer", "description": "OBD-II PID number (0x00-0xFF)"},
+                "interval_ms": {"type": "integer", "default": DEFAULT_INTERVAL_MS, "description": "Delay between samples, at least 100"},
+                "duration_secs": {"type": "integer", "default": DEFAULT_DURATION_SECS, "description": "How long to watch, at most 3600"},
+                "above": {"type": "number", "description": "Alert when a reading rises above this"},
+                "below": {"type": "number", "description": "Alert when a reading drops below this"},
+                "stop": {"type": "string", "description": "ID of a watch to stop"}
+            }
+        }),
+    )
+}
+
+fn default_interval_ms() -> u64 {
+    DEFAULT_INTERVAL_MS
+}
+
+/// A PID to watch (`[[pid_watches]]`, or a `watch_pid` call).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PidWatchConfig {
+    /// OBD-II Mode 01 PID.
+    pub pid: u8,
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    /// Alert when a reading rises above this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<f64>,
+    /// Alert when a reading drops below this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<f64>,
+}
+
+impl PidWatchConfig {
+    /// Every problem with the watch, as (field, message).
+    pub fn issues(&self) -> Vec<(&'static str, String)> {
+        let mut issues = Vec::new();
+        if self.interval_ms < MIN_INTERVAL_MS {
+            issues.push(("interval_ms", format!("must be at least {MIN_INTERVAL_MS}")));
+        }
+        for (field, bound) in [("above", self.above), ("below", self.below)] {
+            if bound.is_some_and(|b| !b.is_finite()) {
+                issues.push((field, "must be a finite number".into()));
+            }
+        }
+        if let (Some(above), Some(below)) = (self.above, self.below)
+            && below >= above
+        {
+            issues.push(("below", "must be less than above".into()));
+        }
+        issues
+    }
+
+    /// The bound `value` is outside of, with its threshold.
+    fn breach(&self, value: f64) -> Option<(ThresholdBound, f64)> {
+        if let Some(above) = self.above
+            && value > above
+        {
+            return Some((ThresholdBound::Above, above));
+        }
+        if let Some(below) = self.below
+            && value < below
+        {
+            return Some((ThresholdBound::Below, below));
+        }
+        None
+    }
+
+    fn describe(&self) -> String {
+        let mut text = format!("PID 0x{:02X} every {} ms", self.pid, self.interval_ms);
+        match (self.above, self.below) {
+            (Some(above), Some(below)) => {
+                text.push_str(&format!(", alerting outside {below}..{above}"))
+            }
+            (Some(above), None) => text.push_str(&format!(", alerting above {above}")),
+            (None, Some(below)) => text.push_str(&format!(", alerting below {below}")),
+            (None, None) => {}
+        }
+        text
+    }
+}
+
+/// Every problem with a `pid_watches` list, as (field, message).
+pub fn list_issues(specs: &[PidWatchConfig]) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    if specs.len() > MAX_WATCHES {
+        issues.push((
+            "pid_watches".to_string(),
+            format!("at most {MAX_WATCHES} watches"),
+        ));
+    }
+    for (i, spec) in specs.iter().enumerate() {
+        for (field, message) in spec.issues() {
+            issues.push((format!("pid_watches[{i}].{field}"), message));
+        }
+    }
+    issues
+}
+
+/// A threshold crossing found by [`PidWatches::record`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Crossing {
+    bound: ThresholdBound,
+    threshold: f64,
+    exceeded: bool,
+}
+
+#[derive(Debug)]
+struct Watch {
+    id: String,
+    spec: PidWatchConfig,
+    /// `None` for standing watches.
+    until: Option<Instant>,
+    next_due: Instant,
+    /// Bound the last reading was outside of.
+    breached: Option<(ThresholdBound, f64)>,
+    last: Option<f64>,
+    samples: u64,
+}
+
+impl Watch {
+    fn new(id: String, spec: PidWatchConfig, until: Option<Instant>) -> Self {
+        Self {
+            id,
+            spec,
+            until,
+            next_due: Instant::now(),
+            breached: None,
+            last: None,
+            samples: 0,
+        }
+    }
+
+    fn is_standing(&self) -> bool {
+        self.until.is_none()
+    }
+
+    fn to_json(&self, now: Instant) -> serde_json::Value {
+        serde_json::json!({
+            "watch_id": self.id,
+            "pid": self.spec.pid,
+            "interval_ms": self.spec.interval_ms,
+            "above": self.spec.above,
+            "below": self.spec.below,
+            "standing": self.is_standing(),
+            "remaining_secs": self.until.map(|u| u.saturating_duration_since(now).as_secs()),
+            "samples": self.samples,
+            "last": self.last,
+            "breached": self.breached.map(|(bound, _)| bound.as_str()),
+        })
+    }
+}
+
+/// The running watches, shared by the executor and [`run`].
+#[derive(Debug, Default)]
+pub struct PidWatches {
+    watches: Mutex<Vec<Watch>>,
+    changed: Notify,
+}
+
+impl PidWatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching `spec` for `duration`; returns the watch ID.
+    pub fn start(&self, spec: PidWatchConfig, duration: Duration) -> Result<String, String> {
+        if let Some((field, message)) = spec.issues().into_iter().next() {
+            return Err(format!("{field} {message}"));
+        }
+        let id = uuid::Uuid::now_v7().to_string();
+        {
+            let mut watches = self.watches.lock().unwrap();
+            if watches.len() >= MAX_WATCHES {
+                return Err(format!("at most {MAX_WATCHES} PID watches may run at once"));
+            }
+            let until = Instant::now() + duration;
+            watches.push(Watch::new(id.clone(), spec, Some(until)));
+        }
+        self.changed.notify_one();
+        Ok(id)
+    }
+
+    /// Stop the watch `id`. Standing watches only stop when `pid_watches`
+    /// changes.
+    pub fn stop(&self, id: &str) -> Result<(), String> {
+        let mut watches = self.watches.lock().unwrap();
+        let Some(i) = watches.iter().position(|w| w.id == id) else {
+            return Err(format!("no PID watch '{id}'"));
+        };
+        if watches[i].is_standing() {
+            return Err(format!(
+                "'{id}' is a standing watch; change pid_watches to stop it"
+            ));
+        }
+        watches.remove(i);
+        Ok(())
+    }
+
+    /// Replace the standing watches with `specs`. Watches whose spec did
+    /// not change keep their state, so a config update does not re-fire
+    /// their alerts.
+    pub fn set_standing(&self, specs: &[PidWatchConfig]) {
+        {
+            let mut watches = self.watches.lock().unwrap();
+            let (mut old, temporary): (Vec<Watch>, Vec<Watch>) = std::mem::take(&mut *watches)
+                .into_iter()
+                .partition(Watch::is_standing);
+            for (i, spec) in specs.iter().enumerate() {
+                let id = format!("{STANDING_PREFIX}{i}");
+                match old.iter().position(|w| w.id == id && w.spec == *spec) {
+                    Some(kept) => watches.push(old.swap_remove(kept)),
+                    None => watches.push(Watch::new(id, spec.clone(), None)),
+                }
+            }
+            watches.extend(temporary);
+        }
+        self.changed.notify_one();
+    }
+
+    /// Every running watch, standing ones first.
+    pub fn list(&self) -> Vec<serde_json::Value> {
+        let now = Instant::now();
+        self.watches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|w| w.to_json(now))
+            .collect()
+    }
+
+    /// When the next watch is due or expires.
+    fn next_wakeup(&self) -> Option<Instant> {
+        self.watches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|w| w.until.map_or(w.next_due, |u| u.min(w.next_due)))
+            .min()
+    }
+
+    /// Drop expired watches and return the due ones as (ID, PID),
+    /// scheduling their next sample.
+    fn take_due(&self, now: Instant) -> Vec<(String, u8)> {
+        let mut watches = self.watches.lock().unwrap();
+        watches.retain(|w| w.until.is_none_or(|u| u > now));
+        watches
+            .iter_mut()
+            .filter(|w| w.next_due <= now)
+            .map(|w| {
+                w.next_due = now + Duration::from_millis(w.spec.interval_ms);
+                (w.id.clone(), w.spec.pid)
+            })
+            .collect()
+    }
+
+    /// Record a reading of watch `id`; returns the crossing, if the
+    /// reading left the bounds, returned, or jumped from one bound past
+    /// the other.
+    fn record(&self, id: &str, value: f64) -> Option<Crossing> {
+        let mut watches = self.watches.lock().unwrap();
+        let watch = watches.iter_mut().find(|w| w.id == id)?;
+        watch.samples += 1;
+        watch.last = Some(value);
+        let now = watch.spec.breach(value);
+        let crossing = match (watch.breached, now) {
+            (None, Some((bound, threshold))) => Some(Crossing {
+                bound,
+                threshold,
+                exceeded: true,
+            }),
+            (Some((bound, threshold)), None) => Some(Crossing {
+                bound,
+                threshold,
+                exceeded: false,
+            }),
+            (Some((was, _)), Some((bound, threshold))) if was != bound => Some(Crossing {
+                bound,
+                threshold,
+                exceeded: true,
+            }),
+            _ => None,
+        };
+        watch.breached = now;
+        crossing
+    }
+
+    /// Run `watch_pid`: start a watch (`pid`), stop one (`stop`), or list
+    /// them. Returns the summary and `data`.
+    pub fn execute(&self, args: &serde_json::Value) -> Result<(String, serde_json::Value), String> {
+        if let Some(id) = args.get("stop").filter(|v| !v.is_null()) {
+            let id = id.as_str().ok_or("stop must be a watch ID string")?;
+            self.stop(id)?;
+            let data = serde_json::json!({"stopped": id, "watches": self.list()});
+            return Ok((format!("Stopped PID watch {id}"), data));
+        }
+        let Some(pid) = args.get("pid").filter(|v| !v.is_null()) else {
+            let watches = self.list();
+            let summary = format!("{} PID watches running", watches.len());
+            return Ok((summary, serde_json::json!({"watches": watches})));
+        };
+
+        let pid = pid
+            .as_u64()
+            .and_then(|p| u8::try_from(p).ok())
+            .ok_or("pid must be an integer 0x00-0xFF")?;
+        let number = |name: &str| -> Result<Option<f64>, String> {
+            match args.get(name).filter(|v| !v.is_null()) {
+                None => Ok(None),
+                Some(v) => v
+                    .as_f64()
+                    .map(Some)
+                    .ok_or_else(|| format!("{name} must be a number")),
+            }
+        };
+        let integer = |name: &str, default: u64| -> Result<u64, String> {
+            match args.get(name).filter(|v| !v.is_null()) {
+                None => Ok(default),
+                Some(v) => v
+                    .as_u64()
+                    .ok_or_else(|| format!("{name} must be a non-negative integer")),
+            }
+        };
+        let duration_secs = integer("duration_secs", DEFAULT_DURATION_SECS)?;
+        if !(1..=MAX_DURATION_SECS).contains(&duration_secs) {
+            return Err(format!(
+                "duration_secs must be 1-{MAX_DURATION_SECS}; use pid_watches for standing watches"
+            ));
+        }
+        let spec = PidWatchConfig {
+            pid,
+            interval_ms: integer("interval_ms", DEFAULT_INTERVAL_MS)?,
+            above: number("above")?,
+            below: number("below")?,
+        };
+        let summary = format!("Watching {} for {duration_secs} s", spec.describe());
+        let id = self.start(spec, Duration::from_secs(duration_secs))?;
+        let data = serde_json::json!({"watch_id": id, "watches": self.list()});
+        Ok((summary, data))
+    }
+}
+
+/// Sample every due watch once, holding `bus_lock`: its telemetry readings
+/// and the threshold events they caused.
+async fn sample_due(
+    can_interface: &dyn CanInterface,
+    bus_lock: &tokio::sync::Mutex<()>,
+    watches: &PidWatches,
+    device_id: &str,
+) -> (Vec<TelemetryReading>, Vec<ThresholdEvent>) {
+    let mut readings = Vec::new();
+    let mut events = Vec::new();
+    let due = watches.take_due(Instant::now());
+    if due.is_empty() {
+        return (readings, events);
+    }
+    let _bus = bus_lock.lock().await;
+    for (id, pid) in due {
+        let Some(pv) = telemetry::read_pid(can_interface, pid, obd::DEFAULT_TIMEOUT).await else {
+            continue;
+        };
+        let metric_name = telemetry::metric_name(pv.name);
+        let unit = (!pv.unit.is_empty()).then(|| pv.unit.to_string());
+        let time = Utc::now();
+        if let Some(crossing) = watches.record(&id, pv.value) {
+            events.push(ThresholdEvent {
+                device_id: device_id.to_string(),
+                watch_id: id.clone(),
+                pid,
+                metric_name: metric_name.clone(),
+                value: pv.value,
+                unit: unit.clone(),
+                bound: crossing.bound,
+                threshold: crossing.threshold,
+                exceeded: crossing.exceeded,
+                time,
+            });
+        }
+        readings.push(TelemetryReading {
+            device_id: device_id.to_string(),
+            time,
+            metric_name,
+            value_numeric: Some(pv.value),
+            value_text: None,
+            value_json: Some(serde_json::json!({"watch_id": id, "pid": pid})),
+            unit,
+            source: TelemetrySource::Obd2,
+        });
+    }
+    (readings, events)
+}
+
+/// Run the watches: standing ones from the runtime config, others as
+/// `watch_pid` starts them. Never returns, so it can sit in the agent's
+/// `select!` alongside the other loops.
+pub async fn run(
+    channel: &MqttChannel,
+    can_interface: &dyn CanInterface,
+    bus_lock: &tokio::sync::Mutex<()>,
+    watches: &PidWatches,
+    mut runtime: RuntimeConfigRx,
+    history: Option<&LocalHistory>,
+) {
+    watches.set_standing(&runtime.borrow_and_update().pid_watches);
+    loop {
+        let wakeup = watches.next_wakeup();
+        tokio::select! {
+            () = async {
+                match wakeup {
+                    Some(at) => tokio::time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            } => {}
+            () = watches.changed.notified() => continue,
+            Ok(()) = runtime.changed() => {
+                watches.set_standing(&runtime.borrow_and_update().pid_watches);
+                continue;
+            }
+        }
+
+        let (readings, events) =
+            sample_due(can_interface, bus_lock, watches, channel.device_id()).await;
+        for event in &events {
+            tracing::info!(
+                watch_id = %event.watch_id,
+                metric = %event.metric_name,
+                value = event.value,
+                bound = event.bound.as_str(),
+                threshold = event.threshold,
+                exceeded = event.exceeded,
+                "PID watch threshold crossed"
+            );
+            if let Err(e) = channel.publish_threshold_event(event).await {
+                tracing::warn!(error = %e, "failed to publish threshold event");
+            }
+        }
+        if readings.is_empty() {
+            continue;
+        }
+        let batch = TelemetryBatch {
+            device_id: channel.device_id().to_string(),
+            readings,
+            collected_at: Utc::now(),
+        };
+        let published = match channel.publish_telemetry(&batch).await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to publish PID watch readings");
+                false
+            }
+        };
+        if let Some(history) = history {
+            history.record_telemetry(&batch.readings, published);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use zc_canbus_tools::{CanFrame, MockCanInterface};
+
+    fn coolant(above: Option<f64>, below: Option<f64>) -> PidWatchConfig {
+        PidWatchConfig {
+            pid: 0x05,
+            interval_ms: 1000,
+            above,
+            below,
+        }
+    }
+
+    #[test]
+    fn watch_specs_are_checked() {
+        assert!(coolant(Some(110.0), Some(60.0)).issues().is_empty());
+        let bad = PidWatchConfig {
+            interval_ms: 10,
+            ..coolant(Some(60.0), Some(110.0))
+        };
+        let fields: Vec<&str> = bad.issues().iter().map(|(f, _)| *f).collect();
+        assert_eq!(fields, ["interval_ms", "below"]);
+        assert_eq!(coolant(Some(f64::NAN), None).issues()[0].0, "above");
+    }
+
+    #[test]
+    fn crossings_fire_once_per_edge() {
+        let watches = PidWatches::new();
+        let id = watches
+            .start(coolant(Some(110.0), Some(60.0)), Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(watches.record(&id, 90.0), None);
+        let up = watches.record(&id, 112.0).unwrap();
+        assert_eq!(
+            (up.bound, up.threshold, up.exceeded),
+            (ThresholdBound::Above, 110.0, true)
+        );
+        // Still hot: no repeat.
+        assert_eq!(watches.record(&id, 115.0), None);
+        let back = watches.record(&id, 100.0).unwrap();
+        assert_eq!((back.bound, back.exceeded), (ThresholdBound::Above, false));
+        // From one side straight past the other.
+        watches.record(&id, 50.0).unwrap();
+        let jump = watches.record(&id, 120.0).unwrap();
+        assert_eq!((jump.bound, jump.exceeded), (ThresholdBound::Above, true));
+    }
+
+    #[test]
+    fn tool_starts_lists_and_stops_watches() {
+        let watches = PidWatches::new();
+        let (summary, data) = watches
+            .execute(&json!({"pid": 5, "above": 110, "duration_secs": 600}))
+            .unwrap();
+        assert_eq!(
+            summary,
+            "Watching PID 0x05 every 1000 ms, alerting above 110 for 600 s"
+        );
+        let id = data["watch_id"].as_str().unwrap().to_string();
+        assert_eq!(data["watches"][0]["remaining_secs"], 599);
+
+        let (summary, data) = watches.execute(&json!({})).unwrap();
+        assert_eq!(summary, "1 PID watches running");
+        assert_eq!(data["watches"][0]["watch_id"], id.as_str());
+
+        watches.execute(&json!({"stop": id})).unwrap();
+        assert!(watches.list().is_empty());
+        assert!(watches.execute(&json!({"stop": id})).is_err());
+
+        for bad in [
+            json!({"pid": 300}),
+            json!({"pid": 5, "duration_secs": 0}),
+            json!({"pid": 5, "duration_secs": 7200}),
+            json!({"pid": 5, "interval_ms": 5}),
+            json!({"pid": 5, "above": "hot"}),
+        ] {
+            assert!(watches.execute(&bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn watch_count_is_capped() {
+        let watches = PidWatches::new();
+        watches.set_standing(&[coolant(None, None), coolant(Some(100.0), None)]);
+        for _ in 2..MAX_WATCHES {
+            watches
+                .start(coolant(None, None), Duration::from_secs(60))
+                .unwrap();
+        }
+        let err = watches
+            .start(coolant(None, None), Duration::from_secs(60))
+            .unwrap_err();
+        assert!(err.contains("at most 8"));
+    }
+
+    #[test]
+    fn standing_watches_keep_state_unless_changed() {
+        let watches = PidWatches::new();
+        watches.set_standing(&[coolant(Some(110.0), None), coolant(None, Some(10.0))]);
+        assert!(watches.record("config-0", 120.0).is_some());
+        assert!(watches.stop("config-0").is_err());
+
+        // config-0 unchanged keeps its breach; config-1 changed starts over.
+        watches.set_standing(&[coolant(Some(110.0), None), coolant(None, Some(20.0))]);
+        assert_eq!(watches.record("config-0", 125.0), None);
+        let list = watches.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0]["samples"], 2);
+        assert_eq!(list[1]["samples"], 0);
+
+        watches.set_standing(&[]);
+        assert!(watches.list().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn due_watches_produce_readings_and_events() {
+        // Coolant 130 - 40 = 90 °C, then 152 - 40 = 112 °C.
+        let can = MockCanInterface::with_responses(vec![
+            CanFrame::new(0x7E8, vec![0x03, 0x41, 0x05, 130, 0, 0, 0, 0]),
+            CanFrame::new(0x7E8, vec![0x03, 0x41, 0x05, 152, 0, 0, 0, 0]),
+        ]);
+        let bus_lock = tokio::sync::Mutex::new(());
+        let watches = PidWatches::new();
+        watches.set_standing(&[coolant(Some(110.0), None)]);
+
+        let (readings, events) = sample_due(&can, &bus_lock, &watches, "rpi-001").await;
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].metric_name, "coolant_temperature");
+        assert_eq!(readings[0].value_numeric, Some(90.0));
+        assert_eq!(
+            readings[0].value_json.as_ref().unwrap()["watch_id"],
+            "config-0"
+        );
+        assert!(events.is_empty());
+
+        // Not due again until the interval has passed.
+        assert!(
+            sample_due(&can, &bus_lock, &watches, "rpi-001")
+                .await
+                .0
+                .is_empty()
+        );
+        tokio::time::advance(Duration::from_millis(1000)).await;
+        let (readings, events) = sample_due(&can, &bus_lock, &watches, "rpi-001").await;
+        assert_eq!(readings[0].value_numeric, Some(112.0));
+        assert_eq!(events.len(), 1);
+        assert!(events[0].exceeded);
+        assert_eq!(events[0].threshold, 110.0);
+        assert_eq!(events[0].unit.as_deref(), Some("°C"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn due_watches_wait_for_a_command_holding_the_bus() {
+        let can = MockCanInterface::with_responses(vec![CanFrame::new(
+            0x7E8,
+            vec![0x03, 0x41, 0x05, 130, 0, 0, 0, 0],
+        )]);
+        let bus_lock = tokio::sync::Mutex::new(());
+        let watches = PidWatches::new();
+        watches.set_standing(&[coolant(None, None)]);
+
+        let command = bus_lock.lock().await;
+        let round = sample_due(&can, &bus_lock, &watches, "rpi-001");
+        tokio::pin!(round);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut round)
+                .await
+                .is_err()
+        );
+        assert!(can.sent_frames().is_empty());
+
+        drop(command);
+        let (readings, _) = round.await;
+        assert_eq!(readings.len(), 1);
+        assert_eq!(can.sent_frames().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_watches_are_dropped() {
+        let watches = PidWatches::new();
+        watches
+            .start(coolant(None, None), Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(watches.take_due(Instant::now()).len(), 1);
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(watches.take_due(Instant::now()).is_empty());
+        assert!(watches.list().is_empty());
+    }
+}
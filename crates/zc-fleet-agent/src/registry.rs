@@ -4,9 +4,15 @@
 //! incoming command envelopes.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use zc_canbus_tools::{CanInterface, CanTool};
-use zc_log_tools::{LogSource, LogTool};
+use zc_canbus_tools::dbc::Dbc;
+use zc_canbus_tools::dtc_db::{self, DtcDatabase};
+use zc_canbus_tools::{CanError, CanInterface, CanTool, ChunkSink};
+use zc_log_tools::{CustomFormats, LogError, LogSource, LogTool};
+use zc_protocol::commands::ErrorCode;
+
+use crate::tool_stats::{ToolStats, ToolTimer};
 
 /// Which subsystem a tool belongs to.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,6 +21,52 @@ pub enum ToolKind {
     Log,
 }
 
+/// A tool that failed to run, with the failure class for the response.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{message}")]
+pub struct ToolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ToolError {
+    fn internal(message: impl ToString) -> Self {
+        Self {
+            code: ErrorCode::Internal,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<CanError> for ToolError {
+    fn from(err: CanError) -> Self {
+        let code = match err {
+            CanError::Timeout { .. } => ErrorCode::CanTimeout,
+            CanError::Other(_) => ErrorCode::Internal,
+            _ => ErrorCode::CanError,
+        };
+        Self {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<LogError> for ToolError {
+    fn from(err: LogError) -> Self {
+        let code = match err {
+            LogError::NotFound(_) => ErrorCode::LogFileNotFound,
+            // Log tools report missing or malformed arguments as `Other`.
+            LogError::Regex(_) | LogError::Other(_) => ErrorCode::InvalidArgs,
+            _ => ErrorCode::LogError,
+        };
+        Self {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
 /// Metadata about a registered tool (used by tool listing API).
 #[allow(dead_code)]
 pub struct ToolInfo {
@@ -32,6 +84,8 @@ pub struct ToolRegistry {
     log_tools: Vec<Box<dyn LogTool>>,
     /// Map from tool name → (kind, index into the appropriate Vec).
     index: HashMap<String, (ToolKind, usize)>,
+    /// Latency, outcome and result size of every execution.
+    stats: ToolStats,
 }
 
 impl ToolRegistry {
@@ -50,14 +104,37 @@ impl ToolRegistry {
             can_tools,
             log_tools,
             index,
+            stats: ToolStats::new(),
         }
     }
 
     /// Build with the default set of all tools from both crates.
     pub fn with_defaults() -> Self {
+        Self::configured(false, Arc::default(), dtc_db::builtin(), Arc::default())
+    }
+
+    /// Build with the default tools plus bench-only tools (`send_frame`).
+    pub fn with_bench_tools() -> Self {
+        Self::configured(true, Arc::default(), dtc_db::builtin(), Arc::default())
+    }
+
+    /// Build the default tools, plus bench-only tools when `bench_mode`,
+    /// with log tools that also understand the device's custom formats, DTC
+    /// tools that describe codes from `dtc_db` and `can_monitor` decoding
+    /// frames with `dbc`.
+    pub fn configured(
+        bench_mode: bool,
+        log_formats: Arc<CustomFormats>,
+        dtc_db: Arc<dyn DtcDatabase>,
+        dbc: Arc<Dbc>,
+    ) -> Self {
+        let mut can_tools = zc_canbus_tools::tools::with_databases(dtc_db, dbc);
+        if bench_mode {
+            can_tools.extend(zc_canbus_tools::tools::bench_tools());
+        }
         Self::new(
-            zc_canbus_tools::tools::all_tools(),
-            zc_log_tools::tools::all_tools(),
+            can_tools,
+            zc_log_tools::tools::with_custom_formats(log_formats),
         )
     }
 
@@ -72,12 +149,15 @@ impl ToolRegistry {
         index: usize,
         args: serde_json::Value,
         interface: &dyn CanInterface,
-    ) -> Result<serde_json::Value, String> {
+    ) -> Result<serde_json::Value, ToolError> {
         let tool = &self.can_tools[index];
-        match tool.execute(args, interface).await {
-            Ok(result) => serde_json::to_value(result).map_err(|e| e.to_string()),
-            Err(e) => Err(e.to_string()),
-        }
+        let timer = self.stats.start(tool.name());
+        let outcome = tool
+            .execute(args, interface)
+            .await
+            .map_err(ToolError::from)
+            .and_then(|result| serde_json::to_value(result).map_err(ToolError::internal));
+        finish(timer, outcome)
     }
 
     /// Execute a log tool by index.
@@ -86,16 +166,67 @@ impl ToolRegistry {
         index: usize,
         args: serde_json::Value,
         source: &dyn LogSource,
-    ) -> Result<serde_json::Value, String> {
+    ) -> Result<serde_json::Value, ToolError> {
         let tool = &self.log_tools[index];
-        match tool.execute(args, source).await {
-            Ok(result) => serde_json::to_value(result).map_err(|e| e.to_string()),
-            Err(e) => Err(e.to_string()),
+        let timer = self.stats.start(tool.name());
+        let outcome = tool
+            .execute(args, source)
+            .await
+            .map_err(ToolError::from)
+            .and_then(|result| serde_json::to_value(result).map_err(ToolError::internal));
+        finish(timer, outcome)
+    }
+
+    /// Execution statistics of every tool run through this registry.
+    pub fn stats(&self) -> &ToolStats {
+        &self.stats
+    }
+
+    /// Whether a tool can emit partial results while it runs.
+    pub fn supports_streaming(&self, kind: ToolKind, index: usize) -> bool {
+        match kind {
+            ToolKind::CanBus => self.can_tools[index].supports_streaming(),
+            ToolKind::Log => self.log_tools[index].supports_streaming(),
         }
     }
 
-    /// List all registered tools with metadata (used by tool listing API).
-    #[allow(dead_code)]
+    /// Execute a CAN tool by index, passing partial results to `sink`.
+    pub async fn execute_can_streaming(
+        &self,
+        index: usize,
+        args: serde_json::Value,
+        interface: &dyn CanInterface,
+        sink: &ChunkSink<'_>,
+    ) -> Result<serde_json::Value, ToolError> {
+        let tool = &self.can_tools[index];
+        let timer = self.stats.start(tool.name());
+        let outcome = tool
+            .execute_streaming(args, interface, sink)
+            .await
+            .map_err(ToolError::from)
+            .and_then(|result| serde_json::to_value(result).map_err(ToolError::internal));
+        finish(timer, outcome)
+    }
+
+    /// Execute a log tool by index, passing partial results to `sink`.
+    pub async fn execute_log_streaming(
+        &self,
+        index: usize,
+        args: serde_json::Value,
+        source: &dyn LogSource,
+        sink: &ChunkSink<'_>,
+    ) -> Result<serde_json::Value, ToolError> {
+        let tool = &self.log_tools[index];
+        let timer = self.stats.start(tool.name());
+        let outcome = tool
+            .execute_streaming(args, source, sink)
+            .await
+            .map_err(ToolError::from)
+            .and_then(|result| serde_json::to_value(result).map_err(ToolError::internal));
+        finish(timer, outcome)
+    }
+
+    /// List all registered tools with metadata (used for the tool catalog).
     pub fn list_tools(&self) -> Vec<ToolInfo> {
         let mut tools = Vec::new();
         for tool in &self.can_tools {
@@ -117,6 +248,15 @@ impl ToolRegistry {
         tools
     }
 
+    /// Names of all registered tools, CAN tools first.
+    pub fn tool_names(&self) -> Vec<String> {
+        self.can_tools
+            .iter()
+            .map(|t| t.name().to_string())
+            .chain(self.log_tools.iter().map(|t| t.name().to_string()))
+            .collect()
+    }
+
     /// Total number of registered tools.
     pub fn len(&self) -> usize {
         self.can_tools.len() + self.log_tools.len()
@@ -128,6 +268,22 @@ impl ToolRegistry {
     }
 }
 
+/// Record how a run ended: errors and results with `success: false` are
+/// failures, sized by their serialized result.
+fn finish(
+    timer: ToolTimer<'_>,
+    outcome: Result<serde_json::Value, ToolError>,
+) -> Result<serde_json::Value, ToolError> {
+    match &outcome {
+        Ok(value) => timer.finish(
+            value["success"].as_bool().unwrap_or(true),
+            value.to_string().len(),
+        ),
+        Err(e) => timer.finish(false, e.message.len()),
+    }
+    outcome
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,7 +291,17 @@ mod tests {
     #[test]
     fn registry_with_defaults() {
         let reg = ToolRegistry::with_defaults();
-        assert_eq!(reg.len(), 13); // 8 CAN + 5 log
+        assert_eq!(reg.len(), 20); // 12 CAN + 8 log
+    }
+
+    #[test]
+    fn send_frame_only_in_bench_mode() {
+        assert!(ToolRegistry::with_defaults().lookup("send_frame").is_none());
+
+        let reg = ToolRegistry::with_bench_tools();
+        assert_eq!(reg.len(), 21);
+        let (kind, _idx) = reg.lookup("send_frame").unwrap();
+        assert_eq!(kind, ToolKind::CanBus);
     }
 
     #[test]
@@ -162,7 +328,7 @@ mod tests {
     fn list_tools_has_all() {
         let reg = ToolRegistry::with_defaults();
         let tools = reg.list_tools();
-        assert_eq!(tools.len(), 13);
+        assert_eq!(tools.len(), 20);
         let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
         assert!(names.contains(&"read_pid"));
         assert!(names.contains(&"read_dtcs"));
@@ -172,11 +338,17 @@ mod tests {
         assert!(names.contains(&"read_uds_dtcs"));
         assert!(names.contains(&"read_uds_did"));
         assert!(names.contains(&"uds_session_control"));
+        assert!(names.contains(&"pid_burst"));
+        assert!(names.contains(&"list_supported_pids"));
+        assert!(names.contains(&"read_readiness"));
+        assert!(names.contains(&"read_vehicle_info"));
         assert!(names.contains(&"search_logs"));
         assert!(names.contains(&"analyze_errors"));
         assert!(names.contains(&"log_stats"));
         assert!(names.contains(&"tail_logs"));
         assert!(names.contains(&"query_journal"));
+        assert!(names.contains(&"detect_anomalies"));
+        assert!(names.contains(&"upload_logs"));
     }
 
     #[tokio::test]
@@ -206,4 +378,65 @@ mod tests {
             .await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn missing_log_file_is_classified() {
+        let reg = ToolRegistry::with_defaults();
+        let (_, idx) = reg.lookup("log_stats").unwrap();
+        let mock = zc_log_tools::MockLogSource::with_syslog_sample();
+        let err = reg
+            .execute_log(
+                idx,
+                serde_json::json!({"path": "/var/log/missing.log"}),
+                &mock,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::LogFileNotFound);
+        let err = reg
+            .execute_log(idx, serde_json::json!({}), &mock)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgs);
+
+        let timeout = ToolError::from(CanError::Timeout { timeout_ms: 1000 });
+        assert_eq!(timeout.code, ErrorCode::CanTimeout);
+        assert_eq!(timeout.message, "Response timeout after 1000ms");
+    }
+
+    #[tokio::test]
+    async fn executions_are_recorded_in_stats() {
+        let reg = ToolRegistry::with_defaults();
+        let (_, idx) = reg.lookup("log_stats").unwrap();
+        let mock = zc_log_tools::MockLogSource::with_syslog_sample();
+        reg.execute_log(idx, serde_json::json!({"path": "/var/log/syslog"}), &mock)
+            .await
+            .unwrap();
+        reg.execute_log(idx, serde_json::json!({}), &mock)
+            .await
+            .unwrap_err();
+
+        let stats = reg.stats().snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].tool, "log_stats");
+        assert_eq!(
+            (stats[0].calls, stats[0].succeeded, stats[0].failed),
+            (2, 1, 1)
+        );
+        assert!(stats[0].payload_bytes.max > 0);
+    }
+
+    #[test]
+    fn streaming_support_flags() {
+        let reg = ToolRegistry::with_defaults();
+        for (name, streams) in [
+            ("can_monitor", true),
+            ("tail_logs", true),
+            ("read_pid", false),
+            ("log_stats", false),
+        ] {
+            let (kind, idx) = reg.lookup(name).unwrap();
+            assert_eq!(reg.supports_streaming(kind, idx), streams, "{name}");
+        }
+    }
 }
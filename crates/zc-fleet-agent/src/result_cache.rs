@@ -0,0 +1,283 @@
+//! Agent-side cache of tool results.
+//!
+//! Dashboards poll the same read-only tools over and over. With the cache
+//! on, a successful result is reused for an identical `(tool_name,
+//! tool_args)` until the tool's TTL runs out, instead of re-running a log
+//! scan or CAN query; such responses carry `cached: true`. Only tools with
+//! a TTL are cached, and streaming runs and failures never are. Entries
+//! live in memory and start empty after a restart.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// TTLs used unless overridden in `[result_cache]`. The VIN and the
+/// supported PIDs do not change while the device stays in one vehicle;
+/// log summaries are cheap to keep for one dashboard refresh.
+pub const DEFAULT_TTLS: &[(&str, Ttl)] = &[
+    ("read_vin", Ttl::Forever),
+    ("list_supported_pids", Ttl::Forever),
+    ("log_stats", Ttl::Secs(30)),
+    ("analyze_errors", Ttl::Secs(30)),
+    ("search_logs", Ttl::Secs(30)),
+    ("detect_anomalies", Ttl::Secs(30)),
+];
+
+/// How long a tool's result stays valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ttl {
+    Forever,
+    Secs(u64),
+}
+
+/// Result cache settings (`[result_cache]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResultCacheConfig {
+    /// Serve repeated identical tool calls from the cache. Off by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Per-tool TTL in seconds, on top of [`DEFAULT_TTLS`]; 0 turns caching
+    /// off for that tool.
+    #[serde(default)]
+    pub ttl_secs: BTreeMap<String, u64>,
+    /// Tools whose results never expire.
+    #[serde(default)]
+    pub forever: Vec<String>,
+    /// Entries kept; expired and then oldest entries are dropped first.
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_max_entries() -> usize {
+    256
+}
+
+impl Default for ResultCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: BTreeMap::new(),
+            forever: Vec::new(),
+            max_entries: default_max_entries(),
+        }
+    }
+}
+
+impl ResultCacheConfig {
+    /// Effective TTL per tool: defaults, then `forever`, then `ttl_secs`.
+    pub fn ttls(&self) -> HashMap<String, Ttl> {
+        let mut ttls: HashMap<String, Ttl> = DEFAULT_TTLS
+            .iter()
+            .map(|(tool, ttl)| (tool.to_string(), *ttl))
+            .collect();
+        for tool in &self.forever {
+            ttls.insert(tool.clone(), Ttl::Forever);
+        }
+        for (tool, secs) in &self.ttl_secs {
+            if *secs == 0 {
+                ttls.remove(tool);
+            } else {
+                ttls.insert(tool.clone(), Ttl::Secs(*secs));
+            }
+        }
+        ttls
+    }
+}
+
+/// A cached successful tool result.
+#[derive(Debug, Clone)]
+pub struct CachedResult {
+    pub response_text: Option<String>,
+    pub response_data: Option<serde_json::Value>,
+    /// Time since the tool actually ran.
+    pub age: Duration,
+}
+
+#[derive(Debug)]
+struct Entry {
+    result: CachedResult,
+    /// Insertion order, for evicting the oldest entry.
+    seq: u64,
+    stored_at: Instant,
+    expires_at: Option<Instant>,
+}
+
+impl Entry {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|at| now < at)
+    }
+}
+
+/// In-memory result cache keyed by tool name and canonical arguments.
+#[derive(Debug)]
+pub struct ResultCache {
+    ttls: HashMap<String, Ttl>,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<(String, String), Entry>,
+    next_seq: u64,
+}
+
+impl ResultCache {
+    pub fn new(config: &ResultCacheConfig) -> Self {
+        Self {
+            ttls: config.ttls(),
+            max_entries: config.max_entries.max(1),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// TTL of `tool`, or `None` if its results are not cached.
+    pub fn ttl(&self, tool: &str) -> Option<Ttl> {
+        self.ttls.get(tool).copied()
+    }
+
+    /// The live cached result for this call, if any.
+    pub fn get(&self, tool: &str, args: &serde_json::Value) -> Option<CachedResult> {
+        self.ttl(tool)?;
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let key = key(tool, args);
+        let entry = entries.map.get(&key)?;
+        if !entry.is_live(now) {
+            entries.map.remove(&key);
+            return None;
+        }
+        let mut result = entry.result.clone();
+        result.age = now - entry.stored_at;
+        Some(result)
+    }
+
+    /// Remember a successful result; ignored for tools without a TTL.
+    pub fn put(
+        &self,
+        tool: &str,
+        args: &serde_json::Value,
+        response_text: Option<String>,
+        response_data: Option<serde_json::Value>,
+    ) {
+        let Some(ttl) = self.ttl(tool) else {
+            return;
+        };
+        let now = Instant::now();
+        let expires_at = match ttl {
+            Ttl::Forever => None,
+            Ttl::Secs(secs) => Some(now + Duration::from_secs(secs)),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        let key = key(tool, args);
+        if !entries.map.contains_key(&key) && entries.map.len() >= self.max_entries {
+            entries.map.retain(|_, e| e.is_live(now));
+            if entries.map.len() >= self.max_entries
+                && let Some(oldest) = entries
+                    .map
+                    .iter()
+                    .min_by_key(|(_, e)| e.seq)
+                    .map(|(k, _)| k.clone())
+            {
+                entries.map.remove(&oldest);
+            }
+        }
+        let seq = entries.next_seq;
+        entries.next_seq += 1;
+        entries.map.insert(
+            key,
+            Entry {
+                result: CachedResult {
+                    response_text,
+                    response_data,
+                    age: Duration::ZERO,
+                },
+                seq,
+                stored_at: now,
+                expires_at,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Cache key: object keys serialize sorted, so argument order does not
+/// matter.
+fn key(tool: &str, args: &serde_json::Value) -> (String, String) {
+    (tool.to_string(), args.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn config_overrides_default_ttls() {
+        let config: ResultCacheConfig = toml::from_str(
+            r#"
+            enabled = true
+            forever = ["read_uds_did"]
+            [ttl_secs]
+            log_stats = 5
+            search_logs = 0
+            "#,
+        )
+        .unwrap();
+        let cache = ResultCache::new(&config);
+        assert_eq!(cache.ttl("read_vin"), Some(Ttl::Forever));
+        assert_eq!(cache.ttl("read_uds_did"), Some(Ttl::Forever));
+        assert_eq!(cache.ttl("log_stats"), Some(Ttl::Secs(5)));
+        assert_eq!(cache.ttl("search_logs"), None);
+        assert_eq!(cache.ttl("read_dtcs"), None);
+    }
+
+    #[test]
+    fn hits_match_tool_and_args() {
+        let cache = ResultCache::new(&ResultCacheConfig::default());
+        let args = json!({"path": "/var/log/syslog", "since": "1h"});
+        cache.put(
+            "log_stats",
+            &args,
+            Some("120 entries".into()),
+            Some(json!({"total": 120})),
+        );
+        cache.put("read_dtcs", &json!({}), None, Some(json!({"dtcs": []})));
+
+        let reordered: serde_json::Value =
+            serde_json::from_str(r#"{"since": "1h", "path": "/var/log/syslog"}"#).unwrap();
+        let hit = cache.get("log_stats", &reordered).unwrap();
+        assert_eq!(hit.response_text.as_deref(), Some("120 entries"));
+        assert!(
+            cache
+                .get("log_stats", &json!({"path": "/var/log/auth.log"}))
+                .is_none()
+        );
+        assert!(cache.get("read_dtcs", &json!({})).is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn oldest_entry_is_dropped_when_full() {
+        let cache = ResultCache::new(&ResultCacheConfig {
+            max_entries: 2,
+            ..Default::default()
+        });
+        for path in ["/a", "/b", "/c"] {
+            cache.put("log_stats", &json!({"path": path}), None, None);
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("log_stats", &json!({"path": "/a"})).is_none());
+        assert!(cache.get("log_stats", &json!({"path": "/c"})).is_some());
+    }
+}
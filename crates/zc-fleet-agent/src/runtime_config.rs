@@ -0,0 +1,312 @@
+//! Settings that can change without restarting the agent.
+//!
+//! The cloud publishes a `ConfigUpdate` on `broadcast/config/update`:
+//!
+//! ```json
+//! {"version": 4, "heartbeat_interval_secs": 15, "telemetry_window_secs": 30,
+//!  "telemetry_sample_interval_ms": 500, "ollama_model": "gemma:2b",
+//!  "log_paths": ["/var/log/syslog"],
+//!  "pid_watches": [{"pid": 5, "interval_ms": 1000, "above": 110.0}]}
+//! ```
+//!
+//! Every field except `version` is optional. The update is validated
+//! against the current settings as a whole and, if valid, the new
+//! [`RuntimeConfig`] replaces the old one in a single `watch` send, so the
+//! heartbeat, telemetry, PID watch and shadow loops never see a
+//! half-applied update. `pid_watches` replaces the standing watches as a
+//! whole.
+//! Updates with a version not above the applied one are ignored. The
+//! applied version is reported in the `diagnostics` shadow.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::config::{
+    AgentConfig, ConfigIssue, HEARTBEAT_SECS, TELEMETRY_SAMPLE_MS, TELEMETRY_WINDOW_SECS,
+    check_range,
+};
+use crate::pid_watch::{self, PidWatchConfig};
+
+/// The runtime-adjustable subset of [`AgentConfig`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuntimeConfig {
+    /// Version of the last applied update; 0 means the config file values.
+    pub version: u64,
+    pub heartbeat_interval_secs: u64,
+    pub telemetry_window_secs: u64,
+    pub telemetry_sample_interval_ms: u64,
+    pub ollama_model: String,
+    pub log_paths: Vec<String>,
+    pub pid_watches: Vec<PidWatchConfig>,
+}
+
+/// A config update from the cloud. Unset fields keep their current value.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigUpdate {
+    pub version: u64,
+    pub heartbeat_interval_secs: Option<u64>,
+    pub telemetry_window_secs: Option<u64>,
+    pub telemetry_sample_interval_ms: Option<u64>,
+    pub ollama_model: Option<String>,
+    pub log_paths: Option<Vec<String>>,
+    pub pid_watches: Option<Vec<PidWatchConfig>>,
+}
+
+/// Why an update was not applied.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UpdateError {
+    #[error("invalid config update: {0}")]
+    Malformed(String),
+
+    #[error("config version {received} is not newer than applied version {applied}")]
+    Stale { received: u64, applied: u64 },
+
+    #[error("config update {version} rejected: {}", format_issues(.issues))]
+    Invalid {
+        version: u64,
+        issues: Vec<ConfigIssue>,
+    },
+}
+
+fn format_issues(issues: &[ConfigIssue]) -> String {
+    issues
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Sender side, held by the MQTT loop.
+pub type RuntimeConfigTx = watch::Sender<RuntimeConfig>;
+
+/// Receiver side, one clone per consuming loop.
+pub type RuntimeConfigRx = watch::Receiver<RuntimeConfig>;
+
+impl RuntimeConfig {
+    /// Initial values from the config file.
+    pub fn from_agent(config: &AgentConfig) -> Self {
+        Self {
+            version: 0,
+            heartbeat_interval_secs: config.heartbeat_interval_secs,
+            telemetry_window_secs: config.telemetry.window_secs,
+            telemetry_sample_interval_ms: config.telemetry.sample_interval_ms,
+            ollama_model: config.ollama.model.clone(),
+            log_paths: config.log_paths.clone(),
+            pid_watches: config.pid_watches.clone(),
+        }
+    }
+
+    /// The config that results from applying `update`, or every reason it
+    /// cannot be applied.
+    pub fn apply(&self, update: &ConfigUpdate) -> Result<Self, UpdateError> {
+        if update.version <= self.version {
+            return Err(UpdateError::Stale {
+                received: update.version,
+                applied: self.version,
+            });
+        }
+
+        let next = Self {
+            version: update.version,
+            heartbeat_interval_secs: update
+                .heartbeat_interval_secs
+                .unwrap_or(self.heartbeat_interval_secs),
+            telemetry_window_secs: update
+                .telemetry_window_secs
+                .unwrap_or(self.telemetry_window_secs),
+            telemetry_sample_interval_ms: update
+                .telemetry_sample_interval_ms
+                .unwrap_or(self.telemetry_sample_interval_ms),
+            ollama_model: update
+                .ollama_model
+                .clone()
+                .unwrap_or_else(|| self.ollama_model.clone()),
+            log_paths: update
+                .log_paths
+                .clone()
+                .unwrap_or_else(|| self.log_paths.clone()),
+            pid_watches: update
+                .pid_watches
+                .clone()
+                .unwrap_or_else(|| self.pid_watches.clone()),
+        };
+
+        let issues = next.validate();
+        if issues.is_empty() {
+            Ok(next)
+        } else {
+            Err(UpdateError::Invalid {
+                version: update.version,
+                issues,
+            })
+        }
+    }
+
+    fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |field: &str, message: String| {
+            issues.push(ConfigIssue {
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        check_range(
+            &mut issue,
+            "heartbeat_interval_secs",
+            self.heartbeat_interval_secs,
+            HEARTBEAT_SECS,
+        );
+        check_range(
+            &mut issue,
+            "telemetry_window_secs",
+            self.telemetry_window_secs,
+            TELEMETRY_WINDOW_SECS,
+        );
+        check_range(
+            &mut issue,
+            "telemetry_sample_interval_ms",
+            self.telemetry_sample_interval_ms,
+            TELEMETRY_SAMPLE_MS,
+        );
+        if self.telemetry_sample_interval_ms > self.telemetry_window_secs * 1000 {
+            issue(
+                "telemetry_sample_interval_ms",
+                "must not exceed the aggregation window".into(),
+            );
+        }
+        if self.ollama_model.trim().is_empty() {
+            issue("ollama_model", "must not be empty".into());
+        }
+        if self.log_paths.iter().any(|p| p.trim().is_empty()) {
+            issue("log_paths", "must not contain empty paths".into());
+        }
+        for (field, message) in pid_watch::list_issues(&self.pid_watches) {
+            issue(&field, message);
+        }
+        issues
+    }
+}
+
+/// Apply a `ConfigUpdate` payload and publish the result to every
+/// receiver. Returns the applied version.
+pub fn handle_update(
+    payload: &serde_json::Value,
+    tx: &RuntimeConfigTx,
+) -> Result<u64, UpdateError> {
+    let update: ConfigUpdate = serde_json::from_value(payload.clone())
+        .map_err(|e| UpdateError::Malformed(e.to_string()))?;
+    let next = tx.borrow().apply(&update)?;
+    let version = next.version;
+    tx.send_replace(next);
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn initial() -> RuntimeConfig {
+        RuntimeConfig {
+            version: 0,
+            heartbeat_interval_secs: 30,
+            telemetry_window_secs: 10,
+            telemetry_sample_interval_ms: 1000,
+            ollama_model: "phi3:mini".into(),
+            log_paths: vec!["/var/log/syslog".into()],
+            pid_watches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn partial_update_keeps_other_fields() {
+        let (tx, mut rx) = watch::channel(initial());
+        let version = handle_update(
+            &json!({"version": 2, "heartbeat_interval_secs": 15, "ollama_model": "gemma:2b"}),
+            &tx,
+        )
+        .unwrap();
+
+        assert_eq!(version, 2);
+        assert!(rx.has_changed().unwrap());
+        let applied = rx.borrow_and_update().clone();
+        assert_eq!(applied.heartbeat_interval_secs, 15);
+        assert_eq!(applied.ollama_model, "gemma:2b");
+        assert_eq!(applied.telemetry_window_secs, 10);
+        assert_eq!(applied.log_paths, vec!["/var/log/syslog"]);
+    }
+
+    #[test]
+    fn invalid_update_is_rejected_whole() {
+        let (tx, rx) = watch::channel(initial());
+        let err = handle_update(
+            &json!({"version": 1, "heartbeat_interval_secs": 1, "ollama_model": "gemma:2b"}),
+            &tx,
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, UpdateError::Invalid { version: 1, .. }));
+        assert!(err.to_string().contains("heartbeat_interval_secs"));
+        assert!(!rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow(), initial());
+    }
+
+    #[test]
+    fn sample_interval_checked_against_new_window() {
+        let err = initial()
+            .apply(&ConfigUpdate {
+                version: 1,
+                heartbeat_interval_secs: None,
+                telemetry_window_secs: Some(1),
+                // Fine under the old 10 s window, too slow for the new one.
+                telemetry_sample_interval_ms: Some(2000),
+                ollama_model: None,
+                log_paths: None,
+                pid_watches: None,
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("telemetry_sample_interval_ms"));
+    }
+
+    #[test]
+    fn stale_and_malformed_updates_are_ignored() {
+        let (tx, _rx) = watch::channel(initial());
+        handle_update(&json!({"version": 3}), &tx).unwrap();
+
+        let stale = handle_update(&json!({"version": 3, "heartbeat_interval_secs": 60}), &tx);
+        assert_eq!(
+            stale.unwrap_err(),
+            UpdateError::Stale {
+                received: 3,
+                applied: 3
+            }
+        );
+
+        let unknown = handle_update(&json!({"version": 4, "telemetry_interval_secs": 60}), &tx);
+        assert!(matches!(unknown, Err(UpdateError::Malformed(_))));
+        assert_eq!(tx.borrow().version, 3);
+    }
+
+    #[test]
+    fn pid_watches_are_replaced_and_checked() {
+        let (tx, rx) = watch::channel(initial());
+        handle_update(
+            &json!({"version": 1, "pid_watches": [{"pid": 5, "above": 110.0}]}),
+            &tx,
+        )
+        .unwrap();
+        let watches = rx.borrow().pid_watches.clone();
+        assert_eq!(watches.len(), 1);
+        assert_eq!(watches[0].interval_ms, 1000);
+
+        let err = handle_update(
+            &json!({"version": 2, "pid_watches": [{"pid": 5, "interval_ms": 1}]}),
+            &tx,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("pid_watches[0].interval_ms"));
+        assert_eq!(rx.borrow().pid_watches, watches);
+    }
+}
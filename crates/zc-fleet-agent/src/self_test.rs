@@ -0,0 +1,580 @@
+//! Device self-test and provisioning verification.
+//!
+//! Checks that a freshly installed device can do its job: CAN interface
+//! present and up, log files readable, Ollama reachable, broker reachable,
+//! clock set, and enough disk space. The agent runs it once on first boot
+//! (guarded by a marker file) and on demand through the `self_test`
+//! command. Either way the [`SelfTestReport`] is published on the
+//! `selftest/report` topic, where the cloud stores it against the device
+//! as its provisioning verification record.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Utc};
+use serde::Deserialize;
+
+use zc_protocol::catalog::ToolSpec;
+use zc_protocol::self_test::{CheckStatus, SelfTestCheck, SelfTestReport, SelfTestTrigger};
+
+use crate::can_bus::CanAdapter;
+use crate::config::AgentConfig;
+use crate::runtime_config::RuntimeConfigRx;
+
+/// Tool name the executor routes to [`SelfTest::run`].
+pub const TOOL_NAME: &str = "self_test";
+
+/// Catalog entry for [`TOOL_NAME`].
+pub fn tool_spec() -> ToolSpec {
+    ToolSpec::new(
+        TOOL_NAME,
+        "Run the device provisioning self-test (CAN interface, log paths, Ollama, broker connectivity, clock, disk space)",
+        serde_json::json!({"type": "object", "properties": {}}),
+    )
+}
+
+/// Timeout for the broker TCP connect.
+const BROKER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Years outside this range mean the clock was never set (no RTC, no NTP yet).
+const CLOCK_YEARS: (i32, i32) = (2025, 2100);
+
+/// Self-test settings (`[self_test]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelfTestConfig {
+    /// Run the self-test once when the agent first starts. On by default.
+    #[serde(default = "default_run_on_first_boot")]
+    pub run_on_first_boot: bool,
+    /// Written after the first-boot report is published; delete it to
+    /// re-run the self-test on the next start.
+    #[serde(default = "default_marker_path")]
+    pub marker_path: String,
+    /// Free space (MB) below which the disk check fails. Below twice this
+    /// value it warns.
+    #[serde(default = "default_min_free_mb")]
+    pub min_free_mb: u64,
+}
+
+fn default_run_on_first_boot() -> bool {
+    true
+}
+
+fn default_marker_path() -> String {
+    "/var/lib/zeroclaw/self_test.done".into()
+}
+
+fn default_min_free_mb() -> u64 {
+    100
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self {
+            run_on_first_boot: default_run_on_first_boot(),
+            marker_path: default_marker_path(),
+            min_free_mb: default_min_free_mb(),
+        }
+    }
+}
+
+/// Runs the provisioning checks against the agent configuration.
+pub struct SelfTest {
+    config: AgentConfig,
+    /// Root of the sysfs tree (`/sys`), overridable for tests.
+    sysfs_root: PathBuf,
+    client: reqwest::Client,
+    /// Current log paths and Ollama model, when updated at runtime.
+    runtime: Option<RuntimeConfigRx>,
+}
+
+impl SelfTest {
+    pub fn new(config: &AgentConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.ollama.timeout_secs))
+            .build()
+            .expect("failed to build reqwest client");
+        Self {
+            config: config.clone(),
+            sysfs_root: PathBuf::from("/sys"),
+            client,
+            runtime: None,
+        }
+    }
+
+    /// Read network interface state from `root` instead of `/sys`.
+    pub fn with_sysfs_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.sysfs_root = root.into();
+        self
+    }
+
+    /// Check the log paths and Ollama model currently in effect rather than
+    /// the config file values.
+    pub fn with_runtime(mut self, runtime: RuntimeConfigRx) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Whether the first-boot run is enabled and has not happened yet.
+    pub fn first_boot_pending(&self) -> bool {
+        self.config.self_test.run_on_first_boot
+            && !Path::new(&self.config.self_test.marker_path).exists()
+    }
+
+    /// Record that the first-boot run has been reported.
+    pub fn mark_done(&self, report: &SelfTestReport) -> std::io::Result<()> {
+        let path = Path::new(&self.config.self_test.marker_path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, format!("{}\n", report.started_at.to_rfc3339()))
+    }
+
+    /// Run every check and build the report.
+    pub async fn run(&self, trigger: SelfTestTrigger) -> SelfTestReport {
+        let started_at = Utc::now();
+        let start = Instant::now();
+
+        let checks = vec![
+            timed("can_interface", || async { self.check_can() }).await,
+            timed("log_paths", || async { self.check_log_paths() }).await,
+            timed("ollama", || self.check_ollama()).await,
+            timed("broker", || self.check_broker()).await,
+            timed("clock", || async { check_clock() }).await,
+            timed("disk_space", || self.check_disk_space()).await,
+        ];
+
+        SelfTestReport {
+            device_id: self.config.device_id.clone(),
+            fleet_id: self.config.fleet_id.clone(),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            trigger,
+            passed: SelfTestReport::all_passed(&checks),
+            checks,
+            started_at,
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+
+    fn check_can(&self) -> (CheckStatus, String) {
+        let buses = self.config.can_buses();
+        if buses.is_empty() {
+            return (CheckStatus::Skipped, "no can_interface configured".into());
+        }
+        let mut status = CheckStatus::Pass;
+        let mut details = Vec::new();
+        for bus in &buses {
+            let iface = &bus.interface;
+            if bus.adapter == CanAdapter::Elm327 {
+                if Path::new(iface).exists() {
+                    details.push(format!("{iface} present"));
+                } else {
+                    status = CheckStatus::Fail;
+                    details.push(format!("{iface} not found"));
+                }
+                continue;
+            }
+            let path = self
+                .sysfs_root
+                .join("class/net")
+                .join(iface)
+                .join("operstate");
+            match std::fs::read_to_string(&path) {
+                // Virtual and some USB adapters report "unknown" while usable.
+                Ok(state) => match state.trim() {
+                    "up" | "unknown" => details.push(format!("{iface} is {}", state.trim())),
+                    other => {
+                        status = CheckStatus::Fail;
+                        details.push(format!("{iface} is {other}"));
+                    }
+                },
+                Err(_) => {
+                    status = CheckStatus::Fail;
+                    details.push(format!("{iface} not found"));
+                }
+            }
+        }
+        (status, details.join(", "))
+    }
+
+    fn check_log_paths(&self) -> (CheckStatus, String) {
+        let paths = match &self.runtime {
+            Some(rx) => rx.borrow().log_paths.clone(),
+            None => self.config.log_paths.clone(),
+        };
+        if paths.is_empty() {
+            return (CheckStatus::Skipped, "no log_paths configured".into());
+        }
+        let unreadable: Vec<&str> = paths
+            .iter()
+            .filter(|p| std::fs::File::open(p).is_err())
+            .map(String::as_str)
+            .collect();
+        if unreadable.is_empty() {
+            (
+                CheckStatus::Pass,
+                format!("{} log files readable", paths.len()),
+            )
+        } else {
+            (
+                CheckStatus::Fail,
+                format!("cannot read {}", unreadable.join(", ")),
+            )
+        }
+    }
+
+    async fn check_ollama(&self) -> (CheckStatus, String) {
+        let ollama = &self.config.ollama;
+        if !ollama.enabled {
+            return (CheckStatus::Skipped, "local inference disabled".into());
+        }
+        let model = match &self.runtime {
+            Some(rx) => rx.borrow().ollama_model.clone(),
+            None => ollama.model.clone(),
+        };
+        let url = format!("{}/api/tags", ollama.host.trim_end_matches('/'));
+        let response = match self.client.get(&url).send().await {
+            Ok(r) if r.status().is_success() => r,
+            Ok(r) => {
+                return (CheckStatus::Fail, format!("{url} returned {}", r.status()));
+            }
+            Err(e) => return (CheckStatus::Fail, format!("{url} unreachable: {e}")),
+        };
+        let tags: serde_json::Value = response.json().await.unwrap_or_default();
+        let pulled = tags["models"].as_array().is_some_and(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["name"].as_str())
+                .any(|name| name == model)
+        });
+        if pulled {
+            (CheckStatus::Pass, format!("{model} available"))
+        } else {
+            (
+                CheckStatus::Warn,
+                format!("reachable, but model {model} is not pulled"),
+            )
+        }
+    }
+
+    async fn check_broker(&self) -> (CheckStatus, String) {
+        let host = self.config.mqtt.broker_host.as_str();
+        let port = self.config.mqtt.broker_port;
+        match tokio::time::timeout(BROKER_TIMEOUT, tokio::net::TcpStream::connect((host, port)))
+            .await
+        {
+            Ok(Ok(_)) => (CheckStatus::Pass, format!("{host}:{port} reachable")),
+            Ok(Err(e)) => (CheckStatus::Fail, format!("{host}:{port} unreachable: {e}")),
+            Err(_) => (
+                CheckStatus::Fail,
+                format!(
+                    "{host}:{port} timed out after {}s",
+                    BROKER_TIMEOUT.as_secs()
+                ),
+            ),
+        }
+    }
+
+    async fn check_disk_space(&self) -> (CheckStatus, String) {
+        let dir = existing_ancestor(Path::new(&self.config.history.path));
+        let output = tokio::process::Command::new("df")
+            .arg("-Pk")
+            .arg(&dir)
+            .output()
+            .await;
+        let available_kb = match output {
+            Ok(out) if out.status.success() => {
+                parse_df_available_kb(&String::from_utf8_lossy(&out.stdout))
+            }
+            _ => None,
+        };
+        let Some(available_kb) = available_kb else {
+            return (
+                CheckStatus::Warn,
+                format!("cannot determine free space on {}", dir.display()),
+            );
+        };
+        disk_status(available_kb / 1024, self.config.self_test.min_free_mb, &dir)
+    }
+}
+
+/// Run one check and time it.
+async fn timed<F, Fut>(name: &str, check: F) -> SelfTestCheck
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = (CheckStatus, String)>,
+{
+    let start = Instant::now();
+    let (status, detail) = check().await;
+    SelfTestCheck {
+        name: name.to_string(),
+        status,
+        detail,
+        duration_ms: start.elapsed().as_millis() as u64,
+    }
+}
+
+fn check_clock() -> (CheckStatus, String) {
+    let now = Utc::now();
+    let (min, max) = CLOCK_YEARS;
+    if (min..max).contains(&now.year()) {
+        (CheckStatus::Pass, now.to_rfc3339())
+    } else {
+        (
+            CheckStatus::Fail,
+            format!("clock not set: {}", now.to_rfc3339()),
+        )
+    }
+}
+
+fn disk_status(free_mb: u64, min_free_mb: u64, dir: &Path) -> (CheckStatus, String) {
+    let detail = format!("{free_mb} MB free on {}", dir.display());
+    if free_mb < min_free_mb {
+        (
+            CheckStatus::Fail,
+            format!("{detail} (minimum {min_free_mb})"),
+        )
+    } else if free_mb < min_free_mb * 2 {
+        (CheckStatus::Warn, detail)
+    } else {
+        (CheckStatus::Pass, detail)
+    }
+}
+
+/// Closest existing directory at or above `path` (the journal may not
+/// exist yet on a fresh device).
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|p| p.is_dir())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+/// "Available" column (KB) from POSIX `df -Pk` output.
+pub(crate) fn parse_df_available_kb(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(extra: &str) -> AgentConfig {
+        let toml = format!(
+            r#"
+fleet_id = "fleet-alpha"
+device_id = "rpi-001"
+{extra}
+
+[mqtt]
+broker_host = "127.0.0.1"
+client_id = "rpi-001"
+"#
+        );
+        toml::from_str(&toml).unwrap()
+    }
+
+    fn find<'a>(report: &'a SelfTestReport, name: &str) -> &'a SelfTestCheck {
+        report.checks.iter().find(|c| c.name == name).unwrap()
+    }
+
+    fn sysfs_with(iface: &str, state: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "zc-selftest-sysfs-{}-{iface}-{state}",
+            std::process::id()
+        ));
+        let dir = root.join("class/net").join(iface);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("operstate"), format!("{state}\n")).unwrap();
+        root
+    }
+
+    #[test]
+    fn can_check_reads_operstate() {
+        let no_can = config("");
+        let config = config(r#"can_interface = "can0""#);
+
+        let up = SelfTest::new(&config).with_sysfs_root(sysfs_with("can0", "up"));
+        assert_eq!(up.check_can().0, CheckStatus::Pass);
+
+        let down = SelfTest::new(&config).with_sysfs_root(sysfs_with("can0", "down"));
+        let (status, detail) = down.check_can();
+        assert_eq!(status, CheckStatus::Fail);
+        assert_eq!(detail, "can0 is down");
+
+        let missing = SelfTest::new(&config).with_sysfs_root(sysfs_with("can1", "up"));
+        assert_eq!(missing.check_can().0, CheckStatus::Fail);
+
+        let none = SelfTest::new(&no_can);
+        assert_eq!(none.check_can().0, CheckStatus::Skipped);
+    }
+
+    #[test]
+    fn can_check_covers_every_bus() {
+        let config = config(
+            "[[can_buses]]\nname = \"powertrain\"\ninterface = \"can0\"\n\n[[can_buses]]\nname = \"body\"\ninterface = \"can1\"\n",
+        );
+        let root = sysfs_with("can1", "down");
+        let dir = root.join("class/net/can0");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("operstate"), "up\n").unwrap();
+
+        let (status, detail) = SelfTest::new(&config).with_sysfs_root(root).check_can();
+        assert_eq!(status, CheckStatus::Fail);
+        assert_eq!(detail, "can0 is up, can1 is down");
+    }
+
+    #[test]
+    fn elm327_buses_check_the_serial_device() {
+        let config = config(
+            "[[can_buses]]\nname = \"obd\"\ninterface = \"/dev/null\"\nadapter = \"elm327\"\n\n[[can_buses]]\nname = \"usb\"\ninterface = \"/dev/zc-no-such-tty\"\nadapter = \"elm327\"\n",
+        );
+        let (status, detail) = SelfTest::new(&config).check_can();
+        assert_eq!(status, CheckStatus::Fail);
+        assert_eq!(detail, "/dev/null present, /dev/zc-no-such-tty not found");
+    }
+
+    #[test]
+    fn log_paths_must_be_readable() {
+        let file = std::env::temp_dir().join(format!("zc-selftest-{}.log", std::process::id()));
+        std::fs::write(&file, "boot\n").unwrap();
+
+        let ok = config(&format!(r#"log_paths = ["{}"]"#, file.display()));
+        assert_eq!(SelfTest::new(&ok).check_log_paths().0, CheckStatus::Pass);
+
+        let bad = config(&format!(
+            r#"log_paths = ["{}", "/nonexistent/zc.log"]"#,
+            file.display()
+        ));
+        let (status, detail) = SelfTest::new(&bad).check_log_paths();
+        assert_eq!(status, CheckStatus::Fail);
+        assert!(detail.contains("/nonexistent/zc.log"));
+    }
+
+    #[tokio::test]
+    async fn ollama_check_looks_for_model() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "models": [{"name": "phi3:mini"}]
+            })))
+            .mount(&server)
+            .await;
+
+        let mut config = config("");
+        config.ollama.host = server.uri();
+        assert_eq!(
+            SelfTest::new(&config).check_ollama().await.0,
+            CheckStatus::Pass
+        );
+
+        config.ollama.model = "gemma:2b".into();
+        assert_eq!(
+            SelfTest::new(&config).check_ollama().await.0,
+            CheckStatus::Warn
+        );
+
+        config.ollama.enabled = false;
+        assert_eq!(
+            SelfTest::new(&config).check_ollama().await.0,
+            CheckStatus::Skipped
+        );
+    }
+
+    #[tokio::test]
+    async fn broker_check_connects() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = config("");
+        config.mqtt.broker_port = listener.local_addr().unwrap().port();
+        assert_eq!(
+            SelfTest::new(&config).check_broker().await.0,
+            CheckStatus::Pass
+        );
+
+        drop(listener);
+        assert_eq!(
+            SelfTest::new(&config).check_broker().await.0,
+            CheckStatus::Fail
+        );
+    }
+
+    #[test]
+    fn disk_thresholds() {
+        let dir = Path::new("/var/lib/zeroclaw");
+        assert_eq!(disk_status(50, 100, dir).0, CheckStatus::Fail);
+        assert_eq!(disk_status(150, 100, dir).0, CheckStatus::Warn);
+        assert_eq!(disk_status(500, 100, dir).0, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn parses_df_output() {
+        let out = "Filesystem     1024-blocks    Used Available Capacity Mounted on\n\
+                   /dev/mmcblk0p2    29000000 8000000  20000000      29% /\n";
+        assert_eq!(parse_df_available_kb(out), Some(20_000_000));
+        assert_eq!(parse_df_available_kb("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn report_covers_every_check() {
+        let mut config = config("");
+        config.ollama.enabled = false;
+        let report = SelfTest::new(&config).run(SelfTestTrigger::Command).await;
+
+        assert_eq!(report.device_id, "rpi-001");
+        assert_eq!(report.trigger, SelfTestTrigger::Command);
+        let names: Vec<&str> = report.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "can_interface",
+                "log_paths",
+                "ollama",
+                "broker",
+                "clock",
+                "disk_space"
+            ]
+        );
+        assert_eq!(find(&report, "clock").status, CheckStatus::Pass);
+        assert_eq!(find(&report, "ollama").status, CheckStatus::Skipped);
+        assert_eq!(report.passed, SelfTestReport::all_passed(&report.checks));
+    }
+
+    #[test]
+    fn first_boot_marker() {
+        let marker = std::env::temp_dir().join(format!(
+            "zc-selftest-marker-{}/self_test.done",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&marker);
+        let mut config = config("");
+        config.self_test.marker_path = marker.display().to_string();
+        let self_test = SelfTest::new(&config);
+        assert!(self_test.first_boot_pending());
+
+        let report = SelfTestReport {
+            device_id: "rpi-001".into(),
+            fleet_id: "fleet-alpha".into(),
+            agent_version: "0.1.0".into(),
+            trigger: SelfTestTrigger::FirstBoot,
+            passed: true,
+            checks: vec![],
+            started_at: Utc::now(),
+            duration_ms: 0,
+        };
+        self_test.mark_done(&report).unwrap();
+        assert!(!self_test.first_boot_pending());
+
+        config.self_test.run_on_first_boot = false;
+        let _ = std::fs::remove_file(&marker);
+        assert!(!SelfTest::new(&config).first_boot_pending());
+    }
+}
@@ -1,7 +1,15 @@
-//! Periodic shadow state reporter.
+//! Periodic shadow state reporter and reconnect resync.
 //!
 //! Reports the device's current state as a shadow update at a configurable
 //! interval, allowing the cloud to maintain an up-to-date view of the device.
+//!
+//! The tool catalog (see [`zc_protocol::catalog`]) does not change while
+//! the agent runs, so it is reported once, on boot, in its own shadow.
+//!
+//! Deltas published while the agent is offline are lost, so on every MQTT
+//! (re)connect [`resync`] asks the cloud for the full document of each
+//! shadow the agent applies, and the outstanding delta in the reply (see
+//! [`document_delta`]) is handled like a freshly received one.
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,8 +18,17 @@ use serde::Serialize;
 use tokio::sync::RwLock;
 use tokio::time;
 
+use crate::runtime_config::RuntimeConfigRx;
+use crate::tool_stats::ToolStatsSnapshot;
 use zc_mqtt_channel::ShadowClient;
 use zc_mqtt_channel::channel::Channel;
+use zc_protocol::catalog::{TOOL_CATALOG_SHADOW, ToolCatalog};
+use zc_protocol::context::VehicleProfile;
+use zc_protocol::device::MqttHealth;
+use zc_protocol::shadows::{ShadowDelta, ShadowDocument, UPDATE_SHADOW};
+
+/// Shadows whose desired state the agent applies, fetched on (re)connect.
+pub const RESYNC_SHADOWS: &[&str] = &["config", UPDATE_SHADOW];
 
 /// Device-side shadow state reported to the cloud.
 #[derive(Debug, Clone, Serialize)]
@@ -21,9 +38,22 @@ pub struct DeviceShadowState {
     pub can_status: String,
     pub ollama_status: String,
     pub tool_count: usize,
+    /// Capability manifest: names of the tools this agent can execute.
+    /// The cloud checks it before dispatching a pre-flighted command.
+    pub tools: Vec<String>,
+    /// Vehicle details known to the agent, used by cloud inference.
+    pub vehicle: VehicleProfile,
     pub last_command_id: Option<String>,
     pub last_command_tool: Option<String>,
     pub last_command_at: Option<String>,
+    /// Version of the last applied runtime config update (0 = config file).
+    pub config_version: u64,
+    /// Why the most recent config update was rejected, if it was.
+    pub config_error: Option<String>,
+    /// MQTT connection health as of the last connect or disconnect.
+    pub mqtt: Option<MqttHealth>,
+    /// Per-tool execution statistics as of the last command.
+    pub tool_stats: Vec<ToolStatsSnapshot>,
 }
 
 /// Shared shadow state that can be updated from the mqtt_loop.
@@ -37,25 +67,38 @@ impl Default for DeviceShadowState {
             can_status: "unknown".to_string(),
             ollama_status: "unknown".to_string(),
             tool_count: 0,
+            tools: Vec::new(),
+            vehicle: VehicleProfile::default(),
             last_command_id: None,
             last_command_tool: None,
             last_command_at: None,
+            config_version: 0,
+            config_error: None,
+            mqtt: None,
+            tool_stats: Vec::new(),
         }
     }
 }
 
 /// Run the shadow sync loop, reporting state at `interval`.
 ///
-/// Reports immediately on boot, then at the configured interval.
+/// Reports `catalog` and the state immediately on boot, then the state at
+/// the configured interval. A newly
+/// applied runtime config triggers an extra report so the cloud sees the
+/// applied version without waiting for the next tick.
 pub async fn run<C: Channel>(
     shadow_client: &ShadowClient<'_, C>,
     shadow_state: &SharedShadowState,
     interval: Duration,
     start_time: tokio::time::Instant,
+    mut runtime: RuntimeConfigRx,
+    catalog: &ToolCatalog,
 ) {
     let mut version: u64 = 0;
 
     // Report immediately on boot.
+    report_catalog(shadow_client, catalog).await;
+    shadow_state.write().await.config_version = runtime.borrow_and_update().version;
     version += 1;
     report_state(shadow_client, shadow_state, start_time, version).await;
 
@@ -64,12 +107,75 @@ pub async fn run<C: Channel>(
     ticker.tick().await;
 
     loop {
-        ticker.tick().await;
+        tokio::select! {
+            _ = ticker.tick() => {}
+            Ok(()) = runtime.changed() => {
+                let applied = runtime.borrow_and_update().version;
+                shadow_state.write().await.config_version = applied;
+            }
+        }
         version += 1;
         report_state(shadow_client, shadow_state, start_time, version).await;
     }
 }
 
+/// Request the full document of every shadow in [`RESYNC_SHADOWS`].
+///
+/// Subscribes to the reply topic first: the session is clean, so
+/// subscriptions do not survive a reconnect.
+pub async fn resync<C: Channel>(shadow_client: &ShadowClient<'_, C>) {
+    if let Err(e) = shadow_client.subscribe_document().await {
+        tracing::warn!(error = %e, "failed to subscribe to shadow documents");
+        return;
+    }
+    for shadow_name in RESYNC_SHADOWS {
+        if let Err(e) = shadow_client.request_document(shadow_name).await {
+            tracing::warn!(error = %e, shadow = shadow_name, "failed to request shadow document");
+        } else {
+            tracing::debug!(shadow = shadow_name, "requested shadow document");
+        }
+    }
+}
+
+/// The delta still to apply from a shadow document, or `None` when the
+/// shadow is in sync.
+pub fn document_delta(document: &ShadowDocument) -> Option<ShadowDelta> {
+    let delta = document.pending_delta();
+    match &delta {
+        Some(d) => tracing::info!(
+            shadow = %document.shadow_name,
+            version = document.version,
+            keys = d.delta.as_object().map_or(0, |o| o.len()),
+            "applying shadow delta missed while offline"
+        ),
+        None => tracing::debug!(
+            shadow = %document.shadow_name,
+            version = document.version,
+            "shadow in sync after reconnect"
+        ),
+    }
+    delta
+}
+
+/// Publish the tool catalog in the [`TOOL_CATALOG_SHADOW`].
+async fn report_catalog<C: Channel>(shadow_client: &ShadowClient<'_, C>, catalog: &ToolCatalog) {
+    let reported = match serde_json::to_value(catalog) {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to serialize tool catalog");
+            return;
+        }
+    };
+    if let Err(e) = shadow_client
+        .report_state(TOOL_CATALOG_SHADOW, reported, 1)
+        .await
+    {
+        tracing::warn!(error = %e, "failed to publish tool catalog");
+    } else {
+        tracing::info!(tools = catalog.tools.len(), "tool catalog reported");
+    }
+}
+
 async fn report_state<C: Channel>(
     shadow_client: &ShadowClient<'_, C>,
     shadow_state: &SharedShadowState,
@@ -129,6 +235,10 @@ mod tests {
         let mock = MockChannel::new();
         let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
         let state = make_shadow_state(9);
+        state.write().await.tools = vec!["read_dtcs".into(), "search_logs".into()];
+        let stats = crate::tool_stats::ToolStats::new();
+        stats.start("read_dtcs").finish(true, 64);
+        state.write().await.tool_stats = stats.snapshot();
         let start = tokio::time::Instant::now();
 
         report_state(&client, &state, start, 1).await;
@@ -137,10 +247,32 @@ mod tests {
         let update: ShadowUpdate = serde_json::from_slice(&msgs[0].payload).unwrap();
         assert_eq!(update.shadow_name, "diagnostics");
         assert_eq!(update.reported["tool_count"], 9);
+        assert_eq!(update.reported["tools"][1], "search_logs");
+        assert_eq!(update.reported["tool_stats"][0]["tool"], "read_dtcs");
+        assert_eq!(update.reported["tool_stats"][0]["calls"], 1);
         assert!(update.reported.get("agent_version").is_some());
         assert!(update.reported.get("uptime_secs").is_some());
     }
 
+    #[tokio::test]
+    async fn catalog_reported_in_own_shadow() {
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+        let catalog = ToolCatalog::new(vec![zc_protocol::catalog::ToolSpec::new(
+            "read_dtcs",
+            "Read DTCs",
+            serde_json::json!({"type": "object"}),
+        )]);
+
+        report_catalog(&client, &catalog).await;
+
+        let msgs = mock.published();
+        let update: ShadowUpdate = serde_json::from_slice(&msgs[0].payload).unwrap();
+        assert_eq!(update.shadow_name, TOOL_CATALOG_SHADOW);
+        assert_eq!(update.reported["tools"][0]["name"], "read_dtcs");
+        assert_eq!(update.reported["tools"][0]["description"], "Read DTCs");
+    }
+
     #[tokio::test]
     async fn version_increments_on_reports() {
         let mock = MockChannel::new();
@@ -158,4 +290,85 @@ mod tests {
         assert_eq!(u1.version, 1);
         assert_eq!(u2.version, 2);
     }
+
+    #[tokio::test]
+    async fn applied_config_version_is_reported() {
+        use crate::runtime_config::RuntimeConfig;
+
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+        let state = make_shadow_state(9);
+        let (tx, rx) = tokio::sync::watch::channel(RuntimeConfig {
+            version: 0,
+            heartbeat_interval_secs: 30,
+            telemetry_window_secs: 10,
+            telemetry_sample_interval_ms: 1000,
+            ollama_model: "phi3:mini".into(),
+            log_paths: vec![],
+            pid_watches: vec![],
+        });
+        let catalog = ToolCatalog::default();
+
+        let sync = run(
+            &client,
+            &state,
+            Duration::from_secs(3600),
+            tokio::time::Instant::now(),
+            rx,
+            &catalog,
+        );
+        let update = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            tx.send_modify(|c| c.version = 7);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        tokio::select! {
+            _ = sync => unreachable!("shadow sync loop returned"),
+            _ = update => {}
+        }
+
+        // The catalog goes out first, then the state reports.
+        let msgs = mock.published();
+        assert_eq!(msgs.len(), 3);
+        let boot: ShadowUpdate = serde_json::from_slice(&msgs[1].payload).unwrap();
+        let applied: ShadowUpdate = serde_json::from_slice(&msgs[2].payload).unwrap();
+        assert_eq!(boot.reported["config_version"], 0);
+        assert_eq!(applied.reported["config_version"], 7);
+        assert_eq!(applied.version, 2);
+    }
+
+    #[tokio::test]
+    async fn resync_requests_documents() {
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+
+        resync(&client).await;
+
+        assert!(mock.is_subscribed_to("fleet/fleet-alpha/rpi-001/shadow/document"));
+        let requests = mock.published_to("fleet/fleet-alpha/rpi-001/shadow/get");
+        assert_eq!(requests.len(), RESYNC_SHADOWS.len());
+        let request: zc_protocol::shadows::ShadowGetRequest =
+            serde_json::from_slice(&requests[0].payload).unwrap();
+        assert_eq!(request.shadow_name, "config");
+    }
+
+    #[test]
+    fn document_delta_only_when_out_of_sync() {
+        let mut document = ShadowDocument {
+            device_id: "rpi-001".into(),
+            shadow_name: "config".into(),
+            desired: serde_json::json!({"log_level": "debug"}),
+            reported: serde_json::json!({"log_level": "info"}),
+            delta: serde_json::json!({"log_level": "debug"}),
+            version: 4,
+            client_token: None,
+            timestamp: chrono::Utc::now(),
+        };
+        let delta = document_delta(&document).unwrap();
+        assert_eq!(delta.delta["log_level"], "debug");
+        assert_eq!(delta.version, 4);
+
+        document.delta = serde_json::json!({});
+        assert!(document_delta(&document).is_none());
+    }
 }
@@ -0,0 +1,489 @@
+//! Edge telemetry sampling with windowed pre-aggregation.
+//!
+//! High-frequency CAN signals (RPM, speed, throttle, ...) are polled at
+//! `sample_interval_ms` but never published sample-by-sample. Instead each
+//! metric is folded into min/max/avg/last over a `window_secs` window and
+//! one `TelemetryReading` per metric is published when the window closes.
+//! Raw samples are still available on demand through the `pid_burst` tool.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time;
+
+use crate::history::LocalHistory;
+use crate::runtime_config::{RuntimeConfig, RuntimeConfigRx};
+use zc_canbus_tools::CanInterface;
+use zc_canbus_tools::obd;
+use zc_canbus_tools::types::MODE_CURRENT_DATA;
+use zc_mqtt_channel::MqttChannel;
+use zc_protocol::telemetry::{TelemetryBatch, TelemetryReading, TelemetrySource};
+
+/// Telemetry sampling and aggregation settings (`[telemetry]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Enable periodic PID sampling. Off by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Aggregation window in seconds.
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Delay between sampling rounds in milliseconds.
+    #[serde(default = "default_sample_interval_ms")]
+    pub sample_interval_ms: u64,
+    /// OBD-II Mode 01 PIDs to sample each round.
+    #[serde(default = "default_pids")]
+    pub pids: Vec<u8>,
+}
+
+fn default_window_secs() -> u64 {
+    10
+}
+
+fn default_sample_interval_ms() -> u64 {
+    1000
+}
+
+fn default_pids() -> Vec<u8> {
+    // RPM, vehicle speed, coolant temp
+    vec![0x0C, 0x0D, 0x05]
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: default_window_secs(),
+            sample_interval_ms: default_sample_interval_ms(),
+            pids: default_pids(),
+        }
+    }
+}
+
+/// Running statistics for one metric within the current window.
+#[derive(Debug, Clone)]
+struct WindowStats {
+    min: f64,
+    max: f64,
+    sum: f64,
+    last: f64,
+    count: u64,
+    unit: String,
+}
+
+/// Folds raw samples into per-metric window statistics.
+#[derive(Debug)]
+pub struct Aggregator {
+    source: TelemetrySource,
+    window_secs: u64,
+    metrics: BTreeMap<String, WindowStats>,
+}
+
+impl Aggregator {
+    pub fn new(source: TelemetrySource, window_secs: u64) -> Self {
+        Self {
+            source,
+            window_secs,
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    /// Record a single sample for `metric`.
+    pub fn record(&mut self, metric: &str, value: f64, unit: &str) {
+        self.metrics
+            .entry(metric.to_string())
+            .and_modify(|s| {
+                s.min = s.min.min(value);
+                s.max = s.max.max(value);
+                s.sum += value;
+                s.last = value;
+                s.count += 1;
+            })
+            .or_insert_with(|| WindowStats {
+                min: value,
+                max: value,
+                sum: value,
+                last: value,
+                count: 1,
+                unit: unit.to_string(),
+            });
+    }
+
+    /// Number of distinct metrics with samples in the current window.
+    pub fn len(&self) -> usize {
+        self.metrics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Close the current window and return one reading per metric.
+    ///
+    /// `value_numeric` carries the window average; the full summary
+    /// (min/max/avg/last/count) is in `value_json`.
+    pub fn flush(&mut self, device_id: &str) -> Vec<TelemetryReading> {
+        let now = Utc::now();
+        std::mem::take(&mut self.metrics)
+            .into_iter()
+            .map(|(name, s)| {
+                let avg = s.sum / s.count as f64;
+                TelemetryReading {
+                    device_id: device_id.to_string(),
+                    time: now,
+                    metric_name: name,
+                    value_numeric: Some(avg),
+                    value_text: None,
+                    value_json: Some(serde_json::json!({
+                        "min": s.min,
+                        "max": s.max,
+                        "avg": avg,
+                        "last": s.last,
+                        "count": s.count,
+                        "window_secs": self.window_secs,
+                    })),
+                    unit: if s.unit.is_empty() {
+                        None
+                    } else {
+                        Some(s.unit)
+                    },
+                    source: self.source,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Convert a PID display name ("Engine RPM") into a metric name ("engine_rpm").
+pub(crate) fn metric_name(display: &str) -> String {
+    display
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Poll each configured PID once and feed decoded values into `agg`.
+///
+/// The round holds `bus_lock`, waiting for a command using the bus to
+/// finish first. PIDs that time out or fail to decode are skipped for this
+/// round.
+async fn sample_round(
+    can_interface: &dyn CanInterface,
+    bus_lock: &Mutex<()>,
+    pids: &[u8],
+    timeout: Duration,
+    agg: &mut Aggregator,
+) {
+    let _bus = bus_lock.lock().await;
+    for &pid in pids {
+        if let Some(pv) = read_pid(can_interface, pid, timeout).await {
+            agg.record(&metric_name(pv.name), pv.value, pv.unit);
+        }
+    }
+}
+
+/// Query one Mode 01 PID and decode it; `None` when it times out or the
+/// response does not decode.
+pub(crate) async fn read_pid(
+    can_interface: &dyn CanInterface,
+    pid: u8,
+    timeout: Duration,
+) -> Option<obd::PidValue> {
+    let request = obd::build_request(MODE_CURRENT_DATA, pid);
+    let response = obd::obd_query(can_interface, &request, timeout)
+        .await
+        .ok()?;
+    let (resp_pid, data) = obd::parse_pid_response(&response, MODE_CURRENT_DATA).ok()?;
+    if resp_pid != pid {
+        return None;
+    }
+    match obd::decode_pid(pid, data) {
+        Ok(pv) => Some(pv),
+        Err(e) => {
+            tracing::debug!(pid = pid, error = %e, "PID decode failed");
+            None
+        }
+    }
+}
+
+/// Run the sampling loop, publishing one aggregated batch per window.
+///
+/// Each window is also written to `history` (if given) with whether the
+/// publish succeeded, so it can be queried with `get_local_history`.
+///
+/// The window length and sampling interval come from the runtime config.
+/// When an update changes them, the current window is closed and
+/// published early and sampling restarts with the new settings.
+///
+/// When sampling is disabled this parks forever rather than returning, so
+/// it can sit in the agent's `select!` alongside the other loops.
+pub async fn run(
+    channel: &MqttChannel,
+    can_interface: &dyn CanInterface,
+    bus_lock: &Mutex<()>,
+    config: &TelemetryConfig,
+    mut runtime: RuntimeConfigRx,
+    history: Option<&LocalHistory>,
+) {
+    if !config.enabled || config.pids.is_empty() {
+        tracing::info!("telemetry sampling disabled");
+        std::future::pending::<()>().await;
+        return;
+    }
+
+    let mut timing = Timing::from(&*runtime.borrow_and_update());
+    let mut agg = Aggregator::new(TelemetrySource::Obd2, timing.window_secs);
+
+    tracing::info!(
+        pids = ?config.pids,
+        window_secs = timing.window_secs,
+        sample_interval_ms = timing.sample_interval_ms,
+        "telemetry sampling enabled"
+    );
+
+    let mut ticker = time::interval(timing.sample_interval());
+    let mut window_start = time::Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            Ok(()) = runtime.changed() => {
+                let next = Timing::from(&*runtime.borrow_and_update());
+                if next != timing {
+                    publish_window(channel, &mut agg, history).await;
+                    timing = next;
+                    agg = Aggregator::new(TelemetrySource::Obd2, timing.window_secs);
+                    ticker = time::interval(timing.sample_interval());
+                    window_start = time::Instant::now();
+                    tracing::info!(
+                        window_secs = timing.window_secs,
+                        sample_interval_ms = timing.sample_interval_ms,
+                        "telemetry timing updated"
+                    );
+                }
+                continue;
+            }
+        }
+        let query_timeout = timing.sample_interval().min(obd::DEFAULT_TIMEOUT);
+        sample_round(
+            can_interface,
+            bus_lock,
+            &config.pids,
+            query_timeout,
+            &mut agg,
+        )
+        .await;
+
+        if window_start.elapsed() < timing.window() {
+            continue;
+        }
+        window_start = time::Instant::now();
+        publish_window(channel, &mut agg, history).await;
+    }
+}
+
+/// Window length and sampling interval currently in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timing {
+    window_secs: u64,
+    sample_interval_ms: u64,
+}
+
+impl Timing {
+    fn sample_interval(self) -> Duration {
+        Duration::from_millis(self.sample_interval_ms.max(10))
+    }
+
+    fn window(self) -> Duration {
+        Duration::from_secs(self.window_secs.max(1))
+    }
+}
+
+impl From<&RuntimeConfig> for Timing {
+    fn from(runtime: &RuntimeConfig) -> Self {
+        Self {
+            window_secs: runtime.telemetry_window_secs,
+            sample_interval_ms: runtime.telemetry_sample_interval_ms,
+        }
+    }
+}
+
+/// Close the current window: publish its readings and journal them.
+async fn publish_window(
+    channel: &MqttChannel,
+    agg: &mut Aggregator,
+    history: Option<&LocalHistory>,
+) {
+    if agg.is_empty() {
+        return;
+    }
+
+    let batch = TelemetryBatch {
+        device_id: channel.device_id().to_string(),
+        readings: agg.flush(channel.device_id()),
+        collected_at: Utc::now(),
+    };
+    let published = match channel.publish_telemetry(&batch).await {
+        Ok(()) => {
+            tracing::debug!(metrics = batch.readings.len(), "telemetry window published");
+            true
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to publish telemetry batch");
+            false
+        }
+    };
+    if let Some(history) = history {
+        history.record_telemetry(&batch.readings, published);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zc_canbus_tools::MockCanInterface;
+    use zc_canbus_tools::types::CanFrame;
+
+    #[test]
+    fn aggregator_computes_window_stats() {
+        let mut agg = Aggregator::new(TelemetrySource::Obd2, 10);
+        agg.record("engine_rpm", 800.0, "rpm");
+        agg.record("engine_rpm", 1600.0, "rpm");
+        agg.record("engine_rpm", 1200.0, "rpm");
+
+        let readings = agg.flush("rpi-001");
+        assert_eq!(readings.len(), 1);
+
+        let r = &readings[0];
+        assert_eq!(r.metric_name, "engine_rpm");
+        assert_eq!(r.value_numeric, Some(1200.0));
+        assert_eq!(r.unit.as_deref(), Some("rpm"));
+        assert_eq!(r.source, TelemetrySource::Obd2);
+
+        let stats = r.value_json.as_ref().unwrap();
+        assert_eq!(stats["min"], 800.0);
+        assert_eq!(stats["max"], 1600.0);
+        assert_eq!(stats["last"], 1200.0);
+        assert_eq!(stats["count"], 3);
+        assert_eq!(stats["window_secs"], 10);
+    }
+
+    #[test]
+    fn flush_resets_window() {
+        let mut agg = Aggregator::new(TelemetrySource::Obd2, 10);
+        agg.record("vehicle_speed", 60.0, "km/h");
+        assert_eq!(agg.flush("rpi-001").len(), 1);
+        assert!(agg.is_empty());
+        assert!(agg.flush("rpi-001").is_empty());
+    }
+
+    #[test]
+    fn one_reading_per_metric() {
+        let mut agg = Aggregator::new(TelemetrySource::Obd2, 10);
+        agg.record("engine_rpm", 900.0, "rpm");
+        agg.record("vehicle_speed", 40.0, "km/h");
+        agg.record("engine_rpm", 1100.0, "rpm");
+        assert_eq!(agg.len(), 2);
+        assert_eq!(agg.flush("rpi-001").len(), 2);
+    }
+
+    #[test]
+    fn metric_name_from_display() {
+        assert_eq!(metric_name("Engine RPM"), "engine_rpm");
+        assert_eq!(
+            metric_name("Cmd Throttle Actuator"),
+            "cmd_throttle_actuator"
+        );
+    }
+
+    #[test]
+    fn config_defaults() {
+        let config = TelemetryConfig::default();
+        assert!(!config.enabled);
+        assert_eq!(config.window_secs, 10);
+        assert_eq!(config.pids, vec![0x0C, 0x0D, 0x05]);
+    }
+
+    #[tokio::test]
+    async fn sample_round_feeds_aggregator() {
+        // RPM = 1000 → raw 4000 = 0x0FA0; speed = 50 km/h
+        let mock = MockCanInterface::with_responses(vec![
+            CanFrame::new(0x7E8, vec![0x04, 0x41, 0x0C, 0x0F, 0xA0, 0, 0, 0]),
+            CanFrame::new(0x7E8, vec![0x03, 0x41, 0x0D, 0x32, 0, 0, 0, 0]),
+        ]);
+        let mut agg = Aggregator::new(TelemetrySource::Obd2, 10);
+
+        let bus_lock = Mutex::new(());
+        sample_round(
+            &mock,
+            &bus_lock,
+            &[0x0C, 0x0D],
+            Duration::from_millis(10),
+            &mut agg,
+        )
+        .await;
+
+        let readings = agg.flush("rpi-001");
+        assert_eq!(readings.len(), 2);
+        let rpm = readings
+            .iter()
+            .find(|r| r.metric_name == "engine_rpm")
+            .unwrap();
+        assert_eq!(rpm.value_numeric, Some(1000.0));
+    }
+
+    #[tokio::test]
+    async fn sample_round_skips_silent_pids() {
+        let mock = MockCanInterface::new();
+        let mut agg = Aggregator::new(TelemetrySource::Obd2, 10);
+        let bus_lock = Mutex::new(());
+        sample_round(
+            &mock,
+            &bus_lock,
+            &[0x0C],
+            Duration::from_millis(10),
+            &mut agg,
+        )
+        .await;
+        assert!(agg.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sample_round_waits_for_a_command_holding_the_bus() {
+        let mock = MockCanInterface::with_responses(vec![CanFrame::new(
+            0x7E8,
+            vec![0x04, 0x41, 0x0C, 0x0F, 0xA0, 0, 0, 0],
+        )]);
+        let bus_lock = Mutex::new(());
+        let mut agg = Aggregator::new(TelemetrySource::Obd2, 10);
+
+        let command = bus_lock.lock().await;
+        {
+            let round = sample_round(
+                &mock,
+                &bus_lock,
+                &[0x0C],
+                Duration::from_millis(10),
+                &mut agg,
+            );
+            tokio::pin!(round);
+            assert!(
+                time::timeout(Duration::from_millis(50), &mut round)
+                    .await
+                    .is_err()
+            );
+            assert!(mock.sent_frames().is_empty());
+
+            drop(command);
+            round.await;
+        }
+        assert_eq!(mock.sent_frames().len(), 1);
+        assert_eq!(agg.flush("rpi-001").len(), 1);
+    }
+}
@@ -0,0 +1,447 @@
+//! Per-tool execution metrics (`agent_stats`).
+//!
+//! [`crate::registry::ToolRegistry`] times every tool it runs and records
+//! the outcome here: call, success and failure counts, a latency histogram
+//! and a histogram of result sizes. A run whose future is dropped — the
+//! command timed out or was cancelled — counts as `cancelled`. Runs slower
+//! than [`SLOW_TOOL_MS`] are logged as a warning and counted as `slow`,
+//! except for tools that run for as long as they are asked to
+//! ([`SLOW_EXEMPT`]).
+//!
+//! Operators read the numbers with the built-in `agent_stats` tool or in
+//! `tool_stats` of the `diagnostics` shadow, refreshed after each command.
+//! They are kept in memory and start over when the agent restarts.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use zc_protocol::catalog::ToolSpec;
+
+/// Tool name answered by [`ToolStats::execute`].
+pub const TOOL_NAME: &str = "agent_stats";
+
+/// Runs at least this long are logged and counted as slow.
+pub const SLOW_TOOL_MS: u64 = 5_000;
+
+/// Tools that capture for a requested duration, so long runs are expected.
+pub const SLOW_EXEMPT: &[&str] = &["can_monitor", "pid_burst"];
+
+/// Upper bounds of the latency buckets in milliseconds; one more bucket
+/// holds everything slower.
+pub const LATENCY_BOUNDS_MS: &[u64] = &[50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000];
+
+/// Upper bounds of the result size buckets in bytes; one more bucket holds
+/// everything larger.
+pub const PAYLOAD_BOUNDS_BYTES: &[u64] = &[256, 1_024, 4_096, 16_384, 65_536, 262_144, 1_048_576];
+
+/// Catalog entry for [`TOOL_NAME`].
+pub fn tool_spec() -> ToolSpec {
+    ToolSpec::new(
+        TOOL_NAME,
+        "Show per-tool execution statistics since the agent started: calls, failures, timeouts, latency and result size histograms, slow runs",
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "tool": {"type": "string", "description": "Only this tool's statistics"}
+            }
+        }),
+    )
+}
+
+/// Fixed-bucket histogram.
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [u64],
+    /// One count per bound plus the overflow bucket.
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+
+    fn record(&mut self, value: u64) {
+        let bucket = self.bounds.partition_point(|&b| b < value);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Upper bound of the bucket holding quantile `q`; the maximum for
+    /// the overflow bucket.
+    fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((self.count as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return self.bounds.get(i).map_or(self.max, |&b| b.min(self.max));
+            }
+        }
+        self.max
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            count: self.count,
+            mean: self.sum.checked_div(self.count).unwrap_or(0),
+            p50: self.quantile(0.5),
+            p95: self.quantile(0.95),
+            max: self.max,
+            bounds: self.bounds,
+            buckets: self.counts.clone(),
+        }
+    }
+}
+
+/// A histogram as reported. Percentiles are bucket upper bounds, capped at
+/// the maximum seen.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub mean: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub max: u64,
+    pub bounds: &'static [u64],
+    /// Count per bucket: one per bound, then the overflow bucket.
+    pub buckets: Vec<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    calls: u64,
+    succeeded: u64,
+    failed: u64,
+    cancelled: u64,
+    slow: u64,
+    latency_ms: Histogram,
+    payload_bytes: Histogram,
+    last_called_at: DateTime<Utc>,
+}
+
+impl Entry {
+    fn new() -> Self {
+        Self {
+            calls: 0,
+            succeeded: 0,
+            failed: 0,
+            cancelled: 0,
+            slow: 0,
+            latency_ms: Histogram::new(LATENCY_BOUNDS_MS),
+            payload_bytes: Histogram::new(PAYLOAD_BOUNDS_BYTES),
+            last_called_at: Utc::now(),
+        }
+    }
+}
+
+/// One tool's statistics, as reported.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolStatsSnapshot {
+    pub tool: String,
+    pub calls: u64,
+    pub succeeded: u64,
+    /// Errors and results with `success: false`.
+    pub failed: u64,
+    /// Stopped before finishing: timed out or cancelled.
+    pub cancelled: u64,
+    /// Runs of at least [`SLOW_TOOL_MS`].
+    pub slow: u64,
+    /// Finished and cancelled runs alike.
+    pub latency_ms: HistogramSnapshot,
+    /// Serialized result size of finished runs.
+    pub payload_bytes: HistogramSnapshot,
+    pub last_called_at: DateTime<Utc>,
+}
+
+/// How a timed run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Statistics for every tool run so far, shared by the registry and
+/// whoever reports them.
+#[derive(Debug, Default)]
+pub struct ToolStats {
+    tools: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl ToolStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start timing a run of `tool`. Call [`ToolTimer::finish`] with the
+    /// outcome; dropping the timer instead records a cancelled run.
+    pub fn start<'a>(&'a self, tool: &'a str) -> ToolTimer<'a> {
+        ToolTimer {
+            stats: self,
+            tool,
+            started: Instant::now(),
+            finished: false,
+        }
+    }
+
+    fn record(&self, tool: &str, outcome: Outcome, elapsed: Duration, payload_bytes: Option<u64>) {
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let slow = elapsed_ms >= SLOW_TOOL_MS && !SLOW_EXEMPT.contains(&tool);
+        if slow {
+            tracing::warn!(
+                tool,
+                latency_ms = elapsed_ms,
+                outcome = ?outcome,
+                "slow tool execution"
+            );
+        }
+
+        let mut tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = tools.entry(tool.to_string()).or_insert_with(Entry::new);
+        entry.calls += 1;
+        match outcome {
+            Outcome::Succeeded => entry.succeeded += 1,
+            Outcome::Failed => entry.failed += 1,
+            Outcome::Cancelled => entry.cancelled += 1,
+        }
+        if slow {
+            entry.slow += 1;
+        }
+        entry.latency_ms.record(elapsed_ms);
+        if let Some(bytes) = payload_bytes {
+            entry.payload_bytes.record(bytes);
+        }
+        entry.last_called_at = Utc::now();
+    }
+
+    /// Every tool run so far, by name.
+    pub fn snapshot(&self) -> Vec<ToolStatsSnapshot> {
+        let tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        tools
+            .iter()
+            .map(|(tool, e)| ToolStatsSnapshot {
+                tool: tool.clone(),
+                calls: e.calls,
+                succeeded: e.succeeded,
+                failed: e.failed,
+                cancelled: e.cancelled,
+                slow: e.slow,
+                latency_ms: e.latency_ms.snapshot(),
+                payload_bytes: e.payload_bytes.snapshot(),
+                last_called_at: e.last_called_at,
+            })
+            .collect()
+    }
+
+    /// Answer `agent_stats`: the snapshot (optionally of one `tool`) and a
+    /// one-line summary naming the slowest and most failing tools.
+    pub fn execute(&self, args: &serde_json::Value) -> (String, serde_json::Value) {
+        let only = args.get("tool").and_then(|v| v.as_str());
+        let tools: Vec<ToolStatsSnapshot> = self
+            .snapshot()
+            .into_iter()
+            .filter(|t| only.is_none_or(|name| t.tool == name))
+            .collect();
+
+        let summary = match (tools.is_empty(), only) {
+            (true, Some(name)) => format!("{name} has not run since the agent started"),
+            (true, None) => "No tools have run since the agent started".to_string(),
+            (false, _) => summarize(&tools),
+        };
+        let data = serde_json::json!({
+            "tools": tools,
+            "slow_after_ms": SLOW_TOOL_MS,
+        });
+        (summary, data)
+    }
+}
+
+/// "42 call(s) across 3 tool(s); slowest: read_vin (p95 ≤ 2500 ms);
+/// most failures: read_pid (3 of 20); 1 slow run(s)".
+fn summarize(tools: &[ToolStatsSnapshot]) -> String {
+    let calls: u64 = tools.iter().map(|t| t.calls).sum();
+    let mut parts = vec![format!("{calls} call(s) across {} tool(s)", tools.len())];
+    if let Some(slowest) = tools
+        .iter()
+        .filter(|t| !SLOW_EXEMPT.contains(&t.tool.as_str()))
+        .max_by_key(|t| t.latency_ms.p95)
+    {
+        parts.push(format!(
+            "slowest: {} (p95 \u{2264} {} ms)",
+            slowest.tool, slowest.latency_ms.p95
+        ));
+    }
+    match tools
+        .iter()
+        .filter(|t| t.failed + t.cancelled > 0)
+        .max_by_key(|t| t.failed + t.cancelled)
+    {
+        Some(worst) => parts.push(format!(
+            "most failures: {} ({} of {})",
+            worst.tool,
+            worst.failed + worst.cancelled,
+            worst.calls
+        )),
+        None => parts.push("no failures".to_string()),
+    }
+    let slow: u64 = tools.iter().map(|t| t.slow).sum();
+    if slow > 0 {
+        parts.push(format!("{slow} slow run(s)"));
+    }
+    parts.join("; ")
+}
+
+/// A run being timed; see [`ToolStats::start`].
+#[must_use = "a dropped timer records a cancelled run"]
+pub struct ToolTimer<'a> {
+    stats: &'a ToolStats,
+    tool: &'a str,
+    started: Instant,
+    finished: bool,
+}
+
+impl ToolTimer<'_> {
+    /// Record the run as finished, with `payload_bytes` of result.
+    pub fn finish(mut self, success: bool, payload_bytes: usize) {
+        self.finished = true;
+        let outcome = if success {
+            Outcome::Succeeded
+        } else {
+            Outcome::Failed
+        };
+        self.stats.record(
+            self.tool,
+            outcome,
+            self.started.elapsed(),
+            Some(payload_bytes as u64),
+        );
+    }
+}
+
+impl Drop for ToolTimer<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.stats
+                .record(self.tool, Outcome::Cancelled, self.started.elapsed(), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_and_percentiles() {
+        let mut h = Histogram::new(LATENCY_BOUNDS_MS);
+        for ms in [10, 20, 30, 40, 60, 70, 80, 90, 200, 40_000] {
+            h.record(ms);
+        }
+        let s = h.snapshot();
+        assert_eq!(s.count, 10);
+        assert_eq!(s.buckets[0], 4);
+        assert_eq!(s.buckets[1], 4);
+        assert_eq!(*s.buckets.last().unwrap(), 1);
+        assert_eq!(s.p50, 100);
+        assert_eq!(s.p95, 40_000);
+        assert_eq!(s.max, 40_000);
+        assert_eq!(s.mean, 4_060);
+        assert_eq!(Histogram::new(LATENCY_BOUNDS_MS).quantile(0.5), 0);
+    }
+
+    #[test]
+    fn timers_record_outcomes() {
+        let stats = ToolStats::new();
+        stats.start("read_pid").finish(true, 120);
+        stats.start("read_pid").finish(false, 80);
+        drop(stats.start("read_pid"));
+        stats.start("read_vin").finish(true, 300);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        let pid = &snapshot[0];
+        assert_eq!(pid.tool, "read_pid");
+        assert_eq!(
+            (pid.calls, pid.succeeded, pid.failed, pid.cancelled),
+            (3, 1, 1, 1)
+        );
+        assert_eq!(pid.latency_ms.count, 3);
+        // Cancelled runs have no result to measure.
+        assert_eq!(pid.payload_bytes.count, 2);
+        assert_eq!(pid.payload_bytes.max, 120);
+    }
+
+    #[test]
+    fn slow_runs_are_counted_unless_exempt() {
+        let stats = ToolStats::new();
+        let slow = Duration::from_millis(SLOW_TOOL_MS);
+        stats.record("read_vin", Outcome::Succeeded, slow, Some(10));
+        stats.record("can_monitor", Outcome::Succeeded, slow * 2, Some(10));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot[0].tool, "can_monitor");
+        assert_eq!(snapshot[0].slow, 0);
+        assert_eq!(snapshot[1].slow, 1);
+    }
+
+    #[test]
+    fn execute_summarizes_and_filters() {
+        let stats = ToolStats::new();
+        assert_eq!(
+            stats.execute(&serde_json::json!({})).0,
+            "No tools have run since the agent started"
+        );
+
+        stats.record(
+            "read_vin",
+            Outcome::Succeeded,
+            Duration::from_millis(2_000),
+            Some(400),
+        );
+        stats.record(
+            "read_pid",
+            Outcome::Failed,
+            Duration::from_millis(30),
+            Some(90),
+        );
+        stats.record(
+            "read_pid",
+            Outcome::Cancelled,
+            Duration::from_secs(10),
+            None,
+        );
+
+        let (summary, data) = stats.execute(&serde_json::json!({}));
+        assert_eq!(
+            summary,
+            "3 call(s) across 2 tool(s); slowest: read_pid (p95 \u{2264} 10000 ms); \
+             most failures: read_pid (2 of 2); 1 slow run(s)"
+        );
+        assert_eq!(data["tools"].as_array().unwrap().len(), 2);
+        assert_eq!(data["slow_after_ms"], SLOW_TOOL_MS);
+
+        let (_, data) = stats.execute(&serde_json::json!({"tool": "read_vin"}));
+        assert_eq!(data["tools"][0]["tool"], "read_vin");
+        assert_eq!(data["tools"][0]["latency_ms"]["p50"], 2_000);
+        assert_eq!(data["tools"].as_array().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,552 @@
+//! Agent self-update.
+//!
+//! A release arrives as an [`AgentUpdate`] on the `update` shadow. The
+//! agent downloads it next to its own binary, checks the SHA-256, makes sure
+//! the new binary runs (`--version` must print the release version) and
+//! swaps it in, keeping the old one as `<binary>.previous`. It then exits
+//! with [`RESTART_EXIT_CODE`] once no command is running, and systemd
+//! starts the new release (`Restart=on-failure`).
+//!
+//! The swap is recorded in a state file. Each start of the new release
+//! counts as a boot attempt; reaching the broker confirms the release,
+//! which is reported as `installed` and drops the backup. A release that
+//! is still unconfirmed after `max_boot_attempts` starts (it crashes, or
+//! never connects) is rolled back: the backup is restored, the agent exits
+//! for systemd to start it, and the old release reports `rolled_back`. The
+//! state file then remembers the release so that redelivering the same
+//! delta does not retry it.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use zc_protocol::shadows::{AgentUpdate, UpdateReport, UpdateStatus};
+
+/// Exit code after installing or rolling back a release. Any non-zero code
+/// makes systemd restart the agent under `Restart=on-failure`.
+pub const RESTART_EXIT_CODE: i32 = 75;
+
+/// Version of the running agent.
+const RUNNING_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Self-update settings (`[update]` in agent.toml).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// Apply releases delivered on the `update` shadow. On by default.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Binary to replace; the running executable when unset.
+    #[serde(default)]
+    pub binary_path: Option<String>,
+    /// Tracks an install across the restart and remembers rolled-back
+    /// releases.
+    #[serde(default = "default_state_path")]
+    pub state_path: String,
+    /// Starts a new release gets to reach the broker before it is rolled
+    /// back.
+    #[serde(default = "default_max_boot_attempts")]
+    pub max_boot_attempts: u32,
+    /// Longest a download may take.
+    #[serde(default = "default_download_timeout_secs")]
+    pub download_timeout_secs: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_state_path() -> String {
+    "/var/lib/zeroclaw/update.json".into()
+}
+
+fn default_max_boot_attempts() -> u32 {
+    3
+}
+
+fn default_download_timeout_secs() -> u64 {
+    300
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            binary_path: None,
+            state_path: default_state_path(),
+            max_boot_attempts: default_max_boot_attempts(),
+            download_timeout_secs: default_download_timeout_secs(),
+        }
+    }
+}
+
+/// Why a release could not be installed.
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateError {
+    #[error("download failed: {0}")]
+    Download(String),
+    #[error("checksum mismatch: expected {expected}, got {actual}")]
+    Checksum { expected: String, actual: String },
+    #[error("new binary does not run as version {0}: {1}")]
+    SmokeTest(String, String),
+    #[error("release {0} was rolled back before, not retrying")]
+    PreviouslyRolledBack(String),
+    #[error("self-update is disabled on this device")]
+    Disabled,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Install progress kept in the state file across restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum UpdateState {
+    /// Swapped in, not yet confirmed by reaching the broker.
+    Pending {
+        update: AgentUpdate,
+        previous_version: String,
+        boot_attempts: u32,
+    },
+    /// Restored the previous release. `reported` once the cloud was told.
+    RolledBack {
+        update: AgentUpdate,
+        reason: String,
+        #[serde(default)]
+        reported: bool,
+    },
+}
+
+/// What [`Updater::on_boot`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootOutcome {
+    /// No install in progress.
+    Idle,
+    /// Running a new release on trial, boot attempt `n`.
+    Trial(u32),
+    /// The new release failed too often and the previous binary is back
+    /// in place; exit with [`RESTART_EXIT_CODE`] to start it.
+    RolledBack,
+}
+
+/// Downloads, installs, confirms and rolls back agent releases.
+#[derive(Debug, Clone)]
+pub struct Updater {
+    enabled: bool,
+    binary: PathBuf,
+    state_path: PathBuf,
+    max_boot_attempts: u32,
+    client: reqwest::Client,
+}
+
+impl Updater {
+    /// Updater for the configured binary (the running executable by
+    /// default).
+    pub fn new(config: &UpdateConfig) -> io::Result<Self> {
+        let binary = match &config.binary_path {
+            Some(path) => PathBuf::from(path),
+            None => std::env::current_exe()?,
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.download_timeout_secs))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            enabled: config.enabled,
+            binary,
+            state_path: PathBuf::from(&config.state_path),
+            max_boot_attempts: config.max_boot_attempts,
+            client,
+        })
+    }
+
+    pub fn binary(&self) -> &Path {
+        &self.binary
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = self.binary.clone().into_os_string();
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+
+    /// The previous release, kept until the new one is confirmed.
+    pub fn backup_path(&self) -> PathBuf {
+        self.sibling(".previous")
+    }
+
+    fn staged_path(&self) -> PathBuf {
+        self.sibling(".new")
+    }
+
+    fn load(&self) -> Option<UpdateState> {
+        let content = std::fs::read_to_string(&self.state_path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                tracing::warn!(path = %self.state_path.display(), error = %e, "ignoring unreadable update state");
+                None
+            }
+        }
+    }
+
+    fn save(&self, state: &UpdateState) -> io::Result<()> {
+        if let Some(dir) = self.state_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_vec(state).map_err(io::Error::other)?;
+        let tmp = self.state_path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, &self.state_path)
+    }
+
+    fn clear(&self) {
+        if let Err(e) = std::fs::remove_file(&self.state_path)
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            tracing::warn!(error = %e, "cannot remove update state");
+        }
+    }
+
+    /// Whether `update` is the release already running, so there is
+    /// nothing to install.
+    pub fn is_running(update: &AgentUpdate) -> bool {
+        update.agent_version == RUNNING_VERSION
+    }
+
+    /// Download, verify and swap in `update`. On success the caller exits
+    /// with [`RESTART_EXIT_CODE`] to start it.
+    pub async fn install(&self, update: &AgentUpdate) -> Result<(), UpdateError> {
+        if !self.enabled {
+            return Err(UpdateError::Disabled);
+        }
+        if let Some(UpdateState::RolledBack { update: failed, .. }) = self.load()
+            && failed == *update
+        {
+            return Err(UpdateError::PreviouslyRolledBack(
+                update.agent_version.clone(),
+            ));
+        }
+
+        let body = self
+            .client
+            .get(&update.url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| UpdateError::Download(e.to_string()))?
+            .bytes()
+            .await
+            .map_err(|e| UpdateError::Download(e.to_string()))?;
+        let actual = sha256_hex(&body);
+        if !actual.eq_ignore_ascii_case(&update.sha256) {
+            return Err(UpdateError::Checksum {
+                expected: update.sha256.clone(),
+                actual,
+            });
+        }
+
+        let staged = self.staged_path();
+        std::fs::write(&staged, &body)?;
+        make_executable(&staged)?;
+        if let Err(e) = smoke_test(&staged, &update.agent_version).await {
+            let _ = std::fs::remove_file(&staged);
+            return Err(e);
+        }
+
+        std::fs::copy(&self.binary, self.backup_path())?;
+        self.save(&UpdateState::Pending {
+            update: update.clone(),
+            previous_version: RUNNING_VERSION.to_string(),
+            boot_attempts: 0,
+        })?;
+        if let Err(e) = std::fs::rename(&staged, &self.binary) {
+            self.clear();
+            return Err(e.into());
+        }
+        tracing::info!(
+            version = %update.agent_version,
+            binary = %self.binary.display(),
+            "agent release installed, restarting"
+        );
+        Ok(())
+    }
+
+    /// Count a start of a release on trial and roll it back once it has
+    /// used up its attempts. Call before connecting.
+    pub fn on_boot(&self) -> BootOutcome {
+        let Some(UpdateState::Pending {
+            update,
+            previous_version,
+            boot_attempts,
+        }) = self.load()
+        else {
+            return BootOutcome::Idle;
+        };
+
+        let boot_attempts = boot_attempts + 1;
+        let reason = if update.agent_version != RUNNING_VERSION {
+            format!("agent restarted as {RUNNING_VERSION} instead")
+        } else if boot_attempts > self.max_boot_attempts {
+            format!(
+                "did not reach the broker in {} starts",
+                self.max_boot_attempts
+            )
+        } else {
+            let state = UpdateState::Pending {
+                update,
+                previous_version,
+                boot_attempts,
+            };
+            if let Err(e) = self.save(&state) {
+                tracing::warn!(error = %e, "cannot record boot attempt");
+            }
+            return BootOutcome::Trial(boot_attempts);
+        };
+
+        // Restore the previous binary unless it is the one running.
+        let restored = update.agent_version == RUNNING_VERSION;
+        if restored && let Err(e) = std::fs::rename(self.backup_path(), &self.binary) {
+            tracing::error!(error = %e, "cannot restore the previous agent release");
+            return BootOutcome::Trial(boot_attempts);
+        }
+        tracing::warn!(
+            version = %update.agent_version,
+            previous_version = %previous_version,
+            reason = %reason,
+            "rolling back agent release"
+        );
+        let state = UpdateState::RolledBack {
+            update,
+            reason,
+            reported: false,
+        };
+        if let Err(e) = self.save(&state) {
+            tracing::warn!(error = %e, "cannot record rollback");
+        }
+        if restored {
+            BootOutcome::RolledBack
+        } else {
+            BootOutcome::Idle
+        }
+    }
+
+    /// Confirm a release on trial, now that the broker was reached, and
+    /// return what to report on the `update` shadow: `installed` for a
+    /// confirmed release, `rolled_back` once after a rollback.
+    pub fn on_connected(&self) -> Option<UpdateReport> {
+        match self.load()? {
+            UpdateState::Pending { update, .. } if update.agent_version == RUNNING_VERSION => {
+                if let Err(e) = std::fs::remove_file(self.backup_path())
+                    && e.kind() != io::ErrorKind::NotFound
+                {
+                    tracing::warn!(error = %e, "cannot remove the previous agent release");
+                }
+                self.clear();
+                tracing::info!(version = %update.agent_version, "agent release confirmed");
+                Some(UpdateReport::new(&update, UpdateStatus::Installed, None))
+            }
+            UpdateState::Pending { .. } => None,
+            UpdateState::RolledBack { reported: true, .. } => None,
+            UpdateState::RolledBack { update, reason, .. } => {
+                let report =
+                    UpdateReport::new(&update, UpdateStatus::RolledBack, Some(reason.clone()));
+                let state = UpdateState::RolledBack {
+                    update,
+                    reason,
+                    reported: true,
+                };
+                if let Err(e) = self.save(&state) {
+                    tracing::warn!(error = %e, "cannot record rollback report");
+                }
+                Some(report)
+            }
+        }
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
+
+/// Run `binary --version` and check it names `version`.
+async fn smoke_test(binary: &Path, version: &str) -> Result<(), UpdateError> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(10),
+        tokio::process::Command::new(binary)
+            .arg("--version")
+            .output(),
+    )
+    .await
+    .map_err(|_| UpdateError::SmokeTest(version.into(), "timed out".into()))?
+    .map_err(|e| UpdateError::SmokeTest(version.into(), e.to_string()))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && stdout.split_whitespace().any(|word| word == version) {
+        Ok(())
+    } else {
+        Err(UpdateError::SmokeTest(
+            version.into(),
+            format!("printed '{}'", stdout.trim()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A fake agent release: a script that prints `version`.
+    fn release_script(version: &str) -> Vec<u8> {
+        format!("#!/bin/sh\necho zc-fleet-agent {version}\n").into_bytes()
+    }
+
+    fn updater(dir: &Path) -> Updater {
+        let binary = dir.join("zc-fleet-agent");
+        std::fs::write(&binary, release_script(RUNNING_VERSION)).unwrap();
+        Updater::new(&UpdateConfig {
+            binary_path: Some(binary.to_string_lossy().into()),
+            state_path: dir.join("update.json").to_string_lossy().into(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    async fn serve(body: Vec<u8>) -> (MockServer, String) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/zc-fleet-agent"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .mount(&server)
+            .await;
+        let url = format!("{}/zc-fleet-agent", server.uri());
+        (server, url)
+    }
+
+    fn tempdir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zc-updater-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn install_verifies_and_swaps_the_binary() {
+        let dir = tempdir("install");
+        let updater = updater(&dir);
+        let body = release_script("9.9.9");
+        let (_server, url) = serve(body.clone()).await;
+
+        let bad = AgentUpdate {
+            agent_version: "9.9.9".into(),
+            url: url.clone(),
+            sha256: "00".repeat(32),
+        };
+        assert!(matches!(
+            updater.install(&bad).await,
+            Err(UpdateError::Checksum { .. })
+        ));
+        let wrong_version = AgentUpdate {
+            agent_version: "9.9.8".into(),
+            url: url.clone(),
+            sha256: sha256_hex(&body),
+        };
+        assert!(matches!(
+            updater.install(&wrong_version).await,
+            Err(UpdateError::SmokeTest(..))
+        ));
+        assert_eq!(
+            std::fs::read(updater.binary()).unwrap(),
+            release_script(RUNNING_VERSION)
+        );
+
+        let update = AgentUpdate {
+            agent_version: "9.9.9".into(),
+            url,
+            sha256: sha256_hex(&body).to_uppercase(),
+        };
+        updater.install(&update).await.unwrap();
+        assert_eq!(std::fs::read(updater.binary()).unwrap(), body);
+        assert_eq!(
+            std::fs::read(updater.backup_path()).unwrap(),
+            release_script(RUNNING_VERSION)
+        );
+        assert!(!updater.staged_path().exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn confirmed_release_reports_installed() {
+        let dir = tempdir("confirm");
+        let updater = updater(&dir);
+        let update = AgentUpdate {
+            agent_version: RUNNING_VERSION.into(),
+            url: "https://releases.example.com/zc-fleet-agent".into(),
+            sha256: "ab".repeat(32),
+        };
+        std::fs::write(updater.backup_path(), b"old").unwrap();
+        updater
+            .save(&UpdateState::Pending {
+                update: update.clone(),
+                previous_version: "0.0.1".into(),
+                boot_attempts: 0,
+            })
+            .unwrap();
+
+        assert_eq!(updater.on_boot(), BootOutcome::Trial(1));
+        let report = updater.on_connected().unwrap();
+        assert_eq!(report.status, UpdateStatus::Installed);
+        assert_eq!(report.url, update.url);
+        assert!(!updater.backup_path().exists());
+        assert_eq!(updater.on_boot(), BootOutcome::Idle);
+        assert!(updater.on_connected().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn release_that_never_connects_is_rolled_back_once() {
+        let dir = tempdir("rollback");
+        let updater = updater(&dir);
+        let update = AgentUpdate {
+            agent_version: RUNNING_VERSION.into(),
+            url: "https://releases.example.com/zc-fleet-agent".into(),
+            sha256: "ab".repeat(32),
+        };
+        std::fs::write(updater.backup_path(), b"old").unwrap();
+        updater
+            .save(&UpdateState::Pending {
+                update: update.clone(),
+                previous_version: "0.0.1".into(),
+                boot_attempts: 0,
+            })
+            .unwrap();
+
+        for attempt in 1..=3 {
+            assert_eq!(updater.on_boot(), BootOutcome::Trial(attempt));
+        }
+        assert_eq!(updater.on_boot(), BootOutcome::RolledBack);
+        assert_eq!(std::fs::read(updater.binary()).unwrap(), b"old");
+
+        let report = updater.on_connected().unwrap();
+        assert_eq!(report.status, UpdateStatus::RolledBack);
+        assert_eq!(report.agent_version, update.agent_version);
+        assert!(report.error.unwrap().contains("3 starts"));
+        assert!(updater.on_connected().is_none());
+        assert!(matches!(
+            updater.install(&update).await,
+            Err(UpdateError::PreviouslyRolledBack(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
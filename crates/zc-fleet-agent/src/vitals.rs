@@ -0,0 +1,102 @@
+//! Host vitals reported in each heartbeat.
+//!
+//! Readings come from procfs and sysfs, plus `df` for the root filesystem
+//! like the self-test's disk check. Each one is best effort: a source the
+//! platform lacks (no thermal zone, no wireless interface) leaves its field
+//! out of the [`DeviceVitals`] rather than failing the heartbeat.
+
+use zc_protocol::device::DeviceVitals;
+
+use crate::self_test::parse_df_available_kb;
+
+const LOADAVG: &str = "/proc/loadavg";
+const MEMINFO: &str = "/proc/meminfo";
+const THERMAL_ZONE: &str = "/sys/class/thermal/thermal_zone0/temp";
+const WIRELESS: &str = "/proc/net/wireless";
+
+/// Sample the vitals, or `None` when nothing could be read.
+pub async fn sample() -> Option<DeviceVitals> {
+    let read = |path| std::fs::read_to_string(path).ok();
+    let vitals = DeviceVitals {
+        cpu_load: read(LOADAVG).as_deref().and_then(parse_loadavg),
+        memory_free_bytes: read(MEMINFO).as_deref().and_then(parse_mem_available),
+        disk_free_bytes: root_disk_free().await,
+        temperature_c: read(THERMAL_ZONE).as_deref().and_then(parse_thermal),
+        rssi_dbm: read(WIRELESS).as_deref().and_then(parse_wireless_rssi),
+    };
+    (!vitals.is_empty()).then_some(vitals)
+}
+
+async fn root_disk_free() -> Option<u64> {
+    let out = tokio::process::Command::new("df")
+        .args(["-Pk", "/"])
+        .output()
+        .await
+        .ok()
+        .filter(|out| out.status.success())?;
+    parse_df_available_kb(&String::from_utf8_lossy(&out.stdout)).map(|kb| kb * 1024)
+}
+
+/// 1-minute load average, the first field of `/proc/loadavg`.
+fn parse_loadavg(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+/// `MemAvailable` from `/proc/meminfo`, in bytes.
+fn parse_mem_available(content: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let kb = line
+            .strip_prefix("MemAvailable:")?
+            .trim()
+            .strip_suffix("kB")?;
+        kb.trim().parse::<u64>().ok().map(|kb| kb * 1024)
+    })
+}
+
+/// Thermal zone temperature, reported in millidegrees Celsius.
+fn parse_thermal(content: &str) -> Option<f64> {
+    content
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .map(|milli| milli as f64 / 1000.0)
+}
+
+/// Signal level (dBm) of the first interface in `/proc/net/wireless`,
+/// after its two header lines.
+fn parse_wireless_rssi(content: &str) -> Option<i32> {
+    let level = content.lines().nth(2)?.split_whitespace().nth(3)?;
+    level.trim_end_matches('.').parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_sources() {
+        assert_eq!(parse_loadavg("0.42 0.30 0.25 1/123 4567\n"), Some(0.42));
+        assert_eq!(parse_loadavg(""), None);
+
+        let meminfo = "MemTotal:        3884328 kB\n\
+                       MemFree:          250000 kB\n\
+                       MemAvailable:    2048000 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(2048000 * 1024));
+        assert_eq!(parse_mem_available("MemTotal: 1 kB\n"), None);
+
+        assert_eq!(parse_thermal("48312\n"), Some(48.312));
+        assert_eq!(parse_thermal("n/a"), None);
+    }
+
+    #[test]
+    fn parses_wireless_signal_level() {
+        let wireless = "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+                        face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n \
+                        wlan0: 0000   54.  -56.  -256        0      0      0      0      3        0\n";
+        assert_eq!(parse_wireless_rssi(wireless), Some(-56));
+
+        // Header only: no wireless interface.
+        let header: String = wireless.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert_eq!(parse_wireless_rssi(&header), None);
+    }
+}
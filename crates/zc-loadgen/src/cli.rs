@@ -0,0 +1,188 @@
+//! Command-line argument handling for the load generator.
+
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage:
+  zc-loadgen [OPTIONS]
+
+Options:
+  --api URL                 cloud API base URL (default: http://localhost:3000)
+  --mqtt HOST:PORT          plaintext MQTT broker the API bridges (default: localhost:1883)
+  --fleet ID                fleet the simulated devices join (default: fleet-alpha)
+  --devices N               simulated devices to provision (default: 10)
+  --duration SECS           length of the traffic phase (default: 60)
+  --heartbeat-rate R        heartbeats per second, fleet-wide (default: 10)
+  --telemetry-rate R        telemetry batches per second (default: 5)
+  --command-rate R          commands dispatched per second (default: 1)
+  --response-delay-ms MS    simulated tool execution time (default: 200)
+  --max-in-flight N         concurrent HTTP requests before ticks are dropped (default: 256)
+  --json                    print the report as JSON
+  --help                    show this message";
+
+/// What the binary was asked to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run(LoadConfig),
+    Help,
+}
+
+/// Parameters of one load run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadConfig {
+    pub api_url: String,
+    pub mqtt_host: String,
+    pub mqtt_port: u16,
+    pub fleet_id: String,
+    pub devices: usize,
+    pub duration: Duration,
+    /// Operations per second, across all devices. `0` disables the stream.
+    pub heartbeat_rate: f64,
+    pub telemetry_rate: f64,
+    pub command_rate: f64,
+    pub response_delay: Duration,
+    pub max_in_flight: usize,
+    pub json: bool,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "http://localhost:3000".into(),
+            mqtt_host: "localhost".into(),
+            mqtt_port: 1883,
+            fleet_id: "fleet-alpha".into(),
+            devices: 10,
+            duration: Duration::from_secs(60),
+            heartbeat_rate: 10.0,
+            telemetry_rate: 5.0,
+            command_rate: 1.0,
+            response_delay: Duration::from_millis(200),
+            max_in_flight: 256,
+            json: false,
+        }
+    }
+}
+
+/// Parse arguments (excluding the program name).
+pub fn parse<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut config = LoadConfig::default();
+    let mut args = args.into_iter();
+
+    while let Some(flag) = args.next() {
+        if matches!(flag.as_str(), "--help" | "-h") {
+            return Ok(Command::Help);
+        }
+        if flag == "--json" {
+            config.json = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("option '{flag}' needs a value"))?;
+        match flag.as_str() {
+            "--api" => config.api_url = value.trim_end_matches('/').to_string(),
+            "--mqtt" => {
+                let (host, port) = value
+                    .rsplit_once(':')
+                    .ok_or_else(|| format!("invalid --mqtt '{value}': expected HOST:PORT"))?;
+                config.mqtt_host = host.to_string();
+                config.mqtt_port = number(&flag, port)?;
+            }
+            "--fleet" => config.fleet_id = value,
+            "--devices" => config.devices = number(&flag, &value)?,
+            "--duration" => config.duration = Duration::from_secs(number(&flag, &value)?),
+            "--heartbeat-rate" => config.heartbeat_rate = rate(&flag, &value)?,
+            "--telemetry-rate" => config.telemetry_rate = rate(&flag, &value)?,
+            "--command-rate" => config.command_rate = rate(&flag, &value)?,
+            "--response-delay-ms" => {
+                config.response_delay = Duration::from_millis(number(&flag, &value)?)
+            }
+            "--max-in-flight" => config.max_in_flight = number(&flag, &value)?,
+            _ => return Err(format!("unknown option '{flag}'")),
+        }
+    }
+
+    if config.devices == 0 {
+        return Err("--devices must be at least 1".into());
+    }
+    if config.max_in_flight == 0 {
+        return Err("--max-in-flight must be at least 1".into());
+    }
+    Ok(Command::Run(config))
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {flag} '{value}': expected a whole number"))
+}
+
+fn rate(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(r) if r.is_finite() && r >= 0.0 => Ok(r),
+        _ => Err(format!(
+            "invalid {flag} '{value}': expected a non-negative rate"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_strs(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn no_args_uses_defaults() {
+        assert_eq!(
+            parse_strs(&[]).unwrap(),
+            Command::Run(LoadConfig::default())
+        );
+    }
+
+    #[test]
+    fn options_override_defaults() {
+        let Command::Run(config) = parse_strs(&[
+            "--api",
+            "http://api:8080/",
+            "--mqtt",
+            "broker:1884",
+            "--devices",
+            "500",
+            "--duration",
+            "300",
+            "--command-rate",
+            "2.5",
+            "--telemetry-rate",
+            "0",
+            "--json",
+        ])
+        .unwrap() else {
+            panic!("expected run");
+        };
+        assert_eq!(config.api_url, "http://api:8080");
+        assert_eq!(config.mqtt_host, "broker");
+        assert_eq!(config.mqtt_port, 1884);
+        assert_eq!(config.devices, 500);
+        assert_eq!(config.duration, Duration::from_secs(300));
+        assert_eq!(config.command_rate, 2.5);
+        assert_eq!(config.telemetry_rate, 0.0);
+        assert!(config.json);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(parse_strs(&["--devices", "0"]).is_err());
+        assert!(parse_strs(&["--devices"]).is_err());
+        assert!(parse_strs(&["--command-rate", "-1"]).is_err());
+        assert!(parse_strs(&["--mqtt", "broker"]).is_err());
+        assert!(parse_strs(&["--verbose", "1"]).is_err());
+        assert_eq!(parse_strs(&["--help"]).unwrap(), Command::Help);
+    }
+}
@@ -0,0 +1,12 @@
+//! ZeroClaw load generator — drives a running `zc-cloud-api` with
+//! realistic mixed traffic from simulated devices.
+//!
+//! - `cli` — argument parsing into a [`cli::LoadConfig`]
+//! - `traffic` — payload builders (heartbeats, telemetry, commands, responses)
+//! - `stats` — latency/error recording and the final [`stats::Report`]
+//! - `runner` — provisioning, rate-paced traffic loops, simulated responders
+
+pub mod cli;
+pub mod runner;
+pub mod stats;
+pub mod traffic;
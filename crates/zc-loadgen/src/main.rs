@@ -0,0 +1,45 @@
+//! ZeroClaw load generator — mixed HTTP + MQTT traffic against a running
+//! cloud API, with a latency/error report at the end.
+//!
+//! Exits non-zero when any operation failed or a dispatched command was
+//! never resolved, so it can gate CI or capacity-regression jobs.
+
+use tracing_subscriber::EnvFilter;
+
+use zc_loadgen::cli::{self, Command};
+use zc_loadgen::runner;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let json = config.json;
+    let report = runner::run(config).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+
+    if report.total_errors() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,340 @@
+//! Load run orchestration.
+//!
+//! 1. Provision `devices` simulated devices over HTTP (tagged `loadgen=<run>`)
+//! 2. Publish one heartbeat per device so the cloud marks them online
+//! 3. For `duration`, pace heartbeats, telemetry batches and command
+//!    dispatches at the configured fleet-wide rates. Ticks are open-loop:
+//!    a slow API does not slow the schedule, and ticks that would exceed
+//!    `max_in_flight` outstanding operations are dropped and counted.
+//! 4. A responder subscribed to the fleet's command topics answers every
+//!    command addressed to this run's devices after `response_delay`
+//! 5. After a grace period, count completed/failed commands through
+//!    `GET /api/v1/commands?initiated_by=…&status=…` (`X-Total-Count`)
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use chrono::Utc;
+use rumqttc::{Event, EventLoop, Packet, QoS};
+use tokio::sync::Semaphore;
+use tokio::time::MissedTickBehavior;
+
+use zc_mqtt_channel::{Channel, IncomingMessage, MqttChannel, classify};
+use zc_protocol::commands::CommandEnvelope;
+use zc_protocol::topics;
+
+use crate::cli::LoadConfig;
+use crate::stats::{CommandOutcomes, Op, Recorder, Report};
+use crate::traffic;
+
+/// Devices go offline in the cloud after this long without a heartbeat.
+const CLOUD_OFFLINE_AFTER_SECS: f64 = 90.0;
+
+/// Time allowed after the traffic phase for responses to be ingested.
+const SETTLE_GRACE: Duration = Duration::from_secs(3);
+
+/// Shared state of one run.
+struct Run {
+    config: LoadConfig,
+    run_id: String,
+    devices: Vec<String>,
+    http: reqwest::Client,
+    mqtt: MqttChannel,
+    recorder: Recorder,
+    in_flight: Arc<Semaphore>,
+    dropped: AtomicU64,
+    dispatched: AtomicU64,
+    started: Instant,
+}
+
+/// Execute a full load run and return its report.
+pub async fn run(config: LoadConfig) -> anyhow::Result<Report> {
+    let run_id = new_run_id();
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    http.get(format!("{}/health", config.api_url))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("cloud API not reachable at {}", config.api_url))?;
+
+    if config.heartbeat_rate * CLOUD_OFFLINE_AFTER_SECS < config.devices as f64 {
+        tracing::warn!(
+            devices = config.devices,
+            heartbeat_rate = config.heartbeat_rate,
+            "heartbeat rate too low to keep every device online; commands will be queued"
+        );
+    }
+
+    let (mqtt, eventloop) = MqttChannel::new_plaintext(
+        &config.mqtt_host,
+        config.mqtt_port,
+        &format!("zc-loadgen-{run_id}"),
+        config.fleet_id.clone(),
+        "loadgen",
+    );
+    mqtt.subscribe(
+        &topics::command_request(&config.fleet_id, "+"),
+        QoS::AtLeastOnce,
+    )
+    .await?;
+
+    let run = Arc::new(Run {
+        devices: (0..config.devices)
+            .map(|i| traffic::device_id(&run_id, i))
+            .collect(),
+        in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+        config,
+        run_id,
+        http,
+        mqtt,
+        recorder: Recorder::new(),
+        dropped: AtomicU64::new(0),
+        dispatched: AtomicU64::new(0),
+        started: Instant::now(),
+    });
+    let responder = tokio::spawn(respond_loop(run.clone(), eventloop));
+
+    tracing::info!(run_id = %run.run_id, devices = run.devices.len(), "provisioning");
+    provision_all(&run).await;
+    for device_id in &run.devices {
+        publish_heartbeat(&run, device_id).await;
+    }
+
+    tracing::info!(
+        duration_secs = run.config.duration.as_secs(),
+        "traffic phase"
+    );
+    let traffic_start = Instant::now();
+    let until = traffic_start + run.config.duration;
+    tokio::join!(
+        paced(
+            run.clone(),
+            run.config.heartbeat_rate,
+            until,
+            heartbeat_tick
+        ),
+        paced(
+            run.clone(),
+            run.config.telemetry_rate,
+            until,
+            telemetry_tick
+        ),
+        paced(run.clone(), run.config.command_rate, until, command_tick),
+    );
+    let elapsed = traffic_start.elapsed();
+
+    // Wait for outstanding operations, then let responses land.
+    let permits = run.config.max_in_flight as u32;
+    let _ =
+        tokio::time::timeout(Duration::from_secs(15), run.in_flight.acquire_many(permits)).await;
+    tokio::time::sleep(run.config.response_delay + SETTLE_GRACE).await;
+    responder.abort();
+
+    let commands = command_outcomes(&run).await;
+    Ok(Report {
+        run_id: run.run_id.clone(),
+        devices: run.devices.len(),
+        duration_secs: elapsed.as_secs_f64(),
+        operations: run.recorder.summarize(elapsed),
+        commands,
+        dropped_ticks: run.dropped.load(Ordering::Relaxed),
+    })
+}
+
+/// Short run identifier: the random tail of a UUIDv7.
+fn new_run_id() -> String {
+    let id = uuid::Uuid::now_v7().simple().to_string();
+    id[id.len() - 8..].to_string()
+}
+
+/// Call `op` at `rate` per second until `until`, without waiting for it.
+async fn paced<F, Fut>(run: Arc<Run>, rate: f64, until: Instant, op: F)
+where
+    F: Fn(Arc<Run>, u64) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    if rate <= 0.0 {
+        return;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let mut seq = 0;
+    loop {
+        interval.tick().await;
+        if Instant::now() >= until {
+            return;
+        }
+        let Ok(permit) = run.in_flight.clone().try_acquire_owned() else {
+            run.dropped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        let fut = op(run.clone(), seq);
+        tokio::spawn(async move {
+            fut.await;
+            drop(permit);
+        });
+        seq += 1;
+    }
+}
+
+fn pick(run: &Run, seq: u64) -> &str {
+    &run.devices[seq as usize % run.devices.len()]
+}
+
+async fn heartbeat_tick(run: Arc<Run>, seq: u64) {
+    publish_heartbeat(&run, pick(&run, seq)).await;
+}
+
+async fn telemetry_tick(run: Arc<Run>, seq: u64) {
+    let device_id = pick(&run, seq);
+    let batch = traffic::telemetry_batch(device_id, seq);
+    let topic = topics::telemetry_obd2(&run.config.fleet_id, device_id);
+    publish(&run, Op::Telemetry, &topic, &batch).await;
+}
+
+async fn command_tick(run: Arc<Run>, seq: u64) {
+    let body = traffic::command_request(&run.run_id, &run.config.fleet_id, pick(&run, seq), seq);
+    let start = Instant::now();
+    let result = run
+        .http
+        .post(format!("{}/api/v1/commands", run.config.api_url))
+        .json(&body)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    match result {
+        Ok(_) => {
+            run.dispatched.fetch_add(1, Ordering::Relaxed);
+            run.recorder.success(Op::CommandDispatch, start.elapsed());
+        }
+        Err(e) => run.recorder.error(Op::CommandDispatch, e),
+    }
+}
+
+async fn publish_heartbeat(run: &Run, device_id: &str) {
+    let uptime = run.started.elapsed().as_secs();
+    let heartbeat = traffic::heartbeat(&run.config.fleet_id, device_id, uptime);
+    let topic = topics::heartbeat(&run.config.fleet_id, device_id);
+    publish(run, Op::Heartbeat, &topic, &heartbeat).await;
+}
+
+async fn publish<T: serde::Serialize>(run: &Run, op: Op, topic: &str, payload: &T) {
+    let bytes = match serde_json::to_vec(payload) {
+        Ok(b) => b,
+        Err(e) => return run.recorder.error(op, e),
+    };
+    let start = Instant::now();
+    match run.mqtt.publish(topic, &bytes, QoS::AtLeastOnce).await {
+        Ok(()) => run.recorder.success(op, start.elapsed()),
+        Err(e) => run.recorder.error(op, e),
+    }
+}
+
+/// Provision every device; an existing device (409) counts as provisioned.
+async fn provision_all(run: &Arc<Run>) {
+    let mut tasks = Vec::with_capacity(run.devices.len());
+    for device_id in run.devices.clone() {
+        let run = run.clone();
+        let permit = run.in_flight.clone().acquire_owned().await;
+        tasks.push(tokio::spawn(async move {
+            let _permit = permit;
+            let body = traffic::provision_request(&run.run_id, &run.config.fleet_id, &device_id);
+            let start = Instant::now();
+            let result = run
+                .http
+                .post(format!("{}/api/v1/devices", run.config.api_url))
+                .json(&body)
+                .send()
+                .await;
+            match result {
+                Ok(r) if r.status().is_success() || r.status() == reqwest::StatusCode::CONFLICT => {
+                    run.recorder.success(Op::Provision, start.elapsed())
+                }
+                Ok(r) => run
+                    .recorder
+                    .error(Op::Provision, format!("HTTP {}", r.status())),
+                Err(e) => run.recorder.error(Op::Provision, e),
+            }
+        }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+/// Drive the MQTT event loop and answer commands for this run's devices.
+async fn respond_loop(run: Arc<Run>, mut eventloop: EventLoop) {
+    let prefix = traffic::device_prefix(&run.run_id);
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                if let IncomingMessage::Command(envelope) = classify(&publish)
+                    && envelope.device_id.starts_with(&prefix)
+                {
+                    let delivery = (Utc::now() - envelope.created_at)
+                        .to_std()
+                        .unwrap_or_default();
+                    run.recorder.success(Op::CommandDelivery, delivery);
+                    tokio::spawn(respond(run.clone(), envelope));
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(error = %e, "mqtt event loop error");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+async fn respond(run: Arc<Run>, envelope: CommandEnvelope) {
+    tokio::time::sleep(run.config.response_delay).await;
+    let response = traffic::response_for(&envelope, run.config.response_delay.as_millis() as u64);
+    let topic = topics::command_response(&envelope.fleet_id, &envelope.device_id);
+    publish(&run, Op::CommandResponse, &topic, &response).await;
+}
+
+async fn command_outcomes(run: &Run) -> CommandOutcomes {
+    let dispatched = run.dispatched.load(Ordering::Relaxed);
+    let completed = count_commands(run, "completed").await;
+    let failed = count_commands(run, "failed").await;
+    CommandOutcomes {
+        dispatched,
+        completed,
+        failed,
+        unresolved: dispatched.saturating_sub(completed + failed),
+    }
+}
+
+/// Commands of this run in `status`, from the list endpoint's total header.
+async fn count_commands(run: &Run, status: &str) -> u64 {
+    let result = run
+        .http
+        .get(format!("{}/api/v1/commands", run.config.api_url))
+        .query(&[
+            ("initiated_by", traffic::operator(&run.run_id).as_str()),
+            ("status", status),
+            ("limit", "1"),
+        ])
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    match result {
+        Ok(r) => r
+            .headers()
+            .get("x-total-count")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+        Err(e) => {
+            tracing::warn!(error = %e, status, "could not count commands");
+            0
+        }
+    }
+}
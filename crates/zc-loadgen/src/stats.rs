@@ -0,0 +1,244 @@
+//! Latency and error recording for a load run.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Kind of operation measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    /// `POST /api/v1/devices`.
+    Provision,
+    /// Heartbeat publish to the broker.
+    Heartbeat,
+    /// Telemetry batch publish to the broker.
+    Telemetry,
+    /// `POST /api/v1/commands`.
+    CommandDispatch,
+    /// Envelope creation in the cloud until a simulated device received it.
+    CommandDelivery,
+    /// Command response publish to the broker.
+    CommandResponse,
+}
+
+impl Op {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Op::Provision => "provision",
+            Op::Heartbeat => "heartbeat",
+            Op::Telemetry => "telemetry",
+            Op::CommandDispatch => "command_dispatch",
+            Op::CommandDelivery => "command_delivery",
+            Op::CommandResponse => "command_response",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct OpSamples {
+    micros: Vec<u64>,
+    errors: u64,
+    last_error: Option<String>,
+}
+
+/// Thread-safe sample collector shared by every traffic task.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    ops: Mutex<BTreeMap<Op, OpSamples>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a successful operation.
+    pub fn success(&self, op: Op, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.ops
+            .lock()
+            .unwrap()
+            .entry(op)
+            .or_default()
+            .micros
+            .push(micros);
+    }
+
+    /// Record a failed operation.
+    pub fn error(&self, op: Op, error: impl fmt::Display) {
+        let mut ops = self.ops.lock().unwrap();
+        let entry = ops.entry(op).or_default();
+        entry.errors += 1;
+        entry.last_error = Some(error.to_string());
+    }
+
+    /// Summarize everything recorded so far over `elapsed` wall time.
+    pub fn summarize(&self, elapsed: Duration) -> Vec<OpSummary> {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let ops = self.ops.lock().unwrap();
+        ops.iter()
+            .map(|(op, samples)| {
+                let mut sorted = samples.micros.clone();
+                sorted.sort_unstable();
+                OpSummary {
+                    op: *op,
+                    count: sorted.len() as u64,
+                    errors: samples.errors,
+                    rate_per_sec: sorted.len() as f64 / secs,
+                    p50_ms: percentile_ms(&sorted, 50.0),
+                    p95_ms: percentile_ms(&sorted, 95.0),
+                    p99_ms: percentile_ms(&sorted, 99.0),
+                    max_ms: sorted.last().map_or(0.0, |m| *m as f64 / 1000.0),
+                    last_error: samples.last_error.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile of sorted microsecond samples, in milliseconds.
+pub fn percentile_ms(sorted: &[u64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1] as f64 / 1000.0
+}
+
+/// Statistics of one operation kind.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpSummary {
+    pub op: Op,
+    /// Successful operations.
+    pub count: u64,
+    pub errors: u64,
+    pub rate_per_sec: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// How many dispatched commands the cloud recorded as finished.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommandOutcomes {
+    pub dispatched: u64,
+    pub completed: u64,
+    pub failed: u64,
+    /// Dispatched but neither completed nor failed when the run ended.
+    pub unresolved: u64,
+}
+
+/// Final result of a load run.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub run_id: String,
+    pub devices: usize,
+    /// Length of the traffic phase; per-second rates are relative to it.
+    pub duration_secs: f64,
+    pub operations: Vec<OpSummary>,
+    pub commands: CommandOutcomes,
+    /// Ticks skipped because `max_in_flight` requests were outstanding.
+    pub dropped_ticks: u64,
+}
+
+impl Report {
+    /// Total errors across all operations, plus unresolved commands.
+    pub fn total_errors(&self) -> u64 {
+        self.operations.iter().map(|o| o.errors).sum::<u64>() + self.commands.unresolved
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "run {} — {} devices, {:.1}s",
+            self.run_id, self.devices, self.duration_secs
+        )?;
+        writeln!(
+            f,
+            "{:<18} {:>8} {:>7} {:>8} {:>9} {:>9} {:>9} {:>9}",
+            "operation", "ok", "errors", "per_sec", "p50_ms", "p95_ms", "p99_ms", "max_ms"
+        )?;
+        for o in &self.operations {
+            writeln!(
+                f,
+                "{:<18} {:>8} {:>7} {:>8.1} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+                o.op.as_str(),
+                o.count,
+                o.errors,
+                o.rate_per_sec,
+                o.p50_ms,
+                o.p95_ms,
+                o.p99_ms,
+                o.max_ms
+            )?;
+        }
+        let c = &self.commands;
+        writeln!(
+            f,
+            "commands: {} dispatched, {} completed, {} failed, {} unresolved",
+            c.dispatched, c.completed, c.failed, c.unresolved
+        )?;
+        if self.dropped_ticks > 0 {
+            writeln!(
+                f,
+                "dropped ticks (client saturated): {}",
+                self.dropped_ticks
+            )?;
+        }
+        for o in self.operations.iter().filter(|o| o.last_error.is_some()) {
+            writeln!(
+                f,
+                "last {} error: {}",
+                o.op.as_str(),
+                o.last_error.as_deref().unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let samples: Vec<u64> = (1..=100).map(|ms| ms * 1000).collect();
+        assert_eq!(percentile_ms(&samples, 50.0), 50.0);
+        assert_eq!(percentile_ms(&samples, 99.0), 99.0);
+        assert_eq!(percentile_ms(&samples, 100.0), 100.0);
+        assert_eq!(percentile_ms(&[2500], 50.0), 2.5);
+        assert_eq!(percentile_ms(&[], 95.0), 0.0);
+    }
+
+    #[test]
+    fn recorder_summarizes_per_op() {
+        let recorder = Recorder::new();
+        for ms in [10, 20, 30, 40] {
+            recorder.success(Op::CommandDispatch, Duration::from_millis(ms));
+        }
+        recorder.success(Op::Heartbeat, Duration::from_micros(500));
+        recorder.error(Op::CommandDispatch, "HTTP 500");
+
+        let summary = recorder.summarize(Duration::from_secs(2));
+        assert_eq!(summary.len(), 2);
+        // Ordered by op declaration.
+        assert_eq!(summary[0].op, Op::Heartbeat);
+        let dispatch = &summary[1];
+        assert_eq!(dispatch.count, 4);
+        assert_eq!(dispatch.errors, 1);
+        assert_eq!(dispatch.rate_per_sec, 2.0);
+        assert_eq!(dispatch.p50_ms, 20.0);
+        assert_eq!(dispatch.max_ms, 40.0);
+        assert_eq!(dispatch.last_error.as_deref(), Some("HTTP 500"));
+    }
+}
@@ -0,0 +1,164 @@
+//! Payload builders for simulated device and operator traffic.
+//!
+//! Values vary with a sequence number rather than randomly so that two
+//! runs with the same settings send the same traffic.
+
+use chrono::Utc;
+use zc_protocol::commands::{CommandEnvelope, CommandResponse, CommandStatus, InferenceTier};
+use zc_protocol::device::{DeviceStatus, Heartbeat, ServiceStatus};
+use zc_protocol::telemetry::{TelemetryBatch, TelemetryReading, TelemetrySource};
+
+/// Operator commands the rule-based engine parses without a cloud LLM.
+pub const COMMANDS: &[&str] = &[
+    "read DTCs",
+    "read engine rpm",
+    "read coolant temperature",
+    "show log stats",
+    "analyze errors in the logs",
+    "read vin",
+    "check disk space",
+    "tail the last 20 log lines",
+];
+
+/// `initiated_by` of every command a run sends; used to count outcomes.
+pub fn operator(run_id: &str) -> String {
+    format!("loadgen-{run_id}")
+}
+
+/// Prefix shared by every simulated device ID of a run.
+pub fn device_prefix(run_id: &str) -> String {
+    format!("lg-{run_id}-")
+}
+
+/// ID of the `index`-th simulated device of a run.
+pub fn device_id(run_id: &str, index: usize) -> String {
+    format!("{}{index:04}", device_prefix(run_id))
+}
+
+/// `POST /api/v1/devices` body. Devices are tagged `loadgen=<run_id>`.
+pub fn provision_request(run_id: &str, fleet_id: &str, device_id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "device_id": device_id,
+        "fleet_id": fleet_id,
+        "hardware_type": "raspberry_pi_4",
+        "metadata": { "simulated": true },
+        "tags": { "loadgen": run_id },
+    })
+}
+
+/// `POST /api/v1/commands` body for the `seq`-th command.
+pub fn command_request(
+    run_id: &str,
+    fleet_id: &str,
+    device_id: &str,
+    seq: u64,
+) -> serde_json::Value {
+    serde_json::json!({
+        "device_id": device_id,
+        "fleet_id": fleet_id,
+        "command": COMMANDS[seq as usize % COMMANDS.len()],
+        "initiated_by": operator(run_id),
+    })
+}
+
+/// Heartbeat of a healthy simulated device.
+pub fn heartbeat(fleet_id: &str, device_id: &str, uptime_secs: u64) -> Heartbeat {
+    Heartbeat {
+        device_id: device_id.to_string(),
+        fleet_id: fleet_id.to_string(),
+        status: DeviceStatus::Online,
+        uptime_secs,
+        ollama_status: ServiceStatus::Running,
+        can_status: ServiceStatus::Running,
+        agent_version: concat!("loadgen-", env!("CARGO_PKG_VERSION")).to_string(),
+        machine_id: None,
+        cert_expires_at: None,
+        encodings: vec![],
+        version: zc_protocol::device::HEARTBEAT_VERSION,
+        vitals: None,
+        timestamp: Utc::now(),
+    }
+}
+
+/// OBD-II telemetry batch (RPM, speed, coolant) for the `seq`-th sample.
+pub fn telemetry_batch(device_id: &str, seq: u64) -> TelemetryBatch {
+    let now = Utc::now();
+    let phase = (seq % 60) as f64 / 60.0 * std::f64::consts::TAU;
+    let reading = |metric: &str, value: f64, unit: &str| TelemetryReading {
+        device_id: device_id.to_string(),
+        time: now,
+        metric_name: metric.to_string(),
+        value_numeric: Some(value),
+        value_text: None,
+        value_json: None,
+        unit: Some(unit.to_string()),
+        source: TelemetrySource::Obd2,
+    };
+    TelemetryBatch {
+        device_id: device_id.to_string(),
+        readings: vec![
+            reading("engine_rpm", 2200.0 + 900.0 * phase.sin(), "rpm"),
+            reading("vehicle_speed", 60.0 + 25.0 * phase.sin(), "km/h"),
+            reading("coolant_temp", 88.0 + 4.0 * phase.cos(), "celsius"),
+        ],
+        collected_at: now,
+    }
+}
+
+/// Successful response a simulated device sends for `envelope`.
+pub fn response_for(envelope: &CommandEnvelope, latency_ms: u64) -> CommandResponse {
+    let tool = envelope
+        .parsed_intent
+        .as_ref()
+        .map_or("unparsed", |i| i.tool_name.as_str());
+    CommandResponse {
+        command_id: envelope.id,
+        correlation_id: envelope.correlation_id,
+        device_id: envelope.device_id.clone(),
+        status: CommandStatus::Completed,
+        inference_tier: InferenceTier::Local,
+        response_text: Some(format!("simulated {tool} result")),
+        response_data: Some(serde_json::json!({ "simulated": true, "tool": tool })),
+        latency_ms,
+        responded_at: Utc::now(),
+        error: None,
+        error_code: None,
+        cached: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_and_requests_are_stable() {
+        assert_eq!(device_id("r1", 7), "lg-r1-0007");
+        let req = command_request("r1", "fleet-alpha", "lg-r1-0007", COMMANDS.len() as u64);
+        assert_eq!(req["command"], COMMANDS[0]);
+        assert_eq!(req["initiated_by"], "loadgen-r1");
+        let req = provision_request("r1", "fleet-alpha", "lg-r1-0007");
+        assert_eq!(req["tags"]["loadgen"], "r1");
+    }
+
+    #[test]
+    fn response_matches_envelope() {
+        let envelope = CommandEnvelope::new("fleet-alpha", "lg-r1-0001", "read DTCs", "loadgen-r1");
+        let response = response_for(&envelope, 200);
+        assert_eq!(response.command_id, envelope.id);
+        assert_eq!(response.correlation_id, envelope.correlation_id);
+        assert_eq!(response.status, CommandStatus::Completed);
+    }
+
+    #[test]
+    fn telemetry_batches_are_obd2() {
+        let batch = telemetry_batch("lg-r1-0001", 15);
+        assert_eq!(batch.readings.len(), 3);
+        assert!(
+            batch
+                .readings
+                .iter()
+                .all(|r| r.source == TelemetrySource::Obd2)
+        );
+    }
+}
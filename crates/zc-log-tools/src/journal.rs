@@ -0,0 +1,218 @@
+//! Journal source abstraction for `query_journal`.
+//!
+//! The systemd journal is not a file the other tools can read through
+//! [`LogSource`](crate::source::LogSource), so `query_journal` talks to a
+//! [`JournalSource`] instead. [`JournaldSource`] queries the live journal by
+//! running `journalctl --output=export` with the unit, priority, time and
+//! boot filters applied by journald itself; the export text is parsed by
+//! [`parsers::journald`](crate::parsers::journald). Devices therefore do not
+//! have to export their journal to files first.
+
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::error::{LogError, LogResult};
+
+/// Default limit on the output read from journalctl (64 KB).
+pub const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// journalctl subprocess timeout.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Priority names accepted by `journalctl --priority`, most severe first.
+pub const PRIORITIES: &[&str] = &[
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Filters for one journal query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalQuery {
+    /// Systemd unit, e.g. `nginx.service`.
+    pub unit: String,
+    /// Most recent entries to return.
+    pub lines: u64,
+    /// Least severe priority to include (`err` also returns crit, alert, emerg).
+    pub priority: Option<String>,
+    /// journalctl time expression (`1 hour ago`, `2024-01-15 08:00`).
+    pub since: Option<String>,
+    /// Boot offset (`0` current, `-1` previous) or 32-hex-digit boot ID.
+    pub boot: Option<String>,
+    /// Most journalctl output kept, in bytes.
+    pub max_bytes: usize,
+}
+
+impl JournalQuery {
+    pub fn new(unit: impl Into<String>) -> Self {
+        Self {
+            unit: unit.into(),
+            lines: 50,
+            priority: None,
+            since: None,
+            boot: None,
+            max_bytes: MAX_OUTPUT_BYTES,
+        }
+    }
+
+    /// Check every filter before it is handed to journalctl.
+    pub fn validate(&self) -> LogResult<()> {
+        if !is_valid_unit_name(&self.unit) {
+            return Err(LogError::Other(format!("invalid unit name: {}", self.unit)));
+        }
+        if let Some(p) = &self.priority
+            && !PRIORITIES.contains(&p.as_str())
+        {
+            return Err(LogError::Other(format!(
+                "invalid priority '{p}': expected one of {}",
+                PRIORITIES.join(", ")
+            )));
+        }
+        if let Some(s) = &self.since
+            && !s
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | ':' | '.' | '/'))
+        {
+            return Err(LogError::Other(format!("invalid since: {s}")));
+        }
+        if let Some(b) = &self.boot
+            && !is_valid_boot(b)
+        {
+            return Err(LogError::Other(format!(
+                "invalid boot '{b}': expected an offset like 0 or -1, or a boot ID"
+            )));
+        }
+        Ok(())
+    }
+
+    /// journalctl arguments for this query.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--output=export".to_string(),
+            "--no-pager".to_string(),
+            format!("--lines={}", self.lines),
+            format!("--unit={}", self.unit),
+        ];
+        if let Some(p) = &self.priority {
+            args.push(format!("--priority={p}"));
+        }
+        if let Some(s) = &self.since {
+            args.push(format!("--since={s}"));
+        }
+        if let Some(b) = &self.boot {
+            args.push(format!("--boot={b}"));
+        }
+        args
+    }
+}
+
+/// Validate a systemd unit name: only alphanumeric, `.`, `@`, `-`, `_`.
+pub fn is_valid_unit_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '@' | '-' | '_'))
+}
+
+/// A boot offset (`0`, `-1`, `1`) or a 128-bit boot ID in hex.
+fn is_valid_boot(boot: &str) -> bool {
+    boot.parse::<i32>().is_ok() || (boot.len() == 32 && boot.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Backend for `query_journal`: returns journal export-format lines.
+#[async_trait]
+pub trait JournalSource: Send + Sync {
+    /// Entries matching `query`, in journal export format.
+    async fn query(&self, query: &JournalQuery) -> LogResult<Vec<String>>;
+}
+
+/// The live systemd journal, read through `journalctl`.
+#[derive(Debug, Default)]
+pub struct JournaldSource;
+
+#[async_trait]
+impl JournalSource for JournaldSource {
+    async fn query(&self, query: &JournalQuery) -> LogResult<Vec<String>> {
+        query.validate()?;
+        let mut cmd = Command::new("journalctl");
+        cmd.args(query.args()).kill_on_drop(true);
+
+        let output = match tokio::time::timeout(TIMEOUT, cmd.output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => return Err(LogError::Io(format!("failed to run journalctl: {e}"))),
+            Err(_) => {
+                return Err(LogError::Other(format!(
+                    "journalctl timed out after {}s",
+                    TIMEOUT.as_secs()
+                )));
+            }
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(LogError::Other(format!(
+                "journalctl exited with {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+
+        let stdout = &output.stdout[..output.stdout.len().min(query.max_bytes)];
+        Ok(String::from_utf8_lossy(stdout)
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_args_carry_all_filters() {
+        let mut query = JournalQuery::new("nginx.service");
+        query.lines = 20;
+        query.priority = Some("err".into());
+        query.since = Some("1 hour ago".into());
+        query.boot = Some("-1".into());
+        query.validate().unwrap();
+        assert_eq!(
+            query.args(),
+            [
+                "--output=export",
+                "--no-pager",
+                "--lines=20",
+                "--unit=nginx.service",
+                "--priority=err",
+                "--since=1 hour ago",
+                "--boot=-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        let base = JournalQuery::new("nginx.service");
+        for query in [
+            JournalQuery::new("$(evil)"),
+            JournalQuery {
+                priority: Some("loud".into()),
+                ..base.clone()
+            },
+            JournalQuery {
+                since: Some("today; reboot".into()),
+                ..base.clone()
+            },
+            JournalQuery {
+                boot: Some("last".into()),
+                ..base.clone()
+            },
+        ] {
+            assert!(query.validate().is_err(), "{query:?} should be rejected");
+        }
+        let by_id = JournalQuery {
+            boot: Some("0123456789abcdef0123456789ABCDEF".into()),
+            ..base
+        };
+        assert!(by_id.validate().is_ok());
+    }
+}
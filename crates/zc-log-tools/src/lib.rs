@@ -1,19 +1,39 @@
 //! Log analysis tools for ZeroClaw.
 //!
 //! Provides multi-format log parsing (syslog RFC 3164/5424, systemd journald,
-//! newline-delimited JSON, plaintext), a `LogSource` abstraction for testability,
-//! and 5 analysis tools: search_logs, analyze_errors, log_stats, tail_logs,
-//! query_journal.
+//! newline-delimited JSON, plaintext, plus custom regex formats configured per
+//! device), `LogSource` / `JournalSource` abstractions for testability (the
+//! latter backed by the live journal via `journalctl`), glob and rotated-file
+//! path expansion (including `.gz`), `since`/`until` time-range filtering,
+//! size-limited cached search patterns with capture groups, Drain-style
+//! message template mining, 7 analysis tools (search_logs, analyze_errors,
+//! log_stats, tail_logs, query_journal, detect_anomalies, correlate_faults)
+//! and upload_logs, which sends a compressed log or journal export to a
+//! pre-signed URL.
+//! Syslog messages from other devices on the network can be received over
+//! UDP and read by the same tools as `remote://<host>`.
 
 pub mod error;
+pub mod journal;
 pub mod mock;
 pub mod parsers;
+pub mod paths;
+pub mod pattern;
+pub mod remote;
 pub mod source;
+pub mod templates;
+pub mod time_range;
 pub mod tools;
 pub mod types;
+pub mod upload;
 
 // Re-export key types for convenience
 pub use error::{LogError, LogResult};
-pub use mock::MockLogSource;
-pub use source::{FileLogSource, LogSource};
-pub use types::{LogEntry, LogFormat, LogSeverity, LogTool, ToolResult};
+pub use journal::{JournalQuery, JournalSource, JournaldSource};
+pub use mock::{MockJournalSource, MockLogSource, MockUploader};
+pub use parsers::{CustomFormatConfig, CustomFormats};
+pub use remote::{RemoteSyslogConfig, SenderNet, SyslogSpool, UdpSyslogListener, UdpSyslogSource};
+pub use source::{FileLogSource, LogChunk, LogSource};
+pub use time_range::TimeRange;
+pub use types::{ChunkSink, LogEntry, LogFormat, LogSeverity, LogTool, ToolResult};
+pub use upload::{HttpUploader, LogUploader};
@@ -1,10 +1,14 @@
-//! Mock log source for testing — serves pre-loaded log content.
+//! Mock log and journal sources for testing — serve pre-loaded content —
+//! and a mock uploader that keeps what it was sent.
 
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Mutex;
 
 use crate::error::{LogError, LogResult};
+use crate::journal::{JournalQuery, JournalSource};
 use crate::source::LogSource;
+use crate::upload::LogUploader;
 
 /// A mock log source that serves pre-loaded content by path.
 pub struct MockLogSource {
@@ -141,6 +145,82 @@ impl LogSource for MockLogSource {
     }
 }
 
+/// A mock journal that answers every query with the same export lines and
+/// records the queries it was given.
+#[derive(Default)]
+pub struct MockJournalSource {
+    lines: Vec<String>,
+    queries: Mutex<Vec<JournalQuery>>,
+}
+
+impl MockJournalSource {
+    pub fn new(lines: Vec<String>) -> Self {
+        Self {
+            lines,
+            queries: Mutex::default(),
+        }
+    }
+
+    /// A journal holding the entries of [`MockLogSource::with_journald_sample`].
+    pub fn with_sample() -> Self {
+        let source = MockLogSource::with_journald_sample();
+        Self::new(source.files["/var/log/journal.export"].clone())
+    }
+
+    /// Queries received so far, oldest first.
+    pub fn queries(&self) -> Vec<JournalQuery> {
+        self.queries.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl JournalSource for MockJournalSource {
+    async fn query(&self, query: &JournalQuery) -> LogResult<Vec<String>> {
+        query.validate()?;
+        self.queries.lock().unwrap().push(query.clone());
+        Ok(self.lines.clone())
+    }
+}
+
+/// A mock uploader that records every upload, or rejects them all.
+#[derive(Default)]
+pub struct MockUploader {
+    uploads: Mutex<Vec<(String, Vec<u8>)>>,
+    fail: bool,
+}
+
+impl MockUploader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An uploader whose every upload fails.
+    pub fn failing() -> Self {
+        Self {
+            fail: true,
+            ..Self::default()
+        }
+    }
+
+    /// `(url, body)` of each upload so far, oldest first.
+    pub fn uploads(&self) -> Vec<(String, Vec<u8>)> {
+        self.uploads.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LogUploader for MockUploader {
+    async fn put(&self, url: &str, body: Vec<u8>) -> LogResult<()> {
+        if self.fail {
+            return Err(LogError::Io(
+                "upload rejected with HTTP 403 Forbidden".into(),
+            ));
+        }
+        self.uploads.lock().unwrap().push((url.to_string(), body));
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
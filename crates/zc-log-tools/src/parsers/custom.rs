@@ -0,0 +1,343 @@
+//! Custom regex log formats.
+//!
+//! Fleets with bespoke application logs describe them as a named regex with
+//! capture groups. `message` is required; `timestamp`, `severity` and
+//! `source` are optional, and any other named group ends up in
+//! [`LogEntry::fields`]. Severity text is looked up in the format's
+//! `severity_map` first, then read as a syslog number (0–7), then matched
+//! against the usual keywords.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::{LogError, LogResult};
+use crate::parsers::{BUILTIN_FORMAT_NAMES, plaintext};
+use crate::types::{LogEntry, LogFormat, LogSeverity};
+
+/// Capture groups with a fixed meaning.
+const RESERVED_GROUPS: &[&str] = &["timestamp", "severity", "source", "message"];
+
+/// Field holding the custom format name on parsed entries.
+pub const FORMAT_FIELD: &str = "custom_format";
+
+/// A custom format as written in configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFormatConfig {
+    /// Name used as the tools' `format` argument.
+    pub name: String,
+    /// Regex matched against each line. Must have a `message` group.
+    pub pattern: String,
+    /// Auto-detection order: formats above 0 are tried before the built-in
+    /// ones, the rest only where the built-ins would fall back to plaintext.
+    /// Higher goes first.
+    #[serde(default)]
+    pub priority: i32,
+    /// chrono format string for the `timestamp` group. RFC 3339 and
+    /// `YYYY-MM-DD HH:MM:SS` are recognised without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_format: Option<String>,
+    /// Application severity labels (e.g. `"E" = "error"`), matched
+    /// case-insensitively.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub severity_map: HashMap<String, LogSeverity>,
+}
+
+/// A compiled custom format.
+#[derive(Debug, Clone)]
+pub struct CustomFormat {
+    name: String,
+    regex: Regex,
+    priority: i32,
+    timestamp_format: Option<String>,
+    severity_map: HashMap<String, LogSeverity>,
+}
+
+impl CustomFormat {
+    /// Compile and validate a format definition.
+    pub fn compile(config: &CustomFormatConfig) -> LogResult<Self> {
+        let name = config.name.trim();
+        if name.is_empty() {
+            return Err(LogError::Format(
+                "custom format name must not be empty".into(),
+            ));
+        }
+        if BUILTIN_FORMAT_NAMES.contains(&name) {
+            return Err(LogError::Format(format!(
+                "custom format '{name}' clashes with a built-in format"
+            )));
+        }
+        let regex = Regex::new(&config.pattern)
+            .map_err(|e| LogError::Regex(format!("custom format '{name}': {e}")))?;
+        if !regex.capture_names().flatten().any(|g| g == "message") {
+            return Err(LogError::Format(format!(
+                "custom format '{name}' needs a (?P<message>...) group"
+            )));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            regex,
+            priority: config.priority,
+            timestamp_format: config.timestamp_format.clone(),
+            severity_map: config
+                .severity_map
+                .iter()
+                .map(|(label, sev)| (label.to_lowercase(), *sev))
+                .collect(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        self.regex.is_match(line)
+    }
+
+    /// Parse one line. Returns `None` if the line does not match.
+    pub fn parse_line(&self, line: &str, line_number: usize) -> Option<LogEntry> {
+        let caps = self.regex.captures(line)?;
+        let group = |name: &str| caps.name(name).map(|m| m.as_str().trim());
+
+        let severity = group("severity")
+            .map(|s| self.severity(s))
+            .unwrap_or_else(|| plaintext::detect_severity(line));
+        let timestamp = group("timestamp").and_then(|ts| self.timestamp(ts));
+
+        let mut fields: HashMap<String, String> = self
+            .regex
+            .capture_names()
+            .flatten()
+            .filter(|g| !RESERVED_GROUPS.contains(g))
+            .filter_map(|g| Some((g.to_string(), caps.name(g)?.as_str().to_string())))
+            .collect();
+        fields.insert(FORMAT_FIELD.into(), self.name.clone());
+
+        Some(LogEntry {
+            timestamp,
+            severity,
+            source: group("source").filter(|s| !s.is_empty()).map(String::from),
+            message: group("message").unwrap_or_default().to_string(),
+            raw: line.to_string(),
+            line_number,
+            format: LogFormat::Custom,
+            fields,
+        })
+    }
+
+    fn severity(&self, label: &str) -> LogSeverity {
+        if let Some(sev) = self.severity_map.get(&label.to_lowercase()) {
+            return *sev;
+        }
+        if let Ok(n) = label.parse::<u8>() {
+            return LogSeverity::from_syslog_severity(n);
+        }
+        plaintext::detect_severity(label)
+    }
+
+    fn timestamp(&self, ts: &str) -> Option<DateTime<Utc>> {
+        if let Some(fmt) = &self.timestamp_format {
+            if let Ok(dt) = DateTime::parse_from_str(ts, fmt) {
+                return Some(dt.with_timezone(&Utc));
+            }
+            return NaiveDateTime::parse_from_str(ts, fmt)
+                .ok()
+                .map(|ndt| ndt.and_utc());
+        }
+        if let Ok(dt) = DateTime::parse_from_rfc3339(ts) {
+            return Some(dt.with_timezone(&Utc));
+        }
+        plaintext::detect_timestamp(ts)
+    }
+}
+
+/// The custom formats configured on a device, highest priority first.
+#[derive(Debug, Clone, Default)]
+pub struct CustomFormats {
+    formats: Vec<CustomFormat>,
+}
+
+impl CustomFormats {
+    /// Compile every definition. Names must be unique.
+    pub fn compile(configs: &[CustomFormatConfig]) -> LogResult<Self> {
+        let mut formats: Vec<CustomFormat> = Vec::with_capacity(configs.len());
+        for config in configs {
+            let format = CustomFormat::compile(config)?;
+            if formats.iter().any(|f| f.name == format.name) {
+                return Err(LogError::Format(format!(
+                    "custom format '{}' is defined twice",
+                    format.name
+                )));
+            }
+            formats.push(format);
+        }
+        // Stable: equal priorities keep their configured order.
+        formats.sort_by_key(|f| std::cmp::Reverse(f.priority));
+        Ok(Self { formats })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CustomFormat> {
+        self.formats.iter().find(|f| f.name == name)
+    }
+
+    /// Format names in detection order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.formats.iter().map(|f| f.name.as_str())
+    }
+
+    /// First format (by priority) among those accepted by `tier` that
+    /// matches most of `sample`.
+    pub(crate) fn detect(
+        &self,
+        sample: &[&str],
+        tier: impl Fn(i32) -> bool,
+    ) -> Option<&CustomFormat> {
+        if sample.is_empty() {
+            return None;
+        }
+        self.formats
+            .iter()
+            .filter(|f| tier(f.priority))
+            .find(|f| sample.iter().filter(|l| f.matches(l)).count() > sample.len() / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str, pattern: &str) -> CustomFormatConfig {
+        CustomFormatConfig {
+            name: name.into(),
+            pattern: pattern.into(),
+            priority: 0,
+            timestamp_format: None,
+            severity_map: HashMap::new(),
+        }
+    }
+
+    fn telematics() -> CustomFormat {
+        let mut config = config(
+            "telematics",
+            r"^\[(?P<timestamp>[^\]]+)\] <(?P<severity>\w)> (?P<source>\w+)\((?P<thread>\d+)\): (?P<message>.*)$",
+        );
+        config.timestamp_format = Some("%d/%m/%Y %H:%M:%S%.3f".into());
+        config.severity_map = HashMap::from([
+            ("E".into(), LogSeverity::Error),
+            ("W".into(), LogSeverity::Warning),
+            ("I".into(), LogSeverity::Info),
+        ]);
+        CustomFormat::compile(&config).unwrap()
+    }
+
+    #[test]
+    fn parse_with_all_groups() {
+        let entry = telematics()
+            .parse_line("[15/01/2024 12:00:01.250] <E> modem(42): link lost", 7)
+            .unwrap();
+        assert_eq!(entry.severity, LogSeverity::Error);
+        assert_eq!(entry.source.as_deref(), Some("modem"));
+        assert_eq!(entry.message, "link lost");
+        assert_eq!(entry.line_number, 7);
+        assert_eq!(entry.format, LogFormat::Custom);
+        assert_eq!(entry.fields["thread"], "42");
+        assert_eq!(entry.fields[FORMAT_FIELD], "telematics");
+        assert_eq!(
+            entry.timestamp.unwrap().to_rfc3339(),
+            "2024-01-15T12:00:01.250+00:00"
+        );
+    }
+
+    #[test]
+    fn non_matching_line_is_none() {
+        assert!(telematics().parse_line("plain text", 1).is_none());
+    }
+
+    #[test]
+    fn severity_falls_back_to_syslog_number_and_keywords() {
+        let format =
+            CustomFormat::compile(&config("lvl", r"^(?P<severity>\S+) (?P<message>.*)$")).unwrap();
+        let sev = |line| format.parse_line(line, 1).unwrap().severity;
+        assert_eq!(sev("3 disk failing"), LogSeverity::Error);
+        assert_eq!(sev("WARN low voltage"), LogSeverity::Warning);
+        assert_eq!(sev("xyz nothing special"), LogSeverity::Info);
+
+        // Without a severity group the whole line is scanned for keywords.
+        let format = CustomFormat::compile(&config("msg", r"^> (?P<message>.*)$")).unwrap();
+        assert_eq!(
+            format.parse_line("> FATAL watchdog", 1).unwrap().severity,
+            LogSeverity::Critical
+        );
+    }
+
+    #[test]
+    fn timestamps_without_format_string() {
+        let format = CustomFormat::compile(&config(
+            "ts",
+            r"^(?P<timestamp>\S+ \S+) \| (?P<message>.*)$",
+        ))
+        .unwrap();
+        let entry = format.parse_line("2024-01-15 12:00:00 | ok", 1).unwrap();
+        assert!(entry.timestamp.is_some());
+    }
+
+    #[test]
+    fn compile_rejects_bad_definitions() {
+        assert!(matches!(
+            CustomFormat::compile(&config("x", r"^(?P<msg>.*)$")),
+            Err(LogError::Format(_))
+        ));
+        assert!(matches!(
+            CustomFormat::compile(&config("x", r"^(?P<message>.*")),
+            Err(LogError::Regex(_))
+        ));
+        assert!(CustomFormat::compile(&config("syslog_3164", r"(?P<message>.*)")).is_err());
+        assert!(CustomFormat::compile(&config(" ", r"(?P<message>.*)")).is_err());
+
+        let dup = config("app", r"(?P<message>.*)");
+        assert!(CustomFormats::compile(&[dup.clone(), dup]).is_err());
+    }
+
+    #[test]
+    fn formats_are_ordered_by_priority() {
+        let mut high = config("high", r"^H (?P<message>.*)$");
+        high.priority = 10;
+        let formats = CustomFormats::compile(&[
+            config("first", r"^(?P<message>.*)$"),
+            high,
+            config("second", r"^(?P<message>.*)$"),
+        ])
+        .unwrap();
+        let names: Vec<_> = formats.names().collect();
+        assert_eq!(names, ["high", "first", "second"]);
+
+        let sample = ["H one", "H two", "three"];
+        assert_eq!(formats.detect(&sample, |_| true).unwrap().name(), "high");
+        assert_eq!(formats.detect(&sample, |p| p <= 0).unwrap().name(), "first");
+        assert!(formats.detect(&[], |_| true).is_none());
+    }
+
+    #[test]
+    fn config_from_toml_shape() {
+        let config: CustomFormatConfig = serde_json::from_value(serde_json::json!({
+            "name": "app",
+            "pattern": "(?P<message>.*)",
+            "severity_map": { "E": "error" }
+        }))
+        .unwrap();
+        assert_eq!(config.priority, 0);
+        assert_eq!(config.severity_map["E"], LogSeverity::Error);
+    }
+}
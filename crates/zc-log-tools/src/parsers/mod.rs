@@ -1,15 +1,102 @@
 //! Multi-format log parsers with auto-detection.
 //!
 //! Supports syslog (RFC 3164/5424), systemd journald export, newline-delimited
-//! JSON, and plaintext with heuristic severity detection.
+//! JSON, and plaintext with heuristic severity detection, plus per-device
+//! [`custom`] regex formats.
 
+pub mod custom;
 pub mod journald;
 pub mod json_lines;
 pub mod plaintext;
 pub mod syslog;
 
+use crate::error::{LogError, LogResult};
 use crate::types::{LogEntry, LogFormat};
 
+pub use custom::{CustomFormat, CustomFormatConfig, CustomFormats};
+
+/// Values of a tool's `format` argument that select a built-in parser.
+pub const BUILTIN_FORMAT_NAMES: &[&str] = &[
+    "syslog_3164",
+    "syslog_5424",
+    "journald",
+    "json_lines",
+    "plaintext",
+];
+
+/// A built-in or custom format, as chosen for one tool run.
+#[derive(Debug, Clone, Copy)]
+pub enum Format<'a> {
+    Builtin(LogFormat),
+    Custom(&'a CustomFormat),
+}
+
+impl Format<'_> {
+    /// Name reported in tool output.
+    pub fn name(&self) -> String {
+        match self {
+            Self::Builtin(format) => format!("{format:?}"),
+            Self::Custom(format) => format.name().to_string(),
+        }
+    }
+
+    /// Parse all lines in this format. Custom formats skip lines that do
+    /// not match.
+    pub fn parse(&self, lines: &[String]) -> Vec<LogEntry> {
+        match self {
+            Self::Builtin(format) => parse_lines(lines, *format),
+            Self::Custom(format) => lines
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| !l.trim().is_empty())
+                .filter_map(|(i, line)| format.parse_line(line, i + 1))
+                .collect(),
+        }
+    }
+}
+
+/// Parse a built-in format name (see [`BUILTIN_FORMAT_NAMES`]).
+pub fn parse_format_name(name: &str) -> LogResult<LogFormat> {
+    match name {
+        "syslog_3164" => Ok(LogFormat::Syslog3164),
+        "syslog_5424" => Ok(LogFormat::Syslog5424),
+        "journald" => Ok(LogFormat::Journald),
+        "json_lines" => Ok(LogFormat::JsonLines),
+        "plaintext" => Ok(LogFormat::Plaintext),
+        other => Err(LogError::Format(format!("unknown format: {other}"))),
+    }
+}
+
+/// Resolve a tool's `format` argument: a built-in name, a custom format
+/// name, or auto-detection when omitted.
+pub fn resolve_format<'a>(
+    arg: Option<&str>,
+    lines: &[String],
+    custom: &'a CustomFormats,
+) -> LogResult<Format<'a>> {
+    match arg {
+        None => Ok(detect_format_with(lines, custom)),
+        Some(name) => match custom.get(name) {
+            Some(format) => Ok(Format::Custom(format)),
+            None => parse_format_name(name).map(Format::Builtin),
+        },
+    }
+}
+
+/// JSON Schema for the `format` argument, listing custom formats too.
+pub fn format_schema(custom: &CustomFormats) -> serde_json::Value {
+    let names: Vec<&str> = BUILTIN_FORMAT_NAMES
+        .iter()
+        .copied()
+        .chain(custom.names())
+        .collect();
+    serde_json::json!({
+        "type": "string",
+        "enum": names,
+        "description": "Log format (auto-detected if omitted)"
+    })
+}
+
 /// Parse a single line using the specified format.
 ///
 /// Returns `None` if the line cannot be parsed in the given format.
@@ -22,6 +109,8 @@ pub fn parse_line(line: &str, line_number: usize, format: LogFormat) -> Option<L
         LogFormat::Plaintext => Some(plaintext::parse_line(line, line_number)),
         // Journald uses multi-line parsing — single-line parse not applicable
         LogFormat::Journald => None,
+        // Custom formats need their definition — see `Format::parse`
+        LogFormat::Custom => None,
     }
 }
 
@@ -52,13 +141,7 @@ pub fn detect_format(lines: &[String]) -> LogFormat {
     }
 
     // Sample first non-empty lines for single-line format detection
-    let sample: Vec<&str> = lines
-        .iter()
-        .map(|s| s.as_str())
-        .filter(|s| !s.trim().is_empty())
-        .take(5)
-        .collect();
-
+    let sample = sample(lines);
     if sample.is_empty() {
         return LogFormat::Plaintext;
     }
@@ -95,6 +178,32 @@ pub fn detect_format(lines: &[String]) -> LogFormat {
     LogFormat::Plaintext
 }
 
+/// Auto-detect including custom formats: those with priority above 0 are
+/// tried before the built-in formats, the others only where detection would
+/// otherwise settle on plaintext.
+pub fn detect_format_with<'a>(lines: &[String], custom: &'a CustomFormats) -> Format<'a> {
+    let sample = sample(lines);
+    if let Some(format) = custom.detect(&sample, |p| p > 0) {
+        return Format::Custom(format);
+    }
+    match detect_format(lines) {
+        LogFormat::Plaintext => custom
+            .detect(&sample, |p| p <= 0)
+            .map_or(Format::Builtin(LogFormat::Plaintext), Format::Custom),
+        builtin => Format::Builtin(builtin),
+    }
+}
+
+/// First non-empty lines, used for single-line format detection.
+fn sample(lines: &[String]) -> Vec<&str> {
+    lines
+        .iter()
+        .map(|s| s.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .take(5)
+        .collect()
+}
+
 /// Parse lines with auto-format detection.
 pub fn auto_parse(lines: &[String]) -> Vec<LogEntry> {
     let format = detect_format(lines);
@@ -199,4 +308,74 @@ mod tests {
         let entries = parse_lines(&lines, LogFormat::JsonLines);
         assert_eq!(entries.len(), 2);
     }
+
+    fn custom_formats(priority: i32) -> CustomFormats {
+        CustomFormats::compile(&[CustomFormatConfig {
+            name: "gateway".into(),
+            pattern: r"^(?:<\d+>)?GW\|(?P<severity>\w+)\|(?P<message>.*)$".into(),
+            priority,
+            timestamp_format: None,
+            severity_map: Default::default(),
+        }])
+        .unwrap()
+    }
+
+    #[test]
+    fn custom_format_detected_after_builtins() {
+        let custom = custom_formats(0);
+        let lines: Vec<String> = vec!["GW|ERROR|can timeout".into(), "GW|INFO|ok".into()];
+        let format = detect_format_with(&lines, &custom);
+        assert_eq!(format.name(), "gateway");
+        let entries = format.parse(&lines);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].severity, crate::types::LogSeverity::Error);
+        assert_eq!(entries[0].message, "can timeout");
+
+        // Low priority never overrides a built-in match.
+        let syslog = vec!["<131>GW|ERROR|x".to_string(), "<134>GW|INFO|y".to_string()];
+        assert_eq!(
+            detect_format_with(&syslog, &custom).name(),
+            format!("{:?}", detect_format(&syslog))
+        );
+    }
+
+    #[test]
+    fn high_priority_custom_format_wins_over_builtins() {
+        let lines = vec!["<131>GW|ERROR|x".to_string(), "<134>GW|INFO|y".to_string()];
+        assert!(syslog::looks_like_syslog(&lines[0]));
+        assert_eq!(
+            detect_format_with(&lines, &custom_formats(5)).name(),
+            "gateway"
+        );
+    }
+
+    #[test]
+    fn resolve_by_name() {
+        let custom = custom_formats(0);
+        let lines = vec![r#"{"level":"info","message":"a"}"#.to_string()];
+        assert_eq!(
+            resolve_format(Some("gateway"), &lines, &custom)
+                .unwrap()
+                .name(),
+            "gateway"
+        );
+        assert_eq!(
+            resolve_format(Some("plaintext"), &lines, &custom)
+                .unwrap()
+                .name(),
+            "Plaintext"
+        );
+        assert_eq!(
+            resolve_format(None, &lines, &custom).unwrap().name(),
+            "JsonLines"
+        );
+        assert!(matches!(
+            resolve_format(Some("nope"), &lines, &custom),
+            Err(LogError::Format(_))
+        ));
+        assert_eq!(
+            format_schema(&custom)["enum"].as_array().unwrap().len(),
+            BUILTIN_FORMAT_NAMES.len() + 1
+        );
+    }
 }
@@ -71,7 +71,7 @@ pub fn detect_severity(line: &str) -> LogSeverity {
 }
 
 /// Try to extract a timestamp from a plaintext log line.
-fn detect_timestamp(line: &str) -> Option<DateTime<Utc>> {
+pub(crate) fn detect_timestamp(line: &str) -> Option<DateTime<Utc>> {
     // Try ISO 8601 / RFC 3339
     if let Some(caps) = RE_ISO_TS.captures(line) {
         if let Ok(dt) = DateTime::parse_from_rfc3339(&caps[1]) {
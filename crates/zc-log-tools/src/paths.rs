@@ -0,0 +1,189 @@
+//! Log path expansion — glob patterns, path lists and rotated siblings.
+//!
+//! Wildcards (`*`, `?`) are supported in the file name only and never match
+//! `/`. Rotated siblings of `/var/log/syslog` are `syslog.1`, `syslog.2.gz`
+//! and so on; they are listed newest first, right after the live file.
+
+use crate::error::{LogError, LogResult};
+use crate::source::LogSource;
+
+/// Most files a single tool call reads.
+pub const MAX_FILES: usize = 32;
+
+/// Whether `path` contains glob wildcards.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Match `text` against a glob `pattern`. `*` and `?` do not match `/`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    // Position of the last `*` and the text index it currently extends to.
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == t[ti] || (p[pi] == '?' && t[ti] != '/')) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star
+            && t[st] != '/'
+        {
+            star = Some((sp, st + 1));
+            pi = sp + 1;
+            ti = st + 1;
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Rotation number of `path` as a rotated sibling of `base`
+/// (`base.1` → 1, `base.2.gz` → 2).
+pub fn rotation_index(base: &str, path: &str) -> Option<u32> {
+    let suffix = path.strip_prefix(base)?.strip_prefix('.')?;
+    let number = suffix.strip_suffix(".gz").unwrap_or(suffix);
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Whether `path` itself is a rotated file (`syslog.1`, `syslog.2.gz`).
+fn is_rotated(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    name.rsplit_once('.').is_some_and(|(base, number)| {
+        !base.is_empty() && !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+/// Resolve path arguments into the files to read, in order.
+///
+/// Globs expand to their sorted matches and must match something; plain
+/// paths are kept as given (a missing file fails when it is read). With
+/// `include_rotated`, each live file is followed by its rotated siblings.
+/// Duplicates are dropped.
+pub async fn resolve(
+    source: &dyn LogSource,
+    specs: &[String],
+    include_rotated: bool,
+) -> LogResult<Vec<String>> {
+    let mut files: Vec<String> = Vec::new();
+    for spec in specs {
+        let matched = if is_glob(spec) {
+            let found = source.expand(spec).await?;
+            if found.is_empty() {
+                return Err(LogError::NotFound(spec.clone()));
+            }
+            found
+        } else {
+            vec![spec.clone()]
+        };
+
+        for path in matched {
+            let mut group = vec![path.clone()];
+            if include_rotated && !is_glob(&path) && !is_rotated(&path) {
+                let mut rotated: Vec<(u32, String)> = source
+                    .expand(&format!("{path}.*"))
+                    .await?
+                    .into_iter()
+                    .filter_map(|p| rotation_index(&path, &p).map(|n| (n, p)))
+                    .collect();
+                rotated.sort();
+                group.extend(rotated.into_iter().map(|(_, p)| p));
+            }
+            for file in group {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+    }
+
+    if files.len() > MAX_FILES {
+        return Err(LogError::Other(format!(
+            "{} log files match, at most {MAX_FILES} can be read at once",
+            files.len()
+        )));
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockLogSource;
+
+    #[test]
+    fn glob_matches_file_names_only() {
+        assert!(glob_match("/var/log/*.log", "/var/log/app.log"));
+        assert!(glob_match("/var/log/syslog*", "/var/log/syslog"));
+        assert!(glob_match("/var/log/syslog.?", "/var/log/syslog.1"));
+        assert!(!glob_match("/var/log/*.log", "/var/log/app.log.1"));
+        assert!(!glob_match("/var/log/*.log", "/var/log/nginx/error.log"));
+        assert!(!glob_match("/var/log/*", "/var/lib/x"));
+    }
+
+    #[test]
+    fn rotation_suffixes() {
+        assert_eq!(
+            rotation_index("/var/log/syslog", "/var/log/syslog.1"),
+            Some(1)
+        );
+        assert_eq!(
+            rotation_index("/var/log/syslog", "/var/log/syslog.12.gz"),
+            Some(12)
+        );
+        assert_eq!(
+            rotation_index("/var/log/syslog", "/var/log/syslog.old"),
+            None
+        );
+        assert!(is_rotated("/var/log/app.log.3.gz"));
+        assert!(!is_rotated("/var/log/app.log"));
+    }
+
+    #[tokio::test]
+    async fn resolves_globs_lists_and_rotations() {
+        let mut source = MockLogSource::new();
+        for path in [
+            "/var/log/syslog",
+            "/var/log/syslog.1",
+            "/var/log/syslog.10.gz",
+            "/var/log/syslog.2.gz",
+            "/var/log/syslog.bak",
+            "/var/log/app.log",
+            "/var/log/kern.log",
+        ] {
+            source.add_file(path, vec![]);
+        }
+
+        let specs = vec!["/var/log/syslog".to_string(), "/var/log/*.log".to_string()];
+        let files = resolve(&source, &specs, true).await.unwrap();
+        assert_eq!(
+            files,
+            [
+                "/var/log/syslog",
+                "/var/log/syslog.1",
+                "/var/log/syslog.2.gz",
+                "/var/log/syslog.10.gz",
+                "/var/log/app.log",
+                "/var/log/kern.log",
+            ]
+        );
+
+        let files = resolve(&source, &specs[..1], false).await.unwrap();
+        assert_eq!(files, ["/var/log/syslog"]);
+
+        let missing = vec!["/var/log/*.json".to_string()];
+        assert!(matches!(
+            resolve(&source, &missing, false).await,
+            Err(LogError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+//! Compiled search patterns for `search_logs`.
+//!
+//! Queries are compiled with size limits so a pathological pattern (huge
+//! counted repetitions, giant alternations) fails fast instead of eating the
+//! device's memory. The `regex` engine matches in linear time, so once a
+//! pattern compiles there is no catastrophic backtracking; the scan itself
+//! is bounded by the tool's time budget. Compiled patterns are cached per
+//! tool, since operators tend to repeat the same searches.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+
+use crate::error::{LogError, LogResult};
+
+/// Longest accepted query, in bytes.
+pub const MAX_PATTERN_LEN: usize = 1024;
+/// Compiled program size limit (the `regex` default is 10 MiB).
+const SIZE_LIMIT: usize = 1 << 20;
+/// Lazy DFA cache limit per search.
+const DFA_SIZE_LIMIT: usize = 2 << 20;
+/// Compiled patterns kept per tool; the cache starts over when full.
+const CACHE_CAPACITY: usize = 64;
+
+/// Compile `query` as a regex, or as a literal substring when `regex` is false.
+pub fn compile(query: &str, regex: bool) -> LogResult<Regex> {
+    if query.len() > MAX_PATTERN_LEN {
+        return Err(LogError::Regex(format!(
+            "pattern is {} bytes, limit is {MAX_PATTERN_LEN}",
+            query.len()
+        )));
+    }
+    let pattern = if regex {
+        Cow::Borrowed(query)
+    } else {
+        Cow::Owned(regex::escape(query))
+    };
+    RegexBuilder::new(&pattern)
+        .size_limit(SIZE_LIMIT)
+        .dfa_size_limit(DFA_SIZE_LIMIT)
+        .build()
+        .map_err(|e| LogError::Regex(e.to_string()))
+}
+
+/// Compiled patterns keyed by query and mode.
+#[derive(Debug, Default)]
+pub struct PatternCache {
+    compiled: Mutex<HashMap<(String, bool), Regex>>,
+}
+
+impl PatternCache {
+    /// The compiled pattern for `query`, compiling it on first use.
+    pub fn get(&self, query: &str, regex: bool) -> LogResult<Regex> {
+        let key = (query.to_string(), regex);
+        if let Some(re) = self.lock().get(&key) {
+            return Ok(re.clone());
+        }
+        let re = compile(query, regex)?;
+        let mut compiled = self.lock();
+        if compiled.len() >= CACHE_CAPACITY {
+            compiled.clear();
+        }
+        compiled.insert(key, re.clone());
+        Ok(re)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(String, bool), Regex>> {
+        self.compiled.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Capture groups of the first match in `text`: named groups by name, the
+/// rest by index. `None` when the pattern has no groups or does not match;
+/// groups that did not take part in the match are `null`.
+pub fn captures(re: &Regex, text: &str) -> Option<Map<String, Value>> {
+    if re.captures_len() <= 1 {
+        return None;
+    }
+    let caps = re.captures(text)?;
+    let groups = re
+        .capture_names()
+        .enumerate()
+        .skip(1)
+        .map(|(i, name)| {
+            let key = name.map_or_else(|| i.to_string(), String::from);
+            let value = caps
+                .get(i)
+                .map_or(Value::Null, |m| Value::String(m.as_str().to_string()));
+            (key, value)
+        })
+        .collect();
+    Some(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn literal_mode_escapes_metacharacters() {
+        let re = compile("disk usage at 95%.", false).unwrap();
+        assert!(re.is_match("Critical: disk usage at 95%."));
+        assert!(!re.is_match("Critical: disk usage at 95%!"));
+        assert!(compile("[invalid", false).is_ok());
+        assert!(compile("[invalid", true).is_err());
+    }
+
+    #[test]
+    fn oversized_patterns_are_rejected() {
+        assert!(compile(&"a".repeat(MAX_PATTERN_LEN + 1), true).is_err());
+        let err = compile(r"(?:\w{100}){100}", true).unwrap_err();
+        assert!(matches!(err, LogError::Regex(_)));
+    }
+
+    #[test]
+    fn cache_reuses_compiled_patterns() {
+        let cache = PatternCache::default();
+        cache.get("timeout", true).unwrap();
+        cache.get("timeout", true).unwrap();
+        assert_eq!(cache.len(), 1);
+        cache.get("timeout", false).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("(", true).is_err());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn captures_by_name_and_index() {
+        let re = compile(r"(?P<bus>CAN \w+): (\w+)(?: after (\d+)ms)?", true).unwrap();
+        let groups = captures(&re, "Error reading CAN bus: timeout").unwrap();
+        assert_eq!(
+            Value::Object(groups),
+            json!({"bus": "CAN bus", "2": "timeout", "3": null})
+        );
+        assert!(captures(&re, "link up").is_none());
+        assert!(captures(&compile("CAN", true).unwrap(), "CAN bus").is_none());
+    }
+}
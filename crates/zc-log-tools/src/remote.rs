@@ -0,0 +1,596 @@
+//! Remote syslog ingestion — messages sent by other devices on the local
+//! network (dashcams, telematics units) over UDP.
+//!
+//! [`UdpSyslogListener`] receives RFC 3164/5424 datagrams (RFC 5426, one
+//! message per datagram) and appends each message to a per-sender file in a
+//! [`SyslogSpool`] directory, so they survive restarts and outages.
+//! [`UdpSyslogSource`] serves the spool next to another [`LogSource`]: the
+//! virtual path `remote://<host>` reads what `<host>` (the sender's IP
+//! address) sent, so every log tool works on it unchanged, including
+//! globs such as `remote://*`.
+//!
+//! Anyone who can reach the port can send, with any source address, so the
+//! listener drops senders outside `allowed_senders` and the spool refuses
+//! new senders once it holds `max_hosts` of them.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UdpSocket;
+
+use crate::error::{LogError, LogResult};
+use crate::source::{LogChunk, LogSource};
+
+/// Prefix of the virtual paths of remote senders.
+pub const REMOTE_PREFIX: &str = "remote://";
+
+/// Largest UDP payload accepted; longer datagrams are truncated.
+const MAX_DATAGRAM: usize = 65_535;
+
+/// Remote syslog settings (`[remote_syslog]` in agent.toml).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteSyslogConfig {
+    /// Listen for remote syslog messages. Off by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// UDP address to listen on.
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    /// Directory holding one file per sender.
+    #[serde(default = "default_spool_dir")]
+    pub spool_dir: String,
+    /// Size a sender's file may reach before its oldest half is dropped.
+    #[serde(default = "default_max_bytes_per_host")]
+    pub max_bytes_per_host: u64,
+    /// Addresses or CIDR networks messages are accepted from; empty
+    /// accepts any sender.
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+    /// Most senders spooled; messages from further ones are dropped.
+    #[serde(default = "default_max_hosts")]
+    pub max_hosts: usize,
+}
+
+fn default_bind() -> String {
+    "0.0.0.0:5514".into()
+}
+
+fn default_spool_dir() -> String {
+    "/var/lib/zeroclaw/remote-logs".into()
+}
+
+fn default_max_bytes_per_host() -> u64 {
+    4 * 1024 * 1024
+}
+
+fn default_max_hosts() -> usize {
+    32
+}
+
+impl Default for RemoteSyslogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_bind(),
+            spool_dir: default_spool_dir(),
+            max_bytes_per_host: default_max_bytes_per_host(),
+            allowed_senders: Vec::new(),
+            max_hosts: default_max_hosts(),
+        }
+    }
+}
+
+impl RemoteSyslogConfig {
+    /// Parsed `allowed_senders`.
+    pub fn sender_nets(&self) -> LogResult<Vec<SenderNet>> {
+        self.allowed_senders
+            .iter()
+            .map(|s| SenderNet::parse(s))
+            .collect()
+    }
+}
+
+/// A sender address, or a network of them (`192.168.1.0/24`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SenderNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl SenderNet {
+    pub fn parse(text: &str) -> LogResult<Self> {
+        let invalid = || LogError::Other(format!("'{text}' is not an address or network"));
+        let (addr, prefix) = match text.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text.trim(), None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(invalid)?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    /// Whether `ip` is this address or in this network.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The virtual path of `host`'s messages.
+pub fn remote_path(host: &str) -> String {
+    format!("{REMOTE_PREFIX}{host}")
+}
+
+/// Whether `host` is usable as a spool file name: letters, digits and
+/// `. - : _`, not starting with a dot.
+fn valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && !host.starts_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '_'))
+}
+
+/// One message as a spool line: control characters are dropped, line
+/// breaks inside the message become spaces. `None` for empty messages.
+fn message_line(datagram: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(datagram);
+    let line: String = text
+        .trim_end_matches(['\n', '\r', '\0'])
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    (!line.trim().is_empty()).then_some(line)
+}
+
+/// Directory of received messages, one `<host>.log` file per sender.
+#[derive(Debug)]
+pub struct SyslogSpool {
+    dir: PathBuf,
+    max_bytes_per_host: u64,
+    max_hosts: usize,
+}
+
+impl SyslogSpool {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes_per_host: u64, max_hosts: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes_per_host,
+            max_hosts,
+        }
+    }
+
+    /// Spool at `config.spool_dir`.
+    pub fn from_config(config: &RemoteSyslogConfig) -> Self {
+        Self::new(
+            &config.spool_dir,
+            config.max_bytes_per_host,
+            config.max_hosts,
+        )
+    }
+
+    fn file(&self, host: &str) -> LogResult<PathBuf> {
+        if !valid_host(host) {
+            return Err(LogError::Other(format!("invalid remote host: {host}")));
+        }
+        Ok(self.dir.join(format!("{host}.log")))
+    }
+
+    /// Append one message received from `host`. Once the file grows past
+    /// the limit, only its newest half is kept. Returns `false`, storing
+    /// nothing, for a new host when the spool already holds `max_hosts`.
+    pub async fn append(&self, host: &str, line: &str) -> LogResult<bool> {
+        let path = self.file(host)?;
+        let io_error = |e: std::io::Error| LogError::Io(format!("{}: {e}", path.display()));
+        if !tokio::fs::try_exists(&path).await.map_err(io_error)?
+            && self.hosts().await?.len() >= self.max_hosts
+        {
+            return Ok(false);
+        }
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| LogError::Io(format!("{}: {e}", self.dir.display())))?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(io_error)?;
+        file.write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(io_error)?;
+        let size = file.metadata().await.map_err(io_error)?.len();
+        drop(file);
+
+        if size > self.max_bytes_per_host {
+            self.trim(&path).await.map_err(io_error)?;
+        }
+        Ok(true)
+    }
+
+    /// Keep the newest lines of `path` that fit in half the limit.
+    async fn trim(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let budget = (self.max_bytes_per_host / 2) as usize;
+        let mut kept = 0;
+        let mut start = content.len();
+        for line in content.lines().rev() {
+            if kept + line.len() + 1 > budget {
+                break;
+            }
+            kept += line.len() + 1;
+            start -= line.len() + 1;
+        }
+        let tmp = path.with_extension("log.tmp");
+        tokio::fs::write(&tmp, &content[start..]).await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+
+    /// Messages received from `host`, oldest first.
+    pub async fn read(&self, host: &str) -> LogResult<Vec<String>> {
+        let path = self.file(host)?;
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => Ok(content.lines().map(String::from).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(LogError::NotFound(remote_path(host)))
+            }
+            Err(e) => Err(LogError::Io(format!("{}: {e}", path.display()))),
+        }
+    }
+
+    /// Hosts that have sent messages, sorted.
+    pub async fn hosts(&self) -> LogResult<Vec<String>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(LogError::Io(format!("{}: {e}", self.dir.display()))),
+        };
+        let mut hosts = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| LogError::Io(format!("{}: {e}", self.dir.display())))?
+        {
+            let name = entry.file_name();
+            if let Some(host) = name.to_str().and_then(|n| n.strip_suffix(".log"))
+                && valid_host(host)
+            {
+                hosts.push(host.to_string());
+            }
+        }
+        hosts.sort();
+        Ok(hosts)
+    }
+}
+
+/// Receives syslog datagrams into a [`SyslogSpool`].
+pub struct UdpSyslogListener {
+    socket: UdpSocket,
+    spool: Arc<SyslogSpool>,
+    allowed: Vec<SenderNet>,
+}
+
+impl UdpSyslogListener {
+    /// Listen on `addr` (e.g. `0.0.0.0:5514`).
+    pub async fn bind(addr: &str, spool: Arc<SyslogSpool>) -> LogResult<Self> {
+        let socket = UdpSocket::bind(addr)
+            .await
+            .map_err(|e| LogError::Io(format!("{addr}: {e}")))?;
+        Ok(Self {
+            socket,
+            spool,
+            allowed: Vec::new(),
+        })
+    }
+
+    /// Accept messages only from `senders` (all senders when empty).
+    pub fn with_allowed_senders(mut self, senders: Vec<SenderNet>) -> Self {
+        self.allowed = senders;
+        self
+    }
+
+    /// The address actually bound (resolves port 0).
+    pub fn local_addr(&self) -> LogResult<SocketAddr> {
+        self.socket
+            .local_addr()
+            .map_err(|e| LogError::Io(e.to_string()))
+    }
+
+    /// Receive messages until the task is dropped. Failures are logged and
+    /// the message is lost.
+    pub async fn run(&self) {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    tracing::warn!(error = %e, "remote syslog receive failed");
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            if !self.allowed.is_empty() && !self.allowed.iter().any(|n| n.contains(from.ip())) {
+                tracing::debug!(sender = %from.ip(), "remote syslog sender not allowed");
+                continue;
+            }
+            let Some(line) = message_line(&buf[..len]) else {
+                continue;
+            };
+            let host = from.ip().to_canonical().to_string();
+            match self.spool.append(&host, &line).await {
+                Ok(true) => {}
+                Ok(false) => tracing::debug!(host, "remote syslog spool full, sender dropped"),
+                Err(e) => tracing::warn!(host, error = %e, "failed to spool remote syslog message"),
+            }
+        }
+    }
+}
+
+/// A [`LogSource`] that serves `remote://<host>` from a [`SyslogSpool`] and
+/// every other path from `local`.
+pub struct UdpSyslogSource<L> {
+    local: L,
+    spool: Arc<SyslogSpool>,
+}
+
+impl<L: LogSource> UdpSyslogSource<L> {
+    pub fn new(local: L, spool: Arc<SyslogSpool>) -> Self {
+        Self { local, spool }
+    }
+
+    async fn remote_sources(&self) -> LogResult<Vec<String>> {
+        Ok(self
+            .spool
+            .hosts()
+            .await?
+            .iter()
+            .map(|h| remote_path(h))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl<L: LogSource> LogSource for UdpSyslogSource<L> {
+    async fn read_lines(&self, path: &str) -> LogResult<Vec<String>> {
+        match path.strip_prefix(REMOTE_PREFIX) {
+            Some(host) => self.spool.read(host).await,
+            None => self.local.read_lines(path).await,
+        }
+    }
+
+    async fn tail_lines(&self, path: &str, count: usize) -> LogResult<Vec<String>> {
+        match path.strip_prefix(REMOTE_PREFIX) {
+            Some(host) => {
+                let all = self.spool.read(host).await?;
+                let start = all.len().saturating_sub(count);
+                Ok(all[start..].to_vec())
+            }
+            None => self.local.tail_lines(path, count).await,
+        }
+    }
+
+    async fn exists(&self, path: &str) -> bool {
+        match path.strip_prefix(REMOTE_PREFIX) {
+            Some(host) => self.spool.read(host).await.is_ok(),
+            None => self.local.exists(path).await,
+        }
+    }
+
+    async fn read_from(&self, path: &str, offset: u64) -> LogResult<LogChunk> {
+        match path.strip_prefix(REMOTE_PREFIX) {
+            Some(host) => Ok(LogChunk::from_lines(self.spool.read(host).await?, offset)),
+            None => self.local.read_from(path, offset).await,
+        }
+    }
+
+    async fn list_sources(&self) -> LogResult<Vec<String>> {
+        let mut sources = self.local.list_sources().await?;
+        sources.extend(self.remote_sources().await?);
+        Ok(sources)
+    }
+
+    async fn expand(&self, pattern: &str) -> LogResult<Vec<String>> {
+        if !pattern.starts_with(REMOTE_PREFIX) {
+            return self.local.expand(pattern).await;
+        }
+        Ok(self
+            .remote_sources()
+            .await?
+            .into_iter()
+            .filter(|p| crate::paths::glob_match(pattern, p))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockLogSource;
+    use crate::tools::SearchLogs;
+    use crate::types::LogTool;
+
+    fn temp_spool(name: &str, max_bytes: u64) -> (PathBuf, Arc<SyslogSpool>) {
+        let dir = std::env::temp_dir().join(format!("zc-remote-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        (dir.clone(), Arc::new(SyslogSpool::new(dir, max_bytes, 4)))
+    }
+
+    #[test]
+    fn messages_become_single_lines() {
+        assert_eq!(
+            message_line(b"<134>Jan 15 12:00:01 cam1 rec: started\n").as_deref(),
+            Some("<134>Jan 15 12:00:01 cam1 rec: started")
+        );
+        assert_eq!(
+            message_line(b"<131>1 2025-01-15T12:00:00Z tcu app - - - a\nb\0").as_deref(),
+            Some("<131>1 2025-01-15T12:00:00Z tcu app - - - a b")
+        );
+        assert_eq!(message_line(b" \r\n"), None);
+        assert!(valid_host("192.168.1.20"));
+        assert!(valid_host("fe80::1"));
+        assert!(!valid_host("../etc/passwd"));
+        assert!(!valid_host("a/b"));
+    }
+
+    #[test]
+    fn sender_nets_match_addresses_and_networks() {
+        let lan = SenderNet::parse("192.168.1.0/24").unwrap();
+        assert!(lan.contains("192.168.1.20".parse().unwrap()));
+        assert!(lan.contains("::ffff:192.168.1.20".parse().unwrap()));
+        assert!(!lan.contains("192.168.2.20".parse().unwrap()));
+        assert!(!lan.contains("fe80::1".parse().unwrap()));
+        let cam = SenderNet::parse("fe80::1").unwrap();
+        assert!(cam.contains("fe80::1".parse().unwrap()));
+        assert!(!cam.contains("fe80::2".parse().unwrap()));
+        assert!(
+            SenderNet::parse("0.0.0.0/0")
+                .unwrap()
+                .contains("10.1.2.3".parse().unwrap())
+        );
+        for bad in ["cam1", "10.0.0.0/33", "10.0.0.0/x", ""] {
+            assert!(SenderNet::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[tokio::test]
+    async fn listener_drops_senders_not_allowed() {
+        let (dir, spool) = temp_spool("allow", 1024 * 1024);
+        let listener = UdpSyslogListener::bind("127.0.0.1:0", spool.clone())
+            .await
+            .unwrap()
+            .with_allowed_senders(vec![SenderNet::parse("10.0.0.0/8").unwrap()]);
+        let addr = listener.local_addr().unwrap();
+        let task = tokio::spawn(async move { listener.run().await });
+
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender.send_to(b"<134>spoofed", addr).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        task.abort();
+        assert!(spool.hosts().await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn full_spool_refuses_new_senders() {
+        let (dir, spool) = temp_spool("hosts", 1024 * 1024);
+        for i in 0..4 {
+            assert!(spool.append(&format!("10.0.0.{i}"), "hello").await.unwrap());
+        }
+        assert!(!spool.append("10.0.0.9", "hello").await.unwrap());
+        assert!(spool.append("10.0.0.1", "again").await.unwrap());
+        assert_eq!(spool.hosts().await.unwrap().len(), 4);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn listener_spools_datagrams_per_sender() {
+        let (dir, spool) = temp_spool("listen", 1024 * 1024);
+        let listener = UdpSyslogListener::bind("127.0.0.1:0", spool.clone())
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let task = tokio::spawn(async move { listener.run().await });
+
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for msg in [
+            "<134>Jan 15 12:00:01 dashcam rec[7]: recording started",
+            "<131>Jan 15 12:00:05 dashcam rec[7]: SD card write failed",
+        ] {
+            sender.send_to(msg.as_bytes(), addr).await.unwrap();
+        }
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            lines = spool.read("127.0.0.1").await.unwrap_or_default();
+            if lines.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        task.abort();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with("SD card write failed"));
+
+        let mut local = MockLogSource::new();
+        local.add_file("/var/log/syslog", vec!["local line".into()]);
+        let source = UdpSyslogSource::new(local, spool);
+        assert_eq!(
+            source.list_sources().await.unwrap(),
+            ["/var/log/syslog", "remote://127.0.0.1"]
+        );
+        assert_eq!(
+            source.expand("remote://*").await.unwrap(),
+            ["remote://127.0.0.1"]
+        );
+        assert!(source.exists("remote://127.0.0.1").await);
+        assert!(!source.exists("remote://10.0.0.9").await);
+        assert_eq!(
+            source.read_lines("/var/log/syslog").await.unwrap(),
+            ["local line"]
+        );
+
+        let result = SearchLogs::default()
+            .execute(
+                serde_json::json!({"path": "remote://127.0.0.1", "query": "failed"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.data.unwrap()["match_count"], 1);
+        assert!(matches!(
+            source.read_lines("remote://10.0.0.9").await,
+            Err(LogError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized_spool_keeps_newest_half() {
+        let (dir, spool) = temp_spool("trim", 100);
+        for i in 0..20 {
+            spool
+                .append("10.0.0.5", &format!("message {i:02}"))
+                .await
+                .unwrap();
+        }
+        let lines = spool.read("10.0.0.5").await.unwrap();
+        assert!(lines.len() < 20);
+        assert_eq!(lines.last().unwrap(), "message 19");
+        assert!(std::fs::metadata(dir.join("10.0.0.5.log")).unwrap().len() <= 100);
+        assert!(spool.append("../x", "nope").await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,41 @@
 //! Log source abstraction — read log data from files, mocks, or other backends.
 
 use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use std::io::{Read, SeekFrom};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 use crate::error::{LogError, LogResult};
 
+/// Lines added to a log since an earlier read; see [`LogSource::read_from`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogChunk {
+    /// Complete lines added after the offset.
+    pub lines: Vec<String>,
+    /// Offset to pass to the next call.
+    pub offset: u64,
+    /// The log shrank (truncated or rotated), so `lines` start from its
+    /// beginning.
+    pub reset: bool,
+}
+
+impl LogChunk {
+    /// The lines after line `offset` of a log read whole.
+    pub(crate) fn from_lines(lines: Vec<String>, offset: u64) -> Self {
+        let total = lines.len() as u64;
+        let (start, reset) = if total < offset {
+            (0, true)
+        } else {
+            (offset as usize, false)
+        };
+        Self {
+            lines: lines.into_iter().skip(start).collect(),
+            offset: total,
+            reset,
+        }
+    }
+}
+
 /// Abstraction for reading log data from various sources.
 ///
 /// Analogous to `CanInterface` in `zc-canbus-tools` — enables mocking
@@ -21,21 +53,131 @@ pub trait LogSource: Send + Sync {
 
     /// List available log sources (e.g., known log file paths).
     async fn list_sources(&self) -> LogResult<Vec<String>>;
+
+    /// Lines added since `offset`, an opaque value returned by an earlier
+    /// call (0 reads from the start). Used to follow a growing log.
+    ///
+    /// The default re-reads the whole log and uses line counts as offsets.
+    async fn read_from(&self, path: &str, offset: u64) -> LogResult<LogChunk> {
+        Ok(LogChunk::from_lines(self.read_lines(path).await?, offset))
+    }
+
+    /// The newest complete lines of a log that fit in `max_bytes` (line
+    /// ends included), and whether older lines were left out.
+    ///
+    /// The default reads the whole log; [`FileLogSource`] keeps no more than
+    /// about `max_bytes` in memory.
+    async fn read_newest(&self, path: &str, max_bytes: usize) -> LogResult<(Vec<String>, bool)> {
+        let mut lines = self.read_lines(path).await?;
+        let mut size = 0;
+        let mut start = lines.len();
+        while start > 0 && size + lines[start - 1].len() < max_bytes {
+            start -= 1;
+            size += lines[start].len() + 1;
+        }
+        Ok((lines.split_off(start), start > 0))
+    }
+
+    /// Paths matching a glob pattern (`*`, `?` in the file name), sorted.
+    ///
+    /// The default matches against [`list_sources`](Self::list_sources).
+    async fn expand(&self, pattern: &str) -> LogResult<Vec<String>> {
+        let mut found: Vec<String> = self
+            .list_sources()
+            .await?
+            .into_iter()
+            .filter(|p| crate::paths::glob_match(pattern, p))
+            .collect();
+        found.sort();
+        Ok(found)
+    }
+}
+
+/// Most bytes [`FileLogSource::read_from`] returns at once; the rest
+/// comes with the next call.
+const MAX_READ_FROM_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Most bytes [`FileLogSource::read_newest`] decompresses from one `.gz`
+/// file, so a compression bomb cannot keep the device busy indefinitely.
+const MAX_DECOMPRESSED_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// The newest complete lines of `reader` within `max_bytes`, and whether
+/// anything before them was dropped. Reading more than `limit` bytes is an
+/// error. At most twice `max_bytes` is buffered.
+fn newest_lines(
+    mut reader: impl Read,
+    max_bytes: usize,
+    limit: u64,
+) -> std::io::Result<(Vec<String>, bool)> {
+    let mut buf = Vec::new();
+    let mut chunk = vec![0; 64 * 1024];
+    let mut total = 0u64;
+    // Byte just before `buf`, if anything was dropped.
+    let mut before: Option<u8> = None;
+    loop {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        total += n as u64;
+        if total > limit {
+            return Err(std::io::Error::other(format!(
+                "decompresses to more than {limit} bytes"
+            )));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > 2 * max_bytes {
+            let cut = buf.len() - max_bytes;
+            before = Some(buf[cut - 1]);
+            buf.drain(..cut);
+        }
+    }
+    if buf.len() > max_bytes {
+        let cut = buf.len() - max_bytes;
+        before = Some(buf[cut - 1]);
+        buf.drain(..cut);
+    }
+    // A line cut in two is dropped whole.
+    if before.is_some_and(|b| b != b'\n') {
+        let start = buf
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(buf.len(), |i| i + 1);
+        buf.drain(..start);
+    }
+    let lines = String::from_utf8_lossy(&buf)
+        .lines()
+        .map(String::from)
+        .collect();
+    Ok((lines, before.is_some()))
+}
+
+fn file_error(path: &str, e: std::io::Error) -> LogError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        LogError::NotFound(path.to_string())
+    } else {
+        LogError::Io(format!("{path}: {e}"))
+    }
 }
 
-/// Reads logs from the local filesystem.
+/// Reads logs from the local filesystem. Files ending in `.gz` (rotated
+/// logs) are decompressed.
 pub struct FileLogSource;
 
 #[async_trait]
 impl LogSource for FileLogSource {
     async fn read_lines(&self, path: &str) -> LogResult<Vec<String>> {
-        let content = tokio::fs::read_to_string(path).await.map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                LogError::NotFound(path.to_string())
-            } else {
-                LogError::Io(format!("{path}: {e}"))
-            }
-        })?;
+        let io_error = |e: std::io::Error| file_error(path, e);
+        let content = if path.ends_with(".gz") {
+            let compressed = tokio::fs::read(path).await.map_err(io_error)?;
+            let mut content = String::new();
+            GzDecoder::new(compressed.as_slice())
+                .read_to_string(&mut content)
+                .map_err(io_error)?;
+            content
+        } else {
+            tokio::fs::read_to_string(path).await.map_err(io_error)?
+        };
         Ok(content.lines().map(String::from).collect())
     }
 
@@ -49,6 +191,58 @@ impl LogSource for FileLogSource {
         tokio::fs::metadata(path).await.is_ok()
     }
 
+    /// Reads only the bytes appended after `offset` (a byte position), up
+    /// to the last complete line. A file shorter than `offset` was
+    /// truncated or rotated and is read from the start.
+    async fn read_from(&self, path: &str, offset: u64) -> LogResult<LogChunk> {
+        if path.ends_with(".gz") {
+            return Ok(LogChunk::from_lines(self.read_lines(path).await?, offset));
+        }
+        let io_error = |e: std::io::Error| file_error(path, e);
+        let mut file = tokio::fs::File::open(path).await.map_err(io_error)?;
+        let len = file.metadata().await.map_err(io_error)?.len();
+        let (start, reset) = if len < offset {
+            (0, true)
+        } else {
+            (offset, false)
+        };
+        file.seek(SeekFrom::Start(start)).await.map_err(io_error)?;
+        let mut buf = Vec::new();
+        file.take(MAX_READ_FROM_BYTES)
+            .read_to_end(&mut buf)
+            .await
+            .map_err(io_error)?;
+        let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        Ok(LogChunk {
+            lines: String::from_utf8_lossy(&buf[..complete])
+                .lines()
+                .map(String::from)
+                .collect(),
+            offset: start + complete as u64,
+            reset,
+        })
+    }
+
+    /// Plain files are read from `max_bytes` before their end; `.gz` files
+    /// are decompressed as a stream, up to [`MAX_DECOMPRESSED_BYTES`].
+    async fn read_newest(&self, path: &str, max_bytes: usize) -> LogResult<(Vec<String>, bool)> {
+        let owned = path.to_string();
+        let read = tokio::task::spawn_blocking(move || {
+            let mut file = std::fs::File::open(&owned)?;
+            if owned.ends_with(".gz") {
+                return newest_lines(GzDecoder::new(file), max_bytes, MAX_DECOMPRESSED_BYTES);
+            }
+            let len = file.metadata()?.len();
+            // One byte early, to tell whether the first line is whole.
+            let start = len.saturating_sub(max_bytes as u64 + 1);
+            std::io::Seek::seek(&mut file, SeekFrom::Start(start))?;
+            newest_lines(file, max_bytes, u64::MAX)
+        })
+        .await
+        .map_err(|e| LogError::Io(format!("{path}: {e}")))?;
+        read.map_err(|e| file_error(path, e))
+    }
+
     async fn list_sources(&self) -> LogResult<Vec<String>> {
         let candidates = [
             "/var/log/syslog",
@@ -65,4 +259,142 @@ impl LogSource for FileLogSource {
         }
         Ok(found)
     }
+
+    async fn expand(&self, pattern: &str) -> LogResult<Vec<String>> {
+        let (dir, name) = pattern.rsplit_once('/').unwrap_or((".", pattern));
+        if crate::paths::is_glob(dir) {
+            return Err(LogError::Other(format!(
+                "{pattern}: wildcards are only supported in the file name"
+            )));
+        }
+        let dir = if dir.is_empty() { "/" } else { dir };
+
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(LogError::Io(format!("{dir}: {e}"))),
+        };
+        let mut found = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| LogError::Io(format!("{dir}: {e}")))?
+        {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if crate::paths::glob_match(name, file_name)
+                && entry.file_type().await.is_ok_and(|t| t.is_file())
+            {
+                found.push(format!("{}/{file_name}", dir.trim_end_matches('/')));
+            }
+        }
+        found.sort();
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn expands_globs_and_reads_gzip() {
+        let dir = std::env::temp_dir().join(format!("zc-logs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.log"), "live line\n").unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b"old line 1\nold line 2\n").unwrap();
+        std::fs::write(dir.join("app.log.2.gz"), gz.finish().unwrap()).unwrap();
+        let dir = dir.to_str().unwrap();
+
+        let source = FileLogSource;
+        let found = source.expand(&format!("{dir}/app.log*")).await.unwrap();
+        assert_eq!(
+            found,
+            [format!("{dir}/app.log"), format!("{dir}/app.log.2.gz")]
+        );
+        let lines = source.read_lines(&found[1]).await.unwrap();
+        assert_eq!(lines, ["old line 1", "old line 2"]);
+        assert!(
+            source
+                .expand("/no/such/dir/*.log")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(source.expand("/var/*/syslog").await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_only_the_newest_lines() {
+        let dir = std::env::temp_dir().join(format!("zc-newest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = "first line\nsecond line\nthird\n";
+        std::fs::write(dir.join("app.log"), text).unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(text.as_bytes()).unwrap();
+        std::fs::write(dir.join("app.log.1.gz"), gz.finish().unwrap()).unwrap();
+
+        let source = FileLogSource;
+        for name in ["app.log", "app.log.1.gz"] {
+            let path = dir.join(name);
+            let path = path.to_str().unwrap();
+            let (lines, truncated) = source.read_newest(path, 20).await.unwrap();
+            assert_eq!(
+                (lines, truncated),
+                (vec!["second line".into(), "third".into()], true)
+            );
+            let (lines, truncated) = source.read_newest(path, 1024).await.unwrap();
+            assert_eq!((lines.len(), truncated), (3, false));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A compression bomb is refused rather than inflated.
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&vec![b'x'; 1024 * 1024]).unwrap();
+        let bomb = gz.finish().unwrap();
+        let err = newest_lines(GzDecoder::new(bomb.as_slice()), 100, 64 * 1024).unwrap_err();
+        assert!(err.to_string().contains("more than"));
+    }
+
+    #[tokio::test]
+    async fn reads_appended_lines_from_an_offset() {
+        let path = std::env::temp_dir().join(format!("zc-follow-{}.log", std::process::id()));
+        std::fs::write(&path, "one\ntwo\npart").unwrap();
+        let path_str = path.to_str().unwrap();
+        let source = FileLogSource;
+
+        let chunk = source.read_from(path_str, 0).await.unwrap();
+        assert_eq!(chunk.lines, ["one", "two"]);
+        assert_eq!(chunk.offset, 8);
+        assert!(!chunk.reset);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"ial\nthree\n").unwrap();
+        let chunk = source.read_from(path_str, chunk.offset).await.unwrap();
+        assert_eq!(chunk.lines, ["partial", "three"]);
+        let unchanged = source.read_from(path_str, chunk.offset).await.unwrap();
+        assert!(unchanged.lines.is_empty());
+
+        std::fs::write(&path, "rotated\n").unwrap();
+        let chunk = source.read_from(path_str, unchanged.offset).await.unwrap();
+        assert_eq!(chunk.lines, ["rotated"]);
+        assert!(chunk.reset);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            source.read_from(path_str, 0).await,
+            Err(LogError::NotFound(_))
+        ));
+    }
 }
@@ -0,0 +1,165 @@
+//! Message templates mined from log lines, Drain-style.
+//!
+//! Messages are tokenized on whitespace and tokens carrying variable data
+//! (anything with a digit, hex IDs) are masked as `<*>` up front. Messages
+//! are then grouped by token count and first token, and within a group a
+//! message joins the most similar template — the share of positions where
+//! the template's fixed tokens match — when that share reaches the
+//! similarity threshold. Joining masks the positions that differ, so
+//! `"connection to 10.0.0.4 lost after 3 retries"` and
+//! `"connection to gw-b lost after 5 retries"` end up as
+//! `"connection to <*> lost after <*> retries"`.
+
+use std::collections::HashMap;
+
+/// Placeholder for a variable token.
+pub const WILDCARD: &str = "<*>";
+/// Default share of matching tokens for a message to join a template.
+pub const DEFAULT_SIMILARITY: f64 = 0.5;
+/// Templates kept per miner; further novel messages are not clustered.
+const MAX_TEMPLATES: usize = 2000;
+
+/// One mined template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub tokens: Vec<String>,
+}
+
+impl Template {
+    pub fn text(&self) -> String {
+        self.tokens.join(" ")
+    }
+
+    /// Share of this template's positions that `tokens` matches; wildcards
+    /// count as matches.
+    fn similarity(&self, tokens: &[String]) -> f64 {
+        if self.tokens.is_empty() {
+            return 1.0;
+        }
+        let same = self
+            .tokens
+            .iter()
+            .zip(tokens)
+            .filter(|(t, m)| *t == WILDCARD || t == m)
+            .count();
+        same as f64 / self.tokens.len() as f64
+    }
+
+    fn merge(&mut self, tokens: &[String]) {
+        for (t, m) in self.tokens.iter_mut().zip(tokens) {
+            if t != m {
+                *t = WILDCARD.to_string();
+            }
+        }
+    }
+}
+
+/// Incremental template miner.
+#[derive(Debug)]
+pub struct TemplateMiner {
+    similarity: f64,
+    templates: Vec<Template>,
+    /// (token count, first token) → template indices.
+    groups: HashMap<(usize, String), Vec<usize>>,
+}
+
+impl Default for TemplateMiner {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIMILARITY)
+    }
+}
+
+impl TemplateMiner {
+    pub fn new(similarity: f64) -> Self {
+        Self {
+            similarity: similarity.clamp(0.0, 1.0),
+            templates: Vec::new(),
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Index of the template `message` belongs to, creating one if needed.
+    /// `None` once the template limit is reached and nothing matches.
+    pub fn add(&mut self, message: &str) -> Option<usize> {
+        let tokens = tokenize(message);
+        let key = (tokens.len(), tokens.first().cloned().unwrap_or_default());
+        let group = self.groups.entry(key).or_default();
+        let best = group
+            .iter()
+            .map(|&i| (i, self.templates[i].similarity(&tokens)))
+            .filter(|(_, sim)| *sim >= self.similarity)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = best {
+            self.templates[i].merge(&tokens);
+            return Some(i);
+        }
+        if self.templates.len() >= MAX_TEMPLATES {
+            return None;
+        }
+        self.templates.push(Template { tokens });
+        let i = self.templates.len() - 1;
+        group.push(i);
+        Some(i)
+    }
+
+    pub fn template(&self, index: usize) -> &Template {
+        &self.templates[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+}
+
+/// Whitespace tokens with variable-looking ones masked.
+pub fn tokenize(message: &str) -> Vec<String> {
+    message
+        .split_whitespace()
+        .map(|t| {
+            if is_variable(t) {
+                WILDCARD.to_string()
+            } else {
+                t.to_string()
+            }
+        })
+        .collect()
+}
+
+fn is_variable(token: &str) -> bool {
+    token.chars().any(|c| c.is_ascii_digit())
+        || (token.len() >= 8 && token.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_tokens_are_masked() {
+        assert_eq!(
+            tokenize("retry 3 for 10.0.0.4 id=deadbeef cafebabe00"),
+            ["retry", "<*>", "for", "<*>", "id=deadbeef", "<*>"]
+        );
+    }
+
+    #[test]
+    fn similar_messages_share_a_template() {
+        let mut miner = TemplateMiner::default();
+        let a = miner.add("connection to 10.0.0.4 lost after 3 retries");
+        let b = miner.add("connection to gw-b lost after 5 retries");
+        let c = miner.add("connection refused by broker");
+        let d = miner.add("disk full on /dev/sda1");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(c, d);
+        assert_eq!(
+            miner.template(a.unwrap()).text(),
+            "connection to <*> lost after <*> retries"
+        );
+        assert_eq!(miner.len(), 3);
+    }
+}
@@ -0,0 +1,172 @@
+//! `since` / `until` filtering shared by the file-based log tools.
+//!
+//! Bounds are RFC 3339 timestamps (`2024-01-15T12:00:00Z`) or durations
+//! back from now (`90s`, `15m`, `2h`, `1d`, `1w`). Both are inclusive. The
+//! range is applied to parsed entries, so it works for every format that
+//! carries timestamps; entries without one cannot be placed and are left
+//! out while a range is set.
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{Value, json};
+
+use crate::error::{LogError, LogResult};
+use crate::types::LogEntry;
+
+/// JSON Schema for the `since` argument.
+pub fn since_schema() -> Value {
+    json!({
+        "type": "string",
+        "description": "Only entries at or after this time: RFC 3339 or relative (e.g. '2h', '30m', '1d')"
+    })
+}
+
+/// JSON Schema for the `until` argument.
+pub fn until_schema() -> Value {
+    json!({
+        "type": "string",
+        "description": "Only entries at or before this time: RFC 3339 or relative (e.g. '1h')"
+    })
+}
+
+/// Parse a bound: RFC 3339, or a duration before `now`.
+pub fn parse_bound(value: &str, now: DateTime<Utc>) -> LogResult<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let invalid = || {
+        LogError::Other(format!(
+            "invalid time '{value}': expected RFC 3339 or a relative duration like '2h'"
+        ))
+    };
+    let span = parse_duration(value).map_err(|_| invalid())?;
+    now.checked_sub_signed(span).ok_or_else(invalid)
+}
+
+/// Parse a duration like `90s`, `15m`, `2h`, `1d` or `1w`.
+pub fn parse_duration(value: &str) -> LogResult<Duration> {
+    let value = value.trim();
+    let invalid = || {
+        LogError::Other(format!(
+            "invalid duration '{value}': expected e.g. '90s', '15m', '2h', '1d'"
+        ))
+    };
+    let unit_at = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(unit_at);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
+
+/// Inclusive time window; either side may be open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /// Range from a tool's `since` / `until` arguments; `None` when neither
+    /// is given.
+    pub fn from_args(args: &Value) -> LogResult<Option<Self>> {
+        Self::from_args_at(args, Utc::now())
+    }
+
+    /// [`TimeRange::from_args`] with relative bounds counted back from `now`.
+    pub fn from_args_at(args: &Value, now: DateTime<Utc>) -> LogResult<Option<Self>> {
+        let bound = |name: &str| -> LogResult<Option<DateTime<Utc>>> {
+            match &args[name] {
+                Value::Null => Ok(None),
+                Value::String(s) => parse_bound(s, now).map(Some),
+                _ => Err(LogError::Other(format!("'{name}' must be a string"))),
+            }
+        };
+        let range = Self {
+            since: bound("since")?,
+            until: bound("until")?,
+        };
+        if let (Some(since), Some(until)) = (range.since, range.until)
+            && since > until
+        {
+            return Err(LogError::Other(format!(
+                "'since' ({since}) is after 'until' ({until})"
+            )));
+        }
+        Ok((range.since.is_some() || range.until.is_some()).then_some(range))
+    }
+
+    /// Whether `entry` falls inside the range. Entries without a timestamp
+    /// never do.
+    pub fn contains(&self, entry: &LogEntry) -> bool {
+        entry.timestamp.is_some_and(|ts| {
+            self.since.is_none_or(|since| ts >= since) && self.until.is_none_or(|until| ts <= until)
+        })
+    }
+
+    /// The resolved bounds, for tool results.
+    pub fn to_json(&self) -> Value {
+        json!({ "since": self.since, "until": self.until })
+    }
+}
+
+/// Keep only the entries inside `range` (all of them when there is none).
+pub fn retain(entries: &mut Vec<LogEntry>, range: Option<&TimeRange>) {
+    if let Some(range) = range {
+        entries.retain(|e| range.contains(e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parses_absolute_and_relative_bounds() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        assert_eq!(
+            parse_bound("2024-01-15T10:30:00+01:00", now).unwrap(),
+            Utc.with_ymd_and_hms(2024, 1, 15, 9, 30, 0).unwrap()
+        );
+        assert_eq!(
+            parse_bound("2h", now).unwrap(),
+            Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_bound("90s", now).unwrap(),
+            Utc.with_ymd_and_hms(2024, 1, 15, 11, 58, 30).unwrap()
+        );
+        assert_eq!(
+            parse_bound("1w", now).unwrap(),
+            Utc.with_ymd_and_hms(2024, 1, 8, 12, 0, 0).unwrap()
+        );
+        for bad in ["", "h", "2 hours", "2y", "yesterday", "-2h"] {
+            assert!(parse_bound(bad, now).is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn range_from_args() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        assert_eq!(TimeRange::from_args_at(&json!({}), now).unwrap(), None);
+
+        let range = TimeRange::from_args_at(&json!({"since": "1h"}), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(range.since, Some(now - Duration::hours(1)));
+        assert_eq!(range.until, None);
+
+        let inverted = json!({"since": "1h", "until": "2h"});
+        assert!(TimeRange::from_args_at(&inverted, now).is_err());
+        assert!(TimeRange::from_args_at(&json!({"until": 5}), now).is_err());
+    }
+}
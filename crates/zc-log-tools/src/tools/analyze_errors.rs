@@ -4,12 +4,14 @@ use async_trait::async_trait;
 use regex::Regex;
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::sync::LazyLock;
 
 use crate::error::{LogError, LogResult};
-use crate::parsers;
+use crate::parsers::{self, CustomFormats};
 use crate::source::LogSource;
-use crate::types::{LogFormat, LogSeverity, LogTool, ToolResult};
+use crate::time_range::{self, TimeRange};
+use crate::types::{LogSeverity, LogTool, ToolResult};
 
 // ── Known error pattern categories ────────────────────────────
 
@@ -81,7 +83,17 @@ static ERROR_PATTERNS: LazyLock<Vec<ErrorPattern>> = LazyLock::new(|| {
 
 // ── Tool implementation ───────────────────────────────────────
 
-pub struct AnalyzeErrors;
+#[derive(Default)]
+pub struct AnalyzeErrors {
+    formats: Arc<CustomFormats>,
+}
+
+impl AnalyzeErrors {
+    /// Also accept the device's custom log formats.
+    pub fn with_formats(formats: Arc<CustomFormats>) -> Self {
+        Self { formats }
+    }
+}
 
 #[async_trait]
 impl LogTool for AnalyzeErrors {
@@ -101,11 +113,9 @@ impl LogTool for AnalyzeErrors {
                     "type": "string",
                     "description": "Path to the log file"
                 },
-                "format": {
-                    "type": "string",
-                    "enum": ["syslog_3164", "syslog_5424", "journald", "json_lines", "plaintext"],
-                    "description": "Log format (auto-detected if omitted)"
-                }
+                "since": time_range::since_schema(),
+                "until": time_range::until_schema(),
+                "format": parsers::format_schema(&self.formats)
             },
             "required": ["path"]
         })
@@ -119,21 +129,13 @@ impl LogTool for AnalyzeErrors {
         let path = args["path"]
             .as_str()
             .ok_or_else(|| LogError::Other("missing 'path' argument".into()))?;
-        let format = args["format"]
-            .as_str()
-            .map(|s| match s {
-                "syslog_3164" => Ok(LogFormat::Syslog3164),
-                "syslog_5424" => Ok(LogFormat::Syslog5424),
-                "journald" => Ok(LogFormat::Journald),
-                "json_lines" => Ok(LogFormat::JsonLines),
-                "plaintext" => Ok(LogFormat::Plaintext),
-                other => Err(LogError::Format(format!("unknown format: {other}"))),
-            })
-            .transpose()?;
+        let format = args["format"].as_str();
+        let range = TimeRange::from_args(&args)?;
 
         let lines = source.read_lines(path).await?;
-        let fmt = format.unwrap_or_else(|| parsers::detect_format(&lines));
-        let entries = parsers::parse_lines(&lines, fmt);
+        let fmt = parsers::resolve_format(format, &lines, &self.formats)?;
+        let mut entries = fmt.parse(&lines);
+        time_range::retain(&mut entries, range.as_ref());
 
         // Filter to error/critical entries
         let errors: Vec<_> = entries
@@ -213,9 +215,9 @@ impl LogTool for AnalyzeErrors {
             100.0
         };
 
-        let data = json!({
+        let mut data = json!({
             "path": path,
-            "format": format!("{fmt:?}"),
+            "format": fmt.name(),
             "total_lines": total_lines,
             "error_count": error_count,
             "warning_count": warning_count,
@@ -224,6 +226,9 @@ impl LogTool for AnalyzeErrors {
             "unclassified_examples": unclassified_examples,
             "classification_rate": classification_rate,
         });
+        if let Some(range) = &range {
+            data["time_filter"] = range.to_json();
+        }
 
         let pattern_count = categories.len();
         Ok(ToolResult::success(
@@ -264,7 +269,7 @@ mod tests {
     #[tokio::test]
     async fn analyze_syslog_errors() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/var/log/syslog"}), &source)
             .await
@@ -280,7 +285,7 @@ mod tests {
     #[tokio::test]
     async fn analyze_json_errors() {
         let source = MockLogSource::with_json_sample();
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.json"}), &source)
             .await
@@ -293,7 +298,7 @@ mod tests {
     #[tokio::test]
     async fn analyze_journald_errors() {
         let source = MockLogSource::with_journald_sample();
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/var/log/journal.export"}), &source)
             .await
@@ -314,7 +319,7 @@ mod tests {
                 r#"{"level":"error","message":"connection timed out"}"#.into(),
             ],
         );
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/test.log"}), &source)
             .await
@@ -338,7 +343,7 @@ mod tests {
                 r#"{"level":"error","message":"access denied for user admin"}"#.into(),
             ],
         );
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/test.log"}), &source)
             .await
@@ -362,7 +367,7 @@ mod tests {
                 r#"{"level":"error","message":"CAN interface error: device offline"}"#.into(),
             ],
         );
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/test.log"}), &source)
             .await
@@ -383,7 +388,7 @@ mod tests {
                 r#"{"level":"debug","message":"trace"}"#.into(),
             ],
         );
-        let tool = AnalyzeErrors;
+        let tool = AnalyzeErrors::default();
         let result = tool
             .execute(json!({"path": "/test.log"}), &source)
             .await
@@ -392,4 +397,20 @@ mod tests {
         assert_eq!(data["error_count"].as_u64().unwrap(), 0);
         assert_eq!(data["classification_rate"].as_f64().unwrap(), 100.0);
     }
+
+    #[tokio::test]
+    async fn analyze_within_time_range() {
+        let source = MockLogSource::with_json_sample();
+        let result = AnalyzeErrors::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "since": "2024-01-15T12:00:15Z"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        // The 12:00:05 CAN bus timeout is outside the window.
+        assert_eq!(data["error_count"], 2);
+        assert_eq!(data["total_lines"], 5);
+    }
 }
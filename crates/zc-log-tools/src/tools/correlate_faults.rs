@@ -0,0 +1,481 @@
+//! correlate_faults — line up DTCs with the log entries logged around them.
+//!
+//! Takes the DTCs of a `read_dtcs` result (or plain codes) and a log file,
+//! picks the entries within `window_secs` of each DTC's detection that
+//! mention CAN, driver or adapter trouble (or the code itself), and merges
+//! both into one time-sorted timeline. In a plan it follows `read_dtcs`:
+//! `{"dtcs": "{{steps.0.data}}"}`.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::LazyLock;
+
+use crate::error::{LogError, LogResult};
+use crate::parsers::{self, CustomFormats};
+use crate::source::LogSource;
+use crate::time_range;
+use crate::types::{LogEntry, LogTool, ToolResult};
+
+/// Seconds either side of a detection searched unless `window_secs` says
+/// otherwise.
+const DEFAULT_WINDOW_SECS: i64 = 300;
+
+/// Largest accepted `window_secs`.
+const MAX_WINDOW_SECS: i64 = 3600;
+
+/// Most log entries in one timeline, keeping it well under the MQTT payload
+/// limit. The entries closest to a detection are kept.
+const MAX_LOG_ENTRIES: usize = 200;
+
+/// Messages that point at the CAN bus, its driver or an OBD-II adapter.
+static FAULT_KEYWORDS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(v?can\d*|socketcan|slcan|bus[\s-]?off|error[\s-]passive|arbitration\s+lost|iso-?tp|obd(-?ii)?|ecu|elm327|mcp251\w*|gs_usb|flexcan|driver|firmware|time(d\s+)?out)\b",
+    )
+    .unwrap()
+});
+
+/// A DTC to correlate and when it was detected.
+struct Detection {
+    code: String,
+    detected_at: DateTime<Utc>,
+    description: Option<String>,
+    severity: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CorrelateFaults {
+    formats: Arc<CustomFormats>,
+}
+
+impl CorrelateFaults {
+    /// Also accept the device's custom log formats.
+    pub fn with_formats(formats: Arc<CustomFormats>) -> Self {
+        Self { formats }
+    }
+}
+
+#[async_trait]
+impl LogTool for CorrelateFaults {
+    fn name(&self) -> &str {
+        "correlate_faults"
+    }
+
+    fn description(&self) -> &str {
+        "Merge DTCs with the CAN/driver log entries logged around their detection into one timeline"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "dtcs": {
+                    "type": "array",
+                    "description": "DTCs to correlate: the data of a read_dtcs result, codes, or {code, detected_at} objects",
+                    "items": {
+                        "type": ["string", "object"]
+                    }
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Path to the log file"
+                },
+                "detected_at": {
+                    "type": "string",
+                    "description": "When DTCs without their own detected_at were detected: RFC 3339 or relative (e.g. '10m'); default now"
+                },
+                "window_secs": {
+                    "type": "integer",
+                    "description": "Seconds either side of each detection to search (default 300, max 3600)"
+                },
+                "keywords": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Extra words that make an entry relevant, besides CAN/driver terms and the codes"
+                },
+                "format": parsers::format_schema(&self.formats)
+            },
+            "required": ["dtcs", "path"]
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        source: &dyn LogSource,
+    ) -> LogResult<ToolResult> {
+        let path = args["path"]
+            .as_str()
+            .ok_or_else(|| LogError::Other("missing 'path' argument".into()))?;
+        let format = args["format"].as_str();
+        let now = Utc::now();
+        let default_at = match args["detected_at"].as_str() {
+            Some(value) => time_range::parse_bound(value, now)?,
+            None => now,
+        };
+        let window_secs = match args.get("window_secs") {
+            None | Some(serde_json::Value::Null) => DEFAULT_WINDOW_SECS,
+            Some(v) => match v.as_i64() {
+                Some(n) if (1..=MAX_WINDOW_SECS).contains(&n) => n,
+                _ => {
+                    return Err(LogError::Other(format!(
+                        "'window_secs' must be an integer from 1 to {MAX_WINDOW_SECS}"
+                    )));
+                }
+            },
+        };
+        let window = Duration::seconds(window_secs);
+        let detections = detections(&args["dtcs"], default_at, now)?;
+        let keywords = keyword_regex(&args["keywords"], &detections)?;
+
+        let lines = source.read_lines(path).await?;
+        let fmt = parsers::resolve_format(format, &lines, &self.formats)?;
+        let entries = fmt.parse(&lines);
+
+        // Each relevant entry with the DTCs whose window holds it and its
+        // distance to the nearest detection.
+        let mut related: Vec<(&LogEntry, Vec<&str>, i64)> = Vec::new();
+        let mut untimed = 0;
+        for entry in &entries {
+            let Some(ts) = entry.timestamp else {
+                untimed += 1;
+                continue;
+            };
+            let near: Vec<&Detection> = detections
+                .iter()
+                .filter(|d| (ts - d.detected_at).abs() <= window)
+                .collect();
+            if near.is_empty() {
+                continue;
+            }
+            if !FAULT_KEYWORDS.is_match(&entry.message) && !keywords.is_match(&entry.message) {
+                continue;
+            }
+            let distance = near
+                .iter()
+                .map(|d| (ts - d.detected_at).num_seconds().abs())
+                .min()
+                .unwrap_or_default();
+            related.push((
+                entry,
+                near.iter().map(|d| d.code.as_str()).collect(),
+                distance,
+            ));
+        }
+        let mut per_code: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, codes, _) in &related {
+            for code in codes {
+                *per_code.entry(code).or_default() += 1;
+            }
+        }
+
+        let matched = related.len();
+        if matched > MAX_LOG_ENTRIES {
+            related.sort_by_key(|(entry, _, distance)| (*distance, entry.line_number));
+            related.truncate(MAX_LOG_ENTRIES);
+        }
+
+        // DTCs sort before log entries of the same second.
+        let mut timeline: Vec<(DateTime<Utc>, u8, usize, serde_json::Value)> = Vec::new();
+        for (i, d) in detections.iter().enumerate() {
+            let mut item = json!({
+                "at": d.detected_at,
+                "kind": "dtc",
+                "code": d.code,
+            });
+            if let Some(description) = &d.description {
+                item["description"] = json!(description);
+            }
+            if let Some(severity) = &d.severity {
+                item["severity"] = json!(severity);
+            }
+            timeline.push((d.detected_at, 0, i, item));
+        }
+        for (entry, codes, _) in &related {
+            let ts = entry.timestamp.unwrap_or_default();
+            let mut item = json!({
+                "at": ts,
+                "kind": "log",
+                "line_number": entry.line_number,
+                "severity": entry.severity,
+                "message": entry.message,
+                "dtcs": codes,
+            });
+            if let Some(src) = &entry.source {
+                item["source"] = json!(src);
+            }
+            timeline.push((ts, 1, entry.line_number, item));
+        }
+        timeline.sort_by_key(|(at, kind, order, _)| (*at, *kind, *order));
+
+        let summary_dtcs: Vec<serde_json::Value> = detections
+            .iter()
+            .map(|d| {
+                json!({
+                    "code": d.code,
+                    "detected_at": d.detected_at,
+                    "related_entries": per_code.get(d.code.as_str()).copied().unwrap_or(0),
+                })
+            })
+            .collect();
+
+        let data = json!({
+            "path": path,
+            "format": fmt.name(),
+            "window_secs": window_secs,
+            "dtcs": summary_dtcs,
+            "timeline": timeline.into_iter().map(|(_, _, _, item)| item).collect::<Vec<_>>(),
+            "matched_entries": matched,
+            "truncated": matched > MAX_LOG_ENTRIES,
+            "untimed_entries": untimed,
+        });
+
+        let summary = if detections.is_empty() {
+            "No DTCs to correlate".to_string()
+        } else if matched == 0 {
+            format!(
+                "No CAN/driver log entries within {window_secs} s of {} DTC(s) in {path}",
+                detections.len()
+            )
+        } else {
+            let counts: Vec<String> = detections
+                .iter()
+                .map(|d| {
+                    let n = per_code.get(d.code.as_str()).copied().unwrap_or(0);
+                    format!("{}: {n}", d.code)
+                })
+                .collect();
+            format!(
+                "Correlated {} DTC(s) with {matched} log entries within {window_secs} s ({})",
+                detections.len(),
+                counts.join(", ")
+            )
+        };
+
+        Ok(ToolResult::success("correlate_faults", data, summary))
+    }
+}
+
+/// The DTCs of the `dtcs` argument: a list of codes or DTC objects, or a
+/// whole `read_dtcs` result. Each is detected at its own `detected_at` or
+/// at `default_at`.
+fn detections(
+    value: &serde_json::Value,
+    default_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> LogResult<Vec<Detection>> {
+    let invalid = || {
+        LogError::Other(
+            "'dtcs' must be a list of DTC codes or objects with a 'code', or a read_dtcs result"
+                .into(),
+        )
+    };
+    let list = match value {
+        serde_json::Value::Array(list) => list,
+        serde_json::Value::Object(result) => match result.get("data") {
+            Some(serde_json::Value::Array(list)) => list,
+            _ => return Err(invalid()),
+        },
+        serde_json::Value::Null => {
+            return Err(LogError::Other("missing 'dtcs' argument".into()));
+        }
+        _ => return Err(invalid()),
+    };
+    let mut detections: Vec<Detection> = Vec::new();
+    for item in list {
+        let (code, detected_at) = match item {
+            serde_json::Value::String(code) => (code.as_str(), None),
+            serde_json::Value::Object(dtc) => (
+                dtc.get("code")
+                    .and_then(|c| c.as_str())
+                    .ok_or_else(invalid)?,
+                dtc.get("detected_at").and_then(|t| t.as_str()),
+            ),
+            _ => return Err(invalid()),
+        };
+        let code = code.trim().to_uppercase();
+        if code.is_empty() {
+            return Err(invalid());
+        }
+        let detected_at = match detected_at {
+            Some(value) => time_range::parse_bound(value, now)?,
+            None => default_at,
+        };
+        // `read_dtcs` with scope "all" lists a code once per list it is in.
+        if detections
+            .iter()
+            .any(|d| d.code == code && d.detected_at == detected_at)
+        {
+            continue;
+        }
+        detections.push(Detection {
+            code,
+            detected_at,
+            description: item["description"].as_str().map(str::to_string),
+            severity: item["severity"].as_str().map(str::to_string),
+        });
+    }
+    Ok(detections)
+}
+
+/// Case-insensitive whole-word match of the DTC codes and the `keywords`
+/// argument.
+fn keyword_regex(value: &serde_json::Value, detections: &[Detection]) -> LogResult<Regex> {
+    let mut words: Vec<String> = detections.iter().map(|d| regex::escape(&d.code)).collect();
+    match value {
+        serde_json::Value::Null => {}
+        serde_json::Value::Array(list) => {
+            for word in list {
+                let word = word
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|w| !w.is_empty())
+                    .ok_or_else(|| {
+                        LogError::Other("'keywords' must be a list of non-empty strings".into())
+                    })?;
+                words.push(regex::escape(word));
+            }
+        }
+        _ => {
+            return Err(LogError::Other(
+                "'keywords' must be a list of non-empty strings".into(),
+            ));
+        }
+    }
+    if words.is_empty() {
+        // Matches nothing.
+        return Ok(Regex::new(r"[^\s\S]").unwrap());
+    }
+    Regex::new(&format!(r"(?i)\b({})\b", words.join("|")))
+        .map_err(|e| LogError::Other(format!("invalid keywords: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockLogSource;
+
+    fn source() -> MockLogSource {
+        let mut source = MockLogSource::new();
+        let line = |ts: &str, level: &str, src: &str, msg: &str| {
+            format!(
+                r#"{{"timestamp":"{ts}","level":"{level}","source":"{src}","message":"{msg}"}}"#
+            )
+        };
+        source.add_file(
+            "/var/log/app.json",
+            vec![
+                line("2024-01-15T11:00:00Z", "error", "kernel", "can0: bus-off"),
+                line("2024-01-15T11:58:00Z", "info", "app", "user logged in"),
+                line(
+                    "2024-01-15T11:59:30Z",
+                    "error",
+                    "kernel",
+                    "mcp251x spi0.0 can0: bus-off",
+                ),
+                line("2024-01-15T12:00:10Z", "warn", "obd", "request timed out"),
+                line(
+                    "2024-01-15T12:00:20Z",
+                    "info",
+                    "app",
+                    "P0300 reported by gateway",
+                ),
+                line(
+                    "2024-01-15T12:00:30Z",
+                    "info",
+                    "app",
+                    "scanning wifi networks",
+                ),
+                line(
+                    "2024-01-15T12:30:00Z",
+                    "error",
+                    "kernel",
+                    "can0: error-passive",
+                ),
+            ],
+        );
+        source
+    }
+
+    #[tokio::test]
+    async fn merges_dtcs_with_nearby_fault_entries() {
+        let tool = CorrelateFaults::default();
+        let dtcs = json!({
+            "tool_name": "read_dtcs",
+            "success": true,
+            "data": [
+                {"code": "P0300", "severity": "high", "description": "Random misfire"},
+                {"code": "U0100", "detected_at": "2024-01-15T12:30:05Z"},
+            ],
+        });
+        let result = tool
+            .execute(
+                json!({
+                    "dtcs": dtcs,
+                    "path": "/var/log/app.json",
+                    "detected_at": "2024-01-15T12:00:00Z",
+                    "window_secs": 120,
+                }),
+                &source(),
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["matched_entries"], 4);
+        assert_eq!(data["dtcs"][0]["related_entries"], 3);
+        assert_eq!(data["dtcs"][1]["related_entries"], 1);
+
+        let timeline = data["timeline"].as_array().unwrap();
+        let kinds: Vec<&str> = timeline
+            .iter()
+            .map(|i| i["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, ["log", "dtc", "log", "log", "log", "dtc"]);
+        assert_eq!(timeline[1]["code"], "P0300");
+        assert_eq!(timeline[1]["description"], "Random misfire");
+        assert_eq!(timeline[0]["line_number"], 3);
+        assert_eq!(timeline[4]["dtcs"], json!(["U0100"]));
+        // "scanning" does not count as CAN, the login is unrelated.
+        assert!(
+            !timeline
+                .iter()
+                .any(|i| i["message"].as_str().is_some_and(|m| m.contains("wifi")))
+        );
+        assert!(result.summary.unwrap().contains("P0300: 3"));
+    }
+
+    #[tokio::test]
+    async fn extra_keywords_and_bad_args() {
+        let tool = CorrelateFaults::default();
+        let result = tool
+            .execute(
+                json!({
+                    "dtcs": ["p0171"],
+                    "path": "/var/log/app.json",
+                    "detected_at": "2024-01-15T12:00:00Z",
+                    "window_secs": 60,
+                    "keywords": ["wifi"],
+                }),
+                &source(),
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["dtcs"][0]["code"], "P0171");
+        assert_eq!(data["matched_entries"], 3);
+
+        for args in [
+            json!({"path": "/var/log/app.json"}),
+            json!({"dtcs": [42], "path": "/var/log/app.json"}),
+            json!({"dtcs": ["P0300"], "path": "/var/log/app.json", "window_secs": 0}),
+            json!({"dtcs": ["P0300"], "path": "/var/log/app.json", "keywords": "wifi"}),
+        ] {
+            assert!(tool.execute(args, &source()).await.is_err());
+        }
+    }
+}
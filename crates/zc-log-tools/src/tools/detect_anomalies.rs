@@ -0,0 +1,366 @@
+//! detect_anomalies — flag message templates whose rate spikes.
+//!
+//! Entries are clustered into message templates ([`crate::templates`]) and
+//! split at `recent` before the newest entry: everything earlier is the
+//! baseline. A template is anomalous when it occurs at least `min_count`
+//! times in the recent window and its per-minute rate there is at least
+//! `threshold` times its baseline rate (`spike`), or it never occurred in
+//! the baseline (`new`). Unlike analyze_errors this needs no predefined
+//! categories, so it also catches unfamiliar messages and non-error noise
+//! such as a reconnect loop logged at info level. Entries without a
+//! timestamp cannot be placed and are skipped.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+use crate::error::{LogError, LogResult};
+use crate::parsers::{self, CustomFormats};
+use crate::source::LogSource;
+use crate::templates::TemplateMiner;
+use crate::time_range::{self, TimeRange};
+use crate::types::{LogSeverity, LogTool, ToolResult};
+
+/// Default length of the recent window.
+const DEFAULT_RECENT: &str = "10m";
+/// Default rate multiple over the baseline that counts as a spike.
+const DEFAULT_THRESHOLD: f64 = 3.0;
+/// Default recent occurrences a template needs before it can be flagged.
+const DEFAULT_MIN_COUNT: u64 = 3;
+/// Default number of anomalies returned.
+const DEFAULT_LIMIT: usize = 20;
+
+#[derive(Default)]
+pub struct DetectAnomalies {
+    formats: Arc<CustomFormats>,
+}
+
+impl DetectAnomalies {
+    /// Also accept the device's custom log formats.
+    pub fn with_formats(formats: Arc<CustomFormats>) -> Self {
+        Self { formats }
+    }
+}
+
+/// Occurrences of one template on each side of the split.
+#[derive(Debug, Default)]
+struct Counts {
+    baseline: u64,
+    recent: u64,
+    severity: Option<LogSeverity>,
+    example: Option<String>,
+}
+
+fn per_minute(count: u64, span: Duration) -> f64 {
+    let minutes = span.num_milliseconds().max(1000) as f64 / 60_000.0;
+    round2(count as f64 / minutes)
+}
+
+fn round2(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+#[async_trait]
+impl LogTool for DetectAnomalies {
+    fn name(&self) -> &str {
+        "detect_anomalies"
+    }
+
+    fn description(&self) -> &str {
+        "Find log message templates whose rate spikes or that are new in the recent window"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path to the log file"
+                },
+                "recent": {
+                    "type": "string",
+                    "description": "Length of the recent window before the newest entry (default '10m'); earlier entries form the baseline"
+                },
+                "threshold": {
+                    "type": "number",
+                    "description": "Recent rate as a multiple of the baseline rate that counts as a spike (default 3)",
+                    "minimum": 1
+                },
+                "min_count": {
+                    "type": "integer",
+                    "description": "Recent occurrences a template needs to be flagged (default 3)",
+                    "minimum": 1
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum anomalies to return (default 20)"
+                },
+                "since": time_range::since_schema(),
+                "until": time_range::until_schema(),
+                "format": parsers::format_schema(&self.formats)
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        source: &dyn LogSource,
+    ) -> LogResult<ToolResult> {
+        let path = args["path"]
+            .as_str()
+            .ok_or_else(|| LogError::Other("missing 'path' argument".into()))?;
+        let format = args["format"].as_str();
+        let recent_arg = args["recent"].as_str().unwrap_or(DEFAULT_RECENT);
+        let recent = time_range::parse_duration(recent_arg)?;
+        if recent <= Duration::zero() {
+            return Err(LogError::Other("'recent' must be positive".into()));
+        }
+        let threshold = args["threshold"]
+            .as_f64()
+            .unwrap_or(DEFAULT_THRESHOLD)
+            .max(1.0);
+        let min_count = args["min_count"]
+            .as_u64()
+            .unwrap_or(DEFAULT_MIN_COUNT)
+            .max(1);
+        let limit = args["limit"].as_u64().map_or(DEFAULT_LIMIT, |l| l as usize);
+        let range = TimeRange::from_args(&args)?;
+
+        let lines = source.read_lines(path).await?;
+        let fmt = parsers::resolve_format(format, &lines, &self.formats)?;
+        let mut entries = fmt.parse(&lines);
+        time_range::retain(&mut entries, range.as_ref());
+
+        let timed: Vec<(DateTime<Utc>, _)> = entries
+            .iter()
+            .filter_map(|e| Some((e.timestamp?, e)))
+            .collect();
+        let (Some(first), Some(last)) = (
+            timed.iter().map(|(ts, _)| *ts).min(),
+            timed.iter().map(|(ts, _)| *ts).max(),
+        ) else {
+            return Ok(ToolResult::success(
+                "detect_anomalies",
+                json!({
+                    "path": path,
+                    "format": fmt.name(),
+                    "total_lines": lines.len(),
+                    "parsed_entries": entries.len(),
+                    "timestamped_entries": 0,
+                    "anomalies": [],
+                }),
+                format!("No timestamped entries in {path}: nothing to compare"),
+            ));
+        };
+        let split = last - recent;
+
+        let mut miner = TemplateMiner::default();
+        let mut counts: Vec<Counts> = Vec::new();
+        let mut unclustered = 0usize;
+        for (ts, entry) in &timed {
+            let Some(i) = miner.add(&entry.message) else {
+                unclustered += 1;
+                continue;
+            };
+            if i == counts.len() {
+                counts.push(Counts::default());
+            }
+            let c = &mut counts[i];
+            if *ts > split {
+                c.recent += 1;
+                c.severity = c.severity.max(Some(entry.severity));
+                c.example = Some(entry.message.clone());
+            } else {
+                c.baseline += 1;
+            }
+        }
+
+        let baseline_span = split - first;
+        let has_baseline = first <= split;
+        let mut anomalies: Vec<(f64, Value)> = Vec::new();
+        if has_baseline {
+            for (i, c) in counts.iter().enumerate() {
+                if c.recent < min_count {
+                    continue;
+                }
+                let recent_rate = per_minute(c.recent, recent);
+                let baseline_rate = per_minute(c.baseline, baseline_span);
+                let (kind, ratio) = if c.baseline == 0 {
+                    ("new", None)
+                } else {
+                    let ratio = round2(recent_rate / baseline_rate.max(0.01));
+                    if ratio < threshold {
+                        continue;
+                    }
+                    ("spike", Some(ratio))
+                };
+                anomalies.push((
+                    ratio.unwrap_or(f64::INFINITY),
+                    json!({
+                        "template": miner.template(i).text(),
+                        "kind": kind,
+                        "recent_count": c.recent,
+                        "baseline_count": c.baseline,
+                        "recent_per_min": recent_rate,
+                        "baseline_per_min": baseline_rate,
+                        "ratio": ratio,
+                        "severity": c.severity,
+                        "example": c.example,
+                    }),
+                ));
+            }
+        }
+        anomalies.sort_by(|a, b| {
+            b.0.total_cmp(&a.0).then_with(|| {
+                b.1["recent_count"]
+                    .as_u64()
+                    .cmp(&a.1["recent_count"].as_u64())
+            })
+        });
+        let found = anomalies.len();
+        let anomalies: Vec<Value> = anomalies.into_iter().take(limit).map(|(_, v)| v).collect();
+
+        let mut data = json!({
+            "path": path,
+            "format": fmt.name(),
+            "total_lines": lines.len(),
+            "parsed_entries": entries.len(),
+            "timestamped_entries": timed.len(),
+            "templates": miner.len(),
+            "unclustered": unclustered,
+            "recent": recent_arg,
+            "threshold": threshold,
+            "min_count": min_count,
+            "baseline_window": {"start": first, "end": split},
+            "recent_window": {"start": split, "end": last},
+            "total_anomalies": found,
+            "anomalies": anomalies,
+        });
+        if let Some(range) = &range {
+            data["time_filter"] = range.to_json();
+        }
+
+        let summary = if !has_baseline {
+            format!(
+                "No baseline: every entry in {path} falls within the last {recent_arg}; widen the time range or shorten 'recent'"
+            )
+        } else if found == 0 {
+            format!(
+                "No anomalies in the last {recent_arg} of {path} ({} templates from {} entries)",
+                miner.len(),
+                timed.len()
+            )
+        } else {
+            format!(
+                "{found} anomalous templates in the last {recent_arg} of {path}; top: {}",
+                anomalies[0]["template"].as_str().unwrap_or_default()
+            )
+        };
+
+        Ok(ToolResult::success("detect_anomalies", data, summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockLogSource;
+
+    /// JSON lines: a steady heartbeat for an hour, then a burst of
+    /// reconnects and a new error in the last five minutes.
+    fn spiking_source() -> MockLogSource {
+        let start = DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let line = |minute: i64, level: &str, msg: String| {
+            let ts = (start + Duration::minutes(minute)).to_rfc3339();
+            json!({"timestamp": ts, "level": level, "message": msg}).to_string()
+        };
+        let mut lines = Vec::new();
+        for m in 0..60 {
+            lines.push(line(m, "info", format!("heartbeat sent seq={m}")));
+            if m % 10 == 0 {
+                lines.push(line(m, "info", format!("mqtt reconnect attempt {m}")));
+            }
+        }
+        for m in 55..60 {
+            for attempt in 0..4 {
+                lines.push(line(m, "warn", format!("mqtt reconnect attempt {attempt}")));
+            }
+            lines.push(line(
+                m,
+                "error",
+                format!("CAN bus-off on can0 after {m} frames"),
+            ));
+        }
+        let mut source = MockLogSource::new();
+        source.add_file("/var/log/app.json", lines);
+        source
+    }
+
+    #[tokio::test]
+    async fn flags_spiking_and_new_templates() {
+        let source = spiking_source();
+        let result = DetectAnomalies::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "recent": "5m"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        let data = result.data.unwrap();
+        let anomalies = data["anomalies"].as_array().unwrap();
+        assert_eq!(anomalies.len(), 2, "{anomalies:?}");
+
+        assert_eq!(anomalies[0]["kind"], "new");
+        assert_eq!(
+            anomalies[0]["template"],
+            "CAN bus-off on <*> after <*> frames"
+        );
+        assert_eq!(anomalies[0]["severity"], "error");
+
+        assert_eq!(anomalies[1]["kind"], "spike");
+        assert_eq!(anomalies[1]["template"], "mqtt reconnect attempt <*>");
+        assert_eq!(anomalies[1]["recent_count"], 20);
+        assert!(anomalies[1]["ratio"].as_f64().unwrap() >= 3.0);
+
+        // The steady heartbeat is not flagged.
+        assert!(result.summary.unwrap().starts_with("2 anomalous templates"));
+        assert_eq!(data["templates"], 3);
+    }
+
+    #[tokio::test]
+    async fn thresholds_and_missing_baseline() {
+        let source = spiking_source();
+        let strict = DetectAnomalies::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "recent": "5m", "threshold": 1000, "min_count": 6}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert_eq!(strict.data.unwrap()["total_anomalies"], 0);
+
+        let no_baseline = DetectAnomalies::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "recent": "2h"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert!(no_baseline.summary.unwrap().starts_with("No baseline"));
+
+        let invalid = DetectAnomalies::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "recent": "soon"}),
+                &source,
+            )
+            .await;
+        assert!(invalid.is_err());
+    }
+}
@@ -1,15 +1,42 @@
 //! log_stats — compute log statistics: severity counts, time range, top sources.
+//!
+//! Besides whole-file aggregates the result carries a `histogram`: entries
+//! counted by severity per minute, hour or day, with each bucket's top
+//! sources, so an "errors over time" chart needs a single tool call.
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
 use crate::error::{LogError, LogResult};
-use crate::parsers;
+use crate::parsers::{self, CustomFormats};
 use crate::source::LogSource;
-use crate::types::{LogFormat, LogSeverity, LogTool, ToolResult};
+use crate::time_range::{self, TimeRange};
+use crate::types::{LogEntry, LogSeverity, LogTool, ToolResult};
 
-pub struct LogStats;
+/// Most histogram buckets in one result, keeping it well under the MQTT
+/// payload limit. Auto bucketing picks the finest width that fits.
+const MAX_BUCKETS: i64 = 240;
+
+/// Sources listed per bucket unless `bucket_top_sources` says otherwise.
+const DEFAULT_BUCKET_TOP_SOURCES: usize = 3;
+
+/// Histogram bucket widths: name and seconds.
+const BUCKET_SIZES: &[(&str, i64)] = &[("minute", 60), ("hour", 3600), ("day", 86_400)];
+
+#[derive(Default)]
+pub struct LogStats {
+    formats: Arc<CustomFormats>,
+}
+
+impl LogStats {
+    /// Also accept the device's custom log formats.
+    pub fn with_formats(formats: Arc<CustomFormats>) -> Self {
+        Self { formats }
+    }
+}
 
 #[async_trait]
 impl LogTool for LogStats {
@@ -29,10 +56,17 @@ impl LogTool for LogStats {
                     "type": "string",
                     "description": "Path to the log file"
                 },
-                "format": {
+                "since": time_range::since_schema(),
+                "until": time_range::until_schema(),
+                "format": parsers::format_schema(&self.formats),
+                "bucket": {
                     "type": "string",
-                    "enum": ["syslog_3164", "syslog_5424", "journald", "json_lines", "plaintext"],
-                    "description": "Log format (auto-detected if omitted)"
+                    "enum": ["auto", "minute", "hour", "day"],
+                    "description": "Histogram bucket width (default auto: the finest giving at most 240 buckets; otherwise the newest 240)"
+                },
+                "bucket_top_sources": {
+                    "type": "integer",
+                    "description": "Top sources listed per histogram bucket (default 3, 0-10)"
                 }
             },
             "required": ["path"]
@@ -47,21 +81,30 @@ impl LogTool for LogStats {
         let path = args["path"]
             .as_str()
             .ok_or_else(|| LogError::Other("missing 'path' argument".into()))?;
-        let format = args["format"]
-            .as_str()
-            .map(|s| match s {
-                "syslog_3164" => Ok(LogFormat::Syslog3164),
-                "syslog_5424" => Ok(LogFormat::Syslog5424),
-                "journald" => Ok(LogFormat::Journald),
-                "json_lines" => Ok(LogFormat::JsonLines),
-                "plaintext" => Ok(LogFormat::Plaintext),
-                other => Err(LogError::Format(format!("unknown format: {other}"))),
-            })
-            .transpose()?;
+        let format = args["format"].as_str();
+        let range = TimeRange::from_args(&args)?;
+        let bucket = args["bucket"].as_str().unwrap_or("auto");
+        if bucket != "auto" && !BUCKET_SIZES.iter().any(|(name, _)| *name == bucket) {
+            return Err(LogError::Other(format!(
+                "unknown bucket '{bucket}' (expected auto, minute, hour or day)"
+            )));
+        }
+        let bucket_top_sources = match args.get("bucket_top_sources") {
+            None | Some(serde_json::Value::Null) => DEFAULT_BUCKET_TOP_SOURCES,
+            Some(v) => match v.as_u64() {
+                Some(n) if n <= 10 => n as usize,
+                _ => {
+                    return Err(LogError::Other(
+                        "'bucket_top_sources' must be an integer from 0 to 10".into(),
+                    ));
+                }
+            },
+        };
 
         let lines = source.read_lines(path).await?;
-        let fmt = format.unwrap_or_else(|| parsers::detect_format(&lines));
-        let entries = parsers::parse_lines(&lines, fmt);
+        let fmt = parsers::resolve_format(format, &lines, &self.formats)?;
+        let mut entries = fmt.parse(&lines);
+        time_range::retain(&mut entries, range.as_ref());
 
         // Severity counts
         let mut severity_counts: HashMap<LogSeverity, usize> = HashMap::new();
@@ -77,13 +120,21 @@ impl LogTool for LogStats {
             }
         }
         let mut top_sources: Vec<_> = source_counts.into_iter().collect();
-        top_sources.sort_by(|a, b| b.1.cmp(&a.1));
+        top_sources.sort_by_key(|s| std::cmp::Reverse(s.1));
         top_sources.truncate(10);
 
         // Time range
         let timestamps: Vec<_> = entries.iter().filter_map(|e| e.timestamp).collect();
         let earliest = timestamps.iter().min();
         let latest = timestamps.iter().max();
+        let histogram = match (earliest, latest) {
+            (Some(first), Some(last)) => {
+                histogram(&entries, bucket, *first, *last, bucket_top_sources)
+            }
+            _ => {
+                json!({"bucket": null, "bucket_secs": null, "buckets": [], "untimed_entries": entries.len(), "earlier_entries": 0})
+            }
+        };
 
         let total = entries.len();
         let error_count = severity_counts
@@ -95,19 +146,12 @@ impl LogTool for LogStats {
                 .copied()
                 .unwrap_or(0);
 
-        let data = json!({
+        let mut data = json!({
             "path": path,
-            "format": format!("{fmt:?}"),
+            "format": fmt.name(),
             "total_lines": lines.len(),
             "parsed_entries": total,
-            "severity_counts": {
-                "critical": severity_counts.get(&LogSeverity::Critical).copied().unwrap_or(0),
-                "error": severity_counts.get(&LogSeverity::Error).copied().unwrap_or(0),
-                "warning": severity_counts.get(&LogSeverity::Warning).copied().unwrap_or(0),
-                "notice": severity_counts.get(&LogSeverity::Notice).copied().unwrap_or(0),
-                "info": severity_counts.get(&LogSeverity::Info).copied().unwrap_or(0),
-                "debug": severity_counts.get(&LogSeverity::Debug).copied().unwrap_or(0),
-            },
+            "severity_counts": severity_json(&severity_counts),
             "time_range": {
                 "earliest": earliest,
                 "latest": latest,
@@ -116,7 +160,11 @@ impl LogTool for LogStats {
                 "source": src,
                 "count": count,
             })).collect::<Vec<_>>(),
+            "histogram": histogram,
         });
+        if let Some(range) = &range {
+            data["time_filter"] = range.to_json();
+        }
 
         Ok(ToolResult::success(
             "log_stats",
@@ -126,6 +174,95 @@ impl LogTool for LogStats {
     }
 }
 
+/// Counts of every severity, zeros included.
+fn severity_json(counts: &HashMap<LogSeverity, usize>) -> serde_json::Value {
+    let count = |s| counts.get(&s).copied().unwrap_or(0);
+    json!({
+        "critical": count(LogSeverity::Critical),
+        "error": count(LogSeverity::Error),
+        "warning": count(LogSeverity::Warning),
+        "notice": count(LogSeverity::Notice),
+        "info": count(LogSeverity::Info),
+        "debug": count(LogSeverity::Debug),
+    })
+}
+
+/// One histogram bucket: counts by severity and by source.
+type Bucket<'a> = (HashMap<LogSeverity, usize>, HashMap<&'a str, usize>);
+
+/// Severity counts and top sources per `bucket` from `first` to `last`,
+/// empty buckets included so charts get a continuous axis. Only the newest
+/// [`MAX_BUCKETS`] are kept; older entries (e.g. stamped 1970 by a device
+/// without a clock) are counted as `earlier_entries`.
+fn histogram(
+    entries: &[LogEntry],
+    bucket: &str,
+    first: DateTime<Utc>,
+    last: DateTime<Utc>,
+    top_sources: usize,
+) -> serde_json::Value {
+    let bucket_count =
+        |secs: i64| (last.timestamp().div_euclid(secs) - first.timestamp().div_euclid(secs)) + 1;
+    let (name, secs) = if bucket == "auto" {
+        BUCKET_SIZES
+            .iter()
+            .copied()
+            .find(|&(_, secs)| bucket_count(secs) <= MAX_BUCKETS)
+            .unwrap_or(BUCKET_SIZES[BUCKET_SIZES.len() - 1])
+    } else {
+        BUCKET_SIZES
+            .iter()
+            .copied()
+            .find(|(n, _)| *n == bucket)
+            .expect("bucket validated")
+    };
+
+    let end = last.timestamp().div_euclid(secs) + 1;
+    let start = end - bucket_count(secs).min(MAX_BUCKETS);
+    let mut buckets: BTreeMap<i64, Bucket> =
+        (start..end).map(|i| (i, Default::default())).collect();
+    let (mut untimed, mut earlier) = (0, 0);
+    for entry in entries {
+        let Some(ts) = entry.timestamp else {
+            untimed += 1;
+            continue;
+        };
+        let Some((severities, sources)) = buckets.get_mut(&ts.timestamp().div_euclid(secs)) else {
+            earlier += 1;
+            continue;
+        };
+        *severities.entry(entry.severity).or_default() += 1;
+        if let Some(src) = &entry.source {
+            *sources.entry(src.as_str()).or_default() += 1;
+        }
+    }
+
+    let buckets: Vec<_> = buckets
+        .into_iter()
+        .map(|(i, (severities, sources))| {
+            let mut sources: Vec<_> = sources.into_iter().collect();
+            sources.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            sources.truncate(top_sources);
+            json!({
+                "start": DateTime::from_timestamp(i * secs, 0),
+                "total": severities.values().sum::<usize>(),
+                "severity_counts": severity_json(&severities),
+                "top_sources": sources.iter().map(|(src, count)| json!({
+                    "source": src,
+                    "count": count,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    json!({
+        "bucket": name,
+        "bucket_secs": secs,
+        "buckets": buckets,
+        "untimed_entries": untimed,
+        "earlier_entries": earlier,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,7 +271,7 @@ mod tests {
     #[tokio::test]
     async fn stats_syslog() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = LogStats;
+        let tool = LogStats::default();
         let result = tool
             .execute(json!({"path": "/var/log/syslog"}), &source)
             .await
@@ -148,7 +285,7 @@ mod tests {
     #[tokio::test]
     async fn stats_json() {
         let source = MockLogSource::with_json_sample();
-        let tool = LogStats;
+        let tool = LogStats::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.json"}), &source)
             .await
@@ -162,7 +299,7 @@ mod tests {
     #[tokio::test]
     async fn stats_journald() {
         let source = MockLogSource::with_journald_sample();
-        let tool = LogStats;
+        let tool = LogStats::default();
         let result = tool
             .execute(json!({"path": "/var/log/journal.export"}), &source)
             .await
@@ -176,7 +313,7 @@ mod tests {
     async fn stats_empty_file() {
         let mut source = MockLogSource::new();
         source.add_file("/empty.log", vec![]);
-        let tool = LogStats;
+        let tool = LogStats::default();
         let result = tool
             .execute(json!({"path": "/empty.log"}), &source)
             .await
@@ -189,7 +326,7 @@ mod tests {
     #[tokio::test]
     async fn stats_has_time_range() {
         let source = MockLogSource::with_json_sample();
-        let tool = LogStats;
+        let tool = LogStats::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.json"}), &source)
             .await
@@ -202,7 +339,7 @@ mod tests {
     #[tokio::test]
     async fn stats_top_sources_sorted() {
         let source = MockLogSource::with_json_sample();
-        let tool = LogStats;
+        let tool = LogStats::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.json"}), &source)
             .await
@@ -218,4 +355,105 @@ mod tests {
             assert!(w[0] >= w[1], "sources should be sorted by count descending");
         }
     }
+
+    #[tokio::test]
+    async fn stats_within_time_range() {
+        let source = MockLogSource::with_json_sample();
+        let result = LogStats::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "until": "2024-01-15T12:00:10Z"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["total_lines"], 8);
+        assert_eq!(data["parsed_entries"], 3);
+        assert_eq!(data["severity_counts"]["error"], 1);
+        assert_eq!(data["time_range"]["latest"], "2024-01-15T12:00:10Z");
+        assert_eq!(data["time_filter"]["until"], "2024-01-15T12:00:10Z");
+    }
+
+    #[tokio::test]
+    async fn stats_histogram_by_minute() {
+        let mut source = MockLogSource::new();
+        source.add_file(
+            "/var/log/app.json",
+            [
+                ("2024-01-15T12:00:05Z", "error", "can"),
+                ("2024-01-15T12:00:40Z", "error", "can"),
+                ("2024-01-15T12:00:50Z", "info", "gps"),
+                ("2024-01-15T12:03:10Z", "warning", "gps"),
+            ]
+            .iter()
+            .map(|(ts, level, src)| {
+                format!(
+                    r#"{{"timestamp":"{ts}","level":"{level}","source":"{src}","message":"m"}}"#
+                )
+            })
+            .collect(),
+        );
+        let result = LogStats::default()
+            .execute(json!({"path": "/var/log/app.json"}), &source)
+            .await
+            .unwrap();
+        let histogram = &result.data.unwrap()["histogram"];
+        assert_eq!(histogram["bucket"], "minute");
+        assert_eq!(histogram["bucket_secs"], 60);
+        let buckets = histogram["buckets"].as_array().unwrap();
+        // 12:00 through 12:03, the empty minutes included.
+        assert_eq!(buckets.len(), 4);
+        assert_eq!(buckets[0]["start"], "2024-01-15T12:00:00Z");
+        assert_eq!(buckets[0]["total"], 3);
+        assert_eq!(buckets[0]["severity_counts"]["error"], 2);
+        assert_eq!(buckets[0]["top_sources"][0]["source"], "can");
+        assert_eq!(buckets[1]["total"], 0);
+        assert_eq!(buckets[3]["severity_counts"]["warning"], 1);
+
+        let result = LogStats::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "bucket": "hour", "bucket_top_sources": 1}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let buckets = result.data.unwrap()["histogram"]["buckets"].clone();
+        assert_eq!(buckets.as_array().unwrap().len(), 1);
+        assert_eq!(buckets[0]["total"], 4);
+        assert_eq!(buckets[0]["top_sources"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stats_histogram_bounds() {
+        let source = MockLogSource::with_json_sample();
+        for args in [
+            json!({"path": "/var/log/app.json", "bucket": "week"}),
+            json!({"path": "/var/log/app.json", "bucket_top_sources": 50}),
+        ] {
+            assert!(LogStats::default().execute(args, &source).await.is_err());
+        }
+
+        let mut source = MockLogSource::new();
+        source.add_file(
+            "/a.json",
+            vec![
+                r#"{"timestamp":"2024-01-01T00:00:00Z","level":"info","message":"a"}"#.into(),
+                r#"{"timestamp":"2024-03-01T00:00:00Z","level":"info","message":"b"}"#.into(),
+            ],
+        );
+        let result = LogStats::default()
+            .execute(json!({"path": "/a.json", "bucket": "minute"}), &source)
+            .await
+            .unwrap();
+        // The newest 240 minutes; January's entry is before them.
+        let histogram = &result.data.unwrap()["histogram"];
+        assert_eq!(histogram["buckets"].as_array().unwrap().len(), 240);
+        assert_eq!(histogram["buckets"][239]["start"], "2024-03-01T00:00:00Z");
+        assert_eq!(histogram["earlier_entries"], 1);
+        let result = LogStats::default()
+            .execute(json!({"path": "/a.json"}), &source)
+            .await
+            .unwrap();
+        assert_eq!(result.data.unwrap()["histogram"]["bucket"], "day");
+    }
 }
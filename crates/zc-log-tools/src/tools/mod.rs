@@ -1,29 +1,47 @@
 //! Log analysis tool implementations.
 //!
-//! 5 tools: search_logs, analyze_errors, log_stats, tail_logs, query_journal.
+//! 8 tools: search_logs, analyze_errors, log_stats, tail_logs, query_journal,
+//! detect_anomalies, correlate_faults, upload_logs.
 
 pub mod analyze_errors;
+pub mod correlate_faults;
+pub mod detect_anomalies;
 pub mod log_stats;
 pub mod query_journal;
 pub mod search_logs;
 pub mod tail_logs;
+pub mod upload_logs;
 
 pub use analyze_errors::AnalyzeErrors;
+pub use correlate_faults::CorrelateFaults;
+pub use detect_anomalies::DetectAnomalies;
 pub use log_stats::LogStats;
 pub use query_journal::QueryJournal;
 pub use search_logs::SearchLogs;
 pub use tail_logs::TailLogs;
+pub use upload_logs::UploadLogs;
 
+use std::sync::Arc;
+
+use crate::parsers::CustomFormats;
 use crate::types::LogTool;
 
 /// Return all available log analysis tools.
 pub fn all_tools() -> Vec<Box<dyn LogTool>> {
+    with_custom_formats(Arc::default())
+}
+
+/// All tools, with the file-based ones also accepting `formats`.
+pub fn with_custom_formats(formats: Arc<CustomFormats>) -> Vec<Box<dyn LogTool>> {
     vec![
-        Box::new(SearchLogs),
-        Box::new(AnalyzeErrors),
-        Box::new(LogStats),
-        Box::new(TailLogs),
-        Box::new(QueryJournal),
+        Box::new(SearchLogs::with_formats(formats.clone())),
+        Box::new(AnalyzeErrors::with_formats(formats.clone())),
+        Box::new(LogStats::with_formats(formats.clone())),
+        Box::new(TailLogs::with_formats(formats.clone())),
+        Box::new(QueryJournal::default()),
+        Box::new(DetectAnomalies::with_formats(formats.clone())),
+        Box::new(CorrelateFaults::with_formats(formats)),
+        Box::new(UploadLogs::default()),
     ]
 }
 
@@ -65,7 +83,28 @@ mod tests {
 
     #[test]
     fn all_tools_count() {
-        assert_eq!(all_tools().len(), 5);
+        assert_eq!(all_tools().len(), 8);
+    }
+
+    #[test]
+    fn custom_formats_appear_in_schemas() {
+        let formats = CustomFormats::compile(&[crate::parsers::CustomFormatConfig {
+            name: "gateway".into(),
+            pattern: r"^GW\|(?P<message>.*)$".into(),
+            priority: 0,
+            timestamp_format: None,
+            severity_map: Default::default(),
+        }])
+        .unwrap();
+        let with_format = with_custom_formats(Arc::new(formats))
+            .iter()
+            .filter(|t| {
+                t.parameters_schema()["properties"]["format"]["enum"]
+                    .as_array()
+                    .is_some_and(|names| names.iter().any(|n| n == "gateway"))
+            })
+            .count();
+        assert_eq!(with_format, 6);
     }
 
     #[test]
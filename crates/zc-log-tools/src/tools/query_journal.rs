@@ -1,33 +1,35 @@
-//! query_journal — Query systemd journal for a service unit via journalctl subprocess.
+//! query_journal — Query the systemd journal for a service unit.
 //!
-//! Unlike the other 4 log tools, this bypasses `LogSource` and runs `journalctl`
-//! directly as a child process. Output is parsed via the existing journald export
-//! parser.
+//! Unlike the other log tools, this bypasses `LogSource`: entries come from
+//! a [`JournalSource`], by default the live journal via `journalctl`
+//! ([`JournaldSource`]), filtered by unit, priority, time and boot. Output is
+//! parsed via the existing journald export parser.
 
 use async_trait::async_trait;
 use serde_json::json;
-use std::time::Duration;
-use tokio::process::Command;
+use std::sync::Arc;
 
 use crate::error::LogResult;
+use crate::journal::{JournalQuery, JournalSource, JournaldSource, PRIORITIES};
 use crate::parsers::journald;
 use crate::source::LogSource;
 use crate::types::{LogTool, ToolResult};
 
-/// Maximum output size from journalctl (64 KB).
-const MAX_OUTPUT_BYTES: usize = 64 * 1024;
-
-/// Subprocess timeout.
-const TIMEOUT: Duration = Duration::from_secs(5);
+pub struct QueryJournal {
+    journal: Arc<dyn JournalSource>,
+}
 
-pub struct QueryJournal;
+impl Default for QueryJournal {
+    fn default() -> Self {
+        Self::with_source(Arc::new(JournaldSource))
+    }
+}
 
-/// Validate a systemd unit name: only alphanumeric, `.`, `@`, `-`, `_`.
-fn is_valid_unit_name(name: &str) -> bool {
-    !name.is_empty()
-        && name
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '@' | '-' | '_'))
+impl QueryJournal {
+    /// Read entries from `journal` instead of the live journal.
+    pub fn with_source(journal: Arc<dyn JournalSource>) -> Self {
+        Self { journal }
+    }
 }
 
 #[async_trait]
@@ -55,12 +57,16 @@ impl LogTool for QueryJournal {
                 },
                 "priority": {
                     "type": "string",
-                    "enum": ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"],
+                    "enum": PRIORITIES,
                     "description": "Maximum syslog priority level to include"
                 },
                 "since": {
                     "type": "string",
                     "description": "Show entries since this time (e.g. '1 hour ago', '2024-01-15')"
+                },
+                "boot": {
+                    "type": "string",
+                    "description": "Only this boot: offset ('0' current, '-1' previous) or boot ID"
                 }
             },
             "required": ["unit"]
@@ -82,72 +88,23 @@ impl LogTool for QueryJournal {
             }
         };
 
-        if !is_valid_unit_name(unit) {
-            return Ok(ToolResult::failure(
-                "query_journal",
-                format!("invalid unit name: {unit}"),
-            ));
-        }
-
-        let lines = args["lines"].as_u64().unwrap_or(50);
-        let priority = args["priority"].as_str();
-        let since = args["since"].as_str();
-
-        let mut cmd = Command::new("journalctl");
-        cmd.arg("--output=export")
-            .arg("--no-pager")
-            .arg(format!("--lines={lines}"))
-            .arg(format!("--unit={unit}"));
-
-        if let Some(p) = priority {
-            cmd.arg(format!("--priority={p}"));
-        }
-        if let Some(s) = since {
-            // Validate since: same charset as unit + spaces and colons for timestamps
-            if s.chars()
-                .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | ':' | '.' | '/'))
-            {
-                cmd.arg(format!("--since={s}"));
-            }
-        }
-
-        let result = match tokio::time::timeout(TIMEOUT, cmd.output()).await {
-            Ok(Ok(output)) => output,
-            Ok(Err(e)) => {
-                return Ok(ToolResult::failure(
-                    "query_journal",
-                    format!("failed to run journalctl: {e}"),
-                ));
-            }
-            Err(_) => {
-                return Ok(ToolResult::failure(
-                    "query_journal",
-                    "journalctl timed out after 5s",
-                ));
-            }
+        let mut query = JournalQuery::new(unit);
+        query.lines = args["lines"].as_u64().unwrap_or(50);
+        query.priority = args["priority"].as_str().map(String::from);
+        query.since = args["since"].as_str().map(String::from);
+        // Accept `"boot": -1` as well as `"boot": "-1"`.
+        query.boot = match &args["boot"] {
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            other => other.as_str().map(String::from),
         };
-
-        if !result.status.success() {
-            let stderr = String::from_utf8_lossy(&result.stderr);
-            return Ok(ToolResult::failure(
-                "query_journal",
-                format!(
-                    "journalctl exited with {}: {}",
-                    result.status,
-                    stderr.trim()
-                ),
-            ));
+        if let Err(e) = query.validate() {
+            return Ok(ToolResult::failure("query_journal", e.to_string()));
         }
 
-        // Cap output size
-        let stdout = if result.stdout.len() > MAX_OUTPUT_BYTES {
-            &result.stdout[..MAX_OUTPUT_BYTES]
-        } else {
-            &result.stdout
+        let output_lines = match self.journal.query(&query).await {
+            Ok(lines) => lines,
+            Err(e) => return Ok(ToolResult::failure("query_journal", e.to_string())),
         };
-
-        let raw = String::from_utf8_lossy(stdout);
-        let output_lines: Vec<String> = raw.lines().map(|l| l.to_string()).collect();
         let entries = journald::parse_entries(&output_lines);
 
         let entry_json: Vec<serde_json::Value> = entries
@@ -163,11 +120,14 @@ impl LogTool for QueryJournal {
             .collect();
 
         let count = entry_json.len();
-        let data = json!({
+        let mut data = json!({
             "unit": unit,
             "entries": entry_json,
             "entry_count": count,
         });
+        if let Some(boot) = &query.boot {
+            data["boot"] = json!(boot);
+        }
 
         Ok(ToolResult::success(
             "query_journal",
@@ -180,11 +140,12 @@ impl LogTool for QueryJournal {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mock::MockLogSource;
+    use crate::journal::is_valid_unit_name;
+    use crate::mock::{MockJournalSource, MockLogSource};
 
     #[test]
     fn schema_has_required_unit() {
-        let tool = QueryJournal;
+        let tool = QueryJournal::default();
         let schema = tool.parameters_schema();
         assert!(schema["properties"]["unit"].is_object());
         let required = schema["required"].as_array().unwrap();
@@ -210,7 +171,7 @@ mod tests {
 
     #[tokio::test]
     async fn missing_unit_arg_returns_failure() {
-        let tool = QueryJournal;
+        let tool = QueryJournal::default();
         let source = MockLogSource::new();
         let result = tool.execute(json!({}), &source).await.unwrap();
         assert!(!result.success);
@@ -219,7 +180,7 @@ mod tests {
 
     #[tokio::test]
     async fn invalid_unit_returns_failure() {
-        let tool = QueryJournal;
+        let tool = QueryJournal::default();
         let source = MockLogSource::new();
         let result = tool
             .execute(json!({"unit": "$(whoami)"}), &source)
@@ -229,11 +190,46 @@ mod tests {
         assert!(result.error.as_ref().unwrap().contains("invalid unit name"));
     }
 
+    #[tokio::test]
+    async fn filters_reach_the_journal_source() {
+        let journal = Arc::new(MockJournalSource::with_sample());
+        let tool = QueryJournal::with_source(journal.clone());
+        let result = tool
+            .execute(
+                json!({"unit": "zeroclaw.service", "lines": 10, "priority": "err", "boot": -1}),
+                &MockLogSource::new(),
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["entry_count"], 3);
+        assert_eq!(data["boot"], "-1");
+
+        let queries = journal.queries();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].unit, "zeroclaw.service");
+        assert_eq!(queries[0].lines, 10);
+        assert_eq!(queries[0].priority.as_deref(), Some("err"));
+        assert_eq!(queries[0].boot.as_deref(), Some("-1"));
+
+        let result = tool
+            .execute(
+                json!({"unit": "zeroclaw.service", "boot": "yesterday"}),
+                &MockLogSource::new(),
+            )
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("invalid boot"));
+        assert_eq!(journal.queries().len(), 1);
+    }
+
     /// Integration test: runs real journalctl. Only passes on Linux with systemd.
     #[tokio::test]
     #[ignore] // Requires systemd — run with `cargo test -- --ignored`
     async fn live_journalctl_query() {
-        let tool = QueryJournal;
+        let tool = QueryJournal::default();
         let source = MockLogSource::new();
         let result = tool
             .execute(
@@ -1,15 +1,62 @@
 //! search_logs — regex search across log files with severity filtering.
+//!
+//! `path` is a file, a glob (`/var/log/*.log`) or an array of them; with
+//! `include_rotated` each file's rotated siblings (`syslog.1`,
+//! `syslog.2.gz`) are searched too. Every match names its file. `since` /
+//! `until` limit matches to a time window.
+//!
+//! `query` is a regex unless `regex: false` asks for a literal substring.
+//! Patterns are size-limited and cached ([`crate::pattern`]), capture groups
+//! are returned with each match, and a search stops once its time budget is
+//! spent, reporting `timed_out`.
 
 use async_trait::async_trait;
-use regex::Regex;
 use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::error::{LogError, LogResult};
-use crate::parsers;
+use crate::parsers::{self, CustomFormats};
+use crate::paths;
+use crate::pattern::{self, PatternCache};
 use crate::source::LogSource;
-use crate::types::{LogFormat, LogSeverity, LogTool, ToolResult};
+use crate::time_range::{self, TimeRange};
+use crate::types::{LogSeverity, LogTool, ToolResult};
 
-pub struct SearchLogs;
+/// Wall-clock limit for scanning files in one search.
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(10);
+
+pub struct SearchLogs {
+    formats: Arc<CustomFormats>,
+    patterns: PatternCache,
+    time_budget: Duration,
+}
+
+impl Default for SearchLogs {
+    fn default() -> Self {
+        Self {
+            formats: Arc::default(),
+            patterns: PatternCache::default(),
+            time_budget: DEFAULT_TIME_BUDGET,
+        }
+    }
+}
+
+impl SearchLogs {
+    /// Also accept the device's custom log formats.
+    pub fn with_formats(formats: Arc<CustomFormats>) -> Self {
+        Self {
+            formats,
+            ..Self::default()
+        }
+    }
+
+    /// Stop scanning after `budget` instead of [`DEFAULT_TIME_BUDGET`].
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = budget;
+        self
+    }
+}
 
 #[async_trait]
 impl LogTool for SearchLogs {
@@ -26,12 +73,25 @@ impl LogTool for SearchLogs {
             "type": "object",
             "properties": {
                 "path": {
-                    "type": "string",
-                    "description": "Path to the log file"
+                    "oneOf": [
+                        { "type": "string" },
+                        { "type": "array", "items": { "type": "string" } }
+                    ],
+                    "description": "Log file path or glob pattern (e.g. /var/log/*.log), or an array of them"
+                },
+                "include_rotated": {
+                    "type": "boolean",
+                    "description": "Also search rotated files (syslog.1, syslog.2.gz, ...), newest first",
+                    "default": false
                 },
                 "query": {
                     "type": "string",
-                    "description": "Regex pattern to search for"
+                    "description": "Regex pattern to search for (capture groups are returned per match)"
+                },
+                "regex": {
+                    "type": "boolean",
+                    "description": "Treat query as a regex; false matches it as a literal substring",
+                    "default": true
                 },
                 "severity": {
                     "type": "string",
@@ -43,11 +103,9 @@ impl LogTool for SearchLogs {
                     "description": "Maximum number of results (default: 100)",
                     "default": 100
                 },
-                "format": {
-                    "type": "string",
-                    "enum": ["syslog_3164", "syslog_5424", "journald", "json_lines", "plaintext"],
-                    "description": "Log format (auto-detected if omitted)"
-                }
+                "since": time_range::since_schema(),
+                "until": time_range::until_schema(),
+                "format": parsers::format_schema(&self.formats)
             },
             "required": ["path", "query"]
         })
@@ -58,9 +116,20 @@ impl LogTool for SearchLogs {
         args: serde_json::Value,
         source: &dyn LogSource,
     ) -> LogResult<ToolResult> {
-        let path = args["path"]
-            .as_str()
-            .ok_or_else(|| LogError::Other("missing 'path' argument".into()))?;
+        let specs: Vec<String> = match &args["path"] {
+            serde_json::Value::String(p) => vec![p.clone()],
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(|v| v.as_str().map(String::from))
+                .collect::<Option<_>>()
+                .filter(|v: &Vec<String>| !v.is_empty())
+                .ok_or_else(|| {
+                    LogError::Other(
+                        "'path' must be a string or a non-empty array of strings".into(),
+                    )
+                })?,
+            _ => return Err(LogError::Other("missing 'path' argument".into())),
+        };
         let query = args["query"]
             .as_str()
             .ok_or_else(|| LogError::Other("missing 'query' argument".into()))?;
@@ -69,51 +138,114 @@ impl LogTool for SearchLogs {
             .as_str()
             .map(parse_severity_arg)
             .transpose()?;
-        let format = args["format"].as_str().map(parse_format_arg).transpose()?;
+        let format = args["format"].as_str();
+        let include_rotated = args["include_rotated"].as_bool().unwrap_or(false);
+        let range = TimeRange::from_args(&args)?;
+        let use_regex = args["regex"].as_bool().unwrap_or(true);
 
-        let re = Regex::new(query).map_err(|e| LogError::Regex(e.to_string()))?;
+        let re = self.patterns.get(query, use_regex)?;
+        let files = paths::resolve(source, &specs, include_rotated).await?;
 
-        let lines = source.read_lines(path).await?;
-        let fmt = format.unwrap_or_else(|| parsers::detect_format(&lines));
-        let entries = parsers::parse_lines(&lines, fmt);
+        let deadline = Instant::now() + self.time_budget;
+        let mut timed_out = false;
+        let mut matches = Vec::new();
+        let mut per_file = Vec::new();
+        let mut formats: Vec<String> = Vec::new();
+        let mut total_lines = 0;
+        for file in &files {
+            if matches.len() >= limit {
+                break;
+            }
+            if Instant::now() >= deadline {
+                timed_out = true;
+                break;
+            }
+            let lines = source.read_lines(file).await?;
+            let fmt = parsers::resolve_format(format, &lines, &self.formats)?;
+            let mut entries = fmt.parse(&lines);
+            time_range::retain(&mut entries, range.as_ref());
+            total_lines += lines.len();
+            let format_name = fmt.name();
+            if !formats.contains(&format_name) {
+                formats.push(format_name.clone());
+            }
 
-        let matches: Vec<_> = entries
-            .iter()
-            .filter(|e| {
+            let before = matches.len();
+            for e in &entries {
+                if matches.len() >= limit {
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    timed_out = true;
+                    break;
+                }
                 if let Some(min) = min_severity
                     && e.severity < min
                 {
-                    return false;
+                    continue;
                 }
-                re.is_match(&e.message) || re.is_match(&e.raw)
-            })
-            .take(limit)
-            .map(|e| {
-                json!({
+                let text = if re.is_match(&e.message) {
+                    &e.message
+                } else if re.is_match(&e.raw) {
+                    &e.raw
+                } else {
+                    continue;
+                };
+                let mut m = json!({
+                    "file": file,
                     "line": e.line_number,
                     "severity": e.severity.as_str(),
                     "message": e.message,
                     "timestamp": e.timestamp,
                     "source": e.source,
-                })
-            })
-            .collect();
+                });
+                if let Some(groups) = pattern::captures(&re, text) {
+                    m["captures"] = groups.into();
+                }
+                matches.push(m);
+            }
+            per_file.push(json!({
+                "path": file,
+                "format": format_name,
+                "total_lines": lines.len(),
+                "match_count": matches.len() - before,
+            }));
+        }
 
         let match_count = matches.len();
-        let data = json!({
-            "path": path,
+        let format_name = match formats.as_slice() {
+            [one] => one.clone(),
+            _ => "mixed".to_string(),
+        };
+        let mut summary = match files.as_slice() {
+            [one] => format!("Found {match_count} matches for '{query}' in {one}"),
+            _ => format!(
+                "Found {match_count} matches for '{query}' across {} files",
+                files.len()
+            ),
+        };
+        if timed_out {
+            summary.push_str(" (stopped at the time budget)");
+        }
+        let mut data = json!({
+            "path": args["path"],
             "query": query,
-            "format": format!("{fmt:?}"),
-            "total_lines": lines.len(),
+            "format": format_name,
+            "total_lines": total_lines,
+            "files": per_file,
             "matches": matches,
             "match_count": match_count,
+            "regex": use_regex,
+            "timed_out": timed_out,
         });
+        if timed_out {
+            tracing::warn!(query, budget = ?self.time_budget, "search_logs stopped at its time budget");
+        }
+        if let Some(range) = &range {
+            data["time_filter"] = range.to_json();
+        }
 
-        Ok(ToolResult::success(
-            "search_logs",
-            data,
-            format!("Found {match_count} matches for '{query}' in {path}"),
-        ))
+        Ok(ToolResult::success("search_logs", data, summary))
     }
 }
 
@@ -129,17 +261,6 @@ fn parse_severity_arg(s: &str) -> LogResult<LogSeverity> {
     }
 }
 
-fn parse_format_arg(s: &str) -> LogResult<LogFormat> {
-    match s {
-        "syslog_3164" => Ok(LogFormat::Syslog3164),
-        "syslog_5424" => Ok(LogFormat::Syslog5424),
-        "journald" => Ok(LogFormat::Journald),
-        "json_lines" => Ok(LogFormat::JsonLines),
-        "plaintext" => Ok(LogFormat::Plaintext),
-        other => Err(LogError::Format(format!("unknown format: {other}"))),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,7 +269,7 @@ mod tests {
     #[tokio::test]
     async fn search_by_pattern() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = SearchLogs;
+        let tool = SearchLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/syslog", "query": "database"}),
@@ -166,7 +287,7 @@ mod tests {
     #[tokio::test]
     async fn search_with_severity_filter() {
         let source = MockLogSource::with_json_sample();
-        let tool = SearchLogs;
+        let tool = SearchLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/app.json", "query": ".*", "severity": "error"}),
@@ -188,7 +309,7 @@ mod tests {
     #[tokio::test]
     async fn search_with_limit() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = SearchLogs;
+        let tool = SearchLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/syslog", "query": ".*", "limit": 3}),
@@ -206,7 +327,7 @@ mod tests {
     #[tokio::test]
     async fn search_invalid_regex() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = SearchLogs;
+        let tool = SearchLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/syslog", "query": "[invalid"}),
@@ -219,7 +340,7 @@ mod tests {
     #[tokio::test]
     async fn search_missing_file() {
         let source = MockLogSource::new();
-        let tool = SearchLogs;
+        let tool = SearchLogs::default();
         let result = tool
             .execute(json!({"path": "/nonexistent", "query": "test"}), &source)
             .await;
@@ -229,7 +350,7 @@ mod tests {
     #[tokio::test]
     async fn search_json_format() {
         let source = MockLogSource::with_json_sample();
-        let tool = SearchLogs;
+        let tool = SearchLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/app.json", "query": "CAN bus"}),
@@ -243,4 +364,197 @@ mod tests {
             .unwrap();
         assert!(count >= 2, "should find CAN bus entries");
     }
+
+    #[tokio::test]
+    async fn search_glob_and_rotated_files() {
+        let mut source = MockLogSource::with_syslog_sample();
+        source.add_file(
+            "/var/log/syslog.1",
+            vec![
+                "<131>Jan 14 23:59:58 edge1 myapp[1234]: Failed to connect to database: timeout"
+                    .into(),
+            ],
+        );
+        source.add_file(
+            "/var/log/syslog.2.gz",
+            vec!["<134>Jan 13 08:00:00 edge1 myapp[1234]: database migration done".into()],
+        );
+        let tool = SearchLogs::default();
+
+        let result = tool
+            .execute(
+                json!({"path": "/var/log/syslog", "query": "database", "include_rotated": true}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        let files: Vec<_> = data["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "/var/log/syslog",
+                "/var/log/syslog.1",
+                "/var/log/syslog.2.gz"
+            ]
+        );
+        assert_eq!(data["files"][1]["match_count"], 1);
+        let last = data["matches"].as_array().unwrap().last().unwrap().clone();
+        assert_eq!(last["file"], "/var/log/syslog.2.gz");
+        assert!(result.summary.unwrap().contains("across 3 files"));
+
+        // A glob and a path list give the same files without rotation.
+        let result = tool
+            .execute(
+                json!({"path": "/var/log/syslog.*", "query": "database"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.data.unwrap()["files"].as_array().unwrap().len(), 2);
+        let result = tool
+            .execute(
+                json!({"path": ["/var/log/syslog.1", "/var/log/syslog.2.gz"], "query": "database", "limit": 1}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["match_count"], 1);
+        assert_eq!(data["files"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_unmatched_glob() {
+        let source = MockLogSource::with_syslog_sample();
+        let result = SearchLogs::default()
+            .execute(json!({"path": "/var/log/*.json", "query": "x"}), &source)
+            .await;
+        assert!(matches!(result, Err(LogError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn search_custom_format() {
+        let mut source = MockLogSource::new();
+        source.add_file(
+            "/var/log/gateway.log",
+            vec![
+                "GW|W|voltage 11.6".into(),
+                "GW|E|CAN bus timeout on can0".into(),
+                "GW|I|CAN bus restarted".into(),
+            ],
+        );
+        let formats = CustomFormats::compile(&[parsers::CustomFormatConfig {
+            name: "gateway".into(),
+            pattern: r"^GW\|(?P<severity>\w)\|(?P<message>.*)$".into(),
+            priority: 0,
+            timestamp_format: None,
+            severity_map: [("E".into(), LogSeverity::Error)].into(),
+        }])
+        .unwrap();
+        let tool = SearchLogs::with_formats(Arc::new(formats));
+
+        let args = json!({"path": "/var/log/gateway.log", "query": "CAN bus", "severity": "error"});
+        let result = tool.execute(args.clone(), &source).await.unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["format"], "gateway");
+        assert_eq!(data["match_count"], 1);
+        assert_eq!(data["matches"][0]["message"], "CAN bus timeout on can0");
+
+        // Selecting the format by name gives the same result.
+        let mut named = args;
+        named["format"] = json!("gateway");
+        let result = tool.execute(named, &source).await.unwrap();
+        assert_eq!(result.data.unwrap()["match_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn search_within_time_range() {
+        let source = MockLogSource::with_json_sample();
+        let result = SearchLogs::default()
+            .execute(
+                json!({
+                    "path": "/var/log/app.json",
+                    "query": "CAN bus",
+                    "since": "2024-01-15T12:00:10Z",
+                    "until": "2024-01-15T12:00:30Z",
+                }),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["match_count"], 1);
+        assert_eq!(data["matches"][0]["timestamp"], "2024-01-15T12:00:30Z");
+        assert_eq!(data["time_filter"]["since"], "2024-01-15T12:00:10Z");
+
+        let bad = SearchLogs::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "query": "x", "since": "last tuesday"}),
+                &source,
+            )
+            .await;
+        assert!(bad.is_err());
+    }
+
+    #[tokio::test]
+    async fn search_literal_and_capture_groups() {
+        let source = MockLogSource::with_syslog_sample();
+        let tool = SearchLogs::default();
+
+        // Metacharacters are literal with regex: false.
+        let result = tool
+            .execute(
+                json!({"path": "/var/log/syslog", "query": "(root)", "regex": false}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["match_count"], 1);
+        assert_eq!(data["regex"], false);
+        assert!(data["matches"][0].get("captures").is_none());
+
+        let result = tool
+            .execute(
+                json!({"path": "/var/log/syslog", "query": r"(?P<what>\w+): timeout after (\d+)ms"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["match_count"], 1);
+        assert_eq!(
+            data["matches"][0]["captures"],
+            json!({"what": "bus", "2": "500"})
+        );
+        assert_eq!(data["timed_out"], false);
+    }
+
+    #[tokio::test]
+    async fn search_stops_at_time_budget() {
+        let source = MockLogSource::with_syslog_sample();
+        let result = SearchLogs::default()
+            .with_time_budget(Duration::ZERO)
+            .execute(json!({"path": "/var/log/syslog", "query": ".*"}), &source)
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["timed_out"], true);
+        assert_eq!(data["match_count"], 0);
+        assert!(result.summary.unwrap().contains("time budget"));
+
+        let oversized = SearchLogs::default()
+            .execute(
+                json!({"path": "/var/log/syslog", "query": r"(?:\w{100}){100}"}),
+                &source,
+            )
+            .await;
+        assert!(matches!(oversized, Err(LogError::Regex(_))));
+    }
 }
@@ -1,14 +1,44 @@
 //! tail_logs — show the last N log entries with optional severity filtering.
+//!
+//! With `follow: true` on a streaming run the tail is the first chunk, then
+//! the log is polled through [`LogSource::read_from`] and only appended
+//! lines are parsed and streamed, for live debugging from the console.
 
 use async_trait::async_trait;
 use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::error::{LogError, LogResult};
-use crate::parsers;
+use crate::parsers::{self, CustomFormats};
 use crate::source::LogSource;
-use crate::types::{LogFormat, LogSeverity, LogTool, ToolResult};
+use crate::time_range::{self, TimeRange};
+use crate::types::{ChunkSink, LogEntry, LogSeverity, LogTool, ToolResult};
 
-pub struct TailLogs;
+/// Follow window when `follow_secs` is not given.
+const DEFAULT_FOLLOW_SECS: u64 = 10;
+
+/// Longest follow window (safety limit).
+const MAX_FOLLOW_SECS: u64 = 60;
+
+/// How often the file is polled for new lines while following.
+const FOLLOW_POLL: Duration = Duration::from_millis(500);
+
+/// Most entries in one streamed chunk, so a burst of lines stays well
+/// under the MQTT payload limit.
+const MAX_CHUNK_ENTRIES: usize = 200;
+
+#[derive(Default)]
+pub struct TailLogs {
+    formats: Arc<CustomFormats>,
+}
+
+impl TailLogs {
+    /// Also accept the device's custom log formats.
+    pub fn with_formats(formats: Arc<CustomFormats>) -> Self {
+        Self { formats }
+    }
+}
 
 #[async_trait]
 impl LogTool for TailLogs {
@@ -17,7 +47,7 @@ impl LogTool for TailLogs {
     }
 
     fn description(&self) -> &str {
-        "Show the last N log entries with optional severity filtering"
+        "Show the last N log entries with optional severity filtering; with follow, stream new entries for up to 60s"
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -38,10 +68,18 @@ impl LogTool for TailLogs {
                     "enum": ["debug", "info", "notice", "warning", "error", "critical"],
                     "description": "Minimum severity level to include"
                 },
-                "format": {
-                    "type": "string",
-                    "enum": ["syslog_3164", "syslog_5424", "journald", "json_lines", "plaintext"],
-                    "description": "Log format (auto-detected if omitted)"
+                "since": time_range::since_schema(),
+                "until": time_range::until_schema(),
+                "format": parsers::format_schema(&self.formats),
+                "follow": {
+                    "type": "boolean",
+                    "description": "Keep streaming new entries as they are written (streaming responses only)",
+                    "default": false
+                },
+                "follow_secs": {
+                    "type": "integer",
+                    "description": "How long to follow in seconds (max 60)",
+                    "default": 10
                 }
             },
             "required": ["path"]
@@ -52,6 +90,33 @@ impl LogTool for TailLogs {
         &self,
         args: serde_json::Value,
         source: &dyn LogSource,
+    ) -> LogResult<ToolResult> {
+        self.tail(args, source, None).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        source: &dyn LogSource,
+        sink: &ChunkSink<'_>,
+    ) -> LogResult<ToolResult> {
+        self.tail(args, source, Some(sink)).await
+    }
+}
+
+impl TailLogs {
+    /// Show the last entries. With a sink and `follow: true`, the initial
+    /// tail is emitted as the first chunk and new entries are emitted as the
+    /// file grows, until `follow_secs` elapses.
+    async fn tail(
+        &self,
+        args: serde_json::Value,
+        source: &dyn LogSource,
+        sink: Option<&ChunkSink<'_>>,
     ) -> LogResult<ToolResult> {
         let path = args["path"]
             .as_str()
@@ -69,59 +134,149 @@ impl LogTool for TailLogs {
                 other => Err(LogError::Other(format!("unknown severity: {other}"))),
             })
             .transpose()?;
-        let format = args["format"]
-            .as_str()
-            .map(|s| match s {
-                "syslog_3164" => Ok(LogFormat::Syslog3164),
-                "syslog_5424" => Ok(LogFormat::Syslog5424),
-                "journald" => Ok(LogFormat::Journald),
-                "json_lines" => Ok(LogFormat::JsonLines),
-                "plaintext" => Ok(LogFormat::Plaintext),
-                other => Err(LogError::Format(format!("unknown format: {other}"))),
-            })
-            .transpose()?;
+        let format = args["format"].as_str();
+        let follow = args["follow"].as_bool().unwrap_or(false);
+        let follow_secs = args["follow_secs"]
+            .as_u64()
+            .unwrap_or(DEFAULT_FOLLOW_SECS)
+            .min(MAX_FOLLOW_SECS);
+        let range = TimeRange::from_args(&args)?;
+
+        let following = follow && sink.is_some();
 
         // Read all lines — needed for multi-line formats (journald) and
-        // severity filtering (can't know how many raw lines to fetch)
-        let lines = source.read_lines(path).await?;
-        let fmt = format.unwrap_or_else(|| parsers::detect_format(&lines));
-        let entries = parsers::parse_lines(&lines, fmt);
-
-        // Apply severity filter
-        let filtered: Vec<_> = if let Some(min) = min_severity {
-            entries.iter().filter(|e| e.severity >= min).collect()
+        // severity filtering (can't know how many raw lines to fetch).
+        // Following starts from the offset this read ends at.
+        let (lines, mut offset) = if following {
+            let chunk = source.read_from(path, 0).await?;
+            (chunk.lines, chunk.offset)
         } else {
-            entries.iter().collect()
+            (source.read_lines(path).await?, 0)
         };
+        let fmt = parsers::resolve_format(format, &lines, &self.formats)?;
+        let entries = fmt.parse(&lines);
+
+        // Apply severity and time filters
+        let filtered: Vec<_> = entries
+            .iter()
+            .filter(|e| min_severity.is_none_or(|min| e.severity >= min))
+            .filter(|e| range.is_none_or(|r| r.contains(e)))
+            .collect();
 
         // Take the last `count` entries
         let start = filtered.len().saturating_sub(count);
         let tail = &filtered[start..];
+        let tail_json: Vec<serde_json::Value> = tail.iter().map(|e| entry_json(e)).collect();
+
+        let (Some(sink), true) = (sink, following) else {
+            let mut data = json!({
+                "path": path,
+                "format": fmt.name(),
+                "total_entries": entries.len(),
+                "filtered_entries": filtered.len(),
+                "shown": tail.len(),
+                "entries": tail_json,
+            });
+            if let Some(range) = &range {
+                data["time_filter"] = range.to_json();
+            }
+
+            let shown = tail.len();
+            return Ok(ToolResult::success(
+                "tail_logs",
+                data,
+                format!("Showing last {shown} entries from {path}"),
+            ));
+        };
+
+        let shown = tail_json.len();
+        sink(json!({ "entries": tail_json }));
+        let mut chunks = 1usize;
+        let mut followed = 0usize;
+        let mut rotations = 0usize;
+        // Line number of the last line read, for numbering new entries.
+        let mut seen_lines = lines.len();
+        let deadline = Instant::now() + Duration::from_secs(follow_secs);
+
+        while Instant::now() < deadline {
+            tokio::time::sleep(FOLLOW_POLL.min(deadline.saturating_duration_since(Instant::now())))
+                .await;
+            let chunk = match source.read_from(path, offset).await {
+                Ok(chunk) => chunk,
+                // Between a rotation's rename and the new file: wait for it.
+                Err(LogError::NotFound(_)) => {
+                    if offset != 0 {
+                        offset = 0;
+                        seen_lines = 0;
+                        rotations += 1;
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            offset = chunk.offset;
+            if chunk.reset {
+                // Truncated or rotated — numbering starts again from the top.
+                seen_lines = 0;
+                rotations += 1;
+            }
+            if chunk.lines.is_empty() {
+                continue;
+            }
+
+            let new: Vec<serde_json::Value> = fmt
+                .parse(&chunk.lines)
+                .into_iter()
+                .filter(|e| min_severity.is_none_or(|min| e.severity >= min))
+                .filter(|e| range.is_none_or(|r| r.contains(e)))
+                .map(|mut e| {
+                    e.line_number += seen_lines;
+                    entry_json(&e)
+                })
+                .collect();
+            seen_lines += chunk.lines.len();
+            followed += new.len();
+            for part in new.chunks(MAX_CHUNK_ENTRIES) {
+                sink(json!({ "entries": part }));
+                chunks += 1;
+            }
+        }
 
-        let data = json!({
+        let mut data = json!({
             "path": path,
-            "format": format!("{fmt:?}"),
+            "format": fmt.name(),
             "total_entries": entries.len(),
             "filtered_entries": filtered.len(),
-            "shown": tail.len(),
-            "entries": tail.iter().map(|e| json!({
-                "line": e.line_number,
-                "severity": e.severity.as_str(),
-                "message": e.message,
-                "timestamp": e.timestamp,
-                "source": e.source,
-            })).collect::<Vec<_>>(),
+            "shown": shown,
+            "entries": [],
+            "followed_entries": followed,
+            "follow_secs": follow_secs,
+            "chunks": chunks,
+            "rotations": rotations,
         });
-
-        let shown = tail.len();
+        if let Some(range) = &range {
+            data["time_filter"] = range.to_json();
+        }
         Ok(ToolResult::success(
             "tail_logs",
             data,
-            format!("Showing last {shown} entries from {path}"),
+            format!(
+                "Streamed last {shown} entries from {path}, then {followed} new in {follow_secs}s"
+            ),
         ))
     }
 }
 
+fn entry_json(e: &LogEntry) -> serde_json::Value {
+    json!({
+        "line": e.line_number,
+        "severity": e.severity.as_str(),
+        "message": e.message,
+        "timestamp": e.timestamp,
+        "source": e.source,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,7 +285,7 @@ mod tests {
     #[tokio::test]
     async fn tail_syslog() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(json!({"path": "/var/log/syslog", "count": 3}), &source)
             .await
@@ -143,7 +298,7 @@ mod tests {
     #[tokio::test]
     async fn tail_with_severity_filter() {
         let source = MockLogSource::with_syslog_sample();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/syslog", "count": 50, "severity": "error"}),
@@ -165,7 +320,7 @@ mod tests {
     #[tokio::test]
     async fn tail_json_logs() {
         let source = MockLogSource::with_json_sample();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.json", "count": 5}), &source)
             .await
@@ -177,7 +332,7 @@ mod tests {
     #[tokio::test]
     async fn tail_journald() {
         let source = MockLogSource::with_journald_sample();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(
                 json!({"path": "/var/log/journal.export", "count": 2}),
@@ -192,7 +347,7 @@ mod tests {
     #[tokio::test]
     async fn tail_more_than_available() {
         let source = MockLogSource::with_json_sample();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.json", "count": 1000}), &source)
             .await
@@ -206,7 +361,7 @@ mod tests {
     #[tokio::test]
     async fn tail_plaintext() {
         let source = MockLogSource::with_plaintext_sample();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(json!({"path": "/var/log/app.log", "count": 3}), &source)
             .await
@@ -218,10 +373,164 @@ mod tests {
     #[tokio::test]
     async fn tail_missing_file() {
         let source = MockLogSource::new();
-        let tool = TailLogs;
+        let tool = TailLogs::default();
         let result = tool
             .execute(json!({"path": "/nonexistent", "count": 10}), &source)
             .await;
         assert!(result.is_err());
     }
+
+    /// Log source whose file grows by one line on every read after the first.
+    struct GrowingSource {
+        reads: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl LogSource for GrowingSource {
+        async fn read_lines(&self, _path: &str) -> LogResult<Vec<String>> {
+            let mut reads = self.reads.lock().unwrap();
+            *reads += 1;
+            Ok((0..*reads + 1)
+                .map(|i| format!(r#"{{"level":"info","message":"line {i}"}}"#))
+                .collect())
+        }
+
+        async fn tail_lines(&self, path: &str, count: usize) -> LogResult<Vec<String>> {
+            let all = self.read_lines(path).await?;
+            Ok(all[all.len().saturating_sub(count)..].to_vec())
+        }
+
+        async fn exists(&self, _path: &str) -> bool {
+            true
+        }
+
+        async fn list_sources(&self) -> LogResult<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn follow_streams_initial_tail_and_new_entries() {
+        let source = GrowingSource {
+            reads: std::sync::Mutex::new(0),
+        };
+        let chunks = std::sync::Mutex::new(Vec::new());
+        let sink = |chunk: serde_json::Value| chunks.lock().unwrap().push(chunk);
+
+        let result = TailLogs::default()
+            .execute_streaming(
+                json!({"path": "/var/log/app.json", "follow": true, "follow_secs": 1}),
+                &source,
+                &sink,
+            )
+            .await
+            .unwrap();
+
+        let chunks = chunks.into_inner().unwrap();
+        assert!(chunks.len() >= 2, "expected initial + followed chunks");
+        assert_eq!(chunks[0]["entries"].as_array().unwrap().len(), 2);
+        assert_eq!(chunks[1]["entries"][0]["message"], "line 2");
+        assert_eq!(chunks[1]["entries"][0]["line"], 3);
+
+        let data = result.data.unwrap();
+        assert!(data["entries"].as_array().unwrap().is_empty());
+        assert_eq!(data["chunks"].as_u64().unwrap() as usize, chunks.len());
+        assert!(data["followed_entries"].as_u64().unwrap() >= 1);
+    }
+
+    #[tokio::test]
+    async fn follow_ignored_without_streaming() {
+        let source = MockLogSource::with_json_sample();
+        let result = TailLogs::default()
+            .execute(
+                json!({"path": "/var/log/app.json", "count": 3, "follow": true}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.data.unwrap()["shown"], 3);
+    }
+
+    #[tokio::test]
+    async fn tail_within_time_range() {
+        let source = MockLogSource::with_json_sample();
+        let result = TailLogs::default()
+            .execute(
+                json!({
+                    "path": "/var/log/app.json",
+                    "count": 2,
+                    "since": "2024-01-15T12:00:05Z",
+                    "until": "2024-01-15T12:00:20Z",
+                }),
+                &source,
+            )
+            .await
+            .unwrap();
+        let data = result.data.unwrap();
+        assert_eq!(data["filtered_entries"], 4);
+        let entries = data["entries"].as_array().unwrap();
+        assert_eq!(entries[0]["timestamp"], "2024-01-15T12:00:15Z");
+        assert_eq!(entries[1]["timestamp"], "2024-01-15T12:00:20Z");
+    }
+
+    /// Log source that gains 250 lines once, then loses its file.
+    struct BurstSource {
+        reads: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl LogSource for BurstSource {
+        async fn read_lines(&self, path: &str) -> LogResult<Vec<String>> {
+            let mut reads = self.reads.lock().unwrap();
+            *reads += 1;
+            match *reads {
+                1 => Ok(vec![r#"{"level":"info","message":"start"}"#.into()]),
+                2 => Ok((0..251)
+                    .map(|i| format!(r#"{{"level":"info","message":"burst {i}"}}"#))
+                    .collect()),
+                _ => Err(LogError::NotFound(path.into())),
+            }
+        }
+
+        async fn tail_lines(&self, path: &str, _count: usize) -> LogResult<Vec<String>> {
+            self.read_lines(path).await
+        }
+
+        async fn exists(&self, _path: &str) -> bool {
+            true
+        }
+
+        async fn list_sources(&self) -> LogResult<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn follow_splits_bursts_and_survives_rotation() {
+        let source = BurstSource {
+            reads: std::sync::Mutex::new(0),
+        };
+        let chunks = std::sync::Mutex::new(Vec::new());
+        let sink = |chunk: serde_json::Value| chunks.lock().unwrap().push(chunk);
+
+        let result = TailLogs::default()
+            .execute_streaming(
+                json!({"path": "/var/log/app.json", "follow": true, "follow_secs": 2}),
+                &source,
+                &sink,
+            )
+            .await
+            .unwrap();
+
+        let chunks = chunks.into_inner().unwrap();
+        let sizes: Vec<usize> = chunks
+            .iter()
+            .map(|c| c["entries"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, [1, MAX_CHUNK_ENTRIES, 50]);
+        assert_eq!(chunks[1]["entries"][0]["message"], "burst 1");
+        let data = result.data.unwrap();
+        assert_eq!(data["followed_entries"], 250);
+        assert_eq!(data["rotations"], 1);
+    }
 }
@@ -0,0 +1,332 @@
+//! upload_logs — Send a whole log file or journal export to object storage.
+//!
+//! For forensic analysis that needs more than fits in an MQTT response: the
+//! log (`path`, read through the `LogSource`, rotated `.gz` files included)
+//! or the journal of a unit (`unit`, in export format) is gzip-compressed
+//! and uploaded with `PUT` to the pre-signed https `upload_url` from the
+//! command. The result carries the object key and sizes, never the URL
+//! itself.
+//!
+//! Logs larger than [`MAX_UPLOAD_BYTES`] keep their newest lines; older
+//! ones are never read into memory.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde_json::json;
+
+use crate::error::LogResult;
+use crate::journal::{JournalQuery, JournalSource, JournaldSource, PRIORITIES};
+use crate::source::LogSource;
+use crate::types::{LogTool, ToolResult};
+use crate::upload::{self, HttpUploader, LogUploader};
+
+/// Most uncompressed log data uploaded (32 MB).
+pub const MAX_UPLOAD_BYTES: usize = 32 * 1024 * 1024;
+
+/// Journal entries exported when `lines` is not given.
+const DEFAULT_JOURNAL_LINES: u64 = 100_000;
+
+pub struct UploadLogs {
+    journal: Arc<dyn JournalSource>,
+    uploader: Arc<dyn LogUploader>,
+}
+
+impl Default for UploadLogs {
+    fn default() -> Self {
+        Self::with_backends(Arc::new(JournaldSource), Arc::new(HttpUploader::default()))
+    }
+}
+
+impl UploadLogs {
+    /// Export the journal from `journal` and upload through `uploader`.
+    pub fn with_backends(journal: Arc<dyn JournalSource>, uploader: Arc<dyn LogUploader>) -> Self {
+        Self { journal, uploader }
+    }
+
+    async fn collect(
+        &self,
+        args: &serde_json::Value,
+        source: &dyn LogSource,
+    ) -> Result<(String, Vec<String>, bool), String> {
+        match (args["path"].as_str(), args["unit"].as_str()) {
+            (Some(path), None) if !path.is_empty() => source
+                .read_newest(path, MAX_UPLOAD_BYTES)
+                .await
+                .map(|(lines, truncated)| (path.to_string(), lines, truncated))
+                .map_err(|e| e.to_string()),
+            (None, Some(unit)) if !unit.is_empty() => {
+                let mut query = JournalQuery::new(unit);
+                query.lines = args["lines"].as_u64().unwrap_or(DEFAULT_JOURNAL_LINES);
+                query.priority = args["priority"].as_str().map(String::from);
+                query.since = args["since"].as_str().map(String::from);
+                query.boot = match &args["boot"] {
+                    serde_json::Value::Number(n) => Some(n.to_string()),
+                    other => other.as_str().map(String::from),
+                };
+                query.max_bytes = MAX_UPLOAD_BYTES;
+                query.validate().map_err(|e| e.to_string())?;
+                self.journal
+                    .query(&query)
+                    .await
+                    .map(|lines| (format!("journal:{unit}"), lines, false))
+                    .map_err(|e| e.to_string())
+            }
+            _ => Err("exactly one of 'path' or 'unit' is required".into()),
+        }
+    }
+}
+
+/// The newest lines that fit in [`MAX_UPLOAD_BYTES`], newline-terminated,
+/// and whether older ones were dropped.
+fn newest_within_limit(lines: &[String]) -> (String, usize, bool) {
+    let mut size = 0;
+    let mut start = lines.len();
+    while start > 0 && size + lines[start - 1].len() < MAX_UPLOAD_BYTES {
+        start -= 1;
+        size += lines[start].len() + 1;
+    }
+    let mut text = String::with_capacity(size);
+    for line in &lines[start..] {
+        text.push_str(line);
+        text.push('\n');
+    }
+    (text, lines.len() - start, start > 0)
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[async_trait]
+impl LogTool for UploadLogs {
+    fn name(&self) -> &str {
+        "upload_logs"
+    }
+
+    fn description(&self) -> &str {
+        "Compress a log file or journal export and upload it to a pre-signed URL"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "upload_url": {
+                    "type": "string",
+                    "description": "Pre-signed https URL accepting a PUT of the gzip archive"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Log file to upload (e.g. /var/log/syslog); rotated .gz files are accepted"
+                },
+                "unit": {
+                    "type": "string",
+                    "description": "Systemd unit whose journal to upload instead of a file"
+                },
+                "lines": {
+                    "type": "integer",
+                    "description": "Journal entries to export (default: 100000)"
+                },
+                "priority": {
+                    "type": "string",
+                    "enum": PRIORITIES,
+                    "description": "Maximum syslog priority level to include (journal only)"
+                },
+                "since": {
+                    "type": "string",
+                    "description": "Journal entries since this time (e.g. '1 hour ago')"
+                },
+                "boot": {
+                    "type": "string",
+                    "description": "Only this boot: offset ('0' current, '-1' previous) or boot ID (journal only)"
+                }
+            },
+            "required": ["upload_url"]
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        source: &dyn LogSource,
+    ) -> LogResult<ToolResult> {
+        let Some(url) = args["upload_url"].as_str() else {
+            return Ok(ToolResult::failure(
+                "upload_logs",
+                "missing required 'upload_url' argument",
+            ));
+        };
+        if let Err(e) = upload::validate_url(url) {
+            return Ok(ToolResult::failure("upload_logs", e.to_string()));
+        }
+        let (origin, lines, read_truncated) = match self.collect(&args, source).await {
+            Ok(collected) => collected,
+            Err(e) => return Ok(ToolResult::failure("upload_logs", e)),
+        };
+
+        let (text, line_count, truncated) = newest_within_limit(&lines);
+        let truncated = truncated || read_truncated;
+        let archive = match gzip(text.as_bytes()) {
+            Ok(archive) => archive,
+            Err(e) => {
+                return Ok(ToolResult::failure(
+                    "upload_logs",
+                    format!("compression failed: {e}"),
+                ));
+            }
+        };
+        let compressed = archive.len();
+        if let Err(e) = self.uploader.put(url, archive).await {
+            return Ok(ToolResult::failure("upload_logs", e.to_string()));
+        }
+
+        let object_key = upload::object_key(url);
+        let summary = format!(
+            "Uploaded {line_count} lines of {origin} ({} bytes, {compressed} compressed) to {object_key}{}",
+            text.len(),
+            if truncated {
+                ", oldest lines dropped"
+            } else {
+                ""
+            }
+        );
+        Ok(ToolResult::success(
+            "upload_logs",
+            json!({
+                "object_key": object_key,
+                "source": origin,
+                "lines": line_count,
+                "bytes": text.len(),
+                "compressed_bytes": compressed,
+                "truncated": truncated,
+            }),
+            summary,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::mock::{MockJournalSource, MockLogSource, MockUploader};
+
+    const URL: &str = "https://bucket.s3.amazonaws.com/rpi-001/syslog.gz?X-Amz-Signature=secret";
+
+    fn gunzip(data: &[u8]) -> String {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(data)
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[tokio::test]
+    async fn uploads_a_compressed_log_file() {
+        let uploader = Arc::new(MockUploader::new());
+        let tool =
+            UploadLogs::with_backends(Arc::new(MockJournalSource::default()), uploader.clone());
+        let source = MockLogSource::with_syslog_sample();
+
+        let result = tool
+            .execute(
+                json!({"upload_url": URL, "path": "/var/log/syslog"}),
+                &source,
+            )
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let data = result.data.unwrap();
+        assert_eq!(data["object_key"], "rpi-001/syslog.gz");
+        assert_eq!(data["lines"], 10);
+        assert_eq!(data["truncated"], false);
+        assert!(!data.to_string().contains("secret"));
+
+        let uploads = uploader.uploads();
+        assert_eq!(uploads[0].0, URL);
+        let text = gunzip(&uploads[0].1);
+        assert_eq!(text.lines().count(), 10);
+        assert_eq!(data["bytes"], text.len());
+    }
+
+    #[tokio::test]
+    async fn uploads_a_journal_export() {
+        let journal = Arc::new(MockJournalSource::with_sample());
+        let uploader = Arc::new(MockUploader::new());
+        let tool = UploadLogs::with_backends(journal.clone(), uploader.clone());
+
+        let result = tool
+            .execute(
+                json!({"upload_url": URL, "unit": "zeroclaw.service", "boot": -1}),
+                &MockLogSource::new(),
+            )
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data.unwrap()["source"], "journal:zeroclaw.service");
+        let query = &journal.queries()[0];
+        assert_eq!(query.boot.as_deref(), Some("-1"));
+        assert_eq!(query.max_bytes, MAX_UPLOAD_BYTES);
+        assert_eq!(uploader.uploads().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn bad_arguments_and_rejected_uploads_fail() {
+        let tool = UploadLogs::with_backends(
+            Arc::new(MockJournalSource::default()),
+            Arc::new(MockUploader::failing()),
+        );
+        let source = MockLogSource::with_syslog_sample();
+        for (args, error) in [
+            (
+                json!({"path": "/var/log/syslog"}),
+                "missing required 'upload_url'",
+            ),
+            (
+                json!({"upload_url": "file:///tmp/x", "path": "/var/log/syslog"}),
+                "https URL",
+            ),
+            (
+                json!({"upload_url": "http://logs.example.com/a.gz", "path": "/var/log/syslog"}),
+                "https URL",
+            ),
+            (json!({"upload_url": URL}), "exactly one of"),
+            (
+                json!({"upload_url": URL, "path": "/var/log/syslog", "unit": "a.service"}),
+                "exactly one of",
+            ),
+            (
+                json!({"upload_url": URL, "path": "/var/log/missing"}),
+                "not found",
+            ),
+            (
+                json!({"upload_url": URL, "path": "/var/log/syslog"}),
+                "HTTP 403",
+            ),
+        ] {
+            let result = tool.execute(args, &source).await.unwrap();
+            assert!(!result.success);
+            let message = result.error.unwrap();
+            assert!(message.contains(error), "{message}");
+            assert!(!message.contains("secret"));
+        }
+    }
+
+    #[test]
+    fn oversized_logs_keep_their_newest_lines() {
+        let line = "x".repeat(1024 * 1024 - 1);
+        let lines: Vec<String> = (0..40).map(|i| format!("{i:02}{}", &line[2..])).collect();
+        let (text, count, truncated) = newest_within_limit(&lines);
+        assert!(truncated);
+        assert_eq!(count, 32);
+        assert!(text.starts_with("08"));
+        assert!(text.len() <= MAX_UPLOAD_BYTES);
+    }
+}
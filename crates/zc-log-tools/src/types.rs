@@ -71,6 +71,8 @@ pub enum LogFormat {
     JsonLines,
     /// Unstructured plaintext.
     Plaintext,
+    /// A device-configured regex format (name in the `custom_format` field).
+    Custom,
 }
 
 // ── Log Entry ─────────────────────────────────────────────────
@@ -146,6 +148,9 @@ impl ToolResult {
 
 // ── LogTool Trait ─────────────────────────────────────────────
 
+/// Receiver for partial results emitted by a streaming tool.
+pub type ChunkSink<'a> = dyn Fn(serde_json::Value) + Send + Sync + 'a;
+
 /// Trait for log analysis tools.
 ///
 /// Mirrors the `CanTool` pattern — trivially wrappable via thin adapter
@@ -167,4 +172,21 @@ pub trait LogTool: Send + Sync {
         args: serde_json::Value,
         source: &dyn crate::source::LogSource,
     ) -> LogResult<ToolResult>;
+
+    /// Whether [`LogTool::execute_streaming`] emits partial results.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Execute, passing partial results to `sink` while the tool runs.
+    /// The returned result summarises the run. Defaults to [`LogTool::execute`].
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        source: &dyn crate::source::LogSource,
+        sink: &ChunkSink<'_>,
+    ) -> LogResult<ToolResult> {
+        let _ = sink;
+        self.execute(args, source).await
+    }
 }
@@ -0,0 +1,169 @@
+//! Upload backend for `upload_logs`.
+//!
+//! Logs too large for an MQTT response are sent to object storage instead:
+//! the command carries a pre-signed URL (S3 or any https endpoint accepting
+//! `PUT`), so the device needs no storage credentials of its own. Plain
+//! `http://` is refused: the logs and the URL's signature would cross the
+//! network in the clear.
+//! [`HttpUploader`] performs the `PUT`; tests use
+//! [`MockUploader`](crate::mock::MockUploader).
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::{LogError, LogResult};
+
+/// Longest an upload may take.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Destination for compressed log archives.
+#[async_trait]
+pub trait LogUploader: Send + Sync {
+    /// Store `body` at the pre-signed `url`.
+    async fn put(&self, url: &str, body: Vec<u8>) -> LogResult<()>;
+}
+
+/// Uploads with an HTTP `PUT`. Redirects are not followed: they would
+/// send the logs to a host the URL's signer did not name.
+pub struct HttpUploader {
+    client: reqwest::Client,
+}
+
+impl Default for HttpUploader {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LogUploader for HttpUploader {
+    async fn put(&self, url: &str, body: Vec<u8>) -> LogResult<()> {
+        let response = self
+            .client
+            .put(url)
+            .body(body)
+            .send()
+            .await
+            // Without the URL: its query string is the upload credential.
+            .map_err(|e| LogError::Io(format!("upload failed: {}", e.without_url())))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(LogError::Io(format!("upload rejected with HTTP {status}")));
+        }
+        Ok(())
+    }
+}
+
+/// Check that `url` is an https URL with a host and an object path.
+pub fn validate_url(url: &str) -> LogResult<()> {
+    let rest = url
+        .strip_prefix("https://")
+        .ok_or_else(|| LogError::Other("upload_url must be an https URL".into()))?;
+    let (host, _) = rest.split_once('/').unwrap_or((rest, ""));
+    if host.is_empty() || object_key(url).is_empty() || url.contains(char::is_whitespace) {
+        return Err(LogError::Other(
+            "upload_url must name a host and an object path".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Object key of a pre-signed URL: its path, without the leading `/` and
+/// the signed query string.
+pub fn object_key(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = rest.split_once('/').map_or("", |(_, path)| path);
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    path.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// A local server answering every request with a redirect to itself.
+    /// Returns its URL and the number of requests served.
+    async fn redirecting_server() -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                // Read the whole request so the client sees the response.
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    let done = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                        let length = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        body.len() >= length
+                    });
+                    if n == 0 || done {
+                        break;
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 307 Temporary Redirect\r\nLocation: http://{addr}/elsewhere\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (format!("http://{addr}/logs/a.gz?sig=1"), requests)
+    }
+
+    #[tokio::test]
+    async fn redirects_are_not_followed() {
+        let (url, requests) = redirecting_server().await;
+        let err = HttpUploader::default()
+            .put(&url, b"logs".to_vec())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("307"), "{err}");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn object_key_drops_host_and_signature() {
+        assert_eq!(
+            object_key(
+                "https://bucket.s3.eu-west-1.amazonaws.com/devices/rpi-001/syslog.gz?X-Amz-Signature=abc"
+            ),
+            "devices/rpi-001/syslog.gz"
+        );
+        assert_eq!(object_key("http://logs.local:9000/a.gz"), "a.gz");
+        assert!(validate_url("https://bucket.example.com/logs/a.gz?sig=1").is_ok());
+        for bad in [
+            "ftp://bucket/a.gz",
+            "http://bucket.example.com/a.gz",
+            "https://bucket.example.com/",
+            "https://bucket.example.com",
+            "https:///a.gz",
+            "https://bucket.example.com/a b.gz",
+        ] {
+            assert!(validate_url(bad).is_err(), "{bad}");
+        }
+    }
+}
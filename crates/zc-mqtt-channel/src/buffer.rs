@@ -0,0 +1,301 @@
+//! Outbound buffer for messages published while the broker is unreachable.
+//!
+//! rumqttc only queues what fits in its request channel and forgets it on
+//! restart, so an agent cut off from the broker would lose heartbeats,
+//! telemetry and command responses. While [`crate::MqttChannel`] is
+//! offline, publishes land here instead and are sent in order once the
+//! connection is back. The buffer is bounded: when full the oldest message
+//! is dropped. With a path it is mirrored to a JSON-lines file so that
+//! messages also survive an agent restart.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{OfflineBufferConfig, QosLevel};
+
+/// A publish waiting for the connection to come back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferedMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QoS,
+    pub queued_at: DateTime<Utc>,
+}
+
+/// One line of the buffer file.
+#[derive(Serialize, Deserialize)]
+struct BufferRecord {
+    topic: String,
+    /// Base64, payloads are not guaranteed to be text.
+    payload: String,
+    qos: u8,
+    queued_at: DateTime<Utc>,
+}
+
+impl From<&BufferedMessage> for BufferRecord {
+    fn from(msg: &BufferedMessage) -> Self {
+        Self {
+            topic: msg.topic.clone(),
+            payload: STANDARD.encode(&msg.payload),
+            qos: msg.qos as u8,
+            queued_at: msg.queued_at,
+        }
+    }
+}
+
+impl BufferRecord {
+    fn into_message(self) -> Option<BufferedMessage> {
+        Some(BufferedMessage {
+            topic: self.topic,
+            payload: STANDARD.decode(self.payload).ok()?,
+            qos: QosLevel::try_from(self.qos).ok()?.into(),
+            queued_at: self.queued_at,
+        })
+    }
+}
+
+/// Bounded FIFO of outbound messages, optionally backed by a file.
+pub struct OfflineBuffer {
+    capacity: usize,
+    path: Option<PathBuf>,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    queue: VecDeque<BufferedMessage>,
+    /// Messages dropped because the buffer was full.
+    dropped: u64,
+    /// Lines currently in the file.
+    lines_on_disk: usize,
+}
+
+impl OfflineBuffer {
+    /// Buffer that lives only in memory.
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            path: None,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Buffer described by `config`: file-backed when it has a path, with
+    /// the messages left from the previous run loaded (newest `capacity`
+    /// kept, unparseable lines skipped).
+    pub fn open(config: &OfflineBufferConfig) -> std::io::Result<Self> {
+        let Some(path) = &config.path else {
+            return Ok(Self::in_memory(config.capacity));
+        };
+        let path = PathBuf::from(path);
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let capacity = config.capacity.max(1);
+        let mut inner = Inner::default();
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                for line in std::io::BufReader::new(file).lines() {
+                    if let Some(msg) = serde_json::from_str::<BufferRecord>(&line?)
+                        .ok()
+                        .and_then(BufferRecord::into_message)
+                    {
+                        inner.queue.push_back(msg);
+                        if inner.queue.len() > capacity {
+                            inner.queue.pop_front();
+                            inner.dropped += 1;
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        rewrite(&path, &inner.queue)?;
+        inner.lines_on_disk = inner.queue.len();
+
+        Ok(Self {
+            capacity,
+            path: Some(path),
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Messages waiting.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Messages dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.inner.lock().unwrap().dropped
+    }
+
+    /// Queue a message, dropping the oldest if full. Returns `false` when
+    /// a message had to be dropped.
+    pub fn push(&self, topic: &str, payload: &[u8], qos: QoS) -> bool {
+        let msg = BufferedMessage {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            qos,
+            queued_at: Utc::now(),
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.queue.push_back(msg.clone());
+        let full = inner.queue.len() > self.capacity;
+        if full {
+            inner.queue.pop_front();
+            inner.dropped += 1;
+        }
+
+        if let Some(path) = &self.path {
+            // Dropped messages stay in the file until it holds twice the
+            // capacity, so a full buffer is not rewritten on every push.
+            let result = if inner.lines_on_disk + 1 > 2 * self.capacity {
+                rewrite(path, &inner.queue).map(|()| inner.queue.len())
+            } else {
+                append(path, &msg).map(|()| inner.lines_on_disk + 1)
+            };
+            match result {
+                Ok(lines) => inner.lines_on_disk = lines,
+                Err(e) => {
+                    tracing::warn!(error = %e, path = %path.display(), "failed to write offline buffer")
+                }
+            }
+        }
+        !full
+    }
+
+    /// Oldest waiting message, left in place until [`Self::pop`].
+    pub fn front(&self) -> Option<BufferedMessage> {
+        self.inner.lock().unwrap().queue.front().cloned()
+    }
+
+    /// Remove the oldest message once it has been handed to the client.
+    pub fn pop(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.queue.pop_front();
+        if let Some(path) = &self.path
+            && inner.queue.is_empty()
+        {
+            match rewrite(path, &inner.queue) {
+                Ok(()) => inner.lines_on_disk = 0,
+                Err(e) => {
+                    tracing::warn!(error = %e, path = %path.display(), "failed to clear offline buffer")
+                }
+            }
+        }
+    }
+
+    /// Write the remaining messages out after a partial flush, so that a
+    /// restart does not send the flushed ones again.
+    pub fn sync(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(path) = &self.path else {
+            return;
+        };
+        if inner.lines_on_disk == inner.queue.len() {
+            return;
+        }
+        match rewrite(path, &inner.queue) {
+            Ok(()) => inner.lines_on_disk = inner.queue.len(),
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path.display(), "failed to write offline buffer")
+            }
+        }
+    }
+}
+
+fn append(path: &Path, msg: &BufferedMessage) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let line = serde_json::to_string(&BufferRecord::from(msg)).map_err(std::io::Error::other)?;
+    writeln!(file, "{line}")
+}
+
+fn rewrite(path: &Path, queue: &VecDeque<BufferedMessage>) -> std::io::Result<()> {
+    let tmp = path.with_extension("jsonl.tmp");
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        for msg in queue {
+            let line =
+                serde_json::to_string(&BufferRecord::from(msg)).map_err(std::io::Error::other)?;
+            writeln!(file, "{line}")?;
+        }
+        file.flush()?;
+    }
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(name: &str, capacity: usize) -> OfflineBufferConfig {
+        let dir = std::env::temp_dir().join(format!("zc-outbox-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        OfflineBufferConfig {
+            enabled: true,
+            capacity,
+            path: Some(dir.join("outbox.jsonl").to_string_lossy().into_owned()),
+        }
+    }
+
+    #[test]
+    fn full_buffer_drops_oldest() {
+        let buffer = OfflineBuffer::in_memory(2);
+        assert!(buffer.push("a", b"1", QoS::AtLeastOnce));
+        assert!(buffer.push("b", b"2", QoS::AtMostOnce));
+        assert!(!buffer.push("c", b"3", QoS::AtLeastOnce));
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.dropped(), 1);
+        assert_eq!(buffer.front().unwrap().topic, "b");
+        buffer.pop();
+        assert_eq!(buffer.front().unwrap().payload, b"3");
+        buffer.pop();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn file_backed_buffer_survives_restart() {
+        let config = temp_config("restart", 3);
+        {
+            let buffer = OfflineBuffer::open(&config).unwrap();
+            for i in 0..5u8 {
+                buffer.push("fleet/f/d/heartbeat/ping", &[i, 0xff], QoS::AtMostOnce);
+            }
+            // One message made it out before the agent stopped.
+            buffer.pop();
+            buffer.sync();
+        }
+
+        let buffer = OfflineBuffer::open(&config).unwrap();
+        assert_eq!(buffer.len(), 2);
+        let msg = buffer.front().unwrap();
+        assert_eq!(msg.payload, vec![3, 0xff]);
+        assert_eq!(msg.qos, QoS::AtMostOnce);
+
+        buffer.pop();
+        buffer.pop();
+        let reopened = OfflineBuffer::open(&config).unwrap();
+        assert!(reopened.is_empty());
+    }
+}
@@ -1,17 +1,34 @@
 //! MQTT channel — async client for AWS IoT Core communication.
 //!
 //! Wraps `rumqttc::AsyncClient` with typed publish helpers for
-//! commands, telemetry, heartbeats, and shadow operations.
+//! commands, telemetry, heartbeats, and shadow operations. With an
+//! [`OfflineBuffer`] attached, publishes made while disconnected are held
+//! and sent after the next connect. Speaks MQTT 3.1.1 or, with
+//! `protocol = "v5"`, MQTT 5 (message expiry and user properties through
+//! [`Channel::publish_with_properties`]). Typed payloads are JSON, or CBOR
+//! with `encoding = "cbor"`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use async_trait::async_trait;
 use rumqttc::{AsyncClient, EventLoop, MqttOptions, QoS};
 use serde::Serialize;
 
-use crate::config::MqttConfig;
+use crate::buffer::OfflineBuffer;
+use crate::config::{MqttConfig, MqttProtocol, QosConfig, QosLevel, ReconnectConfig};
 use crate::error::{MqttError, MqttResult};
+use crate::eventloop::MqttEventLoop;
+use crate::properties::MessageProperties;
+use crate::reconnect::ConnectionMonitor;
 use crate::tls;
 use zc_protocol::{
-    TelemetrySource, commands::CommandResponse, device::Heartbeat, telemetry::TelemetryBatch,
+    TelemetrySource,
+    commands::{CommandResponse, CommandResponseChunk},
+    device::{DevicePresence, Heartbeat},
+    encoding::{self, Encoding},
+    self_test::SelfTestReport,
+    telemetry::{TelemetryBatch, ThresholdEvent},
     topics,
 };
 
@@ -27,31 +44,128 @@ pub trait Channel: Send + Sync {
 
     /// Subscribe to a topic filter.
     async fn subscribe(&self, filter: &str, qos: QoS) -> MqttResult<()>;
+
+    /// Publish with MQTT v5 properties. Channels that cannot carry them
+    /// (MQTT 3.1.1) publish without.
+    async fn publish_with_properties(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        _properties: &MessageProperties,
+    ) -> MqttResult<()> {
+        self.publish(topic, payload, qos).await
+    }
+}
+
+// ── Protocol clients ──────────────────────────────────────────
+
+/// rumqttc client for the configured protocol version.
+enum Client {
+    V311(AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+}
+
+impl Client {
+    async fn publish(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        properties: Option<&MessageProperties>,
+    ) -> MqttResult<()> {
+        let result = match (self, properties) {
+            (Self::V311(client), _) => client
+                .publish(topic, qos, false, payload)
+                .await
+                .map_err(|e| e.to_string()),
+            (Self::V5(client), Some(properties)) if !properties.is_empty() => client
+                .publish_with_properties(
+                    topic,
+                    qos_v5(qos),
+                    false,
+                    payload.to_vec(),
+                    properties.to_v5(),
+                )
+                .await
+                .map_err(|e| e.to_string()),
+            (Self::V5(client), _) => client
+                .publish(topic, qos_v5(qos), false, payload.to_vec())
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        result.map_err(MqttError::Publish)
+    }
+
+    /// Queue a publish without waiting; `false` if the request queue is
+    /// full or closed.
+    fn try_publish(&self, topic: String, payload: Vec<u8>, qos: QoS, retain: bool) -> bool {
+        match self {
+            Self::V311(client) => client.try_publish(topic, qos, retain, payload).is_ok(),
+            Self::V5(client) => client
+                .try_publish(topic, qos_v5(qos), retain, payload)
+                .is_ok(),
+        }
+    }
+
+    async fn subscribe(&self, filter: &str, qos: QoS) -> MqttResult<()> {
+        let result = match self {
+            Self::V311(client) => client
+                .subscribe(filter, qos)
+                .await
+                .map_err(|e| e.to_string()),
+            Self::V5(client) => client
+                .subscribe(filter, qos_v5(qos))
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        result.map_err(MqttError::Subscribe)
+    }
+}
+
+fn qos_v5(qos: QoS) -> rumqttc::v5::mqttbytes::QoS {
+    match qos {
+        QoS::AtMostOnce => rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+        QoS::AtLeastOnce => rumqttc::v5::mqttbytes::QoS::AtLeastOnce,
+        QoS::ExactlyOnce => rumqttc::v5::mqttbytes::QoS::ExactlyOnce,
+    }
 }
 
 // ── MqttChannel ───────────────────────────────────────────────
 
 /// MQTT channel connected to AWS IoT Core.
 ///
-/// Owns the `AsyncClient` for publishing/subscribing. The `EventLoop`
+/// Owns the rumqttc client for publishing/subscribing. The event loop
 /// is returned separately from `new()` — the caller (fleet agent) must
 /// drive it in a spawned task via `eventloop.poll()`.
 pub struct MqttChannel {
-    client: AsyncClient,
+    client: Client,
     fleet_id: String,
     device_id: String,
+    qos: QosConfig,
+    /// Encoding of the typed publishes.
+    encoding: Encoding,
+    buffer: Option<OfflineBuffer>,
+    /// Set by [`Self::on_connected`], cleared by [`Self::on_disconnected`].
+    online: AtomicBool,
+    health: Arc<ConnectionMonitor>,
 }
 
 impl MqttChannel {
-    /// Create a new MQTT channel with TLS (production mode).
+    /// Create a new MQTT channel from `config`: mTLS unless `use_tls` is
+    /// off, in the configured protocol version, with its keep-alive,
+    /// session and QoS settings.
     ///
     /// Returns `(channel, event_loop)`. The caller must poll the event loop:
     /// ```ignore
     /// tokio::spawn(async move {
     ///     loop {
-    ///         if let Err(e) = eventloop.poll().await {
-    ///             tracing::error!("MQTT event loop error: {e}");
-    ///             tokio::time::sleep(Duration::from_secs(5)).await;
+    ///         match eventloop.poll().await {
+    ///             Ok(ChannelEvent::Connected { .. }) => {
+    ///                 health.on_connected();
+    ///             }
+    ///             Ok(_) => {}
+    ///             Err(e) => tokio::time::sleep(health.on_error(&e)).await,
     ///         }
     ///     }
     /// });
@@ -60,26 +174,87 @@ impl MqttChannel {
         config: &MqttConfig,
         fleet_id: impl Into<String>,
         device_id: impl Into<String>,
-    ) -> MqttResult<(Self, EventLoop)> {
+    ) -> MqttResult<(Self, MqttEventLoop)> {
         let fleet_id = fleet_id.into();
         let device_id = device_id.into();
+        let keep_alive = std::time::Duration::from_secs(config.keepalive_secs.into());
+        // The will is fixed at connect time, hence no timestamp.
+        let will = config.last_will.then(|| {
+            let presence = DevicePresence {
+                device_id: device_id.clone(),
+                fleet_id: fleet_id.clone(),
+                online: false,
+                timestamp: None,
+            };
+            (
+                topics::presence(&fleet_id, &device_id),
+                encoding::encode(&presence, config.encoding).unwrap_or_default(),
+            )
+        });
+        let transport = if config.use_tls {
+            Some(tls::load_tls_transport(config)?)
+        } else {
+            None
+        };
 
-        let mut options =
-            MqttOptions::new(&config.client_id, &config.broker_host, config.broker_port);
-        options.set_keep_alive(std::time::Duration::from_secs(config.keepalive_secs.into()));
-        // AWS IoT Core supports 128 KB payloads; rumqttc defaults to 10 KB.
-        options.set_max_packet_size(256 * 1024, 256 * 1024);
-
-        let transport = tls::load_tls_transport(config)?;
-        options.set_transport(transport);
-
-        let (client, eventloop) = AsyncClient::new(options, 64);
+        let (client, eventloop) = match config.protocol {
+            MqttProtocol::V311 => {
+                let mut options =
+                    MqttOptions::new(&config.client_id, &config.broker_host, config.broker_port);
+                options.set_keep_alive(keep_alive);
+                // AWS IoT Core supports 128 KB payloads; rumqttc defaults to 10 KB.
+                options.set_max_packet_size(256 * 1024, 256 * 1024);
+                options.set_clean_session(config.clean_session);
+                if let Some((topic, payload)) = will {
+                    options.set_last_will(rumqttc::LastWill::new(
+                        topic,
+                        payload,
+                        QoS::AtLeastOnce,
+                        true,
+                    ));
+                }
+                if let Some(transport) = transport {
+                    options.set_transport(transport);
+                }
+                let (client, eventloop) = AsyncClient::new(options, 64);
+                (Client::V311(client), eventloop.into())
+            }
+            MqttProtocol::V5 => {
+                let mut options = rumqttc::v5::MqttOptions::new(
+                    &config.client_id,
+                    &config.broker_host,
+                    config.broker_port,
+                );
+                options.set_keep_alive(keep_alive);
+                options.set_max_packet_size(Some(256 * 1024));
+                options.set_clean_start(config.clean_session);
+                if let Some((topic, payload)) = will {
+                    options.set_last_will(rumqttc::v5::mqttbytes::v5::LastWill::new(
+                        topic,
+                        payload,
+                        qos_v5(QoS::AtLeastOnce),
+                        true,
+                        None,
+                    ));
+                }
+                if let Some(transport) = transport {
+                    options.set_transport(transport);
+                }
+                let (client, eventloop) = rumqttc::v5::AsyncClient::new(options, 64);
+                (Client::V5(client), eventloop.into())
+            }
+        };
 
         Ok((
             Self {
                 client,
                 fleet_id,
                 device_id,
+                qos: config.qos.clone(),
+                encoding: config.encoding,
+                buffer: None,
+                online: AtomicBool::new(false),
+                health: Arc::new(ConnectionMonitor::new(config.reconnect.clone())),
             },
             eventloop,
         ))
@@ -101,14 +276,80 @@ impl MqttChannel {
 
         (
             Self {
-                client,
+                client: Client::V311(client),
                 fleet_id: fleet_id.into(),
                 device_id: device_id.into(),
+                qos: QosConfig::default(),
+                encoding: Encoding::Json,
+                buffer: None,
+                online: AtomicBool::new(false),
+                health: Arc::new(ConnectionMonitor::new(ReconnectConfig::default())),
             },
             eventloop,
         )
     }
 
+    /// Hold publishes made while disconnected in `buffer`. The caller
+    /// driving the event loop must then report the connection state with
+    /// [`Self::on_connected`] and [`Self::on_disconnected`].
+    pub fn with_offline_buffer(mut self, buffer: OfflineBuffer) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// The offline buffer, if one is attached.
+    pub fn offline_buffer(&self) -> Option<&OfflineBuffer> {
+        self.buffer.as_ref()
+    }
+
+    /// Connection health and reconnect backoff, shared with health
+    /// reporting.
+    pub fn health(&self) -> &Arc<ConnectionMonitor> {
+        &self.health
+    }
+
+    /// The broker accepted the connection (`ConnAck`): publish directly
+    /// again and start sending buffered messages. Returns how many were
+    /// handed to the client.
+    pub fn on_connected(&self) -> usize {
+        self.online.store(true, Ordering::SeqCst);
+        self.flush_offline()
+    }
+
+    /// The connection dropped: buffer publishes until the next connect.
+    pub fn on_disconnected(&self) {
+        self.online.store(false, Ordering::SeqCst);
+    }
+
+    /// Hand buffered messages to the client, oldest first, until the
+    /// buffer is empty or the client's request queue is full. Never waits,
+    /// so it is safe to call from the task that polls the event loop; call
+    /// it again on later events to send the rest. Returns how many were
+    /// sent.
+    pub fn flush_offline(&self) -> usize {
+        let Some(buffer) = &self.buffer else {
+            return 0;
+        };
+        let mut sent = 0;
+        while self.online.load(Ordering::SeqCst)
+            && let Some(msg) = buffer.front()
+        {
+            if !self
+                .client
+                .try_publish(msg.topic, msg.payload, msg.qos, false)
+            {
+                break;
+            }
+            buffer.pop();
+            sent += 1;
+        }
+        if sent > 0 {
+            buffer.sync();
+            tracing::info!(sent, remaining = buffer.len(), "flushed offline buffer");
+        }
+        sent
+    }
+
     pub fn fleet_id(&self) -> &str {
         &self.fleet_id
     }
@@ -117,12 +358,27 @@ impl MqttChannel {
         &self.device_id
     }
 
+    /// Encoding of the typed publishes (`mqtt.encoding`).
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
     // ── Typed publish helpers ─────────────────────────────────
 
-    /// Publish a command response.
+    /// Publish a command response, tagged with its correlation ID.
     pub async fn publish_response(&self, response: &CommandResponse) -> MqttResult<()> {
         let topic = topics::command_response(&self.fleet_id, &self.device_id);
-        self.publish_json(&topic, response).await
+        let properties = MessageProperties::correlated(response.correlation_id);
+        self.publish_encoded_with(&topic, response, self.qos.response, &properties)
+            .await
+    }
+
+    /// Publish a partial result for a command that is still running.
+    pub async fn publish_response_chunk(&self, chunk: &CommandResponseChunk) -> MqttResult<()> {
+        let topic = topics::command_stream(&self.fleet_id, &self.device_id);
+        let properties = MessageProperties::correlated(chunk.correlation_id);
+        self.publish_encoded_with(&topic, chunk, self.qos.response, &properties)
+            .await
     }
 
     /// Publish a telemetry batch, routing to the correct source topic.
@@ -140,28 +396,67 @@ impl MqttChannel {
                 }
             }
         };
-        self.publish_json(&topic, batch).await
+        self.publish_encoded(&topic, batch, self.qos.telemetry)
+            .await
     }
 
     /// Publish a heartbeat.
     pub async fn publish_heartbeat(&self, heartbeat: &Heartbeat) -> MqttResult<()> {
         let topic = topics::heartbeat(&self.fleet_id, &self.device_id);
-        self.publish_json(&topic, heartbeat).await
+        self.publish_encoded(&topic, heartbeat, self.qos.heartbeat)
+            .await
+    }
+
+    /// Announce the device as online on its retained presence topic,
+    /// replacing the "offline" Last Will left by a previous connection.
+    /// Call on every `ConnAck`; like [`Self::flush_offline`] it never waits.
+    pub fn publish_online(&self) -> bool {
+        let presence = DevicePresence {
+            device_id: self.device_id.clone(),
+            fleet_id: self.fleet_id.clone(),
+            online: true,
+            timestamp: Some(chrono::Utc::now()),
+        };
+        let Ok(payload) = encoding::encode(&presence, self.encoding) else {
+            return false;
+        };
+        self.client.try_publish(
+            topics::presence(&self.fleet_id, &self.device_id),
+            payload,
+            QoS::AtLeastOnce,
+            true,
+        )
+    }
+
+    /// Publish a self-test (provisioning verification) report.
+    pub async fn publish_self_test(&self, report: &SelfTestReport) -> MqttResult<()> {
+        let topic = topics::self_test_report(&self.fleet_id, &self.device_id);
+        self.publish_encoded(&topic, report, self.qos.other).await
+    }
+
+    /// Publish a PID watch threshold crossing.
+    pub async fn publish_threshold_event(&self, event: &ThresholdEvent) -> MqttResult<()> {
+        let topic = topics::alert(&self.fleet_id, &self.device_id);
+        self.publish_encoded(&topic, event, self.qos.other).await
     }
 
     /// Publish a command acknowledgement.
     pub async fn publish_ack(&self, ack: &serde_json::Value) -> MqttResult<()> {
         let topic = topics::command_ack(&self.fleet_id, &self.device_id);
-        self.publish_json(&topic, ack).await
+        self.publish_encoded(&topic, ack, self.qos.response).await
     }
 
     // ── Subscription helpers ──────────────────────────────────
 
-    /// Subscribe to incoming commands (device-specific + broadcast).
+    /// Subscribe to incoming commands (device-specific + broadcast) and
+    /// cancellation requests.
     pub async fn subscribe_commands(&self) -> MqttResult<()> {
         let device_topic = topics::command_request(&self.fleet_id, &self.device_id);
         self.subscribe(&device_topic, QoS::AtLeastOnce).await?;
 
+        let cancel_topic = topics::command_cancel(&self.fleet_id, &self.device_id);
+        self.subscribe(&cancel_topic, QoS::AtLeastOnce).await?;
+
         let broadcast = topics::broadcast_command(&self.fleet_id);
         self.subscribe(&broadcast, QoS::AtLeastOnce).await
     }
@@ -172,6 +467,15 @@ impl MqttChannel {
         self.subscribe(&topic, QoS::AtLeastOnce).await
     }
 
+    /// Subscribe to file transfer offers and pushed chunks.
+    pub async fn subscribe_transfers(&self) -> MqttResult<()> {
+        let offer = topics::transfer_offer(&self.fleet_id, &self.device_id);
+        self.subscribe(&offer, QoS::AtLeastOnce).await?;
+
+        let chunk = topics::transfer_chunk(&self.fleet_id, &self.device_id);
+        self.subscribe(&chunk, QoS::AtLeastOnce).await
+    }
+
     /// Subscribe to broadcast config updates.
     pub async fn subscribe_config(&self) -> MqttResult<()> {
         let topic = topics::broadcast_config(&self.fleet_id);
@@ -186,6 +490,12 @@ impl MqttChannel {
         self.subscribe(&topic, QoS::AtLeastOnce).await
     }
 
+    /// Subscribe to all streamed response chunks in the fleet (cloud-side).
+    pub async fn subscribe_fleet_response_streams(&self) -> MqttResult<()> {
+        let topic = topics::fleet_command_streams(&self.fleet_id);
+        self.subscribe(&topic, QoS::AtLeastOnce).await
+    }
+
     /// Subscribe to all heartbeats in the fleet (cloud-side).
     pub async fn subscribe_fleet_heartbeats(&self) -> MqttResult<()> {
         let topic = topics::fleet_heartbeats(&self.fleet_id);
@@ -204,28 +514,134 @@ impl MqttChannel {
         self.subscribe(&topic, QoS::AtLeastOnce).await
     }
 
+    /// Subscribe to all self-test reports in the fleet (cloud-side).
+    pub async fn subscribe_fleet_self_tests(&self) -> MqttResult<()> {
+        let topic = topics::fleet_self_test_reports(&self.fleet_id);
+        self.subscribe(&topic, QoS::AtLeastOnce).await
+    }
+
+    /// Subscribe to every device-to-cloud topic of `fleet_id`, which may
+    /// be [`topics::ALL_FLEETS`] (cloud-side).
+    pub async fn subscribe_bridge(&self, fleet_id: &str) -> MqttResult<()> {
+        for filter in topics::bridge_subscriptions(fleet_id) {
+            self.subscribe(&filter, QoS::AtLeastOnce).await?;
+        }
+        Ok(())
+    }
+
+    /// Subscribe to claim requests from devices without an identity
+    /// (cloud-side).
+    pub async fn subscribe_bootstrap_claims(&self) -> MqttResult<()> {
+        self.subscribe(&topics::bootstrap_claims(), QoS::AtLeastOnce)
+            .await
+    }
+
     // ── Internal helpers ──────────────────────────────────────
 
-    async fn publish_json<T: Serialize>(&self, topic: &str, payload: &T) -> MqttResult<()> {
-        let bytes =
-            serde_json::to_vec(payload).map_err(|e| MqttError::Serialization(e.to_string()))?;
-        self.publish(topic, &bytes, QoS::AtLeastOnce).await
+    async fn publish_encoded<T: Serialize>(
+        &self,
+        topic: &str,
+        payload: &T,
+        qos: QosLevel,
+    ) -> MqttResult<()> {
+        self.publish_encoded_with(topic, payload, qos, &MessageProperties::default())
+            .await
+    }
+
+    async fn publish_encoded_with<T: Serialize>(
+        &self,
+        topic: &str,
+        payload: &T,
+        qos: QosLevel,
+        properties: &MessageProperties,
+    ) -> MqttResult<()> {
+        let bytes = encoding::encode(payload, self.encoding)
+            .map_err(|e| MqttError::Serialization(e.to_string()))?;
+        self.publish_with_properties(topic, &bytes, qos.into(), properties)
+            .await
     }
 }
 
 #[async_trait]
 impl Channel for MqttChannel {
     async fn publish(&self, topic: &str, payload: &[u8], qos: QoS) -> MqttResult<()> {
-        self.client
-            .publish(topic, qos, false, payload)
+        self.publish_with_properties(topic, payload, qos, &MessageProperties::default())
             .await
-            .map_err(|e| MqttError::Publish(e.to_string()))
     }
 
     async fn subscribe(&self, filter: &str, qos: QoS) -> MqttResult<()> {
+        self.client.subscribe(filter, qos).await
+    }
+
+    async fn publish_with_properties(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        properties: &MessageProperties,
+    ) -> MqttResult<()> {
+        // Buffered messages go out without properties; an expiry would be
+        // measured from the flush, not from when the message was made.
+        if let Some(buffer) = &self.buffer
+            && !self.online.load(Ordering::SeqCst)
+        {
+            if !buffer.push(topic, payload, qos) {
+                tracing::warn!(
+                    topic = topic,
+                    dropped = buffer.dropped(),
+                    "offline buffer full, dropped oldest message"
+                );
+            }
+            return Ok(());
+        }
         self.client
-            .subscribe(filter, qos)
+            .publish(topic, payload, qos, Some(properties))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn offline_publishes_are_buffered_until_connected() {
+        let (channel, _eventloop) =
+            MqttChannel::new_plaintext("localhost", 1883, "rpi-001", "fleet-alpha", "rpi-001");
+        let channel = channel.with_offline_buffer(OfflineBuffer::in_memory(10));
+
+        channel
+            .publish(
+                "fleet/fleet-alpha/rpi-001/heartbeat/ping",
+                b"{}",
+                QoS::AtMostOnce,
+            )
+            .await
+            .unwrap();
+        channel
+            .publish(
+                "fleet/fleet-alpha/rpi-001/command/response",
+                b"{}",
+                QoS::AtLeastOnce,
+            )
+            .await
+            .unwrap();
+        assert_eq!(channel.offline_buffer().unwrap().len(), 2);
+
+        // The event loop is never polled, but the client's request queue
+        // takes the buffered messages without waiting.
+        assert_eq!(channel.on_connected(), 2);
+        assert!(channel.offline_buffer().unwrap().is_empty());
+
+        channel.on_disconnected();
+        channel
+            .publish(
+                "fleet/fleet-alpha/rpi-001/heartbeat/ping",
+                b"{}",
+                QoS::AtMostOnce,
+            )
             .await
-            .map_err(|e| MqttError::Subscribe(e.to_string()))
+            .unwrap();
+        assert_eq!(channel.offline_buffer().unwrap().len(), 1);
     }
 }
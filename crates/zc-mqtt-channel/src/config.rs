@@ -1,7 +1,10 @@
-use serde::Deserialize;
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use zc_protocol::encoding::Encoding;
 
 /// MQTT connection configuration, loadable from TOML or environment.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct MqttConfig {
     /// MQTT broker hostname (e.g., AWS IoT endpoint).
     pub broker_host: String,
@@ -25,6 +28,31 @@ pub struct MqttConfig {
     /// Keep-alive interval in seconds.
     #[serde(default = "default_keepalive")]
     pub keepalive_secs: u16,
+    /// MQTT protocol version: `"v311"` (default) or `"v5"`.
+    #[serde(default)]
+    pub protocol: MqttProtocol,
+    /// Encoding of published payloads: `"json"` (default) or `"cbor"`.
+    /// Only pick CBOR once the cloud reads it; inbound payloads may be
+    /// either.
+    #[serde(default)]
+    pub encoding: Encoding,
+    /// Start a clean session on every connect. With `false` the broker keeps
+    /// subscriptions and queues QoS 1 messages while the client is away.
+    #[serde(default = "default_clean_session")]
+    pub clean_session: bool,
+    /// Register a Last Will so the broker publishes a retained "offline"
+    /// presence message when the connection drops (on by default).
+    #[serde(default = "default_last_will")]
+    pub last_will: bool,
+    /// QoS per outbound message class.
+    #[serde(default)]
+    pub qos: QosConfig,
+    /// Buffering of outbound messages while the broker is unreachable.
+    #[serde(default)]
+    pub offline_buffer: OfflineBufferConfig,
+    /// Reconnect backoff and storm detection.
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
 }
 
 fn default_use_tls() -> bool {
@@ -38,3 +66,232 @@ fn default_port() -> u16 {
 fn default_keepalive() -> u16 {
     30
 }
+
+fn default_clean_session() -> bool {
+    true
+}
+
+fn default_last_will() -> bool {
+    true
+}
+
+/// MQTT protocol version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MqttProtocol {
+    /// MQTT 3.1.1.
+    #[default]
+    V311,
+    /// MQTT 5: message expiry, user properties and reason codes.
+    V5,
+}
+
+impl std::str::FromStr for MqttProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "v311" | "3.1.1" | "4" => Ok(Self::V311),
+            "v5" | "5" => Ok(Self::V5),
+            other => Err(format!(
+                "unknown MQTT protocol '{other}' (expected v311 or v5)"
+            )),
+        }
+    }
+}
+
+/// MQTT QoS level, written as 0, 1 or 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum QosLevel {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl TryFrom<u8> for QosLevel {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            0 => Ok(Self::AtMostOnce),
+            1 => Ok(Self::AtLeastOnce),
+            2 => Ok(Self::ExactlyOnce),
+            other => Err(format!("QoS must be 0, 1 or 2, got {other}")),
+        }
+    }
+}
+
+impl From<QosLevel> for QoS {
+    fn from(level: QosLevel) -> Self {
+        match level {
+            QosLevel::AtMostOnce => QoS::AtMostOnce,
+            QosLevel::AtLeastOnce => QoS::AtLeastOnce,
+            QosLevel::ExactlyOnce => QoS::ExactlyOnce,
+        }
+    }
+}
+
+/// QoS per outbound message class (`[mqtt.qos]`). Everything defaults to
+/// QoS 1.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QosConfig {
+    /// Command responses, streamed chunks and acks.
+    #[serde(default = "default_qos")]
+    pub response: QosLevel,
+    /// Telemetry batches.
+    #[serde(default = "default_qos")]
+    pub telemetry: QosLevel,
+    /// Heartbeats.
+    #[serde(default = "default_qos")]
+    pub heartbeat: QosLevel,
+    /// Everything else published through the typed helpers (self-test
+    /// reports).
+    #[serde(default = "default_qos")]
+    pub other: QosLevel,
+}
+
+fn default_qos() -> QosLevel {
+    QosLevel::AtLeastOnce
+}
+
+impl Default for QosConfig {
+    fn default() -> Self {
+        Self {
+            response: default_qos(),
+            telemetry: default_qos(),
+            heartbeat: default_qos(),
+            other: default_qos(),
+        }
+    }
+}
+
+/// Outbound buffer settings (`[mqtt.offline_buffer]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OfflineBufferConfig {
+    /// Hold messages published while disconnected and send them after
+    /// reconnecting. On by default.
+    #[serde(default = "default_buffer_enabled")]
+    pub enabled: bool,
+    /// Messages kept; the oldest is dropped when full.
+    #[serde(default = "default_buffer_capacity")]
+    pub capacity: usize,
+    /// JSON-lines file that keeps the buffer across restarts. In memory
+    /// only when unset.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+fn default_buffer_enabled() -> bool {
+    true
+}
+
+fn default_buffer_capacity() -> usize {
+    1000
+}
+
+impl Default for OfflineBufferConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_buffer_enabled(),
+            capacity: default_buffer_capacity(),
+            path: None,
+        }
+    }
+}
+
+/// Reconnect settings (`[mqtt.reconnect]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReconnectConfig {
+    /// Wait after the first failed attempt; doubles per further failure.
+    #[serde(default = "default_initial_delay")]
+    pub initial_delay_secs: u64,
+    /// Upper bound of the wait.
+    #[serde(default = "default_max_delay")]
+    pub max_delay_secs: u64,
+    /// Reconnects within `storm_window_secs` that count as a storm.
+    #[serde(default = "default_storm_threshold")]
+    pub storm_threshold: u32,
+    #[serde(default = "default_storm_window")]
+    pub storm_window_secs: u64,
+}
+
+fn default_initial_delay() -> u64 {
+    1
+}
+
+fn default_max_delay() -> u64 {
+    60
+}
+
+fn default_storm_threshold() -> u32 {
+    5
+}
+
+fn default_storm_window() -> u64 {
+    300
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay_secs: default_initial_delay(),
+            max_delay_secs: default_max_delay(),
+            storm_threshold: default_storm_threshold(),
+            storm_window_secs: default_storm_window(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qos_and_buffer_settings_parse() {
+        let config: MqttConfig = serde_json::from_value(serde_json::json!({
+            "broker_host": "localhost",
+            "client_id": "rpi-001",
+            "clean_session": false,
+            "qos": {"heartbeat": 0, "response": 2},
+            "offline_buffer": {"capacity": 50, "path": "/tmp/outbox.jsonl"}
+        }))
+        .unwrap();
+        assert!(!config.clean_session);
+        assert!(config.last_will);
+        assert_eq!(config.protocol, MqttProtocol::V311);
+        assert_eq!(config.encoding, Encoding::Json);
+        assert_eq!(config.qos.heartbeat, QosLevel::AtMostOnce);
+        assert_eq!(config.qos.response, QosLevel::ExactlyOnce);
+        assert_eq!(config.qos.telemetry, QosLevel::AtLeastOnce);
+        assert!(config.offline_buffer.enabled);
+        assert_eq!(config.offline_buffer.capacity, 50);
+        assert_eq!(config.reconnect.max_delay_secs, 60);
+
+        let bad = serde_json::json!({
+            "broker_host": "localhost",
+            "client_id": "rpi-001",
+            "qos": {"telemetry": 3}
+        });
+        assert!(serde_json::from_value::<MqttConfig>(bad).is_err());
+    }
+
+    #[test]
+    fn protocol_parses() {
+        assert_eq!("v5".parse::<MqttProtocol>().unwrap(), MqttProtocol::V5);
+        assert_eq!("3.1.1".parse::<MqttProtocol>().unwrap(), MqttProtocol::V311);
+        assert!("v6".parse::<MqttProtocol>().is_err());
+        let config: MqttConfig = serde_json::from_value(serde_json::json!({
+            "broker_host": "localhost",
+            "client_id": "rpi-001",
+            "protocol": "v5",
+            "encoding": "cbor"
+        }))
+        .unwrap();
+        assert_eq!(config.protocol, MqttProtocol::V5);
+        assert_eq!(config.encoding, Encoding::Cbor);
+    }
+}
@@ -8,6 +8,18 @@ pub enum MqttError {
     #[error("connection error: {0}")]
     Connection(String),
 
+    /// The broker refused the connection; carries its reason code.
+    #[error("connection refused by broker: {0}")]
+    ConnectionRefused(String),
+
+    /// An MQTT v5 broker answered a publish or subscribe with a failure
+    /// reason code.
+    #[error("{packet} rejected by broker: {reason}")]
+    Rejected {
+        packet: &'static str,
+        reason: String,
+    },
+
     #[error("publish error: {0}")]
     Publish(String),
 
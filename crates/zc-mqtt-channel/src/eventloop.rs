@@ -0,0 +1,170 @@
+//! Protocol-independent MQTT event loop.
+//!
+//! [`crate::MqttChannel::new`] returns a [`MqttEventLoop`] for either
+//! MQTT 3.1.1 or MQTT 5. [`MqttEventLoop::poll`] reduces the packets of both
+//! to the few [`ChannelEvent`]s the agent and the cloud bridge act on, so
+//! neither has to care which protocol is in use. Incoming v5 publishes are
+//! converted to the 3.1.1 [`Publish`] type that [`crate::classify`] takes,
+//! with their properties alongside.
+
+use rumqttc::v5::mqttbytes::v5::{Packet as PacketV5, PubAckReason, SubscribeReasonCode};
+use rumqttc::{Event, Packet, Publish, QoS};
+
+use crate::config::MqttConfig;
+use crate::error::{MqttError, MqttResult};
+use crate::properties::MessageProperties;
+
+/// Event loop of an [`crate::MqttChannel`]; drive it with [`Self::poll`].
+///
+/// Both loops are boxed; they are large and differ widely in size.
+pub enum MqttEventLoop {
+    V311(Box<rumqttc::EventLoop>),
+    V5(Box<rumqttc::v5::EventLoop>),
+}
+
+/// What a poll produced.
+#[derive(Debug)]
+pub enum ChannelEvent {
+    /// The broker accepted the connection (`ConnAck`).
+    Connected { session_present: bool },
+    /// An incoming publish. `properties` is empty under MQTT 3.1.1.
+    Publish {
+        publish: Publish,
+        properties: MessageProperties,
+    },
+    /// The broker refused a publish or subscription
+    /// ([`MqttError::Rejected`]); the connection stays up.
+    Rejected(MqttError),
+    /// Acks, pings and outgoing packets.
+    Other,
+}
+
+impl From<rumqttc::EventLoop> for MqttEventLoop {
+    fn from(eventloop: rumqttc::EventLoop) -> Self {
+        Self::V311(Box::new(eventloop))
+    }
+}
+
+impl From<rumqttc::v5::EventLoop> for MqttEventLoop {
+    fn from(eventloop: rumqttc::v5::EventLoop) -> Self {
+        Self::V5(Box::new(eventloop))
+    }
+}
+
+impl MqttEventLoop {
+    /// Switch to the TLS certificates now on disk (see
+    /// [`crate::tls::install_certificate`]). The connection is dropped and
+    /// the next [`Self::poll`] reconnects with them; unacknowledged
+    /// publishes are resent. On error nothing changes.
+    pub fn reload_tls(&mut self, config: &MqttConfig) -> MqttResult<()> {
+        let transport = crate::tls::load_tls_transport(config)?;
+        match self {
+            Self::V311(eventloop) => {
+                eventloop.mqtt_options.set_transport(transport);
+                eventloop.clean();
+            }
+            Self::V5(eventloop) => {
+                eventloop.options.set_transport(transport);
+                eventloop.clean();
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for the next event. An error means the connection is down;
+    /// the next call reconnects. A broker refusal surfaces as
+    /// [`MqttError::ConnectionRefused`] with its reason code.
+    pub async fn poll(&mut self) -> MqttResult<ChannelEvent> {
+        match self {
+            Self::V311(eventloop) => match eventloop.poll().await {
+                Ok(Event::Incoming(packet)) => Ok(v311_event(packet)),
+                Ok(Event::Outgoing(_)) => Ok(ChannelEvent::Other),
+                Err(rumqttc::ConnectionError::ConnectionRefused(code)) => {
+                    Err(MqttError::ConnectionRefused(format!("{code:?}")))
+                }
+                Err(e) => Err(MqttError::Connection(e.to_string())),
+            },
+            Self::V5(eventloop) => match eventloop.poll().await {
+                Ok(rumqttc::v5::Event::Incoming(packet)) => v5_event(packet),
+                Ok(rumqttc::v5::Event::Outgoing(_)) => Ok(ChannelEvent::Other),
+                Err(rumqttc::v5::ConnectionError::ConnectionRefused(code)) => {
+                    Err(MqttError::ConnectionRefused(format!("{code:?}")))
+                }
+                Err(e) => Err(MqttError::Connection(e.to_string())),
+            },
+        }
+    }
+}
+
+fn v311_event(packet: Packet) -> ChannelEvent {
+    match packet {
+        Packet::ConnAck(ack) => ChannelEvent::Connected {
+            session_present: ack.session_present,
+        },
+        Packet::Publish(publish) => ChannelEvent::Publish {
+            publish,
+            properties: MessageProperties::default(),
+        },
+        Packet::SubAck(ack)
+            if ack
+                .return_codes
+                .iter()
+                .any(|c| matches!(c, rumqttc::SubscribeReasonCode::Failure)) =>
+        {
+            ChannelEvent::Rejected(MqttError::Rejected {
+                packet: "subscribe",
+                reason: "Failure".into(),
+            })
+        }
+        _ => ChannelEvent::Other,
+    }
+}
+
+fn v5_event(packet: PacketV5) -> MqttResult<ChannelEvent> {
+    Ok(match packet {
+        PacketV5::ConnAck(ack) => ChannelEvent::Connected {
+            session_present: ack.session_present,
+        },
+        PacketV5::Publish(publish) => {
+            let topic = String::from_utf8_lossy(&publish.topic).into_owned();
+            let qos = match publish.qos {
+                rumqttc::v5::mqttbytes::QoS::AtMostOnce => QoS::AtMostOnce,
+                rumqttc::v5::mqttbytes::QoS::AtLeastOnce => QoS::AtLeastOnce,
+                rumqttc::v5::mqttbytes::QoS::ExactlyOnce => QoS::ExactlyOnce,
+            };
+            ChannelEvent::Publish {
+                publish: Publish::new(topic, qos, publish.payload.to_vec()),
+                properties: MessageProperties::from_v5(publish.properties),
+            }
+        }
+        PacketV5::PubAck(ack)
+            if !matches!(
+                ack.reason,
+                PubAckReason::Success | PubAckReason::NoMatchingSubscribers
+            ) =>
+        {
+            ChannelEvent::Rejected(MqttError::Rejected {
+                packet: "publish",
+                reason: format!("{:?}", ack.reason),
+            })
+        }
+        PacketV5::SubAck(ack) => match ack
+            .return_codes
+            .iter()
+            .find(|c| !matches!(c, SubscribeReasonCode::Success(_)))
+        {
+            Some(code) => ChannelEvent::Rejected(MqttError::Rejected {
+                packet: "subscribe",
+                reason: format!("{code:?}"),
+            }),
+            None => ChannelEvent::Other,
+        },
+        PacketV5::Disconnect(disconnect) => {
+            return Err(MqttError::Connection(format!(
+                "disconnected by broker: {:?}",
+                disconnect.reason_code
+            )));
+        }
+        _ => ChannelEvent::Other,
+    })
+}
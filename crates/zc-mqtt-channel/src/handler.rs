@@ -4,21 +4,30 @@
 //! so the fleet agent can dispatch them without topic string matching.
 
 use rumqttc::Publish;
-use serde_json;
 
-use zc_protocol::commands::CommandEnvelope;
-use zc_protocol::shadows::ShadowDelta;
+use zc_protocol::commands::{CommandCancel, CommandEnvelope};
+use zc_protocol::encoding::decode;
+use zc_protocol::shadows::{ShadowDelta, ShadowDocument};
 use zc_protocol::topics;
+use zc_protocol::transfer::{TransferChunk, TransferOffer};
 
 /// A classified incoming MQTT message.
 #[derive(Debug)]
 pub enum IncomingMessage {
     /// Command request from the cloud (device-specific or broadcast).
     Command(CommandEnvelope),
+    /// Request to abort a queued or running command.
+    CommandCancel(CommandCancel),
     /// Shadow delta — desired state diverged from reported.
     ShadowDelta(ShadowDelta),
+    /// Full shadow document, in reply to a shadow get request.
+    ShadowDocument(ShadowDocument),
     /// Config update broadcast for the fleet.
     ConfigUpdate(serde_json::Value),
+    /// Cloud starts or resumes a file transfer.
+    TransferOffer(TransferOffer),
+    /// File data pushed by the cloud.
+    TransferChunk(TransferChunk),
     /// Unrecognized topic or payload.
     Unknown { topic: String, payload: Vec<u8> },
 }
@@ -26,7 +35,7 @@ pub enum IncomingMessage {
 /// Classify a raw MQTT publish into a typed message.
 ///
 /// Uses `zc_protocol::topics::parse_topic` to extract category/action,
-/// then attempts JSON deserialization into the appropriate type.
+/// then deserializes the JSON or CBOR payload into the appropriate type.
 pub fn classify(publish: &Publish) -> IncomingMessage {
     let topic = &publish.topic;
     let payload = &publish.payload;
@@ -39,27 +48,55 @@ pub fn classify(publish: &Publish) -> IncomingMessage {
     };
 
     match (parsed.category.as_str(), parsed.action.as_str()) {
-        ("command", "request") => match serde_json::from_slice::<CommandEnvelope>(payload) {
+        ("command", "request") => match decode::<CommandEnvelope>(payload) {
             Ok(envelope) => IncomingMessage::Command(envelope),
             Err(_) => IncomingMessage::Unknown {
                 topic: topic.clone(),
                 payload: payload.to_vec(),
             },
         },
-        ("shadow", "delta") => match serde_json::from_slice::<ShadowDelta>(payload) {
+        ("command", "cancel") => match decode::<CommandCancel>(payload) {
+            Ok(cancel) => IncomingMessage::CommandCancel(cancel),
+            Err(_) => IncomingMessage::Unknown {
+                topic: topic.clone(),
+                payload: payload.to_vec(),
+            },
+        },
+        ("shadow", "delta") => match decode::<ShadowDelta>(payload) {
             Ok(delta) => IncomingMessage::ShadowDelta(delta),
             Err(_) => IncomingMessage::Unknown {
                 topic: topic.clone(),
                 payload: payload.to_vec(),
             },
         },
-        ("config", "update") => match serde_json::from_slice::<serde_json::Value>(payload) {
+        ("shadow", "document") => match decode::<ShadowDocument>(payload) {
+            Ok(document) => IncomingMessage::ShadowDocument(document),
+            Err(_) => IncomingMessage::Unknown {
+                topic: topic.clone(),
+                payload: payload.to_vec(),
+            },
+        },
+        ("config", "update") => match decode::<serde_json::Value>(payload) {
             Ok(value) => IncomingMessage::ConfigUpdate(value),
             Err(_) => IncomingMessage::Unknown {
                 topic: topic.clone(),
                 payload: payload.to_vec(),
             },
         },
+        ("transfer", "offer") => match decode::<TransferOffer>(payload) {
+            Ok(offer) => IncomingMessage::TransferOffer(offer),
+            Err(_) => IncomingMessage::Unknown {
+                topic: topic.clone(),
+                payload: payload.to_vec(),
+            },
+        },
+        ("transfer", "chunk") => match decode::<TransferChunk>(payload) {
+            Ok(chunk) => IncomingMessage::TransferChunk(chunk),
+            Err(_) => IncomingMessage::Unknown {
+                topic: topic.clone(),
+                payload: payload.to_vec(),
+            },
+        },
         _ => IncomingMessage::Unknown {
             topic: topic.clone(),
             payload: payload.to_vec(),
@@ -88,6 +125,16 @@ mod tests {
         assert!(matches!(msg, IncomingMessage::Command(ref e) if e.device_id == "rpi-001"));
     }
 
+    #[test]
+    fn classify_cbor_command_request() {
+        let cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "operator@test.com");
+        let payload =
+            zc_protocol::encoding::encode(&cmd, zc_protocol::encoding::Encoding::Cbor).unwrap();
+        let publish = make_publish("fleet/fleet-alpha/rpi-001/command/request", &payload);
+        let msg = classify(&publish);
+        assert!(matches!(msg, IncomingMessage::Command(ref e) if e.id == cmd.id));
+    }
+
     #[test]
     fn classify_broadcast_command() {
         let cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "status check", "admin");
@@ -97,6 +144,23 @@ mod tests {
         assert!(matches!(msg, IncomingMessage::Command(_)));
     }
 
+    #[test]
+    fn classify_command_cancel() {
+        let cancel = CommandCancel {
+            command_id: CommandEnvelope::new("fleet-alpha", "rpi-001", "monitor CAN", "admin").id,
+            device_id: "rpi-001".into(),
+            requested_by: "admin".into(),
+            reason: Some("wrong vehicle".into()),
+            requested_at: chrono::Utc::now(),
+        };
+        let payload = serde_json::to_vec(&cancel).unwrap();
+        let publish = make_publish("fleet/fleet-alpha/rpi-001/command/cancel", &payload);
+        let msg = classify(&publish);
+        assert!(
+            matches!(msg, IncomingMessage::CommandCancel(ref c) if c.reason.as_deref() == Some("wrong vehicle"))
+        );
+    }
+
     #[test]
     fn classify_shadow_delta() {
         let delta = zc_protocol::shadows::ShadowDelta {
@@ -112,6 +176,24 @@ mod tests {
         assert!(matches!(msg, IncomingMessage::ShadowDelta(ref d) if d.version == 5));
     }
 
+    #[test]
+    fn classify_shadow_document() {
+        let document = zc_protocol::shadows::ShadowDocument {
+            device_id: "rpi-001".into(),
+            shadow_name: "config".into(),
+            desired: json!({"firmware_version": "0.2.0"}),
+            reported: json!({}),
+            delta: json!({"firmware_version": "0.2.0"}),
+            version: 3,
+            client_token: None,
+            timestamp: chrono::Utc::now(),
+        };
+        let payload = serde_json::to_vec(&document).unwrap();
+        let publish = make_publish("fleet/fleet-alpha/rpi-001/shadow/document", &payload);
+        let msg = classify(&publish);
+        assert!(matches!(msg, IncomingMessage::ShadowDocument(ref d) if d.version == 3));
+    }
+
     #[test]
     fn classify_config_update() {
         let config = json!({"telemetry_interval_secs": 60});
@@ -123,6 +205,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn classify_transfer_messages() {
+        let chunk = TransferChunk::new(uuid::Uuid::now_v7(), 0, b"BO_ 100 Engine: 8 ECU");
+        let payload = serde_json::to_vec(&chunk).unwrap();
+        let publish = make_publish("fleet/fleet-alpha/rpi-001/transfer/chunk", &payload);
+        assert!(
+            matches!(classify(&publish), IncomingMessage::TransferChunk(ref c) if c.offset == 0)
+        );
+
+        let publish = make_publish("fleet/fleet-alpha/rpi-001/transfer/offer", &payload);
+        assert!(matches!(
+            classify(&publish),
+            IncomingMessage::Unknown { .. }
+        ));
+    }
+
     #[test]
     fn classify_unknown_topic() {
         let publish = make_publish("some/random/topic", b"data");
@@ -5,20 +5,37 @@
 //! - `MqttChannel` with TLS (mTLS) for production
 //! - `MockChannel` for testing without a broker
 //! - `ShadowClient` for device shadow operations
+//! - `TransferClient` for the device side of file transfers
+//! - `MqttEventLoop` driving either MQTT 3.1.1 or MQTT 5, with
+//!   `MessageProperties` (expiry, user properties) in v5 mode
+//! - `ConnectionMonitor` for reconnect backoff and connection health
+//! - `OfflineBuffer` holding outbound messages while the broker is unreachable
 //! - `IncomingMessage` classification for dispatching events
 
+pub mod buffer;
 pub mod channel;
 pub mod config;
 pub mod error;
+pub mod eventloop;
 pub mod handler;
 pub mod mock;
+pub mod properties;
+pub mod reconnect;
 pub mod shadows;
 pub mod tls;
+pub mod transfers;
 
 // Re-exports for convenience.
+pub use buffer::OfflineBuffer;
 pub use channel::{Channel, MqttChannel};
-pub use config::MqttConfig;
+pub use config::{
+    MqttConfig, MqttProtocol, OfflineBufferConfig, QosConfig, QosLevel, ReconnectConfig,
+};
 pub use error::{MqttError, MqttResult};
+pub use eventloop::{ChannelEvent, MqttEventLoop};
 pub use handler::{IncomingMessage, classify};
 pub use mock::MockChannel;
+pub use properties::MessageProperties;
+pub use reconnect::{ConnectOutcome, ConnectionMonitor};
 pub use shadows::ShadowClient;
+pub use transfers::TransferClient;
@@ -6,9 +6,11 @@
 use async_trait::async_trait;
 use rumqttc::QoS;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::channel::Channel;
-use crate::error::MqttResult;
+use crate::error::{MqttError, MqttResult};
+use crate::properties::MessageProperties;
 
 /// A recorded publish call.
 #[derive(Debug, Clone)]
@@ -16,6 +18,8 @@ pub struct PublishedMessage {
     pub topic: String,
     pub payload: Vec<u8>,
     pub qos: QoS,
+    /// Properties passed to `publish_with_properties`; empty otherwise.
+    pub properties: MessageProperties,
 }
 
 /// Mock implementation of the `Channel` trait.
@@ -25,6 +29,7 @@ pub struct PublishedMessage {
 pub struct MockChannel {
     published: Mutex<Vec<PublishedMessage>>,
     subscriptions: Mutex<Vec<(String, QoS)>>,
+    fail_publishes: AtomicBool,
 }
 
 impl MockChannel {
@@ -32,9 +37,16 @@ impl MockChannel {
         Self {
             published: Mutex::new(Vec::new()),
             subscriptions: Mutex::new(Vec::new()),
+            fail_publishes: AtomicBool::new(false),
         }
     }
 
+    /// Make later publishes fail (`true`), as on a broken connection, or
+    /// succeed again. Failed publishes are not recorded.
+    pub fn fail_publishes(&self, fail: bool) {
+        self.fail_publishes.store(fail, Ordering::SeqCst);
+    }
+
     /// Get all published messages.
     pub fn published(&self) -> Vec<PublishedMessage> {
         self.published.lock().unwrap().clone()
@@ -86,10 +98,25 @@ impl Default for MockChannel {
 #[async_trait]
 impl Channel for MockChannel {
     async fn publish(&self, topic: &str, payload: &[u8], qos: QoS) -> MqttResult<()> {
+        self.publish_with_properties(topic, payload, qos, &MessageProperties::default())
+            .await
+    }
+
+    async fn publish_with_properties(
+        &self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        properties: &MessageProperties,
+    ) -> MqttResult<()> {
+        if self.fail_publishes.load(Ordering::SeqCst) {
+            return Err(MqttError::Publish("mock publish failure".into()));
+        }
         self.published.lock().unwrap().push(PublishedMessage {
             topic: topic.to_string(),
             payload: payload.to_vec(),
             qos,
+            properties: properties.clone(),
         });
         Ok(())
     }
@@ -0,0 +1,100 @@
+//! MQTT v5 publish properties.
+//!
+//! [`MessageProperties`] is the protocol-neutral view used by callers of
+//! [`crate::Channel::publish_with_properties`]. In MQTT 3.1.1 mode the
+//! properties are dropped on the way out and come back empty on the way in.
+
+use rumqttc::v5::mqttbytes::v5::PublishProperties;
+
+/// User property carrying the command correlation ID.
+pub const CORRELATION_ID_PROPERTY: &str = "correlation_id";
+
+/// Properties attached to a publish.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageProperties {
+    /// Seconds the broker may hold the message for a subscriber before
+    /// discarding it, so that e.g. a command is not delivered hours after
+    /// it was sent.
+    pub expiry_secs: Option<u32>,
+    /// Correlation ID, sent as the [`CORRELATION_ID_PROPERTY`] user property.
+    pub correlation_id: Option<String>,
+    /// Other user properties, in order.
+    pub user_properties: Vec<(String, String)>,
+}
+
+impl MessageProperties {
+    /// Properties carrying only a correlation ID.
+    pub fn correlated(correlation_id: impl ToString) -> Self {
+        Self {
+            correlation_id: Some(correlation_id.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Set the message expiry interval.
+    pub fn with_expiry(mut self, secs: u32) -> Self {
+        self.expiry_secs = Some(secs);
+        self
+    }
+
+    /// Append a user property.
+    pub fn with_user_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.user_properties.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn to_v5(&self) -> PublishProperties {
+        let mut user_properties = Vec::with_capacity(self.user_properties.len() + 1);
+        if let Some(id) = &self.correlation_id {
+            user_properties.push((CORRELATION_ID_PROPERTY.to_string(), id.clone()));
+        }
+        user_properties.extend(self.user_properties.iter().cloned());
+        PublishProperties {
+            message_expiry_interval: self.expiry_secs,
+            user_properties,
+            ..PublishProperties::default()
+        }
+    }
+
+    pub(crate) fn from_v5(properties: Option<PublishProperties>) -> Self {
+        let Some(properties) = properties else {
+            return Self::default();
+        };
+        let mut out = Self {
+            expiry_secs: properties.message_expiry_interval,
+            ..Self::default()
+        };
+        for (key, value) in properties.user_properties {
+            if key == CORRELATION_ID_PROPERTY && out.correlation_id.is_none() {
+                out.correlation_id = Some(value);
+            } else {
+                out.user_properties.push((key, value));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v5_roundtrip_moves_correlation_into_user_properties() {
+        let props = MessageProperties::correlated("c-42")
+            .with_expiry(600)
+            .with_user_property("command_id", "abc");
+        let v5 = props.to_v5();
+        assert_eq!(v5.message_expiry_interval, Some(600));
+        assert_eq!(
+            v5.user_properties[0],
+            ("correlation_id".to_string(), "c-42".to_string())
+        );
+        assert_eq!(MessageProperties::from_v5(Some(v5)), props);
+        assert!(MessageProperties::from_v5(None).is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+//! Reconnect backoff and connection health.
+//!
+//! Both MQTT loops (agent and cloud bridge) report every connect and every
+//! event-loop error to a [`ConnectionMonitor`]. It answers how long to wait
+//! before the next attempt — exponential backoff from
+//! [`ReconnectConfig::initial_delay_secs`] up to
+//! [`ReconnectConfig::max_delay_secs`], with jitter so that a fleet losing
+//! the same broker does not reconnect in lockstep — and tracks the
+//! connection state for health reporting. A connection that comes back
+//! [`ReconnectConfig::storm_threshold`] times within
+//! [`ReconnectConfig::storm_window_secs`] is flagged
+//! [`ConnectionState::Unstable`] and counted as a reconnect storm.
+
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use zc_protocol::device::{ConnectionState, MqttHealth};
+
+use crate::config::ReconnectConfig;
+use crate::error::MqttError;
+
+/// What a successful connect meant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectOutcome {
+    /// Not the first connect since start.
+    pub reconnect: bool,
+    /// This reconnect started a storm.
+    pub storm_started: bool,
+}
+
+/// Tracks one MQTT connection; shared by the event-loop owner and whoever
+/// reports health.
+pub struct ConnectionMonitor {
+    config: ReconnectConfig,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    state: ConnectionState,
+    since: DateTime<Utc>,
+    ever_connected: bool,
+    consecutive_failures: u32,
+    reconnects: u64,
+    storms: u64,
+    last_error: Option<String>,
+    in_storm: bool,
+    /// Reconnect times inside the storm window.
+    recent: VecDeque<Instant>,
+}
+
+impl ConnectionMonitor {
+    pub fn new(config: ReconnectConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner {
+                state: ConnectionState::Connecting,
+                since: Utc::now(),
+                ever_connected: false,
+                consecutive_failures: 0,
+                reconnects: 0,
+                storms: 0,
+                last_error: None,
+                in_storm: false,
+                recent: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// The broker accepted the connection.
+    pub fn on_connected(&self) -> ConnectOutcome {
+        self.connected_at(Instant::now())
+    }
+
+    /// The event loop failed. Returns how long to wait before polling
+    /// again.
+    pub fn on_error(&self, error: &MqttError) -> Duration {
+        let failures = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+            inner.last_error = Some(error.to_string());
+            if inner.state != ConnectionState::Reconnecting && inner.ever_connected {
+                inner.state = ConnectionState::Reconnecting;
+                inner.since = Utc::now();
+            }
+            inner.consecutive_failures
+        };
+        jitter(self.delay(failures))
+    }
+
+    /// Current health.
+    pub fn snapshot(&self) -> MqttHealth {
+        let inner = self.inner.lock().unwrap();
+        MqttHealth {
+            state: inner.state,
+            since: inner.since,
+            consecutive_failures: inner.consecutive_failures,
+            reconnects: inner.reconnects,
+            storms: inner.storms,
+            last_error: inner.last_error.clone(),
+        }
+    }
+
+    /// Whether the connection is currently up.
+    pub fn is_connected(&self) -> bool {
+        matches!(
+            self.inner.lock().unwrap().state,
+            ConnectionState::Connected | ConnectionState::Unstable
+        )
+    }
+
+    fn connected_at(&self, now: Instant) -> ConnectOutcome {
+        let mut inner = self.inner.lock().unwrap();
+        let reconnect = inner.ever_connected;
+        inner.ever_connected = true;
+        inner.consecutive_failures = 0;
+
+        let window = Duration::from_secs(self.config.storm_window_secs);
+        while inner
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > window)
+        {
+            inner.recent.pop_front();
+        }
+        if reconnect {
+            inner.reconnects += 1;
+            inner.recent.push_back(now);
+        }
+
+        let storm = inner.recent.len() >= self.config.storm_threshold.max(1) as usize;
+        let storm_started = storm && !inner.in_storm;
+        if storm_started {
+            inner.storms += 1;
+        }
+        inner.in_storm = storm;
+        inner.state = if storm {
+            ConnectionState::Unstable
+        } else {
+            ConnectionState::Connected
+        };
+        inner.since = Utc::now();
+        ConnectOutcome {
+            reconnect,
+            storm_started,
+        }
+    }
+
+    /// Backoff before the `failures`-th consecutive retry, without jitter.
+    fn delay(&self, failures: u32) -> Duration {
+        let initial = Duration::from_secs(self.config.initial_delay_secs.max(1));
+        let max = Duration::from_secs(self.config.max_delay_secs).max(initial);
+        let exp = failures.saturating_sub(1).min(16);
+        initial.saturating_mul(1 << exp).min(max)
+    }
+}
+
+/// "Equal jitter": somewhere between half and all of `delay`.
+fn jitter(delay: Duration) -> Duration {
+    let half = delay / 2;
+    let span = (delay - half).as_millis() as u64;
+    if span == 0 {
+        return delay;
+    }
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    half + Duration::from_millis(random % (span + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> ConnectionMonitor {
+        ConnectionMonitor::new(ReconnectConfig {
+            initial_delay_secs: 1,
+            max_delay_secs: 30,
+            storm_threshold: 3,
+            storm_window_secs: 60,
+        })
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_with_jitter() {
+        let monitor = monitor();
+        let err = MqttError::Connection("refused".into());
+        let mut ceilings = Vec::new();
+        for _ in 0..7 {
+            let delay = monitor.on_error(&err);
+            let ceiling = monitor.delay(monitor.snapshot().consecutive_failures);
+            assert!(
+                delay >= ceiling / 2 && delay <= ceiling,
+                "{delay:?} vs {ceiling:?}"
+            );
+            ceilings.push(ceiling.as_secs());
+        }
+        assert_eq!(ceilings, vec![1, 2, 4, 8, 16, 30, 30]);
+
+        let health = monitor.snapshot();
+        // Never connected yet: still the initial state.
+        assert_eq!(health.state, ConnectionState::Connecting);
+        assert_eq!(
+            health.last_error.as_deref(),
+            Some("connection error: refused")
+        );
+
+        monitor.on_connected();
+        assert_eq!(monitor.snapshot().consecutive_failures, 0);
+        assert_eq!(monitor.delay(1), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn frequent_reconnects_are_a_storm() {
+        let monitor = monitor();
+        let err = MqttError::Connection("reset".into());
+        let start = Instant::now();
+
+        assert!(!monitor.connected_at(start).reconnect);
+        let mut started = Vec::new();
+        for i in 1..=4 {
+            monitor.on_error(&err);
+            assert_eq!(monitor.snapshot().state, ConnectionState::Reconnecting);
+            let outcome = monitor.connected_at(start + Duration::from_secs(i));
+            assert!(outcome.reconnect);
+            started.push(outcome.storm_started);
+        }
+        // The third reconnect within the window starts the storm; the
+        // fourth is part of it.
+        assert_eq!(started, vec![false, false, true, false]);
+        let health = monitor.snapshot();
+        assert_eq!(health.state, ConnectionState::Unstable);
+        assert_eq!(health.reconnects, 4);
+        assert_eq!(health.storms, 1);
+
+        // Quiet for longer than the window: stable again.
+        monitor.on_error(&err);
+        let outcome = monitor.connected_at(start + Duration::from_secs(120));
+        assert!(!outcome.storm_started);
+        assert_eq!(monitor.snapshot().state, ConnectionState::Connected);
+    }
+}
@@ -1,13 +1,15 @@
 //! AWS IoT Device Shadow MQTT operations.
 //!
-//! Provides typed helpers for publishing shadow updates and subscribing
-//! to shadow delta notifications via the MQTT channel.
+//! Provides typed helpers for publishing shadow updates, requesting the
+//! full shadow document and subscribing to shadow delta notifications via
+//! the MQTT channel.
 
 use rumqttc::QoS;
 
 use crate::channel::Channel;
 use crate::error::{MqttError, MqttResult};
-use zc_protocol::{shadows::ShadowUpdate, topics};
+use zc_protocol::shadows::{ShadowGetRequest, ShadowUpdate};
+use zc_protocol::topics;
 
 /// Shadow operations backed by a `Channel` implementation.
 ///
@@ -57,6 +59,26 @@ impl<'a, C: Channel> ShadowClient<'a, C> {
         let topic = topics::shadow_delta(&self.fleet_id, &self.device_id);
         self.channel.subscribe(&topic, QoS::AtLeastOnce).await
     }
+
+    /// Subscribe to shadow documents sent in reply to [`Self::request_document`].
+    pub async fn subscribe_document(&self) -> MqttResult<()> {
+        let topic = topics::shadow_document(&self.fleet_id, &self.device_id);
+        self.channel.subscribe(&topic, QoS::AtLeastOnce).await
+    }
+
+    /// Ask the cloud for a shadow's full document (desired, reported and
+    /// outstanding delta).
+    pub async fn request_document(&self, shadow_name: &str) -> MqttResult<()> {
+        let request = ShadowGetRequest {
+            device_id: self.device_id.clone(),
+            shadow_name: shadow_name.to_string(),
+            client_token: None,
+        };
+        let topic = topics::shadow_get(&self.fleet_id, &self.device_id);
+        let bytes =
+            serde_json::to_vec(&request).map_err(|e| MqttError::Serialization(e.to_string()))?;
+        self.channel.publish(&topic, &bytes, QoS::AtLeastOnce).await
+    }
 }
 
 #[cfg(test)]
@@ -113,4 +135,20 @@ mod tests {
 
         assert!(mock.is_subscribed_to("fleet/fleet-alpha/rpi-001/shadow/delta"));
     }
+
+    #[tokio::test]
+    async fn request_shadow_document() {
+        let mock = MockChannel::new();
+        let client = ShadowClient::new(&mock, "fleet-alpha", "rpi-001");
+
+        client.subscribe_document().await.unwrap();
+        client.request_document("config").await.unwrap();
+
+        assert!(mock.is_subscribed_to("fleet/fleet-alpha/rpi-001/shadow/document"));
+        let msgs = mock.published_to("fleet/fleet-alpha/rpi-001/shadow/get");
+        assert_eq!(msgs.len(), 1);
+        let request: ShadowGetRequest = serde_json::from_slice(&msgs[0].payload).unwrap();
+        assert_eq!(request.device_id, "rpi-001");
+        assert_eq!(request.shadow_name, "config");
+    }
 }
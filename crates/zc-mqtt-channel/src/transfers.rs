@@ -0,0 +1,85 @@
+//! Device side of file transfers over MQTT.
+//!
+//! Typed helpers for the device-to-cloud transfer messages; see
+//! [`zc_protocol::transfer`] for the protocol.
+
+use rumqttc::QoS;
+use serde::Serialize;
+
+use crate::channel::Channel;
+use crate::error::{MqttError, MqttResult};
+use zc_protocol::topics;
+use zc_protocol::transfer::{TransferAck, TransferChunk, TransferComplete};
+
+/// Transfer publishes backed by a `Channel` implementation.
+pub struct TransferClient<'a, C: Channel + ?Sized> {
+    channel: &'a C,
+    fleet_id: String,
+    device_id: String,
+}
+
+impl<'a, C: Channel + ?Sized> TransferClient<'a, C> {
+    pub fn new(channel: &'a C, fleet_id: impl Into<String>, device_id: impl Into<String>) -> Self {
+        Self {
+            channel,
+            fleet_id: fleet_id.into(),
+            device_id: device_id.into(),
+        }
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    /// Report progress on a transfer.
+    pub async fn publish_ack(&self, ack: &TransferAck) -> MqttResult<()> {
+        let topic = topics::transfer_ack(&self.fleet_id, &self.device_id);
+        self.publish(&topic, ack).await
+    }
+
+    /// Send a chunk of a fetched file.
+    pub async fn publish_upload(&self, chunk: &TransferChunk) -> MqttResult<()> {
+        let topic = topics::transfer_upload(&self.fleet_id, &self.device_id);
+        self.publish(&topic, chunk).await
+    }
+
+    /// Report the end of a transfer.
+    pub async fn publish_complete(&self, complete: &TransferComplete) -> MqttResult<()> {
+        let topic = topics::transfer_complete(&self.fleet_id, &self.device_id);
+        self.publish(&topic, complete).await
+    }
+
+    async fn publish<T: Serialize>(&self, topic: &str, payload: &T) -> MqttResult<()> {
+        let bytes =
+            serde_json::to_vec(payload).map_err(|e| MqttError::Serialization(e.to_string()))?;
+        self.channel.publish(topic, &bytes, QoS::AtLeastOnce).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockChannel;
+
+    #[tokio::test]
+    async fn publishes_on_device_topics() {
+        let mock = MockChannel::new();
+        let client = TransferClient::new(&mock, "fleet-alpha", "rpi-001");
+        let transfer_id = uuid::Uuid::now_v7();
+
+        client
+            .publish_upload(&TransferChunk::new(transfer_id, 0, b"core"))
+            .await
+            .unwrap();
+        client
+            .publish_complete(&TransferComplete::failed(transfer_id, "rpi-001", "gone"))
+            .await
+            .unwrap();
+
+        let msgs = mock.published();
+        assert_eq!(msgs[0].topic, "fleet/fleet-alpha/rpi-001/transfer/upload");
+        assert_eq!(msgs[1].topic, "fleet/fleet-alpha/rpi-001/transfer/complete");
+        let complete: TransferComplete = serde_json::from_slice(&msgs[1].payload).unwrap();
+        assert_eq!(complete.error.as_deref(), Some("gone"));
+    }
+}
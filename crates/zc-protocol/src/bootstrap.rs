@@ -0,0 +1,124 @@
+//! First-connect onboarding with a one-time claim code.
+//!
+//! An operator creates a claim code in the cloud for a fleet (optionally
+//! naming the device ID) and puts it in the agent's config. An agent that
+//! has no identity yet connects, subscribes to its bootstrap result topic
+//! and publishes a [`ClaimRequest`] with the code and its hardware
+//! identity on the bootstrap claim topic. The cloud checks the code,
+//! provisions the device and answers with a [`ClaimResult`] carrying the
+//! assigned fleet and device IDs and initial runtime settings. The agent
+//! keeps that identity and connects as the device from then on.
+//!
+//! Bootstrap topics are keyed by hardware ID rather than fleet and device,
+//! which the agent does not know yet. Bootstrap messages are always JSON:
+//! no encoding has been negotiated at that point.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Claim a device identity. Published by the agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimRequest {
+    /// New for every attempt; echoed in the result so an agent ignores
+    /// answers to earlier attempts.
+    pub request_id: Uuid,
+    /// The one-time code, as configured on the device.
+    pub claim_code: String,
+    /// Stable hardware fingerprint (normally `/etc/machine-id`); must match
+    /// the topic.
+    pub hardware_id: String,
+    /// Hardware platform as the device registry names it (`raspberry_pi_5`,
+    /// `industrial_sbc`, or any custom name).
+    pub hardware_type: String,
+    pub agent_version: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Whether a claim was accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimStatus {
+    Accepted,
+    Rejected,
+}
+
+/// Answer to a [`ClaimRequest`]. Published by the cloud.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimResult {
+    /// The [`ClaimRequest::request_id`] answered.
+    pub request_id: Uuid,
+    pub status: ClaimStatus,
+    /// Accepted: fleet the device now belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fleet_id: Option<String>,
+    /// Accepted: the device's ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    /// Accepted: initial runtime settings, in the shape of a broadcast
+    /// config update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<serde_json::Value>,
+    /// Rejected: why (unknown, expired or used code, ...).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ClaimResult {
+    /// An accepted claim for `request_id`.
+    pub fn accepted(
+        request_id: Uuid,
+        fleet_id: impl Into<String>,
+        device_id: impl Into<String>,
+        config: Option<serde_json::Value>,
+    ) -> Self {
+        Self {
+            request_id,
+            status: ClaimStatus::Accepted,
+            fleet_id: Some(fleet_id.into()),
+            device_id: Some(device_id.into()),
+            config,
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// A rejected claim for `request_id`.
+    pub fn rejected(request_id: Uuid, error: impl Into<String>) -> Self {
+        Self {
+            request_id,
+            status: ClaimStatus::Rejected,
+            fleet_id: None,
+            device_id: None,
+            config: None,
+            error: Some(error.into()),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claim_result_omits_unset_fields() {
+        let id = Uuid::now_v7();
+        let rejected = serde_json::to_value(ClaimResult::rejected(id, "code expired")).unwrap();
+        assert_eq!(rejected["status"], "rejected");
+        assert_eq!(rejected["error"], "code expired");
+        assert!(rejected.get("device_id").is_none());
+
+        let accepted = ClaimResult::accepted(
+            id,
+            "fleet-alpha",
+            "rpi-101",
+            Some(serde_json::json!({"version": 1, "heartbeat_interval_secs": 15})),
+        );
+        let json = serde_json::to_string(&accepted).unwrap();
+        let back: ClaimResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, accepted);
+        assert_eq!(back.device_id.as_deref(), Some("rpi-101"));
+    }
+}
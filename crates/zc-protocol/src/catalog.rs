@@ -0,0 +1,165 @@
+//! Tool catalog advertised by the agent.
+//!
+//! The agent publishes the tools it can run — name, description and JSON
+//! schema of the arguments — in the `tool_catalog` shadow on startup. Cloud
+//! inference builds its tool list and validation from the target device's
+//! catalog instead of a hard-coded copy that drifts from the agent's
+//! registry (bench tools, tools added in newer agent versions).
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Shadow whose reported state is the agent's [`ToolCatalog`].
+pub const TOOL_CATALOG_SHADOW: &str = "tool_catalog";
+
+/// One tool the agent can run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of `tool_args` (`{"type": "object", "properties": ...}`).
+    #[serde(default)]
+    pub parameters_schema: Value,
+}
+
+impl ToolSpec {
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters_schema: Value,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters_schema,
+        }
+    }
+
+    /// `n. name — description. Args: ...` for an LLM prompt.
+    pub fn prompt_line(&self, n: usize) -> String {
+        format!(
+            "{n}. {} — {}. Args: {}",
+            self.name,
+            self.description.trim_end_matches('.'),
+            describe_args(&self.parameters_schema)
+        )
+    }
+}
+
+/// The tools of one agent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCatalog {
+    #[serde(default)]
+    pub tools: Vec<ToolSpec>,
+}
+
+impl ToolCatalog {
+    pub fn new(tools: Vec<ToolSpec>) -> Self {
+        Self { tools }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.iter().any(|t| t.name == name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tools.iter().map(|t| t.name.clone()).collect()
+    }
+
+    /// Numbered tool list for an LLM prompt.
+    pub fn prompt_list(&self) -> String {
+        self.tools
+            .iter()
+            .enumerate()
+            .map(|(i, tool)| tool.prompt_line(i + 1))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// `{}` or `{"pid": <string, required> OBD-II PID, ...}` from a JSON schema.
+fn describe_args(schema: &Value) -> String {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return "{}".into();
+    };
+    if properties.is_empty() {
+        return "{}".into();
+    }
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let args: Vec<String> = properties
+        .iter()
+        .map(|(name, property)| {
+            let mut kind = property
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or("any")
+                .to_string();
+            if required.contains(&name.as_str()) {
+                kind.push_str(", required");
+            }
+            if let Some(default) = property.get("default") {
+                kind.push_str(&format!(", default {default}"));
+            }
+            match property.get("description").and_then(Value::as_str) {
+                Some(description) => format!("\"{name}\": <{kind}> {description}"),
+                None => format!("\"{name}\": <{kind}>"),
+            }
+        })
+        .collect();
+    format!("{{{}}}", args.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn prompt_lines_describe_arguments() {
+        let catalog = ToolCatalog::new(vec![
+            ToolSpec::new("read_vin", "Read the VIN.", json!({"type": "object"})),
+            ToolSpec::new(
+                "read_pid",
+                "Read an OBD-II PID",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "pid": {"type": "string", "description": "PID in hex"},
+                        "timeout_ms": {"type": "integer", "default": 1000}
+                    },
+                    "required": ["pid"]
+                }),
+            ),
+        ]);
+        assert_eq!(
+            catalog.prompt_list(),
+            "1. read_vin — Read the VIN. Args: {}\n\
+             2. read_pid — Read an OBD-II PID. Args: {\"pid\": <string, required> PID in hex, \
+             \"timeout_ms\": <integer, default 1000>}"
+        );
+        assert!(catalog.contains("read_pid"));
+        assert!(!catalog.contains("send_frame"));
+    }
+
+    #[test]
+    fn catalog_roundtrips_through_shadow_json() {
+        let catalog: ToolCatalog =
+            serde_json::from_value(json!({"tools": [{"name": "self_test", "description": "x"}]}))
+                .unwrap();
+        assert_eq!(catalog.names(), ["self_test"]);
+        assert!(catalog.tools[0].parameters_schema.is_null());
+        assert!(
+            serde_json::from_value::<ToolCatalog>(json!({}))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -2,8 +2,19 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::conversation::ConversationTurn;
+use crate::plan::PlanStep;
+
+/// `device_id` of an envelope published on the fleet broadcast topic.
+pub const BROADCAST_DEVICE_ID: &str = "*";
+
+/// Key in a shell action's `response_data` holding the command line the
+/// agent ran, after sanitizing what it was sent.
+pub const SHELL_COMMAND_KEY: &str = "shell_command";
+
 /// Envelope wrapping a command sent from cloud to device.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CommandEnvelope {
     /// Unique command ID (UUIDv7 for time-sortability).
     pub id: Uuid,
@@ -25,6 +36,13 @@ pub struct CommandEnvelope {
     /// Command timeout in seconds (default 30).
     #[serde(default = "default_timeout_secs")]
     pub timeout_secs: u32,
+    /// Conversation this command continues, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<Uuid>,
+    /// Earlier turns of the conversation, oldest first, for on-device
+    /// inference of follow-ups. Only sent when `parsed_intent` is absent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<ConversationTurn>,
 }
 
 fn default_timeout_secs() -> u32 {
@@ -33,6 +51,7 @@ fn default_timeout_secs() -> u32 {
 
 /// What kind of action the parsed intent represents.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ActionKind {
     /// Invoke one of the 9 registered tools (CAN bus + log).
@@ -46,6 +65,7 @@ pub enum ActionKind {
 
 /// Parsed intent extracted from natural language by the LLM.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ParsedIntent {
     /// What kind of action to take.
     #[serde(default)]
@@ -60,10 +80,23 @@ pub struct ParsedIntent {
     pub tool_args: serde_json::Value,
     /// LLM confidence score (0.0 - 1.0).
     pub confidence: f64,
+    /// Tool calls to run in order instead of `tool_name` alone. By
+    /// convention `tool_name` / `tool_args` mirror the first step.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<PlanStep>,
+}
+
+impl ParsedIntent {
+    /// Every tool this intent may invoke: `tool_name` and each step's.
+    pub fn tool_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.tool_name.as_str())
+            .chain(self.steps.iter().map(|s| s.tool_name.as_str()))
+    }
 }
 
 /// Response from device back to cloud after executing a command.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CommandResponse {
     /// ID of the original command.
     pub command_id: Uuid,
@@ -88,12 +121,68 @@ pub struct CommandResponse {
     /// Error message if status is Failed.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Machine-readable class of the failure, for UI and alert rules. Also
+    /// set on a completed response whose data was dropped to fit the MQTT
+    /// packet limit ([`ErrorCode::PayloadTooLarge`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// Served from the agent's result cache instead of re-running the tool.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+/// Partial result emitted while a long-running tool is still executing.
+///
+/// Published on the `command/stream` topic. Chunks for a command carry
+/// increasing `seq` numbers starting at 0; the regular [`CommandResponse`]
+/// on `command/response` still marks completion and reports how many
+/// chunks were sent in `response_data.streamed_chunks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandResponseChunk {
+    /// ID of the original command.
+    pub command_id: Uuid,
+    /// Correlation ID matching the request.
+    pub correlation_id: Uuid,
+    /// Device that is executing the command.
+    pub device_id: String,
+    /// Position of this chunk in the stream (0-based).
+    pub seq: u32,
+    /// Tool-specific partial data (e.g. a batch of CAN frames or log entries).
+    pub data: serde_json::Value,
+    /// When the chunk was emitted.
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Request from the cloud to abort a command that is queued or running
+/// on the device.
+///
+/// Published on the `command/cancel` topic. The device answers with a
+/// regular [`CommandResponse`] whose status is [`CommandStatus::Cancelled`];
+/// a command that already finished is left as is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandCancel {
+    /// ID of the command to abort.
+    pub command_id: Uuid,
+    /// Device the command was sent to.
+    pub device_id: String,
+    /// Who requested the cancellation.
+    pub requested_by: String,
+    /// Optional operator-supplied reason.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the cancellation was requested.
+    pub requested_at: DateTime<Utc>,
 }
 
 /// Lifecycle status of a command.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum CommandStatus {
+    /// Held in the cloud until the target device comes back online.
+    Queued,
+    /// High-risk command held in the cloud until a second operator approves it.
+    AwaitingApproval,
     Pending,
     Sent,
     Processing,
@@ -101,10 +190,95 @@ pub enum CommandStatus {
     Failed,
     Timeout,
     Cancelled,
+    /// Rejected unrun by the agent's command rate limit.
+    Throttled,
+}
+
+/// Failure class of a [`CommandResponse`].
+///
+/// `error` stays the human-readable message; the code lets the dashboard and
+/// alert rules branch on what went wrong without matching message text.
+/// Codes added by newer agents deserialize as [`ErrorCode::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The requested tool is not in the agent's registry.
+    UnknownTool,
+    /// Inference understood the query but matched no tool.
+    InferenceNoMatch,
+    /// No inference engine could be reached to parse the query.
+    InferenceUnavailable,
+    /// Free-text query sent to an agent restricted to structured commands.
+    StructuredOnly,
+    /// Tool arguments are missing or invalid.
+    InvalidArgs,
+    /// Shell command rejected by the allowlist or a blocked operator.
+    ShellBlocked,
+    /// Shell command ran but exited non-zero or could not be spawned.
+    ShellFailed,
+    /// No reply on the CAN bus within the timeout.
+    CanTimeout,
+    /// CAN interface missing or bus error.
+    CanError,
+    /// The requested log file does not exist.
+    LogFileNotFound,
+    /// Reading or parsing a log failed.
+    LogError,
+    /// The result was too large to publish.
+    PayloadTooLarge,
+    /// The command exceeded its `timeout_secs`.
+    Timeout,
+    /// The command was cancelled.
+    Cancelled,
+    /// The agent is receiving commands faster than its rate limit allows.
+    Throttled,
+    /// A step of a multi-step plan failed.
+    PlanFailed,
+    /// The cloud could not publish the command to the device.
+    DeliveryFailed,
+    /// Any other tool or agent failure.
+    Internal,
+    /// Code not known to this build.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UnknownTool => "unknown_tool",
+            Self::InferenceNoMatch => "inference_no_match",
+            Self::InferenceUnavailable => "inference_unavailable",
+            Self::StructuredOnly => "structured_only",
+            Self::InvalidArgs => "invalid_args",
+            Self::ShellBlocked => "shell_blocked",
+            Self::ShellFailed => "shell_failed",
+            Self::CanTimeout => "can_timeout",
+            Self::CanError => "can_error",
+            Self::LogFileNotFound => "log_file_not_found",
+            Self::LogError => "log_error",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::Timeout => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::Throttled => "throttled",
+            Self::PlanFailed => "plan_failed",
+            Self::DeliveryFailed => "delivery_failed",
+            Self::Internal => "internal",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Which inference engine handled the query.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum InferenceTier {
     /// Local Ollama (Phi-3 Mini / TinyLlama / Gemma 2B).
@@ -135,14 +309,111 @@ impl CommandEnvelope {
             initiated_by: initiated_by.into(),
             created_at: Utc::now(),
             timeout_secs: default_timeout_secs(),
+            conversation_id: None,
+            history: Vec::new(),
         }
     }
+
+    /// Create an envelope for every device in a fleet, to be published on
+    /// the fleet broadcast topic. Its `id` is the broadcast ID.
+    pub fn broadcast(
+        fleet_id: impl Into<String>,
+        natural_language: impl Into<String>,
+        initiated_by: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            fleet_id,
+            BROADCAST_DEVICE_ID,
+            natural_language,
+            initiated_by,
+        )
+    }
+
+    /// Whether this envelope addresses the whole fleet.
+    pub fn is_broadcast(&self) -> bool {
+        self.device_id == BROADCAST_DEVICE_ID
+    }
+
+    /// One device's copy of a broadcast. The cloud and the agent both derive
+    /// it, so they agree on the command ID without a round trip; the
+    /// broadcast ID is kept as `correlation_id`.
+    pub fn for_device(&self, device_id: &str) -> Self {
+        Self {
+            id: fleet_command_id(self.id, device_id),
+            device_id: device_id.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Command ID of `device_id`'s share of broadcast `broadcast_id`.
+///
+/// Keeps the broadcast's UUIDv7 timestamp (so IDs still sort by creation
+/// time) and fills the random bits with FNV-1a hashes of both inputs. FNV
+/// is used rather than `DefaultHasher` because the result must not change
+/// between builds of the cloud and the agent.
+pub fn fleet_command_id(broadcast_id: Uuid, device_id: &str) -> Uuid {
+    fn fnv1a(chunks: &[&[u8]]) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in chunks.iter().flat_map(|c| c.iter()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        hash
+    }
+
+    let id = broadcast_id.as_bytes();
+    let device = device_id.as_bytes();
+    let high = fnv1a(&[id, device]).to_be_bytes();
+    let low = fnv1a(&[device, id]).to_be_bytes();
+
+    let mut bytes = *id;
+    bytes[6] = 0x70 | (high[0] & 0x0f);
+    bytes[7] = high[1];
+    bytes[8] = 0x80 | (high[2] & 0x3f);
+    bytes[9] = high[3];
+    bytes[10..16].copy_from_slice(&low[..6]);
+    Uuid::from_bytes(bytes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn command_cancel_roundtrip() {
+        let cancel = CommandCancel {
+            command_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            requested_by: "admin".into(),
+            reason: None,
+            requested_at: Utc::now(),
+        };
+        let json = serde_json::to_string(&cancel).unwrap();
+        assert!(!json.contains("reason"));
+        let parsed: CommandCancel = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.command_id, cancel.command_id);
+        assert_eq!(parsed.requested_by, "admin");
+    }
+
+    #[test]
+    fn error_codes_are_snake_case() {
+        assert_eq!(
+            serde_json::to_value(ErrorCode::LogFileNotFound).unwrap(),
+            "log_file_not_found"
+        );
+        for code in [
+            ErrorCode::UnknownTool,
+            ErrorCode::CanTimeout,
+            ErrorCode::PayloadTooLarge,
+            ErrorCode::Throttled,
+        ] {
+            assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+        }
+        let newer: ErrorCode = serde_json::from_str("\"battery_low\"").unwrap();
+        assert_eq!(newer, ErrorCode::Unknown);
+    }
+
     #[test]
     fn command_envelope_roundtrip() {
         let cmd = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "operator@test.com");
@@ -154,6 +425,22 @@ mod tests {
         assert_eq!(deserialized.timeout_secs, 30);
     }
 
+    #[test]
+    fn response_chunk_roundtrip() {
+        let chunk = CommandResponseChunk {
+            command_id: Uuid::now_v7(),
+            correlation_id: Uuid::now_v7(),
+            device_id: "rpi-001".into(),
+            seq: 3,
+            data: serde_json::json!({"frames": [{"id": "0x7E8"}]}),
+            sent_at: Utc::now(),
+        };
+        let json = serde_json::to_string(&chunk).unwrap();
+        let back: CommandResponseChunk = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.seq, 3);
+        assert_eq!(back.data["frames"][0]["id"], "0x7E8");
+    }
+
     #[test]
     fn command_status_serialization() {
         let status = CommandStatus::Completed;
@@ -215,6 +502,28 @@ mod tests {
         assert_eq!(intent.tool_args["message"], "I'm doing well!");
     }
 
+    #[test]
+    fn parsed_intent_with_steps() {
+        let json = r#"{"tool_name": "read_dtcs", "confidence": 0.9, "steps": [
+            {"tool_name": "read_dtcs"},
+            {"tool_name": "read_freeze", "tool_args": {"code": "{{item.code}}"}, "for_each": "steps.0.data"}
+        ]}"#;
+        let intent: ParsedIntent = serde_json::from_str(json).unwrap();
+        assert_eq!(intent.steps.len(), 2);
+        assert_eq!(intent.steps[1].for_each.as_deref(), Some("steps.0.data"));
+        assert_eq!(
+            intent.tool_names().collect::<Vec<_>>(),
+            ["read_dtcs", "read_dtcs", "read_freeze"]
+        );
+        // Single-tool intents serialize as before.
+        let single = serde_json::to_value(ParsedIntent {
+            steps: Vec::new(),
+            ..intent
+        })
+        .unwrap();
+        assert!(single.get("steps").is_none());
+    }
+
     #[test]
     fn command_response_with_error() {
         let resp = CommandResponse {
@@ -228,9 +537,34 @@ mod tests {
             latency_ms: 50,
             responded_at: Utc::now(),
             error: Some("CAN bus interface not available".into()),
+            error_code: None,
+            cached: false,
         };
         let json = serde_json::to_string(&resp).unwrap();
         assert!(json.contains("CAN bus interface not available"));
         assert!(!json.contains("response_text")); // skip_serializing_if = None
     }
+
+    #[test]
+    fn broadcast_fans_out_to_stable_device_ids() {
+        let broadcast = CommandEnvelope::broadcast("fleet-alpha", "read DTCs", "admin");
+        assert!(broadcast.is_broadcast());
+        assert_eq!(broadcast.correlation_id, broadcast.id);
+
+        let a = broadcast.for_device("rpi-001");
+        let b = broadcast.for_device("rpi-002");
+        assert!(!a.is_broadcast());
+        assert_eq!(a.device_id, "rpi-001");
+        assert_eq!(a.correlation_id, broadcast.id);
+        assert_ne!(a.id, b.id);
+        assert_ne!(a.id, broadcast.id);
+
+        // Same inputs, same ID — on the agent as in the cloud.
+        let received: CommandEnvelope =
+            serde_json::from_str(&serde_json::to_string(&broadcast).unwrap()).unwrap();
+        assert_eq!(received.for_device("rpi-001").id, a.id);
+
+        assert_eq!(a.id.get_version_num(), 7);
+        assert_eq!(a.id.as_bytes()[..6], broadcast.id.as_bytes()[..6]);
+    }
 }
@@ -0,0 +1,192 @@
+//! Device context for natural-language inference.
+//!
+//! Both inference engines — Ollama on the device, Bedrock in the cloud —
+//! append a short description of the target device to their system prompt:
+//! the vehicle it is installed in, its CAN link and the tools its agent
+//! actually has. Without it the model happily suggests spark-ignition PIDs
+//! on a diesel truck or tools the device cannot run.
+
+use serde::{Deserialize, Serialize};
+
+/// Engine / drivetrain type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FuelType {
+    Gasoline,
+    Diesel,
+    Hybrid,
+    Electric,
+}
+
+/// What is known about the vehicle a device is installed in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VehicleProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vin: Option<String>,
+    /// Manufacturer, configured or decoded from the VIN.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_year: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel_type: Option<FuelType>,
+    /// CAN link the agent talks to (e.g. "can0, 500 kbit/s").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub can_protocol: Option<String>,
+    /// Mode 0x01 PIDs the ECU reported from `list_supported_pids`. Empty
+    /// until discovery has run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supported_pids: Vec<u8>,
+}
+
+impl VehicleProfile {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// "2019 Freightliner Cascadia", from whatever parts are known.
+    fn description(&self) -> Option<String> {
+        let year = self.model_year.map(|y| y.to_string());
+        let parts: Vec<&str> = [year.as_deref(), self.make.as_deref(), self.model.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+/// Device context for one inference request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceContext {
+    #[serde(default)]
+    pub vehicle: VehicleProfile,
+    /// Tools the device can execute (its capability manifest). Empty when
+    /// not known.
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+impl DeviceContext {
+    /// Section appended to an inference system prompt, or `None` if nothing
+    /// is known about the device.
+    pub fn prompt_section(&self) -> Option<String> {
+        let vehicle = &self.vehicle;
+        let mut lines = Vec::new();
+
+        let mut described = vehicle.description();
+        if let Some(vin) = &vehicle.vin {
+            described = Some(match described {
+                Some(d) => format!("{d} (VIN {vin})"),
+                None => format!("VIN {vin}"),
+            });
+        }
+        if let Some(d) = described {
+            lines.push(format!("- Vehicle: {d}"));
+        }
+        if let Some(can) = &vehicle.can_protocol {
+            lines.push(format!("- CAN bus: {can}"));
+        }
+        match vehicle.fuel_type {
+            Some(FuelType::Diesel) => lines.push(
+                "- Diesel engine: do not use spark-ignition PIDs (0x0E timing advance, \
+                 0x06–0x09 fuel trims, 0x14–0x1B oxygen sensors)"
+                    .into(),
+            ),
+            Some(FuelType::Electric) => lines.push(
+                "- Electric vehicle: no combustion engine, so engine PIDs (0x0C RPM, \
+                 0x0E timing advance, 0x2F fuel level, fuel trims) do not apply"
+                    .into(),
+            ),
+            Some(FuelType::Gasoline) => lines.push("- Gasoline engine".into()),
+            Some(FuelType::Hybrid) => lines.push("- Hybrid drivetrain".into()),
+            None => {}
+        }
+        if !vehicle.supported_pids.is_empty() {
+            let pids: Vec<String> = vehicle
+                .supported_pids
+                .iter()
+                .map(|pid| format!("0x{pid:02X}"))
+                .collect();
+            lines.push(format!(
+                "- Supported PIDs: {} — only read PIDs from this list",
+                pids.join(", ")
+            ));
+        }
+        if !self.tools.is_empty() {
+            lines.push(format!(
+                "- Available tools: {} — only choose tools from this list",
+                self.tools.join(", ")
+            ));
+        }
+
+        if lines.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "## This device\n{}\nTailor the command to this device.",
+            lines.join("\n")
+        ))
+    }
+
+    /// `system_prompt` with this context appended.
+    pub fn apply_to(&self, system_prompt: &str) -> String {
+        match self.prompt_section() {
+            Some(section) => format!("{system_prompt}\n\n{section}"),
+            None => system_prompt.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_context_adds_nothing() {
+        let context = DeviceContext::default();
+        assert!(context.prompt_section().is_none());
+        assert_eq!(context.apply_to("base"), "base");
+        assert!(context.vehicle.is_empty());
+    }
+
+    #[test]
+    fn diesel_truck_section() {
+        let context = DeviceContext {
+            vehicle: VehicleProfile {
+                vin: Some("1FUJGLDR7CLBP8834".into()),
+                make: Some("Freightliner".into()),
+                model: Some("Cascadia".into()),
+                model_year: Some(2012),
+                fuel_type: Some(FuelType::Diesel),
+                can_protocol: Some("can0, 250 kbit/s".into()),
+                supported_pids: vec![0x05, 0x0C, 0x0D],
+            },
+            tools: vec!["read_dtcs".into(), "read_pid".into()],
+        };
+        let prompt = context.apply_to("base");
+        assert!(prompt.starts_with("base\n\n## This device\n"));
+        assert!(prompt.contains("- Vehicle: 2012 Freightliner Cascadia (VIN 1FUJGLDR7CLBP8834)"));
+        assert!(prompt.contains("- CAN bus: can0, 250 kbit/s"));
+        assert!(prompt.contains("do not use spark-ignition PIDs"));
+        assert!(prompt.contains("- Supported PIDs: 0x05, 0x0C, 0x0D"));
+        assert!(prompt.contains("- Available tools: read_dtcs, read_pid"));
+    }
+
+    #[test]
+    fn profile_round_trips_without_unknown_fields() {
+        let profile = VehicleProfile {
+            make: Some("Tesla".into()),
+            fuel_type: Some(FuelType::Electric),
+            ..VehicleProfile::default()
+        };
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"make": "Tesla", "fuel_type": "electric"})
+        );
+        let back: VehicleProfile = serde_json::from_value(json).unwrap();
+        assert_eq!(back, profile);
+    }
+}
@@ -0,0 +1,157 @@
+//! Conversation history for multi-turn commands.
+//!
+//! Operators follow up: "tail the syslog", then "show me more lines"; "run
+//! a CAN monitor on can0", then "now do the same on can1". Commands sent
+//! with the same `conversation_id` form a conversation, and its last few
+//! turns are appended to the system prompt of both inference engines —
+//! Bedrock in the cloud and Ollama on the device (which receives them in
+//! [`CommandEnvelope::history`](crate::commands::CommandEnvelope::history)) —
+//! so follow-ups can be resolved against what ran before.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{ActionKind, ParsedIntent};
+
+/// Most turns passed to the engines.
+pub const MAX_HISTORY_TURNS: usize = 5;
+/// Characters of a turn's response kept in the prompt.
+pub const MAX_RESPONSE_CHARS: usize = 300;
+
+/// One earlier command in a conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConversationTurn {
+    /// What the operator typed.
+    pub command: String,
+    /// How it was interpreted, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intent: Option<ParsedIntent>,
+    /// The device's response text, truncated to [`MAX_RESPONSE_CHARS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl ConversationTurn {
+    pub fn new(
+        command: impl Into<String>,
+        intent: Option<ParsedIntent>,
+        response: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            command: command.into(),
+            intent,
+            response: response.map(truncate),
+            at,
+        }
+    }
+
+    fn prompt_line(&self, n: usize) -> String {
+        let mut line = format!("{n}. Operator: \"{}\"", self.command);
+        if let Some(intent) = &self.intent {
+            let ran = match intent.action {
+                ActionKind::Tool => format!("{} {}", intent.tool_name, intent.tool_args),
+                ActionKind::Shell => format!("shell `{}`", intent.tool_name),
+                ActionKind::Reply => "reply".to_string(),
+            };
+            line.push_str(&format!("\n   Ran: {ran}"));
+        }
+        if let Some(response) = &self.response {
+            line.push_str(&format!("\n   Result: {response}"));
+        }
+        line
+    }
+}
+
+/// Truncate to [`MAX_RESPONSE_CHARS`] on a character boundary, on one line.
+fn truncate(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX_RESPONSE_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// The "## Conversation so far" prompt section, or `None` without history.
+/// Turns are oldest first.
+pub fn prompt_section(history: &[ConversationTurn]) -> Option<String> {
+    if history.is_empty() {
+        return None;
+    }
+    let skip = history.len().saturating_sub(MAX_HISTORY_TURNS);
+    let turns: Vec<String> = history[skip..]
+        .iter()
+        .enumerate()
+        .map(|(i, turn)| turn.prompt_line(i + 1))
+        .collect();
+    Some(format!(
+        "## Conversation so far\n{}\nThe operator may refer to these commands (\"do the same on can1\", \
+         \"show me more lines\"): reuse the previous tool and arguments, changing only what they ask for.",
+        turns.join("\n")
+    ))
+}
+
+/// `system_prompt` with the conversation section appended.
+pub fn apply_history(system_prompt: &str, history: &[ConversationTurn]) -> String {
+    match prompt_section(history) {
+        Some(section) => format!("{system_prompt}\n\n{section}"),
+        None => system_prompt.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tail_turn() -> ConversationTurn {
+        ConversationTurn::new(
+            "tail the syslog",
+            Some(ParsedIntent {
+                action: ActionKind::Tool,
+                tool_name: "tail_logs".into(),
+                tool_args: json!({"path": "/var/log/syslog", "lines": 50}),
+                confidence: 0.9,
+                steps: Vec::new(),
+            }),
+            Some("50 lines\nlast: kernel: eth0 up"),
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn no_history_adds_nothing() {
+        assert!(prompt_section(&[]).is_none());
+        assert_eq!(apply_history("base", &[]), "base");
+    }
+
+    #[test]
+    fn history_section_lists_turns() {
+        let prompt = apply_history("base", &[tail_turn()]);
+        assert!(prompt.starts_with("base\n\n## Conversation so far\n"));
+        assert!(prompt.contains("1. Operator: \"tail the syslog\""));
+        assert!(prompt.contains("Ran: tail_logs {"));
+        assert!(prompt.contains(r#""lines":50"#));
+        assert!(prompt.contains("Result: 50 lines last: kernel: eth0 up"));
+    }
+
+    #[test]
+    fn only_recent_turns_and_short_responses_are_kept() {
+        let mut history: Vec<ConversationTurn> = (0..8)
+            .map(|i| ConversationTurn::new(format!("command {i}"), None, None, Utc::now()))
+            .collect();
+        history.push(ConversationTurn::new(
+            "tail",
+            None,
+            Some(&"x".repeat(1000)),
+            Utc::now(),
+        ));
+        let section = prompt_section(&history).unwrap();
+        assert!(!section.contains("command 3"));
+        assert!(section.contains("1. Operator: \"command 4\""));
+        let response = history[8].response.as_ref().unwrap();
+        assert_eq!(response.chars().count(), MAX_RESPONSE_CHARS + 1);
+    }
+}
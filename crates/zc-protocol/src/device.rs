@@ -1,9 +1,14 @@
+use std::borrow::Cow;
+
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
+use crate::encoding::Encoding;
+
 /// Unique fleet identifier.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct FleetId(pub Uuid);
 
 impl FleetId {
@@ -26,10 +31,13 @@ impl std::fmt::Display for FleetId {
 
 /// Device lifecycle status.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum DeviceStatus {
     Provisioning,
     Online,
+    /// Heartbeats are late but the device is not yet considered offline.
+    Degraded,
     Offline,
     Maintenance,
     Decommissioned,
@@ -37,6 +45,7 @@ pub enum DeviceStatus {
 
 /// Hardware type of the edge device.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum HardwareType {
     RaspberryPi4,
@@ -47,6 +56,7 @@ pub enum HardwareType {
 
 /// Core device information stored in the registry.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct DeviceInfo {
     /// Internal database ID.
     pub id: Uuid,
@@ -64,9 +74,16 @@ pub struct DeviceInfo {
     /// X.509 certificate ID for mTLS.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub certificate_id: Option<String>,
+    /// When the client certificate in use expires, as last reported in a
+    /// heartbeat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_expires_at: Option<DateTime<Utc>>,
     /// Last heartbeat received from the device.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_heartbeat: Option<DateTime<Utc>>,
+    /// System vitals from the latest heartbeat that carried them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vitals: Option<DeviceVitals>,
     /// Flexible metadata (firmware version, location, etc.).
     #[serde(default)]
     pub metadata: serde_json::Value,
@@ -76,8 +93,17 @@ pub struct DeviceInfo {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Heartbeat schema version sent by this build. Version 1 heartbeats
+/// predate the field; version 2 adds [`Heartbeat::vitals`].
+pub const HEARTBEAT_VERSION: u32 = 2;
+
+fn legacy_heartbeat_version() -> u32 {
+    1
+}
+
 /// Heartbeat message sent by devices on a 30-second interval.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Heartbeat {
     pub device_id: String,
     pub fleet_id: String,
@@ -90,11 +116,101 @@ pub struct Heartbeat {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub machine_id: Option<String>,
+    /// Expiry (`notAfter`) of the client certificate the agent connects
+    /// with; absent over plaintext MQTT.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_expires_at: Option<DateTime<Utc>>,
+    /// Payload encodings the agent reads; empty from agents that predate
+    /// negotiation, which read JSON only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encodings: Vec<Encoding>,
+    /// Schema version; absent (1) from agents that predate versioning.
+    #[serde(default = "legacy_heartbeat_version")]
+    pub version: u32,
+    /// System vitals (version 2+); absent when none could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vitals: Option<DeviceVitals>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Host health sampled by the agent for each heartbeat. Every field is
+/// optional: a reading the platform does not offer (no thermal zone, no
+/// wireless interface) is left out rather than reported as zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeviceVitals {
+    /// 1-minute load average.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_load: Option<f64>,
+    /// Memory available to new processes (`MemAvailable`), in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_free_bytes: Option<u64>,
+    /// Free space on the root filesystem, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_free_bytes: Option<u64>,
+    /// SoC temperature in degrees Celsius.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_c: Option<f64>,
+    /// Wi-Fi or cellular signal strength, in dBm.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi_dbm: Option<i32>,
+}
+
+impl DeviceVitals {
+    /// Whether no reading was taken.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Borrowed view of a [`Heartbeat`] with only the fields the cloud needs
+/// to track liveness. Strings borrow from the payload unless they contain
+/// escapes; the remaining fields are skipped without allocating.
+#[derive(Debug, Deserialize)]
+pub struct HeartbeatView<'a> {
+    #[serde(borrow)]
+    pub device_id: Cow<'a, str>,
+    #[serde(borrow)]
+    pub fleet_id: Cow<'a, str>,
+    #[serde(borrow, default, deserialize_with = "borrowed_opt_str")]
+    pub machine_id: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub cert_expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub encodings: Vec<Encoding>,
+    #[serde(default)]
+    pub vitals: Option<DeviceVitals>,
     pub timestamp: DateTime<Utc>,
 }
 
+impl From<Heartbeat> for HeartbeatView<'static> {
+    fn from(hb: Heartbeat) -> Self {
+        Self {
+            device_id: Cow::Owned(hb.device_id),
+            fleet_id: Cow::Owned(hb.fleet_id),
+            machine_id: hb.machine_id.map(Cow::Owned),
+            cert_expires_at: hb.cert_expires_at,
+            encodings: hb.encodings,
+            vitals: hb.vitals,
+            timestamp: hb.timestamp,
+        }
+    }
+}
+
+/// `Option<Cow<str>>` always deserializes owned; this keeps the borrow.
+fn borrowed_opt_str<'de: 'a, 'a, D>(deserializer: D) -> Result<Option<Cow<'a, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+
+    Ok(Option::<Borrowed<'a>>::deserialize(deserializer)?.map(|b| b.0))
+}
+
 /// Status of an edge subsystem.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ServiceStatus {
     Running,
@@ -103,6 +219,52 @@ pub enum ServiceStatus {
     Unknown,
 }
 
+/// Device presence on `status/presence`, published retained. The agent
+/// sends `online` after connecting and registers `online: false` as its
+/// MQTT Last Will, which the broker publishes when the connection drops
+/// without a clean disconnect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DevicePresence {
+    pub device_id: String,
+    pub fleet_id: String,
+    pub online: bool,
+    /// When the device came online. Absent in the Last Will, which is
+    /// fixed at connect time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// State of an MQTT connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Not connected yet since start.
+    Connecting,
+    /// Connected and stable.
+    Connected,
+    /// Lost the connection; retrying with backoff.
+    Reconnecting,
+    /// Connected, but reconnecting too often (a reconnect storm).
+    Unstable,
+}
+
+/// Health of an MQTT connection, reported by the agent in its
+/// `diagnostics` shadow and by the cloud on `/health`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MqttHealth {
+    pub state: ConnectionState,
+    /// When `state` was entered.
+    pub since: DateTime<Utc>,
+    /// Failed connection attempts since the last successful connect.
+    pub consecutive_failures: u32,
+    /// Successful connects after the first.
+    pub reconnects: u64,
+    /// Reconnect storms detected (too many reconnects within a window).
+    pub storms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,9 +311,19 @@ mod tests {
             can_status: ServiceStatus::Running,
             agent_version: "0.1.0".into(),
             machine_id: Some("a8b9c0d1e2f34567890abcdef0123456".into()),
+            cert_expires_at: Some("2027-03-10T12:00:00Z".parse().unwrap()),
+            encodings: vec![],
+            version: HEARTBEAT_VERSION,
+            vitals: Some(DeviceVitals {
+                cpu_load: Some(0.42),
+                memory_free_bytes: Some(512 << 20),
+                temperature_c: Some(48.5),
+                ..Default::default()
+            }),
             timestamp: Utc::now(),
         };
         let json = serde_json::to_string(&hb).unwrap();
+        assert!(!json.contains("rssi_dbm"));
         let deserialized: Heartbeat = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.device_id, "rpi-001");
         assert_eq!(deserialized.ollama_status, ServiceStatus::Running);
@@ -159,6 +331,9 @@ mod tests {
             deserialized.machine_id.as_deref(),
             Some("a8b9c0d1e2f34567890abcdef0123456")
         );
+        assert_eq!(deserialized.cert_expires_at, hb.cert_expires_at);
+        assert_eq!(deserialized.version, HEARTBEAT_VERSION);
+        assert_eq!(deserialized.vitals, hb.vitals);
     }
 
     #[test]
@@ -176,5 +351,37 @@ mod tests {
         }"#;
         let hb: Heartbeat = serde_json::from_str(json).unwrap();
         assert!(hb.machine_id.is_none());
+        assert!(hb.cert_expires_at.is_none());
+        assert_eq!(hb.version, 1);
+        assert!(hb.vitals.is_none());
+    }
+
+    #[test]
+    fn heartbeat_view_borrows_from_payload() {
+        let json = r#"{
+            "device_id": "rpi-001",
+            "fleet_id": "fleet-alpha",
+            "status": "online",
+            "uptime_secs": 100,
+            "ollama_status": "running",
+            "can_status": "stopped",
+            "agent_version": "0.1.0",
+            "machine_id": "a8b9c0d1",
+            "cert_expires_at": "2027-03-10T12:00:00Z",
+            "timestamp": "2026-03-10T12:00:00Z"
+        }"#;
+        let view: HeartbeatView = serde_json::from_str(json).unwrap();
+        assert!(view.cert_expires_at.is_some());
+        assert!(matches!(view.device_id, Cow::Borrowed("rpi-001")));
+        assert!(matches!(view.fleet_id, Cow::Borrowed("fleet-alpha")));
+        assert!(matches!(view.machine_id, Some(Cow::Borrowed("a8b9c0d1"))));
+
+        // Escaped strings fall back to owned; missing machine_id is None.
+        let json = r#"{"device_id": "rpi\u002d002", "fleet_id": "f",
+                       "timestamp": "2026-03-10T12:00:00Z"}"#;
+        let view: HeartbeatView = serde_json::from_str(json).unwrap();
+        assert_eq!(view.device_id, "rpi-002");
+        assert!(matches!(view.device_id, Cow::Owned(_)));
+        assert!(view.machine_id.is_none());
     }
 }
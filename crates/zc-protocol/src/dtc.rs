@@ -23,6 +23,9 @@ pub struct DtcCode {
     pub raw_dtc: Option<String>,
     /// Whether MIL (check engine light) is illuminated.
     pub mil_status: bool,
+    /// OBD-II list the code was read from (only for OBD-II DTCs).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<DtcScope>,
     /// Freeze frame data captured when DTC was set.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub freeze_frame: Option<FreezeFrame>,
@@ -42,6 +45,37 @@ pub enum DtcCategory {
     Network,
 }
 
+/// OBD-II DTC list a code was reported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DtcScope {
+    /// Mode 0x03 — confirmed codes (may illuminate the MIL).
+    Stored,
+    /// Mode 0x07 — detected during the current or last drive cycle, not yet confirmed.
+    Pending,
+    /// Mode 0x0A — cannot be cleared by a scan tool; erased by the ECU once repaired.
+    Permanent,
+}
+
+impl DtcScope {
+    /// OBD-II service mode that reads this list.
+    pub fn mode(self) -> u8 {
+        match self {
+            Self::Stored => 0x03,
+            Self::Pending => 0x07,
+            Self::Permanent => 0x0A,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stored => "stored",
+            Self::Pending => "pending",
+            Self::Permanent => "permanent",
+        }
+    }
+}
+
 /// Severity classification of a DTC.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -57,8 +91,14 @@ pub enum DtcSeverity {
 }
 
 /// Freeze frame data captured at the moment a DTC was set.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FreezeFrame {
+    /// Frame number (0 is the first frame the ECU stored).
+    #[serde(default)]
+    pub frame: u8,
+    /// DTC that caused the frame to be stored (PID 0x02).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dtc: Option<String>,
     /// Engine RPM at time of fault.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub engine_rpm: Option<f64>,
@@ -80,6 +120,75 @@ pub struct FreezeFrame {
     /// Long-term fuel trim (%).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub long_term_fuel_trim: Option<f64>,
+    /// Every PID read from the frame, decoded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pids: Vec<FreezeFramePid>,
+}
+
+/// One decoded PID of a freeze frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreezeFramePid {
+    pub pid: u8,
+    pub name: String,
+    pub value: f64,
+    pub unit: String,
+}
+
+/// Emissions readiness from Mode 0x01 PID 0x01.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Readiness {
+    /// Malfunction indicator lamp (check engine light) commanded on.
+    pub mil_on: bool,
+    /// Number of confirmed emissions-related DTCs.
+    pub dtc_count: u8,
+    pub ignition: IgnitionType,
+    /// Every monitor defined for the ignition type, continuous ones first.
+    pub monitors: Vec<ReadinessMonitor>,
+}
+
+impl Readiness {
+    /// Supported monitors that have not yet completed since codes were
+    /// last cleared.
+    pub fn incomplete(&self) -> impl Iterator<Item = &ReadinessMonitor> {
+        self.monitors
+            .iter()
+            .filter(|m| m.state == MonitorState::Incomplete)
+    }
+
+    /// MIL off and every supported monitor complete — the strict reading
+    /// of an inspection check (some programs allow one or two incomplete).
+    pub fn ready(&self) -> bool {
+        !self.mil_on && self.incomplete().next().is_none()
+    }
+}
+
+/// Engine type, which decides what the non-continuous monitor bits mean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IgnitionType {
+    Spark,
+    Compression,
+}
+
+/// One on-board monitor and its test status.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadinessMonitor {
+    /// e.g. "catalyst", "evaporative_system".
+    pub name: String,
+    /// Runs continuously (misfire, fuel system, components) rather than
+    /// once per drive cycle.
+    pub continuous: bool,
+    pub state: MonitorState,
+}
+
+/// Status of a readiness monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorState {
+    Complete,
+    Incomplete,
+    /// The vehicle does not have this monitor.
+    Unsupported,
 }
 
 impl DtcCode {
@@ -118,7 +227,10 @@ mod tests {
             failure_type: None,
             raw_dtc: None,
             mil_status: true,
+            scope: None,
             freeze_frame: Some(FreezeFrame {
+                frame: 0,
+                dtc: Some("P0300".into()),
                 engine_rpm: Some(2500.0),
                 vehicle_speed: Some(60.0),
                 coolant_temp: Some(92.0),
@@ -126,6 +238,7 @@ mod tests {
                 fuel_system_status: Some("closed loop".into()),
                 short_term_fuel_trim: Some(1.5),
                 long_term_fuel_trim: Some(-2.3),
+                pids: vec![],
             }),
         };
         let json = serde_json::to_string_pretty(&dtc).unwrap();
@@ -158,6 +271,7 @@ mod tests {
             failure_type: Some("Circuit Short to Ground".into()),
             raw_dtc: Some("030007".into()),
             mil_status: false,
+            scope: None,
             freeze_frame: None,
         };
         let json = serde_json::to_string(&dtc).unwrap();
@@ -187,6 +301,7 @@ mod tests {
             failure_type: None,
             raw_dtc: None,
             mil_status: false,
+            scope: None,
             freeze_frame: None,
         };
         let json = serde_json::to_string(&dtc).unwrap();
@@ -196,6 +311,19 @@ mod tests {
         assert!(!json.contains("severity_source"));
         assert!(!json.contains("description"));
         assert!(!json.contains("freeze_frame"));
+        assert!(!json.contains("scope"));
+    }
+
+    #[test]
+    fn dtc_scope_serialization_and_modes() {
+        assert_eq!(
+            serde_json::to_string(&DtcScope::Permanent).unwrap(),
+            r#""permanent""#
+        );
+        assert_eq!(DtcScope::Stored.mode(), 0x03);
+        assert_eq!(DtcScope::Pending.mode(), 0x07);
+        assert_eq!(DtcScope::Permanent.mode(), 0x0A);
+        assert_eq!(DtcScope::Pending.as_str(), "pending");
     }
 
     #[test]
@@ -212,5 +340,6 @@ mod tests {
         assert!(dtc.failure_type.is_none());
         assert!(dtc.raw_dtc.is_none());
         assert!(dtc.severity_source.is_none());
+        assert!(dtc.scope.is_none());
     }
 }
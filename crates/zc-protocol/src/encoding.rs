@@ -0,0 +1,184 @@
+//! Wire encoding of MQTT payloads.
+//!
+//! Payloads are JSON unless both ends speak CBOR (RFC 8949), which drops
+//! the quoting and number formatting and shrinks telemetry batches on
+//! metered cellular links. A CBOR payload
+//! starts with the self-describe tag 55799 ([`CBOR_MAGIC`]), a byte
+//! sequence no JSON document can start with, so [`decode`] tells the two
+//! apart without a topic suffix or envelope field and every receiver keeps
+//! accepting JSON from older peers.
+//!
+//! Devices list the encodings they can read in their heartbeat
+//! ([`crate::device::Heartbeat::encodings`]); the cloud only sends CBOR to
+//! devices that listed it and keeps fleet broadcasts in JSON.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// CBOR self-describe tag (55799) that prefixes every CBOR payload.
+pub const CBOR_MAGIC: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// Payload encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// UTF-8 JSON, understood by every peer.
+    #[default]
+    Json,
+    /// CBOR prefixed with [`CBOR_MAGIC`].
+    Cbor,
+}
+
+impl Encoding {
+    /// Every encoding, as advertised by peers that read them all.
+    pub const ALL: [Encoding; 2] = [Encoding::Json, Encoding::Cbor];
+
+    /// Encoding of a received payload.
+    pub fn detect(payload: &[u8]) -> Self {
+        if payload.starts_with(&CBOR_MAGIC) {
+            Self::Cbor
+        } else {
+            Self::Json
+        }
+    }
+
+    /// The most compact of `accepted`, falling back to JSON when it is
+    /// empty (peers that predate encoding negotiation).
+    pub fn preferred(accepted: &[Encoding]) -> Self {
+        if accepted.contains(&Self::Cbor) {
+            Self::Cbor
+        } else {
+            Self::Json
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Cbor => "cbor",
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "cbor" => Ok(Self::Cbor),
+            other => Err(format!(
+                "unknown encoding '{other}' (expected json or cbor)"
+            )),
+        }
+    }
+}
+
+/// Why a payload could not be encoded or decoded.
+#[derive(Debug, thiserror::Error)]
+pub enum EncodingError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error("cbor: {0}")]
+    Cbor(String),
+}
+
+/// Serialize `value` as `encoding`.
+pub fn encode<T: Serialize + ?Sized>(
+    value: &T,
+    encoding: Encoding,
+) -> Result<Vec<u8>, EncodingError> {
+    match encoding {
+        Encoding::Json => Ok(serde_json::to_vec(value)?),
+        Encoding::Cbor => {
+            let mut bytes = CBOR_MAGIC.to_vec();
+            ciborium::into_writer(value, &mut bytes)
+                .map_err(|e| EncodingError::Cbor(e.to_string()))?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Deserialize a payload in whichever encoding it was sent.
+pub fn decode<T: DeserializeOwned>(payload: &[u8]) -> Result<T, EncodingError> {
+    match Encoding::detect(payload) {
+        Encoding::Json => Ok(serde_json::from_slice(payload)?),
+        Encoding::Cbor => ciborium::from_reader(&payload[CBOR_MAGIC.len()..])
+            .map_err(|e| EncodingError::Cbor(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::CommandEnvelope;
+    use crate::telemetry::{TelemetryBatch, TelemetryReading, TelemetrySource};
+
+    fn batch() -> TelemetryBatch {
+        let readings = (0..20)
+            .map(|i| TelemetryReading {
+                time: chrono::Utc::now(),
+                device_id: "rpi-001".into(),
+                metric_name: "engine_rpm".into(),
+                value_numeric: Some(800.0 + f64::from(i)),
+                value_text: None,
+                value_json: None,
+                unit: Some("rpm".into()),
+                source: TelemetrySource::Obd2,
+            })
+            .collect();
+        TelemetryBatch {
+            device_id: "rpi-001".into(),
+            readings,
+            collected_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn cbor_round_trips_and_is_smaller() {
+        let batch = batch();
+        let cbor = encode(&batch, Encoding::Cbor).unwrap();
+        let json = encode(&batch, Encoding::Json).unwrap();
+        assert_eq!(Encoding::detect(&cbor), Encoding::Cbor);
+        assert_eq!(Encoding::detect(&json), Encoding::Json);
+        assert!(cbor.len() < json.len(), "{} >= {}", cbor.len(), json.len());
+
+        let back: TelemetryBatch = decode(&cbor).unwrap();
+        assert_eq!(back.readings.len(), 20);
+        assert_eq!(back.readings[3].value_numeric, Some(803.0));
+    }
+
+    #[test]
+    fn decode_accepts_both_encodings() {
+        let mut envelope = CommandEnvelope::new("fleet-alpha", "rpi-001", "read DTCs", "ops");
+        envelope.conversation_id = Some(envelope.id);
+        for encoding in Encoding::ALL {
+            let bytes = encode(&envelope, encoding).unwrap();
+            let back: CommandEnvelope = decode(&bytes).unwrap();
+            assert_eq!(back.id, envelope.id);
+            assert_eq!(back.conversation_id, Some(envelope.id));
+            assert_eq!(back.created_at, envelope.created_at);
+        }
+        assert!(decode::<CommandEnvelope>(&CBOR_MAGIC).is_err());
+    }
+
+    #[test]
+    fn negotiation_falls_back_to_json() {
+        assert_eq!(Encoding::preferred(&[]), Encoding::Json);
+        assert_eq!(Encoding::preferred(&[Encoding::Json]), Encoding::Json);
+        assert_eq!(Encoding::preferred(&Encoding::ALL), Encoding::Cbor);
+        assert_eq!("CBOR".parse::<Encoding>(), Ok(Encoding::Cbor));
+        assert!("msgpack".parse::<Encoding>().is_err());
+    }
+}
@@ -1,12 +1,29 @@
+pub mod bootstrap;
+pub mod catalog;
 pub mod commands;
+pub mod context;
+pub mod conversation;
 pub mod device;
 pub mod dtc;
+pub mod encoding;
+pub mod plan;
+pub mod rate_limit;
+pub mod self_test;
 pub mod shadows;
 pub mod telemetry;
 pub mod topics;
+pub mod transfer;
 
+pub use bootstrap::*;
+pub use catalog::*;
 pub use commands::*;
+pub use context::*;
+pub use conversation::*;
 pub use device::*;
 pub use dtc::*;
+pub use plan::*;
+pub use rate_limit::*;
+pub use self_test::*;
 pub use shadows::*;
 pub use telemetry::*;
+pub use transfer::*;
@@ -0,0 +1,235 @@
+//! Multi-step tool plans.
+//!
+//! A [`ParsedIntent`](crate::commands::ParsedIntent) with `steps` runs them
+//! in order on the device: "read the DTCs and the freeze frame for each
+//! code" is `read_dtcs`, then `read_freeze` once per code found. Arguments
+//! of later steps may refer to earlier outputs with `{{...}}` templates:
+//!
+//! - `{{steps.N.<path>}}` — output of step `N` (0-based), the tool's result
+//!   object (`success`, `data`, `summary`, ...). A `for_each` step's output
+//!   is the array of its runs' outputs.
+//! - `{{prev.<path>}}` — output of the previous step.
+//! - `{{item.<path>}}` — the current element in a `for_each` step.
+//!
+//! Paths are dot-separated; numeric segments index arrays
+//! (`steps.0.data.0.code`). A string that is exactly one template takes the
+//! referenced value as is (numbers stay numbers); otherwise values are
+//! interpolated as text.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Most steps in a plan.
+pub const MAX_PLAN_STEPS: usize = 8;
+/// Most runs of one `for_each` step.
+pub const MAX_FOR_EACH_ITEMS: usize = 16;
+
+/// One tool call of a plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PlanStep {
+    /// Tool to invoke.
+    pub tool_name: String,
+    /// Arguments, possibly with `{{...}}` templates.
+    #[serde(default)]
+    pub tool_args: Value,
+    /// Template path of an array from an earlier step (e.g.
+    /// `steps.0.data`): the step runs once per element, available as
+    /// `{{item}}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub for_each: Option<String>,
+}
+
+impl PlanStep {
+    pub fn new(tool_name: impl Into<String>, tool_args: Value) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            tool_args,
+            for_each: None,
+        }
+    }
+
+    /// Run this step once per element of `path`.
+    pub fn for_each(mut self, path: impl Into<String>) -> Self {
+        self.for_each = Some(path.into());
+        self
+    }
+}
+
+/// Check a plan's size and tool names before running it.
+pub fn validate(steps: &[PlanStep]) -> Result<(), String> {
+    if steps.len() > MAX_PLAN_STEPS {
+        return Err(format!(
+            "plan has {} steps, at most {MAX_PLAN_STEPS} are allowed",
+            steps.len()
+        ));
+    }
+    match steps.iter().position(|s| s.tool_name.trim().is_empty()) {
+        Some(i) => Err(format!("plan step {i} has no tool_name")),
+        None => Ok(()),
+    }
+}
+
+/// Values templates can refer to while running a plan.
+#[derive(Debug, Clone, Default)]
+pub struct PlanVars {
+    outputs: Vec<Value>,
+    item: Option<Value>,
+}
+
+impl PlanVars {
+    /// Record the output of the next step.
+    pub fn push_output(&mut self, output: Value) {
+        self.outputs.push(output);
+    }
+
+    /// These variables with `item` set to the current `for_each` element.
+    pub fn with_item(&self, item: Value) -> Self {
+        Self {
+            outputs: self.outputs.clone(),
+            item: Some(item),
+        }
+    }
+
+    /// Value at a template path, e.g. `steps.0.data.0.code`.
+    pub fn resolve(&self, path: &str) -> Result<&Value, String> {
+        let mut segments = path.trim().split('.');
+        let mut value = match segments.next() {
+            Some("steps") => {
+                let index = segments.next().unwrap_or_default();
+                index
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| self.outputs.get(i))
+                    .ok_or_else(|| format!("'{path}': no earlier step {index}"))?
+            }
+            Some("prev") => self
+                .outputs
+                .last()
+                .ok_or_else(|| format!("'{path}': no previous step"))?,
+            Some("item") => self
+                .item
+                .as_ref()
+                .ok_or_else(|| format!("'{path}': item outside a for_each step"))?,
+            _ => return Err(format!("'{path}': unknown variable")),
+        };
+        for segment in segments {
+            let next = match (value, segment.parse::<usize>()) {
+                (Value::Array(items), Ok(i)) => items.get(i),
+                (Value::Object(fields), _) => fields.get(segment),
+                _ => None,
+            };
+            value = next.ok_or_else(|| format!("'{path}': no '{segment}'"))?;
+        }
+        Ok(value)
+    }
+
+    /// Elements a `for_each` step runs over.
+    pub fn items(&self, path: &str) -> Result<Vec<Value>, String> {
+        match self.resolve(path)? {
+            Value::Array(items) if items.len() > MAX_FOR_EACH_ITEMS => Err(format!(
+                "for_each over {} items, at most {MAX_FOR_EACH_ITEMS} are allowed",
+                items.len()
+            )),
+            Value::Array(items) => Ok(items.clone()),
+            _ => Err(format!("for_each '{path}' is not an array")),
+        }
+    }
+
+    /// `args` with every template replaced.
+    pub fn render(&self, args: &Value) -> Result<Value, String> {
+        match args {
+            Value::String(text) => self.render_str(text),
+            Value::Array(items) => items.iter().map(|v| self.render(v)).collect(),
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), self.render(v)?)))
+                .collect::<Result<_, String>>()
+                .map(Value::Object),
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn render_str(&self, text: &str) -> Result<Value, String> {
+        if let Some(path) = text
+            .strip_prefix("{{")
+            .and_then(|t| t.strip_suffix("}}"))
+            .filter(|p| !p.contains("{{"))
+        {
+            return self.resolve(path).cloned();
+        }
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            match self.resolve(&rest[start + 2..start + len])? {
+                Value::String(s) => out.push_str(s),
+                value => out.push_str(&value.to_string()),
+            }
+            rest = &rest[start + len + 2..];
+        }
+        out.push_str(rest);
+        Ok(Value::String(out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars() -> PlanVars {
+        let mut vars = PlanVars::default();
+        vars.push_output(json!({
+            "success": true,
+            "data": [{"code": "P0300"}, {"code": "P0171"}],
+            "summary": "Found 2 DTC(s)"
+        }));
+        vars
+    }
+
+    #[test]
+    fn templates_resolve_earlier_outputs() {
+        let vars = vars();
+        assert_eq!(
+            vars.render(&json!({"code": "{{steps.0.data.1.code}}", "n": 3}))
+                .unwrap(),
+            json!({"code": "P0171", "n": 3})
+        );
+        // Whole-value templates keep the type; embedded ones become text.
+        assert_eq!(
+            vars.render(&json!("{{prev.success}}")).unwrap(),
+            json!(true)
+        );
+        assert_eq!(
+            vars.render(&json!("first: {{prev.data.0.code}}!")).unwrap(),
+            json!("first: P0300!")
+        );
+        assert!(vars.render(&json!("{{steps.1.data}}")).is_err());
+        assert!(vars.render(&json!("{{item}}")).is_err());
+    }
+
+    #[test]
+    fn for_each_items_are_bound() {
+        let vars = vars();
+        let items = vars.items("steps.0.data").unwrap();
+        assert_eq!(items.len(), 2);
+        let bound = vars.with_item(items[1].clone());
+        assert_eq!(
+            bound.render(&json!({"code": "{{item.code}}"})).unwrap(),
+            json!({"code": "P0171"})
+        );
+        assert!(vars.items("steps.0.summary").is_err());
+    }
+
+    #[test]
+    fn plans_are_bounded() {
+        let step = PlanStep::new("read_dtcs", json!({}));
+        assert!(validate(&vec![step.clone(); MAX_PLAN_STEPS]).is_ok());
+        assert!(validate(&vec![step; MAX_PLAN_STEPS + 1]).is_err());
+        assert!(validate(&[PlanStep::new(" ", json!({}))]).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Token-bucket rate limits on commands.
+//!
+//! The cloud limits how fast commands are submitted per device and per
+//! initiator; the agent limits how fast it accepts them, in case commands
+//! reach it some other way (several cloud replicas, a misbehaving client on
+//! the broker). A bucket holds up to `burst` tokens and refills at
+//! `per_minute` tokens a minute; each command takes one. A burst is let
+//! through while the bucket lasts, after that commands are admitted at the
+//! refill rate.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Size and refill rate of a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Commands accepted back to back from a full bucket.
+    pub burst: u32,
+    /// Tokens added per minute.
+    pub per_minute: u32,
+}
+
+impl RateLimit {
+    pub const fn new(burst: u32, per_minute: u32) -> Self {
+        Self { burst, per_minute }
+    }
+
+    /// Time for one token to be added.
+    fn refill_interval(&self) -> Duration {
+        Duration::from_secs(60) / self.per_minute.max(1)
+    }
+}
+
+/// `burst/per_minute`, e.g. `10/30`.
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (burst, per_minute) = s
+            .split_once('/')
+            .ok_or_else(|| format!("rate limit '{s}' is not 'burst/per_minute'"))?;
+        let number = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("'{v}' in rate limit '{s}' is not a positive number"))
+        };
+        Ok(Self::new(number(burst)?, number(per_minute)?))
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.burst, self.per_minute)
+    }
+}
+
+/// Tokens left under a [`RateLimit`].
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket.
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let added = elapsed * f64::from(self.limit.per_minute) / 60.0;
+        self.tokens = (self.tokens + added).min(f64::from(self.limit.burst));
+        self.updated = now;
+    }
+
+    /// How long until a token is available; zero when one is.
+    pub fn wait_time(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+        self.limit.refill_interval().mul_f64(1.0 - self.tokens)
+    }
+
+    /// Take a token, or return how long until one is available.
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        match self.wait_time(now) {
+            Duration::ZERO => {
+                self.tokens -= 1.0;
+                Ok(())
+            }
+            wait => Err(wait),
+        }
+    }
+
+    /// Whether the bucket has refilled completely (and can be forgotten).
+    pub fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= f64::from(self.limit.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_refill_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(3, 60), start);
+        for _ in 0..3 {
+            assert!(bucket.try_take(start).is_ok());
+        }
+        let wait = bucket.try_take(start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+
+        // One token a second at 60 per minute.
+        let later = start + Duration::from_millis(1500);
+        assert!(bucket.try_take(later).is_ok());
+        assert!(bucket.try_take(later).is_err());
+        assert!(!bucket.is_full(later));
+        assert!(bucket.is_full(start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn parses_burst_per_minute() {
+        assert_eq!("10/30".parse(), Ok(RateLimit::new(10, 30)));
+        assert_eq!(RateLimit::new(10, 30).to_string(), "10/30");
+        assert!("10".parse::<RateLimit>().is_err());
+        assert!("0/30".parse::<RateLimit>().is_err());
+        assert!("10/fast".parse::<RateLimit>().is_err());
+    }
+}
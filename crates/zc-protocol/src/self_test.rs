@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a single self-test check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// Usable, but worth attention (e.g., low disk space).
+    Warn,
+    Fail,
+    /// Not applicable to this device's configuration.
+    Skipped,
+}
+
+/// Result of one check in a self-test run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestCheck {
+    /// Check name (e.g., "can_interface", "ollama", "broker").
+    pub name: String,
+    pub status: CheckStatus,
+    /// Human-readable detail (what was checked, or why it failed).
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+/// What started a self-test run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTestTrigger {
+    /// Automatic run on the agent's first boot.
+    FirstBoot,
+    /// Requested through the `self_test` command.
+    Command,
+}
+
+/// Provisioning verification report published by the agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub device_id: String,
+    pub fleet_id: String,
+    pub agent_version: String,
+    pub trigger: SelfTestTrigger,
+    /// True when no check failed (warnings and skips still pass).
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+}
+
+impl SelfTestReport {
+    /// Whether a set of checks counts as a pass.
+    pub fn all_passed(checks: &[SelfTestCheck]) -> bool {
+        checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    /// Names of failed checks.
+    pub fn failed_checks(&self) -> Vec<&str> {
+        self.checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Fail)
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, status: CheckStatus) -> SelfTestCheck {
+        SelfTestCheck {
+            name: name.into(),
+            status,
+            detail: String::new(),
+            duration_ms: 1,
+        }
+    }
+
+    #[test]
+    fn warnings_and_skips_still_pass() {
+        let checks = vec![
+            check("clock", CheckStatus::Pass),
+            check("disk_space", CheckStatus::Warn),
+            check("ollama", CheckStatus::Skipped),
+        ];
+        assert!(SelfTestReport::all_passed(&checks));
+
+        let mut failing = checks.clone();
+        failing.push(check("broker", CheckStatus::Fail));
+        assert!(!SelfTestReport::all_passed(&failing));
+    }
+
+    #[test]
+    fn report_roundtrip() {
+        let checks = vec![
+            check("can_interface", CheckStatus::Fail),
+            check("clock", CheckStatus::Pass),
+        ];
+        let report = SelfTestReport {
+            device_id: "rpi-001".into(),
+            fleet_id: "fleet-alpha".into(),
+            agent_version: "0.1.0".into(),
+            trigger: SelfTestTrigger::FirstBoot,
+            passed: SelfTestReport::all_passed(&checks),
+            checks,
+            started_at: Utc::now(),
+            duration_ms: 42,
+        };
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains(r#""trigger":"first_boot""#));
+        assert!(json.contains(r#""status":"fail""#));
+
+        let back: SelfTestReport = serde_json::from_str(&json).unwrap();
+        assert!(!back.passed);
+        assert_eq!(back.failed_checks(), vec!["can_interface"]);
+    }
+}
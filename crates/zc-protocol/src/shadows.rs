@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 /// Modeled after AWS IoT Device Shadows: reported (from device),
 /// desired (from cloud), and delta (difference).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ShadowState {
     /// State reported by the device.
     #[serde(default)]
@@ -19,6 +20,80 @@ pub struct ShadowState {
     pub last_updated: DateTime<Utc>,
 }
 
+/// Shadow that delivers a rotated MQTT client certificate. Its desired
+/// state is a [`CertificateRotation`]; the agent installs the certificate,
+/// reconnects with it and reports the delta back.
+pub const CERTIFICATE_SHADOW: &str = "certificate";
+
+/// Desired state of the [`CERTIFICATE_SHADOW`]. Private keys never travel
+/// this way: the certificate is for a key the device generated (CSR).
+///
+/// Deltas only carry what changed, so `ca_cert_pem` is absent when the CA
+/// is the one the device already has.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateRotation {
+    /// Hex SHA-256 fingerprint of the certificate.
+    pub certificate_id: String,
+    pub certificate_pem: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_pem: Option<String>,
+}
+
+/// Shadow that delivers an agent release. Its desired state is an
+/// [`AgentUpdate`]; the agent reports an [`UpdateReport`], echoing the
+/// release once it runs it (which clears the delta).
+pub const UPDATE_SHADOW: &str = "update";
+
+/// Desired state of the [`UPDATE_SHADOW`]: the release to run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentUpdate {
+    pub agent_version: String,
+    /// Where the agent binary is downloaded from.
+    pub url: String,
+    /// Lower-case hex SHA-256 of the binary.
+    pub sha256: String,
+}
+
+/// Outcome of an agent update, as reported on the [`UPDATE_SHADOW`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStatus {
+    /// The new release is running and has reached the broker.
+    Installed,
+    /// Download, verification or installation failed; the old release
+    /// keeps running.
+    Failed,
+    /// The new release did not come up and the previous one was restored.
+    RolledBack,
+}
+
+/// Reported state of the [`UPDATE_SHADOW`]. It echoes the release it is
+/// about whatever the outcome, so the delta clears and a failed release is
+/// not redelivered; `status` tells whether it runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateReport {
+    pub agent_version: String,
+    pub url: String,
+    pub sha256: String,
+    pub status: UpdateStatus,
+    /// Why the update failed or was rolled back; `null` clears an earlier
+    /// error.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl UpdateReport {
+    pub fn new(update: &AgentUpdate, status: UpdateStatus, error: Option<String>) -> Self {
+        Self {
+            agent_version: update.agent_version.clone(),
+            url: update.url.clone(),
+            sha256: update.sha256.clone(),
+            status,
+            error,
+        }
+    }
+}
+
 /// A named shadow for a device (AWS IoT supports multiple shadows per thing).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamedShadow {
@@ -35,7 +110,9 @@ pub struct ShadowDelta {
     pub device_id: String,
     /// Shadow name.
     pub shadow_name: String,
-    /// The delta (difference between desired and reported).
+    /// The delta (difference between desired and reported). A `null` value
+    /// is a tombstone: the device should drop the key and report it as
+    /// `null`.
     pub delta: serde_json::Value,
     /// Shadow version this delta was computed from.
     pub version: u64,
@@ -56,11 +133,85 @@ pub struct ShadowUpdate {
     pub version: u64,
 }
 
+/// Request from a device for a shadow's full document, published on
+/// every (re)connect so desired state set while it was offline is not lost.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowGetRequest {
+    /// Device asking.
+    pub device_id: String,
+    /// Shadow to fetch.
+    pub shadow_name: String,
+    /// Echoed in the [`ShadowDocument`] so the device can match the reply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_token: Option<String>,
+}
+
+/// Cloud reply to a [`ShadowGetRequest`]. A shadow that does not exist
+/// yet comes back with empty state and version 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowDocument {
+    /// Device the document belongs to.
+    pub device_id: String,
+    /// Shadow name.
+    pub shadow_name: String,
+    /// Full desired state.
+    #[serde(default)]
+    pub desired: serde_json::Value,
+    /// Full reported state as last seen by the cloud.
+    #[serde(default)]
+    pub reported: serde_json::Value,
+    /// Outstanding delta, computed as for [`ShadowDelta`] (tombstones
+    /// included).
+    #[serde(default)]
+    pub delta: serde_json::Value,
+    /// Current shadow version.
+    pub version: u64,
+    /// Token from the request, if one was sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_token: Option<String>,
+    /// When the document was read.
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ShadowDocument {
+    /// The outstanding delta as a [`ShadowDelta`], or `None` when desired
+    /// and reported state already agree.
+    pub fn pending_delta(&self) -> Option<ShadowDelta> {
+        if self.delta.as_object().is_none_or(|o| o.is_empty()) {
+            return None;
+        }
+        Some(ShadowDelta {
+            device_id: self.device_id.clone(),
+            shadow_name: self.shadow_name.clone(),
+            delta: self.delta.clone(),
+            version: self.version,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn update_report_clears_the_desired_release() {
+        let update = AgentUpdate {
+            agent_version: "0.3.0".into(),
+            url: "https://releases.example.com/zc-fleet-agent-0.3.0".into(),
+            sha256: "ab".repeat(32),
+        };
+        let report = UpdateReport::new(&update, UpdateStatus::Installed, None);
+        let desired = serde_json::to_value(&update).unwrap();
+        let reported = serde_json::to_value(&report).unwrap();
+        for key in ["agent_version", "url", "sha256"] {
+            assert_eq!(desired[key], reported[key]);
+        }
+        assert_eq!(reported["status"], "installed");
+        assert!(reported["error"].is_null());
+    }
+
     #[test]
     fn shadow_state_roundtrip() {
         let shadow = ShadowState {
@@ -114,4 +265,26 @@ mod tests {
         assert_eq!(deserialized.name, "diagnostics");
         assert_eq!(deserialized.state.reported["dtc_count"], 3);
     }
+
+    #[test]
+    fn shadow_document_pending_delta() {
+        let mut doc = ShadowDocument {
+            device_id: "rpi-001".into(),
+            shadow_name: "config".into(),
+            desired: json!({"firmware": "0.2.0", "old": null}),
+            reported: json!({"firmware": "0.1.0", "old": 1}),
+            delta: json!({"firmware": "0.2.0", "old": null}),
+            version: 7,
+            client_token: None,
+            timestamp: Utc::now(),
+        };
+        let json = serde_json::to_string(&doc).unwrap();
+        assert!(!json.contains("client_token"));
+        let delta = doc.pending_delta().unwrap();
+        assert_eq!(delta.version, 7);
+        assert!(delta.delta["old"].is_null());
+
+        doc.delta = json!({});
+        assert!(doc.pending_delta().is_none());
+    }
 }
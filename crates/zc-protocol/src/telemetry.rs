@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 /// A single telemetry reading from a device.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TelemetryReading {
     /// Device that produced this reading.
     pub device_id: String,
@@ -28,6 +29,7 @@ pub struct TelemetryReading {
 
 /// Source subsystem for telemetry data.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum TelemetrySource {
     Obd2,
@@ -35,6 +37,17 @@ pub enum TelemetrySource {
     Canbus,
 }
 
+impl TelemetrySource {
+    /// Wire name, as used in the `source` column and events.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Obd2 => "obd2",
+            Self::System => "system",
+            Self::Canbus => "canbus",
+        }
+    }
+}
+
 /// OBD-II sensor data from a specific PID.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensorData {
@@ -75,12 +88,49 @@ pub struct SystemMetrics {
 
 /// Batch of telemetry readings for efficient MQTT publishing.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TelemetryBatch {
     pub device_id: String,
     pub readings: Vec<TelemetryReading>,
     pub collected_at: DateTime<Utc>,
 }
 
+/// Which bound of a PID watch a reading crossed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdBound {
+    Above,
+    Below,
+}
+
+impl ThresholdBound {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Above => "above",
+            Self::Below => "below",
+        }
+    }
+}
+
+/// A watched PID crossing one of its thresholds, published on
+/// `alert/notify` when the reading leaves the bounds (`exceeded`) and again
+/// when it returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdEvent {
+    pub device_id: String,
+    pub watch_id: String,
+    pub pid: u8,
+    /// Metric name, as in the watch's telemetry (e.g. "coolant_temp").
+    pub metric_name: String,
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    pub bound: ThresholdBound,
+    pub threshold: f64,
+    pub exceeded: bool,
+    pub time: DateTime<Utc>,
+}
+
 mod hex_bytes {
     use serde::{self, Deserialize, Deserializer, Serializer};
 
@@ -160,6 +210,26 @@ mod tests {
         assert!(deserialized.ollama_running);
     }
 
+    #[test]
+    fn threshold_event_roundtrip() {
+        let event = ThresholdEvent {
+            device_id: "rpi-001".into(),
+            watch_id: "config-0".into(),
+            pid: 0x05,
+            metric_name: "coolant_temp".into(),
+            value: 112.0,
+            unit: Some("°C".into()),
+            bound: ThresholdBound::Above,
+            threshold: 110.0,
+            exceeded: true,
+            time: Utc::now(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["bound"], "above");
+        let back: ThresholdEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(back, event);
+    }
+
     #[test]
     fn telemetry_source_serialization() {
         assert_eq!(
@@ -170,5 +240,15 @@ mod tests {
             serde_json::to_string(&TelemetrySource::System).unwrap(),
             r#""system""#
         );
+        for source in [
+            TelemetrySource::Obd2,
+            TelemetrySource::System,
+            TelemetrySource::Canbus,
+        ] {
+            assert_eq!(
+                serde_json::to_value(source).unwrap(),
+                serde_json::Value::from(source.as_str())
+            );
+        }
     }
 }
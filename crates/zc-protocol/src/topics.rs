@@ -5,16 +5,33 @@
 //! fleet/{fleet_id}/{device_id}/command/request
 //! fleet/{fleet_id}/{device_id}/command/response
 //! fleet/{fleet_id}/{device_id}/command/ack
+//! fleet/{fleet_id}/{device_id}/command/stream
+//! fleet/{fleet_id}/{device_id}/command/cancel
 //! fleet/{fleet_id}/{device_id}/telemetry/{source}
 //! fleet/{fleet_id}/{device_id}/shadow/update
 //! fleet/{fleet_id}/{device_id}/shadow/delta
+//! fleet/{fleet_id}/{device_id}/shadow/get
+//! fleet/{fleet_id}/{device_id}/shadow/document
 //! fleet/{fleet_id}/{device_id}/heartbeat/ping
 //! fleet/{fleet_id}/{device_id}/alert/notify
+//! fleet/{fleet_id}/{device_id}/selftest/report
+//! fleet/{fleet_id}/{device_id}/status/presence
+//! fleet/{fleet_id}/{device_id}/transfer/offer
+//! fleet/{fleet_id}/{device_id}/transfer/chunk
+//! fleet/{fleet_id}/{device_id}/transfer/ack
+//! fleet/{fleet_id}/{device_id}/transfer/upload
+//! fleet/{fleet_id}/{device_id}/transfer/complete
 //! fleet/{fleet_id}/broadcast/command/request
 //! fleet/{fleet_id}/broadcast/config/update
+//! bootstrap/{hardware_id}/claim
+//! bootstrap/{hardware_id}/result
 //! ```
+//!
+//! Bootstrap topics carry claim-code onboarding (see
+//! [`crate::bootstrap`]) for agents that have no fleet or device ID yet.
 
 const PREFIX: &str = "fleet";
+const BOOTSTRAP_PREFIX: &str = "bootstrap";
 
 // ─── Command topics ───
 
@@ -30,6 +47,14 @@ pub fn command_ack(fleet_id: &str, device_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/{device_id}/command/ack")
 }
 
+pub fn command_stream(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/command/stream")
+}
+
+pub fn command_cancel(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/command/cancel")
+}
+
 // ─── Telemetry topics ───
 
 pub fn telemetry_obd2(fleet_id: &str, device_id: &str) -> String {
@@ -54,6 +79,16 @@ pub fn shadow_delta(fleet_id: &str, device_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/{device_id}/shadow/delta")
 }
 
+/// Device asks for its full shadow document (on connect).
+pub fn shadow_get(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/shadow/get")
+}
+
+/// Cloud reply to a [`shadow_get`] request.
+pub fn shadow_document(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/shadow/document")
+}
+
 // ─── Heartbeat & alert ───
 
 pub fn heartbeat(fleet_id: &str, device_id: &str) -> String {
@@ -64,8 +99,46 @@ pub fn alert(fleet_id: &str, device_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/{device_id}/alert/notify")
 }
 
+pub fn self_test_report(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/selftest/report")
+}
+
+/// Retained device presence; the agent's Last Will publishes "offline" here.
+pub fn presence(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/status/presence")
+}
+
+// ─── File transfer topics ───
+
+/// Cloud starts or resumes a transfer.
+pub fn transfer_offer(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/transfer/offer")
+}
+
+/// File data pushed by the cloud.
+pub fn transfer_chunk(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/transfer/chunk")
+}
+
+/// Device progress on a transfer.
+pub fn transfer_ack(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/transfer/ack")
+}
+
+/// File data fetched from the device.
+pub fn transfer_upload(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/transfer/upload")
+}
+
+/// Device reports the end of a transfer.
+pub fn transfer_complete(fleet_id: &str, device_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/{device_id}/transfer/complete")
+}
+
 // ─── Broadcast topics ───
 
+/// Fleet-wide command topic. Envelopes published here carry
+/// `device_id = "*"`; each agent runs its own `for_device` copy.
 pub fn broadcast_command(fleet_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/broadcast/command/request")
 }
@@ -74,6 +147,34 @@ pub fn broadcast_config(fleet_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/broadcast/config/update")
 }
 
+// ─── Bootstrap topics ───
+
+/// Claim request from a device without an identity (device → cloud).
+pub fn bootstrap_claim(hardware_id: &str) -> String {
+    format!("{BOOTSTRAP_PREFIX}/{hardware_id}/claim")
+}
+
+/// Answer to a claim request (cloud → device).
+pub fn bootstrap_result(hardware_id: &str) -> String {
+    format!("{BOOTSTRAP_PREFIX}/{hardware_id}/result")
+}
+
+/// Subscribe to every claim request (for cloud bridge).
+pub fn bootstrap_claims() -> String {
+    format!("{BOOTSTRAP_PREFIX}/+/claim")
+}
+
+/// Split a bootstrap topic into hardware ID and action. `None` for fleet
+/// topics and anything else.
+pub fn parse_bootstrap_topic(topic: &str) -> Option<(&str, &str)> {
+    let rest = topic.strip_prefix(BOOTSTRAP_PREFIX)?.strip_prefix('/')?;
+    let (hardware_id, action) = rest.split_once('/')?;
+    if hardware_id.is_empty() || action.is_empty() || action.contains('/') {
+        return None;
+    }
+    Some((hardware_id, action))
+}
+
 // ─── Subscription patterns (with MQTT wildcards) ───
 
 /// Subscribe to all topics for a specific device.
@@ -86,6 +187,11 @@ pub fn fleet_command_responses(fleet_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/+/command/response")
 }
 
+/// Subscribe to all streamed response chunks in a fleet (for cloud bridge).
+pub fn fleet_command_streams(fleet_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/+/command/stream")
+}
+
 /// Subscribe to all heartbeats in a fleet.
 pub fn fleet_heartbeats(fleet_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/+/heartbeat/ping")
@@ -101,6 +207,61 @@ pub fn fleet_shadow_updates(fleet_id: &str) -> String {
     format!("{PREFIX}/{fleet_id}/+/shadow/update")
 }
 
+/// Subscribe to all shadow get requests in a fleet (for cloud bridge).
+pub fn fleet_shadow_gets(fleet_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/+/shadow/get")
+}
+
+/// Subscribe to all device alerts in a fleet (for cloud bridge).
+pub fn fleet_alerts(fleet_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/+/alert/notify")
+}
+
+/// Subscribe to all self-test reports in a fleet (for cloud bridge).
+pub fn fleet_self_test_reports(fleet_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/+/selftest/report")
+}
+
+/// Subscribe to all device presence messages in a fleet (for cloud bridge).
+pub fn fleet_presence(fleet_id: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/+/status/presence")
+}
+
+/// Subscribe to device-to-cloud transfer messages (`ack`, `upload`,
+/// `complete`) in a fleet (for cloud bridge).
+pub fn fleet_transfers(fleet_id: &str, action: &str) -> String {
+    format!("{PREFIX}/{fleet_id}/+/transfer/{action}")
+}
+
+/// Fleet ID placeholder matching every fleet in subscription filters.
+pub const ALL_FLEETS: &str = "+";
+
+/// Every filter the cloud bridge subscribes to for one fleet
+/// ([`ALL_FLEETS`] for all of them).
+pub fn bridge_subscriptions(fleet_id: &str) -> Vec<String> {
+    let mut filters = vec![
+        fleet_command_responses(fleet_id),
+        fleet_command_streams(fleet_id),
+        fleet_heartbeats(fleet_id),
+        fleet_shadow_updates(fleet_id),
+        fleet_shadow_gets(fleet_id),
+        fleet_self_test_reports(fleet_id),
+        fleet_presence(fleet_id),
+        fleet_alerts(fleet_id),
+    ];
+    filters.extend(
+        ["ack", "upload", "complete"]
+            .iter()
+            .map(|action| fleet_transfers(fleet_id, action)),
+    );
+    filters.extend(
+        ["obd2", "system", "canbus"]
+            .iter()
+            .map(|source| fleet_telemetry(fleet_id, source)),
+    );
+    filters
+}
+
 // ─── Topic parsing ───
 
 /// Parsed MQTT topic components.
@@ -112,38 +273,56 @@ pub struct ParsedTopic {
     pub action: String,
 }
 
+/// Topic components borrowed from the topic string.
+///
+/// Same shape as [`ParsedTopic`] without allocating; used on hot paths
+/// that only need to look at the parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicRef<'a> {
+    pub fleet_id: &'a str,
+    pub device_id: Option<&'a str>,
+    pub category: &'a str,
+    pub action: &'a str,
+}
+
+impl TopicRef<'_> {
+    /// Owned copy of the components.
+    pub fn into_owned(self) -> ParsedTopic {
+        ParsedTopic {
+            fleet_id: self.fleet_id.to_string(),
+            device_id: self.device_id.map(String::from),
+            category: self.category.to_string(),
+            action: self.action.to_string(),
+        }
+    }
+}
+
 /// Parse a topic string into its components.
 /// Returns `None` if the topic doesn't match the expected format.
 pub fn parse_topic(topic: &str) -> Option<ParsedTopic> {
-    let parts: Vec<&str> = topic.split('/').collect();
+    parse_topic_ref(topic).map(TopicRef::into_owned)
+}
 
-    if parts.first() != Some(&"fleet") || parts.len() < 4 {
+/// Like [`parse_topic`], borrowing the components from `topic`.
+pub fn parse_topic_ref(topic: &str) -> Option<TopicRef<'_>> {
+    let mut parts = topic.split('/');
+    if parts.next() != Some(PREFIX) {
         return None;
     }
-
-    let fleet_id = parts[1].to_string();
+    let fleet_id = parts.next()?;
+    let scope = parts.next()?;
+    let category = parts.next()?;
+    let action = parts.next()?;
 
     // Broadcast topic: fleet/{fleet_id}/broadcast/{category}/{action}
-    if parts[2] == "broadcast" && parts.len() >= 5 {
-        return Some(ParsedTopic {
-            fleet_id,
-            device_id: None,
-            category: parts[3].to_string(),
-            action: parts[4].to_string(),
-        });
-    }
-
-    // Device topic: fleet/{fleet_id}/{device_id}/{category}/{action}
-    if parts.len() >= 5 {
-        return Some(ParsedTopic {
-            fleet_id,
-            device_id: Some(parts[2].to_string()),
-            category: parts[3].to_string(),
-            action: parts[4].to_string(),
-        });
-    }
-
-    None
+    // Device topic:    fleet/{fleet_id}/{device_id}/{category}/{action}
+    let device_id = (scope != "broadcast").then_some(scope);
+    Some(TopicRef {
+        fleet_id,
+        device_id,
+        category,
+        action,
+    })
 }
 
 #[cfg(test)]
@@ -166,6 +345,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn command_stream_topic() {
+        assert_eq!(
+            command_stream("fleet-alpha", "rpi-001"),
+            "fleet/fleet-alpha/rpi-001/command/stream"
+        );
+        assert_eq!(
+            fleet_command_streams("fleet-alpha"),
+            "fleet/fleet-alpha/+/command/stream"
+        );
+    }
+
+    #[test]
+    fn self_test_report_topic() {
+        let topic = self_test_report("fleet-alpha", "rpi-001");
+        assert_eq!(topic, "fleet/fleet-alpha/rpi-001/selftest/report");
+        assert_eq!(
+            fleet_self_test_reports("fleet-alpha"),
+            "fleet/fleet-alpha/+/selftest/report"
+        );
+        let parsed = parse_topic(&topic).unwrap();
+        assert_eq!(parsed.device_id.as_deref(), Some("rpi-001"));
+        assert_eq!(parsed.category, "selftest");
+        assert_eq!(parsed.action, "report");
+    }
+
+    #[test]
+    fn transfer_topics() {
+        assert_eq!(
+            transfer_offer("fleet-alpha", "rpi-001"),
+            "fleet/fleet-alpha/rpi-001/transfer/offer"
+        );
+        assert_eq!(
+            fleet_transfers("fleet-alpha", "upload"),
+            "fleet/fleet-alpha/+/transfer/upload"
+        );
+        let parsed = parse_topic(&transfer_complete("fleet-alpha", "rpi-001")).unwrap();
+        assert_eq!(parsed.category, "transfer");
+        assert_eq!(parsed.action, "complete");
+        assert!(bridge_subscriptions("fleet-alpha").contains(&transfer_ack("fleet-alpha", "+")));
+    }
+
+    #[test]
+    fn command_cancel_topic() {
+        let topic = command_cancel("fleet-alpha", "rpi-001");
+        assert_eq!(topic, "fleet/fleet-alpha/rpi-001/command/cancel");
+        let parsed = parse_topic(&topic).unwrap();
+        assert_eq!(parsed.category, "command");
+        assert_eq!(parsed.action, "cancel");
+    }
+
     #[test]
     fn telemetry_topics() {
         assert_eq!(
@@ -192,6 +422,13 @@ mod tests {
             shadow_delta("fleet-alpha", "rpi-001"),
             "fleet/fleet-alpha/rpi-001/shadow/delta"
         );
+        assert_eq!(
+            shadow_get("fleet-alpha", "rpi-001"),
+            "fleet/fleet-alpha/rpi-001/shadow/get"
+        );
+        let parsed = parse_topic(&shadow_document("fleet-alpha", "rpi-001")).unwrap();
+        assert_eq!(parsed.category, "shadow");
+        assert_eq!(parsed.action, "document");
     }
 
     #[test]
@@ -262,4 +499,55 @@ mod tests {
         assert!(parse_topic("fleet/abc").is_none());
         assert!(parse_topic("").is_none());
     }
+
+    #[test]
+    fn parse_topic_ref_borrows_components() {
+        let topic = "fleet/fleet-alpha/rpi-001/heartbeat/ping";
+        let parsed = parse_topic_ref(topic).unwrap();
+        assert_eq!(parsed.fleet_id, "fleet-alpha");
+        assert_eq!(parsed.device_id, Some("rpi-001"));
+        assert_eq!(parsed.category, "heartbeat");
+        assert_eq!(parsed.action, "ping");
+        assert_eq!(parsed.into_owned(), parse_topic(topic).unwrap());
+
+        let broadcast = parse_topic_ref("fleet/fleet-alpha/broadcast/config/update").unwrap();
+        assert_eq!(broadcast.device_id, None);
+        assert_eq!(broadcast.category, "config");
+
+        assert!(parse_topic_ref("fleet/fleet-alpha/rpi-001/heartbeat").is_none());
+        assert!(parse_topic_ref("other/fleet-alpha/rpi-001/heartbeat/ping").is_none());
+    }
+
+    #[test]
+    fn bootstrap_topics() {
+        assert_eq!(bootstrap_claim("a8b9c0d1"), "bootstrap/a8b9c0d1/claim");
+        assert_eq!(bootstrap_result("a8b9c0d1"), "bootstrap/a8b9c0d1/result");
+        assert_eq!(bootstrap_claims(), "bootstrap/+/claim");
+        assert_eq!(
+            parse_bootstrap_topic("bootstrap/a8b9c0d1/claim"),
+            Some(("a8b9c0d1", "claim"))
+        );
+        assert!(parse_bootstrap_topic("bootstrap//claim").is_none());
+        assert!(parse_bootstrap_topic("bootstrap/a8b9c0d1/claim/x").is_none());
+        assert!(parse_bootstrap_topic("fleet/fleet-alpha/rpi-001/heartbeat/ping").is_none());
+        // Bootstrap topics are not fleet topics.
+        assert!(parse_topic_ref("bootstrap/a8b9c0d1/claim").is_none());
+    }
+
+    #[test]
+    fn bridge_subscriptions_cover_all_fleets() {
+        let filters = bridge_subscriptions(ALL_FLEETS);
+        assert_eq!(filters.len(), 14);
+        assert!(filters.contains(&"fleet/+/+/alert/notify".to_string()));
+        assert!(filters.contains(&"fleet/+/+/transfer/upload".to_string()));
+        assert!(filters.contains(&"fleet/+/+/status/presence".to_string()));
+        assert!(filters.contains(&"fleet/+/+/shadow/get".to_string()));
+        assert!(filters.contains(&"fleet/+/+/heartbeat/ping".to_string()));
+        assert!(filters.contains(&"fleet/+/+/telemetry/canbus".to_string()));
+        assert!(
+            bridge_subscriptions("fleet-alpha")
+                .iter()
+                .all(|f| f.starts_with("fleet/fleet-alpha/+/"))
+        );
+    }
 }
@@ -0,0 +1,182 @@
+//! Chunked file transfer between the cloud and a device.
+//!
+//! The cloud starts every transfer with a [`TransferOffer`]:
+//!
+//! - **push** (cloud → device, e.g. DBC files, CA bundles, config blobs):
+//!   the device answers each offer and chunk with a [`TransferAck`] naming
+//!   the next offset it wants, and the cloud sends that chunk. Once all
+//!   bytes are in, the device checks the SHA-256, moves the file into
+//!   place and publishes a [`TransferComplete`].
+//! - **fetch** (device → cloud, e.g. core dumps, pcap captures): the device
+//!   acknowledges with the file size, publishes the file from the offered
+//!   offset as [`TransferChunk`]s on the upload topic and finishes with a
+//!   [`TransferComplete`] carrying the size and SHA-256 of the whole file.
+//!
+//! Both directions resume: a push offer repeated for the same transfer is
+//! acknowledged with the bytes the device already holds, and a fetch offer
+//! names the offset the cloud has received up to. Chunk data travels as
+//! base64 so that JSON and CBOR peers read the same message.
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Largest chunk of file data in one message; stays under the broker and
+/// bridge payload limits once base64-encoded.
+pub const MAX_CHUNK_BYTES: u32 = 32 * 1024;
+
+/// Which way the file travels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    /// Cloud to device.
+    Push,
+    /// Device to cloud.
+    Fetch,
+}
+
+impl TransferDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Push => "push",
+            Self::Fetch => "fetch",
+        }
+    }
+}
+
+/// Start (or resume) a transfer. Published by the cloud.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferOffer {
+    pub transfer_id: Uuid,
+    pub direction: TransferDirection,
+    /// Absolute path of the file on the device.
+    pub path: String,
+    /// Push: size of the file in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Push: lowercase hex SHA-256 of the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Fetch: byte offset to send from (what the cloud already holds).
+    #[serde(default)]
+    pub offset: u64,
+    /// Fetch: largest chunk to send, at most [`MAX_CHUNK_BYTES`].
+    pub chunk_size: u32,
+    pub offered_at: DateTime<Utc>,
+}
+
+/// A slice of file data starting at `offset`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferChunk {
+    pub transfer_id: Uuid,
+    pub offset: u64,
+    /// Base64 (standard alphabet, padded).
+    pub data: String,
+}
+
+impl TransferChunk {
+    pub fn new(transfer_id: Uuid, offset: u64, bytes: &[u8]) -> Self {
+        Self {
+            transfer_id,
+            offset,
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    /// The decoded data.
+    pub fn bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        base64::engine::general_purpose::STANDARD.decode(&self.data)
+    }
+}
+
+/// Device progress on a transfer. Published by the device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferAck {
+    pub transfer_id: Uuid,
+    pub device_id: String,
+    /// Push: the offset of the next chunk the device wants.
+    /// Fetch: the offset the upload starts from.
+    pub next_offset: u64,
+    /// Fetch: size of the file being uploaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// How a transfer ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferOutcome {
+    Completed,
+    Failed,
+}
+
+/// End of a transfer. Published by the device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferComplete {
+    pub transfer_id: Uuid,
+    pub device_id: String,
+    pub outcome: TransferOutcome,
+    /// Size of the whole file.
+    #[serde(default)]
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the whole file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Why the transfer failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl TransferComplete {
+    /// A failed transfer.
+    pub fn failed(transfer_id: Uuid, device_id: &str, error: impl Into<String>) -> Self {
+        Self {
+            transfer_id,
+            device_id: device_id.to_string(),
+            outcome: TransferOutcome::Failed,
+            size: 0,
+            sha256: None,
+            error: Some(error.into()),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_data_round_trips_in_both_encodings() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let chunk = TransferChunk::new(Uuid::now_v7(), 4096, &bytes);
+        for encoding in crate::encoding::Encoding::ALL {
+            let payload = crate::encoding::encode(&chunk, encoding).unwrap();
+            let back: TransferChunk = crate::encoding::decode(&payload).unwrap();
+            assert_eq!(back.offset, 4096);
+            assert_eq!(back.bytes().unwrap(), bytes);
+        }
+        let bad = TransferChunk {
+            data: "not base64!".into(),
+            ..chunk
+        };
+        assert!(bad.bytes().is_err());
+    }
+
+    #[test]
+    fn fetch_offer_defaults() {
+        let offer: TransferOffer = serde_json::from_value(serde_json::json!({
+            "transfer_id": Uuid::now_v7(),
+            "direction": "fetch",
+            "path": "/var/crash/core.1234",
+            "chunk_size": MAX_CHUNK_BYTES,
+            "offered_at": Utc::now(),
+        }))
+        .unwrap();
+        assert_eq!(offer.direction, TransferDirection::Fetch);
+        assert_eq!(offer.offset, 0);
+        assert!(offer.size.is_none() && offer.sha256.is_none());
+    }
+}
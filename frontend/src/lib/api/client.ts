@@ -9,6 +9,7 @@ import type {
 	CommandSummary,
 	SendCommandRequest,
 	HealthResponse,
+	TelemetryQuery,
 	TelemetryResponse,
 	ShadowSummary,
 	ShadowResponse
@@ -47,9 +48,9 @@ export const api = {
 		return request('/health');
 	},
 
-	/** GET /api/v1/devices */
+	/** GET /api/v1/devices (largest page; the API defaults to 50) */
 	listDevices(): Promise<DeviceSummary[]> {
-		return request(`${BASE}/devices`);
+		return request(`${BASE}/devices?limit=500`);
 	},
 
 	/** GET /api/v1/devices/:id */
@@ -66,10 +67,13 @@ export const api = {
 	},
 
 	/** GET /api/v1/devices/:id/telemetry */
-	getTelemetry(id: string, source?: string, limit?: number): Promise<TelemetryResponse> {
+	getTelemetry(id: string, query: TelemetryQuery = {}): Promise<TelemetryResponse> {
 		const params = new URLSearchParams();
-		if (source) params.set('source', source);
-		if (limit) params.set('limit', String(limit));
+		if (query.source) params.set('source', query.source);
+		if (query.metric) params.set('metric', query.metric);
+		if (query.limit) params.set('limit', String(query.limit));
+		if (query.cursor) params.set('cursor', query.cursor);
+		if (query.order) params.set('order', query.order);
 		const qs = params.toString();
 		return request(`${BASE}/devices/${encodeURIComponent(id)}/telemetry${qs ? `?${qs}` : ''}`);
 	},
@@ -1,11 +1,18 @@
 /** Reactive WebSocket client for real-time events. */
 
-import type { WsEvent } from '$lib/types';
+import { WS_EVENT_SCHEMA_VERSION, type WsEvent } from '$lib/types';
 
 export type ConnectionStatus = 'connecting' | 'connected' | 'disconnected';
 
 export type WsEventHandler = (event: WsEvent) => void;
 
+/** Server-side event filter. Empty or omitted lists match everything. */
+export interface WsSubscription {
+	device_ids?: string[];
+	fleet_ids?: string[];
+	event_types?: WsEvent['type'][];
+}
+
 const RECONNECT_DELAY_MS = 3000;
 const MAX_RECONNECT_DELAY_MS = 30000;
 
@@ -18,6 +25,8 @@ class WebSocketStore {
 	private reconnectDelay = RECONNECT_DELAY_MS;
 	private handlers: Set<WsEventHandler> = new Set();
 	private shouldReconnect = false;
+	private subscription: WsSubscription | null = null;
+	private versionWarned = false;
 
 	/** Connect to the WebSocket endpoint. */
 	connect() {
@@ -36,11 +45,22 @@ class WebSocketStore {
 		this.ws.onopen = () => {
 			this.status = 'connected';
 			this.reconnectDelay = RECONNECT_DELAY_MS;
+			this.sendSubscription();
 		};
 
 		this.ws.onmessage = (msg) => {
 			try {
 				const event: WsEvent = JSON.parse(msg.data);
+				if (
+					event.schema_version !== undefined &&
+					event.schema_version !== WS_EVENT_SCHEMA_VERSION &&
+					!this.versionWarned
+				) {
+					this.versionWarned = true;
+					console.warn(
+						`WebSocket event schema v${event.schema_version}, dashboard expects v${WS_EVENT_SCHEMA_VERSION}`
+					);
+				}
 				this.lastEvent = event;
 				for (const handler of this.handlers) {
 					handler(event);
@@ -75,12 +95,26 @@ class WebSocketStore {
 		this.status = 'disconnected';
 	}
 
+	/** Only receive matching events (re-applied after reconnects). Pass null for all events. */
+	subscribe(filter: WsSubscription | null) {
+		this.subscription = filter;
+		this.sendSubscription();
+	}
+
 	/** Register an event handler. Returns an unsubscribe function. */
 	onEvent(handler: WsEventHandler): () => void {
 		this.handlers.add(handler);
 		return () => this.handlers.delete(handler);
 	}
 
+	private sendSubscription() {
+		if (this.ws?.readyState !== WebSocket.OPEN) return;
+		const msg = this.subscription
+			? { type: 'subscribe', ...this.subscription }
+			: { type: 'unsubscribe' };
+		this.ws.send(JSON.stringify(msg));
+	}
+
 	private scheduleReconnect() {
 		this.reconnectTimer = setTimeout(() => {
 			this.reconnectTimer = null;
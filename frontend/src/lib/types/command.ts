@@ -1,16 +1,53 @@
 /** Mirrors zc-protocol command types. */
 
-export type CommandStatus = 'pending' | 'sent' | 'received' | 'executing' | 'completed' | 'failed';
+export type CommandStatus =
+	| 'queued'
+	| 'awaiting_approval'
+	| 'pending'
+	| 'sent'
+	| 'received'
+	| 'executing'
+	| 'completed'
+	| 'failed';
 
 export type InferenceTier = 'local' | 'cloud';
 
 export type ActionKind = 'tool' | 'shell' | 'reply';
 
+/** Machine-readable failure class of a response (`ErrorCode`). */
+export type ErrorCode =
+	| 'unknown_tool'
+	| 'inference_no_match'
+	| 'inference_unavailable'
+	| 'structured_only'
+	| 'invalid_args'
+	| 'shell_blocked'
+	| 'shell_failed'
+	| 'can_timeout'
+	| 'can_error'
+	| 'log_file_not_found'
+	| 'log_error'
+	| 'payload_too_large'
+	| 'timeout'
+	| 'cancelled'
+	| 'throttled'
+	| 'plan_failed'
+	| 'delivery_failed'
+	| 'internal'
+	| 'unknown';
+
+export interface PlanStep {
+	tool_name: string;
+	tool_args?: Record<string, unknown>;
+	for_each?: string;
+}
+
 export interface ParsedIntent {
 	action?: ActionKind;
 	tool_name: string;
 	tool_args: Record<string, unknown>;
 	confidence: number;
+	steps?: PlanStep[];
 }
 
 export interface CommandEnvelope {
@@ -31,14 +68,33 @@ export interface CommandResponse {
 	inference_tier: InferenceTier;
 	result: Record<string, unknown> | null;
 	error: string | null;
+	error_code?: ErrorCode;
 	latency_ms: number;
 	timestamp: string;
 }
 
+/** Where a command's time went; durations are null until known. */
+export interface CommandTiming {
+	created_at: string;
+	published_at: string | null;
+	received_at: string | null;
+	/** Device wall clock. */
+	device_responded_at: string | null;
+	/** Device clock minus cloud clock, from heartbeats. */
+	clock_offset_ms: number | null;
+	queue_ms: number | null;
+	execution_ms: number | null;
+	transport_ms: number | null;
+	outbound_ms: number | null;
+	inbound_ms: number | null;
+	total_ms: number | null;
+}
+
 export interface CommandRecord {
 	command: CommandEnvelope;
 	response: CommandResponse | null;
 	created_at: string;
+	timing?: CommandTiming;
 }
 
 export interface CommandSummary {
@@ -46,6 +102,7 @@ export interface CommandSummary {
 	device_id: string;
 	command: string;
 	status: CommandStatus | null;
+	error_code?: ErrorCode | null;
 	response_text?: string | null;
 	created_at: string;
 }
@@ -53,6 +110,16 @@ export interface CommandSummary {
 export interface SendCommandRequest {
 	device_id: string;
 	fleet_id: string;
-	command: string;
+	/** Natural-language text; optional when `tool_name` is set. */
+	command?: string;
+	/** Tool to run without inference (required in structured-only fleets). */
+	tool_name?: string;
+	tool_args?: Record<string, unknown>;
 	initiated_by: string;
+	/** Run readiness checks first; dispatch is refused (412) on failure. */
+	preflight?: boolean;
+	/** Dispatch even if pre-flight checks fail. */
+	force?: boolean;
+	/** Resubmitting with the same key returns the original command. */
+	idempotency_key?: string;
 }
@@ -1,6 +1,6 @@
 /** Mirrors zc-protocol device types. */
 
-export type DeviceStatus = 'online' | 'offline' | 'error' | 'provisioning';
+export type DeviceStatus = 'online' | 'degraded' | 'offline' | 'error' | 'provisioning';
 
 /** Serde-serialized Rust enum: simple variants are strings, Custom(s) is { custom: s }. */
 export type HardwareType =
@@ -7,6 +7,9 @@ export type DtcSeverity = 'info' | 'warning' | 'critical' | 'unknown';
 /** DTC category matching zc-protocol DtcCategory. */
 export type DtcCategory = 'powertrain' | 'chassis' | 'body' | 'network';
 
+/** OBD-II DTC list matching zc-protocol DtcScope. */
+export type DtcScope = 'stored' | 'pending' | 'permanent';
+
 /** Diagnostic Trouble Code — mirrors zc-protocol DtcCode. */
 export interface DtcCode {
 	code: string;
@@ -16,7 +19,13 @@ export interface DtcCode {
 	description?: string;
 	failure_type?: string;
 	raw_dtc?: string;
+	/** Repair guidance from the cloud DTC knowledge base. */
+	repair_hint?: string;
+	/** Reference links from the cloud DTC knowledge base. */
+	links?: { title: string; url: string }[];
 	mil_status: boolean;
+	/** OBD-II list the code was read from (absent for UDS DTCs). */
+	scope?: DtcScope;
 	freeze_frame?: {
 		engine_rpm?: number;
 		vehicle_speed?: number;
@@ -45,12 +54,24 @@ export interface TelemetryReading {
 	source: TelemetrySource;
 }
 
+export type SortOrder = 'asc' | 'desc';
+
+export interface TelemetryQuery {
+	source?: string;
+	metric?: string;
+	limit?: number;
+	cursor?: string;
+	order?: SortOrder;
+}
+
 export interface TelemetryResponse {
 	device_id: string;
 	source: string | null;
+	metric: string | null;
+	order: SortOrder;
 	limit: number;
 	readings: TelemetryReading[];
-	message?: string;
+	next_cursor: string | null;
 }
 
 export interface ApiError {
@@ -72,10 +93,112 @@ export interface ShadowResponse {
 	delta: unknown;
 	version: number;
 	last_updated: string;
+	/** Delivery history of the delta, once one has been published. */
+	reconciliation?: ShadowReconciliation;
+}
+
+export interface ShadowReconciliation {
+	status: 'pending' | 'converged' | 'exhausted';
+	attempts: number;
+	last_attempt_at: string | null;
+	converged_at: string | null;
+}
+
+/** Event contract version the dashboard was built against (see GET /api/v1/events/schema). */
+export const WS_EVENT_SCHEMA_VERSION = 1;
+
+/** Fields present on every event frame. */
+export interface WsEventFrame {
+	schema_version: number;
+}
+
+/** A failed command attached to a tool failure-rate alert. */
+export interface ToolErrorSample {
+	command_id: string;
+	device_id: string;
+	error: string | null;
+	at: string;
 }
 
 /** WebSocket event types matching server-side WsEvent. */
-export type WsEvent =
+export type AlertState = 'open' | 'acknowledged' | 'snoozed' | 'resolved';
+
+export type AlertAction = 'raised' | 'reoccurred' | 'acknowledged' | 'snoozed' | 'resolved';
+
+export interface AlertTransition {
+	at: string;
+	actor: string;
+	action: AlertAction;
+	comment?: string;
+	snoozed_until?: string;
+}
+
+export interface Alert {
+	id: string;
+	kind: string;
+	fleet_id: string;
+	device_id: string;
+	scope: 'device' | 'fleet';
+	title: string;
+	detail: Record<string, unknown>;
+	state: AlertState;
+	occurrences: number;
+	first_seen_at: string;
+	last_seen_at: string;
+	acknowledged_by: string | null;
+	acknowledged_at: string | null;
+	snoozed_until: string | null;
+	resolved_by: string | null;
+	resolved_at: string | null;
+	updated_at: string;
+	history: AlertTransition[];
+}
+
+export interface AlertCounts {
+	open: number;
+	acknowledged: number;
+	snoozed: number;
+	unresolved: number;
+}
+
+export interface Schedule {
+	id: string;
+	name: string;
+	cron: string;
+	fleet_id: string;
+	device_id: string | null;
+	tags: string[];
+	command: string;
+	tool_name: string | null;
+	tool_args: Record<string, unknown> | null;
+	enabled: boolean;
+	created_by: string;
+	created_at: string;
+	updated_at: string;
+	next_run_at: string | null;
+	last_run_at: string | null;
+	run_count: number;
+}
+
+export interface ScheduleRun {
+	id: string;
+	schedule_id: string;
+	scheduled_for: string;
+	fired_at: string;
+	command_ids: string[];
+	broadcast_id: string | null;
+	error: string | null;
+}
+
+export interface FleetSummary {
+	fleet_id: string;
+	devices: number;
+	reachable: number;
+	alerts: AlertCounts;
+}
+
+export type WsEvent = WsEventFrame &
+	(
 	| {
 			type: 'command_dispatched';
 			command_id: string;
@@ -84,6 +207,18 @@ export type WsEvent =
 			initiated_by: string;
 			created_at: string;
 	  }
+	| {
+			type: 'command_queued';
+			command_id: string;
+			device_id: string;
+			queued_at: string;
+	  }
+	| {
+			type: 'command_queue_flushed';
+			device_id: string;
+			count: number;
+			flushed_at: string;
+	  }
 	| {
 			type: 'command_response';
 			command_id: string;
@@ -93,9 +228,26 @@ export type WsEvent =
 			response_text: string | null;
 			response_data: unknown | null;
 			error: string | null;
+			error_code: string | null;
 			latency_ms: number | null;
 			responded_at: string;
 	  }
+	| {
+			type: 'command_response_chunk';
+			command_id: string;
+			device_id: string;
+			seq: number;
+			data: unknown;
+			sent_at: string;
+	  }
+	| {
+			type: 'command_cancelled';
+			command_id: string;
+			device_id: string;
+			requested_by: string;
+			reason: string | null;
+			cancelled_at: string;
+	  }
 	| {
 			type: 'device_heartbeat';
 			device_id: string;
@@ -128,4 +280,65 @@ export type WsEvent =
 			shadow_name: string;
 			version: number;
 			timestamp: string;
-	  };
+	  }
+	| {
+			type: 'shadow_reconcile';
+			device_id: string;
+			shadow_name: string;
+			status: string;
+			attempts: number;
+			timestamp: string;
+	  }
+	| {
+			type: 'tool_failure_anomaly';
+			device_id: string;
+			fleet_id: string;
+			scope: 'device' | 'fleet';
+			tool_name: string;
+			failures: number;
+			samples: number;
+			failure_rate: number;
+			threshold: number;
+			recent_errors: ToolErrorSample[];
+			detected_at: string;
+	  }
+	| {
+			type: 'self_test_reported';
+			device_id: string;
+			trigger: string;
+			passed: boolean;
+			failed_checks: string[];
+			reported_at: string;
+	  }
+	| {
+			type: 'critical_dtc_detected';
+			device_id: string;
+			fleet_id: string;
+			command_id: string;
+			codes: string[];
+			detected_at: string;
+	  }
+	| {
+			type: 'alert_raised';
+			alert_id: string;
+			device_id: string;
+			fleet_id: string;
+			kind: string;
+			scope: 'device' | 'fleet';
+			title: string;
+			raised_at: string;
+	  }
+	| {
+			type: 'alert_updated';
+			alert_id: string;
+			device_id: string;
+			fleet_id: string;
+			action: AlertAction;
+			state: AlertState;
+			actor: string;
+			comment: string | null;
+			occurrences: number;
+			snoozed_until: string | null;
+			updated_at: string;
+	  }
+	);